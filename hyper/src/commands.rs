@@ -6,6 +6,8 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+pub mod debug;
 pub mod list;
 pub mod resolve;
 pub mod show;
+pub mod topology;
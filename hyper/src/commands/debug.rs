@@ -0,0 +1,86 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use hyperactor as reference;
+use hyperactor::debugger;
+use hyperactor::debugger::DebugMessage;
+use hyperactor::debugger::DebugReply;
+use hyperactor_mesh::context;
+use wirevalue::Encoding;
+
+/// Debug an actor. Its proc must run with `HYPERACTOR_DEBUG_PORT=true`.
+#[derive(clap::Args, Debug)]
+pub struct DebugCommand {
+    /// The actor to debug.
+    actor: reference::ActorAddr,
+
+    #[command(subcommand)]
+    action: DebugAction,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum DebugAction {
+    /// Stop the actor from handling queued messages.
+    Freeze,
+    /// Resume handling queued messages.
+    Thaw,
+    /// List the queued messages.
+    List,
+    /// Handle the next queued message of a frozen actor.
+    Step,
+    /// Post a message to the actor's handler for its type.
+    Inject {
+        /// The fully qualified name of the message type.
+        typename: String,
+        /// The message, as JSON.
+        message: String,
+    },
+}
+
+impl DebugCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let cx = context().await;
+        let client = cx.actor_instance;
+
+        let reply = match self.action {
+            DebugAction::Freeze => {
+                debugger::request(client, &self.actor, |reply| DebugMessage::Freeze { reply })
+                    .await?
+            }
+            DebugAction::Thaw => {
+                debugger::request(client, &self.actor, |reply| DebugMessage::Thaw { reply }).await?
+            }
+            DebugAction::List => {
+                debugger::request(client, &self.actor, |reply| DebugMessage::List { reply }).await?
+            }
+            DebugAction::Step => {
+                debugger::request(client, &self.actor, |reply| DebugMessage::Step { reply }).await?
+            }
+            DebugAction::Inject { typename, message } => {
+                // Validate locally: the actor would only fail to decode it.
+                serde_json::from_str::<serde_json::Value>(&message)?;
+                let message = wirevalue::Any::from_encoded(
+                    Encoding::Json,
+                    typeuri::cityhasher::hash(typename.as_str()),
+                    message,
+                )?;
+                debugger::request(client, &self.actor, |reply| DebugMessage::Inject {
+                    message,
+                    reply,
+                })
+                .await?
+            }
+        };
+
+        if let DebugReply::Error(message) = reply {
+            anyhow::bail!("cannot debug actor {}: {}", self.actor, message);
+        }
+        println!("{}", serde_json::to_string_pretty(&reply)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use hyperactor as reference;
+use hyperactor::channel::ChannelAddr;
+use hyperactor::mailbox::topology::Topology;
+use hyperactor_mesh::context;
+use hyperactor_mesh::host::SERVICE_PROC_NAME;
+use hyperactor_mesh::host_mesh::host_agent::HOST_MESH_AGENT_ACTOR_NAME;
+use hyperactor_mesh::host_mesh::host_agent::HostAgent;
+use hyperactor_mesh::mesh_id::ResourceId;
+use hyperactor_mesh::proc_agent::PROC_AGENT_ACTOR_NAME;
+use hyperactor_mesh::proc_agent::ProcAgent;
+use hyperactor_mesh::topology::TopologyDumpClient;
+
+#[derive(clap::Args, Debug)]
+pub struct TopologyCommand {
+    /// The procs whose routing topologies to render, merged into one
+    /// graph, with the cast edges of their comm actors. A host address
+    /// stands for the host's service proc.
+    #[arg(required = true)]
+    references: Vec<String>,
+
+    /// Print the topology as JSON instead of Graphviz DOT.
+    #[arg(long)]
+    json: bool,
+}
+
+impl TopologyCommand {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let cx = context().await;
+        let client = cx.actor_instance;
+
+        let mut topology = Topology::new();
+        for reference in &self.references {
+            let proc = match reference.parse::<reference::Addr>() {
+                Ok(reference::Addr::Proc(proc)) => proc,
+                Ok(_) => anyhow::bail!("cannot render topology of {}: not a proc", reference),
+                Err(_) => {
+                    let host: ChannelAddr = reference.parse().map_err(|e| {
+                        anyhow::anyhow!(
+                            "could not parse '{}' as a proc or host reference: {}",
+                            reference,
+                            e
+                        )
+                    })?;
+                    ResourceId::proc_addr_from_name(host, SERVICE_PROC_NAME)
+                }
+            };
+
+            let is_service = proc
+                .uid()
+                .as_singleton()
+                .is_some_and(|label| label.as_str() == SERVICE_PROC_NAME);
+            let result = if is_service {
+                let agent: reference::ActorRef<HostAgent> =
+                    reference::ActorRef::attest(proc.actor_addr(HOST_MESH_AGENT_ACTOR_NAME));
+                agent.topology_dump(&client).await?
+            } else {
+                let agent: reference::ActorRef<ProcAgent> =
+                    reference::ActorRef::attest(proc.actor_addr(PROC_AGENT_ACTOR_NAME));
+                agent.topology_dump(&client).await?
+            };
+            topology.merge(result.topology);
+        }
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&topology)?);
+        } else {
+            print!("{}", topology.to_dot());
+        }
+
+        Ok(())
+    }
+}
@@ -17,9 +17,11 @@ use clap::Subcommand;
 // explicit in the generated Cargo.toml.
 use tokio as _;
 
+use crate::commands::debug::DebugCommand;
 use crate::commands::list::ListCommand;
 use crate::commands::resolve::ResolveCommand;
 use crate::commands::show::ShowCommand;
+use crate::commands::topology::TopologyCommand;
 
 #[derive(Parser)]
 #[command()]
@@ -38,6 +40,12 @@ enum Command {
 
     #[clap(about = "Resolve a MAST job handle to a mesh admin URL")]
     Resolve(ResolveCommand),
+
+    #[clap(about = "Freeze an actor and inspect, step, or inject its messages")]
+    Debug(DebugCommand),
+
+    #[clap(about = "Render the routing topology of procs as Graphviz DOT or JSON")]
+    Topology(TopologyCommand),
 }
 
 #[cfg(fbcode_build)]
@@ -60,6 +68,8 @@ async fn run() -> Result<(), anyhow::Error> {
         Command::Show(command) => command.run().await,
         Command::List(command) => command.run().await,
         Command::Resolve(command) => command.run().await,
+        Command::Debug(command) => command.run().await,
+        Command::Topology(command) => command.run().await,
     };
 
     // Allow the channel layer to flush pending acks before exit.
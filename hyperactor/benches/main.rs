@@ -6,6 +6,11 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::alloc::System;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 use std::time::Duration;
 use std::time::Instant;
 
@@ -15,6 +20,8 @@ use criterion::Throughput;
 use criterion::criterion_group;
 use criterion::criterion_main;
 use futures::future::join_all;
+use hyperactor::Client;
+use hyperactor::PortRef;
 use hyperactor::channel;
 use hyperactor::channel::ChannelAddr;
 use hyperactor::channel::ChannelTransport;
@@ -23,10 +30,13 @@ use hyperactor::channel::TcpMode;
 use hyperactor::channel::Tx;
 use hyperactor::channel::dial;
 use hyperactor::channel::serve;
+use hyperactor::endpoint::Endpoint as _;
 use hyperactor::mailbox::Mailbox;
 use hyperactor::mailbox::PortSender;
 use hyperactor::mailbox::monitored_return_handle;
+use hyperactor::proc::Proc;
 use hyperactor::testing::ids::test_actor_id;
+use hyperactor_config::Flattrs;
 use serde::Deserialize;
 use serde::Serialize;
 use serde_multipart::Part;
@@ -35,6 +45,41 @@ use tokio::runtime::Runtime;
 use tokio::sync::oneshot;
 use typeuri::Named;
 
+/// Counts heap allocations, so that benchmarks can report the allocations
+/// made per message along with their timings.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        // SAFETY: forwarded unchanged to the system allocator.
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: forwarded unchanged to the system allocator.
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// The number of allocations and allocated bytes made by `f`.
+fn count_allocations(f: impl FnOnce()) -> (u64, u64) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    f();
+    (
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes,
+    )
+}
+
 fn new_runtime() -> Runtime {
     runtime::Builder::new_current_thread()
         .enable_all()
@@ -347,6 +392,105 @@ fn bench_mailbox_message_rates(c: &mut Criterion) {
     group.finish();
 }
 
+// Benchmark concurrent posts to a mailbox's bound ports, which are
+// dominated by the mailbox's port lookup.
+fn bench_mailbox_post(c: &mut Criterion) {
+    const POSTERS: usize = 4;
+
+    let mut group = c.benchmark_group("mailbox_post");
+    group.throughput(Throughput::Elements(POSTERS as u64));
+    for num_ports in [1, 1000] {
+        group.bench_function(BenchmarkId::new("ports", num_ports), move |b| {
+            b.iter_custom(|iters| {
+                let actor_id = test_actor_id("world_0", "actor");
+                let mbox = Mailbox::new(actor_id);
+                let (ports, _receivers): (Vec<_>, Vec<_>) = (0..num_ports)
+                    .map(|_| {
+                        let (port, receiver) = mbox.open_port::<u64>();
+                        (port.bind(), receiver)
+                    })
+                    .unzip();
+
+                let start = Instant::now();
+                std::thread::scope(|scope| {
+                    for poster in 0..POSTERS {
+                        let mbox = &mbox;
+                        let ports = &ports;
+                        scope.spawn(move || {
+                            for i in 0..iters as usize {
+                                let port = &ports[(i * POSTERS + poster) % ports.len()];
+                                mbox.serialize_and_send(port, i as u64, monitored_return_handle())
+                                    .unwrap();
+                            }
+                        });
+                    }
+                });
+                start.elapsed()
+            });
+        });
+    }
+    group.finish();
+}
+
+// Benchmark PortRef posts to a port in the same proc, which are delivered
+// by value, against posting the same message serialized.
+fn bench_local_port_ref_post(c: &mut Criterion) {
+    fn post(client: &Client, port: &PortRef<Message>, serialized: bool, msg: Message) {
+        if serialized {
+            let data = wirevalue::Any::serialize(&msg).unwrap();
+            port.post_serialized(client, Flattrs::new(), data);
+        } else {
+            port.post(client, msg);
+        }
+    }
+
+    for size in [10_000, 10_000_000] {
+        let mut group = c.benchmark_group("local_port_ref_post");
+        group.throughput(Throughput::Bytes(size as u64));
+        group.sampling_mode(criterion::SamplingMode::Flat);
+        group.sample_size(10);
+        for serialized in [false, true] {
+            let path = if serialized { "serialized" } else { "local" };
+
+            // Report the allocations made by a single post alongside
+            // the timings.
+            new_runtime().block_on(async {
+                let proc = Proc::isolated();
+                let client = proc.client("client");
+                let (port, mut receiver) = client.open_port::<Message>();
+                let port = port.bind();
+                let msg = Message::new(0, size);
+                let (allocations, bytes) =
+                    count_allocations(|| post(&client, &port, serialized, msg));
+                receiver.recv().await.unwrap();
+                println!(
+                    "local_port_ref_post/{}/{}: {} allocations, {} bytes per post",
+                    path, size, allocations, bytes
+                );
+            });
+
+            group.bench_function(BenchmarkId::new(path, size), move |b| {
+                let mut b = b.to_async(new_runtime());
+                b.iter_custom(|iters| async move {
+                    let proc = Proc::isolated();
+                    let client = proc.client("client");
+                    let (port, mut receiver) = client.open_port::<Message>();
+                    let port = port.bind();
+
+                    let msg = Message::new(0, size);
+                    let start = Instant::now();
+                    for _ in 0..iters {
+                        post(&client, &port, serialized, msg.clone());
+                        receiver.recv().await.unwrap();
+                    }
+                    start.elapsed()
+                });
+            });
+        }
+        group.finish();
+    }
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().without_plots();
@@ -354,6 +498,8 @@ criterion_group! {
     bench_message_rates,
     bench_mailbox_message_sizes,
     bench_mailbox_message_rates,
+    bench_mailbox_post,
+    bench_local_port_ref_post,
     bench_channel_ping_pong,
 }
 
@@ -10,6 +10,7 @@
 
 use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::sync::Arc;
 use std::sync::OnceLock;
 use std::time::Duration;
 
@@ -60,6 +61,55 @@ pub struct StreamingReducerOpts {
     pub initial_update_interval: Option<Duration>,
 }
 
+/// When an accumulator port delivers its state to its receiver; see
+/// [`crate::Mailbox::open_accum_port_with_policy`]. Every update is
+/// accumulated into the state regardless of the policy; the policy
+/// only controls which of the resulting states are delivered.
+#[derive(Clone)]
+pub enum EmitPolicy<S> {
+    /// Deliver only the latest state: states that the receiver has not
+    /// yet received are replaced by newer ones. This is the policy of
+    /// [`crate::Mailbox::open_accum_port`].
+    Latest,
+    /// Deliver the state after every update, in order.
+    Every,
+    /// Deliver the latest state at most once per interval. A state
+    /// updated within an interval of the previous delivery is delivered
+    /// at the end of the interval, so the final state is always
+    /// delivered.
+    Throttle(Duration),
+    /// Deliver the state when `predicate(last_delivered, current)` is
+    /// true; `last_delivered` is the default state until the first
+    /// delivery. States for which the predicate is false are not
+    /// delivered, but are reflected in the next state that is.
+    Delta(Arc<dyn Fn(&S, &S) -> bool + Send + Sync>),
+}
+
+impl<S> EmitPolicy<S> {
+    /// Deliver the state when `predicate(last_delivered, current)` is
+    /// true. See [`EmitPolicy::Delta`].
+    pub fn delta(predicate: impl Fn(&S, &S) -> bool + Send + Sync + 'static) -> Self {
+        Self::Delta(Arc::new(predicate))
+    }
+}
+
+impl<S> Default for EmitPolicy<S> {
+    fn default() -> Self {
+        Self::Latest
+    }
+}
+
+impl<S> std::fmt::Debug for EmitPolicy<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Latest => write!(f, "Latest"),
+            Self::Every => write!(f, "Every"),
+            Self::Throttle(interval) => f.debug_tuple("Throttle").field(interval).finish(),
+            Self::Delta(_) => write!(f, "Delta(..)"),
+        }
+    }
+}
+
 /// The mode in which a reducer operates.
 #[derive(
     Debug,
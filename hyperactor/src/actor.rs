@@ -62,6 +62,7 @@ use crate::proc::HandlerPorts;
 use crate::proc::Instance;
 use crate::proc::InstanceCell;
 use crate::proc::Proc;
+use crate::proc::admission::ResourceRequirements;
 use crate::supervision::ActorSupervisionEvent;
 
 pub mod remote;
@@ -207,6 +208,13 @@ pub trait Actor: Sized + Send + 'static {
     fn display_name(&self) -> Option<String> {
         None
     }
+
+    /// The proc resources that the actor reserves while it runs. The
+    /// actor is admitted to its proc only once they are reserved; see
+    /// [`crate::proc::admission`]. By default, actors reserve nothing.
+    fn resources(&self) -> ResourceRequirements {
+        ResourceRequirements::default()
+    }
 }
 
 /// Default implementation of [`Actor::handle_delivery_failure_event`]. Defined
@@ -335,6 +343,38 @@ pub trait Handler<M>: Actor {
     async fn handle(&mut self, cx: &Context<Self>, message: M) -> Result<(), anyhow::Error>;
 }
 
+/// A BatchHandler allows an actor to handle several M-typed messages in
+/// a single activation, amortizing per-message overhead. Batching is
+/// enabled for a port by exporting it with a batch size, for example
+/// `#[export(M { batch = 64 })]`: when the actor is ready to handle an
+/// M-typed message, the M-typed messages queued directly behind it (up
+/// to the batch size) are handled together with it. Messages are never
+/// reordered with respect to the actor's other messages.
+#[async_trait]
+pub trait BatchHandler<M>: Actor {
+    /// Handle a non-empty batch of M-typed messages, in delivery order.
+    /// The context carries the headers of the first message.
+    async fn handle_batch(
+        &mut self,
+        cx: &Context<Self>,
+        messages: Vec<M>,
+    ) -> Result<(), anyhow::Error>;
+}
+
+/// A batch of messages, handled by [`BatchHandler::handle_batch`].
+pub(crate) struct Batch<M>(pub(crate) Vec<M>);
+
+#[async_trait]
+impl<A, M> Handler<Batch<M>> for A
+where
+    A: BatchHandler<M>,
+    M: Message,
+{
+    async fn handle(&mut self, cx: &Context<Self>, batch: Batch<M>) -> Result<(), anyhow::Error> {
+        self.handle_batch(cx, batch.0).await
+    }
+}
+
 /// Blanket Handler impls for bypass-workq message types. Since these messages
 /// bypass workq, they will never be sent to actor's handler.
 ///
@@ -432,6 +472,9 @@ impl<A: Actor> Handler<Undeliverable<MessageEnvelope>> for A {
         let log_fields = (tracing::enabled!(tracing::Level::DEBUG)
             || tracing::enabled!(tracing::Level::ERROR))
         .then(|| DeliveryFailureLogFields::new(&message));
+        // Split ports forwarding to a remote port that is gone are
+        // closed with it.
+        cx.mailbox().unbind_returned_splits(&message);
         let result = self.handle_delivery_failure_event(cx, message).await;
         match result {
             Ok(_) => {
@@ -892,6 +935,11 @@ impl<A: Actor> ActorHandle<A> {
         &self.cell
     }
 
+    /// The actor's handler ports.
+    pub(crate) fn ports(&self) -> &HandlerPorts<A> {
+        &self.ports
+    }
+
     /// The [`ActorAddr`] of the actor represented by this handle.
     pub fn actor_addr(&self) -> &ActorAddr {
         self.cell.actor_addr()
@@ -10,9 +10,11 @@
 
 use std::any::TypeId;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::LazyLock;
+use std::sync::RwLock;
 
 use hyperactor_config::Flattrs;
 
@@ -97,8 +99,22 @@ pub struct SpawnableActor {
 
 inventory::collect!(SpawnableActor);
 
+/// Actors registered at runtime with [`Remote::register_dynamic`].
+#[derive(Default)]
+struct Dynamic {
+    /// The current entry of each actor type, by name.
+    by_name: HashMap<&'static str, &'static SpawnableActor>,
+    /// The addresses of all entries ever registered, including those
+    /// replaced by later versions.
+    registered: HashSet<usize>,
+}
+
+static DYNAMIC: LazyLock<RwLock<Dynamic>> = LazyLock::new(Default::default);
+
 /// Registry of actors linked into this image and registered by way of
-/// [`crate::register_spawnable`].
+/// [`crate::register_spawnable`]. Lookups by name fall back to the actors
+/// registered at runtime with [`Remote::register_dynamic`], e.g. by
+/// dynamically loaded plugins.
 #[derive(Debug)]
 pub struct Remote {
     by_name: HashMap<&'static str, &'static SpawnableActor>,
@@ -110,7 +126,18 @@ impl Remote {
     pub fn collect() -> Self {
         let mut by_name = HashMap::new();
         let mut by_type_id = HashMap::new();
+        let dynamic = DYNAMIC.read().expect("dynamic actor registry poisoned");
         for entry in inventory::iter::<SpawnableActor> {
+            // Libraries loaded at runtime add their entries to the
+            // inventory, where successive versions of an actor type
+            // share its name; they are looked up through the dynamic
+            // registry instead.
+            if dynamic
+                .registered
+                .contains(&(entry as *const SpawnableActor as usize))
+            {
+                continue;
+            }
             if by_name.insert(**entry.name, entry).is_some() {
                 panic!("actor name {} registered multiple times", **entry.name);
             }
@@ -141,6 +168,56 @@ impl Remote {
             .map(|entry| **entry.name)
     }
 
+    /// Returns the registration entry of the provided actor, if registered,
+    /// including by a library loaded after this registry was collected.
+    pub fn entry_of<A: Actor>(&self) -> Option<&'static SpawnableActor> {
+        self.by_type_id
+            .get(&TypeId::of::<A>())
+            .copied()
+            .or_else(|| {
+                inventory::iter::<SpawnableActor>
+                    .into_iter()
+                    .find(|entry| (entry.get_type_id)() == TypeId::of::<A>())
+            })
+    }
+
+    /// Returns whether an actor type named `actor_type` is registered.
+    pub fn contains(&self, actor_type: &str) -> bool {
+        self.lookup(actor_type).is_some()
+    }
+
+    /// Register `entry` at runtime, making its actor type spawnable by name
+    /// in every registry in this process. A later registration under the same
+    /// name replaces an earlier one, so that a new version of an actor type
+    /// can be rolled out; actors already spawned are unaffected. Returns an
+    /// error if the name belongs to a different actor linked into this image.
+    pub fn register_dynamic(entry: &'static SpawnableActor) -> Result<(), anyhow::Error> {
+        let name: &'static str = **entry.name;
+        if let Some(linked) = Remote::global().by_name.get(name) {
+            if std::ptr::eq(*linked, entry) {
+                return Ok(());
+            }
+            anyhow::bail!("actor name {} is already linked into this image", name);
+        }
+        let mut dynamic = DYNAMIC.write().expect("dynamic actor registry poisoned");
+        dynamic.by_name.insert(name, entry);
+        dynamic
+            .registered
+            .insert(entry as *const SpawnableActor as usize);
+        Ok(())
+    }
+
+    fn lookup(&self, actor_type: &str) -> Option<&'static SpawnableActor> {
+        self.by_name.get(actor_type).copied().or_else(|| {
+            DYNAMIC
+                .read()
+                .expect("dynamic actor registry poisoned")
+                .by_name
+                .get(actor_type)
+                .copied()
+        })
+    }
+
     /// Spawns the actor with the provided sender, actor uid,
     /// and serialized parameters. Returns an error if the actor is not
     /// registered, or if the actor's spawn fails.
@@ -153,8 +230,7 @@ impl Remote {
         environment: Flattrs,
     ) -> Result<crate::ActorAddr, anyhow::Error> {
         let entry = self
-            .by_name
-            .get(actor_type)
+            .lookup(actor_type)
             .ok_or_else(|| anyhow::anyhow!("actor type {} not registered", actor_type))?;
         (entry.gspawn_root_bind)(proc, actor_uid, params, environment).await
     }
@@ -171,8 +247,7 @@ impl Remote {
         environment: Flattrs,
     ) -> Result<AnyActorHandle, anyhow::Error> {
         let entry = self
-            .by_name
-            .get(actor_type)
+            .lookup(actor_type)
             .ok_or_else(|| anyhow::anyhow!("actor type {} not registered", actor_type))?;
         (entry.gspawn_child)(proc, parent, actor_uid, params, environment).await
     }
@@ -458,6 +458,8 @@ pub struct PortAddr {
     location: Location,
 }
 
+hyperactor_config::impl_attrvalue!(PortAddr);
+
 impl PortAddr {
     /// Create a new [`PortAddr`].
     pub fn new(id: PortId, location: Location) -> Self {
@@ -0,0 +1,432 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! A content-addressed blob cache shared by the procs on a host.
+//!
+//! Blobs are files in [`BLOB_CACHE_DIR`], named by the SHA-256 [`Digest`]
+//! of their contents. Since the directory is shared, a payload that is
+//! stored by one proc is available to every other proc on the host, so
+//! that procs need not each transfer and store identical payloads
+//! (model weights, datasets, and the like). It is used by:
+//!
+//! - [large-message spill](crate::mailbox::spill), whose receivers
+//!   read spilled payloads from the cache when present, rather than
+//!   fetching them from the sender;
+//! - code sync, which [deduplicates](BlobCache::dedupe) large synced
+//!   files against the cache.
+//!
+//! Blobs are written to a temporary file and renamed into place, so
+//! that concurrent procs never observe a partially written blob. Blobs
+//! older than [`BLOB_CACHE_MAX_AGE`] are evicted, as are the oldest
+//! blobs when the cache grows beyond [`BLOB_CACHE_MAX_BYTES`]. The
+//! cache directory is created accessible only to its owner.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use hyperactor_config::global;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest as _;
+use sha2::Sha256;
+use typeuri::Named;
+
+use crate::config::BLOB_CACHE_DIR;
+use crate::config::BLOB_CACHE_MAX_AGE;
+use crate::config::BLOB_CACHE_MAX_BYTES;
+
+/// The SHA-256 digest of a blob's contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Named)]
+pub struct Digest([u8; 32]);
+
+impl Digest {
+    /// The digest of `data`.
+    pub fn of(data: &[u8]) -> Self {
+        Self(Sha256::digest(data).into())
+    }
+
+    /// The digest of the contents of the file at `path`.
+    pub fn of_file(path: &Path) -> io::Result<Self> {
+        let mut file = fs::File::open(path)?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; 1 << 20];
+        loop {
+            let len = file.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            hasher.update(&buf[..len]);
+        }
+        Ok(Self(hasher.finalize().into()))
+    }
+
+    /// The digest's bytes.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// The digest with the given bytes.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Errors parsing a [`Digest`].
+#[derive(thiserror::Error, Debug)]
+#[error("invalid digest: {0}")]
+pub struct ParseDigestError(String);
+
+impl FromStr for Digest {
+    type Err = ParseDigestError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(ParseDigestError(s.to_string()));
+        }
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16)
+                .map_err(|_| ParseDigestError(s.to_string()))?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+/// A directory of content-addressed blobs.
+#[derive(Debug, Clone)]
+pub struct BlobCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    max_age: Duration,
+}
+
+impl BlobCache {
+    /// The cache shared by the procs on this host, in [`BLOB_CACHE_DIR`].
+    pub fn host() -> io::Result<&'static BlobCache> {
+        static HOST: OnceLock<Result<BlobCache, String>> = OnceLock::new();
+        HOST.get_or_init(|| {
+            let dir = global::get(BLOB_CACHE_DIR);
+            let dir = if dir.is_empty() {
+                std::env::temp_dir().join("hyperactor-blob-cache")
+            } else {
+                PathBuf::from(dir)
+            };
+            let cache = BlobCache::new(dir, global::get(BLOB_CACHE_MAX_BYTES))
+                .map_err(|err| err.to_string())?
+                .with_max_age(global::get(BLOB_CACHE_MAX_AGE));
+            // Evict the blobs left behind by earlier processes.
+            let evicting = cache.clone();
+            std::thread::spawn(move || {
+                if let Err(err) = evicting.evict() {
+                    tracing::warn!(
+                        "failed to evict blobs from {}: {}",
+                        evicting.dir.display(),
+                        err
+                    );
+                }
+            });
+            Ok(cache)
+        })
+        .as_ref()
+        .map_err(|err| io::Error::other(err.clone()))
+    }
+
+    /// A cache in `dir`, holding at most `max_bytes` of blobs. The
+    /// directory is created, if needed, accessible only to its owner.
+    pub fn new(dir: PathBuf, max_bytes: u64) -> io::Result<Self> {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&dir)?;
+        Ok(Self {
+            dir,
+            max_bytes,
+            max_age: Duration::MAX,
+        })
+    }
+
+    /// Evict blobs once they are older than `max_age`.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// The path of the blob `digest`, which may not be cached.
+    pub fn path(&self, digest: &Digest) -> PathBuf {
+        self.dir.join(digest.to_string())
+    }
+
+    /// Whether the blob `digest` is cached.
+    pub fn contains(&self, digest: &Digest) -> bool {
+        self.path(digest).is_file()
+    }
+
+    /// The contents of the blob `digest`, if cached.
+    pub async fn get(&self, digest: Digest) -> io::Result<Option<Vec<u8>>> {
+        match tokio::fs::read(self.path(&digest)).await {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Cache `data`, returning its digest.
+    pub async fn insert(&self, data: Vec<u8>) -> io::Result<Digest> {
+        let cache = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let digest = Digest::of(&data);
+            if !cache.contains(&digest) {
+                let tmp = cache.tmp_path(&digest);
+                fs::write(&tmp, &data)?;
+                cache.persist(&tmp, &digest)?;
+            }
+            Ok(digest)
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+
+    /// Cache the contents of the file at `path`, so that identical files
+    /// on this host share storage where the filesystem supports
+    /// reflinks. The file and the cached blob remain independent: a
+    /// change to one never affects the other. Returns the file's digest.
+    pub async fn dedupe(&self, path: &Path) -> io::Result<Digest> {
+        let cache = self.clone();
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let digest = Digest::of_file(&path)?;
+            let blob = cache.path(&digest);
+            if !blob.is_file() {
+                let tmp = cache.tmp_path(&digest);
+                clone_file(&path, &tmp)?;
+                cache.persist(&tmp, &digest)?;
+                return Ok(digest);
+            }
+            // Replace the file with a reflink to the blob, keeping its
+            // permissions and modification time. Without reflinks, a copy
+            // would save nothing, so the file is left as is.
+            #[cfg(target_os = "linux")]
+            {
+                let metadata = fs::metadata(&path)?;
+                let tmp = path.with_extension(format!("dedupe.{}", uuid::Uuid::new_v4()));
+                if reflink(&blob, &tmp).is_ok() {
+                    let replaced = fs::set_permissions(&tmp, metadata.permissions())
+                        .and_then(|()| {
+                            fs::File::options()
+                                .write(true)
+                                .open(&tmp)?
+                                .set_modified(metadata.modified()?)
+                        })
+                        .and_then(|()| fs::rename(&tmp, &path));
+                    if replaced.is_err() {
+                        let _ = fs::remove_file(&tmp);
+                    }
+                    replaced?;
+                }
+            }
+            Ok(digest)
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+
+    fn tmp_path(&self, digest: &Digest) -> PathBuf {
+        self.dir
+            .join(format!(".{}.{}.tmp", digest, uuid::Uuid::new_v4()))
+    }
+
+    /// Move the blob at `tmp` into place as `digest`, then evict blobs
+    /// beyond the cache's capacity.
+    fn persist(&self, tmp: &Path, digest: &Digest) -> io::Result<()> {
+        fs::rename(tmp, self.path(digest))?;
+        if let Err(err) = self.evict() {
+            tracing::warn!("failed to evict blobs from {}: {}", self.dir.display(), err);
+        }
+        Ok(())
+    }
+
+    /// Remove the blobs older than `max_age`, then the oldest blobs
+    /// until the cache holds at most `max_bytes`.
+    fn evict(&self) -> io::Result<()> {
+        let expiry = SystemTime::now().checked_sub(self.max_age);
+        let mut blobs = Vec::new();
+        let mut total = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            let modified = metadata.modified()?;
+            if expiry.is_some_and(|expiry| modified < expiry) {
+                match fs::remove_file(entry.path()) {
+                    Ok(()) => continue,
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                    Err(err) => return Err(err),
+                }
+            }
+            total += metadata.len();
+            blobs.push((modified, metadata.len(), entry.path()));
+        }
+        if total <= self.max_bytes {
+            return Ok(());
+        }
+        blobs.sort();
+        for (_, len, path) in blobs {
+            if total <= self.max_bytes {
+                break;
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => total -= len,
+                // Another proc evicted it first.
+                Err(err) if err.kind() == io::ErrorKind::NotFound => total -= len,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Copy the file at `from` to a new file at `to`, sharing its storage
+/// (a reflink) where the filesystem supports it.
+fn clone_file(from: &Path, to: &Path) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    if reflink(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to).map(|_| ())
+}
+
+/// Create `to` as a reflink of `from`, which shares its storage until
+/// either is written.
+#[cfg(target_os = "linux")]
+fn reflink(from: &Path, to: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    nix::ioctl_write_int!(ficlone, 0x94, 9);
+
+    let src = fs::File::open(from)?;
+    let dst = fs::File::create_new(to)?;
+    // SAFETY: FICLONE takes the source descriptor as its argument, and
+    // both descriptors are open for the duration of the call.
+    match unsafe { ficlone(dst.as_raw_fd(), src.as_raw_fd() as _) } {
+        Ok(_) => Ok(()),
+        Err(errno) => {
+            drop(dst);
+            let _ = fs::remove_file(to);
+            Err(errno.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    #[test]
+    fn test_digest() {
+        let digest = Digest::of(b"hello");
+        assert_eq!(
+            digest.to_string(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(digest.to_string().parse::<Digest>().unwrap(), digest);
+        assert!("nope".parse::<Digest>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_insert_and_evict() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = BlobCache::new(dir.path().to_path_buf(), 10).unwrap();
+
+        let first = cache.insert(b"123456".to_vec()).await.unwrap();
+        assert_eq!(first, Digest::of(b"123456"));
+        assert_eq!(cache.get(first).await.unwrap().unwrap(), b"123456");
+        // Inserting the same contents is a no-op.
+        assert_eq!(cache.insert(b"123456".to_vec()).await.unwrap(), first);
+
+        // Exceeding the capacity evicts the oldest blob.
+        let file = fs::File::options()
+            .write(true)
+            .open(cache.path(&first))
+            .unwrap();
+        file.set_modified(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap();
+        let second = cache.insert(b"abcdef".to_vec()).await.unwrap();
+        assert!(!cache.contains(&first));
+        assert!(cache.contains(&second));
+        assert_eq!(cache.get(first).await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_evict_expired() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = BlobCache::new(dir.path().to_path_buf(), u64::MAX)
+            .unwrap()
+            .with_max_age(Duration::from_secs(3600));
+        let old = cache.path(&Digest::of(b"old"));
+        let new = cache.path(&Digest::of(b"new"));
+        fs::write(&old, b"old").unwrap();
+        fs::write(&new, b"new").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(7200))
+            .unwrap();
+
+        cache.evict().unwrap();
+        assert!(!old.exists());
+        assert!(new.exists());
+    }
+
+    #[tokio::test]
+    async fn test_dedupe() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = BlobCache::new(dir.path().join("cache"), u64::MAX).unwrap();
+        let mode = fs::metadata(dir.path().join("cache"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+        fs::write(&a, b"weights").unwrap();
+        fs::copy(&a, &b).unwrap();
+
+        let digest = cache.dedupe(&a).await.unwrap();
+        assert_eq!(cache.dedupe(&b).await.unwrap(), digest);
+        assert_eq!(fs::read(&b).unwrap(), b"weights");
+
+        // The files and the cached blob are independent: changing a
+        // file does not change the blob.
+        fs::write(&a, b"changed").unwrap();
+        assert_eq!(cache.get(digest).await.unwrap().unwrap(), b"weights");
+        assert_eq!(fs::read(&b).unwrap(), b"weights");
+    }
+}
@@ -0,0 +1,143 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Typed error replies.
+//!
+//! A call message whose reply port carries a `Result<T, E>` can mark
+//! the port with `#[reply(result)]`; the client methods generated by
+//! `HandleClient` and `RefClient` then return `Result<T, CallError<E>>`
+//! instead of nesting the handler's result in the transport's:
+//!
+//! ```ignore
+//! #[derive(Handler, HandleClient, RefClient, Debug, Serialize, Deserialize, Named)]
+//! enum Store {
+//!     Get(String, #[reply(result)] OncePortRef<Result<u64, StoreError>>),
+//! }
+//!
+//! match store.get(cx, key).await {
+//!     Ok(value) => ..,
+//!     Err(CallError::Reply(StoreError::NotFound)) => ..,
+//!     Err(err) => return Err(err.into()),
+//! }
+//! ```
+//!
+//! The error type must implement `Display`. It is sent as a value of
+//! the domain type `E`, not as a string. The typehash of the reply
+//! (`Result<T, E>`) is derived from `E`'s, so a reply with a different
+//! error type is rejected by the reply port as a type mismatch rather
+//! than decoded into the wrong type.
+
+use std::fmt;
+
+use typeuri::Named;
+
+use crate::mailbox::MailboxError;
+
+/// The error of a call whose reply is a `Result<T, E>`.
+#[derive(Debug)]
+pub enum CallError<E> {
+    /// The actor replied with an error.
+    Reply(E),
+    /// No reply was received.
+    Failed(anyhow::Error),
+}
+
+impl<E> CallError<E> {
+    /// Flatten a received reply into the call's result.
+    pub fn from_reply<T>(reply: Result<Result<T, E>, MailboxError>) -> Result<T, Self> {
+        match reply {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(err)) => Err(Self::Reply(err)),
+            Err(err) => Err(Self::Failed(err.into())),
+        }
+    }
+
+    /// The error the actor replied with, if any.
+    pub fn reply(&self) -> Option<&E> {
+        match self {
+            Self::Reply(err) => Some(err),
+            Self::Failed(_) => None,
+        }
+    }
+
+    /// The error the actor replied with, or the failure converted
+    /// with `f`.
+    pub fn into_reply(self, f: impl FnOnce(anyhow::Error) -> E) -> E {
+        match self {
+            Self::Reply(err) => err,
+            Self::Failed(err) => f(err),
+        }
+    }
+}
+
+impl<E: Named + fmt::Display> fmt::Display for CallError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reply(err) => write!(f, "call failed with {}: {}", E::typename(), err),
+            Self::Failed(err) => write!(f, "call failed: {}", err),
+        }
+    }
+}
+
+impl<E: Named + fmt::Debug + fmt::Display> std::error::Error for CallError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Reply(_) => None,
+            Self::Failed(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde::Serialize;
+
+    use super::*;
+    use crate::Endpoint as _;
+    use crate::Proc;
+    use crate::mailbox::open_once_port;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named)]
+    enum LookupError {
+        NotFound(String),
+    }
+
+    impl fmt::Display for LookupError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::NotFound(key) => write!(f, "{} not found", key),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_typed_error_reply() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+
+        let (port, receiver) = open_once_port::<Result<u64, LookupError>>(&client);
+        port.bind()
+            .post(&client, Err(LookupError::NotFound("a".to_string())));
+        let err = CallError::from_reply(receiver.recv().await).unwrap_err();
+        assert_eq!(err.reply(), Some(&LookupError::NotFound("a".to_string())));
+
+        // The typed error survives conversion into anyhow.
+        let err = anyhow::Error::from(err);
+        assert!(matches!(
+            err.downcast_ref::<CallError<LookupError>>(),
+            Some(CallError::Reply(LookupError::NotFound(_)))
+        ));
+
+        // Replies with other error types have other typehashes.
+        assert_ne!(
+            Result::<u64, LookupError>::typehash(),
+            Result::<u64, String>::typehash()
+        );
+    }
+}
@@ -0,0 +1,357 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Structured cancellation.
+//!
+//! A caller attaches a [`CancelToken`] to the messages of an operation
+//! by setting [`crate::mailbox::headers::CANCEL_TOKEN`]. Messages sent
+//! by an actor while it handles a message carrying a token inherit the
+//! token, so it follows the operation through the actors (and casts)
+//! that it reaches.
+//!
+//! Each actor remembers which actors it sent a token to. When the
+//! caller cancels the token with [`crate::proc::Instance::cancel`], a
+//! [`Cancel`] message is delivered to the control port
+//! ([`crate::port::ControlPort::Cancel`]) of each of those actors,
+//! which in turn forward it to the actors they sent the token to.
+//! Cancellation is recorded when the control message is delivered,
+//! independently of the actor's work queue, so a handler that is busy
+//! with a long computation can observe it with
+//! [`crate::proc::Context::is_cancelled`] or
+//! [`crate::proc::Context::cancelled`].
+//!
+//! Cancellation is advisory: handlers decide whether and when to
+//! abort. Each actor remembers a bounded number of tokens (see
+//! [`crate::config::CANCEL_TOKEN_CAPACITY`]); the oldest are
+//! forgotten first.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use hyperactor_config::AttrValue;
+use hyperactor_config::Flattrs;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::sync::Notify;
+use typeuri::Named;
+use uuid::Uuid;
+
+use crate::ActorAddr;
+use crate::config;
+use crate::mailbox::MailboxSender;
+use crate::mailbox::MessageEnvelope;
+use crate::port::ControlPort;
+use crate::port::Port;
+
+/// Identifies a cancellable operation.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    Named,
+    AttrValue
+)]
+pub struct CancelToken(Uuid);
+
+impl CancelToken {
+    /// Create a new, unique cancel token.
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for CancelToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for CancelToken {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+/// The control message that cancels a token at the receiving actor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named)]
+pub struct Cancel {
+    /// The cancelled token.
+    pub token: CancelToken,
+}
+wirevalue::register_type!(Cancel);
+
+/// Per-actor cancellation state: the tokens the actor has cancelled,
+/// and the actors it has sent each live token to.
+#[derive(Debug)]
+pub(crate) struct Cancellations {
+    state: Mutex<State>,
+    notify: Notify,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Downstream actors per token, with tokens in insertion order.
+    targets: HashMap<CancelToken, HashSet<ActorAddr>>,
+    targets_order: VecDeque<CancelToken>,
+    /// Cancelled tokens, in cancellation order.
+    cancelled: HashSet<CancelToken>,
+    cancelled_order: VecDeque<CancelToken>,
+}
+
+impl Cancellations {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Mutex::new(State::default()),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Record that a message carrying `token` was sent to `dest`.
+    pub(crate) fn record(&self, token: CancelToken, dest: &ActorAddr) {
+        let capacity = hyperactor_config::global::get(config::CANCEL_TOKEN_CAPACITY);
+        let mut state = self.state.lock().unwrap();
+        if capacity == 0 || state.cancelled.contains(&token) {
+            return;
+        }
+        if !state.targets.contains_key(&token) {
+            while state.targets_order.len() >= capacity {
+                let Some(oldest) = state.targets_order.pop_front() else {
+                    break;
+                };
+                state.targets.remove(&oldest);
+            }
+            state.targets_order.push_back(token);
+        }
+        state.targets.entry(token).or_default().insert(dest.clone());
+    }
+
+    /// Whether `token` has been cancelled at this actor.
+    pub(crate) fn is_cancelled(&self, token: &CancelToken) -> bool {
+        self.state.lock().unwrap().cancelled.contains(token)
+    }
+
+    /// Wait until `token` is cancelled at this actor.
+    pub(crate) async fn cancelled(&self, token: &CancelToken) {
+        loop {
+            let notified = self.notify.notified();
+            if self.is_cancelled(token) {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Cancel `token`, returning the actors it was sent to, or `None`
+    /// if it was already cancelled.
+    fn cancel(&self, token: CancelToken) -> Option<HashSet<ActorAddr>> {
+        let capacity = hyperactor_config::global::get(config::CANCEL_TOKEN_CAPACITY).max(1);
+        let targets = {
+            let mut state = self.state.lock().unwrap();
+            if !state.cancelled.insert(token) {
+                return None;
+            }
+            state.cancelled_order.push_back(token);
+            while state.cancelled_order.len() > capacity {
+                let Some(oldest) = state.cancelled_order.pop_front() else {
+                    break;
+                };
+                state.cancelled.remove(&oldest);
+            }
+            state.targets_order.retain(|t| *t != token);
+            state.targets.remove(&token).unwrap_or_default()
+        };
+        self.notify.notify_waiters();
+        Some(targets)
+    }
+}
+
+/// Cancel `token` at the actor `sender` and forward the cancellation
+/// to the actors it sent the token to.
+pub(crate) fn cancel_and_propagate(
+    cancellations: &Cancellations,
+    sender: &ActorAddr,
+    router: &impl MailboxSender,
+    token: CancelToken,
+) {
+    let Some(targets) = cancellations.cancel(token) else {
+        return;
+    };
+    tracing::debug!(
+        actor_id = %sender,
+        token = %token,
+        "cancelling token at {} downstream actors",
+        targets.len()
+    );
+    for target in targets {
+        if &target == sender {
+            continue;
+        }
+        let data = match wirevalue::Any::serialize(&Cancel { token }) {
+            Ok(data) => data,
+            Err(err) => {
+                tracing::error!("failed to serialize cancel message: {}", err);
+                return;
+            }
+        };
+        let mut envelope = MessageEnvelope::new(
+            sender.clone(),
+            target.port_addr(Port::control(ControlPort::Cancel)),
+            data,
+            Flattrs::new(),
+        );
+        // Downstream actors may already be gone; that is not an error.
+        envelope.set_return_undeliverable(false);
+        router.post(envelope, crate::mailbox::monitored_return_handle());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use timed_test::async_timed_test;
+
+    use super::*;
+    use crate as hyperactor;
+    use crate::Actor;
+    use crate::ActorRef;
+    use crate::Context;
+    use crate::Endpoint as _;
+    use crate::Handler;
+    use crate::PortRef;
+    use crate::Proc;
+    use crate::RemoteEndpoint;
+    use crate::mailbox::headers::CANCEL_TOKEN;
+
+    #[derive(Debug, Clone, Serialize, Deserialize, Named)]
+    struct Work {
+        started: PortRef<()>,
+        cancelled: PortRef<CancelToken>,
+    }
+
+    /// Forwards work to a worker; the cancel token follows implicitly.
+    #[derive(Debug)]
+    #[hyperactor::export(handlers = [Work])]
+    struct Forwarder {
+        worker: ActorRef<Worker>,
+    }
+
+    #[async_trait]
+    impl Actor for Forwarder {}
+
+    #[async_trait]
+    impl Handler<Work> for Forwarder {
+        async fn handle(&mut self, cx: &Context<Self>, work: Work) -> anyhow::Result<()> {
+            self.worker.post(cx, work);
+            Ok(())
+        }
+    }
+
+    /// Blocks in its handler until the operation is cancelled.
+    #[derive(Debug, Default)]
+    #[hyperactor::export(handlers = [Work])]
+    struct Worker;
+
+    #[async_trait]
+    impl Actor for Worker {}
+
+    #[async_trait]
+    impl Handler<Work> for Worker {
+        async fn handle(&mut self, cx: &Context<Self>, work: Work) -> anyhow::Result<()> {
+            assert!(!cx.is_cancelled());
+            work.started.post(cx, ());
+            cx.cancelled().await;
+            assert!(cx.is_cancelled());
+            work.cancelled.post(cx, cx.cancel_token().unwrap());
+            Ok(())
+        }
+    }
+
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_cancel_propagates_through_forwarder() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let worker: ActorRef<Worker> = proc.spawn_with_label("worker", Worker).bind();
+        let forwarder: ActorRef<Forwarder> = proc
+            .spawn_with_label("forwarder", Forwarder { worker })
+            .bind();
+
+        let (started_tx, mut started_rx) = client.open_port::<()>();
+        let (cancelled_tx, mut cancelled_rx) = client.open_port::<CancelToken>();
+        let token = CancelToken::new();
+        let mut headers = Flattrs::new();
+        headers.set(CANCEL_TOKEN, token);
+        RemoteEndpoint::post_with_headers(
+            &forwarder.port::<Work>(),
+            &client,
+            headers,
+            Work {
+                started: started_tx.bind(),
+                cancelled: cancelled_tx.bind(),
+            },
+        );
+
+        // The worker is busy in its handler when the token is cancelled.
+        started_rx.recv().await.unwrap();
+        client.cancel(token);
+        assert_eq!(cancelled_rx.recv().await.unwrap(), token);
+    }
+
+    #[test]
+    fn test_cancel_is_idempotent_and_bounded() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(config::CANCEL_TOKEN_CAPACITY, 2);
+
+        let cancellations = Cancellations::new();
+        let dest = crate::testing::ids::test_actor_id("worker_0", "worker");
+        let tokens: Vec<_> = (0..3).map(|_| CancelToken::new()).collect();
+        for token in &tokens {
+            cancellations.record(*token, &dest);
+        }
+        // The oldest token's targets were forgotten.
+        assert_eq!(cancellations.cancel(tokens[0]), Some(HashSet::new()));
+        assert_eq!(
+            cancellations.cancel(tokens[1]),
+            Some(HashSet::from([dest.clone()]))
+        );
+        assert!(cancellations.is_cancelled(&tokens[1]));
+        assert_eq!(cancellations.cancel(tokens[1]), None);
+
+        // Sends after cancellation are not recorded.
+        cancellations.record(tokens[1], &dest);
+        assert!(
+            !cancellations
+                .state
+                .lock()
+                .unwrap()
+                .targets
+                .contains_key(&tokens[1])
+        );
+
+        // Cancelled tokens are bounded too.
+        cancellations.cancel(tokens[2]);
+        assert!(!cancellations.is_cancelled(&tokens[0]));
+        assert!(cancellations.is_cancelled(&tokens[2]));
+    }
+}
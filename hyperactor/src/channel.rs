@@ -13,6 +13,7 @@ use core::net::SocketAddr;
 use std::fmt;
 use std::net::IpAddr;
 use std::net::Ipv6Addr;
+use std::net::ToSocketAddrs;
 #[cfg(target_os = "linux")]
 use std::os::linux::net::SocketAddrExt;
 use std::os::unix::io::FromRawFd;
@@ -34,6 +35,7 @@ use crate as hyperactor;
 use crate::RemoteMessage;
 pub(crate) mod local;
 pub(crate) mod net;
+pub mod pool;
 
 // Public TLS API for HTTP services (mesh admin, TUI, etc.). The
 // implementation lives in `net` but we re-export here to keep `net`'s
@@ -158,6 +160,11 @@ pub enum CloseReason {
         /// `CODEC_MAX_FRAME_LENGTH` at the time of rejection.
         max: usize,
     },
+    /// The transport's keepalive detected that the peer stopped
+    /// responding (the connection was half-open, e.g. because the peer's
+    /// host crashed or was partitioned away), and the peer could not be
+    /// reached again within the delivery timeout.
+    PeerUnresponsive(String),
     /// Any close reason the transport hasn't classified further. The string
     /// is for display/logging only — do not parse it. If a caller needs to
     /// branch on a sub-case, lift it into its own variant on this enum.
@@ -171,6 +178,7 @@ impl fmt::Display for CloseReason {
             Self::OversizedFrame { size, max } => {
                 write!(f, "oversized frame: len={size} > max={max}")
             }
+            Self::PeerUnresponsive(s) => write!(f, "peer unresponsive: {}", s),
             Self::Other(s) => f.write_str(s),
         }
     }
@@ -419,6 +427,15 @@ impl TlsAddr {
     pub fn hostname(&self) -> &str {
         &self.hostname
     }
+
+    /// The `host:port` form of this address used in URLs, with IPv6
+    /// hosts enclosed in brackets.
+    fn url_authority(&self) -> String {
+        match self.hostname.parse::<IpAddr>() {
+            Ok(IpAddr::V6(ip)) => format!("[{}]:{}", ip, self.port),
+            _ => format!("{}:{}", self.hostname, self.port),
+        }
+    }
 }
 
 impl FromStr for TlsAddr {
@@ -441,6 +458,170 @@ impl fmt::Display for TlsAddr {
     }
 }
 
+/// Address format for TCP channels to a DNS name, which is resolved
+/// each time the channel (re)connects, so that a service may move
+/// between hosts without its address changing.
+///
+/// A `DnsAddr` either names a host and port (`name:port`), resolved to
+/// its A and AAAA records, or, without a port, names an SRV record
+/// (e.g. `_trainer._tcp.example.com`), whose targets and ports are
+/// dialed in priority order. SRV record names start with `_`; any
+/// other name must be given a port.
+#[derive(
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    Ord,
+    PartialOrd
+)]
+pub struct DnsAddr {
+    /// The DNS name to resolve.
+    pub name: Hostname,
+    /// The port to connect to, or `None` if `name` is an SRV record.
+    pub port: Option<Port>,
+}
+
+impl DnsAddr {
+    /// An address resolving `name`'s A and AAAA records.
+    pub fn new(name: impl Into<Hostname>, port: Port) -> Self {
+        Self {
+            name: name.into(),
+            port: Some(port),
+        }
+    }
+
+    /// An address resolving the SRV record `name`.
+    pub fn srv(name: impl Into<Hostname>) -> Self {
+        Self {
+            name: name.into(),
+            port: None,
+        }
+    }
+}
+
+impl FromStr for DnsAddr {
+    type Err = anyhow::Error;
+
+    fn from_str(addr: &str) -> Result<Self, Self::Err> {
+        let addr = match addr.rsplit_once(':') {
+            Some((name, port)) => Self::new(
+                name,
+                port.parse()
+                    .map_err(|_| anyhow::anyhow!("invalid DNS address port: {}", port))?,
+            ),
+            None if addr.starts_with('_') => Self::srv(addr),
+            None => anyhow::bail!(
+                "DNS address {} has no port (SRV record names start with '_')",
+                addr
+            ),
+        };
+        if addr.name.is_empty() || addr.name.contains(':') {
+            anyhow::bail!("invalid DNS name: {}", addr.name);
+        }
+        Ok(addr)
+    }
+}
+
+impl fmt::Display for DnsAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.port {
+            Some(port) => write!(f, "{}:{}", self.name, port),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// The order in which to try the IP addresses that a hostname resolves
+/// to, when it resolves to both IPv6 and IPv4 addresses. Addresses of
+/// the same family are tried in the order the resolver returns them.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    typeuri::Named,
+    strum::Display,
+    strum::EnumString
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum AddressPreference {
+    /// Try IPv6 addresses first, falling back to IPv4.
+    #[default]
+    PreferV6,
+    /// Try IPv4 addresses first, falling back to IPv6.
+    PreferV4,
+    /// Use only IPv6 addresses.
+    V6Only,
+    /// Use only IPv4 addresses.
+    V4Only,
+}
+
+impl AddressPreference {
+    /// The preference configured by
+    /// [`CHANNEL_ADDRESS_PREFERENCE`](crate::config::CHANNEL_ADDRESS_PREFERENCE).
+    pub fn configured() -> Self {
+        hyperactor_config::global::get(crate::config::CHANNEL_ADDRESS_PREFERENCE)
+    }
+
+    /// Whether addresses of `ip`'s family may be used.
+    fn allows(self, ip: &IpAddr) -> bool {
+        match self {
+            Self::V6Only => ip.is_ipv6(),
+            Self::V4Only => ip.is_ipv4(),
+            Self::PreferV6 | Self::PreferV4 => true,
+        }
+    }
+
+    /// Order `addrs` by this preference, dropping the addresses of
+    /// excluded families.
+    pub fn order(self, addrs: impl IntoIterator<Item = IpAddr>) -> Vec<IpAddr> {
+        let mut addrs: Vec<IpAddr> = addrs.into_iter().filter(|ip| self.allows(ip)).collect();
+        // A stable sort, so `false` (the preferred family) comes first.
+        addrs.sort_by_key(|ip| match self {
+            Self::PreferV4 => ip.is_ipv6(),
+            _ => ip.is_ipv4(),
+        });
+        addrs
+    }
+
+    /// Resolve `hostname` to the socket addresses to dial on `port`, in
+    /// preference order.
+    pub fn resolve(self, hostname: &str, port: Port) -> std::io::Result<Vec<SocketAddr>> {
+        let addrs = (hostname, port).to_socket_addrs()?.map(|addr| addr.ip());
+        let addrs: Vec<SocketAddr> = self
+            .order(addrs)
+            .into_iter()
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect();
+        if addrs.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} has no addresses allowed by {}", hostname, self),
+            ));
+        }
+        Ok(addrs)
+    }
+}
+
+impl AttrValue for AddressPreference {
+    fn display(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(s: &str) -> Result<Self, anyhow::Error> {
+        Ok(s.parse()?)
+    }
+}
+
 /// Types of channel transports.
 #[derive(
     Clone,
@@ -752,6 +933,12 @@ pub enum ChannelAddr {
         /// The address to which the server should bind to.
         bind_to: Box<ChannelAddr>,
     },
+
+    /// A DNS name used to establish TCP channels, resolved when the
+    /// channel is dialed, and again each time it reconnects. Written
+    /// `tcp!name:port`, or `tcp!_service._proto.name` for an SRV record.
+    /// Variants are encoded by index, so new ones go last.
+    Dns(DnsAddr),
 }
 
 impl From<SocketAddr> for ChannelAddr {
@@ -867,6 +1054,7 @@ impl ChannelAddr {
                     ChannelTransport::Tcp(TcpMode::Hostname)
                 }
             }
+            Self::Dns(_) => ChannelTransport::Tcp(TcpMode::Hostname),
             Self::MetaTls(addr) => match addr.hostname.parse::<IpAddr>() {
                 Ok(IpAddr::V6(_)) => ChannelTransport::MetaTls(TlsMode::IpV6),
                 Ok(IpAddr::V4(_)) => ChannelTransport::MetaTls(TlsMode::Hostname),
@@ -902,6 +1090,7 @@ impl fmt::Display for ChannelAddr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Tcp(addr) => write!(f, "tcp:{}", addr),
+            Self::Dns(addr) => write!(f, "tcp!{}", addr),
             Self::MetaTls(addr) => write!(f, "metatls:{}", addr),
             Self::Tls(addr) => write!(f, "tls:{}", addr),
             Self::Quic(addr) => write!(f, "quic:{}", addr),
@@ -919,6 +1108,12 @@ impl FromStr for ChannelAddr {
     type Err = anyhow::Error;
 
     fn from_str(addr: &str) -> Result<Self, Self::Err> {
+        // `tcp!` addresses that are not socket addresses are DNS names.
+        if let Some(rest) = addr.strip_prefix("tcp!")
+            && rest.parse::<SocketAddr>().is_err()
+        {
+            return DnsAddr::from_str(rest).map(Self::Dns);
+        }
         match addr.split_once('!').or_else(|| addr.split_once(':')) {
             Some(("local", rest)) => rest
                 .parse::<u64>()
@@ -978,6 +1173,7 @@ impl ChannelAddr {
     /// Parse ZMQ-style URL format: scheme://address
     /// Supports:
     /// - tcp://hostname:port or tcp://*:port (wildcard binding)
+    /// - dns://hostname:port or dns://srv-name (resolved at dial time)
     /// - inproc://endpoint-name (equivalent to local)
     /// - ipc://path (equivalent to unix)
     /// - metatls://hostname:port or metatls://*:port
@@ -1059,6 +1255,7 @@ impl ChannelAddr {
                 };
                 Ok((Self::Tcp(socket_addr), listener))
             }
+            "dns" => Ok((Self::Dns(DnsAddr::from_str(address)?), None)),
             "inproc" => {
                 let port = address.parse::<u64>().map_err(|_| {
                     anyhow::anyhow!("inproc endpoint must be a valid port number: {}", address)
@@ -1119,10 +1316,11 @@ impl ChannelAddr {
     pub fn to_zmq_url(&self) -> String {
         match self {
             Self::Tcp(addr) => format!("tcp://{}", addr),
-            Self::MetaTls(addr) => format!("metatls://{}:{}", addr.hostname, addr.port),
-            Self::Tls(addr) => format!("tls://{}:{}", addr.hostname, addr.port),
-            Self::Quic(addr) => format!("quic://{}:{}", addr.hostname, addr.port),
-            Self::MetaQuic(addr) => format!("metaquic://{}:{}", addr.hostname, addr.port),
+            Self::Dns(addr) => format!("dns://{}", addr),
+            Self::MetaTls(addr) => format!("metatls://{}", addr.url_authority()),
+            Self::Tls(addr) => format!("tls://{}", addr.url_authority()),
+            Self::Quic(addr) => format!("quic://{}", addr.url_authority()),
+            Self::MetaQuic(addr) => format!("metaquic://{}", addr.url_authority()),
             Self::Local(index) => format!("inproc://{}", index),
             Self::Unix(addr) => format!("ipc://{}", addr),
             Self::Alias { dial_to, bind_to } => {
@@ -1145,13 +1343,14 @@ impl ChannelAddr {
             return Ok(SocketAddr::new(ip_addr, port));
         }
 
-        // If not an IP, try hostname resolution
-        use std::net::ToSocketAddrs;
-        let mut addrs = (host_clean, port)
-            .to_socket_addrs()
+        // If not an IP, try hostname resolution, taking the most
+        // preferred address.
+        let addrs = AddressPreference::configured()
+            .resolve(host_clean, port)
             .map_err(|e| anyhow::anyhow!("failed to resolve hostname '{}': {}", host_clean, e))?;
 
         addrs
+            .into_iter()
             .next()
             .ok_or_else(|| anyhow::anyhow!("no addresses found for hostname '{}'", host_clean))
     }
@@ -1250,18 +1449,31 @@ impl<M: RemoteMessage> Rx<M> for ChannelRx<M> {
 #[allow(clippy::result_large_err)] // TODO: Consider reducing the size of `ChannelError`.
 #[track_caller]
 pub fn dial<M: RemoteMessage>(addr: ChannelAddr) -> Result<ChannelTx<M>, ChannelError> {
+    dial_with_preference(addr, AddressPreference::configured())
+}
+
+/// Like [`dial`], but when the address's hostname resolves to several
+/// IP addresses, try them in the order given by `preference` rather
+/// than the configured [`AddressPreference`].
+#[allow(clippy::result_large_err)] // TODO: Consider reducing the size of `ChannelError`.
+#[track_caller]
+pub fn dial_with_preference<M: RemoteMessage>(
+    addr: ChannelAddr,
+    preference: AddressPreference,
+) -> Result<ChannelTx<M>, ChannelError> {
     let addr = addr.into_dial_addr();
     tracing::debug!(name = "dial", caller = %Location::caller(), %addr, "dialing channel {}", addr);
     let inner = match addr {
         ChannelAddr::Local(port) => ChannelTxKind::Local(local::dial(port)?),
         ChannelAddr::Tcp(_)
+        | ChannelAddr::Dns(_)
         | ChannelAddr::Unix(_)
         | ChannelAddr::Tls(_)
         | ChannelAddr::MetaTls(_)
         | ChannelAddr::Quic(_)
-        | ChannelAddr::MetaQuic(_) => {
-            ChannelTxKind::Net(net::spawn(net::link(addr, net::SessionId::random(), 0)?))
-        }
+        | ChannelAddr::MetaQuic(_) => ChannelTxKind::Net(net::spawn(
+            net::link(addr, net::SessionId::random(), 0)?.with_address_preference(preference),
+        )),
         ChannelAddr::Alias { .. } => unreachable!("aliases are canonicalized before dialing"),
     };
     Ok(ChannelTx { inner })
@@ -1398,6 +1610,7 @@ fn serve_inner<M: RemoteMessage>(
             Ok((addr, ChannelRxKind::Net(rx)))
         }
         ChannelAddr::Tcp(_)
+        | ChannelAddr::Dns(_)
         | ChannelAddr::Tls(_)
         | ChannelAddr::MetaTls(_)
         | ChannelAddr::Quic(_)
@@ -1532,6 +1745,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_dns_channel_addr() {
+        let cases = vec![
+            (
+                "tcp!trainer.example.com:1234",
+                ChannelAddr::Dns(DnsAddr::new("trainer.example.com", 1234)),
+            ),
+            (
+                "tcp!_trainer._tcp.example.com",
+                ChannelAddr::Dns(DnsAddr::srv("_trainer._tcp.example.com")),
+            ),
+        ];
+        for (raw, parsed) in cases {
+            let addr = raw.parse::<ChannelAddr>().unwrap();
+            assert_eq!(addr, parsed);
+            assert_eq!(addr.to_string(), raw);
+            assert_eq!(ChannelAddr::from_zmq_url(&addr.to_zmq_url()).unwrap(), addr);
+            assert_eq!(addr.transport(), ChannelTransport::Tcp(TcpMode::Hostname));
+        }
+
+        // Socket addresses are still parsed as such.
+        assert_eq!(
+            "tcp!127.0.0.1:8080".parse::<ChannelAddr>().unwrap(),
+            ChannelAddr::Tcp("127.0.0.1:8080".parse().unwrap())
+        );
+        assert!("tcp!example.com:port".parse::<ChannelAddr>().is_err());
+        // A name without a port must be an SRV record's.
+        assert!("tcp!example.com".parse::<ChannelAddr>().is_err());
+    }
+
     #[test]
     fn test_zmq_style_channel_addr() {
         // Test TCP addresses
@@ -1980,4 +2223,86 @@ mod tests {
         let addrs = vec![link_local_v6, link_local_v4, routable_v4, routable_v6];
         assert_eq!(find_routable_address(&addrs), Some(routable_v4));
     }
+
+    #[test]
+    fn test_address_preference() {
+        let v4a: IpAddr = "10.0.0.1".parse().unwrap();
+        let v4b: IpAddr = "10.0.0.2".parse().unwrap();
+        let v6a: IpAddr = "2001:db8::1".parse().unwrap();
+        let v6b: IpAddr = "2001:db8::2".parse().unwrap();
+        let addrs = [v4a, v6a, v4b, v6b];
+
+        assert_eq!(
+            AddressPreference::PreferV6.order(addrs),
+            vec![v6a, v6b, v4a, v4b]
+        );
+        assert_eq!(
+            AddressPreference::PreferV4.order(addrs),
+            vec![v4a, v4b, v6a, v6b]
+        );
+        assert_eq!(AddressPreference::V6Only.order(addrs), vec![v6a, v6b]);
+        assert_eq!(AddressPreference::V4Only.order(addrs), vec![v4a, v4b]);
+
+        for preference in [
+            AddressPreference::PreferV6,
+            AddressPreference::PreferV4,
+            AddressPreference::V6Only,
+            AddressPreference::V4Only,
+        ] {
+            assert_eq!(
+                <AddressPreference as AttrValue>::parse(&preference.display()).unwrap(),
+                preference
+            );
+        }
+        assert_eq!(
+            "prefer_v4".parse::<AddressPreference>().unwrap(),
+            AddressPreference::PreferV4
+        );
+
+        assert_eq!(
+            AddressPreference::V4Only
+                .resolve("::1", 8080)
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            AddressPreference::PreferV4.resolve("::1", 8080).unwrap(),
+            vec!["[::1]:8080".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_ipv6_zmq_url_round_trip() {
+        for url in [
+            "tls://[::1]:443",
+            "metatls://[2001:db8::1]:443",
+            "quic://[::1]:8443",
+            "metaquic://[2001:db8::1]:8443",
+        ] {
+            let addr = ChannelAddr::from_zmq_url(url).unwrap();
+            assert_eq!(addr.to_zmq_url(), url);
+            assert_eq!(ChannelAddr::from_zmq_url(&addr.to_zmq_url()).unwrap(), addr);
+        }
+    }
+
+    #[tokio::test]
+    // TODO: OSS: binding [::]:0 requires IPv6 support in the sandbox.
+    #[cfg_attr(not(fbcode_build), ignore)]
+    async fn test_dual_stack_serve() {
+        // A server on the IPv6 unspecified address is reachable over IPv4
+        // when dual-stack binds are enabled.
+        let (listen_addr, mut rx) =
+            crate::channel::serve::<i32>(ChannelAddr::Tcp("[::]:0".parse().unwrap())).unwrap();
+        let ChannelAddr::Tcp(socket_addr) = listen_addr else {
+            panic!("unexpected listen address {}", listen_addr);
+        };
+        let tx = crate::channel::dial::<i32>(ChannelAddr::Tcp(SocketAddr::new(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            socket_addr.port(),
+        )))
+        .unwrap();
+        tx.post(123);
+        assert_eq!(rx.recv().await.unwrap(), 123);
+    }
 }
@@ -103,7 +103,7 @@ pub struct LocalTx<M: RemoteMessage> {
 #[async_trait]
 impl<M: RemoteMessage> Tx<M> for LocalTx<M> {
     fn do_post(&self, message: M, return_channel: Option<oneshot::Sender<SendError<M>>>) {
-        let data = match serde_multipart::serialize_bincode(&message) {
+        let data = match serde_multipart::serialize_bincode_pooled(&message, pool::global()) {
             Ok(data) => data,
             Err(err) => {
                 if let Some(return_channel) = return_channel {
@@ -49,7 +49,6 @@
 
 use std::fmt;
 use std::fmt::Debug;
-use std::net::ToSocketAddrs;
 use std::time::Duration;
 
 use backoff::ExponentialBackoffBuilder;
@@ -69,8 +68,10 @@ use tokio::time::Instant;
 use super::*;
 use crate::RemoteMessage;
 
+pub(crate) mod dns;
 pub mod duplex;
 mod framed;
+pub(crate) mod proxy;
 pub(crate) mod quic;
 pub(super) mod server;
 pub(super) mod session;
@@ -180,33 +181,43 @@ use session::Session;
 use crate::config;
 use crate::metrics;
 
-/// TCP keepalive probe interval after the idle period elapses.
-const TCP_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5);
-
-/// Number of failed keepalive probes before the kernel marks the
-/// connection dead.
-const TCP_KEEPALIVE_RETRIES: u32 = 3;
-
 /// Enable TCP keepalive on a freshly-created socket so the kernel can
-/// surface peer death on otherwise-idle connections.
+/// surface peer death on otherwise-idle connections, and bound how long
+/// written data may go unacknowledged, so that half-open connections
+/// with data in flight are detected too.
 /// [`config::CHANNEL_TCP_KEEPALIVE_IDLE`] is the kernel idle period —
 /// the gap from last activity to the first probe — so on a healthy
 /// idle connection it's also the probe cadence. Total detection time
-/// is `idle + TCP_KEEPALIVE_RETRIES * TCP_KEEPALIVE_INTERVAL`. Logs
-/// and ignores errors: keepalive is best-effort and some test
-/// harnesses use sockets that don't support it.
+/// is `idle + retries * interval` on an idle connection, and
+/// [`config::CHANNEL_TCP_USER_TIMEOUT`] on a busy one. The kernel
+/// reports either as a timeout, on which the send loop reconnects; the
+/// link closes only if the peer cannot be reached again within the
+/// delivery timeout. Logs and ignores errors: keepalive is best-effort
+/// and some test harnesses use sockets that don't support it.
 fn set_tcp_keepalive(stream: &tokio::net::TcpStream) {
     let idle = hyperactor_config::global::get(config::CHANNEL_TCP_KEEPALIVE_IDLE);
+    let interval = hyperactor_config::global::get(config::CHANNEL_TCP_KEEPALIVE_INTERVAL);
+    let retries = hyperactor_config::global::get(config::CHANNEL_TCP_KEEPALIVE_RETRIES);
 
     let ka = socket2::TcpKeepalive::new()
         .with_time(idle)
-        .with_interval(TCP_KEEPALIVE_INTERVAL)
-        .with_retries(TCP_KEEPALIVE_RETRIES);
+        .with_interval(interval)
+        .with_retries(retries);
 
     let sock = socket2::SockRef::from(stream);
     if let Err(err) = sock.set_tcp_keepalive(&ka) {
         tracing::warn!(?err, "failed to set TCP keepalive on stream");
     }
+
+    #[cfg(target_os = "linux")]
+    {
+        let user_timeout = hyperactor_config::global::get(config::CHANNEL_TCP_USER_TIMEOUT);
+        if !user_timeout.is_zero()
+            && let Err(err) = sock.set_tcp_user_timeout(Some(user_timeout))
+        {
+            tracing::warn!(?err, "failed to set TCP user timeout on stream");
+        }
+    }
 }
 
 pub(crate) enum LinkStatus {
@@ -308,6 +319,25 @@ fn log_send_error(
             );
             true
         }
+        session::SendLoopError::PeerUnresponsive(err) => {
+            tracing::warn!(
+                dest = %dest,
+                session_id,
+                mode,
+                error = %err,
+                "peer stopped responding; {link_status}"
+            );
+            metrics::CHANNEL_ERRORS.add(
+                1,
+                hyperactor_telemetry::kv_pairs!(
+                    "dest" => dest.to_string(),
+                    "session_id" => session_id.to_string(),
+                    "error_type" => metrics::ChannelErrorType::SendError.as_str(),
+                    "mode" => mode.to_string(),
+                ),
+            );
+            false
+        }
     }
 }
 
@@ -417,7 +447,7 @@ pub(crate) fn spawn_unordered<M: RemoteMessage>(links: Vec<impl Link + 'static>)
                                     match ack_result {
                                         Ok(Some(buffer)) => {
                                             let response = deserialize_response(buffer)
-                                                .map_err(|e| session::SendLoopError::Io(e.into()))?;
+                                                .map_err(|e| session::SendLoopError::io(std::io::Error::other(e)))?;
                                             match response {
                                                 NetRxResponse::Ack(ack) => {
                                                     let mut guard = unacked.lock().await;
@@ -434,7 +464,7 @@ pub(crate) fn spawn_unordered<M: RemoteMessage>(links: Vec<impl Link + 'static>)
                                             }
                                         }
                                         Ok(None) => return Ok(()),
-                                        Err(e) => return Err(session::SendLoopError::Io(e.into())),
+                                        Err(e) => return Err(session::SendLoopError::io(e)),
                                     }
                                 }
 
@@ -451,7 +481,7 @@ pub(crate) fn spawn_unordered<M: RemoteMessage>(links: Vec<impl Link + 'static>)
                                         return_channel,
                                     } = pending;
                                     let frame = Frame::Message(seq, message);
-                                    let serialized = match serde_multipart::serialize_bincode(&frame) {
+                                    let serialized = match serde_multipart::serialize_bincode_pooled(&frame, pool::global()) {
                                         Ok(m) => m,
                                         Err(e) => {
                                             tracing::error!(
@@ -471,9 +501,7 @@ pub(crate) fn spawn_unordered<M: RemoteMessage>(links: Vec<impl Link + 'static>)
                                         return_channel,
                                     };
                                     let framed = queued.message.clone().framed();
-                                    stream.write(framed).drive().await.map_err(|e| {
-                                        session::SendLoopError::Io(e.into())
-                                    })?;
+                                    stream.write(framed).drive().await.map_err(session::SendLoopError::io)?;
                                     queued.sent_at = Some(tokio::time::Instant::now());
                                     unacked.lock().await.insert(queued.seq, queued);
                                 }
@@ -612,6 +640,9 @@ fn spawn_inner<M: RemoteMessage>(link: impl Link) -> NetTx<M> {
             .build();
 
         let mut link_status = LinkStatus::NeverConnected;
+        // Why the peer was last found unresponsive, until the link is
+        // connected again.
+        let mut unresponsive: Option<String> = None;
 
         let reason: CloseReason = 'outer: loop {
             let connected = match deliveries.expiry_time() {
@@ -631,6 +662,11 @@ fn spawn_inner<M: RemoteMessage>(link: impl Link) -> NetTx<M> {
                         tracing::error!(
                             dest = %dest, session_id = session_id.0, "{}", error_msg
                         );
+                        if let Some(reason) = unresponsive {
+                            break 'outer CloseReason::PeerUnresponsive(format!(
+                                "{log_id}: {reason}; {error_msg}"
+                            ));
+                        }
                         break 'outer CloseReason::Other(format!("{log_id}: {error_msg}"));
                     }
                 },
@@ -663,6 +699,7 @@ fn spawn_inner<M: RemoteMessage>(link: impl Link) -> NetTx<M> {
             deliveries.requeue_unacked();
 
             link_status.connected();
+            unresponsive = None;
             let connected_at = tokio::time::Instant::now();
 
             let result = {
@@ -696,6 +733,9 @@ fn spawn_inner<M: RemoteMessage>(link: impl Link) -> NetTx<M> {
                     if log_send_error(e, &dest, session_id.0, "simplex", &link_status) {
                         break 'outer classify_send_loop_error(e, &log_id);
                     }
+                    if let session::SendLoopError::PeerUnresponsive(reason) = e {
+                        unresponsive = Some(reason.clone());
+                    }
                     // Recoverable error — reconnect after backoff.
                     if let Some(delay) = reconnect_backoff.next_backoff() {
                         tracing::info!(
@@ -747,6 +787,7 @@ fn spawn_inner<M: RemoteMessage>(link: impl Link) -> NetTx<M> {
 #[derive(Debug)]
 pub(crate) enum NetLink {
     Tcp(tcp::TcpLink),
+    Dns(dns::DnsLink),
     Unix(unix::UnixLink),
     Tls(tls::TlsLink),
     Quic(quic::QuicLink),
@@ -764,6 +805,7 @@ pub(crate) fn link(
         ChannelAddr::Tcp(socket_addr) => {
             Ok(NetLink::Tcp(tcp::link(socket_addr, session_id, stream_id)))
         }
+        ChannelAddr::Dns(dns_addr) => Ok(NetLink::Dns(dns::link(dns_addr, session_id, stream_id))),
         ChannelAddr::Unix(unix_addr) => {
             Ok(NetLink::Unix(unix::link(unix_addr, session_id, stream_id)))
         }
@@ -791,6 +833,24 @@ pub(crate) fn link(
     }
 }
 
+impl NetLink {
+    /// Try the addresses that the link's hostname resolves to in the
+    /// order given by `preference`. Links to socket addresses are
+    /// unaffected.
+    pub(crate) fn with_address_preference(
+        mut self,
+        preference: crate::channel::AddressPreference,
+    ) -> Self {
+        match &mut self {
+            Self::Dns(link) => link.preference = preference,
+            Self::Tls(link) => link.preference = preference,
+            Self::Quic(link) => link.preference = preference,
+            Self::Tcp(_) | Self::Unix(_) => {}
+        }
+        self
+    }
+}
+
 #[async_trait]
 impl Link for NetLink {
     type Stream = Box<dyn Stream>;
@@ -798,6 +858,7 @@ impl Link for NetLink {
     fn dest(&self) -> ChannelAddr {
         match self {
             Self::Tcp(l) => l.dest(),
+            Self::Dns(l) => l.dest(),
             Self::Unix(l) => l.dest(),
             Self::Tls(l) => l.dest(),
             Self::Quic(l) => l.dest(),
@@ -807,6 +868,7 @@ impl Link for NetLink {
     fn link_id(&self) -> SessionId {
         match self {
             Self::Tcp(l) => l.link_id(),
+            Self::Dns(l) => l.link_id(),
             Self::Unix(l) => l.link_id(),
             Self::Tls(l) => l.link_id(),
             Self::Quic(l) => l.link_id(),
@@ -816,6 +878,7 @@ impl Link for NetLink {
     async fn next(&mut self) -> Result<Box<dyn Stream>, ClientError> {
         match self {
             Self::Tcp(l) => Ok(Box::new(l.next().await?)),
+            Self::Dns(l) => Ok(Box::new(l.next().await?)),
             Self::Unix(l) => Ok(Box::new(l.next().await?)),
             Self::Tls(l) => Ok(Box::new(l.next().await?)),
             Self::Quic(l) => Ok(Box::new(l.next().await?)),
@@ -869,6 +932,38 @@ impl Listener for NetListener {
     }
 }
 
+/// Bind a TCP listener to the first of `addrs` that can be bound. A
+/// listener on the IPv6 unspecified address (`[::]`) also accepts IPv4
+/// connections when [`config::CHANNEL_DUAL_STACK`] is set, regardless
+/// of the host's `net.ipv6.bindv6only` default.
+fn bind_tcp_listener(addrs: &[SocketAddr]) -> std::io::Result<std::net::TcpListener> {
+    let dual_stack = hyperactor_config::global::get(config::CHANNEL_DUAL_STACK);
+    let mut last_err = None;
+    for addr in addrs {
+        let bind = || {
+            let socket = socket2::Socket::new(
+                socket2::Domain::for_address(*addr),
+                socket2::Type::STREAM,
+                Some(socket2::Protocol::TCP),
+            )?;
+            if let SocketAddr::V6(v6) = addr
+                && v6.ip().is_unspecified()
+            {
+                socket.set_only_v6(!dual_stack)?;
+            }
+            socket.set_reuse_address(true)?;
+            socket.bind(&(*addr).into())?;
+            socket.listen(1024)?;
+            std::io::Result::Ok(std::net::TcpListener::from(socket))
+        };
+        match bind() {
+            Ok(listener) => return Ok(listener),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| std::io::Error::other("no address to bind")))
+}
+
 /// Bind a listener for the given channel address, optionally using a pre-opened TCP listener.
 /// Returns the listener and the canonical address callers should advertise.
 /// When `prebound` is `Some`, it is used for TCP/TLS transports instead of binding a new socket.
@@ -880,7 +975,7 @@ pub(crate) fn listen_with_prebound(
         ChannelAddr::Tcp(socket_addr) => {
             let std_listener = match prebound {
                 Some(l) => l,
-                None => std::net::TcpListener::bind(socket_addr)
+                None => bind_tcp_listener(&[socket_addr])
                     .map_err(|err| ServerError::Listen(ChannelAddr::Tcp(socket_addr), err))?,
             };
             std_listener
@@ -897,6 +992,45 @@ pub(crate) fn listen_with_prebound(
             };
             Ok((NetListener::Tcp(listener), ChannelAddr::Tcp(local_addr)))
         }
+        ChannelAddr::Dns(ref dns_addr) => {
+            // Bind to the name's addresses, but advertise the name, so
+            // that dialers re-resolve it when they reconnect.
+            let Some(port) = dns_addr.port else {
+                return Err(ServerError::Listen(
+                    addr.clone(),
+                    std::io::Error::other("cannot serve on an SRV record"),
+                ));
+            };
+            let std_listener = match prebound {
+                Some(l) => l,
+                None => {
+                    let addrs = crate::channel::AddressPreference::configured()
+                        .resolve(&dns_addr.name, port)
+                        .map_err(|err| ServerError::Resolve(addr.clone(), err))?;
+                    bind_tcp_listener(&addrs)
+                        .map_err(|err| ServerError::Listen(addr.clone(), err))?
+                }
+            };
+            std_listener
+                .set_nonblocking(true)
+                .map_err(|err| ServerError::Listen(addr.clone(), err))?;
+            let tokio_listener = tokio::net::TcpListener::from_std(std_listener)
+                .map_err(|err| ServerError::Listen(addr.clone(), err))?;
+            let local_addr = tokio_listener
+                .local_addr()
+                .map_err(|err| ServerError::Resolve(addr.clone(), err))?;
+            let listener = tcp::TcpSocketListener {
+                inner: tokio_listener,
+                addr: local_addr,
+            };
+            Ok((
+                NetListener::Tcp(listener),
+                ChannelAddr::Dns(crate::channel::DnsAddr::new(
+                    dns_addr.name.clone(),
+                    local_addr.port(),
+                )),
+            ))
+        }
         ChannelAddr::Unix(ref unix_addr) => {
             use std::os::unix::net::UnixDatagram as StdUnixDatagram;
             use std::os::unix::net::UnixListener as StdUnixListener;
@@ -940,22 +1074,14 @@ pub(crate) fn listen_with_prebound(
                 }
             };
 
-            let addrs: Vec<core::net::SocketAddr> = (hostname.as_ref(), port)
-                .to_socket_addrs()
-                .map_err(|err| ServerError::Resolve(make_channel_addr(&hostname, port), err))?
-                .collect();
-
-            if addrs.is_empty() {
-                return Err(ServerError::Resolve(
-                    make_channel_addr(&hostname, port),
-                    std::io::Error::other("no available socket addr"),
-                ));
-            }
+            let addrs = crate::channel::AddressPreference::configured()
+                .resolve(&hostname, port)
+                .map_err(|err| ServerError::Resolve(make_channel_addr(&hostname, port), err))?;
 
             let channel_addr = make_channel_addr(&hostname, port);
             let std_listener = match prebound {
                 Some(l) => l,
-                None => std::net::TcpListener::bind(&addrs[..])
+                None => bind_tcp_listener(&addrs)
                     .map_err(|err| ServerError::Listen(channel_addr.clone(), err))?,
             };
             std_listener
@@ -1502,8 +1628,19 @@ pub(crate) mod tcp {
                 .with_max_interval(Duration::from_millis(1000))
                 .with_max_elapsed_time(Some(reconnect_timeout))
                 .build();
+            let proxy = proxy::for_destination(&self.addr.ip().to_string()).map_err(|err| {
+                ClientError::Connect(self.dest(), err, "invalid proxy configuration".to_string())
+            })?;
             loop {
-                match TcpStream::connect(&self.addr).await {
+                let connected = match &proxy {
+                    Some(proxy) => {
+                        proxy
+                            .connect(&self.addr.ip().to_string(), self.addr.port())
+                            .await
+                    }
+                    None => TcpStream::connect(&self.addr).await,
+                };
+                match connected {
                     Ok(mut stream) => {
                         stream.set_nodelay(true).map_err(|err| {
                             ClientError::Connect(
@@ -1695,6 +1832,7 @@ pub(crate) mod meta {
             addr_type: tls::TlsAddrType::MetaTls,
             session_id,
             stream_id,
+            preference: crate::channel::AddressPreference::configured(),
         })
     }
 }
@@ -1883,6 +2021,7 @@ pub(crate) mod tls {
         pub(crate) addr_type: TlsAddrType,
         pub(crate) session_id: SessionId,
         pub(crate) stream_id: u8,
+        pub(crate) preference: crate::channel::AddressPreference,
     }
 
     impl std::fmt::Debug for TlsLink {
@@ -1929,12 +2068,23 @@ pub(crate) mod tls {
                 .with_max_interval(Duration::from_millis(1000))
                 .with_max_elapsed_time(Some(reconnect_timeout))
                 .build();
+            let proxy = proxy::for_destination(&self.hostname).map_err(|err| {
+                ClientError::Connect(self.dest(), err, "invalid proxy configuration".to_string())
+            })?;
             loop {
-                let mut addrs = (self.hostname.as_ref(), self.port)
-                    .to_socket_addrs()
-                    .map_err(|_| ClientError::Resolve(self.dest()))?;
-                let addr = addrs.next().ok_or(ClientError::Resolve(self.dest()))?;
-                match TcpStream::connect(&addr).await {
+                let connected = match &proxy {
+                    // The proxy resolves the hostname.
+                    Some(proxy) => proxy.connect(&self.hostname, self.port).await,
+                    None => {
+                        // Try each resolved address in preference order.
+                        let addrs = self
+                            .preference
+                            .resolve(&self.hostname, self.port)
+                            .map_err(|_| ClientError::Resolve(self.dest()))?;
+                        TcpStream::connect(&addrs[..]).await
+                    }
+                };
+                match connected {
                     Ok(stream) => {
                         stream.set_nodelay(true).map_err(|err| {
                             ClientError::Connect(
@@ -2004,6 +2154,7 @@ pub(crate) mod tls {
             addr_type: TlsAddrType::Tls,
             session_id,
             stream_id,
+            preference: crate::channel::AddressPreference::configured(),
         })
     }
 
@@ -2522,6 +2673,49 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(target_os = "linux")] // uses abstract names
+    #[tokio::test]
+    async fn test_unix_coalesced_writes() -> Result<()> {
+        let config = hyperactor_config::global::lock();
+        let _guard_frames = config.override_key(config::CHANNEL_COALESCE_MAX_FRAMES, 8);
+        let _guard_linger =
+            config.override_key(config::CHANNEL_COALESCE_LINGER, Duration::from_millis(20));
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let unique_address = format!("test_unix_coalesced_writes_{}", timestamp);
+        let (addr, mut rx) = server::serve::<u64>(
+            ChannelAddr::Unix(unix::SocketAddr::from_abstract_name(&unique_address)?),
+            None,
+        )
+        .unwrap();
+
+        let tx = channel::dial::<u64>(addr).unwrap();
+        // A lone message is written once the linger window elapses.
+        tx.post(0);
+        assert_eq!(rx.recv().await.unwrap(), 0);
+        // A burst is split into writes of at most 8 frames, in order.
+        for i in 1..=100 {
+            tx.post(i);
+        }
+        for i in 1..=100 {
+            assert_eq!(rx.recv().await.unwrap(), i);
+        }
+
+        // Queued messages are still written after the sender is dropped.
+        for i in 101..=103 {
+            tx.post(i);
+        }
+        drop(tx);
+        for i in 101..=103 {
+            assert_eq!(rx.recv().await.unwrap(), i);
+        }
+
+        Ok(())
+    }
+
     #[cfg(target_os = "linux")] // uses abstract names
     #[tracing_test::traced_test]
     #[tokio::test]
@@ -4863,4 +5057,34 @@ mod tests {
             "in-progress recv should resolve with ChannelError::Closed after join, got {result:?}"
         );
     }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_tcp_keepalive_config() {
+        let config = hyperactor_config::global::lock();
+        let _idle = config.override_key(config::CHANNEL_TCP_KEEPALIVE_IDLE, Duration::from_secs(7));
+        let _interval = config.override_key(
+            config::CHANNEL_TCP_KEEPALIVE_INTERVAL,
+            Duration::from_secs(2),
+        );
+        let _retries = config.override_key(config::CHANNEL_TCP_KEEPALIVE_RETRIES, 4);
+        let _user_timeout =
+            config.override_key(config::CHANNEL_TCP_USER_TIMEOUT, Duration::from_secs(9));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stream = tokio::net::TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        set_tcp_keepalive(&stream);
+
+        let sock = socket2::SockRef::from(&stream);
+        assert!(sock.keepalive().unwrap());
+        assert_eq!(sock.keepalive_time().unwrap(), Duration::from_secs(7));
+        assert_eq!(sock.keepalive_interval().unwrap(), Duration::from_secs(2));
+        assert_eq!(sock.keepalive_retries().unwrap(), 4);
+        assert_eq!(
+            sock.tcp_user_timeout().unwrap(),
+            Some(Duration::from_secs(9))
+        );
+    }
 }
@@ -0,0 +1,225 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! TCP links to DNS names. The name is resolved each time the link
+//! connects, so a link follows its service when the service moves
+//! between hosts.
+
+use std::io;
+use std::sync::LazyLock;
+
+use hickory_resolver::TokioResolver;
+use tokio::net::TcpStream;
+
+use super::*;
+use crate::channel::AddressPreference;
+use crate::channel::DnsAddr;
+
+#[derive(Debug)]
+pub(crate) struct DnsLink {
+    addr: DnsAddr,
+    session_id: SessionId,
+    stream_id: u8,
+    pub(super) preference: AddressPreference,
+}
+
+#[async_trait]
+impl Link for DnsLink {
+    type Stream = TcpStream;
+
+    fn dest(&self) -> ChannelAddr {
+        ChannelAddr::Dns(self.addr.clone())
+    }
+
+    fn link_id(&self) -> SessionId {
+        self.session_id
+    }
+
+    async fn next(&mut self) -> Result<Self::Stream, ClientError> {
+        let reconnect_timeout = hyperactor_config::global::get(config::CHANNEL_RECONNECT_TIMEOUT);
+        let mut backoff = ExponentialBackoffBuilder::new()
+            .with_initial_interval(Duration::from_millis(1))
+            .with_multiplier(2.0)
+            .with_randomization_factor(0.1)
+            .with_max_interval(Duration::from_millis(1000))
+            .with_max_elapsed_time(Some(reconnect_timeout))
+            .build();
+        // SRV records are resolved locally, as proxies cannot resolve
+        // them.
+        let proxy = match self.addr.port {
+            Some(_) => proxy::for_destination(&self.addr.name).map_err(|err| {
+                ClientError::Connect(self.dest(), err, "invalid proxy configuration".to_string())
+            })?,
+            None => None,
+        };
+        loop {
+            // Resolve on every attempt, so that a failed connect picks up
+            // records that changed since the last one.
+            let connected = match (&proxy, self.addr.port) {
+                (Some(proxy), Some(port)) => proxy.connect(&self.addr.name, port).await,
+                _ => match resolve(&self.addr, self.preference).await {
+                    Ok(addrs) => TcpStream::connect(&addrs[..]).await,
+                    Err(err) => Err(err),
+                },
+            };
+            match connected {
+                Ok(mut stream) => {
+                    stream.set_nodelay(true).map_err(|err| {
+                        ClientError::Connect(
+                            self.dest(),
+                            err,
+                            "cannot disable Nagle algorithm".to_string(),
+                        )
+                    })?;
+                    set_tcp_keepalive(&stream);
+                    write_link_init(&mut stream, self.session_id, self.stream_id)
+                        .await
+                        .map_err(|err| ClientError::Io(self.dest(), err))?;
+                    return Ok(stream);
+                }
+                Err(err) => {
+                    tracing::debug!(
+                        addr = %self.addr,
+                        error = %err,
+                        "dns connect failed, backing off"
+                    );
+                    match backoff.next_backoff() {
+                        Some(delay) => tokio::time::sleep(delay).await,
+                        None => {
+                            return Err(ClientError::ConnectTimeout(
+                                self.dest(),
+                                reconnect_timeout,
+                                err,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Create a TCP link to the given DNS address.
+pub(crate) fn link(addr: DnsAddr, session_id: SessionId, stream_id: u8) -> DnsLink {
+    DnsLink {
+        addr,
+        session_id,
+        stream_id,
+        preference: AddressPreference::configured(),
+    }
+}
+
+/// Resolve `addr` to the socket addresses to connect to, in the order
+/// in which they should be tried.
+pub(crate) async fn resolve(
+    addr: &DnsAddr,
+    preference: AddressPreference,
+) -> io::Result<Vec<SocketAddr>> {
+    let addrs = match addr.port {
+        Some(port) => {
+            let ips = tokio::net::lookup_host((addr.name.as_str(), port))
+                .await?
+                .map(|addr| addr.ip());
+            preference
+                .order(ips)
+                .into_iter()
+                .map(|ip| SocketAddr::new(ip, port))
+                .collect()
+        }
+        None => resolve_srv(&addr.name, preference).await?,
+    };
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} has no addresses allowed by {}", addr, preference),
+        ));
+    }
+    Ok(addrs)
+}
+
+/// The resolver for SRV records, built once from the system
+/// configuration and shared by all links.
+static SRV_RESOLVER: LazyLock<Result<TokioResolver, String>> = LazyLock::new(|| {
+    TokioResolver::builder_tokio()
+        .map(|builder| builder.build())
+        .map_err(|err| err.to_string())
+});
+
+/// Resolve the SRV record `name` to its targets' socket addresses:
+/// targets with lower priority values first, and among targets of
+/// equal priority, those with greater weights first.
+async fn resolve_srv(name: &str, preference: AddressPreference) -> io::Result<Vec<SocketAddr>> {
+    let resolver = SRV_RESOLVER
+        .as_ref()
+        .map_err(|err| io::Error::other(err.clone()))?;
+    let lookup = resolver.srv_lookup(name).await.map_err(io::Error::other)?;
+    let mut records: Vec<_> = lookup.iter().cloned().collect();
+    records.sort_by_key(|srv| (srv.priority(), std::cmp::Reverse(srv.weight())));
+
+    let mut addrs = Vec::new();
+    for srv in records {
+        match resolver.lookup_ip(srv.target().clone()).await {
+            Ok(ips) => addrs.extend(
+                preference
+                    .order(ips.iter())
+                    .into_iter()
+                    .map(|ip| SocketAddr::new(ip, srv.port())),
+            ),
+            Err(err) => {
+                tracing::debug!(
+                    %name,
+                    target = %srv.target(),
+                    error = %err,
+                    "failed to resolve SRV target"
+                );
+            }
+        }
+    }
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resolve_localhost() {
+        let addrs = resolve(&DnsAddr::new("localhost", 8080), AddressPreference::V4Only)
+            .await
+            .unwrap();
+        assert!(!addrs.is_empty());
+        assert!(
+            addrs
+                .iter()
+                .all(|addr| addr.is_ipv4() && addr.ip().is_loopback() && addr.port() == 8080),
+            "{:?}",
+            addrs
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dial_dns_addr() {
+        let (listen_addr, mut rx) =
+            server::serve::<u64>(ChannelAddr::Tcp("127.0.0.1:0".parse().unwrap()), None).unwrap();
+        let ChannelAddr::Tcp(socket_addr) = listen_addr else {
+            panic!("unexpected listen address {}", listen_addr);
+        };
+        let addr: ChannelAddr = format!("tcp!localhost:{}", socket_addr.port())
+            .parse()
+            .unwrap();
+        assert_eq!(
+            addr,
+            ChannelAddr::Dns(DnsAddr::new("localhost", socket_addr.port()))
+        );
+
+        let tx =
+            crate::channel::dial_with_preference::<u64>(addr, AddressPreference::V4Only).unwrap();
+        tx.post(123);
+        assert_eq!(rx.recv().await.unwrap(), 123);
+    }
+}
@@ -519,6 +519,14 @@ async fn dispatch_duplex_stream<In: RemoteMessage, Out: RemoteMessage>(
                 );
                 false
             }
+            Err(Either::Send(session::SendLoopError::PeerUnresponsive(err))) => {
+                tracing::warn!(
+                    session_id = session_id.0,
+                    error = %err,
+                    "duplex peer stopped responding (recoverable)",
+                );
+                false
+            }
             Err(Either::Recv(session::RecvLoopError::Io(err))) => {
                 tracing::info!(
                     session_id = session_id.0,
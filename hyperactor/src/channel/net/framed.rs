@@ -8,6 +8,7 @@
 
 //! This module implements a cancellation-safe zero-copy framer for network channels.
 
+use std::collections::VecDeque;
 use std::fmt;
 use std::io;
 use std::io::IoSlice;
@@ -23,6 +24,9 @@ use tokio::io::AsyncWrite;
 use tokio::io::AsyncWriteExt;
 use tokio::io::ReadBuf;
 
+use crate::channel::pool;
+use crate::channel::pool::PooledBuffer;
+
 /// A FrameReader reads frames from an underlying [`AsyncRead`].
 pub struct FrameReader<R> {
     reader: R,
@@ -34,7 +38,11 @@ enum FrameReaderState {
     /// Accumulating 8-byte header: `[tag: 1B][len: 7B BE]`.
     ReadLen { buf: [u8; 8], off: usize },
     /// Accumulating body of exactly `len` bytes.
-    ReadBody { tag: u8, buf: Vec<u8>, len: usize }, // buf.len() <= len
+    ReadBody {
+        tag: u8,
+        buf: PooledBuffer,
+        len: usize,
+    }, // buf.len() <= len
 }
 
 impl<R: AsyncRead + Unpin> FrameReader<R> {
@@ -106,7 +114,7 @@ impl<R: AsyncRead + Unpin> FrameReader<R> {
                     }
                     self.state = FrameReaderState::ReadBody {
                         tag,
-                        buf: Vec::with_capacity(len),
+                        buf: pool::global().take(len),
                         len,
                     };
                 }
@@ -132,16 +140,17 @@ impl<R: AsyncRead + Unpin> FrameReader<R> {
                     if num_read == 0 {
                         return Err(io::ErrorKind::UnexpectedEof.into());
                     }
+                    let filled = buf.len() + num_read;
                     // SAFETY: Adding the number of bytes that were just read should be the correct length of this buffer
                     unsafe {
-                        buf.set_len(buf.len() + num_read);
+                        buf.set_len(filled);
                     }
                 }
 
                 FrameReaderState::ReadBody { tag, buf, len } => {
                     assert_eq!(buf.len(), *len);
                     let tag = *tag;
-                    let frame = take(buf).into();
+                    let frame = take(buf).freeze();
                     self.state = FrameReaderState::ReadLen {
                         buf: [0; 8],
                         off: 0,
@@ -153,6 +162,119 @@ impl<R: AsyncRead + Unpin> FrameReader<R> {
     }
 }
 
+/// The maximum number of chunks passed to a single vectored write.
+const MAX_WRITE_CHUNKS: usize = 32;
+
+/// Encode the 8-byte header `[tag: 1B][len: 7B BE]` of a frame whose
+/// body is `len` bytes long.
+fn frame_header(len: usize, max_len: usize, tag: u8) -> io::Result<Bytes> {
+    if len > max_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame length {} exceeds max {}", len, max_len),
+        ));
+    }
+    if len > (1 << 56) - 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame length {} exceeds 7-byte maximum", len),
+        ));
+    }
+    let mut len_buf = [0u8; 8];
+    len_buf[0] = tag;
+    // Encode length as 7-byte big-endian in bytes [1..8].
+    let len_be = (len as u64).to_be_bytes();
+    len_buf[1..8].copy_from_slice(&len_be[1..8]);
+    Ok(Bytes::copy_from_slice(&len_buf))
+}
+
+/// A sequence of complete frames (each with its own header) that are
+/// written back to back by a single [`FrameWrite`] (see
+/// [`FrameWrite::prefixed`]), so that several small frames share
+/// vectored writes and a flush. The reader observes ordinary frames.
+pub struct FrameBatch<B> {
+    /// Each frame's remaining header and body bytes.
+    frames: VecDeque<(Bytes, B)>,
+    remaining: usize,
+    max_len: usize,
+    tag: u8,
+}
+
+impl<B: Buf> FrameBatch<B> {
+    /// Create an empty batch of frames with the provided tag, whose
+    /// bodies may be at most `max_len` bytes long.
+    pub fn new(max_len: usize, tag: u8) -> Self {
+        Self {
+            frames: VecDeque::new(),
+            remaining: 0,
+            max_len,
+            tag,
+        }
+    }
+
+    /// Append a frame with the provided body to the batch. Fails if the
+    /// body exceeds the batch's maximum frame length.
+    pub fn push(&mut self, body: B) -> io::Result<()> {
+        let header = frame_header(body.remaining(), self.max_len, self.tag)?;
+        self.remaining += header.len() + body.remaining();
+        self.frames.push_back((header, body));
+        Ok(())
+    }
+}
+
+impl<B: Buf> Buf for FrameBatch<B> {
+    fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    fn chunk(&self) -> &[u8] {
+        match self.frames.front() {
+            Some((header, _)) if header.has_remaining() => header.chunk(),
+            Some((_, body)) => body.chunk(),
+            None => &[],
+        }
+    }
+
+    fn chunks_vectored<'a>(&'a self, dst: &mut [IoSlice<'a>]) -> usize {
+        let mut n = 0;
+        for (header, body) in &self.frames {
+            if n == dst.len() {
+                break;
+            }
+            if header.has_remaining() {
+                dst[n] = IoSlice::new(header.chunk());
+                n += 1;
+            }
+            let filled = body.chunks_vectored(&mut dst[n..]);
+            let covered: usize = dst[n..n + filled].iter().map(|slice| slice.len()).sum();
+            n += filled;
+            if covered < body.remaining() {
+                // The rest of this body did not fit; later frames must
+                // not be written ahead of it.
+                break;
+            }
+        }
+        n
+    }
+
+    fn advance(&mut self, mut cnt: usize) {
+        assert!(cnt <= self.remaining, "advance past end of frame batch");
+        self.remaining -= cnt;
+        while cnt > 0 {
+            let (header, body) = self.frames.front_mut().expect("cnt <= remaining");
+            let n = cnt.min(header.remaining());
+            header.advance(n);
+            cnt -= n;
+            let n = cnt.min(body.remaining());
+            body.advance(n);
+            cnt -= n;
+            if !header.has_remaining() && !body.has_remaining() {
+                self.frames.pop_front();
+            }
+        }
+    }
+}
+
 /// A Writer for message frames. `FrameWrite` requires the user to drive
 /// the underlying state machines through (possibly) successive calls to
 /// `send`, retaining cancellation safety. The `FrameWrite` owns the underlying
@@ -193,36 +315,25 @@ impl<W: AsyncWrite + Unpin, B: Buf> FrameWrite<W, B> {
     /// On error, returns the I/O error if the frame length exceeds
     /// `max_len`.
     pub fn new(writer: W, body: B, max_len: usize, tag: u8) -> Result<Self, (W, io::Error)> {
-        let len = body.remaining();
-        if len > max_len {
-            return Err((
-                writer,
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("frame length {} exceeds max {}", len, max_len),
-                ),
-            ));
-        }
-        if len > (1 << 56) - 1 {
-            return Err((
+        match frame_header(body.remaining(), max_len, tag) {
+            Ok(len_buf) => Ok(Self {
                 writer,
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    format!("frame length {} exceeds 7-byte maximum", len),
-                ),
-            ));
+                len_buf,
+                body,
+            }),
+            Err(e) => Err((writer, e)),
         }
-        let mut len_buf = [0u8; 8];
-        len_buf[0] = tag;
-        // Encode length as 7-byte big-endian in bytes [1..8].
-        let len_be = (len as u64).to_be_bytes();
-        len_buf[1..8].copy_from_slice(&len_be[1..8]);
-        let len_buf = Bytes::copy_from_slice(&len_buf);
-        Ok(Self {
+    }
+
+    /// Create a writer for `frames`, which must already consist of
+    /// complete frames, headers included (e.g., a [`FrameBatch`]).
+    /// No header is added.
+    pub fn prefixed(writer: W, frames: B) -> Self {
+        Self {
             writer,
-            len_buf,
-            body,
-        })
+            len_buf: Bytes::new(),
+            body: frames,
+        }
     }
 
     /// Drive the underlying state machine. The frame is written when this
@@ -246,14 +357,11 @@ impl<W: AsyncWrite + Unpin, B: Buf> FrameWrite<W, B> {
                 // > other branch completes first, then it is guaranteed that no data was
                 // > written to this `AsyncWrite`.
                 //
-                // We write at most 4 chunks at a time (to be tuned). We may also consider
-                // using MaybeUninit here to avoid initialization overhead.
-                let mut chunks = [
-                    IoSlice::new(&[]),
-                    IoSlice::new(&[]),
-                    IoSlice::new(&[]),
-                    IoSlice::new(&[]),
-                ];
+                // We write at most `MAX_WRITE_CHUNKS` chunks at a time, enough for
+                // a batch of several small frames to go out in a single write. We
+                // may also consider using MaybeUninit here to avoid initialization
+                // overhead.
+                let mut chunks = [IoSlice::new(&[]); MAX_WRITE_CHUNKS];
                 let num_chunks = self.body.chunks_vectored(&mut chunks);
                 let count = self.writer.write_vectored(&chunks[0..num_chunks]).await?;
                 self.body.advance(count);
@@ -991,6 +1099,77 @@ mod tests {
         assert!(reader.next().await.unwrap().is_none());
     }
 
+    #[tokio::test]
+    async fn test_frame_batch_roundtrip() {
+        const MAX_LEN: usize = 1024;
+
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let (r, _wu) = tokio::io::split(a);
+        let (_ru, w) = tokio::io::split(b);
+        let mut reader = FrameReader::new(r, MAX_LEN);
+
+        let bodies: Vec<Bytes> = (0..100)
+            .map(|i| {
+                if i % 10 == 0 {
+                    Bytes::new()
+                } else {
+                    random_buffer(MAX_LEN)
+                }
+            })
+            .collect();
+        let mut batch = FrameBatch::new(MAX_LEN, 3);
+        for body in &bodies {
+            batch.push(body.clone()).unwrap();
+        }
+        assert!(batch.push(Bytes::from(vec![0u8; MAX_LEN + 1])).is_err());
+
+        let mut fw = FrameWrite::prefixed(w, batch);
+        fw.send().await.unwrap();
+        assert!(!fw.body.has_remaining());
+        let _w = fw.complete();
+
+        for body in bodies {
+            assert_eq!(reader.next().await.unwrap(), Some((3, body)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frame_batch_cancellation_resume() {
+        const MAX_LEN: usize = 1024;
+
+        let (a, b) = tokio::io::duplex(4096);
+        let (r, _wu) = tokio::io::split(a);
+        let (_ru, w) = tokio::io::split(b);
+        let mut reader = FrameReader::new(r, MAX_LEN);
+
+        let mut batch = FrameBatch::new(MAX_LEN, 0);
+        batch.push(Bytes::from_static(b"hello")).unwrap();
+        batch.push(Bytes::from_static(b"world")).unwrap();
+        let mut fw = FrameWrite::prefixed(Throttled::new(w), batch);
+
+        // Allow the first frame and part of the second frame's header
+        // to be written, then cancel.
+        fw.writer.set_budget(8 + 5 + 3);
+        tokio::select! {
+            _ = fw.send() => panic!("send unexpectedly completed"),
+            _ = tokio::time::sleep(std::time::Duration::from_millis(5)) => {}
+        }
+        // The rest of the second frame's header, and its body.
+        assert_eq!(fw.body.remaining(), 5 + 5);
+        assert_eq!(
+            reader.next().await.unwrap(),
+            Some((0, Bytes::from_static(b"hello")))
+        );
+
+        fw.writer.set_budget(usize::MAX);
+        fw.send().await.unwrap();
+        let _w = fw.complete();
+        assert_eq!(
+            reader.next().await.unwrap(),
+            Some((0, Bytes::from_static(b"world")))
+        );
+    }
+
     #[tokio::test]
     async fn test_reader_accepts_exact_max_len_frames() {
         const MAX: usize = 1024;
@@ -90,6 +90,7 @@ pub(crate) struct QuicLink {
     addr_type: QuicAddrType,
     session_id: SessionId,
     stream_id: u8,
+    pub(super) preference: crate::channel::AddressPreference,
 }
 
 impl std::fmt::Debug for QuicLink {
@@ -126,45 +127,48 @@ impl Link for QuicLink {
             .build();
 
         loop {
-            let mut addrs = (self.hostname.as_ref(), self.port)
-                .to_socket_addrs()
+            // Try each resolved address in preference order.
+            let addrs = self
+                .preference
+                .resolve(&self.hostname, self.port)
                 .map_err(|_| ClientError::Resolve(self.dest()))?;
-            let addr = addrs.next().ok_or(ClientError::Resolve(self.dest()))?;
-            let bind_addr = if addr.is_ipv6() {
-                (Ipv6Addr::UNSPECIFIED, 0).into()
-            } else {
-                (std::net::Ipv4Addr::UNSPECIFIED, 0).into()
-            };
-            let endpoint = Endpoint::client(bind_addr).map_err(|err| {
-                ClientError::Connect(
-                    self.dest(),
-                    err,
-                    "failed to bind QUIC client endpoint".to_string(),
-                )
-            })?;
-            match endpoint.connect_with(self.client_config.clone(), addr, &self.hostname) {
-                Ok(connecting) => match connecting.await {
-                    Ok(connection) => match connection.open_bi().await {
-                        Ok((mut send, recv)) => {
-                            write_link_init(&mut send, self.session_id, self.stream_id)
-                                .await
-                                .map_err(|err| ClientError::Io(self.dest(), err))?;
-                            return Ok(QuicStream::new(send, recv));
-                        }
+            for addr in addrs {
+                let bind_addr = if addr.is_ipv6() {
+                    (Ipv6Addr::UNSPECIFIED, 0).into()
+                } else {
+                    (std::net::Ipv4Addr::UNSPECIFIED, 0).into()
+                };
+                let endpoint = Endpoint::client(bind_addr).map_err(|err| {
+                    ClientError::Connect(
+                        self.dest(),
+                        err,
+                        "failed to bind QUIC client endpoint".to_string(),
+                    )
+                })?;
+                match endpoint.connect_with(self.client_config.clone(), addr, &self.hostname) {
+                    Ok(connecting) => match connecting.await {
+                        Ok(connection) => match connection.open_bi().await {
+                            Ok((mut send, recv)) => {
+                                write_link_init(&mut send, self.session_id, self.stream_id)
+                                    .await
+                                    .map_err(|err| ClientError::Io(self.dest(), err))?;
+                                return Ok(QuicStream::new(send, recv));
+                            }
+                            Err(err) => {
+                                tracing::debug!(%addr, error = %err, "quic open_bi failed");
+                            }
+                        },
                         Err(err) => {
-                            tracing::debug!(error = %err, "quic open_bi failed, backing off");
+                            tracing::debug!(%addr, error = %err, "quic connect failed");
                         }
                     },
                     Err(err) => {
-                        tracing::debug!(error = %err, "quic connect failed, backing off");
+                        return Err(ClientError::Connect(
+                            self.dest(),
+                            io::Error::other(err.to_string()),
+                            "failed to start QUIC connection".to_string(),
+                        ));
                     }
-                },
-                Err(err) => {
-                    return Err(ClientError::Connect(
-                        self.dest(),
-                        io::Error::other(err.to_string()),
-                        "failed to start QUIC connection".to_string(),
-                    ));
                 }
             }
 
@@ -217,13 +221,30 @@ impl super::Listener for QuicSocketListener {
     }
 }
 
+/// Keepalive and idle timeout for QUIC connections: a connection that
+/// hears nothing from its peer for [`config::CHANNEL_QUIC_IDLE_TIMEOUT`]
+/// fails with a timeout, while keepalives every
+/// [`config::CHANNEL_QUIC_KEEPALIVE_INTERVAL`] keep healthy idle
+/// connections open.
+fn transport_config() -> anyhow::Result<Arc<quinn::TransportConfig>> {
+    let keepalive = hyperactor_config::global::get(config::CHANNEL_QUIC_KEEPALIVE_INTERVAL);
+    let idle_timeout = hyperactor_config::global::get(config::CHANNEL_QUIC_IDLE_TIMEOUT);
+    let mut transport = quinn::TransportConfig::default();
+    transport
+        .keep_alive_interval((!keepalive.is_zero()).then_some(keepalive))
+        .max_idle_timeout(Some(quinn::IdleTimeout::try_from(idle_timeout)?));
+    Ok(Arc::new(transport))
+}
+
 fn client_config(addr_type: QuicAddrType) -> anyhow::Result<quinn::ClientConfig> {
     let rustls_config = match addr_type {
         QuicAddrType::Quic => tls::client_config_from_bundle(&tls::get_pem_bundle())?,
         QuicAddrType::MetaQuic => meta::client_config()?,
     };
     let crypto = quinn::crypto::rustls::QuicClientConfig::try_from(Arc::new(rustls_config))?;
-    Ok(quinn::ClientConfig::new(Arc::new(crypto)))
+    let mut config = quinn::ClientConfig::new(Arc::new(crypto));
+    config.transport_config(transport_config()?);
+    Ok(config)
 }
 
 fn server_config(addr_type: QuicAddrType) -> anyhow::Result<quinn::ServerConfig> {
@@ -232,7 +253,9 @@ fn server_config(addr_type: QuicAddrType) -> anyhow::Result<quinn::ServerConfig>
         QuicAddrType::MetaQuic => meta::server_config(true)?,
     };
     let crypto = quinn::crypto::rustls::QuicServerConfig::try_from(Arc::new(rustls_config))?;
-    Ok(quinn::ServerConfig::with_crypto(Arc::new(crypto)))
+    let mut config = quinn::ServerConfig::with_crypto(Arc::new(crypto));
+    config.transport_config(transport_config()?);
+    Ok(config)
 }
 
 pub(crate) fn link(
@@ -256,6 +279,7 @@ pub(crate) fn link(
         addr_type,
         session_id,
         stream_id,
+        preference: crate::channel::AddressPreference::configured(),
     })
 }
 
@@ -270,19 +294,21 @@ pub(crate) fn listen(
         )
     })?;
     let TlsAddr { hostname, port } = addr;
-    let addrs: Vec<core::net::SocketAddr> = (hostname.as_ref(), port)
-        .to_socket_addrs()
-        .map_err(|err| ServerError::Resolve(addr_type.addr(TlsAddr::new(&hostname, port)), err))?
-        .collect();
+    let addrs = crate::channel::AddressPreference::configured()
+        .resolve(&hostname, port)
+        .map_err(|err| ServerError::Resolve(addr_type.addr(TlsAddr::new(&hostname, port)), err))?;
 
-    if addrs.is_empty() {
-        return Err(ServerError::Resolve(
-            addr_type.addr(TlsAddr::new(&hostname, port)),
-            io::Error::other("no available socket addr"),
-        ));
+    // Like a TCP server (see `bind_tcp_listener`), a QUIC server binds
+    // a single address: the first of the name's addresses, in
+    // preference order, that can be bound.
+    let mut bound = Err(io::Error::other("no address to bind"));
+    for addr in addrs {
+        bound = Endpoint::server(server_config.clone(), addr);
+        if bound.is_ok() {
+            break;
+        }
     }
-
-    let endpoint = Endpoint::server(server_config, addrs[0])
+    let endpoint = bound
         .map_err(|err| ServerError::Listen(addr_type.addr(TlsAddr::new(&hostname, port)), err))?;
     let local_addr = endpoint
         .local_addr()
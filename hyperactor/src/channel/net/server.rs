@@ -647,7 +647,9 @@ pub(in crate::channel) fn serve<M: RemoteMessage>(
         ),
     );
 
-    let (tx, rx) = mpsc::channel::<M>(1024);
+    let (tx, rx) = mpsc::channel::<M>(
+        hyperactor_config::global::get(config::CHANNEL_NET_RX_BUFFER_SIZE).max(1),
+    );
     let cancel_token = CancellationToken::new();
     let child_token = cancel_token.child_token();
 
@@ -741,7 +743,9 @@ where
     L: super::Listener + 'static,
     L::Stream: Unpin + std::fmt::Debug + 'static,
 {
-    let (tx, rx) = mpsc::channel::<M>(1024);
+    let (tx, rx) = mpsc::channel::<M>(
+        hyperactor_config::global::get(config::CHANNEL_NET_RX_BUFFER_SIZE).max(1),
+    );
     let cancel_token = CancellationToken::new();
     let child_token = cancel_token.child_token();
 
@@ -43,6 +43,7 @@ use super::Link;
 use super::NetRxResponse;
 use super::Stream;
 use super::deserialize_response;
+use super::framed::FrameBatch;
 use super::framed::FrameReader;
 use super::framed::FrameWrite;
 use super::serialize_response;
@@ -51,6 +52,7 @@ use crate::channel::ChannelAddr;
 use crate::channel::ChannelError;
 use crate::channel::SendError;
 use crate::channel::SendErrorReason;
+use crate::channel::pool;
 use crate::config;
 use crate::metrics;
 
@@ -177,17 +179,19 @@ impl<W: AsyncWrite + Unpin> AsyncWrite for OwnedWriter<W> {
 /// Cancel safety: `drive()` is cancel-safe at every await point.
 /// Dropping a `Completion` before it completes releases the lock but
 /// corrupts the stream (the connection should be torn down).
+///
+/// `tag` is `None` when `body` is already framed (a [`FrameBatch`]).
 pub(super) enum Completion<W: AsyncWrite + Unpin + Send, B: Buf> {
     Pending {
         writer: Arc<tokio::sync::Mutex<W>>,
         body: B,
-        tag: u8,
+        tag: Option<u8>,
         max_len: usize,
     },
     Acquiring {
         writer: Arc<tokio::sync::Mutex<W>>,
         body: B,
-        tag: u8,
+        tag: Option<u8>,
         max_len: usize,
     },
     Writing(FrameWrite<OwnedWriter<W>, B>),
@@ -226,6 +230,10 @@ impl<W: AsyncWrite + Unpin + Send, B: Buf> Completion<W, B> {
                     else {
                         unreachable!()
                     };
+                    let Some(tag) = tag else {
+                        *self = Self::Writing(FrameWrite::prefixed(OwnedWriter(guard), body));
+                        continue;
+                    };
                     match FrameWrite::new(OwnedWriter(guard), body, max_len, tag) {
                         Ok(fw) => *self = Self::Writing(fw),
                         Err((_owned, e)) => {
@@ -274,7 +282,24 @@ impl<R: AsyncRead + Unpin + Send, W: AsyncWrite + Unpin + Send> TaggedStream<R,
         Completion::Pending {
             writer: Arc::clone(&self.writer),
             body,
-            tag: self.tag,
+            tag: Some(self.tag),
+            max_len: self.max_frame_len,
+        }
+    }
+
+    /// Create an empty [`FrameBatch`] of frames for this stream.
+    pub fn batch<B: Buf>(&self) -> FrameBatch<B> {
+        FrameBatch::new(self.max_frame_len, self.tag)
+    }
+
+    /// Begin writing a batch of frames, created by [`batch`](Self::batch),
+    /// in as few writes as possible. Like [`write`](Self::write), the
+    /// returned [`Completion`] must be driven to completion.
+    pub fn write_batch<B: Buf>(&self, batch: FrameBatch<B>) -> Completion<W, FrameBatch<B>> {
+        Completion::Pending {
+            writer: Arc::clone(&self.writer),
+            body: batch,
+            tag: None,
             max_len: self.max_frame_len,
         }
     }
@@ -489,8 +514,32 @@ impl<M: RemoteMessage> Outbox<M> {
         self.deque.is_empty()
     }
 
-    pub(super) fn front_message(&self) -> Option<serde_multipart::Message> {
-        self.deque.front().map(|msg| msg.message.clone())
+    pub(super) fn len(&self) -> usize {
+        self.deque.len()
+    }
+
+    /// The number of messages at the front of the outbox that can be
+    /// coalesced into a single write of at most `max_frames` frames and
+    /// `max_bytes` bytes, and whether that write is full (no further
+    /// message could be added to it). The front message is always
+    /// included; later messages longer than `max_len` never are.
+    pub(super) fn coalescable(
+        &self,
+        max_frames: usize,
+        max_bytes: usize,
+        max_len: usize,
+    ) -> (usize, bool) {
+        let mut count = 0;
+        let mut bytes = 0;
+        for msg in self.deque.iter() {
+            let len = msg.message.frame_len();
+            if count > 0 && (count >= max_frames || len > max_len || bytes + len > max_bytes) {
+                return (count, true);
+            }
+            count += 1;
+            bytes += len;
+        }
+        (count, count >= max_frames || bytes >= max_bytes)
     }
 
     pub(super) fn front_size(&self) -> Option<usize> {
@@ -516,7 +565,7 @@ impl<M: RemoteMessage> Outbox<M> {
         );
 
         let frame = Frame::Message(self.next_seq, message);
-        let message = serde_multipart::serialize_bincode(&frame)
+        let message = serde_multipart::serialize_bincode_pooled(&frame, pool::global())
             .map_err(|e| format!("serialization error: {e}"))?;
         let message_size = message.frame_len();
         metrics::REMOTE_MESSAGE_SEND_SIZE.record(message_size as f64, &[]);
@@ -936,7 +985,8 @@ pub(super) async fn multi_stream_recv_connected<
 /// Error from the send protocol loop. An `Ok(())` from
 /// [`send_connected`] indicates normal connection close (EOF).
 pub(super) enum SendLoopError {
-    /// I/O error on the underlying connection.
+    /// I/O error on the underlying connection. Constructed by
+    /// [`SendLoopError::io`], which tells unresponsive peers apart.
     Io(anyhow::Error),
     /// Application closed the send channel.
     AppClosed,
@@ -948,6 +998,45 @@ pub(super) enum SendLoopError {
     DeliveryTimeout,
     /// Frame `size` exceeded `max` (= `CODEC_MAX_FRAME_LENGTH`).
     OversizedFrame { size: usize, max: usize },
+    /// The transport's keepalive gave up on the peer: the connection
+    /// was half-open. Like [`SendLoopError::Io`], this is recoverable
+    /// by reconnecting, as the peer may only have stalled.
+    PeerUnresponsive(String),
+}
+
+impl SendLoopError {
+    /// Classify an I/O error on the connection. Timeouts reported by
+    /// the OS are raised only when keepalive probes or unacknowledged
+    /// writes go unanswered (see `set_tcp_keepalive`), as is the QUIC
+    /// idle timeout, so they identify an unresponsive peer.
+    pub(super) fn io(err: io::Error) -> Self {
+        if is_peer_timeout(&err) {
+            Self::PeerUnresponsive(err.to_string())
+        } else {
+            Self::Io(err.into())
+        }
+    }
+}
+
+/// Whether `err` reports that the peer stopped responding. Timeouts
+/// synthesized above the transport (e.g., by TLS or a proxy) do not
+/// qualify: they carry no OS error.
+fn is_peer_timeout(err: &io::Error) -> bool {
+    if err.kind() == io::ErrorKind::TimedOut {
+        return err.raw_os_error().is_some();
+    }
+    let lost = match err.get_ref() {
+        Some(inner) => match (
+            inner.downcast_ref::<quinn::ReadError>(),
+            inner.downcast_ref::<quinn::WriteError>(),
+        ) {
+            (Some(quinn::ReadError::ConnectionLost(lost)), _)
+            | (_, Some(quinn::WriteError::ConnectionLost(lost))) => Some(lost),
+            _ => None,
+        },
+        None => None,
+    };
+    matches!(lost, Some(quinn::ConnectionError::TimedOut))
 }
 
 impl fmt::Display for SendLoopError {
@@ -958,6 +1047,7 @@ impl fmt::Display for SendLoopError {
             Self::Rejected(r) => write!(f, "rejected: {r}"),
             Self::ServerClosed => write!(f, "server closed"),
             Self::DeliveryTimeout => write!(f, "delivery timeout"),
+            Self::PeerUnresponsive(e) => write!(f, "peer unresponsive: {e}"),
             Self::OversizedFrame { size, max } => write!(
                 f,
                 "oversized frame: rejecting oversize frame: len={size} > max={max}. \
@@ -1314,6 +1404,13 @@ mod ack_watermark_tests {
 /// manage the outbox/unacked buffers. Runs on a single physical
 /// connection. Cancel-safe: the caller may wrap the returned future
 /// in a `select!` branch.
+///
+/// Small messages are coalesced: messages queued behind the one being
+/// written are written together with it, as a [`FrameBatch`], up to
+/// [`config::CHANNEL_COALESCE_MAX_FRAMES`] frames and
+/// [`config::CHANNEL_COALESCE_MAX_BYTES`] bytes. With a nonzero
+/// [`config::CHANNEL_COALESCE_LINGER`], a write that is not full waits
+/// up to that long for more messages before it begins.
 pub(super) async fn send_connected<M, R, W>(
     stream: &TaggedStream<R, W>,
     deliveries: &mut Deliveries<M>,
@@ -1324,7 +1421,18 @@ where
     R: AsyncRead + Unpin + Send,
     W: AsyncWrite + Unpin + Send,
 {
-    let mut pending: Option<Completion<W, serde_multipart::Frame>> = None;
+    let max_frames = hyperactor_config::global::get(config::CHANNEL_COALESCE_MAX_FRAMES).max(1);
+    let max_bytes = hyperactor_config::global::get(config::CHANNEL_COALESCE_MAX_BYTES);
+    let linger = hyperactor_config::global::get(config::CHANNEL_COALESCE_LINGER);
+
+    // The write in progress, and the number of outbox messages it covers.
+    let mut pending: Option<(Completion<W, FrameBatch<serde_multipart::Frame>>, usize)> = None;
+    // While a write that is not full waits for more messages, the time
+    // at which it begins regardless.
+    let mut linger_until: Option<Instant> = None;
+    // Whether the application has dropped its sender; queued messages
+    // are still written.
+    let mut app_closed = false;
 
     loop {
         // Begin write if idle and outbox has messages.
@@ -1340,8 +1448,37 @@ where
                     .try_return(Some(reason));
                 return Err(SendLoopError::OversizedFrame { size: len, max });
             }
-            let message = deliveries.outbox.front_message().expect("not empty");
-            pending = Some(stream.write(message.framed()));
+
+            // Pick up messages that are already waiting, without blocking.
+            while !app_closed && deliveries.outbox.len() < max_frames {
+                match receiver.try_recv() {
+                    Ok(item) => {
+                        if let Err(e) = deliveries.outbox.push_back(item) {
+                            return Err(SendLoopError::io(io::Error::other(e)));
+                        }
+                    }
+                    Err(mpsc::error::TryRecvError::Empty) => break,
+                    Err(mpsc::error::TryRecvError::Disconnected) => app_closed = true,
+                }
+            }
+
+            let (count, full) = deliveries.outbox.coalescable(max_frames, max_bytes, max);
+            let deadline = *linger_until.get_or_insert_with(|| Instant::now() + linger);
+            if full || app_closed || Instant::now() >= deadline {
+                linger_until = None;
+                let mut batch = stream.batch();
+                for message in deliveries.outbox.deque.iter().take(count) {
+                    batch
+                        .push(message.message.clone().framed())
+                        .map_err(SendLoopError::io)?;
+                }
+                metrics::CHANNEL_COALESCED_FRAMES.record(count as f64, &[]);
+                pending = Some((stream.write_batch(batch), count));
+            }
+        }
+
+        if app_closed && pending.is_none() && deliveries.outbox.is_empty() {
+            return Err(SendLoopError::AppClosed);
         }
 
         tokio::select! {
@@ -1353,7 +1490,7 @@ where
                     Ok(Some(buffer)) => {
                         let response = match deserialize_response(buffer) {
                             Ok(r) => r,
-                            Err(e) => return Err(SendLoopError::Io(e.into())),
+                            Err(e) => return Err(SendLoopError::io(io::Error::other(e))),
                         };
                         match response {
                             NetRxResponse::Ack(ack) => {
@@ -1373,7 +1510,7 @@ where
                         }
                     }
                     Ok(None) => return Ok(()),
-                    Err(e) => return Err(SendLoopError::Io(e.into())),
+                    Err(e) => return Err(SendLoopError::io(e)),
                 }
             }
 
@@ -1383,30 +1520,40 @@ where
             }
 
             // Drive frame write to completion.
-            send_result = async { pending.as_mut().unwrap().drive().await },
+            send_result = async { pending.as_mut().unwrap().0.drive().await },
                 if pending.is_some() => {
                 match send_result {
                     Ok(()) => {
-                        pending = None;
-                        let mut message = deliveries.outbox.pop_front()
-                            .expect("outbox should not be empty");
-                        message.sent_at = Some(tokio::time::Instant::now());
-                        deliveries.unacked.push_back(message);
+                        let (_, count) = pending.take().expect("pending write");
+                        let sent_at = tokio::time::Instant::now();
+                        for _ in 0..count {
+                            let mut message = deliveries.outbox.pop_front()
+                                .expect("outbox should not be empty");
+                            message.sent_at = Some(sent_at);
+                            deliveries.unacked.push_back(message);
+                        }
                     }
-                    Err(e) => return Err(SendLoopError::Io(e.into())),
+                    Err(e) => return Err(SendLoopError::io(e)),
                 }
             }
 
+            // Stop waiting for more messages to coalesce.
+            _ = tokio::time::sleep_until(linger_until.unwrap_or_else(Instant::now)),
+                if linger_until.is_some() => {}
+
             // Accept new messages from the application (only when
-            // outbox is empty so queued messages are sent first).
-            msg = receiver.recv(), if deliveries.outbox.is_empty() => {
+            // outbox is empty so queued messages are sent first, or
+            // while waiting for more messages to coalesce).
+            msg = receiver.recv(),
+                if !app_closed && (deliveries.outbox.is_empty() || linger_until.is_some()) => {
                 match msg {
                     Some(item) => {
                         if let Err(e) = deliveries.outbox.push_back(item) {
-                            return Err(SendLoopError::Io(anyhow::anyhow!(e)));
+                            return Err(SendLoopError::io(io::Error::other(e)));
                         }
                     }
-                    None => return Err(SendLoopError::AppClosed),
+                    None if deliveries.outbox.is_empty() => return Err(SendLoopError::AppClosed),
+                    None => app_closed = true,
                 }
             }
         }
@@ -1670,4 +1817,33 @@ mod tests {
         assert!(demux.next_tagged(tag_a).await.unwrap().is_none());
         assert!(demux.next_tagged(tag_b).await.unwrap().is_none());
     }
+
+    #[test]
+    fn test_send_loop_error_classifies_peer_timeouts() {
+        use std::io;
+
+        use super::SendLoopError;
+
+        let timed_out = io::Error::from_raw_os_error(nix::libc::ETIMEDOUT);
+        assert!(matches!(
+            SendLoopError::io(timed_out),
+            SendLoopError::PeerUnresponsive(_)
+        ));
+        // Timeouts not reported by the OS are not the peer's.
+        let synthesized = io::Error::from(io::ErrorKind::TimedOut);
+        assert!(matches!(
+            SendLoopError::io(synthesized),
+            SendLoopError::Io(_)
+        ));
+        let quic_timed_out = io::Error::from(quinn::ReadError::ConnectionLost(
+            quinn::ConnectionError::TimedOut,
+        ));
+        assert!(matches!(
+            SendLoopError::io(quic_timed_out),
+            SendLoopError::PeerUnresponsive(_)
+        ));
+        // Other errors are recoverable by reconnecting.
+        let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+        assert!(matches!(SendLoopError::io(reset), SendLoopError::Io(_)));
+    }
 }
@@ -0,0 +1,46 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! The process-wide pool of serialized message buffers.
+//!
+//! Frames sent and received by network channels, and message payloads
+//! serialized for remote delivery, take their buffers from this pool,
+//! which reclaims them once the message is dropped. The pool is sized by
+//! [`crate::config::BUFFER_POOL_MIN_BUFFER_SIZE`],
+//! [`crate::config::BUFFER_POOL_MAX_BUFFER_SIZE`], and
+//! [`crate::config::BUFFER_POOL_BUFFERS_PER_CLASS`] when first used, and
+//! reports its reuse through the `buffer_pool.*` metrics.
+
+use std::sync::Arc;
+use std::sync::LazyLock;
+use std::sync::OnceLock;
+
+use hyperactor_config::global;
+pub use serde_multipart::pool::BufferPool;
+pub use serde_multipart::pool::PoolConfig;
+pub use serde_multipart::pool::PoolStats;
+pub use serde_multipart::pool::PooledBuffer;
+
+use crate::config;
+use crate::metrics;
+
+/// The global buffer pool.
+pub fn global() -> &'static Arc<BufferPool> {
+    static POOL: OnceLock<Arc<BufferPool>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let pool = BufferPool::new(PoolConfig {
+            min_buffer_size: global::get(config::BUFFER_POOL_MIN_BUFFER_SIZE),
+            max_buffer_size: global::get(config::BUFFER_POOL_MAX_BUFFER_SIZE),
+            buffers_per_class: global::get(config::BUFFER_POOL_BUFFERS_PER_CLASS),
+        });
+        LazyLock::force(&metrics::BUFFER_POOL_REUSE_RATE);
+        LazyLock::force(&metrics::BUFFER_POOL_REUSED);
+        LazyLock::force(&metrics::BUFFER_POOL_ALLOCATED);
+        pool
+    })
+}
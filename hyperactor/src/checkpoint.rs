@@ -0,0 +1,472 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Actor checkpoints.
+//!
+//! A [`Checkpoint`] captures an actor's state, as returned by
+//! [`Checkpointable::snapshot`], together with the handler messages that
+//! were queued for the actor but not yet handled. Checkpoints are taken
+//! by the actor loop between two messages (see
+//! [`ActorHandle::checkpoint`]), so the state and the pending messages
+//! are consistent with each other: every message is either reflected in
+//! the state or included in `pending`, never both.
+//!
+//! [`Proc::restore`] rebuilds the actor from a checkpoint and replays its
+//! pending messages, in order, before any other message can reach the
+//! restored actor. Together with a [`CheckpointStore`], this allows a
+//! preempted controller to resume on another proc.
+//!
+//! Only messages in the actor's handler work queue are captured. Messages
+//! held in the receiver-local reorder buffer (waiting on an earlier
+//! sequence number), and messages on ports opened with
+//! [`Instance::open_port`](crate::Instance::open_port), are not.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use hyperactor_config::Flattrs;
+use serde::Deserialize;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tokio::sync::oneshot;
+use typeuri::Named;
+
+use crate::Actor;
+use crate::ActorAddr;
+use crate::ActorHandle;
+use crate::Proc;
+use crate::actor::Binds;
+use crate::actor::Referable;
+use crate::mailbox::migrate::MessageVersion;
+use crate::proc::CheckpointRequest;
+
+/// An actor whose state can be captured in a [`Checkpoint`].
+pub trait Checkpointable: Actor {
+    /// The serialized form of the actor's state.
+    type State: Serialize + DeserializeOwned + Named + Send + Sync + 'static;
+
+    /// Capture the actor's current state.
+    fn snapshot(&self) -> Result<Self::State, anyhow::Error>;
+
+    /// Rebuild the actor from a previously captured state.
+    fn restore(state: Self::State) -> Result<Self, anyhow::Error>;
+}
+
+/// A handler message that was queued, but not yet handled, when a
+/// checkpoint was taken.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct PendingMessage {
+    /// The message headers.
+    pub headers: Flattrs,
+    /// The wire version of the message type when it was captured.
+    pub version: MessageVersion,
+    /// The serialized message.
+    pub data: wirevalue::Any,
+}
+wirevalue::register_type!(PendingMessage);
+
+/// A snapshot of an actor's state and unprocessed messages.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct Checkpoint {
+    actor: ActorAddr,
+    state: wirevalue::Any,
+    pending: Vec<PendingMessage>,
+}
+wirevalue::register_type!(Checkpoint);
+
+impl Checkpoint {
+    pub(crate) fn new<A: Checkpointable>(
+        actor_addr: ActorAddr,
+        actor: &A,
+        pending: Vec<PendingMessage>,
+    ) -> Result<Self, CheckpointError> {
+        let state = actor.snapshot().map_err(CheckpointError::Snapshot)?;
+        Ok(Self {
+            actor: actor_addr,
+            state: wirevalue::Any::serialize(&state)?,
+            pending,
+        })
+    }
+
+    /// The actor that was checkpointed.
+    pub fn actor_addr(&self) -> &ActorAddr {
+        &self.actor
+    }
+
+    /// The serialized actor state.
+    pub fn state(&self) -> &wirevalue::Any {
+        &self.state
+    }
+
+    /// The messages that were queued for the actor, in delivery order.
+    pub fn pending(&self) -> &[PendingMessage] {
+        &self.pending
+    }
+}
+
+/// Errors that occur while taking or restoring a checkpoint.
+#[derive(Debug, thiserror::Error)]
+pub enum CheckpointError {
+    /// The actor failed to snapshot its state.
+    #[error("snapshot failed: {0}")]
+    Snapshot(#[source] anyhow::Error),
+
+    /// The actor failed to rebuild itself from its state.
+    #[error("restore failed: {0}")]
+    Restore(#[source] anyhow::Error),
+
+    /// A pending message's type is not registered, and so it cannot be
+    /// serialized.
+    #[error("pending message of unregistered type {0} cannot be checkpointed")]
+    Unregistered(&'static str),
+
+    /// A pending message of this type is queued on an
+    /// earliest-deadline-first handler port, and so cannot be
+    /// checkpointed; see [`crate::proc::Instance::handle_by_deadline`].
+    #[error("pending message of type {0} is queued by deadline and cannot be checkpointed")]
+    DeadlinePort(&'static str),
+
+    /// The actor is not running, or stopped before the checkpoint was taken.
+    #[error("actor {0} is not running")]
+    NotRunning(ActorAddr),
+
+    /// A state or message could not be (de)serialized.
+    #[error(transparent)]
+    Wirevalue(#[from] wirevalue::Error),
+
+    /// The checkpoint store failed.
+    #[error("checkpoint store: {0}")]
+    Store(#[source] anyhow::Error),
+}
+
+/// Durable storage for checkpoints, keyed by caller-chosen names.
+#[async_trait]
+pub trait CheckpointStore: Send + Sync {
+    /// Store `checkpoint` under `key`, replacing any previous checkpoint.
+    async fn put(&self, key: &str, checkpoint: &Checkpoint) -> Result<(), anyhow::Error>;
+
+    /// Retrieve the checkpoint stored under `key`, if any.
+    async fn get(&self, key: &str) -> Result<Option<Checkpoint>, anyhow::Error>;
+}
+
+/// A [`CheckpointStore`] that keeps checkpoints in memory.
+#[derive(Debug, Default)]
+pub struct MemoryCheckpointStore {
+    checkpoints: Mutex<HashMap<String, Checkpoint>>,
+}
+
+#[async_trait]
+impl CheckpointStore for MemoryCheckpointStore {
+    async fn put(&self, key: &str, checkpoint: &Checkpoint) -> Result<(), anyhow::Error> {
+        self.checkpoints
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), checkpoint.clone());
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Checkpoint>, anyhow::Error> {
+        Ok(self.checkpoints.lock().unwrap().get(key).cloned())
+    }
+}
+
+/// A [`CheckpointStore`] that writes each checkpoint to a file in a
+/// directory. Files are replaced atomically, so that a reader never
+/// observes a partially written checkpoint.
+#[derive(Debug, Clone)]
+pub struct FileCheckpointStore {
+    dir: PathBuf,
+}
+
+impl FileCheckpointStore {
+    /// Create a store that keeps checkpoints in `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The file of `key`. Characters other than ASCII alphanumerics, `-`,
+    /// and `_` are percent-encoded, so that every key has its own file in
+    /// the store's directory.
+    fn path(&self, key: &str) -> PathBuf {
+        let mut name = String::with_capacity(key.len() + 11);
+        for byte in key.bytes() {
+            if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+                name.push(byte as char);
+            } else {
+                name.push_str(&format!("%{:02X}", byte));
+            }
+        }
+        name.push_str(".checkpoint");
+        self.dir.join(name)
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for FileCheckpointStore {
+    async fn put(&self, key: &str, checkpoint: &Checkpoint) -> Result<(), anyhow::Error> {
+        let data = bincode::serde::encode_to_vec(checkpoint, bincode::config::legacy())?;
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.path(key);
+        let tmp = path.with_extension("checkpoint.tmp");
+        tokio::fs::write(&tmp, data).await?;
+        tokio::fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Checkpoint>, anyhow::Error> {
+        let data = match tokio::fs::read(self.path(key)).await {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let (checkpoint, _) = bincode::serde::decode_from_slice(&data, bincode::config::legacy())?;
+        Ok(Some(checkpoint))
+    }
+}
+
+impl<A: Checkpointable> ActorHandle<A> {
+    /// Checkpoint the actor. The checkpoint is taken by the actor loop
+    /// once the actor finishes handling its current message, ahead of any
+    /// queued messages, which are captured as pending. The actor then
+    /// continues to run.
+    ///
+    /// This must not be awaited from within the actor's own handlers.
+    pub async fn checkpoint(&self) -> Result<Checkpoint, CheckpointError> {
+        let (tx, rx) = oneshot::channel();
+        let actor_addr = self.actor_addr().clone();
+        let request: CheckpointRequest<A> = Box::new(move |actor: &A, pending| {
+            let _ =
+                tx.send(pending.and_then(|pending| Checkpoint::new(actor_addr, actor, pending)));
+        });
+        let not_running = || CheckpointError::NotRunning(self.actor_addr().clone());
+        if !self.ports().request_checkpoint(request) {
+            return Err(not_running());
+        }
+        rx.await.map_err(|_| not_running())?
+    }
+
+    /// Checkpoint the actor and store the result under `key`.
+    pub async fn checkpoint_to(
+        &self,
+        store: &dyn CheckpointStore,
+        key: &str,
+    ) -> Result<Checkpoint, CheckpointError> {
+        let checkpoint = self.checkpoint().await?;
+        store
+            .put(key, &checkpoint)
+            .await
+            .map_err(CheckpointError::Store)?;
+        Ok(checkpoint)
+    }
+}
+
+impl Proc {
+    /// Spawn an actor from `checkpoint`, and replay its pending messages.
+    /// The actor's ports are bound through `A`'s [`Binds`] implementation
+    /// before the pending messages are replayed.
+    pub fn restore<A>(
+        &self,
+        label: &str,
+        checkpoint: Checkpoint,
+    ) -> Result<ActorHandle<A>, CheckpointError>
+    where
+        A: Checkpointable + Referable + Binds<A>,
+    {
+        let state = checkpoint.state.deserialized::<A::State>()?;
+        let actor = A::restore(state).map_err(CheckpointError::Restore)?;
+        let handle = self.spawn_with_label(label, actor);
+        handle.bind::<A>();
+        handle.ports().replay(&checkpoint.actor, checkpoint.pending);
+        Ok(handle)
+    }
+
+    /// Restore the checkpoint stored under `key`. Returns `None` if the
+    /// store holds no such checkpoint.
+    pub async fn restore_from<A>(
+        &self,
+        label: &str,
+        store: &dyn CheckpointStore,
+        key: &str,
+    ) -> Result<Option<ActorHandle<A>>, CheckpointError>
+    where
+        A: Checkpointable + Referable + Binds<A>,
+    {
+        match store.get(key).await.map_err(CheckpointError::Store)? {
+            Some(checkpoint) => self.restore(label, checkpoint).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate as hyperactor;
+    use crate::Context;
+    use crate::Endpoint;
+    use crate::Handler;
+    use crate::OncePortRef;
+    use crate::client::Client;
+
+    #[derive(Debug, Default)]
+    #[hyperactor::export(handlers = [Add, Hold, Get])]
+    struct Counter {
+        total: u64,
+    }
+
+    #[async_trait]
+    impl Actor for Counter {}
+
+    #[derive(Debug, Clone, Serialize, Deserialize, Named)]
+    struct Add(u64);
+    wirevalue::register_type!(Add);
+
+    /// Acknowledge, then keep the actor busy for a while, so that
+    /// messages queue up behind this one.
+    #[derive(Debug, Clone, Serialize, Deserialize, Named)]
+    struct Hold(OncePortRef<()>);
+    wirevalue::register_type!(Hold);
+
+    #[derive(Debug, Clone, Serialize, Deserialize, Named)]
+    struct Get(OncePortRef<u64>);
+    wirevalue::register_type!(Get);
+
+    #[async_trait]
+    impl Handler<Add> for Counter {
+        async fn handle(&mut self, _cx: &Context<Self>, Add(n): Add) -> anyhow::Result<()> {
+            self.total += n;
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Handler<Hold> for Counter {
+        async fn handle(&mut self, cx: &Context<Self>, Hold(ack): Hold) -> anyhow::Result<()> {
+            ack.post(cx, ());
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Handler<Get> for Counter {
+        async fn handle(&mut self, cx: &Context<Self>, Get(reply): Get) -> anyhow::Result<()> {
+            reply.post(cx, self.total);
+            Ok(())
+        }
+    }
+
+    impl Checkpointable for Counter {
+        type State = u64;
+
+        fn snapshot(&self) -> Result<u64, anyhow::Error> {
+            Ok(self.total)
+        }
+
+        fn restore(total: u64) -> Result<Self, anyhow::Error> {
+            Ok(Self { total })
+        }
+    }
+
+    async fn total(client: &Client, actor: &ActorHandle<Counter>) -> u64 {
+        let (port, receiver) = client.open_once_port::<u64>();
+        actor.bind::<Counter>().post(client, Get(port.bind()));
+        receiver.recv().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_restore() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let actor = proc.spawn(Counter::default());
+        let actor_ref = actor.bind::<Counter>();
+
+        actor_ref.post(&client, Add(1));
+        actor_ref.post(&client, Add(2));
+        assert_eq!(total(&client, &actor).await, 3);
+
+        // Queue messages behind a busy handler, so that they are pending
+        // when the checkpoint is taken.
+        let (port, receiver) = client.open_once_port::<()>();
+        actor_ref.post(&client, Hold(port.bind()));
+        receiver.recv().await.unwrap();
+        actor_ref.post(&client, Add(10));
+        actor_ref.post(&client, Add(20));
+
+        let store = MemoryCheckpointStore::default();
+        let checkpoint = actor.checkpoint_to(&store, "counter").await.unwrap();
+        assert_eq!(checkpoint.actor_addr(), actor.actor_addr());
+        assert_eq!(checkpoint.state().deserialized::<u64>().unwrap(), 3);
+        assert_eq!(checkpoint.pending().len(), 2);
+
+        // The original actor keeps running, and handles its queued messages.
+        assert_eq!(total(&client, &actor).await, 33);
+
+        let restored = proc
+            .restore_from::<Counter>("restored", &store, "counter")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_ne!(restored.actor_addr(), actor.actor_addr());
+        assert_eq!(total(&client, &restored).await, 33);
+
+        assert!(
+            proc.restore_from::<Counter>("missing", &store, "missing")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileCheckpointStore::new(dir.path());
+        assert!(store.get("a").await.unwrap().is_none());
+
+        let proc = Proc::isolated();
+        let actor = proc.spawn(Counter { total: 7 });
+        actor.checkpoint_to(&store, "a").await.unwrap();
+        let checkpoint = store.get("a").await.unwrap().unwrap();
+        assert_eq!(checkpoint.state().deserialized::<u64>().unwrap(), 7);
+        assert!(checkpoint.pending().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_file_store_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileCheckpointStore::new(dir.path().join("store"));
+        let proc = Proc::isolated();
+        let keys = ["job-1/counter", "../escape", "a.b", "a%2Eb"];
+        for (total, key) in keys.into_iter().enumerate() {
+            let actor = proc.spawn(Counter {
+                total: total as u64,
+            });
+            actor.checkpoint_to(&store, key).await.unwrap();
+        }
+        for (total, key) in keys.into_iter().enumerate() {
+            let checkpoint = store.get(key).await.unwrap().unwrap();
+            assert_eq!(
+                checkpoint.state().deserialized::<u64>().unwrap(),
+                total as u64
+            );
+        }
+        // Every key has its own file in the store's directory.
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert_eq!(
+            std::fs::read_dir(dir.path().join("store")).unwrap().count(),
+            4
+        );
+    }
+}
@@ -22,6 +22,7 @@ use crate::actor::ActorHandle;
 use crate::actor::ActorStatus;
 use crate::actor::AnyActorHandle;
 use crate::actor::Binds;
+use crate::cancel::CancelToken;
 use crate::context;
 use crate::context::Mailbox as MailboxContext;
 use crate::id::Uid;
@@ -104,6 +105,12 @@ impl Client {
         self.instance.open_once_port()
     }
 
+    /// Name `port` in the reply-to header of the messages posted by this
+    /// client; see [`Instance::set_reply_port`].
+    pub fn set_reply_port<M: RemoteMessage>(&self, port: &PortHandle<M>) -> anyhow::Result<()> {
+        self.instance.set_reply_port(port)
+    }
+
     /// Bind a handler port to this client.
     pub fn bind_handler_port<M: RemoteMessage>(&self) -> (PortHandle<M>, PortReceiver<M>) {
         self.instance.mailbox().bind_handler_port()
@@ -154,6 +161,12 @@ impl Client {
         self.instance.sequencer()
     }
 
+    /// Cancel `token` at this client and at the actors it (transitively)
+    /// sent messages carrying the token to; see [`crate::cancel`].
+    pub fn cancel(&self, token: CancelToken) {
+        self.instance.cancel(token)
+    }
+
     /// The client's lifecycle status.
     pub fn status(&self) -> tokio::sync::watch::Receiver<ActorStatus> {
         self.instance.status()
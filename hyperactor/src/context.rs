@@ -28,9 +28,11 @@ use hyperactor_config::attrs::OPERATION_CONTEXT_HEADER;
 use hyperactor_config::attrs::copy_marked_flattrs;
 
 use crate::ActorAddr;
+use crate::EndpointLocation;
 use crate::Instance;
 use crate::PortAddr;
 use crate::Proc;
+use crate::RemoteMessage;
 use crate::accum;
 use crate::accum::ErasedCommReducer;
 use crate::accum::ReducerMode;
@@ -38,8 +40,14 @@ use crate::accum::ReducerSpec;
 use crate::config;
 use crate::id::Uid;
 use crate::mailbox;
+use crate::mailbox::DeliveryFailureReport;
 use crate::mailbox::MailboxSender;
 use crate::mailbox::MessageEnvelope;
+use crate::mailbox::headers::CANCEL_TOKEN;
+use crate::mailbox::headers::Causality;
+use crate::mailbox::headers::MIRRORED;
+use crate::mailbox::headers::REPLAYED;
+use crate::mailbox::headers::REPLY_TO;
 use crate::ordering::SEQ_INFO;
 use crate::port::Port;
 use crate::time::Alarm;
@@ -123,6 +131,18 @@ pub(crate) trait MailboxExt: Mailbox {
         seq_info_policy: SeqInfoPolicy,
     );
 
+    /// Post `message` by value to a typed port of an actor in this proc,
+    /// without serializing it. If the destination does not admit a
+    /// by-value send, the headers and message are returned, to be posted
+    /// serialized instead.
+    fn post_local<M: RemoteMessage>(
+        &self,
+        dest: &PortAddr,
+        headers: Flattrs,
+        message: M,
+        return_undeliverable: bool,
+    ) -> Result<(), (Flattrs, M)>;
+
     /// Split a port, using a provided reducer spec, if provided.
     fn split(
         &self,
@@ -145,6 +165,92 @@ fn operation_context_headers(headers: &Flattrs) -> Flattrs {
     operation_headers
 }
 
+/// Prepare the headers of a message posted by `cx` to `dest`: link it to
+/// the message being handled, propagate the cancel token, name the reply
+/// port and assign the sequence number.
+fn prepare_headers<T: Actor>(
+    cx: &T,
+    dest: &PortAddr,
+    headers: &mut Flattrs,
+    seq_info_policy: SeqInfoPolicy,
+) {
+    assert!(
+        !headers.contains_key(SEQ_INFO) || seq_info_policy == SeqInfoPolicy::AllowExternal,
+        "SEQ_INFO must not be set on headers outside of fn post unless explicitly allowed"
+    );
+
+    // Link the message to the message being handled, if any.
+    mailbox::headers::stamp_causality(headers, Causality::of(cx.headers()));
+
+    // Propagate the cancel token of the message being handled, and
+    // remember where it went so that cancellation can follow it.
+    if let Some(token) = headers
+        .get(CANCEL_TOKEN)
+        .or_else(|| cx.headers().get(CANCEL_TOKEN))
+    {
+        headers.set(CANCEL_TOKEN, token);
+        cx.instance()
+            .cancellations()
+            .record(token, &dest.actor_addr());
+    }
+
+    // Name the sender's reply port, unless the message names its own.
+    if !headers.contains_key(REPLY_TO)
+        && let Some(port) = cx.instance().reply_port()
+    {
+        headers.set(REPLY_TO, port);
+    }
+
+    if !headers.contains_key(SEQ_INFO) {
+        // Posting cannot be abandoned past this point, so it is okay to
+        // assign the sequence number without worrying about rollback.
+        let sequencer = cx.instance().sequencer();
+        let seq_info = sequencer.assign_seq(dest);
+        // Pair the SENDER_ACTOR_ID stamp with the seq we just assigned.
+        // Helper applies the (seq<=4 || stale) gate, the handler-port +
+        // non-bypass guard, and the framework-owned overwrite semantics.
+        crate::mailbox::headers::stamp_sender_actor_id(
+            headers,
+            &seq_info,
+            dest,
+            cx.mailbox().actor_addr(),
+        );
+        headers.set(SEQ_INFO, seq_info);
+    }
+}
+
+/// Whether a message posted by `cx` to `dest` must be dropped because
+/// `cx` is handling a [`REPLAYED`] or [`MIRRORED`] message. Messages an
+/// actor posts to itself are delivered, but marked in turn.
+///
+/// This depends only on the headers of `cx`, so that handlers running
+/// concurrently (see [`crate::proc::parallel`]) are isolated from each
+/// other's messages.
+fn suppressed_while_isolated<T: Actor>(cx: &T, dest: &PortAddr, headers: &mut Flattrs) -> bool {
+    let current = cx.headers();
+    let mirrored = current.get(MIRRORED);
+    if mirrored.is_none() && !current.get(REPLAYED).unwrap_or(false) {
+        return false;
+    }
+    // The mirror collects the shadow's response through its reply port.
+    if mirrored == Some(true) && current.get(REPLY_TO).as_ref() == Some(dest) {
+        return false;
+    }
+    if dest.actor_addr() != *cx.mailbox().actor_addr() {
+        tracing::debug!(
+            actor_id = %cx.mailbox().actor_addr(),
+            dest = %dest,
+            "dropping message posted while handling a replayed or mirrored message"
+        );
+        return true;
+    }
+    match mirrored {
+        Some(_) => headers.set(MIRRORED, false),
+        None => headers.set(REPLAYED, true),
+    }
+    false
+}
+
 /// Only actors CanSend because they need a return port.
 impl<T: Actor + Send + Sync> MailboxExt for T {
     fn post(
@@ -171,27 +277,10 @@ impl<T: Actor + Send + Sync> MailboxExt for T {
             mailbox::monitored_return_handle()
         });
 
-        assert!(
-            !headers.contains_key(SEQ_INFO) || seq_info_policy == SeqInfoPolicy::AllowExternal,
-            "SEQ_INFO must not be set on headers outside of fn post unless explicitly allowed"
-        );
-
-        if !headers.contains_key(SEQ_INFO) {
-            // This method is infallible so is okay to assign the sequence number
-            // without worrying about rollback.
-            let sequencer = self.instance().sequencer();
-            let seq_info = sequencer.assign_seq(&dest);
-            // Pair the SENDER_ACTOR_ID stamp with the seq we just assigned.
-            // Helper applies the (seq<=4 || stale) gate, the handler-port +
-            // non-bypass guard, and the framework-owned overwrite semantics.
-            crate::mailbox::headers::stamp_sender_actor_id(
-                &mut headers,
-                &seq_info,
-                &dest,
-                self.mailbox().actor_addr(),
-            );
-            headers.set(SEQ_INFO, seq_info);
+        if suppressed_while_isolated(self, &dest, &mut headers) {
+            return;
         }
+        prepare_headers(self, &dest, &mut headers, seq_info_policy);
 
         let mut envelope =
             MessageEnvelope::new(self.mailbox().actor_addr().clone(), dest, data, headers);
@@ -199,6 +288,37 @@ impl<T: Actor + Send + Sync> MailboxExt for T {
         MailboxSender::post(self.instance().proc(), envelope, return_handle);
     }
 
+    fn post_local<M: RemoteMessage>(
+        &self,
+        dest: &PortAddr,
+        mut headers: Flattrs,
+        message: M,
+        return_undeliverable: bool,
+    ) -> Result<(), (Flattrs, M)> {
+        let Some(local) = self.instance().proc().local_sender::<M>(dest, &headers) else {
+            return Err((headers, message));
+        };
+        if suppressed_while_isolated(self, dest, &mut headers) {
+            return Ok(());
+        }
+        prepare_headers(self, dest, &mut headers, SeqInfoPolicy::AssignNew);
+        let sender = self.mailbox().actor_addr();
+        if let Err(err) = local.send(sender, headers, message) {
+            // The message is gone, so report the failure in its place,
+            // as for other by-value sends.
+            if return_undeliverable {
+                self.instance().report_delivery_failure(
+                    DeliveryFailureReport::from_send_error::<M>(
+                        sender.clone(),
+                        EndpointLocation::Port(dest.clone()),
+                        &err,
+                    ),
+                );
+            }
+        }
+        Ok(())
+    }
+
     fn split(
         &self,
         port_id: PortAddr,
@@ -241,6 +361,11 @@ impl<T: Actor + Send + Sync> MailboxExt for T {
             );
         }
 
+        // Split ports are closed along with their parent: directly when
+        // the parent belongs to a mailbox in this proc, and otherwise once
+        // a forwarded message is returned because the parent is gone.
+        let parent = self.instance().proc().muxer().local(port_id.actor_id());
+        let parent_port = port_id.clone();
         let port_index = self.mailbox().allocate_port();
         let split_port = self
             .mailbox()
@@ -453,8 +578,19 @@ impl<T: Actor + Send + Sync> MailboxExt for T {
         };
         self.mailbox().bind_untyped(
             &split_port,
-            mailbox::UntypedUnboundedSender { sender: enqueue },
+            mailbox::UntypedUnboundedSender {
+                port_id: split_port.clone(),
+                sender: enqueue,
+            },
         );
+        match parent {
+            Some(mailbox) => {
+                mailbox.add_split(&parent_port.port(), self.mailbox(), split_port.port())
+            }
+            None => self
+                .mailbox()
+                .add_remote_split(parent_port, split_port.port()),
+        }
         Ok(split_port)
     }
 }
@@ -0,0 +1,362 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Interactive message inspection and stepping.
+//!
+//! When [`crate::config::DEBUG_PORT`] is enabled (it is off by
+//! default), every actor serves [`DebugMessage`]s on its debug control
+//! port ([`ControlPort::Debug`]). Requests are served only if their
+//! reply port belongs to their sender, and system actors, such as the
+//! proc agent, refuse them. They are handled by the actor loop between
+//! messages, ahead of queued work:
+//!
+//! - [`DebugMessage::Freeze`] stops the actor from handling the
+//!   messages queued for its handlers. Messages keep arriving and
+//!   queue up; signals and supervision events are still handled.
+//! - [`DebugMessage::List`] lists the queued messages in delivery
+//!   order, deserialized to JSON through the type registry.
+//! - [`DebugMessage::Step`] hands the next queued message to the
+//!   frozen actor.
+//! - [`DebugMessage::Inject`] posts a message to one of the actor's
+//!   own handlers, behind the messages already queued.
+//! - [`DebugMessage::Thaw`] resumes normal delivery.
+//!
+//! Freezing takes effect between messages: a handler that is running
+//! when the actor is frozen runs to completion. The `hyper debug`
+//! command drives these messages from the command line; programs use
+//! [`request`].
+
+use hyperactor_config::Flattrs;
+use hyperactor_telemetry::hash_to_u64;
+use serde::Deserialize;
+use serde::Serialize;
+use typeuri::Named;
+
+use crate::ActorAddr;
+use crate::OncePortRef;
+use crate::PortRef;
+use crate::checkpoint::CheckpointError;
+use crate::checkpoint::PendingMessage;
+use crate::context;
+use crate::endpoint::Endpoint as _;
+use crate::mailbox::MailboxError;
+use crate::mailbox::headers::SENDER_ACTOR_ID;
+use crate::mailbox::headers::SENDER_ACTOR_ID_HASH;
+use crate::mailbox::open_once_port;
+use crate::port::ControlPort;
+
+/// A debug request, sent to an actor's debug control port.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub enum DebugMessage {
+    /// Stop handling queued messages. Replies with
+    /// [`DebugReply::Frozen`].
+    Freeze {
+        /// Reply port.
+        reply: OncePortRef<DebugReply>,
+    },
+    /// Resume handling queued messages. Replies with
+    /// [`DebugReply::Frozen`].
+    Thaw {
+        /// Reply port.
+        reply: OncePortRef<DebugReply>,
+    },
+    /// List the queued messages. Replies with [`DebugReply::Queued`].
+    List {
+        /// Reply port.
+        reply: OncePortRef<DebugReply>,
+    },
+    /// Handle the next queued message. Only valid while frozen.
+    /// Replies with [`DebugReply::Stepped`] before the message is
+    /// handled.
+    Step {
+        /// Reply port.
+        reply: OncePortRef<DebugReply>,
+    },
+    /// Post `message` to the actor's handler for its type. Replies
+    /// with [`DebugReply::Frozen`].
+    Inject {
+        /// The message to post.
+        message: wirevalue::Any,
+        /// Reply port.
+        reply: OncePortRef<DebugReply>,
+    },
+}
+wirevalue::register_type!(DebugMessage);
+
+impl DebugMessage {
+    fn reply(&self) -> &OncePortRef<DebugReply> {
+        match self {
+            DebugMessage::Freeze { reply }
+            | DebugMessage::Thaw { reply }
+            | DebugMessage::List { reply }
+            | DebugMessage::Step { reply }
+            | DebugMessage::Inject { reply, .. } => reply,
+        }
+    }
+
+    /// The request's reply port.
+    pub(crate) fn into_reply(self) -> OncePortRef<DebugReply> {
+        match self {
+            DebugMessage::Freeze { reply }
+            | DebugMessage::Thaw { reply }
+            | DebugMessage::List { reply }
+            | DebugMessage::Step { reply }
+            | DebugMessage::Inject { reply, .. } => reply,
+        }
+    }
+
+    /// Whether the request, delivered with `headers`, replies to the
+    /// actor that sent it.
+    pub(crate) fn is_from(&self, headers: &Flattrs) -> bool {
+        let caller = self.reply().port_addr().actor_addr();
+        headers.get(SENDER_ACTOR_ID_HASH) == Some(hash_to_u64(caller.id()))
+    }
+}
+
+/// The reply to a [`DebugMessage`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named)]
+pub enum DebugReply {
+    /// Whether the actor is frozen.
+    Frozen(bool),
+    /// The queued messages, in delivery order.
+    Queued(Vec<QueuedEnvelope>),
+    /// The message handed to the actor, or `None` if no message was
+    /// queued.
+    Stepped(Option<QueuedEnvelope>),
+    /// The request could not be carried out.
+    Error(String),
+}
+wirevalue::register_type!(DebugReply);
+
+/// A queued message, as listed by the debugger.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named)]
+pub struct QueuedEnvelope {
+    /// The message's position in the queue; 0 is delivered next.
+    pub position: usize,
+    /// The name of the message type, if it is registered.
+    pub typename: Option<String>,
+    /// The sender of the message, if recorded.
+    pub sender: Option<ActorAddr>,
+    /// The message as JSON, or why it cannot be shown.
+    pub message: Result<String, String>,
+}
+wirevalue::register_type!(QueuedEnvelope);
+
+impl QueuedEnvelope {
+    /// Describe the work item at `position`: `None` for runtime work
+    /// that is not a message.
+    pub(crate) fn describe(
+        position: usize,
+        pending: Option<Result<PendingMessage, CheckpointError>>,
+    ) -> Self {
+        let (typename, sender, message) = match pending {
+            None => (None, None, Err("runtime work".to_string())),
+            Some(Err(err)) => (None, None, Err(err.to_string())),
+            Some(Ok(pending)) => (
+                pending.data.typename().map(str::to_string),
+                pending.headers.get(SENDER_ACTOR_ID),
+                pending
+                    .data
+                    .dump()
+                    .map(|value| value.to_string())
+                    .map_err(|err| err.to_string()),
+            ),
+        };
+        Self {
+            position,
+            typename,
+            sender,
+            message,
+        }
+    }
+}
+
+/// Send the debug request built by `message` to `actor`, and wait for
+/// its reply.
+pub async fn request(
+    cx: &impl context::Actor,
+    actor: &ActorAddr,
+    message: impl FnOnce(OncePortRef<DebugReply>) -> DebugMessage,
+) -> Result<DebugReply, MailboxError> {
+    let (reply, receiver) = open_once_port::<DebugReply>(cx);
+    PortRef::<DebugMessage>::attest_control_port(actor, ControlPort::Debug)
+        .post(cx, message(reply.bind()));
+    receiver.recv().await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate as hyperactor;
+    use crate::Actor;
+    use crate::Context;
+    use crate::Endpoint;
+    use crate::Handler;
+    use crate::Instance;
+    use crate::Proc;
+
+    #[derive(Debug, Default)]
+    #[hyperactor::export(handlers = [Add])]
+    struct Sink;
+
+    impl Actor for Sink {}
+
+    #[derive(Debug, Default)]
+    struct SystemSink;
+
+    #[async_trait]
+    impl Actor for SystemSink {
+        async fn init(&mut self, this: &Instance<Self>) -> anyhow::Result<()> {
+            this.set_system();
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize, Named)]
+    struct Add(u64);
+    wirevalue::register_type!(Add);
+
+    #[async_trait]
+    impl Handler<Add> for Sink {
+        async fn handle(&mut self, _cx: &Context<Self>, _message: Add) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_freeze_list_step() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(crate::config::DEBUG_PORT, true);
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let handle = proc.spawn(Sink);
+        let sink = handle.bind::<Sink>();
+        let actor = handle.actor_addr().clone();
+
+        let reply = request(&client, &actor, |reply| DebugMessage::Freeze { reply }).await;
+        assert_eq!(reply.unwrap(), DebugReply::Frozen(true));
+
+        sink.post(&client, Add(1));
+        sink.post(&client, Add(2));
+        let message = wirevalue::Any::serialize(&Add(3)).unwrap();
+        let reply = request(&client, &actor, |reply| DebugMessage::Inject {
+            message,
+            reply,
+        })
+        .await;
+        assert_eq!(reply.unwrap(), DebugReply::Frozen(true));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let DebugReply::Queued(queued) =
+            request(&client, &actor, |reply| DebugMessage::List { reply })
+                .await
+                .unwrap()
+        else {
+            panic!("expected a queue listing");
+        };
+        assert_eq!(queued.len(), 3);
+        assert_eq!(queued[0].typename.as_deref(), Some(Add::typename()));
+        assert_eq!(queued[0].message, Ok("1".to_string()));
+        assert_eq!(queued[2].message, Ok("3".to_string()));
+        assert_eq!(queued[2].sender.as_ref(), Some(&actor));
+
+        let DebugReply::Stepped(Some(stepped)) =
+            request(&client, &actor, |reply| DebugMessage::Step { reply })
+                .await
+                .unwrap()
+        else {
+            panic!("expected a stepped message");
+        };
+        assert_eq!(stepped.position, 0);
+        assert_eq!(stepped.message, Ok("1".to_string()));
+
+        // The first message has not been received yet, so the next step
+        // releases the message behind it.
+        let DebugReply::Stepped(Some(stepped)) =
+            request(&client, &actor, |reply| DebugMessage::Step { reply })
+                .await
+                .unwrap()
+        else {
+            panic!("expected a stepped message");
+        };
+        assert_eq!(stepped.position, 1);
+        assert_eq!(stepped.message, Ok("2".to_string()));
+
+        let DebugReply::Queued(queued) =
+            request(&client, &actor, |reply| DebugMessage::List { reply })
+                .await
+                .unwrap()
+        else {
+            panic!("expected a queue listing");
+        };
+        assert_eq!(queued.len(), 1);
+
+        let reply = request(&client, &actor, |reply| DebugMessage::Thaw { reply }).await;
+        assert_eq!(reply.unwrap(), DebugReply::Frozen(false));
+        let reply = request(&client, &actor, |reply| DebugMessage::Step { reply }).await;
+        assert!(matches!(reply.unwrap(), DebugReply::Error(_)));
+    }
+
+    #[tokio::test]
+    async fn test_debug_port_checks() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let other = proc.client("other");
+
+        // Disabled by default.
+        let handle = proc.spawn(Sink);
+        let (reply, receiver) = open_once_port::<DebugReply>(&client);
+        let mut port =
+            PortRef::<DebugMessage>::attest_control_port(handle.actor_addr(), ControlPort::Debug);
+        port.return_undeliverable(false);
+        port.post(
+            &client,
+            DebugMessage::Freeze {
+                reply: reply.bind(),
+            },
+        );
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), receiver.recv())
+                .await
+                .is_err()
+        );
+
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(crate::config::DEBUG_PORT, true);
+        let handle = proc.spawn(Sink);
+        let actor = handle.actor_addr().clone();
+
+        // A request whose reply port is not its sender's is dropped.
+        let (reply, receiver) = open_once_port::<DebugReply>(&other);
+        PortRef::<DebugMessage>::attest_control_port(&actor, ControlPort::Debug).post(
+            &client,
+            DebugMessage::Freeze {
+                reply: reply.bind(),
+            },
+        );
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), receiver.recv())
+                .await
+                .is_err()
+        );
+        let reply = request(&client, &actor, |reply| DebugMessage::Step { reply }).await;
+        assert!(matches!(reply.unwrap(), DebugReply::Error(_)));
+
+        // System actors refuse to be debugged.
+        let handle = proc.spawn(SystemSink);
+        let reply = request(&client, handle.actor_addr(), |reply| DebugMessage::Freeze {
+            reply,
+        })
+        .await;
+        assert!(matches!(reply.unwrap(), DebugReply::Error(_)));
+    }
+}
@@ -16,7 +16,9 @@ use serde::Serialize;
 
 use crate::ActorAddr;
 use crate::PortAddr;
+use crate::RemoteMessage;
 use crate::context;
+use crate::mailbox::OncePortReceiver;
 use crate::mailbox::PortLocation;
 
 /// The logical location of an endpoint.
@@ -95,6 +97,48 @@ pub trait RemoteEndpoint<M>: Endpoint<M> {
     fn post_with_headers<C>(self, cx: &C, headers: Flattrs, message: M)
     where
         C: context::Actor;
+
+    /// Post `message` to this endpoint from `cx`, with a [`REPLY_TO`]
+    /// header naming a fresh one-shot port owned by `cx`. The receiving
+    /// handler replies with [`crate::proc::Context::respond`]; the reply
+    /// is received through the returned receiver.
+    ///
+    /// [`REPLY_TO`]: crate::mailbox::headers::REPLY_TO
+    fn request<C, R>(self, cx: &C, message: M) -> OncePortReceiver<R>
+    where
+        Self: Sized,
+        C: context::Actor,
+        R: RemoteMessage,
+    {
+        let (reply_handle, reply_rx) = crate::mailbox::open_once_port::<R>(cx);
+        let reply_ref = reply_handle.bind();
+        let mut headers = Flattrs::new();
+        headers.set(
+            crate::mailbox::headers::REPLY_TO,
+            reply_ref.into_port_addr(),
+        );
+        self.post_with_headers(cx, headers, message);
+        reply_rx
+    }
+
+    /// Post `message` to this endpoint from `cx`, with a
+    /// [`DELIVERY_DEADLINE`] header `deadline` from now. Ports opened
+    /// with [`crate::Mailbox::open_deadline_port`] drop the message if
+    /// it is not received by then.
+    ///
+    /// [`DELIVERY_DEADLINE`]: crate::mailbox::headers::DELIVERY_DEADLINE
+    fn post_with_deadline<C>(self, cx: &C, deadline: std::time::Duration, message: M)
+    where
+        Self: Sized,
+        C: context::Actor,
+    {
+        let mut headers = Flattrs::new();
+        headers.set(
+            crate::mailbox::headers::DELIVERY_DEADLINE,
+            std::time::SystemTime::now() + deadline,
+        );
+        self.post_with_headers(cx, headers, message);
+    }
 }
 
 #[cfg(test)]
@@ -135,6 +179,19 @@ mod tests {
         }
     }
 
+    #[derive(Debug)]
+    struct IncrementActor;
+
+    #[async_trait]
+    impl Actor for IncrementActor {}
+
+    #[async_trait]
+    impl Handler<u64> for IncrementActor {
+        async fn handle(&mut self, cx: &Context<Self>, message: u64) -> anyhow::Result<()> {
+            cx.respond(message + 1)
+        }
+    }
+
     struct TestBehavior;
 
     impl Named for TestBehavior {
@@ -246,4 +303,35 @@ mod tests {
             (456, 123)
         );
     }
+
+    #[tokio::test]
+    async fn test_request_respond() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let handle = proc.spawn(IncrementActor);
+        let port_ref = handle.port::<u64>().bind();
+
+        let reply = RemoteEndpoint::request::<_, u64>(&port_ref, &client, 1u64);
+        assert_eq!(reply.recv().await.expect("reply should arrive"), 2);
+    }
+
+    #[tokio::test]
+    async fn test_reply_port_respond() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let handle = proc.spawn(IncrementActor);
+        let port_ref = handle.port::<u64>().bind();
+
+        let (reply_tx, mut reply_rx) = client.open_port::<u64>();
+        client.set_reply_port(&reply_tx).unwrap();
+        Endpoint::post(&port_ref, &client, 1u64);
+        Endpoint::post(&port_ref, &client, 10u64);
+        assert_eq!(reply_rx.recv().await.unwrap(), 2);
+        assert_eq!(reply_rx.recv().await.unwrap(), 11);
+
+        // A port owned by another actor cannot be the reply port.
+        let other = proc.client("other");
+        let (other_tx, _other_rx) = other.open_port::<u64>();
+        assert!(client.set_reply_port(&other_tx).is_err());
+    }
 }
@@ -0,0 +1,550 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Event-sourced actor state.
+//!
+//! An [`EventSourced`] state changes only by applying events. A
+//! [`Journaled`] state persists each event to an [`EventLog`] before
+//! applying it, so that the state can be rebuilt deterministically by
+//! replaying the log, for example when a controller that manages a
+//! long-running job restarts on another proc.
+//!
+//! Handlers decide which events a message produces, from the current
+//! state, and then commit them:
+//!
+//! ```ignore
+//! async fn handle(&mut self, cx: &Context<Self>, Submit(job): Submit) -> anyhow::Result<()> {
+//!     self.jobs
+//!         .handle_with(|jobs| Ok(vec![JobEvent::Submitted(jobs.next_id(), job)]))
+//!         .await?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Side effects, such as sending messages, belong after the commit:
+//! replay applies events without re-running handlers, so that effects
+//! are not repeated on recovery.
+//!
+//! Logs are appended to with optimistic concurrency: each append states
+//! the length of the log that it extends, so that a stale incarnation
+//! of an actor cannot interleave its events with those of its
+//! replacement.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use typeuri::Named;
+
+/// A state that changes only by applying events.
+pub trait EventSourced: Send + Sync + 'static {
+    /// The events that change the state.
+    type Event: Serialize + DeserializeOwned + Named + Send + Sync + 'static;
+
+    /// Apply `event` to the state. This must be deterministic, and must
+    /// not have side effects, as it is also used to replay the log.
+    fn apply(&mut self, event: &Self::Event);
+}
+
+/// Errors that occur while persisting or replaying events.
+#[derive(Debug, thiserror::Error)]
+pub enum EventSourcingError {
+    /// The log was extended by another writer.
+    #[error("event log {stream} has {actual} events, expected {expected}")]
+    Conflict {
+        /// The log's stream.
+        stream: String,
+        /// The length that the append expected.
+        expected: u64,
+        /// The log's actual length.
+        actual: u64,
+    },
+
+    /// An event could not be (de)serialized.
+    #[error(transparent)]
+    Wirevalue(#[from] wirevalue::Error),
+
+    /// The event log failed.
+    #[error("event log: {0}")]
+    Log(#[source] anyhow::Error),
+}
+
+/// Append-only, durable storage for streams of events, keyed by
+/// caller-chosen stream names.
+#[async_trait]
+pub trait EventLog: Send + Sync {
+    /// Append `events` to `stream`, if it has exactly `expected`
+    /// events. Once this returns, the events are durable.
+    async fn append(
+        &self,
+        stream: &str,
+        expected: u64,
+        events: &[wirevalue::Any],
+    ) -> Result<(), EventSourcingError>;
+
+    /// Read all events of `stream`, in the order they were appended.
+    async fn read(&self, stream: &str) -> Result<Vec<wirevalue::Any>, EventSourcingError>;
+}
+
+fn check_len(stream: &str, expected: u64, actual: u64) -> Result<(), EventSourcingError> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(EventSourcingError::Conflict {
+            stream: stream.to_string(),
+            expected,
+            actual,
+        })
+    }
+}
+
+/// An [`EventLog`] that keeps events in memory.
+#[derive(Debug, Default)]
+pub struct MemoryEventLog {
+    streams: Mutex<HashMap<String, Vec<wirevalue::Any>>>,
+}
+
+#[async_trait]
+impl EventLog for MemoryEventLog {
+    async fn append(
+        &self,
+        stream: &str,
+        expected: u64,
+        events: &[wirevalue::Any],
+    ) -> Result<(), EventSourcingError> {
+        let mut streams = self.streams.lock().unwrap();
+        let log = streams.entry(stream.to_string()).or_default();
+        check_len(stream, expected, log.len() as u64)?;
+        log.extend_from_slice(events);
+        Ok(())
+    }
+
+    async fn read(&self, stream: &str) -> Result<Vec<wirevalue::Any>, EventSourcingError> {
+        Ok(self
+            .streams
+            .lock()
+            .unwrap()
+            .get(stream)
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+/// An [`EventLog`] that appends each stream's events to a file in a
+/// directory, as length-prefixed records, and syncs the file before an
+/// append returns. A record torn by a crash during an append is
+/// discarded, with the rest of the append, when the log is next read.
+///
+/// Appends hold an exclusive lock on the stream's file while they check
+/// its length, so that concurrent appends are detected among all
+/// processes that share the directory, provided that its file system
+/// supports file locks.
+#[derive(Debug)]
+pub struct FileEventLog {
+    dir: PathBuf,
+    /// The number of valid records, and their length in bytes, by
+    /// stream, as of this log's last append. Valid records are never
+    /// removed, so these are current if the file has the same length.
+    lens: tokio::sync::Mutex<HashMap<String, (u64, u64)>>,
+}
+
+impl FileEventLog {
+    /// Create a log that keeps its streams in `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            lens: tokio::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The file of `stream`. Characters other than ASCII alphanumerics,
+    /// `-`, and `_` are percent-encoded, so that every stream has its
+    /// own file in the log's directory.
+    fn path(&self, stream: &str) -> PathBuf {
+        let mut name = String::with_capacity(stream.len() + 7);
+        for byte in stream.bytes() {
+            if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+                name.push(byte as char);
+            } else {
+                name.push_str(&format!("%{:02X}", byte));
+            }
+        }
+        name.push_str(".events");
+        self.dir.join(name)
+    }
+
+    /// Read the valid records of `stream`, and their length in bytes.
+    async fn read_records(
+        &self,
+        stream: &str,
+    ) -> Result<(Vec<wirevalue::Any>, u64), EventSourcingError> {
+        let path = self.path(stream);
+        tokio::task::spawn_blocking(move || {
+            let mut file = match std::fs::File::open(&path) {
+                Ok(file) => file,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    return Ok((Vec::new(), 0));
+                }
+                Err(err) => return Err(EventSourcingError::Log(err.into())),
+            };
+            file.lock_shared()
+                .map_err(|err| EventSourcingError::Log(err.into()))?;
+            parse_records(&mut file)
+        })
+        .await
+        .map_err(|err| EventSourcingError::Log(err.into()))?
+    }
+}
+
+/// Read the valid records of `file`, and their length in bytes.
+fn parse_records(
+    file: &mut std::fs::File,
+) -> Result<(Vec<wirevalue::Any>, u64), EventSourcingError> {
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)
+        .map_err(|err| EventSourcingError::Log(err.into()))?;
+    let mut events = Vec::new();
+    let mut offset = 0;
+    while let Some(header) = data.get(offset..offset + 4) {
+        let len = u32::from_le_bytes(header.try_into().unwrap()) as usize;
+        let Some(record) = data.get(offset + 4..offset + 4 + len) else {
+            break;
+        };
+        let (event, _) = bincode::serde::decode_from_slice(record, bincode::config::legacy())
+            .map_err(|err| EventSourcingError::Log(err.into()))?;
+        events.push(event);
+        offset += 4 + len;
+    }
+    Ok((events, offset as u64))
+}
+
+#[async_trait]
+impl EventLog for FileEventLog {
+    async fn append(
+        &self,
+        stream: &str,
+        expected: u64,
+        events: &[wirevalue::Any],
+    ) -> Result<(), EventSourcingError> {
+        let mut lens = self.lens.lock().await;
+        let cached = lens.get(stream).copied();
+
+        let mut data = Vec::new();
+        for event in events {
+            let record = bincode::serde::encode_to_vec(event, bincode::config::legacy())
+                .map_err(|err| EventSourcingError::Log(err.into()))?;
+            data.extend_from_slice(&(record.len() as u32).to_le_bytes());
+            data.extend_from_slice(&record);
+        }
+        let appended = (events.len() as u64, data.len() as u64);
+        let path = self.path(stream);
+        let dir = self.dir.clone();
+        let name = stream.to_string();
+        let len = tokio::task::spawn_blocking(move || {
+            let io = |err: std::io::Error| EventSourcingError::Log(err.into());
+            std::fs::create_dir_all(&dir).map_err(io)?;
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .read(true)
+                .write(true)
+                .truncate(false)
+                .open(&path)
+                .map_err(io)?;
+            // Released when the file is closed.
+            file.lock().map_err(io)?;
+            let (len, bytes) = match cached {
+                Some((len, bytes)) if file.metadata().map_err(io)?.len() == bytes => (len, bytes),
+                _ => {
+                    let (events, bytes) = parse_records(&mut file)?;
+                    (events.len() as u64, bytes)
+                }
+            };
+            check_len(&name, expected, len)?;
+
+            // Writes past the valid records replace a torn record, if any.
+            file.set_len(bytes).map_err(io)?;
+            std::io::Seek::seek(&mut file, std::io::SeekFrom::Start(bytes)).map_err(io)?;
+            file.write_all(&data).map_err(io)?;
+            file.sync_data().map_err(io)?;
+            Ok((len + appended.0, bytes + appended.1))
+        })
+        .await
+        .map_err(|err| EventSourcingError::Log(err.into()))??;
+
+        lens.insert(stream.to_string(), len);
+        Ok(())
+    }
+
+    async fn read(&self, stream: &str) -> Result<Vec<wirevalue::Any>, EventSourcingError> {
+        Ok(self.read_records(stream).await?.0)
+    }
+}
+
+/// An [`EventSourced`] state whose events are persisted to a stream of
+/// an [`EventLog`] before they are applied. See the [module
+/// documentation](self).
+pub struct Journaled<S: EventSourced> {
+    state: S,
+    log: Arc<dyn EventLog>,
+    stream: String,
+    len: u64,
+}
+
+impl<S: EventSourced> Journaled<S> {
+    /// Recover the state persisted to `stream` of `log`, by replaying
+    /// its events onto `initial`.
+    pub async fn recover(
+        mut initial: S,
+        log: Arc<dyn EventLog>,
+        stream: impl Into<String>,
+    ) -> Result<Self, EventSourcingError> {
+        let stream = stream.into();
+        let events = log.read(&stream).await?;
+        for event in &events {
+            initial.apply(&event.deserialized::<S::Event>()?);
+        }
+        Ok(Self {
+            state: initial,
+            log,
+            stream,
+            len: events.len() as u64,
+        })
+    }
+
+    /// The current state.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// The number of events persisted to, and applied from, the log.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether no events have been persisted.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Persist `events`, then apply them to the state. If persisting
+    /// fails, the state is unchanged.
+    pub async fn commit(
+        &mut self,
+        events: impl IntoIterator<Item = S::Event>,
+    ) -> Result<(), EventSourcingError> {
+        let events: Vec<S::Event> = events.into_iter().collect();
+        if events.is_empty() {
+            return Ok(());
+        }
+        let serialized = events
+            .iter()
+            .map(wirevalue::Any::serialize)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.log.append(&self.stream, self.len, &serialized).await?;
+        self.len += events.len() as u64;
+        for event in &events {
+            self.state.apply(event);
+        }
+        Ok(())
+    }
+
+    /// Decide, from the current state, which events to commit, and
+    /// commit them. Returns the committed events.
+    pub async fn handle_with(
+        &mut self,
+        decide: impl FnOnce(&S) -> anyhow::Result<Vec<S::Event>>,
+    ) -> anyhow::Result<Vec<S::Event>>
+    where
+        S::Event: Clone,
+    {
+        let events = decide(&self.state)?;
+        self.commit(events.clone()).await?;
+        Ok(events)
+    }
+}
+
+impl<S: EventSourced + std::fmt::Debug> std::fmt::Debug for Journaled<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Journaled")
+            .field("state", &self.state)
+            .field("stream", &self.stream)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Jobs {
+        running: Vec<String>,
+        finished: u64,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize, Named)]
+    enum JobEvent {
+        Started(String),
+        Finished(String),
+    }
+
+    impl EventSourced for Jobs {
+        type Event = JobEvent;
+
+        fn apply(&mut self, event: &JobEvent) {
+            match event {
+                JobEvent::Started(job) => self.running.push(job.clone()),
+                JobEvent::Finished(job) => {
+                    self.running.retain(|running| running != job);
+                    self.finished += 1;
+                }
+            }
+        }
+    }
+
+    async fn run_jobs(log: Arc<dyn EventLog>) {
+        let mut jobs = Journaled::recover(Jobs::default(), log.clone(), "jobs")
+            .await
+            .unwrap();
+        assert!(jobs.is_empty());
+        jobs.commit([
+            JobEvent::Started("a".to_string()),
+            JobEvent::Started("b".to_string()),
+        ])
+        .await
+        .unwrap();
+        let finished = jobs
+            .handle_with(|jobs| {
+                anyhow::ensure!(jobs.running.contains(&"a".to_string()));
+                Ok(vec![JobEvent::Finished("a".to_string())])
+            })
+            .await
+            .unwrap();
+        assert_eq!(finished.len(), 1);
+        // A failed decision commits nothing.
+        assert!(
+            jobs.handle_with(|_| anyhow::bail!("rejected"))
+                .await
+                .is_err()
+        );
+        assert_eq!(jobs.len(), 3);
+
+        // Recovery replays the log to the same state.
+        let recovered = Journaled::recover(Jobs::default(), log.clone(), "jobs")
+            .await
+            .unwrap();
+        assert_eq!(recovered.state(), jobs.state());
+        assert_eq!(
+            recovered.state(),
+            &Jobs {
+                running: vec!["b".to_string()],
+                finished: 1,
+            }
+        );
+
+        // The replaced incarnation can no longer append.
+        let mut recovered = recovered;
+        recovered
+            .commit([JobEvent::Finished("b".to_string())])
+            .await
+            .unwrap();
+        assert!(matches!(
+            jobs.commit([JobEvent::Started("c".to_string())]).await,
+            Err(EventSourcingError::Conflict {
+                expected: 3,
+                actual: 4,
+                ..
+            })
+        ));
+        assert_eq!(jobs.state().running, vec!["b".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_memory_log() {
+        run_jobs(Arc::new(MemoryEventLog::default())).await;
+    }
+
+    #[tokio::test]
+    async fn test_file_log() {
+        let dir = tempfile::tempdir().unwrap();
+        run_jobs(Arc::new(FileEventLog::new(dir.path()))).await;
+
+        // A torn record is discarded.
+        let path = dir.path().join("jobs.events");
+        let mut data = std::fs::read(&path).unwrap();
+        data.extend_from_slice(&[100, 0, 0, 0, 1, 2]);
+        std::fs::write(&path, data).unwrap();
+        let log = Arc::new(FileEventLog::new(dir.path()));
+        let mut jobs = Journaled::recover(Jobs::default(), log.clone(), "jobs")
+            .await
+            .unwrap();
+        assert_eq!(jobs.len(), 4);
+        jobs.commit([JobEvent::Started("c".to_string())])
+            .await
+            .unwrap();
+        let jobs = Journaled::recover(Jobs::default(), log, "jobs")
+            .await
+            .unwrap();
+        assert_eq!(jobs.state().running, vec!["c".to_string()]);
+        assert_eq!(jobs.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_file_log_fences_other_writers() {
+        let dir = tempfile::tempdir().unwrap();
+        let event = || wirevalue::Any::serialize(&JobEvent::Started("a".to_string())).unwrap();
+        let stale = FileEventLog::new(dir.path());
+        stale.append("jobs", 0, &[event()]).await.unwrap();
+
+        // A replacement, e.g. in another process, appends...
+        let replacement = FileEventLog::new(dir.path());
+        replacement.append("jobs", 1, &[event()]).await.unwrap();
+
+        // ...and the stale log can no longer append, nor truncate the
+        // replacement's events.
+        assert!(matches!(
+            stale.append("jobs", 1, &[event()]).await,
+            Err(EventSourcingError::Conflict {
+                expected: 1,
+                actual: 2,
+                ..
+            })
+        ));
+        assert_eq!(replacement.read("jobs").await.unwrap().len(), 2);
+        stale.append("jobs", 2, &[event()]).await.unwrap();
+        assert_eq!(replacement.read("jobs").await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_file_log_stream_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = FileEventLog::new(dir.path().join("log"));
+        let event = wirevalue::Any::serialize(&JobEvent::Started("a".to_string())).unwrap();
+        for stream in ["job-1/launch", "../escape", "a.b", "a%2Eb"] {
+            log.append(stream, 0, std::slice::from_ref(&event))
+                .await
+                .unwrap();
+            assert_eq!(log.read(stream).await.unwrap().len(), 1);
+        }
+        // Every stream has its own file in the log's directory.
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert_eq!(
+            std::fs::read_dir(dir.path().join("log")).unwrap().count(),
+            4
+        );
+    }
+}
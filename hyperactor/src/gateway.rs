@@ -31,7 +31,6 @@ use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
 use std::sync::OnceLock;
-use std::sync::RwLock;
 use std::sync::Weak;
 
 use async_trait::async_trait;
@@ -60,6 +59,7 @@ use crate::mailbox::TransportFailureReason;
 use crate::mailbox::Undeliverable;
 use crate::mailbox::UndeliverableReason;
 use crate::mailbox::UnroutableMailboxSender;
+use crate::mailbox::snapshot::Snapshot;
 use crate::proc::Proc;
 use crate::proc::WeakProc;
 
@@ -100,12 +100,17 @@ impl Drop for AttachedProcGuard {
         let Some(state) = self.gateway.upgrade() else {
             return;
         };
-        let mut locals = state.locals.write().unwrap();
-        if locals
-            .get(&self.proc_id)
-            .is_some_and(|weak| weak.ptr_eq(&self.weak))
-        {
-            locals.remove(&self.proc_id);
+        let attached = |locals: &HashMap<ProcId, WeakProc>| {
+            locals
+                .get(&self.proc_id)
+                .is_some_and(|weak| weak.ptr_eq(&self.weak))
+        };
+        if attached(&state.locals.load()) {
+            state.locals.update(|locals| {
+                if attached(locals) {
+                    locals.remove(&self.proc_id);
+                }
+            });
         }
     }
 }
@@ -131,7 +136,7 @@ impl Drop for PeerAttachGuard {
         let Some(state) = self.gateway.upgrade() else {
             return;
         };
-        state.peers.write().unwrap().remove(&self.uid);
+        state.peers.update(|peers| peers.remove(&self.uid));
     }
 }
 
@@ -144,6 +149,9 @@ pub struct PeerAttachError {
     pub uid: Uid,
 }
 
+/// The gateway's routing state. The tables that are consulted on every
+/// post are [`Snapshot`]s, so that a panic while they change cannot
+/// poison them for later posts.
 struct GatewayState {
     /// A random, stable identifier for this gateway. It is just a
     /// routing key in peers' tables: peers route messages
@@ -160,11 +168,11 @@ struct GatewayState {
     /// it with `Via(self.uid, peer_default_location)` so addresses
     /// handed out by this gateway carry the via prefix and route back
     /// through the peer.
-    default_location: RwLock<Location>,
+    default_location: Snapshot<Location>,
 
     /// Sender used to forward messages whose destination is neither an
     /// attached proc nor matched by [`peers`].
-    forwarder: RwLock<BoxedMailboxSender>,
+    forwarder: BoxedMailboxSender,
 
     /// Local delivery targets registered with this gateway, keyed by
     /// id. Each value is a [`WeakProc`] so the gateway does not
@@ -172,18 +180,18 @@ struct GatewayState {
     /// delivers directly to the muxer when the destination is a
     /// local-delivery target. Internal: never exposed; populated
     /// only by [`Gateway::attach_proc`], which is itself internal.
-    locals: RwLock<HashMap<ProcId, WeakProc>>,
+    locals: Snapshot<HashMap<ProcId, WeakProc>>,
 
     /// Locations currently served by this gateway. The last location
     /// is the default advertised location.
-    active_servers: RwLock<Vec<Location>>,
+    active_servers: Snapshot<Vec<Location>>,
 
     /// Senders to gateways that have attached *to* this one. Each key
     /// is the attaching gateway's uid; values are senders that put
     /// envelopes back onto the duplex toward that gateway. Source
     /// routes (`Location::Via(uid, ...)`) consult this table to peel
     /// the outermost hop and forward.
-    peers: RwLock<HashMap<Uid, BoxedMailboxSender>>,
+    peers: Snapshot<HashMap<Uid, BoxedMailboxSender>>,
 }
 
 impl Gateway {
@@ -225,11 +233,11 @@ impl Gateway {
             inner: Arc::new(GatewayState {
                 uid: Uid::anonymous(),
                 fallback_location: default_location.clone(),
-                default_location: RwLock::new(default_location),
-                forwarder: RwLock::new(forwarder),
-                locals: RwLock::new(HashMap::new()),
-                active_servers: RwLock::new(Vec::new()),
-                peers: RwLock::new(HashMap::new()),
+                default_location: Snapshot::new(default_location),
+                forwarder,
+                locals: Snapshot::new(HashMap::new()),
+                active_servers: Snapshot::new(Vec::new()),
+                peers: Snapshot::new(HashMap::new()),
             }),
         }
     }
@@ -243,19 +251,39 @@ impl Gateway {
 
     /// The gateway's default advertised location.
     pub fn default_location(&self) -> Location {
-        self.inner.default_location.read().unwrap().clone()
+        self.inner.default_location.get()
     }
 
     /// The outbound forwarder. Inbound traffic for destinations that
     /// don't match a bound proc, route, or via peer is handed off to
     /// this sender.
     pub fn forwarder(&self) -> BoxedMailboxSender {
-        self.inner.forwarder.read().unwrap().clone()
+        self.inner.forwarder.clone()
     }
 
     /// Set the gateway's default advertised location.
     pub fn set_default_location(&self, location: Location) {
-        *self.inner.default_location.write().unwrap() = location;
+        self.inner.default_location.replace(location);
+    }
+
+    /// The live procs attached to this gateway.
+    pub(crate) fn attached_procs(&self) -> Vec<Proc> {
+        self.inner
+            .locals
+            .load()
+            .values()
+            .filter_map(WeakProc::upgrade)
+            .collect()
+    }
+
+    /// The uids of the gateways attached to this one as peers.
+    pub(crate) fn peer_uids(&self) -> Vec<Uid> {
+        self.inner.peers.load().keys().cloned().collect()
+    }
+
+    /// The locations currently served by this gateway.
+    pub(crate) fn served_locations(&self) -> Vec<Location> {
+        self.inner.active_servers.get()
     }
 
     /// Attach a proc to this gateway, establishing the two-way
@@ -282,20 +310,19 @@ impl Gateway {
     pub(crate) fn attach_proc(&self, proc: &Proc) -> AttachedProcGuard {
         let proc_id = proc.proc_id().clone();
         let weak = proc.downgrade();
-        let existing = self
-            .inner
-            .locals
-            .write()
-            .unwrap()
-            .insert(proc_id.clone(), weak.clone());
-        match existing {
-            // No prior entry, or the prior entry was a dead handle
-            // whose slot is now ours.
-            None => {}
-            Some(weak) if weak.upgrade().is_none() => {}
-            Some(_) => {
-                panic!("gateway already has a proc attached with id {}", proc_id)
+        let attached = self.inner.locals.update(|locals| {
+            match locals.get(&proc_id) {
+                // No prior entry, or the prior entry was a dead handle
+                // whose slot is now ours.
+                None => {}
+                Some(existing) if existing.upgrade().is_none() => {}
+                Some(_) => return false,
             }
+            locals.insert(proc_id.clone(), weak.clone());
+            true
+        });
+        if !attached {
+            panic!("gateway already has a proc attached with id {}", proc_id)
         }
         AttachedProcGuard {
             gateway: Arc::downgrade(&self.inner),
@@ -322,11 +349,13 @@ impl Gateway {
         uid: Uid,
         sender: BoxedMailboxSender,
     ) -> Result<PeerAttachGuard, PeerAttachError> {
-        let mut via = self.inner.peers.write().unwrap();
-        if via.contains_key(&uid) {
-            return Err(PeerAttachError { uid });
-        }
-        via.insert(uid.clone(), sender);
+        self.inner.peers.update(|peers| {
+            if peers.contains_key(&uid) {
+                return Err(PeerAttachError { uid: uid.clone() });
+            }
+            peers.insert(uid.clone(), sender);
+            Ok(())
+        })?;
         Ok(PeerAttachGuard {
             gateway: Arc::downgrade(&self.inner),
             uid,
@@ -379,7 +408,7 @@ impl Gateway {
         // construction time. Subsequent local-any serves should allocate new
         // ports, so multiple local servers can coexist for the same gateway.
         if addr == ChannelAddr::any(ChannelTransport::Local)
-            && self.inner.active_servers.read().unwrap().is_empty()
+            && self.inner.active_servers.load().is_empty()
             && matches!(self.inner.fallback_location.addr(), ChannelAddr::Local(_))
         {
             return self.inner.fallback_location.addr().clone();
@@ -388,21 +417,23 @@ impl Gateway {
     }
 
     fn add_server(&self, location: Location) {
-        let mut active_servers = self.inner.active_servers.write().unwrap();
-        active_servers.push(location.clone());
-        *self.inner.default_location.write().unwrap() = location;
+        self.inner.active_servers.update(|active_servers| {
+            active_servers.push(location.clone());
+            self.inner.default_location.replace(location);
+        });
     }
 
     fn remove_server(&self, location: &Location) {
-        let mut active_servers = self.inner.active_servers.write().unwrap();
-        if let Some(index) = active_servers.iter().rposition(|active| active == location) {
-            active_servers.remove(index);
-        }
-        let default_location = active_servers
-            .last()
-            .cloned()
-            .unwrap_or_else(|| self.inner.fallback_location.clone());
-        *self.inner.default_location.write().unwrap() = default_location;
+        self.inner.active_servers.update(|active_servers| {
+            if let Some(index) = active_servers.iter().rposition(|active| active == location) {
+                active_servers.remove(index);
+            }
+            let default_location = active_servers
+                .last()
+                .cloned()
+                .unwrap_or_else(|| self.inner.fallback_location.clone());
+            self.inner.default_location.replace(default_location);
+        });
     }
 
     /// Flush pending gateway traffic.
@@ -430,8 +461,7 @@ impl Gateway {
         let local_procs: Vec<_> = self
             .inner
             .locals
-            .read()
-            .unwrap()
+            .load()
             .values()
             .filter_map(|weak| weak.upgrade())
             .collect();
@@ -447,8 +477,7 @@ impl Gateway {
         .buffer_unordered(concurrency)
         .collect()
         .await;
-        let forwarder = self.inner.forwarder.read().unwrap().clone();
-        let forwarder_result = forwarder.flush().await;
+        let forwarder_result = self.inner.forwarder.flush().await;
 
         // Best-effort: all flushes have run; surface the first error.
         proc_results
@@ -602,7 +631,7 @@ impl crate::mailbox::MailboxSender for Gateway {
         // its inner proc id happens to match a local proc.
         let dest_location = envelope.dest().location().clone();
         if let Ok((via_uid, inner_location)) = dest_location.pop_via() {
-            if let Some(sender) = self.inner.peers.read().unwrap().get(&via_uid).cloned() {
+            if let Some(sender) = self.inner.peers.load().get(&via_uid).cloned() {
                 // Peer hop: rewrite the destination to the inner
                 // location so the peer routes by it, then forward.
                 let dest_id = envelope.dest().id().clone();
@@ -615,8 +644,7 @@ impl crate::mailbox::MailboxSender for Gateway {
                 // waypoint, not the destination. Forward toward the
                 // default route, which itself returns the message as
                 // undeliverable if it is terminal.
-                let forwarder = self.inner.forwarder.read().unwrap().clone();
-                forwarder.post(envelope, return_handle);
+                self.inner.forwarder.post(envelope, return_handle);
                 return;
             }
             // Our own hop: this gateway is the named leaf. Deliver to
@@ -627,8 +655,7 @@ impl crate::mailbox::MailboxSender for Gateway {
             let local = self
                 .inner
                 .locals
-                .read()
-                .unwrap()
+                .load()
                 .get(dest_proc.id())
                 .and_then(|weak| weak.upgrade());
             match local {
@@ -654,8 +681,7 @@ impl crate::mailbox::MailboxSender for Gateway {
         let local = self
             .inner
             .locals
-            .read()
-            .unwrap()
+            .load()
             .get(dest_proc.id())
             .and_then(|weak| weak.upgrade());
         if let Some(proc) = local
@@ -664,8 +690,7 @@ impl crate::mailbox::MailboxSender for Gateway {
             proc.muxer().post(envelope, return_handle);
             return;
         }
-        let forwarder = self.inner.forwarder.read().unwrap().clone();
-        forwarder.post(envelope, return_handle)
+        self.inner.forwarder.post(envelope, return_handle)
     }
 
     async fn flush(&self) -> Result<(), anyhow::Error> {
@@ -771,10 +796,14 @@ mod tests {
                 .expect("self has no via entry for the peer gateway's uid");
 
             // Advertise each side's destinations through the peer's uid.
-            *self.inner.default_location.write().unwrap() =
-                Location::Via(self.inner.uid.clone(), Box::new(pre_peer_default.clone()));
-            *peer.inner.default_location.write().unwrap() =
-                Location::Via(peer.inner.uid.clone(), Box::new(pre_self_default.clone()));
+            self.inner.default_location.replace(Location::Via(
+                self.inner.uid.clone(),
+                Box::new(pre_peer_default.clone()),
+            ));
+            peer.inner.default_location.replace(Location::Via(
+                peer.inner.uid.clone(),
+                Box::new(pre_self_default.clone()),
+            ));
 
             AttachGuard {
                 self_gateway: Arc::downgrade(&self.inner),
@@ -809,12 +838,12 @@ mod tests {
             if let Some(state) = self.self_gateway.upgrade()
                 && let Some(loc) = self.prev_self_default.take()
             {
-                *state.default_location.write().unwrap() = loc;
+                state.default_location.replace(loc);
             }
             if let Some(state) = self.peer_gateway.upgrade()
                 && let Some(loc) = self.prev_peer_default.take()
             {
-                *state.default_location.write().unwrap() = loc;
+                state.default_location.replace(loc);
             }
             // via guards and serve handles drop themselves.
         }
@@ -1181,7 +1210,7 @@ mod tests {
         );
 
         // Sanity: two procs registered, both live.
-        assert_eq!(gateway.inner.locals.read().unwrap().len(), 2);
+        assert_eq!(gateway.inner.locals.load().len(), 2);
 
         gateway.flush().await.unwrap();
 
@@ -1267,8 +1296,7 @@ mod tests {
             gateway
                 .inner
                 .locals
-                .read()
-                .unwrap()
+                .load()
                 .values()
                 .filter_map(WeakProc::upgrade)
                 .count(),
@@ -1352,7 +1380,7 @@ mod tests {
         drop(first);
 
         // AttachedProcGuard::drop removed the entry from the map.
-        assert_eq!(gateway.inner.locals.read().unwrap().len(), 0);
+        assert_eq!(gateway.inner.locals.load().len(), 0);
 
         // The slot is free, so registering a new proc with the same id
         // is a fresh insert — no panic.
@@ -1361,7 +1389,7 @@ mod tests {
             .shared_gateway(gateway.clone())
             .build()
             .unwrap();
-        assert_eq!(gateway.inner.locals.read().unwrap().len(), 1);
+        assert_eq!(gateway.inner.locals.load().len(), 1);
 
         // Verify the new proc is reachable via the gateway.
         let client = second.client("client");
@@ -1552,7 +1580,7 @@ mod tests {
         drop(attach);
         assert_eq!(gw_a.default_location(), pre_a_default);
         assert_eq!(gw_b.default_location(), pre_b_default);
-        assert!(gw_a.inner.peers.read().unwrap().is_empty());
-        assert!(gw_b.inner.peers.read().unwrap().is_empty());
+        assert!(gw_a.inner.peers.load().is_empty());
+        assert!(gw_b.inner.peers.load().is_empty());
     }
 }
@@ -0,0 +1,271 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Hot-swapping actor implementations.
+//!
+//! [`ActorHandle::hot_swap`] replaces the handler implementation of a
+//! running actor in place: the actor keeps its id, its mailbox, and its
+//! bound ports, so refs held by peers remain valid, and no message is
+//! lost. The swap is performed by the actor loop between two messages,
+//! ahead of any queued messages, which are handled by the replacement.
+//!
+//! An actor opts in by implementing [`HotSwap`]: it delegates its
+//! handlers to an implementation that it holds as a boxed trait object,
+//! [`HotSwap::Implementation`], so that any type implementing the trait
+//! can be swapped in. The running implementation's state is moved into
+//! the replacement by [`HotSwap::migrate`].
+//!
+//! The replacement is not initialized: [`Actor::init`] runs only once,
+//! when the actor is spawned.
+
+use tokio::sync::oneshot;
+
+use crate::Actor;
+use crate::ActorAddr;
+use crate::ActorHandle;
+use crate::proc::SwapRequest;
+
+/// An actor whose handler implementation can be replaced in place.
+pub trait HotSwap: Actor {
+    /// The interface of the actor's implementations, typically a trait
+    /// object such as `dyn CounterImpl`.
+    type Implementation: ?Sized + Send + 'static;
+
+    /// The running implementation.
+    fn implementation(&mut self) -> &mut Box<Self::Implementation>;
+
+    /// Move the state of the running implementation, `previous`, into
+    /// its replacement, `next`. If migration fails, the swap is
+    /// abandoned, and `previous` continues to run.
+    fn migrate(
+        next: &mut Self::Implementation,
+        previous: &mut Self::Implementation,
+    ) -> Result<(), anyhow::Error>;
+}
+
+/// Errors that occur while hot-swapping an actor.
+#[derive(Debug, thiserror::Error)]
+pub enum HotSwapError {
+    /// The replacement failed to migrate the running implementation's
+    /// state.
+    #[error("migration failed: {0}")]
+    Migrate(#[source] anyhow::Error),
+
+    /// The actor is not running, or stopped before the swap was performed.
+    #[error("actor {0} is not running")]
+    NotRunning(ActorAddr),
+}
+
+impl<A: HotSwap> ActorHandle<A> {
+    /// Replace the actor's implementation with `next`. The swap is
+    /// performed by the actor loop once the actor finishes handling its
+    /// current message, ahead of any queued messages.
+    ///
+    /// This must not be awaited from within the actor's own handlers.
+    pub async fn hot_swap(&self, mut next: Box<A::Implementation>) -> Result<(), HotSwapError> {
+        let (tx, rx) = oneshot::channel();
+        let actor_addr = self.actor_addr().clone();
+        let request: SwapRequest<A> = Box::new(move |actor: &mut A| {
+            let running = actor.implementation();
+            let result = A::migrate(&mut next, running).map(|()| {
+                std::mem::swap(running, &mut next);
+                tracing::info!(actor_id = %actor_addr, "hot-swapped actor implementation");
+            });
+            let _ = tx.send(result.map_err(HotSwapError::Migrate));
+        });
+        let not_running = || HotSwapError::NotRunning(self.actor_addr().clone());
+        if !self.ports().request_swap(request) {
+            return Err(not_running());
+        }
+        rx.await.map_err(|_| not_running())?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use serde::Deserialize;
+    use serde::Serialize;
+    use typeuri::Named;
+
+    use super::*;
+    use crate as hyperactor;
+    use crate::Context;
+    use crate::Endpoint;
+    use crate::Handler;
+    use crate::OncePortRef;
+    use crate::Proc;
+    use crate::client::Client;
+
+    /// The implementation of a [`Counter`]'s handlers.
+    trait CounterImpl: std::fmt::Debug + Send + Sync {
+        fn add(&mut self, n: u64);
+
+        fn total(&self) -> u64;
+
+        /// Take over the total counted by a previous implementation.
+        fn restore(&mut self, total: u64) -> anyhow::Result<()>;
+    }
+
+    /// Counts added amounts.
+    #[derive(Debug, Default)]
+    struct Plain {
+        total: u64,
+    }
+
+    impl CounterImpl for Plain {
+        fn add(&mut self, n: u64) {
+            self.total += n;
+        }
+
+        fn total(&self) -> u64 {
+            self.total
+        }
+
+        fn restore(&mut self, total: u64) -> anyhow::Result<()> {
+            self.total = total;
+            Ok(())
+        }
+    }
+
+    /// Counts added amounts, scaled by `scale`.
+    #[derive(Debug)]
+    struct Scaled {
+        total: u64,
+        scale: u64,
+    }
+
+    impl CounterImpl for Scaled {
+        fn add(&mut self, n: u64) {
+            self.total += n * self.scale;
+        }
+
+        fn total(&self) -> u64 {
+            self.total
+        }
+
+        fn restore(&mut self, total: u64) -> anyhow::Result<()> {
+            anyhow::ensure!(self.scale > 0, "invalid scale");
+            self.total = total;
+            Ok(())
+        }
+    }
+
+    fn scaled(scale: u64) -> Box<dyn CounterImpl> {
+        Box::new(Scaled { total: 0, scale })
+    }
+
+    /// Counts added amounts, as its implementation does.
+    #[derive(Debug)]
+    #[hyperactor::export(handlers = [Add, Hold, Get])]
+    struct Counter {
+        counter: Box<dyn CounterImpl>,
+    }
+
+    #[async_trait]
+    impl Actor for Counter {}
+
+    #[derive(Debug, Clone, Serialize, Deserialize, Named)]
+    struct Add(u64);
+    wirevalue::register_type!(Add);
+
+    /// Acknowledge, then keep the actor busy for a while, so that
+    /// messages queue up behind this one.
+    #[derive(Debug, Clone, Serialize, Deserialize, Named)]
+    struct Hold(OncePortRef<()>);
+    wirevalue::register_type!(Hold);
+
+    #[derive(Debug, Clone, Serialize, Deserialize, Named)]
+    struct Get(OncePortRef<u64>);
+    wirevalue::register_type!(Get);
+
+    #[async_trait]
+    impl Handler<Add> for Counter {
+        async fn handle(&mut self, _cx: &Context<Self>, Add(n): Add) -> anyhow::Result<()> {
+            self.counter.add(n);
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Handler<Hold> for Counter {
+        async fn handle(&mut self, cx: &Context<Self>, Hold(ack): Hold) -> anyhow::Result<()> {
+            ack.post(cx, ());
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Handler<Get> for Counter {
+        async fn handle(&mut self, cx: &Context<Self>, Get(reply): Get) -> anyhow::Result<()> {
+            reply.post(cx, self.counter.total());
+            Ok(())
+        }
+    }
+
+    impl HotSwap for Counter {
+        type Implementation = dyn CounterImpl;
+
+        fn implementation(&mut self) -> &mut Box<dyn CounterImpl> {
+            &mut self.counter
+        }
+
+        fn migrate(
+            next: &mut dyn CounterImpl,
+            previous: &mut dyn CounterImpl,
+        ) -> Result<(), anyhow::Error> {
+            next.restore(previous.total())
+        }
+    }
+
+    async fn total(client: &Client, actor: &ActorHandle<Counter>) -> u64 {
+        let (port, receiver) = client.open_once_port::<u64>();
+        actor.bind::<Counter>().post(client, Get(port.bind()));
+        receiver.recv().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_hot_swap() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let actor = proc.spawn(Counter {
+            counter: Box::new(Plain::default()),
+        });
+        let actor_ref = actor.bind::<Counter>();
+
+        actor_ref.post(&client, Add(1));
+        assert_eq!(total(&client, &actor).await, 1);
+
+        // Messages queued behind a busy handler are handled by the
+        // replacement, a different implementation, through the same ref.
+        let (port, receiver) = client.open_once_port::<()>();
+        actor_ref.post(&client, Hold(port.bind()));
+        receiver.recv().await.unwrap();
+        actor_ref.post(&client, Add(2));
+        actor.hot_swap(scaled(10)).await.unwrap();
+        assert_eq!(total(&client, &actor).await, 21);
+
+        // A failed migration leaves the running implementation in place.
+        assert!(matches!(
+            actor.hot_swap(scaled(0)).await,
+            Err(HotSwapError::Migrate(_))
+        ));
+        actor_ref.post(&client, Add(1));
+        assert_eq!(total(&client, &actor).await, 31);
+
+        actor.drain_and_stop("test").unwrap();
+        actor.clone().await;
+        assert!(matches!(
+            actor.hot_swap(scaled(1)).await,
+            Err(HotSwapError::NotRunning(_))
+        ));
+    }
+}
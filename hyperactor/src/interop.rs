@@ -0,0 +1,391 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Interop with external actor systems.
+//!
+//! Services built on other actor systems, such as Ray actors or Erlang
+//! processes, can take part in a mesh while they are migrated, through
+//! an [`InteropGateway`]. The gateway emulates a mailbox in a proc for
+//! each external actor registered with it, so that the external actor
+//! has an [`ActorAddr`] that hyperactor actors can send to, and that
+//! appears as the sender of the messages it sends into the mesh:
+//!
+//! - Messages sent to any port of an emulated actor are handed to the
+//!   gateway's [`ExternalTransport`] as an [`ExternalDelivery`], which
+//!   the transport forwards to the external actor, for example through
+//!   an Erlang port or a Ray actor handle.
+//! - The external system sends messages into the mesh with
+//!   [`InteropGateway::post`], on behalf of a registered actor. Messages
+//!   that cannot be delivered are returned to the transport with
+//!   [`ExternalTransport::undeliverable`].
+//!
+//! Payloads are [`wirevalue::Any`] values: transports translate
+//! between them and the external system's message encoding.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use async_trait::async_trait;
+use hyperactor_config::Flattrs;
+use hyperactor_config::attrs::fnv1a_hash;
+use serde::Deserialize;
+use serde::Serialize;
+use typeuri::Named;
+
+use crate::ActorAddr;
+use crate::PortAddr;
+use crate::RemoteMessage;
+use crate::mailbox::DeliveryFailure;
+use crate::mailbox::Mailbox;
+use crate::mailbox::MailboxSender;
+use crate::mailbox::MessageEnvelope;
+use crate::mailbox::PortHandle;
+use crate::mailbox::TransportFailure;
+use crate::mailbox::TransportFailureReason;
+use crate::mailbox::Undeliverable;
+use crate::mailbox::UndeliverableReason;
+use crate::proc::Proc;
+use crate::ref_::PortRef;
+
+/// The identity of an actor in an external actor system.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Named)]
+pub struct ExternalId {
+    /// The external system, e.g. "ray" or "erlang".
+    pub system: String,
+    /// The actor's identity within the system, e.g. a Ray actor id or
+    /// an Erlang pid.
+    pub id: String,
+}
+wirevalue::register_type!(ExternalId);
+
+impl ExternalId {
+    /// The identity `id` in `system`.
+    pub fn new(system: impl Into<String>, id: impl Into<String>) -> Self {
+        Self {
+            system: system.into(),
+            id: id.into(),
+        }
+    }
+
+    /// The name of the actor that emulates this identity. External ids
+    /// need not be valid actor names, so they are hashed.
+    fn actor_name(&self) -> String {
+        format!("{}-{:016x}", self.system, fnv1a_hash(self.id.as_bytes()))
+    }
+}
+
+impl fmt::Display for ExternalId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.system, self.id)
+    }
+}
+
+/// A message sent from the mesh to an external actor.
+#[derive(Debug, Clone)]
+pub struct ExternalDelivery {
+    /// The external actor.
+    pub to: ExternalId,
+    /// The port of the emulated actor to which the message was sent.
+    pub port: PortAddr,
+    /// The sender of the message.
+    pub sender: ActorAddr,
+    /// The message headers.
+    pub headers: Flattrs,
+    /// The message.
+    pub data: wirevalue::Any,
+}
+
+/// Carries messages between an [`InteropGateway`] and an external
+/// actor system.
+pub trait ExternalTransport: Send + Sync + 'static {
+    /// Forward `delivery` to its external actor. Deliveries that fail
+    /// are returned to their senders as undeliverable.
+    fn deliver(&self, delivery: ExternalDelivery) -> Result<(), anyhow::Error>;
+
+    /// A message posted by the external actor `from` could not be
+    /// delivered. By default, this is logged.
+    fn undeliverable(&self, from: ExternalId, envelope: MessageEnvelope) {
+        tracing::warn!(
+            "message from external actor {} to {} is undeliverable: {:?}",
+            from,
+            envelope.dest(),
+            envelope.delivery_failures(),
+        );
+    }
+}
+
+/// The sender that emulates the mailbox of an external actor.
+struct ExternalSender {
+    id: ExternalId,
+    transport: Arc<dyn ExternalTransport>,
+}
+
+#[async_trait]
+impl MailboxSender for ExternalSender {
+    fn post_unchecked(
+        &self,
+        envelope: MessageEnvelope,
+        return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+    ) {
+        let delivery = ExternalDelivery {
+            to: self.id.clone(),
+            port: envelope.dest().clone(),
+            sender: envelope.sender().clone(),
+            headers: envelope.headers().clone(),
+            data: envelope.data().clone(),
+        };
+        if let Err(err) = self.transport.deliver(delivery) {
+            let failure =
+                DeliveryFailure::new(UndeliverableReason::Transport(TransportFailure::new(
+                    envelope.dest().clone(),
+                    TransportFailureReason::LinkUnavailable(format!(
+                        "external actor {}: {}",
+                        self.id, err
+                    )),
+                )));
+            envelope.undeliverable(failure, return_handle);
+        }
+    }
+}
+
+/// A registered external actor.
+struct Registration {
+    addr: ActorAddr,
+    bound: bool,
+}
+
+/// Maps the actors of an external actor system into a proc, and
+/// bridges messages between them and the mesh. See the [module
+/// documentation](self).
+pub struct InteropGateway {
+    proc: Proc,
+    transport: Arc<dyn ExternalTransport>,
+    /// The gateway's own mailbox, which receives undeliverable messages
+    /// posted by external actors.
+    mailbox: Mailbox,
+    return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+    registrations: RwLock<HashMap<ExternalId, Registration>>,
+    external_ids: Arc<RwLock<HashMap<ActorAddr, ExternalId>>>,
+}
+
+impl InteropGateway {
+    /// Create a gateway in `proc`, named `name`, that carries messages
+    /// to and from external actors over `transport`.
+    pub fn new(
+        proc: &Proc,
+        name: &str,
+        transport: impl ExternalTransport,
+    ) -> Result<Self, anyhow::Error> {
+        let transport: Arc<dyn ExternalTransport> = Arc::new(transport);
+        let mailbox = proc.attach(name)?;
+        let external_ids: Arc<RwLock<HashMap<ActorAddr, ExternalId>>> = Arc::default();
+        let return_handle = mailbox.open_enqueue_port({
+            let transport = transport.clone();
+            let external_ids = external_ids.clone();
+            move |_, undeliverable: Undeliverable<MessageEnvelope>| {
+                let envelope = match undeliverable {
+                    Undeliverable::Returned(envelope) => envelope,
+                    Undeliverable::Report(report) => {
+                        tracing::warn!(
+                            "message from external actor is undeliverable: {:?}",
+                            report
+                        );
+                        return Ok(());
+                    }
+                };
+                let from = external_ids.read().unwrap().get(envelope.sender()).cloned();
+                match from {
+                    Some(from) => transport.undeliverable(from, envelope),
+                    None => tracing::warn!(
+                        "message from unregistered external actor {} is undeliverable",
+                        envelope.sender()
+                    ),
+                }
+                Ok(())
+            }
+        });
+        Ok(Self {
+            proc: proc.clone(),
+            transport,
+            mailbox,
+            return_handle,
+            registrations: RwLock::default(),
+            external_ids,
+        })
+    }
+
+    /// Register the external actor `id`, returning the address at which
+    /// the mesh reaches it. Registering an actor again returns the same
+    /// address.
+    pub fn register(&self, id: ExternalId) -> Result<ActorAddr, anyhow::Error> {
+        let mut registrations = self.registrations.write().unwrap();
+        let sender = ExternalSender {
+            id: id.clone(),
+            transport: self.transport.clone(),
+        };
+        if let Some(registration) = registrations.get_mut(&id) {
+            if !registration.bound {
+                self.proc
+                    .muxer()
+                    .bind(registration.addr.id().clone(), sender);
+                registration.bound = true;
+            }
+            return Ok(registration.addr.clone());
+        }
+        let addr = self.proc.bind_sender(&id.actor_name(), sender)?;
+        self.external_ids
+            .write()
+            .unwrap()
+            .insert(addr.clone(), id.clone());
+        registrations.insert(
+            id,
+            Registration {
+                addr: addr.clone(),
+                bound: true,
+            },
+        );
+        Ok(addr)
+    }
+
+    /// Unregister the external actor `id`. Messages sent to it are
+    /// then undeliverable, until it is registered again.
+    pub fn unregister(&self, id: &ExternalId) {
+        if let Some(registration) = self.registrations.write().unwrap().get_mut(id)
+            && registration.bound
+        {
+            self.proc.muxer().unbind(registration.addr.id());
+            registration.bound = false;
+        }
+    }
+
+    /// The address of the registered external actor `id`.
+    pub fn actor_addr(&self, id: &ExternalId) -> Option<ActorAddr> {
+        self.registrations
+            .read()
+            .unwrap()
+            .get(id)
+            .filter(|registration| registration.bound)
+            .map(|registration| registration.addr.clone())
+    }
+
+    /// The external actor emulated at `addr`, if any.
+    pub fn external_id(&self, addr: &ActorAddr) -> Option<ExternalId> {
+        self.external_ids.read().unwrap().get(addr).cloned()
+    }
+
+    /// Post a serialized message to `dest`, on behalf of the registered
+    /// external actor `from`.
+    pub fn post_serialized(
+        &self,
+        from: &ExternalId,
+        dest: PortAddr,
+        data: wirevalue::Any,
+        mut headers: Flattrs,
+    ) -> Result<(), anyhow::Error> {
+        let sender = self
+            .actor_addr(from)
+            .ok_or_else(|| anyhow::anyhow!("external actor {} is not registered", from))?;
+        crate::mailbox::headers::set_send_timestamp(&mut headers);
+        let envelope = MessageEnvelope::new(sender, dest, data, headers);
+        self.proc.post(envelope, self.return_handle.clone());
+        Ok(())
+    }
+
+    /// Post `message` to `port`, on behalf of the registered external
+    /// actor `from`.
+    pub fn post<M: RemoteMessage>(
+        &self,
+        from: &ExternalId,
+        port: &PortRef<M>,
+        message: &M,
+    ) -> Result<(), anyhow::Error> {
+        let mut headers = Flattrs::new();
+        crate::mailbox::headers::set_rust_message_type::<M>(&mut headers);
+        self.post_serialized(
+            from,
+            port.port_addr().clone(),
+            wirevalue::Any::serialize(message)?,
+            headers,
+        )
+    }
+}
+
+impl fmt::Debug for InteropGateway {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InteropGateway")
+            .field("actor_addr", self.mailbox.actor_addr())
+            .field("registered", &self.registrations.read().unwrap().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::Endpoint;
+    use crate::mailbox::PortReceiver;
+    use crate::port::Port;
+
+    /// A transport that forwards deliveries to a channel, standing in
+    /// for an external system.
+    struct ChannelTransport(mpsc::UnboundedSender<ExternalDelivery>);
+
+    impl ExternalTransport for ChannelTransport {
+        fn deliver(&self, delivery: ExternalDelivery) -> Result<(), anyhow::Error> {
+            Ok(self.0.send(delivery)?)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_interop_gateway() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let gateway = InteropGateway::new(&proc, "interop", ChannelTransport(tx)).unwrap();
+
+        let pid = ExternalId::new("erlang", "<0.123.0>");
+        let addr = gateway.register(pid.clone()).unwrap();
+        assert_eq!(gateway.register(pid.clone()).unwrap(), addr);
+        assert_eq!(gateway.external_id(&addr), Some(pid.clone()));
+
+        // The mesh sends to the external actor...
+        let external_port = PortRef::<u64>::attest(addr.port_addr(Port::handler::<u64>()));
+        Endpoint::post(&external_port, &client, 42u64);
+        let delivery = rx.recv().await.unwrap();
+        assert_eq!(delivery.to, pid);
+        assert_eq!(delivery.port, *external_port.port_addr());
+        assert_eq!(delivery.data.deserialized::<u64>().unwrap(), 42);
+
+        // ...which replies, as its emulated actor.
+        let (handle, mut receiver): (_, PortReceiver<String>) = client.mailbox().open_port();
+        let reply_port = handle.bind();
+        gateway
+            .post(&pid, &reply_port, &"hello".to_string())
+            .unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), "hello");
+
+        // Unregistered actors can neither send nor be sent to.
+        gateway.unregister(&pid);
+        assert!(gateway.actor_addr(&pid).is_none());
+        assert!(
+            gateway
+                .post(&pid, &reply_port, &"again".to_string())
+                .is_err()
+        );
+        assert_eq!(gateway.register(pid.clone()).unwrap(), addr);
+        Endpoint::post(&external_port, &client, 43u64);
+        assert_eq!(
+            rx.recv().await.unwrap().data.deserialized::<u64>().unwrap(),
+            43
+        );
+    }
+}
@@ -182,10 +182,12 @@
 //!   so a bad snapshot can never empty or invalidate `attrs` (with IA-1,
 //!   IA-5).
 
+use std::collections::BTreeMap;
 use std::fmt;
 use std::str::FromStr;
 use std::time::SystemTime;
 
+use hyperactor_config::AttrValue;
 use hyperactor_config::Attrs;
 use hyperactor_config::INTROSPECT;
 use hyperactor_config::IntrospectAttr;
@@ -446,6 +448,24 @@ declare_attrs! {
     })
     pub attr ACTOR_QUEUE_DEPTH: u64 = 0;
 
+    /// Approximate serialized bytes of the messages in the actor's
+    /// work queue (see `InstanceCell::queued_bytes`).
+    @meta(INTROSPECT = IntrospectAttr {
+        name: "queued_bytes".into(),
+        desc: "Approximate serialized bytes of the messages in the actor's work queue".into(),
+    })
+    pub attr ACTOR_QUEUED_BYTES: u64 = 0;
+
+    /// Approximate serialized bytes queued to each of the actor's
+    /// handler ports, keyed by message type (see
+    /// `InstanceCell::queued_bytes_by_port`). Absent when nothing is
+    /// queued.
+    @meta(INTROSPECT = IntrospectAttr {
+        name: "queued_bytes_by_port".into(),
+        desc: "Approximate serialized bytes queued to each handler port, by message type".into(),
+    })
+    pub attr ACTOR_QUEUED_BYTES_BY_PORT: QueuedBytesByPort;
+
     /// Per-session reorder state from the sequenced receiver snapshot.
     /// `sessions[*].buffered_count` reports messages held by
     /// receiver-local sequencing waiting for a seq gap to fill. Independent
@@ -521,6 +541,33 @@ pub struct FailureAttrs {
     pub is_propagated: bool,
 }
 
+/// Approximate serialized bytes queued to each handler port of an
+/// actor, keyed by message type. Encoded as a JSON object.
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    Named,
+    AttrValue
+)]
+pub struct QueuedBytesByPort(pub BTreeMap<String, u64>);
+
+impl fmt::Display for QueuedBytesByPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(&self.0).unwrap())
+    }
+}
+
+impl FromStr for QueuedBytesByPort {
+    type Err = serde_json::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s).map(Self)
+    }
+}
+
 /// Typed view over attrs for an actor node.
 #[derive(Debug, Clone, PartialEq)]
 pub struct ActorAttrsView {
@@ -545,6 +592,12 @@ pub struct ActorAttrsView {
     /// no arithmetic contract between the two -- see IO-3. Defaults
     /// to 0 when the attr is absent.
     pub queue_depth: u64,
+    /// Approximate serialized bytes of the messages in the actor's
+    /// work queue. Defaults to 0 when the attr is absent.
+    pub queued_bytes: u64,
+    /// Approximate serialized bytes queued to each handler port, by
+    /// message type. Empty when the attr is absent.
+    pub queued_bytes_by_port: BTreeMap<String, u64>,
     /// Flight recorder JSON, if available.
     pub flight_recorder: Option<String>,
     /// Whether this is a system/infrastructure actor.
@@ -583,6 +636,11 @@ impl ActorAttrsView {
         let last_handler = attrs.get(LAST_HANDLER).cloned();
         let total_processing_time_us = *attrs.get(TOTAL_PROCESSING_TIME_US).unwrap_or(&0);
         let queue_depth = *attrs.get(ACTOR_QUEUE_DEPTH).unwrap_or(&0);
+        let queued_bytes = *attrs.get(ACTOR_QUEUED_BYTES).unwrap_or(&0);
+        let queued_bytes_by_port = attrs
+            .get(ACTOR_QUEUED_BYTES_BY_PORT)
+            .map(|ports| ports.0.clone())
+            .unwrap_or_default();
         let flight_recorder = attrs.get(FLIGHT_RECORDER).cloned();
         let is_system = *attrs.get(IS_SYSTEM).unwrap_or(&false);
         let inbound_ordering = attrs.get(INBOUND_ORDERING).cloned();
@@ -660,6 +718,8 @@ impl ActorAttrsView {
             last_handler,
             total_processing_time_us,
             queue_depth,
+            queued_bytes,
+            queued_bytes_by_port,
             flight_recorder,
             is_system,
             inbound_ordering,
@@ -685,6 +745,13 @@ impl ActorAttrsView {
         }
         attrs.set(TOTAL_PROCESSING_TIME_US, self.total_processing_time_us);
         attrs.set(ACTOR_QUEUE_DEPTH, self.queue_depth);
+        attrs.set(ACTOR_QUEUED_BYTES, self.queued_bytes);
+        if !self.queued_bytes_by_port.is_empty() {
+            attrs.set(
+                ACTOR_QUEUED_BYTES_BY_PORT,
+                QueuedBytesByPort(self.queued_bytes_by_port.clone()),
+            );
+        }
         if let Some(fr) = &self.flight_recorder {
             attrs.set(FLIGHT_RECORDER, fr.clone());
         }
@@ -862,6 +929,19 @@ fn build_actor_attrs(cell: &crate::InstanceCell, snap: &ActorSnapshot) -> String
     attrs.set(IS_SYSTEM, snap.is_system);
     attrs.set(INSTANCE_ID, cell.instance_id().to_string());
     attrs.set(ACTOR_QUEUE_DEPTH, cell.queue_depth());
+    attrs.set(ACTOR_QUEUED_BYTES, cell.queued_bytes());
+    let by_port = cell.queued_bytes_by_port();
+    if !by_port.is_empty() {
+        attrs.set(
+            ACTOR_QUEUED_BYTES_BY_PORT,
+            QueuedBytesByPort(
+                by_port
+                    .into_iter()
+                    .map(|(port, bytes)| (port.to_string(), bytes))
+                    .collect(),
+            ),
+        );
+    }
 
     if let Some(snapshot) = cell.inbound_ordering_snapshot() {
         // TODO: truncation / filtering of long session lists belongs at
@@ -1142,6 +1222,8 @@ mod tests {
             ("failure_is_propagated", FAILURE_IS_PROPAGATED.attrs()),
             ("instance_id", INSTANCE_ID.attrs()),
             ("queue_depth", ACTOR_QUEUE_DEPTH.attrs()),
+            ("queued_bytes", ACTOR_QUEUED_BYTES.attrs()),
+            ("queued_bytes_by_port", ACTOR_QUEUED_BYTES_BY_PORT.attrs()),
             ("inbound_ordering", INBOUND_ORDERING.attrs()),
         ];
 
@@ -64,15 +64,23 @@ pub mod accum;
 pub mod actor;
 pub mod actor_local;
 pub mod addr;
+pub mod blob_cache;
+pub mod call;
+pub mod cancel;
 pub mod channel;
+pub mod checkpoint;
 pub mod client;
 pub mod config;
 pub mod context;
+pub mod debugger;
 pub mod endpoint;
+pub mod event_sourcing;
 /// Gateway management for proc connectivity.
 pub mod gateway;
+pub mod hot_swap;
 pub mod id;
 mod init;
+pub mod interop;
 pub mod introspect;
 pub mod mailbox;
 pub mod message;
@@ -84,9 +92,12 @@ pub mod port;
 pub mod proc;
 pub mod ref_;
 pub mod remote;
+pub mod replay;
+pub mod saga;
 pub(crate) mod sequenced;
 mod signal_handler;
 mod stdio_redirect;
+pub mod stream;
 pub mod subject;
 pub mod supervision;
 pub mod sync;
@@ -117,6 +128,7 @@ pub use actor::ActorGuard;
 pub use actor::ActorHandle;
 pub use actor::AnyActorGuard;
 pub use actor::AnyActorHandle;
+pub use actor::BatchHandler;
 pub use actor::Handler;
 pub use actor::HandlerInfo;
 pub use actor::RemoteHandles;
@@ -190,6 +202,7 @@ pub use proc::Instance;
 pub use proc::InstanceCell;
 pub use proc::Proc;
 pub use proc::WeakProc;
+pub use proc::parallel::ParallelHandler;
 pub use ref_::ActorRef;
 pub use ref_::OncePortRef;
 pub use ref_::PortRef;
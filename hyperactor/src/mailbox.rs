@@ -103,9 +103,11 @@
 use std::any::Any;
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::fmt;
 use std::fmt::Debug;
-use std::future;
 use std::future::Future;
 use std::ops::Bound::Excluded;
 use std::pin::Pin;
@@ -119,13 +121,18 @@ use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::task::Context;
 use std::task::Poll;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use dashmap::DashMap;
 use dashmap::mapref::entry::Entry;
 use enum_as_inner::EnumAsInner;
+use futures::FutureExt;
 use futures::Sink;
 use futures::Stream;
+use futures::StreamExt;
+use futures::future::BoxFuture;
+use futures::stream::FuturesOrdered;
 use hyperactor_config::Flattrs;
 use hyperactor_telemetry::hash_to_u64;
 use serde::Deserialize;
@@ -142,12 +149,15 @@ use crate::ActorAddr;
 use crate::Addr;
 use crate::Endpoint;
 use crate::EndpointLocation;
+use crate::Location;
 // for macros
 use crate::OncePortRef;
 use crate::PortAddr;
 use crate::PortRef;
 use crate::ProcAddr;
+use crate::Uid;
 use crate::accum::Accumulator;
+use crate::accum::EmitPolicy;
 use crate::accum::ReducerSpec;
 use crate::accum::StreamingReducerOpts;
 use crate::actor::ActorStatus;
@@ -181,9 +191,60 @@ pub use undeliverable::monitored_return_handle; // TODO: Audit
 pub mod mailbox_admin_message;
 pub use mailbox_admin_message::MailboxAdminMessage;
 pub use mailbox_admin_message::MailboxAdminMessageHandler;
+pub use mailbox_admin_message::PortDump;
 /// For message headers and latency tracking.
 pub mod headers;
 
+pub mod header_stats;
+
+pub mod migrate;
+use migrate::MessageVersion;
+
+pub mod transaction;
+
+mod port_table;
+use port_table::PortTable;
+
+pub(crate) mod snapshot;
+use snapshot::Snapshot;
+
+pub mod locality;
+
+pub mod routing;
+use routing::ChosenRoute;
+use routing::RouterKind;
+use routing::RoutingPolicy;
+
+pub mod federation;
+
+pub mod content_router;
+
+pub mod error_code;
+use error_code::ErrorCode;
+use error_code::HasErrorCode as _;
+
+pub mod reliable;
+
+pub mod sampling;
+
+pub mod spill;
+
+pub mod compress;
+
+pub mod shaping;
+
+pub mod deadline;
+
+pub mod mirror;
+
+pub mod topology;
+
+pub mod port_events;
+use port_events::PortEvent;
+use port_events::PortObserver;
+use port_events::PortObserverHandle;
+use port_events::PortObservers;
+
 /// Message collects the necessary requirements for messages that are deposited
 /// into mailboxes.
 pub trait Message: Send + Sync + 'static {}
@@ -337,6 +398,15 @@ pub enum InvalidReferenceReason {
     /// The envelope was delivered to the wrong mailbox owner.
     #[error("wrong mailbox owner")]
     WrongMailboxOwner,
+
+    /// The port was closed by an administrator.
+    #[error("port closed")]
+    PortClosed,
+
+    /// The port is forwarded, so delivery through it cannot be
+    /// confirmed.
+    #[error("port forwarded")]
+    PortForwarded,
 }
 
 /// A delivery failure caused by message expiration.
@@ -450,6 +520,10 @@ pub enum TransportFailureReason {
     /// The forwarder is unavailable.
     #[error("forwarder unavailable")]
     ForwarderUnavailable,
+
+    /// The sender or destination exceeded a resource quota.
+    #[error("quota exceeded: {0}")]
+    QuotaExceeded(String),
 }
 
 /// A port whose ordinary recipient is gone.
@@ -511,10 +585,11 @@ impl MessageEnvelope {
         sender: impl Into<ActorAddr>,
         dest: impl Into<PortAddr>,
         data: wirevalue::Any,
-        headers: Flattrs,
+        mut headers: Flattrs,
     ) -> Self {
         let sender = sender.into();
         let dest = dest.into();
+        migrate::set_version(&mut headers, migrate::local_version(data.typehash()));
         Self {
             sender,
             dest,
@@ -542,8 +617,9 @@ impl MessageEnvelope {
         source: impl Into<ActorAddr>,
         dest: impl Into<PortAddr>,
         value: &T,
-        headers: Flattrs,
+        mut headers: Flattrs,
     ) -> Result<Self, wirevalue::Error> {
+        migrate::set_version(&mut headers, migrate::local_version(T::typehash()));
         Ok(Self {
             headers,
             data: wirevalue::Any::serialize(value)?,
@@ -622,6 +698,26 @@ impl MessageEnvelope {
         &self.headers
     }
 
+    /// The message headers, mutably.
+    pub fn headers_mut(&mut self) -> &mut Flattrs {
+        &mut self.headers
+    }
+
+    /// The wire version of the payload type in the sending binary,
+    /// carried in the [`MESSAGE_VERSION`](headers::MESSAGE_VERSION)
+    /// header.
+    pub fn version(&self) -> MessageVersion {
+        migrate::version_of(&self.headers)
+    }
+
+    /// Return this envelope with its payload version replaced by `version`.
+    /// This is used when relaying a payload that was encoded by a binary
+    /// at a different version of its type.
+    pub fn with_version(mut self, version: MessageVersion) -> Self {
+        migrate::set_version(&mut self.headers, version);
+        self
+    }
+
     /// Tells whether this is a signal message.
     pub fn is_signal(&self) -> bool {
         self.dest
@@ -630,6 +726,12 @@ impl MessageEnvelope {
 
     /// Push a structured delivery failure onto this message's failure history.
     pub fn push_delivery_failure(&mut self, failure: DeliveryFailure) {
+        if self.delivery_failures.is_empty() {
+            self.headers.set(
+                crate::mailbox::headers::ERROR_CODE,
+                failure.error_code().as_u16(),
+            );
+        }
         self.delivery_failures.push(failure)
     }
 
@@ -677,6 +779,7 @@ impl MessageEnvelope {
                 "dest_actor_id" => self.dest.to_string(),
                 "message_type" => self.data.typename().unwrap_or("unknown"),
                 "error_type" => error,
+                "error_code" => failure.error_code().as_u16() as i64,
             ),
         );
 
@@ -695,6 +798,12 @@ impl MessageEnvelope {
         self.delivery_failures.first()
     }
 
+    /// The error code of the root delivery failure, if any.
+    pub fn error_code(&self) -> Option<ErrorCode> {
+        self.root_delivery_failure()
+            .map(|failure| failure.error_code())
+    }
+
     /// Get the root structured delivery failure mutably.
     pub fn root_delivery_failure_mut(&mut self) -> Option<&mut DeliveryFailure> {
         self.delivery_failures.first_mut()
@@ -789,6 +898,39 @@ impl fmt::Display for MessageEnvelope {
     }
 }
 
+/// A batch of messages for the same port, carried in a single envelope;
+/// see [`PortRef::send_batch`]. On delivery, the batch is unpacked, and
+/// its messages are delivered to the port in order, each with the
+/// envelope's headers: the `i`th message as if it had been sent with
+/// the envelope's sequence number plus `i`.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct MessageBatch {
+    /// The wire version of the messages' type in the sending binary.
+    version: MessageVersion,
+    messages: Vec<wirevalue::Any>,
+}
+wirevalue::register_type!(MessageBatch);
+
+impl MessageBatch {
+    /// A batch of `messages`, serialized from M-typed values.
+    pub(crate) fn new<M: Named>(messages: Vec<wirevalue::Any>) -> Self {
+        Self {
+            version: migrate::local_version(M::typehash()),
+            messages,
+        }
+    }
+
+    /// The number of messages in the batch.
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Whether the batch is empty.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
 /// Metadata about a message sent via a MessageEnvelope.
 #[derive(Clone)]
 pub struct MessageMetadata {
@@ -860,6 +1002,10 @@ pub enum MailboxErrorKind {
     /// The owning actor terminated (either stopped or failed).
     #[error("owner terminated: {0}")]
     OwnerTerminated(ActorStatus),
+
+    /// A one-shot port expired before receiving its message.
+    #[error("{0}: timed out after {1:?}")]
+    Timeout(PortAddr, Duration),
 }
 
 impl MailboxError {
@@ -954,6 +1100,18 @@ pub enum MailboxSenderErrorKind {
     #[error("deserialization error for type {0}: {1}")]
     Deserialize(&'static str, anyhow::Error),
 
+    /// The sender's layout for the message type differs from the
+    /// receiver's; the payload was rejected before decoding.
+    #[error(
+        "schema mismatch for type {0}: sender fingerprint {1:016x}, receiver fingerprint {2:016x}"
+    )]
+    SchemaMismatch(&'static str, u64, u64),
+
+    /// The payload was produced at a different version of the message
+    /// type, and could not be migrated to the local version.
+    #[error(transparent)]
+    Migrate(#[from] migrate::MigrateError),
+
     /// A send to an invalid port.
     #[error("invalid port")]
     Invalid,
@@ -962,6 +1120,11 @@ pub enum MailboxSenderErrorKind {
     #[error("port closed")]
     Closed,
 
+    /// A send that must be confirmed, to a port that only relays its
+    /// messages; see [`Mailbox::forward_port`].
+    #[error("port forwarded")]
+    Forwarded,
+
     // The following pass through underlying errors:
     /// An underlying mailbox error.
     #[error(transparent)]
@@ -1069,6 +1232,31 @@ pub trait MailboxSender: Send + Sync + Any {
     async fn flush(&self) -> Result<(), anyhow::Error> {
         Ok(())
     }
+
+    /// The number of messages accepted by this sender for `dest` that
+    /// have not yet been consumed downstream. A [`MailboxServer`] holds
+    /// back messages to destinations whose backlog is at or above
+    /// [`MAILBOX_SERVER_WINDOW`](crate::config::MAILBOX_SERVER_WINDOW).
+    /// The default implementation reports no backlog.
+    fn backlog(&self, _dest: &PortAddr) -> u64 {
+        0
+    }
+
+    /// A notification raised when the backlog of a destination falls
+    /// below [`MAILBOX_SERVER_WINDOW`](crate::config::MAILBOX_SERVER_WINDOW),
+    /// if this sender reports one. Mailbox servers wait on it to
+    /// release the messages they hold back.
+    fn backlog_drained(&self) -> Option<Arc<tokio::sync::Notify>> {
+        None
+    }
+
+    /// Route messages for `dest` to `addr`, replacing any existing
+    /// route, if this sender routes by address (see
+    /// [`MailboxAdminMessage::UpdateAddress`]). Returns whether the
+    /// route was updated. The default implementation has no routes.
+    fn update_address(&self, _dest: &Addr, _addr: &ChannelAddr) -> bool {
+        false
+    }
 }
 
 /// PortSender extends [`MailboxSender`] by providing typed endpoints
@@ -1249,6 +1437,18 @@ impl MailboxSender for BoxedMailboxSender {
     async fn flush(&self) -> Result<(), anyhow::Error> {
         self.0.flush().await
     }
+
+    fn backlog(&self, dest: &PortAddr) -> u64 {
+        self.0.backlog(dest)
+    }
+
+    fn backlog_drained(&self) -> Option<Arc<tokio::sync::Notify>> {
+        self.0.backlog_drained()
+    }
+
+    fn update_address(&self, dest: &Addr, addr: &ChannelAddr) -> bool {
+        self.0.update_address(dest, addr)
+    }
 }
 
 /// Errors that occur during mailbox serving.
@@ -1310,6 +1510,18 @@ impl Future for MailboxServerHandle {
 
 /// Serve a port on the provided [`channel::Rx`]. This dispatches all
 /// channel messages directly to the port.
+///
+/// Servers participate in credit-based flow control with their
+/// [`MailboxClient`]s: a client transmits at most
+/// [`MAILBOX_CLIENT_WINDOW`](crate::config::MAILBOX_CLIENT_WINDOW)
+/// unacknowledged messages, and the server grants credits by
+/// acknowledging messages as it receives them. Messages to a
+/// destination whose downstream [backlog](MailboxSender::backlog)
+/// reaches [`MAILBOX_SERVER_WINDOW`](crate::config::MAILBOX_SERVER_WINDOW)
+/// are held back until it [drains](MailboxSender::backlog_drained),
+/// while messages to other destinations flow. Once the window is full
+/// of held messages, the server stops receiving, and thus withholds
+/// credits.
 pub trait MailboxServer: MailboxSender + Clone + Sized + 'static {
     /// Serve the provided port on the given channel on this sender on
     /// a background task which may be joined with the returned handle.
@@ -1381,20 +1593,82 @@ pub trait MailboxServer: MailboxSender + Clone + Sized + 'static {
             }
         });
 
+        let window = hyperactor_config::global::get(crate::config::MAILBOX_SERVER_WINDOW) as u64;
+        let drained = self.backlog_drained();
         let (stopped_tx, mut stopped_rx) = watch::channel(false);
         let join_handle = tokio::spawn(async move {
             let mut detached = false;
+            let mut exhausted = false;
+            let mut delivery = ServerDelivery::new(self.clone(), return_handle);
+            // Messages held back, by destination, and their total count.
+            let mut held: HashMap<PortAddr, VecDeque<MessageEnvelope>> = HashMap::new();
+            let mut num_held = 0u64;
 
             let result = loop {
                 if *stopped_rx.borrow_and_update() {
                     break Ok(());
                 }
 
+                // Register for the drain notification before checking
+                // backlogs, so that a drain in between is not missed.
+                let notified = drained.as_deref().map(tokio::sync::Notify::notified);
+                tokio::pin!(notified);
+                if let Some(notified) = notified.as_mut().as_pin_mut() {
+                    notified.enable();
+                }
+
+                // Release held messages, in order, to the destinations
+                // whose backlog has drained below the window.
+                if num_held > 0 {
+                    held.retain(|dest, queue| {
+                        while self.backlog(dest) < window
+                            && let Some(envelope) = queue.pop_front()
+                        {
+                            num_held -= 1;
+                            delivery.deliver(envelope);
+                        }
+                        !queue.is_empty()
+                    });
+                }
+
+                // Once the window is full of held messages, stop
+                // receiving: unreceived messages are not acknowledged,
+                // which holds back the clients.
+                if window > 0 && num_held >= window {
+                    if !exhausted {
+                        tracing::debug!(
+                            num_held,
+                            window,
+                            "mailbox server window exhausted for Rx {}",
+                            rx.addr()
+                        );
+                        metrics::MAILBOX_SERVER_WINDOW_EXHAUSTED.add(
+                            1,
+                            hyperactor_telemetry::kv_pairs!("addr" => rx.addr().to_string()),
+                        );
+                    }
+                    exhausted = true;
+                } else {
+                    exhausted = false;
+                }
+
                 tokio::select! {
-                    message = rx.recv() => {
+                    message = rx.recv(), if !exhausted => {
                         match message {
-                            // Relay the message to the port directly.
-                            Ok(envelope) => self.post(envelope, return_handle.clone()),
+                            // Hold back messages to a backlogged destination,
+                            // behind any already held for it, so that it does
+                            // not hold up the others.
+                            Ok(envelope)
+                                if window > 0
+                                    && (held.contains_key(envelope.dest())
+                                        || self.backlog(envelope.dest()) >= window) =>
+                            {
+                                held.entry(envelope.dest().clone())
+                                    .or_default()
+                                    .push_back(envelope);
+                                num_held += 1;
+                            }
+                            Ok(envelope) => delivery.deliver(envelope),
 
                             // Closed is a "graceful" error in this case.
                             // We simply stop serving.
@@ -1402,6 +1676,15 @@ pub trait MailboxServer: MailboxSender + Clone + Sized + 'static {
                             Err(channel_err) => break Err(MailboxServerError::from(channel_err)),
                         }
                     }
+                    _ = async {
+                        match notified.as_mut().as_pin_mut() {
+                            Some(notified) => notified.await,
+                            None => std::future::pending().await,
+                        }
+                    }, if num_held > 0 => {}
+                    Some(restored) = delivery.restoring.next(), if !delivery.restoring.is_empty() => {
+                        delivery.restored(restored);
+                    }
                     result = stopped_rx.changed(), if !detached  => {
                         detached = result.is_err();
                         if detached {
@@ -1417,6 +1700,17 @@ pub trait MailboxServer: MailboxSender + Clone + Sized + 'static {
                 }
             };
 
+            // Held messages have been acknowledged: deliver them
+            // rather than drop them.
+            for (_, queue) in held {
+                for envelope in queue {
+                    delivery.deliver(envelope);
+                }
+            }
+            while let Some(restored) = delivery.restoring.next().await {
+                delivery.restored(restored);
+            }
+
             // Join the channel receiver to ensure pending acks are
             // sent before the underlying channel server is torn down.
             rx.join().await;
@@ -1433,6 +1727,73 @@ pub trait MailboxServer: MailboxSender + Clone + Sized + 'static {
 
 impl<T: MailboxSender + Clone + Sized + Sync + Send + 'static> MailboxServer for T {}
 
+/// The result of restoring an envelope's payload in a [`ServerDelivery`].
+type Restored = Result<MessageEnvelope, (MessageEnvelope, String)>;
+
+/// Delivers the envelopes received by a [`MailboxServer`] to its
+/// sender, in order. Spilled payloads are fetched without holding up
+/// the server's receive loop; envelopes received while any fetch is
+/// outstanding are queued behind it.
+struct ServerDelivery<S> {
+    sender: S,
+    return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+    restoring: FuturesOrdered<BoxFuture<'static, Restored>>,
+}
+
+impl<S: MailboxSender> ServerDelivery<S> {
+    fn new(sender: S, return_handle: PortHandle<Undeliverable<MessageEnvelope>>) -> Self {
+        Self {
+            sender,
+            return_handle,
+            restoring: FuturesOrdered::new(),
+        }
+    }
+
+    fn deliver(&mut self, mut envelope: MessageEnvelope) {
+        if spill::is_spilled(&envelope) {
+            self.restoring.push_back(
+                async move {
+                    match spill::restore(&mut envelope).await {
+                        Ok(()) => Ok(envelope),
+                        Err(err) => Err((
+                            envelope,
+                            format!("failed to fetch spilled payload: {}", err),
+                        )),
+                    }
+                }
+                .boxed(),
+            );
+            return;
+        }
+        let decompressed = match compress::decompress(&mut envelope) {
+            Ok(()) => Ok(envelope),
+            Err(err) => Err((envelope, format!("failed to decompress payload: {}", err))),
+        };
+        if self.restoring.is_empty() {
+            self.restored(decompressed);
+        } else {
+            self.restoring
+                .push_back(futures::future::ready(decompressed).boxed());
+        }
+    }
+
+    /// Relay a restored envelope to the port, or return it if it could
+    /// not be restored.
+    fn restored(&self, restored: Restored) {
+        match restored {
+            Ok(envelope) => self.sender.post(envelope, self.return_handle.clone()),
+            Err((envelope, reason)) => {
+                let failure =
+                    DeliveryFailure::new(UndeliverableReason::Transport(TransportFailure::new(
+                        envelope.dest().clone(),
+                        TransportFailureReason::LinkUnavailable(reason),
+                    )));
+                envelope.undeliverable(failure, self.return_handle.clone());
+            }
+        }
+    }
+}
+
 struct Buffer<T: Message> {
     queue: mpsc::UnboundedSender<(T, PortHandle<Undeliverable<T>>)>,
     #[allow(dead_code)]
@@ -1496,6 +1857,13 @@ pub struct MailboxClient {
     // Watcher exposing the underlying Tx's health. Callers can peek to detect
     // a closed client before submitting, e.g. for routing-cache eviction.
     tx_status: watch::Receiver<TxStatus>,
+
+    // The flow-control window: the maximum number of unacknowledged
+    // messages in flight, or 0 if unlimited.
+    window: usize,
+
+    // The class of traffic this client carries.
+    class: shaping::ChannelClass,
 }
 
 impl fmt::Debug for MailboxClient {
@@ -1510,66 +1878,140 @@ impl MailboxClient {
     /// Create a new client that sends messages destined for a
     /// [`MailboxServer`] on the provided Tx channel.
     pub fn new(tx: impl channel::Tx<MessageEnvelope> + Send + Sync + 'static) -> Self {
+        Self::new_with_class(tx, shaping::ChannelClass::default())
+    }
+
+    /// Like [`MailboxClient::new`], but shape the client's traffic to
+    /// the ceilings of `class`; see [`shaping`].
+    pub fn new_with_class(
+        tx: impl channel::Tx<MessageEnvelope> + Send + Sync + 'static,
+        class: shaping::ChannelClass,
+    ) -> Self {
         let addr = tx.addr();
         let tx = Arc::new(tx);
         let tx_status = tx.status().clone();
         let tx_monitoring = CancellationToken::new();
         let completed = Arc::new(AtomicUsize::new(0));
         let completed_notify = Arc::new(tokio::sync::Notify::new());
+        let window = hyperactor_config::global::get(crate::config::MAILBOX_CLIENT_WINDOW);
         let buffer = {
             let completed = completed.clone();
             let completed_notify = completed_notify.clone();
             let addr = addr.clone();
-            Buffer::new(move |envelope, return_handle| {
+            // Counts messages handed to the tx; together with `completed`,
+            // this tracks the messages in flight.
+            let transmitted = Arc::new(AtomicUsize::new(0));
+            let link = Arc::new(compress::Link::default());
+            Buffer::new(move |mut envelope, return_handle| {
                 let tx = Arc::clone(&tx);
+                let link = link.clone();
                 let addr = addr.clone();
-                let (return_channel, return_receiver) =
-                    oneshot::channel::<SendError<MessageEnvelope>>();
-                // Set up for delivery failure.
-                let return_handle_0 = return_handle.clone();
                 let completed = completed.clone();
                 let completed_notify = completed_notify.clone();
-                tokio::spawn(async move {
-                    match return_receiver.await {
-                        Ok(SendError {
-                            error,
-                            message,
-                            reason,
-                        }) => {
-                            let target = message.dest().clone();
-                            let reason_text = reason
-                                .as_ref()
-                                .map(ToString::to_string)
-                                .unwrap_or_else(|| "channel closed".to_owned());
-                            let reason = match reason {
-                                Some(SendErrorReason::OversizedFrame { len, max }) => {
-                                    TransportFailureReason::OversizedFrame { len, max }
+                let transmitted = transmitted.clone();
+                async move {
+                    // Wait for a credit. The server grants credits by
+                    // acknowledging messages; until then, further messages
+                    // are held in the buffer.
+                    if window > 0 {
+                        let mut exhausted = false;
+                        loop {
+                            let notified = completed_notify.notified();
+                            if transmitted.load(Ordering::SeqCst) - completed.load(Ordering::SeqCst)
+                                < window
+                            {
+                                break;
+                            }
+                            if !exhausted {
+                                exhausted = true;
+                                tracing::debug!(
+                                    window,
+                                    "mailbox client window exhausted for {}",
+                                    addr
+                                );
+                                metrics::MAILBOX_CLIENT_WINDOW_EXHAUSTED.add(
+                                    1,
+                                    hyperactor_telemetry::kv_pairs!("addr" => addr.to_string()),
+                                );
+                            }
+                            notified.await;
+                        }
+                    }
+                    transmitted.fetch_add(1, Ordering::SeqCst);
+                    // Payloads with a dictionary are compressed, and large
+                    // payloads are sent by reference.
+                    let compressed = compress::compress(&mut envelope, &link);
+                    let spilled = spill::spill(&mut envelope).await;
+                    // Hold the message until its class is under its
+                    // ceilings.
+                    shaping::shape(class, envelope.data.len()).await;
+
+                    let (return_channel, return_receiver) =
+                        oneshot::channel::<SendError<MessageEnvelope>>();
+                    // Set up for delivery failure.
+                    let return_handle_0 = return_handle.clone();
+                    tokio::spawn(async move {
+                        match return_receiver.await {
+                            Ok(SendError {
+                                error,
+                                mut message,
+                                reason,
+                            }) => {
+                                // Return the payload itself, rather than
+                                // a reference to it.
+                                if let Some((_, data)) = spilled {
+                                    message.data = data;
                                 }
-                                Some(SendErrorReason::Other(_)) | None => {
-                                    TransportFailureReason::ChannelClosed { addr }
+                                if let Some((data, sent)) = compressed {
+                                    message.data = data;
+                                    // The dictionary never reached the
+                                    // server; send it again with the next
+                                    // payload that uses it.
+                                    if let Some(id) = sent {
+                                        link.forget(id);
+                                    }
                                 }
-                            };
-                            let failure = DeliveryFailure::new(UndeliverableReason::Transport(
-                                TransportFailure::new(target, reason.clone()),
-                            ));
-                            tracing::debug!(
-                                %error,
-                                send_error_reason = %reason_text,
-                                ?reason,
-                                "failed to enqueue in mailbox client while processing buffer",
-                            );
-                            message.undeliverable(failure, return_handle_0);
-                        }
-                        Err(_) => {
-                            // Oneshot sender was dropped — message was acked.
+                                let target = message.dest().clone();
+                                let reason_text = reason
+                                    .as_ref()
+                                    .map(ToString::to_string)
+                                    .unwrap_or_else(|| "channel closed".to_owned());
+                                let reason = match reason {
+                                    Some(SendErrorReason::OversizedFrame { len, max }) => {
+                                        TransportFailureReason::OversizedFrame { len, max }
+                                    }
+                                    Some(SendErrorReason::Other(_)) | None => {
+                                        TransportFailureReason::ChannelClosed { addr }
+                                    }
+                                };
+                                let failure = DeliveryFailure::new(UndeliverableReason::Transport(
+                                    TransportFailure::new(target, reason.clone()),
+                                ));
+                                tracing::debug!(
+                                    %error,
+                                    send_error_reason = %reason_text,
+                                    ?reason,
+                                    "failed to enqueue in mailbox client while processing buffer",
+                                );
+                                message.undeliverable(failure, return_handle_0);
+                            }
+                            Err(_) => {
+                                // Oneshot sender was dropped — message was acked.
+                                // Keep serving a spilled payload for as long as
+                                // the server may take to fetch it.
+                                if let Some((lease, _)) = spilled {
+                                    lease.release_after(hyperactor_config::global::get(
+                                        crate::config::MESSAGE_SPILL_FETCH_TIMEOUT,
+                                    ));
+                                }
+                            }
                         }
-                    }
-                    completed.fetch_add(1, Ordering::SeqCst);
-                    completed_notify.notify_waiters();
-                });
-                // Send the message for transmission.
-                tx.try_post(envelope, return_channel);
-                future::ready(())
+                        completed.fetch_add(1, Ordering::SeqCst);
+                        completed_notify.notify_waiters();
+                    });
+                    // Send the message for transmission.
+                    tx.try_post(envelope, return_channel);
+                }
             })
         };
         let this = Self {
@@ -1580,11 +2022,18 @@ impl MailboxClient {
             completed,
             completed_notify,
             tx_status: tx_status.clone(),
+            window,
+            class,
         };
         Self::monitor_tx_health(tx_status, tx_monitoring, addr);
         this
     }
 
+    /// The class of traffic this client carries.
+    pub fn class(&self) -> shaping::ChannelClass {
+        self.class
+    }
+
     /// A means to monitor the health of the underlying [`channel::Tx`]. The
     /// watcher transitions to [`TxStatus::Closed`] when the tx is no longer
     /// usable for message delivery (e.g. peer rejected the session).
@@ -1592,12 +2041,51 @@ impl MailboxClient {
         &self.tx_status
     }
 
+    /// The number of messages that may be posted before this client's
+    /// flow-control window (see
+    /// [`MAILBOX_CLIENT_WINDOW`](crate::config::MAILBOX_CLIENT_WINDOW))
+    /// is exhausted, or `None` if flow control is disabled. Messages
+    /// posted without credits are buffered by the client until the
+    /// server grants more.
+    pub fn credits(&self) -> Option<usize> {
+        if self.window == 0 {
+            return None;
+        }
+        let backlog = self
+            .submitted
+            .load(Ordering::SeqCst)
+            .saturating_sub(self.completed.load(Ordering::SeqCst));
+        Some(self.window.saturating_sub(backlog))
+    }
+
+    /// Wait until this client has credits to transmit another message.
+    /// Producers should await this before posting, so that they observe
+    /// the server's backpressure instead of growing the client's buffer.
+    pub async fn ready(&self) {
+        loop {
+            let notified = self.completed_notify.notified();
+            if self.credits() != Some(0) {
+                return;
+            }
+            notified.await;
+        }
+    }
+
     /// Convenience constructor, to set up a mailbox client that forwards messages
     /// to the provided address.
     pub fn dial(addr: ChannelAddr) -> Result<MailboxClient, ChannelError> {
         Ok(MailboxClient::new(channel::dial(addr)?))
     }
 
+    /// Like [`MailboxClient::dial`], but shape the client's traffic to
+    /// the ceilings of `class`.
+    pub fn dial_with_class(
+        addr: ChannelAddr,
+        class: shaping::ChannelClass,
+    ) -> Result<MailboxClient, ChannelError> {
+        Ok(MailboxClient::new_with_class(channel::dial(addr)?, class))
+    }
+
     // Set up a watch for the tx's health.
     fn monitor_tx_health(
         mut rx: watch::Receiver<TxStatus>,
@@ -1698,6 +2186,15 @@ impl<C: context::Actor, M: RemoteMessage> Sink<M> for PortSink<C, M> {
     }
 }
 
+/// The state of an accumulator port under [`EmitPolicy::Throttle`].
+struct Throttle<S> {
+    state: S,
+    /// When the state was last delivered.
+    last_emit: Option<tokio::time::Instant>,
+    /// Whether a delivery is scheduled for the end of the interval.
+    flush_pending: bool,
+}
+
 /// A mailbox coordinates message delivery to actors through typed
 /// [`Port`]s associated with the mailbox.
 #[derive(Clone, Debug)]
@@ -1732,13 +2229,17 @@ impl Mailbox {
             self.inner.actor_id,
             port_id
         );
+        let receiver = PortReceiver::new(receiver, port_id, /*coalesce=*/ false, self.clone());
+        self.inner
+            .port_queues
+            .insert(Port::from(port_index), receiver.receiver.clone());
         (
             PortHandle::new(
                 self.clone(),
                 port_index,
                 UnboundedPortSender::Sequenced(sender),
             ),
-            PortReceiver::new(receiver, port_id, /*coalesce=*/ false, self.clone()),
+            receiver,
         )
     }
 
@@ -1771,6 +2272,8 @@ impl Mailbox {
 
     /// Open a new port with an accumulator with default reduce options.
     /// See [`open_accum_port_opts`] for more details.
+    /// To deliver states other than the latest, use
+    /// [`Mailbox::open_accum_port_with_policy`].
     pub fn open_accum_port<A>(&self, accum: A) -> (PortHandle<A::Update>, PortReceiver<A::State>)
     where
         A: Accumulator + Send + Sync + 'static,
@@ -1792,6 +2295,36 @@ impl Mailbox {
         accum: A,
         streaming_opts: StreamingReducerOpts,
     ) -> (PortHandle<A::Update>, PortReceiver<A::State>)
+    where
+        A: Accumulator + Send + Sync + 'static,
+        A::Update: Message,
+        A::State: Message + Default + Clone,
+    {
+        self.open_accum_port_full(accum, EmitPolicy::Latest, streaming_opts)
+    }
+
+    /// Open a new port with an accumulator, like [`Mailbox::open_accum_port`],
+    /// whose states are delivered to the returned receiver according to
+    /// `policy`.
+    pub fn open_accum_port_with_policy<A>(
+        &self,
+        accum: A,
+        policy: EmitPolicy<A::State>,
+    ) -> (PortHandle<A::Update>, PortReceiver<A::State>)
+    where
+        A: Accumulator + Send + Sync + 'static,
+        A::Update: Message,
+        A::State: Message + Default + Clone,
+    {
+        self.open_accum_port_full(accum, policy, StreamingReducerOpts::default())
+    }
+
+    fn open_accum_port_full<A>(
+        &self,
+        accum: A,
+        policy: EmitPolicy<A::State>,
+        streaming_opts: StreamingReducerOpts,
+    ) -> (PortHandle<A::Update>, PortReceiver<A::State>)
     where
         A: Accumulator + Send + Sync + 'static,
         A::Update: Message,
@@ -1800,30 +2333,90 @@ impl Mailbox {
         let port_index = self.inner.allocate_port();
         let (sender, receiver) = sequenced_unbounded::<SequencedEnvelope<A::State>>();
         let port_id = self.inner.actor_id.port_addr(Port::from(port_index));
-        let state = Mutex::new(A::State::default());
         let reducer_spec = accum.reducer_spec();
-        let enqueue = move |_, update: A::Update| {
-            let mut state = state.lock().unwrap();
-            accum.accumulate(&mut state, update)?;
-            let _ = sender.send(SequencedEnvelope::new(SeqInfo::Direct, None, state.clone()));
-            Ok(())
+        // Under the latest-state policies, undelivered states are
+        // superseded by newer ones.
+        let coalesce = matches!(policy, EmitPolicy::Latest | EmitPolicy::Throttle(_));
+        let emit = |sender: &mpsc::UnboundedSender<SequencedEnvelope<A::State>>,
+                    state: A::State| {
+            let _ = sender.send(SequencedEnvelope::new(SeqInfo::Direct, None, state));
         };
+        let enqueue: Arc<dyn Fn(Flattrs, A::Update) -> Result<(), anyhow::Error> + Send + Sync> =
+            match policy {
+                EmitPolicy::Latest | EmitPolicy::Every => {
+                    let state = Mutex::new(A::State::default());
+                    Arc::new(move |_, update: A::Update| {
+                        let mut state = state.lock().unwrap();
+                        accum.accumulate(&mut state, update)?;
+                        emit(&sender, state.clone());
+                        Ok(())
+                    })
+                }
+                EmitPolicy::Delta(predicate) => {
+                    // The current state, and the last delivered state.
+                    let states = Mutex::new((A::State::default(), A::State::default()));
+                    Arc::new(move |_, update: A::Update| {
+                        let mut states = states.lock().unwrap();
+                        let (state, delivered) = &mut *states;
+                        accum.accumulate(state, update)?;
+                        if predicate(delivered, state) {
+                            *delivered = state.clone();
+                            emit(&sender, state.clone());
+                        }
+                        Ok(())
+                    })
+                }
+                EmitPolicy::Throttle(interval) => {
+                    let throttle = Arc::new(Mutex::new(Throttle {
+                        state: A::State::default(),
+                        last_emit: None,
+                        flush_pending: false,
+                    }));
+                    Arc::new(move |_, update: A::Update| {
+                        let mut guard = throttle.lock().unwrap();
+                        accum.accumulate(&mut guard.state, update)?;
+                        if guard.flush_pending {
+                            // The pending flush delivers this update.
+                            return Ok(());
+                        }
+                        let now = tokio::time::Instant::now();
+                        match (guard.last_emit, tokio::runtime::Handle::try_current()) {
+                            (Some(last_emit), Ok(runtime)) if now < last_emit + interval => {
+                                guard.flush_pending = true;
+                                let throttle = throttle.clone();
+                                let sender = sender.clone();
+                                runtime.spawn(async move {
+                                    tokio::time::sleep_until(last_emit + interval).await;
+                                    let mut guard = throttle.lock().unwrap();
+                                    guard.flush_pending = false;
+                                    guard.last_emit = Some(tokio::time::Instant::now());
+                                    emit(&sender, guard.state.clone());
+                                });
+                            }
+                            _ => {
+                                guard.last_emit = Some(now);
+                                emit(&sender, guard.state.clone());
+                            }
+                        }
+                        Ok(())
+                    })
+                }
+            };
         (
             PortHandle::new_full(
                 self.clone(),
                 port_index,
-                UnboundedPortSender::Func(Arc::new(enqueue)),
+                UnboundedPortSender::Func(enqueue),
                 reducer_spec,
                 streaming_opts,
             ),
-            PortReceiver::new(receiver, port_id, /*coalesce=*/ true, self.clone()),
+            PortReceiver::new(receiver, port_id, coalesce, self.clone()),
         )
     }
 
     /// Open a port that accepts M-typed messages, using the provided function
     /// to enqueue.
     // TODO: consider making lifetime bound to Self instead.
-    #[cfg(test)]
     pub(crate) fn open_enqueue_port<M: Message>(
         &self,
         enqueue: impl Fn(Flattrs, M) -> Result<(), anyhow::Error> + Send + Sync + 'static,
@@ -1875,10 +2468,40 @@ impl Mailbox {
                 receiver: Some(receiver),
                 port_id,
                 mailbox: self.clone(),
+                expiry: None,
+                expiry_timer: None,
             },
         )
     }
 
+    /// Open a new one-shot port, as [`Mailbox::open_once_port`], that
+    /// expires after `expiry`. On expiry, the port is unbound even if
+    /// its receiver is kept, so that a message arriving late is
+    /// returned as undeliverable; and a pending
+    /// [`OncePortReceiver::recv`] fails with
+    /// [`MailboxErrorKind::Timeout`].
+    pub fn open_once_port_with_expiry<M: Message>(
+        &self,
+        expiry: Duration,
+    ) -> (OncePortHandle<M>, OncePortReceiver<M>) {
+        let (handle, mut receiver) = self.open_once_port();
+        let deadline = tokio::time::Instant::now() + expiry;
+        receiver.expiry = Some((deadline, expiry));
+
+        let inner = Arc::downgrade(&self.inner);
+        let port = receiver.port();
+        let timer = tokio::spawn(async move {
+            tokio::time::sleep_until(deadline).await;
+            if let Some(inner) = inner.upgrade() {
+                inner.remove_port(&port);
+            }
+        });
+        // The timer is aborted once the receiver is done with the port.
+        receiver.expiry_timer = Some(timer.abort_handle());
+
+        (handle, receiver)
+    }
+
     /// Open a new one-shot port with a reducer. This port is designed
     /// to be used with casting, where the port is split across multiple
     /// destinations and responses are accumulated using the reducer.
@@ -1920,6 +2543,8 @@ impl Mailbox {
                 receiver: Some(receiver),
                 port_id,
                 mailbox: self.clone(),
+                expiry: None,
+                expiry_timer: None,
             },
         )
     }
@@ -1929,6 +2554,11 @@ impl Mailbox {
         MailboxError::new(self.inner.actor_id.clone(), err)
     }
 
+    /// Whether `port` is bound in this mailbox.
+    pub(crate) fn is_bound(&self, port: &Port) -> bool {
+        self.inner.ports.contains_key(port)
+    }
+
     fn lookup_sender<M: RemoteMessage>(&self) -> Option<UnboundedPortSender<M>> {
         let port = Port::handler::<M>();
         self.inner.ports.get(&port).and_then(|boxed| {
@@ -1946,6 +2576,37 @@ impl Mailbox {
         })
     }
 
+    /// Prepare a by-value send of `M`-typed messages to `dest`, a port of
+    /// this mailbox. This succeeds only if the port is bound to an
+    /// `M`-typed sender and the mailbox is open; otherwise the message
+    /// must be posted serialized, so that the failure is reported in full.
+    ///
+    /// For handler ports, the returned [`LocalSend`] holds the port's
+    /// ingress gate, so the send cannot race with draining the mailbox.
+    pub(crate) fn local_sender<M: RemoteMessage>(&self, dest: &PortAddr) -> Option<LocalSend<M>> {
+        if dest.actor_id() != self.inner.actor_id.id() {
+            return None;
+        }
+        let sender = self
+            .inner
+            .ports
+            .get(&dest.port())?
+            .as_any()
+            .downcast_ref::<UnboundedSender<M>>()?
+            .clone();
+        if self.inner.closed.read().unwrap().is_some()
+            || self.inner.port_admin.contains_key(&dest.port())
+        {
+            return None;
+        }
+        let guard = sender.sender.reserve().ok()?;
+        Some(LocalSend {
+            mailbox: self.clone(),
+            sender,
+            _guard: guard,
+        })
+    }
+
     /// Retrieve the bound undeliverable handler port handle.
     pub fn bound_return_handle(&self) -> Option<PortHandle<Undeliverable<MessageEnvelope>>> {
         self.lookup_sender::<Undeliverable<MessageEnvelope>>()
@@ -1956,6 +2617,13 @@ impl Mailbox {
         self.inner.allocate_port()
     }
 
+    /// Register `observer` to be notified of each port subsequently
+    /// bound or unbound in this mailbox; see [`port_events`]. The
+    /// observer is deregistered when the returned handle is dropped.
+    pub fn observe_ports(&self, observer: impl PortObserver) -> PortObserverHandle {
+        PortObserverHandle::register(&self.inner, Arc::new(observer))
+    }
+
     fn bind<M: RemoteMessage>(&self, handle: &PortHandle<M>) -> PortRef<M> {
         assert_eq!(
             handle.inner.mailbox.actor_addr(),
@@ -1968,15 +2636,13 @@ impl Mailbox {
         let port_ref = self
             .actor_addr()
             .port_addr(Port::from(handle.inner.bind_target.ephemeral_index()));
-        match self.inner.ports.entry(port_ref.port()) {
-            Entry::Vacant(entry) => {
-                entry.insert(Arc::new(UnboundedSender::new(
-                    handle.inner.sender.clone(),
-                    port_ref.clone(),
-                )));
-            }
-            Entry::Occupied(_entry) => {}
-        }
+        self.inner.bind_port(
+            port_ref.port(),
+            Arc::new(UnboundedSender::new(
+                handle.inner.sender.clone(),
+                port_ref.clone(),
+            )),
+        );
 
         PortRef::attest(port_ref)
     }
@@ -1997,25 +2663,23 @@ impl Mailbox {
         );
 
         let port_ref = self.actor_addr().port_addr(port.clone());
-        match self.inner.ports.entry(port) {
-            Entry::Vacant(entry) => {
-                entry.insert(Arc::new(UnboundedSender::new(
-                    handle.inner.sender.clone(),
-                    port_ref.clone(),
-                )));
-            }
-            Entry::Occupied(_entry) => panic!("port {} already bound", port_ref),
+        if !self.inner.bind_port(
+            port,
+            Arc::new(UnboundedSender::new(
+                handle.inner.sender.clone(),
+                port_ref.clone(),
+            )),
+        ) {
+            panic!("port {} already bound", port_ref);
         }
     }
 
     fn bind_once<M: RemoteMessage>(&self, handle: OncePortHandle<M>) {
         let port_id = handle.port_addr().clone();
-        match self.inner.ports.entry(port_id.port()) {
-            Entry::Vacant(entry) => {
-                entry.insert(Arc::new(OnceSender::new(handle.sender, port_id.clone())));
-            }
-            Entry::Occupied(_entry) => {}
-        }
+        self.inner.bind_port(
+            port_id.port(),
+            Arc::new(OnceSender::new(handle.sender, port_id.clone())),
+        );
     }
 
     pub(crate) fn bind_untyped(&self, port_id: &PortAddr, sender: UntypedUnboundedSender) {
@@ -2025,12 +2689,102 @@ impl Mailbox {
             "port does not belong to mailbox"
         );
 
-        match self.inner.ports.entry(port_id.port()) {
-            Entry::Vacant(entry) => {
-                entry.insert(Arc::new(sender));
-            }
-            Entry::Occupied(_entry) => {}
+        self.inner.bind_port(port_id.port(), Arc::new(sender));
+    }
+
+    /// Record that `split`, a port of `child`, was split from `parent`,
+    /// a port of this mailbox: when `parent` is unbound, `split` is
+    /// unbound too, and so are the ports subsequently split from it, so
+    /// that messages posted to them are returned to their senders
+    /// rather than forwarded to a port that no longer exists.
+    pub(crate) fn add_split(&self, parent: &Port, child: &Mailbox, split: Port) {
+        self.inner
+            .add_split_child(parent, Arc::downgrade(&child.inner), split);
+    }
+
+    /// Record that `split`, a port of this mailbox, was split from
+    /// `parent`, a port in another proc. The parent's mailbox cannot
+    /// unbind `split` when `parent` is unbound; instead, `split` is
+    /// unbound once a message it forwarded is returned because `parent`
+    /// is gone (see [`Mailbox::unbind_returned_splits`]).
+    pub(crate) fn add_remote_split(&self, parent: PortAddr, split: Port) {
+        self.inner.add_remote_split(parent, split);
+    }
+
+    /// If `undeliverable` was returned because its destination port is
+    /// gone, unbind the split ports of this mailbox that were split
+    /// from it, and their descendants.
+    pub(crate) fn unbind_returned_splits(&self, undeliverable: &Undeliverable<MessageEnvelope>) {
+        let Undeliverable::Returned(envelope) = undeliverable else {
+            return;
+        };
+        let gone = match envelope
+            .root_delivery_failure()
+            .map(|failure| &failure.kind)
+        {
+            Some(DeliveryFailureKind::Undeliverable(UndeliverableReason::PortGone(_))) => true,
+            Some(DeliveryFailureKind::InvalidReference(invalid)) => matches!(
+                invalid.reason,
+                InvalidReferenceReason::ActorStopped | InvalidReferenceReason::ActorFailed
+            ),
+            _ => false,
+        };
+        if gone {
+            self.inner.unbind_remote_splits(envelope.dest());
+        }
+    }
+
+    /// Forward the ephemeral port `from_index` of this mailbox to `to`,
+    /// which may be local or remote: messages posted to the port are
+    /// relayed to `to`, through `relay` unless `to` belongs to this
+    /// mailbox. Forwarding a port again re-points it, so that a
+    /// well-known port index can be moved to a new implementation, e.g.
+    /// during a hot upgrade, without its senders noticing. Returns a
+    /// reference to the forwarded port.
+    ///
+    /// Fails if the port is bound to anything but a forward, if it
+    /// would be forwarded to itself, or if `from_index` is `u64::MAX`,
+    /// which would leave no indices for ephemeral ports. Relayed
+    /// messages retain their senders, and undeliverable ones are
+    /// returned to them.
+    #[allow(clippy::result_large_err)] // TODO: Consider reducing the size of `MailboxError`.
+    pub fn forward_port<M: RemoteMessage>(
+        &self,
+        from_index: u64,
+        to: PortRef<M>,
+        relay: impl MailboxSender + 'static,
+    ) -> Result<PortRef<M>, MailboxError> {
+        let port = Port::from(from_index);
+        let port_id = self.actor_addr().port_addr(port.clone());
+        let bound = self.inner.ports.get(&port);
+        let next_index = from_index.checked_add(1);
+        if to.port_addr() == &port_id
+            || next_index.is_none()
+            || bound.is_some_and(|sender| sender.as_any().downcast_ref::<PortForward>().is_none())
+        {
+            return Err(MailboxError::new(
+                self.actor_addr().clone(),
+                MailboxErrorKind::InvalidPort(port_id),
+            ));
+        }
+
+        // Never allocate the forwarded index to an ephemeral port.
+        if let Some(next_index) = next_index {
+            self.inner
+                .next_ephemeral_port
+                .fetch_max(next_index, Ordering::SeqCst);
+        }
+        let forward = Arc::new(PortForward {
+            port_id: port_id.clone(),
+            to: to.port_addr().clone(),
+            relay: BoxedMailboxSender::new(relay),
+        });
+        if self.inner.ports.insert(port, forward) {
+            self.inner
+                .port_observers
+                .notify(|| PortEvent::Bound(port_id.clone()));
         }
+        Ok(PortRef::attest(port_id))
     }
 
     pub(crate) fn close(&self, status: ActorStatus) {
@@ -2061,13 +2815,174 @@ impl Mailbox {
     pub(crate) fn drain(&self) {
         self.inner.handler_ingress.drain();
     }
-}
-
-impl context::Mailbox for Mailbox {
-    fn mailbox(&self) -> &Mailbox {
-        self
-    }
-}
+
+    /// Close `port`: messages subsequently posted to it are returned to
+    /// their senders, until the port is reset. If the port was
+    /// draining, the messages it held are returned too. See
+    /// [`MailboxAdminMessage::ClosePort`].
+    pub fn close_port(&self, port: Port) {
+        if let Some(PortAdmin::Draining { held, .. }) =
+            self.inner.port_admin.insert(port, PortAdmin::Closed)
+        {
+            for (envelope, return_handle) in held {
+                let failure = DeliveryFailure::new(InvalidReference::new(
+                    envelope.dest().clone(),
+                    InvalidReferenceReason::PortClosed,
+                ));
+                envelope.undeliverable(failure, return_handle);
+            }
+        }
+    }
+
+    /// Drain `port`: messages subsequently posted to it are held
+    /// instead of delivered, until the port is reset. Returns, and
+    /// removes, the messages queued at the port but not yet received,
+    /// followed by those held so far. See
+    /// [`MailboxAdminMessage::DrainPort`].
+    pub fn drain_port(&self, port: Port) -> PortDump {
+        let port_addr = self.actor_addr().port_addr(port.clone());
+        let mut admin = self
+            .inner
+            .port_admin
+            .entry(port)
+            .or_insert(PortAdmin::Closed);
+        let (held, dropped) = match std::mem::replace(
+            &mut *admin,
+            PortAdmin::Draining {
+                held: Vec::new(),
+                dropped: 0,
+            },
+        ) {
+            PortAdmin::Closed => (Vec::new(), 0),
+            PortAdmin::Draining { held, dropped } => (held, dropped),
+        };
+        drop(admin);
+        let mut messages = match (
+            self.inner.ports.get(&port_addr.port()),
+            self.inner.port_queues.get(&port_addr.port()),
+        ) {
+            (Some(sender), Some(queue)) => sender.take_queued(queue.value().as_ref()),
+            _ => Vec::new(),
+        };
+        messages.extend(held.into_iter().map(|(envelope, _)| envelope));
+        PortDump {
+            port: port_addr,
+            messages,
+            dropped,
+        }
+    }
+
+    /// Reset `port`, resuming normal delivery: messages still held by
+    /// a draining port are delivered, and the port's idempotency keys
+    /// are forgotten. See [`MailboxAdminMessage::ResetPort`].
+    pub fn reset_port(&self, port: Port) {
+        self.inner.seen_keys.remove(&port);
+        if let Some((_, PortAdmin::Draining { held, .. })) = self.inner.port_admin.remove(&port) {
+            for (envelope, return_handle) in held {
+                self.post_unchecked(envelope, return_handle);
+            }
+        }
+    }
+
+    /// Apply the administrative override of the envelope's destination
+    /// port, if any. Returns the envelope if it should be delivered.
+    fn intercept(
+        &self,
+        envelope: MessageEnvelope,
+        return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+    ) -> Option<(MessageEnvelope, PortHandle<Undeliverable<MessageEnvelope>>)> {
+        {
+            let Some(mut admin) = self.inner.port_admin.get_mut(&envelope.dest().port()) else {
+                return Some((envelope, return_handle));
+            };
+            if let PortAdmin::Draining { held, dropped } = &mut *admin {
+                let capacity =
+                    hyperactor_config::global::get(crate::config::MAILBOX_PORT_DUMP_CAPACITY);
+                if held.len() < capacity {
+                    held.push((envelope, return_handle));
+                    return None;
+                }
+                *dropped += 1;
+            }
+            // The entry is released before returning the message, which
+            // may post back to this mailbox.
+        }
+        let failure = DeliveryFailure::new(InvalidReference::new(
+            envelope.dest().clone(),
+            InvalidReferenceReason::PortClosed,
+        ));
+        envelope.undeliverable(failure, return_handle);
+        None
+    }
+
+    /// Resolve the sender for the envelope's destination port, failing
+    /// if the envelope is not addressed to this mailbox, the port is not
+    /// bound, or the mailbox is closed.
+    fn route(
+        &self,
+        envelope: &MessageEnvelope,
+    ) -> Result<Arc<dyn SerializedSender>, Box<DeliveryFailure>> {
+        if envelope.dest().actor_id() != self.inner.actor_id.id() {
+            return Err(Box::new(DeliveryFailure::new(InvalidReference::new(
+                envelope.dest().actor_addr(),
+                InvalidReferenceReason::WrongMailboxOwner,
+            ))));
+        }
+
+        // The port table hands out a clone of the sender, so no lock is held
+        // while calling send_serialized, which may itself post to (and thus
+        // look up or bind) another port on the same mailbox.
+        let Some(ref_) = self.inner.ports.get(&envelope.dest().port()) else {
+            return Err(Box::new(unbound_port_delivery_failure(
+                envelope.dest(),
+                envelope.data(),
+                self.inner.next_ephemeral_port.load(Ordering::SeqCst),
+            )));
+        };
+        let closed = self.inner.closed.read().unwrap();
+        if let Some(status) = &*closed {
+            let failure = match status {
+                ActorStatus::Stopped(reason) => {
+                    tracing::debug!(
+                        owner=%self.inner.actor_id,
+                        %reason,
+                        "mailbox owner is stopped",
+                    );
+                    DeliveryFailure::new(InvalidReference::new(
+                        envelope.dest().actor_addr(),
+                        InvalidReferenceReason::ActorStopped,
+                    ))
+                }
+                ActorStatus::Failed(actor_error) => {
+                    tracing::debug!(
+                        owner=%self.inner.actor_id,
+                        %actor_error,
+                        "mailbox owner failed",
+                    );
+                    DeliveryFailure::new(InvalidReference::new(
+                        envelope.dest().actor_addr(),
+                        InvalidReferenceReason::ActorFailed,
+                    ))
+                }
+                _ => DeliveryFailure::new(UndeliverableReason::Transport(TransportFailure::new(
+                    envelope.dest().actor_addr(),
+                    TransportFailureReason::LinkUnavailable(format!(
+                        "mailbox owner {} closed unexpectedly: {:?}",
+                        self.inner.actor_id, status
+                    )),
+                ))),
+            };
+            return Err(Box::new(failure));
+        }
+        Ok(ref_)
+    }
+}
+
+impl context::Mailbox for Mailbox {
+    fn mailbox(&self) -> &Mailbox {
+        self
+    }
+}
 
 // TODO: figure out what to do with these interfaces -- possibly these caps
 // do not have to be private.
@@ -2085,6 +3000,15 @@ pub fn open_once_port<M: Message>(
     cx.mailbox().open_once_port()
 }
 
+/// Open a one-shot port that expires after `expiry`, given a capability.
+/// See [`Mailbox::open_once_port_with_expiry`].
+pub fn open_once_port_with_expiry<M: Message>(
+    cx: &impl context::Mailbox,
+    expiry: Duration,
+) -> (OncePortHandle<M>, OncePortReceiver<M>) {
+    cx.mailbox().open_once_port_with_expiry(expiry)
+}
+
 #[async_trait]
 impl MailboxSender for Mailbox {
     /// Deliver a serialized message to the provided port ID. This method fails
@@ -2109,84 +3033,78 @@ impl MailboxSender for Mailbox {
             envelope.dest
         );
 
-        if envelope.dest().actor_id() != self.inner.actor_id.id() {
-            let failure = DeliveryFailure::new(InvalidReference::new(
-                envelope.dest().actor_addr(),
-                InvalidReferenceReason::WrongMailboxOwner,
-            ));
-            return envelope.undeliverable(failure, return_handle);
-        }
-
         let port = envelope.dest().port();
-
-        // Clone the Arc<dyn SerializedSender> out of the DashMap while holding
-        // only a short-lived read lock, then release the lock before calling
-        // send_serialized. This prevents a deadlock that occurs when
-        // send_serialized itself tries to post a message to another port on the
-        // same mailbox: if both ports hash to the same DashMap shard, acquiring
-        // the shard's write lock a second time on the same thread deadlocks
-        // (RwLock is not reentrant). DashMap uses a random per-process hasher,
-        // so whether two port indices collide in the same shard varies across
-        // test runs, explaining the longstanding flaky timeout failures.
-        let port_sender = match self.inner.ports.get(&port) {
-            None => {
-                let failure = unbound_port_delivery_failure(
-                    envelope.dest(),
-                    envelope.data(),
-                    self.inner.next_ephemeral_port.load(Ordering::SeqCst),
-                );
-                return envelope.undeliverable(failure, return_handle);
-            }
-            Some(ref_) => {
-                let closed = self.inner.closed.read().unwrap();
-                if let Some(status) = &*closed {
-                    match status {
-                        ActorStatus::Stopped(reason) => {
-                            tracing::debug!(
-                                owner=%self.inner.actor_id,
-                                %reason,
-                                "mailbox owner is stopped",
-                            );
-                            let failure = DeliveryFailure::new(InvalidReference::new(
-                                envelope.dest().actor_addr(),
-                                InvalidReferenceReason::ActorStopped,
-                            ));
-                            return envelope.undeliverable(failure, return_handle);
-                        }
-                        ActorStatus::Failed(actor_error) => {
-                            tracing::debug!(
-                                owner=%self.inner.actor_id,
-                                %actor_error,
-                                "mailbox owner failed",
-                            );
-                            let failure = DeliveryFailure::new(InvalidReference::new(
-                                envelope.dest().actor_addr(),
-                                InvalidReferenceReason::ActorFailed,
-                            ));
-                            return envelope.undeliverable(failure, return_handle);
-                        }
-                        _ => {
-                            let failure = DeliveryFailure::new(UndeliverableReason::Transport(
-                                TransportFailure::new(
-                                    envelope.dest().actor_addr(),
-                                    TransportFailureReason::LinkUnavailable(format!(
-                                        "mailbox owner {} closed unexpectedly: {:?}",
-                                        self.inner.actor_id, status
-                                    )),
-                                ),
-                            ));
-                            return envelope.undeliverable(failure, return_handle);
-                        }
-                    }
-                }
-                // Clone the Arc so we can release the shard read lock before
-                // calling send_serialized, which may re-enter post_unchecked.
-                Arc::clone(&*ref_)
-            }
+        let port_sender = match self.route(&envelope) {
+            Ok(port_sender) => port_sender,
+            Err(failure) => return envelope.undeliverable(*failure, return_handle),
+        };
+        let (envelope, return_handle) = match self.intercept(envelope, return_handle) {
+            Some(unintercepted) => unintercepted,
+            None => return,
         };
-        // Shard read lock is released here when `ref_` is dropped.
+        if let Some(forward) = port_sender.as_any().downcast_ref::<PortForward>() {
+            return forward.relay_envelope(self, envelope, return_handle);
+        }
 
         let (metadata, data) = envelope.open();
+        let idempotency_key = metadata
+            .headers
+            .get(crate::mailbox::headers::IDEMPOTENCY_KEY);
+        let duplicate = idempotency_key
+            .as_deref()
+            .is_some_and(|key| !self.admit_key(&port, key, &metadata.sender, &metadata.dest));
+        if duplicate {
+            return;
+        }
+
+        if data.typehash() == MessageBatch::typehash() {
+            return self.deliver_batch(&*port_sender, &port, metadata, data, return_handle);
+        }
+        self.deliver(
+            &*port_sender,
+            &port,
+            metadata,
+            data,
+            idempotency_key.as_deref(),
+            return_handle,
+        );
+    }
+}
+
+impl Mailbox {
+    /// Record delivery of a message from `sender` with the provided
+    /// idempotency key to `port`. Returns false if the message is a
+    /// duplicate, which should be dropped.
+    fn admit_key(&self, port: &Port, key: &str, sender: &ActorAddr, dest: &PortAddr) -> bool {
+        if self.inner.observe_key(port, key) {
+            return true;
+        }
+        tracing::debug!(
+            actor_id = sender.to_string(),
+            key,
+            "dropping duplicate message to {}",
+            dest
+        );
+        metrics::MAILBOX_DUPLICATES_DROPPED.add(
+            1,
+            hyperactor_telemetry::kv_pairs!(
+                "dest_actor_id" => dest.actor_addr().to_string(),
+            ),
+        );
+        false
+    }
+
+    /// Deliver a message routed to `port_sender`, returning it to the
+    /// sender if it cannot be delivered.
+    fn deliver(
+        &self,
+        port_sender: &dyn SerializedSender,
+        port: &Port,
+        metadata: MessageMetadata,
+        data: wirevalue::Any,
+        idempotency_key: Option<&str>,
+        return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+    ) {
         let MessageMetadata {
             mut headers,
             sender,
@@ -2196,36 +3114,20 @@ impl MailboxSender for Mailbox {
             return_undeliverable,
         } = metadata;
 
-        let to_actor_id = hash_to_u64(dest.actor_addr().id());
-        let message_id = hyperactor_telemetry::generate_message_id(to_actor_id);
-        headers.set(crate::mailbox::headers::TELEMETRY_MESSAGE_ID, message_id);
-        // Only set sender hash if not already present (cast path
-        // pre-sets it with the originating actor).
-        if !headers.contains_key(crate::mailbox::headers::SENDER_ACTOR_ID_HASH) {
-            headers.set(
-                crate::mailbox::headers::SENDER_ACTOR_ID_HASH,
-                hash_to_u64(sender.id()),
-            );
-        }
-        headers.set(crate::mailbox::headers::TELEMETRY_PORT_INDEX, dest.index());
+        let message_id = stamp_delivery_headers(&mut headers, &sender, &dest, &data);
+        sampling::sample_delivery(message_id, &sender, &dest, &data);
 
-        match port_sender.send_serialized(headers, data) {
+        let version = migrate::version_of(&headers);
+        match port_sender.send_serialized(headers, version, data) {
             Ok(disposition) => {
-                hyperactor_telemetry::notify_message_status(
-                    hyperactor_telemetry::MessageStatusEvent {
-                        timestamp: std::time::SystemTime::now(),
-                        id: hyperactor_telemetry::generate_status_event_id(message_id),
-                        message_id,
-                        status: "queued".to_string(),
-                    },
-                );
+                notify_queued(message_id);
 
                 if disposition == SerializedSendDisposition::DeliveredAndExhausted {
-                    self.inner.ports.remove(&port);
+                    self.inner.remove_port(port);
                 }
             }
             Err(SerializedSendFailure::Dead { data, headers }) => {
-                self.inner.ports.remove(&port);
+                self.inner.remove_port(port);
                 let failure = port_gone_delivery_failure(&dest, &data);
 
                 MessageEnvelope::seal(
@@ -2246,6 +3148,11 @@ impl MailboxSender for Mailbox {
                 error: sender_error,
                 headers,
             })) => {
+                // The message was not delivered, so a retry must not be
+                // dropped as a duplicate.
+                if let Some(key) = idempotency_key {
+                    self.inner.forget_key(port, key);
+                }
                 let failure = serialized_send_error_delivery_failure(&dest, &sender_error);
 
                 let envelope = MessageEnvelope::seal(
@@ -2263,6 +3170,128 @@ impl MailboxSender for Mailbox {
             }
         }
     }
+
+    /// Unpack a [`MessageBatch`] routed to `port_sender`, and deliver its
+    /// messages in order, as one delivery. Messages that cannot be
+    /// delivered are returned to the sender individually.
+    fn deliver_batch(
+        &self,
+        port_sender: &dyn SerializedSender,
+        port: &Port,
+        metadata: MessageMetadata,
+        data: wirevalue::Any,
+        return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+    ) {
+        let batch: MessageBatch = match data.deserialized() {
+            Ok(batch) => batch,
+            Err(err) => {
+                let error = MailboxSenderError::new_bound(
+                    metadata.dest.clone(),
+                    MailboxSenderErrorKind::Deserialize(MessageBatch::typename(), err.into()),
+                );
+                let failure = serialized_send_error_delivery_failure(&metadata.dest, &error);
+                return MessageEnvelope::seal(metadata, data).undeliverable(failure, return_handle);
+            }
+        };
+        let MessageMetadata {
+            mut headers,
+            sender,
+            dest,
+            delivery_failures,
+            ttl,
+            return_undeliverable,
+        } = metadata;
+
+        let message_id = stamp_delivery_headers(&mut headers, &sender, &dest, &data);
+        sampling::sample_delivery(message_id, &sender, &dest, &data);
+
+        let sent = port_sender.send_serialized_batch(headers, batch.version, batch.messages);
+        if let Some(disposition) = sent.delivered {
+            notify_queued(message_id);
+            if disposition == SerializedSendDisposition::DeliveredAndExhausted {
+                self.inner.remove_port(port);
+            }
+        }
+        let Some((failure, rest)) = sent.failed else {
+            return;
+        };
+        let (headers, data, error) = match failure {
+            SerializedSendFailure::Dead { headers, data } => {
+                self.inner.remove_port(port);
+                (headers, data, None)
+            }
+            SerializedSendFailure::Error(SerializedSendError {
+                headers,
+                data,
+                error,
+            }) => (headers, data, Some(error)),
+        };
+        // The messages after the failed one were not attempted: they
+        // are returned along with it, for the same reason.
+        let rest = rest
+            .into_iter()
+            .enumerate()
+            .map(|(offset, data)| (batch_message_headers(&headers, offset + 1), data));
+        for (mut headers, data) in std::iter::once((headers.clone(), data)).chain(rest) {
+            migrate::set_version(&mut headers, batch.version);
+            let failure = match &error {
+                None => port_gone_delivery_failure(&dest, &data),
+                Some(error) => serialized_send_error_delivery_failure(&dest, error),
+            };
+            MessageEnvelope::seal(
+                MessageMetadata {
+                    headers,
+                    sender: sender.clone(),
+                    dest: dest.clone(),
+                    delivery_failures: delivery_failures.clone(),
+                    ttl,
+                    return_undeliverable,
+                },
+                data,
+            )
+            .undeliverable(failure, return_handle.clone());
+        }
+    }
+}
+
+/// Stamp the telemetry headers of a message being delivered to a local
+/// port, returning its telemetry message id.
+fn stamp_delivery_headers(
+    headers: &mut Flattrs,
+    sender: &ActorAddr,
+    dest: &PortAddr,
+    data: &wirevalue::Any,
+) -> u64 {
+    let message_id = stamp_telemetry_headers(headers, sender, dest);
+    headers.set(crate::mailbox::headers::MESSAGE_BYTES, data.len() as u64);
+    message_id
+}
+
+/// The telemetry part of [`stamp_delivery_headers`], shared with
+/// by-value local sends, which size their messages with [`encoded_len`].
+fn stamp_telemetry_headers(headers: &mut Flattrs, sender: &ActorAddr, dest: &PortAddr) -> u64 {
+    let to_actor_id = hash_to_u64(dest.actor_addr().id());
+    let message_id = hyperactor_telemetry::generate_message_id(to_actor_id);
+    headers.set(crate::mailbox::headers::TELEMETRY_MESSAGE_ID, message_id);
+    // Only set sender hash if not already present (cast path
+    // pre-sets it with the originating actor).
+    if !headers.contains_key(crate::mailbox::headers::SENDER_ACTOR_ID_HASH) {
+        headers.set(
+            crate::mailbox::headers::SENDER_ACTOR_ID_HASH,
+            hash_to_u64(sender.id()),
+        );
+    }
+    headers.set(crate::mailbox::headers::TELEMETRY_PORT_INDEX, dest.index());
+    message_id
+}
+
+fn notify_queued(message_id: u64) {
+    hyperactor_telemetry::notify_message_status(hyperactor_telemetry::MessageStatusEvent {
+        timestamp: std::time::SystemTime::now(),
+        id: hyperactor_telemetry::generate_status_event_id(message_id),
+        message_id,
+        status: "queued".to_string(),
+    });
 }
 
 fn unbound_port_delivery_failure(
@@ -2291,7 +3320,9 @@ fn serialized_send_error_delivery_failure(
     sender_error: &MailboxSenderError,
 ) -> DeliveryFailure {
     match sender_error.kind() {
-        MailboxSenderErrorKind::Deserialize(_, _) => DeliveryFailure::new(InvalidReference::new(
+        MailboxSenderErrorKind::Deserialize(_, _)
+        | MailboxSenderErrorKind::SchemaMismatch(..)
+        | MailboxSenderErrorKind::Migrate(_) => DeliveryFailure::new(InvalidReference::new(
             dest.clone(),
             InvalidReferenceReason::ProtocolMismatch,
         )),
@@ -2306,6 +3337,16 @@ fn serialized_send_error_delivery_failure(
         MailboxSenderErrorKind::Closed => DeliveryFailure::new(UndeliverableReason::PortGone(
             PortGone::new(dest.clone(), None),
         )),
+        MailboxSenderErrorKind::Forwarded => DeliveryFailure::new(InvalidReference::new(
+            dest.clone(),
+            InvalidReferenceReason::PortForwarded,
+        )),
+        MailboxSenderErrorKind::Other(err) if err.is::<crate::proc::QueuedBytesExceeded>() => {
+            DeliveryFailure::new(UndeliverableReason::Transport(TransportFailure::new(
+                dest.clone(),
+                TransportFailureReason::QuotaExceeded(err.to_string()),
+            )))
+        }
         _ => DeliveryFailure::new(UndeliverableReason::Transport(TransportFailure::new(
             dest.clone(),
             TransportFailureReason::LinkUnavailable(sender_error.to_string()),
@@ -2729,7 +3770,9 @@ impl<M: Message> fmt::Display for OncePortHandle<M> {
 /// on open ports.
 #[derive(Debug)]
 pub struct PortReceiver<M> {
-    receiver: SequencedReceiver<SequencedEnvelope<M>>,
+    /// Shared with the mailbox, which takes queued messages from it
+    /// when the port is drained (see [`Mailbox::drain_port`]).
+    receiver: Arc<PortQueue<M>>,
     port_id: PortAddr,
     /// When multiple messages are put in channel, only receive the latest one
     /// if coalesce is true. Other messages will be discarded.
@@ -2747,7 +3790,7 @@ impl<M> PortReceiver<M> {
         mailbox: Mailbox,
     ) -> Self {
         Self {
-            receiver,
+            receiver: Arc::new(tokio::sync::Mutex::new(receiver)),
             port_id,
             coalesce,
             mailbox,
@@ -2759,13 +3802,20 @@ impl<M> PortReceiver<M> {
     /// and returns a MailboxError if the receiver is disconnected.
     #[allow(clippy::result_large_err)] // TODO: Consider reducing the size of `MailboxError`.
     pub fn try_recv(&mut self) -> Result<Option<M>, MailboxError> {
-        let mut next = self.receiver.try_recv();
-        // To coalesce, drain the mpsc queue and only keep the last one.
-        if self.coalesce
-            && let Some(latest) = self.drain().pop()
-        {
-            next = Ok(latest);
-        }
+        let next = match self.receiver.try_lock() {
+            Ok(mut receiver) => {
+                let mut next = receiver.try_recv();
+                // To coalesce, drain the mpsc queue and only keep the last one.
+                if self.coalesce
+                    && let Some(latest) = Self::drain_queue(&mut receiver, true).pop()
+                {
+                    next = Ok(latest);
+                }
+                next
+            }
+            // The mailbox is taking the queued messages.
+            Err(_) => Err(mpsc::error::TryRecvError::Empty),
+        };
         match next {
             Ok(msg) => Ok(Some(msg)),
             Err(mpsc::error::TryRecvError::Empty) => Ok(None),
@@ -2779,11 +3829,12 @@ impl<M> PortReceiver<M> {
     /// Receive the next message from the port corresponding with this
     /// receiver.
     pub async fn recv(&mut self) -> Result<M, MailboxError> {
-        let mut next = self.receiver.recv().await;
+        let mut receiver = self.receiver.lock().await;
+        let mut next = receiver.recv().await;
         // To coalesce, get the last message from the queue if there are
         // more on the mspc queue.
         if self.coalesce
-            && let Some(latest) = self.drain().pop()
+            && let Some(latest) = Self::drain_queue(&mut receiver, true).pop()
         {
             next = Some(latest);
         }
@@ -2793,12 +3844,66 @@ impl<M> PortReceiver<M> {
         ))
     }
 
+    /// Receive up to `max` messages from the port, so that they can be
+    /// processed together. Waits until `max` messages have been received
+    /// or `deadline` passes, and returns the messages received so far;
+    /// the result is empty only if no message arrived before the
+    /// deadline. Returns an error if the port is closed before any
+    /// message is received. Coalescing ports return only the latest
+    /// message.
+    pub async fn recv_batch(
+        &mut self,
+        max: usize,
+        deadline: tokio::time::Instant,
+    ) -> Result<Vec<M>, MailboxError> {
+        let mut receiver = self.receiver.lock().await;
+        let mut batch = Vec::new();
+        while batch.len() < max {
+            let next = match receiver.try_recv() {
+                Ok(msg) => Some(msg),
+                Err(mpsc::error::TryRecvError::Disconnected) => None,
+                Err(mpsc::error::TryRecvError::Empty) => {
+                    match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                        Ok(next) => next,
+                        Err(_) => break,
+                    }
+                }
+            };
+            match next {
+                Some(msg) => batch.push(msg),
+                None if batch.is_empty() => {
+                    return Err(MailboxError::new(
+                        self.actor_addr().clone(),
+                        MailboxErrorKind::Closed,
+                    ));
+                }
+                None => break,
+            }
+        }
+        if self.coalesce {
+            batch.extend(Self::drain_queue(&mut receiver, true));
+            batch = batch.pop().into_iter().collect();
+        }
+        Ok(batch)
+    }
+
     /// Drains all available messages from the port.
     pub fn drain(&mut self) -> Vec<M> {
+        match self.receiver.try_lock() {
+            Ok(mut receiver) => Self::drain_queue(&mut receiver, self.coalesce),
+            // The mailbox is taking the queued messages.
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn drain_queue(
+        receiver: &mut SequencedReceiver<SequencedEnvelope<M>>,
+        coalesce: bool,
+    ) -> Vec<M> {
         let mut drained: Vec<M> = Vec::new();
-        while let Ok(msg) = self.receiver.try_recv() {
+        while let Ok(msg) = receiver.try_recv() {
             // To coalesce, discard the old message if there is any.
-            if self.coalesce {
+            if coalesce {
                 drained.pop();
             }
             drained.push(msg);
@@ -2820,7 +3925,7 @@ impl<M> Drop for PortReceiver<M> {
         // MARIUS: do we need to tombstone these? or should we
         // error out if we have removed the receiver before serializing the port ref?
         // ("no longer live")?
-        self.mailbox.inner.ports.remove(&self.port());
+        self.mailbox.inner.remove_closed_port(&self.port());
     }
 }
 
@@ -2842,22 +3947,45 @@ pub struct OncePortReceiver<M> {
     /// Mailbox is used to remove the port from service when the receiver
     /// is dropped.
     mailbox: Mailbox,
+
+    /// The deadline of an expiring port, and its expiry.
+    expiry: Option<(tokio::time::Instant, Duration)>,
+
+    /// The task unbinding an expiring port at its deadline.
+    expiry_timer: Option<tokio::task::AbortHandle>,
 }
 
 impl<M> OncePortReceiver<M> {
     /// Receive message from the one-shot port associated with this
     /// receiver.  Recv consumes the receiver: it is no longer valid
     /// after this call.
+    ///
+    /// If the port expires first, fails with [`MailboxErrorKind::Timeout`].
     pub async fn recv(mut self) -> Result<M, MailboxError> {
-        std::mem::take(&mut self.receiver)
-            .unwrap()
-            .await
-            .map_err(|err| {
-                MailboxError::new(
-                    self.actor_addr().clone(),
-                    MailboxErrorKind::Recv(self.port_id.clone(), err.into()),
-                )
-            })
+        let receiver = std::mem::take(&mut self.receiver).unwrap();
+        let result = match self.expiry {
+            None => receiver.await,
+            Some((deadline, _)) => match tokio::time::timeout_at(deadline, receiver).await {
+                Ok(result) => result,
+                Err(_) => return Err(self.timeout()),
+            },
+        };
+        result.map_err(|err| match self.expiry {
+            // The sender was dropped because the port expired.
+            Some((deadline, _)) if tokio::time::Instant::now() >= deadline => self.timeout(),
+            _ => MailboxError::new(
+                self.actor_addr().clone(),
+                MailboxErrorKind::Recv(self.port_id.clone(), err.into()),
+            ),
+        })
+    }
+
+    fn timeout(&self) -> MailboxError {
+        let expiry = self.expiry.map_or(Duration::ZERO, |(_, expiry)| expiry);
+        MailboxError::new(
+            self.actor_addr(),
+            MailboxErrorKind::Timeout(self.port_id.clone(), expiry),
+        )
     }
 
     fn port(&self) -> Port {
@@ -2874,7 +4002,10 @@ impl<M> Drop for OncePortReceiver<M> {
         // MARIUS: do we need to tombstone these? or should we
         // error out if we have removed the receiver before serializing the port ref?
         // ("no longer live")?
-        self.mailbox.inner.ports.remove(&self.port());
+        self.mailbox.inner.remove_closed_port(&self.port());
+        if let Some(timer) = self.expiry_timer.take() {
+            timer.abort();
+        }
     }
 }
 
@@ -2902,6 +4033,66 @@ pub(crate) enum SerializedSendFailure {
     Error(SerializedSendError),
 }
 
+/// The outcome of [`SerializedSender::send_serialized_batch`].
+#[derive(Default)]
+pub(crate) struct SerializedBatchSend {
+    /// The disposition of the last message delivered, if any was.
+    pub(crate) delivered: Option<SerializedSendDisposition>,
+    /// The first message that was not delivered, and the messages
+    /// after it, which were not attempted.
+    pub(crate) failed: Option<(SerializedSendFailure, Vec<wirevalue::Any>)>,
+}
+
+/// The headers of the message at `offset` in a batch sent with
+/// `headers`: the `i`th message takes the batch's seq plus `i`.
+fn batch_message_headers(headers: &Flattrs, offset: usize) -> Flattrs {
+    let mut headers = headers.clone();
+    if let Some(seq_info) = headers.get(SEQ_INFO) {
+        headers.set(SEQ_INFO, batch_message_seq(seq_info, offset));
+    }
+    headers
+}
+
+/// The seq of the message at `offset` in a batch sent with `seq_info`.
+fn batch_message_seq(seq_info: SeqInfo, offset: usize) -> SeqInfo {
+    match seq_info {
+        SeqInfo::Session { session_id, seq } => SeqInfo::Session {
+            session_id,
+            seq: seq + offset as u64,
+        },
+        seq_info => seq_info,
+    }
+}
+
+/// Send a batch through `sender` one message at a time; the default
+/// [`SerializedSender::send_serialized_batch`].
+fn send_serialized_each(
+    sender: &(impl SerializedSender + ?Sized),
+    headers: Flattrs,
+    version: MessageVersion,
+    messages: Vec<wirevalue::Any>,
+) -> SerializedBatchSend {
+    let mut sent = SerializedBatchSend::default();
+    let mut messages = messages.into_iter().enumerate();
+    while let Some((offset, data)) = messages.next() {
+        let headers = batch_message_headers(&headers, offset);
+        // A one-shot port is gone after its first message.
+        let result = if sent.delivered == Some(SerializedSendDisposition::DeliveredAndExhausted) {
+            Err(SerializedSendFailure::Dead { headers, data })
+        } else {
+            sender.send_serialized(headers, version, data)
+        };
+        match result {
+            Ok(disposition) => sent.delivered = Some(disposition),
+            Err(failure) => {
+                sent.failed = Some((failure, messages.map(|(_, data)| data).collect()));
+                break;
+            }
+        }
+    }
+    sent
+}
+
 /// SerializedSender encapsulates senders:
 ///   - It performs type erasure (and thus it is object-safe).
 ///   - It abstracts over [`Port`]s and [`OncePort`]s, by dynamically tracking the
@@ -2914,17 +4105,139 @@ trait SerializedSender: Send + Sync {
     /// `&UnboundedSender<M>` via `Any::downcast_ref`.
     fn as_any(&self) -> &dyn Any;
 
-    /// Send a serialized message. SerializedSender will deserialize the
-    /// message (failing if it fails to deserialize), and then send the
-    /// resulting message on the underlying port.
+    /// Send a serialized message. SerializedSender will migrate the
+    /// message from the sender's wire `version` (see [`migrate`]),
+    /// deserialize it (failing if it fails to deserialize), and then send
+    /// the resulting message on the underlying port.
     ///
     /// The returned disposition describes successful delivery. Errors
     /// report both the failed message and whether the sender remains live.
     fn send_serialized(
         &self,
         headers: Flattrs,
+        version: MessageVersion,
         serialized: wirevalue::Any,
     ) -> Result<SerializedSendDisposition, SerializedSendFailure>;
+
+    /// Send a batch of serialized messages (see [`MessageBatch`]) in
+    /// order, as [`SerializedSender::send_serialized`] would one at a
+    /// time, the `i`th with the seq in `headers` advanced by `i`.
+    /// Sending stops at the first message that is not delivered.
+    fn send_serialized_batch(
+        &self,
+        headers: Flattrs,
+        version: MessageVersion,
+        messages: Vec<wirevalue::Any>,
+    ) -> SerializedBatchSend {
+        send_serialized_each(self, headers, version, messages)
+    }
+
+    /// Perform every step of [`SerializedSender::send_serialized`] up to,
+    /// but not including, enqueueing the message. The returned
+    /// [`PreparedSend`] holds any reservation needed for the enqueue to
+    /// succeed (the one-shot sender, or the handler ingress gate), which
+    /// is released if it is dropped without being committed.
+    fn prepare_serialized(
+        self: Arc<Self>,
+        headers: Flattrs,
+        version: MessageVersion,
+        serialized: wirevalue::Any,
+    ) -> Result<Box<dyn PreparedSend>, SerializedSendFailure>;
+
+    /// Take the messages queued at the port, but not yet received,
+    /// from `queue`, the port's [`PortQueue`]. Nothing is taken if the
+    /// queue is not of this sender's message type, or if its receiver
+    /// is receiving from it.
+    fn take_queued(&self, _queue: &(dyn Any + Send + Sync)) -> Vec<MessageEnvelope> {
+        Vec::new()
+    }
+}
+
+/// The queue of messages delivered to a port, but not yet received.
+type PortQueue<M> = tokio::sync::Mutex<SequencedReceiver<SequencedEnvelope<M>>>;
+
+/// A message that has been decoded for its destination port, but not
+/// yet enqueued. See [`SerializedSender::prepare_serialized`].
+trait PreparedSend: Send {
+    /// Enqueue the message.
+    fn commit(self: Box<Self>) -> Result<SerializedSendDisposition, SerializedSendFailure>;
+}
+
+/// Reject payloads whose sender-side layout fingerprint disagrees with
+/// the local registration of the payload type.
+fn schema_check<M: Named>(
+    port_id: &PortAddr,
+    headers: &Flattrs,
+    serialized: &wirevalue::Any,
+) -> Result<(), MailboxSenderError> {
+    headers::check_schema_fingerprint(headers, serialized).map_err(|(remote, local)| {
+        MailboxSenderError::new_bound(
+            port_id.clone(),
+            MailboxSenderErrorKind::SchemaMismatch(M::typename(), remote, local),
+        )
+    })
+}
+
+/// Bring a payload encoded at wire `version` to the local version of its
+/// type. Payloads at the local version are checked against the local
+/// layout fingerprint; others are migrated, in which case the payload as
+/// sent is returned alongside, so that failures downstream report the
+/// message in its original form.
+fn localize<M: Named>(
+    port_id: &PortAddr,
+    headers: &Flattrs,
+    version: MessageVersion,
+    serialized: wirevalue::Any,
+) -> Result<(wirevalue::Any, Option<wirevalue::Any>), SerializedSendError> {
+    if version == migrate::local_version(serialized.typehash()) {
+        return match schema_check::<M>(port_id, headers, &serialized) {
+            Ok(()) => Ok((serialized, None)),
+            Err(error) => Err(SerializedSendError {
+                headers: headers.clone(),
+                data: serialized,
+                error,
+            }),
+        };
+    }
+    let sent = serialized.clone();
+    match migrate::to_local(version, serialized) {
+        Ok(migrated) => Ok((migrated, Some(sent))),
+        Err((data, err)) => Err(SerializedSendError {
+            headers: headers.clone(),
+            data,
+            error: MailboxSenderError::new_bound(port_id.clone(), err.into()),
+        }),
+    }
+}
+
+/// Localize (see [`localize`]) and decode a payload for an M-typed
+/// port. Returns the message along with the payload as sent, for
+/// reporting failures downstream. Unless `checked`, the payload's type
+/// is not verified against M.
+fn decode<M: RemoteMessage>(
+    port_id: &PortAddr,
+    headers: &Flattrs,
+    version: MessageVersion,
+    serialized: wirevalue::Any,
+    checked: bool,
+) -> Result<(M, wirevalue::Any), SerializedSendError> {
+    let (serialized, sent) = localize::<M>(port_id, headers, version, serialized)?;
+    let message = if checked {
+        serialized.deserialized()
+    } else {
+        serialized.deserialized_unchecked()
+    };
+    match message {
+        Ok(message) => Ok((message, sent.unwrap_or(serialized))),
+        Err(err) => Err(SerializedSendError {
+            data: sent.unwrap_or(serialized),
+            error: MailboxSenderError::new_bound(
+                port_id.clone(),
+                MailboxSenderErrorKind::Deserialize(M::typename(), err.into()),
+            ),
+            headers: headers.clone(),
+        }),
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -2966,6 +4279,24 @@ impl<M: Message> UnboundedPortSender<M> {
             Self::Handler(sender) => sender.send(headers, message),
         }
     }
+
+    /// Enter the handler ingress gate, if this is a handler port. While
+    /// the returned guard is held, draining the mailbox waits for
+    /// [`UnboundedPortSender::send_reserved`].
+    fn reserve(&self) -> Result<Option<HandlerIngressGuard>, anyhow::Error> {
+        match self {
+            Self::Handler(sender) => Ok(Some(sender.gate.try_enter()?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Send under a reservation obtained from [`UnboundedPortSender::reserve`].
+    fn send_reserved(&self, headers: Flattrs, message: M) -> Result<(), anyhow::Error> {
+        match self {
+            Self::Handler(sender) => sender.sender.send(headers, message),
+            _ => self.send(headers, message),
+        }
+    }
 }
 
 // We implement Clone manually as derive(Clone) places unnecessarily
@@ -3141,6 +4472,32 @@ impl<M: Message> Clone for UnboundedSender<M> {
     }
 }
 
+impl<M: RemoteMessage> UnboundedSender<M> {
+    /// Map the result of a send on the underlying port, reporting `data`
+    /// as the failed message.
+    fn sent(
+        &self,
+        result: Result<(), anyhow::Error>,
+        headers: Flattrs,
+        data: wirevalue::Any,
+    ) -> Result<SerializedSendDisposition, SerializedSendFailure> {
+        match result {
+            Ok(()) => Ok(SerializedSendDisposition::Delivered),
+            Err(_) if matches!(&self.sender, UnboundedPortSender::Sequenced(_)) => {
+                Err(SerializedSendFailure::Dead { data, headers })
+            }
+            Err(err) => Err(SerializedSendFailure::Error(SerializedSendError {
+                data,
+                error: MailboxSenderError::new_bound(
+                    self.port_id.clone(),
+                    classify_sender_error(err),
+                ),
+                headers,
+            })),
+        }
+    }
+}
+
 impl<M: RemoteMessage> SerializedSender for UnboundedSender<M> {
     fn as_any(&self) -> &dyn Any {
         self
@@ -3149,6 +4506,7 @@ impl<M: RemoteMessage> SerializedSender for UnboundedSender<M> {
     fn send_serialized(
         &self,
         headers: Flattrs,
+        version: MessageVersion,
         serialized: wirevalue::Any,
     ) -> Result<SerializedSendDisposition, SerializedSendFailure> {
         // Here, the stack ensures that this port is only instantiated for M-typed messages.
@@ -3156,34 +4514,198 @@ impl<M: RemoteMessage> SerializedSender for UnboundedSender<M> {
         // but it is required as we have some usages that rely on representational equivalence
         // to provide type indexing, specifically in `IndexedErasedUnbound` which is used to
         // support port aggregation.
-        match serialized.deserialized_unchecked() {
-            Ok(message) => match self.sender.send(headers.clone(), message) {
-                Ok(()) => Ok(SerializedSendDisposition::Delivered),
-                Err(_) if matches!(&self.sender, UnboundedPortSender::Sequenced(_)) => {
-                    Err(SerializedSendFailure::Dead {
-                        data: serialized,
-                        headers,
-                    })
-                }
-                Err(err) => Err(SerializedSendFailure::Error(SerializedSendError {
-                    data: serialized,
-                    error: MailboxSenderError::new_bound(
-                        self.port_id.clone(),
-                        classify_sender_error(err),
-                    ),
-                    headers,
-                })),
-            },
+        let (message, data) = decode::<M>(&self.port_id, &headers, version, serialized, false)
+            .map_err(SerializedSendFailure::Error)?;
+        let result = self.sender.send(headers.clone(), message);
+        self.sent(result, headers, data)
+    }
+
+    fn send_serialized_batch(
+        &self,
+        headers: Flattrs,
+        version: MessageVersion,
+        messages: Vec<wirevalue::Any>,
+    ) -> SerializedBatchSend {
+        let UnboundedPortSender::Sequenced(sender) = &self.sender else {
+            return send_serialized_each(self, headers, version, messages);
+        };
+        let seq_info = headers.get(SEQ_INFO).unwrap_or(SeqInfo::Direct);
+        if !seq_info.is_valid() {
+            return send_serialized_each(self, headers, version, messages);
+        }
+        // Enqueue the messages directly, reading the headers once for
+        // the whole batch.
+        let sender_addr = headers.get(crate::mailbox::headers::SENDER_ACTOR_ID);
+        let mut sent = SerializedBatchSend::default();
+        let mut messages = messages.into_iter().enumerate();
+        while let Some((offset, serialized)) = messages.next() {
+            let failure = match decode::<M>(&self.port_id, &headers, version, serialized, false) {
+                Ok((message, data)) => match sender.send(SequencedEnvelope::new(
+                    batch_message_seq(seq_info.clone(), offset),
+                    sender_addr.clone(),
+                    message,
+                )) {
+                    Ok(()) => {
+                        sent.delivered = Some(SerializedSendDisposition::Delivered);
+                        continue;
+                    }
+                    Err(_) => SerializedSendFailure::Dead {
+                        headers: batch_message_headers(&headers, offset),
+                        data,
+                    },
+                },
+                Err(mut err) => {
+                    err.headers = batch_message_headers(&headers, offset);
+                    SerializedSendFailure::Error(err)
+                }
+            };
+            sent.failed = Some((failure, messages.map(|(_, data)| data).collect()));
+            break;
+        }
+        sent
+    }
+
+    fn prepare_serialized(
+        self: Arc<Self>,
+        headers: Flattrs,
+        version: MessageVersion,
+        serialized: wirevalue::Any,
+    ) -> Result<Box<dyn PreparedSend>, SerializedSendFailure> {
+        let (message, data) = decode::<M>(&self.port_id, &headers, version, serialized, false)
+            .map_err(SerializedSendFailure::Error)?;
+        match self.sender.reserve() {
+            Ok(guard) => Ok(Box::new(PreparedUnbounded {
+                sender: self,
+                _guard: guard,
+                headers,
+                message,
+                data,
+            })),
             Err(err) => Err(SerializedSendFailure::Error(SerializedSendError {
-                data: serialized,
+                data,
                 error: MailboxSenderError::new_bound(
                     self.port_id.clone(),
-                    MailboxSenderErrorKind::Deserialize(M::typename(), err.into()),
+                    classify_sender_error(err),
                 ),
                 headers,
             })),
         }
     }
+
+    fn take_queued(&self, queue: &(dyn Any + Send + Sync)) -> Vec<MessageEnvelope> {
+        let Some(queue) = queue.downcast_ref::<PortQueue<M>>() else {
+            return Vec::new();
+        };
+        let Ok(mut receiver) = queue.try_lock() else {
+            return Vec::new();
+        };
+        let mut messages = Vec::new();
+        while let Ok(message) = receiver.try_recv() {
+            // The original senders and headers are not kept in the queue.
+            match MessageEnvelope::serialize(
+                self.port_id.actor_addr(),
+                self.port_id.clone(),
+                &message,
+                Flattrs::new(),
+            ) {
+                Ok(envelope) => messages.push(envelope),
+                Err(err) => {
+                    tracing::warn!(port = %self.port_id, "dropping queued message: {}", err)
+                }
+            }
+        }
+        messages
+    }
+}
+
+struct PreparedUnbounded<M: RemoteMessage> {
+    sender: Arc<UnboundedSender<M>>,
+    _guard: Option<HandlerIngressGuard>,
+    headers: Flattrs,
+    message: M,
+    data: wirevalue::Any,
+}
+
+impl<M: RemoteMessage> PreparedSend for PreparedUnbounded<M> {
+    fn commit(self: Box<Self>) -> Result<SerializedSendDisposition, SerializedSendFailure> {
+        let Self {
+            sender,
+            _guard,
+            headers,
+            message,
+            data,
+        } = *self;
+        let result = sender.sender.send_reserved(headers.clone(), message);
+        sender.sent(result, headers, data)
+    }
+}
+
+/// The bincode-encoded size of `message`, which approximates its size
+/// under any of the [`wirevalue`] encodings, computed without encoding
+/// it into a buffer.
+fn encoded_len<M: Serialize>(message: &M) -> Result<u64, bincode::error::EncodeError> {
+    let mut writer = bincode::enc::write::SizeWriter::default();
+    bincode::serde::encode_into_writer(message, &mut writer, bincode::config::legacy())?;
+    Ok(writer.bytes_written as u64)
+}
+
+/// A by-value send to a local port, obtained from [`Mailbox::local_sender`].
+pub(crate) struct LocalSend<M: RemoteMessage> {
+    mailbox: Mailbox,
+    sender: UnboundedSender<M>,
+    _guard: Option<HandlerIngressGuard>,
+}
+
+impl<M: RemoteMessage> LocalSend<M> {
+    /// Enqueue `message` on the port, stamping the same delivery headers
+    /// as a serialized post. The message is consumed even on failure.
+    pub(crate) fn send(
+        self,
+        sender: &ActorAddr,
+        mut headers: Flattrs,
+        message: M,
+    ) -> Result<(), MailboxSenderError> {
+        let dest = &self.sender.port_id;
+        metrics::MAILBOX_POSTS.add(
+            1,
+            hyperactor_telemetry::kv_pairs!(
+                "actor_id" => sender.to_string(),
+                "dest_actor_id" => dest.actor_addr().to_string(),
+            ),
+        );
+        let message_id = stamp_telemetry_headers(&mut headers, sender, dest);
+        // Handler ports account queued messages by their serialized size,
+        // so by-value messages carry what they would have serialized to.
+        // This also subjects them to the actor's queued-bytes cap.
+        if matches!(&self.sender.sender, UnboundedPortSender::Handler(_)) {
+            match encoded_len(&message) {
+                Ok(bytes) => headers.set(crate::mailbox::headers::MESSAGE_BYTES, bytes),
+                Err(err) => {
+                    return Err(MailboxSenderError::new_bound(
+                        dest.clone(),
+                        MailboxSenderErrorKind::Serialize(err.into()),
+                    ));
+                }
+            }
+        }
+        match self.sender.sender.send_reserved(headers, message) {
+            Ok(()) => {
+                notify_queued(message_id);
+                Ok(())
+            }
+            Err(_) if matches!(&self.sender.sender, UnboundedPortSender::Sequenced(_)) => {
+                self.mailbox.inner.remove_port(&dest.port());
+                Err(MailboxSenderError::new_bound(
+                    dest.clone(),
+                    MailboxSenderErrorKind::Closed,
+                ))
+            }
+            Err(err) => Err(MailboxSenderError::new_bound(
+                dest.clone(),
+                classify_sender_error(err),
+            )),
+        }
+    }
 }
 
 /// OnceSender encapsulates an underlying one-shot sender, dynamically
@@ -3248,29 +4770,89 @@ impl<M: RemoteMessage> SerializedSender for OnceSender<M> {
     fn send_serialized(
         &self,
         headers: Flattrs,
+        version: MessageVersion,
         serialized: wirevalue::Any,
     ) -> Result<SerializedSendDisposition, SerializedSendFailure> {
-        match serialized.deserialized() {
-            Ok(message) => self
-                .send_once(message)
-                .map_err(|_| SerializedSendFailure::Dead {
-                    data: serialized,
-                    headers,
-                }),
-            Err(err) => Err(SerializedSendFailure::Error(SerializedSendError {
-                data: serialized,
-                error: MailboxSenderError::new_bound(
-                    self.port_id.clone(),
-                    MailboxSenderErrorKind::Deserialize(M::typename(), err.into()),
-                ),
+        let (message, data) = decode::<M>(&self.port_id, &headers, version, serialized, true)
+            .map_err(SerializedSendFailure::Error)?;
+        self.send_once(message)
+            .map_err(|_| SerializedSendFailure::Dead { data, headers })
+    }
+
+    fn prepare_serialized(
+        self: Arc<Self>,
+        headers: Flattrs,
+        version: MessageVersion,
+        serialized: wirevalue::Any,
+    ) -> Result<Box<dyn PreparedSend>, SerializedSendFailure> {
+        let (message, data) = decode::<M>(&self.port_id, &headers, version, serialized, true)
+            .map_err(SerializedSendFailure::Error)?;
+        match self.sender.lock().unwrap().take() {
+            Some(sender) => Ok(Box::new(PreparedOnce {
+                reservation: OnceReservation {
+                    port: Arc::clone(&self.sender),
+                    sender: Some(sender),
+                },
                 headers,
+                message,
+                data,
             })),
+            None => Err(SerializedSendFailure::Dead { data, headers }),
+        }
+    }
+}
+
+/// A one-shot sender taken from its port, which is put back unless it
+/// is used.
+struct OnceReservation<M: Message> {
+    port: Arc<Mutex<Option<oneshot::Sender<M>>>>,
+    sender: Option<oneshot::Sender<M>>,
+}
+
+impl<M: Message> OnceReservation<M> {
+    fn send(mut self, message: M) -> Result<(), M> {
+        self.sender
+            .take()
+            .expect("reservation is used only once")
+            .send(message)
+    }
+}
+
+impl<M: Message> Drop for OnceReservation<M> {
+    fn drop(&mut self) {
+        if let Some(sender) = self.sender.take() {
+            *self.port.lock().unwrap() = Some(sender);
+        }
+    }
+}
+
+struct PreparedOnce<M: Message> {
+    reservation: OnceReservation<M>,
+    headers: Flattrs,
+    message: M,
+    data: wirevalue::Any,
+}
+
+impl<M: Message> PreparedSend for PreparedOnce<M> {
+    fn commit(self: Box<Self>) -> Result<SerializedSendDisposition, SerializedSendFailure> {
+        let Self {
+            reservation,
+            headers,
+            message,
+            data,
+        } = *self;
+        match reservation.send(message) {
+            Ok(()) => Ok(SerializedSendDisposition::DeliveredAndExhausted),
+            Err(_) => Err(SerializedSendFailure::Dead { data, headers }),
         }
     }
 }
 
 /// Use the provided function to send untyped messages (i.e. Any objects).
+/// Messages are migrated to the local version of their type before they
+/// are handed to the function.
 pub(crate) struct UntypedUnboundedSender {
+    pub(crate) port_id: PortAddr,
     pub(crate) sender: Box<
         dyn Fn(Flattrs, wirevalue::Any) -> Result<SerializedSendDisposition, SerializedSendFailure>
             + Send
@@ -3286,10 +4868,148 @@ impl SerializedSender for UntypedUnboundedSender {
     fn send_serialized(
         &self,
         headers: Flattrs,
+        version: MessageVersion,
         serialized: wirevalue::Any,
     ) -> Result<SerializedSendDisposition, SerializedSendFailure> {
+        let (headers, serialized) = self.localize(headers, version, serialized)?;
         (self.sender)(headers, serialized)
     }
+
+    /// Untyped senders hand the payload to a function, so there is
+    /// nothing to reserve: preparation migrates the payload, and the
+    /// function is only called on commit.
+    fn prepare_serialized(
+        self: Arc<Self>,
+        headers: Flattrs,
+        version: MessageVersion,
+        serialized: wirevalue::Any,
+    ) -> Result<Box<dyn PreparedSend>, SerializedSendFailure> {
+        let (headers, data) = self.localize(headers, version, serialized)?;
+        Ok(Box::new(PreparedUntyped {
+            sender: self,
+            headers,
+            data,
+        }))
+    }
+}
+
+impl UntypedUnboundedSender {
+    /// Migrate a payload encoded at wire `version` to the local version
+    /// of its type, along with the headers that describe it.
+    fn localize(
+        &self,
+        mut headers: Flattrs,
+        version: MessageVersion,
+        serialized: wirevalue::Any,
+    ) -> Result<(Flattrs, wirevalue::Any), SerializedSendFailure> {
+        let migrated = version != migrate::local_version(serialized.typehash());
+        match migrate::to_local(version, serialized) {
+            Ok(serialized) => {
+                if migrated {
+                    // The stamped fingerprint and version describe the
+                    // payload as sent; restamp them for the migrated payload.
+                    headers::set_schema_fingerprint(&mut headers, &serialized);
+                    migrate::set_version(
+                        &mut headers,
+                        migrate::local_version(serialized.typehash()),
+                    );
+                }
+                Ok((headers, serialized))
+            }
+            Err((data, err)) => Err(SerializedSendFailure::Error(SerializedSendError {
+                headers,
+                data,
+                error: MailboxSenderError::new_bound(self.port_id.clone(), err.into()),
+            })),
+        }
+    }
+}
+
+/// A payload migrated for an untyped port, which is handed to the
+/// port's function on commit.
+struct PreparedUntyped {
+    sender: Arc<UntypedUnboundedSender>,
+    headers: Flattrs,
+    data: wirevalue::Any,
+}
+
+impl PreparedSend for PreparedUntyped {
+    fn commit(self: Box<Self>) -> Result<SerializedSendDisposition, SerializedSendFailure> {
+        (self.sender.sender)(self.headers, self.data)
+    }
+}
+
+/// The binding of a forwarded port; see [`Mailbox::forward_port`].
+struct PortForward {
+    /// The forwarded port.
+    port_id: PortAddr,
+    /// The port to which messages are relayed.
+    to: PortAddr,
+    relay: BoxedMailboxSender,
+}
+
+impl PortForward {
+    /// Relay an envelope posted to the forwarded port, retaining its
+    /// sender and return handle. Relaying is a hop, so forwarding
+    /// cycles are broken by the envelope's TTL.
+    fn relay_envelope(
+        &self,
+        mailbox: &Mailbox,
+        envelope: MessageEnvelope,
+        return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+    ) {
+        let envelope = envelope.with_dest(self.to.clone());
+        if self.to.actor_id() == mailbox.inner.actor_id.id() {
+            mailbox.post(envelope, return_handle);
+        } else {
+            self.relay.post(envelope, return_handle);
+        }
+    }
+}
+
+impl SerializedSender for PortForward {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    /// Envelopes posted to the mailbox are relayed by
+    /// [`PortForward::relay_envelope`]. Messages sent to the port
+    /// otherwise are relayed on behalf of the mailbox's owner.
+    fn send_serialized(
+        &self,
+        headers: Flattrs,
+        version: MessageVersion,
+        serialized: wirevalue::Any,
+    ) -> Result<SerializedSendDisposition, SerializedSendFailure> {
+        let envelope = MessageEnvelope::new(
+            self.port_id.actor_addr(),
+            self.to.clone(),
+            serialized,
+            headers,
+        )
+        .with_version(version);
+        self.relay.post(envelope, monitored_return_handle());
+        Ok(SerializedSendDisposition::Delivered)
+    }
+
+    /// Relayed messages are delivered, if at all, after the relay
+    /// returns, so they cannot be prepared: a message that must be
+    /// delivered with others (see [`transaction`]) is rejected.
+    fn prepare_serialized(
+        self: Arc<Self>,
+        headers: Flattrs,
+        _version: MessageVersion,
+        serialized: wirevalue::Any,
+    ) -> Result<Box<dyn PreparedSend>, SerializedSendFailure> {
+        Err(SerializedSendFailure::Error(SerializedSendError {
+            headers,
+            data: serialized,
+            error: MailboxSenderError::new_bound(
+                self.port_id.clone(),
+                MailboxSenderErrorKind::Forwarded,
+            ),
+        }))
+    }
 }
 
 /// State is the internal state of the mailbox.
@@ -3300,7 +5020,7 @@ struct State {
     // insert if it's serializable; otherwise don't.
     /// The set of active ports in the mailbox. All currently
     /// allocated ports are
-    ports: DashMap<Port, Arc<dyn SerializedSender>>,
+    ports: PortTable<dyn SerializedSender>,
 
     /// The next ephemeral port ID to allocate.
     next_ephemeral_port: AtomicU64,
@@ -3311,6 +5031,74 @@ struct State {
 
     /// Gate that closes and drains runtime-dispatched handler ingress.
     handler_ingress: Arc<HandlerIngressGate>,
+
+    /// Idempotency keys recently delivered to each port.
+    seen_keys: DashMap<Port, SeenKeys>,
+
+    /// Ports whose delivery has been overridden by an administrator;
+    /// see [`MailboxAdminMessage`].
+    port_admin: DashMap<Port, PortAdmin>,
+
+    /// The receive queues of the open ports, by which
+    /// [`Mailbox::drain_port`] takes the messages queued at a port.
+    /// Each is a [`PortQueue`] of the port's message type.
+    port_queues: DashMap<Port, Arc<dyn Any + Send + Sync>>,
+
+    /// Observers of the ports bound and unbound in the mailbox.
+    port_observers: PortObservers,
+
+    /// The split ports created from each port of the mailbox, by this
+    /// or another mailbox in the proc. They are unbound along with the
+    /// port they were split from.
+    split_children: DashMap<Port, SplitPorts<(Weak<State>, Port)>>,
+
+    /// The split ports of the mailbox created from ports in other
+    /// procs, by the port they were split from; see
+    /// [`Mailbox::add_remote_split`].
+    remote_splits: DashMap<PortAddr, SplitPorts<Port>>,
+}
+
+/// The split ports created from a port. Those that were unbound on
+/// their own (e.g. once ports that have delivered their reduction) are
+/// pruned whenever the list has doubled, so that recording a split
+/// takes amortized constant time.
+struct SplitPorts<T> {
+    ports: Vec<T>,
+    prune_at: usize,
+}
+
+impl<T> Default for SplitPorts<T> {
+    fn default() -> Self {
+        Self {
+            ports: Vec::new(),
+            prune_at: 0,
+        }
+    }
+}
+
+impl<T> SplitPorts<T> {
+    /// Add `port`, first pruning the ports that are no longer `bound`
+    /// if the list has doubled since it was last pruned.
+    fn push(&mut self, port: T, bound: impl Fn(&T) -> bool) {
+        if self.ports.len() >= self.prune_at {
+            self.ports.retain(bound);
+            self.prune_at = (2 * self.ports.len()).max(8);
+        }
+        self.ports.push(port);
+    }
+}
+
+/// An administrative override of delivery to a port.
+enum PortAdmin {
+    /// Messages are returned to their senders.
+    Closed,
+    /// Messages are held, up to
+    /// [`crate::config::MAILBOX_PORT_DUMP_CAPACITY`], until they are
+    /// dumped or the port is reset.
+    Draining {
+        held: Vec<(MessageEnvelope, PortHandle<Undeliverable<MessageEnvelope>>)>,
+        dropped: usize,
+    },
 }
 
 impl State {
@@ -3318,10 +5106,16 @@ impl State {
     fn new(actor_id: ActorAddr) -> Self {
         Self {
             actor_id,
-            ports: DashMap::new(),
+            ports: PortTable::new(),
             next_ephemeral_port: AtomicU64::new(0),
             closed: RwLock::new(None),
             handler_ingress: Arc::new(HandlerIngressGate::new()),
+            seen_keys: DashMap::new(),
+            port_admin: DashMap::new(),
+            port_queues: DashMap::new(),
+            port_observers: PortObservers::new(),
+            split_children: DashMap::new(),
+            remote_splits: DashMap::new(),
         }
     }
 
@@ -3329,20 +5123,156 @@ impl State {
     fn allocate_port(&self) -> u64 {
         self.next_ephemeral_port.fetch_add(1, Ordering::SeqCst)
     }
+
+    /// Bind `sender` to `port` unless the port is already bound.
+    /// Returns whether `sender` was bound.
+    fn bind_port(&self, port: Port, sender: Arc<dyn SerializedSender>) -> bool {
+        let bound = self.ports.insert_if_vacant(port.clone(), sender);
+        if bound {
+            self.port_observers
+                .notify(|| PortEvent::Bound(self.actor_id.port_addr(port)));
+        }
+        bound
+    }
+
+    /// Remove a port, along with its idempotency keys.
+    fn remove_port(&self, port: &Port) {
+        self.unbind_port(port, PortEvent::Unbound);
+    }
+
+    /// Remove a port whose receiver was dropped.
+    fn remove_closed_port(&self, port: &Port) {
+        self.port_queues.remove(port);
+        self.unbind_port(port, PortEvent::Closed);
+    }
+
+    fn unbind_port(&self, port: &Port, event: fn(PortAddr) -> PortEvent) {
+        let unbound = self.ports.remove(port);
+        self.seen_keys.remove(port);
+        if unbound {
+            self.port_observers
+                .notify(|| event(self.actor_id.port_addr(port.clone())));
+        }
+        self.unbind_split_children(port);
+    }
+
+    /// Record that `split`, a port of the mailbox with `child`'s state,
+    /// was split from `parent`, a port of this mailbox.
+    fn add_split_child(&self, parent: &Port, child: Weak<State>, split: Port) {
+        self.split_children.entry(parent.clone()).or_default().push(
+            (child, split),
+            |(state, port)| {
+                state
+                    .upgrade()
+                    .is_some_and(|state| state.ports.get(port).is_some())
+            },
+        );
+        // The parent may have been unbound before the split was recorded.
+        if self.ports.get(parent).is_none() {
+            self.unbind_split_children(parent);
+        }
+    }
+
+    /// Unbind the split ports created from `port`, and their
+    /// descendants.
+    fn unbind_split_children(&self, port: &Port) {
+        let Some((_, children)) = self.split_children.remove(port) else {
+            return;
+        };
+        for (state, split) in children.ports {
+            if let Some(state) = state.upgrade() {
+                state.remove_port(&split);
+            }
+        }
+    }
+
+    /// Record that `split`, a port of this mailbox, was split from
+    /// `parent`, a port in another proc.
+    fn add_remote_split(&self, parent: PortAddr, split: Port) {
+        self.remote_splits
+            .entry(parent)
+            .or_default()
+            .push(split, |port| self.ports.get(port).is_some());
+    }
+
+    /// Unbind the split ports of this mailbox created from `parent`, a
+    /// port in another proc, and their descendants.
+    fn unbind_remote_splits(&self, parent: &PortAddr) {
+        let Some((_, splits)) = self.remote_splits.remove(parent) else {
+            return;
+        };
+        for split in splits.ports {
+            self.remove_port(&split);
+        }
+    }
+
+    /// Record delivery of a message with the provided idempotency key
+    /// to `port`. Returns false if the key was recently delivered to the
+    /// port, in which case the message is a duplicate.
+    fn observe_key(&self, port: &Port, key: &str) -> bool {
+        let capacity = hyperactor_config::global::get(crate::config::IDEMPOTENCY_CACHE_CAPACITY);
+        if capacity == 0 {
+            return true;
+        }
+        self.seen_keys
+            .entry(port.clone())
+            .or_default()
+            .observe(key, capacity)
+    }
+
+    /// Forget a key recorded by [`State::observe_key`].
+    fn forget_key(&self, port: &Port, key: &str) {
+        if let Some(mut seen) = self.seen_keys.get_mut(port) {
+            seen.forget(key);
+        }
+    }
+}
+
+/// A bounded set of the idempotency keys recently delivered to a port.
+/// When full, the least recently seen key is evicted.
+#[derive(Default)]
+struct SeenKeys {
+    keys: HashSet<String>,
+    /// Keys in order of last sighting, least recent first.
+    order: VecDeque<String>,
+}
+
+impl SeenKeys {
+    /// Record a sighting of `key`, returning false if it was already
+    /// present.
+    fn observe(&mut self, key: &str, capacity: usize) -> bool {
+        if self.keys.contains(key) {
+            // Duplicates are rare, so a linear scan to refresh the key
+            // is acceptable.
+            if let Some(index) = self.order.iter().position(|seen| seen == key) {
+                let seen = self.order.remove(index).unwrap();
+                self.order.push_back(seen);
+            }
+            return false;
+        }
+        while self.order.len() >= capacity {
+            let Some(evicted) = self.order.pop_front() else {
+                break;
+            };
+            self.keys.remove(&evicted);
+        }
+        self.keys.insert(key.to_string());
+        self.order.push_back(key.to_string());
+        true
+    }
+
+    fn forget(&mut self, key: &str) {
+        if self.keys.remove(key) {
+            self.order.retain(|seen| seen != key);
+        }
+    }
 }
 
 impl fmt::Debug for State {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         f.debug_struct("State")
             .field("actor_id", &self.actor_id)
-            .field(
-                "open_ports",
-                &self
-                    .ports
-                    .iter()
-                    .map(|e| e.key().clone())
-                    .collect::<Vec<_>>(),
-            )
+            .field("open_ports", &self.ports.ports())
             .field("next_ephemeral_port", &self.next_ephemeral_port)
             .finish()
     }
@@ -3354,6 +5284,9 @@ impl fmt::Debug for State {
 #[derive(Clone)]
 pub struct MailboxMuxer {
     mailboxes: Arc<DashMap<ActorId, Box<dyn MailboxSender + Send + Sync>>>,
+    /// The subset of bound senders that are local mailboxes, which
+    /// can take part in [`MailboxMuxer::post_all`].
+    locals: Arc<DashMap<ActorId, Mailbox>>,
 }
 
 impl Default for MailboxMuxer {
@@ -3367,6 +5300,7 @@ impl MailboxMuxer {
     pub fn new() -> Self {
         Self {
             mailboxes: Arc::new(DashMap::new()),
+            locals: Arc::new(DashMap::new()),
         }
     }
 
@@ -3386,15 +5320,37 @@ impl MailboxMuxer {
 
     /// Convenience function to bind a mailbox.
     pub fn bind_mailbox(&self, mailbox: Mailbox) -> bool {
-        self.bind(mailbox.actor_addr().id().clone(), mailbox)
+        let actor_id = mailbox.actor_addr().id().clone();
+        if !self.bind(actor_id.clone(), mailbox.clone()) {
+            return false;
+        }
+        self.locals.insert(actor_id, mailbox);
+        true
+    }
+
+    /// The locally bound mailbox for the provided actor ID, if any.
+    pub(crate) fn local(&self, actor_id: &ActorId) -> Option<Mailbox> {
+        self.locals.get(actor_id).map(|entry| entry.value().clone())
+    }
+
+    /// The locally bound mailbox that owns `port`, for port
+    /// administration (see [`MailboxAdminMessage`]).
+    #[allow(clippy::result_large_err)] // TODO: Consider reducing the size of `MailboxError`.
+    pub fn port_mailbox(&self, port: &PortAddr) -> Result<Mailbox, MailboxError> {
+        self.local(port.actor_id()).ok_or_else(|| {
+            MailboxError::new(
+                port.actor_addr(),
+                MailboxErrorKind::InvalidPort(port.clone()),
+            )
+        })
     }
 
     /// Unbind the sender associated with the provided actor ID. After
     /// unbinding, the muxer will no longer be able to send messages to
     /// that actor.
-    #[allow(dead_code)]
     pub(crate) fn unbind(&self, actor_id: &ActorId) {
         self.mailboxes.remove(actor_id);
+        self.locals.remove(actor_id);
     }
 }
 
@@ -3408,13 +5364,21 @@ impl MailboxSender for MailboxMuxer {
         let dest_actor_ref = envelope.dest().actor_addr();
         match self.mailboxes.get(dest_actor_ref.id()) {
             None => {
+                routing::audit(RouterKind::Muxer, envelope.dest(), || {
+                    ChosenRoute::Unroutable
+                });
                 let failure = DeliveryFailure::new(InvalidReference::new(
                     dest_actor_ref,
                     InvalidReferenceReason::ActorNotExist,
                 ));
                 envelope.undeliverable(failure, return_handle)
             }
-            Some(sender) => sender.post(envelope, return_handle),
+            Some(sender) => {
+                routing::audit(RouterKind::Muxer, envelope.dest(), || {
+                    ChosenRoute::Bound(Addr::Actor(dest_actor_ref))
+                });
+                sender.post(envelope, return_handle)
+            }
         }
     }
 
@@ -3433,11 +5397,22 @@ impl MailboxSender for MailboxMuxer {
     }
 }
 
-/// MailboxRouter routes messages to the sender that is bound to its
-/// nearest prefix.
+/// The routes of a [`MailboxRouter`]. An [`im::OrdMap`], as for the
+/// address book of a [`DialMailboxRouter`], so that a bind copies
+/// O(log N) of the table rather than all of it.
+type RouterEntries = Snapshot<im::OrdMap<Addr, Arc<dyn MailboxSender + Send + Sync>>>;
+
+/// MailboxRouter routes messages to the sender that is bound to the
+/// route selected by its [`RoutingPolicy`]: by default, the nearest
+/// prefix of the destination actor.
+///
+/// Like [`DialMailboxRouter`]'s address book, the routes are a
+/// [`Snapshot`], so a panic while they change (for example, in the
+/// `Drop` of an unbound sender) cannot lose them or fail later posts.
 #[derive(Clone)]
 pub struct MailboxRouter {
-    entries: Arc<RwLock<BTreeMap<Addr, Arc<dyn MailboxSender + Send + Sync>>>>,
+    entries: Arc<RouterEntries>,
+    policy: Arc<dyn RoutingPolicy>,
 }
 
 impl Default for MailboxRouter {
@@ -3450,13 +5425,22 @@ impl MailboxRouter {
     /// Create a new, empty router.
     pub fn new() -> Self {
         Self {
-            entries: Arc::new(RwLock::new(BTreeMap::new())),
+            entries: Arc::new(Snapshot::new(im::OrdMap::new())),
+            policy: routing::default_policy(),
         }
     }
 
+    /// Route messages by `policy` instead of the default, which is
+    /// [`routing::LongestPrefix`] unless [`crate::config::LOCALITY`] is
+    /// configured.
+    pub fn with_routing_policy(mut self, policy: impl RoutingPolicy) -> Self {
+        self.policy = Arc::new(policy);
+        self
+    }
+
     /// Downgrade this router to a [`WeakMailboxRouter`].
     pub fn downgrade(&self) -> WeakMailboxRouter {
-        WeakMailboxRouter(Arc::downgrade(&self.entries))
+        WeakMailboxRouter(Arc::downgrade(&self.entries), Arc::clone(&self.policy))
     }
 
     /// Returns a boxed sender that first attempts to find a route in
@@ -3472,34 +5456,38 @@ impl MailboxRouter {
 
     /// Bind the provided sender to the given reference. The destination
     /// is treated as a prefix to which messages can be routed, and
-    /// messages are routed to their longest matching prefix.
+    /// messages are routed as selected by the router's policy.
     pub fn bind(&self, dest: impl Into<Addr>, sender: impl MailboxSender + 'static) {
         let dest = dest.into();
-        let mut w = self.entries.write().unwrap();
-        w.insert(dest, Arc::new(sender));
+        let sender: Arc<dyn MailboxSender + Send + Sync> = Arc::new(sender);
+        self.entries.update(|entries| {
+            entries.insert(dest, sender);
+        });
     }
 
     /// Remove the binding for the given reference. Only the exact
     /// point is removed; other bindings under the same prefix are
     /// unaffected.
     pub fn unbind(&self, dest: &Addr) {
-        let mut w = self.entries.write().unwrap();
-        w.remove(dest);
+        if !self.entries.load().contains_key(dest) {
+            return;
+        }
+        self.entries.update(|entries| {
+            entries.remove(dest);
+        });
     }
 
-    fn sender(&self, actor_ref: &ActorAddr) -> Option<Arc<dyn MailboxSender + Send + Sync>> {
-        let reference = Addr::from(actor_ref.clone());
-        match self
-            .entries
-            .read()
-            .unwrap()
-            .lower_bound(Excluded(&reference))
-            .prev()
-        {
-            None => None,
-            Some((key, sender)) if key.is_prefix_of(&reference) => Some(sender.clone()),
-            Some(_) => None,
-        }
+    /// The sender routing `dest`, auditing the route as chosen by
+    /// `router` if there is one.
+    fn sender(
+        &self,
+        dest: &PortAddr,
+        router: RouterKind,
+    ) -> Option<Arc<dyn MailboxSender + Send + Sync>> {
+        let entries = self.entries.load();
+        let route = self.policy.route(&**entries, &Addr::from(dest.clone()))?;
+        routing::audit(router, dest, || ChosenRoute::Bound(route.key.clone()));
+        entries.get(route.key).cloned()
     }
 }
 
@@ -3510,9 +5498,11 @@ impl MailboxSender for MailboxRouter {
         envelope: MessageEnvelope,
         return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
     ) {
-        let dest_actor_ref = envelope.dest().actor_addr();
-        match self.sender(&dest_actor_ref) {
+        match self.sender(envelope.dest(), RouterKind::Router) {
             None => {
+                routing::audit(RouterKind::Router, envelope.dest(), || {
+                    ChosenRoute::Unroutable
+                });
                 let target = envelope.dest().clone();
                 let failure = DeliveryFailure::new(UndeliverableReason::Transport(
                     TransportFailure::new(target, TransportFailureReason::NoRoute),
@@ -3524,7 +5514,7 @@ impl MailboxSender for MailboxRouter {
     }
 
     async fn flush(&self) -> Result<(), anyhow::Error> {
-        let senders: Vec<_> = self.entries.read().unwrap().values().cloned().collect();
+        let senders: Vec<_> = self.entries.load().values().cloned().collect();
         let futs: Vec<_> = senders.iter().map(|s| s.flush()).collect();
         futures::future::try_join_all(futs).await?;
         Ok(())
@@ -3532,7 +5522,7 @@ impl MailboxSender for MailboxRouter {
 }
 
 /// A router that first checks a [`MailboxRouter`] for a matching
-/// prefix route, falling back to a default sender when none is found.
+/// route, falling back to a default sender when none is found.
 #[derive(Clone)]
 pub struct FallbackMailboxRouter {
     router: MailboxRouter,
@@ -3553,10 +5543,17 @@ impl MailboxSender for FallbackMailboxRouter {
         envelope: MessageEnvelope,
         return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
     ) {
-        let dest_actor_ref = envelope.dest().actor_addr();
-        match self.router.sender(&dest_actor_ref) {
+        match self
+            .router
+            .sender(envelope.dest(), RouterKind::FallbackRouter)
+        {
             Some(sender) => sender.post(envelope, return_handle),
-            None => self.default.post(envelope, return_handle),
+            None => {
+                routing::audit(RouterKind::FallbackRouter, envelope.dest(), || {
+                    ChosenRoute::Default
+                });
+                self.default.post(envelope, return_handle)
+            }
         }
     }
 
@@ -3577,12 +5574,15 @@ impl MailboxSender for FallbackMailboxRouter {
 /// the granularity of each entry. Possibly the router should allow weak references
 /// on a per-entry basis.
 #[derive(Debug, Clone)]
-pub struct WeakMailboxRouter(Weak<RwLock<BTreeMap<Addr, Arc<dyn MailboxSender + Send + Sync>>>>);
+pub struct WeakMailboxRouter(Weak<RouterEntries>, Arc<dyn RoutingPolicy>);
 
 impl WeakMailboxRouter {
     /// Upgrade the weak router to a strong reference router.
     pub fn upgrade(&self) -> Option<MailboxRouter> {
-        self.0.upgrade().map(|entries| MailboxRouter { entries })
+        self.0.upgrade().map(|entries| MailboxRouter {
+            entries,
+            policy: Arc::clone(&self.1),
+        })
     }
 }
 
@@ -3617,6 +5617,22 @@ impl MailboxSender for WeakMailboxRouter {
     }
 }
 
+/// The references among `references`, which are sorted, that are not
+/// prefixed by another.
+fn covering_prefixes<'a>(references: impl IntoIterator<Item = &'a Addr>) -> BTreeSet<Addr> {
+    let mut prefixes: BTreeSet<Addr> = BTreeSet::new();
+    for reference in references {
+        match prefixes.lower_bound(Excluded(reference)).peek_prev() {
+            Some(candidate) if candidate.is_prefix_of(reference) => (),
+            _ => {
+                prefixes.insert(reference.clone());
+            }
+        }
+    }
+
+    prefixes
+}
+
 /// Returns true if `status` is `Closed` with a typed reason identifying a
 /// stale session — the K8s "out-of-sequence message, expected seq 0, got N"
 /// case where the peer's dispatcher GC'd the `SessionId` but our cached
@@ -3642,11 +5658,22 @@ fn is_stale_session_close(status: &TxStatus) -> bool {
 /// is reachable), then DialMailboxRouter dials the proc directly.
 ///
 /// Messages sent to unknown destinations are routed to the `default`
-/// sender, if present.
+/// sender, if present. Which bound reference routes a destination is
+/// selected by the router's [`RoutingPolicy`]; by default, its nearest
+/// prefix.
+///
+/// The address book is a [`Snapshot`] that is replaced as a whole
+/// whenever it changes, so lookups, which happen on every post, never
+/// block on (or observe a poisoned) lock. It is a persistent map, so
+/// a change copies only the path to the changed entry, and binding
+/// many procs one by one stays O(n log n).
 #[derive(Clone)]
 pub struct DialMailboxRouter {
-    address_book: Arc<RwLock<BTreeMap<Addr, ChannelAddr>>>,
-    sender_cache: Arc<DashMap<ChannelAddr, Arc<MailboxClient>>>,
+    address_book: Arc<Snapshot<im::OrdMap<Addr, ChannelAddr>>>,
+    // Whole worlds, keyed by the uid of the gateway behind which their
+    // procs are located; see [`DialMailboxRouter::bind_world`].
+    worlds: Arc<Snapshot<BTreeMap<Uid, ChannelAddr>>>,
+    sender_cache: Arc<DashMap<ChannelAddr, DialedClient>>,
 
     // The default sender, to which messages for unknown recipients
     // are sent. (This is like a default route in a routing table.)
@@ -3655,6 +5682,16 @@ pub struct DialMailboxRouter {
     // When true, only dial direct-addressed procs if their transport
     // type is remote. Otherwise, fall back to the default sender.
     direct_addressed_remote_only: bool,
+
+    policy: Arc<dyn RoutingPolicy>,
+
+    // The address family preference used when dialing; the configured
+    // preference if unset.
+    address_preference: Option<channel::AddressPreference>,
+
+    // The class of the clients dialed by this router; the configured
+    // class if unset.
+    channel_class: Option<shaping::ChannelClass>,
 }
 
 impl Default for DialMailboxRouter {
@@ -3675,10 +5712,14 @@ impl DialMailboxRouter {
     /// direct-addressed, in which case it is dialed directly.
     pub fn new_with_default(default: BoxedMailboxSender) -> Self {
         Self {
-            address_book: Arc::new(RwLock::new(BTreeMap::new())),
+            address_book: Arc::new(Snapshot::new(im::OrdMap::new())),
+            worlds: Arc::new(Snapshot::new(BTreeMap::new())),
             sender_cache: Arc::new(DashMap::new()),
             default,
             direct_addressed_remote_only: false,
+            policy: routing::default_policy(),
+            address_preference: None,
+            channel_class: None,
         }
     }
 
@@ -3688,13 +5729,41 @@ impl DialMailboxRouter {
     /// direct-addressed *and* has a remote channel transport type.
     pub fn new_with_default_direct_addressed_remote_only(default: BoxedMailboxSender) -> Self {
         Self {
-            address_book: Arc::new(RwLock::new(BTreeMap::new())),
+            address_book: Arc::new(Snapshot::new(im::OrdMap::new())),
+            worlds: Arc::new(Snapshot::new(BTreeMap::new())),
             sender_cache: Arc::new(DashMap::new()),
             default,
             direct_addressed_remote_only: true,
+            policy: routing::default_policy(),
+            address_preference: None,
+            channel_class: None,
         }
     }
 
+    /// Route messages by `policy` instead of the default, which is
+    /// [`routing::LongestPrefix`] unless [`crate::config::LOCALITY`] is
+    /// configured.
+    pub fn with_routing_policy(mut self, policy: impl RoutingPolicy) -> Self {
+        self.policy = Arc::new(policy);
+        self
+    }
+
+    /// Dial hostnames that resolve to several IP addresses in the
+    /// order given by `preference`, instead of the configured
+    /// [`channel::AddressPreference`].
+    pub fn with_address_preference(mut self, preference: channel::AddressPreference) -> Self {
+        self.address_preference = Some(preference);
+        self
+    }
+
+    /// Shape the traffic of the clients dialed by this router to the
+    /// ceilings of `class`, instead of the configured
+    /// [`shaping::ChannelClass`].
+    pub fn with_channel_class(mut self, class: shaping::ChannelClass) -> Self {
+        self.channel_class = Some(class);
+        self
+    }
+
     /// Binds a [`Addr`] to a [`ChannelAddr`], replacing any
     /// existing binding.
     ///
@@ -3703,16 +5772,17 @@ impl DialMailboxRouter {
     pub fn bind(&self, dest: impl Into<Addr>, addr: ChannelAddr) {
         let dest = dest.into();
         let addr = addr.into_dial_addr();
-        if let Ok(mut w) = self.address_book.write() {
-            if let Some(old_addr) = w.insert(dest.clone(), addr.clone())
+        if self.address_book.load().get(&dest) == Some(&addr) {
+            return;
+        }
+        self.address_book.update(|address_book| {
+            if let Some(old_addr) = address_book.insert(dest.clone(), addr.clone())
                 && old_addr != addr
             {
                 tracing::info!("rebinding {:?} from {:?} to {:?}", dest, old_addr, addr);
                 self.sender_cache.remove(&old_addr);
             }
-        } else {
-            tracing::error!("address book poisoned during bind of {:?}", dest);
-        }
+        });
     }
 
     /// Removes all address mappings with the given prefix from the
@@ -3721,8 +5791,18 @@ impl DialMailboxRouter {
     /// Also evicts any corresponding cached senders to prevent reuse
     /// of stale connections.
     pub fn unbind(&self, dest: &Addr) {
-        if let Ok(mut w) = self.address_book.write() {
-            let to_remove: Vec<(Addr, ChannelAddr)> = w
+        // Don't copy the address book unless something is removed.
+        let bound = |address_book: &im::OrdMap<Addr, ChannelAddr>| {
+            address_book
+                .range(dest..)
+                .next()
+                .is_some_and(|(key, _)| dest.is_prefix_of(key))
+        };
+        if !bound(&self.address_book.load()) {
+            return;
+        }
+        self.address_book.update(|address_book| {
+            let to_remove: Vec<(Addr, ChannelAddr)> = address_book
                 .range(dest..)
                 .take_while(|(key, _)| dest.is_prefix_of(key))
                 .map(|(key, addr)| (key.clone(), addr.clone()))
@@ -3730,58 +5810,118 @@ impl DialMailboxRouter {
 
             for (key, addr) in to_remove {
                 tracing::info!("unbinding {:?} from {:?}", key, addr);
-                w.remove(&key);
+                address_book.remove(&key);
                 self.sender_cache.remove(&addr);
             }
-        } else {
-            tracing::error!("address book poisoned during unbind of {:?}", dest);
+        });
+    }
+
+    /// Binds the world behind the gateway with uid `gateway` to `addr`:
+    /// destinations whose location's outermost hop is a
+    /// [`Location::Via`] through the gateway are routed to `addr`,
+    /// unless a prefix of the destination is bound. This lets a router
+    /// reach another cluster's procs through a single route.
+    pub fn bind_world(&self, gateway: Uid, addr: ChannelAddr) {
+        let addr = addr.into_dial_addr();
+        self.worlds.update(|worlds| {
+            if let Some(old_addr) = worlds.insert(gateway.clone(), addr.clone())
+                && old_addr != addr
+            {
+                tracing::info!(
+                    "rebinding world {} from {:?} to {:?}",
+                    gateway,
+                    old_addr,
+                    addr
+                );
+                self.sender_cache.remove(&old_addr);
+            }
+        });
+    }
+
+    /// Removes the route of the world behind `gateway`, if any.
+    pub fn unbind_world(&self, gateway: &Uid) {
+        if !self.worlds.load().contains_key(gateway) {
+            return;
         }
+        self.worlds.update(|worlds| {
+            if let Some(addr) = worlds.remove(gateway) {
+                tracing::info!("unbinding world {} from {:?}", gateway, addr);
+                self.sender_cache.remove(&addr);
+            }
+        });
+    }
+
+    /// The gateways whose worlds are bound in this router.
+    pub fn worlds(&self) -> BTreeSet<Uid> {
+        self.worlds.load().keys().cloned().collect()
+    }
+
+    /// The world route of `dest`, if it is located behind a gateway
+    /// whose world is bound.
+    fn world_route(&self, dest: &Addr) -> Option<(Uid, ChannelAddr)> {
+        let proc = dest.proc_addr();
+        let Location::Via(gateway, _) = proc.location() else {
+            return None;
+        };
+        let addr = self.worlds.load().get(gateway)?.clone();
+        Some((gateway.clone(), addr))
     }
 
     /// Lookup an actor's channel in the router's address bok.
     pub fn lookup_addr(&self, actor_ref: &ActorAddr) -> Option<ChannelAddr> {
-        let address_book = self.address_book.read().unwrap();
-        let reference = Addr::from(actor_ref.clone());
-        let found = address_book.lower_bound(Excluded(&reference)).prev();
+        self.route_addr(&Addr::from(actor_ref.clone()), None)
+    }
+
+    /// Lookup the channel of `dest`, an actor or a port. If `audited`
+    /// is given, the lookup routes a message to it, and is audited.
+    fn route_addr(&self, dest: &Addr, audited: Option<&PortAddr>) -> Option<ChannelAddr> {
+        let address_book = self.address_book.load();
+        let found = self.policy.route(&**address_book, dest);
 
         // First try to look up the address in our address book; failing that,
         // extract the address from the ProcAddr (all procs are direct-addressed now).
-        if let Some((key, addr)) = found
-            && key.is_prefix_of(&reference)
+        if let Some(route) = found
+            && let Some(addr) = route.addr
         {
+            if let Some(port) = audited {
+                routing::audit(RouterKind::DialRouter, port, || {
+                    ChosenRoute::Bound(route.key.clone())
+                });
+            }
             Some(addr.clone().into_dial_addr())
+        } else if let Some((gateway, addr)) = self.world_route(dest) {
+            if let Some(port) = audited {
+                routing::audit(RouterKind::DialRouter, port, || ChosenRoute::World(gateway));
+            }
+            Some(addr)
         } else {
-            let addr = actor_ref.addr().clone().into_dial_addr();
-            if self.direct_addressed_remote_only {
+            let addr = dest.proc_addr().addr().clone().into_dial_addr();
+            let addr = if self.direct_addressed_remote_only {
                 addr.transport().is_remote().then_some(addr)
             } else {
                 Some(addr)
+            };
+            if let Some(port) = audited {
+                routing::audit(RouterKind::DialRouter, port, || match &addr {
+                    Some(addr) => ChosenRoute::Direct(addr.clone()),
+                    None => ChosenRoute::Default,
+                });
             }
+            addr
         }
     }
 
     /// Return all covering prefixes of this router. That is, all references that are not
     /// prefixed by another reference in the routing table
     pub fn prefixes(&self) -> BTreeSet<Addr> {
-        let addrs = self.address_book.read().unwrap();
-        let mut prefixes: BTreeSet<Addr> = BTreeSet::new();
-        for (reference, _) in addrs.iter() {
-            match prefixes.lower_bound(Excluded(reference)).peek_prev() {
-                Some(candidate) if candidate.is_prefix_of(reference) => (),
-                _ => {
-                    prefixes.insert(reference.clone());
-                }
-            }
-        }
-
-        prefixes
+        covering_prefixes(self.address_book.load().keys())
     }
 
     fn dial(
         &self,
         addr: &ChannelAddr,
         actor_ref: &ActorAddr,
-    ) -> Result<Arc<MailboxClient>, MailboxSenderError> {
+    ) -> Result<DialedClient, MailboxSenderError> {
         // The cache must self-heal when a peer rejects a stale session
         // (e.g. its dispatcher GC'd the SessionId after the prior connection
         // ended, but our cached NetTx has an Outbox.next_seq past 0). Without
@@ -3798,7 +5938,7 @@ impl DialMailboxRouter {
         loop {
             match self.sender_cache.entry(addr.clone()) {
                 Entry::Occupied(entry) => {
-                    let status = entry.get().tx_status().borrow().clone();
+                    let status = entry.get().client.tx_status().borrow().clone();
                     if is_stale_session_close(&status) {
                         tracing::info!(
                             ?addr,
@@ -3811,21 +5951,39 @@ impl DialMailboxRouter {
                     return Ok(entry.get().clone());
                 }
                 Entry::Vacant(entry) => {
-                    let tx = channel::dial(addr.clone()).map_err(|err| {
-                        MailboxSenderError::new_unbound_type(
-                            actor_ref.clone(),
-                            MailboxSenderErrorKind::Channel(err),
-                            "unknown",
-                        )
-                    })?;
-                    let sender = Arc::new(MailboxClient::new(tx));
-                    return Ok(entry.insert(sender).value().clone());
+                    let preference = self
+                        .address_preference
+                        .unwrap_or_else(channel::AddressPreference::configured);
+                    let tx =
+                        channel::dial_with_preference(addr.clone(), preference).map_err(|err| {
+                            MailboxSenderError::new_unbound_type(
+                                actor_ref.clone(),
+                                MailboxSenderErrorKind::Channel(err),
+                                "unknown",
+                            )
+                        })?;
+                    let class = self
+                        .channel_class
+                        .unwrap_or_else(shaping::ChannelClass::configured);
+                    let dialed = DialedClient {
+                        client: Arc::new(MailboxClient::new_with_class(tx, class)),
+                        cross_dc: locality::CrossDc::of(addr).map(Arc::new),
+                    };
+                    return Ok(entry.insert(dialed).value().clone());
                 }
             }
         }
     }
 }
 
+/// A client dialed by a [`DialMailboxRouter`], with the datacenters it
+/// links if they differ, placed once when it is dialed.
+#[derive(Clone)]
+struct DialedClient {
+    client: Arc<MailboxClient>,
+    cross_dc: Option<Arc<locality::CrossDc>>,
+}
+
 #[async_trait]
 impl MailboxSender for DialMailboxRouter {
     fn post_unchecked(
@@ -3834,10 +5992,13 @@ impl MailboxSender for DialMailboxRouter {
         return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
     ) {
         let dest_actor_ref = envelope.dest().actor_addr();
-        let Some(addr) = self.lookup_addr(&dest_actor_ref) else {
+        let Some(addr) =
+            self.route_addr(&Addr::from(envelope.dest().clone()), Some(envelope.dest()))
+        else {
             self.default.post(envelope, return_handle);
             return;
         };
+        header_stats::record(&envelope);
 
         match self.dial(&addr, &dest_actor_ref) {
             Err(err) => {
@@ -3852,7 +6013,12 @@ impl MailboxSender for DialMailboxRouter {
                     )));
                 envelope.undeliverable(failure, return_handle)
             }
-            Ok(sender) => sender.post(envelope, return_handle),
+            Ok(dialed) => {
+                if let Some(cross_dc) = &dialed.cross_dc {
+                    cross_dc.record(envelope.data().len());
+                }
+                dialed.client.post(envelope, return_handle)
+            }
         }
     }
 
@@ -3860,13 +6026,18 @@ impl MailboxSender for DialMailboxRouter {
         let senders: Vec<_> = self
             .sender_cache
             .iter()
-            .map(|entry| entry.value().clone())
+            .map(|entry| Arc::clone(&entry.value().client))
             .collect();
         let mut futs: Vec<_> = senders.iter().map(|s| s.flush()).collect();
         futs.push(self.default.flush());
         futures::future::try_join_all(futs).await?;
         Ok(())
     }
+
+    fn update_address(&self, dest: &Addr, addr: &ChannelAddr) -> bool {
+        self.bind(dest.clone(), addr.clone());
+        true
+    }
 }
 
 /// A MailboxSender that reports any envelope as undeliverable due to
@@ -3912,6 +6083,7 @@ mod tests {
     use crate::context::Mailbox as MailboxContext;
     use crate::context::MailboxExt as _;
     use crate::endpoint::Endpoint as _;
+    use crate::endpoint::RemoteEndpoint as _;
     use crate::proc::Proc;
     use crate::testing::ids::test_actor_id;
     use crate::testing::ids::test_port_id;
@@ -4199,24 +6371,270 @@ mod tests {
                 .expect("serialize");
         let (return_handle, mut return_rx) = undeliverable::new_undeliverable_port();
 
-        mbox.post(envelope, return_handle);
+        mbox.post(envelope, return_handle);
+
+        let undelivered = tokio::time::timeout(Duration::from_secs(1), return_rx.recv())
+            .await
+            .expect("timed out waiting for undeliverable")
+            .expect("return port closed")
+            .into_message()
+            .expect("expected returned envelope");
+        let root_failure = undelivered
+            .root_delivery_failure()
+            .expect("expected root delivery failure");
+        let DeliveryFailureKind::InvalidReference(invalid_reference) = &root_failure.kind else {
+            panic!("expected invalid reference, got {root_failure}");
+        };
+        assert_eq!(
+            invalid_reference.reason,
+            InvalidReferenceReason::PortNeverAllocated
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forward_port() {
+        let muxer = MailboxMuxer::new();
+        let mbox = Mailbox::new(test_actor_id("0", "service"));
+        let v1 = Mailbox::new(test_actor_id("0", "service_v1"));
+        let v2 = Mailbox::new(test_actor_id("0", "service_v2"));
+        muxer.bind(v1.actor_addr().id().clone(), v1.clone());
+        muxer.bind(v2.actor_addr().id().clone(), v2.clone());
+        let post = |dest: &PortRef<u64>, value: u64| {
+            mbox.post(
+                MessageEnvelope::serialize(
+                    test_actor_id("0", "client"),
+                    dest.port_addr().clone(),
+                    &value,
+                    Flattrs::new(),
+                )
+                .unwrap(),
+                monitored_return_handle(),
+            )
+        };
+
+        let (port, mut v1_receiver) = v1.open_port::<u64>();
+        let well_known = mbox.forward_port(1000, port.bind(), muxer.clone()).unwrap();
+        assert_eq!(
+            *well_known.port_addr(),
+            mbox.actor_addr().port_addr(Port::from(1000))
+        );
+        post(&well_known, 1);
+        assert_eq!(v1_receiver.recv().await.unwrap(), 1);
+
+        // Re-point the port at a new implementation.
+        let (port, mut v2_receiver) = v2.open_port::<u64>();
+        mbox.forward_port(1000, port.bind(), muxer.clone()).unwrap();
+        post(&well_known, 2);
+        assert_eq!(v2_receiver.recv().await.unwrap(), 2);
+        assert!(v1_receiver.try_recv().unwrap().is_none());
+
+        // Forwarded indices are never allocated, and a port may be
+        // forwarded within its own mailbox.
+        let (port, mut receiver) = mbox.open_port::<u64>();
+        let port = port.bind();
+        assert!(port.port_addr().index() > 1000);
+        mbox.forward_port(1000, port.clone(), muxer.clone())
+            .unwrap();
+        post(&well_known, 3);
+        assert_eq!(receiver.recv().await.unwrap(), 3);
+
+        // Ports bound to receivers, and forwards to themselves, are
+        // rejected.
+        assert!(
+            mbox.forward_port(1000, well_known.clone(), muxer.clone())
+                .is_err()
+        );
+        assert!(
+            mbox.forward_port(u64::MAX, port.clone(), muxer.clone())
+                .is_err()
+        );
+        assert!(
+            mbox.forward_port(port.port_addr().index(), well_known, muxer)
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_observe_ports() {
+        let mbox = Mailbox::new(test_actor_id("0", "test"));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let observer = mbox.observe_ports({
+            let events = Arc::clone(&events);
+            move |event: &PortEvent| events.lock().unwrap().push(event.clone())
+        });
+        let take = || std::mem::take(&mut *events.lock().unwrap());
+
+        // Binding a port twice binds it once; dropping its receiver
+        // closes it.
+        let (port, receiver) = mbox.open_port::<u64>();
+        let port_addr = port.bind().port_addr().clone();
+        port.bind();
+        drop(receiver);
+        assert_eq!(
+            take(),
+            vec![
+                PortEvent::Bound(port_addr.clone()),
+                PortEvent::Closed(port_addr)
+            ]
+        );
+
+        // Ports that were never bound are not reported.
+        drop(mbox.open_port::<u64>());
+        assert_eq!(take(), vec![]);
+
+        // A one-shot port is unbound once it receives its message.
+        let (port, receiver) = mbox.open_once_port::<u64>();
+        let port = port.bind();
+        mbox.post(
+            MessageEnvelope::serialize(
+                test_actor_id("0", "client"),
+                port.port_addr().clone(),
+                &1u64,
+                Flattrs::new(),
+            )
+            .unwrap(),
+            monitored_return_handle(),
+        );
+        assert_eq!(receiver.recv().await.unwrap(), 1);
+        assert_eq!(
+            take(),
+            vec![
+                PortEvent::Bound(port.port_addr().clone()),
+                PortEvent::Unbound(port.port_addr().clone())
+            ]
+        );
+
+        // Dropping the handle deregisters the observer.
+        drop(observer);
+        let (port, _receiver) = mbox.open_port::<u64>();
+        port.bind();
+        assert_eq!(take(), vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_port_admin() {
+        let mbox = Mailbox::new(test_actor_id("0", "test"));
+        let (port, mut receiver) = mbox.open_port::<u64>();
+        let port = port.bind();
+        let (return_handle, mut return_rx) = undeliverable::new_undeliverable_port();
+        let post = |value: u64| {
+            mbox.post(
+                MessageEnvelope::serialize(
+                    test_actor_id("0", "client"),
+                    port.port_addr().clone(),
+                    &value,
+                    Flattrs::new(),
+                )
+                .unwrap(),
+                return_handle.clone(),
+            )
+        };
 
-        let undelivered = tokio::time::timeout(Duration::from_secs(1), return_rx.recv())
-            .await
-            .expect("timed out waiting for undeliverable")
-            .expect("return port closed")
-            .into_message()
-            .expect("expected returned envelope");
-        let root_failure = undelivered
-            .root_delivery_failure()
-            .expect("expected root delivery failure");
+        // Closed ports return messages to their senders.
+        mbox.close_port(port.port_addr().port());
+        post(1);
+        let undelivered = return_rx.recv().await.unwrap().into_message().unwrap();
+        let root_failure = undelivered.root_delivery_failure().unwrap();
         let DeliveryFailureKind::InvalidReference(invalid_reference) = &root_failure.kind else {
             panic!("expected invalid reference, got {root_failure}");
         };
-        assert_eq!(
-            invalid_reference.reason,
-            InvalidReferenceReason::PortNeverAllocated
+        assert_eq!(invalid_reference.reason, InvalidReferenceReason::PortClosed);
+        assert!(receiver.try_recv().unwrap().is_none());
+
+        // Draining ports dump the messages queued at them, and hold
+        // further messages until they are dumped.
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(crate::config::MAILBOX_PORT_DUMP_CAPACITY, 2);
+        mbox.reset_port(port.port_addr().port());
+        post(0);
+        let dump = mbox.drain_port(port.port_addr().port());
+        let values: Vec<u64> = dump
+            .messages
+            .iter()
+            .map(|envelope| envelope.deserialized().unwrap())
+            .collect();
+        assert_eq!(values, vec![0]);
+        post(2);
+        post(3);
+        post(4);
+        return_rx.recv().await.unwrap();
+        let dump = mbox.drain_port(port.port_addr().port());
+        assert_eq!(dump.port, *port.port_addr());
+        assert_eq!(dump.dropped, 1);
+        let values: Vec<u64> = dump
+            .messages
+            .iter()
+            .map(|envelope| envelope.deserialized().unwrap())
+            .collect();
+        assert_eq!(values, vec![2, 3]);
+        assert!(receiver.try_recv().unwrap().is_none());
+
+        // Resetting delivers the messages still held.
+        post(5);
+        mbox.reset_port(port.port_addr().port());
+        assert_eq!(receiver.recv().await.unwrap(), 5);
+        post(6);
+        assert_eq!(receiver.recv().await.unwrap(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_port_ref_send_batch() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(crate::config::MESSAGE_BATCHING, true);
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let (port, mut receiver) = client.open_port::<u64>();
+        let port_ref = port.bind();
+
+        // Batches and single posts share the port's sequence.
+        port_ref.send_batch(&client, vec![1, 2, 3]).unwrap();
+        port_ref.post(&client, 4);
+        port_ref.send_batch(&client, vec![5, 6]).unwrap();
+        port_ref.send_batch(&client, Vec::new()).unwrap();
+        for expected in 1..=6 {
+            assert_eq!(receiver.recv().await.unwrap(), expected);
+        }
+        assert!(receiver.try_recv().unwrap().is_none());
+
+        // Messages that do not fit the port are returned individually.
+        let (once, once_receiver) = client.open_once_port::<u64>();
+        let once_ref: PortRef<u64> = PortRef::attest(once.bind().into_port_addr());
+        let (return_handle, mut returns) = client.open_port::<Undeliverable<MessageEnvelope>>();
+        let batch = MessageBatch::new::<u64>(
+            [7u64, 8, 9]
+                .iter()
+                .map(|n| wirevalue::Any::serialize(n).unwrap())
+                .collect(),
+        );
+        client.mailbox().post(
+            MessageEnvelope::serialize(
+                client.self_addr().clone(),
+                once_ref.port_addr().clone(),
+                &batch,
+                Flattrs::new(),
+            )
+            .unwrap(),
+            return_handle,
         );
+        assert_eq!(once_receiver.recv().await.unwrap(), 7);
+        for expected in [8, 9] {
+            let returned = returns.recv().await.unwrap().into_message().unwrap();
+            assert_eq!(returned.deserialized::<u64>().unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_port_ref_send_batch_unbatched() {
+        // With batching off (the default), the messages are posted one
+        // at a time, and so reach receivers that predate batching.
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let (port, mut receiver) = client.open_port::<u64>();
+        port.bind().send_batch(&client, vec![1, 2, 3]).unwrap();
+        for expected in 1..=3 {
+            assert_eq!(receiver.recv().await.unwrap(), expected);
+        }
+        assert!(receiver.try_recv().unwrap().is_none());
     }
 
     #[tokio::test]
@@ -4253,6 +6671,52 @@ mod tests {
         assert_eq!(receiver.recv().await.unwrap().get(), &9);
     }
 
+    #[tokio::test]
+    async fn test_mailbox_accum_emit_policy() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+
+        // Every state is delivered, in order.
+        let (port, mut receiver) = client
+            .mailbox()
+            .open_accum_port_with_policy(accum::sum::<u64>(), EmitPolicy::Every);
+        for i in 1..=4 {
+            port.post(&client, i);
+        }
+        for expected in [1, 3, 6, 10] {
+            assert_eq!(receiver.recv().await.unwrap(), expected);
+        }
+
+        // States are delivered when they differ from the last delivered
+        // state by at least 5.
+        let (port, mut receiver) = client.mailbox().open_accum_port_with_policy(
+            accum::sum::<u64>(),
+            EmitPolicy::delta(|delivered: &u64, current: &u64| current - delivered >= 5),
+        );
+        for i in [2, 2, 2, 1, 1, 4] {
+            port.post(&client, i);
+        }
+        assert_eq!(receiver.recv().await.unwrap(), 6);
+        assert_eq!(receiver.recv().await.unwrap(), 12);
+        assert!(receiver.try_recv().unwrap().is_none());
+
+        // The first state is delivered immediately; the rest are
+        // delivered together at the end of the interval.
+        let (port, mut receiver) = client.mailbox().open_accum_port_with_policy(
+            accum::sum::<u64>(),
+            EmitPolicy::Throttle(Duration::from_millis(200)),
+        );
+        port.post(&client, 1);
+        assert_eq!(receiver.recv().await.unwrap(), 1);
+        let start = tokio::time::Instant::now();
+        for i in 2..=4 {
+            port.post(&client, i);
+        }
+        assert!(receiver.try_recv().unwrap().is_none());
+        assert_eq!(receiver.recv().await.unwrap(), 10);
+        assert!(start.elapsed() >= Duration::from_millis(150));
+    }
+
     #[test]
     fn test_port_and_reducer() {
         let mbox = Mailbox::new(test_actor_id("0", "test"));
@@ -4317,6 +6781,148 @@ mod tests {
         assert_matches!(err.location(), PortLocation::Bound(bound) if *bound == *port.port_addr());
     }
 
+    #[tokio::test]
+    async fn test_mailbox_rejects_schema_fingerprint_mismatch() {
+        let mbox = Mailbox::new(test_actor_id("0", "test"));
+        let (port, mut receiver) = mbox.open_port::<crate::actor::StopMode>();
+        let port = port.bind();
+        let (return_handle, mut return_receiver) =
+            crate::mailbox::undeliverable::new_undeliverable_port();
+
+        let data = wirevalue::Any::serialize(&crate::actor::StopMode::DrainAndStop).unwrap();
+        let mut headers = Flattrs::new();
+        headers.set(headers::SCHEMA_FINGERPRINT, 0);
+        mbox.post(
+            MessageEnvelope::new(
+                test_actor_id("1", "sender"),
+                port.port_addr().clone(),
+                data.clone(),
+                headers,
+            ),
+            return_handle.clone(),
+        );
+
+        let envelope = tokio::time::timeout(Duration::from_secs(1), return_receiver.recv())
+            .await
+            .expect("schema mismatch should be returned")
+            .unwrap()
+            .into_message()
+            .expect("expected returned envelope");
+        assert_eq!(
+            root_invalid_reference(&envelope).reason,
+            InvalidReferenceReason::ProtocolMismatch
+        );
+
+        // A matching fingerprint is delivered normally.
+        let mut headers = Flattrs::new();
+        headers::set_schema_fingerprint(&mut headers, &data);
+        mbox.post(
+            MessageEnvelope::new(
+                test_actor_id("1", "sender"),
+                port.port_addr().clone(),
+                data,
+                headers,
+            ),
+            return_handle,
+        );
+        assert_matches!(
+            receiver.recv().await.unwrap(),
+            crate::actor::StopMode::DrainAndStop
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mailbox_drops_duplicate_idempotency_keys() {
+        let config = hyperactor_config::global::lock();
+        let _config_guard = config.override_key(crate::config::IDEMPOTENCY_CACHE_CAPACITY, 2);
+
+        let mbox = Mailbox::new(test_actor_id("0", "test"));
+        let (port, mut receiver) = mbox.open_port::<u64>();
+        let port = port.bind();
+        let (other_port, mut other_receiver) = mbox.open_port::<u64>();
+        let other_port = other_port.bind();
+        let post = |port: &PortRef<u64>, key: &str, value: u64| {
+            let mut headers = Flattrs::new();
+            headers.set(headers::IDEMPOTENCY_KEY, key.to_string());
+            mbox.post(
+                MessageEnvelope::new(
+                    test_actor_id("1", "sender"),
+                    port.port_addr().clone(),
+                    wirevalue::Any::serialize(&value).unwrap(),
+                    headers,
+                ),
+                monitored_return_handle(),
+            );
+        };
+
+        post(&port, "a", 1);
+        post(&port, "a", 2);
+        post(&port, "b", 3);
+        // Keys are tracked per port.
+        post(&other_port, "a", 4);
+        assert_eq!(receiver.recv().await.unwrap(), 1);
+        assert_eq!(receiver.recv().await.unwrap(), 3);
+        assert_eq!(other_receiver.recv().await.unwrap(), 4);
+
+        // "a" was seen most recently, so "b" is evicted by "c".
+        post(&port, "a", 5);
+        post(&port, "c", 6);
+        post(&port, "b", 7);
+        post(&port, "c", 8);
+        assert_eq!(receiver.recv().await.unwrap(), 6);
+        assert_eq!(receiver.recv().await.unwrap(), 7);
+        assert!(receiver.try_recv().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_port_ref_local_post_skips_serialization() {
+        static SERIALIZED: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Debug, PartialEq, Deserialize, typeuri::Named)]
+        struct Counted(u64);
+
+        impl Serialize for Counted {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                SERIALIZED.fetch_add(1, Ordering::SeqCst);
+                serializer.serialize_newtype_struct("Counted", &self.0)
+            }
+        }
+
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let (port, mut receiver) = client.open_port::<Counted>();
+        let port = port.bind();
+
+        port.post(&client, Counted(1));
+        port.post(&client, Counted(2));
+        assert_eq!(receiver.recv().await.unwrap(), Counted(1));
+        assert_eq!(receiver.recv().await.unwrap(), Counted(2));
+        assert_eq!(SERIALIZED.load(Ordering::SeqCst), 0);
+
+        // Idempotency keys are checked on serialized envelopes, so this
+        // send falls back to serialization, in sequence with the rest.
+        let mut headers = Flattrs::new();
+        headers.set(headers::IDEMPOTENCY_KEY, "a".to_string());
+        port.post_with_headers(&client, headers, Counted(3));
+        port.post(&client, Counted(4));
+        assert_eq!(receiver.recv().await.unwrap(), Counted(3));
+        assert_eq!(receiver.recv().await.unwrap(), Counted(4));
+        assert_eq!(SERIALIZED.load(Ordering::SeqCst), 1);
+
+        // A failed by-value send evicts the closed port, as a serialized
+        // send would.
+        drop(receiver);
+        let mut port = port;
+        port.return_undeliverable(false);
+        port.post(&client, Counted(5));
+        assert!(
+            client
+                .mailbox()
+                .local_sender::<Counted>(port.port_addr())
+                .is_none()
+        );
+    }
+
     #[tokio::test]
     async fn test_mailbox_type_mismatch_does_not_evict_unbounded_port() {
         let mbox = Mailbox::new(test_actor_id("0", "test"));
@@ -4421,6 +7027,68 @@ mod tests {
         );
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn test_mailbox_once_expiry() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let mbox = client.mailbox();
+
+        // Delivered before expiry.
+        let (port, receiver) = mbox.open_once_port_with_expiry::<u64>(Duration::from_secs(10));
+        port.post(&client, 1u64);
+        assert_eq!(receiver.recv().await.unwrap(), 1);
+
+        // Expires while the receiver is waiting.
+        let (port, receiver) = mbox.open_once_port_with_expiry::<u64>(Duration::from_secs(10));
+        let port_ref = port.bind();
+        let err = receiver.recv().await.unwrap_err();
+        assert_matches!(
+            err.kind(),
+            MailboxErrorKind::Timeout(port_addr, expiry)
+                if port_addr == port_ref.port_addr() && *expiry == Duration::from_secs(10)
+        );
+
+        // Expires while the receiver is kept: the late message is
+        // returned, not leaked.
+        let (port, _receiver) = mbox.open_once_port_with_expiry::<u64>(Duration::from_secs(10));
+        let port_ref = port.bind();
+        tokio::time::sleep(Duration::from_secs(11)).await;
+        let (return_handle, mut return_rx) = undeliverable::new_undeliverable_port();
+        mbox.post(
+            MessageEnvelope::serialize(
+                mbox.actor_addr().clone(),
+                port_ref.port_addr().clone(),
+                &2u64,
+                Flattrs::new(),
+            )
+            .unwrap(),
+            return_handle,
+        );
+        let undelivered = return_rx
+            .recv()
+            .await
+            .unwrap()
+            .into_message()
+            .expect("expected returned envelope");
+        assert_eq!(undelivered.deserialized::<u64>().unwrap(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_mailbox_once_expiry_timer_stops_with_receiver() {
+        let mbox = Mailbox::new(test_actor_id("0", "test"));
+        let metrics = tokio::runtime::Handle::current().metrics();
+        let tasks = metrics.num_alive_tasks();
+
+        // The expiry timer does not outlive the receiver.
+        let (_port, receiver) = mbox.open_once_port_with_expiry::<u64>(Duration::from_secs(3600));
+        assert_eq!(metrics.num_alive_tasks(), tasks + 1);
+        drop(receiver);
+        for _ in 0..8 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(metrics.num_alive_tasks(), tasks);
+    }
+
     #[tokio::test]
     async fn test_mailbox_once_type_mismatch_preserves_sender_until_delivery() {
         let mbox = Mailbox::new(test_actor_id("0", "test"));
@@ -4494,6 +7162,47 @@ mod tests {
         assert!(receiver.drain().is_empty());
     }
 
+    #[tokio::test]
+    async fn test_recv_batch() {
+        let mbox = Mailbox::new(test_actor_id("0", "test"));
+        let (port, mut receiver) = mbox.open_port();
+        let port = port.bind();
+
+        for i in 0..5 {
+            mbox.serialize_and_send(&port, i, monitored_return_handle())
+                .unwrap();
+        }
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+        assert_eq!(
+            receiver.recv_batch(3, deadline).await.unwrap(),
+            vec![0, 1, 2]
+        );
+
+        // Fewer than `max` messages arrive before the deadline.
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(100);
+        assert_eq!(receiver.recv_batch(3, deadline).await.unwrap(), vec![3, 4]);
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(100);
+        assert!(receiver.recv_batch(3, deadline).await.unwrap().is_empty());
+
+        // Messages that arrive while waiting are included.
+        let sender = {
+            let mbox = mbox.clone();
+            tokio::spawn(async move {
+                for i in 5..8 {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    mbox.serialize_and_send(&port, i, monitored_return_handle())
+                        .unwrap();
+                }
+            })
+        };
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+        assert_eq!(
+            receiver.recv_batch(3, deadline).await.unwrap(),
+            vec![5, 6, 7]
+        );
+        sender.await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_mailbox_muxer() {
         let muxer = MailboxMuxer::new();
@@ -4644,32 +7353,183 @@ mod tests {
 
         // Test undeliverable messages, and that it is delivered with the appropriate fallback.
 
-        let mbox4 = Mailbox::new(test_actor_id("fallback_0", "actor"));
+        let mbox4 = Mailbox::new(test_actor_id("fallback_0", "actor"));
+
+        let (return_handle, mut return_receiver) =
+            crate::mailbox::undeliverable::new_undeliverable_port();
+        let (port, _receiver) = mbox4.open_once_port();
+        let port = port.bind();
+        let target: Addr = port.port_addr().clone().into();
+        router
+            .serialize_and_send_once(port, 0, return_handle.clone())
+            .unwrap();
+        let undelivered = return_receiver
+            .recv()
+            .await
+            .unwrap()
+            .into_message()
+            .expect("expected returned envelope");
+        let transport = root_transport_failure(&undelivered);
+        assert_eq!(transport.target, target);
+        assert_eq!(transport.reason, TransportFailureReason::NoRoute);
+
+        let router = router.fallback(mbox4.clone().into_boxed());
+        let (port, receiver) = mbox4.open_once_port();
+        router
+            .serialize_and_send_once(port.bind(), 0, return_handle)
+            .unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_mailbox_router_routing_policy() {
+        let mbox = Mailbox::new(test_actor_id("world0_0", "actor0"));
+        let (return_handle, mut return_receiver) =
+            crate::mailbox::undeliverable::new_undeliverable_port();
+
+        for (router, port_routed) in [
+            (MailboxRouter::new(), false),
+            (
+                MailboxRouter::new().with_routing_policy(routing::ExactMatchFirst),
+                true,
+            ),
+        ] {
+            let (port, receiver) = mbox.open_once_port::<u64>();
+            let port = port.bind();
+            let (other_port, _other_receiver) = mbox.open_once_port::<u64>();
+            let other_port = other_port.bind();
+            router.bind(
+                test_actor_ref("world0_0", "actor0"),
+                UnroutableMailboxSender,
+            );
+            router.bind(port.port_addr().clone(), mbox.clone());
+
+            router
+                .serialize_and_send_once(port, 1, return_handle.clone())
+                .unwrap();
+            if port_routed {
+                assert_eq!(receiver.recv().await.unwrap(), 1);
+            } else {
+                let undelivered = return_receiver
+                    .recv()
+                    .await
+                    .unwrap()
+                    .into_message()
+                    .unwrap();
+                assert_eq!(
+                    root_transport_failure(&undelivered).reason,
+                    TransportFailureReason::NoRoute
+                );
+            }
+
+            // Other ports are routed through the actor's route.
+            router
+                .serialize_and_send_once(other_port, 2, return_handle.clone())
+                .unwrap();
+            let undelivered = return_receiver
+                .recv()
+                .await
+                .unwrap()
+                .into_message()
+                .unwrap();
+            assert_eq!(
+                root_transport_failure(&undelivered).reason,
+                TransportFailureReason::NoRoute
+            );
+        }
+    }
+
+    /// A sender that panics when it is dropped.
+    #[derive(Debug)]
+    struct PanicOnDropSender;
+
+    #[async_trait]
+    impl MailboxSender for PanicOnDropSender {
+        fn post_unchecked(
+            &self,
+            envelope: MessageEnvelope,
+            return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+        ) {
+            UnroutableMailboxSender.post_unchecked(envelope, return_handle)
+        }
+    }
+
+    impl Drop for PanicOnDropSender {
+        fn drop(&mut self) {
+            panic!("sender dropped");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mailbox_router_survives_panicking_unbind() {
+        let mbox = Mailbox::new(test_actor_id("world0_0", "actor0"));
+        let router = MailboxRouter::new();
+        router.bind(test_proc_ref("world0_0"), mbox.clone());
+        router.bind(test_proc_ref("world1_0"), PanicOnDropSender);
+
+        // Unbinding drops the sender, which panics. This used to
+        // poison the routes' lock, failing every later post.
+        let unbind = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            router.unbind(&test_proc_ref("world1_0"))
+        }));
+        assert!(unbind.is_err());
+
+        let (port, receiver) = mbox.open_once_port::<u64>();
+        router
+            .serialize_and_send_once(port.bind(), 1, monitored_return_handle())
+            .unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), 1);
 
+        // Changes still apply.
+        router.unbind(&test_proc_ref("world0_0"));
         let (return_handle, mut return_receiver) =
             crate::mailbox::undeliverable::new_undeliverable_port();
-        let (port, _receiver) = mbox4.open_once_port();
-        let port = port.bind();
-        let target: Addr = port.port_addr().clone().into();
+        let (port, _receiver) = mbox.open_once_port::<u64>();
         router
-            .serialize_and_send_once(port, 0, return_handle.clone())
+            .serialize_and_send_once(port.bind(), 2, return_handle)
             .unwrap();
         let undelivered = return_receiver
             .recv()
             .await
             .unwrap()
             .into_message()
-            .expect("expected returned envelope");
-        let transport = root_transport_failure(&undelivered);
-        assert_eq!(transport.target, target);
-        assert_eq!(transport.reason, TransportFailureReason::NoRoute);
-
-        let router = router.fallback(mbox4.clone().into_boxed());
-        let (port, receiver) = mbox4.open_once_port();
-        router
-            .serialize_and_send_once(port.bind(), 0, return_handle)
             .unwrap();
-        assert_eq!(receiver.recv().await.unwrap(), 0);
+        assert_eq!(
+            root_transport_failure(&undelivered).reason,
+            TransportFailureReason::NoRoute
+        );
+    }
+
+    #[test]
+    fn test_dial_mailbox_router_routing_policy() {
+        let router = DialMailboxRouter::new().with_routing_policy(
+            routing::StaticOverrides::new(routing::LongestPrefix)
+                .with_override(test_proc_ref("world0_0"), test_proc_ref("forwarder")),
+        );
+        router.bind(test_proc_ref("world0_0"), "unix!@1".parse().unwrap());
+        router.bind(test_proc_ref("world1_0"), "unix!@2".parse().unwrap());
+        router.bind(test_proc_ref("forwarder"), "unix!@3".parse().unwrap());
+
+        assert_eq!(
+            router
+                .lookup_addr(&test_actor_id("world0_0", "actor"))
+                .unwrap(),
+            "unix!@3".parse().unwrap(),
+        );
+        assert_eq!(
+            router
+                .lookup_addr(&test_actor_id("world1_0", "actor"))
+                .unwrap(),
+            "unix!@2".parse().unwrap(),
+        );
+        // Without the forwarder, the override no longer applies.
+        router.unbind(&test_proc_ref("forwarder"));
+        assert_eq!(
+            router
+                .lookup_addr(&test_actor_id("world0_0", "actor"))
+                .unwrap(),
+            "unix!@1".parse().unwrap(),
+        );
     }
 
     #[tokio::test]
@@ -4798,6 +7658,66 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_dial_mailbox_router_channel_class() {
+        let actor = test_actor_id("class_0", "actor0");
+        let (addr, _rx) =
+            channel::serve::<MessageEnvelope>(ChannelAddr::any(ChannelTransport::Local)).unwrap();
+
+        // Clients are of the configured class by default.
+        {
+            let config = hyperactor_config::global::lock();
+            let _guard = config.override_key(
+                crate::config::CHANNEL_CLASS,
+                shaping::ChannelClass::Telemetry,
+            );
+            let router = DialMailboxRouter::new();
+            assert_eq!(
+                router.dial(&addr, &actor).unwrap().client.class(),
+                shaping::ChannelClass::Telemetry
+            );
+        }
+
+        let router = DialMailboxRouter::new().with_channel_class(shaping::ChannelClass::Bulk);
+        assert_eq!(
+            router.dial(&addr, &actor).unwrap().client.class(),
+            shaping::ChannelClass::Bulk
+        );
+    }
+
+    #[test]
+    fn test_dial_mailbox_router_concurrent_binds() {
+        let router = DialMailboxRouter::new();
+        router.bind(test_proc_ref("base"), "unix!@base".parse().unwrap());
+
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let router = &router;
+                scope.spawn(move || {
+                    for i in 0..50 {
+                        let proc = format!("world_{}_{}", thread, i);
+                        let addr: ChannelAddr = format!("unix!@{}_{}", thread, i).parse().unwrap();
+                        router.bind(test_proc_ref(&proc), addr.clone());
+                        assert_eq!(
+                            router.lookup_addr(&test_actor_id(&proc, "actor")),
+                            Some(addr)
+                        );
+                        // Lookups under an unrelated prefix are unaffected.
+                        assert_eq!(
+                            router.lookup_addr(&test_actor_id("base", "actor")),
+                            Some("unix!@base".parse().unwrap())
+                        );
+                    }
+                });
+            }
+        });
+
+        // No binding was lost to a concurrent change.
+        assert_eq!(router.prefixes().len(), 1 + 4 * 50);
+        router.unbind(&test_proc_ref("world_0_0"));
+        assert_eq!(router.prefixes().len(), 4 * 50);
+    }
+
     #[test]
     fn test_dial_mailbox_router_canonicalizes_alias_addresses() {
         let router = DialMailboxRouter::new();
@@ -4945,7 +7865,7 @@ mod tests {
         let router = DialMailboxRouter::new();
         router.bind(Addr::from(mbox.actor_addr().clone()), addr.clone());
 
-        let client1 = router.dial(&addr, mbox.actor_addr()).unwrap();
+        let client1 = router.dial(&addr, mbox.actor_addr()).unwrap().client;
         let mut status = client1.tx_status().clone();
 
         // LocalRx::drop closes the watcher with a non-stale reason — the
@@ -4958,12 +7878,12 @@ mod tests {
         }
         assert!(!is_stale_session_close(&status.borrow()));
 
-        let client2 = router.dial(&addr, mbox.actor_addr()).unwrap();
+        let client2 = router.dial(&addr, mbox.actor_addr()).unwrap().client;
         assert!(
             Arc::ptr_eq(&client1, &client2),
             "router must not evict a client closed for a non-stale reason"
         );
-        let client3 = router.dial(&addr, mbox.actor_addr()).unwrap();
+        let client3 = router.dial(&addr, mbox.actor_addr()).unwrap().client;
         assert!(Arc::ptr_eq(&client1, &client3));
     }
 
@@ -5387,7 +8307,7 @@ mod tests {
         Ok(messeges)
     }
 
-    #[async_timed_test(timeout_secs = 30)]
+    #[async_timed_test(timeout_secs = 30, start_paused = true)]
     async fn test_split_port_id_sum_reducer() {
         let config = hyperactor_config::global::lock();
         let _config_guard = config.override_key(crate::config::SPLIT_MAX_BUFFER_SIZE, 1);
@@ -5465,7 +8385,7 @@ mod tests {
         assert_eq!(msg, None);
     }
 
-    #[async_timed_test(timeout_secs = 30)]
+    #[async_timed_test(timeout_secs = 30, start_paused = true)]
     async fn test_split_port_timeout_flush() {
         let config = hyperactor_config::global::lock();
         let _config_guard = config.override_key(crate::config::SPLIT_MAX_BUFFER_SIZE, 100);
@@ -5509,7 +8429,7 @@ mod tests {
         assert_eq!(msg, None);
     }
 
-    #[async_timed_test(timeout_secs = 30)]
+    #[async_timed_test(timeout_secs = 30, start_paused = true)]
     async fn test_split_port_timeout_and_size_flush() {
         let config = hyperactor_config::global::lock();
         let _config_guard = config.override_key(crate::config::SPLIT_MAX_BUFFER_SIZE, 3);
@@ -5706,6 +8626,131 @@ mod tests {
         assert_eq!(msg, None);
     }
 
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_split_port_closed_with_parent() {
+        let proc = Proc::isolated();
+        let actor0 = proc.client("actor0");
+        let actor1 = proc.client("actor1");
+        let (port_handle, receiver) = actor0.open_port::<u64>();
+        let port_id = port_handle.bind().port_addr().clone();
+
+        // Split the port on another actor, and split the split port.
+        let split = port_id
+            .split(&actor1, None, ReducerMode::default(), true)
+            .unwrap();
+        let split_split = split
+            .split(&actor1, None, ReducerMode::default(), true)
+            .unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let _observer = actor1.mailbox().observe_ports({
+            let events = Arc::clone(&events);
+            move |event: &PortEvent| events.lock().unwrap().push(event.clone())
+        });
+
+        // Dropping the receiver unbinds the split ports.
+        drop(receiver);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                PortEvent::Unbound(split.clone()),
+                PortEvent::Unbound(split_split.clone())
+            ]
+        );
+
+        // Messages posted to them are returned.
+        let (undeliverable_handle, mut undeliverable_receiver) =
+            undeliverable::new_undeliverable_port();
+        for dest in [&split, &split_split] {
+            actor1.mailbox().post(
+                MessageEnvelope::new(
+                    actor1.mailbox().actor_addr().clone(),
+                    dest.clone(),
+                    wirevalue::Any::serialize(&1u64).unwrap(),
+                    Flattrs::new(),
+                ),
+                undeliverable_handle.clone(),
+            );
+            let undeliverable = undeliverable_receiver.recv().await.unwrap();
+            assert_eq!(undeliverable.into_message().unwrap().dest(), dest);
+        }
+    }
+
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_split_port_closed_with_remote_parent() {
+        let proc = Proc::isolated();
+        let actor = proc.client("actor");
+        // A port in another proc.
+        let parent = test_actor_id("remote", "actor").port_addr(Port::from(7));
+        let split = parent
+            .split(&actor, None, ReducerMode::default(), true)
+            .unwrap();
+        let split_split = split
+            .split(&actor, None, ReducerMode::default(), true)
+            .unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let _observer = actor.mailbox().observe_ports({
+            let events = Arc::clone(&events);
+            move |event: &PortEvent| events.lock().unwrap().push(event.clone())
+        });
+
+        let returned = |reason: UndeliverableReason| {
+            let mut envelope = MessageEnvelope::new(
+                actor.mailbox().actor_addr().clone(),
+                parent.clone(),
+                wirevalue::Any::serialize(&1u64).unwrap(),
+                Flattrs::new(),
+            );
+            envelope.push_delivery_failure(DeliveryFailure::new(reason));
+            Undeliverable::Returned(envelope)
+        };
+
+        // A transport failure does not mean the parent is gone.
+        actor
+            .mailbox()
+            .unbind_returned_splits(&returned(UndeliverableReason::Transport(
+                TransportFailure::new(
+                    parent.clone(),
+                    TransportFailureReason::LinkUnavailable("test".to_string()),
+                ),
+            )));
+        assert!(events.lock().unwrap().is_empty());
+
+        // A message returned because the parent is gone unbinds the
+        // split ports.
+        actor
+            .mailbox()
+            .unbind_returned_splits(&returned(UndeliverableReason::PortGone(PortGone::new(
+                parent.clone(),
+                None,
+            ))));
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                PortEvent::Unbound(split.clone()),
+                PortEvent::Unbound(split_split.clone())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_ports_pruned() {
+        let bound = |port: &u64| port % 2 == 0;
+        let mut splits = SplitPorts::default();
+        for port in 0..100 {
+            splits.push(port, bound);
+            // Unbound ports are pruned once the list has doubled.
+            assert!(splits.ports.len() <= 2 * splits.ports.iter().filter(|p| bound(p)).count() + 8);
+        }
+        assert!(splits.ports.len() < 100);
+        assert!(
+            (0..100)
+                .filter(bound)
+                .all(|port| splits.ports.contains(&port))
+        );
+    }
+
     #[test]
     fn test_dial_mailbox_router_prefixes_empty() {
         assert_eq!(DialMailboxRouter::new().prefixes().len(), 0);
@@ -6215,6 +9260,266 @@ mod tests {
         serve_handle.await.unwrap().unwrap();
     }
 
+    /// A tx that holds on to posted messages, acknowledging them only
+    /// when released.
+    struct HoldingTx {
+        held: Arc<Mutex<Vec<oneshot::Sender<SendError<MessageEnvelope>>>>>,
+        status: watch::Receiver<TxStatus>,
+        _status_tx: watch::Sender<TxStatus>,
+    }
+
+    #[async_trait]
+    impl channel::Tx<MessageEnvelope> for HoldingTx {
+        fn do_post(
+            &self,
+            _message: MessageEnvelope,
+            return_channel: Option<oneshot::Sender<SendError<MessageEnvelope>>>,
+        ) {
+            self.held.lock().unwrap().extend(return_channel);
+        }
+
+        fn addr(&self) -> ChannelAddr {
+            ChannelAddr::any(ChannelTransport::Local)
+        }
+
+        fn status(&self) -> &watch::Receiver<TxStatus> {
+            &self.status
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mailbox_client_window() {
+        let config = hyperactor_config::global::lock();
+        let _config_guard = config.override_key(crate::config::MAILBOX_CLIENT_WINDOW, 2);
+
+        let held = Arc::new(Mutex::new(Vec::new()));
+        let (status_tx, status) = watch::channel(TxStatus::Active);
+        let client = MailboxClient::new(HoldingTx {
+            held: Arc::clone(&held),
+            status,
+            _status_tx: status_tx,
+        });
+        let mbox = Mailbox::new(test_actor_id("0", "actor0"));
+        let (port, _receiver) = mbox.open_port::<u64>();
+        let port = port.bind();
+
+        assert_eq!(client.credits(), Some(2));
+        for i in 0..5u64 {
+            client
+                .serialize_and_send(&port, i, monitored_return_handle())
+                .unwrap();
+        }
+        assert_eq!(client.credits(), Some(0));
+
+        // Only a window's worth of messages is transmitted.
+        let transmitted = |n: usize| {
+            let held = Arc::clone(&held);
+            async move {
+                while held.lock().unwrap().len() < n {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), transmitted(2))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(held.lock().unwrap().len(), 2);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), client.ready())
+                .await
+                .is_err()
+        );
+
+        // Acknowledging the messages grants credits for the rest.
+        held.lock().unwrap().clear();
+        tokio::time::timeout(Duration::from_secs(5), transmitted(2))
+            .await
+            .unwrap();
+        held.lock().unwrap().clear();
+        tokio::time::timeout(Duration::from_secs(5), transmitted(1))
+            .await
+            .unwrap();
+        held.lock().unwrap().clear();
+        tokio::time::timeout(Duration::from_secs(5), client.flush())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(client.credits(), Some(2));
+        client.ready().await;
+    }
+
+    /// A sender to a mailbox with a configurable downstream backlog
+    /// per destination.
+    #[derive(Clone, Debug)]
+    struct BackloggedSender {
+        mailbox: Mailbox,
+        backlogs: Arc<Mutex<HashMap<PortAddr, u64>>>,
+        drained: Arc<tokio::sync::Notify>,
+    }
+
+    impl BackloggedSender {
+        fn set_backlog(&self, dest: &PortAddr, backlog: u64) {
+            self.backlogs.lock().unwrap().insert(dest.clone(), backlog);
+            self.drained.notify_waiters();
+        }
+    }
+
+    #[async_trait]
+    impl MailboxSender for BackloggedSender {
+        fn post_unchecked(
+            &self,
+            envelope: MessageEnvelope,
+            return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+        ) {
+            self.mailbox.post_unchecked(envelope, return_handle);
+        }
+
+        fn backlog(&self, dest: &PortAddr) -> u64 {
+            self.backlogs
+                .lock()
+                .unwrap()
+                .get(dest)
+                .copied()
+                .unwrap_or(0)
+        }
+
+        fn backlog_drained(&self) -> Option<Arc<tokio::sync::Notify>> {
+            Some(Arc::clone(&self.drained))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mailbox_server_window() {
+        let config = hyperactor_config::global::lock();
+        let _config_guard = config.override_key(crate::config::MAILBOX_SERVER_WINDOW, 4);
+
+        let mbox = Mailbox::new(test_actor_id("0", "actor0"));
+        let sender = BackloggedSender {
+            mailbox: mbox.clone(),
+            backlogs: Arc::new(Mutex::new(HashMap::new())),
+            drained: Arc::new(tokio::sync::Notify::new()),
+        };
+        let (tx, rx) = channel::local::new();
+        let serve_handle = sender.clone().serve(rx);
+        let client = MailboxClient::new(tx);
+
+        let (blocked, mut blocked_receiver) = mbox.open_port::<u64>();
+        let blocked = blocked.bind();
+        let (open, mut open_receiver) = mbox.open_port::<u64>();
+        let open = open.bind();
+        sender.set_backlog(blocked.port_addr(), 4);
+
+        client
+            .serialize_and_send(&blocked, 123u64, monitored_return_handle())
+            .unwrap();
+        client
+            .serialize_and_send(&open, 456u64, monitored_return_handle())
+            .unwrap();
+
+        // Messages to other destinations are not held up behind the
+        // backlogged one.
+        let message = tokio::time::timeout(Duration::from_secs(5), open_receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(message, 456u64);
+
+        // The backlogged destination's message is held back.
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), blocked_receiver.recv())
+                .await
+                .is_err()
+        );
+
+        // It is released once the backlog drains.
+        sender.set_backlog(blocked.port_addr(), 3);
+        let message = tokio::time::timeout(Duration::from_secs(5), blocked_receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(message, 123u64);
+
+        serve_handle.stop("test done");
+        serve_handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_spilled_payload_delivery() {
+        let config = hyperactor_config::global::lock();
+        let _config_guard = config.override_key(crate::config::MESSAGE_SPILL_THRESHOLD, 1024);
+
+        let mbox = Mailbox::new(test_actor_id("0", "actor0"));
+        let (tx, rx) = channel::local::new();
+        let serve_handle = mbox.clone().serve(rx);
+        let client = MailboxClient::new(tx);
+
+        let (port, mut receiver) = mbox.open_port::<Vec<u8>>();
+        let port = port.bind();
+        let large: Vec<u8> = (0..1_000_000u32).map(|i| i as u8).collect();
+        client
+            .serialize_and_send(&port, large.clone(), monitored_return_handle())
+            .unwrap();
+        client
+            .serialize_and_send(&port, vec![1, 2, 3], monitored_return_handle())
+            .unwrap();
+
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            received.push(
+                tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+                    .await
+                    .unwrap()
+                    .unwrap(),
+            );
+        }
+        // The small message is not delivered ahead of the spilled one.
+        assert_eq!(received, vec![large, vec![1, 2, 3]]);
+
+        serve_handle.stop("test done");
+        serve_handle.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_compressed_payload_delivery() {
+        let config = hyperactor_config::global::lock();
+        let _config_guard =
+            config.override_key(crate::config::MESSAGE_COMPRESSION_DICTIONARY_SIZE, 1024);
+
+        let message = |i: u32| format!("{{\"metric\": \"mailbox.posts\", \"value\": {}}}", i);
+        let samples: Vec<Vec<u8>> = (0..1000)
+            .map(|i| {
+                let data = wirevalue::Any::serialize(&message(i)).unwrap();
+                bincode::serde::encode_to_vec(&data, bincode::config::legacy()).unwrap()
+            })
+            .collect();
+        let dictionary = compress::Dictionary::train(String::typehash(), &samples).unwrap();
+        compress::install(dictionary);
+
+        let mbox = Mailbox::new(test_actor_id("0", "actor0"));
+        let (tx, rx) = channel::local::new();
+        let serve_handle = mbox.clone().serve(rx);
+        let client = MailboxClient::new(tx);
+
+        let (port, mut receiver) = mbox.open_port::<String>();
+        let port = port.bind();
+        for i in 0..3 {
+            client
+                .serialize_and_send(&port, message(i), monitored_return_handle())
+                .unwrap();
+        }
+        for i in 0..3 {
+            let received = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(received, message(i));
+        }
+
+        serve_handle.stop("test done");
+        serve_handle.await.unwrap().unwrap();
+    }
+
     #[test]
     fn test_drain_waits_for_active_handler_enqueue() {
         let mailbox = Mailbox::new(test_actor_id("drain", "actor"));
@@ -0,0 +1,650 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Dictionary compression of message payloads.
+//!
+//! Small messages compress poorly on their own, but messages of the same
+//! type, sent at high volume (e.g. telemetry updates), share most of
+//! their structure. A zstd [`Dictionary`] trained on samples of a
+//! type's payloads captures that structure, so that each payload
+//! compresses to a fraction of its size.
+//!
+//! Dictionaries are kept in a process-wide table, by the typehash of the
+//! messages they compress. A dictionary is either trained in-process,
+//! by sampling the payloads that mailbox clients send after
+//! [`train`] is called for a type, or trained elsewhere and
+//! [installed](install) directly.
+//!
+//! A [`MailboxClient`] compresses each payload of at least
+//! [`MESSAGE_COMPRESSION_MIN_BYTES`] bytes whose type has a dictionary,
+//! and sends it as a [`CompressedPayload`] in place of the original.
+//! The first payload compressed with a dictionary on each link carries
+//! the dictionary itself, which the receiving [`MailboxServer`] keeps
+//! before decompressing; subsequent payloads refer to the dictionary by
+//! its id.
+//!
+//! Received dictionaries are scoped to the link (one per
+//! [`MailboxClient`]) that sent them, so that each link starts afresh:
+//! the new links dialed to a restarted peer send it their dictionaries
+//! again. At most [`MESSAGE_COMPRESSION_MAX_DICTIONARIES`] of them are
+//! kept; the least recently used is dropped beyond it.
+//!
+//! [`MailboxClient`]: crate::mailbox::MailboxClient
+//! [`MailboxServer`]: crate::mailbox::MailboxServer
+//! [`MESSAGE_COMPRESSION_MIN_BYTES`]: crate::config::MESSAGE_COMPRESSION_MIN_BYTES
+//! [`MESSAGE_COMPRESSION_MAX_DICTIONARIES`]: crate::config::MESSAGE_COMPRESSION_MAX_DICTIONARIES
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+
+use hyperactor_config::global;
+use serde::Deserialize;
+use serde::Serialize;
+use typeuri::Named;
+use zstd::dict::DecoderDictionary;
+use zstd::dict::EncoderDictionary;
+
+use crate::blob_cache::Digest;
+use crate::config::CODEC_MAX_FRAME_LENGTH;
+use crate::config::MESSAGE_COMPRESSION_DICTIONARY_SIZE;
+use crate::config::MESSAGE_COMPRESSION_LEVEL;
+use crate::config::MESSAGE_COMPRESSION_MAX_DICTIONARIES;
+use crate::config::MESSAGE_COMPRESSION_MIN_BYTES;
+use crate::config::MESSAGE_COMPRESSION_TRAINING_SAMPLES;
+use crate::config::MESSAGE_SPILL_THRESHOLD;
+use crate::mailbox::MessageEnvelope;
+
+/// Errors that occur when training dictionaries or decompressing
+/// payloads.
+#[derive(thiserror::Error, Debug)]
+pub enum CompressionError {
+    /// zstd failed to train, compress, or decompress.
+    #[error("zstd error: {0}")]
+    Zstd(#[from] std::io::Error),
+
+    /// The payload could not be encoded or decoded.
+    #[error("failed to encode compressed payload: {0}")]
+    Encoding(String),
+
+    /// The payload refers to a dictionary that is not installed.
+    #[error("compression dictionary {0:016x} is not installed")]
+    MissingDictionary(u64),
+
+    /// The payload would decompress to more than the maximum frame
+    /// length ([`CODEC_MAX_FRAME_LENGTH`]).
+    #[error("decompressed payload of {len} bytes exceeds the maximum of {max} bytes")]
+    TooLarge { len: u64, max: usize },
+}
+
+/// A zstd dictionary for the payloads of one message type.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Named)]
+pub struct Dictionary {
+    /// The dictionary's id, derived from its contents.
+    id: u64,
+    /// The typehash of the messages the dictionary compresses.
+    typehash: u64,
+    bytes: Vec<u8>,
+}
+wirevalue::register_type!(Dictionary);
+
+impl Dictionary {
+    /// A dictionary for messages with typehash `typehash`, from the raw
+    /// zstd dictionary `bytes`.
+    pub fn new(typehash: u64, bytes: Vec<u8>) -> Self {
+        let digest = Digest::of(&bytes);
+        let id = u64::from_be_bytes(digest.as_bytes()[..8].try_into().unwrap());
+        Self {
+            id,
+            typehash,
+            bytes,
+        }
+    }
+
+    /// Train a dictionary for messages with typehash `typehash` from
+    /// samples of their encoded payloads.
+    pub fn train<S: AsRef<[u8]>>(typehash: u64, samples: &[S]) -> Result<Self, CompressionError> {
+        let max_size = global::get(MESSAGE_COMPRESSION_DICTIONARY_SIZE);
+        Ok(Self::new(
+            typehash,
+            zstd::dict::from_samples(samples, max_size)?,
+        ))
+    }
+
+    /// The dictionary's id.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// The typehash of the messages the dictionary compresses.
+    pub fn typehash(&self) -> u64 {
+        self.typehash
+    }
+
+    /// The raw zstd dictionary.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl fmt::Debug for Dictionary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dictionary")
+            .field("id", &format_args!("{:016x}", self.id))
+            .field("typehash", &self.typehash)
+            .field("len", &self.bytes.len())
+            .finish()
+    }
+}
+
+/// A compressed payload, sent in place of the original.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct CompressedPayload {
+    /// The id of the link on which the payload was sent.
+    link: u64,
+    /// The id of the dictionary the payload was compressed with.
+    dictionary_id: u64,
+    /// The dictionary itself, when this is the first payload compressed
+    /// with it on the link.
+    dictionary: Option<Dictionary>,
+    /// The size of the encoded payload before compression.
+    len: u64,
+    bytes: Vec<u8>,
+}
+wirevalue::register_type!(CompressedPayload);
+
+/// A dictionary, prepared for compression and decompression.
+struct Prepared {
+    dictionary: Dictionary,
+    encoder: EncoderDictionary<'static>,
+    decoder: DecoderDictionary<'static>,
+}
+
+impl Prepared {
+    fn new(dictionary: Dictionary) -> Self {
+        let level = global::get(MESSAGE_COMPRESSION_LEVEL);
+        Self {
+            encoder: EncoderDictionary::copy(&dictionary.bytes, level),
+            decoder: DecoderDictionary::copy(&dictionary.bytes),
+            dictionary,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Dictionaries {
+    /// The dictionary used to compress each type, by typehash.
+    by_type: HashMap<u64, Arc<Prepared>>,
+    /// Dictionaries received from peers, by the link that sent them and
+    /// their id, along with when they were last used.
+    received: HashMap<(u64, u64), (Arc<Prepared>, u64)>,
+    /// The number of uses of received dictionaries so far, by which
+    /// they are ordered for eviction.
+    uses: u64,
+    /// Sampled payloads of the types being trained, by typehash.
+    training: HashMap<u64, Vec<Vec<u8>>>,
+}
+
+impl Dictionaries {
+    /// Keep `dictionary`, received on `link`, dropping the least
+    /// recently used received dictionaries beyond
+    /// [`MESSAGE_COMPRESSION_MAX_DICTIONARIES`].
+    fn receive(&mut self, link: u64, dictionary: Dictionary) {
+        let key = (link, dictionary.id);
+        if self.received.contains_key(&key) {
+            return;
+        }
+        // Links that send the same dictionary share its prepared form.
+        let prepared = self
+            .received
+            .values()
+            .map(|(prepared, _)| prepared)
+            .find(|prepared| prepared.dictionary.id == dictionary.id)
+            .cloned()
+            .unwrap_or_else(|| Arc::new(Prepared::new(dictionary)));
+        let max = global::get(MESSAGE_COMPRESSION_MAX_DICTIONARIES).max(1);
+        while self.received.len() >= max {
+            let Some(lru) = self
+                .received
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| *key)
+            else {
+                break;
+            };
+            self.received.remove(&lru);
+        }
+        self.uses += 1;
+        self.received.insert(key, (prepared, self.uses));
+    }
+
+    /// The dictionary `id` received on `link`, if it is kept.
+    fn lookup(&mut self, link: u64, id: u64) -> Option<Arc<Prepared>> {
+        self.uses += 1;
+        let (prepared, used) = self.received.get_mut(&(link, id))?;
+        *used = self.uses;
+        Some(prepared.clone())
+    }
+}
+
+static DICTIONARIES: LazyLock<Mutex<Dictionaries>> =
+    LazyLock::new(|| Mutex::new(Dictionaries::default()));
+
+/// Install `dictionary`, so that it is used to compress the payloads of
+/// its type, in place of any previous dictionary for the type.
+pub fn install(dictionary: Dictionary) {
+    let mut dictionaries = DICTIONARIES.lock().unwrap();
+    if dictionaries
+        .by_type
+        .get(&dictionary.typehash)
+        .is_some_and(|prepared| prepared.dictionary.id == dictionary.id)
+    {
+        return;
+    }
+    dictionaries
+        .by_type
+        .insert(dictionary.typehash, Arc::new(Prepared::new(dictionary)));
+}
+
+/// The dictionary used to compress the payloads of type `typehash`, if
+/// any; e.g., to distribute it to other procs ahead of time.
+pub fn dictionary(typehash: u64) -> Option<Dictionary> {
+    DICTIONARIES
+        .lock()
+        .unwrap()
+        .by_type
+        .get(&typehash)
+        .map(|prepared| prepared.dictionary.clone())
+}
+
+/// Train a dictionary for the messages with typehash `typehash` from
+/// the next [`MESSAGE_COMPRESSION_TRAINING_SAMPLES`] payloads of the
+/// type sent by this process's mailbox clients. The dictionary is
+/// installed once trained.
+///
+/// [`MESSAGE_COMPRESSION_TRAINING_SAMPLES`]: crate::config::MESSAGE_COMPRESSION_TRAINING_SAMPLES
+pub fn train(typehash: u64) {
+    DICTIONARIES
+        .lock()
+        .unwrap()
+        .training
+        .entry(typehash)
+        .or_default();
+}
+
+/// Record `encoded`, a payload of type `typehash`, if the type is being
+/// trained, and train its dictionary once enough payloads are sampled.
+fn sample(typehash: u64, encoded: &[u8]) {
+    let samples = {
+        let mut dictionaries = DICTIONARIES.lock().unwrap();
+        let Some(samples) = dictionaries.training.get_mut(&typehash) else {
+            return;
+        };
+        samples.push(encoded.to_vec());
+        if samples.len() < global::get(MESSAGE_COMPRESSION_TRAINING_SAMPLES) {
+            return;
+        }
+        dictionaries.training.remove(&typehash).unwrap()
+    };
+    match Dictionary::train(typehash, &samples) {
+        Ok(dictionary) => {
+            tracing::info!(
+                typehash,
+                samples = samples.len(),
+                "trained compression dictionary {:?}",
+                dictionary
+            );
+            install(dictionary);
+        }
+        Err(err) => {
+            tracing::warn!(typehash, "failed to train compression dictionary: {}", err);
+        }
+    }
+}
+
+/// The dictionaries sent on a link, so that each is sent only once.
+#[derive(Debug)]
+pub(crate) struct Link {
+    /// The link's id, by which its receiver scopes the dictionaries
+    /// sent on it.
+    id: u64,
+    sent: Mutex<HashSet<u64>>,
+}
+
+impl Default for Link {
+    fn default() -> Self {
+        Self {
+            id: rand::random(),
+            sent: Mutex::default(),
+        }
+    }
+}
+
+impl Link {
+    /// Forget that the dictionary `id` was sent, e.g. because the
+    /// message carrying it was not delivered.
+    pub(crate) fn forget(&self, id: u64) {
+        self.sent.lock().unwrap().remove(&id);
+    }
+}
+
+/// Whether `envelope`'s payload is compressed.
+pub fn is_compressed(envelope: &MessageEnvelope) -> bool {
+    envelope.data.is::<CompressedPayload>()
+}
+
+/// Compress `envelope`'s payload with its type's dictionary, if one is
+/// installed and the payload is at least
+/// [`MESSAGE_COMPRESSION_MIN_BYTES`] bytes. Returns the original payload
+/// and whether the dictionary was sent with it, if compressed.
+/// Payloads that do not shrink, or fail to compress, are sent as they
+/// are.
+///
+/// [`MESSAGE_COMPRESSION_MIN_BYTES`]: crate::config::MESSAGE_COMPRESSION_MIN_BYTES
+pub(crate) fn compress(
+    envelope: &mut MessageEnvelope,
+    link: &Link,
+) -> Option<(wirevalue::Any, Option<u64>)> {
+    // Payloads large enough to be spilled are left alone: they are
+    // restored out of order, and so cannot carry a dictionary that
+    // later payloads depend on.
+    let spill_threshold = global::get(MESSAGE_SPILL_THRESHOLD);
+    if envelope.data.is_broken()
+        || is_compressed(envelope)
+        || envelope.data.len() < global::get(MESSAGE_COMPRESSION_MIN_BYTES)
+        || (spill_threshold > 0 && envelope.data.len() >= spill_threshold)
+    {
+        return None;
+    }
+    let typehash = envelope.data.typehash();
+    let prepared = {
+        let dictionaries = DICTIONARIES.lock().unwrap();
+        let prepared = dictionaries.by_type.get(&typehash).cloned();
+        let training = dictionaries.training.contains_key(&typehash);
+        drop(dictionaries);
+        if prepared.is_none() && !training {
+            return None;
+        }
+        prepared
+    };
+
+    let result = (|| {
+        let encoded = bincode::serde::encode_to_vec(&envelope.data, bincode::config::legacy())
+            .map_err(|err| CompressionError::Encoding(err.to_string()))?;
+        let Some(prepared) = prepared else {
+            sample(typehash, &encoded);
+            return Ok(None);
+        };
+        let bytes = zstd::bulk::Compressor::with_prepared_dictionary(&prepared.encoder)?
+            .compress(&encoded)?;
+        if bytes.len() >= encoded.len() {
+            return Ok(None);
+        }
+        let id = prepared.dictionary.id;
+        let dictionary = link
+            .sent
+            .lock()
+            .unwrap()
+            .insert(id)
+            .then(|| prepared.dictionary.clone());
+        let payload = CompressedPayload {
+            link: link.id,
+            dictionary_id: id,
+            dictionary,
+            len: encoded.len() as u64,
+            bytes,
+        };
+        let sent = payload.dictionary.as_ref().map(Dictionary::id);
+        wirevalue::Any::serialize(&payload)
+            .map(|data| Some((data, sent)))
+            .map_err(|err| CompressionError::Encoding(err.to_string()))
+    })();
+    match result {
+        Ok(Some((data, sent))) => {
+            let original = std::mem::replace(&mut envelope.data, data);
+            Some((original, sent))
+        }
+        Ok(None) => None,
+        Err(err) => {
+            tracing::warn!(
+                dest = %envelope.dest,
+                size = envelope.data.len(),
+                "failed to compress message payload, sending it uncompressed: {}",
+                err
+            );
+            None
+        }
+    }
+}
+
+/// Decompress `envelope`'s payload, if it is compressed, keeping the
+/// dictionary sent with it, if any, for the link that sent it.
+pub(crate) fn decompress(envelope: &mut MessageEnvelope) -> Result<(), CompressionError> {
+    if !is_compressed(envelope) {
+        return Ok(());
+    }
+    let payload: CompressedPayload = envelope
+        .data
+        .deserialized()
+        .map_err(|err| CompressionError::Encoding(err.to_string()))?;
+    // The size is taken from the wire: bound it before allocating.
+    let max = global::get(CODEC_MAX_FRAME_LENGTH);
+    if payload.len > max as u64 {
+        return Err(CompressionError::TooLarge {
+            len: payload.len,
+            max,
+        });
+    }
+    let prepared = {
+        let mut dictionaries = DICTIONARIES.lock().unwrap();
+        if let Some(dictionary) = payload.dictionary {
+            dictionaries.receive(payload.link, dictionary);
+        }
+        dictionaries
+            .lookup(payload.link, payload.dictionary_id)
+            .ok_or(CompressionError::MissingDictionary(payload.dictionary_id))?
+    };
+    let encoded = zstd::bulk::Decompressor::with_prepared_dictionary(&prepared.decoder)?
+        .decompress(&payload.bytes, payload.len as usize)?;
+    let (data, _) = bincode::serde::decode_from_slice(&encoded, bincode::config::legacy())
+        .map_err(|err| CompressionError::Encoding(err.to_string()))?;
+    envelope.data = data;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor_config::Flattrs;
+
+    use super::*;
+    use crate::port::Port;
+    use crate::testing::ids::test_actor_id;
+
+    /// A telemetry-like message, small and highly repetitive.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named)]
+    struct Sample {
+        metric: String,
+        host: String,
+        value: u64,
+    }
+
+    fn sample_message(i: u64) -> Sample {
+        Sample {
+            metric: format!("hyperactor.mailbox.posts.{}", i % 7),
+            host: format!("worker-{}.cluster.example.com", i % 13),
+            value: i * 7919,
+        }
+    }
+
+    fn envelope(i: u64) -> MessageEnvelope {
+        MessageEnvelope::serialize(
+            test_actor_id("proc", "sender"),
+            test_actor_id("proc", "actor").port_addr(Port::from(1)),
+            &sample_message(i),
+            Flattrs::new(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_train_compress_decompress() {
+        let config = hyperactor_config::global::lock();
+        let _samples = config.override_key(MESSAGE_COMPRESSION_TRAINING_SAMPLES, 1000);
+        let _size = config.override_key(MESSAGE_COMPRESSION_DICTIONARY_SIZE, 1024);
+        let _min_bytes = config.override_key(MESSAGE_COMPRESSION_MIN_BYTES, 0);
+
+        let typehash = envelope(0).data.typehash();
+        let link = Link::default();
+        train(typehash);
+        for i in 0..1000 {
+            assert!(compress(&mut envelope(i), &link).is_none());
+        }
+        let dictionary = dictionary(typehash).expect("dictionary trained");
+        assert_eq!(dictionary.typehash(), typehash);
+
+        // The first compressed payload on the link carries the
+        // dictionary; later ones refer to it.
+        let mut first = envelope(5000);
+        let (original, sent) = compress(&mut first, &link).unwrap();
+        assert_eq!(sent, Some(dictionary.id()));
+        assert!(is_compressed(&first));
+        let mut second = envelope(5001);
+        let original_len = second.data.len();
+        let (_, sent) = compress(&mut second, &link).unwrap();
+        assert_eq!(sent, None);
+        assert!(second.data.len() < original_len / 2);
+
+        decompress(&mut first).unwrap();
+        assert_eq!(first.data.len(), original.len());
+        assert_eq!(
+            first.data.deserialized::<Sample>().unwrap(),
+            sample_message(5000)
+        );
+        decompress(&mut second).unwrap();
+        assert_eq!(
+            second.data.deserialized::<Sample>().unwrap(),
+            sample_message(5001)
+        );
+    }
+
+    /// A dictionary trained on payloads of [`sample_message`]s.
+    fn trained_dictionary() -> Dictionary {
+        let samples: Vec<Vec<u8>> = (0..1000)
+            .map(|i| {
+                bincode::serde::encode_to_vec(&envelope(i).data, bincode::config::legacy()).unwrap()
+            })
+            .collect();
+        Dictionary::train(envelope(0).data.typehash(), &samples).unwrap()
+    }
+
+    /// Send `envelope` on a link other than the one it was compressed
+    /// for.
+    fn relink(envelope: &mut MessageEnvelope, link: u64) {
+        let mut payload: CompressedPayload = envelope.data.deserialized().unwrap();
+        payload.link = link;
+        envelope.data = wirevalue::Any::serialize(&payload).unwrap();
+    }
+
+    #[test]
+    fn test_dictionaries_scoped_to_link() {
+        let config = hyperactor_config::global::lock();
+        let _size = config.override_key(MESSAGE_COMPRESSION_DICTIONARY_SIZE, 1024);
+        let _min_bytes = config.override_key(MESSAGE_COMPRESSION_MIN_BYTES, 0);
+        let dictionary = trained_dictionary();
+        install(dictionary.clone());
+
+        let link = Link::default();
+        let mut first = envelope(1);
+        compress(&mut first, &link).unwrap();
+        decompress(&mut first).unwrap();
+        let mut second = envelope(2);
+        assert_eq!(compress(&mut second, &link).unwrap().1, None);
+
+        // Another link (e.g., from a restarted peer) cannot use the
+        // dictionary sent on this one.
+        let mut other = second.clone();
+        relink(&mut other, link.id.wrapping_add(1));
+        assert!(matches!(
+            decompress(&mut other),
+            Err(CompressionError::MissingDictionary(id)) if id == dictionary.id()
+        ));
+        decompress(&mut second).unwrap();
+        assert_eq!(
+            second.data.deserialized::<Sample>().unwrap(),
+            sample_message(2)
+        );
+    }
+
+    #[test]
+    fn test_received_dictionaries_bounded() {
+        let config = hyperactor_config::global::lock();
+        let _size = config.override_key(MESSAGE_COMPRESSION_DICTIONARY_SIZE, 1024);
+        let _min_bytes = config.override_key(MESSAGE_COMPRESSION_MIN_BYTES, 0);
+        let _max = config.override_key(MESSAGE_COMPRESSION_MAX_DICTIONARIES, 2);
+        install(trained_dictionary());
+
+        let links: Vec<Link> = (0..3).map(|_| Link::default()).collect();
+        for (i, link) in links.iter().enumerate() {
+            let mut first = envelope(i as u64);
+            compress(&mut first, link).unwrap();
+            decompress(&mut first).unwrap();
+        }
+        assert!(DICTIONARIES.lock().unwrap().received.len() <= 2);
+
+        // The first link's dictionary was the least recently used, and
+        // was dropped; the last link's is kept.
+        let mut evicted = envelope(10);
+        compress(&mut evicted, &links[0]).unwrap();
+        assert!(matches!(
+            decompress(&mut evicted),
+            Err(CompressionError::MissingDictionary(_))
+        ));
+        let mut kept = envelope(11);
+        compress(&mut kept, &links[2]).unwrap();
+        decompress(&mut kept).unwrap();
+    }
+
+    #[test]
+    fn test_decompressed_size_bounded() {
+        let config = hyperactor_config::global::lock();
+        let _max = config.override_key(CODEC_MAX_FRAME_LENGTH, 1024);
+        let payload = CompressedPayload {
+            link: 0,
+            dictionary_id: 0x1234,
+            dictionary: None,
+            len: 1 << 40,
+            bytes: vec![1, 2, 3],
+        };
+        let mut envelope = envelope(0);
+        envelope.data = wirevalue::Any::serialize(&payload).unwrap();
+        assert!(matches!(
+            decompress(&mut envelope),
+            Err(CompressionError::TooLarge { max: 1024, .. })
+        ));
+    }
+
+    #[test]
+    fn test_missing_dictionary() {
+        let payload = CompressedPayload {
+            link: 0,
+            dictionary_id: 0x1234,
+            dictionary: None,
+            len: 3,
+            bytes: vec![1, 2, 3],
+        };
+        let mut envelope = envelope(0);
+        envelope.data = wirevalue::Any::serialize(&payload).unwrap();
+        assert!(matches!(
+            decompress(&mut envelope),
+            Err(CompressionError::MissingDictionary(0x1234))
+        ));
+    }
+}
@@ -0,0 +1,300 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Routing by message content.
+//!
+//! The routers in [`crate::mailbox`] route a message by its
+//! destination. A [`ContentRouter`] instead routes it by what it
+//! carries: an ordered list of rules, each a [`MessageMatch`] predicate
+//! over the message's headers and type, selects the sender of the
+//! first rule that matches, and all other messages go to a fallback
+//! sender. For example, a proc can send all of its telemetry to a local
+//! aggregator and everything else upstream:
+//!
+//! ```ignore
+//! let router = ContentRouter::new(upstream)
+//!     .route(MessageMatch::TypenamePrefix("hyperactor_telemetry::".into()), aggregator);
+//! ```
+//!
+//! Because a `ContentRouter` is itself a [`MailboxSender`], it composes
+//! with the other routers through [`BoxedMailboxSender`]: the fallback
+//! and the rule targets may be destination routers, and a
+//! `ContentRouter` may be bound as a route of one.
+//!
+//! Rules can also be configured declaratively, as a serializable
+//! [`ContentRoutingSpec`] whose rules name their targets; see
+//! [`ContentRouter::from_spec`].
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use hyperactor_config::attrs::Key;
+use hyperactor_config::attrs::fnv1a_hash;
+use serde::Deserialize;
+use serde::Serialize;
+use typeuri::Named;
+
+use crate::PortAddr;
+use crate::mailbox::BoxedMailboxSender;
+use crate::mailbox::MailboxSender;
+use crate::mailbox::MessageEnvelope;
+use crate::mailbox::PortHandle;
+use crate::mailbox::Undeliverable;
+
+/// A predicate over a message's headers and type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageMatch {
+    /// Messages of the type with this typehash.
+    Typehash(u64),
+    /// Messages whose typename starts with this prefix, for example
+    /// `"hyperactor_telemetry::"`.
+    TypenamePrefix(String),
+    /// Messages that carry the header with this key name.
+    Header(String),
+    /// Messages that match all of the predicates.
+    All(Vec<MessageMatch>),
+    /// Messages that match any of the predicates.
+    Any(Vec<MessageMatch>),
+    /// Messages that do not match the predicate.
+    Not(Box<MessageMatch>),
+}
+
+impl MessageMatch {
+    /// Messages of type `T`.
+    pub fn type_of<T: Named>() -> Self {
+        Self::Typehash(T::typehash())
+    }
+
+    /// Messages that carry the header `key`.
+    pub fn header<T>(key: Key<T>) -> Self {
+        Self::Header(key.name().to_string())
+    }
+
+    /// Whether `envelope` matches this predicate.
+    pub fn matches(&self, envelope: &MessageEnvelope) -> bool {
+        match self {
+            Self::Typehash(typehash) => envelope.data().typehash() == *typehash,
+            Self::TypenamePrefix(prefix) => envelope
+                .data()
+                .typename()
+                .is_some_and(|typename| typename.starts_with(prefix.as_str())),
+            Self::Header(name) => {
+                let key_hash = fnv1a_hash(name.as_bytes());
+                envelope.headers().iter().any(|(hash, _)| hash == key_hash)
+            }
+            Self::All(predicates) => predicates.iter().all(|p| p.matches(envelope)),
+            Self::Any(predicates) => predicates.iter().any(|p| p.matches(envelope)),
+            Self::Not(predicate) => !predicate.matches(envelope),
+        }
+    }
+}
+
+/// A rule of a [`ContentRoutingSpec`]: messages that match `when` are
+/// sent to the target named `target`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentRule {
+    /// The messages to which the rule applies.
+    pub when: MessageMatch,
+    /// The name of the sender to which they are routed.
+    pub target: String,
+}
+
+/// A declarative configuration of a [`ContentRouter`]: its rules, in
+/// order, and the name of its fallback sender.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentRoutingSpec {
+    /// The rules, of which the first that matches a message applies.
+    pub rules: Vec<ContentRule>,
+    /// The name of the sender of messages that match no rule.
+    pub fallback: String,
+}
+
+/// A [`MailboxSender`] that routes each message to the sender of the
+/// first rule matching its content, or to a fallback sender. See the
+/// [module documentation](self).
+#[derive(Clone)]
+pub struct ContentRouter {
+    rules: Arc<Vec<(MessageMatch, BoxedMailboxSender)>>,
+    fallback: BoxedMailboxSender,
+}
+
+impl ContentRouter {
+    /// A router that sends all messages to `fallback`, until rules are
+    /// added with [`ContentRouter::route`].
+    pub fn new(fallback: BoxedMailboxSender) -> Self {
+        Self {
+            rules: Arc::new(Vec::new()),
+            fallback,
+        }
+    }
+
+    /// Add a rule, after the existing ones, routing messages that match
+    /// `when` to `sender`.
+    pub fn route(mut self, when: MessageMatch, sender: BoxedMailboxSender) -> Self {
+        Arc::make_mut(&mut self.rules).push((when, sender));
+        self
+    }
+
+    /// Build a router from `spec`, resolving its target names in
+    /// `targets`. Fails if the spec names a target that is not in
+    /// `targets`.
+    pub fn from_spec(
+        spec: &ContentRoutingSpec,
+        targets: &HashMap<String, BoxedMailboxSender>,
+    ) -> anyhow::Result<Self> {
+        let target = |name: &str| {
+            targets
+                .get(name)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("content routing target {} is not defined", name))
+        };
+        let mut router = Self::new(target(&spec.fallback)?);
+        for rule in &spec.rules {
+            router = router.route(rule.when.clone(), target(&rule.target)?);
+        }
+        Ok(router)
+    }
+
+    /// The sender to which `envelope` is routed.
+    fn sender(&self, envelope: &MessageEnvelope) -> &BoxedMailboxSender {
+        self.rules
+            .iter()
+            .find(|(when, _)| when.matches(envelope))
+            .map_or(&self.fallback, |(_, sender)| sender)
+    }
+}
+
+#[async_trait]
+impl MailboxSender for ContentRouter {
+    fn post_unchecked(
+        &self,
+        envelope: MessageEnvelope,
+        return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+    ) {
+        self.sender(&envelope).post(envelope, return_handle)
+    }
+
+    async fn flush(&self) -> Result<(), anyhow::Error> {
+        let futs = self
+            .rules
+            .iter()
+            .map(|(_, sender)| sender)
+            .chain(std::iter::once(&self.fallback))
+            .map(|sender| sender.flush());
+        futures::future::try_join_all(futs).await?;
+        Ok(())
+    }
+
+    fn backlog(&self, dest: &PortAddr) -> u64 {
+        self.rules
+            .iter()
+            .map(|(_, sender)| sender.backlog(dest))
+            .sum::<u64>()
+            + self.fallback.backlog(dest)
+    }
+
+    fn backlog_drained(&self) -> Option<Arc<tokio::sync::Notify>> {
+        self.fallback.backlog_drained()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use hyperactor_config::Flattrs;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::mailbox::IntoBoxedMailboxSender as _;
+    use crate::mailbox::headers::SEND_TIMESTAMP;
+    use crate::mailbox::monitored_return_handle;
+    use crate::port::Port;
+    use crate::testing::ids::test_actor_id;
+
+    #[derive(Clone)]
+    struct RecordingSender(mpsc::UnboundedSender<MessageEnvelope>);
+
+    #[async_trait]
+    impl MailboxSender for RecordingSender {
+        fn post_unchecked(
+            &self,
+            envelope: MessageEnvelope,
+            _return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+        ) {
+            self.0.send(envelope).unwrap();
+        }
+    }
+
+    fn recorder() -> (BoxedMailboxSender, mpsc::UnboundedReceiver<MessageEnvelope>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (RecordingSender(tx).into_boxed(), rx)
+    }
+
+    fn envelope<T: Serialize + Named>(value: &T, headers: Flattrs) -> MessageEnvelope {
+        MessageEnvelope::serialize(
+            test_actor_id("proc", "sender"),
+            test_actor_id("proc", "dest").port_addr(Port::from(1)),
+            value,
+            headers,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_content_router() {
+        let (upstream, mut upstream_rx) = recorder();
+        let (numbers, mut numbers_rx) = recorder();
+        let (stamped, mut stamped_rx) = recorder();
+        let spec: ContentRoutingSpec = serde_json::from_value(serde_json::json!({
+            "rules": [
+                {"when": {"typehash": u64::typehash()}, "target": "numbers"},
+                {"when": {"header": SEND_TIMESTAMP.name()}, "target": "stamped"},
+            ],
+            "fallback": "upstream",
+        }))
+        .unwrap();
+        let router = ContentRouter::from_spec(
+            &spec,
+            &HashMap::from([
+                ("upstream".to_string(), upstream),
+                ("numbers".to_string(), numbers),
+                ("stamped".to_string(), stamped),
+            ]),
+        )
+        .unwrap();
+
+        let mut headers = Flattrs::new();
+        headers.set(SEND_TIMESTAMP, SystemTime::now());
+        // The first matching rule applies.
+        router.post(envelope(&1u64, headers.clone()), monitored_return_handle());
+        router.post(
+            envelope(&"stamped".to_string(), headers),
+            monitored_return_handle(),
+        );
+        router.post(
+            envelope(&"other".to_string(), Flattrs::new()),
+            monitored_return_handle(),
+        );
+
+        let number = numbers_rx.recv().await.unwrap();
+        assert_eq!(number.data().deserialized::<u64>().unwrap(), 1);
+        let stamped = stamped_rx.recv().await.unwrap();
+        assert_eq!(stamped.data().deserialized::<String>().unwrap(), "stamped");
+        let other = upstream_rx.recv().await.unwrap();
+        assert_eq!(other.data().deserialized::<String>().unwrap(), "other");
+        assert!(numbers_rx.try_recv().is_err());
+        assert!(stamped_rx.try_recv().is_err());
+        assert!(upstream_rx.try_recv().is_err());
+
+        // Specs must only name defined targets.
+        assert!(ContentRouter::from_spec(&spec, &HashMap::new()).is_err());
+    }
+}
@@ -0,0 +1,368 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Earliest-deadline-first ports.
+//!
+//! Messages for latency-sensitive work, such as metrics scraping and
+//! heartbeats, are only useful if they are handled in time. A port
+//! opened with [`Mailbox::open_deadline_port`] queues its messages by
+//! their [`DELIVERY_DEADLINE`] header instead of by arrival: its
+//! receiver always gets the most urgent message next, and messages
+//! whose deadline has passed are dropped instead of delivered, and
+//! counted.
+//!
+//! Messages without a deadline are delivered after all messages with
+//! one. Messages with equal deadlines are delivered in the order they
+//! arrived; otherwise, deadline ports do not preserve the order of a
+//! sender's messages.
+//!
+//! Actors queue the messages for one of their handlers in the same way
+//! with [`Instance::handle_by_deadline`](crate::proc::Instance::handle_by_deadline).
+//!
+//! Senders set deadlines with
+//! [`RemoteEndpoint::post_with_deadline`](crate::endpoint::RemoteEndpoint::post_with_deadline).
+//! Deadlines are wall-clock times, so they are only as precise as the
+//! clocks of the sender and receiver are synchronized.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use hyperactor_config::Flattrs;
+use tokio::sync::Notify;
+
+use super::Mailbox;
+use super::MailboxError;
+use super::MailboxErrorKind;
+use super::Message;
+use super::PortHandle;
+use super::UnboundedPortSender;
+use crate::PortAddr;
+use crate::mailbox::headers::DELIVERY_DEADLINE;
+use crate::metrics;
+use crate::port::Port;
+
+/// A queued message.
+struct Entry<M> {
+    deadline: Option<SystemTime>,
+    /// The arrival order, to deliver messages with equal deadlines in
+    /// order.
+    seq: u64,
+    message: M,
+}
+
+impl<M> Entry<M> {
+    /// Messages with smaller keys are more urgent.
+    fn key(&self) -> (bool, Option<SystemTime>, u64) {
+        (self.deadline.is_none(), self.deadline, self.seq)
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.deadline.is_some_and(|deadline| deadline < now)
+    }
+}
+
+impl<M> PartialEq for Entry<M> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<M> Eq for Entry<M> {}
+
+impl<M> PartialOrd for Entry<M> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<M> Ord for Entry<M> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap pops the greatest entry: the most urgent.
+        other.key().cmp(&self.key())
+    }
+}
+
+struct Queue<M> {
+    heap: BinaryHeap<Entry<M>>,
+    next_seq: u64,
+    expired: u64,
+    closed: bool,
+}
+
+/// Messages queued earliest deadline first: the queue of a deadline
+/// port, or of an actor's handler port; see
+/// [`Instance::handle_by_deadline`](crate::proc::Instance::handle_by_deadline).
+pub(crate) struct DeadlineQueue<M> {
+    /// The type of the queued messages, with which expired messages are
+    /// counted.
+    message_type: &'static str,
+    queue: Mutex<Queue<M>>,
+    notify: Notify,
+}
+
+impl<M> DeadlineQueue<M> {
+    pub(crate) fn new(message_type: &'static str) -> Self {
+        Self {
+            message_type,
+            queue: Mutex::new(Queue {
+                heap: BinaryHeap::new(),
+                next_seq: 0,
+                expired: 0,
+                closed: false,
+            }),
+            notify: Notify::new(),
+        }
+    }
+
+    /// Queue `message`, which must be delivered by `deadline`, if any.
+    /// Returns false if the deadline has already passed, in which case
+    /// the message is dropped.
+    pub(crate) fn push(&self, deadline: Option<SystemTime>, message: M) -> bool {
+        let mut queue = self.queue.lock().unwrap();
+        let entry = Entry {
+            deadline,
+            seq: queue.next_seq,
+            message,
+        };
+        queue.next_seq += 1;
+        if entry.is_expired(SystemTime::now()) {
+            self.expire(&mut queue);
+            return false;
+        }
+        queue.heap.push(entry);
+        self.notify.notify_one();
+        true
+    }
+
+    /// Take the most urgent message whose deadline has not passed by
+    /// `now`, dropping those whose deadline has.
+    pub(crate) fn pop(&self, now: SystemTime) -> Option<M> {
+        let mut queue = self.queue.lock().unwrap();
+        while let Some(entry) = queue.heap.pop() {
+            if entry.is_expired(now) {
+                self.expire(&mut queue);
+            } else {
+                return Some(entry.message);
+            }
+        }
+        None
+    }
+
+    fn expire(&self, queue: &mut Queue<M>) {
+        queue.expired += 1;
+        metrics::MAILBOX_DEADLINE_EXPIRED.add(
+            1,
+            hyperactor_telemetry::kv_pairs!("message_type" => self.message_type),
+        );
+    }
+
+    fn close(&self) {
+        self.queue.lock().unwrap().closed = true;
+        self.notify.notify_one();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.queue.lock().unwrap().closed
+    }
+
+    fn len(&self) -> usize {
+        self.queue.lock().unwrap().heap.len()
+    }
+
+    fn expired(&self) -> u64 {
+        self.queue.lock().unwrap().expired
+    }
+}
+
+struct Shared<M> {
+    port_id: PortAddr,
+    queue: DeadlineQueue<M>,
+}
+
+/// Closes the queue when the port's sender is dropped.
+struct SenderGuard<M>(Arc<Shared<M>>);
+
+impl<M> Drop for SenderGuard<M> {
+    fn drop(&mut self) {
+        self.0.queue.close();
+    }
+}
+
+impl Mailbox {
+    /// Open a new port that accepts M-typed messages, and delivers them
+    /// earliest deadline first; see the [module documentation](self).
+    pub fn open_deadline_port<M: Message>(&self) -> (PortHandle<M>, DeadlinePortReceiver<M>) {
+        let port_index = self.inner.allocate_port();
+        let port_id = self.inner.actor_id.port_addr(Port::from(port_index));
+        let shared = Arc::new(Shared {
+            port_id: port_id.clone(),
+            queue: DeadlineQueue::new(std::any::type_name::<M>()),
+        });
+        let guard = SenderGuard(shared.clone());
+        let enqueue = move |headers: Flattrs, message: M| {
+            guard.0.queue.push(headers.get(DELIVERY_DEADLINE), message);
+            Ok(())
+        };
+        (
+            PortHandle::new(
+                self.clone(),
+                port_index,
+                UnboundedPortSender::Func(Arc::new(enqueue)),
+            ),
+            DeadlinePortReceiver {
+                shared,
+                mailbox: self.clone(),
+            },
+        )
+    }
+}
+
+/// A receiver of M-typed messages on a port opened with
+/// [`Mailbox::open_deadline_port`].
+pub struct DeadlinePortReceiver<M> {
+    shared: Arc<Shared<M>>,
+    /// Used to remove the port from service when the receiver is
+    /// dropped.
+    mailbox: Mailbox,
+}
+
+impl<M> DeadlinePortReceiver<M> {
+    /// Receive the most urgent message whose deadline has not passed,
+    /// if any is queued. Returns an error if the port is closed and no
+    /// message is queued.
+    #[allow(clippy::result_large_err)] // TODO: Consider reducing the size of `MailboxError`.
+    pub fn try_recv(&mut self) -> Result<Option<M>, MailboxError> {
+        self.try_recv_at(SystemTime::now())
+    }
+
+    /// Like [`DeadlinePortReceiver::try_recv`], at time `now`.
+    #[allow(clippy::result_large_err)] // TODO: Consider reducing the size of `MailboxError`.
+    fn try_recv_at(&mut self, now: SystemTime) -> Result<Option<M>, MailboxError> {
+        // Check for closure first: the queue is closed only after the
+        // last message was queued.
+        let closed = self.shared.queue.is_closed();
+        if let Some(message) = self.shared.queue.pop(now) {
+            return Ok(Some(message));
+        }
+        if closed {
+            return Err(MailboxError::new(
+                self.shared.port_id.actor_addr(),
+                MailboxErrorKind::Closed,
+            ));
+        }
+        Ok(None)
+    }
+
+    /// Receive the most urgent message whose deadline has not passed,
+    /// waiting for one to arrive.
+    pub async fn recv(&mut self) -> Result<M, MailboxError> {
+        loop {
+            let notified = self.shared.queue.notify.notified();
+            if let Some(message) = self.try_recv()? {
+                return Ok(message);
+            }
+            notified.await;
+        }
+    }
+
+    /// The number of queued messages, including those whose deadline
+    /// has passed but that have not yet been dropped.
+    pub fn len(&self) -> usize {
+        self.shared.queue.len()
+    }
+
+    /// Whether no messages are queued.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of messages dropped because their deadline passed
+    /// before they were received.
+    pub fn expired(&self) -> u64 {
+        self.shared.queue.expired()
+    }
+
+    /// The port's address.
+    pub fn port_addr(&self) -> &PortAddr {
+        &self.shared.port_id
+    }
+}
+
+impl<M> fmt::Debug for DeadlinePortReceiver<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeadlinePortReceiver")
+            .field("port_id", &self.shared.port_id)
+            .field("len", &self.len())
+            .field("expired", &self.expired())
+            .finish()
+    }
+}
+
+impl<M> Drop for DeadlinePortReceiver<M> {
+    fn drop(&mut self) {
+        self.mailbox
+            .inner
+            .remove_closed_port(&self.shared.port_id.port());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::Proc;
+    use crate::endpoint::RemoteEndpoint;
+
+    #[tokio::test]
+    async fn test_deadline_port() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let (handle, mut receiver) = client.mailbox().open_deadline_port::<u64>();
+        let port_ref = handle.bind();
+
+        let now = SystemTime::now();
+        let post = |deadline: Option<SystemTime>, message: u64| {
+            let mut headers = Flattrs::new();
+            if let Some(deadline) = deadline {
+                headers.set(DELIVERY_DEADLINE, deadline);
+            }
+            RemoteEndpoint::post_with_headers(&port_ref, &client, headers, message);
+        };
+        post(None, 0);
+        post(Some(now + Duration::from_secs(60)), 1);
+        post(Some(now + Duration::from_secs(40)), 2);
+        post(Some(now + Duration::from_secs(60)), 3);
+        // Already expired on arrival.
+        post(Some(now - Duration::from_secs(1)), 4);
+        RemoteEndpoint::post_with_deadline(&port_ref, &client, Duration::from_secs(30), 5);
+        while receiver.len() < 5 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(receiver.expired(), 1);
+
+        // Receive as if 35s later, once message 5 has expired.
+        let later = now + Duration::from_secs(35);
+        let mut received = Vec::new();
+        while let Some(message) = receiver.try_recv_at(later).unwrap() {
+            received.push(message);
+        }
+        // The most urgent first, messages with equal deadlines in
+        // order, and messages without a deadline last.
+        assert_eq!(received, vec![2, 1, 3, 0]);
+        assert_eq!(receiver.expired(), 2);
+
+        post(Some(SystemTime::now() + Duration::from_secs(60)), 6);
+        assert_eq!(receiver.recv().await.unwrap(), 6);
+        assert!(receiver.is_empty());
+    }
+}
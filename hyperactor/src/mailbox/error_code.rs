@@ -0,0 +1,423 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Stable error codes for mailbox and delivery errors.
+//!
+//! Mailbox errors ([`MailboxError`], [`MailboxSenderError`]) and
+//! delivery failures ([`DeliveryFailure`]) carry rich, but unstable,
+//! messages. Each also maps to an [`ErrorCode`]: a stable numeric code
+//! belonging to an [`ErrorCategory`], on which callers can branch
+//! instead of matching message strings.
+//!
+//! Codes are grouped by category, in blocks of 100:
+//!
+//! | Codes   | Category                          |
+//! |---------|-----------------------------------|
+//! | 100-199 | [`ErrorCategory::Routing`]        |
+//! | 200-299 | [`ErrorCategory::Serialization`]  |
+//! | 300-399 | [`ErrorCategory::Capacity`]       |
+//! | 400-499 | [`ErrorCategory::Auth`]           |
+//! | 500-599 | [`ErrorCategory::Lifecycle`]      |
+//! | 900-999 | [`ErrorCategory::Other`]          |
+//!
+//! Codes are serialized as their numbers, are never reused, and are
+//! never renumbered. Codes unknown to a binary (introduced by a newer
+//! peer) decode as [`ErrorCode::Unrecognized`], which keeps their number
+//! and so their category. The code of an envelope's
+//! root delivery failure is carried in its
+//! [`ERROR_CODE`](crate::mailbox::headers::ERROR_CODE) header.
+//!
+//! [`MailboxError`]: crate::mailbox::MailboxError
+//! [`MailboxSenderError`]: crate::mailbox::MailboxSenderError
+//! [`DeliveryFailure`]: crate::mailbox::DeliveryFailure
+
+use std::fmt;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::channel::ChannelError;
+use crate::mailbox::DeliveryFailure;
+use crate::mailbox::DeliveryFailureKind;
+use crate::mailbox::InvalidReferenceReason;
+use crate::mailbox::MailboxError;
+use crate::mailbox::MailboxErrorKind;
+use crate::mailbox::MailboxSenderError;
+use crate::mailbox::MailboxSenderErrorKind;
+use crate::mailbox::TransportFailureReason;
+use crate::mailbox::UndeliverableReason;
+
+/// The category of an [`ErrorCode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ErrorCategory {
+    /// The destination could not be found or reached.
+    Routing,
+    /// The message could not be encoded or decoded.
+    Serialization,
+    /// A size, quota, or time limit was exceeded.
+    Capacity,
+    /// The message was rejected by the destination's owner.
+    Auth,
+    /// The destination, or the port, is no longer running.
+    Lifecycle,
+    /// Uncategorized errors.
+    Other,
+}
+
+impl ErrorCategory {
+    /// The name of this category.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Routing => "routing",
+            Self::Serialization => "serialization",
+            Self::Capacity => "capacity",
+            Self::Auth => "auth",
+            Self::Lifecycle => "lifecycle",
+            Self::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+macro_rules! error_codes {
+    ($($(#[$meta:meta])* $name:ident = $code:literal,)*) => {
+        /// A stable error code. See the [module documentation](self).
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        #[serde(into = "u16", from = "u16")]
+        #[non_exhaustive]
+        pub enum ErrorCode {
+            $($(#[$meta])* $name,)*
+            /// A code unknown to this binary, introduced by a newer peer.
+            Unrecognized(u16),
+        }
+
+        impl ErrorCode {
+            /// All error codes known to this binary.
+            pub const ALL: &'static [ErrorCode] = &[$(ErrorCode::$name,)*];
+
+            /// The code with number `code`, or [`ErrorCode::Unrecognized`].
+            pub fn from_u16(code: u16) -> Self {
+                match code {
+                    $($code => Self::$name,)*
+                    _ => Self::Unrecognized(code),
+                }
+            }
+
+            /// The number of this code.
+            pub fn as_u16(&self) -> u16 {
+                match self {
+                    $(Self::$name => $code,)*
+                    Self::Unrecognized(code) => *code,
+                }
+            }
+
+            /// The name of this code.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$name => stringify!($name),)*
+                    Self::Unrecognized(_) => "Unrecognized",
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    /// The destination actor does not exist.
+    ActorNotExist = 100,
+    /// The destination handler port is not bound.
+    HandlerNotBound = 101,
+    /// The destination port was never allocated.
+    PortNeverAllocated = 102,
+    /// The port is invalid.
+    InvalidPort = 103,
+    /// There is no sender for the port.
+    NoSenderForPort = 104,
+    /// No route to the destination is known.
+    NoRoute = 105,
+    /// Dialing the destination failed.
+    DialFailed = 106,
+    /// The channel to the destination closed.
+    ChannelClosed = 107,
+    /// A link in the delivery path is unavailable.
+    LinkUnavailable = 108,
+    /// The forwarder is unavailable.
+    ForwarderUnavailable = 109,
+    /// The destination is unreachable.
+    Unreachable = 110,
+    /// A channel operation failed.
+    Channel = 111,
+
+    /// The message could not be serialized.
+    Serialize = 200,
+    /// The message could not be deserialized.
+    Deserialize = 201,
+    /// The sender's and receiver's layouts of the message type differ.
+    SchemaMismatch = 202,
+    /// The message could not be migrated to the receiver's version.
+    Migrate = 203,
+    /// The message is incompatible with the destination.
+    ProtocolMismatch = 204,
+
+    /// The serialized frame exceeded the channel frame limit.
+    OversizedFrame = 300,
+    /// A resource quota was exceeded.
+    QuotaExceeded = 301,
+    /// The message exceeded its TTL.
+    Expired = 302,
+    /// A delivery acknowledgement timed out.
+    AckTimedOut = 303,
+    /// An operation timed out.
+    Timeout = 304,
+
+    /// The envelope was delivered to a mailbox that does not own its
+    /// destination.
+    WrongMailboxOwner = 400,
+
+    /// The mailbox is closed.
+    MailboxClosed = 500,
+    /// The port is closed.
+    PortClosed = 501,
+    /// The port's recipient is gone.
+    PortGone = 502,
+    /// The destination actor stopped.
+    ActorStopped = 503,
+    /// The destination actor failed.
+    ActorFailed = 504,
+    /// The mailbox's owner terminated.
+    OwnerTerminated = 505,
+
+    /// An uncategorized error.
+    Unknown = 900,
+}
+
+impl ErrorCode {
+    /// The category of this code.
+    pub fn category(&self) -> ErrorCategory {
+        match self.as_u16() / 100 {
+            1 => ErrorCategory::Routing,
+            2 => ErrorCategory::Serialization,
+            3 => ErrorCategory::Capacity,
+            4 => ErrorCategory::Auth,
+            5 => ErrorCategory::Lifecycle,
+            _ => ErrorCategory::Other,
+        }
+    }
+}
+
+impl From<ErrorCode> for u16 {
+    fn from(code: ErrorCode) -> Self {
+        code.as_u16()
+    }
+}
+
+impl From<u16> for ErrorCode {
+    fn from(code: u16) -> Self {
+        Self::from_u16(code)
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "E{} ({}/{})",
+            self.as_u16(),
+            self.category(),
+            self.name()
+        )
+    }
+}
+
+/// Errors that map to an [`ErrorCode`].
+pub trait HasErrorCode {
+    /// The code of this error.
+    fn error_code(&self) -> ErrorCode;
+}
+
+impl HasErrorCode for DeliveryFailure {
+    fn error_code(&self) -> ErrorCode {
+        self.kind.error_code()
+    }
+}
+
+impl HasErrorCode for DeliveryFailureKind {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidReference(invalid) => invalid.reason.error_code(),
+            Self::Undeliverable(reason) => reason.error_code(),
+            Self::Expired(_) => ErrorCode::Expired,
+        }
+    }
+}
+
+impl HasErrorCode for InvalidReferenceReason {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::ActorNotExist => ErrorCode::ActorNotExist,
+            Self::HandlerNotBound => ErrorCode::HandlerNotBound,
+            Self::ActorStopped => ErrorCode::ActorStopped,
+            Self::ActorFailed => ErrorCode::ActorFailed,
+            Self::PortNeverAllocated => ErrorCode::PortNeverAllocated,
+            Self::ProtocolMismatch => ErrorCode::ProtocolMismatch,
+            Self::WrongMailboxOwner => ErrorCode::WrongMailboxOwner,
+            Self::PortClosed => ErrorCode::PortClosed,
+        }
+    }
+}
+
+impl HasErrorCode for UndeliverableReason {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::Transport(failure) => failure.reason.error_code(),
+            Self::PortGone(_) => ErrorCode::PortGone,
+        }
+    }
+}
+
+impl HasErrorCode for TransportFailureReason {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::ChannelClosed { .. } => ErrorCode::ChannelClosed,
+            Self::AckTimedOut { .. } => ErrorCode::AckTimedOut,
+            Self::DialFailed { .. } => ErrorCode::DialFailed,
+            Self::NoRoute => ErrorCode::NoRoute,
+            Self::OversizedFrame { .. } => ErrorCode::OversizedFrame,
+            Self::LinkUnavailable(_) => ErrorCode::LinkUnavailable,
+            Self::ForwarderUnavailable => ErrorCode::ForwarderUnavailable,
+            Self::QuotaExceeded(_) => ErrorCode::QuotaExceeded,
+        }
+    }
+}
+
+impl HasErrorCode for MailboxError {
+    fn error_code(&self) -> ErrorCode {
+        self.kind().error_code()
+    }
+}
+
+impl HasErrorCode for MailboxErrorKind {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::Closed => ErrorCode::MailboxClosed,
+            Self::InvalidPort(_) => ErrorCode::InvalidPort,
+            Self::NoSenderForPort(_) | Self::NoLocalSenderForPort(_) => ErrorCode::NoSenderForPort,
+            Self::PortClosed(_) => ErrorCode::PortClosed,
+            Self::Send(_, _) | Self::Recv(_, _) => ErrorCode::Unknown,
+            Self::Serialize(_) => ErrorCode::Serialize,
+            Self::Deserialize(_, _) => ErrorCode::Deserialize,
+            Self::Channel(err) => err.error_code(),
+            Self::OwnerTerminated(_) => ErrorCode::OwnerTerminated,
+            Self::Timeout(_, _) => ErrorCode::Timeout,
+        }
+    }
+}
+
+impl HasErrorCode for MailboxSenderError {
+    fn error_code(&self) -> ErrorCode {
+        self.kind().error_code()
+    }
+}
+
+impl HasErrorCode for MailboxSenderErrorKind {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::Serialize(_) => ErrorCode::Serialize,
+            Self::Deserialize(_, _) => ErrorCode::Deserialize,
+            Self::SchemaMismatch(_, _, _) => ErrorCode::SchemaMismatch,
+            Self::Migrate(_) => ErrorCode::Migrate,
+            Self::Invalid => ErrorCode::InvalidPort,
+            Self::Closed => ErrorCode::PortClosed,
+            Self::Mailbox(err) => err.error_code(),
+            Self::Channel(err) => err.error_code(),
+            Self::Other(_) => ErrorCode::Unknown,
+            Self::Unreachable(_) => ErrorCode::Unreachable,
+        }
+    }
+}
+
+impl HasErrorCode for ChannelError {
+    fn error_code(&self) -> ErrorCode {
+        match self {
+            Self::Closed => ErrorCode::ChannelClosed,
+            Self::InvalidAddress(_) => ErrorCode::InvalidPort,
+            Self::BincodeEncode(_) => ErrorCode::Serialize,
+            Self::BincodeDecode(_) => ErrorCode::Deserialize,
+            Self::Timeout(_) => ErrorCode::Timeout,
+            _ => ErrorCode::Channel,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::mailbox::PortGone;
+    use crate::mailbox::TransportFailure;
+    use crate::testing::ids::test_port_id;
+
+    #[test]
+    fn test_codes_are_stable() {
+        let mut seen = HashSet::new();
+        for code in ErrorCode::ALL {
+            assert!(seen.insert(code.as_u16()), "duplicate code {code}");
+            assert_eq!(ErrorCode::from_u16(code.as_u16()), *code);
+            assert_eq!(
+                bincode::serde::decode_from_slice::<ErrorCode, _>(
+                    &bincode::serde::encode_to_vec(code, bincode::config::legacy()).unwrap(),
+                    bincode::config::legacy()
+                )
+                .unwrap()
+                .0,
+                *code
+            );
+        }
+        assert_eq!(ErrorCode::NoRoute.as_u16(), 105);
+        // Codes from a newer peer keep their number and category.
+        let unrecognized = ErrorCode::from_u16(305);
+        assert_eq!(unrecognized, ErrorCode::Unrecognized(305));
+        assert_eq!(unrecognized.as_u16(), 305);
+        assert_eq!(unrecognized.category(), ErrorCategory::Capacity);
+        assert_eq!(
+            serde_json::from_str::<ErrorCode>("999").unwrap(),
+            ErrorCode::Unrecognized(999)
+        );
+        assert_eq!(serde_json::to_string(&ErrorCode::PortGone).unwrap(), "502");
+    }
+
+    #[test]
+    fn test_delivery_failure_codes() {
+        let port = test_port_id("proc", "actor", 1);
+        let failure = DeliveryFailure::new(UndeliverableReason::Transport(TransportFailure::new(
+            port.clone(),
+            TransportFailureReason::QuotaExceeded("full".to_string()),
+        )));
+        assert_eq!(failure.error_code(), ErrorCode::QuotaExceeded);
+        assert_eq!(failure.error_code().category(), ErrorCategory::Capacity);
+
+        let failure = DeliveryFailure::new(UndeliverableReason::PortGone(PortGone::new(
+            port.clone(),
+            None,
+        )));
+        assert_eq!(failure.error_code().category(), ErrorCategory::Lifecycle);
+
+        let error = MailboxSenderError::new_bound(
+            port,
+            MailboxSenderErrorKind::SchemaMismatch("Message", 1, 2),
+        );
+        assert_eq!(error.error_code(), ErrorCode::SchemaMismatch);
+        assert_eq!(error.error_code().category(), ErrorCategory::Serialization);
+    }
+}
@@ -0,0 +1,424 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Federation of routers across clusters.
+//!
+//! A multi-cluster mesh should not need every proc's address in every
+//! router. Instead, the router of each cluster (a child) summarizes the
+//! references it routes as its covering prefixes, and advertises them
+//! to a parent router in a [`RouteAdvertisement`]. The parent, through
+//! a [`RouterFederation`], binds each advertised prefix to the address
+//! at which the child serves, so that messages to any destination under
+//! the prefix are delegated to the child, which routes them onward.
+//! Parents may in turn advertise summaries of their own routes to their
+//! parents.
+//!
+//! Procs of another cluster are located behind that cluster's gateway
+//! (their locations are [`Location::Via`] hops through it), so a child
+//! can summarize them further, as whole worlds: it advertises the
+//! gateway's uid, and the parent routes every destination behind the
+//! gateway to the child through a single route, however many procs the
+//! world has.
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::Addr;
+use crate::Location;
+use crate::Uid;
+use crate::channel::ChannelAddr;
+use crate::mailbox::DialMailboxRouter;
+
+/// The routes of a child router, advertised to its parents.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, typeuri::Named)]
+pub struct RouteAdvertisement {
+    /// The address at which the child router serves.
+    pub router: ChannelAddr,
+    /// Orders the advertisements of a router. Advertisements older
+    /// than the last one applied are ignored.
+    pub generation: u64,
+    /// The prefixes routed by the router. These replace the prefixes of
+    /// its previous advertisement.
+    pub prefixes: BTreeSet<Addr>,
+    /// The gateways whose worlds are routed by the router. These
+    /// replace the worlds of its previous advertisement.
+    pub worlds: BTreeSet<Uid>,
+}
+wirevalue::register_type!(RouteAdvertisement);
+
+impl RouteAdvertisement {
+    /// Advertise the routes of `router`, served at `addr`, summarized as
+    /// its covering prefixes and its worlds.
+    pub fn new(router: &DialMailboxRouter, addr: ChannelAddr, generation: u64) -> Self {
+        Self {
+            router: addr,
+            generation,
+            prefixes: router.prefixes(),
+            worlds: router.worlds(),
+        }
+    }
+
+    /// Advertise `prefixes`, served at `addr`, summarized as their
+    /// covering prefixes. Use this to advertise coarser prefixes than
+    /// those bound in a router, e.g. whole procs instead of their
+    /// actors.
+    pub fn summarize(
+        addr: ChannelAddr,
+        generation: u64,
+        prefixes: impl IntoIterator<Item = Addr>,
+    ) -> Self {
+        Self {
+            router: addr,
+            generation,
+            prefixes: super::covering_prefixes(&prefixes.into_iter().collect::<BTreeSet<_>>()),
+            worlds: BTreeSet::new(),
+        }
+    }
+
+    /// Also advertise the worlds behind `gateways`. Prefixes located
+    /// behind these gateways are covered by their worlds, and are not
+    /// advertised.
+    pub fn with_worlds(mut self, gateways: impl IntoIterator<Item = Uid>) -> Self {
+        self.worlds.extend(gateways);
+        let worlds = &self.worlds;
+        self.prefixes
+            .retain(|prefix| match prefix.proc_addr().location() {
+                Location::Via(gateway, _) => !worlds.contains(gateway),
+                Location::Addr(_) => true,
+            });
+        self
+    }
+}
+
+/// The prefixes and worlds applied from a child's last advertisement.
+struct Advertised {
+    generation: u64,
+    prefixes: BTreeSet<Addr>,
+    worlds: BTreeSet<Uid>,
+}
+
+/// The parent side of a federation: applies the advertisements of child
+/// routers to a [`DialMailboxRouter`].
+///
+/// When children advertise the same prefix, the most recently applied
+/// advertisement routes it.
+#[derive(Clone)]
+pub struct RouterFederation {
+    router: DialMailboxRouter,
+    children: Arc<Mutex<HashMap<ChannelAddr, Advertised>>>,
+}
+
+impl RouterFederation {
+    /// Federate child routers into `router`.
+    pub fn new(router: DialMailboxRouter) -> Self {
+        Self {
+            router,
+            children: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The parent router.
+    pub fn router(&self) -> &DialMailboxRouter {
+        &self.router
+    }
+
+    /// Apply `advertisement`, binding its prefixes and worlds to the
+    /// advertising router and unbinding those of its previous
+    /// advertisement that it no longer includes. Returns false, applying
+    /// nothing, if the advertisement is older than the last one applied
+    /// for the router.
+    pub fn advertise(&self, advertisement: RouteAdvertisement) -> bool {
+        let RouteAdvertisement {
+            router: child,
+            generation,
+            prefixes,
+            worlds,
+        } = advertisement;
+        let child = child.into_dial_addr();
+        let mut children = self.children.lock().unwrap_or_else(|e| e.into_inner());
+        let none = Advertised {
+            generation,
+            prefixes: BTreeSet::new(),
+            worlds: BTreeSet::new(),
+        };
+        let previous = match children.get(&child) {
+            Some(advertised) if advertised.generation > generation => {
+                tracing::debug!(
+                    router = %child,
+                    generation,
+                    last_generation = advertised.generation,
+                    "ignoring stale route advertisement"
+                );
+                return false;
+            }
+            Some(advertised) => advertised,
+            None => &none,
+        };
+
+        self.router.address_book.update(|address_book| {
+            for prefix in previous.prefixes.difference(&prefixes) {
+                if address_book.get(prefix) == Some(&child) {
+                    tracing::info!("withdrawing {:?} from {:?}", prefix, child);
+                    address_book.remove(prefix);
+                }
+            }
+            for prefix in &prefixes {
+                if let Some(old_addr) = address_book.insert(prefix.clone(), child.clone())
+                    && old_addr != child
+                {
+                    tracing::info!("rebinding {:?} from {:?} to {:?}", prefix, old_addr, child);
+                    self.router.sender_cache.remove(&old_addr);
+                }
+            }
+        });
+        self.router.worlds.update(|bound| {
+            for gateway in previous.worlds.difference(&worlds) {
+                if bound.get(gateway) == Some(&child) {
+                    tracing::info!("withdrawing world {} from {:?}", gateway, child);
+                    bound.remove(gateway);
+                }
+            }
+            for gateway in &worlds {
+                if let Some(old_addr) = bound.insert(gateway.clone(), child.clone())
+                    && old_addr != child
+                {
+                    tracing::info!(
+                        "rebinding world {} from {:?} to {:?}",
+                        gateway,
+                        old_addr,
+                        child
+                    );
+                    self.router.sender_cache.remove(&old_addr);
+                }
+            }
+        });
+        children.insert(
+            child,
+            Advertised {
+                generation,
+                prefixes,
+                worlds,
+            },
+        );
+        true
+    }
+
+    /// Unbind all prefixes and worlds advertised by the router at
+    /// `router`, e.g. when it is gone.
+    pub fn withdraw(&self, router: &ChannelAddr) {
+        let child = router.clone().into_dial_addr();
+        let mut children = self.children.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(advertised) = children.remove(&child) else {
+            return;
+        };
+        self.router.address_book.update(|address_book| {
+            for prefix in &advertised.prefixes {
+                if address_book.get(prefix) == Some(&child) {
+                    tracing::info!("withdrawing {:?} from {:?}", prefix, child);
+                    address_book.remove(prefix);
+                }
+            }
+        });
+        self.router.worlds.update(|bound| {
+            for gateway in &advertised.worlds {
+                if bound.get(gateway) == Some(&child) {
+                    tracing::info!("withdrawing world {} from {:?}", gateway, child);
+                    bound.remove(gateway);
+                }
+            }
+        });
+        self.router.sender_cache.remove(&child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProcAddr;
+    use crate::channel;
+    use crate::channel::ChannelTransport;
+    use crate::mailbox::Mailbox;
+    use crate::mailbox::MailboxServer as _;
+    use crate::mailbox::PortSender as _;
+    use crate::mailbox::monitored_return_handle;
+    use crate::testing::ids::test_actor_id;
+    use crate::testing::ids::test_proc_id;
+
+    fn lookup(router: &DialMailboxRouter, proc_name: &str) -> ChannelAddr {
+        router
+            .lookup_addr(&test_actor_id(proc_name, "actor"))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_route_advertisements() {
+        let child0: ChannelAddr = "unix!@child0".parse().unwrap();
+        let child1: ChannelAddr = "unix!@child1".parse().unwrap();
+        let direct = ChannelAddr::any(ChannelTransport::Local);
+        let federation = RouterFederation::new(DialMailboxRouter::new());
+
+        // Bound actors are summarized by their procs.
+        let child_router = DialMailboxRouter::new();
+        child_router.bind(test_proc_id("a"), "unix!@a".parse().unwrap());
+        child_router.bind(test_actor_id("a", "actor"), "unix!@a".parse().unwrap());
+        child_router.bind(test_actor_id("b", "actor"), "unix!@b".parse().unwrap());
+        let advertisement = RouteAdvertisement::new(&child_router, child0.clone(), 1);
+        assert_eq!(
+            advertisement.prefixes,
+            BTreeSet::from([test_proc_id("a").into(), test_actor_id("b", "actor").into()])
+        );
+        let serialized = wirevalue::Any::serialize(&advertisement).unwrap();
+        assert_eq!(
+            serialized.deserialized::<RouteAdvertisement>().unwrap(),
+            advertisement
+        );
+        assert_eq!(
+            RouteAdvertisement::summarize(
+                child0.clone(),
+                1,
+                [
+                    test_actor_id("a", "actor").into(),
+                    test_proc_id("a").into(),
+                    test_proc_id("b").into(),
+                ]
+            )
+            .prefixes,
+            BTreeSet::from([test_proc_id("a").into(), test_proc_id("b").into()])
+        );
+
+        assert!(federation.advertise(RouteAdvertisement::summarize(
+            child0.clone(),
+            1,
+            [test_proc_id("a").into(), test_proc_id("b").into()],
+        )));
+        assert_eq!(lookup(federation.router(), "a"), child0);
+        assert_eq!(lookup(federation.router(), "b"), child0);
+
+        // A later advertisement replaces the earlier one.
+        assert!(federation.advertise(RouteAdvertisement::summarize(
+            child0.clone(),
+            2,
+            [test_proc_id("a").into()],
+        )));
+        assert_eq!(lookup(federation.router(), "a"), child0);
+        assert_eq!(lookup(federation.router(), "b"), direct);
+
+        // Stale advertisements are ignored.
+        assert!(!federation.advertise(RouteAdvertisement::summarize(
+            child0.clone(),
+            1,
+            [test_proc_id("b").into()],
+        )));
+        assert_eq!(lookup(federation.router(), "b"), direct);
+
+        // Another child takes over a prefix; withdrawing the first
+        // child does not unbind it.
+        assert!(federation.advertise(RouteAdvertisement::summarize(
+            child1.clone(),
+            1,
+            [test_proc_id("a").into()],
+        )));
+        assert_eq!(lookup(federation.router(), "a"), child1);
+        federation.withdraw(&child0);
+        assert_eq!(lookup(federation.router(), "a"), child1);
+        federation.withdraw(&child1);
+        assert_eq!(lookup(federation.router(), "a"), direct);
+    }
+
+    #[test]
+    fn test_world_advertisements() {
+        let child: ChannelAddr = "unix!@world_child".parse().unwrap();
+        let gateway = Uid::anonymous();
+        let other_gateway = Uid::anonymous();
+        let located = |proc_name: &str, gateway: &Uid| {
+            let location = Location::from("unix!@world_proc".parse::<ChannelAddr>().unwrap())
+                .with_via(gateway.clone());
+            ProcAddr::new(test_proc_id(proc_name), location)
+        };
+        let federation = RouterFederation::new(DialMailboxRouter::new());
+
+        // Prefixes behind an advertised world are summarized by it.
+        let advertisement = RouteAdvertisement::summarize(
+            child.clone(),
+            1,
+            [
+                located("a", &gateway).into(),
+                located("b", &other_gateway).into(),
+            ],
+        )
+        .with_worlds([gateway.clone()]);
+        assert_eq!(
+            advertisement.prefixes,
+            BTreeSet::from([located("b", &other_gateway).into()])
+        );
+        assert!(federation.advertise(advertisement));
+
+        // Every proc behind the gateway is routed to the child, with a
+        // single route.
+        let router = federation.router();
+        assert_eq!(router.worlds(), BTreeSet::from([gateway.clone()]));
+        for proc_name in ["a", "c", "d"] {
+            assert_eq!(
+                router.lookup_addr(&located(proc_name, &gateway).actor_addr("actor")),
+                Some(child.clone())
+            );
+        }
+        assert_eq!(
+            router.lookup_addr(&located("b", &other_gateway).actor_addr("actor")),
+            Some(child.clone())
+        );
+        assert_ne!(
+            router.lookup_addr(&located("c", &other_gateway).actor_addr("actor")),
+            Some(child.clone())
+        );
+
+        // A child's own router advertises its worlds.
+        let child_router = DialMailboxRouter::new();
+        child_router.bind_world(other_gateway.clone(), "unix!@gateway".parse().unwrap());
+        let advertisement = RouteAdvertisement::new(&child_router, child.clone(), 2);
+        assert_eq!(
+            advertisement.worlds,
+            BTreeSet::from([other_gateway.clone()])
+        );
+        assert!(federation.advertise(advertisement));
+        assert_eq!(router.worlds(), BTreeSet::from([other_gateway.clone()]));
+
+        federation.withdraw(&child);
+        assert!(router.worlds().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delegate_to_child_router() {
+        // The proc's own address is not served, so it is reachable only
+        // through the child router.
+        let proc = ProcAddr::singleton("unix!@unserved_federated_proc".parse().unwrap(), "proc");
+        let mbox = Mailbox::new(proc.actor_addr("actor"));
+        let (mbox_addr, rx) = channel::serve(ChannelAddr::any(ChannelTransport::Local)).unwrap();
+        let _mbox_handle = mbox.clone().serve(rx);
+
+        let child_router = DialMailboxRouter::new();
+        child_router.bind(proc.clone(), mbox_addr);
+        let (child_addr, rx) = channel::serve(ChannelAddr::any(ChannelTransport::Local)).unwrap();
+        let _child_handle = child_router.clone().serve(rx);
+
+        let federation = RouterFederation::new(DialMailboxRouter::new());
+        assert!(federation.advertise(RouteAdvertisement::new(&child_router, child_addr, 1)));
+
+        let (port, receiver) = mbox.open_once_port::<u64>();
+        federation
+            .router()
+            .serialize_and_send_once(port.bind(), 123u64, monitored_return_handle())
+            .unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), 123u64);
+    }
+}
@@ -0,0 +1,150 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Measurement of message header overhead.
+//!
+//! Every message carries its headers (sender, sequence number, cast
+//! point, and so on) alongside its payload, and for small messages the
+//! headers can outweigh the payload. When [`config::HEADER_STATS`] is
+//! enabled, the [`DialMailboxRouter`](crate::mailbox::DialMailboxRouter)
+//! measures the headers of each message it sends to a remote process,
+//! by message type. [`header_overhead`] reports the measurements, in
+//! both header wire formats, so that large meshes can decide whether
+//! to enable [`COMPACT_HEADERS`], and which headers to trim.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+use hyperactor_config::flattrs::COMPACT_HEADERS;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::config;
+use crate::mailbox::MessageEnvelope;
+
+/// The header overhead measured for a message type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaderOverhead {
+    /// The number of messages measured.
+    pub messages: u64,
+    /// The total size of their headers in the standard wire format.
+    pub header_bytes: u64,
+    /// The total size of their headers in the compact wire format.
+    pub compact_header_bytes: u64,
+    /// The total size of their payloads.
+    pub payload_bytes: u64,
+}
+
+impl HeaderOverhead {
+    /// The mean header size per message, in the wire format selected
+    /// by [`COMPACT_HEADERS`].
+    pub fn mean_header_bytes(&self) -> f64 {
+        let header_bytes = if hyperactor_config::global::get(COMPACT_HEADERS) {
+            self.compact_header_bytes
+        } else {
+            self.header_bytes
+        };
+        header_bytes as f64 / self.messages.max(1) as f64
+    }
+
+    /// The fraction of the bytes sent that are headers, in the wire
+    /// format selected by [`COMPACT_HEADERS`].
+    pub fn header_fraction(&self) -> f64 {
+        let header_bytes = self.mean_header_bytes() * self.messages as f64;
+        let total = header_bytes + self.payload_bytes as f64;
+        if total == 0.0 {
+            0.0
+        } else {
+            header_bytes / total
+        }
+    }
+
+    fn add(&mut self, envelope: &MessageEnvelope) {
+        let headers = envelope.headers();
+        self.messages += 1;
+        self.header_bytes += headers.encoded_len() as u64;
+        self.compact_header_bytes += headers.compact_encoded_len() as u64;
+        self.payload_bytes += envelope.data().len() as u64;
+    }
+}
+
+fn stats() -> &'static Mutex<HashMap<String, HeaderOverhead>> {
+    static STATS: OnceLock<Mutex<HashMap<String, HeaderOverhead>>> = OnceLock::new();
+    STATS.get_or_init(Default::default)
+}
+
+/// The name under which messages of the type of `envelope` are
+/// measured: their typename, or their typehash if it is unknown.
+fn message_type(envelope: &MessageEnvelope) -> String {
+    match envelope.data().typename() {
+        Some(typename) => typename.to_string(),
+        None => format!("{:016x}", envelope.data().typehash()),
+    }
+}
+
+/// Measure the headers of `envelope`, if [`config::HEADER_STATS`] is
+/// enabled.
+pub(crate) fn record(envelope: &MessageEnvelope) {
+    if !hyperactor_config::global::get(config::HEADER_STATS) {
+        return;
+    }
+    stats()
+        .lock()
+        .unwrap()
+        .entry(message_type(envelope))
+        .or_default()
+        .add(envelope);
+}
+
+/// The header overhead measured in this process since it started, or
+/// since the last [`reset_header_overhead`], by message type.
+pub fn header_overhead() -> HashMap<String, HeaderOverhead> {
+    stats().lock().unwrap().clone()
+}
+
+/// Discard the measurements made so far.
+pub fn reset_header_overhead() {
+    stats().lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor_config::Flattrs;
+    use typeuri::Named;
+
+    use super::*;
+    use crate::mailbox::headers::TELEMETRY_MESSAGE_ID;
+    use crate::port::Port;
+    use crate::testing::ids::test_actor_id;
+
+    #[test]
+    fn test_header_overhead() {
+        let mut headers = Flattrs::new();
+        headers.set(TELEMETRY_MESSAGE_ID, 7);
+        let envelope = MessageEnvelope::serialize(
+            test_actor_id("proc", "sender"),
+            test_actor_id("proc", "dest").port_addr(Port::from(1)),
+            &1u64,
+            headers,
+        )
+        .unwrap();
+
+        let mut overhead = HeaderOverhead::default();
+        overhead.add(&envelope);
+        overhead.add(&envelope);
+        assert_eq!(overhead.messages, 2);
+        // Two bytes of count, then a 12-byte entry header and an 8-byte
+        // value, per message.
+        assert_eq!(overhead.header_bytes, 2 * (2 + 12 + 8));
+        // TELEMETRY_MESSAGE_ID has a compact id, so its entry header is 2 bytes.
+        assert_eq!(overhead.compact_header_bytes, 2 * (2 + 2 + 8));
+        assert_eq!(overhead.payload_bytes, 2 * envelope.data().len() as u64);
+        assert_eq!(message_type(&envelope), u64::typename());
+    }
+}
@@ -15,6 +15,7 @@ use std::any::type_name;
 use std::time::SystemTime;
 
 use hyperactor_config::Flattrs;
+use hyperactor_config::attrs::COMPACT_KEY;
 use hyperactor_config::attrs::OPERATION_CONTEXT_HEADER;
 use hyperactor_config::attrs::declare_attrs;
 use hyperactor_config::global;
@@ -24,14 +25,19 @@ use crate::PortAddr;
 use crate::metrics::MESSAGE_LATENCY_MICROS;
 use crate::ordering::SeqInfo;
 
+// Compact key ids 1-63 are reserved for hyperactor's headers, and
+// encode in a single byte.
 declare_attrs! {
     /// Send timestamp for message latency tracking
+    @meta(COMPACT_KEY = 1)
     pub attr SEND_TIMESTAMP: SystemTime;
 
     /// The rust type of the message.
+    @meta(COMPACT_KEY = 2)
     pub attr RUST_MESSAGE_TYPE: String;
 
     /// Hashed ActorId of the message sender, injected in post_unchecked().
+    @meta(COMPACT_KEY = 3)
     pub attr SENDER_ACTOR_ID_HASH: u64;
 
     /// Full ActorAddr of the session owner — the actor whose Sequencer
@@ -48,14 +54,109 @@ declare_attrs! {
     ///
     /// Larger than SENDER_ACTOR_ID_HASH (~50-100 bytes vs 8); both kept
     /// so the hash remains available for high-cardinality OTel labels.
+    @meta(COMPACT_KEY = 4)
     pub attr SENDER_ACTOR_ID: ActorAddr;
 
     /// Telemetry message ID for correlating lifecycle events, injected in post_unchecked().
+    @meta(COMPACT_KEY = 5)
     pub attr TELEMETRY_MESSAGE_ID: u64;
 
     /// Port index the message was delivered to, injected in post_unchecked().
+    @meta(COMPACT_KEY = 6)
     pub attr TELEMETRY_PORT_INDEX: u64;
 
+    /// The [`TELEMETRY_MESSAGE_ID`] of the message whose handler posted
+    /// this message, if it was posted while handling one.
+    @meta(COMPACT_KEY = 8)
+    pub attr PARENT_MESSAGE_ID: u64;
+
+    /// The [`TELEMETRY_MESSAGE_ID`] of the first message of this
+    /// message's causal chain: following [`PARENT_MESSAGE_ID`] from this
+    /// message leads back to it. Unset on messages that were not posted
+    /// while handling one, which are the roots of their chains.
+    @meta(COMPACT_KEY = 9)
+    pub attr ROOT_MESSAGE_ID: u64;
+
+    /// Serialized size of the message payload, injected in
+    /// post_unchecked(), or estimated for messages sent locally by
+    /// value. Handler ports account it against the
+    /// receiving actor's queued bytes until the message is dequeued;
+    /// see [`crate::config::ACTOR_MAX_QUEUED_BYTES`].
+    @meta(COMPACT_KEY = 10)
+    pub attr MESSAGE_BYTES: u64;
+
+    /// Layout fingerprint of the message payload type in the sender's
+    /// binary (see [`wirevalue::schema`]). Checked by the receiving
+    /// port before the payload is decoded.
+    @meta(COMPACT_KEY = 11)
+    pub attr SCHEMA_FINGERPRINT: u64;
+
+    /// The wire version of the message payload type in the sender's
+    /// binary; see [`crate::mailbox::migrate`]. Absent for version 0, so
+    /// that messages from binaries that predate versioning are read as
+    /// version 0.
+    @meta(COMPACT_KEY = 14)
+    pub attr MESSAGE_VERSION: crate::mailbox::migrate::MessageVersion;
+
+    /// The time by which the message should be delivered. Ports opened
+    /// with [`crate::Mailbox::open_deadline_port`] deliver the message
+    /// with the earliest deadline first, and drop messages whose
+    /// deadline has passed; see [`crate::mailbox::deadline`].
+    @meta(COMPACT_KEY = 13)
+    pub attr DELIVERY_DEADLINE: SystemTime;
+
+    /// A sender-chosen key identifying a logical message across
+    /// retries. Receiving mailboxes drop a message whose key was
+    /// recently delivered to the same port, so that at-least-once retry
+    /// loops deliver the message once; see
+    /// [`crate::config::IDEMPOTENCY_CACHE_CAPACITY`].
+    pub attr IDEMPOTENCY_KEY: String;
+
+    /// The cancellable operation a message belongs to; see
+    /// [`crate::cancel`]. Messages sent while handling a message that
+    /// carries a token inherit it.
+    pub attr CANCEL_TOKEN: crate::cancel::CancelToken;
+
+    /// Identifies the two-phase transaction a message is prepared in;
+    /// see [`crate::mailbox::transaction`].
+    pub attr TXN_ID: u64;
+
+    /// The position of a message within its two-phase transaction.
+    pub attr TXN_INDEX: u64;
+
+    /// The coordinator's port for votes on a two-phase transaction.
+    pub attr TXN_VOTE_PORT: PortAddr;
+
+    /// The port to which a reply to the message should be sent. Set by
+    /// [`crate::endpoint::RemoteEndpoint::request`], or on every message
+    /// posted by an actor with a reply port (see
+    /// [`crate::proc::Instance::set_reply_port`]), and replied to with
+    /// [`crate::proc::Context::respond`].
+    pub attr REPLY_TO: PortAddr;
+
+    /// The [`ErrorCode`](crate::mailbox::error_code::ErrorCode) number of
+    /// the root delivery failure of an undeliverable message.
+    pub attr ERROR_CODE: u16;
+
+    /// The blob holding the message's payload, when the payload was
+    /// spilled to disk instead of being sent inline; see
+    /// [`crate::mailbox::spill`].
+    pub attr SPILLED_PAYLOAD: crate::mailbox::spill::BlobRef;
+
+    /// Set on messages re-delivered by [`crate::replay`]. Everything the
+    /// handler of such a message posts to other actors through its
+    /// context is dropped, so that a replay cannot act on the live
+    /// system.
+    pub attr REPLAYED: bool;
+
+    /// Set on the copies of messages that a
+    /// [`MirroringSender`](crate::mailbox::mirror::MirroringSender)
+    /// posts to shadow actors. Everything the handler of such a message
+    /// posts to other actors is dropped, as for [`REPLAYED`] messages,
+    /// except, if the value is true, responses to the message's
+    /// [`REPLY_TO`] port, which the mirror rewrote to collect them.
+    pub attr MIRRORED: bool;
+
     // Operation-context headers (see `OPERATION_CONTEXT_HEADER` in
     // `hyperactor_config::attrs`). Carried from the caller's outgoing
     // request onto the reply envelope by a consumer-side helper that
@@ -97,6 +198,33 @@ pub fn set_rust_message_type<M>(headers: &mut Flattrs) {
     headers.set(RUST_MESSAGE_TYPE, type_name::<M>().to_string());
 }
 
+/// Stamp the sender's layout fingerprint for the payload type, if the
+/// type is registered and schema checks are enabled.
+pub fn set_schema_fingerprint(headers: &mut Flattrs, data: &wirevalue::Any) {
+    if !global::get(crate::config::MESSAGE_SCHEMA_CHECK) {
+        return;
+    }
+    if let Some(fingerprint) = wirevalue::schema::fingerprint(data.typehash()) {
+        headers.set(SCHEMA_FINGERPRINT, fingerprint);
+    }
+}
+
+/// Check the sender's layout fingerprint (if stamped) against the local
+/// registration of the payload type. On mismatch, returns the sender's
+/// and the local fingerprints.
+pub(crate) fn check_schema_fingerprint(
+    headers: &Flattrs,
+    data: &wirevalue::Any,
+) -> Result<(), (u64, u64)> {
+    let Some(remote) = headers.get(SCHEMA_FINGERPRINT) else {
+        return Ok(());
+    };
+    match wirevalue::schema::fingerprint(data.typehash()) {
+        Some(local) if local != remote => Err((remote, local)),
+        _ => Ok(()),
+    }
+}
+
 /// Stamp `SENDER_ACTOR_ID` into `headers` if the gate conditions are met.
 /// Framework-owned: overwrites existing values, never "sets if absent".
 ///
@@ -137,6 +265,42 @@ pub(crate) fn stamp_sender_actor_id_fresh(
     }
 }
 
+/// The position of a delivered message in its causal chain: its own
+/// [`TELEMETRY_MESSAGE_ID`], and the [`ROOT_MESSAGE_ID`] of its chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Causality {
+    /// The message's ID.
+    pub message_id: u64,
+    /// The ID of the first message of the message's causal chain.
+    pub root_message_id: u64,
+}
+
+impl Causality {
+    /// The causality of the message with `headers`, if it was assigned
+    /// a telemetry message ID on delivery.
+    pub fn of(headers: &Flattrs) -> Option<Self> {
+        let message_id = headers.get(TELEMETRY_MESSAGE_ID)?;
+        Some(Self {
+            message_id,
+            root_message_id: headers.get(ROOT_MESSAGE_ID).unwrap_or(message_id),
+        })
+    }
+}
+
+/// Link a message being posted to `parent`, the message being handled
+/// when it was posted, unless it is already linked (as when a message
+/// is forwarded with its headers).
+pub(crate) fn stamp_causality(headers: &mut Flattrs, parent: Option<Causality>) {
+    let Some(parent) = parent else {
+        return;
+    };
+    if headers.contains_key(PARENT_MESSAGE_ID) {
+        return;
+    }
+    headers.set(PARENT_MESSAGE_ID, parent.message_id);
+    headers.set(ROOT_MESSAGE_ID, parent.root_message_id);
+}
+
 /// This function checks the configured sampling rate and, if the random sample passes,
 /// calculates the latency between the send timestamp and the current time, then records
 /// the latency metric with the associated actor ID.
@@ -173,6 +337,36 @@ mod tests {
     use crate::port::Port;
     use crate::testing::ids::test_actor_id;
 
+    #[test]
+    fn test_stamp_causality() {
+        // A message posted outside a handler is a root.
+        let mut root = Flattrs::new();
+        stamp_causality(&mut root, None);
+        assert_eq!(root.get(PARENT_MESSAGE_ID), None);
+        // Delivery assigns its telemetry id.
+        root.set(TELEMETRY_MESSAGE_ID, 1);
+        let root_cause = Causality::of(&root).unwrap();
+        assert_eq!(root_cause.message_id, 1);
+        assert_eq!(root_cause.root_message_id, 1);
+
+        let mut child = Flattrs::new();
+        stamp_causality(&mut child, Some(root_cause));
+        assert_eq!(child.get(PARENT_MESSAGE_ID), Some(1));
+        child.set(TELEMETRY_MESSAGE_ID, 2);
+        let child_cause = Causality::of(&child).unwrap();
+        assert_eq!(child_cause.root_message_id, 1);
+
+        let mut grandchild = Flattrs::new();
+        stamp_causality(&mut grandchild, Some(child_cause));
+        assert_eq!(grandchild.get(PARENT_MESSAGE_ID), Some(2));
+        assert_eq!(grandchild.get(ROOT_MESSAGE_ID), Some(1));
+
+        // A forwarded message keeps its links.
+        grandchild.set(TELEMETRY_MESSAGE_ID, 3);
+        stamp_causality(&mut grandchild, Causality::of(&child));
+        assert_eq!(grandchild.get(PARENT_MESSAGE_ID), Some(2));
+    }
+
     fn session(seq: u64) -> SeqInfo {
         SeqInfo::Session {
             session_id: Uuid::now_v7(),
@@ -272,6 +466,26 @@ mod tests {
         assert_eq!(headers.get(SENDER_ACTOR_ID), Some(owner));
     }
 
+    #[test]
+    fn test_schema_fingerprint_roundtrip() {
+        let data = wirevalue::Any::serialize(&crate::mailbox::MessageEnvelope::new_unknown(
+            handler_port("test_0").1,
+            wirevalue::Any::serialize(&1u64).unwrap(),
+        ))
+        .unwrap();
+        let mut headers = Flattrs::new();
+        set_schema_fingerprint(&mut headers, &data);
+        assert!(headers.get(SCHEMA_FINGERPRINT).is_some());
+        assert_eq!(check_schema_fingerprint(&headers, &data), Ok(()));
+
+        headers.set(SCHEMA_FINGERPRINT, 0);
+        let local = wirevalue::schema::fingerprint(data.typehash()).unwrap();
+        assert_eq!(check_schema_fingerprint(&headers, &data), Err((0, local)));
+
+        // Unstamped messages are always accepted.
+        assert_eq!(check_schema_fingerprint(&Flattrs::new(), &data), Ok(()));
+    }
+
     #[test]
     fn test_stamp_fresh_helper_skips_on_seq_5() {
         let (owner, dest) = handler_port("test_0");
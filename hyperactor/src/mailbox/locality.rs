@@ -0,0 +1,416 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Datacenter, zone, and host locality, and the cost of routing
+//! between localities.
+//!
+//! A [`Locality`] labels where a process runs: its datacenter, its
+//! zone, and its host. This process's locality is configured by
+//! [`config::LOCALITY`]; the localities of other hosts are labeled in
+//! the process-wide [`LocalityMap`], by the host names and IP addresses
+//! that appear in their channel addresses, so that any reference can be
+//! placed by the address of its proc.
+//!
+//! When a locality is configured, routers route by the [`CostAware`]
+//! policy, which routes each destination that is not bound exactly
+//! through its cheapest bound route under a [`CostModel`], so that dials
+//! prefer same-host, then same-zone, then same-datacenter links. Bytes
+//! dialed to another datacenter are counted by the
+//! `mailbox.cross_dc_bytes` metric, to quantify how much traffic
+//! crosses datacenters.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::OnceLock;
+
+use hyperactor_telemetry::opentelemetry::KeyValue;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::Addr;
+use crate::channel::ChannelAddr;
+use crate::config;
+use crate::mailbox::routing::ExactMatchFirst;
+use crate::mailbox::routing::Route;
+use crate::mailbox::routing::RoutingPolicy;
+use crate::mailbox::routing::RoutingTable;
+use crate::mailbox::routing::prefixes;
+use crate::mailbox::snapshot::Snapshot;
+use crate::metrics;
+
+/// Where a process runs. Unknown labels are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Locality {
+    /// The datacenter.
+    pub dc: Option<String>,
+    /// The zone within the datacenter.
+    pub zone: Option<String>,
+    /// The host.
+    pub host: Option<String>,
+}
+
+impl Locality {
+    /// This process's locality, as configured by [`config::LOCALITY`].
+    /// Invalid configurations are logged, and yield an unknown locality.
+    pub fn local() -> Self {
+        let labels = hyperactor_config::global::get(config::LOCALITY);
+        labels.parse().unwrap_or_else(|err| {
+            tracing::warn!("invalid {}: {}", config::LOCALITY.name(), err);
+            Self::default()
+        })
+    }
+
+    /// How close `other` is to this locality. Labels are compared
+    /// from the outside in, so that two localities in different
+    /// datacenters are never considered close.
+    pub fn proximity(&self, other: &Locality) -> Proximity {
+        fn same(a: &Option<String>, b: &Option<String>) -> Option<bool> {
+            Some(a.as_ref()? == b.as_ref()?)
+        }
+        match (
+            same(&self.dc, &other.dc),
+            same(&self.zone, &other.zone),
+            same(&self.host, &other.host),
+        ) {
+            (Some(false), _, _) => Proximity::CrossDc,
+            (_, _, Some(true)) => Proximity::SameHost,
+            (_, Some(true), _) => Proximity::SameZone,
+            (Some(true), _, _) => Proximity::SameDc,
+            _ => Proximity::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for Locality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let labels = [("dc", &self.dc), ("zone", &self.zone), ("host", &self.host)];
+        let mut sep = "";
+        for (name, value) in labels {
+            if let Some(value) = value {
+                write!(f, "{}{}={}", sep, name, value)?;
+                sep = ",";
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Locality {
+    type Err = anyhow::Error;
+
+    /// Parse comma-separated `dc`, `zone`, and `host` labels, for
+    /// example `dc=east,zone=east-1,host=node7`. Labels may be omitted.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut locality = Self::default();
+        for label in s.split(',').map(str::trim).filter(|l| !l.is_empty()) {
+            let (name, value) = label
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("invalid locality label {}", label))?;
+            let slot = match name.trim() {
+                "dc" => &mut locality.dc,
+                "zone" => &mut locality.zone,
+                "host" => &mut locality.host,
+                other => anyhow::bail!("unknown locality label {}", other),
+            };
+            *slot = Some(value.trim().to_string());
+        }
+        Ok(locality)
+    }
+}
+
+/// How close two localities are, from closest to farthest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Proximity {
+    /// On the same host.
+    SameHost,
+    /// In the same zone.
+    SameZone,
+    /// In the same datacenter.
+    SameDc,
+    /// In different datacenters.
+    CrossDc,
+    /// Not known, as the localities are not labeled.
+    Unknown,
+}
+
+/// The localities of hosts, labeled by the host names and IP addresses
+/// that appear in their channel addresses. The labels are read on
+/// every routed post, so they are kept in a [`Snapshot`].
+#[derive(Debug, Default)]
+pub struct LocalityMap {
+    hosts: Snapshot<HashMap<String, Locality>>,
+}
+
+impl LocalityMap {
+    /// The process-wide map, consulted by configured [`CostAware`]
+    /// routing and by the `mailbox.cross_dc_bytes` metric.
+    pub fn global() -> Arc<LocalityMap> {
+        static GLOBAL: OnceLock<Arc<LocalityMap>> = OnceLock::new();
+        Arc::clone(GLOBAL.get_or_init(Arc::default))
+    }
+
+    /// Label `host`, a host name or an IP address as it appears in
+    /// channel addresses, with `locality`.
+    pub fn label(&self, host: impl Into<String>, locality: Locality) {
+        let host = host.into();
+        self.hosts.update(|hosts| {
+            hosts.insert(host, locality);
+        });
+    }
+
+    /// Remove the label of `host`.
+    pub fn unlabel(&self, host: &str) {
+        if !self.hosts.load().contains_key(host) {
+            return;
+        }
+        self.hosts.update(|hosts| {
+            hosts.remove(host);
+        });
+    }
+
+    /// The locality of the host of `addr`, if labeled. Local and Unix
+    /// channel addresses are always on this process's host.
+    pub fn of(&self, addr: &ChannelAddr) -> Option<Locality> {
+        match host_of(addr) {
+            None => Some(Locality::local()),
+            Some(host) => self.hosts.load().get(&*host).cloned(),
+        }
+    }
+
+    /// The locality of the proc of the reference `dest`, if labeled.
+    pub fn of_ref(&self, dest: &Addr) -> Option<Locality> {
+        self.of(dest.proc_addr().addr())
+    }
+}
+
+/// The host of `addr`, as it appears in the address, or `None` for
+/// addresses, such as Local and Unix ones, that are always on the local
+/// host.
+pub(crate) fn host_of(addr: &ChannelAddr) -> Option<Cow<'_, str>> {
+    match addr {
+        ChannelAddr::Local(_) | ChannelAddr::Unix(_) => None,
+        ChannelAddr::Tcp(socket_addr) => Some(Cow::Owned(socket_addr.ip().to_string())),
+        ChannelAddr::Dns(dns_addr) => Some(Cow::Borrowed(&dns_addr.name)),
+        ChannelAddr::MetaTls(tls_addr)
+        | ChannelAddr::Tls(tls_addr)
+        | ChannelAddr::Quic(tls_addr)
+        | ChannelAddr::MetaQuic(tls_addr) => Some(Cow::Borrowed(tls_addr.hostname())),
+        ChannelAddr::Alias { dial_to, .. } => host_of(dial_to),
+    }
+}
+
+/// The relative costs of routing at each [`Proximity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CostModel {
+    /// The cost of a route on the same host.
+    pub same_host: u64,
+    /// The cost of a route in the same zone.
+    pub same_zone: u64,
+    /// The cost of a route in the same datacenter.
+    pub same_dc: u64,
+    /// The cost of a route to another datacenter.
+    pub cross_dc: u64,
+    /// The cost of a route whose locality is unknown.
+    pub unknown: u64,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self {
+            same_host: 0,
+            same_zone: 1,
+            same_dc: 10,
+            cross_dc: 100,
+            unknown: 50,
+        }
+    }
+}
+
+impl CostModel {
+    /// The cost of a route at `proximity`.
+    pub fn cost(&self, proximity: Proximity) -> u64 {
+        match proximity {
+            Proximity::SameHost => self.same_host,
+            Proximity::SameZone => self.same_zone,
+            Proximity::SameDc => self.same_dc,
+            Proximity::CrossDc => self.cross_dc,
+            Proximity::Unknown => self.unknown,
+        }
+    }
+}
+
+/// Routes a destination through the route bound to exactly it, if any,
+/// and otherwise through the cheapest of its bound prefixes whose
+/// channel address is known, under a [`CostModel`], preferring longer
+/// prefixes among equally cheap ones. Routes are placed with a
+/// [`LocalityMap`]. Destinations without such a route are routed as
+/// [`ExactMatchFirst`] does.
+#[derive(Debug, Clone)]
+pub struct CostAware {
+    local: Locality,
+    model: CostModel,
+    map: Arc<LocalityMap>,
+}
+
+impl CostAware {
+    /// Route from `local` under `model`, placing routes with `map`.
+    pub fn new(local: Locality, model: CostModel, map: Arc<LocalityMap>) -> Self {
+        Self { local, model, map }
+    }
+
+    /// Route from this process's configured locality under the default
+    /// cost model, placing routes with the global [`LocalityMap`].
+    pub fn configured() -> Self {
+        Self::new(
+            Locality::local(),
+            CostModel::default(),
+            LocalityMap::global(),
+        )
+    }
+
+    fn cost(&self, addr: &ChannelAddr) -> u64 {
+        let proximity = if host_of(addr).is_none() {
+            Proximity::SameHost
+        } else {
+            match self.map.of(addr) {
+                Some(locality) => self.local.proximity(&locality),
+                None => Proximity::Unknown,
+            }
+        };
+        self.model.cost(proximity)
+    }
+}
+
+impl RoutingPolicy for CostAware {
+    fn route<'a>(&self, table: &'a dyn RoutingTable, dest: &Addr) -> Option<Route<'a>> {
+        if let Some(route) = table.get(dest) {
+            return Some(route);
+        }
+        prefixes(dest)
+            .iter()
+            .filter_map(|prefix| table.get(prefix))
+            .filter_map(|route| Some((self.cost(route.addr?), route)))
+            // The first of the cheapest routes, which is the longest.
+            .min_by_key(|(cost, _)| *cost)
+            .map(|(_, route)| route)
+            .or_else(|| ExactMatchFirst.route(table, dest))
+    }
+}
+
+/// The datacenters between which a dialed link carries messages, whose
+/// bytes are counted by the `mailbox.cross_dc_bytes` metric. A link is
+/// placed once, when it is dialed, with the global [`LocalityMap`].
+#[derive(Debug)]
+pub(crate) struct CrossDc {
+    attributes: [KeyValue; 2],
+}
+
+impl CrossDc {
+    /// The datacenters linked by dialing `addr`, if it is labeled as
+    /// being in another datacenter than this process.
+    pub(crate) fn of(addr: &ChannelAddr) -> Option<Self> {
+        host_of(addr)?;
+        let remote = LocalityMap::global().of(addr)?;
+        let local = Locality::local();
+        (local.proximity(&remote) == Proximity::CrossDc).then(|| Self {
+            attributes: [
+                KeyValue::new("src_dc", local.dc.unwrap_or_default()),
+                KeyValue::new("dest_dc", remote.dc.unwrap_or_default()),
+            ],
+        })
+    }
+
+    /// Count `bytes` sent over the link.
+    pub(crate) fn record(&self, bytes: usize) {
+        metrics::MAILBOX_CROSS_DC_BYTES.add(bytes as u64, &self.attributes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::testing::ids::test_actor_id;
+    use crate::testing::ids::test_port_id;
+    use crate::testing::ids::test_proc_id;
+
+    fn locality(s: &str) -> Locality {
+        s.parse().unwrap()
+    }
+
+    fn tcp(addr: &str) -> ChannelAddr {
+        ChannelAddr::Tcp(addr.parse().unwrap())
+    }
+
+    #[test]
+    fn test_locality() {
+        let here = locality("dc=east, zone=east-1,host=node1");
+        assert_eq!(here.to_string(), "dc=east,zone=east-1,host=node1");
+        assert_eq!(locality(""), Locality::default());
+        assert!("dc".parse::<Locality>().is_err());
+        assert!("rack=1".parse::<Locality>().is_err());
+
+        let cases = [
+            ("dc=east,zone=east-1,host=node1", Proximity::SameHost),
+            ("dc=east,zone=east-1,host=node2", Proximity::SameZone),
+            ("dc=east,zone=east-2,host=node3", Proximity::SameDc),
+            ("dc=west,zone=east-1,host=node1", Proximity::CrossDc),
+            ("zone=east-1", Proximity::SameZone),
+            ("dc=east", Proximity::SameDc),
+            ("", Proximity::Unknown),
+        ];
+        for (other, proximity) in cases {
+            assert_eq!(here.proximity(&locality(other)), proximity, "{}", other);
+        }
+    }
+
+    #[test]
+    fn test_cost_aware() {
+        let map = Arc::new(LocalityMap::default());
+        map.label("10.1.0.1", locality("dc=east,zone=east-1,host=a"));
+        map.label("10.1.0.2", locality("dc=east,zone=east-2,host=b"));
+        map.label("10.2.0.1", locality("dc=west,zone=west-1,host=c"));
+
+        let proc: Addr = test_proc_id("proc").into();
+        let actor: Addr = test_actor_id("proc", "actor").into();
+        let port: Addr = test_port_id("proc", "actor", 1).into();
+        let mut table = BTreeMap::new();
+        table.insert(proc.clone(), tcp("10.1.0.2:1"));
+        table.insert(actor.clone(), tcp("10.2.0.1:1"));
+
+        let routed = |policy: &CostAware, table: &BTreeMap<Addr, ChannelAddr>, dest: &Addr| {
+            policy.route(table, dest).map(|route| route.key.clone())
+        };
+        let east = CostAware::new(
+            locality("dc=east,zone=east-1,host=z"),
+            CostModel::default(),
+            Arc::clone(&map),
+        );
+        let west = CostAware::new(
+            locality("dc=west,zone=west-1,host=z"),
+            CostModel::default(),
+            Arc::clone(&map),
+        );
+        // A route bound to exactly the destination is always taken.
+        assert_eq!(routed(&east, &table, &actor), Some(actor.clone()));
+        // Otherwise, the same-datacenter route is cheaper than the
+        // longer cross-datacenter one.
+        assert_eq!(routed(&east, &table, &port), Some(proc.clone()));
+        assert_eq!(routed(&west, &table, &port), Some(actor.clone()));
+
+        // Equally cheap routes prefer the longer prefix.
+        table.insert(proc.clone(), tcp("10.9.0.1:1"));
+        table.insert(actor.clone(), tcp("10.9.0.2:1"));
+        assert_eq!(routed(&east, &table, &port), Some(actor.clone()));
+    }
+}
@@ -12,9 +12,12 @@ use serde::Serialize;
 pub use crate as hyperactor;
 use crate::HandleClient;
 use crate::Handler;
+use crate::OncePortRef;
+use crate::PortAddr;
 use crate::ProcAddr;
 use crate::RefClient;
 use crate::mailbox::ChannelAddr;
+use crate::mailbox::MessageEnvelope;
 
 /// Messages relating to mailbox administration.
 #[derive(
@@ -37,4 +40,53 @@ pub enum MailboxAdminMessage {
         /// The address at which it listens.
         addr: ChannelAddr,
     },
+
+    /// Close a port: messages subsequently posted to it are returned
+    /// to their senders as undeliverable, until the port is reset. If
+    /// the port was draining, the messages it held are returned too.
+    ClosePort {
+        /// The port to close.
+        port: PortAddr,
+    },
+
+    /// Drain a port: messages subsequently posted to it are held by
+    /// the mailbox instead of being delivered, until the port is
+    /// reset. Replies with, and removes, the messages queued at the
+    /// port but not yet received, followed by the messages held so
+    /// far. Messages already dispatched to an actor's handlers are
+    /// not in the port's queue, and are not dumped.
+    DrainPort {
+        /// The port to drain.
+        port: PortAddr,
+
+        /// Receives the dump of held messages.
+        #[reply]
+        reply: OncePortRef<PortDump>,
+    },
+
+    /// Reset a closed or draining port, resuming normal delivery.
+    /// Messages still held by a draining port are delivered, and the
+    /// port's idempotency keys are forgotten.
+    ResetPort {
+        /// The port to reset.
+        port: PortAddr,
+    },
+}
+wirevalue::register_type!(MailboxAdminMessage);
+
+/// Messages held by a draining port; see
+/// [`MailboxAdminMessage::DrainPort`].
+#[derive(Debug, Serialize, Deserialize, Clone, typeuri::Named)]
+pub struct PortDump {
+    /// The drained port.
+    pub port: PortAddr,
+
+    /// The held messages, in the order they were posted.
+    pub messages: Vec<MessageEnvelope>,
+
+    /// The number of messages returned to their senders because the
+    /// port held [`crate::config::MAILBOX_PORT_DUMP_CAPACITY`]
+    /// messages already.
+    pub dropped: usize,
 }
+wirevalue::register_type!(PortDump);
@@ -0,0 +1,352 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Versioned message evolution.
+//!
+//! Every [`MessageEnvelope`](crate::mailbox::MessageEnvelope) carries
+//! the wire version of its payload in its [`MESSAGE_VERSION`] header,
+//! stamped by the sending binary from its [`MessageMigrate`]
+//! registration for the payload type (types without a registration are
+//! at version 0, and carry no header). On delivery, the receiving port
+//! compares the envelope version against its own and runs the type's
+//! upgrade or downgrade hook before the payload is decoded. This lets
+//! binaries built against different versions of a message type coexist
+//! in one mesh during a rolling upgrade, including binaries that
+//! predate versioning, whose envelopes are read as version 0. Cast
+//! messages carry the sender's version in their end-to-end headers, and
+//! are migrated by the comm actor that delivers them.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize, Named)]
+//! struct Greeting { name: String, lang: String }
+//!
+//! impl MessageMigrate for Greeting {
+//!     const VERSION: MessageVersion = 1;
+//!
+//!     fn upgrade(from: MessageVersion, data: &wirevalue::Any) -> anyhow::Result<Self> {
+//!         anyhow::ensure!(from == 0);
+//!         let v0: v0::Greeting = data.deserialized_unchecked()?;
+//!         Ok(Greeting { name: v0.name, lang: "en".into() })
+//!     }
+//! }
+//!
+//! hyperactor::register_migration!(Greeting);
+//! ```
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use hyperactor_config::Flattrs;
+
+use crate::RemoteMessage;
+use crate::mailbox::headers::MESSAGE_VERSION;
+
+/// The wire version of a message type.
+pub type MessageVersion = u32;
+
+/// Per-type hooks for converting payloads produced by binaries built
+/// against a different version of the type. Register implementations
+/// with [`crate::register_migration`].
+pub trait MessageMigrate: RemoteMessage {
+    /// The version of this type produced by the current binary.
+    const VERSION: MessageVersion;
+
+    /// Decode a payload encoded at an older version `from` into the
+    /// current version.
+    fn upgrade(from: MessageVersion, data: &wirevalue::Any) -> Result<Self, anyhow::Error>;
+
+    /// Decode a payload encoded at a newer version `from` into the
+    /// current version. Types whose newer versions are not readable by
+    /// older binaries leave the default, which rejects the payload.
+    fn downgrade(from: MessageVersion, data: &wirevalue::Any) -> Result<Self, anyhow::Error> {
+        let _ = data;
+        Err(anyhow::anyhow!(
+            "cannot read version {} of {}: newest known version is {}",
+            from,
+            Self::typename(),
+            Self::VERSION
+        ))
+    }
+}
+
+/// Register a [`MessageMigrate`] implementation so that envelopes
+/// carrying the type are stamped with its version, and received
+/// payloads are migrated to it.
+///
+/// Example:
+///
+/// ```ignore
+/// impl MessageMigrate for MyMessage { ... }
+///
+/// register_migration!(MyMessage);
+/// ```
+#[macro_export]
+macro_rules! register_migration {
+    ($type:ty) => {
+        $crate::internal_macro_support::inventory::submit! {
+            $crate::mailbox::migrate::MigrationInfo {
+                typehash: <$type as $crate::internal_macro_support::typeuri::Named>::typehash,
+                version: || <$type as $crate::mailbox::migrate::MessageMigrate>::VERSION,
+                migrate: $crate::mailbox::migrate::migrate_erased::<$type>,
+            }
+        }
+    };
+}
+
+/// A type-erased migration registration entry. These are constructed
+/// via [`crate::register_migration`].
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct MigrationInfo {
+    /// The typehash of the migrated type.
+    pub typehash: fn() -> u64,
+    /// The type's [`MessageMigrate::VERSION`].
+    pub version: fn() -> MessageVersion,
+    /// Migrates a payload at the given version to the local version.
+    pub migrate: fn(MessageVersion, &wirevalue::Any) -> Result<wirevalue::Any, anyhow::Error>,
+}
+
+inventory::collect!(MigrationInfo);
+
+static MIGRATIONS: LazyLock<HashMap<u64, &'static MigrationInfo>> = LazyLock::new(|| {
+    inventory::iter::<MigrationInfo>()
+        .map(|info| ((info.typehash)(), info))
+        .collect()
+});
+
+#[doc(hidden)]
+pub fn migrate_erased<M: MessageMigrate>(
+    from: MessageVersion,
+    data: &wirevalue::Any,
+) -> Result<wirevalue::Any, anyhow::Error> {
+    let message = if from < M::VERSION {
+        M::upgrade(from, data)?
+    } else {
+        M::downgrade(from, data)?
+    };
+    Ok(wirevalue::Any::serialize(&message)?)
+}
+
+/// The local wire version for the type with the provided typehash.
+pub fn local_version(typehash: u64) -> MessageVersion {
+    MIGRATIONS.get(&typehash).map_or(0, |info| (info.version)())
+}
+
+/// The wire version of the payload of a message with `headers`.
+pub fn version_of(headers: &Flattrs) -> MessageVersion {
+    headers.get(MESSAGE_VERSION).unwrap_or(0)
+}
+
+/// Record `version` as the wire version of the payload of a message
+/// with `headers`.
+pub fn set_version(headers: &mut Flattrs, version: MessageVersion) {
+    if version == 0 {
+        headers.remove(MESSAGE_VERSION);
+    } else {
+        headers.set(MESSAGE_VERSION, version);
+    }
+}
+
+/// A payload could not be migrated to the local version of its type.
+#[derive(Debug, thiserror::Error)]
+#[error("cannot migrate {typename} from version {from} to version {to}: {error}")]
+pub struct MigrateError {
+    /// The payload's typename, if registered.
+    pub typename: String,
+    /// The version the payload was encoded at.
+    pub from: MessageVersion,
+    /// The local version of the type.
+    pub to: MessageVersion,
+    /// The error returned by the migration hook.
+    #[source]
+    pub error: anyhow::Error,
+}
+
+/// Bring `data`, encoded at wire version `version`, to the local
+/// version of its type. Payloads already at the local version are
+/// returned as is. On failure, the original payload is returned
+/// along with the error.
+pub(crate) fn to_local(
+    version: MessageVersion,
+    data: wirevalue::Any,
+) -> Result<wirevalue::Any, (wirevalue::Any, MigrateError)> {
+    let info = MIGRATIONS.get(&data.typehash());
+    let local = info.map_or(0, |info| (info.version)());
+    if version == local {
+        return Ok(data);
+    }
+    let result = match info {
+        Some(info) => (info.migrate)(version, &data),
+        None => Err(anyhow::anyhow!("no migration is registered")),
+    };
+    result.map_err(|error| {
+        let typename = data.typename().unwrap_or("unknown").to_string();
+        (
+            data,
+            MigrateError {
+                typename,
+                from: version,
+                to: local,
+                error,
+            },
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor_config::Flattrs;
+    use serde::Deserialize;
+    use serde::Serialize;
+    use typeuri::Named;
+
+    use super::*;
+    use crate::mailbox::Mailbox;
+    use crate::mailbox::MailboxSender;
+    use crate::mailbox::MessageEnvelope;
+    use crate::testing::ids::test_actor_id;
+
+    mod v0 {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, Named)]
+        #[named(name = "hyperactor::mailbox::migrate::tests::Greeting")]
+        pub struct Greeting {
+            pub name: String,
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize, Named)]
+    #[named(name = "hyperactor::mailbox::migrate::tests::Greeting")]
+    struct Greeting {
+        name: String,
+        lang: String,
+    }
+
+    impl MessageMigrate for Greeting {
+        const VERSION: MessageVersion = 1;
+
+        fn upgrade(from: MessageVersion, data: &wirevalue::Any) -> Result<Self, anyhow::Error> {
+            anyhow::ensure!(from == 0, "unknown version {}", from);
+            let v0: v0::Greeting = data.deserialized_unchecked()?;
+            Ok(Greeting {
+                name: v0.name,
+                lang: "en".to_string(),
+            })
+        }
+    }
+
+    crate::register_migration!(Greeting);
+
+    #[test]
+    fn test_local_version() {
+        assert_eq!(local_version(Greeting::typehash()), 1);
+        assert_eq!(local_version(u64::typehash()), 0);
+    }
+
+    #[test]
+    fn test_version_header() {
+        let dest = test_actor_id("0", "test").port_addr(crate::Port::from(0));
+        let envelope =
+            MessageEnvelope::serialize(test_actor_id("1", "sender"), dest, &7u64, Flattrs::new())
+                .unwrap();
+        // Unversioned payloads carry no header, as from binaries that
+        // predate versioning.
+        assert!(!envelope.headers().contains_key(MESSAGE_VERSION));
+        assert_eq!(envelope.version(), 0);
+
+        let envelope = envelope.with_version(3);
+        assert_eq!(envelope.headers().get(MESSAGE_VERSION), Some(3));
+        let envelope = envelope.with_version(0);
+        assert!(!envelope.headers().contains_key(MESSAGE_VERSION));
+    }
+
+    #[test]
+    fn test_upgrade() {
+        let data = wirevalue::Any::serialize(&v0::Greeting {
+            name: "world".to_string(),
+        })
+        .unwrap();
+        let upgraded = to_local(0, data).unwrap();
+        assert_eq!(
+            upgraded.deserialized::<Greeting>().unwrap(),
+            Greeting {
+                name: "world".to_string(),
+                lang: "en".to_string(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mailbox_upgrades_on_delivery() {
+        let mbox = Mailbox::new(test_actor_id("0", "test"));
+        let (port, mut receiver) = mbox.open_port::<Greeting>();
+        let port = port.bind();
+        let (return_handle, mut return_receiver) =
+            crate::mailbox::undeliverable::new_undeliverable_port();
+
+        let data = wirevalue::Any::serialize(&v0::Greeting {
+            name: "world".to_string(),
+        })
+        .unwrap();
+        let envelope = MessageEnvelope::new(
+            test_actor_id("1", "sender"),
+            port.port_addr().clone(),
+            data,
+            Flattrs::new(),
+        );
+        assert_eq!(envelope.version(), 1);
+        mbox.post(envelope.with_version(0), return_handle.clone());
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            Greeting {
+                name: "world".to_string(),
+                lang: "en".to_string(),
+            }
+        );
+
+        // Payloads from a newer binary are returned without a downgrade hook.
+        let data = wirevalue::Any::serialize(&Greeting {
+            name: "world".to_string(),
+            lang: "fr".to_string(),
+        })
+        .unwrap();
+        mbox.post(
+            MessageEnvelope::new(
+                test_actor_id("1", "sender"),
+                port.port_addr().clone(),
+                data,
+                Flattrs::new(),
+            )
+            .with_version(2),
+            return_handle,
+        );
+        let envelope = return_receiver
+            .recv()
+            .await
+            .unwrap()
+            .into_message()
+            .expect("expected returned envelope");
+        assert_eq!(envelope.version(), 2);
+    }
+
+    #[test]
+    fn test_downgrade_unsupported() {
+        let data = wirevalue::Any::serialize(&Greeting {
+            name: "world".to_string(),
+            lang: "fr".to_string(),
+        })
+        .unwrap();
+        let (returned, err) = to_local(2, data.clone()).unwrap_err();
+        assert_eq!(returned, data);
+        assert_eq!((err.from, err.to), (2, 1));
+
+        let (_, err) = to_local(1, wirevalue::Any::serialize(&7u64).unwrap()).unwrap_err();
+        assert_eq!((err.from, err.to), (1, 0));
+    }
+}
@@ -0,0 +1,624 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Shadow-traffic mirroring.
+//!
+//! A [`MirroringSender`] tries out a new implementation of an actor on
+//! live traffic. It sends every message on to its sender as usual, and
+//! also sends a copy of each matching message addressed to a handler
+//! of the *primary* actor to the same handler of a *shadow* actor (the
+//! implementation under test). The primary keeps serving: copies are
+//! posted without undeliverable returns, so a failing shadow is never
+//! visible to the senders. Copies are marked [`MIRRORED`]: while the
+//! shadow handles one, everything it posts to other actors is dropped,
+//! including replies to ports carried inside the message, so the shadow
+//! cannot reach the requesters or act on the live system.
+//!
+//! ```ignore
+//! let sender = MirroringSender::new(proc.clone().into_boxed())
+//!     .mirror(primary, canary, MessageMatch::type_of::<Lookup>())
+//!     .diff_responses(&proc, "lookup_mirror", |responses: MirroredResponses| {
+//!         if responses.differs() {
+//!             tracing::warn!("canary diverged: {:?}", responses);
+//!         }
+//!     })?;
+//! ```
+//!
+//! With [`MirroringSender::diff_responses`], the responses to mirrored
+//! requests that carry a [`REPLY_TO`] header (see
+//! [`Context::respond`](crate::Context::respond)) are compared: the
+//! primary's response is relayed to the requester, the shadow's is
+//! withheld, and both are handed to a [`ResponseDiffer`] once they are
+//! in, or after [`config::MIRROR_RESPONSE_TIMEOUT`]. A request is
+//! forgotten once it expires: a primary response arriving later is
+//! returned to the primary as undeliverable. Replies to ports carried
+//! inside a message are not compared.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::Weak;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use async_trait::async_trait;
+use tokio::time::Instant;
+
+use crate::ActorAddr;
+use crate::PortAddr;
+use crate::Proc;
+use crate::WeakProc;
+use crate::config;
+use crate::mailbox::BoxedMailboxSender;
+use crate::mailbox::DeliveryFailure;
+use crate::mailbox::MailboxSender;
+use crate::mailbox::MessageEnvelope;
+use crate::mailbox::PortGone;
+use crate::mailbox::PortHandle;
+use crate::mailbox::Undeliverable;
+use crate::mailbox::content_router::MessageMatch;
+use crate::mailbox::headers::MIRRORED;
+use crate::mailbox::headers::PARENT_MESSAGE_ID;
+use crate::mailbox::headers::REPLY_TO;
+use crate::mailbox::monitored_return_handle;
+use crate::metrics;
+use crate::ordering::SEQ_INFO;
+use crate::ordering::SeqInfo;
+use crate::port::Port;
+
+/// The responses of the primary and shadow actors to a mirrored
+/// request.
+#[derive(Debug, Clone)]
+pub struct MirroredResponses {
+    /// The actor that served the request.
+    pub primary: ActorAddr,
+    /// The actor to which the request was mirrored.
+    pub shadow: ActorAddr,
+    /// The name of the request's type, if it is registered.
+    pub typename: Option<String>,
+    /// The [`PARENT_MESSAGE_ID`] of the request: the message whose
+    /// handler sent it, if any.
+    pub parent_message_id: Option<u64>,
+    /// The primary's response, or `None` if it did not respond in time.
+    pub primary_response: Option<wirevalue::Any>,
+    /// The shadow's response, or `None` if it did not respond in time.
+    pub shadow_response: Option<wirevalue::Any>,
+}
+
+impl MirroredResponses {
+    /// Whether the responses differ: only one actor responded, or both
+    /// did with different values. Values are compared as JSON if both
+    /// types are registered, and by their encoding otherwise.
+    pub fn differs(&self) -> bool {
+        match (&self.primary_response, &self.shadow_response) {
+            (None, None) => false,
+            (Some(primary), Some(shadow)) => match (primary.dump(), shadow.dump()) {
+                (Ok(primary), Ok(shadow)) => primary != shadow,
+                _ => {
+                    primary.typehash() != shadow.typehash()
+                        || primary.encoded_bytes() != shadow.encoded_bytes()
+                }
+            },
+            _ => true,
+        }
+    }
+}
+
+/// Compares the responses to mirrored requests; see
+/// [`MirroringSender::diff_responses`]. Implemented for closures.
+pub trait ResponseDiffer: Send + Sync + 'static {
+    /// Called once for each mirrored request that carries a
+    /// [`REPLY_TO`] header.
+    fn diff(&self, responses: MirroredResponses);
+}
+
+impl<F: Fn(MirroredResponses) + Send + Sync + 'static> ResponseDiffer for F {
+    fn diff(&self, responses: MirroredResponses) {
+        self(responses)
+    }
+}
+
+/// A rule of a [`MirroringSender`].
+#[derive(Debug, Clone)]
+struct MirrorRule {
+    primary: ActorAddr,
+    shadow: ActorAddr,
+    when: MessageMatch,
+}
+
+/// A [`MailboxSender`] that sends copies of selected messages to
+/// shadow actors. See the [module documentation](self).
+#[derive(Clone)]
+pub struct MirroringSender {
+    sender: BoxedMailboxSender,
+    rules: Arc<Vec<MirrorRule>>,
+    diffing: Option<(ActorAddr, Arc<Exchanges>)>,
+}
+
+impl MirroringSender {
+    /// A sender that sends all messages to `sender`, until mirrors are
+    /// added with [`MirroringSender::mirror`]. Copies are sent through
+    /// `sender` as well.
+    pub fn new(sender: BoxedMailboxSender) -> Self {
+        Self {
+            sender,
+            rules: Arc::new(Vec::new()),
+            diffing: None,
+        }
+    }
+
+    /// Mirror the messages to `primary`'s handlers that match `when` to
+    /// the same handlers of `shadow`. Only the first rule that matches
+    /// a message applies.
+    pub fn mirror(mut self, primary: ActorAddr, shadow: ActorAddr, when: MessageMatch) -> Self {
+        Arc::make_mut(&mut self.rules).push(MirrorRule {
+            primary,
+            shadow,
+            when,
+        });
+        self
+    }
+
+    /// Compare the responses of the primary and shadow actors with
+    /// `differ`. Responses are collected by a root actor bound in
+    /// `proc` with the provided name, through which the primary's
+    /// responses are relayed to the requesters. Must be called within a
+    /// Tokio runtime, which runs the sweep that expires requests.
+    pub fn diff_responses(
+        mut self,
+        proc: &Proc,
+        name: &str,
+        differ: impl ResponseDiffer,
+    ) -> Result<Self, anyhow::Error> {
+        let exchanges = Arc::new(Exchanges {
+            proc: proc.downgrade(),
+            differ: Box::new(differ),
+            next_id: AtomicU64::new(0),
+            pending: Mutex::new(HashMap::new()),
+        });
+        let sink = proc.bind_sender(name, ResponseSink(Arc::downgrade(&exchanges)))?;
+        tokio::spawn(Exchanges::sweep(Arc::downgrade(&exchanges)));
+        self.diffing = Some((sink, exchanges));
+        Ok(self)
+    }
+
+    /// The rule that applies to `envelope`, if any.
+    fn rule(&self, envelope: &MessageEnvelope) -> Option<&MirrorRule> {
+        if !envelope.dest().is_handler_port() {
+            return None;
+        }
+        self.rules.iter().find(|rule| {
+            envelope.dest().actor_id() == rule.primary.id() && rule.when.matches(envelope)
+        })
+    }
+}
+
+impl fmt::Debug for MirroringSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MirroringSender")
+            .field("rules", &self.rules)
+            .field("diffing", &self.diffing.as_ref().map(|(sink, _)| sink))
+            .finish()
+    }
+}
+
+#[async_trait]
+impl MailboxSender for MirroringSender {
+    fn post_unchecked(
+        &self,
+        mut envelope: MessageEnvelope,
+        return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+    ) {
+        if let Some(rule) = self.rule(&envelope) {
+            let dest = rule.shadow.port_addr(envelope.dest().port());
+            let mut shadow = envelope.clone().with_dest(dest);
+            shadow.set_return_undeliverable(false);
+            // The copy is not part of the sender's session with the shadow.
+            shadow.headers_mut().set(SEQ_INFO, SeqInfo::Direct);
+            shadow.headers_mut().set(MIRRORED, false);
+
+            if let Some((sink, exchanges)) = &self.diffing
+                && let Some(reply_to) = envelope.headers().get(REPLY_TO)
+            {
+                let id = exchanges.open(rule, &envelope, reply_to);
+                envelope
+                    .headers_mut()
+                    .set(REPLY_TO, sink.port_addr(Port::ephemeral(2 * id)));
+                shadow
+                    .headers_mut()
+                    .set(REPLY_TO, sink.port_addr(Port::ephemeral(2 * id + 1)));
+                // Let the shadow's response through to the sink.
+                shadow.headers_mut().set(MIRRORED, true);
+            }
+
+            metrics::MAILBOX_MIRRORED.add(
+                1,
+                hyperactor_telemetry::kv_pairs!("shadow" => rule.shadow.to_string()),
+            );
+            self.sender.post(shadow, monitored_return_handle());
+        }
+        self.sender.post(envelope, return_handle)
+    }
+
+    async fn flush(&self) -> Result<(), anyhow::Error> {
+        self.sender.flush().await
+    }
+
+    fn backlog(&self, dest: &PortAddr) -> u64 {
+        self.sender.backlog(dest)
+    }
+
+    fn backlog_drained(&self) -> Option<Arc<tokio::sync::Notify>> {
+        self.sender.backlog_drained()
+    }
+}
+
+/// A mirrored request whose responses are being collected.
+struct Exchange {
+    responses: MirroredResponses,
+    /// The requester's reply port, until the primary responds.
+    reply_to: Option<PortAddr>,
+    /// When the responses are reported, whether or not they are in.
+    expires: Instant,
+}
+
+/// The mirrored requests whose responses are being collected. A
+/// request's responses are sent to the sink's ephemeral ports `2 * id`
+/// (primary) and `2 * id + 1` (shadow).
+struct Exchanges {
+    proc: WeakProc,
+    differ: Box<dyn ResponseDiffer>,
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<u64, Exchange>>,
+}
+
+impl Exchanges {
+    /// Start collecting the responses to `envelope`, returning its id.
+    fn open(
+        self: &Arc<Self>,
+        rule: &MirrorRule,
+        envelope: &MessageEnvelope,
+        reply_to: PortAddr,
+    ) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let timeout = hyperactor_config::global::get(config::MIRROR_RESPONSE_TIMEOUT);
+        let exchange = Exchange {
+            responses: MirroredResponses {
+                primary: rule.primary.clone(),
+                shadow: rule.shadow.clone(),
+                typename: envelope.data().typename().map(str::to_string),
+                parent_message_id: envelope.headers().get(PARENT_MESSAGE_ID),
+                primary_response: None,
+                shadow_response: None,
+            },
+            reply_to: Some(reply_to),
+            expires: Instant::now() + timeout,
+        };
+        self.pending.lock().unwrap().insert(id, exchange);
+        id
+    }
+
+    /// Expire the requests of `exchanges` until they are dropped,
+    /// checking a few times per [`config::MIRROR_RESPONSE_TIMEOUT`].
+    async fn sweep(exchanges: Weak<Self>) {
+        loop {
+            let timeout = hyperactor_config::global::get(config::MIRROR_RESPONSE_TIMEOUT);
+            tokio::time::sleep(timeout / 4).await;
+            match exchanges.upgrade() {
+                Some(exchanges) => exchanges.expire(Instant::now()),
+                None => return,
+            }
+        }
+    }
+
+    /// Forget the requests that expired by `now`, reporting the
+    /// responses collected so far.
+    fn expire(&self, now: Instant) {
+        let expired: Vec<_> = {
+            let mut pending = self.pending.lock().unwrap();
+            let ids: Vec<_> = pending
+                .iter()
+                .filter(|(_, exchange)| exchange.expires <= now)
+                .map(|(id, _)| *id)
+                .collect();
+            ids.into_iter()
+                .filter_map(|id| pending.remove(&id))
+                .collect()
+        };
+        for exchange in expired {
+            self.report(exchange.responses);
+        }
+    }
+
+    fn report(&self, responses: MirroredResponses) {
+        if responses.differs() {
+            metrics::MAILBOX_MIRROR_MISMATCHES.add(
+                1,
+                hyperactor_telemetry::kv_pairs!("shadow" => responses.shadow.to_string()),
+            );
+        }
+        self.differ.diff(responses);
+    }
+
+    /// Collect a response posted to the sink, relaying the primary's to
+    /// the requester.
+    fn respond(
+        &self,
+        envelope: MessageEnvelope,
+        return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+    ) {
+        let Some(index) = envelope.dest().ephemeral_index() else {
+            return Self::port_gone(envelope, return_handle);
+        };
+        let (id, from_shadow) = (index / 2, index % 2 == 1);
+
+        let mut pending = self.pending.lock().unwrap();
+        let Some(exchange) = pending.get_mut(&id) else {
+            drop(pending);
+            if from_shadow {
+                // Late, or a duplicate: it was already reported.
+                return;
+            }
+            return Self::port_gone(envelope, return_handle);
+        };
+
+        let relay = if from_shadow {
+            if exchange.responses.shadow_response.is_none() {
+                exchange.responses.shadow_response = Some(envelope.data().clone());
+            }
+            None
+        } else {
+            let Some(reply_to) = exchange.reply_to.take() else {
+                drop(pending);
+                return Self::port_gone(envelope, return_handle);
+            };
+            exchange.responses.primary_response = Some(envelope.data().clone());
+            let mut envelope = envelope.with_dest(reply_to);
+            envelope.headers_mut().set(SEQ_INFO, SeqInfo::Direct);
+            Some(envelope)
+        };
+
+        let complete = exchange.reply_to.is_none() && exchange.responses.shadow_response.is_some();
+        let report = complete.then(|| pending.remove(&id).unwrap().responses);
+        drop(pending);
+
+        if let Some(envelope) = relay {
+            match self.proc.upgrade() {
+                Some(proc) => proc.post(envelope, return_handle),
+                None => Self::port_gone(envelope, return_handle),
+            }
+        }
+        if let Some(responses) = report {
+            self.report(responses);
+        }
+    }
+
+    fn port_gone(
+        envelope: MessageEnvelope,
+        return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+    ) {
+        let failure = DeliveryFailure::new(PortGone::new(
+            envelope.dest().clone(),
+            envelope.data().typename().map(str::to_string),
+        ));
+        envelope.undeliverable(failure, return_handle);
+    }
+}
+
+/// The sender bound to receive the responses to mirrored requests.
+struct ResponseSink(Weak<Exchanges>);
+
+#[async_trait]
+impl MailboxSender for ResponseSink {
+    fn post_unchecked(
+        &self,
+        envelope: MessageEnvelope,
+        return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+    ) {
+        match self.0.upgrade() {
+            Some(exchanges) => exchanges.respond(envelope, return_handle),
+            None => Exchanges::port_gone(envelope, return_handle),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use hyperactor_config::Flattrs;
+    use serde::Deserialize;
+    use serde::Serialize;
+    use tokio::sync::mpsc;
+    use typeuri::Named;
+
+    use super::*;
+    use crate as hyperactor;
+    use crate::Actor;
+    use crate::ActorHandle;
+    use crate::Context;
+    use crate::Handler;
+    use crate::PortRef;
+    use crate::client::Client;
+    use crate::mailbox::IntoBoxedMailboxSender as _;
+    use crate::mailbox::open_once_port;
+
+    #[derive(Debug, Clone, Serialize, Deserialize, Named)]
+    struct Double(u64);
+    wirevalue::register_type!(Double);
+
+    /// Asks for the doubled value on the carried port.
+    #[derive(Debug, Clone, Serialize, Deserialize, Named)]
+    struct DoubleTo(u64, PortRef<u64>);
+    wirevalue::register_type!(DoubleTo);
+
+    /// Responds to [`Double`] with the doubled value, plus `error`, or
+    /// not at all if `mute`.
+    #[derive(Debug)]
+    #[hyperactor::export(handlers = [Double, DoubleTo])]
+    struct Doubler {
+        error: u64,
+        mute: bool,
+    }
+
+    impl Doubler {
+        fn new(error: u64) -> Self {
+            Self { error, mute: false }
+        }
+    }
+
+    impl Actor for Doubler {}
+
+    #[async_trait]
+    impl Handler<Double> for Doubler {
+        async fn handle(&mut self, cx: &Context<Self>, Double(n): Double) -> anyhow::Result<()> {
+            if self.mute {
+                return Ok(());
+            }
+            cx.respond(2 * n + self.error)
+        }
+    }
+
+    #[async_trait]
+    impl Handler<DoubleTo> for Doubler {
+        async fn handle(
+            &mut self,
+            cx: &Context<Self>,
+            DoubleTo(n, port): DoubleTo,
+        ) -> anyhow::Result<()> {
+            port.post(cx, 2 * n + self.error);
+            Ok(())
+        }
+    }
+
+    fn mirror_doubles(
+        proc: &Proc,
+        primary: &ActorHandle<Doubler>,
+        shadow: &ActorHandle<Doubler>,
+    ) -> (MirroringSender, mpsc::UnboundedReceiver<MirroredResponses>) {
+        let (diffs_tx, diffs) = mpsc::unbounded_channel();
+        let sender = MirroringSender::new(proc.clone().into_boxed())
+            .mirror(
+                primary.actor_addr().clone(),
+                shadow.actor_addr().clone(),
+                MessageMatch::type_of::<Double>(),
+            )
+            .mirror(
+                primary.actor_addr().clone(),
+                shadow.actor_addr().clone(),
+                MessageMatch::type_of::<DoubleTo>(),
+            )
+            .diff_responses(proc, "mirror", move |responses: MirroredResponses| {
+                diffs_tx.send(responses).unwrap();
+            })
+            .unwrap();
+        (sender, diffs)
+    }
+
+    fn request(
+        client: &Client,
+        primary: &ActorHandle<Doubler>,
+        n: u64,
+        reply: &PortAddr,
+    ) -> MessageEnvelope {
+        let mut headers = Flattrs::new();
+        headers.set(REPLY_TO, reply.clone());
+        headers.set(SEQ_INFO, SeqInfo::Direct);
+        MessageEnvelope::serialize(
+            client.self_addr().clone(),
+            primary.actor_addr().port_addr(Port::handler::<Double>()),
+            &Double(n),
+            headers,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_mirror_and_diff() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let primary = proc.spawn(Doubler::new(0));
+        let shadow = proc.spawn(Doubler::new(1));
+        let (sender, mut diffs) = mirror_doubles(&proc, &primary, &shadow);
+
+        let (reply, receiver) = open_once_port::<u64>(&client);
+        let envelope = request(&client, &primary, 2, reply.bind().port_addr());
+        sender.post(envelope, monitored_return_handle());
+
+        // The requester gets the primary's response only.
+        assert_eq!(receiver.recv().await.unwrap(), 4);
+
+        let responses = diffs.recv().await.unwrap();
+        assert_eq!(&responses.primary, primary.actor_addr());
+        assert_eq!(responses.typename.as_deref(), Some(Double::typename()));
+        let response = |response: &Option<wirevalue::Any>| {
+            response.as_ref().unwrap().deserialized::<u64>().unwrap()
+        };
+        assert_eq!(response(&responses.primary_response), 4);
+        assert_eq!(response(&responses.shadow_response), 5);
+        assert!(responses.differs());
+    }
+
+    #[tokio::test]
+    async fn test_mirror_drops_shadow_replies_to_carried_ports() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let primary = proc.spawn(Doubler::new(0));
+        let shadow = proc.spawn(Doubler::new(1));
+        let (sender, mut diffs) = mirror_doubles(&proc, &primary, &shadow);
+
+        let (port, mut receiver) = client.open_port::<u64>();
+        let envelope = MessageEnvelope::serialize(
+            client.self_addr().clone(),
+            primary.actor_addr().port_addr(Port::handler::<DoubleTo>()),
+            &DoubleTo(2, port.bind()),
+            Flattrs::new(),
+        )
+        .unwrap();
+        sender.post(envelope, monitored_return_handle());
+        assert_eq!(receiver.recv().await.unwrap(), 4);
+
+        // The shadow handles messages in order, so once its response to
+        // a later request is in, it has handled the first.
+        let (reply, once) = open_once_port::<u64>(&client);
+        let envelope = request(&client, &primary, 3, reply.bind().port_addr());
+        sender.post(envelope, monitored_return_handle());
+        assert_eq!(once.recv().await.unwrap(), 6);
+        let responses = diffs.recv().await.unwrap();
+        assert!(responses.differs());
+        assert!(receiver.try_recv().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_mirror_expires_requests() {
+        let config = hyperactor_config::global::lock();
+        let _guard =
+            config.override_key(config::MIRROR_RESPONSE_TIMEOUT, Duration::from_millis(100));
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let primary = proc.spawn(Doubler::new(0));
+        let shadow = proc.spawn(Doubler {
+            error: 0,
+            mute: true,
+        });
+        let (sender, mut diffs) = mirror_doubles(&proc, &primary, &shadow);
+
+        let (reply, receiver) = open_once_port::<u64>(&client);
+        let envelope = request(&client, &primary, 2, reply.bind().port_addr());
+        sender.post(envelope, monitored_return_handle());
+        assert_eq!(receiver.recv().await.unwrap(), 4);
+
+        let responses = diffs.recv().await.unwrap();
+        assert!(responses.shadow_response.is_none());
+        assert!(responses.differs());
+        let (_, exchanges) = sender.diffing.as_ref().unwrap();
+        assert!(exchanges.pending.lock().unwrap().is_empty());
+    }
+}
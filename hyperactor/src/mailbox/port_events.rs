@@ -0,0 +1,149 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Port binding lifecycle events.
+//!
+//! A mailbox reports to its [`PortObserver`]s each port it binds, and
+//! each port it unbinds, so that metrics, debuggers, and administrative
+//! actors can track a mailbox's live ports without polling its port
+//! table. Observers are registered with [`Mailbox::observe_ports`], and
+//! stay registered until the returned [`PortObserverHandle`] is dropped.
+//!
+//! [`Mailbox::observe_ports`]: crate::mailbox::Mailbox::observe_ports
+
+use std::sync::Arc;
+use std::sync::Weak;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use arc_swap::ArcSwap;
+
+use crate::PortAddr;
+
+/// A change to the set of ports bound in a mailbox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortEvent {
+    /// The port was bound, and messages posted to it are delivered.
+    Bound(PortAddr),
+    /// The port was unbound while its receiver was live: a one-shot
+    /// port received its message, a port expired, or its sender was
+    /// found dead.
+    Unbound(PortAddr),
+    /// The port was unbound because its receiver was dropped.
+    Closed(PortAddr),
+}
+
+impl PortEvent {
+    /// The port the event is about.
+    pub fn port_addr(&self) -> &PortAddr {
+        match self {
+            Self::Bound(port) | Self::Unbound(port) | Self::Closed(port) => port,
+        }
+    }
+}
+
+/// An observer of a mailbox's [`PortEvent`]s.
+///
+/// Observers are called synchronously, by the task that bound or
+/// unbound the port, so they should return quickly and must not block.
+/// Observers that do more work should forward events to a channel.
+pub trait PortObserver: Send + Sync + 'static {
+    /// Observe `event`.
+    fn port_event(&self, event: &PortEvent);
+}
+
+impl<F: Fn(&PortEvent) + Send + Sync + 'static> PortObserver for F {
+    fn port_event(&self, event: &PortEvent) {
+        self(event)
+    }
+}
+
+/// The observers registered with a mailbox.
+pub(super) struct PortObservers {
+    next_id: AtomicU64,
+    // Copied on registration, so that notifications, which are far
+    // more frequent, are lock-free.
+    observers: ArcSwap<Vec<(u64, Arc<dyn PortObserver>)>>,
+}
+
+impl PortObservers {
+    pub(super) fn new() -> Self {
+        Self {
+            next_id: AtomicU64::new(0),
+            observers: ArcSwap::from_pointee(Vec::new()),
+        }
+    }
+
+    /// Register `observer`, returning its ID.
+    fn add(&self, observer: Arc<dyn PortObserver>) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.observers.rcu(|observers| {
+            let mut observers = Vec::clone(observers);
+            observers.push((id, Arc::clone(&observer)));
+            observers
+        });
+        id
+    }
+
+    /// Deregister the observer with `id`.
+    fn remove(&self, id: u64) {
+        self.observers.rcu(|observers| {
+            observers
+                .iter()
+                .filter(|(observer_id, _)| *observer_id != id)
+                .cloned()
+                .collect::<Vec<_>>()
+        });
+    }
+
+    /// Notify the registered observers, if there are any, of the event
+    /// built by `event`.
+    pub(super) fn notify(&self, event: impl FnOnce() -> PortEvent) {
+        let observers = self.observers.load();
+        if observers.is_empty() {
+            return;
+        }
+        let event = event();
+        for (_, observer) in observers.iter() {
+            observer.port_event(&event);
+        }
+    }
+}
+
+/// The registration of a [`PortObserver`] with a mailbox. Dropping the
+/// handle deregisters the observer.
+#[must_use = "the observer is deregistered when its handle is dropped"]
+pub struct PortObserverHandle {
+    state: Weak<super::State>,
+    id: u64,
+}
+
+impl PortObserverHandle {
+    pub(super) fn register(state: &Arc<super::State>, observer: Arc<dyn PortObserver>) -> Self {
+        Self {
+            state: Arc::downgrade(state),
+            id: state.port_observers.add(observer),
+        }
+    }
+}
+
+impl std::fmt::Debug for PortObserverHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PortObserverHandle")
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+impl Drop for PortObserverHandle {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.upgrade() {
+            state.port_observers.remove(self.id);
+        }
+    }
+}
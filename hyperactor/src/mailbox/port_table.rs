@@ -0,0 +1,361 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! The table of a mailbox's bound ports.
+//!
+//! Every post looks up its destination port, so lookups must be cheap.
+//! Ephemeral ports are allocated densely from 0, so they are kept in a
+//! slab indexed by the port index, whose lookups are lock-free: an
+//! index computation and an atomic load. Handler and control ports, and
+//! ephemeral ports beyond the slab's capacity, are kept in a map.
+//!
+//! The slab is a sequence of segments, each twice as large as the
+//! previous one, that are allocated as ports with higher indices are
+//! bound, so bound slots never move. Since ephemeral ports are
+//! allocated in increasing order, a segment whose ports have all been
+//! unbound once higher ports are in use is unlikely to be used again,
+//! and is freed.
+
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use arc_swap::ArcSwapOption;
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+
+use crate::port::Port;
+
+/// The number of slots in the first segment of the slab.
+const FIRST_SEGMENT_LEN: u64 = 64;
+
+/// The number of segments in the slab, which holds ephemeral ports
+/// with indices below `FIRST_SEGMENT_LEN * (2^NUM_SEGMENTS - 1)`.
+const NUM_SEGMENTS: usize = 10;
+
+/// The `live` count of a segment that has been freed, and must not be
+/// bound to.
+const RETIRED: usize = usize::MAX;
+
+/// The value bound to a slab slot. `ArcSwapOption` requires a sized
+/// pointee, so the (possibly unsized) value's `Arc` is boxed in one.
+struct Slot<T: ?Sized>(Arc<T>);
+
+/// The slots of one slab segment.
+struct Segment<T: ?Sized> {
+    slots: Box<[ArcSwapOption<Slot<T>>]>,
+    /// The number of bound slots, or [`RETIRED`].
+    live: AtomicUsize,
+}
+
+impl<T: ?Sized> Segment<T> {
+    fn new(len: u64) -> Self {
+        Self {
+            slots: (0..len).map(|_| ArcSwapOption::empty()).collect(),
+            live: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// A map from ports to values of `T`; see the [module documentation](self).
+pub(super) struct PortTable<T: ?Sized> {
+    segments: [ArcSwapOption<Segment<T>>; NUM_SEGMENTS],
+    /// The highest ephemeral index bound in the slab.
+    high: AtomicU64,
+    sparse: DashMap<Port, Arc<T>>,
+}
+
+impl<T: ?Sized> PortTable<T> {
+    /// Create an empty table.
+    pub(super) fn new() -> Self {
+        Self {
+            segments: std::array::from_fn(|_| ArcSwapOption::empty()),
+            high: AtomicU64::new(0),
+            sparse: DashMap::new(),
+        }
+    }
+
+    /// The value bound to `port`, if any.
+    pub(super) fn get(&self, port: &Port) -> Option<Arc<T>> {
+        match slab_position(port) {
+            Some((segment, offset)) => self.segments[segment]
+                .load()
+                .as_ref()?
+                .slots
+                .get(offset)?
+                .load()
+                .as_ref()
+                .map(|slot| Arc::clone(&slot.0)),
+            None => self.sparse.get(port).map(|value| Arc::clone(&value)),
+        }
+    }
+
+    /// Whether a value is bound to `port`.
+    #[cfg(test)]
+    pub(super) fn contains_key(&self, port: &Port) -> bool {
+        self.get(port).is_some()
+    }
+
+    /// Bind `value` to `port` unless a value is already bound to it.
+    /// Returns whether `value` was bound.
+    pub(super) fn insert_if_vacant(&self, port: Port, value: Arc<T>) -> bool {
+        let Some((segment, offset)) = slab_position(&port) else {
+            return match self.sparse.entry(port) {
+                Entry::Vacant(entry) => {
+                    entry.insert(value);
+                    true
+                }
+                Entry::Occupied(_) => false,
+            };
+        };
+        let slot = Some(Arc::new(Slot(value)));
+        let slots = self.enter(segment, &port);
+        let previous = slots.slots[offset].compare_and_swap(&None::<Arc<Slot<T>>>, slot);
+        if previous.is_some() {
+            slots.live.fetch_sub(1, Ordering::AcqRel);
+            return false;
+        }
+        true
+    }
+
+    /// Bind `value` to `port`, replacing any value already bound to it.
+    /// Returns whether the port was vacant.
+    pub(super) fn insert(&self, port: Port, value: Arc<T>) -> bool {
+        match slab_position(&port) {
+            Some((segment, offset)) => {
+                let slots = self.enter(segment, &port);
+                let vacant = slots.slots[offset]
+                    .swap(Some(Arc::new(Slot(value))))
+                    .is_none();
+                if !vacant {
+                    slots.live.fetch_sub(1, Ordering::AcqRel);
+                }
+                vacant
+            }
+            None => self.sparse.insert(port, value).is_none(),
+        }
+    }
+
+    /// Unbind `port`. Returns whether a value was bound to it.
+    pub(super) fn remove(&self, port: &Port) -> bool {
+        let Some((segment, offset)) = slab_position(port) else {
+            return self.sparse.remove(port).is_some();
+        };
+        let Some(slots) = self.segments[segment].load_full() else {
+            return false;
+        };
+        if slots.slots[offset].swap(None).is_none() {
+            return false;
+        }
+        if slots.live.fetch_sub(1, Ordering::AcqRel) == 1
+            && self.high.load(Ordering::Acquire) >= segment_start(segment + 1)
+        {
+            self.retire(segment, slots);
+        }
+        true
+    }
+
+    /// Free `segment`'s slots if they are all unbound, unless a
+    /// concurrent bind has entered it in the meantime.
+    fn retire(&self, segment: usize, slots: Arc<Segment<T>>) {
+        if slots
+            .live
+            .compare_exchange(0, RETIRED, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+        {
+            self.segments[segment].compare_and_swap(&Some(slots), None);
+        }
+    }
+
+    /// The bound ports, in no particular order.
+    pub(super) fn ports(&self) -> Vec<Port> {
+        let mut ports = Vec::new();
+        for (segment, slots) in self.segments.iter().enumerate() {
+            let slots = slots.load();
+            let Some(slots) = slots.as_ref() else {
+                continue;
+            };
+            let start = segment_start(segment);
+            for (offset, slot) in slots.slots.iter().enumerate() {
+                if slot.load().is_some() {
+                    ports.push(Port::Ephemeral(start + offset as u64));
+                }
+            }
+        }
+        ports.extend(self.sparse.iter().map(|entry| entry.key().clone()));
+        ports
+    }
+
+    /// Enter `segment` to bind a slot of `port`, allocating the segment
+    /// if it is absent or retired. The entry is counted as live, so the
+    /// segment is not retired while it is being bound to.
+    fn enter(&self, segment: usize, port: &Port) -> Arc<Segment<T>> {
+        if let Some(index) = port.ephemeral_index() {
+            self.high.fetch_max(index, Ordering::AcqRel);
+        }
+        loop {
+            let current = self.segments[segment].load_full();
+            if let Some(slots) = &current
+                && slots
+                    .live
+                    .fetch_update(Ordering::AcqRel, Ordering::Acquire, |live| {
+                        (live != RETIRED).then_some(live + 1)
+                    })
+                    .is_ok()
+            {
+                return Arc::clone(slots);
+            }
+            let fresh = Arc::new(Segment::new(FIRST_SEGMENT_LEN << segment));
+            fresh.live.store(1, Ordering::Release);
+            let previous =
+                self.segments[segment].compare_and_swap(&current, Some(Arc::clone(&fresh)));
+            if same(&*previous, &current) {
+                // Empty segments left behind by the new one are freed.
+                for lower in 0..segment {
+                    if let Some(slots) = self.segments[lower].load_full() {
+                        self.retire(lower, slots);
+                    }
+                }
+                return fresh;
+            }
+        }
+    }
+}
+
+/// Whether `a` and `b` refer to the same segment.
+fn same<T: ?Sized>(a: &Option<Arc<Segment<T>>>, b: &Option<Arc<Segment<T>>>) -> bool {
+    a.as_ref().map(Arc::as_ptr) == b.as_ref().map(Arc::as_ptr)
+}
+
+/// The index of the first port in `segment`.
+fn segment_start(segment: usize) -> u64 {
+    FIRST_SEGMENT_LEN * ((1 << segment) - 1)
+}
+
+/// The segment and offset of `port`'s slab slot, or None if the port
+/// is kept in the sparse map.
+fn slab_position(port: &Port) -> Option<(usize, usize)> {
+    let index = port.ephemeral_index()?;
+    // Segment `s` holds indices in
+    // [FIRST_SEGMENT_LEN * (2^s - 1), FIRST_SEGMENT_LEN * (2^(s+1) - 1)).
+    let segment = (index / FIRST_SEGMENT_LEN + 1).ilog2() as usize;
+    if segment >= NUM_SEGMENTS {
+        return None;
+    }
+    Some((segment, (index - segment_start(segment)) as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::port::ControlPort;
+
+    #[test]
+    fn test_slab_positions() {
+        assert_eq!(slab_position(&Port::Ephemeral(0)), Some((0, 0)));
+        assert_eq!(slab_position(&Port::Ephemeral(63)), Some((0, 63)));
+        assert_eq!(slab_position(&Port::Ephemeral(64)), Some((1, 0)));
+        assert_eq!(slab_position(&Port::Ephemeral(191)), Some((1, 127)));
+        assert_eq!(slab_position(&Port::Ephemeral(192)), Some((2, 0)));
+        let capacity = segment_start(NUM_SEGMENTS);
+        assert_eq!(
+            slab_position(&Port::Ephemeral(capacity - 1)),
+            Some((
+                NUM_SEGMENTS - 1,
+                (FIRST_SEGMENT_LEN << (NUM_SEGMENTS - 1)) as usize - 1
+            ))
+        );
+        assert_eq!(slab_position(&Port::Ephemeral(capacity)), None);
+        assert_eq!(slab_position(&Port::Ephemeral(u64::MAX)), None);
+        assert_eq!(slab_position(&Port::control(ControlPort::Signal)), None);
+    }
+
+    #[test]
+    fn test_dense_and_sparse_ports() {
+        let table = PortTable::<str>::new();
+        let capacity = segment_start(NUM_SEGMENTS);
+        let ports = [
+            Port::Ephemeral(0),
+            Port::Ephemeral(1000),
+            Port::Ephemeral(capacity),
+            Port::control(ControlPort::Signal),
+            Port::handler_id(123, None),
+        ];
+        for port in &ports {
+            assert!(!table.contains_key(port));
+            assert!(table.insert_if_vacant(port.clone(), Arc::from(port.to_string())));
+            assert!(!table.insert_if_vacant(port.clone(), Arc::from("other")));
+            assert_eq!(table.get(port).as_deref(), Some(&*port.to_string()));
+        }
+        // Only the segments holding bound ports are allocated.
+        assert_eq!(
+            table
+                .segments
+                .iter()
+                .filter(|segment| segment.load().is_some())
+                .count(),
+            2
+        );
+        let mut bound = table.ports();
+        bound.sort();
+        let mut expected = ports.to_vec();
+        expected.sort();
+        assert_eq!(bound, expected);
+
+        for port in &ports {
+            table.remove(port);
+            assert!(!table.contains_key(port));
+            assert_eq!(table.get(port), None);
+        }
+        assert!(table.ports().is_empty());
+        table.insert(Port::Ephemeral(1), Arc::from("one"));
+        table.insert(Port::Ephemeral(1), Arc::from("uno"));
+        assert_eq!(table.get(&Port::Ephemeral(1)).as_deref(), Some("uno"));
+    }
+
+    #[test]
+    fn test_empty_segments_behind_bound_ports_are_freed() {
+        let table = PortTable::<str>::new();
+        let allocated = |table: &PortTable<str>| {
+            table
+                .segments
+                .iter()
+                .map(|segment| segment.load().is_some())
+                .collect::<Vec<_>>()[..3]
+                .to_vec()
+        };
+        for index in 0..FIRST_SEGMENT_LEN + 1 {
+            assert!(table.insert_if_vacant(Port::Ephemeral(index), Arc::from("port")));
+        }
+        assert_eq!(allocated(&table), vec![true, true, false]);
+
+        // The first segment is freed once its last port is unbound.
+        table.remove(&Port::Ephemeral(0));
+        assert_eq!(allocated(&table), vec![true, true, false]);
+        for index in 1..FIRST_SEGMENT_LEN {
+            table.remove(&Port::Ephemeral(index));
+        }
+        assert_eq!(allocated(&table), vec![false, true, false]);
+
+        // The segment holding the highest bound port is kept, until a
+        // higher segment is allocated.
+        table.remove(&Port::Ephemeral(FIRST_SEGMENT_LEN));
+        assert_eq!(allocated(&table), vec![false, true, false]);
+        let port = Port::Ephemeral(segment_start(2));
+        assert!(table.insert_if_vacant(port.clone(), Arc::from("port")));
+        assert_eq!(allocated(&table), vec![false, false, true]);
+        table.remove(&port);
+
+        // A freed segment is allocated again when bound to.
+        assert!(table.insert_if_vacant(Port::Ephemeral(1), Arc::from("one")));
+        assert_eq!(table.get(&Port::Ephemeral(1)).as_deref(), Some("one"));
+        assert_eq!(table.ports(), vec![Port::Ephemeral(1)]);
+        assert_eq!(allocated(&table), vec![true, false, true]);
+    }
+}
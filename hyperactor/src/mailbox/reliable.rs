@@ -0,0 +1,488 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Retrying sends.
+//!
+//! A [`ReliableSender`] wraps a [`MailboxSender`], and retries messages
+//! returned as undeliverable with a transient failure (see
+//! [`is_transient`]) according to a [`RetryPolicy`]:
+//!
+//! - at most [`RetryPolicy::max_attempts`] attempts are made;
+//! - attempts are spaced by a jittered, exponential backoff;
+//! - retries to each destination proc are limited to
+//!   [`RetryPolicy::budget`] per [`RetryPolicy::budget_window`], so that
+//!   an unavailable proc does not cause a retry storm.
+//!
+//! An attempt is considered delivered once the wrapped sender has
+//! flushed it (see [`MailboxSender::flush`]) without returning it. The
+//! final outcome of a send is returned from [`ReliableSender::send`].
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::ActorAddr;
+use crate::ProcAddr;
+use crate::channel::ChannelAddr;
+use crate::channel::ChannelTransport;
+use crate::mailbox::DeliveryFailure;
+use crate::mailbox::Mailbox;
+use crate::mailbox::MailboxSender;
+use crate::mailbox::MessageEnvelope;
+use crate::mailbox::Undeliverable;
+use crate::mailbox::error_code::ErrorCode;
+use crate::mailbox::error_code::HasErrorCode as _;
+
+/// How a [`ReliableSender`] retries.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts per message, including the first.
+    pub max_attempts: u32,
+    /// The backoff before the first retry.
+    pub initial_backoff: Duration,
+    /// The maximum backoff between retries.
+    pub max_backoff: Duration,
+    /// The factor by which the backoff grows after each retry.
+    pub multiplier: f64,
+    /// The fraction by which each backoff is randomly varied, up or
+    /// down, between 0 and 1.
+    pub jitter: f64,
+    /// The maximum number of retries to each destination proc per
+    /// `budget_window`.
+    pub budget: u32,
+    /// The window over which `budget` applies.
+    pub budget_window: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+            jitter: 0.2,
+            budget: 100,
+            budget_window: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The backoff before retry `retry`, counting from 1.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let base = self.initial_backoff.as_secs_f64()
+            * self.multiplier.powi(retry.saturating_sub(1) as i32);
+        let base = base.min(self.max_backoff.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = 1.0 - jitter + 2.0 * jitter * fastrand::f64();
+        Duration::from_secs_f64(base * factor)
+    }
+}
+
+/// Whether `failure` is transient, so that the message may be
+/// delivered if retried.
+pub fn is_transient(failure: &DeliveryFailure) -> bool {
+    matches!(
+        failure.error_code(),
+        ErrorCode::NoRoute
+            | ErrorCode::DialFailed
+            | ErrorCode::ChannelClosed
+            | ErrorCode::LinkUnavailable
+            | ErrorCode::ForwarderUnavailable
+            | ErrorCode::Unreachable
+            | ErrorCode::AckTimedOut
+    )
+}
+
+/// The final failure of a [`ReliableSender::send`].
+#[derive(Debug, thiserror::Error)]
+pub enum ReliableSendError {
+    /// The message failed with a failure that is not transient.
+    #[error("message failed permanently after {attempts} attempt(s)")]
+    Permanent {
+        /// The number of attempts made.
+        attempts: u32,
+        /// The message, as last returned.
+        undeliverable: Box<Undeliverable<MessageEnvelope>>,
+    },
+
+    /// Every attempt failed.
+    #[error("message failed after {attempts} attempt(s)")]
+    AttemptsExhausted {
+        /// The number of attempts made.
+        attempts: u32,
+        /// The message, as last returned.
+        undeliverable: Box<Undeliverable<MessageEnvelope>>,
+    },
+
+    /// The retry budget of the destination proc was exhausted.
+    #[error("retry budget for {dest} exhausted after {attempts} attempt(s)")]
+    BudgetExhausted {
+        /// The destination proc.
+        dest: ProcAddr,
+        /// The number of attempts made.
+        attempts: u32,
+        /// The message, as last returned.
+        undeliverable: Box<Undeliverable<MessageEnvelope>>,
+    },
+
+    /// The wrapped sender failed to flush the last attempt, so its
+    /// delivery could not be confirmed.
+    #[error("delivery unconfirmed after {attempts} attempt(s): {source}")]
+    Unconfirmed {
+        /// The number of attempts made.
+        attempts: u32,
+        /// The flush error.
+        #[source]
+        source: anyhow::Error,
+    },
+}
+
+impl ReliableSendError {
+    /// The message, as last returned, if it was returned.
+    pub fn undeliverable(&self) -> Option<&Undeliverable<MessageEnvelope>> {
+        match self {
+            Self::Permanent { undeliverable, .. }
+            | Self::AttemptsExhausted { undeliverable, .. }
+            | Self::BudgetExhausted { undeliverable, .. } => Some(undeliverable),
+            Self::Unconfirmed { .. } => None,
+        }
+    }
+}
+
+/// A [`MailboxSender`] wrapper that retries undeliverable messages.
+/// See the [module documentation](self).
+#[derive(Debug)]
+pub struct ReliableSender<S> {
+    sender: S,
+    policy: RetryPolicy,
+    /// Owns the ports to which failed attempts are returned.
+    mailbox: Mailbox,
+    /// Retries spent per destination proc, in the current window.
+    budgets: Mutex<HashMap<ProcAddr, (Instant, u32)>>,
+}
+
+impl<S: MailboxSender> ReliableSender<S> {
+    /// Wrap `sender`, retrying according to `policy`.
+    pub fn new(sender: S, policy: RetryPolicy) -> Self {
+        let proc = ProcAddr::instance(ChannelAddr::any(ChannelTransport::Local), "reliable_sender");
+        let mailbox = Mailbox::new(ActorAddr::root(
+            proc,
+            crate::id::Label::strip("reliable_sender"),
+        ));
+        Self {
+            sender,
+            policy,
+            mailbox,
+            budgets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The retry policy.
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    /// Send `envelope`, retrying it on transient failures. Returns the
+    /// number of attempts made to deliver it.
+    pub async fn send(&self, envelope: MessageEnvelope) -> Result<u32, ReliableSendError> {
+        let dest = envelope.dest().actor_addr().proc_addr();
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let (return_handle, mut returns) =
+                self.mailbox.open_port::<Undeliverable<MessageEnvelope>>();
+            self.sender.post(envelope.clone(), return_handle);
+            let returned = tokio::select! {
+                biased;
+                Ok(undeliverable) = returns.recv() => Some(undeliverable),
+                flushed = self.sender.flush() => match returns.try_recv() {
+                    Ok(Some(undeliverable)) => Some(undeliverable),
+                    _ => match flushed {
+                        Ok(()) => None,
+                        Err(source) => {
+                            return Err(ReliableSendError::Unconfirmed { attempts, source });
+                        }
+                    },
+                },
+            };
+            let Some(undeliverable) = returned else {
+                // Flushed without being returned: the attempt was delivered.
+                return Ok(attempts);
+            };
+            let undeliverable = Box::new(undeliverable);
+
+            if !undeliverable
+                .root_delivery_failure()
+                .is_some_and(is_transient)
+            {
+                return Err(ReliableSendError::Permanent {
+                    attempts,
+                    undeliverable,
+                });
+            }
+            if attempts >= self.policy.max_attempts {
+                return Err(ReliableSendError::AttemptsExhausted {
+                    attempts,
+                    undeliverable,
+                });
+            }
+            if !self.take_budget(&dest) {
+                return Err(ReliableSendError::BudgetExhausted {
+                    dest,
+                    attempts,
+                    undeliverable,
+                });
+            }
+            tracing::debug!(
+                dest = %envelope.dest(),
+                attempts,
+                "retrying undeliverable message",
+            );
+            tokio::time::sleep(self.policy.backoff(attempts)).await;
+        }
+    }
+
+    /// Spend one retry of the budget of `dest`, if any remains.
+    fn take_budget(&self, dest: &ProcAddr) -> bool {
+        let now = Instant::now();
+        let mut budgets = self.budgets.lock().unwrap();
+        budgets.retain(|_, (start, _)| now.duration_since(*start) < self.policy.budget_window);
+        let (_, spent) = budgets.entry(dest.clone()).or_insert((now, 0));
+        if *spent >= self.policy.budget {
+            return false;
+        }
+        *spent += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicU32;
+    use std::sync::atomic::Ordering;
+
+    use async_trait::async_trait;
+    use hyperactor_config::Flattrs;
+    use timed_test::async_timed_test;
+
+    use super::*;
+    use crate::mailbox::InvalidReference;
+    use crate::mailbox::InvalidReferenceReason;
+    use crate::mailbox::PortHandle;
+    use crate::mailbox::TransportFailure;
+    use crate::mailbox::TransportFailureReason;
+    use crate::mailbox::UndeliverableReason;
+    use crate::testing::ids::test_actor_id;
+    use crate::testing::ids::test_port_id;
+
+    /// Returns the first `failures` messages posted to it with
+    /// `failure`, and drops the rest.
+    #[derive(Debug, Clone)]
+    struct FlakySender {
+        failures: u32,
+        failure: DeliveryFailure,
+        posted: Arc<AtomicU32>,
+    }
+
+    impl FlakySender {
+        fn new(failures: u32, failure: DeliveryFailure) -> Self {
+            Self {
+                failures,
+                failure,
+                posted: Arc::new(AtomicU32::new(0)),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl MailboxSender for FlakySender {
+        fn post_unchecked(
+            &self,
+            envelope: MessageEnvelope,
+            return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+        ) {
+            if self.posted.fetch_add(1, Ordering::SeqCst) < self.failures {
+                envelope.undeliverable(self.failure.clone(), return_handle);
+            }
+        }
+    }
+
+    fn no_route() -> DeliveryFailure {
+        DeliveryFailure::new(UndeliverableReason::Transport(TransportFailure::new(
+            test_port_id("dest", "actor", 1),
+            TransportFailureReason::NoRoute,
+        )))
+    }
+
+    fn envelope() -> MessageEnvelope {
+        MessageEnvelope::serialize(
+            test_actor_id("src", "actor"),
+            test_port_id("dest", "actor", 1),
+            &123u64,
+            Flattrs::new(),
+        )
+        .unwrap()
+    }
+
+    #[async_timed_test(timeout_secs = 30, start_paused = true)]
+    async fn test_retry_until_delivered() {
+        let sender = FlakySender::new(2, no_route());
+        let reliable = ReliableSender::new(sender.clone(), RetryPolicy::default());
+        assert_eq!(reliable.send(envelope()).await.unwrap(), 3);
+        assert_eq!(sender.posted.load(Ordering::SeqCst), 3);
+
+        // A delivered attempt completes as soon as it is flushed.
+        let start = Instant::now();
+        assert_eq!(reliable.send(envelope()).await.unwrap(), 1);
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    /// Flushes after `delay`, then returns every message posted to it
+    /// with `failure`.
+    #[derive(Debug)]
+    struct LateFailureSender {
+        delay: Duration,
+        failure: DeliveryFailure,
+        pending: Mutex<Vec<(MessageEnvelope, PortHandle<Undeliverable<MessageEnvelope>>)>>,
+    }
+
+    #[async_trait]
+    impl MailboxSender for LateFailureSender {
+        fn post_unchecked(
+            &self,
+            envelope: MessageEnvelope,
+            return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+        ) {
+            self.pending.lock().unwrap().push((envelope, return_handle));
+        }
+
+        async fn flush(&self) -> Result<(), anyhow::Error> {
+            tokio::time::sleep(self.delay).await;
+            let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+            for (envelope, return_handle) in pending {
+                envelope.undeliverable(self.failure.clone(), return_handle);
+            }
+            Ok(())
+        }
+    }
+
+    #[async_timed_test(timeout_secs = 30, start_paused = true)]
+    async fn test_failure_before_ack() {
+        // A failure returned before the attempt is acknowledged, however
+        // late, fails the send.
+        let sender = LateFailureSender {
+            delay: Duration::from_secs(5),
+            failure: DeliveryFailure::new(InvalidReference::new(
+                test_actor_id("dest", "actor"),
+                InvalidReferenceReason::ActorNotExist,
+            )),
+            pending: Mutex::new(Vec::new()),
+        };
+        let reliable = ReliableSender::new(sender, RetryPolicy::default());
+        assert!(matches!(
+            reliable.send(envelope()).await,
+            Err(ReliableSendError::Permanent { attempts: 1, .. })
+        ));
+    }
+
+    #[async_timed_test(timeout_secs = 30, start_paused = true)]
+    async fn test_retry_exhausted() {
+        let sender = FlakySender::new(u32::MAX, no_route());
+        let reliable = ReliableSender::new(
+            sender.clone(),
+            RetryPolicy {
+                max_attempts: 3,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            reliable.send(envelope()).await,
+            Err(ReliableSendError::AttemptsExhausted { attempts: 3, .. })
+        ));
+
+        // Failures that are not transient are not retried.
+        let sender = FlakySender::new(
+            u32::MAX,
+            DeliveryFailure::new(InvalidReference::new(
+                test_actor_id("dest", "actor"),
+                InvalidReferenceReason::ActorNotExist,
+            )),
+        );
+        let reliable = ReliableSender::new(sender.clone(), RetryPolicy::default());
+        assert!(matches!(
+            reliable.send(envelope()).await,
+            Err(ReliableSendError::Permanent { attempts: 1, .. })
+        ));
+        assert_eq!(sender.posted.load(Ordering::SeqCst), 1);
+
+        // Neither are exceeded quotas.
+        let sender = FlakySender::new(
+            u32::MAX,
+            DeliveryFailure::new(UndeliverableReason::Transport(TransportFailure::new(
+                test_port_id("dest", "actor", 1),
+                TransportFailureReason::QuotaExceeded("full".to_string()),
+            ))),
+        );
+        let reliable = ReliableSender::new(sender.clone(), RetryPolicy::default());
+        assert!(matches!(
+            reliable.send(envelope()).await,
+            Err(ReliableSendError::Permanent { attempts: 1, .. })
+        ));
+        assert_eq!(sender.posted.load(Ordering::SeqCst), 1);
+    }
+
+    #[async_timed_test(timeout_secs = 30, start_paused = true)]
+    async fn test_retry_budget() {
+        let sender = FlakySender::new(u32::MAX, no_route());
+        let reliable = ReliableSender::new(
+            sender.clone(),
+            RetryPolicy {
+                max_attempts: 10,
+                budget: 3,
+                budget_window: Duration::from_secs(3600),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            reliable.send(envelope()).await,
+            Err(ReliableSendError::BudgetExhausted { attempts: 4, .. })
+        ));
+        // The budget is shared by all sends to the proc.
+        assert!(matches!(
+            reliable.send(envelope()).await,
+            Err(ReliableSendError::BudgetExhausted { attempts: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            jitter: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+        assert_eq!(policy.backoff(10), Duration::from_secs(1));
+
+        let policy = RetryPolicy {
+            jitter: 0.5,
+            ..policy
+        };
+        for _ in 0..100 {
+            let backoff = policy.backoff(1);
+            assert!(backoff >= Duration::from_millis(50) && backoff <= Duration::from_millis(150));
+        }
+    }
+}
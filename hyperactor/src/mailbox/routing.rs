@@ -0,0 +1,495 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Routing policies for [`MailboxRouter`](super::MailboxRouter) and
+//! [`DialMailboxRouter`](super::DialMailboxRouter).
+//!
+//! A router keeps a table of routes, keyed by the references ([`Addr`]s)
+//! they are bound to. For each message, the router's [`RoutingPolicy`]
+//! selects the route through which the message's destination is
+//! reached. [`LongestPrefix`], the default, routes a destination
+//! through the route bound to the longest prefix of its actor. When
+//! [`config::LOCALITY`] is configured, routers instead default to
+//! [`CostAware`], which prefers the routes closest to this process.
+//!
+//! When [`config::ROUTING_AUDIT`] is enabled, routers and muxers record
+//! each of their decisions in a bounded, process-wide log, retrieved
+//! with [`audit_log`], so that misroutes can be diagnosed after the
+//! fact.
+
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::Addr;
+use crate::PortAddr;
+use crate::Uid;
+use crate::channel::ChannelAddr;
+use crate::config;
+use crate::mailbox::MailboxSender;
+use crate::mailbox::locality::CostAware;
+use crate::mailbox::locality::host_of;
+
+/// A route in a router's table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route<'a> {
+    /// The reference to which the route is bound.
+    pub key: &'a Addr,
+    /// The channel address through which the route is reached, if the
+    /// router knows it.
+    pub addr: Option<&'a ChannelAddr>,
+}
+
+/// A router's table of routes, as seen by its [`RoutingPolicy`].
+pub trait RoutingTable {
+    /// The route bound to exactly `key`, if any.
+    fn get(&self, key: &Addr) -> Option<Route<'_>>;
+}
+
+impl RoutingTable for im::OrdMap<Addr, Arc<dyn MailboxSender + Send + Sync>> {
+    fn get(&self, key: &Addr) -> Option<Route<'_>> {
+        self.get_key_value(key)
+            .map(|(key, _)| Route { key, addr: None })
+    }
+}
+
+impl RoutingTable for BTreeMap<Addr, ChannelAddr> {
+    fn get(&self, key: &Addr) -> Option<Route<'_>> {
+        self.get_key_value(key).map(|(key, addr)| Route {
+            key,
+            addr: Some(addr),
+        })
+    }
+}
+
+impl RoutingTable for im::OrdMap<Addr, ChannelAddr> {
+    fn get(&self, key: &Addr) -> Option<Route<'_>> {
+        self.get_key_value(key).map(|(key, addr)| Route {
+            key,
+            addr: Some(addr),
+        })
+    }
+}
+
+/// Selects the route through which messages reach a destination.
+pub trait RoutingPolicy: fmt::Debug + Send + Sync + 'static {
+    /// The route in `table` through which `dest` is reached, or None if
+    /// it is unroutable. `dest` is a message's destination port, or an
+    /// actor when a router is asked for an actor's route.
+    fn route<'a>(&self, table: &'a dyn RoutingTable, dest: &Addr) -> Option<Route<'a>>;
+}
+
+/// The references that are prefixes of `dest`, longest first.
+pub(crate) fn prefixes(dest: &Addr) -> Vec<Addr> {
+    match dest {
+        Addr::Port(port) => {
+            let actor = port.actor_addr();
+            let proc = actor.proc_addr();
+            vec![dest.clone(), Addr::Actor(actor), Addr::Proc(proc)]
+        }
+        Addr::Actor(actor) => vec![dest.clone(), Addr::Proc(actor.proc_addr())],
+        Addr::Proc(_) => vec![dest.clone()],
+    }
+}
+
+/// The destination's actor, or `dest` itself if it is not a port.
+fn actor_of(dest: &Addr) -> Addr {
+    match dest {
+        Addr::Port(port) => Addr::Actor(port.actor_addr()),
+        _ => dest.clone(),
+    }
+}
+
+/// Routes a destination through the route bound to the longest prefix
+/// of its actor: the actor itself, or its proc. Routes bound to ports
+/// are not considered.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LongestPrefix;
+
+impl RoutingPolicy for LongestPrefix {
+    fn route<'a>(&self, table: &'a dyn RoutingTable, dest: &Addr) -> Option<Route<'a>> {
+        prefixes(&actor_of(dest))
+            .iter()
+            .find_map(|prefix| table.get(prefix))
+    }
+}
+
+/// Routes a destination through the route bound to exactly it, if any,
+/// and otherwise as [`LongestPrefix`] does. This allows individual
+/// ports to be routed differently from the rest of their actor.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExactMatchFirst;
+
+impl RoutingPolicy for ExactMatchFirst {
+    fn route<'a>(&self, table: &'a dyn RoutingTable, dest: &Addr) -> Option<Route<'a>> {
+        table.get(dest).or_else(|| LongestPrefix.route(table, dest))
+    }
+}
+
+/// Routes a destination through the longest of its bound prefixes whose
+/// channel address is on the local host, and otherwise as
+/// [`ExactMatchFirst`] does. Local and Unix channel addresses are
+/// always on the local host; routes whose channel address is unknown
+/// never are.
+///
+/// Locality takes precedence over specificity: a local route bound to
+/// the destination's proc is preferred to a remote route bound to the
+/// destination itself. Specificity orders only the local routes, and
+/// the remote ones when there are none. (A table binds each prefix at
+/// most once, so ranking by specificity first would leave no ties for
+/// locality to break.)
+#[derive(Debug, Clone)]
+pub struct LocalityAware {
+    host: String,
+}
+
+impl LocalityAware {
+    /// Prefer routes to `host`, a hostname or an IP address as it
+    /// appears in channel addresses.
+    pub fn new(host: impl Into<String>) -> Self {
+        Self { host: host.into() }
+    }
+
+    fn is_local(&self, addr: &ChannelAddr) -> bool {
+        host_of(addr).is_none_or(|host| host == self.host)
+    }
+}
+
+impl RoutingPolicy for LocalityAware {
+    fn route<'a>(&self, table: &'a dyn RoutingTable, dest: &Addr) -> Option<Route<'a>> {
+        prefixes(dest)
+            .iter()
+            .filter_map(|prefix| table.get(prefix))
+            .find(|route| route.addr.is_some_and(|addr| self.is_local(addr)))
+            .or_else(|| ExactMatchFirst.route(table, dest))
+    }
+}
+
+/// Routes destinations through statically configured routes, and
+/// otherwise by a fallback policy. An override applies to the
+/// destinations it is a prefix of, the longest applicable override
+/// taking precedence. Overrides whose route is not bound in the table
+/// are ignored.
+#[derive(Debug, Clone)]
+pub struct StaticOverrides {
+    overrides: BTreeMap<Addr, Addr>,
+    fallback: Arc<dyn RoutingPolicy>,
+}
+
+impl StaticOverrides {
+    /// Create an empty override table, routing by `fallback`.
+    pub fn new(fallback: impl RoutingPolicy) -> Self {
+        Self {
+            overrides: BTreeMap::new(),
+            fallback: Arc::new(fallback),
+        }
+    }
+
+    /// Route the destinations under `dest` through the route bound to
+    /// `route`, replacing any existing override for `dest`.
+    pub fn with_override(mut self, dest: impl Into<Addr>, route: impl Into<Addr>) -> Self {
+        self.overrides.insert(dest.into(), route.into());
+        self
+    }
+}
+
+impl RoutingPolicy for StaticOverrides {
+    fn route<'a>(&self, table: &'a dyn RoutingTable, dest: &Addr) -> Option<Route<'a>> {
+        prefixes(dest)
+            .iter()
+            .filter_map(|prefix| self.overrides.get(prefix))
+            .find_map(|route| table.get(route))
+            .or_else(|| self.fallback.route(table, dest))
+    }
+}
+
+/// The default routing policy: [`CostAware`] if this process's
+/// locality is configured, and [`LongestPrefix`] otherwise.
+pub(crate) fn default_policy() -> Arc<dyn RoutingPolicy> {
+    if hyperactor_config::global::get(config::LOCALITY).is_empty() {
+        Arc::new(LongestPrefix)
+    } else {
+        Arc::new(CostAware::configured())
+    }
+}
+
+/// The kind of router that made a [`RoutingDecision`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RouterKind {
+    /// A [`MailboxRouter`](super::MailboxRouter).
+    Router,
+    /// A [`FallbackMailboxRouter`](super::FallbackMailboxRouter).
+    FallbackRouter,
+    /// A [`DialMailboxRouter`](super::DialMailboxRouter).
+    DialRouter,
+    /// A [`MailboxMuxer`](super::MailboxMuxer).
+    Muxer,
+}
+
+/// The route chosen by a [`RoutingDecision`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChosenRoute {
+    /// The route bound to the reference.
+    Bound(Addr),
+    /// The destination's direct address, as it had no bound route.
+    Direct(ChannelAddr),
+    /// The router's default sender, as the destination had no route.
+    Default,
+    /// None: the message was returned as undeliverable.
+    Unroutable,
+    /// The route bound to the world behind the gateway with this uid;
+    /// see [`DialMailboxRouter::bind_world`](super::DialMailboxRouter::bind_world).
+    World(Uid),
+}
+
+/// A message routed by a router or muxer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoutingDecision {
+    /// When the message was routed.
+    pub time: SystemTime,
+    /// The router that routed the message.
+    pub router: RouterKind,
+    /// The message's destination.
+    pub dest: PortAddr,
+    /// The route chosen for the message.
+    pub route: ChosenRoute,
+}
+
+static AUDIT_LOG: Mutex<VecDeque<RoutingDecision>> = Mutex::new(VecDeque::new());
+
+/// Record the routing of a message to `dest` if
+/// [`config::ROUTING_AUDIT`] is enabled. `route` is evaluated only then.
+pub(crate) fn audit(router: RouterKind, dest: &PortAddr, route: impl FnOnce() -> ChosenRoute) {
+    if !hyperactor_config::global::get(config::ROUTING_AUDIT) {
+        return;
+    }
+    let decision = RoutingDecision {
+        time: SystemTime::now(),
+        router,
+        dest: dest.clone(),
+        route: route(),
+    };
+    let capacity = hyperactor_config::global::get(config::ROUTING_AUDIT_CAPACITY);
+    let mut log = AUDIT_LOG.lock().unwrap_or_else(|e| e.into_inner());
+    while log.len() >= capacity.max(1) {
+        log.pop_front();
+    }
+    log.push_back(decision);
+}
+
+/// The most recent routing decisions of this process, oldest first.
+/// Empty unless [`config::ROUTING_AUDIT`] is enabled.
+pub fn audit_log() -> Vec<RoutingDecision> {
+    let log = AUDIT_LOG.lock().unwrap_or_else(|e| e.into_inner());
+    log.iter().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor_config::Flattrs;
+
+    use super::*;
+    use crate::channel::TlsAddr;
+    use crate::mailbox::DialMailboxRouter;
+    use crate::mailbox::IntoBoxedMailboxSender as _;
+    use crate::mailbox::Mailbox;
+    use crate::mailbox::MailboxRouter;
+    use crate::mailbox::MessageEnvelope;
+    use crate::mailbox::UnroutableMailboxSender;
+    use crate::mailbox::undeliverable::new_undeliverable_port;
+    use crate::testing::ids::test_actor_id;
+    use crate::testing::ids::test_port_id;
+    use crate::testing::ids::test_proc_id;
+
+    fn tcp(addr: &str) -> ChannelAddr {
+        ChannelAddr::Tcp(addr.parse().unwrap())
+    }
+
+    fn routed(
+        policy: &dyn RoutingPolicy,
+        table: &BTreeMap<Addr, ChannelAddr>,
+        dest: &Addr,
+    ) -> Option<Addr> {
+        policy.route(table, dest).map(|route| route.key.clone())
+    }
+
+    #[test]
+    fn test_policies() {
+        let proc: Addr = test_proc_id("proc").into();
+        let actor: Addr = test_actor_id("proc", "actor").into();
+        let port: Addr = test_port_id("proc", "actor", 1).into();
+        let other_port: Addr = test_port_id("proc", "actor", 2).into();
+        let other_actor: Addr = test_port_id("proc", "other", 1).into();
+        let forwarder: Addr = test_proc_id("forwarder").into();
+        let unbound: Addr = test_port_id("unbound", "actor", 1).into();
+
+        let mut table = BTreeMap::new();
+        table.insert(proc.clone(), tcp("10.0.0.1:1"));
+        table.insert(actor.clone(), tcp("10.0.0.2:1"));
+        table.insert(port.clone(), tcp("10.0.0.3:1"));
+        table.insert(forwarder.clone(), tcp("10.0.0.4:1"));
+
+        // Port routes are considered only by ExactMatchFirst.
+        assert_eq!(routed(&LongestPrefix, &table, &port), Some(actor.clone()));
+        assert_eq!(routed(&ExactMatchFirst, &table, &port), Some(port.clone()));
+        assert_eq!(
+            routed(&ExactMatchFirst, &table, &other_port),
+            Some(actor.clone())
+        );
+        for policy in [&LongestPrefix as &dyn RoutingPolicy, &ExactMatchFirst] {
+            assert_eq!(routed(policy, &table, &other_actor), Some(proc.clone()));
+            assert_eq!(routed(policy, &table, &unbound), None);
+        }
+
+        // Prefer the longest route on the local host.
+        assert_eq!(
+            routed(&LocalityAware::new("10.0.0.1"), &table, &port),
+            Some(proc.clone())
+        );
+        assert_eq!(
+            routed(&LocalityAware::new("10.0.0.2"), &table, &port),
+            Some(actor.clone())
+        );
+        assert_eq!(
+            routed(&LocalityAware::new("10.0.0.9"), &table, &port),
+            Some(port.clone())
+        );
+
+        let policy = StaticOverrides::new(LongestPrefix)
+            .with_override(proc.clone(), forwarder.clone())
+            .with_override(actor.clone(), unbound.clone());
+        // The override for the actor's route is unbound, so the
+        // override for its proc applies.
+        assert_eq!(routed(&policy, &table, &port), Some(forwarder.clone()));
+        assert_eq!(routed(&policy, &table, &forwarder), Some(forwarder.clone()));
+        assert_eq!(routed(&policy, &table, &unbound), None);
+    }
+
+    #[test]
+    fn test_locality() {
+        let policy = LocalityAware::new("host.example.com");
+        assert!(policy.is_local(&ChannelAddr::Local(0)));
+        assert!(policy.is_local(&ChannelAddr::MetaTls(TlsAddr::new("host.example.com", 1))));
+        assert!(!policy.is_local(&ChannelAddr::MetaTls(TlsAddr::new("other.example.com", 1))));
+        assert!(!policy.is_local(&tcp("10.0.0.1:1")));
+        assert!(LocalityAware::new("10.0.0.1").is_local(&tcp("10.0.0.1:1")));
+    }
+
+    #[test]
+    fn test_locality_precedence() {
+        let proc: Addr = test_proc_id("proc").into();
+        let actor: Addr = test_actor_id("proc", "actor").into();
+        let port: Addr = test_port_id("proc", "actor", 1).into();
+        let policy = LocalityAware::new("10.0.0.1");
+
+        let mut table = BTreeMap::new();
+        table.insert(proc.clone(), tcp("10.0.0.1:1"));
+        table.insert(port.clone(), tcp("10.0.0.2:1"));
+        // A local route to the proc is preferred to a remote route to
+        // the port itself.
+        assert_eq!(routed(&policy, &table, &port), Some(proc.clone()));
+
+        // Among local routes, the most specific is preferred.
+        table.insert(actor.clone(), ChannelAddr::Local(1));
+        assert_eq!(routed(&policy, &table, &port), Some(actor.clone()));
+        table.insert(port.clone(), tcp("10.0.0.1:2"));
+        assert_eq!(routed(&policy, &table, &port), Some(port.clone()));
+
+        // Without local routes, the most specific remote route is.
+        let remote = LocalityAware::new("10.0.0.9");
+        table.insert(actor.clone(), tcp("10.0.0.3:1"));
+        assert_eq!(routed(&remote, &table, &port), Some(port.clone()));
+        table.remove(&port);
+        assert_eq!(routed(&remote, &table, &port), Some(actor.clone()));
+    }
+
+    #[tokio::test]
+    async fn test_audit_log() {
+        let mbox = Mailbox::new(test_actor_id("audit", "actor"));
+        let (port, _receiver) = mbox.open_port::<u64>();
+        let port = port.bind().port_addr().clone();
+        let unbound = test_port_id("audit_unbound", "actor", 1);
+
+        let router = MailboxRouter::new();
+        router.bind(test_proc_id("audit"), mbox.clone());
+        let senders = [
+            router.clone().into_boxed(),
+            router.fallback(UnroutableMailboxSender.into_boxed()),
+            DialMailboxRouter::new_with_default_direct_addressed_remote_only(
+                UnroutableMailboxSender.into_boxed(),
+            )
+            .into_boxed(),
+        ];
+        let (return_handle, _return_receiver) = new_undeliverable_port();
+        let post_all = || {
+            for sender in &senders {
+                for dest in [&port, &unbound] {
+                    let data = wirevalue::Any::serialize(&1u64).unwrap();
+                    let envelope = MessageEnvelope::new(
+                        test_actor_id("audit", "sender"),
+                        dest.clone(),
+                        data,
+                        Flattrs::new(),
+                    );
+                    sender.post(envelope, return_handle.clone());
+                }
+            }
+        };
+        // Other tests may route concurrently, so only consider the
+        // decisions for this test's destinations.
+        let audited = || {
+            audit_log()
+                .into_iter()
+                .filter(|decision| decision.dest == port || decision.dest == unbound)
+                .map(|decision| (decision.router, decision.dest, decision.route))
+                .collect::<Vec<_>>()
+        };
+
+        let config = hyperactor_config::global::lock();
+        // Not audited by default.
+        post_all();
+        assert!(audited().is_empty());
+
+        let guard = config.override_key(crate::config::ROUTING_AUDIT, true);
+        post_all();
+        drop(guard);
+        let bound = ChosenRoute::Bound(test_proc_id("audit").into());
+        assert_eq!(
+            audited(),
+            vec![
+                (RouterKind::Router, port.clone(), bound.clone()),
+                (RouterKind::Router, unbound.clone(), ChosenRoute::Unroutable),
+                (RouterKind::FallbackRouter, port.clone(), bound),
+                (
+                    RouterKind::FallbackRouter,
+                    unbound.clone(),
+                    ChosenRoute::Default
+                ),
+                // Local procs are not dialed directly.
+                (RouterKind::DialRouter, port.clone(), ChosenRoute::Default),
+                (
+                    RouterKind::DialRouter,
+                    unbound.clone(),
+                    ChosenRoute::Default
+                ),
+            ]
+        );
+
+        // The log is bounded.
+        let _guard = config.override_key(crate::config::ROUTING_AUDIT, true);
+        let _capacity = config.override_key(crate::config::ROUTING_AUDIT_CAPACITY, 2);
+        post_all();
+        assert!(audit_log().len() <= 2);
+    }
+}
@@ -0,0 +1,195 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Sampled message tracing.
+//!
+//! Logging every message is too expensive at scale. Instead, a fraction
+//! of the serialized envelopes delivered to local ports is recorded
+//! through telemetry, as `message_sample` events. The fraction is
+//! [`MESSAGE_TRACE_SAMPLING_RATE`] by default, and can be overridden at
+//! runtime per message type and per destination actor name through
+//! [`MessageSampler::global`]; destination rates take precedence over
+//! type rates.
+//!
+//! Samples are keyed by the message's telemetry message id, which is
+//! also the id of its lifecycle events (see
+//! [`TELEMETRY_MESSAGE_ID`](crate::mailbox::headers::TELEMETRY_MESSAGE_ID)),
+//! so that a sampled message can be traced through its queueing and
+//! handling. The sampling decision is a deterministic function of this
+//! id, so that the same message is either always or never sampled at a
+//! given rate.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+use hyperactor_config::global;
+
+use crate::ActorAddr;
+use crate::PortAddr;
+use crate::config::MESSAGE_TRACE_SAMPLING_RATE;
+use crate::mailbox::snapshot::Snapshot;
+use crate::metrics;
+
+/// Per-type and per-destination message sampling rates. The rates are
+/// read on every delivery, so they are kept in [`Snapshot`]s.
+#[derive(Debug, Default)]
+pub struct MessageSampler {
+    types: Snapshot<HashMap<String, f64>>,
+    destinations: Snapshot<HashMap<String, f64>>,
+    /// Whether any per-type or per-destination rate is set. Lets
+    /// [`MessageSampler::rate`] skip the lookups in the common case
+    /// where none is.
+    overridden: AtomicBool,
+}
+
+impl MessageSampler {
+    /// The process-wide sampler, consulted on message delivery.
+    pub fn global() -> &'static MessageSampler {
+        static GLOBAL: OnceLock<MessageSampler> = OnceLock::new();
+        GLOBAL.get_or_init(MessageSampler::default)
+    }
+
+    /// Sample messages of type `typename` at `rate`, between 0 and 1.
+    pub fn set_type_rate(&self, typename: impl Into<String>, rate: f64) {
+        let typename = typename.into();
+        self.types.update(|types| {
+            types.insert(typename, rate.clamp(0.0, 1.0));
+        });
+        self.overridden.store(true, Ordering::Release);
+    }
+
+    /// Sample messages to actors named `actor_name` at `rate`, between
+    /// 0 and 1.
+    pub fn set_destination_rate(&self, actor_name: impl Into<String>, rate: f64) {
+        let actor_name = actor_name.into();
+        self.destinations.update(|destinations| {
+            destinations.insert(actor_name, rate.clamp(0.0, 1.0));
+        });
+        self.overridden.store(true, Ordering::Release);
+    }
+
+    /// Remove all per-type and per-destination rates.
+    pub fn clear(&self) {
+        self.types.replace(HashMap::new());
+        self.destinations.replace(HashMap::new());
+        self.overridden.store(false, Ordering::Release);
+    }
+
+    /// The sampling rate of messages of type `typename` to `dest`.
+    pub fn rate(&self, typename: Option<&str>, dest: &PortAddr) -> f64 {
+        if !self.overridden.load(Ordering::Acquire) {
+            return global::get(MESSAGE_TRACE_SAMPLING_RATE);
+        }
+        if let Some(label) = dest.actor_addr().label()
+            && let Some(rate) = self.destinations.load().get(label.as_str())
+        {
+            return *rate;
+        }
+        if let Some(typename) = typename
+            && let Some(rate) = self.types.load().get(typename)
+        {
+            return *rate;
+        }
+        global::get(MESSAGE_TRACE_SAMPLING_RATE)
+    }
+
+    /// Whether to sample the message with telemetry message id
+    /// `message_id`, of type `typename`, to `dest`.
+    pub fn should_sample(&self, message_id: u64, typename: Option<&str>, dest: &PortAddr) -> bool {
+        sampled(message_id, self.rate(typename, dest))
+    }
+}
+
+/// Whether `message_id` falls within the sampled fraction `rate`.
+fn sampled(message_id: u64, rate: f64) -> bool {
+    if rate <= 0.0 {
+        return false;
+    }
+    if rate >= 1.0 {
+        return true;
+    }
+    // Telemetry message ids are structured; mix them (splitmix64) so
+    // that the sampled fraction is uniform.
+    let mut x = message_id;
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^= x >> 31;
+    (x as f64) < rate * u64::MAX as f64
+}
+
+/// Record the delivery of `data` from `sender` to `dest`, if sampled.
+pub(crate) fn sample_delivery(
+    message_id: u64,
+    sender: &ActorAddr,
+    dest: &PortAddr,
+    data: &wirevalue::Any,
+) {
+    let typename = data.typename();
+    if !MessageSampler::global().should_sample(message_id, typename, dest) {
+        return;
+    }
+    tracing::info!(
+        name = "message_sample",
+        trace_id = format!("{:016x}", message_id),
+        message_id,
+        message_type = typename.unwrap_or("unknown"),
+        sender = %sender,
+        dest = %dest,
+        size = data.len(),
+    );
+    metrics::MAILBOX_MESSAGES_SAMPLED.add(
+        1,
+        hyperactor_telemetry::kv_pairs!(
+            "message_type" => typename.unwrap_or("unknown").to_string(),
+        ),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::port::Port;
+    use crate::testing::ids::test_actor_id;
+
+    #[test]
+    fn test_sampled() {
+        assert!(!sampled(1, 0.0));
+        assert!(sampled(1, 1.0));
+        let count = (0..10_000u64).filter(|id| sampled(*id, 0.25)).count();
+        assert!((2_000..3_000).contains(&count), "sampled {count}");
+        // Sampling is consistent for a given message id.
+        for id in 0..100u64 {
+            assert_eq!(sampled(id, 0.5), sampled(id, 0.5));
+            assert!(!sampled(id, 0.25) || sampled(id, 0.5));
+        }
+    }
+
+    #[test]
+    fn test_rate() {
+        let sampler = MessageSampler::default();
+        let dest = test_actor_id("proc", "worker").port_addr(Port::from(1));
+        let other = test_actor_id("proc", "other").port_addr(Port::from(1));
+        assert_eq!(sampler.rate(Some("my::Message"), &dest), 0.0);
+
+        sampler.set_type_rate("my::Message", 0.5);
+        assert_eq!(sampler.rate(Some("my::Message"), &dest), 0.5);
+        assert_eq!(sampler.rate(Some("my::Other"), &dest), 0.0);
+
+        sampler.set_destination_rate("worker", 2.0);
+        assert_eq!(sampler.rate(Some("my::Message"), &dest), 1.0);
+        assert_eq!(sampler.rate(Some("my::Message"), &other), 0.5);
+        assert!(sampler.should_sample(7, None, &dest));
+        assert!(!sampler.should_sample(7, None, &other));
+
+        sampler.clear();
+        assert!(!sampler.overridden.load(Ordering::Acquire));
+        assert_eq!(sampler.rate(Some("my::Message"), &dest), 0.0);
+    }
+}
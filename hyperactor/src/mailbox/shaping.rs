@@ -0,0 +1,249 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Traffic shaping of mailbox clients by channel class.
+//!
+//! Each [`MailboxClient`] is tagged with a [`ChannelClass`]: by
+//! default, the class configured by [`config::CHANNEL_CLASS`] for the
+//! process, so that a process running, say, bulk transfers can be
+//! launched with `HYPERACTOR_CHANNEL_CLASS=bulk`. The
+//! clients of a class share, process-wide, a message-rate ceiling and a
+//! bandwidth ceiling, configured by the `CHANNEL_<CLASS>_MAX_*_PER_SEC`
+//! attributes in [`crate::config`]. A client whose class is over its
+//! ceiling holds further messages in its buffer until the class is back
+//! under it. Capping the bulk and telemetry classes keeps their
+//! transfers from delaying control-plane messages sent on other
+//! channels.
+//!
+//! Ceilings are token buckets that hold up to one second of traffic, so
+//! short bursts are transmitted immediately. A message larger than the
+//! bucket is sent once the bucket is full, and delays the class's later
+//! messages until it has been paid for.
+//!
+//! [`MailboxClient`]: crate::mailbox::MailboxClient
+
+use std::sync::LazyLock;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use hyperactor_config::attrs::AttrValue;
+use hyperactor_config::attrs::Key;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::time::Instant;
+
+use crate::config;
+use crate::metrics;
+
+/// The class of traffic that a channel carries, which determines the
+/// ceilings it is shaped to.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    typeuri::Named,
+    strum::Display,
+    strum::EnumString
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum ChannelClass {
+    /// Latency-sensitive control-plane messages. This is the default.
+    #[default]
+    Control,
+    /// Large data transfers.
+    Bulk,
+    /// Metrics, logs, and other telemetry.
+    Telemetry,
+}
+
+impl ChannelClass {
+    /// The class configured by [`config::CHANNEL_CLASS`].
+    pub fn configured() -> Self {
+        hyperactor_config::global::get(config::CHANNEL_CLASS)
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Self::Control => 0,
+            Self::Bulk => 1,
+            Self::Telemetry => 2,
+        }
+    }
+
+    /// The configured message-rate and bandwidth ceilings of this class.
+    fn ceilings(self) -> (Key<u64>, Key<u64>) {
+        match self {
+            Self::Control => (
+                config::CHANNEL_CONTROL_MAX_MESSAGES_PER_SEC,
+                config::CHANNEL_CONTROL_MAX_BYTES_PER_SEC,
+            ),
+            Self::Bulk => (
+                config::CHANNEL_BULK_MAX_MESSAGES_PER_SEC,
+                config::CHANNEL_BULK_MAX_BYTES_PER_SEC,
+            ),
+            Self::Telemetry => (
+                config::CHANNEL_TELEMETRY_MAX_MESSAGES_PER_SEC,
+                config::CHANNEL_TELEMETRY_MAX_BYTES_PER_SEC,
+            ),
+        }
+    }
+}
+
+impl AttrValue for ChannelClass {
+    fn display(&self) -> String {
+        self.to_string()
+    }
+
+    fn parse(s: &str) -> Result<Self, anyhow::Error> {
+        Ok(s.parse()?)
+    }
+}
+
+/// A token bucket holding up to one second of tokens at its rate.
+#[derive(Debug)]
+struct Bucket {
+    // The available tokens; negative while a message larger than the
+    // bucket is being paid for.
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    /// A full bucket.
+    fn new() -> Self {
+        Self {
+            tokens: f64::INFINITY,
+            refilled: Instant::now(),
+        }
+    }
+
+    /// Take `amount` tokens at `rate` tokens per second (0 for
+    /// unlimited), returning how long the caller must wait before it
+    /// may transmit.
+    fn take(&mut self, amount: f64, rate: u64, now: Instant) -> Duration {
+        if rate == 0 {
+            self.tokens = f64::INFINITY;
+            self.refilled = now;
+            return Duration::ZERO;
+        }
+        let rate = rate as f64;
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(rate);
+        self.refilled = now;
+        // Wait until the bucket can cover the message, or is full.
+        let wait = if self.tokens >= amount.min(rate) {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((amount.min(rate) - self.tokens) / rate)
+        };
+        self.tokens -= amount;
+        wait
+    }
+}
+
+/// The message-rate and bandwidth buckets of a channel class.
+#[derive(Debug)]
+struct Shaper {
+    buckets: Mutex<(Bucket, Bucket)>,
+}
+
+static SHAPERS: LazyLock<[Shaper; 3]> = LazyLock::new(|| {
+    std::array::from_fn(|_| Shaper {
+        buckets: Mutex::new((Bucket::new(), Bucket::new())),
+    })
+});
+
+/// The time a message of `len` bytes must wait before it is
+/// transmitted on a channel of `class`, reserving its share of the
+/// class's ceilings.
+fn reserve(class: ChannelClass, len: usize) -> Duration {
+    let (max_messages, max_bytes) = class.ceilings();
+    let max_messages = hyperactor_config::global::get(max_messages);
+    let max_bytes = hyperactor_config::global::get(max_bytes);
+    if max_messages == 0 && max_bytes == 0 {
+        return Duration::ZERO;
+    }
+    let now = Instant::now();
+    let mut buckets = SHAPERS[class.index()].buckets.lock().unwrap();
+    let (messages, bytes) = &mut *buckets;
+    messages
+        .take(1.0, max_messages, now)
+        .max(bytes.take(len as f64, max_bytes, now))
+}
+
+/// Wait until a message of `len` bytes may be transmitted on a channel
+/// of `class`.
+pub(crate) async fn shape(class: ChannelClass, len: usize) {
+    let wait = reserve(class, len);
+    if wait.is_zero() {
+        return;
+    }
+    metrics::CHANNEL_SHAPING_DELAYED.add(
+        1,
+        hyperactor_telemetry::kv_pairs!("class" => class.to_string()),
+    );
+    tokio::time::sleep(wait).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new();
+
+        // A full bucket holds one second of tokens.
+        assert_eq!(bucket.take(60.0, 100, start), Duration::ZERO);
+        assert_eq!(bucket.take(40.0, 100, start), Duration::ZERO);
+        assert_eq!(bucket.take(50.0, 100, start), Duration::from_secs_f64(0.5));
+        // The bucket refills at its rate, and is in debt for the
+        // message above.
+        let later = start + Duration::from_millis(500);
+        assert_eq!(bucket.take(50.0, 100, later), Duration::from_secs_f64(0.5));
+
+        // A message larger than the bucket waits for a full bucket, and
+        // the debt delays later messages.
+        let mut bucket = Bucket::new();
+        assert_eq!(bucket.take(250.0, 100, start), Duration::ZERO);
+        assert_eq!(bucket.take(1.0, 100, start), Duration::from_secs_f64(1.51));
+
+        // A zero rate is unlimited.
+        assert_eq!(bucket.take(1e9, 0, start), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shape_by_class() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(config::CHANNEL_BULK_MAX_BYTES_PER_SEC, 1000);
+
+        // Bulk traffic is limited to 1000 bytes a second; control
+        // traffic is not.
+        let start = Instant::now();
+        for _ in 0..5 {
+            shape(ChannelClass::Bulk, 1000).await;
+            shape(ChannelClass::Control, 1000).await;
+        }
+        let elapsed = start.elapsed();
+        assert!(
+            elapsed >= Duration::from_secs(4) && elapsed < Duration::from_secs(6),
+            "elapsed {:?}",
+            elapsed
+        );
+
+        assert_eq!("bulk".parse::<ChannelClass>().unwrap(), ChannelClass::Bulk);
+        assert_eq!(ChannelClass::Telemetry.to_string(), "telemetry");
+    }
+}
@@ -0,0 +1,125 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Routing state that cannot be poisoned.
+//!
+//! Routing tables are read on every post and changed rarely. A
+//! [`Snapshot`] holds such a table as an immutable snapshot that is
+//! replaced as a whole whenever it changes: reads load the current
+//! snapshot without taking a lock, and changes are applied to a copy,
+//! which is then published.
+//!
+//! A panic can therefore never leave a table half-changed, or leave a
+//! lock poisoned for the posts that follow: a change that panics
+//! publishes nothing, so the table keeps its last published state.
+//! Changes are serialized by a lock that guards no data, so a panicking
+//! change does not stop later ones either.
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use arc_swap::ArcSwap;
+use arc_swap::Guard;
+
+/// A value read as immutable snapshots; see the [module
+/// documentation](self).
+pub(crate) struct Snapshot<T> {
+    current: ArcSwap<T>,
+    // Serializes changes, so that concurrent changes are not lost.
+    writer: Mutex<()>,
+}
+
+impl<T> Snapshot<T> {
+    /// A snapshot of `value`.
+    pub(crate) fn new(value: T) -> Self {
+        Self {
+            current: ArcSwap::from_pointee(value),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// The current snapshot.
+    pub(crate) fn load(&self) -> Guard<Arc<T>> {
+        self.current.load()
+    }
+
+    /// A copy of the current snapshot.
+    pub(crate) fn get(&self) -> T
+    where
+        T: Clone,
+    {
+        T::clone(&self.current.load())
+    }
+
+    /// Apply `update` to a copy of the current snapshot, and publish
+    /// the copy. If `update` panics, nothing is published.
+    pub(crate) fn update<R>(&self, update: impl FnOnce(&mut T) -> R) -> R
+    where
+        T: Clone,
+    {
+        let _writer = self.lock_writer();
+        let mut value = T::clone(&self.current.load());
+        let result = update(&mut value);
+        self.current.store(Arc::new(value));
+        result
+    }
+
+    /// Publish `value`, replacing the current snapshot without copying
+    /// it, for example to clear a table.
+    pub(crate) fn replace(&self, value: T) {
+        let _writer = self.lock_writer();
+        self.current.store(Arc::new(value));
+    }
+
+    fn lock_writer(&self) -> std::sync::MutexGuard<'_, ()> {
+        // The lock guards no data, so a poisoned lock is safe to reuse.
+        self.writer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<T: Default> Default for Snapshot<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Snapshot<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self.current.load(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::AssertUnwindSafe;
+    use std::panic::catch_unwind;
+
+    use super::*;
+
+    #[test]
+    fn test_panicking_update_publishes_nothing() {
+        let snapshot = Snapshot::new(vec![1, 2]);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            snapshot.update(|value| {
+                value.push(3);
+                panic!("update failed");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(**snapshot.load(), vec![1, 2]);
+
+        // Later changes are not blocked by the failed one.
+        snapshot.update(|value| value.push(4));
+        assert_eq!(snapshot.get(), vec![1, 2, 4]);
+        snapshot.replace(vec![5]);
+        assert_eq!(**snapshot.load(), vec![5]);
+    }
+}
@@ -0,0 +1,559 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Spilling of large message payloads.
+//!
+//! Payloads of at least [`MESSAGE_SPILL_THRESHOLD`] bytes are not sent
+//! inline on the control channel. Instead, a [`MailboxClient`] stores
+//! the payload in the host's [`BlobCache`], and transmits the envelope
+//! with an empty payload and a [`SPILLED_PAYLOAD`] header referring to
+//! the blob. The receiving [`MailboxServer`] reads the blob from its own
+//! host's cache when present, and otherwise fetches it over the
+//! sender's [`BlobStore`], a bulk side channel (a plain TCP stream),
+//! caching it for the other procs on its host. It then reconstructs the
+//! payload before dispatching the envelope, in order with the messages
+//! that follow it. This keeps large payloads off the control channel.
+//!
+//! A store serves only the blobs it holds a [`BlobLease`] on, and only
+//! to requesters that present the blob's token: a digest keyed with a
+//! secret of the store, which is carried in the [`BlobRef`]. Thus only
+//! the recipients of a spilled message can fetch its payload. Once its
+//! last lease is dropped, the store removes the blob from the cache if
+//! the store added it.
+//!
+//! [`MailboxClient`]: crate::mailbox::MailboxClient
+//! [`MailboxServer`]: crate::mailbox::MailboxServer
+//! [`SPILLED_PAYLOAD`]: crate::mailbox::headers::SPILLED_PAYLOAD
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use hyperactor_config::AttrValue;
+use hyperactor_config::global;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use typeuri::Named;
+
+use crate::blob_cache::BlobCache;
+use crate::blob_cache::Digest;
+use crate::channel::ChannelAddr;
+use crate::channel::ChannelTransport;
+use crate::channel::TcpMode;
+use crate::config::MESSAGE_SPILL_FETCH_TIMEOUT;
+use crate::config::MESSAGE_SPILL_THRESHOLD;
+use crate::mailbox::MessageEnvelope;
+use crate::mailbox::headers::SPILLED_PAYLOAD;
+
+/// Sent in place of a blob's length when the blob is not served by the
+/// store, or the requester's token does not match.
+const MISSING: u64 = u64::MAX;
+
+/// Errors that occur when spilling or fetching payloads.
+#[derive(thiserror::Error, Debug)]
+pub enum SpillError {
+    /// The blob store could not read or write a blob.
+    #[error("blob store i/o error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The payload could not be encoded or decoded.
+    #[error("failed to encode spilled payload: {0}")]
+    Encoding(String),
+
+    /// The blob is not (or no longer) served by the sender's store.
+    #[error("blob {0} is missing")]
+    Missing(Digest),
+
+    /// The fetched blob's contents do not match its digest.
+    #[error("blob {0} is corrupt")]
+    Corrupt(Digest),
+
+    /// The blob was not fetched within [`MESSAGE_SPILL_FETCH_TIMEOUT`].
+    #[error("timed out fetching blob {0}")]
+    Timeout(Digest),
+
+    /// The blob store could not be started.
+    #[error("blob store is unavailable: {0}")]
+    Unavailable(String),
+}
+
+/// A reference to a spilled payload, carried in the
+/// [`SPILLED_PAYLOAD`] header in place of the payload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Named, AttrValue)]
+pub struct BlobRef {
+    /// The address of the sender's blob store.
+    pub addr: SocketAddr,
+    /// The digest of the blob's contents.
+    pub digest: Digest,
+    /// The length of the blob, in bytes.
+    pub len: u64,
+    /// The token with which to request the blob from the store.
+    pub token: Digest,
+}
+
+impl fmt::Display for BlobRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_json::to_string(self).unwrap())
+    }
+}
+
+impl FromStr for BlobRef {
+    type Err = serde_json::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+/// Serves the leased blobs of a [`BlobCache`] to remote procs over TCP.
+/// Each connection requests a single blob by writing its 32-byte digest
+/// followed by its 32-byte token; the store replies with the blob's
+/// length as a big-endian `u64`, followed by its contents.
+///
+/// Dropping the store stops serving, and removes the blobs it added to
+/// the cache.
+#[derive(Debug)]
+pub struct BlobStore {
+    cache: BlobCache,
+    addr: SocketAddr,
+    key: Digest,
+    leases: Arc<Leases>,
+    serving: tokio::task::AbortHandle,
+}
+
+/// The blobs leased by a [`BlobStore`], by digest.
+#[derive(Debug, Default)]
+struct Leases(Mutex<HashMap<Digest, Lease>>);
+
+#[derive(Debug)]
+struct Lease {
+    /// The number of live [`BlobLease`]s on the blob.
+    count: usize,
+    /// Whether the store added the blob to the cache, and should thus
+    /// remove it.
+    owned: bool,
+}
+
+impl Leases {
+    fn contains(&self, digest: &Digest) -> bool {
+        self.0.lock().unwrap().contains_key(digest)
+    }
+
+    fn acquire(&self, digest: Digest, owned: bool) {
+        self.0
+            .lock()
+            .unwrap()
+            .entry(digest)
+            .and_modify(|lease| {
+                lease.count += 1;
+                lease.owned |= owned;
+            })
+            .or_insert(Lease { count: 1, owned });
+    }
+
+    /// Release a lease on `digest`, removing the blob from `cache` when
+    /// it was the last, and the store added it.
+    fn release(&self, cache: &BlobCache, digest: &Digest) {
+        let mut leases = self.0.lock().unwrap();
+        let Some(lease) = leases.get_mut(digest) else {
+            return;
+        };
+        lease.count -= 1;
+        if lease.count == 0 {
+            let owned = lease.owned;
+            leases.remove(digest);
+            if owned {
+                remove_blob(cache, digest);
+            }
+        }
+    }
+
+    /// Release every lease, removing the blobs the store added.
+    fn clear(&self, cache: &BlobCache) {
+        for (digest, lease) in self.0.lock().unwrap().drain() {
+            if lease.owned {
+                remove_blob(cache, &digest);
+            }
+        }
+    }
+}
+
+fn remove_blob(cache: &BlobCache, digest: &Digest) {
+    match std::fs::remove_file(cache.path(digest)) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => tracing::warn!("failed to remove spilled blob {}: {}", digest, err),
+    }
+}
+
+/// A lease on a blob served by a [`BlobStore`]. The store serves the
+/// blob for as long as it is leased.
+#[derive(Debug)]
+pub struct BlobLease {
+    blob: BlobRef,
+    cache: BlobCache,
+    leases: Arc<Leases>,
+}
+
+impl BlobLease {
+    /// The reference with which to fetch the blob.
+    pub fn blob(&self) -> &BlobRef {
+        &self.blob
+    }
+
+    /// Release the lease once `delay` has elapsed.
+    pub fn release_after(self, delay: Duration) {
+        crate::init::get_runtime().spawn(async move {
+            tokio::time::sleep(delay).await;
+            drop(self);
+        });
+    }
+}
+
+impl Drop for BlobLease {
+    fn drop(&mut self) {
+        self.leases.release(&self.cache, &self.blob.digest);
+    }
+}
+
+impl BlobStore {
+    /// The process-wide store, serving the [host cache](BlobCache::host),
+    /// started on first use.
+    pub fn global() -> Result<&'static BlobStore, SpillError> {
+        static GLOBAL: OnceLock<Result<BlobStore, String>> = OnceLock::new();
+        GLOBAL
+            .get_or_init(|| {
+                BlobCache::host()
+                    .map_err(SpillError::from)
+                    .and_then(|cache| BlobStore::new(cache.clone()))
+                    .map_err(|err| err.to_string())
+            })
+            .as_ref()
+            .map_err(|err| SpillError::Unavailable(err.clone()))
+    }
+
+    /// Serve `cache` on a routable address of this host.
+    pub fn new(cache: BlobCache) -> Result<Self, SpillError> {
+        let bind_addr = match ChannelAddr::any(ChannelTransport::Tcp(TcpMode::Hostname)) {
+            ChannelAddr::Tcp(addr) => addr,
+            addr => unreachable!("tcp transport produced {}", addr),
+        };
+        let listener = std::net::TcpListener::bind(bind_addr)?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+
+        let key = Digest::from_bytes(rand::random());
+        let leases = Arc::new(Leases::default());
+        let serve_cache = cache.clone();
+        let serve_leases = Arc::clone(&leases);
+        let serving = crate::init::get_runtime().spawn(async move {
+            let listener = match tokio::net::TcpListener::from_std(listener) {
+                Ok(listener) => listener,
+                Err(err) => {
+                    tracing::error!("blob store at {} failed to listen: {}", addr, err);
+                    return;
+                }
+            };
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        let cache = serve_cache.clone();
+                        let leases = Arc::clone(&serve_leases);
+                        tokio::spawn(async move {
+                            if let Err(err) = serve_blob(&cache, &key, &leases, stream).await {
+                                tracing::warn!("failed to serve blob to {}: {}", peer, err);
+                            }
+                        });
+                    }
+                    Err(err) => {
+                        tracing::warn!("blob store at {} failed to accept: {}", addr, err);
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            cache,
+            addr,
+            key,
+            leases,
+            serving: serving.abort_handle(),
+        })
+    }
+
+    /// The address of this store's bulk side channel.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Store `data` as a blob, served for as long as the returned lease
+    /// is held.
+    pub async fn put(&self, data: Vec<u8>) -> Result<BlobLease, SpillError> {
+        let len = data.len() as u64;
+        let owned = !self.cache.contains(&Digest::of(&data));
+        let digest = self.cache.insert(data).await?;
+        self.leases.acquire(digest, owned);
+        Ok(BlobLease {
+            blob: BlobRef {
+                addr: self.addr,
+                digest,
+                len,
+                token: token(&self.key, &digest),
+            },
+            cache: self.cache.clone(),
+            leases: Arc::clone(&self.leases),
+        })
+    }
+}
+
+impl Drop for BlobStore {
+    fn drop(&mut self) {
+        self.serving.abort();
+        self.leases.clear(&self.cache);
+    }
+}
+
+/// The token for the blob `digest` in the store with secret `key`.
+fn token(key: &Digest, digest: &Digest) -> Digest {
+    let mut keyed = [0u8; 64];
+    keyed[..32].copy_from_slice(key.as_bytes());
+    keyed[32..].copy_from_slice(digest.as_bytes());
+    Digest::of(&keyed)
+}
+
+async fn serve_blob(
+    cache: &BlobCache,
+    key: &Digest,
+    leases: &Leases,
+    mut stream: tokio::net::TcpStream,
+) -> std::io::Result<()> {
+    let mut request = [0u8; 64];
+    stream.read_exact(&mut request).await?;
+    let digest = Digest::from_bytes(request[..32].try_into().unwrap());
+    let expected = token(key, &digest);
+    // Compare in constant time, so as not to leak the token.
+    let authorized = expected
+        .as_bytes()
+        .iter()
+        .zip(&request[32..])
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0;
+    let file = if authorized && leases.contains(&digest) {
+        tokio::fs::File::open(cache.path(&digest)).await.ok()
+    } else {
+        None
+    };
+    let mut file = match file {
+        Some(file) => file,
+        None => {
+            stream.write_u64(MISSING).await?;
+            return stream.shutdown().await;
+        }
+    };
+    stream.write_u64(file.metadata().await?.len()).await?;
+    tokio::io::copy(&mut file, &mut stream).await?;
+    stream.shutdown().await
+}
+
+/// Fetch the blob `blob` from its store, verifying its digest.
+pub async fn fetch(blob: &BlobRef) -> Result<Vec<u8>, SpillError> {
+    let timeout = global::get(MESSAGE_SPILL_FETCH_TIMEOUT);
+    let data = tokio::time::timeout(timeout, async {
+        let mut stream = tokio::net::TcpStream::connect(blob.addr).await?;
+        stream.write_all(blob.digest.as_bytes()).await?;
+        stream.write_all(blob.token.as_bytes()).await?;
+        let len = stream.read_u64().await?;
+        if len == MISSING {
+            return Err(SpillError::Missing(blob.digest));
+        }
+        if len != blob.len {
+            return Err(SpillError::Corrupt(blob.digest));
+        }
+        let mut data = vec![0u8; len as usize];
+        stream.read_exact(&mut data).await?;
+        Ok(data)
+    })
+    .await
+    .map_err(|_| SpillError::Timeout(blob.digest))??;
+    let digest = blob.digest;
+    let verified = tokio::task::spawn_blocking(move || (Digest::of(&data) == digest, data))
+        .await
+        .map_err(|err| SpillError::Io(std::io::Error::other(err)))?;
+    match verified {
+        (true, data) => Ok(data),
+        (false, _) => Err(SpillError::Corrupt(blob.digest)),
+    }
+}
+
+/// Whether `envelope`'s payload has been spilled.
+pub fn is_spilled(envelope: &MessageEnvelope) -> bool {
+    envelope.data.is_broken() && envelope.headers.contains_key(SPILLED_PAYLOAD)
+}
+
+/// Spill `envelope`'s payload to the host's [`BlobCache`] if it is at
+/// least [`MESSAGE_SPILL_THRESHOLD`] bytes. Returns the lease on the
+/// blob and the original payload, if spilled. Payloads that fail to
+/// spill are sent inline.
+pub(crate) async fn spill(envelope: &mut MessageEnvelope) -> Option<(BlobLease, wirevalue::Any)> {
+    let threshold = global::get(MESSAGE_SPILL_THRESHOLD);
+    if threshold == 0 || envelope.data.len() < threshold || is_spilled(envelope) {
+        return None;
+    }
+    let result = async {
+        let encoded = bincode::serde::encode_to_vec(&envelope.data, bincode::config::legacy())
+            .map_err(|err| SpillError::Encoding(err.to_string()))?;
+        BlobStore::global()?.put(encoded).await
+    }
+    .await;
+    match result {
+        Ok(lease) => {
+            envelope.headers.set(SPILLED_PAYLOAD, lease.blob().clone());
+            let data = std::mem::replace(&mut envelope.data, wirevalue::Any::new_broken());
+            Some((lease, data))
+        }
+        Err(err) => {
+            tracing::warn!(
+                dest = %envelope.dest,
+                size = envelope.data.len(),
+                "failed to spill message payload, sending it inline: {}",
+                err
+            );
+            None
+        }
+    }
+}
+
+/// Restore `envelope`'s spilled payload, if any, from the host's
+/// [`BlobCache`] or else from the sender's [`BlobStore`].
+pub(crate) async fn restore(envelope: &mut MessageEnvelope) -> Result<(), SpillError> {
+    if !is_spilled(envelope) {
+        return Ok(());
+    }
+    let blob = envelope
+        .headers
+        .get(SPILLED_PAYLOAD)
+        .expect("spilled envelope has a blob reference");
+    let cache = BlobCache::host()?;
+    let encoded = match cache.get(blob.digest).await? {
+        Some(encoded) => encoded,
+        None => {
+            let encoded = fetch(&blob).await?;
+            // Share the payload with the other procs on this host.
+            if let Err(err) = cache.insert(encoded.clone()).await {
+                tracing::warn!("failed to cache spilled payload {}: {}", blob.digest, err);
+            }
+            encoded
+        }
+    };
+    let (data, _) = bincode::serde::decode_from_slice(&encoded, bincode::config::legacy())
+        .map_err(|err| SpillError::Encoding(err.to_string()))?;
+    envelope.data = data;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor_config::Flattrs;
+
+    use super::*;
+    use crate::port::Port;
+    use crate::testing::ids::test_actor_id;
+
+    #[tokio::test]
+    async fn test_blob_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = BlobCache::new(dir.path().to_path_buf(), u64::MAX).unwrap();
+        let store = BlobStore::new(cache.clone()).unwrap();
+        let data: Vec<u8> = (0..1_000_000u32).map(|i| i as u8).collect();
+
+        let lease = store.put(data.clone()).await.unwrap();
+        let blob = lease.blob().clone();
+        assert_eq!(blob.len, data.len() as u64);
+        assert_eq!(blob.digest, Digest::of(&data));
+        assert_eq!(blob.to_string().parse::<BlobRef>().unwrap(), blob);
+        assert_eq!(fetch(&blob).await.unwrap(), data);
+
+        // Requests must present the blob's token.
+        let forged = BlobRef {
+            token: Digest::of(b"forged"),
+            ..blob.clone()
+        };
+        assert!(matches!(fetch(&forged).await, Err(SpillError::Missing(_))));
+
+        // Blobs in the cache that the store does not lease are not
+        // served, even with a token.
+        let other = cache.insert(b"other".to_vec()).await.unwrap();
+        let unleased = BlobRef {
+            digest: other,
+            len: 5,
+            token: token(&store.key, &other),
+            ..blob.clone()
+        };
+        assert!(matches!(
+            fetch(&unleased).await,
+            Err(SpillError::Missing(_))
+        ));
+
+        // Blobs whose contents do not match their digest are rejected.
+        std::fs::write(cache.path(&blob.digest), vec![0u8; data.len()]).unwrap();
+        assert!(matches!(fetch(&blob).await, Err(SpillError::Corrupt(_))));
+
+        // Releasing the last lease removes the blob.
+        drop(lease);
+        assert!(!cache.contains(&blob.digest));
+        assert!(matches!(fetch(&blob).await, Err(SpillError::Missing(_))));
+
+        // Dropping the store removes the blobs it added, but not the
+        // others.
+        let lease = store.put(data.clone()).await.unwrap();
+        drop(store);
+        assert!(!cache.contains(&lease.blob().digest));
+        assert!(cache.contains(&other));
+        drop(lease);
+    }
+
+    #[tokio::test]
+    async fn test_spill_and_restore() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(MESSAGE_SPILL_THRESHOLD, 1024);
+
+        let dest = test_actor_id("proc", "actor").port_addr(Port::from(1));
+        let sender = test_actor_id("proc", "sender");
+        let small = MessageEnvelope::serialize(
+            sender.clone(),
+            dest.clone(),
+            &vec![1u8; 16],
+            Flattrs::new(),
+        )
+        .unwrap();
+        let large = vec![7u8; 64 * 1024];
+        let mut envelope =
+            MessageEnvelope::serialize(sender, dest, &large, Flattrs::new()).unwrap();
+
+        let mut unspilled = small.clone();
+        assert!(spill(&mut unspilled).await.is_none());
+        assert!(!is_spilled(&unspilled));
+
+        let (lease, data) = spill(&mut envelope).await.unwrap();
+        assert_eq!(data.deserialized::<Vec<u8>>().unwrap(), large);
+        assert!(is_spilled(&envelope));
+        assert!(envelope.data().len() < 1024);
+        assert!(lease.blob().len >= large.len() as u64);
+
+        restore(&mut envelope).await.unwrap();
+        assert!(!is_spilled(&envelope));
+        assert_eq!(envelope.deserialized::<Vec<u8>>().unwrap(), large);
+    }
+}
@@ -0,0 +1,528 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Routing topology export.
+//!
+//! A [`Topology`] is a graph of how messages flow through a process:
+//! its gateways, the procs attached to them and the actors bound in
+//! their muxers, the routers that forward the process's egress, and
+//! the channels through which those routers dial remote procs.
+//! [`Topology::of_gateway`] builds one by walking a gateway's tables
+//! and, recursively, the routers it forwards through.
+//!
+//! Nodes are identified by the references they stand for, so that the
+//! topologies of different processes can be [merged](Topology::merge)
+//! into one graph of a mesh. A topology is exported as JSON (it is
+//! `Serialize`), or as a Graphviz graph with [`Topology::to_dot`].
+//!
+//! Senders that the walk does not know are exported as opaque
+//! [`NodeKind::Sender`] nodes.
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::fmt::Write as _;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::Addr;
+use crate::Location;
+use crate::channel::ChannelAddr;
+use crate::gateway::Gateway;
+use crate::id::Uid;
+use crate::mailbox::BoxedMailboxSender;
+use crate::mailbox::DialMailboxRouter;
+use crate::mailbox::FallbackMailboxRouter;
+use crate::mailbox::Mailbox;
+use crate::mailbox::MailboxClient;
+use crate::mailbox::MailboxRouter;
+use crate::mailbox::MailboxSender;
+use crate::mailbox::UnroutableMailboxSender;
+use crate::mailbox::WeakMailboxRouter;
+use crate::proc::Proc;
+use crate::proc::WeakProc;
+
+/// The kind of a [`TopologyNode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NodeKind {
+    /// A [`Gateway`].
+    Gateway,
+    /// A proc, together with its muxer.
+    Proc,
+    /// An actor.
+    Actor,
+    /// A port.
+    Port,
+    /// A [`MailboxRouter`].
+    Router,
+    /// A [`FallbackMailboxRouter`].
+    FallbackRouter,
+    /// A [`DialMailboxRouter`].
+    DialRouter,
+    /// A channel address.
+    Channel,
+    /// A sender of a kind that the walk does not know.
+    Sender,
+}
+
+/// The kind of a [`TopologyEdge`].
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize
+)]
+pub enum EdgeKind {
+    /// A gateway delivers to a proc attached to it.
+    Attached,
+    /// A gateway accepts traffic on a channel it serves.
+    Serves,
+    /// A gateway forwards to a peer gateway attached to it.
+    Peer,
+    /// Messages with no other route are forwarded to the target.
+    Forwards,
+    /// A proc's muxer or a router routes the reference in the edge's
+    /// label through the target.
+    Binding,
+    /// A comm actor forwards casts to a peer comm actor.
+    Cast,
+}
+
+/// A node of a [`Topology`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopologyNode {
+    /// The kind of the node.
+    pub kind: NodeKind,
+    /// A human-readable label, usually the node's reference.
+    pub label: String,
+}
+
+/// An edge of a [`Topology`], from the node that sends messages to the
+/// node that receives them.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TopologyEdge {
+    /// The id of the sending node.
+    pub from: String,
+    /// The id of the receiving node.
+    pub to: String,
+    /// The kind of the edge.
+    pub kind: EdgeKind,
+    /// The reference routed along the edge, for bindings.
+    pub label: Option<String>,
+}
+
+/// A routing topology; see the [module documentation](self).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Topology {
+    /// The nodes, keyed by id.
+    pub nodes: BTreeMap<String, TopologyNode>,
+    /// The edges between the nodes.
+    pub edges: BTreeSet<TopologyEdge>,
+}
+
+impl Topology {
+    /// An empty topology.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The topology of `gateway`: its attached procs and their actors,
+    /// its served channels and peers, and the routers through which it
+    /// forwards.
+    pub fn of_gateway(gateway: &Gateway) -> Self {
+        let mut walk = Walk::default();
+        walk.gateway(gateway);
+        walk.topology
+    }
+
+    /// Add a node, unless a node with the same id exists.
+    pub fn add_node(&mut self, id: impl Into<String>, kind: NodeKind, label: impl Into<String>) {
+        self.nodes.entry(id.into()).or_insert_with(|| TopologyNode {
+            kind,
+            label: label.into(),
+        });
+    }
+
+    /// Add an edge between the nodes with ids `from` and `to`.
+    pub fn add_edge(
+        &mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+        kind: EdgeKind,
+        label: Option<String>,
+    ) {
+        self.edges.insert(TopologyEdge {
+            from: from.into(),
+            to: to.into(),
+            kind,
+            label,
+        });
+    }
+
+    /// Add the nodes and edges of `other`. Nodes that are in both
+    /// topologies keep their label in this one.
+    pub fn merge(&mut self, other: Topology) {
+        for (id, node) in other.nodes {
+            self.nodes.entry(id).or_insert(node);
+        }
+        self.edges.extend(other.edges);
+    }
+
+    /// The topology as a Graphviz `digraph`.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph topology {\n    rankdir=LR;\n");
+        for (id, node) in &self.nodes {
+            let shape = match node.kind {
+                NodeKind::Gateway => "doubleoctagon",
+                NodeKind::Proc => "box",
+                NodeKind::Actor => "ellipse",
+                NodeKind::Port => "circle",
+                NodeKind::Router | NodeKind::FallbackRouter | NodeKind::DialRouter => "diamond",
+                NodeKind::Channel => "cds",
+                NodeKind::Sender => "box, style=dashed",
+            };
+            let _ = writeln!(
+                dot,
+                "    \"{}\" [label=\"{}\", shape={}];",
+                escape(id),
+                escape(&node.label),
+                shape
+            );
+        }
+        for edge in &self.edges {
+            let mut attrs = Vec::new();
+            if let Some(label) = &edge.label {
+                attrs.push(format!("label=\"{}\"", escape(label)));
+            }
+            match edge.kind {
+                EdgeKind::Forwards => attrs.push("style=dashed".to_string()),
+                EdgeKind::Cast => attrs.push("color=blue".to_string()),
+                _ => {}
+            }
+            let _ = write!(
+                dot,
+                "    \"{}\" -> \"{}\"",
+                escape(&edge.from),
+                escape(&edge.to)
+            );
+            if !attrs.is_empty() {
+                let _ = write!(dot, " [{}]", attrs.join(", "));
+            }
+            dot.push_str(";\n");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Escape `s` for use in a quoted DOT string.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The node id of a gateway.
+pub fn gateway_node(uid: &Uid) -> String {
+    format!("gateway:{}", uid)
+}
+
+/// The node id of the proc, actor or port `addr` refers to.
+pub fn addr_node(addr: &Addr) -> String {
+    match addr {
+        Addr::Proc(proc) => format!("proc:{}", proc.id()),
+        Addr::Actor(actor) => format!("actor:{}", actor.id()),
+        Addr::Port(port) => format!("port:{}", port.id()),
+    }
+}
+
+/// The node id of a channel.
+pub fn channel_node(addr: &ChannelAddr) -> String {
+    format!("channel:{}", addr)
+}
+
+/// Where a walked sender leads.
+enum Target {
+    /// To the node with this id.
+    Node(String),
+    /// Nowhere: messages are returned as undeliverable.
+    Unroutable,
+    /// To a sender that the walk does not know.
+    Opaque,
+}
+
+#[derive(Default)]
+struct Walk {
+    topology: Topology,
+    // The routers that were walked, by the address of their table, so
+    // that a router reachable from itself is walked once.
+    routers: HashSet<usize>,
+}
+
+impl Walk {
+    fn gateway(&mut self, gateway: &Gateway) -> String {
+        let id = gateway_node(gateway.uid());
+        if self.topology.nodes.contains_key(&id) {
+            return id;
+        }
+        self.topology
+            .add_node(&id, NodeKind::Gateway, gateway.uid().to_string());
+
+        for location in gateway.served_locations() {
+            match location {
+                Location::Addr(addr) => {
+                    let channel = channel_node(&addr);
+                    self.topology
+                        .add_node(&channel, NodeKind::Channel, addr.to_string());
+                    self.topology.add_edge(&id, channel, EdgeKind::Serves, None);
+                }
+                // The gateway is served through an upstream gateway.
+                Location::Via(uid, _) => {
+                    let upstream = gateway_node(&uid);
+                    self.topology
+                        .add_node(&upstream, NodeKind::Gateway, uid.to_string());
+                    self.topology.add_edge(upstream, &id, EdgeKind::Peer, None);
+                }
+            }
+        }
+        for uid in gateway.peer_uids() {
+            let peer = gateway_node(&uid);
+            self.topology
+                .add_node(&peer, NodeKind::Gateway, uid.to_string());
+            self.topology.add_edge(&id, peer, EdgeKind::Peer, None);
+        }
+        for proc in gateway.attached_procs() {
+            let proc = self.proc(&proc);
+            self.topology.add_edge(&id, proc, EdgeKind::Attached, None);
+        }
+        let forwarder = gateway.forwarder();
+        self.forward(&id, &format!("{}/forwarder", id), &forwarder);
+        id
+    }
+
+    fn proc(&mut self, proc: &Proc) -> String {
+        let proc_addr = proc.proc_addr();
+        let id = addr_node(&Addr::Proc(proc_addr.clone()));
+        if self.topology.nodes.contains_key(&id) {
+            return id;
+        }
+        self.topology
+            .add_node(&id, NodeKind::Proc, proc_addr.to_string());
+        for entry in proc.muxer().mailboxes.iter() {
+            let actor = format!("actor:{}", entry.key());
+            self.topology
+                .add_node(&actor, NodeKind::Actor, entry.key().to_string());
+            self.topology.add_edge(&id, actor, EdgeKind::Binding, None);
+        }
+        id
+    }
+
+    /// Add a [`EdgeKind::Forwards`] edge from `from` to where `sender`
+    /// leads, walked as the node with id `path` if it is a router.
+    fn forward(&mut self, from: &str, path: &str, sender: &BoxedMailboxSender) {
+        let to = match self.sender(path, &*sender.0) {
+            Target::Node(to) => to,
+            Target::Unroutable => return,
+            Target::Opaque => {
+                self.topology.add_node(path, NodeKind::Sender, "sender");
+                path.to_string()
+            }
+        };
+        self.topology.add_edge(from, to, EdgeKind::Forwards, None);
+    }
+
+    /// Add [`EdgeKind::Binding`] edges from the router `from` to where
+    /// each of `entries` leads. Entries whose sender is not known lead
+    /// to the bound reference itself.
+    fn bindings<'a>(
+        &mut self,
+        from: &str,
+        entries: impl IntoIterator<Item = (&'a Addr, &'a (dyn MailboxSender + Send + Sync))>,
+    ) {
+        for (key, sender) in entries {
+            let to = match self.sender(&format!("{}/{}", from, key), sender) {
+                Target::Node(to) => to,
+                Target::Unroutable => continue,
+                Target::Opaque => {
+                    let to = addr_node(key);
+                    self.topology.add_node(&to, addr_kind(key), key.to_string());
+                    to
+                }
+            };
+            self.topology
+                .add_edge(from, to, EdgeKind::Binding, Some(key.to_string()));
+        }
+    }
+
+    fn sender(&mut self, path: &str, sender: &(dyn MailboxSender + Send + Sync)) -> Target {
+        let sender = sender as &dyn Any;
+        if let Some(boxed) = sender.downcast_ref::<BoxedMailboxSender>() {
+            self.sender(path, &*boxed.0)
+        } else if sender.is::<UnroutableMailboxSender>() {
+            Target::Unroutable
+        } else if let Some(gateway) = sender.downcast_ref::<Gateway>() {
+            Target::Node(self.gateway(gateway))
+        } else if let Some(proc) = sender.downcast_ref::<Proc>() {
+            Target::Node(self.proc(proc))
+        } else if let Some(weak) = sender.downcast_ref::<WeakProc>() {
+            match weak.upgrade() {
+                Some(proc) => Target::Node(self.proc(&proc)),
+                None => Target::Unroutable,
+            }
+        } else if let Some(mailbox) = sender.downcast_ref::<Mailbox>() {
+            let id = addr_node(&Addr::Actor(mailbox.actor_addr().clone()));
+            self.topology
+                .add_node(&id, NodeKind::Actor, mailbox.actor_addr().to_string());
+            Target::Node(id)
+        } else if let Some(client) = sender.downcast_ref::<MailboxClient>() {
+            let id = channel_node(&client.addr);
+            self.topology
+                .add_node(&id, NodeKind::Channel, client.addr.to_string());
+            Target::Node(id)
+        } else if let Some(router) = sender.downcast_ref::<MailboxRouter>() {
+            Target::Node(self.router(path, NodeKind::Router, router))
+        } else if let Some(weak) = sender.downcast_ref::<WeakMailboxRouter>() {
+            match weak.upgrade() {
+                Some(router) => Target::Node(self.router(path, NodeKind::Router, &router)),
+                None => Target::Unroutable,
+            }
+        } else if let Some(fallback) = sender.downcast_ref::<FallbackMailboxRouter>() {
+            let id = self.router(path, NodeKind::FallbackRouter, &fallback.router);
+            self.forward(&id, &format!("{}/default", id), &fallback.default);
+            Target::Node(id)
+        } else if let Some(dial) = sender.downcast_ref::<DialMailboxRouter>() {
+            Target::Node(self.dial_router(path, dial))
+        } else {
+            Target::Opaque
+        }
+    }
+
+    fn router(&mut self, path: &str, kind: NodeKind, router: &MailboxRouter) -> String {
+        let id = path.to_string();
+        // A router's table is shared by its clones.
+        if !self
+            .routers
+            .insert(std::sync::Arc::as_ptr(&router.entries) as usize)
+        {
+            return id;
+        }
+        self.topology.add_node(&id, kind, "router");
+        let entries = router.entries.load();
+        self.bindings(
+            &id,
+            entries
+                .iter()
+                .map(|(key, sender)| (key, &**sender as &(dyn MailboxSender + Send + Sync))),
+        );
+        id
+    }
+
+    fn dial_router(&mut self, path: &str, router: &DialMailboxRouter) -> String {
+        let id = path.to_string();
+        if !self
+            .routers
+            .insert(std::sync::Arc::as_ptr(&router.address_book) as usize)
+        {
+            return id;
+        }
+        self.topology
+            .add_node(&id, NodeKind::DialRouter, "dial router");
+        for (key, addr) in router.address_book.load().iter() {
+            let channel = channel_node(addr);
+            self.topology
+                .add_node(&channel, NodeKind::Channel, addr.to_string());
+            self.topology
+                .add_edge(&id, channel, EdgeKind::Binding, Some(key.to_string()));
+        }
+        self.forward(&id, &format!("{}/default", id), &router.default);
+        id
+    }
+}
+
+/// The node kind of the proc, actor or port `addr` refers to.
+fn addr_kind(addr: &Addr) -> NodeKind {
+    match addr {
+        Addr::Proc(_) => NodeKind::Proc,
+        Addr::Actor(_) => NodeKind::Actor,
+        Addr::Port(_) => NodeKind::Port,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mailbox::IntoBoxedMailboxSender as _;
+    use crate::testing::ids::test_proc_id;
+
+    #[tokio::test]
+    async fn test_gateway_topology() {
+        let addr = ChannelAddr::Tcp("10.0.0.1:1".parse().unwrap());
+        let remote = test_proc_id("remote");
+        let dial = DialMailboxRouter::new();
+        dial.bind(remote.clone(), addr.clone());
+        let gateway = Gateway::configured(
+            crate::channel::reserve_local_addr().into(),
+            dial.into_boxed(),
+        );
+        let proc = Proc::builder()
+            .shared_gateway(gateway.clone())
+            .build()
+            .unwrap();
+        let actor = proc.client("client");
+
+        let topology = Topology::of_gateway(&gateway);
+        let gateway_id = gateway_node(gateway.uid());
+        let proc_id = addr_node(&Addr::Proc(proc.proc_addr()));
+        let actor_id = format!("actor:{}", actor.self_addr().id());
+        let router_id = format!("{}/forwarder", gateway_id);
+        let channel_id = channel_node(&addr);
+        assert_eq!(topology.nodes[&gateway_id].kind, NodeKind::Gateway);
+        assert_eq!(topology.nodes[&proc_id].kind, NodeKind::Proc);
+        assert_eq!(topology.nodes[&router_id].kind, NodeKind::DialRouter);
+        assert_eq!(topology.nodes[&channel_id].kind, NodeKind::Channel);
+        for (from, to, kind, label) in [
+            (&gateway_id, &proc_id, EdgeKind::Attached, None),
+            (&proc_id, &actor_id, EdgeKind::Binding, None),
+            (&gateway_id, &router_id, EdgeKind::Forwards, None),
+            (
+                &router_id,
+                &channel_id,
+                EdgeKind::Binding,
+                Some(remote.to_string()),
+            ),
+        ] {
+            assert!(
+                topology.edges.contains(&TopologyEdge {
+                    from: from.clone(),
+                    to: to.clone(),
+                    kind,
+                    label,
+                }),
+                "missing edge {} -> {}",
+                from,
+                to
+            );
+        }
+
+        // Merging is idempotent, and the topology round-trips as JSON.
+        let mut merged = topology.clone();
+        merged.merge(topology.clone());
+        assert_eq!(merged, topology);
+        let json = serde_json::to_string(&topology).unwrap();
+        assert_eq!(serde_json::from_str::<Topology>(&json).unwrap(), topology);
+
+        let dot = topology.to_dot();
+        assert!(dot.starts_with("digraph topology {"));
+        assert!(dot.contains(&format!("\"{}\" -> \"{}\"", gateway_id, proc_id)));
+    }
+}
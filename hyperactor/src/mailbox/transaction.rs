@@ -0,0 +1,629 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! All-or-nothing delivery of message sets.
+//!
+//! Some coordination messages must not partially apply: a set of
+//! messages to several ports is either enqueued in full, or not at all.
+//!
+//! [`MailboxMuxer::post_all`] (and [`crate::Proc::post_all`]) provide
+//! this for ports of local mailboxes. Delivery proceeds in two steps.
+//! First every message is prepared: it is routed to its port, migrated,
+//! checked and decoded, and the port is reserved (one-shot senders are
+//! taken, and handler ports are held open against a concurrent drain).
+//! If any message fails to prepare, the reservations are released and
+//! nothing is enqueued. Otherwise every prepared message is enqueued.
+//! This can fail only if the receiver of a port is dropped while the
+//! set is being enqueued, which is reported as
+//! [`TransactionError::Incomplete`]. Forwarded ports (see
+//! [`Mailbox::forward_port`]) relay their messages rather than enqueue
+//! them, and ports under an administrative override (see
+//! [`super::MailboxAdminMessage`]) neither enqueue nor hold them, so
+//! messages to either are rejected. As when posted, a message whose
+//! idempotency key ([`super::headers::IDEMPOTENCY_KEY`]) was recently
+//! delivered to its port is dropped as a duplicate when the set is
+//! enqueued.
+//!
+//! [`post_all_two_phase`] extends this, on a best-effort basis, to
+//! mailboxes in other procs. The coordinator posts each message tagged
+//! with a transaction id ([`TXN_ID`]) and its vote port
+//! ([`TXN_VOTE_PORT`]). The destination proc prepares the message,
+//! stages it, and votes. If every message is prepared, the coordinator
+//! tells each participating proc to commit, and the participant posts
+//! its staged messages with [`MailboxMuxer::post_all`]; otherwise, it
+//! tells them to abort, and the staged messages are discarded.
+//! Nothing is reserved between the two phases, so a participant whose
+//! state changes after it votes (for example, because an actor
+//! stopped) fails to commit, which is logged. Staged messages whose
+//! decision is lost are discarded after
+//! [`crate::config::TRANSACTION_TIMEOUT`].
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use hyperactor_config::Flattrs;
+use serde::Deserialize;
+use serde::Serialize;
+use typeuri::Named;
+
+use super::DeliveryFailure;
+use super::InvalidReference;
+use super::InvalidReferenceReason;
+use super::Mailbox;
+use super::MailboxMuxer;
+use super::MailboxSender;
+use super::MessageEnvelope;
+use super::MessageMetadata;
+use super::PortHandle;
+use super::PreparedSend;
+use super::SerializedSendDisposition;
+use super::SerializedSendFailure;
+use super::Undeliverable;
+use super::headers::IDEMPOTENCY_KEY;
+use super::headers::TXN_ID;
+use super::headers::TXN_INDEX;
+use super::headers::TXN_VOTE_PORT;
+use crate::ActorAddr;
+use crate::PortAddr;
+use crate::context;
+
+/// Errors that abort delivery of a message set.
+#[derive(Debug, thiserror::Error)]
+pub enum TransactionError {
+    /// A message could not be prepared for delivery. No message was
+    /// enqueued.
+    #[error("message {index} was rejected: {failure}")]
+    Rejected {
+        /// The position of the message in the set.
+        index: usize,
+        /// Why the message could not be delivered.
+        failure: Box<DeliveryFailure>,
+    },
+
+    /// A prepared message could not be enqueued, because its port's
+    /// receiver was dropped concurrently. The messages before it in the
+    /// set were enqueued.
+    #[error("message {index} failed to enqueue: {failure}")]
+    Incomplete {
+        /// The position of the message in the set.
+        index: usize,
+        /// Why the message could not be delivered.
+        failure: Box<DeliveryFailure>,
+    },
+
+    /// Not every message of a two-phase transaction was prepared within
+    /// [`crate::config::TRANSACTION_TIMEOUT`]. No message was enqueued.
+    #[error("{pending} of {total} messages were not prepared in time")]
+    Timeout {
+        /// The number of messages without a vote.
+        pending: usize,
+        /// The number of messages in the set.
+        total: usize,
+    },
+}
+
+/// A message that is ready to be enqueued on its port.
+struct PreparedDelivery {
+    mailbox: Mailbox,
+    sender: ActorAddr,
+    dest: PortAddr,
+    message_id: u64,
+    idempotency_key: Option<String>,
+    send: Box<dyn PreparedSend>,
+}
+
+impl PreparedDelivery {
+    fn commit(self) -> Result<(), Box<DeliveryFailure>> {
+        let port = self.dest.port();
+        if let Some(key) = &self.idempotency_key
+            && !self.mailbox.admit_key(&port, key, &self.sender, &self.dest)
+        {
+            return Ok(());
+        }
+        match self.send.commit() {
+            Ok(disposition) => {
+                super::notify_queued(self.message_id);
+                if disposition == SerializedSendDisposition::DeliveredAndExhausted {
+                    self.mailbox.inner.remove_port(&port);
+                }
+                Ok(())
+            }
+            Err(failure) => {
+                // The message was not delivered, so a retry must not be
+                // dropped as a duplicate.
+                if let Some(key) = &self.idempotency_key {
+                    self.mailbox.inner.forget_key(&port, key);
+                }
+                if matches!(failure, SerializedSendFailure::Dead { .. }) {
+                    self.mailbox.inner.remove_port(&port);
+                }
+                Err(send_failure(&self.dest, failure))
+            }
+        }
+    }
+}
+
+fn send_failure(dest: &PortAddr, failure: SerializedSendFailure) -> Box<DeliveryFailure> {
+    Box::new(match failure {
+        SerializedSendFailure::Dead { data, .. } => super::port_gone_delivery_failure(dest, &data),
+        SerializedSendFailure::Error(err) => {
+            super::serialized_send_error_delivery_failure(dest, &err.error)
+        }
+    })
+}
+
+impl Mailbox {
+    /// Prepare delivery of an envelope addressed to this mailbox,
+    /// without enqueueing it.
+    fn prepare(&self, envelope: MessageEnvelope) -> Result<PreparedDelivery, Box<DeliveryFailure>> {
+        let port_sender = self.route(&envelope)?;
+        if self.inner.port_admin.contains_key(&envelope.dest().port()) {
+            return Err(Box::new(DeliveryFailure::new(InvalidReference::new(
+                envelope.dest().clone(),
+                InvalidReferenceReason::PortClosed,
+            ))));
+        }
+        let (metadata, data) = envelope.open();
+        let MessageMetadata {
+            mut headers,
+            sender,
+            dest,
+            ..
+        } = metadata;
+        let idempotency_key = headers.get(IDEMPOTENCY_KEY);
+        let message_id = super::stamp_delivery_headers(&mut headers, &sender, &dest, &data);
+        let version = super::migrate::version_of(&headers);
+        match port_sender.prepare_serialized(headers, version, data) {
+            Ok(send) => Ok(PreparedDelivery {
+                mailbox: self.clone(),
+                sender,
+                dest,
+                message_id,
+                idempotency_key,
+                send,
+            }),
+            Err(failure) => Err(send_failure(&dest, failure)),
+        }
+    }
+}
+
+impl MailboxMuxer {
+    fn prepare(&self, envelope: MessageEnvelope) -> Result<PreparedDelivery, Box<DeliveryFailure>> {
+        let mailbox = self
+            .locals
+            .get(envelope.dest().actor_id())
+            .map(|entry| entry.value().clone());
+        match mailbox {
+            Some(mailbox) => mailbox.prepare(envelope),
+            None => Err(Box::new(DeliveryFailure::new(InvalidReference::new(
+                envelope.dest().actor_addr(),
+                InvalidReferenceReason::ActorNotExist,
+            )))),
+        }
+    }
+
+    /// Post every envelope, or none of them. Each envelope must be
+    /// addressed to a mailbox bound with [`MailboxMuxer::bind_mailbox`].
+    /// Unlike [`MailboxSender::post`], failures are returned to the
+    /// caller rather than to the senders of the messages, and the
+    /// messages are dropped.
+    pub fn post_all(&self, envelopes: Vec<MessageEnvelope>) -> Result<(), TransactionError> {
+        let mut prepared = Vec::with_capacity(envelopes.len());
+        for (index, envelope) in envelopes.into_iter().enumerate() {
+            match self.prepare(envelope) {
+                Ok(delivery) => prepared.push(delivery),
+                Err(failure) => return Err(TransactionError::Rejected { index, failure }),
+            }
+        }
+        for (index, delivery) in prepared.into_iter().enumerate() {
+            if let Err(failure) = delivery.commit() {
+                return Err(TransactionError::Incomplete { index, failure });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A participant's vote on one message of a two-phase transaction.
+#[derive(Debug, Serialize, Deserialize, Named)]
+struct TxnVote {
+    id: u64,
+    index: u64,
+    result: Result<(), DeliveryFailure>,
+}
+
+/// The coordinator's decision on a two-phase transaction.
+#[derive(Debug, Serialize, Deserialize, Named)]
+struct TxnDecision {
+    commit: bool,
+}
+
+struct Staged {
+    expires: Instant,
+    envelopes: Vec<MessageEnvelope>,
+}
+
+/// The two-phase transactions staged in a proc, keyed by their
+/// coordinator's vote port and transaction id.
+#[derive(Default)]
+pub(crate) struct Participant {
+    staged: Mutex<HashMap<(PortAddr, u64), Staged>>,
+}
+
+impl Participant {
+    /// Handle an envelope carrying [`TXN_ID`], delivered to a proc with
+    /// the provided muxer. A [`TxnDecision`] resolves its transaction;
+    /// any other message is prepared, staged, and voted on. Votes are
+    /// posted through `sender`.
+    pub(crate) fn handle(
+        &self,
+        muxer: &MailboxMuxer,
+        sender: &impl MailboxSender,
+        envelope: MessageEnvelope,
+        return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+    ) {
+        let headers = envelope.headers();
+        let (Some(id), Some(vote_port)) = (headers.get(TXN_ID), headers.get(TXN_VOTE_PORT)) else {
+            tracing::warn!(
+                "transactional message to {} has no vote port; delivering it directly",
+                envelope.dest()
+            );
+            return muxer.post(envelope, return_handle);
+        };
+
+        if envelope.data().typehash() == TxnDecision::typehash() {
+            let staged = self.staged.lock().unwrap().remove(&(vote_port, id));
+            match (envelope.deserialized::<TxnDecision>(), staged) {
+                (Ok(TxnDecision { commit: true }), Some(staged)) => {
+                    if let Err(err) = muxer.post_all(staged.envelopes) {
+                        tracing::warn!(id, "failed to commit prepared transaction: {}", err);
+                    }
+                }
+                (Ok(_), _) => (),
+                (Err(err), _) => tracing::warn!(id, "invalid transaction decision: {}", err),
+            }
+            return;
+        }
+
+        let index = headers.get(TXN_INDEX).unwrap_or_default();
+        // Only validate the message here: reservations are not held
+        // across the network round trip.
+        let result = muxer
+            .prepare(envelope.clone())
+            .map(|_| ())
+            .map_err(|failure| *failure);
+        let voter = envelope.dest().actor_addr();
+        if result.is_ok() {
+            let now = Instant::now();
+            let expires = now + hyperactor_config::global::get(crate::config::TRANSACTION_TIMEOUT);
+            let mut staged = self.staged.lock().unwrap();
+            staged.retain(|_, staged| staged.expires > now);
+            staged
+                .entry((vote_port.clone(), id))
+                .or_insert_with(|| Staged {
+                    expires,
+                    envelopes: Vec::new(),
+                })
+                .envelopes
+                .push(envelope);
+        }
+        match MessageEnvelope::serialize(
+            voter,
+            vote_port,
+            &TxnVote { id, index, result },
+            Flattrs::new(),
+        ) {
+            Ok(vote) => sender.post(vote, super::monitored_return_handle()),
+            Err(err) => tracing::error!(id, "failed to serialize transaction vote: {}", err),
+        }
+    }
+}
+
+/// Post every envelope, or none of them, to mailboxes in any proc. This
+/// is a best-effort two-phase protocol; see the [module
+/// documentation](self) for its guarantees.
+pub async fn post_all_two_phase(
+    cx: &impl context::Actor,
+    envelopes: Vec<MessageEnvelope>,
+) -> Result<(), TransactionError> {
+    let proc = cx.instance().proc();
+    let id: u64 = rand::random();
+    let (vote_port, mut votes) = cx.mailbox().open_port::<TxnVote>();
+    let vote_port = vote_port.bind().port_addr().clone();
+
+    let mut headers = Flattrs::new();
+    headers.set(TXN_ID, id);
+    headers.set(TXN_VOTE_PORT, vote_port.clone());
+
+    // One destination in each participating proc, to which the decision
+    // is addressed.
+    let mut participants = HashMap::new();
+    let total = envelopes.len();
+    for (index, mut envelope) in envelopes.into_iter().enumerate() {
+        let dest = envelope.dest();
+        participants
+            .entry(dest.actor_addr().proc_addr())
+            .or_insert_with(|| dest.clone());
+        envelope.headers.set(TXN_ID, id);
+        envelope.headers.set(TXN_INDEX, index as u64);
+        envelope.headers.set(TXN_VOTE_PORT, vote_port.clone());
+        proc.post(envelope, super::monitored_return_handle());
+    }
+
+    let deadline = tokio::time::Instant::now()
+        + hyperactor_config::global::get(crate::config::TRANSACTION_TIMEOUT);
+    let mut pending: HashSet<u64> = (0..total as u64).collect();
+    let mut result = Ok(());
+    while !pending.is_empty() {
+        let vote = match tokio::time::timeout_at(deadline, votes.recv()).await {
+            Ok(Ok(vote)) if vote.id == id => vote,
+            Ok(Ok(_)) => continue,
+            Ok(Err(_)) | Err(_) => {
+                result = Err(TransactionError::Timeout {
+                    pending: pending.len(),
+                    total,
+                });
+                break;
+            }
+        };
+        pending.remove(&vote.index);
+        if let Err(failure) = vote.result {
+            result = Err(TransactionError::Rejected {
+                index: vote.index as usize,
+                failure: Box::new(failure),
+            });
+            break;
+        }
+    }
+
+    let decision = TxnDecision {
+        commit: result.is_ok(),
+    };
+    for dest in participants.into_values() {
+        match MessageEnvelope::serialize(
+            cx.mailbox().actor_addr().clone(),
+            dest,
+            &decision,
+            headers.clone(),
+        ) {
+            Ok(envelope) => proc.post(envelope, super::monitored_return_handle()),
+            Err(err) => tracing::error!(id, "failed to serialize transaction decision: {}", err),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::Proc;
+    use crate::context::Mailbox as _;
+    use crate::mailbox::DeliveryFailureKind;
+
+    fn envelope<T: Serialize + Named>(
+        sender: &Mailbox,
+        dest: &PortAddr,
+        value: &T,
+    ) -> MessageEnvelope {
+        MessageEnvelope::serialize(
+            sender.actor_addr().clone(),
+            dest.clone(),
+            value,
+            Flattrs::new(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_post_all() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let mbox = client.mailbox();
+        let (counts, mut counts_rx) = mbox.open_port::<u64>();
+        let counts = counts.bind().port_addr().clone();
+        let (names, mut names_rx) = mbox.open_port::<String>();
+        let names = names.bind().port_addr().clone();
+        let (once, once_rx) = mbox.open_once_port::<u64>();
+        let once = once.bind().port_addr().clone();
+
+        // The last message does not decode as a String, so none is delivered.
+        let err = proc
+            .post_all(vec![
+                envelope(mbox, &counts, &1u64),
+                envelope(mbox, &once, &2u64),
+                envelope(mbox, &names, &3u64),
+            ])
+            .unwrap_err();
+        assert!(matches!(err, TransactionError::Rejected { index: 2, .. }));
+        assert_eq!(counts_rx.try_recv().unwrap(), None);
+
+        // The reservation of the one-shot port was released.
+        proc.post_all(vec![
+            envelope(mbox, &counts, &1u64),
+            envelope(mbox, &once, &2u64),
+            envelope(mbox, &names, &"three".to_string()),
+        ])
+        .unwrap();
+        assert_eq!(counts_rx.recv().await.unwrap(), 1);
+        assert_eq!(once_rx.recv().await.unwrap(), 2);
+        assert_eq!(names_rx.recv().await.unwrap(), "three");
+
+        // The one-shot port was used.
+        let err = proc
+            .post_all(vec![envelope(mbox, &once, &4u64)])
+            .unwrap_err();
+        assert!(matches!(err, TransactionError::Rejected { index: 0, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_post_all_rejects_unpreparable_ports() {
+        use std::sync::atomic::AtomicUsize;
+        use std::sync::atomic::Ordering;
+
+        use crate::Port;
+        use crate::mailbox::UntypedUnboundedSender;
+
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let mbox = client.mailbox();
+        let (counts, mut counts_rx) = mbox.open_port::<u64>();
+        let counts = counts.bind();
+
+        // Untyped ports migrate payloads when they are prepared.
+        let calls = Arc::new(AtomicUsize::new(0));
+        let untyped = mbox.actor_addr().port_addr(Port::from(2000));
+        mbox.bind_untyped(
+            &untyped,
+            UntypedUnboundedSender {
+                port_id: untyped.clone(),
+                sender: Box::new({
+                    let calls = Arc::clone(&calls);
+                    move |_, _| {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        Ok(SerializedSendDisposition::Delivered)
+                    }
+                }),
+            },
+        );
+        // No migration of u64 from version 1 is registered.
+        let err = proc
+            .post_all(vec![
+                envelope(mbox, counts.port_addr(), &1u64),
+                envelope(mbox, &untyped, &2u64).with_version(1),
+            ])
+            .unwrap_err();
+        assert!(matches!(err, TransactionError::Rejected { index: 1, .. }));
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+
+        // Forwarded ports only relay their messages.
+        let forwarded = mbox
+            .forward_port(1000, counts.clone(), proc.clone())
+            .unwrap();
+        let err = proc
+            .post_all(vec![
+                envelope(mbox, counts.port_addr(), &3u64),
+                envelope(mbox, forwarded.port_addr(), &4u64),
+            ])
+            .unwrap_err();
+        let TransactionError::Rejected { index: 1, failure } = err else {
+            panic!("expected the forwarded message to be rejected: {}", err);
+        };
+        let DeliveryFailureKind::InvalidReference(invalid) = &failure.kind else {
+            panic!("expected invalid reference, got {}", failure);
+        };
+        assert_eq!(invalid.reason, InvalidReferenceReason::PortForwarded);
+        assert_eq!(counts_rx.try_recv().unwrap(), None);
+
+        proc.post_all(vec![
+            envelope(mbox, counts.port_addr(), &5u64),
+            envelope(mbox, &untyped, &6u64),
+        ])
+        .unwrap();
+        assert_eq!(counts_rx.recv().await.unwrap(), 5);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_post_all_checks_ports_as_posted() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let mbox = client.mailbox();
+        let (counts, mut counts_rx) = mbox.open_port::<u64>();
+        let counts = counts.bind().port_addr().clone();
+        let (names, mut names_rx) = mbox.open_port::<String>();
+        let names = names.bind().port_addr().clone();
+        let keyed = |mut envelope: MessageEnvelope, key: &str| {
+            envelope.set_header(IDEMPOTENCY_KEY, key.to_string());
+            envelope
+        };
+
+        // Ports under an administrative override reject messages,
+        // whether they are closed or draining.
+        mbox.close_port(names.port());
+        let err = proc
+            .post_all(vec![
+                envelope(mbox, &counts, &1u64),
+                envelope(mbox, &names, &"one".to_string()),
+            ])
+            .unwrap_err();
+        let TransactionError::Rejected { index: 1, failure } = err else {
+            panic!(
+                "expected the message to the closed port to be rejected: {}",
+                err
+            );
+        };
+        let DeliveryFailureKind::InvalidReference(invalid) = &failure.kind else {
+            panic!("expected invalid reference, got {}", failure);
+        };
+        assert_eq!(invalid.reason, InvalidReferenceReason::PortClosed);
+        mbox.drain_port(names.port());
+        let err = proc
+            .post_all(vec![
+                envelope(mbox, &counts, &2u64),
+                envelope(mbox, &names, &"two".to_string()),
+            ])
+            .unwrap_err();
+        assert!(matches!(err, TransactionError::Rejected { index: 1, .. }));
+        mbox.reset_port(names.port());
+        assert_eq!(counts_rx.try_recv().unwrap(), None);
+        assert_eq!(names_rx.try_recv().unwrap(), None);
+
+        // Duplicates of delivered messages are dropped.
+        proc.post_all(vec![keyed(envelope(mbox, &counts, &3u64), "a")])
+            .unwrap();
+        proc.post_all(vec![
+            keyed(envelope(mbox, &counts, &4u64), "a"),
+            keyed(envelope(mbox, &names, &"five".to_string()), "b"),
+        ])
+        .unwrap();
+        assert_eq!(counts_rx.recv().await.unwrap(), 3);
+        assert_eq!(names_rx.recv().await.unwrap(), "five");
+        assert_eq!(counts_rx.try_recv().unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_post_all_two_phase() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let peer = proc.client("peer");
+        let (counts, mut counts_rx) = peer.open_port::<u64>();
+        let counts = counts.bind().port_addr().clone();
+        let (names, mut names_rx) = peer.open_port::<String>();
+        let names = names.bind().port_addr().clone();
+        let mbox = client.mailbox();
+
+        let err = post_all_two_phase(
+            &client,
+            vec![
+                envelope(mbox, &counts, &1u64),
+                envelope(mbox, &names, &2u64),
+            ],
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(err, TransactionError::Rejected { index: 1, .. }));
+
+        post_all_two_phase(
+            &client,
+            vec![
+                envelope(mbox, &counts, &3u64),
+                envelope(mbox, &names, &"four".to_string()),
+            ],
+        )
+        .await
+        .unwrap();
+        // The aborted transaction's messages were discarded.
+        assert_eq!(counts_rx.recv().await.unwrap(), 3);
+        assert_eq!(names_rx.recv().await.unwrap(), "four");
+        assert_eq!(counts_rx.try_recv().unwrap(), None);
+    }
+}
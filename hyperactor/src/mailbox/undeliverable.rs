@@ -47,7 +47,13 @@
 //!   rendering the literal `"unknown"`. `"unknown"` is reserved for
 //!   envelopes lacking both.
 
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::sync::Mutex;
 use std::sync::OnceLock;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use enum_as_inner::EnumAsInner;
 use serde::Deserialize;
@@ -61,6 +67,7 @@ use crate::EndpointLocation;
 // for macros
 use crate::Message;
 use crate::Proc;
+use crate::config;
 use crate::mailbox::DeliveryFailure;
 use crate::mailbox::MailboxSender;
 use crate::mailbox::MailboxSenderError;
@@ -74,6 +81,7 @@ use crate::mailbox::UndeliverableReason;
 use crate::mailbox::headers::OPERATION_ADVERB;
 use crate::mailbox::headers::OPERATION_ENDPOINT;
 use crate::mailbox::headers::RUST_MESSAGE_TYPE;
+use crate::metrics;
 
 /// Metadata for a delivery failure whose original payload is unavailable.
 #[derive(Debug, Serialize, Deserialize, Clone, typeuri::Named)]
@@ -88,6 +96,11 @@ pub struct DeliveryFailureReport {
     /// entries are failures encountered while returning or forwarding the
     /// failed message.
     pub delivery_failures: Vec<DeliveryFailure>,
+    /// The number of further undeliverable messages from `sender` to
+    /// the same destination that this report summarizes. Their
+    /// envelopes were not returned; see [`return_undeliverable`].
+    #[serde(default)]
+    pub repeats: u64,
 }
 
 impl DeliveryFailureReport {
@@ -103,6 +116,7 @@ impl DeliveryFailureReport {
             dest,
             message_type,
             delivery_failures: vec![failure],
+            repeats: 0,
         }
     }
 
@@ -134,6 +148,7 @@ impl DeliveryFailureReport {
             dest,
             message_type: Some(std::any::type_name::<M>().to_string()),
             delivery_failures: vec![failure],
+            repeats: 0,
         }
     }
 
@@ -160,6 +175,13 @@ impl DeliveryFailureReport {
         self.delivery_failures.first()
     }
 
+    /// The error code of the root delivery failure, if any.
+    pub fn error_code(&self) -> Option<crate::mailbox::error_code::ErrorCode> {
+        use crate::mailbox::error_code::HasErrorCode as _;
+        self.root_delivery_failure()
+            .map(|failure| failure.error_code())
+    }
+
     /// Get the string representation of the errors in this report.
     pub fn error_msg(&self) -> Option<String> {
         if self.delivery_failures.is_empty() {
@@ -304,6 +326,7 @@ pub fn monitored_return_handle() -> PortHandle<Undeliverable<MessageEnvelope>> {
                             dest = %report.dest,
                             message_type = report.message_type.as_deref().unwrap_or("unknown"),
                             error = %report.error_msg().unwrap_or_default(),
+                            repeats = report.repeats,
                             "undeliverable message report returned to undeliverable port"
                         );
                     }
@@ -343,6 +366,7 @@ pub fn custom_monitored_return_handle(caller: &str) -> PortHandle<Undeliverable<
                         dest = %report.dest,
                         message_type = report.message_type.as_deref().unwrap_or("unknown"),
                         error = %report.error_msg().unwrap_or_default(),
+                        repeats = report.repeats,
                         "{caller} took back an undeliverable message report"
                     );
                 }
@@ -352,18 +376,35 @@ pub fn custom_monitored_return_handle(caller: &str) -> PortHandle<Undeliverable<
     return_handle
 }
 
+// A global client for returning undeliverable messages.
+fn return_client() -> &'static Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| Proc::global().client("global_return_client"))
+}
+
 /// Returns a message envelope to its original sender.
+///
+/// A burst of messages to a dead destination would otherwise return
+/// one envelope per message. Returns are instead aggregated per
+/// (sender, destination actor) over a window of
+/// [`config::UNDELIVERABLE_AGGREGATION_WINDOW`]: the first
+/// [`config::UNDELIVERABLE_AGGREGATION_MAX_RETURNED`] envelopes of a
+/// window are returned in full, and the rest are summarized in a
+/// single [`Undeliverable::Report`], posted when the window closes,
+/// whose `repeats` counts them.
 pub(crate) fn return_undeliverable(
     return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
     envelope: MessageEnvelope,
 ) {
     if envelope.return_undeliverable() {
-        // A global client for returning undeliverable messages.
-        static CLIENT: OnceLock<Client> = OnceLock::new();
-        let client = CLIENT.get_or_init(|| Proc::global().client("global_return_client"));
+        let window = hyperactor_config::global::get(config::UNDELIVERABLE_AGGREGATION_WINDOW);
+        if !window.is_zero() && !ReturnAggregator::global().admit(&return_handle, &envelope, window)
+        {
+            return;
+        }
         let envelope_copy = envelope.clone();
         if return_handle
-            .try_post(client, Undeliverable::message(envelope))
+            .try_post(return_client(), Undeliverable::message(envelope))
             .is_err()
         {
             UndeliverableMailboxSender.post(envelope_copy, /*unused*/ return_handle)
@@ -371,6 +412,131 @@ pub(crate) fn return_undeliverable(
     }
 }
 
+/// Tracks the undeliverable messages returned per (sender,
+/// destination actor) in the current aggregation window; see
+/// [`return_undeliverable`].
+struct ReturnAggregator {
+    windows: Mutex<HashMap<(ActorAddr, ActorAddr), ReturnWindow>>,
+    next_window_id: AtomicU64,
+}
+
+/// An open aggregation window.
+struct ReturnWindow {
+    /// Distinguishes this window from later windows for the same key,
+    /// so that its expiry does not close them.
+    id: u64,
+    /// The number of envelopes returned in full.
+    returned: usize,
+    /// The envelopes that were not returned, if any.
+    suppressed: Option<SuppressedReturns>,
+}
+
+/// The undeliverable messages suppressed in a window, to be summarized
+/// when it closes.
+struct SuppressedReturns {
+    /// A report describing the most recently suppressed message.
+    report: DeliveryFailureReport,
+    /// Where to post the report.
+    return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+}
+
+impl ReturnAggregator {
+    fn global() -> &'static Self {
+        static AGGREGATOR: OnceLock<ReturnAggregator> = OnceLock::new();
+        AGGREGATOR.get_or_init(|| Self {
+            windows: Mutex::new(HashMap::new()),
+            next_window_id: AtomicU64::new(0),
+        })
+    }
+
+    /// Account for `envelope` in its window, opening one if necessary.
+    /// Returns whether the envelope should be returned in full.
+    fn admit(
+        &'static self,
+        return_handle: &PortHandle<Undeliverable<MessageEnvelope>>,
+        envelope: &MessageEnvelope,
+        window: Duration,
+    ) -> bool {
+        let max_returned =
+            hyperactor_config::global::get(config::UNDELIVERABLE_AGGREGATION_MAX_RETURNED);
+        let key = (envelope.sender().clone(), envelope.dest().actor_addr());
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let open = match windows.entry(key) {
+            Entry::Vacant(entry) => {
+                let id = self.next_window_id.fetch_add(1, Ordering::Relaxed);
+                let key = entry.key().clone();
+                crate::init::get_runtime().spawn(async move {
+                    tokio::time::sleep(window).await;
+                    self.close(&key, id);
+                });
+                entry.insert(ReturnWindow {
+                    id,
+                    returned: 0,
+                    suppressed: None,
+                })
+            }
+            Entry::Occupied(entry) => entry.into_mut(),
+        };
+        if open.returned < max_returned {
+            open.returned += 1;
+            return true;
+        }
+
+        let repeats = open
+            .suppressed
+            .as_ref()
+            .map_or(0, |suppressed| suppressed.report.repeats);
+        let message_type = envelope
+            .data()
+            .typename()
+            .map(|s| s.to_string())
+            .or_else(|| envelope.headers().get(RUST_MESSAGE_TYPE));
+        open.suppressed = Some(SuppressedReturns {
+            report: DeliveryFailureReport {
+                sender: envelope.sender().clone(),
+                dest: EndpointLocation::Port(envelope.dest().clone()),
+                message_type,
+                delivery_failures: envelope.delivery_failures().to_vec(),
+                repeats: repeats + 1,
+            },
+            return_handle: return_handle.clone(),
+        });
+        metrics::MAILBOX_UNDELIVERABLE_MESSAGES_AGGREGATED.add(
+            1,
+            hyperactor_telemetry::kv_pairs!(
+                "sender_actor_id" => envelope.sender().to_string(),
+                "dest_actor_id" => envelope.dest().to_string(),
+            ),
+        );
+        false
+    }
+
+    /// Close window `id` of `key`, posting the summary of any
+    /// suppressed returns.
+    fn close(&self, key: &(ActorAddr, ActorAddr), id: u64) {
+        let closed = {
+            let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+            match windows.get(key) {
+                Some(open) if open.id == id => windows.remove(key),
+                _ => None,
+            }
+        };
+        let Some(SuppressedReturns {
+            report,
+            return_handle,
+        }) = closed.and_then(|closed| closed.suppressed)
+        else {
+            return;
+        };
+        if let Err(err) = return_handle.try_post(return_client(), Undeliverable::report(report)) {
+            tracing::error!(
+                error = %err,
+                "failed to return an undeliverable message report to its sender"
+            );
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 /// Errors that occur during message delivery and return.
 pub enum UndeliverableMessageError {
@@ -472,6 +638,9 @@ impl std::fmt::Display for UndeliverableMessageError {
                     "\terror: {}",
                     report.error_msg().unwrap_or("<none>".to_string())
                 )?;
+                if report.repeats > 0 {
+                    writeln!(f, "\trepeats: {}", report.repeats)?;
+                }
                 return Ok(());
             }
         };
@@ -504,6 +673,7 @@ mod tests {
     use hyperactor_config::Flattrs;
 
     use super::*;
+    use crate::PortAddr;
     use crate::mailbox::InvalidReference;
     use crate::mailbox::InvalidReferenceReason;
     use crate::mailbox::MessageEnvelope;
@@ -762,4 +932,69 @@ mod tests {
             "UE-5: with no typename and no RUST_MESSAGE_TYPE, must render \"unknown\", got:\n{rendered}"
         );
     }
+
+    #[tokio::test]
+    async fn test_return_aggregation() {
+        let config = hyperactor_config::global::lock();
+        let _window = config.override_key(
+            config::UNDELIVERABLE_AGGREGATION_WINDOW,
+            Duration::from_millis(200),
+        );
+        let _max_returned = config.override_key(config::UNDELIVERABLE_AGGREGATION_MAX_RETURNED, 2);
+
+        let (return_handle, mut rx) = new_undeliverable_port();
+        let sender = test_actor_id("ue_proc", "ue_aggregation_sender");
+        let dest = test_port_id("ue_dest_proc", "ue_aggregation_dest", 42);
+        let other_dest = test_port_id("ue_dest_proc", "ue_aggregation_other_dest", 42);
+        let undeliverable = |dest: &PortAddr, payload: &str| {
+            let data = wirevalue::Any::serialize(&payload.to_string()).unwrap();
+            let mut envelope =
+                MessageEnvelope::new(sender.clone(), dest.clone(), data, Flattrs::new());
+            envelope.push_delivery_failure(DeliveryFailure::new(UndeliverableReason::Transport(
+                TransportFailure::new(
+                    dest.clone(),
+                    TransportFailureReason::LinkUnavailable(payload.to_string()),
+                ),
+            )));
+            envelope
+        };
+
+        for i in 0..5 {
+            return_undeliverable(
+                return_handle.clone(),
+                undeliverable(&dest, &format!("m{i}")),
+            );
+        }
+        // Other destinations are aggregated separately.
+        return_undeliverable(return_handle.clone(), undeliverable(&other_dest, "other"));
+
+        let mut returned = Vec::new();
+        for _ in 0..3 {
+            match rx.recv().await.unwrap() {
+                Undeliverable::Returned(envelope) => returned.push(envelope.dest().clone()),
+                Undeliverable::Report(report) => panic!("unexpected report: {report:?}"),
+            }
+        }
+        assert_eq!(returned, vec![dest.clone(), dest.clone(), other_dest]);
+
+        // The suppressed envelopes are summarized once the window closes.
+        let report = tokio::time::timeout(Duration::from_secs(10), rx.recv())
+            .await
+            .unwrap()
+            .unwrap()
+            .into_report()
+            .unwrap();
+        assert_eq!(report.sender, sender);
+        assert_eq!(report.dest, EndpointLocation::Port(dest.clone()));
+        assert_eq!(report.repeats, 3);
+        // The report describes the most recently suppressed envelope.
+        assert!(report.error_msg().unwrap().contains("m4"));
+        assert!(
+            format!("{}", UndeliverableMessageError::Report { report }).contains("\trepeats: 3\n")
+        );
+
+        // A new window returns envelopes in full again.
+        return_undeliverable(return_handle.clone(), undeliverable(&dest, "m5"));
+        assert!(rx.recv().await.unwrap().is_returned());
+    }
 }
@@ -46,6 +46,9 @@ use crate::RemoteMessage;
 use crate::actor::Referable;
 use crate::context;
 use crate::endpoint::Endpoint as _;
+use crate::mailbox::migrate;
+use crate::mailbox::migrate::MessageVersion;
+use crate::mailbox::migrate::MigrateError;
 
 /// An object `T` that is [`Unbind`] can extract a set of parameters from itself,
 /// and store in [`Bindings`]. The extracted parameters in [`Bindings`] can be
@@ -193,6 +196,16 @@ impl ErasedUnbound {
         &self.message
     }
 
+    /// Migrate the inner message, encoded by a binary at wire `version`
+    /// of its type, to the local version; see [`crate::mailbox::migrate`].
+    pub fn migrate(&mut self, version: MessageVersion) -> Result<(), MigrateError> {
+        if version != migrate::local_version(self.message.typehash()) {
+            self.message =
+                migrate::to_local(version, self.message.clone()).map_err(|(_, err)| err)?;
+        }
+        Ok(())
+    }
+
     /// Create an object from a typed message.
     // Note: cannot implement TryFrom<T> due to conflict with core crate's blanket impl.
     // More can be found in this issue: https://github.com/rust-lang/rust/issues/50133
@@ -240,6 +253,14 @@ impl<M: DeserializeOwned + Named> IndexedErasedUnbound<M> {
     }
 }
 
+impl<M: DeserializeOwned + Named + Bind> IndexedErasedUnbound<M> {
+    /// Recover the message as it was before it was erased, such as
+    /// from a cast that was returned as undeliverable.
+    pub fn bound(self) -> anyhow::Result<M> {
+        self.downcast()?.bind()
+    }
+}
+
 impl<M: Bind> IndexedErasedUnbound<M> {
     /// Used in unit tests to bind CastBlobT<M> to the given actor. Do not use in
     /// production.
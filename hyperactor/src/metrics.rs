@@ -10,6 +10,7 @@
 //!
 //! This module contains metrics definitions for various components of hyperactor.
 
+use hyperactor_telemetry::declare_observable_gauge;
 use hyperactor_telemetry::declare_static_counter;
 use hyperactor_telemetry::declare_static_histogram;
 use hyperactor_telemetry::declare_static_timer;
@@ -46,8 +47,37 @@ declare_static_counter!(
     MAILBOX_UNDELIVERABLE_MESSAGES,
     "mailbox.undeliverable_messages"
 );
+// Tracks undeliverable messages that were not returned to their senders, but summarized in an aggregated report
+declare_static_counter!(
+    MAILBOX_UNDELIVERABLE_MESSAGES_AGGREGATED,
+    "mailbox.undeliverable_messages_aggregated"
+);
 // Tracks the number of messages that were posted.
 hyperactor_telemetry::declare_static_counter!(MAILBOX_POSTS, "mailbox.posts");
+// Tracks the number of messages dropped as duplicates of a recently delivered idempotency key.
+declare_static_counter!(MAILBOX_DUPLICATES_DROPPED, "mailbox.duplicates_dropped");
+// Tracks the number of delivered messages recorded by the message sampler.
+declare_static_counter!(MAILBOX_MESSAGES_SAMPLED, "mailbox.messages_sampled");
+// Tracks the number of times a mailbox client exhausted its flow-control window.
+declare_static_counter!(
+    MAILBOX_CLIENT_WINDOW_EXHAUSTED,
+    "mailbox.client_window_exhausted"
+);
+// Tracks the number of messages held back by a channel class's traffic-shaping ceilings.
+declare_static_counter!(CHANNEL_SHAPING_DELAYED, "channel.shaping_delayed");
+// Tracks the number of times a mailbox server stopped receiving because its downstream backlog was full.
+declare_static_counter!(
+    MAILBOX_SERVER_WINDOW_EXHAUSTED,
+    "mailbox.server_window_exhausted"
+);
+// Tracks the number of messages dropped by deadline ports because their deadline passed.
+declare_static_counter!(MAILBOX_DEADLINE_EXPIRED, "mailbox.deadline_expired");
+// Tracks the number of message bytes dialed to routes in another datacenter.
+declare_static_counter!(MAILBOX_CROSS_DC_BYTES, "mailbox.cross_dc_bytes");
+// Tracks the number of messages copied to shadow actors by mirroring senders.
+declare_static_counter!(MAILBOX_MIRRORED, "mailbox.mirrored");
+// Tracks the number of mirrored requests whose primary and shadow responses differed.
+declare_static_counter!(MAILBOX_MIRROR_MISMATCHES, "mailbox.mirror_mismatches");
 
 // ACTOR
 // Tracks the current size of the message queue for actors (increases when messages are queued, decreases when processed)
@@ -64,6 +94,9 @@ declare_static_timer!(
     "actor.message_handler_duration",
     hyperactor_telemetry::TimeUnit::Nanos
 );
+// Cumulative CPU time of message handlers, in microseconds, if measured;
+// see proc::timing
+declare_static_counter!(ACTOR_HANDLER_CPU_US, "actor.handler_cpu.us");
 
 // CHANNEL
 declare_static_histogram!(REMOTE_MESSAGE_SEND_SIZE, "channel.remote_message_send_size");
@@ -85,6 +118,26 @@ declare_static_counter!(CHANNEL_THROUGHPUT_BYTES, "channel.throughput.bytes");
 declare_static_counter!(CHANNEL_THROUGHPUT_MESSAGES, "channel.throughput.messages");
 // Tracks message latency for each channel pair in microseconds
 declare_static_histogram!(CHANNEL_LATENCY_MICROS, "channel.latency.us");
+// Tracks the number of frames coalesced into each channel write
+declare_static_histogram!(CHANNEL_COALESCED_FRAMES, "channel.coalesced_frames");
+
+// BUFFER POOL
+// The fraction of serialized message buffers taken from the pool that were reused
+declare_observable_gauge!(
+    BUFFER_POOL_REUSE_RATE,
+    "buffer_pool.reuse_rate",
+    |observer| {
+        observer.observe(crate::channel::pool::global().stats().reuse_rate(), &[]);
+    }
+);
+// Tracks the number of serialized message buffers reused from the pool
+declare_observable_gauge!(BUFFER_POOL_REUSED, "buffer_pool.reused", |observer| {
+    observer.observe(crate::channel::pool::global().stats().reused as f64, &[]);
+});
+// Tracks the number of serialized message buffers the pool had to allocate
+declare_observable_gauge!(BUFFER_POOL_ALLOCATED, "buffer_pool.allocated", |observer| {
+    observer.observe(crate::channel::pool::global().stats().allocated as f64, &[]);
+});
 
 // PROC MESH
 // Tracks the number of active processes in the process mesh
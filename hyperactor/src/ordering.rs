@@ -16,6 +16,7 @@ use std::sync::Arc;
 use std::sync::Mutex;
 
 use hyperactor_config::AttrValue;
+use hyperactor_config::attrs::COMPACT_KEY;
 use hyperactor_config::attrs::declare_attrs;
 use serde::Deserialize;
 use serde::Serialize;
@@ -33,6 +34,7 @@ use crate::PortAddr;
 /// (i.e. one that must not be wired through `Ports::get`).
 pub(crate) fn is_bypass_workq_type_id(id: TypeId) -> bool {
     id == TypeId::of::<crate::introspect::IntrospectMessage>()
+        || id == TypeId::of::<crate::cancel::Cancel>()
 }
 
 /// Per-session receiver-local ordering snapshot. This does not lock the
@@ -190,6 +192,7 @@ impl std::str::FromStr for SeqInfo {
 declare_attrs! {
     /// The sender of this message, the session ID, and the message's sequence
     /// number assigned by this session.
+    @meta(COMPACT_KEY = 12)
     pub attr SEQ_INFO: SeqInfo;
 }
 
@@ -218,6 +221,12 @@ impl Sequencer {
     /// - Ephemeral ports: get individual sequence schemes (keyed by PortAddr).
     /// - Control ports: bypass receive-side reordering and use [`SeqInfo::Direct`].
     pub fn assign_seq(&self, port_id: &PortAddr) -> SeqInfo {
+        self.assign_seqs(port_id, 1)
+    }
+
+    /// Assign `n` consecutive seqs for a port, as [`Sequencer::assign_seq`]
+    /// would over `n` calls, and return the first of them.
+    pub fn assign_seqs(&self, port_id: &PortAddr, n: u64) -> SeqInfo {
         if port_id.port().is_control() {
             return SeqInfo::Direct;
         }
@@ -230,10 +239,11 @@ impl Sequencer {
 
         let mut guard = self.last_seqs.lock().unwrap();
         let entry = guard.entry(key).or_default();
-        *entry += 1;
+        let seq = *entry + 1;
+        *entry += n;
         SeqInfo::Session {
             session_id: self.session_id,
-            seq: *entry,
+            seq,
         }
     }
 
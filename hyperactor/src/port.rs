@@ -59,6 +59,10 @@ pub enum ControlPort {
     Introspect,
     /// Actor lifecycle signals.
     Signal,
+    /// Cancellation of operations; see [`crate::cancel`].
+    Cancel,
+    /// Message inspection and stepping; see [`crate::debugger`].
+    Debug,
 }
 
 /// Errors that can occur when parsing a [`ControlPort`].
@@ -118,6 +122,8 @@ impl Port {
             }),
             Self::Control(ControlPort::Introspect) => 0,
             Self::Control(ControlPort::Signal) => 1,
+            Self::Control(ControlPort::Cancel) => 2,
+            Self::Control(ControlPort::Debug) => 3,
         }
     }
 }
@@ -170,6 +176,8 @@ impl fmt::Display for ControlPort {
         match self {
             Self::Introspect => f.write_str("introspect"),
             Self::Signal => f.write_str("signal"),
+            Self::Cancel => f.write_str("cancel"),
+            Self::Debug => f.write_str("debug"),
         }
     }
 }
@@ -181,6 +189,8 @@ impl FromStr for ControlPort {
         match s {
             "introspect" => Ok(Self::Introspect),
             "signal" => Ok(Self::Signal),
+            "cancel" => Ok(Self::Cancel),
+            "debug" => Ok(Self::Debug),
             _ => Err(ControlPortParseError::Unknown(s.to_string())),
         }
     }
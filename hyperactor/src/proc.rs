@@ -96,6 +96,7 @@ use std::any::Any;
 use std::any::TypeId;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::fmt;
 use std::future::Future;
 use std::ops::Deref;
@@ -159,6 +160,8 @@ use crate::actor::ActorErrorKind;
 use crate::actor::ActorHandle;
 use crate::actor::ActorStatus;
 use crate::actor::AnyActorHandle;
+use crate::actor::Batch;
+use crate::actor::BatchHandler;
 use crate::actor::Binds;
 use crate::actor::HandlerInfo;
 use crate::actor::Referable;
@@ -166,14 +169,23 @@ use crate::actor::RemoteHandles;
 use crate::actor::Signal;
 use crate::actor::StopMode;
 use crate::actor_local::ActorLocalStorage;
+use crate::cancel;
+use crate::cancel::Cancel;
+use crate::cancel::CancelToken;
+use crate::cancel::Cancellations;
 use crate::channel;
 use crate::channel::ChannelAddr;
 use crate::channel::ChannelError;
+use crate::checkpoint::CheckpointError;
+use crate::checkpoint::PendingMessage;
 use crate::client::Client;
 use crate::client::ClientActor;
 use crate::config;
 use crate::context;
 use crate::context::Mailbox as _;
+use crate::debugger::DebugMessage;
+use crate::debugger::DebugReply;
+use crate::debugger::QueuedEnvelope;
 use crate::endpoint::Endpoint as _;
 use crate::gateway::Gateway;
 use crate::id::ActorId;
@@ -197,10 +209,13 @@ use crate::mailbox::TransportFailure;
 use crate::mailbox::TransportFailureReason;
 use crate::mailbox::Undeliverable;
 use crate::mailbox::UndeliverableReason;
+use crate::metrics::ACTOR_HANDLER_CPU_US;
 use crate::metrics::ACTOR_MESSAGE_HANDLER_DURATION;
 use crate::metrics::ACTOR_MESSAGE_QUEUE_SIZE;
 use crate::metrics::ACTOR_MESSAGES_RECEIVED;
 use crate::port::Port;
+use crate::proc::parallel::ParallelHandler;
+use crate::replay::RecorderSlot;
 use crate::subject::AsSubject as _;
 
 tokio::task_local! {
@@ -251,6 +266,14 @@ pub(crate) struct ProcQueueStats {
     /// Clock function for timestamps. Defaults to `wall_clock_epoch_ms`.
     /// Tests can override via `with_clock` for deterministic behavior.
     clock: fn() -> u64,
+    /// The per-actor queue depth below which mailbox servers release
+    /// the messages they hold back (see
+    /// [`crate::config::MAILBOX_SERVER_WINDOW`]), or 0 if they hold
+    /// none back.
+    drain_threshold: u64,
+    /// Notified when an actor's queue depth falls below
+    /// `drain_threshold`.
+    drained: Arc<tokio::sync::Notify>,
 }
 
 impl ProcQueueStats {
@@ -260,6 +283,9 @@ impl ProcQueueStats {
             high_water_mark: AtomicU64::new(0),
             last_nonzero_epoch_ms: AtomicU64::new(0),
             clock: wall_clock_epoch_ms,
+            drain_threshold: hyperactor_config::global::get(crate::config::MAILBOX_SERVER_WINDOW)
+                as u64,
+            drained: Arc::new(tokio::sync::Notify::new()),
         }
     }
 
@@ -271,6 +297,8 @@ impl ProcQueueStats {
             high_water_mark: AtomicU64::new(0),
             last_nonzero_epoch_ms: AtomicU64::new(0),
             clock,
+            drain_threshold: 0,
+            drained: Arc::new(tokio::sync::Notify::new()),
         }
     }
 
@@ -279,6 +307,15 @@ impl ProcQueueStats {
         (self.clock)()
     }
 
+    /// Record that an actor's queue depth fell from `prev_depth`.
+    fn on_depth_decreased(&self, prev_depth: u64) {
+        // Only the crossing wakes waiters, to keep notifications off
+        // the common dequeue path.
+        if self.drain_threshold > 0 && prev_depth == self.drain_threshold {
+            self.drained.notify_waiters();
+        }
+    }
+
     /// Current proc-wide running total.
     pub(crate) fn running_total(&self) -> u64 {
         self.running_total.load(Ordering::Relaxed)
@@ -332,7 +369,8 @@ fn account_enqueue(queue_depth: &AtomicU64, proc_stats: &ProcQueueStats, actor_i
 /// timestamp when the proc-wide queue remains non-zero after
 /// this dequeue.
 fn account_dequeue(queue_depth: &AtomicU64, proc_stats: &ProcQueueStats, actor_id: &str) {
-    queue_depth.fetch_sub(1, Ordering::Relaxed);
+    let prev_depth = queue_depth.fetch_sub(1, Ordering::Relaxed);
+    proc_stats.on_depth_decreased(prev_depth);
     let prev_total = proc_stats.running_total.fetch_sub(1, Ordering::Relaxed);
     // PD-7: if the queue is still non-zero after this dequeue,
     // update the timestamp so last_nonzero_age_ms reflects
@@ -358,13 +396,102 @@ fn account_dequeue(queue_depth: &AtomicU64, proc_stats: &ProcQueueStats, actor_i
 /// touch `last_nonzero_epoch_ms` (best-effort observational
 /// timestamp; brief overcount on failed sends is acceptable).
 fn account_cancel_enqueue(queue_depth: &AtomicU64, proc_stats: &ProcQueueStats, actor_id: &str) {
-    queue_depth.fetch_sub(1, Ordering::Relaxed);
+    let prev_depth = queue_depth.fetch_sub(1, Ordering::Relaxed);
+    proc_stats.on_depth_decreased(prev_depth);
     proc_stats.running_total.fetch_sub(1, Ordering::Relaxed);
     ACTOR_MESSAGE_QUEUE_SIZE.add(
         -1,
         hyperactor_telemetry::kv_pairs!("actor_id" => actor_id.to_owned()),
     );
 }
+
+/// Approximate serialized bytes of the messages queued for an actor's
+/// handlers, in total and per handler port, along with the actor's
+/// cap on the total.
+///
+/// Messages are accounted by [`crate::mailbox::headers::MESSAGE_BYTES`],
+/// which is stamped on serialized posts and by-value local sends alike.
+#[derive(Debug)]
+struct QueuedBytes {
+    total: AtomicU64,
+    /// Handler ports are keyed by their message type.
+    by_port: DashMap<&'static str, Arc<AtomicU64>>,
+    /// The cap on `total`; 0 means unlimited.
+    limit: AtomicU64,
+}
+
+impl QueuedBytes {
+    fn new() -> Self {
+        Self {
+            total: AtomicU64::new(0),
+            by_port: DashMap::new(),
+            limit: AtomicU64::new(hyperactor_config::global::get(
+                config::ACTOR_MAX_QUEUED_BYTES,
+            )),
+        }
+    }
+
+    /// The counter for the handler port of the given message type.
+    fn port(&self, message_type: &'static str) -> Arc<AtomicU64> {
+        Arc::clone(
+            self.by_port
+                .entry(message_type)
+                .or_insert_with(|| Arc::new(AtomicU64::new(0)))
+                .value(),
+        )
+    }
+
+    /// Account `bytes` queued to `port` until the returned reservation
+    /// is dropped, failing if this would exceed the actor's cap.
+    fn reserve(
+        self: &Arc<Self>,
+        port: &Arc<AtomicU64>,
+        bytes: u64,
+    ) -> Result<QueuedBytesReservation, QueuedBytesExceeded> {
+        let limit = self.limit.load(Ordering::Relaxed);
+        let queued = self.total.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if limit > 0 && queued > limit {
+            self.total.fetch_sub(bytes, Ordering::Relaxed);
+            return Err(QueuedBytesExceeded {
+                limit,
+                queued: queued - bytes,
+            });
+        }
+        port.fetch_add(bytes, Ordering::Relaxed);
+        Ok(QueuedBytesReservation {
+            queued: Arc::clone(self),
+            port: Arc::clone(port),
+            bytes,
+        })
+    }
+}
+
+/// A message rejected because queueing it would exceed its actor's
+/// queued-bytes cap. The mailbox returns such messages to their sender
+/// with [`crate::mailbox::TransportFailureReason::QuotaExceeded`].
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "actor queued message bytes would exceed its limit of {limit} bytes ({queued} bytes queued)"
+)]
+pub(crate) struct QueuedBytesExceeded {
+    limit: u64,
+    queued: u64,
+}
+
+/// Bytes accounted to a queued message, released when the message is
+/// dequeued (or dropped).
+struct QueuedBytesReservation {
+    queued: Arc<QueuedBytes>,
+    port: Arc<AtomicU64>,
+    bytes: u64,
+}
+
+impl Drop for QueuedBytesReservation {
+    fn drop(&mut self) {
+        self.port.fetch_sub(self.bytes, Ordering::Relaxed);
+        self.queued.total.fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
 use crate::ordering::SEQ_INFO;
 use crate::ordering::SeqInfo;
 use crate::ordering::Sequencer;
@@ -374,6 +501,12 @@ use crate::sequenced::SequencedReceiver;
 use crate::sequenced::sequenced_unbounded_with_buffering;
 use crate::supervision::ActorSupervisionEvent;
 
+pub mod admission;
+mod deadline;
+pub mod parallel;
+pub mod tenant;
+pub mod timing;
+
 /// A proc instance is the runtime managing a single proc in Hyperactor.
 /// It is responsible for spawning actors in the proc, multiplexing messages
 /// to/within actors in the proc, and providing fallback routing to external
@@ -405,6 +538,18 @@ struct ProcState {
     /// the proc.
     proc_muxer: MailboxMuxer,
 
+    /// Two-phase transactions prepared in this proc.
+    transactions: crate::mailbox::transaction::Participant,
+
+    /// Tenants hosted by this proc, and their actors.
+    tenants: tenant::Tenants,
+
+    /// Resource reservations of this proc's actors.
+    admission: admission::Admission,
+
+    /// The pool that runs parallel handlers; started on first use.
+    parallel_pool: OnceLock<Arc<parallel::ParallelPool>>,
+
     /// Reserved root actor uids. Prevents races between concurrent
     /// `allocate_root_id` callers — insert returns false if the uid
     /// was already reserved.
@@ -510,6 +655,42 @@ pub struct ActorInstance<A: Actor> {
 /// Receiver for actor handler work.
 pub struct ActorWorkReceiver<A: Actor> {
     inner: SequencedReceiver<SequencedEnvelope<WorkCell<A>>>,
+    /// Checkpoint requests, served ahead of queued work.
+    checkpoints: mpsc::UnboundedReceiver<CheckpointRequest<A>>,
+    /// Hot-swap requests, served ahead of queued work.
+    swaps: mpsc::UnboundedReceiver<SwapRequest<A>>,
+    /// Debug requests, served ahead of queued work; see
+    /// [`crate::debugger`].
+    debug: mpsc::UnboundedReceiver<DebugMessage>,
+    /// Work already taken from `inner` to capture a checkpoint or list
+    /// the queue, to be delivered before anything else in `inner`.
+    stash: VecDeque<WorkCell<A>>,
+    /// Whether queued work is held back by the debugger.
+    frozen: bool,
+    /// The number of work items the debugger has released while
+    /// frozen, and that have not yet been received.
+    steps: usize,
+}
+
+/// A request to checkpoint an actor. The actor loop calls the request
+/// with the actor and its pending messages between two messages.
+pub(crate) type CheckpointRequest<A> =
+    Box<dyn FnOnce(&A, Result<Vec<PendingMessage>, CheckpointError>) + Send + Sync>;
+
+/// A request to hot-swap an actor. The actor loop calls the request with
+/// the actor between two messages.
+pub(crate) type SwapRequest<A> = Box<dyn FnOnce(&mut A) + Send>;
+
+/// An item received by the actor loop.
+pub(crate) enum ActorWork<A: Actor> {
+    /// Handler work.
+    Work(WorkCell<A>),
+    /// A checkpoint request.
+    Checkpoint(CheckpointRequest<A>),
+    /// A hot-swap request.
+    Swap(SwapRequest<A>),
+    /// A debug request.
+    Debug(DebugMessage),
 }
 
 impl<A: Actor> fmt::Debug for ActorWorkReceiver<A> {
@@ -519,18 +700,174 @@ impl<A: Actor> fmt::Debug for ActorWorkReceiver<A> {
 }
 
 impl<A: Actor> ActorWorkReceiver<A> {
-    fn new(inner: SequencedReceiver<SequencedEnvelope<WorkCell<A>>>) -> Self {
-        Self { inner }
+    fn new(
+        inner: SequencedReceiver<SequencedEnvelope<WorkCell<A>>>,
+        checkpoints: mpsc::UnboundedReceiver<CheckpointRequest<A>>,
+        swaps: mpsc::UnboundedReceiver<SwapRequest<A>>,
+        debug: mpsc::UnboundedReceiver<DebugMessage>,
+    ) -> Self {
+        Self {
+            inner,
+            checkpoints,
+            swaps,
+            debug,
+            stash: VecDeque::new(),
+            frozen: false,
+            steps: 0,
+        }
     }
 
     /// Receive the next deliverable handler work item.
     pub async fn recv(&mut self) -> Option<WorkCell<A>> {
-        self.inner.recv().await
+        match self.stash.pop_front() {
+            Some(work) => Some(work),
+            None => self.inner.recv().await,
+        }
     }
 
     /// Try to receive the next deliverable handler work item without waiting.
     pub fn try_recv(&mut self) -> Result<WorkCell<A>, mpsc::error::TryRecvError> {
-        self.inner.try_recv()
+        match self.stash.pop_front() {
+            Some(work) => Ok(work),
+            None => self.inner.try_recv(),
+        }
+    }
+
+    /// Receive the next checkpoint request, hot-swap request, debug
+    /// request, or handler work item, preferring requests. While frozen,
+    /// work items are only received as the debugger releases them.
+    async fn recv_any(&mut self) -> Option<ActorWork<A>> {
+        let Self {
+            inner,
+            checkpoints,
+            swaps,
+            debug,
+            stash,
+            frozen,
+            steps,
+        } = self;
+        tokio::select! {
+            biased;
+            Some(request) = checkpoints.recv() => Some(ActorWork::Checkpoint(request)),
+            Some(request) = swaps.recv() => Some(ActorWork::Swap(request)),
+            Some(request) = debug.recv() => Some(ActorWork::Debug(request)),
+            work = async {
+                if *frozen && *steps == 0 {
+                    std::future::pending::<()>().await;
+                }
+                let work = match stash.pop_front() {
+                    Some(work) => Some(work),
+                    None => inner.recv().await,
+                };
+                if *frozen && work.is_some() {
+                    *steps -= 1;
+                }
+                work
+            } => work.map(ActorWork::Work),
+        }
+    }
+
+    /// If `work` is a batch, append to it the deliverable work items
+    /// that directly follow it and belong to the batch. Returns the
+    /// number of work items absorbed.
+    fn extend_batch(&mut self, work: &mut WorkCell<A>) -> u64 {
+        let mut absorbed = 0;
+        // The debugger steps a frozen actor through one work item at a
+        // time.
+        if self.frozen {
+            return absorbed;
+        }
+        while work.is_batch() {
+            let Ok(next) = self.try_recv() else {
+                break;
+            };
+            if let Err(next) = work.absorb(next) {
+                self.stash.push_front(next);
+                break;
+            }
+            absorbed += 1;
+        }
+        absorbed
+    }
+
+    /// The messages of all deliverable work items, in delivery order.
+    /// The work items themselves remain queued.
+    fn pending(&mut self) -> Result<Vec<PendingMessage>, CheckpointError> {
+        self.fill_stash();
+        self.stash.iter().filter_map(WorkCell::pending).collect()
+    }
+
+    /// Move all deliverable work items from `inner` to the stash.
+    fn fill_stash(&mut self) {
+        while let Ok(work) = self.inner.try_recv() {
+            self.stash.push_back(work);
+        }
+    }
+
+    /// Carry out a debug request on behalf of `instance`.
+    fn debug(&mut self, instance: &Instance<A>, request: DebugMessage) {
+        // System actors (e.g. the proc agent) are not debugged: freezing
+        // them would stall the proc.
+        if instance.inner.cell.is_system() {
+            let reply = request.into_reply();
+            reply.post(
+                instance,
+                DebugReply::Error("system actors cannot be debugged".to_string()),
+            );
+            return;
+        }
+        let (reply, result) = match request {
+            DebugMessage::Freeze { reply } => {
+                self.frozen = true;
+                (reply, DebugReply::Frozen(true))
+            }
+            DebugMessage::Thaw { reply } => {
+                self.frozen = false;
+                self.steps = 0;
+                (reply, DebugReply::Frozen(false))
+            }
+            DebugMessage::List { reply } => {
+                self.fill_stash();
+                let queued = self
+                    .stash
+                    .iter()
+                    .enumerate()
+                    .map(|(position, work)| QueuedEnvelope::describe(position, work.pending()))
+                    .collect();
+                (reply, DebugReply::Queued(queued))
+            }
+            DebugMessage::Step { reply } if !self.frozen => (
+                reply,
+                DebugReply::Error("cannot step an actor that is not frozen".to_string()),
+            ),
+            DebugMessage::Step { reply } => {
+                self.fill_stash();
+                // Work items already released by earlier steps are
+                // ahead of this one.
+                let position = self.steps;
+                let stepped = self.stash.get(position).map(|work| {
+                    self.steps += 1;
+                    QueuedEnvelope::describe(position, work.pending())
+                });
+                (reply, DebugReply::Stepped(stepped))
+            }
+            DebugMessage::Inject { message, reply } => {
+                let port = Port::handler_id(message.typehash(), None);
+                let result = if instance.inner.mailbox.is_bound(&port) {
+                    instance.self_addr().port_addr(port).send(instance, message);
+                    DebugReply::Frozen(self.frozen)
+                } else {
+                    DebugReply::Error(format!(
+                        "actor has no handler for {}",
+                        message
+                            .typename()
+                            .map_or_else(|| message.typehash().to_string(), str::to_string)
+                    ))
+                };
+                (reply, result)
+            }
+        };
+        reply.post(instance, result);
     }
 }
 
@@ -654,6 +991,10 @@ impl Proc {
                 proc_id: proc_id.clone(),
                 gateway: gateway.clone(),
                 proc_muxer: MailboxMuxer::new(),
+                transactions: Default::default(),
+                tenants: Default::default(),
+                admission: Default::default(),
+                parallel_pool: OnceLock::new(),
                 reserved_roots: DashSet::new(),
                 reserved_child_uids: DashSet::new(),
                 instances: DashMap::new(),
@@ -915,11 +1256,27 @@ impl Proc {
         &self.inner.proc_muxer
     }
 
+    /// Post every envelope to actors in this proc, or none of them. See
+    /// [`crate::mailbox::transaction`].
+    pub fn post_all(
+        &self,
+        envelopes: Vec<MessageEnvelope>,
+    ) -> Result<(), crate::mailbox::transaction::TransactionError> {
+        self.state().proc_muxer.post_all(envelopes)
+    }
+
     /// Convenience accessor for state.
     fn state(&self) -> &ProcState {
         self.inner.as_ref()
     }
 
+    /// The pool that runs this proc's parallel handlers.
+    fn parallel_pool(&self) -> &Arc<parallel::ParallelPool> {
+        self.state()
+            .parallel_pool
+            .get_or_init(|| Arc::new(parallel::ParallelPool::new()))
+    }
+
     /// Attach a mailbox to the proc with the provided root name.
     pub fn attach(&self, name: &str) -> Result<Mailbox, anyhow::Error> {
         let actor_id: ActorAddr = self.allocate_root_id(name)?;
@@ -942,6 +1299,19 @@ impl Proc {
         mbox
     }
 
+    /// Bind `sender` to receive the messages of a new root actor with
+    /// the provided name, emulating an actor that is not spawned in the
+    /// proc; see [`crate::interop`].
+    pub(crate) fn bind_sender(
+        &self,
+        name: &str,
+        sender: impl MailboxSender + 'static,
+    ) -> Result<ActorAddr, anyhow::Error> {
+        let actor_id = self.allocate_root_id(name)?;
+        self.state().proc_muxer.bind(actor_id.id().clone(), sender);
+        Ok(actor_id)
+    }
+
     /// Attach a mailbox to the proc with the provided root name, and bind an [`ActorAddr`].
     /// This is intended only for testing, and will be replaced by simpled utilities.
     pub fn attach_actor<R, M>(
@@ -1081,6 +1451,26 @@ impl Proc {
         }
     }
 
+    /// Close handler intake for the provided root actors and all of
+    /// their descendants, without stopping them. Subsequent handler
+    /// messages are returned to their senders, while work that was
+    /// already accepted continues to be processed, so callers can
+    /// wait for the returned instances' queues to drain.
+    pub fn close_intake(&self, roots: &[ActorId]) -> Vec<InstanceCell> {
+        let mut cells = Vec::new();
+        for root in roots {
+            if let Some(cell) = self.get_instance_by_id(root) {
+                cell.traverse(&mut |cell, _| cells.push(cell.clone()));
+            }
+        }
+        for cell in &cells {
+            if let Some(mailbox) = self.state().proc_muxer.local(cell.actor_addr().id()) {
+                mailbox.drain();
+            }
+        }
+        cells
+    }
+
     /// Proc-wide running total of queued work items.
     pub fn queue_depth_total(&self) -> u64 {
         self.state().queue_stats.running_total()
@@ -1577,6 +1967,28 @@ impl Proc {
 
         dest_proc.id() == local_proc_id
     }
+
+    /// Prepare a by-value send to `dest`, if it is a typed port of an actor
+    /// in this proc and the send needs none of the policies that operate
+    /// on serialized envelopes (tenant checks, transactions, and
+    /// idempotency keys).
+    pub(crate) fn local_sender<M: RemoteMessage>(
+        &self,
+        dest: &PortAddr,
+        headers: &Flattrs,
+    ) -> Option<crate::mailbox::LocalSend<M>> {
+        if !self.is_local_delivery_target(&dest.actor_addr().proc_addr())
+            || !self.state().tenants.is_empty()
+            || headers.contains_key(crate::mailbox::headers::TXN_ID)
+            || headers.contains_key(crate::mailbox::headers::IDEMPOTENCY_KEY)
+        {
+            return None;
+        }
+        self.state()
+            .proc_muxer
+            .local(dest.actor_id())?
+            .local_sender(dest)
+    }
 }
 
 fn requires_location_for_local_delivery_identity(proc_id: &ProcId) -> bool {
@@ -1640,6 +2052,20 @@ impl MailboxSender for Proc {
     ) {
         let dest_proc = envelope.dest().actor_addr().proc_addr();
         if self.is_local_delivery_target(&dest_proc) {
+            if let Err(failure) = self.state().tenants.check_local(&envelope) {
+                envelope.undeliverable(*failure, return_handle);
+                return;
+            }
+            if envelope
+                .headers()
+                .contains_key(crate::mailbox::headers::TXN_ID)
+            {
+                let state = self.state();
+                state
+                    .transactions
+                    .handle(&state.proc_muxer, self, envelope, return_handle);
+                return;
+            }
             self.state().proc_muxer.post(envelope, return_handle);
             return;
         }
@@ -1649,12 +2075,32 @@ impl MailboxSender for Proc {
         // child procs attached via `gateway.attach_peer(child_uid,
         // sender)` — are reached by an in-gateway lookup rather than
         // bouncing out through the forwarder.
+        if let Err(failure) = self.state().tenants.check_egress(&envelope) {
+            envelope.undeliverable(*failure, return_handle);
+            return;
+        }
         self.state().gateway.post(envelope, return_handle);
     }
 
     async fn flush(&self) -> Result<(), anyhow::Error> {
         self.gateway().flush().await
     }
+
+    /// The queue depth of the destination actor: messages accepted for
+    /// its handlers and not yet dequeued by it. Other ports have no
+    /// backlog, so that replies are never held back behind handler
+    /// messages.
+    fn backlog(&self, dest: &PortAddr) -> u64 {
+        if !dest.is_handler_port() {
+            return 0;
+        }
+        self.get_instance(&dest.actor_addr())
+            .map_or(0, |cell| cell.queue_depth())
+    }
+
+    fn backlog_drained(&self) -> Option<Arc<tokio::sync::Notify>> {
+        Some(Arc::clone(&self.state().queue_stats.drained))
+    }
 }
 
 /// A weak reference to a Proc that doesn't prevent it from being dropped.
@@ -1704,21 +2150,196 @@ impl MailboxSender for WeakProc {
             None => Ok(()),
         }
     }
+
+    fn backlog(&self, dest: &PortAddr) -> u64 {
+        self.upgrade().map_or(0, |proc| proc.backlog(dest))
+    }
+
+    fn backlog_drained(&self) -> Option<Arc<tokio::sync::Notify>> {
+        self.upgrade().and_then(|proc| proc.backlog_drained())
+    }
 }
 
 /// Represents a single work item used by the instance to dispatch to
 /// actor handles. Specifically, this enables handler polymorphism.
-pub struct WorkCell<A: Actor + Send>(
-    Box<
-        dyn for<'a> FnOnce(
-                &'a mut A,
-                &'a Instance<A>,
-            )
-                -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + 'a + Send>>
-            + Send
-            + Sync,
-    >,
-);
+pub struct WorkCell<A: Actor + Send>(Work<A>);
+
+enum Work<A: Actor + Send> {
+    /// Runtime work.
+    Func(
+        Box<
+            dyn for<'a> FnOnce(
+                    &'a mut A,
+                    &'a Instance<A>,
+                ) -> Pin<
+                    Box<dyn Future<Output = Result<(), anyhow::Error>> + 'a + Send>,
+                > + Send
+                + Sync,
+        >,
+    ),
+    /// A message for one of the actor's handlers.
+    Message(Box<dyn QueuedMessage<A>>),
+}
+
+/// A message queued for a handler of actor A.
+trait QueuedMessage<A: Actor>: Send + Sync {
+    fn handle<'a>(
+        self: Box<Self>,
+        actor: &'a mut A,
+        instance: &'a Instance<A>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + Send + 'a>>;
+
+    /// Serialize the message for a checkpoint.
+    fn pending(&self) -> Result<PendingMessage, CheckpointError>;
+
+    /// Serialize each message, for a recording; see [`crate::replay`].
+    fn pending_all(&self) -> Vec<Result<PendingMessage, CheckpointError>> {
+        vec![self.pending()]
+    }
+
+    /// Whether this message is handled in batches; see
+    /// [`crate::actor::BatchHandler`].
+    fn is_batch(&self) -> bool {
+        false
+    }
+
+    /// Append `next` to this message's batch, or return it if it does
+    /// not belong to the batch.
+    fn absorb(&mut self, next: Box<dyn QueuedMessage<A>>) -> Result<(), Box<dyn QueuedMessage<A>>> {
+        Err(next)
+    }
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+struct HandlerMessage<M: Message> {
+    type_info: Option<&'static TypeInfo>,
+    headers: Flattrs,
+    message: M,
+    /// Released once the message is dequeued for handling.
+    queued_bytes: Option<QueuedBytesReservation>,
+}
+
+impl<A: Handler<M>, M: Message> QueuedMessage<A> for HandlerMessage<M> {
+    fn handle<'a>(
+        self: Box<Self>,
+        actor: &'a mut A,
+        instance: &'a Instance<A>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + Send + 'a>> {
+        let Self {
+            type_info,
+            headers,
+            message,
+            queued_bytes,
+        } = *self;
+        drop(queued_bytes);
+        Box::pin(async move {
+            // SAFETY: type_info was looked up by M's TypeId.
+            unsafe {
+                instance
+                    .handle_message(actor, type_info, headers, message)
+                    .await
+            }
+        })
+    }
+
+    fn pending(&self) -> Result<PendingMessage, CheckpointError> {
+        HandlerMessage::pending(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl<M: Message> HandlerMessage<M> {
+    fn pending(&self) -> Result<PendingMessage, CheckpointError> {
+        let type_info = self
+            .type_info
+            .ok_or(CheckpointError::Unregistered(std::any::type_name::<M>()))?;
+        // SAFETY: type_info was looked up by M's TypeId.
+        let data =
+            unsafe { type_info.serialize_unchecked(&self.message as *const M as *const ()) }?;
+        Ok(PendingMessage {
+            headers: self.headers.clone(),
+            version: crate::mailbox::migrate::local_version(type_info.typehash()),
+            data,
+        })
+    }
+}
+
+/// A run of consecutive messages queued for A's M-typed batch handler.
+/// Queued batches hold a single message; the actor loop absorbs the
+/// messages that follow a batch just before handling it.
+struct BatchMessage<M: Message> {
+    limit: usize,
+    messages: Vec<HandlerMessage<M>>,
+}
+
+impl<A: BatchHandler<M>, M: Message> QueuedMessage<A> for BatchMessage<M> {
+    fn handle<'a>(
+        self: Box<Self>,
+        actor: &'a mut A,
+        instance: &'a Instance<A>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + Send + 'a>> {
+        Box::pin(async move {
+            let mut messages = self.messages.into_iter();
+            let Some(first) = messages.next() else {
+                return Ok(());
+            };
+            // SAFETY: type_info was looked up by M's TypeId.
+            let (handler_info, endpoint) =
+                unsafe { Instance::<A>::handler_info(first.type_info, &first.message) };
+            let batch = std::iter::once(first.message)
+                .chain(messages.map(|message| message.message))
+                .collect();
+            instance
+                .handle_message_with_handler_info(
+                    actor,
+                    handler_info,
+                    first.headers,
+                    Batch(batch),
+                    endpoint,
+                )
+                .await
+        })
+    }
+
+    fn pending(&self) -> Result<PendingMessage, CheckpointError> {
+        self.messages[0].pending()
+    }
+
+    fn pending_all(&self) -> Vec<Result<PendingMessage, CheckpointError>> {
+        self.messages.iter().map(HandlerMessage::pending).collect()
+    }
+
+    fn is_batch(&self) -> bool {
+        true
+    }
+
+    fn absorb(&mut self, next: Box<dyn QueuedMessage<A>>) -> Result<(), Box<dyn QueuedMessage<A>>> {
+        if self.messages.len() >= self.limit || !next.as_any().is::<Self>() {
+            return Err(next);
+        }
+        let next = next.into_any().downcast::<Self>().unwrap();
+        self.messages.extend(next.messages);
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
 
 impl<A: Actor + Send> WorkCell<A> {
     /// Create a new WorkCell from a concrete function (closure).
@@ -1732,7 +2353,66 @@ impl<A: Actor + Send> WorkCell<A> {
         + Sync
         + 'static,
     ) -> Self {
-        Self(Box::new(f))
+        Self(Work::Func(Box::new(f)))
+    }
+
+    /// Create a new WorkCell that handles `message` with A's M-typed
+    /// handler. `type_info` must be M's type info, if registered.
+    fn message<M: Message>(
+        type_info: Option<&'static TypeInfo>,
+        headers: Flattrs,
+        message: M,
+        queued_bytes: Option<QueuedBytesReservation>,
+    ) -> Self
+    where
+        A: Handler<M>,
+    {
+        Self(Work::Message(Box::new(HandlerMessage {
+            type_info,
+            headers,
+            message,
+            queued_bytes,
+        })))
+    }
+
+    /// Create a new WorkCell that handles `message` with A's M-typed
+    /// batch handler, together with up to `limit - 1` M-typed messages
+    /// queued directly behind it.
+    fn batch_message<M: Message>(
+        type_info: Option<&'static TypeInfo>,
+        limit: usize,
+        headers: Flattrs,
+        message: M,
+        queued_bytes: Option<QueuedBytesReservation>,
+    ) -> Self
+    where
+        A: BatchHandler<M>,
+    {
+        Self(Work::Message(Box::new(BatchMessage {
+            limit,
+            messages: vec![HandlerMessage {
+                type_info,
+                headers,
+                message,
+                queued_bytes,
+            }],
+        })))
+    }
+
+    /// Whether this work cell can absorb the messages queued behind it.
+    fn is_batch(&self) -> bool {
+        matches!(&self.0, Work::Message(message) if message.is_batch())
+    }
+
+    /// Append `next` to this work cell's batch, or return it if it does
+    /// not belong to the batch.
+    fn absorb(&mut self, next: WorkCell<A>) -> Result<(), WorkCell<A>> {
+        match (&mut self.0, next.0) {
+            (Work::Message(message), Work::Message(next)) => message
+                .absorb(next)
+                .map_err(|next| WorkCell(Work::Message(next))),
+            (_, next) => Err(WorkCell(next)),
+        }
     }
 
     /// Handle the message represented by this work cell.
@@ -1741,7 +2421,28 @@ impl<A: Actor + Send> WorkCell<A> {
         actor: &'a mut A,
         instance: &'a Instance<A>,
     ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + Send + 'a>> {
-        (self.0)(actor, instance)
+        match self.0 {
+            Work::Func(f) => f(actor, instance),
+            Work::Message(message) => message.handle(actor, instance),
+        }
+    }
+
+    /// The message carried by this work cell, serialized for a checkpoint.
+    /// Runtime work carries no message.
+    fn pending(&self) -> Option<Result<PendingMessage, CheckpointError>> {
+        match &self.0 {
+            Work::Func(_) => None,
+            Work::Message(message) => Some(message.pending()),
+        }
+    }
+
+    /// The messages carried by this work cell, serialized for a
+    /// recording. Runtime work carries no messages.
+    fn pending_all(&self) -> Vec<Result<PendingMessage, CheckpointError>> {
+        match &self.0 {
+            Work::Func(_) => Vec::new(),
+            Work::Message(message) => message.pending_all(),
+        }
     }
 }
 
@@ -1761,6 +2462,43 @@ impl<'a, A: Actor> Context<'a, A> {
     pub fn headers(&self) -> &Flattrs {
         &self.headers
     }
+
+    /// The cancel token of the message being handled, if any; see
+    /// [`crate::cancel`].
+    pub fn cancel_token(&self) -> Option<CancelToken> {
+        self.headers.get(crate::mailbox::headers::CANCEL_TOKEN)
+    }
+
+    /// Whether the operation that the message being handled belongs to
+    /// has been cancelled. Long-running handlers should check this
+    /// periodically and abort when it returns true.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel_token()
+            .is_some_and(|token| self.instance.inner.cancellations.is_cancelled(&token))
+    }
+
+    /// Reply to the message being handled, at the port named by its
+    /// [`REPLY_TO`](crate::mailbox::headers::REPLY_TO) header; see
+    /// [`crate::RemoteEndpoint::request`] and [`Instance::set_reply_port`].
+    /// Fails if the message carries no reply port.
+    pub fn respond<R: RemoteMessage>(&self, message: R) -> Result<(), anyhow::Error> {
+        let port = self
+            .headers
+            .get(crate::mailbox::headers::REPLY_TO)
+            .ok_or_else(|| anyhow::anyhow!("message carries no reply-to port"))?;
+        crate::OncePortRef::<R>::attest(port).post(self, message);
+        Ok(())
+    }
+
+    /// Wait until the operation that the message being handled belongs
+    /// to is cancelled. Never completes if the message carries no
+    /// cancel token.
+    pub async fn cancelled(&self) {
+        match self.cancel_token() {
+            Some(token) => self.instance.inner.cancellations.cancelled(&token).await,
+            None => std::future::pending().await,
+        }
+    }
 }
 
 impl<A: Actor> Deref for Context<'_, A> {
@@ -1810,6 +2548,14 @@ struct InstanceState<A: Actor> {
 
     /// Per-instance local storage.
     instance_locals: ActorLocalStorage,
+
+    /// Cancellation state for the operations this actor takes part in.
+    cancellations: Arc<Cancellations>,
+
+    /// The port named by the [`REPLY_TO`](crate::mailbox::headers::REPLY_TO)
+    /// header of messages posted by this actor; see
+    /// [`Instance::set_reply_port`].
+    reply_port: Mutex<Option<PortAddr>>,
 }
 
 type DelayedPost<A> = Box<dyn FnOnce(&Instance<A>) + Send>;
@@ -2168,14 +2914,21 @@ impl<A: Actor> Instance<A> {
         let enable_buffering =
             hyperactor_config::global::get(config::ENABLE_DEST_ACTOR_REORDERING_BUFFER);
         let (work_tx, work_rx) = sequenced_unbounded_with_buffering(enable_buffering);
+        let (checkpoint_tx, checkpoint_rx) = mpsc::unbounded_channel();
+        let (swap_tx, swap_rx) = mpsc::unbounded_channel();
+        let (debug_tx, debug_rx) = mpsc::unbounded_channel();
         let inbound_ordering_snapshot_handle = work_rx.snapshot_handle();
         let queue_depth = Arc::new(AtomicU64::new(0));
+        let queued_bytes = Arc::new(QueuedBytes::new());
         let proc_stats = Arc::clone(&proc.state().queue_stats);
         let ports: Arc<HandlerPorts<A>> = Arc::new(HandlerPorts::new(
             mailbox.clone(),
             work_tx,
+            checkpoint_tx,
+            swap_tx,
             enable_buffering,
             Arc::clone(&queue_depth),
+            Arc::clone(&queued_bytes),
             proc_stats,
         ));
         proc.state().proc_muxer.bind_mailbox(mailbox.clone());
@@ -2207,6 +2960,43 @@ impl<A: Actor> Instance<A> {
         let (introspect_port, introspect_receiver) = mailbox.open_port::<IntrospectMessage>();
         introspect_port.bind_control_port(crate::port::ControlPort::Introspect);
 
+        // Cancel port: cancellations are recorded (and forwarded
+        // downstream) as they are delivered, so that handlers busy in
+        // the actor's message loop can observe them.
+        let cancellations = Arc::new(Cancellations::new());
+        let cancel_port = mailbox.open_enqueue_port::<Cancel>({
+            let cancellations = Arc::clone(&cancellations);
+            let actor_id = actor_id.clone();
+            let proc = proc.downgrade();
+            move |_headers, Cancel { token }| {
+                cancel::cancel_and_propagate(&cancellations, &actor_id, &proc, token);
+                Ok(())
+            }
+        });
+        cancel_port.bind_control_port(crate::port::ControlPort::Cancel);
+
+        // Debug port: requests are carried out by the actor loop, between
+        // messages; see the debugger module doc. Only bound when enabled,
+        // and only requests whose replies go to their sender are served.
+        if hyperactor_config::global::get(config::DEBUG_PORT) {
+            let debug_port = mailbox.open_enqueue_port::<DebugMessage>({
+                let actor_id = actor_id.clone();
+                move |headers, request| {
+                    if !request.is_from(&headers) {
+                        tracing::warn!(
+                            actor_id = %actor_id,
+                            "dropping debug request whose reply port is not its sender's",
+                        );
+                        return Ok(());
+                    }
+                    // The actor loop has exited; there is nothing to debug.
+                    let _ = debug_tx.send(request);
+                    Ok(())
+                }
+            });
+            debug_port.bind_control_port(crate::port::ControlPort::Debug);
+        }
+
         let instance_id = Uuid::now_v7();
 
         // Type-erased snapshot callback: captures only the receiver-local
@@ -2227,6 +3017,7 @@ impl<A: Actor> Instance<A> {
             parent,
             ports.clone(),
             queue_depth,
+            queued_bytes,
             inbound_ordering_snapshot,
         );
         let inner = Arc::new(InstanceState {
@@ -2239,12 +3030,14 @@ impl<A: Actor> Instance<A> {
             sequencer: Sequencer::new(instance_id),
             id: instance_id,
             instance_locals: ActorLocalStorage::new(),
+            cancellations,
+            reply_port: Mutex::new(None),
         });
         (
             Self { inner },
             InstanceReceivers {
                 actor_loop: actor_loop_receivers,
-                work: ActorWorkReceiver::new(work_rx),
+                work: ActorWorkReceiver::new(work_rx, checkpoint_rx, swap_rx, debug_rx),
                 introspect: introspect_receiver,
             },
         )
@@ -2453,6 +3246,20 @@ impl<A: Actor> Instance<A> {
         self.inner.cell.set_attrs_snapshot(callback);
     }
 
+    /// Cap the serialized bytes of messages that may be queued for
+    /// this actor's handlers, overriding
+    /// [`config::ACTOR_MAX_QUEUED_BYTES`]. Messages that would exceed
+    /// the cap are returned to their senders as undeliverable. `None`
+    /// removes the cap.
+    pub fn set_max_queued_bytes(&self, limit: Option<u64>) {
+        self.inner
+            .cell
+            .inner
+            .queued_bytes
+            .limit
+            .store(limit.unwrap_or(0), Ordering::Relaxed);
+    }
+
     /// Mark this actor as system/infrastructure. System actors are
     /// hidden by default in the TUI (toggled via `s`).
     pub fn set_system(&self) {
@@ -2752,6 +3559,11 @@ impl<A: Actor> Instance<A> {
         let result = self
             .run_actor_tree(&mut actor, actor_loop_receivers, &mut work_rx)
             .await;
+        self.inner
+            .proc
+            .state()
+            .admission
+            .release(self.self_addr().id());
 
         assert!(self.is_stopping());
         // Compute the terminal status and supervision event, but defer
@@ -2983,6 +3795,51 @@ impl<A: Actor> Instance<A> {
         let (signal_receiver, supervision_event_receiver) = actor_loop_receivers;
 
         self.change_status(ActorStatus::Initializing);
+        let parent = self.inner.cell.parent();
+        self.inner
+            .proc
+            .state()
+            .tenants
+            .admit(
+                self.self_addr().id(),
+                parent.as_ref().map(|parent| parent.actor_addr().id()),
+                &self.inner.cell.inner.queue_depth,
+                &self.inner.cell.inner.queued_bytes,
+            )
+            .map_err(|err| ActorError::new(self.self_addr(), ActorErrorKind::init(err.into())))?;
+        let resources = actor.resources();
+        if !resources.is_empty() {
+            let reserve = self
+                .inner
+                .proc
+                .state()
+                .admission
+                .reserve(self.self_addr(), resources);
+            tokio::pin!(reserve);
+            // An actor that is stopped while it is queued for admission
+            // stops without being initialized.
+            loop {
+                tokio::select! {
+                    biased;
+                    result = &mut reserve => {
+                        result.map_err(|err| ActorError::new(self.self_addr(), ActorErrorKind::init(err.into())))?;
+                        break;
+                    }
+                    signal = signal_receiver.recv() => match signal {
+                        Some(Signal::Stop(reason) | Signal::DrainAndStop(reason) | Signal::ExitRequested(reason)) => {
+                            return Ok(reason);
+                        }
+                        Some(Signal::Kill(reason)) => {
+                            return Err(ActorError::new(self.self_addr(), ActorErrorKind::Aborted(reason)));
+                        }
+                        Some(Signal::ChildStopped(_)) => {}
+                        None => {
+                            return Err(ActorError::new(self.self_addr(), ActorErrorKind::SignalChannelClosed));
+                        }
+                    },
+                }
+            }
+        }
         self.inner
             .proc
             .with_current(actor.init(self))
@@ -3030,11 +3887,32 @@ impl<A: Actor> Instance<A> {
                         }
                     }
                 }
-                work = work_rx.recv() => {
-                    ACTOR_MESSAGES_RECEIVED.add(1, metric_pairs);
-                    account_dequeue(&self.inner.cell.inner.queue_depth, &self.inner.proc.state().queue_stats, &actor_id_str);
-                    let _ = ACTOR_MESSAGE_HANDLER_DURATION.start(metric_pairs);
-                    let work = work.expect("inconsistent work queue state");
+                work = work_rx.recv_any() => {
+                    let mut work = match work.expect("inconsistent work queue state") {
+                        ActorWork::Work(work) => work,
+                        ActorWork::Checkpoint(request) => {
+                            let pending = work_rx.pending();
+                            request(actor, pending);
+                            continue 'messages;
+                        }
+                        ActorWork::Swap(request) => {
+                            request(actor);
+                            continue 'messages;
+                        }
+                        ActorWork::Debug(request) => {
+                            work_rx.debug(self, request);
+                            continue 'messages;
+                        }
+                    };
+                    let received = 1 + work_rx.extend_batch(&mut work);
+                    self.inner.cell.inner.message_recorder.record(|| work.pending_all());
+                    ACTOR_MESSAGES_RECEIVED.add(received, metric_pairs);
+                    for _ in 0..received {
+                        account_dequeue(&self.inner.cell.inner.queue_depth, &self.inner.proc.state().queue_stats, &actor_id_str);
+                    }
+                    let _timer = ACTOR_MESSAGE_HANDLER_DURATION.start(
+                        hyperactor_telemetry::kv_pairs!("actor_type" => std::any::type_name::<A>()),
+                    );
                     if let Err(err) = work.handle(actor, self).await {
                         while let Ok(supervision_event) = supervision_event_receiver.try_recv() {
                             self.handle_supervision_event(actor, supervision_event).await?;
@@ -3112,6 +3990,19 @@ impl<A: Actor> Instance<A> {
         }
     }
 
+    /// Record the timing of a handler that took `wall` and used `cpu`
+    /// in the actor's [`timing::ActorTiming`], and its CPU time, if
+    /// measured, in telemetry.
+    pub(crate) fn record_timing(&self, wall: Duration, cpu: Duration) {
+        self.inner.cell.inner.timing.record(wall, cpu);
+        if !cpu.is_zero() {
+            ACTOR_HANDLER_CPU_US.add(
+                cpu.as_micros() as u64,
+                hyperactor_telemetry::kv_pairs!("actor_type" => std::any::type_name::<A>()),
+            );
+        }
+    }
+
     async unsafe fn handle_message<M: Message>(
         &self,
         actor: &mut A,
@@ -3122,11 +4013,28 @@ impl<A: Actor> Instance<A> {
     where
         A: Handler<M>,
     {
+        // SAFETY: The caller promises to pass the correct type info.
+        let (handler_info, endpoint) = unsafe { Self::handler_info(type_info, &message) };
+
+        // Use a helper function for a better instrument log.
+        self.handle_message_with_handler_info(actor, handler_info, headers, message, endpoint)
+            .await
+    }
+
+    /// The handler info and endpoint name of `message`.
+    ///
+    /// # Safety
+    ///
+    /// `type_info`, if provided, must be M's type info.
+    unsafe fn handler_info<M: Message>(
+        type_info: Option<&'static TypeInfo>,
+        message: &M,
+    ) -> (HandlerInfo, Option<String>) {
         // Build HandlerInfo from TypeInfo (zero-copy) or fall back to type_name.
         let handler_info = match type_info {
             Some(info) => {
                 // SAFETY: The caller promises to pass the correct type info.
-                let arm = unsafe { info.arm_unchecked(&message as *const M as *const ()) };
+                let arm = unsafe { info.arm_unchecked(message as *const M as *const ()) };
                 HandlerInfo::from_static(info.typename(), arm)
             }
             None => {
@@ -3137,12 +4045,9 @@ impl<A: Actor> Instance<A> {
 
         let endpoint = type_info.and_then(|info| {
             // SAFETY: The caller promises to pass the correct type info.
-            unsafe { info.endpoint_name(&message as *const M as *const ()) }
+            unsafe { info.endpoint_name(message as *const M as *const ()) }
         });
-
-        // Use a helper function for a better instrument log.
-        self.handle_message_with_handler_info(actor, handler_info, headers, message, endpoint)
-            .await
+        (handler_info, endpoint)
     }
 
     #[tracing::instrument(level = "debug", name = "handle_message", skip_all, fields(message_type = %handler_info))]
@@ -3181,6 +4086,8 @@ impl<A: Actor> Instance<A> {
                 to_actor_id,
                 endpoint,
                 port_index,
+                parent_message_id: headers.get(crate::mailbox::headers::PARENT_MESSAGE_ID),
+                root_message_id: headers.get(crate::mailbox::headers::ROOT_MESSAGE_ID),
             });
 
             notify_message_status(hyperactor_telemetry::MessageStatusEvent {
@@ -3200,18 +4107,25 @@ impl<A: Actor> Instance<A> {
         // &Instance<A>.
         let start = Instant::now();
         let subject_str = self.self_addr().subject().to_string();
-        let result = self
+        let (result, cpu) = self
             .inner
-            .proc
-            .with_current(actor.handle(&context, message))
-            .instrument(self.inner.cell.inner.recording.span(&subject_str))
+            .cell
+            .inner
+            .timing
+            .time(
+                self.inner
+                    .proc
+                    .with_current(actor.handle(&context, message))
+                    .instrument(self.inner.cell.inner.recording.span(&subject_str)),
+            )
             .await;
-        let elapsed_us = start.elapsed().as_micros() as u64;
+        let elapsed = start.elapsed();
         self.inner
             .cell
             .inner
             .total_processing_time_us
-            .fetch_add(elapsed_us, Ordering::SeqCst);
+            .fetch_add(elapsed.as_micros() as u64, Ordering::SeqCst);
+        self.record_timing(elapsed, cpu);
 
         if let Some(message_id) = message_id {
             notify_message_status(hyperactor_telemetry::MessageStatusEvent {
@@ -3346,6 +4260,98 @@ impl<A: Actor> Instance<A> {
         &self.inner.sequencer
     }
 
+    /// Cancel `token`. Handlers of this actor, and of the actors that
+    /// messages carrying the token were (transitively) sent to, observe
+    /// the cancellation; see [`crate::cancel`].
+    pub fn cancel(&self, token: CancelToken) {
+        cancel::cancel_and_propagate(
+            &self.inner.cancellations,
+            self.self_addr(),
+            &self.inner.proc,
+            token,
+        );
+    }
+
+    /// Handle M-typed messages with `handler` on the proc's parallel
+    /// handler pool instead of this actor's work queue. The actor's own
+    /// [`Handler<M>`] is no longer invoked. See [`parallel`].
+    pub fn handle_in_parallel<M, H>(&self, handler: H)
+    where
+        A: Handler<M>,
+        M: Message,
+        H: ParallelHandler<A, M>,
+    {
+        let route = parallel::route(self, handler);
+        *self.inner.ports.parallel_route::<M>().write().unwrap() = Some(route);
+        // Provision the port, in case it has not been bound yet.
+        self.inner.ports.get::<M>();
+    }
+
+    /// Handle M-typed messages with `handler` on a queue of their own,
+    /// consumed by a task dedicated to the port, instead of this actor's
+    /// work queue. The messages are handled one at a time, in delivery
+    /// order, and never delay the actor's other messages. The actor's own
+    /// [`Handler<M>`] is no longer invoked. See [`parallel`].
+    pub fn handle_on_dedicated_queue<M, H>(&self, handler: H)
+    where
+        A: Handler<M>,
+        M: Message,
+        H: ParallelHandler<A, M>,
+    {
+        let route = parallel::dedicated_route(self, handler);
+        *self.inner.ports.parallel_route::<M>().write().unwrap() = Some(route);
+        // Provision the port, in case it has not been bound yet.
+        self.inner.ports.get::<M>();
+    }
+
+    /// Handle M-typed messages earliest deadline first, by their
+    /// [`DELIVERY_DEADLINE`](crate::mailbox::headers::DELIVERY_DEADLINE)
+    /// header, instead of in delivery order: each M-typed message that the
+    /// actor handles is the most urgent one queued, and messages whose
+    /// deadline has passed are dropped. See [`deadline`].
+    pub fn handle_by_deadline<M>(&self)
+    where
+        A: Handler<M>,
+        M: Message,
+    {
+        let route = deadline::route(self);
+        *self.inner.ports.parallel_route::<M>().write().unwrap() = Some(route);
+        // Provision the port, in case it has not been bound yet.
+        self.inner.ports.get::<M>();
+    }
+
+    /// This instance's cancellation state.
+    pub(crate) fn cancellations(&self) -> &Cancellations {
+        &self.inner.cancellations
+    }
+
+    /// Name `port` in the [`REPLY_TO`](crate::mailbox::headers::REPLY_TO)
+    /// header of every message subsequently posted by this actor that does
+    /// not already carry one, so that receiving handlers can reply with
+    /// [`Context::respond`]. Fails if `port` is not owned by this actor.
+    pub fn set_reply_port<R: RemoteMessage>(&self, port: &PortHandle<R>) -> anyhow::Result<()> {
+        let port_addr = port.bind().into_port_addr();
+        anyhow::ensure!(
+            &port_addr.actor_addr() == self.self_addr(),
+            "reply port {} is not owned by {}",
+            port_addr,
+            self.self_addr()
+        );
+        *self.inner.reply_port.lock().unwrap() = Some(port_addr);
+        Ok(())
+    }
+
+    /// Stop naming a reply port in the messages posted by this actor; see
+    /// [`Instance::set_reply_port`].
+    pub fn clear_reply_port(&self) {
+        *self.inner.reply_port.lock().unwrap() = None;
+    }
+
+    /// The reply port set with [`Instance::set_reply_port`], if any.
+    pub(crate) fn reply_port(&self) -> Option<PortAddr> {
+        self.inner.reply_port.lock().unwrap().clone()
+    }
+
     /// Reserve (consume) the next `count` ordering sequence numbers for
     /// the given destination without posting any messages. Subsequent
     /// normal sends to this destination pick up at `last_reserved + 1`,
@@ -3601,6 +4607,13 @@ struct InstanceCellState {
     /// Total time spent processing messages, in microseconds.
     total_processing_time_us: AtomicU64,
 
+    /// Per-handler wall and CPU time; see [`timing`].
+    timing: timing::ActorTiming,
+
+    /// The recorder of the messages handled by this actor, if any; see
+    /// [`crate::replay`].
+    message_recorder: RecorderSlot,
+
     /// Current actor work-queue depth.
     ///
     /// Two consumers of one accounting path (PD-5e): this field is
@@ -3613,6 +4626,10 @@ struct InstanceCellState {
     /// path, decremented when the actor loop receives from `work_rx`.
     queue_depth: Arc<AtomicU64>,
 
+    /// Serialized bytes of the messages in the actor's work queue,
+    /// shared with `HandlerPorts<A>`.
+    queued_bytes: Arc<QueuedBytes>,
+
     /// The log recording associated with this actor. It is used to
     /// store a 'flight record' of events while the actor is running.
     recording: Recording,
@@ -3762,6 +4779,7 @@ impl InstanceCell {
         parent: Option<InstanceCell>,
         ports: Arc<dyn Any + Send + Sync>,
         queue_depth: Arc<AtomicU64>,
+        queued_bytes: Arc<QueuedBytes>,
         inbound_ordering_snapshot: Option<
             Box<dyn Fn() -> crate::ordering::OrderingSnapshot + Send + Sync>,
         >,
@@ -3784,7 +4802,10 @@ impl InstanceCell {
                 created_at: std::time::SystemTime::now(),
                 last_message_handler: RwLock::new(None),
                 total_processing_time_us: AtomicU64::new(0),
+                timing: timing::ActorTiming::new(),
+                message_recorder: RecorderSlot::default(),
                 queue_depth,
+                queued_bytes,
                 recording: hyperactor_telemetry::recorder().record(64),
                 published_attrs: RwLock::new(None),
                 query_child_handler: RwLock::new(None),
@@ -4005,11 +5026,51 @@ impl InstanceCell {
         self.inner.total_processing_time_us.load(Ordering::SeqCst)
     }
 
+    /// A summary of this actor's handler timing.
+    pub fn timing(&self) -> timing::TimingSnapshot {
+        self.inner.timing.snapshot()
+    }
+
+    /// The recorder of the messages handled by this actor, if any.
+    pub(crate) fn message_recorder(&self) -> &RecorderSlot {
+        &self.inner.message_recorder
+    }
+
     /// Current actor work-queue depth (PD-5).
     pub fn queue_depth(&self) -> u64 {
         self.inner.queue_depth.load(Ordering::Relaxed)
     }
 
+    /// Approximate serialized bytes of the messages in the actor's
+    /// work queue.
+    pub fn queued_bytes(&self) -> u64 {
+        self.inner.queued_bytes.total.load(Ordering::Relaxed)
+    }
+
+    /// Approximate serialized bytes queued to each of the actor's
+    /// handler ports, keyed by message type. Ports with nothing queued
+    /// are omitted.
+    pub fn queued_bytes_by_port(&self) -> Vec<(&'static str, u64)> {
+        let mut ports: Vec<_> = self
+            .inner
+            .queued_bytes
+            .by_port
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().load(Ordering::Relaxed)))
+            .filter(|(_, bytes)| *bytes > 0)
+            .collect();
+        ports.sort();
+        ports
+    }
+
+    /// The actor's cap on queued message bytes, if any.
+    pub fn max_queued_bytes(&self) -> Option<u64> {
+        match self.inner.queued_bytes.limit.load(Ordering::Relaxed) {
+            0 => None,
+            limit => Some(limit),
+        }
+    }
+
     /// Stable per-instance identifier (`Uuid::now_v7`) assigned at
     /// `Instance::new` and threaded through to the cell at construction.
     pub fn instance_id(&self) -> Uuid {
@@ -4250,6 +5311,8 @@ impl Drop for InstanceCellState {
             tracing::error!("instance {} was dropped but not in proc", self.actor_id);
         }
         self.proc.inner.root_actors.remove(self.actor_id.id());
+        self.proc.inner.tenants.release(self.actor_id.id());
+        self.proc.inner.admission.release(self.actor_id.id());
     }
 }
 
@@ -4284,12 +5347,19 @@ impl WeakInstanceCell {
 /// actor.
 pub struct HandlerPorts<A: Actor> {
     ports: DashMap<TypeId, Box<dyn Any + Send + Sync + 'static>>,
+    /// Per message type, the route that takes over the handler port's
+    /// messages, if any; see [`parallel`] and [`deadline`].
+    parallel: DashMap<TypeId, Box<dyn Any + Send + Sync + 'static>>,
     bound: DashMap<Port, &'static str>,
     mailbox: Mailbox,
     workq: mpsc::UnboundedSender<SequencedEnvelope<WorkCell<A>>>,
+    checkpoints: mpsc::UnboundedSender<CheckpointRequest<A>>,
+    swaps: mpsc::UnboundedSender<SwapRequest<A>>,
     enable_buffering: bool,
     /// Per-actor queue depth (PD-5). Shared with `InstanceCellState`.
     queue_depth: Arc<AtomicU64>,
+    /// Per-actor queued message bytes. Shared with `InstanceCellState`.
+    queued_bytes: Arc<QueuedBytes>,
     /// Proc-level queue-pressure stats (PD-6 through PD-9).
     proc_stats: Arc<ProcQueueStats>,
 }
@@ -4298,17 +5368,24 @@ impl<A: Actor> HandlerPorts<A> {
     fn new(
         mailbox: Mailbox,
         workq: mpsc::UnboundedSender<SequencedEnvelope<WorkCell<A>>>,
+        checkpoints: mpsc::UnboundedSender<CheckpointRequest<A>>,
+        swaps: mpsc::UnboundedSender<SwapRequest<A>>,
         enable_buffering: bool,
         queue_depth: Arc<AtomicU64>,
+        queued_bytes: Arc<QueuedBytes>,
         proc_stats: Arc<ProcQueueStats>,
     ) -> Self {
         Self {
             ports: DashMap::new(),
+            parallel: DashMap::new(),
             bound: DashMap::new(),
             mailbox,
             workq,
+            checkpoints,
+            swaps,
             enable_buffering,
             queue_depth,
+            queued_bytes,
             proc_stats,
         }
     }
@@ -4318,6 +5395,35 @@ impl<A: Actor> HandlerPorts<A> {
     where
         A: Handler<M>,
     {
+        self.get_with(WorkCell::message)
+    }
+
+    /// Get a port for the BatchHandler<M> of actor A, handling up to
+    /// `limit` messages per batch.
+    pub(crate) fn get_batch<M: Message>(&self, limit: usize) -> PortHandle<M>
+    where
+        A: BatchHandler<M>,
+    {
+        let limit = limit.max(1);
+        self.get_with(move |type_info, headers, message, queued_bytes| {
+            WorkCell::batch_message(type_info, limit, headers, message, queued_bytes)
+        })
+    }
+
+    /// Get a port for M-typed messages, provisioning it if needed with
+    /// `work`, which creates the work item that handles each message.
+    fn get_with<M: Message>(
+        &self,
+        work: impl Fn(
+            Option<&'static TypeInfo>,
+            Flattrs,
+            M,
+            Option<QueuedBytesReservation>,
+        ) -> WorkCell<A>
+        + Send
+        + Sync
+        + 'static,
+    ) -> PortHandle<M> {
         let key = TypeId::of::<M>();
         match self.ports.entry(key) {
             Entry::Vacant(entry) => {
@@ -4337,6 +5443,9 @@ impl<A: Actor> HandlerPorts<A> {
                 let actor_id = self.mailbox.actor_addr().to_string();
                 let enqueue_depth = Arc::clone(&self.queue_depth);
                 let enqueue_proc_stats = Arc::clone(&self.proc_stats);
+                let queued_bytes = Arc::clone(&self.queued_bytes);
+                let port_bytes = queued_bytes.port(std::any::type_name::<M>());
+                let parallel = self.parallel_route::<M>();
                 // Handler-port draining holds an ingress guard while this
                 // closure runs. Therefore, the drain guarantee depends on this
                 // closure synchronously finishing all work that it admits into
@@ -4377,17 +5486,18 @@ impl<A: Actor> HandlerPorts<A> {
                         return Err(anyhow::anyhow!(error_msg));
                     }
                     let sender = headers.get(crate::mailbox::headers::SENDER_ACTOR_ID);
+                    // Exceeding the actor's queued-bytes cap rejects the
+                    // message, returning it to the sender as undeliverable.
+                    let reservation = match headers.get(crate::mailbox::headers::MESSAGE_BYTES) {
+                        Some(bytes) => Some(queued_bytes.reserve(&port_bytes, bytes)?),
+                        None => None,
+                    };
+                    let route = parallel.read().unwrap().clone();
+                    if let Some(route) = route {
+                        return route(headers, msg, reservation);
+                    }
 
-                    let work = WorkCell::new(move |actor: &mut A, instance: &Instance<A>| {
-                        Box::pin(async move {
-                            // SAFETY: we guarantee that the passed type_info is for type M.
-                            unsafe {
-                                instance
-                                    .handle_message(actor, type_info, headers, msg)
-                                    .await
-                            }
-                        })
-                    });
+                    let work = work(type_info, headers, msg, reservation);
                     // PD-5b: account the enqueue BEFORE handing the work
                     // to the queue. Otherwise the consumer can race and
                     // call `account_dequeue` before this thread accounts
@@ -4416,15 +5526,77 @@ impl<A: Actor> HandlerPorts<A> {
         }
     }
 
+    /// The slot holding the parallel handler route for M-typed messages.
+    fn parallel_route<M: Message>(&self) -> Arc<RwLock<Option<parallel::Route<M>>>> {
+        let slot = self
+            .parallel
+            .entry(TypeId::of::<M>())
+            .or_insert_with(|| Box::new(Arc::new(RwLock::new(None::<parallel::Route<M>>))));
+        Arc::clone(
+            slot.downcast_ref::<Arc<RwLock<Option<parallel::Route<M>>>>>()
+                .unwrap(),
+        )
+    }
+
+    /// Ask the actor loop to serve a checkpoint request. Returns false if
+    /// the actor loop is no longer running.
+    pub(crate) fn request_checkpoint(&self, request: CheckpointRequest<A>) -> bool {
+        self.checkpoints.send(request).is_ok()
+    }
+
+    /// Ask the actor loop to serve a hot-swap request. Returns false if
+    /// the actor loop is no longer running.
+    pub(crate) fn request_swap(&self, request: SwapRequest<A>) -> bool {
+        self.swaps.send(request).is_ok()
+    }
+
+    /// Replay checkpointed messages to their handler ports, as if they
+    /// were sent by `sender`. The messages are marked
+    /// [`REPLAYED`](crate::mailbox::headers::REPLAYED), so that their
+    /// handlers cannot post to other actors.
+    pub(crate) fn replay(&self, sender: &ActorAddr, pending: Vec<PendingMessage>) {
+        for PendingMessage {
+            mut headers,
+            version,
+            data,
+        } in pending
+        {
+            // Sequence numbers belong to the checkpointed actor's sessions.
+            headers.set(SEQ_INFO, SeqInfo::Direct);
+            headers.set(crate::mailbox::headers::REPLAYED, true);
+            let dest = self
+                .mailbox
+                .actor_addr()
+                .port_addr(Port::handler_id(data.typehash(), None));
+            self.mailbox.post(
+                MessageEnvelope::new(sender.clone(), dest, data, headers).with_version(version),
+                crate::mailbox::monitored_return_handle(),
+            );
+        }
+    }
+
     /// Bind the given message type to its handler port.
     pub fn bind<M: RemoteMessage>(&self)
     where
         A: Handler<M>,
     {
+        self.bind_with::<M>(|| self.get::<M>());
+    }
+
+    /// Bind the given message type to its handler port, handling up to
+    /// `limit` messages at a time with A's [`BatchHandler<M>`].
+    pub fn bind_batch<M: RemoteMessage>(&self, limit: usize)
+    where
+        A: BatchHandler<M>,
+    {
+        self.bind_with::<M>(|| self.get_batch::<M>(limit));
+    }
+
+    fn bind_with<M: RemoteMessage>(&self, get: impl FnOnce() -> PortHandle<M>) {
         let port = Port::handler::<M>();
         match self.bound.entry(port.clone()) {
             Entry::Vacant(entry) => {
-                let _ = self.get::<M>().bind();
+                let _ = get().bind();
                 entry.insert(M::typename());
             }
             Entry::Occupied(entry) => {
@@ -5837,6 +7009,37 @@ mod tests {
         assert_matches!(handle.await, ActorStatus::Stopped(reason) if reason == "test");
     }
 
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_close_intake() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let root = proc.spawn_with_label::<TestActor>("root", TestActor);
+        let child = TestActor::spawn_child(&client, &root).await;
+        let other = proc.spawn_with_label::<TestActor>("other", TestActor);
+
+        let cells = proc.close_intake(&[root.actor_addr().id().clone()]);
+        let mut closed: Vec<_> = cells.iter().map(|cell| cell.actor_addr().clone()).collect();
+        closed.sort();
+        let mut expected = vec![root.actor_addr().clone(), child.actor_addr().clone()];
+        expected.sort();
+        assert_eq!(closed, expected);
+
+        // Handler intake is closed for the subtree, but the actors keep running.
+        for handle in [&root, &child] {
+            let err = handle
+                .port::<TestActorMessage>()
+                .try_post(&client, TestActorMessage::Noop())
+                .unwrap_err();
+            assert_matches!(err.kind(), crate::mailbox::MailboxSenderErrorKind::Closed);
+            assert!(!handle.status().borrow().is_terminal());
+        }
+        // Actors outside the subtree are unaffected.
+        other
+            .port::<TestActorMessage>()
+            .try_post(&client, TestActorMessage::Noop())
+            .unwrap();
+    }
+
     #[async_timed_test(timeout_secs = 30)]
     async fn test_parent_failure() {
         let proc = Proc::isolated();
@@ -6949,6 +8152,194 @@ mod tests {
         );
     }
 
+    #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, typeuri::Named)]
+    struct PayloadMsg(Vec<u8>);
+
+    /// Handles each message only once a permit is added to `gate`.
+    #[derive(Debug)]
+    #[hyperactor::export(handlers = [PayloadMsg])]
+    struct GatedActor {
+        gate: Arc<tokio::sync::Semaphore>,
+    }
+
+    #[async_trait]
+    impl Actor for GatedActor {}
+
+    #[async_trait]
+    impl Handler<PayloadMsg> for GatedActor {
+        async fn handle(
+            &mut self,
+            _cx: &crate::Context<Self>,
+            _msg: PayloadMsg,
+        ) -> anyhow::Result<()> {
+            self.gate.acquire().await?.forget();
+            Ok(())
+        }
+    }
+
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_queued_bytes_accounting_and_cap() {
+        let config = hyperactor_config::global::lock();
+        let _g1 = config.override_key(config::ENABLE_DEST_ACTOR_REORDERING_BUFFER, false);
+        let _g2 = config.override_key(config::ACTOR_MAX_QUEUED_BYTES, 2500);
+
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let gate = Arc::new(tokio::sync::Semaphore::new(0));
+        let handle = proc.spawn_with_label(
+            "gated",
+            GatedActor {
+                gate: Arc::clone(&gate),
+            },
+        );
+        let _actor_ref: crate::ActorRef<GatedActor> = handle.bind();
+        let cell = proc.get_instance(handle.actor_addr()).unwrap();
+        assert_eq!(cell.max_queued_bytes(), Some(2500));
+
+        let dest = handle.actor_addr().port_addr(Port::handler::<PayloadMsg>());
+        let (return_handle, mut return_rx) = client
+            .mailbox()
+            .open_port::<Undeliverable<MessageEnvelope>>();
+        let payload = wirevalue::Any::serialize(&PayloadMsg(vec![0; 1000])).unwrap();
+        let size = payload.len() as u64;
+        let post = || {
+            proc.post(
+                MessageEnvelope::new(
+                    client.self_addr().clone(),
+                    dest.clone(),
+                    payload.clone(),
+                    Flattrs::new(),
+                ),
+                return_handle.clone(),
+            )
+        };
+
+        // The first message is dequeued, and blocks the actor.
+        post();
+        while cell.queue_depth() > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(cell.queued_bytes(), 0);
+
+        // By-value local sends are accounted at their serialized size.
+        post();
+        crate::Endpoint::post(
+            &PortRef::<PayloadMsg>::attest(dest.clone()),
+            &client,
+            PayloadMsg(vec![0; 1000]),
+        );
+        assert_eq!(cell.queued_bytes(), 2 * size);
+        assert_eq!(
+            cell.queued_bytes_by_port(),
+            vec![(std::any::type_name::<PayloadMsg>(), 2 * size)]
+        );
+
+        // A third queued message would exceed the cap.
+        post();
+        let envelope = return_rx.recv().await.unwrap().into_message().unwrap();
+        assert!(envelope.error_msg().unwrap().contains("limit"));
+        assert_eq!(
+            envelope.error_code(),
+            Some(crate::mailbox::error_code::ErrorCode::QuotaExceeded)
+        );
+        assert_eq!(cell.queue_depth(), 2);
+
+        gate.add_permits(3);
+        while cell.queue_depth() > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(cell.queued_bytes(), 0);
+        assert!(cell.queued_bytes_by_port().is_empty());
+    }
+
+    #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, typeuri::Named)]
+    struct BatchItem(u64);
+
+    #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, typeuri::Named)]
+    struct BatchBarrier;
+
+    /// Reports each batch (and each barrier, as an empty batch), then
+    /// waits for a permit from `gate`.
+    #[derive(Debug)]
+    #[hyperactor::export(BatchItem { batch = 4 }, BatchBarrier)]
+    struct BatchingActor {
+        gate: Arc<tokio::sync::Semaphore>,
+        batches: crate::PortRef<Vec<u64>>,
+    }
+
+    #[async_trait]
+    impl Actor for BatchingActor {}
+
+    #[async_trait]
+    impl crate::actor::BatchHandler<BatchItem> for BatchingActor {
+        async fn handle_batch(
+            &mut self,
+            cx: &crate::Context<Self>,
+            items: Vec<BatchItem>,
+        ) -> anyhow::Result<()> {
+            self.batches
+                .post(cx, items.into_iter().map(|BatchItem(i)| i).collect());
+            self.gate.acquire().await?.forget();
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Handler<BatchBarrier> for BatchingActor {
+        async fn handle(
+            &mut self,
+            cx: &crate::Context<Self>,
+            _barrier: BatchBarrier,
+        ) -> anyhow::Result<()> {
+            self.batches.post(cx, Vec::new());
+            self.gate.acquire().await?.forget();
+            Ok(())
+        }
+    }
+
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_batch_handler() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let (batches, mut batches_rx) = client.open_port::<Vec<u64>>();
+        let gate = Arc::new(tokio::sync::Semaphore::new(0));
+        let handle = proc.spawn_with_label(
+            "batching",
+            BatchingActor {
+                gate: Arc::clone(&gate),
+                batches: batches.bind(),
+            },
+        );
+        let actor_ref: crate::ActorRef<BatchingActor> = handle.bind();
+        let cell = proc.get_instance(handle.actor_addr()).unwrap();
+
+        // The first item is handled on its own, and blocks the actor.
+        actor_ref.post(&client, BatchItem(0));
+        assert_eq!(batches_rx.recv().await.unwrap(), vec![0]);
+
+        for i in 1..3 {
+            actor_ref.post(&client, BatchItem(i));
+        }
+        actor_ref.post(&client, BatchBarrier);
+        for i in 3..8 {
+            actor_ref.post(&client, BatchItem(i));
+        }
+        while cell.queue_depth() < 8 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Batches hold consecutive items, up to the batch size, and do
+        // not cross other messages.
+        gate.add_permits(5);
+        assert_eq!(batches_rx.recv().await.unwrap(), vec![1, 2]);
+        assert_eq!(batches_rx.recv().await.unwrap(), Vec::<u64>::new());
+        assert_eq!(batches_rx.recv().await.unwrap(), vec![3, 4, 5, 6]);
+        assert_eq!(batches_rx.recv().await.unwrap(), vec![7]);
+        while cell.queue_depth() > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
     // Test-only Named message + dedicated actor with explicit handler
     // export, so the integration test can bind the BufferTestMsg
     // handler port (`handle.bind()`) and drive ordered traffic through
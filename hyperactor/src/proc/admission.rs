@@ -0,0 +1,368 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Resource reservation and admission control for spawns.
+//!
+//! Actors declare the resources they need through
+//! [`Actor::resources`](crate::Actor::resources). A proc configured with
+//! a capacity ([`Proc::set_capacity`]) reserves each spawned actor's
+//! resources before the actor initializes, and releases them when the
+//! actor terminates. An actor whose resources do not fit in what is left
+//! of the capacity is, according to the proc's [`AdmissionPolicy`],
+//! either failed during initialization, or queued until enough resources
+//! are released.
+//!
+//! Actors that declare no resources, and actors of procs without a
+//! capacity, are always placed.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use super::Proc;
+use crate::ActorAddr;
+use crate::id::ActorId;
+
+/// The resources reserved by an actor while it runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceRequirements {
+    /// Memory, in bytes.
+    pub memory_bytes: u64,
+    /// Whole GPUs.
+    pub gpus: u64,
+    /// CPU shares, e.g. in thousandths of a core.
+    pub cpu_shares: u64,
+}
+
+impl ResourceRequirements {
+    /// Whether no resources are required.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.memory_bytes += other.memory_bytes;
+        self.gpus += other.gpus;
+        self.cpu_shares += other.cpu_shares;
+    }
+
+    fn sub(&mut self, other: &Self) {
+        self.memory_bytes -= other.memory_bytes;
+        self.gpus -= other.gpus;
+        self.cpu_shares -= other.cpu_shares;
+    }
+}
+
+/// The resources of a proc available to its actors. Resources that are
+/// `None` are not limited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcCapacity {
+    /// Memory, in bytes.
+    pub memory_bytes: Option<u64>,
+    /// Whole GPUs.
+    pub gpus: Option<u64>,
+    /// CPU shares.
+    pub cpu_shares: Option<u64>,
+}
+
+/// What to do with an actor whose resources do not fit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AdmissionPolicy {
+    /// Fail the actor during initialization.
+    #[default]
+    Reject,
+    /// Wait, before initializing the actor, until enough resources are
+    /// released. Actors whose resources exceed the proc's whole capacity
+    /// are failed.
+    Queue,
+}
+
+/// Errors admitting an actor.
+#[derive(Debug, thiserror::Error)]
+pub enum AdmissionError {
+    /// The actor's resources do not fit in what is left of the proc's
+    /// capacity.
+    #[error("insufficient {resource}: requested {requested}, {available} available")]
+    Insufficient {
+        /// The resource that does not fit.
+        resource: &'static str,
+        /// The amount requested by the actor.
+        requested: u64,
+        /// The amount left, or the whole capacity if the request can
+        /// never fit.
+        available: u64,
+    },
+}
+
+/// An actor's reservation of proc resources.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reservation {
+    /// The actor holding the reservation.
+    pub actor_addr: ActorAddr,
+    /// The reserved resources.
+    pub resources: ResourceRequirements,
+}
+
+#[derive(Default)]
+struct AdmissionState {
+    capacity: ProcCapacity,
+    policy: AdmissionPolicy,
+    reserved: ResourceRequirements,
+    reservations: BTreeMap<ActorId, Reservation>,
+}
+
+impl AdmissionState {
+    /// Check that `resources` fit in `available` of each limited
+    /// resource, where `available` computes what is left of a limit.
+    fn check(
+        &self,
+        resources: &ResourceRequirements,
+        available: impl Fn(u64, u64) -> u64,
+    ) -> Result<(), AdmissionError> {
+        let limits = [
+            (
+                "memory",
+                resources.memory_bytes,
+                self.capacity.memory_bytes,
+                self.reserved.memory_bytes,
+            ),
+            (
+                "gpus",
+                resources.gpus,
+                self.capacity.gpus,
+                self.reserved.gpus,
+            ),
+            (
+                "cpu shares",
+                resources.cpu_shares,
+                self.capacity.cpu_shares,
+                self.reserved.cpu_shares,
+            ),
+        ];
+        for (resource, requested, limit, reserved) in limits {
+            let Some(limit) = limit else {
+                continue;
+            };
+            let available = available(limit, reserved);
+            if requested > available {
+                return Err(AdmissionError::Insufficient {
+                    resource,
+                    requested,
+                    available,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The admission controller of a proc.
+#[derive(Default)]
+pub(crate) struct Admission {
+    state: Mutex<AdmissionState>,
+    /// Notified whenever resources are released or the capacity changes.
+    changed: Notify,
+}
+
+impl Admission {
+    /// Reserve `resources` for the actor, waiting for them to be
+    /// released if the policy queues actors.
+    pub(crate) async fn reserve(
+        &self,
+        actor_addr: &ActorAddr,
+        resources: ResourceRequirements,
+    ) -> Result<(), AdmissionError> {
+        if resources.is_empty() {
+            return Ok(());
+        }
+        loop {
+            // Register for notification before checking, so that a
+            // release between the check and the wait is not missed.
+            let changed = self.changed.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                // Requests that exceed the whole capacity never fit.
+                state.check(&resources, |limit, _| limit)?;
+                match state.check(&resources, |limit, reserved| limit.saturating_sub(reserved)) {
+                    Ok(()) => {
+                        state.reserved.add(&resources);
+                        state.reservations.insert(
+                            actor_addr.id().clone(),
+                            Reservation {
+                                actor_addr: actor_addr.clone(),
+                                resources,
+                            },
+                        );
+                        return Ok(());
+                    }
+                    Err(err) if state.policy == AdmissionPolicy::Reject => return Err(err),
+                    Err(err) => {
+                        tracing::info!(
+                            actor_id = %actor_addr,
+                            "queueing actor until resources are released: {}",
+                            err
+                        );
+                    }
+                }
+            }
+            changed.await;
+        }
+    }
+
+    /// Release the reservation of a terminated actor, if any.
+    pub(crate) fn release(&self, actor_id: &ActorId) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(reservation) = state.reservations.remove(actor_id) {
+            state.reserved.sub(&reservation.resources);
+            drop(state);
+            self.changed.notify_waiters();
+        }
+    }
+}
+
+impl Proc {
+    /// Limit the resources reserved by this proc's actors to `capacity`.
+    /// Actors that do not fit are handled according to `policy`. See the
+    /// [module documentation](self) for details.
+    ///
+    /// Reservations already held are kept, even if they exceed the new
+    /// capacity.
+    pub fn set_capacity(&self, capacity: ProcCapacity, policy: AdmissionPolicy) {
+        let admission = &self.state().admission;
+        {
+            let mut state = admission.state.lock().unwrap();
+            state.capacity = capacity;
+            state.policy = policy;
+        }
+        admission.changed.notify_waiters();
+    }
+
+    /// This proc's capacity, and its admission policy.
+    pub fn capacity(&self) -> (ProcCapacity, AdmissionPolicy) {
+        let state = self.state().admission.state.lock().unwrap();
+        (state.capacity, state.policy)
+    }
+
+    /// The current reservations of this proc's actors.
+    pub fn reservations(&self) -> Vec<Reservation> {
+        let state = self.state().admission.state.lock().unwrap();
+        state.reservations.values().cloned().collect()
+    }
+
+    /// The total of the current reservations of this proc's actors.
+    pub fn reserved(&self) -> ResourceRequirements {
+        self.state().admission.state.lock().unwrap().reserved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::assert_matches;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::Actor;
+    use crate::actor::ActorStatus;
+    use crate::testing::proc_supervison::ProcSupervisionCoordinator;
+
+    #[derive(Debug)]
+    struct GpuActor(u64);
+
+    impl Actor for GpuActor {
+        fn resources(&self) -> ResourceRequirements {
+            ResourceRequirements {
+                gpus: self.0,
+                ..Default::default()
+            }
+        }
+    }
+
+    async fn idle(actor: &crate::ActorHandle<GpuActor>) {
+        actor
+            .status()
+            .wait_for(|status| matches!(status, ActorStatus::Idle))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reject() {
+        let proc = Proc::isolated();
+        let (_reported, _coordinator) = ProcSupervisionCoordinator::set(&proc).await.unwrap();
+        proc.set_capacity(
+            ProcCapacity {
+                gpus: Some(2),
+                ..Default::default()
+            },
+            AdmissionPolicy::Reject,
+        );
+
+        let first = proc.spawn(GpuActor(2));
+        idle(&first).await;
+        assert_eq!(
+            proc.reservations(),
+            vec![Reservation {
+                actor_addr: first.actor_addr().clone(),
+                resources: ResourceRequirements {
+                    gpus: 2,
+                    ..Default::default()
+                },
+            }]
+        );
+
+        // Actors that declare no resources are always placed.
+        let free = proc.spawn(GpuActor(0));
+        idle(&free).await;
+
+        let second = proc.spawn(GpuActor(1));
+        assert_matches!(
+            second.await,
+            ActorStatus::Failed(err) if err.to_string().contains("insufficient gpus")
+        );
+
+        first.drain_and_stop("test").unwrap();
+        first.clone().await;
+        assert!(proc.reservations().is_empty());
+        let third = proc.spawn(GpuActor(1));
+        idle(&third).await;
+    }
+
+    #[tokio::test]
+    async fn test_queue() {
+        let proc = Proc::isolated();
+        let (_reported, _coordinator) = ProcSupervisionCoordinator::set(&proc).await.unwrap();
+        proc.set_capacity(
+            ProcCapacity {
+                gpus: Some(2),
+                ..Default::default()
+            },
+            AdmissionPolicy::Queue,
+        );
+
+        let first = proc.spawn(GpuActor(2));
+        idle(&first).await;
+        let queued = proc.spawn(GpuActor(1));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_matches!(*queued.status().borrow(), ActorStatus::Initializing);
+
+        // Releasing the first actor's reservation places the queued one.
+        first.drain_and_stop("test").unwrap();
+        idle(&queued).await;
+        assert_eq!(proc.reservations().len(), 1);
+
+        // Requests that can never fit are rejected.
+        let oversized = proc.spawn(GpuActor(3));
+        assert_matches!(
+            oversized.await,
+            ActorStatus::Failed(err) if err.to_string().contains("3, 2 available")
+        );
+    }
+}
@@ -0,0 +1,198 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Earliest-deadline-first handler ports.
+//!
+//! With [`Instance::handle_by_deadline`], an actor's M-typed messages
+//! are queued by their [`DELIVERY_DEADLINE`] header, as on a port opened
+//! with [`crate::Mailbox::open_deadline_port`]. Each message still
+//! enqueues one work item on the actor's work queue, so that the actor
+//! handles as many M-typed messages as before, interleaved with its
+//! other messages; but the item handles whichever queued M-typed message
+//! is most urgent when the actor gets to it, and messages whose deadline
+//! has passed are dropped instead of handled.
+//!
+//! Messages queued on a deadline port cannot be checkpointed.
+
+use std::any::Any;
+use std::any::TypeId;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use wirevalue::TypeInfo;
+
+use super::HandlerMessage;
+use super::Instance;
+use super::QueuedMessage;
+use super::Work;
+use super::WorkCell;
+use super::parallel::Route;
+use crate::Actor;
+use crate::Handler;
+use crate::Message;
+use crate::checkpoint::CheckpointError;
+use crate::checkpoint::PendingMessage;
+use crate::mailbox::deadline::DeadlineQueue;
+use crate::mailbox::headers::DELIVERY_DEADLINE;
+
+/// Create the route that queues M-typed messages for `instance` earliest
+/// deadline first.
+pub(super) fn route<A, M>(instance: &Instance<A>) -> Route<M>
+where
+    A: Handler<M>,
+    M: Message,
+{
+    let queue = Arc::new(DeadlineQueue::new(std::any::type_name::<M>()));
+    let type_info = TypeInfo::get_by_typeid(TypeId::of::<M>());
+    // As with parallel routes, the route must not keep the instance
+    // alive.
+    let weak = Arc::downgrade(&instance.inner);
+    Arc::new(move |headers, message, queued_bytes| {
+        let Some(inner) = weak.upgrade() else {
+            anyhow::bail!("actor is no longer running");
+        };
+        let deadline = headers.get(DELIVERY_DEADLINE);
+        let message = HandlerMessage {
+            type_info,
+            headers,
+            message,
+            queued_bytes,
+        };
+        if !queue.push(deadline, message) {
+            return Ok(());
+        }
+        let work = WorkCell(Work::Message(Box::new(DeadlineMessage {
+            queue: Arc::clone(&queue),
+        })));
+        Instance { inner }.enqueue_runtime_work(work)?;
+        Ok(())
+    })
+}
+
+/// Handles the most urgent M-typed message queued when it is dequeued.
+struct DeadlineMessage<M: Message> {
+    queue: Arc<DeadlineQueue<HandlerMessage<M>>>,
+}
+
+impl<A: Handler<M>, M: Message> QueuedMessage<A> for DeadlineMessage<M> {
+    fn handle<'a>(
+        self: Box<Self>,
+        actor: &'a mut A,
+        instance: &'a Instance<A>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + Send + 'a>> {
+        match self.queue.pop(SystemTime::now()) {
+            Some(message) => QueuedMessage::<A>::handle(Box::new(message), actor, instance),
+            // The messages expired while queued.
+            None => Box::pin(async { Ok(()) }),
+        }
+    }
+
+    fn pending(&self) -> Result<PendingMessage, CheckpointError> {
+        Err(CheckpointError::DeadlinePort(std::any::type_name::<M>()))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use hyperactor_config::Flattrs;
+    use timed_test::async_timed_test;
+    use tokio::sync::Notify;
+
+    use super::*;
+    use crate::Context;
+    use crate::Endpoint as _;
+    use crate::Proc;
+    use crate::actor::ActorStatus;
+    use crate::endpoint::RemoteEndpoint;
+    use crate::mailbox::PortHandle;
+
+    /// Blocks the actor until the gate is opened.
+    #[derive(Debug)]
+    struct Block(Arc<Notify>);
+
+    #[derive(Debug)]
+    struct UrgentActor {
+        handled: PortHandle<u64>,
+    }
+
+    #[async_trait]
+    impl Actor for UrgentActor {
+        async fn init(&mut self, this: &Instance<Self>) -> anyhow::Result<()> {
+            this.handle_by_deadline::<u64>();
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Handler<Block> for UrgentActor {
+        async fn handle(&mut self, _cx: &Context<Self>, Block(gate): Block) -> anyhow::Result<()> {
+            gate.notified().await;
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Handler<u64> for UrgentActor {
+        async fn handle(&mut self, cx: &Context<Self>, message: u64) -> anyhow::Result<()> {
+            self.handled.post(cx, message);
+            Ok(())
+        }
+    }
+
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_handle_by_deadline() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let (handled, mut handled_rx) = client.open_port::<u64>();
+        let handle = proc.spawn_with_label("urgent", UrgentActor { handled });
+        handle
+            .status()
+            .wait_for(|status| matches!(*status, ActorStatus::Idle))
+            .await
+            .unwrap();
+
+        // The messages queue up while the actor is blocked.
+        let gate = Arc::new(Notify::new());
+        handle.post(&client, Block(Arc::clone(&gate)));
+        let port_ref = handle.port::<u64>().bind();
+        let now = SystemTime::now();
+        let post = |deadline: Option<SystemTime>, message: u64| {
+            let mut headers = Flattrs::new();
+            if let Some(deadline) = deadline {
+                headers.set(DELIVERY_DEADLINE, deadline);
+            }
+            RemoteEndpoint::post_with_headers(&port_ref, &client, headers, message);
+        };
+        post(None, 0);
+        post(Some(now + Duration::from_secs(60)), 1);
+        post(Some(now + Duration::from_secs(10)), 2);
+        // Already expired on arrival.
+        post(Some(now - Duration::from_secs(1)), 3);
+        post(Some(now + Duration::from_secs(60)), 4);
+        gate.notify_one();
+
+        let mut received = Vec::new();
+        for _ in 0..4 {
+            received.push(handled_rx.recv().await.unwrap());
+        }
+        assert_eq!(received, vec![2, 1, 4, 0]);
+    }
+}
@@ -0,0 +1,723 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Parallel message handling.
+//!
+//! An actor handles the messages in its work queue one at a time, since
+//! each handler has exclusive access to the actor. Handlers that need no
+//! such access can instead implement [`ParallelHandler`], and an actor
+//! can opt in to using one with [`Instance::handle_in_parallel`]. The
+//! messages for its port are then handled by a pool shared by all of the
+//! proc's actors, concurrently with each other and with the actor's work
+//! queue.
+//!
+//! Each message is handled on its own task, so a handler that awaits
+//! holds up no other. The number of handlers running at once is bounded
+//! by [`crate::config::PARALLEL_HANDLER_CONCURRENCY`]; the others wait
+//! for one to finish. Handlers with [`ParallelHandler::ORDERED`] set
+//! handle their port's messages one at a time, in delivery order; other
+//! handlers' messages may be handled in any order.
+//!
+//! Alternatively, with [`Instance::handle_on_dedicated_queue`], a port's
+//! messages are queued separately and handled by a task dedicated to the
+//! port, one at a time and in delivery order. A high rate of messages on
+//! such a port (e.g., telemetry) then delays neither the actor's work
+//! queue nor other actors' parallel handlers.
+//!
+//! Each handler's [`Context`] carries the headers of its own message,
+//! so a message's cancel token, and whether it was replayed or mirrored
+//! (see [`crate::replay`]), apply only to what its handler posts, not to
+//! messages handled concurrently.
+//!
+//! A parallel handler's error (or panic) fails the actor, as if returned
+//! by one of its handlers. Messages that are still queued in the pool,
+//! or in a dedicated queue, when the actor stops are dropped.
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::Weak;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use futures::FutureExt;
+use hyperactor_config::Flattrs;
+use tokio::sync::Semaphore;
+use tokio::sync::mpsc;
+
+use super::Context;
+use super::Instance;
+use super::QueuedBytesReservation;
+use super::WorkCell;
+use crate::Actor;
+use crate::ActorAddr;
+use crate::Message;
+use crate::config;
+use crate::panic_handler;
+
+/// A handler for `M`-typed messages sent to actor `A` that needs no
+/// exclusive access to the actor, so that the messages can be handled
+/// in parallel. See [`crate::proc::parallel`].
+#[async_trait]
+pub trait ParallelHandler<A: Actor, M>: Send + Sync + 'static {
+    /// Whether the messages must be handled one at a time, in the order
+    /// in which they were delivered to the actor.
+    const ORDERED: bool = false;
+
+    /// Handle the next M-typed message.
+    async fn handle(&self, cx: &Context<A>, message: M) -> Result<(), anyhow::Error>;
+}
+
+/// Routes a message for a handler port to the parallel pool.
+pub(super) type Route<M> =
+    Arc<dyn Fn(Flattrs, M, Option<QueuedBytesReservation>) -> anyhow::Result<()> + Send + Sync>;
+
+/// Create the route for messages handled by `handler` on behalf of
+/// `instance`.
+pub(super) fn route<A, M, H>(instance: &Instance<A>, handler: H) -> Route<M>
+where
+    A: Actor,
+    M: Message,
+    H: ParallelHandler<A, M>,
+{
+    let handler = Arc::new(handler);
+    // The route is owned by the instance's ports; a strong reference
+    // would keep the instance alive forever.
+    let weak = Arc::downgrade(&instance.inner);
+    let pool = Arc::clone(instance.proc().parallel_pool());
+    let key = H::ORDERED.then(|| (instance.self_addr().clone(), TypeId::of::<M>()));
+    Arc::new(move |headers, message, queued_bytes| {
+        let Some(inner) = weak.upgrade() else {
+            anyhow::bail!("actor is no longer running");
+        };
+        let instance = Instance { inner };
+        let handler = Arc::clone(&handler);
+        pool.submit(
+            key.clone(),
+            Box::pin(async move {
+                drop(queued_bytes);
+                instance.handle_parallel(&*handler, headers, message).await;
+            }),
+        );
+        Ok(())
+    })
+}
+
+/// Create the route for messages handled by `handler` on behalf of
+/// `instance` on a dedicated queue, and spawn the task that consumes it.
+pub(super) fn dedicated_route<A, M, H>(instance: &Instance<A>, handler: H) -> Route<M>
+where
+    A: Actor,
+    M: Message,
+    H: ParallelHandler<A, M>,
+{
+    let (queue, mut receiver) =
+        mpsc::unbounded_channel::<(Flattrs, M, Option<QueuedBytesReservation>)>();
+    // As in `route`, the task must not keep the instance alive. The task
+    // exits once the route, and thus the queue's sender, is dropped with
+    // the instance.
+    let weak = Arc::downgrade(&instance.inner);
+    tokio::spawn(async move {
+        while let Some((headers, message, queued_bytes)) = receiver.recv().await {
+            drop(queued_bytes);
+            let Some(inner) = weak.upgrade() else {
+                return;
+            };
+            let instance = Instance { inner };
+            instance.handle_parallel(&handler, headers, message).await;
+        }
+    });
+    Arc::new(move |headers, message, queued_bytes| {
+        queue
+            .send((headers, message, queued_bytes))
+            .map_err(|_| anyhow::anyhow!("actor is no longer running"))
+    })
+}
+
+impl<A: Actor> Instance<A> {
+    async fn handle_parallel<M, H>(&self, handler: &H, headers: Flattrs, message: M)
+    where
+        M: Message,
+        H: ParallelHandler<A, M>,
+    {
+        if self.is_terminal() || self.is_stopping() {
+            return;
+        }
+        let context = Context::new(self, headers);
+        let start = Instant::now();
+        let (result, cpu) = self
+            .inner
+            .cell
+            .inner
+            .timing
+            .time(
+                AssertUnwindSafe(
+                    self.inner
+                        .proc
+                        .with_current(handler.handle(&context, message)),
+                )
+                .catch_unwind(),
+            )
+            .await;
+        let elapsed = start.elapsed();
+        self.inner
+            .cell
+            .inner
+            .total_processing_time_us
+            .fetch_add(elapsed.as_micros() as u64, Ordering::SeqCst);
+        self.record_timing(elapsed, cpu);
+        let err = match result {
+            Ok(Ok(())) => return,
+            Ok(Err(err)) => err,
+            Err(_) => {
+                let panic_info = panic_handler::take_panic_info()
+                    .map(|info| info.to_string())
+                    .unwrap_or_else(|e| format!("Cannot take backtrace due to: {:?}", e));
+                anyhow::anyhow!("parallel handler panicked: {}", panic_info)
+            }
+        };
+        // Fail the actor from its own loop, exactly as if one of its
+        // handlers had returned the error.
+        let work = WorkCell::new(move |_actor: &mut A, _instance: &Instance<A>| {
+            Box::pin(async move { Err(err) })
+        });
+        if let Err(err) = self.enqueue_runtime_work(work) {
+            tracing::error!(
+                actor_id = %self.self_addr(),
+                "failed to report parallel handler failure: {}",
+                err
+            );
+        }
+    }
+}
+
+type Job = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Identifies the jobs that must run in order: those for one actor's
+/// handler of one message type.
+pub(crate) type OrderKey = (ActorAddr, TypeId);
+
+/// A pool that runs each job on its own task, with a bounded number of
+/// jobs running at once.
+pub(crate) struct ParallelPool {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    /// A permit is held by each running job.
+    permits: Arc<Semaphore>,
+    /// Ordered keys with a job in the pool, and the jobs waiting
+    /// behind it.
+    ordered: Mutex<HashMap<OrderKey, VecDeque<Job>>>,
+}
+
+impl ParallelPool {
+    /// Create a pool whose jobs run on the current runtime.
+    pub(crate) fn new() -> Self {
+        Self::with_concurrency(hyperactor_config::global::get(
+            config::PARALLEL_HANDLER_CONCURRENCY,
+        ))
+    }
+
+    fn with_concurrency(concurrency: usize) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                permits: Arc::new(Semaphore::new(concurrency.max(1))),
+                ordered: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Run `job` on the pool. Jobs submitted with the same key run one at
+    /// a time, in submission order.
+    pub(crate) fn submit(&self, key: Option<OrderKey>, job: Job) {
+        let Some(key) = key else {
+            let permits = Arc::clone(&self.shared.permits);
+            tokio::spawn(async move {
+                let Ok(_permit) = permits.acquire().await else {
+                    return;
+                };
+                job.await;
+            });
+            return;
+        };
+        {
+            let mut ordered = self.shared.ordered.lock().unwrap();
+            if let Some(waiting) = ordered.get_mut(&key) {
+                waiting.push_back(job);
+                return;
+            }
+            ordered.insert(key.clone(), VecDeque::new());
+        }
+        tokio::spawn(run_ordered(
+            Arc::downgrade(&self.shared),
+            Arc::clone(&self.shared.permits),
+            key,
+            job,
+        ));
+    }
+}
+
+impl Drop for ParallelPool {
+    fn drop(&mut self) {
+        // Jobs waiting for a permit are dropped.
+        self.shared.permits.close();
+    }
+}
+
+/// Run `job`, followed by the jobs queued behind it with the same key.
+/// Each job holds a permit only while it runs, so that a long run of
+/// ordered jobs does not hold one throughout.
+async fn run_ordered(shared: Weak<Shared>, permits: Arc<Semaphore>, key: OrderKey, mut job: Job) {
+    loop {
+        {
+            let Ok(_permit) = permits.acquire().await else {
+                return;
+            };
+            job.await;
+        }
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        let mut ordered = shared.ordered.lock().unwrap();
+        let Some(next) = ordered.get_mut(&key).and_then(VecDeque::pop_front) else {
+            ordered.remove(&key);
+            return;
+        };
+        job = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::assert_matches;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    use serde::Deserialize;
+    use serde::Serialize;
+    use timed_test::async_timed_test;
+    use tokio::sync::Barrier;
+    use tokio::sync::Notify;
+    use tokio::sync::mpsc;
+    use typeuri::Named;
+
+    use super::*;
+    use crate as hyperactor;
+    use crate::ActorHandle;
+    use crate::ActorRef;
+    use crate::Endpoint as _;
+    use crate::Handler;
+    use crate::PortRef;
+    use crate::Proc;
+    use crate::RemoteEndpoint;
+    use crate::actor::ActorStatus;
+    use crate::mailbox::PortHandle;
+    use crate::mailbox::headers::MIRRORED;
+    use crate::testing::ids::test_actor_id;
+    use crate::testing::proc_supervison::ProcSupervisionCoordinator;
+
+    /// Completes only once `barrier` is reached by all of its parties.
+    #[derive(Debug)]
+    struct Meet {
+        barrier: Arc<Barrier>,
+        done: PortHandle<()>,
+    }
+
+    struct MeetHandler;
+
+    #[async_trait]
+    impl ParallelHandler<MeetActor, Meet> for MeetHandler {
+        async fn handle(&self, cx: &Context<MeetActor>, message: Meet) -> anyhow::Result<()> {
+            message.barrier.wait().await;
+            message.done.post(cx, ());
+            Ok(())
+        }
+    }
+
+    #[derive(Debug)]
+    struct Fail;
+
+    struct FailHandler;
+
+    #[async_trait]
+    impl ParallelHandler<MeetActor, Fail> for FailHandler {
+        async fn handle(&self, _cx: &Context<MeetActor>, _message: Fail) -> anyhow::Result<()> {
+            anyhow::bail!("parallel failure")
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct MeetActor;
+
+    #[async_trait]
+    impl Actor for MeetActor {
+        async fn init(&mut self, this: &Instance<Self>) -> anyhow::Result<()> {
+            this.handle_in_parallel(MeetHandler);
+            this.handle_in_parallel(FailHandler);
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Handler<Meet> for MeetActor {
+        async fn handle(&mut self, cx: &Context<Self>, message: Meet) -> anyhow::Result<()> {
+            MeetHandler.handle(cx, message).await
+        }
+    }
+
+    #[async_trait]
+    impl Handler<Fail> for MeetActor {
+        async fn handle(&mut self, cx: &Context<Self>, message: Fail) -> anyhow::Result<()> {
+            FailHandler.handle(cx, message).await
+        }
+    }
+
+    async fn spawn_meet_actor(proc: &Proc) -> ActorHandle<MeetActor> {
+        let handle = proc.spawn_with_label("meet", MeetActor);
+        handle
+            .status()
+            .wait_for(|status| matches!(*status, ActorStatus::Idle))
+            .await
+            .unwrap();
+        handle
+    }
+
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_actor_handles_messages_in_parallel() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(config::PARALLEL_HANDLER_CONCURRENCY, 4);
+
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let handle = spawn_meet_actor(&proc).await;
+
+        // Handled serially, the first message would block the others
+        // forever.
+        let barrier = Arc::new(Barrier::new(4));
+        let (done, mut done_rx) = client.open_port::<()>();
+        for _ in 0..4 {
+            handle.post(
+                &client,
+                Meet {
+                    barrier: Arc::clone(&barrier),
+                    done: done.clone(),
+                },
+            );
+        }
+        for _ in 0..4 {
+            done_rx.recv().await.unwrap();
+        }
+        assert_matches!(*handle.status().borrow(), ActorStatus::Idle);
+    }
+
+    /// Telemetry-like messages, handled on a dedicated queue. The first
+    /// message with a gate is not handled until the gate is opened.
+    #[derive(Debug)]
+    struct Ingest {
+        seq: u64,
+        gate: Option<Arc<Notify>>,
+        done: PortHandle<u64>,
+    }
+
+    struct IngestHandler;
+
+    #[async_trait]
+    impl ParallelHandler<IngestActor, Ingest> for IngestHandler {
+        async fn handle(&self, cx: &Context<IngestActor>, message: Ingest) -> anyhow::Result<()> {
+            if let Some(gate) = message.gate {
+                gate.notified().await;
+            }
+            message.done.post(cx, message.seq);
+            Ok(())
+        }
+    }
+
+    #[derive(Debug)]
+    struct Command(PortHandle<()>);
+
+    #[derive(Debug, Default)]
+    struct IngestActor;
+
+    #[async_trait]
+    impl Actor for IngestActor {
+        async fn init(&mut self, this: &Instance<Self>) -> anyhow::Result<()> {
+            this.handle_on_dedicated_queue(IngestHandler);
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Handler<Ingest> for IngestActor {
+        async fn handle(&mut self, cx: &Context<Self>, message: Ingest) -> anyhow::Result<()> {
+            IngestHandler.handle(cx, message).await
+        }
+    }
+
+    #[async_trait]
+    impl Handler<Command> for IngestActor {
+        async fn handle(
+            &mut self,
+            cx: &Context<Self>,
+            Command(reply): Command,
+        ) -> anyhow::Result<()> {
+            reply.post(cx, ());
+            Ok(())
+        }
+    }
+
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_dedicated_queue_does_not_block_work_queue() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let handle = proc.spawn_with_label("ingest", IngestActor);
+        handle
+            .status()
+            .wait_for(|status| matches!(*status, ActorStatus::Idle))
+            .await
+            .unwrap();
+
+        let gate = Arc::new(Notify::new());
+        let (done, mut done_rx) = client.open_port::<u64>();
+        for seq in 0..5 {
+            handle.post(
+                &client,
+                Ingest {
+                    seq,
+                    gate: (seq == 0).then(|| Arc::clone(&gate)),
+                    done: done.clone(),
+                },
+            );
+        }
+
+        // The blocked ingest queue does not delay commands.
+        let (reply, mut reply_rx) = client.open_port::<()>();
+        handle.post(&client, Command(reply));
+        reply_rx.recv().await.unwrap();
+
+        gate.notify_one();
+        for seq in 0..5 {
+            assert_eq!(done_rx.recv().await.unwrap(), seq);
+        }
+        assert_matches!(*handle.status().borrow(), ActorStatus::Idle);
+    }
+
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_parallel_handler_failure_fails_actor() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let (_reported, _coordinator) = ProcSupervisionCoordinator::set(&proc).await.unwrap();
+        let handle = spawn_meet_actor(&proc).await;
+
+        handle.post(&client, Fail);
+        assert_matches!(
+            handle.await,
+            ActorStatus::Failed(err) if err.to_string().contains("parallel failure")
+        );
+    }
+
+    /// Handled on the work queue.
+    #[derive(Debug, Clone, Serialize, Deserialize, Named)]
+    struct Echo(u64, PortRef<u64>);
+
+    /// Handled in parallel.
+    #[derive(Debug, Clone, Serialize, Deserialize, Named)]
+    struct ParallelEcho(u64, PortRef<u64>);
+
+    /// Replies to [`Echo`] and [`ParallelEcho`] messages with their
+    /// number. Each handler first waits for another to reach the
+    /// barrier, so an `Echo` and a `ParallelEcho` are handled at the
+    /// same time.
+    #[derive(Debug)]
+    #[hyperactor::export(handlers = [Echo, ParallelEcho])]
+    struct EchoActor {
+        barrier: Arc<Barrier>,
+        handled: mpsc::UnboundedSender<u64>,
+    }
+
+    async fn echo(
+        cx: &Context<'_, EchoActor>,
+        barrier: &Barrier,
+        handled: &mpsc::UnboundedSender<u64>,
+        n: u64,
+        reply: PortRef<u64>,
+    ) -> anyhow::Result<()> {
+        barrier.wait().await;
+        reply.post(cx, n);
+        handled.send(n)?;
+        Ok(())
+    }
+
+    struct ParallelEchoHandler {
+        barrier: Arc<Barrier>,
+        handled: mpsc::UnboundedSender<u64>,
+    }
+
+    #[async_trait]
+    impl ParallelHandler<EchoActor, ParallelEcho> for ParallelEchoHandler {
+        async fn handle(
+            &self,
+            cx: &Context<EchoActor>,
+            ParallelEcho(n, reply): ParallelEcho,
+        ) -> anyhow::Result<()> {
+            echo(cx, &self.barrier, &self.handled, n, reply).await
+        }
+    }
+
+    #[async_trait]
+    impl Actor for EchoActor {
+        async fn init(&mut self, this: &Instance<Self>) -> anyhow::Result<()> {
+            this.handle_in_parallel(ParallelEchoHandler {
+                barrier: Arc::clone(&self.barrier),
+                handled: self.handled.clone(),
+            });
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Handler<Echo> for EchoActor {
+        async fn handle(&mut self, cx: &Context<Self>, Echo(n, reply): Echo) -> anyhow::Result<()> {
+            echo(cx, &self.barrier, &self.handled, n, reply).await
+        }
+    }
+
+    #[async_trait]
+    impl Handler<ParallelEcho> for EchoActor {
+        async fn handle(
+            &mut self,
+            cx: &Context<Self>,
+            ParallelEcho(n, reply): ParallelEcho,
+        ) -> anyhow::Result<()> {
+            echo(cx, &self.barrier, &self.handled, n, reply).await
+        }
+    }
+
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_mirrored_and_live_messages_handled_concurrently() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let (handled, mut handled_rx) = mpsc::unbounded_channel();
+        let handle = proc.spawn_with_label(
+            "echo",
+            EchoActor {
+                barrier: Arc::new(Barrier::new(2)),
+                handled,
+            },
+        );
+        handle
+            .status()
+            .wait_for(|status| matches!(*status, ActorStatus::Idle))
+            .await
+            .unwrap();
+        let actor: ActorRef<EchoActor> = handle.bind();
+        let (reply, mut reply_rx) = client.open_port::<u64>();
+        let mut mirrored = Flattrs::new();
+        mirrored.set(MIRRORED, false);
+
+        // A mirrored message on the work queue and a live one in
+        // parallel, then the other way around. Only the replies to the
+        // live messages are delivered.
+        RemoteEndpoint::post_with_headers(
+            &actor.port::<Echo>(),
+            &client,
+            mirrored.clone(),
+            Echo(0, reply.bind()),
+        );
+        actor
+            .port::<ParallelEcho>()
+            .post(&client, ParallelEcho(1, reply.bind()));
+        let mut handled = vec![
+            handled_rx.recv().await.unwrap(),
+            handled_rx.recv().await.unwrap(),
+        ];
+        actor.port::<Echo>().post(&client, Echo(2, reply.bind()));
+        RemoteEndpoint::post_with_headers(
+            &actor.port::<ParallelEcho>(),
+            &client,
+            mirrored,
+            ParallelEcho(3, reply.bind()),
+        );
+        handled.push(handled_rx.recv().await.unwrap());
+        handled.push(handled_rx.recv().await.unwrap());
+        handled.sort();
+        assert_eq!(handled, vec![0, 1, 2, 3]);
+
+        let mut replies = Vec::new();
+        while let Some(n) = reply_rx.try_recv().unwrap() {
+            replies.push(n);
+        }
+        replies.sort();
+        assert_eq!(replies, vec![1, 2]);
+    }
+
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_concurrency_is_bounded() {
+        let pool = ParallelPool::with_concurrency(2);
+        let gate = Arc::new(Semaphore::new(0));
+        let started = Arc::new(AtomicUsize::new(0));
+        let (done_tx, mut done_rx) = mpsc::unbounded_channel();
+        for _ in 0..3 {
+            let gate = Arc::clone(&gate);
+            let started = Arc::clone(&started);
+            let done_tx = done_tx.clone();
+            pool.submit(
+                None,
+                Box::pin(async move {
+                    started.fetch_add(1, Ordering::SeqCst);
+                    gate.acquire().await.unwrap().forget();
+                    done_tx.send(()).unwrap();
+                }),
+            );
+        }
+
+        // Two jobs wait at the gate; the third waits for one of them.
+        while started.load(Ordering::SeqCst) < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+
+        gate.add_permits(3);
+        for _ in 0..3 {
+            done_rx.recv().await.unwrap();
+        }
+        assert_eq!(started.load(Ordering::SeqCst), 3);
+    }
+
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_ordered_jobs_run_in_order() {
+        let pool = ParallelPool::with_concurrency(4);
+        let key = (test_actor_id("proc_0", "actor"), TypeId::of::<u64>());
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for i in 0..20u64 {
+            let tx = tx.clone();
+            pool.submit(
+                Some(key.clone()),
+                Box::pin(async move {
+                    // Later jobs finish faster, unless they are ordered.
+                    tokio::time::sleep(Duration::from_millis(20 - i)).await;
+                    tx.send(i).unwrap();
+                }),
+            );
+        }
+        for i in 0..20 {
+            assert_eq!(rx.recv().await.unwrap(), i);
+        }
+        // The key is released once its last job completes.
+        while !pool.shared.ordered.lock().unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
@@ -0,0 +1,543 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Isolation and quotas for worlds sharing a proc.
+//!
+//! A proc that serves several meshes (for example, a shared utility
+//! proc) can host each mesh's actors in a separate tenant. Tenants are
+//! created with [`Proc::create_tenant`], and root actors are spawned into
+//! them with [`Proc::spawn_in_tenant`]; child actors join the tenant of
+//! their parent. The proc then:
+//!
+//! - routes each tenant's local messages only to its own actors and to
+//!   actors outside of any tenant (the proc's system actors). Messages
+//!   to another tenant's actors are returned as if the actor did not
+//!   exist;
+//! - fails actors that would exceed [`TenantQuota::max_actors`] during
+//!   initialization;
+//! - returns messages to a tenant whose actors together have
+//!   [`TenantQuota::max_queued_messages`] messages, or
+//!   [`TenantQuota::max_queued_bytes`] bytes, queued; and
+//! - returns messages sent to other procs by a tenant's actors in
+//!   excess of [`TenantQuota::max_egress_bytes_per_sec`].
+//!
+//! Actors outside of any tenant are not subject to any of these, and
+//! procs in which no tenant was created do not look up tenants at all.
+
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
+
+use super::Proc;
+use super::QueuedBytes;
+use crate::Actor;
+use crate::ActorHandle;
+use crate::id::ActorId;
+use crate::mailbox::DeliveryFailure;
+use crate::mailbox::InvalidReference;
+use crate::mailbox::InvalidReferenceReason;
+use crate::mailbox::MessageEnvelope;
+use crate::mailbox::TransportFailure;
+use crate::mailbox::TransportFailureReason;
+use crate::mailbox::UndeliverableReason;
+
+/// Resource limits for the actors of a tenant. Limits that are `None`
+/// are not enforced.
+#[derive(Debug, Clone, Default)]
+pub struct TenantQuota {
+    /// The maximum number of live actors.
+    pub max_actors: Option<usize>,
+    /// The maximum number of messages queued across the tenant's
+    /// actors. Messages that arrive while the tenant is at its limit are
+    /// returned to their senders.
+    pub max_queued_messages: Option<u64>,
+    /// The maximum number of message bytes queued across the tenant's
+    /// actors, as accounted by each actor's queued bytes (see
+    /// [`crate::config::ACTOR_MAX_QUEUED_BYTES`]). Messages that arrive
+    /// while the tenant is at its limit are returned to their senders.
+    pub max_queued_bytes: Option<u64>,
+    /// The maximum rate, in payload bytes per second, at which the
+    /// tenant's actors may send messages to other procs. Up to one
+    /// second's worth of bytes may be sent in a burst.
+    pub max_egress_bytes_per_sec: Option<u64>,
+}
+
+/// Errors managing tenants.
+#[derive(Debug, thiserror::Error)]
+pub enum TenantError {
+    /// A tenant with the name already exists in the proc.
+    #[error("tenant {0} already exists")]
+    Exists(String),
+
+    /// No tenant with the name exists in the proc.
+    #[error("unknown tenant {0}")]
+    Unknown(String),
+
+    /// The tenant is at its [`TenantQuota::max_actors`] limit.
+    #[error("tenant {tenant} is at its limit of {limit} actors")]
+    ActorLimit {
+        /// The tenant's name.
+        tenant: String,
+        /// The tenant's actor limit.
+        limit: usize,
+    },
+}
+
+struct Tenant {
+    name: String,
+    quota: TenantQuota,
+    num_actors: AtomicUsize,
+    /// The queue depth and queued bytes of each admitted actor.
+    queues: DashMap<ActorId, (Arc<AtomicU64>, Arc<QueuedBytes>)>,
+    egress: Mutex<EgressBudget>,
+}
+
+impl Tenant {
+    fn queued_messages(&self) -> u64 {
+        self.queues
+            .iter()
+            .map(|entry| entry.value().0.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    fn queued_bytes(&self) -> u64 {
+        self.queues
+            .iter()
+            .map(|entry| entry.value().1.total.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    fn quota_exceeded(&self, envelope: &MessageEnvelope, reason: String) -> Box<DeliveryFailure> {
+        Box::new(DeliveryFailure::new(UndeliverableReason::Transport(
+            TransportFailure::new(
+                envelope.dest().clone(),
+                TransportFailureReason::QuotaExceeded(format!("tenant {} {}", self.name, reason)),
+            ),
+        )))
+    }
+}
+
+/// A token bucket of egress bytes.
+struct EgressBudget {
+    available: f64,
+    refilled_at: Instant,
+}
+
+impl EgressBudget {
+    /// Take `bytes` from the budget, refilled at `rate` bytes per
+    /// second. Returns false if the budget is exhausted.
+    fn take(&mut self, bytes: usize, rate: u64) -> bool {
+        let now = Instant::now();
+        let refill = now.duration_since(self.refilled_at).as_secs_f64() * rate as f64;
+        self.available = (self.available + refill).min(rate as f64);
+        self.refilled_at = now;
+        if self.available < bytes as f64 {
+            return false;
+        }
+        self.available -= bytes as f64;
+        true
+    }
+}
+
+/// The tenants of a proc, and their members.
+#[derive(Default)]
+pub(crate) struct Tenants {
+    tenants: DashMap<String, Arc<Tenant>>,
+    /// The tenant of each actor. Actors are members from when they are
+    /// spawned, but count against their tenant's quota only once
+    /// admitted.
+    members: DashMap<ActorId, Arc<Tenant>>,
+    /// Whether a tenant was ever created. Checked before any lookup,
+    /// so that procs without tenants pay only an atomic load per
+    /// message.
+    active: AtomicBool,
+}
+
+impl Tenants {
+    fn create(&self, name: &str, quota: TenantQuota) -> Result<(), TenantError> {
+        match self.tenants.entry(name.to_string()) {
+            Entry::Occupied(_) => Err(TenantError::Exists(name.to_string())),
+            Entry::Vacant(entry) => {
+                let rate = quota.max_egress_bytes_per_sec.unwrap_or(0);
+                entry.insert(Arc::new(Tenant {
+                    name: name.to_string(),
+                    quota,
+                    num_actors: AtomicUsize::new(0),
+                    queues: DashMap::new(),
+                    egress: Mutex::new(EgressBudget {
+                        available: rate as f64,
+                        refilled_at: Instant::now(),
+                    }),
+                }));
+                self.active.store(true, Ordering::Release);
+                Ok(())
+            }
+        }
+    }
+
+    fn tenant_of(&self, actor_id: &ActorId) -> Option<Arc<Tenant>> {
+        self.members
+            .get(actor_id)
+            .map(|entry| entry.value().clone())
+    }
+
+    /// Admit a starting actor to its tenant, which is either the one it
+    /// was spawned into, or its parent's.
+    pub(crate) fn admit(
+        &self,
+        actor_id: &ActorId,
+        parent: Option<&ActorId>,
+        queue_depth: &Arc<AtomicU64>,
+        queued_bytes: &Arc<QueuedBytes>,
+    ) -> Result<(), TenantError> {
+        if !self.active.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        let tenant = match self.tenant_of(actor_id) {
+            Some(tenant) => tenant,
+            None => match parent.and_then(|parent| self.tenant_of(parent)) {
+                Some(tenant) => {
+                    self.members.insert(actor_id.clone(), tenant.clone());
+                    tenant
+                }
+                None => return Ok(()),
+            },
+        };
+
+        let limit = tenant.quota.max_actors.unwrap_or(usize::MAX);
+        if tenant
+            .num_actors
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < limit).then_some(n + 1)
+            })
+            .is_err()
+        {
+            self.members.remove(actor_id);
+            return Err(TenantError::ActorLimit {
+                tenant: tenant.name.clone(),
+                limit,
+            });
+        }
+        tenant.queues.insert(
+            actor_id.clone(),
+            (Arc::clone(queue_depth), Arc::clone(queued_bytes)),
+        );
+        Ok(())
+    }
+
+    /// Remove a terminated actor from its tenant.
+    pub(crate) fn release(&self, actor_id: &ActorId) {
+        if !self.active.load(Ordering::Acquire) {
+            return;
+        }
+        let Some((_, tenant)) = self.members.remove(actor_id) else {
+            return;
+        };
+        if tenant.queues.remove(actor_id).is_some() {
+            tenant.num_actors.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Whether no tenant was created in this proc.
+    pub(crate) fn is_empty(&self) -> bool {
+        !self.active.load(Ordering::Acquire)
+    }
+
+    /// Check that an envelope may be delivered to an actor in this proc.
+    pub(crate) fn check_local(
+        &self,
+        envelope: &MessageEnvelope,
+    ) -> Result<(), Box<DeliveryFailure>> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let dest = envelope.dest().actor_addr();
+        let Some(dest_tenant) = self.tenant_of(dest.id()) else {
+            return Ok(());
+        };
+        let sender_tenant = self.tenant_of(envelope.sender().id());
+        if sender_tenant.is_some_and(|sender_tenant| !Arc::ptr_eq(&sender_tenant, &dest_tenant)) {
+            return Err(Box::new(DeliveryFailure::new(InvalidReference::new(
+                dest.clone(),
+                InvalidReferenceReason::ActorNotExist,
+            ))));
+        }
+        if let Some(limit) = dest_tenant.quota.max_queued_messages
+            && dest_tenant.queued_messages() >= limit
+        {
+            return Err(
+                dest_tenant.quota_exceeded(envelope, format!("has {} messages queued", limit))
+            );
+        }
+        if let Some(limit) = dest_tenant.quota.max_queued_bytes {
+            let queued = dest_tenant.queued_bytes();
+            if queued + envelope.data().len() as u64 > limit {
+                return Err(dest_tenant.quota_exceeded(
+                    envelope,
+                    format!(
+                        "has {} bytes queued, of its limit of {} bytes",
+                        queued, limit
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that an envelope may be sent to another proc.
+    pub(crate) fn check_egress(
+        &self,
+        envelope: &MessageEnvelope,
+    ) -> Result<(), Box<DeliveryFailure>> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let Some(tenant) = self.tenant_of(envelope.sender().id()) else {
+            return Ok(());
+        };
+        let Some(rate) = tenant.quota.max_egress_bytes_per_sec else {
+            return Ok(());
+        };
+        if tenant
+            .egress
+            .lock()
+            .unwrap()
+            .take(envelope.data().len(), rate)
+        {
+            return Ok(());
+        }
+        Err(tenant.quota_exceeded(
+            envelope,
+            format!("exceeded its egress limit of {} bytes/s", rate),
+        ))
+    }
+}
+
+impl Proc {
+    /// Create a tenant in this proc with the provided quota. See the
+    /// [module documentation](self) for details.
+    pub fn create_tenant(&self, name: &str, quota: TenantQuota) -> Result<(), TenantError> {
+        self.state().tenants.create(name, quota)
+    }
+
+    /// Spawn a root actor into the named tenant. The actor fails
+    /// during initialization if the tenant is at its actor limit.
+    pub fn spawn_in_tenant<A: Actor>(
+        &self,
+        tenant: &str,
+        actor: A,
+    ) -> Result<ActorHandle<A>, TenantError> {
+        let state = self.state();
+        let tenant = state
+            .tenants
+            .tenants
+            .get(tenant)
+            .map(|entry| entry.value().clone())
+            .ok_or_else(|| TenantError::Unknown(tenant.to_string()))?;
+        let actor_id = self.allocate_root_type::<A>();
+        state.tenants.members.insert(actor_id.id().clone(), tenant);
+        Ok(self.spawn_inner(actor_id, actor, None))
+    }
+
+    /// The name of the tenant of the provided actor, if any.
+    pub fn tenant_of(&self, actor_id: &ActorId) -> Option<String> {
+        self.state()
+            .tenants
+            .tenant_of(actor_id)
+            .map(|tenant| tenant.name.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::assert_matches;
+
+    use hyperactor_config::Flattrs;
+
+    use super::*;
+    use crate::actor::ActorStatus;
+    use crate::testing::ids::test_actor_id;
+    use crate::testing::proc_supervison::ProcSupervisionCoordinator;
+
+    #[derive(Debug, Default)]
+    struct TestActor;
+
+    impl Actor for TestActor {}
+
+    fn join(
+        tenants: &Tenants,
+        tenant: &str,
+        actor_id: &ActorId,
+    ) -> (Arc<AtomicU64>, Arc<QueuedBytes>) {
+        let tenant = tenants.tenants.get(tenant).unwrap().value().clone();
+        tenants.members.insert(actor_id.clone(), tenant);
+        let queue_depth = Arc::new(AtomicU64::new(0));
+        let queued_bytes = Arc::new(QueuedBytes::new());
+        tenants
+            .admit(actor_id, None, &queue_depth, &queued_bytes)
+            .unwrap();
+        (queue_depth, queued_bytes)
+    }
+
+    fn envelope(sender: &crate::ActorAddr, dest: &crate::ActorAddr, len: usize) -> MessageEnvelope {
+        MessageEnvelope::new(
+            sender.clone(),
+            dest.port_addr(0u64.into()),
+            wirevalue::Any::serialize(&vec![0u8; len]).unwrap(),
+            Flattrs::new(),
+        )
+    }
+
+    #[test]
+    fn test_isolation_and_queue_quota() {
+        let tenants = Tenants::default();
+        tenants.create("a", TenantQuota::default()).unwrap();
+        tenants
+            .create(
+                "b",
+                TenantQuota {
+                    max_queued_messages: Some(2),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_matches!(
+            tenants.create("a", TenantQuota::default()),
+            Err(TenantError::Exists(_))
+        );
+
+        let a = test_actor_id("0", "a");
+        let b1 = test_actor_id("0", "b1");
+        let b2 = test_actor_id("0", "b2");
+        let system = test_actor_id("0", "system");
+        join(&tenants, "a", a.id());
+        let (b1_depth, _) = join(&tenants, "b", b1.id());
+        let (b2_depth, _) = join(&tenants, "b", b2.id());
+
+        assert!(tenants.check_local(&envelope(&b1, &b2, 1)).is_ok());
+        assert!(tenants.check_local(&envelope(&system, &b1, 1)).is_ok());
+        assert!(tenants.check_local(&envelope(&a, &system, 1)).is_ok());
+        let failure = tenants.check_local(&envelope(&a, &b1, 1)).unwrap_err();
+        assert_matches!(
+            failure.kind,
+            crate::mailbox::DeliveryFailureKind::InvalidReference(InvalidReference {
+                reason: InvalidReferenceReason::ActorNotExist,
+                ..
+            })
+        );
+
+        // The queue quota is shared by the tenant's actors.
+        b1_depth.store(1, Ordering::Relaxed);
+        b2_depth.store(1, Ordering::Relaxed);
+        assert!(tenants.check_local(&envelope(&system, &b1, 1)).is_err());
+        assert!(tenants.check_local(&envelope(&a, &a, 1)).is_ok());
+        tenants.release(b2.id());
+        assert!(tenants.check_local(&envelope(&system, &b1, 1)).is_ok());
+    }
+
+    #[test]
+    fn test_queued_bytes_quota() {
+        let tenants = Tenants::default();
+        let a = test_actor_id("0", "a");
+        let system = test_actor_id("0", "system");
+        // Without tenants, nothing is tracked.
+        tenants
+            .admit(
+                a.id(),
+                None,
+                &Arc::new(AtomicU64::new(0)),
+                &Arc::new(QueuedBytes::new()),
+            )
+            .unwrap();
+        assert!(tenants.is_empty());
+        assert!(tenants.members.is_empty());
+
+        tenants
+            .create(
+                "a",
+                TenantQuota {
+                    max_queued_bytes: Some(1000),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(!tenants.is_empty());
+        let (_, queued_bytes) = join(&tenants, "a", a.id());
+
+        assert!(tenants.check_local(&envelope(&system, &a, 500)).is_ok());
+        queued_bytes.total.store(600, Ordering::Relaxed);
+        assert!(tenants.check_local(&envelope(&system, &a, 500)).is_err());
+        assert!(tenants.check_local(&envelope(&system, &a, 100)).is_ok());
+    }
+
+    #[test]
+    fn test_egress_quota() {
+        let tenants = Tenants::default();
+        tenants
+            .create(
+                "a",
+                TenantQuota {
+                    max_egress_bytes_per_sec: Some(1000),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let a = test_actor_id("0", "a");
+        let remote = test_actor_id("1", "remote");
+        join(&tenants, "a", a.id());
+
+        assert!(tenants.check_egress(&envelope(&a, &remote, 600)).is_ok());
+        assert!(tenants.check_egress(&envelope(&a, &remote, 600)).is_err());
+        // Actors outside of the tenant are not limited.
+        assert!(tenants.check_egress(&envelope(&remote, &a, 600)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_actor_quota() {
+        let proc = Proc::isolated();
+        let (_reported, _coordinator) = ProcSupervisionCoordinator::set(&proc).await.unwrap();
+        proc.create_tenant(
+            "a",
+            TenantQuota {
+                max_actors: Some(1),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_matches!(
+            proc.spawn_in_tenant("b", TestActor),
+            Err(TenantError::Unknown(_))
+        );
+
+        let first = proc.spawn_in_tenant("a", TestActor).unwrap();
+        let mut status = first.status();
+        status
+            .wait_for(|status| matches!(status, ActorStatus::Idle))
+            .await
+            .unwrap();
+        assert_eq!(
+            proc.tenant_of(first.actor_addr().id()),
+            Some("a".to_string())
+        );
+
+        let second = proc.spawn_in_tenant("a", TestActor).unwrap();
+        let status = second.await;
+        assert_matches!(status, ActorStatus::Failed(err) if err.to_string().contains("limit of 1 actors"));
+        assert_eq!(
+            proc.tenant_of(first.actor_addr().id()),
+            Some("a".to_string())
+        );
+    }
+}
@@ -0,0 +1,249 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Per-actor handler timing.
+//!
+//! The proc runtime times every message handler an actor runs in wall
+//! time and, if [`ACTOR_CPU_TIMING`] is set, in CPU time. CPU time is
+//! measured per poll of the handler's future, on the thread that polls
+//! it, so time that the handler spends awaiting is not counted, and a
+//! handler that migrates between worker threads is still measured
+//! accurately. This costs two reads of the thread CPU clock per poll,
+//! which is why it is off by default.
+//!
+//! Each actor keeps an [`ActorTiming`]: the number of handlers run,
+//! a histogram of their wall-time latencies, and their cumulative CPU
+//! time. [`ActorTiming::snapshot`] summarizes these as a
+//! [`TimingSnapshot`], with the mean and the (approximate) 99th
+//! percentile latency, for admin queries. Wall time is also exported
+//! as the `actor.message_handler_duration` metric, and CPU time as the
+//! `actor.handler_cpu.us` metric, by actor type.
+
+use std::future::Future;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::config::ACTOR_CPU_TIMING;
+
+/// The number of histogram buckets per doubling of latency.
+const SUB_BUCKETS: u32 = 4;
+
+/// The number of histogram buckets, covering latencies up to 2^40
+/// microseconds (about 12 days).
+const BUCKETS: usize = 40 * SUB_BUCKETS as usize + 1;
+
+/// A histogram of latencies, in microseconds, with logarithmically
+/// sized buckets: each bucket spans 2^(1/4) (about 19%) of its lower
+/// bound, which bounds the error of estimated percentiles.
+#[derive(Debug)]
+struct LatencyHistogram {
+    counts: Box<[AtomicU64; BUCKETS]>,
+    count: AtomicU64,
+    sum_us: AtomicU64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            counts: Box::new([const { AtomicU64::new(0) }; BUCKETS]),
+            count: AtomicU64::new(0),
+            sum_us: AtomicU64::new(0),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    fn bucket(us: u64) -> usize {
+        if us == 0 {
+            return 0;
+        }
+        let index = ((us as f64).log2() * SUB_BUCKETS as f64).floor() as usize + 1;
+        index.min(BUCKETS - 1)
+    }
+
+    /// The upper bound of `bucket`, in microseconds.
+    fn upper_bound(bucket: usize) -> u64 {
+        if bucket == 0 {
+            return 0;
+        }
+        2f64.powf(bucket as f64 / SUB_BUCKETS as f64).ceil() as u64
+    }
+
+    fn record(&self, us: u64) {
+        self.counts[Self::bucket(us)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+    }
+
+    fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    fn mean(&self) -> u64 {
+        self.sum_us
+            .load(Ordering::Relaxed)
+            .checked_div(self.count())
+            .unwrap_or(0)
+    }
+
+    /// The upper bound of the bucket containing the `q`th quantile.
+    /// Handlers recorded concurrently may or may not be counted.
+    fn quantile(&self, q: f64) -> u64 {
+        let total = self.count();
+        if total == 0 {
+            return 0;
+        }
+        let rank = ((total as f64) * q).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count.load(Ordering::Relaxed);
+            if seen >= rank {
+                return Self::upper_bound(bucket);
+            }
+        }
+        Self::upper_bound(BUCKETS - 1)
+    }
+}
+
+/// A summary of an actor's handler timing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimingSnapshot {
+    /// The number of handlers run.
+    pub messages_handled: u64,
+    /// The mean handler wall time, in microseconds.
+    pub mean_latency_us: u64,
+    /// The 99th percentile handler wall time, in microseconds,
+    /// accurate to within 19%.
+    pub p99_latency_us: u64,
+    /// The cumulative CPU time of all handlers, in microseconds; zero
+    /// unless [`ACTOR_CPU_TIMING`] is set.
+    pub cpu_time_us: u64,
+}
+
+/// The handler timing of an actor; see the
+/// [module documentation](self).
+#[derive(Debug, Default)]
+pub struct ActorTiming {
+    /// Whether handler CPU time is measured.
+    cpu_timed: bool,
+    latency: LatencyHistogram,
+    cpu_time_us: AtomicU64,
+}
+
+impl ActorTiming {
+    /// Timing for a new actor, measuring CPU time if
+    /// [`ACTOR_CPU_TIMING`] is set.
+    pub(crate) fn new() -> Self {
+        Self {
+            cpu_timed: hyperactor_config::global::get(ACTOR_CPU_TIMING),
+            ..Self::default()
+        }
+    }
+
+    /// Run `fut`, a handler, returning its output along with the CPU
+    /// time spent polling it, or zero if CPU time is not measured.
+    pub(crate) async fn time<F: Future>(&self, fut: F) -> (F::Output, Duration) {
+        if self.cpu_timed {
+            cpu_timed(fut).await
+        } else {
+            (fut.await, Duration::ZERO)
+        }
+    }
+
+    /// Record a handler that took `wall` and used `cpu`.
+    pub(crate) fn record(&self, wall: Duration, cpu: Duration) {
+        self.latency.record(wall.as_micros() as u64);
+        self.cpu_time_us
+            .fetch_add(cpu.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Summarize the handlers recorded so far.
+    pub fn snapshot(&self) -> TimingSnapshot {
+        TimingSnapshot {
+            messages_handled: self.latency.count(),
+            mean_latency_us: self.latency.mean(),
+            p99_latency_us: self.latency.quantile(0.99),
+            cpu_time_us: self.cpu_time_us.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The CPU time used by the calling thread.
+fn thread_cpu_time() -> Duration {
+    nix::time::clock_gettime(nix::time::ClockId::CLOCK_THREAD_CPUTIME_ID)
+        .map(Duration::from)
+        .unwrap_or_default()
+}
+
+/// Run `fut`, returning its output along with the CPU time spent
+/// polling it.
+async fn cpu_timed<F: Future>(fut: F) -> (F::Output, Duration) {
+    let mut fut = std::pin::pin!(fut);
+    let mut cpu = Duration::ZERO;
+    let output = std::future::poll_fn(|cx| {
+        let start = thread_cpu_time();
+        let poll = fut.as_mut().poll(cx);
+        cpu += thread_cpu_time().saturating_sub(start);
+        poll
+    })
+    .await;
+    (output, cpu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_histogram() {
+        let histogram = LatencyHistogram::default();
+        assert_eq!(histogram.quantile(0.99), 0);
+        for _ in 0..990 {
+            histogram.record(100);
+        }
+        for _ in 0..10 {
+            histogram.record(10_000);
+        }
+        assert_eq!(histogram.mean(), (990 * 100 + 10 * 10_000) / 1000);
+        let p99 = histogram.quantile(0.99);
+        assert!((100..=119).contains(&p99), "p99 {}", p99);
+        let p999 = histogram.quantile(0.999);
+        assert!((10_000..=11_900).contains(&p999), "p99.9 {}", p999);
+    }
+
+    #[test]
+    fn test_actor_timing() {
+        let timing = ActorTiming::default();
+        timing.record(Duration::from_millis(2), Duration::from_millis(1));
+        timing.record(Duration::from_millis(4), Duration::from_millis(3));
+        let snapshot = timing.snapshot();
+        assert_eq!(snapshot.messages_handled, 2);
+        assert_eq!(snapshot.mean_latency_us, 3000);
+        assert!(snapshot.p99_latency_us >= 4000);
+        assert_eq!(snapshot.cpu_time_us, 4000);
+    }
+
+    #[tokio::test]
+    async fn test_cpu_timed() {
+        let ((), cpu) = cpu_timed(async {
+            // Sleeping does not use CPU; spinning does.
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let start = std::time::Instant::now();
+            while start.elapsed() < Duration::from_millis(20) {
+                std::hint::black_box(0);
+            }
+        })
+        .await;
+        assert!(cpu >= Duration::from_millis(10), "cpu {:?}", cpu);
+        assert!(cpu < Duration::from_millis(50), "cpu {:?}", cpu);
+    }
+}
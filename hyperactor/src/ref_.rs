@@ -39,10 +39,12 @@ use crate::context::MailboxExt;
 use crate::mailbox::DeliveryFailureReport;
 use crate::mailbox::MailboxSenderError;
 use crate::mailbox::MailboxSenderErrorKind;
+use crate::mailbox::MessageBatch;
 use crate::mailbox::PortSink;
 use crate::message::Bind;
 use crate::message::Bindings;
 use crate::message::Unbind;
+use crate::ordering::SEQ_INFO;
 use crate::port::ControlPort;
 use crate::port::Port;
 
@@ -232,6 +234,15 @@ pub struct PortRef<M> {
         Hash = "ignore"
     )]
     unsplit: bool,
+    /// The encoding of messages sent through this reference; not carried
+    /// on the wire, as it is the sender's choice.
+    #[derivative(
+        PartialEq = "ignore",
+        PartialOrd = "ignore",
+        Ord = "ignore",
+        Hash = "ignore"
+    )]
+    encoding: Option<wirevalue::Encoding>,
 }
 
 #[doc(hidden)]
@@ -269,6 +280,7 @@ impl<M> TryFrom<PortRefRepr> for PortRef<M> {
             phantom: PhantomData,
             return_undeliverable: repr.return_undeliverable,
             unsplit: repr.unsplit,
+            encoding: None,
         })
     }
 }
@@ -291,6 +303,7 @@ impl<M: RemoteMessage> PortRef<M> {
             phantom: PhantomData,
             return_undeliverable: true,
             unsplit: false,
+            encoding: None,
         }
     }
 
@@ -308,6 +321,7 @@ impl<M: RemoteMessage> PortRef<M> {
             phantom: PhantomData,
             return_undeliverable: true,
             unsplit: false,
+            encoding: None,
         }
     }
 
@@ -317,6 +331,20 @@ impl<M: RemoteMessage> PortRef<M> {
         self
     }
 
+    /// Serialize messages sent through this reference with `encoding`,
+    /// instead of [`wirevalue::default_encoding`]; e.g., MessagePack for a
+    /// port whose messages are consumed outside of Rust. Receivers decode
+    /// values of any encoding, so this affects only this sender.
+    pub fn with_encoding(mut self, encoding: wirevalue::Encoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    /// The encoding selected with [`PortRef::with_encoding`], if any.
+    pub fn encoding(&self) -> Option<wirevalue::Encoding> {
+        self.encoding
+    }
+
     /// The caller attests that the provided actor exposes a reachable handler
     /// port for message type `M`.
     pub fn attest_handler_port(actor: &ActorAddr) -> Self {
@@ -366,6 +394,7 @@ impl<M: RemoteMessage> PortRef<M> {
     ) {
         crate::mailbox::headers::set_send_timestamp(&mut headers);
         crate::mailbox::headers::set_rust_message_type::<M>(&mut headers);
+        crate::mailbox::headers::set_schema_fingerprint(&mut headers, &message);
         cx.post(
             self.port_addr.clone(),
             headers,
@@ -375,6 +404,74 @@ impl<M: RemoteMessage> PortRef<M> {
         );
     }
 
+    /// Send `messages` to this port in a single envelope, provided a sending
+    /// capability, such as [`crate::actor::Instance`]. The messages are
+    /// delivered in order, as if they had been posted one at a time, but
+    /// share one wire frame, and are dispatched to the port together; this
+    /// amortizes per-message overheads for high-rate small messages.
+    ///
+    /// Unless [`crate::config::MESSAGE_BATCHING`] is enabled, the messages
+    /// are posted one at a time, as receivers may not understand batches.
+    pub fn send_batch(
+        &self,
+        cx: &impl context::Actor,
+        messages: Vec<M>,
+    ) -> Result<(), MailboxSenderError> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        let serialize_error = |err: wirevalue::Error| {
+            MailboxSenderError::new_bound(
+                self.port_addr.clone(),
+                MailboxSenderErrorKind::Serialize(err.into()),
+            )
+        };
+        let messages = messages
+            .iter()
+            .map(|message| match self.encoding {
+                Some(encoding) => wirevalue::Any::serialize_with_encoding(encoding, message),
+                None => wirevalue::Any::serialize(message),
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(serialize_error)?;
+        if !hyperactor_config::global::get(crate::config::MESSAGE_BATCHING) {
+            for message in messages {
+                self.post_serialized(cx, Flattrs::new(), message);
+            }
+            return Ok(());
+        }
+
+        let mut headers = Flattrs::new();
+        crate::mailbox::headers::set_send_timestamp(&mut headers);
+        crate::mailbox::headers::set_rust_message_type::<M>(&mut headers);
+        // Fingerprint the messages' type, against which each message is
+        // checked once the batch is unpacked.
+        crate::mailbox::headers::set_schema_fingerprint(&mut headers, &messages[0]);
+        // The batch takes one seq per message; see `MessageBatch`.
+        let seq_info = cx
+            .instance()
+            .sequencer()
+            .assign_seqs(&self.port_addr, messages.len() as u64);
+        crate::mailbox::headers::stamp_sender_actor_id(
+            &mut headers,
+            &seq_info,
+            &self.port_addr,
+            cx.mailbox().actor_addr(),
+        );
+        headers.set(SEQ_INFO, seq_info);
+
+        let batch = wirevalue::Any::serialize(&MessageBatch::new::<M>(messages))
+            .map_err(serialize_error)?;
+        cx.post(
+            self.port_addr.clone(),
+            headers,
+            batch,
+            self.return_undeliverable,
+            context::SeqInfoPolicy::AllowExternal,
+        );
+        Ok(())
+    }
+
     /// Convert this port into a sink that can be used to send messages using the given capability.
     pub fn into_sink<C: context::Actor>(self, cx: C) -> PortSink<C, M> {
         PortSink::new(cx, self)
@@ -413,11 +510,24 @@ impl<M> RemoteEndpoint<M> for &PortRef<M>
 where
     M: RemoteMessage,
 {
-    fn post_with_headers<C>(self, cx: &C, headers: Flattrs, message: M)
+    fn post_with_headers<C>(self, cx: &C, mut headers: Flattrs, message: M)
     where
         C: context::Actor,
     {
-        let serialized = match wirevalue::Any::serialize(&message).map_err(|err| {
+        // Messages to actors in the same proc are handed over by value,
+        // without serializing them.
+        crate::mailbox::headers::set_send_timestamp(&mut headers);
+        crate::mailbox::headers::set_rust_message_type::<M>(&mut headers);
+        let Err((headers, message)) =
+            cx.post_local(&self.port_addr, headers, message, self.return_undeliverable)
+        else {
+            return;
+        };
+        let serialized = match self.encoding {
+            Some(encoding) => wirevalue::Any::serialize_with_encoding(encoding, &message),
+            None => wirevalue::Any::serialize_pooled(&message, crate::channel::pool::global()),
+        };
+        let serialized = match serialized.map_err(|err| {
             MailboxSenderError::new_bound(
                 self.port_addr.clone(),
                 MailboxSenderErrorKind::Serialize(err.into()),
@@ -447,6 +557,7 @@ impl<M: RemoteMessage> Clone for PortRef<M> {
             phantom: PhantomData,
             return_undeliverable: self.return_undeliverable,
             unsplit: self.unsplit,
+            encoding: self.encoding,
         }
     }
 }
@@ -657,23 +768,27 @@ where
         C: context::Actor,
     {
         crate::mailbox::headers::set_send_timestamp(&mut headers);
-        let serialized = match wirevalue::Any::serialize(&message).map_err(|err| {
-            MailboxSenderError::new_bound(
-                self.port_addr.clone(),
-                MailboxSenderErrorKind::Serialize(err.into()),
-            )
-        }) {
-            Ok(serialized) => serialized,
-            Err(err) => {
-                cx.instance()
-                    .report_delivery_failure(DeliveryFailureReport::from_send_error::<M>(
-                        cx.mailbox().actor_addr().clone(),
-                        self.endpoint_location(),
-                        &err,
-                    ));
-                return;
-            }
-        };
+        let serialized =
+            match wirevalue::Any::serialize_pooled(&message, crate::channel::pool::global())
+                .map_err(|err| {
+                    MailboxSenderError::new_bound(
+                        self.port_addr.clone(),
+                        MailboxSenderErrorKind::Serialize(err.into()),
+                    )
+                }) {
+                Ok(serialized) => serialized,
+                Err(err) => {
+                    cx.instance().report_delivery_failure(
+                        DeliveryFailureReport::from_send_error::<M>(
+                            cx.mailbox().actor_addr().clone(),
+                            self.endpoint_location(),
+                            &err,
+                        ),
+                    );
+                    return;
+                }
+            };
+        crate::mailbox::headers::set_schema_fingerprint(&mut headers, &serialized);
         cx.post(
             self.port_addr.clone(),
             headers,
@@ -878,6 +993,22 @@ mod tests {
         assert_same_port_ref(&deserialized.port, &value.port);
     }
 
+    #[test]
+    fn test_port_ref_encoding_is_sender_local() {
+        let port = test_port_ref().with_encoding(wirevalue::Encoding::MessagePack);
+        assert_eq!(port.encoding(), Some(wirevalue::Encoding::MessagePack));
+        assert_eq!(
+            port.clone().encoding(),
+            Some(wirevalue::Encoding::MessagePack)
+        );
+
+        let encoded = bincode::serde::encode_to_vec(&port, bincode::config::standard()).unwrap();
+        let (deserialized, _): (PortRef<String>, usize) =
+            bincode::serde::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+        assert_eq!(deserialized.encoding(), None);
+        assert_same_port_ref(&deserialized, &port);
+    }
+
     #[test]
     fn test_once_port_ref_regular_serde_uses_repr() {
         let value = OncePortRefEnvelope {
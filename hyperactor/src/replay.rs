@@ -0,0 +1,464 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Record and replay of an actor's inbound messages.
+//!
+//! A [`MessageRecorder`], started with [`ActorHandle::record`] or
+//! [`ActorHandle::record_to`], captures the handler messages an actor
+//! handles, with their headers, in the order it handles them and with
+//! the time it began handling each. Messages are captured by the actor
+//! loop just before they are handled, so a recording reflects the order
+//! the actor actually observed, after reordering and batching, whether
+//! the messages arrived serialized or through the local typed path.
+//!
+//! [`MessageRecording::replay`] spawns a fresh actor and feeds it the
+//! recorded messages, in order, before any other message can reach it.
+//! This reproduces bugs that depend only on an actor's message stream,
+//! and a recording saved with a test makes a regression test of it.
+//! Replayed messages are marked
+//! [`REPLAYED`](crate::mailbox::headers::REPLAYED): while handling one,
+//! the actor's posts to other actors (including replies to the recorded
+//! reply ports, which may still be live) are dropped, so a replay cannot
+//! act on the live system. Messages sent to the actor after the replay
+//! are handled normally.
+//!
+//! A recorder started with [`ActorHandle::record_to`] appends each
+//! message to its file as it is recorded, so the file is useful even if
+//! the process dies mid-recording. [`MessageRecording::load`] reads
+//! such a file, up to its last complete message.
+//!
+//! As with [checkpoints](crate::checkpoint), only messages of registered
+//! types can be recorded: others are counted in
+//! [`MessageRecording::unrecorded`], and a replay without them is not
+//! faithful. Signals, supervision events, and messages on ports opened
+//! with [`Instance::open_port`](crate::Instance::open_port) are not
+//! recorded.
+
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use arc_swap::ArcSwapOption;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use typeuri::Named;
+
+use crate::Actor;
+use crate::ActorAddr;
+use crate::ActorHandle;
+use crate::InstanceCell;
+use crate::Proc;
+use crate::actor::Binds;
+use crate::actor::Referable;
+use crate::checkpoint::CheckpointError;
+use crate::checkpoint::PendingMessage;
+
+/// Errors that occur when recording or loading a recording.
+#[derive(Debug, thiserror::Error)]
+pub enum RecordingError {
+    /// The actor already has a recorder.
+    #[error("actor {0} is already being recorded")]
+    AlreadyRecording(ActorAddr),
+
+    /// The recording file could not be read or written.
+    #[error("recording file: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The recording file is not a recording.
+    #[error("malformed recording file: {0}")]
+    Malformed(String),
+
+    /// The recorder task failed.
+    #[error("recorder failed: {0}")]
+    Recorder(#[from] tokio::task::JoinError),
+}
+
+/// A handler message, as recorded when the actor handled it.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct RecordedMessage {
+    /// The message's position in the actor's handling order, counting
+    /// unrecorded messages, from 0.
+    pub seq: u64,
+    /// When the actor began handling the message.
+    pub handled_at: SystemTime,
+    /// The message and its headers.
+    pub message: PendingMessage,
+}
+wirevalue::register_type!(RecordedMessage);
+
+/// The messages an actor handled while it was recorded.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct MessageRecording {
+    actor: ActorAddr,
+    messages: Vec<RecordedMessage>,
+    unrecorded: u64,
+}
+wirevalue::register_type!(MessageRecording);
+
+/// A record in a recording file. The first record names the actor.
+#[derive(Debug, Serialize, Deserialize)]
+enum Record {
+    Actor(ActorAddr),
+    Message(RecordedMessage),
+    Unrecorded,
+}
+
+/// A message captured by the actor loop, or `None` if it could not be
+/// serialized.
+type Capture = Option<(SystemTime, PendingMessage)>;
+
+/// The recorder installed on an actor, if any.
+#[derive(Default)]
+pub(crate) struct RecorderSlot {
+    sender: ArcSwapOption<mpsc::UnboundedSender<Capture>>,
+    /// Serializes installs, so that an actor has at most one recorder.
+    install: Mutex<()>,
+}
+
+impl RecorderSlot {
+    /// Record the messages of a work item the actor is about to handle.
+    /// `messages` is only called while a recorder is installed.
+    pub(crate) fn record(
+        &self,
+        messages: impl FnOnce() -> Vec<Result<PendingMessage, CheckpointError>>,
+    ) {
+        let sender = self.sender.load();
+        let Some(sender) = &*sender else {
+            return;
+        };
+        let handled_at = SystemTime::now();
+        for message in messages() {
+            // The recorder has failed; it reports its error when stopped.
+            let _ = sender.send(message.ok().map(|message| (handled_at, message)));
+        }
+    }
+
+    fn install(&self, sender: mpsc::UnboundedSender<Capture>) -> bool {
+        let _guard = self.install.lock().unwrap();
+        if self.sender.load().is_some() {
+            return false;
+        }
+        self.sender.store(Some(Arc::new(sender)));
+        true
+    }
+
+    fn clear(&self) {
+        self.sender.store(None);
+    }
+}
+
+/// Records the messages handled by an actor, until stopped or dropped;
+/// see the [module documentation](self).
+pub struct MessageRecorder {
+    cell: InstanceCell,
+    task: Option<JoinHandle<Result<MessageRecording, RecordingError>>>,
+}
+
+impl MessageRecorder {
+    fn start(cell: &InstanceCell, file: Option<tokio::fs::File>) -> Result<Self, RecordingError> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        if !cell.message_recorder().install(sender) {
+            return Err(RecordingError::AlreadyRecording(cell.actor_addr().clone()));
+        }
+        Ok(Self {
+            cell: cell.clone(),
+            task: Some(tokio::spawn(collect(
+                cell.actor_addr().clone(),
+                receiver,
+                file,
+            ))),
+        })
+    }
+
+    /// Stop recording, and return the recording. Messages the actor
+    /// begins handling after this call are not recorded.
+    pub async fn stop(mut self) -> Result<MessageRecording, RecordingError> {
+        self.cell.message_recorder().clear();
+        self.task.take().unwrap().await?
+    }
+}
+
+impl Drop for MessageRecorder {
+    fn drop(&mut self) {
+        if self.task.is_some() {
+            self.cell.message_recorder().clear();
+        }
+    }
+}
+
+/// Collect captured messages into a recording, appending each to `file`
+/// if there is one.
+async fn collect(
+    actor: ActorAddr,
+    mut receiver: mpsc::UnboundedReceiver<Capture>,
+    mut file: Option<tokio::fs::File>,
+) -> Result<MessageRecording, RecordingError> {
+    if let Some(file) = &mut file {
+        write_record(file, &Record::Actor(actor.clone())).await?;
+    }
+    let mut recording = MessageRecording {
+        actor,
+        messages: Vec::new(),
+        unrecorded: 0,
+    };
+    while let Some(capture) = receiver.recv().await {
+        let record = match capture {
+            Some((handled_at, message)) => Record::Message(RecordedMessage {
+                seq: recording.messages.len() as u64 + recording.unrecorded,
+                handled_at,
+                message,
+            }),
+            None => Record::Unrecorded,
+        };
+        if let Some(file) = &mut file {
+            write_record(file, &record).await?;
+        }
+        recording.push(record)?;
+    }
+    if let Some(file) = &mut file {
+        file.sync_data().await?;
+    }
+    Ok(recording)
+}
+
+/// Append a length-prefixed record to `file`.
+async fn write_record(file: &mut tokio::fs::File, record: &Record) -> Result<(), RecordingError> {
+    let data = bincode::serde::encode_to_vec(record, bincode::config::legacy())
+        .map_err(|err| RecordingError::Malformed(err.to_string()))?;
+    file.write_all(&(data.len() as u32).to_le_bytes()).await?;
+    file.write_all(&data).await?;
+    file.flush().await?;
+    Ok(())
+}
+
+impl MessageRecording {
+    /// The recorded actor.
+    pub fn actor_addr(&self) -> &ActorAddr {
+        &self.actor
+    }
+
+    /// The recorded messages, in the order the actor handled them.
+    pub fn messages(&self) -> &[RecordedMessage] {
+        &self.messages
+    }
+
+    /// The number of messages the actor handled while recorded that
+    /// could not be recorded, because their types are not registered.
+    pub fn unrecorded(&self) -> u64 {
+        self.unrecorded
+    }
+
+    fn push(&mut self, record: Record) -> Result<(), RecordingError> {
+        match record {
+            Record::Actor(actor) => {
+                return Err(RecordingError::Malformed(format!(
+                    "unexpected actor record {}",
+                    actor
+                )));
+            }
+            Record::Message(message) => self.messages.push(message),
+            Record::Unrecorded => self.unrecorded += 1,
+        }
+        Ok(())
+    }
+
+    /// Write the recording to `path`, in the format written by
+    /// [`ActorHandle::record_to`].
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<(), RecordingError> {
+        let mut file = tokio::fs::File::create(path).await?;
+        write_record(&mut file, &Record::Actor(self.actor.clone())).await?;
+        // Unrecorded messages are written after the recorded ones: their
+        // positions are implied by the gaps in sequence numbers.
+        for message in &self.messages {
+            write_record(&mut file, &Record::Message(message.clone())).await?;
+        }
+        for _ in 0..self.unrecorded {
+            write_record(&mut file, &Record::Unrecorded).await?;
+        }
+        file.sync_data().await?;
+        Ok(())
+    }
+
+    /// Read a recording from `path`. A truncated last record, left by a
+    /// process that died while recording, is ignored.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self, RecordingError> {
+        let data = tokio::fs::read(path).await?;
+        let mut records = Vec::new();
+        let mut offset = 0;
+        while let Some(header) = data.get(offset..offset + 4) {
+            let len = u32::from_le_bytes(header.try_into().unwrap()) as usize;
+            let Some(record) = data.get(offset + 4..offset + 4 + len) else {
+                break;
+            };
+            let (record, _): (Record, _) =
+                bincode::serde::decode_from_slice(record, bincode::config::legacy())
+                    .map_err(|err| RecordingError::Malformed(err.to_string()))?;
+            records.push(record);
+            offset += 4 + len;
+        }
+        let mut records = records.into_iter();
+        let Some(Record::Actor(actor)) = records.next() else {
+            return Err(RecordingError::Malformed(
+                "missing actor record".to_string(),
+            ));
+        };
+        let mut recording = Self {
+            actor,
+            messages: Vec::new(),
+            unrecorded: 0,
+        };
+        for record in records {
+            recording.push(record)?;
+        }
+        Ok(recording)
+    }
+
+    /// Spawn `actor` on `proc`, and replay the recorded messages to it,
+    /// in order, as if sent by the recorded actor. The actor's ports are
+    /// bound through `A`'s [`Binds`] implementation before the messages
+    /// are replayed. Anything the actor posts to other actors while
+    /// handling a replayed message is dropped.
+    pub fn replay<A>(&self, proc: &Proc, label: &str, actor: A) -> ActorHandle<A>
+    where
+        A: Actor + Referable + Binds<A>,
+    {
+        let handle = proc.spawn_with_label(label, actor);
+        handle.bind::<A>();
+        handle.ports().replay(
+            &self.actor,
+            self.messages
+                .iter()
+                .map(|message| message.message.clone())
+                .collect(),
+        );
+        handle
+    }
+}
+
+impl<A: Actor> ActorHandle<A> {
+    /// Record the messages the actor handles, in memory, until the
+    /// returned recorder is stopped. Fails if the actor is already being
+    /// recorded.
+    pub fn record(&self) -> Result<MessageRecorder, RecordingError> {
+        MessageRecorder::start(self.cell(), None)
+    }
+
+    /// Record the messages the actor handles, appending each to the file
+    /// at `path` as it is recorded, until the returned recorder is
+    /// stopped. The file is replaced if it exists.
+    pub async fn record_to(
+        &self,
+        path: impl Into<PathBuf>,
+    ) -> Result<MessageRecorder, RecordingError> {
+        let file = tokio::fs::File::create(path.into()).await?;
+        MessageRecorder::start(self.cell(), Some(file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate as hyperactor;
+    use crate::Context;
+    use crate::Endpoint;
+    use crate::Handler;
+    use crate::OncePortRef;
+    use crate::client::Client;
+
+    #[derive(Debug, Default)]
+    #[hyperactor::export(handlers = [Push, Get])]
+    struct Log {
+        entries: Vec<u64>,
+    }
+
+    impl Actor for Log {}
+
+    #[derive(Debug, Clone, Serialize, Deserialize, Named)]
+    struct Push(u64);
+    wirevalue::register_type!(Push);
+
+    #[derive(Debug, Clone, Serialize, Deserialize, Named)]
+    struct Get(OncePortRef<Vec<u64>>);
+    wirevalue::register_type!(Get);
+
+    #[async_trait]
+    impl Handler<Push> for Log {
+        async fn handle(&mut self, _cx: &Context<Self>, Push(n): Push) -> anyhow::Result<()> {
+            self.entries.push(n);
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Handler<Get> for Log {
+        async fn handle(&mut self, cx: &Context<Self>, Get(reply): Get) -> anyhow::Result<()> {
+            reply.post(cx, self.entries.clone());
+            Ok(())
+        }
+    }
+
+    async fn entries(client: &Client, actor: &ActorHandle<Log>) -> Vec<u64> {
+        let (port, receiver) = client.open_once_port::<Vec<u64>>();
+        actor.bind::<Log>().post(client, Get(port.bind()));
+        receiver.recv().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_record_replay() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let actor = proc.spawn(Log::default());
+        let actor_ref = actor.bind::<Log>();
+
+        // Not recorded.
+        actor_ref.post(&client, Push(0));
+        assert_eq!(entries(&client, &actor).await, vec![0]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.recording");
+        let recorder = actor.record_to(&path).await.unwrap();
+        assert!(matches!(
+            actor.record(),
+            Err(RecordingError::AlreadyRecording(_))
+        ));
+        for n in 1..=3 {
+            actor_ref.post(&client, Push(n));
+        }
+        assert_eq!(entries(&client, &actor).await, vec![0, 1, 2, 3]);
+        // A get whose reply port outlives the recording.
+        let (live, mut live_rx) = client.open_port::<Vec<u64>>();
+        actor_ref.post(
+            &client,
+            Get(OncePortRef::attest(live.bind().port_addr().clone())),
+        );
+        assert_eq!(live_rx.recv().await.unwrap(), vec![0, 1, 2, 3]);
+        let recording = recorder.stop().await.unwrap();
+
+        // Three pushes and two gets.
+        assert_eq!(recording.messages().len(), 5);
+        assert_eq!(recording.actor_addr(), actor.actor_addr());
+        let seqs: Vec<_> = recording.messages().iter().map(|m| m.seq).collect();
+        assert_eq!(seqs, vec![0, 1, 2, 3, 4]);
+
+        let loaded = MessageRecording::load(&path).await.unwrap();
+        assert_eq!(loaded.messages().len(), 5);
+
+        // Replayed gets reply to nobody; live requests are answered.
+        let replayed = loaded.replay(&proc, "replayed", Log::default());
+        assert_eq!(entries(&client, &replayed).await, vec![1, 2, 3]);
+        assert!(live_rx.try_recv().unwrap().is_none());
+        assert!(!replayed.status().borrow().is_terminal());
+    }
+}
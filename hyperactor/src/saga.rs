@@ -0,0 +1,517 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Sagas: multi-step operations that are unwound on failure.
+//!
+//! A [`Saga`] runs a sequence of steps, typically RPCs to other actors
+//! (allocate procs, sync code, spawn actors), each paired with a
+//! compensation that undoes it. If a step fails, the compensations of
+//! the steps that completed run in reverse order, and the saga fails
+//! with the step's error.
+//!
+//! Progress is persisted to an [`EventLog`] as it is made: each step's
+//! output is persisted when the step completes, and each compensation
+//! when it runs. A controller that restarts runs its saga again, over
+//! the same log and stream, to resume it where it stopped: completed
+//! steps are not run again, and compensations receive the outputs of
+//! the steps that they undo, as persisted.
+//!
+//! A step that was running when its controller stopped is run again on
+//! resumption, as is a compensation; both must therefore be
+//! idempotent. A compensation that fails leaves the saga unwinding,
+//! so that a later run retries it.
+//!
+//! ```ignore
+//! let saga = Saga::new(log, "job-1/launch")
+//!     .step(
+//!         "allocate",
+//!         |cx, _| async move { allocate(cx, 8).await }.boxed(),
+//!         |cx, procs: Vec<ProcAddr>| async move { release(cx, procs).await }.boxed(),
+//!     )
+//!     .step(
+//!         "spawn",
+//!         |cx, outputs| {
+//!             async move {
+//!                 let procs: Vec<ProcAddr> = outputs.get("allocate")?;
+//!                 spawn(cx, procs).await
+//!             }
+//!             .boxed()
+//!         },
+//!         |cx, actors: Vec<ActorAddr>| async move { stop(cx, actors).await }.boxed(),
+//!     );
+//! let outputs = saga.run(cx).await?;
+//! ```
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use typeuri::Named;
+
+use crate::event_sourcing::EventLog;
+use crate::event_sourcing::EventSourced;
+use crate::event_sourcing::EventSourcingError;
+use crate::event_sourcing::Journaled;
+
+/// Errors that occur while running a saga.
+#[derive(Debug, thiserror::Error)]
+pub enum SagaError {
+    /// A step failed, and the completed steps were unwound.
+    #[error("saga step {step} failed and was unwound: {error}")]
+    Aborted {
+        /// The step that failed.
+        step: String,
+        /// The step's error.
+        error: String,
+    },
+
+    /// A compensation failed; the saga remains unwinding, and the
+    /// compensation is retried when the saga is next run.
+    #[error("compensation of saga step {step} failed: {error}")]
+    CompensationFailed {
+        /// The step whose compensation failed.
+        step: String,
+        /// The compensation's error.
+        error: String,
+    },
+
+    /// The persisted progress was made by a saga with different steps.
+    #[error("saga step {index} is {expected}, but progress was persisted for {actual}")]
+    Mismatch {
+        /// The step's index.
+        index: usize,
+        /// The step's name in this saga.
+        expected: String,
+        /// The step's name in the persisted progress.
+        actual: String,
+    },
+
+    /// A step's output could not be (de)serialized.
+    #[error(transparent)]
+    Wirevalue(#[from] wirevalue::Error),
+
+    /// The progress could not be persisted or read.
+    #[error(transparent)]
+    Progress(#[from] EventSourcingError),
+}
+
+/// The persisted progress of a saga.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+enum SagaEvent {
+    /// A step completed with an output.
+    StepCompleted {
+        step: String,
+        output: wirevalue::Any,
+    },
+    /// A step failed, and unwinding started.
+    StepFailed { step: String, error: String },
+    /// The compensation of the step at an index completed.
+    StepCompensated { index: usize },
+    /// All steps completed.
+    Completed,
+    /// All completed steps were compensated.
+    Aborted,
+}
+wirevalue::register_type!(SagaEvent);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+enum Status {
+    #[default]
+    Running,
+    Unwinding,
+    Completed,
+    Aborted,
+}
+
+/// A saga's progress, as rebuilt from its events.
+#[derive(Debug, Default)]
+struct Progress {
+    status: Status,
+    /// The names and outputs of the completed steps, in order.
+    completed: Vec<(String, wirevalue::Any)>,
+    /// The failed step, and its error.
+    failure: Option<(String, String)>,
+    /// The indices of the compensated steps.
+    compensated: BTreeSet<usize>,
+}
+
+impl EventSourced for Progress {
+    type Event = SagaEvent;
+
+    fn apply(&mut self, event: &SagaEvent) {
+        match event {
+            SagaEvent::StepCompleted { step, output } => {
+                self.completed.push((step.clone(), output.clone()))
+            }
+            SagaEvent::StepFailed { step, error } => {
+                self.status = Status::Unwinding;
+                self.failure = Some((step.clone(), error.clone()));
+            }
+            SagaEvent::StepCompensated { index } => {
+                self.compensated.insert(*index);
+            }
+            SagaEvent::Completed => self.status = Status::Completed,
+            SagaEvent::Aborted => self.status = Status::Aborted,
+        }
+    }
+}
+
+/// The outputs of a saga's completed steps, by step name.
+#[derive(Debug, Clone, Default)]
+pub struct SagaOutputs(Vec<(String, wirevalue::Any)>);
+
+impl SagaOutputs {
+    /// The output of the completed step `step`.
+    pub fn get<T: DeserializeOwned + Named>(&self, step: &str) -> anyhow::Result<T> {
+        let (_, output) = self
+            .0
+            .iter()
+            .find(|(name, _)| name == step)
+            .ok_or_else(|| anyhow::anyhow!("saga step {} has not completed", step))?;
+        Ok(output.deserialized()?)
+    }
+
+    /// The number of completed steps.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether no steps have completed.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+type Action<C> = Box<
+    dyn for<'a> Fn(&'a C, &'a SagaOutputs) -> BoxFuture<'a, anyhow::Result<wirevalue::Any>>
+        + Send
+        + Sync,
+>;
+type Compensation<C> = Box<
+    dyn for<'a> Fn(&'a C, &'a wirevalue::Any) -> BoxFuture<'a, anyhow::Result<()>> + Send + Sync,
+>;
+
+/// Fix the signature of an erased action, so that its future may
+/// borrow its arguments.
+fn action<C, F>(f: F) -> F
+where
+    F: for<'a> Fn(&'a C, &'a SagaOutputs) -> BoxFuture<'a, anyhow::Result<wirevalue::Any>>,
+{
+    f
+}
+
+/// Fix the signature of an erased compensation, as for [`action`].
+fn compensation<C, F>(f: F) -> F
+where
+    F: for<'a> Fn(&'a C, &'a wirevalue::Any) -> BoxFuture<'a, anyhow::Result<()>>,
+{
+    f
+}
+
+struct Step<C> {
+    name: String,
+    action: Action<C>,
+    compensation: Compensation<C>,
+}
+
+/// A sequence of steps with compensations, whose progress is persisted.
+/// See the [module documentation](self).
+pub struct Saga<C> {
+    log: Arc<dyn EventLog>,
+    stream: String,
+    steps: Vec<Step<C>>,
+}
+
+impl<C: Sync> Saga<C> {
+    /// Create a saga whose progress is persisted to `stream` of `log`.
+    pub fn new(log: Arc<dyn EventLog>, stream: impl Into<String>) -> Self {
+        Self {
+            log,
+            stream: stream.into(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Add a step named `name`. `action` receives the outputs of the
+    /// preceding steps; `compensate` undoes the step, given its output.
+    pub fn step<T, A, U>(mut self, name: impl Into<String>, run: A, compensate: U) -> Self
+    where
+        T: Serialize + DeserializeOwned + Named + Send + 'static,
+        A: for<'a> Fn(&'a C, &'a SagaOutputs) -> BoxFuture<'a, anyhow::Result<T>>
+            + Send
+            + Sync
+            + 'static,
+        U: for<'a> Fn(&'a C, T) -> BoxFuture<'a, anyhow::Result<()>> + Send + Sync + 'static,
+    {
+        let name = name.into();
+        self.steps.push(Step {
+            name,
+            action: Box::new(action(move |cx: &C, outputs| {
+                let output = run(cx, outputs);
+                Box::pin(async move { Ok(wirevalue::Any::serialize(&output.await?)?) })
+            })),
+            compensation: Box::new(compensation(move |cx: &C, output| {
+                match output.deserialized::<T>() {
+                    Ok(output) => compensate(cx, output),
+                    Err(err) => Box::pin(async move { Err(err.into()) }),
+                }
+            })),
+        });
+        self
+    }
+
+    /// Run the saga, or resume it from its persisted progress. Returns
+    /// the outputs of its steps if they all complete.
+    pub async fn run(&self, cx: &C) -> Result<SagaOutputs, SagaError> {
+        let mut progress =
+            Journaled::recover(Progress::default(), self.log.clone(), &self.stream).await?;
+        for (index, (actual, _)) in progress.state().completed.iter().enumerate() {
+            let expected = self.steps.get(index).map_or("<none>", |step| &step.name);
+            if expected != actual {
+                return Err(SagaError::Mismatch {
+                    index,
+                    expected: expected.to_string(),
+                    actual: actual.clone(),
+                });
+            }
+        }
+
+        if progress.state().status == Status::Running {
+            for step in &self.steps[progress.state().completed.len()..] {
+                let outputs = SagaOutputs(progress.state().completed.clone());
+                match (step.action)(cx, &outputs).await {
+                    Ok(output) => {
+                        progress
+                            .commit([SagaEvent::StepCompleted {
+                                step: step.name.clone(),
+                                output,
+                            }])
+                            .await?
+                    }
+                    Err(err) => {
+                        tracing::warn!(
+                            "saga {} step {} failed, unwinding: {:#}",
+                            self.stream,
+                            step.name,
+                            err
+                        );
+                        progress
+                            .commit([SagaEvent::StepFailed {
+                                step: step.name.clone(),
+                                error: format!("{:#}", err),
+                            }])
+                            .await?;
+                        break;
+                    }
+                }
+            }
+            if progress.state().status == Status::Running {
+                progress.commit([SagaEvent::Completed]).await?;
+            }
+        }
+
+        if progress.state().status == Status::Unwinding {
+            let completed = progress.state().completed.clone();
+            for (index, (_, output)) in completed.iter().enumerate().rev() {
+                if progress.state().compensated.contains(&index) {
+                    continue;
+                }
+                let step = &self.steps[index];
+                if let Err(err) = (step.compensation)(cx, output).await {
+                    return Err(SagaError::CompensationFailed {
+                        step: step.name.clone(),
+                        error: format!("{:#}", err),
+                    });
+                }
+                progress
+                    .commit([SagaEvent::StepCompensated { index }])
+                    .await?;
+            }
+            progress.commit([SagaEvent::Aborted]).await?;
+        }
+
+        match progress.state().status {
+            Status::Completed => Ok(SagaOutputs(progress.state().completed.clone())),
+            _ => {
+                let (step, error) = progress.state().failure.clone().unwrap_or_default();
+                Err(SagaError::Aborted { step, error })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::Ordering;
+    use std::time::Duration;
+
+    use futures::FutureExt;
+
+    use super::*;
+    use crate::event_sourcing::MemoryEventLog;
+
+    /// The calls made by a saga's steps, and the failures to inject.
+    #[derive(Default)]
+    struct Calls {
+        calls: Mutex<Vec<String>>,
+        fail_spawn: AtomicBool,
+        fail_release: AtomicBool,
+        hang_sync: AtomicBool,
+    }
+
+    impl Calls {
+        fn record(&self, call: String) {
+            self.calls.lock().unwrap().push(call);
+        }
+
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut self.calls.lock().unwrap())
+        }
+    }
+
+    fn launch(log: Arc<dyn EventLog>) -> Saga<Calls> {
+        Saga::new(log, "launch")
+            .step(
+                "allocate",
+                |cx: &Calls, _| {
+                    async move {
+                        cx.record("allocate".to_string());
+                        Ok(vec![0u64, 1])
+                    }
+                    .boxed()
+                },
+                |cx: &Calls, procs: Vec<u64>| {
+                    async move {
+                        if cx.fail_release.load(Ordering::SeqCst) {
+                            anyhow::bail!("release unavailable");
+                        }
+                        cx.record(format!("release {:?}", procs));
+                        Ok(())
+                    }
+                    .boxed()
+                },
+            )
+            .step(
+                "sync",
+                |cx: &Calls, _| {
+                    async move {
+                        if cx.hang_sync.load(Ordering::SeqCst) {
+                            futures::future::pending::<()>().await;
+                        }
+                        cx.record("sync".to_string());
+                        Ok(())
+                    }
+                    .boxed()
+                },
+                |cx: &Calls, ()| {
+                    async move {
+                        cx.record("unsync".to_string());
+                        Ok(())
+                    }
+                    .boxed()
+                },
+            )
+            .step(
+                "spawn",
+                |cx: &Calls, outputs| {
+                    async move {
+                        let procs: Vec<u64> = outputs.get("allocate")?;
+                        if cx.fail_spawn.load(Ordering::SeqCst) {
+                            anyhow::bail!("spawn failed");
+                        }
+                        cx.record(format!("spawn {:?}", procs));
+                        Ok(procs.len() as u64)
+                    }
+                    .boxed()
+                },
+                |cx: &Calls, spawned: u64| {
+                    async move {
+                        cx.record(format!("stop {}", spawned));
+                        Ok(())
+                    }
+                    .boxed()
+                },
+            )
+    }
+
+    #[tokio::test]
+    async fn test_saga_completes() {
+        let log: Arc<dyn EventLog> = Arc::new(MemoryEventLog::default());
+        let calls = Calls::default();
+        let outputs = launch(log.clone()).run(&calls).await.unwrap();
+        assert_eq!(outputs.len(), 3);
+        assert_eq!(outputs.get::<u64>("spawn").unwrap(), 2);
+        assert_eq!(calls.take(), vec!["allocate", "sync", "spawn [0, 1]"]);
+
+        // A completed saga is not run again.
+        let outputs = launch(log).run(&calls).await.unwrap();
+        assert_eq!(outputs.get::<Vec<u64>>("allocate").unwrap(), vec![0, 1]);
+        assert!(calls.take().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_saga_unwinds() {
+        let log: Arc<dyn EventLog> = Arc::new(MemoryEventLog::default());
+        let calls = Calls::default();
+        calls.fail_spawn.store(true, Ordering::SeqCst);
+        calls.fail_release.store(true, Ordering::SeqCst);
+        assert!(matches!(
+            launch(log.clone()).run(&calls).await,
+            Err(SagaError::CompensationFailed { step, .. }) if step == "allocate"
+        ));
+        assert_eq!(calls.take(), vec!["allocate", "sync", "unsync"]);
+
+        // A later run resumes unwinding where it stopped.
+        calls.fail_release.store(false, Ordering::SeqCst);
+        assert!(matches!(
+            launch(log.clone()).run(&calls).await,
+            Err(SagaError::Aborted { step, error }) if step == "spawn" && error == "spawn failed"
+        ));
+        assert_eq!(calls.take(), vec!["release [0, 1]"]);
+        assert!(matches!(
+            launch(log).run(&calls).await,
+            Err(SagaError::Aborted { .. })
+        ));
+        assert!(calls.take().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_saga_resumes() {
+        let log: Arc<dyn EventLog> = Arc::new(MemoryEventLog::default());
+        let calls = Calls::default();
+        calls.hang_sync.store(true, Ordering::SeqCst);
+        // The controller stops while syncing.
+        let saga = launch(log.clone());
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), saga.run(&calls))
+                .await
+                .is_err()
+        );
+        assert_eq!(calls.take(), vec!["allocate"]);
+
+        calls.hang_sync.store(false, Ordering::SeqCst);
+        launch(log.clone()).run(&calls).await.unwrap();
+        assert_eq!(calls.take(), vec!["sync", "spawn [0, 1]"]);
+
+        // Progress cannot be resumed by a different saga.
+        let other = Saga::new(log, "launch").step(
+            "reserve",
+            |_: &Calls, _| async { Ok(()) }.boxed(),
+            |_: &Calls, ()| async { Ok(()) }.boxed(),
+        );
+        assert!(matches!(
+            other.run(&calls).await,
+            Err(SagaError::Mismatch { index: 0, .. })
+        ));
+    }
+}
@@ -0,0 +1,540 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Flow-controlled streams between actors.
+//!
+//! A stream carries a sequence of items from a [`StreamSender`] to a
+//! [`StreamReceiver`] over a pair of ports: items flow over the
+//! receiver's frame port, and credits flow back over the sender's
+//! control port. The receiver grants a window of
+//! [`config::STREAM_WINDOW`] items, and replenishes it as items are
+//! consumed; the sender waits for credit before sending, so that a
+//! slow receiver is never flooded.
+//!
+//! Either side may create the stream, and hand a serializable
+//! reference to the other side, which connects to it:
+//!
+//! - [`open_stream`] creates the receiver, and a [`StreamRef`] from
+//!   which a sender is connected. This is used for server-streaming
+//!   calls: a caller sends a `StreamRef` with its request, and the
+//!   handler streams its responses to it. Messages whose reply argument
+//!   is a `StreamRef` generate such calls (see [`crate::Handler`]).
+//! - [`open_sink`] creates the sender, and a [`SinkRef`] from which a
+//!   receiver is connected. This is used for client-streaming calls: a
+//!   caller sends a `SinkRef` with its request, and streams its
+//!   requests to the handler. Messages whose reply argument is a
+//!   `SinkRef` generate such calls.
+//!
+//! A stream ends when its sender calls [`StreamSender::end`] or
+//! [`StreamSender::fail`], and fails if its sender is dropped before
+//! then. A receiver may cancel the stream with
+//! [`StreamReceiver::cancel`], or by being dropped before the stream
+//! ends, which fails subsequent sends.
+
+use hyperactor_config::Flattrs;
+use hyperactor_config::global;
+use serde::Deserialize;
+use serde::Serialize;
+use typeuri::Named;
+
+use crate::ActorAddr;
+use crate::Endpoint as _;
+use crate::PortRef;
+use crate::RemoteMessage;
+use crate::config;
+use crate::context;
+use crate::mailbox;
+use crate::mailbox::MailboxError;
+use crate::mailbox::MailboxSender as _;
+use crate::mailbox::MessageEnvelope;
+use crate::mailbox::PortReceiver;
+use crate::ordering::SEQ_INFO;
+use crate::ordering::Sequencer;
+use crate::proc::Proc;
+
+/// Errors sending or receiving over a stream.
+#[derive(Debug, thiserror::Error)]
+pub enum StreamError {
+    /// The receiver cancelled the stream.
+    #[error("stream cancelled by its receiver")]
+    Cancelled,
+
+    /// The sender failed the stream.
+    #[error("stream failed: {0}")]
+    Failed(String),
+
+    /// The stream has already ended.
+    #[error("stream ended")]
+    Ended,
+
+    /// An underlying mailbox error.
+    #[error(transparent)]
+    Mailbox(#[from] MailboxError),
+}
+
+/// Messages from a stream's sender to its receiver.
+#[derive(Debug, Serialize, Deserialize, Named)]
+pub enum StreamFrame<T> {
+    /// The sender's control port. Sent first by senders connected from
+    /// a [`StreamRef`].
+    Open(PortRef<StreamControl<T>>),
+    /// The next item.
+    Item(T),
+    /// The stream ended.
+    End,
+    /// The stream failed.
+    Error(String),
+}
+
+/// Messages from a stream's receiver to its sender.
+#[derive(Debug, Serialize, Deserialize, Named)]
+pub enum StreamControl<T> {
+    /// The receiver's frame port. Sent first by receivers connected
+    /// from a [`SinkRef`].
+    Open(PortRef<StreamFrame<T>>),
+    /// The sender may send this many more items.
+    Grant(u64),
+    /// The receiver cancelled the stream.
+    Cancel,
+}
+
+/// The capability of one end of a stream to post to the other end
+/// when it is dropped, without a context.
+#[derive(Debug)]
+struct Peer {
+    proc: Proc,
+    sender: ActorAddr,
+    sequencer: Sequencer,
+}
+
+impl Peer {
+    fn new(cx: &impl context::Actor) -> Self {
+        Self {
+            proc: cx.instance().proc().clone(),
+            sender: cx.mailbox().actor_addr().clone(),
+            sequencer: cx.instance().sequencer().clone(),
+        }
+    }
+
+    /// Post `message` to `port`, ordered with the messages posted
+    /// through the context from which this peer was created. Delivery
+    /// failures are ignored.
+    fn post<M: RemoteMessage>(&self, port: &PortRef<M>, message: M) {
+        let dest = port.port_addr();
+        let mut headers = Flattrs::new();
+        mailbox::headers::set_send_timestamp(&mut headers);
+        mailbox::headers::set_rust_message_type::<M>(&mut headers);
+        let seq_info = self.sequencer.assign_seq(dest);
+        mailbox::headers::stamp_sender_actor_id(&mut headers, &seq_info, dest, &self.sender);
+        headers.set(SEQ_INFO, seq_info);
+        match MessageEnvelope::serialize(self.sender.clone(), dest.clone(), &message, headers) {
+            Ok(mut envelope) => {
+                // The other end may well be gone too.
+                envelope.set_return_undeliverable(false);
+                self.proc.post(envelope, mailbox::monitored_return_handle());
+            }
+            Err(err) => tracing::warn!("failed to serialize stream message to {}: {}", dest, err),
+        }
+    }
+}
+
+/// A reference to a [`StreamReceiver`], from which a sender is
+/// connected.
+#[derive(Debug, Serialize, Deserialize, Named)]
+pub struct StreamRef<T> {
+    frames: PortRef<StreamFrame<T>>,
+}
+
+impl<T: RemoteMessage> StreamRef<T> {
+    /// Connect a sender to the referenced receiver.
+    pub fn connect(self, cx: &impl context::Actor) -> StreamSender<T> {
+        let (control, control_rx) = crate::mailbox::open_port::<StreamControl<T>>(cx);
+        self.frames.post(cx, StreamFrame::Open(control.bind()));
+        StreamSender {
+            frames: Some(self.frames),
+            control: control_rx,
+            credits: 0,
+            ended: false,
+            peer: Peer::new(cx),
+        }
+    }
+}
+
+/// A reference to a [`StreamSender`], from which a receiver is
+/// connected.
+#[derive(Debug, Serialize, Deserialize, Named)]
+pub struct SinkRef<T> {
+    control: PortRef<StreamControl<T>>,
+}
+
+impl<T: RemoteMessage> SinkRef<T> {
+    /// Connect a receiver to the referenced sender, with a window of
+    /// [`config::STREAM_WINDOW`] items.
+    pub fn connect(self, cx: &impl context::Actor) -> StreamReceiver<T> {
+        self.connect_with_window(cx, global::get(config::STREAM_WINDOW))
+    }
+
+    /// Connect a receiver to the referenced sender, with a window of
+    /// `window` items.
+    pub fn connect_with_window(self, cx: &impl context::Actor, window: u64) -> StreamReceiver<T> {
+        let (frames, frames_rx) = crate::mailbox::open_port::<StreamFrame<T>>(cx);
+        self.control.post(cx, StreamControl::Open(frames.bind()));
+        let mut receiver = StreamReceiver::new(cx, frames_rx, window);
+        receiver.open(cx, self.control);
+        receiver
+    }
+}
+
+/// Open a stream, returning a reference from which its sender is
+/// connected, and its receiver, with a window of
+/// [`config::STREAM_WINDOW`] items.
+pub fn open_stream<T: RemoteMessage>(
+    cx: &impl context::Actor,
+) -> (StreamRef<T>, StreamReceiver<T>) {
+    open_stream_with_window(cx, global::get(config::STREAM_WINDOW))
+}
+
+/// Open a stream, returning a reference from which its sender is
+/// connected, and its receiver, with a window of `window` items.
+pub fn open_stream_with_window<T: RemoteMessage>(
+    cx: &impl context::Actor,
+    window: u64,
+) -> (StreamRef<T>, StreamReceiver<T>) {
+    let (frames, frames_rx) = crate::mailbox::open_port::<StreamFrame<T>>(cx);
+    (
+        StreamRef {
+            frames: frames.bind(),
+        },
+        StreamReceiver::new(cx, frames_rx, window),
+    )
+}
+
+/// Open a stream, returning a reference from which its receiver is
+/// connected, and its sender.
+pub fn open_sink<T: RemoteMessage>(cx: &impl context::Actor) -> (SinkRef<T>, StreamSender<T>) {
+    let (control, control_rx) = crate::mailbox::open_port::<StreamControl<T>>(cx);
+    (
+        SinkRef {
+            control: control.bind(),
+        },
+        StreamSender {
+            frames: None,
+            control: control_rx,
+            credits: 0,
+            ended: false,
+            peer: Peer::new(cx),
+        },
+    )
+}
+
+/// The sending side of a stream.
+#[derive(Debug)]
+pub struct StreamSender<T: RemoteMessage> {
+    /// The receiver's frame port, once known.
+    frames: Option<PortRef<StreamFrame<T>>>,
+    control: PortReceiver<StreamControl<T>>,
+    /// The number of items that may be sent without waiting.
+    credits: u64,
+    ended: bool,
+    /// Fails the stream if the sender is dropped before it ends.
+    peer: Peer,
+}
+
+impl<T: RemoteMessage> StreamSender<T> {
+    /// Send `item`, waiting for the receiver to grant credit for it.
+    pub async fn send(&mut self, cx: &impl context::Actor, item: T) -> Result<(), StreamError> {
+        if self.ended {
+            return Err(StreamError::Ended);
+        }
+        while let Some(control) = self.control.try_recv()? {
+            self.apply(control)?;
+        }
+        while self.frames.is_none() || self.credits == 0 {
+            let control = self.control.recv().await?;
+            self.apply(control)?;
+        }
+        self.credits -= 1;
+        if let Some(frames) = &self.frames {
+            frames.post(cx, StreamFrame::Item(item));
+        }
+        Ok(())
+    }
+
+    /// The number of items that may currently be sent without waiting.
+    pub fn credits(&self) -> u64 {
+        self.credits
+    }
+
+    /// End the stream.
+    pub fn end(&mut self, cx: &impl context::Actor) {
+        self.finish(cx, StreamFrame::End);
+    }
+
+    /// Fail the stream with `error`.
+    pub fn fail(&mut self, cx: &impl context::Actor, error: impl std::fmt::Display) {
+        self.finish(cx, StreamFrame::Error(error.to_string()));
+    }
+
+    fn finish(&mut self, cx: &impl context::Actor, frame: StreamFrame<T>) {
+        if self.ended {
+            return;
+        }
+        self.ended = true;
+        while self.frames.is_none() {
+            match self.control.try_recv() {
+                Ok(Some(control)) => {
+                    let _ = self.apply(control);
+                }
+                // The receiver has not connected: there is no one to tell.
+                Ok(None) | Err(_) => return,
+            }
+        }
+        if let Some(frames) = &self.frames {
+            frames.post(cx, frame);
+        }
+    }
+
+    fn apply(&mut self, control: StreamControl<T>) -> Result<(), StreamError> {
+        match control {
+            StreamControl::Open(frames) => self.frames = Some(frames),
+            StreamControl::Grant(credits) => self.credits += credits,
+            StreamControl::Cancel => {
+                self.ended = true;
+                return Err(StreamError::Cancelled);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The receiving side of a stream.
+#[derive(Debug)]
+pub struct StreamReceiver<T: RemoteMessage> {
+    frames: PortReceiver<StreamFrame<T>>,
+    /// The sender's control port, once known.
+    control: Option<PortRef<StreamControl<T>>>,
+    window: u64,
+    /// Items consumed since credit was last granted.
+    consumed: u64,
+    ended: bool,
+    /// Cancels the stream if the receiver is dropped before it ends.
+    peer: Peer,
+}
+
+impl<T: RemoteMessage> StreamReceiver<T> {
+    fn new(cx: &impl context::Actor, frames: PortReceiver<StreamFrame<T>>, window: u64) -> Self {
+        Self {
+            frames,
+            control: None,
+            window: window.max(1),
+            consumed: 0,
+            ended: false,
+            peer: Peer::new(cx),
+        }
+    }
+
+    fn open(&mut self, cx: &impl context::Actor, control: PortRef<StreamControl<T>>) {
+        control.post(cx, StreamControl::Grant(self.window));
+        self.control = Some(control);
+    }
+
+    /// Receive the next item, or `None` if the stream has ended.
+    pub async fn recv(&mut self, cx: &impl context::Actor) -> Result<Option<T>, StreamError> {
+        if self.ended {
+            return Ok(None);
+        }
+        loop {
+            match self.frames.recv().await? {
+                StreamFrame::Open(control) => self.open(cx, control),
+                StreamFrame::Item(item) => {
+                    self.consumed += 1;
+                    if self.consumed >= (self.window / 2).max(1)
+                        && let Some(control) = &self.control
+                    {
+                        control.post(cx, StreamControl::Grant(self.consumed));
+                        self.consumed = 0;
+                    }
+                    return Ok(Some(item));
+                }
+                StreamFrame::End => {
+                    self.ended = true;
+                    return Ok(None);
+                }
+                StreamFrame::Error(error) => {
+                    self.ended = true;
+                    return Err(StreamError::Failed(error));
+                }
+            }
+        }
+    }
+
+    /// Cancel the stream. Subsequent sends fail with
+    /// [`StreamError::Cancelled`].
+    pub fn cancel(&mut self, cx: &impl context::Actor) {
+        self.ended = true;
+        if let Some(control) = &self.control {
+            control.post(cx, StreamControl::Cancel);
+        }
+    }
+}
+
+impl<T: RemoteMessage> Drop for StreamReceiver<T> {
+    fn drop(&mut self) {
+        if !self.ended
+            && let Some(control) = &self.control
+        {
+            self.peer.post(control, StreamControl::Cancel);
+        }
+    }
+}
+
+impl<T: RemoteMessage> Drop for StreamSender<T> {
+    fn drop(&mut self) {
+        if !self.ended
+            && let Some(frames) = &self.frames
+        {
+            self.peer.post(
+                frames,
+                StreamFrame::Error("stream sender dropped".to_string()),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::proc::Proc;
+
+    #[tokio::test]
+    async fn test_stream() {
+        let proc = Proc::isolated();
+        let receiver = proc.client("receiver");
+        let sender = proc.client("sender");
+
+        let (stream, mut rx) = open_stream::<u64>(&receiver);
+        let mut tx = stream.connect(&sender);
+        let (sent, received) = tokio::join!(
+            async {
+                for i in 0..100 {
+                    tx.send(&sender, i).await?;
+                }
+                tx.end(&sender);
+                Ok::<_, StreamError>(())
+            },
+            async {
+                let mut items = Vec::new();
+                while let Some(item) = rx.recv(&receiver).await? {
+                    items.push(item);
+                }
+                Ok::<_, StreamError>(items)
+            }
+        );
+        sent.unwrap();
+        assert_eq!(received.unwrap(), (0..100).collect::<Vec<_>>());
+        assert!(matches!(
+            tx.send(&sender, 100).await,
+            Err(StreamError::Ended)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stream_flow_control() {
+        let proc = Proc::isolated();
+        let receiver = proc.client("receiver");
+        let sender = proc.client("sender");
+        let blocked = Duration::from_millis(100);
+
+        let (stream, mut rx) = open_stream_with_window::<u64>(&receiver, 2);
+        let mut tx = stream.connect(&sender);
+        // No credit is granted until the receiver sees the sender.
+        assert!(
+            tokio::time::timeout(blocked, tx.send(&sender, 0))
+                .await
+                .is_err()
+        );
+
+        let (item, sent) = tokio::join!(rx.recv(&receiver), tx.send(&sender, 0));
+        assert_eq!(item.unwrap(), Some(0));
+        sent.unwrap();
+        tx.send(&sender, 1).await.unwrap();
+        tx.send(&sender, 2).await.unwrap();
+        // The window is full.
+        assert!(
+            tokio::time::timeout(blocked, tx.send(&sender, 3))
+                .await
+                .is_err()
+        );
+
+        assert_eq!(rx.recv(&receiver).await.unwrap(), Some(1));
+        tx.send(&sender, 3).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sink() {
+        let proc = Proc::isolated();
+        let receiver = proc.client("receiver");
+        let sender = proc.client("sender");
+
+        let (sink, mut tx) = open_sink::<u64>(&sender);
+        let mut rx = sink.connect(&receiver);
+        tx.send(&sender, 1).await.unwrap();
+        tx.fail(&sender, "boom");
+        assert_eq!(rx.recv(&receiver).await.unwrap(), Some(1));
+        assert!(matches!(
+            rx.recv(&receiver).await,
+            Err(StreamError::Failed(error)) if error == "boom"
+        ));
+
+        let (sink, mut tx) = open_sink::<u64>(&sender);
+        let mut rx = sink.connect(&receiver);
+        rx.cancel(&receiver);
+        // Cancellation is observed once the control message arrives.
+        let mut result = Ok(());
+        for i in 0..100 {
+            result = tx.send(&sender, i).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        assert!(matches!(result, Err(StreamError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_drop() {
+        let proc = Proc::isolated();
+        let receiver = proc.client("receiver");
+        let sender = proc.client("sender");
+
+        // Dropping the receiver cancels the stream.
+        let (sink, mut tx) = open_sink::<u64>(&sender);
+        drop(sink.connect(&receiver));
+        let mut result = Ok(());
+        for i in 0..100 {
+            result = tx.send(&sender, i).await;
+            if result.is_err() {
+                break;
+            }
+        }
+        assert!(matches!(result, Err(StreamError::Cancelled)));
+
+        // Dropping the sender fails the stream.
+        let (stream, mut rx) = open_stream::<u64>(&receiver);
+        let mut tx = stream.connect(&sender);
+        let (item, sent) = tokio::join!(rx.recv(&receiver), tx.send(&sender, 1));
+        assert_eq!(item.unwrap(), Some(1));
+        sent.unwrap();
+        drop(tx);
+        assert!(matches!(
+            rx.recv(&receiver).await,
+            Err(StreamError::Failed(error)) if error == "stream sender dropped"
+        ));
+    }
+}
@@ -10,6 +10,8 @@
 pub mod cancel_safe;
 /// Standardized test ID constructors.
 pub mod ids;
+/// Model-based testing of mailbox delivery semantics.
+pub mod mailbox_model;
 /// PingPongActor test util.
 pub mod pingpong;
 /// ProcSupervisionCoordinator test util.
@@ -0,0 +1,460 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Model-based testing of mailbox delivery semantics.
+//!
+//! [`check`] drives a [`Mailbox`] with a random (but seeded, and
+//! thus reproducible) sequence of operations, and checks the outcome
+//! of every message against a model of the mailbox.
+//!
+//! The run proceeds in rounds. In each round, the controller first
+//! applies a few port operations (open, bind, drop); then several
+//! sender threads concurrently post messages to some of the bound
+//! ports, while the controller keeps opening, binding and dropping
+//! the other ports. The ports targeted by messages do not change
+//! while messages are in flight, so the model predicts the outcome of
+//! each message exactly, while the port table still changes under
+//! the senders:
+//!
+//! - a message to a live port is delivered to its receiver, exactly
+//!   once;
+//! - a message to a dropped port is returned as undeliverable,
+//!   exactly once: no message is lost silently;
+//! - messages from the same sender to the same port are received in
+//!   the order they were sent.
+//!
+//! Any discrepancy is reported as a [`Violation`], along with the
+//! seed and the history of operations that led to it.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use hyperactor_config::Flattrs;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::PortRef;
+use crate::mailbox::Mailbox;
+use crate::mailbox::MailboxSender as _;
+use crate::mailbox::MessageEnvelope;
+use crate::mailbox::PortHandle;
+use crate::mailbox::PortReceiver;
+use crate::mailbox::Undeliverable;
+use crate::testing::ids::test_actor_id;
+
+/// How long to wait for an expected message before declaring it lost.
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The parameters of a model-based run.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The seed of the run; a failing run is reproduced by its seed.
+    pub seed: u64,
+    /// The number of rounds.
+    pub rounds: usize,
+    /// The number of concurrent sender threads.
+    pub senders: usize,
+    /// The maximum number of ports opened over the run.
+    pub max_ports: usize,
+    /// The maximum number of messages posted by each sender per round.
+    pub max_sends: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            rounds: 16,
+            senders: 4,
+            max_ports: 8,
+            max_sends: 32,
+        }
+    }
+}
+
+/// An operation applied to the mailbox.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    /// Open a new port.
+    Open { port: usize },
+    /// Bind an open port, making it addressable.
+    Bind { port: usize },
+    /// Drop the receiver of a port.
+    Drop { port: usize },
+    /// Post message `seq` from `sender` to a port.
+    Send {
+        sender: usize,
+        port: usize,
+        seq: u64,
+    },
+}
+
+/// A discrepancy between the mailbox and its model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// The message was neither delivered nor returned.
+    Lost {
+        sender: usize,
+        port: usize,
+        seq: u64,
+    },
+    /// The message was delivered to a dropped port, or returned from
+    /// a live port.
+    WrongOutcome {
+        sender: usize,
+        port: usize,
+        seq: u64,
+        delivered: bool,
+    },
+    /// The message was delivered or returned more than once.
+    Duplicated {
+        sender: usize,
+        port: usize,
+        seq: u64,
+    },
+    /// The message was received after a later message from the same
+    /// sender.
+    Reordered {
+        sender: usize,
+        port: usize,
+        seq: u64,
+        after: u64,
+    },
+}
+
+/// A failed model-based run.
+#[derive(Debug, Clone)]
+pub struct Violation {
+    /// The seed of the run.
+    pub seed: u64,
+    /// The round in which the violation was detected.
+    pub round: usize,
+    /// The violation.
+    pub kind: ViolationKind,
+    /// The operations applied up to and including the failing round.
+    /// The port operations applied while a round's messages are in
+    /// flight are recorded after its sends.
+    pub history: Vec<Op>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "seed {} round {}: {:?} (after {} operations)",
+            self.seed,
+            self.round,
+            self.kind,
+            self.history.len()
+        )
+    }
+}
+
+impl std::error::Error for Violation {}
+
+/// The message posted by the model's senders.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, typeuri::Named)]
+struct Tagged {
+    sender: usize,
+    seq: u64,
+}
+
+/// A port under test, and its state in the model.
+enum ModelPort {
+    Open(PortHandle<Tagged>, PortReceiver<Tagged>),
+    Bound(PortRef<Tagged>, PortReceiver<Tagged>),
+    Dropped(PortRef<Tagged>),
+    /// Transiently, while the port changes state.
+    Changing,
+}
+
+impl ModelPort {
+    fn port_ref(&self) -> Option<&PortRef<Tagged>> {
+        match self {
+            Self::Open(..) | Self::Changing => None,
+            Self::Bound(port_ref, _) | Self::Dropped(port_ref) => Some(port_ref),
+        }
+    }
+}
+
+/// Run the model described by `config` against a fresh mailbox.
+/// Returns the history of operations applied if the mailbox behaved
+/// as modeled.
+pub async fn check(config: Config) -> Result<Vec<Op>, Violation> {
+    let mut rng = fastrand::Rng::with_seed(config.seed);
+    let mailbox = Mailbox::new(test_actor_id("model", "receiver"));
+    let (return_handle, mut return_receiver) = Mailbox::new(test_actor_id("model", "returns"))
+        .open_port::<Undeliverable<MessageEnvelope>>();
+
+    let mut ports: Vec<ModelPort> = Vec::new();
+    let mut next_seq = vec![0u64; config.senders];
+    let mut history = Vec::new();
+
+    for round in 0..config.rounds {
+        let violation = |kind, history: &Vec<Op>| Violation {
+            seed: config.seed,
+            round,
+            kind,
+            history: history.clone(),
+        };
+
+        // Port operations, while no message is in flight.
+        for _ in 0..rng.usize(1..=3) {
+            match rng.u8(0..3) {
+                0 if ports.len() < config.max_ports => {
+                    let (handle, receiver) = mailbox.open_port::<Tagged>();
+                    history.push(Op::Open { port: ports.len() });
+                    ports.push(ModelPort::Open(handle, receiver));
+                }
+                1 => {
+                    let open: Vec<usize> = (0..ports.len())
+                        .filter(|&i| matches!(ports[i], ModelPort::Open(..)))
+                        .collect();
+                    if let Some(&port) = pick(&mut rng, &open) {
+                        let ModelPort::Open(handle, receiver) =
+                            std::mem::replace(&mut ports[port], ModelPort::Changing)
+                        else {
+                            unreachable!()
+                        };
+                        ports[port] = ModelPort::Bound(handle.bind(), receiver);
+                        history.push(Op::Bind { port });
+                    }
+                }
+                _ => {
+                    let bound: Vec<usize> = (0..ports.len())
+                        .filter(|&i| matches!(ports[i], ModelPort::Bound(..)))
+                        .collect();
+                    if let Some(&port) = pick(&mut rng, &bound) {
+                        let ModelPort::Bound(port_ref, receiver) =
+                            std::mem::replace(&mut ports[port], ModelPort::Changing)
+                        else {
+                            unreachable!()
+                        };
+                        drop(receiver);
+                        ports[port] = ModelPort::Dropped(port_ref);
+                        history.push(Op::Drop { port });
+                    }
+                }
+            }
+        }
+
+        // Set aside some of the bound ports, to be dropped while
+        // messages are in flight, and plan the sends of each sender to
+        // the other addressable ports.
+        let churned: Vec<usize> = (0..ports.len())
+            .filter(|&i| matches!(ports[i], ModelPort::Bound(..)) && rng.u8(0..4) == 0)
+            .collect();
+        let addressable: Vec<usize> = (0..ports.len())
+            .filter(|&i| ports[i].port_ref().is_some() && !churned.contains(&i))
+            .collect();
+        let mut plans: Vec<Vec<(usize, u64)>> = vec![Vec::new(); config.senders];
+        if !addressable.is_empty() {
+            for (sender, plan) in plans.iter_mut().enumerate() {
+                for _ in 0..rng.usize(0..=config.max_sends) {
+                    let port = *pick(&mut rng, &addressable).unwrap();
+                    let seq = next_seq[sender];
+                    next_seq[sender] += 1;
+                    plan.push((port, seq));
+                    history.push(Op::Send { sender, port, seq });
+                }
+            }
+        }
+
+        // Post concurrently, one thread per sender.
+        let senders: Vec<_> = plans
+            .iter()
+            .enumerate()
+            .map(|(sender, plan)| {
+                let mailbox = mailbox.clone();
+                let return_handle = return_handle.clone();
+                let envelopes: Vec<MessageEnvelope> = plan
+                    .iter()
+                    .map(|&(port, seq)| {
+                        MessageEnvelope::serialize(
+                            test_actor_id("model", &format!("sender{}", sender)),
+                            ports[port].port_ref().unwrap().port_addr().clone(),
+                            &Tagged { sender, seq },
+                            Flattrs::new(),
+                        )
+                        .expect("serialize")
+                    })
+                    .collect();
+                std::thread::spawn(move || {
+                    for envelope in envelopes {
+                        mailbox.post(envelope, return_handle.clone());
+                    }
+                })
+            })
+            .collect();
+
+        // Meanwhile, change the ports not targeted by any message.
+        for port in churned {
+            let ModelPort::Bound(port_ref, receiver) =
+                std::mem::replace(&mut ports[port], ModelPort::Changing)
+            else {
+                unreachable!()
+            };
+            drop(receiver);
+            ports[port] = ModelPort::Dropped(port_ref);
+            history.push(Op::Drop { port });
+        }
+        for _ in 0..rng.usize(0..=2) {
+            if ports.len() >= config.max_ports {
+                break;
+            }
+            let (handle, receiver) = mailbox.open_port::<Tagged>();
+            let port = ports.len();
+            history.push(Op::Open { port });
+            if rng.bool() {
+                ports.push(ModelPort::Bound(handle.bind(), receiver));
+                history.push(Op::Bind { port });
+            } else {
+                ports.push(ModelPort::Open(handle, receiver));
+            }
+        }
+
+        for sender in senders {
+            sender.join().expect("sender thread panicked");
+        }
+
+        // Collect the outcome of every message.
+        let mut expected: HashMap<(usize, u64), (usize, bool)> = HashMap::new();
+        for (sender, plan) in plans.iter().enumerate() {
+            for &(port, seq) in plan {
+                let live = matches!(ports[port], ModelPort::Bound(..));
+                expected.insert((sender, seq), (port, live));
+            }
+        }
+        let expected_returns = expected.values().filter(|(_, live)| !live).count();
+
+        let mut outcomes: Vec<(usize, Tagged, bool)> = Vec::new();
+        for (port, model_port) in ports.iter_mut().enumerate() {
+            let ModelPort::Bound(_, receiver) = model_port else {
+                continue;
+            };
+            let want = expected
+                .values()
+                .filter(|&&(p, live)| p == port && live)
+                .count();
+            let mut last_seq: HashMap<usize, u64> = HashMap::new();
+            for message in drain(receiver, want).await {
+                if let Some(&after) = last_seq.get(&message.sender)
+                    && after > message.seq
+                {
+                    return Err(violation(
+                        ViolationKind::Reordered {
+                            sender: message.sender,
+                            port,
+                            seq: message.seq,
+                            after,
+                        },
+                        &history,
+                    ));
+                }
+                last_seq.insert(message.sender, message.seq);
+                outcomes.push((port, message, true));
+            }
+        }
+        for returned in drain(&mut return_receiver, expected_returns).await {
+            let envelope = returned
+                .into_message()
+                .expect("returned message should carry its envelope");
+            let message: Tagged = envelope.deserialized().expect("deserialize");
+            let port = ports
+                .iter()
+                .position(|p| p.port_ref().map(|r| r.port_addr()) == Some(envelope.dest()))
+                .expect("returned message should be addressed to a known port");
+            outcomes.push((port, message, false));
+        }
+
+        // Check the outcomes against the model.
+        for (port, Tagged { sender, seq }, delivered) in outcomes {
+            let Some((expected_port, live)) = expected.remove(&(sender, seq)) else {
+                return Err(violation(
+                    ViolationKind::Duplicated { sender, port, seq },
+                    &history,
+                ));
+            };
+            if expected_port != port || live != delivered {
+                return Err(violation(
+                    ViolationKind::WrongOutcome {
+                        sender,
+                        port,
+                        seq,
+                        delivered,
+                    },
+                    &history,
+                ));
+            }
+        }
+        if let Some((&(sender, seq), &(port, _))) = expected.iter().next() {
+            return Err(violation(
+                ViolationKind::Lost { sender, port, seq },
+                &history,
+            ));
+        }
+    }
+
+    Ok(history)
+}
+
+/// Receive `want` messages from `receiver` (waiting for each for at
+/// most [`RECV_TIMEOUT`]), and then any extra message already queued.
+async fn drain<M: crate::Message>(receiver: &mut PortReceiver<M>, want: usize) -> Vec<M> {
+    let mut messages = Vec::new();
+    while messages.len() < want {
+        match tokio::time::timeout(RECV_TIMEOUT, receiver.recv()).await {
+            Ok(Ok(message)) => messages.push(message),
+            _ => return messages,
+        }
+    }
+    while let Ok(Some(message)) = receiver.try_recv() {
+        messages.push(message);
+    }
+    messages
+}
+
+fn pick<'a, T>(rng: &mut fastrand::Rng, items: &'a [T]) -> Option<&'a T> {
+    if items.is_empty() {
+        None
+    } else {
+        items.get(rng.usize(..items.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mailbox_model() {
+        for seed in 0..8 {
+            let history = check(Config {
+                seed,
+                ..Config::default()
+            })
+            .await
+            .unwrap_or_else(|violation| panic!("{}", violation));
+            assert!(history.iter().any(|op| matches!(op, Op::Send { .. })));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mailbox_model_reproducible() {
+        let config = Config {
+            seed: 42,
+            rounds: 4,
+            ..Config::default()
+        };
+        assert_eq!(
+            check(config.clone()).await.unwrap(),
+            check(config).await.unwrap()
+        );
+    }
+}
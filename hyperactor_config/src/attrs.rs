@@ -192,6 +192,55 @@ declare_attrs! {
     /// abandonment log can name the operation. `OPERATION_*` names
     /// the operation; it does not imply request/reply direction.
     pub attr OPERATION_CONTEXT_HEADER: bool;
+
+    /// Meta-attribute assigning a small numeric id to a key that is
+    /// carried on most messages. Attrs declared with
+    /// `@meta(COMPACT_KEY = id)` are written with this id, instead of
+    /// their 8-byte key hash, in the compact wire format of
+    /// [`Flattrs`] (see [`crate::flattrs::COMPACT_HEADERS`]). Ids must
+    /// be unique across all linked crates; they are allocated in the
+    /// crates' header modules.
+    pub attr COMPACT_KEY: u16;
+}
+
+/// The compact-key tables: key hashes by compact id, and compact ids
+/// by key hash. Panics if two declared keys share a compact id.
+fn compact_keys() -> &'static (HashMap<u16, u64>, HashMap<u64, u16>) {
+    static COMPACT_KEYS: LazyLock<(HashMap<u16, u64>, HashMap<u64, u16>)> = LazyLock::new(|| {
+        let mut by_id: HashMap<u16, &'static AttrKeyInfo> = HashMap::new();
+        for info in inventory::iter::<AttrKeyInfo>() {
+            let Some(&id) = info.meta.get(COMPACT_KEY) else {
+                continue;
+            };
+            if let Some(other) = by_id.insert(id, info) {
+                panic!(
+                    "attrs {} and {} share compact key id {}",
+                    other.name, info.name, id
+                );
+            }
+        }
+        let by_hash = by_id
+            .iter()
+            .map(|(&id, info)| (info.key_hash, id))
+            .collect();
+        let by_id = by_id
+            .into_iter()
+            .map(|(id, info)| (id, info.key_hash))
+            .collect();
+        (by_id, by_hash)
+    });
+    &COMPACT_KEYS
+}
+
+/// The compact id of the declared key with hash `key_hash`, if it
+/// carries [`COMPACT_KEY`].
+pub fn compact_key_id(key_hash: u64) -> Option<u16> {
+    compact_keys().1.get(&key_hash).copied()
+}
+
+/// The hash of the declared key with compact id `id`, if any.
+pub fn compact_key_hash(id: u16) -> Option<u64> {
+    compact_keys().0.get(&id).copied()
 }
 
 /// Returns `Some(true)` when the declared attribute key with this
@@ -27,6 +27,25 @@
 //! - Key IDs are FNV-1a hashes of key names (stable, computed at compile time)
 //! - Uses linear search (optimal for typical small header counts of 2-5 entries)
 //!
+//! # Compact Wire Format
+//!
+//! When [`COMPACT_HEADERS`] is enabled, headers are serialized in a
+//! compact format, marked by the high bit of `num_entries`:
+//!
+//! ```text
+//! ┌──────────────────────┬─────────────────────────────────────────┐
+//! │ num_entries | 0x8000 │ entries...                              │
+//! │ (u16)                │ (tag: varint, [key_hash: u64],          │
+//! │                      │  len: varint, value: [u8])...           │
+//! └──────────────────────┴─────────────────────────────────────────┘
+//! ```
+//!
+//! Keys declared with `@meta(COMPACT_KEY = id)` are written as the tag
+//! `id << 1 | 1`, typically one byte; other keys as the tag 0 followed
+//! by their key hash. Compact headers are expanded to the format above
+//! when they are deserialized, so both formats are always accepted;
+//! malformed compact headers fail to deserialize.
+//!
 //! # Design Benefits
 //!
 //! - **Zero-copy passthrough**: Forward the entire buffer without parsing
@@ -53,6 +72,10 @@
 //! let ts: Option<u64> = headers.get(TIMESTAMP);
 //! ```
 
+use std::sync::LazyLock;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
 use bytes::Bytes;
 use bytes::BytesMut;
 use serde::Deserialize;
@@ -60,9 +83,42 @@ use serde::Serialize;
 use serde::de::DeserializeOwned;
 use serde_multipart::Part;
 
+use crate::CONFIG;
+use crate::ConfigAttr;
 use crate::attrs::AttrValue;
 use crate::attrs::Attrs;
 use crate::attrs::Key;
+use crate::attrs::compact_key_hash;
+use crate::attrs::compact_key_id;
+use crate::attrs::declare_attrs;
+
+declare_attrs! {
+    /// Whether to serialize headers in the compact wire format (see
+    /// the [module documentation](self)). Every process accepts both
+    /// formats; enable this only once all peers do.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_COMPACT_HEADERS".to_string()),
+        Some("compact_headers".to_string()),
+    ))
+    pub attr COMPACT_HEADERS: bool = false;
+}
+
+/// [`COMPACT_HEADERS`], cached so that serializing headers does not
+/// consult the global configuration; refreshed whenever the
+/// configuration changes.
+static COMPACT: LazyLock<AtomicBool> =
+    LazyLock::new(|| AtomicBool::new(crate::global::get(COMPACT_HEADERS)));
+
+/// Whether headers are serialized in the compact wire format, as
+/// configured by [`COMPACT_HEADERS`].
+pub fn compact_headers() -> bool {
+    COMPACT.load(Ordering::Relaxed)
+}
+
+/// Re-read [`COMPACT_HEADERS`] after the configuration changed.
+pub(crate) fn refresh_compact_headers() {
+    COMPACT.store(crate::global::get(COMPACT_HEADERS), Ordering::Relaxed);
+}
 
 /// Header size: num_entries as u16
 const HEADER_SIZE: usize = 2;
@@ -70,6 +126,13 @@ const HEADER_SIZE: usize = 2;
 /// Entry header size: key_hash (u64) + len (u32) = 12 bytes
 const ENTRY_HEADER_SIZE: usize = 12;
 
+/// Marks the compact wire format in `num_entries`.
+const COMPACT_FLAG: u16 = 0x8000;
+
+/// Key hashes standing for compact ids that are not declared in this
+/// binary, so that such entries are forwarded under their ids.
+const UNDECLARED_COMPACT_KEY: u64 = 0xffff_ffff_ffff_0000;
+
 /// Flat attribute storage for message headers.
 ///
 /// Uses a single contiguous buffer with inline entry lengths.
@@ -91,11 +154,18 @@ impl Flattrs {
         Self { buffer }
     }
 
-    /// Create from a `Part`.
-    pub fn from_part(part: Part) -> Self {
-        Self {
-            buffer: BytesMut::from(part.into_bytes().as_ref()),
+    /// Create from a `Part`, in either wire format. Fails if the
+    /// headers are in the compact format and are malformed.
+    pub fn from_part(part: Part) -> anyhow::Result<Self> {
+        let bytes = part.into_bytes();
+        if bytes.len() >= HEADER_SIZE
+            && u16::from_le_bytes([bytes[0], bytes[1]]) & COMPACT_FLAG != 0
+        {
+            return Self::from_compact(&bytes);
         }
+        Ok(Self {
+            buffer: BytesMut::from(bytes.as_ref()),
+        })
     }
 
     /// Convert to wire format for transmission.
@@ -105,6 +175,72 @@ impl Flattrs {
         Part::from(Bytes::copy_from_slice(&self.buffer))
     }
 
+    /// Convert to the compact wire format for transmission.
+    pub fn to_compact_part(&self) -> Part {
+        let mut buffer = Vec::with_capacity(self.compact_encoded_len());
+        buffer.extend_from_slice(&(self.len() as u16 | COMPACT_FLAG).to_le_bytes());
+        for (key_hash, value) in self.iter() {
+            match compact_id(key_hash) {
+                Some(id) => put_varint(&mut buffer, (u64::from(id) << 1) | 1),
+                None => {
+                    put_varint(&mut buffer, 0);
+                    buffer.extend_from_slice(&key_hash.to_le_bytes());
+                }
+            }
+            put_varint(&mut buffer, value.len() as u64);
+            buffer.extend_from_slice(value);
+        }
+        Part::from(Bytes::from(buffer))
+    }
+
+    /// Expand headers in the compact wire format.
+    fn from_compact(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut flattrs = Self::new();
+        let count = u16::from_le_bytes([bytes[0], bytes[1]]) & !COMPACT_FLAG;
+        let mut rest = &bytes[HEADER_SIZE..];
+        for index in 0..count {
+            let (key_hash, value, remaining) = read_compact_entry(rest).ok_or_else(|| {
+                anyhow::anyhow!("malformed compact header entry {} of {}", index, count)
+            })?;
+            flattrs.append_entry(key_hash, value);
+            rest = remaining;
+        }
+        if !rest.is_empty() {
+            anyhow::bail!("{} trailing bytes after compact headers", rest.len());
+        }
+        Ok(flattrs)
+    }
+
+    /// The size of these headers in the standard wire format.
+    pub fn encoded_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// The size of these headers in the compact wire format.
+    pub fn compact_encoded_len(&self) -> usize {
+        HEADER_SIZE
+            + self
+                .iter()
+                .map(|(key_hash, value)| {
+                    let key_len = match compact_id(key_hash) {
+                        Some(id) => varint_len((u64::from(id) << 1) | 1),
+                        None => 1 + 8,
+                    };
+                    key_len + varint_len(value.len() as u64) + value.len()
+                })
+                .sum::<usize>()
+    }
+
+    /// The size of these headers as they are serialized, in the wire
+    /// format selected by [`COMPACT_HEADERS`].
+    pub fn wire_len(&self) -> usize {
+        if compact_headers() {
+            self.compact_encoded_len()
+        } else {
+            self.encoded_len()
+        }
+    }
+
     /// Serialize a value and store it.
     ///
     /// If the key already exists:
@@ -142,22 +278,23 @@ impl Flattrs {
             }
 
             // Different size - remove old entry by shifting
-            let entry_size = ENTRY_HEADER_SIZE + old_len;
-            let end = offset + entry_size;
-
-            if end < self.buffer.len() {
-                self.buffer.copy_within(end.., offset);
-            }
-            self.buffer.truncate(self.buffer.len() - entry_size);
-
-            // Decrement entry count since `self.append_entry` will increment it
-            let count = self.len();
-            self.buffer[0..2].copy_from_slice(&((count - 1) as u16).to_le_bytes());
+            self.remove_entry(offset, old_len);
         }
 
         self.append_entry(key_hash, serialized);
     }
 
+    /// Remove the entry under `key`, returning whether there was one.
+    pub fn remove<T>(&mut self, key: Key<T>) -> bool {
+        match self.find_entry_location(key.key_hash()) {
+            Some((offset, len)) => {
+                self.remove_entry(offset, len);
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Get a value, deserializing from the buffer.
     ///
     /// Uses linear search which is optimal for the typical small
@@ -290,6 +427,19 @@ impl Flattrs {
     }
 
     /// Append a new entry to the buffer.
+    fn remove_entry(&mut self, offset: usize, len: usize) {
+        let entry_size = ENTRY_HEADER_SIZE + len;
+        let end = offset + entry_size;
+
+        if end < self.buffer.len() {
+            self.buffer.copy_within(end.., offset);
+        }
+        self.buffer.truncate(self.buffer.len() - entry_size);
+
+        let count = self.len();
+        self.buffer[0..2].copy_from_slice(&((count - 1) as u16).to_le_bytes());
+    }
+
     fn append_entry(&mut self, key_hash: u64, value: &[u8]) {
         let len = self.len();
         self.buffer[0..2].copy_from_slice(&((len + 1) as u16).to_le_bytes());
@@ -302,6 +452,58 @@ impl Flattrs {
     }
 }
 
+/// The compact id under which the entry with `key_hash` is written.
+fn compact_id(key_hash: u64) -> Option<u16> {
+    if key_hash & UNDECLARED_COMPACT_KEY == UNDECLARED_COMPACT_KEY {
+        return Some(key_hash as u16);
+    }
+    compact_key_id(key_hash)
+}
+
+fn put_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn varint_len(value: u64) -> usize {
+    (64 - value.leading_zeros() as usize).max(1).div_ceil(7)
+}
+
+fn read_varint(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    let mut value = 0u64;
+    for (i, &byte) in bytes.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f).checked_shl(7 * i as u32)?;
+        if byte & 0x80 == 0 {
+            return Some((value, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
+/// Read one compact entry, returning its key hash, its value, and the
+/// bytes that follow it.
+fn read_compact_entry(bytes: &[u8]) -> Option<(u64, &[u8], &[u8])> {
+    let (tag, rest) = read_varint(bytes)?;
+    let (key_hash, rest) = if tag & 1 == 1 {
+        let id = u16::try_from(tag >> 1).ok()?;
+        let key_hash = compact_key_hash(id).unwrap_or(UNDECLARED_COMPACT_KEY | u64::from(id));
+        (key_hash, rest)
+    } else {
+        let (key_hash, rest) = rest.split_first_chunk::<8>()?;
+        (u64::from_le_bytes(*key_hash), rest)
+    };
+    let (len, rest) = read_varint(rest)?;
+    let len = usize::try_from(len).ok()?;
+    if len > rest.len() {
+        return None;
+    }
+    let (value, rest) = rest.split_at(len);
+    Some((key_hash, value, rest))
+}
+
 /// Iterator over `Flattrs` entries as `(key_hash, value_bytes)`.
 ///
 /// Produced by [`Flattrs::iter`].
@@ -398,7 +600,11 @@ impl Serialize for Flattrs {
     where
         S: serde::Serializer,
     {
-        self.to_part().serialize(serializer)
+        if compact_headers() {
+            self.to_compact_part().serialize(serializer)
+        } else {
+            self.to_part().serialize(serializer)
+        }
     }
 }
 
@@ -408,19 +614,21 @@ impl<'de> Deserialize<'de> for Flattrs {
         D: serde::Deserializer<'de>,
     {
         let part: Part = Deserialize::deserialize(deserializer)?;
-        Ok(Self::from_part(part))
+        Self::from_part(part).map_err(serde::de::Error::custom)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::attrs::declare_attrs;
+    use crate::attrs::COMPACT_KEY;
 
     declare_attrs! {
         attr TEST_U64: u64;
         attr TEST_STRING: String;
         attr TEST_BOOL: bool;
+        @meta(COMPACT_KEY = 1000)
+        attr TEST_COMPACT: u64;
     }
 
     #[test]
@@ -558,6 +766,21 @@ mod tests {
         assert!(attrs.contains_key(TEST_U64));
     }
 
+    #[test]
+    fn test_remove() {
+        let mut attrs = Flattrs::new();
+        attrs.set(TEST_U64, 42u64);
+        attrs.set(TEST_STRING, "hello".to_string());
+        attrs.set(TEST_BOOL, true);
+
+        assert!(attrs.remove(TEST_STRING));
+        assert!(!attrs.remove(TEST_STRING));
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(attrs.get(TEST_U64), Some(42u64));
+        assert_eq!(attrs.get(TEST_BOOL), Some(true));
+        assert!(!attrs.contains_key(TEST_STRING));
+    }
+
     #[test]
     fn test_serde_roundtrip() {
         let mut attrs = Flattrs::new();
@@ -583,7 +806,7 @@ mod tests {
         attrs.set(TEST_STRING, "hello".to_string());
 
         let wire = attrs.to_part();
-        let received = Flattrs::from_part(wire);
+        let received = Flattrs::from_part(wire).unwrap();
 
         assert_eq!(received.get(TEST_U64), Some(42u64));
         assert_eq!(received.get(TEST_STRING), Some("hello".to_string()));
@@ -644,4 +867,50 @@ mod tests {
             "hyperactor_config::flattrs::tests::test_u64=1,hyperactor_config::flattrs::tests::test_string=hello"
         );
     }
+
+    #[test]
+    fn test_compact_round_trip() {
+        let mut attrs = Flattrs::new();
+        attrs.set(TEST_COMPACT, 7u64);
+        attrs.set(TEST_STRING, "hello".to_string());
+        // An id that no key in this binary declares.
+        attrs.set_serialized(UNDECLARED_COMPACT_KEY | 1001, &[1, 2, 3]);
+
+        let compact = attrs.to_compact_part();
+        assert_eq!(compact.len(), attrs.compact_encoded_len());
+        assert!(attrs.compact_encoded_len() < attrs.encoded_len());
+        // The compact key costs a two-byte tag instead of an 8-byte hash.
+        assert_eq!(
+            attrs.compact_encoded_len(),
+            HEADER_SIZE + (2 + 1 + 8) + (1 + 8 + 1 + 13) + (2 + 1 + 3)
+        );
+
+        let decoded = Flattrs::from_part(compact).unwrap();
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded.get(TEST_COMPACT), Some(7u64));
+        assert_eq!(decoded.get(TEST_STRING), Some("hello".to_string()));
+        // Undeclared ids are forwarded as they were received.
+        assert_eq!(decoded.to_compact_part(), attrs.to_compact_part());
+
+        // The standard format is still accepted.
+        let decoded = Flattrs::from_part(attrs.to_part()).unwrap();
+        assert_eq!(decoded.get(TEST_COMPACT), Some(7u64));
+
+        // Malformed headers are rejected.
+        let compact = attrs.to_compact_part().into_bytes();
+        assert!(Flattrs::from_part(Part::from(compact.slice(..compact.len() - 1))).is_err());
+        let mut padded = compact.to_vec();
+        padded.push(0);
+        assert!(Flattrs::from_part(Part::from(Bytes::from(padded))).is_err());
+    }
+
+    #[test]
+    fn test_compact_headers_config() {
+        let config = crate::global::lock();
+        assert!(!compact_headers());
+        let guard = config.override_key(COMPACT_HEADERS, true);
+        assert!(compact_headers());
+        drop(guard);
+        assert!(!compact_headers());
+    }
 }
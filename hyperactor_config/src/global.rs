@@ -540,6 +540,7 @@ static GLOBAL: LazyLock<GlobalConfig> = LazyLock::new(|| {
 /// snapshot is consistent with the layers.
 fn rematerialize(layers: &Layers) {
     GLOBAL.materialized.store(Arc::new(layers.materialize()));
+    crate::flattrs::refresh_compact_headers();
 }
 
 /// Monotonically increasing sequence used to assign unique tokens to
@@ -779,7 +780,8 @@ pub fn propagatable_attrs() -> Attrs {
 pub struct ConfigEntry {
     /// Fully qualified key name (e.g. `hyperactor::config::codec_max_frame_length`).
     pub name: String,
-    /// Display representation of the resolved value.
+    /// Display representation of the resolved value, or [`REDACTED`]
+    /// for secret keys.
     pub value: String,
     /// Display representation of the declared default, if any.
     pub default_value: Option<String>,
@@ -791,6 +793,10 @@ pub struct ConfigEntry {
     pub env_var: Option<String>,
 }
 
+/// The value shown by [`config_entries`] for secret keys (see
+/// [`crate::ConfigAttr::secret`]) that are set.
+pub const REDACTED: &str = "<redacted>";
+
 /// Snapshot all CONFIG-marked keys with their resolved values and
 /// source layers.
 ///
@@ -840,7 +846,11 @@ pub fn config_entries() -> Vec<ConfigEntry> {
 
         entries.push(ConfigEntry {
             name: name.to_string(),
-            value: value_str,
+            value: if cfg_meta.secret && changed_from_default {
+                REDACTED.to_string()
+            } else {
+                value_str
+            },
             default_value: default_str,
             source,
             changed_from_default,
@@ -1464,6 +1474,12 @@ mod tests {
           None,
       ).process_local())
       pub attr NON_PROPAGATE_KEY: bool = true;
+
+      @meta(CONFIG = ConfigAttr::new(
+          None,
+          None,
+      ).secret())
+      pub attr SECRET_KEY: String = String::new();
     }
 
     #[test]
@@ -1941,6 +1957,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_config_entries_redacts_secret_keys() {
+        let lock = lock();
+        reset_to_defaults();
+
+        let secret = |entries: Vec<ConfigEntry>| {
+            entries
+                .into_iter()
+                .find(|e| e.name.contains("secret_key"))
+                .expect("SECRET_KEY should appear")
+        };
+        // An unset secret shows its (empty) default.
+        assert_eq!(secret(config_entries()).value, "");
+
+        let _guard = lock.override_key(SECRET_KEY, "user:password".to_string());
+        let entry = secret(config_entries());
+        assert_eq!(entry.value, REDACTED);
+        assert!(entry.changed_from_default);
+        // The value itself is unaffected.
+        assert_eq!(get_cloned(SECRET_KEY), "user:password");
+    }
+
     #[test]
     fn test_config_entries_changed_from_default_same_value() {
         let lock = lock();
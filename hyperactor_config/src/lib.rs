@@ -65,6 +65,8 @@ pub use typeuri;
 ///  - `propagate`: whether this config should be inherited by child
 ///    processes spawned via `BootstrapProcManager`. Set to `false`
 ///    for process-local configs (e.g., TLS cert paths).
+///  - `secret`: whether the config's value is redacted from config
+///    dumps (e.g., because it carries credentials).
 ///
 /// All configuration keys should carry this meta-attribute via
 /// `@meta(CONFIG = ConfigAttr { ... })`.
@@ -80,6 +82,11 @@ pub struct ConfigAttr {
     /// Whether this config should be inherited by child processes.
     /// Set to `false` for process-local configs like TLS cert paths.
     pub propagate: bool,
+
+    /// Whether this config's value is redacted from
+    /// [`global::config_entries()`].
+    #[serde(default)]
+    pub secret: bool,
 }
 
 impl ConfigAttr {
@@ -93,6 +100,7 @@ impl ConfigAttr {
             env_name,
             py_name,
             propagate: true,
+            secret: false,
         }
     }
 
@@ -104,6 +112,15 @@ impl ConfigAttr {
         self.propagate = false;
         self
     }
+
+    /// Mark this config as secret: its value is redacted from config
+    /// dumps.
+    ///
+    /// Use for configs that carry credentials.
+    pub fn secret(mut self) -> Self {
+        self.secret = true;
+        self
+    }
 }
 
 impl Named for ConfigAttr {
@@ -46,10 +46,12 @@ use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 
 const REPLY_VARIANT_ERROR: &str = indoc! {r#"
-`call` message expects a typed port ref (`OncePortRef` or `PortRef`) or handle (`OncePortHandle` or `PortHandle`) argument in the last position
+`call` message expects a typed port ref (`OncePortRef` or `PortRef`), handle (`OncePortHandle` or `PortHandle`), or stream ref (`StreamRef` or `SinkRef`) argument in the last position
 
 = help: use `MyCall(Arg1Type, Arg2Type, .., OncePortRef<ReplyType>)`
 = help: use `MyCall(Arg1Type, Arg2Type, .., OncePortHandle<ReplyType>)`
+= help: use `MyCall(Arg1Type, Arg2Type, .., StreamRef<ItemType>)`
+= help: use `MyCall(Arg1Type, Arg2Type, .., SinkRef<ItemType>)`
 "#};
 
 const REPLY_USAGE_ERROR: &str = indoc! {r#"
@@ -59,9 +61,18 @@ const REPLY_USAGE_ERROR: &str = indoc! {r#"
 = help: use `MyCall(Arg1Type, Arg2Type, .., #[reply] OncePortHandle<ReplyType>)`
 "#};
 
+const REPLY_RESULT_ERROR: &str = indoc! {r#"
+`#[reply(result)]` expects a port (not a stream) whose reply type is a `Result`
+
+= help: use `MyCall(Arg1Type, Arg2Type, .., #[reply(result)] OncePortRef<Result<ReplyType, ErrorType>>)`
+"#};
+
 enum FieldFlag {
     None,
     Reply,
+    /// The reply is a `Result`, flattened by the client methods into
+    /// a [`hyperactor::call::CallError`].
+    ResultReply,
 }
 
 /// Represents a variant of an enum.
@@ -188,6 +199,10 @@ impl Variant {
 struct ReplyPort {
     is_handle: bool,
     is_once: bool,
+    /// Replies are streamed through a [`hyperactor::stream::StreamRef`].
+    is_stream: bool,
+    /// Requests are streamed through a [`hyperactor::stream::SinkRef`].
+    is_sink: bool,
 }
 
 impl ReplyPort {
@@ -195,6 +210,40 @@ impl ReplyPort {
         ReplyPort {
             is_handle: last_segment == "PortHandle" || last_segment == "OncePortHandle",
             is_once: last_segment == "OncePortHandle" || last_segment == "OncePortRef",
+            is_stream: last_segment == "StreamRef",
+            is_sink: last_segment == "SinkRef",
+        }
+    }
+
+    /// Whether the call streams its replies or its requests.
+    fn is_streaming(&self) -> bool {
+        self.is_stream || self.is_sink
+    }
+
+    /// The end of the stream passed to the handler of a streaming call.
+    fn handler_stream_type(&self) -> proc_macro2::TokenStream {
+        if self.is_stream {
+            quote! { hyperactor::stream::StreamSender }
+        } else {
+            quote! { hyperactor::stream::StreamReceiver }
+        }
+    }
+
+    /// The end of the stream returned to the client of a streaming call.
+    fn client_stream_type(&self) -> proc_macro2::TokenStream {
+        if self.is_stream {
+            quote! { hyperactor::stream::StreamReceiver }
+        } else {
+            quote! { hyperactor::stream::StreamSender }
+        }
+    }
+
+    /// The function opening the stream of a streaming call.
+    fn open_stream_op(&self) -> proc_macro2::TokenStream {
+        if self.is_stream {
+            quote! { hyperactor::stream::open_stream }
+        } else {
+            quote! { hyperactor::stream::open_sink }
         }
     }
 
@@ -227,6 +276,8 @@ enum Message {
         reply_port: ReplyPort,
         /// The underlying return type (i.e., the type of the reply port).
         return_type: Type,
+        /// The success and error types of a `#[reply(result)]` port.
+        result_types: Option<(Type, Type)>,
         /// the log level for generated instrumentation for handlers of this message.
         log_level: Option<Ident>,
     },
@@ -244,13 +295,13 @@ impl Message {
             .iter()
             .zip(variant.field_types())
             .filter_map(|(flag, ty)| match flag {
-                FieldFlag::Reply => Some(ty),
+                FieldFlag::Reply | FieldFlag::ResultReply => Some((flag, ty)),
                 FieldFlag::None => None,
             })
-            .collect::<Vec<&Type>>()[..]
+            .collect::<Vec<(&FieldFlag, &Type)>>()[..]
         {
             [] => Ok(Self::OneWay { variant, log_level }),
-            [reply_port_ty] => {
+            [(flag, reply_port_ty)] => {
                 let syn::Type::Path(type_path) = reply_port_ty else {
                     return Err(syn::Error::new(span, REPLY_VARIANT_ERROR));
                 };
@@ -261,6 +312,8 @@ impl Message {
                     && last_segment.ident != "OncePortHandle"
                     && last_segment.ident != "PortRef"
                     && last_segment.ident != "PortHandle"
+                    && last_segment.ident != "StreamRef"
+                    && last_segment.ident != "SinkRef"
                 {
                     return Err(syn::Error::new_spanned(last_segment, REPLY_VARIANT_ERROR));
                 }
@@ -271,11 +324,23 @@ impl Message {
                     return Err(syn::Error::new_spanned(&args.args, REPLY_VARIANT_ERROR));
                 };
                 let reply_port = ReplyPort::from_last_segment(&last_segment.ident);
+                let result_types = match flag {
+                    FieldFlag::ResultReply if !reply_port.is_streaming() => {
+                        Some(result_types(return_ty).ok_or_else(|| {
+                            syn::Error::new_spanned(return_ty, REPLY_RESULT_ERROR)
+                        })?)
+                    }
+                    FieldFlag::ResultReply => {
+                        return Err(syn::Error::new_spanned(last_segment, REPLY_RESULT_ERROR));
+                    }
+                    _ => None,
+                };
                 let return_type = return_ty.clone();
                 Ok(Self::Call {
                     variant,
                     reply_port,
                     return_type,
+                    result_types,
                     log_level,
                 })
             }
@@ -311,7 +376,7 @@ impl Message {
         self.variant()
             .field_flags()
             .iter()
-            .position(|flag| matches!(flag, FieldFlag::Reply))
+            .position(|flag| matches!(flag, FieldFlag::Reply | FieldFlag::ResultReply))
     }
 
     /// The reply port argument of this message.
@@ -327,6 +392,58 @@ impl Message {
             Message::OneWay { .. } => None,
         }
     }
+
+    /// The return type of the generated client method of a (non-stream)
+    /// call.
+    fn client_return_type(&self) -> proc_macro2::TokenStream {
+        match self {
+            Message::Call {
+                result_types: Some((ok_type, err_type)),
+                ..
+            } => quote! { Result<#ok_type, hyperactor::call::CallError<#err_type>> },
+            Message::Call { return_type, .. } => {
+                quote! { Result<#return_type, hyperactor::internal_macro_support::anyhow::Error> }
+            }
+            Message::OneWay { .. } => {
+                quote! { Result<(), hyperactor::internal_macro_support::anyhow::Error> }
+            }
+        }
+    }
+
+    /// The expression of a generated client method of a (non-stream)
+    /// call that receives its reply from `reply_receiver`.
+    fn client_reply(&self) -> proc_macro2::TokenStream {
+        match self {
+            Message::Call {
+                result_types: Some(_),
+                ..
+            } => quote! { hyperactor::call::CallError::from_reply(reply_receiver.recv().await) },
+            _ => quote! {
+                reply_receiver.recv().await.map_err(hyperactor::internal_macro_support::anyhow::Error::from)
+            },
+        }
+    }
+}
+
+/// The success and error types of `ty`, if it is a `Result`.
+fn result_types(ty: &Type) -> Option<(Type, Type)> {
+    let syn::Type::Path(type_path) = ty else {
+        return None;
+    };
+    let last_segment = type_path.path.segments.last()?;
+    if last_segment.ident != "Result" {
+        return None;
+    }
+    let syn::PathArguments::AngleBracketed(args) = &last_segment.arguments else {
+        return None;
+    };
+    match args.args.iter().collect::<Vec<_>>()[..] {
+        [
+            syn::GenericArgument::Type(ok_type),
+            syn::GenericArgument::Type(err_type),
+        ] => Some((ok_type.clone(), err_type.clone())),
+        _ => None,
+    }
 }
 
 fn parse_log_level(attrs: &[Attribute]) -> Result<Option<Ident>, syn::Error> {
@@ -383,14 +500,31 @@ fn parse_log_level(attrs: &[Attribute]) -> Result<Option<Ident>, syn::Error> {
     )))
 }
 
-fn parse_field_flag(field: &Field) -> FieldFlag {
+fn parse_field_flag(field: &Field) -> Result<FieldFlag, syn::Error> {
     for attr in field.attrs.iter() {
-        match &attr.meta {
-            syn::Meta::Path(path) if path.is_ident("reply") => return FieldFlag::Reply,
-            _ => {}
+        if !attr.path().is_ident("reply") {
+            continue;
         }
+        return match &attr.meta {
+            syn::Meta::Path(_) => Ok(FieldFlag::Reply),
+            syn::Meta::List(list)
+                if list
+                    .parse_args::<Ident>()
+                    .is_ok_and(|flag| flag == "result") =>
+            {
+                Ok(FieldFlag::ResultReply)
+            }
+            _ => Err(syn::Error::new_spanned(
+                attr,
+                indoc! {"
+                    unknown `reply` attribute
+
+                    = help use `#[reply]` or `#[reply(result)]`
+                "},
+            )),
+        };
     }
-    FieldFlag::None
+    Ok(FieldFlag::None)
 }
 
 /// Parse a message enum or struct into its constituent messages.
@@ -412,7 +546,11 @@ fn parse_messages(input: DeriveInput) -> Result<Vec<Message>, syn::Error> {
                             .iter()
                             .map(|field| field.ty.clone())
                             .collect(),
-                        field_flags: fields_.unnamed.iter().map(parse_field_flag).collect(),
+                        field_flags: fields_
+                            .unnamed
+                            .iter()
+                            .map(parse_field_flag)
+                            .collect::<Result<_, _>>()?,
                         is_struct: false,
                         generics: input.generics.clone(),
                     },
@@ -425,7 +563,11 @@ fn parse_messages(input: DeriveInput) -> Result<Vec<Message>, syn::Error> {
                             .map(|field| field.ident.clone().unwrap())
                             .collect(),
                         field_types: fields_.named.iter().map(|field| field.ty.clone()).collect(),
-                        field_flags: fields_.named.iter().map(parse_field_flag).collect(),
+                        field_flags: fields_
+                            .named
+                            .iter()
+                            .map(parse_field_flag)
+                            .collect::<Result<_, _>>()?,
                         is_struct: false,
                         generics: input.generics.clone(),
                     },
@@ -469,7 +611,11 @@ fn parse_messages(input: DeriveInput) -> Result<Vec<Message>, syn::Error> {
                         .iter()
                         .map(|field| field.ty.clone())
                         .collect(),
-                    field_flags: fields_.unnamed.iter().map(parse_field_flag).collect(),
+                    field_flags: fields_
+                        .unnamed
+                        .iter()
+                        .map(parse_field_flag)
+                        .collect::<Result<_, _>>()?,
                     is_struct: true,
                     generics: input.generics.clone(),
                 },
@@ -482,7 +628,11 @@ fn parse_messages(input: DeriveInput) -> Result<Vec<Message>, syn::Error> {
                         .map(|field| field.ident.clone().unwrap())
                         .collect(),
                     field_types: fields_.named.iter().map(|field| field.ty.clone()).collect(),
-                    field_flags: fields_.named.iter().map(parse_field_flag).collect(),
+                    field_flags: fields_
+                        .named
+                        .iter()
+                        .map(parse_field_flag)
+                        .collect::<Result<_, _>>()?,
                     is_struct: true,
                     generics: input.generics.clone(),
                 },
@@ -522,6 +672,12 @@ fn parse_messages(input: DeriveInput) -> Result<Vec<Message>, syn::Error> {
 /// for `ActorRef<Actor>` accordingly. We require two implementations because
 /// not `ActorRef`s require that its message type is serializable.
 ///
+/// A reply port whose reply type is a `Result<T, E>` can be marked
+/// `#[reply(result)]`: its client methods then return
+/// `Result<T, hyperactor::call::CallError<E>>`, so that callers can
+/// match on the error the actor replied with. `E` must implement
+/// `Display`. See [`hyperactor::call`].
+///
 /// The associated [`hyperactor_macros::handler`] macro can be used to add
 /// a dispatching handler directly to an [`hyperactor::Actor`].
 ///
@@ -686,6 +842,7 @@ pub fn derive_handler(input: TokenStream) -> TokenStream {
                 reply_port,
                 return_type,
                 log_level,
+                ..
             } => {
                 let (arg_names, arg_types): (Vec<_>, Vec<_>) = message.args().into_iter().unzip();
                 let variant_name_snake = variant.snake_name();
@@ -716,6 +873,64 @@ pub fn derive_handler(input: TokenStream) -> TokenStream {
                         ));
                 };
 
+                if reply_port.is_streaming() {
+                    // Streaming calls are handled with the connected end of
+                    // the stream. Streamed replies are ended (or failed) when
+                    // the handler returns; streamed requests are cancelled if
+                    // the handler returns before they end.
+                    let (reply_port_arg, _) = message.reply_port_arg().unwrap();
+                    let constructor = variant.constructor();
+                    let handler_stream_type = reply_port.handler_stream_type();
+                    let client_stream_type = reply_port.client_stream_type();
+                    handler_trait_methods.push(quote! {
+                        #[doc = "The generated handler method for this enum variant."]
+                        async fn #variant_name_snake(
+                            &mut self,
+                            cx: &hyperactor::Context<Self>,
+                            #(#arg_names: #arg_types,)*
+                            #reply_port_arg: &mut #handler_stream_type<#return_type>)
+                            -> Result<(), hyperactor::internal_macro_support::anyhow::Error>;
+                    });
+
+                    client_trait_methods.push(quote! {
+                        #[doc = "The generated client method for this enum variant."]
+                        async fn #variant_name_snake(
+                            &self,
+                            cx: &impl hyperactor::context::Actor,
+                            #(#arg_names: #arg_types),*)
+                            -> Result<#client_stream_type<#return_type>, hyperactor::internal_macro_support::anyhow::Error>;
+
+                        #[doc = "The DEPRECATED DO NOT USE generated client method for this enum variant."]
+                        async fn #variant_name_snake_deprecated(
+                            &self,
+                            cx: &impl hyperactor::context::Actor,
+                            #(#arg_names: #arg_types),*)
+                            -> Result<#client_stream_type<#return_type>, hyperactor::internal_macro_support::anyhow::Error>;
+                    });
+
+                    let result_ident = Ident::new("result", Span::mixed_site());
+                    let finish = if reply_port.is_stream {
+                        quote! {
+                            match &#result_ident {
+                                Ok(()) => #reply_port_arg.end(cx),
+                                Err(err) => #reply_port_arg.fail(cx, err),
+                            }
+                        }
+                    } else {
+                        quote! {}
+                    };
+                    match_arms.push(quote! {
+                        #constructor => {
+                            #log_message
+                            let mut #reply_port_arg = #reply_port_arg.connect(cx);
+                            let #result_ident = self.#variant_name_snake(cx, #(#arg_names,)* &mut #reply_port_arg).await;
+                            #finish
+                            #result_ident
+                        }
+                    });
+                    continue;
+                }
+
                 handler_trait_methods.push(quote! {
                     #[doc = "The generated handler method for this enum variant."]
                     async fn #variant_name_snake(
@@ -725,20 +940,21 @@ pub fn derive_handler(input: TokenStream) -> TokenStream {
                         -> Result<#return_type, hyperactor::internal_macro_support::anyhow::Error>;
                 });
 
+                let client_return_type = message.client_return_type();
                 client_trait_methods.push(quote! {
                     #[doc = "The generated client method for this enum variant."]
                     async fn #variant_name_snake(
                         &self,
                         cx: &impl hyperactor::context::Actor,
                         #(#arg_names: #arg_types),*)
-                        -> Result<#return_type, hyperactor::internal_macro_support::anyhow::Error>;
+                        -> #client_return_type;
 
                     #[doc = "The DEPRECATED DO NOT USE generated client method for this enum variant."]
                     async fn #variant_name_snake_deprecated(
                         &self,
                         cx: &impl hyperactor::context::Actor,
                         #(#arg_names: #arg_types),*)
-                        -> Result<#return_type, hyperactor::internal_macro_support::anyhow::Error>;
+                        -> #client_return_type;
                 });
 
                 let (reply_port_arg, _) = message.reply_port_arg().unwrap();
@@ -902,6 +1118,7 @@ fn derive_client(input: TokenStream, is_handle: bool) -> TokenStream {
                 reply_port,
                 return_type,
                 log_level,
+                ..
             } => {
                 let (arg_names, arg_types): (Vec<_>, Vec<_>) = message.args().into_iter().unzip();
                 let variant_name_snake = variant.snake_name();
@@ -936,20 +1153,52 @@ fn derive_client(input: TokenStream, is_handle: bool) -> TokenStream {
                 };
                 let open_port = reply_port.open_op();
                 let rx_mod = reply_port.rx_modifier();
-                if reply_port.is_handle {
+                let client_return_type = message.client_return_type();
+                let client_reply = message.client_reply();
+                if reply_port.is_streaming() {
+                    let client_stream_type = reply_port.client_stream_type();
+                    let open_stream = reply_port.open_stream_op();
+                    impl_methods.push(quote! {
+                        #[hyperactor::instrument(level=#log_level, rpc="call", message_type=#name)]
+                        async fn #variant_name_snake(
+                            &self,
+                            cx: &impl hyperactor::context::Actor,
+                            #(#arg_names: #arg_types),*)
+                            -> Result<#client_stream_type<#return_type>, hyperactor::internal_macro_support::anyhow::Error> {
+                            let (#reply_port_arg, stream) = #open_stream::<#return_type>(cx);
+                            let message = #constructor;
+                            #log_message;
+                            #send_message;
+                            Ok(stream)
+                        }
+
+                        #[hyperactor::instrument(level=#log_level, rpc="call", message_type=#name)]
+                        async fn #variant_name_snake_deprecated(
+                            &self,
+                            cx: &impl hyperactor::context::Actor,
+                            #(#arg_names: #arg_types),*)
+                            -> Result<#client_stream_type<#return_type>, hyperactor::internal_macro_support::anyhow::Error> {
+                            let (#reply_port_arg, stream) = #open_stream::<#return_type>(cx);
+                            let message = #constructor;
+                            #log_message;
+                            #send_message;
+                            Ok(stream)
+                        }
+                    });
+                } else if reply_port.is_handle {
                     impl_methods.push(quote! {
                         #[hyperactor::instrument(level=#log_level, rpc = "call", message_type=#name)]
                         async fn #variant_name_snake(
                             &self,
                             cx: &impl hyperactor::context::Actor,
                             #(#arg_names: #arg_types),*)
-                            -> Result<#return_type, hyperactor::internal_macro_support::anyhow::Error> {
+                            -> #client_return_type {
                             let (#reply_port_arg, #rx_mod reply_receiver) =
                                 #open_port::<#return_type>(cx);
                             let message = #constructor;
                             #log_message;
                             #send_message;
-                            reply_receiver.recv().await.map_err(hyperactor::internal_macro_support::anyhow::Error::from)
+                            #client_reply
                         }
 
                         #[hyperactor::instrument(level=#log_level, rpc = "call", message_type=#name)]
@@ -957,13 +1206,13 @@ fn derive_client(input: TokenStream, is_handle: bool) -> TokenStream {
                             &self,
                             cx: &impl hyperactor::context::Actor,
                             #(#arg_names: #arg_types),*)
-                            -> Result<#return_type, hyperactor::internal_macro_support::anyhow::Error> {
+                            -> #client_return_type {
                             let (#reply_port_arg, #rx_mod reply_receiver) =
                                 #open_port::<#return_type>(cx);
                             let message = #constructor;
                             #log_message;
                             #send_message;
-                            reply_receiver.recv().await.map_err(hyperactor::internal_macro_support::anyhow::Error::from)
+                            #client_reply
                         }
                     });
                 } else {
@@ -973,14 +1222,14 @@ fn derive_client(input: TokenStream, is_handle: bool) -> TokenStream {
                             &self,
                             cx: &impl hyperactor::context::Actor,
                             #(#arg_names: #arg_types),*)
-                            -> Result<#return_type, hyperactor::internal_macro_support::anyhow::Error> {
+                            -> #client_return_type {
                             let (#reply_port_arg, #rx_mod reply_receiver) =
                                 #open_port::<#return_type>(cx);
                             let #reply_port_arg = #reply_port_arg.bind();
                             let message = #constructor;
                             #log_message;
                             #send_message;
-                            reply_receiver.recv().await.map_err(hyperactor::internal_macro_support::anyhow::Error::from)
+                            #client_reply
                         }
 
                         #[hyperactor::instrument(level=#log_level, rpc="call", message_type=#name)]
@@ -988,14 +1237,14 @@ fn derive_client(input: TokenStream, is_handle: bool) -> TokenStream {
                             &self,
                             cx: &impl hyperactor::context::Actor,
                             #(#arg_names: #arg_types),*)
-                            -> Result<#return_type, hyperactor::internal_macro_support::anyhow::Error> {
+                            -> #client_return_type {
                             let (#reply_port_arg, #rx_mod reply_receiver) =
                                 #open_port::<#return_type>(cx);
                             let #reply_port_arg = #reply_port_arg.bind();
                             let message = #constructor;
                             #log_message;
                             #send_message;
-                            reply_receiver.recv().await.map_err(hyperactor::internal_macro_support::anyhow::Error::from)
+                            #client_reply
                         }
                     });
                 }
@@ -1224,6 +1473,8 @@ pub fn instrument_infallible(args: TokenStream, input: TokenStream) -> TokenStre
 struct HandlerSpec {
     ty: Type,
     cast: bool,
+    /// The batch size, if the message is handled in batches.
+    batch: Option<Expr>,
 }
 
 impl Parse for HandlerSpec {
@@ -1233,29 +1484,50 @@ impl Parse for HandlerSpec {
         if input.peek(syn::token::Brace) {
             let content;
             syn::braced!(content in input);
-            let key: Ident = content.parse()?;
-            content.parse::<Token![=]>()?;
-            let expr: Expr = content.parse()?;
-
-            let cast = if key == "cast" {
-                if let Expr::Lit(ExprLit {
-                    lit: Lit::Bool(b), ..
-                }) = expr
-                {
-                    b.value
+            let mut cast = false;
+            let mut batch = None;
+            while !content.is_empty() {
+                let key: Ident = content.parse()?;
+                content.parse::<Token![=]>()?;
+                let expr: Expr = content.parse()?;
+
+                if key == "cast" {
+                    if let Expr::Lit(ExprLit {
+                        lit: Lit::Bool(b), ..
+                    }) = expr
+                    {
+                        cast = b.value;
+                    } else {
+                        return Err(syn::Error::new_spanned(expr, "expected boolean for `cast`"));
+                    }
+                } else if key == "batch" {
+                    batch = Some(expr);
                 } else {
-                    return Err(syn::Error::new_spanned(expr, "expected boolean for `cast`"));
+                    return Err(syn::Error::new_spanned(
+                        key,
+                        "unsupported field (expected `cast` or `batch`)",
+                    ));
                 }
-            } else {
+
+                if !content.is_empty() {
+                    content.parse::<Token![,]>()?;
+                }
+            }
+
+            if cast && let Some(batch) = &batch {
                 return Err(syn::Error::new_spanned(
-                    key,
-                    "unsupported field (expected `cast`)",
+                    batch,
+                    "`batch` cannot be combined with `cast`",
                 ));
-            };
+            }
 
-            Ok(HandlerSpec { ty, cast })
+            Ok(HandlerSpec { ty, cast, batch })
         } else if input.is_empty() || input.peek(Token![,]) {
-            Ok(HandlerSpec { ty, cast: false })
+            Ok(HandlerSpec {
+                ty,
+                cast: false,
+                batch: None,
+            })
         } else {
             // Something unexpected follows the type
             let unexpected: proc_macro2::TokenTree = input.parse()?;
@@ -1270,7 +1542,7 @@ impl Parse for HandlerSpec {
 impl HandlerSpec {
     fn add_indexed(handlers: Vec<HandlerSpec>) -> Vec<Type> {
         let mut tys = Vec::new();
-        for HandlerSpec { ty, cast } in handlers {
+        for HandlerSpec { ty, cast, .. } in handlers {
             if cast {
                 let wrapped = quote! { hyperactor::message::IndexedErasedUnbound<#ty> };
                 let wrapped_ty: Type = syn::parse2(wrapped).unwrap();
@@ -1427,6 +1699,19 @@ impl Parse for ExportAttr {
 /// #[export(MyMessage, MyOtherMessage)]
 /// struct MyActor {}
 /// ```
+///
+/// Each handler may be followed by options in braces:
+///
+/// - `cast = true` also exports the handler for messages cast to the
+///   actor's mesh.
+/// - `batch = N` handles up to `N` queued messages at a time with the
+///   actor's [`hyperactor::actor::BatchHandler`] for the type, instead
+///   of its `Handler`.
+///
+/// ```ignore
+/// #[export(MyMessage { cast = true }, MyWrite { batch = 64 })]
+/// struct MyActor {}
+/// ```
 #[proc_macro_attribute]
 pub fn export(attr: TokenStream, item: TokenStream) -> TokenStream {
     let input: DeriveInput = parse_macro_input!(item as DeriveInput);
@@ -1443,7 +1728,7 @@ pub fn export(attr: TokenStream, item: TokenStream) -> TokenStream {
     let mut bind_predicates = Vec::new();
     let actor_ty: Type = syn::parse_quote!(#data_type_name #ty_generics);
 
-    for HandlerSpec { ty, cast } in &handlers {
+    for HandlerSpec { ty, cast, batch } in &handlers {
         let message_generics = generics_with_predicates(
             &named_generics,
             [syn::parse_quote!(#ty: hyperactor::RemoteMessage)],
@@ -1456,11 +1741,22 @@ pub fn export(attr: TokenStream, item: TokenStream) -> TokenStream {
             impl #message_impl_generics hyperactor::remote::Accepts<#ty>
                 for #data_type_name #message_ty_generics #message_where_clause {}
         });
-        bindings.push(quote! {
-            ports.bind::<#ty>();
-        });
         bind_predicates.push(syn::parse_quote!(#ty: hyperactor::RemoteMessage));
-        bind_predicates.push(syn::parse_quote!(#actor_ty: hyperactor::Handler<#ty>));
+        match batch {
+            Some(batch) => {
+                bindings.push(quote! {
+                    ports.bind_batch::<#ty>(#batch);
+                });
+                bind_predicates
+                    .push(syn::parse_quote!(#actor_ty: hyperactor::actor::BatchHandler<#ty>));
+            }
+            None => {
+                bindings.push(quote! {
+                    ports.bind::<#ty>();
+                });
+                bind_predicates.push(syn::parse_quote!(#actor_ty: hyperactor::Handler<#ty>));
+            }
+        }
 
         if *cast {
             let indexed_ty: Type =
@@ -17,6 +17,7 @@ use async_trait::async_trait;
 use hyperactor as reference;
 use hyperactor::Actor;
 use hyperactor::Context;
+use hyperactor::Endpoint as _;
 use hyperactor::HandleClient;
 use hyperactor::Handler;
 use hyperactor::RefClient;
@@ -120,6 +121,102 @@ impl TestVariantFormsHandler for TestVariantFormsActor {
     }
 }
 
+// Streaming calls reply through a `StreamRef`; the handler sends
+// items through the connected sender, and the stream ends when it returns.
+// Calls may instead stream their requests through a `SinkRef`; the handler
+// receives them through the connected receiver.
+#[derive(Handler, HandleClient, RefClient, Debug, Serialize, Deserialize, Named)]
+enum Counter {
+    CountTo {
+        n: u64,
+        #[reply]
+        items: reference::stream::StreamRef<u64>,
+    },
+    Sum {
+        total: reference::PortRef<u64>,
+        #[reply]
+        items: reference::stream::SinkRef<u64>,
+    },
+}
+
+#[derive(Debug, Default)]
+#[hyperactor::export(handlers = [Counter])]
+struct CounterActor {}
+
+impl Actor for CounterActor {}
+
+#[async_trait]
+#[handle(Counter)]
+impl CounterHandler for CounterActor {
+    async fn count_to(
+        &mut self,
+        cx: &Context<Self>,
+        n: u64,
+        items: &mut reference::stream::StreamSender<u64>,
+    ) -> Result<()> {
+        for i in 0..n {
+            items.send(cx, i).await?;
+        }
+        Ok(())
+    }
+
+    async fn sum(
+        &mut self,
+        cx: &Context<Self>,
+        total: reference::PortRef<u64>,
+        items: &mut reference::stream::StreamReceiver<u64>,
+    ) -> Result<()> {
+        let mut sum = 0;
+        while let Some(item) = items.recv(cx).await? {
+            sum += item;
+        }
+        total.post(cx, sum);
+        Ok(())
+    }
+}
+
+// Calls with `#[reply(result)]` reply with typed errors, which the
+// client methods return as `CallError::Reply`.
+#[derive(Handler, HandleClient, RefClient, Debug, Serialize, Deserialize, Named)]
+enum Divider {
+    Divide {
+        a: u64,
+        b: u64,
+        #[reply(result)]
+        quotient: reference::OncePortRef<Result<u64, DivideError>>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named)]
+enum DivideError {
+    DivisionByZero,
+}
+
+impl std::fmt::Display for DivideError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "division by zero")
+    }
+}
+
+#[derive(Debug, Default)]
+#[hyperactor::export(handlers = [Divider])]
+struct DividerActor {}
+
+impl Actor for DividerActor {}
+
+#[async_trait]
+#[handle(Divider)]
+impl DividerHandler for DividerActor {
+    async fn divide(
+        &mut self,
+        _cx: &Context<Self>,
+        a: u64,
+        b: u64,
+    ) -> Result<Result<u64, DivideError>> {
+        Ok(a.checked_div(b).ok_or(DivideError::DivisionByZero))
+    }
+}
+
 #[instrument(fields(name = 4))]
 async fn yolo() -> Result<i32, i32> {
     Ok(10)
@@ -166,6 +263,7 @@ struct SimpleStructMessage {
 
 #[cfg(test)]
 mod tests {
+    use hyperactor::call::CallError;
     use hyperactor::id::Label;
     use hyperactor::id::Uid;
     use hyperactor::proc::Proc;
@@ -192,6 +290,46 @@ mod tests {
         assert_eq!(actor_ref.call_struct(&client, 10).await.unwrap(), 10,);
     }
 
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_stream_call() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let actor_handle = proc.spawn(CounterActor {});
+
+        let mut items = actor_handle.count_to(&client, 100).await.unwrap();
+        let mut received = Vec::new();
+        while let Some(item) = items.recv(&client).await.unwrap() {
+            received.push(item);
+        }
+        assert_eq!(received, (0..100).collect::<Vec<_>>());
+
+        let (total, mut total_rx) = client.open_port::<u64>();
+        let mut items = actor_handle.sum(&client, total.bind()).await.unwrap();
+        for i in 0..100 {
+            items.send(&client, i).await.unwrap();
+        }
+        items.end(&client);
+        assert_eq!(total_rx.recv().await.unwrap(), 4950);
+    }
+
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_result_call() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let actor_handle = proc.spawn(DividerActor {});
+        let actor_ref = actor_handle.bind::<DividerActor>();
+
+        assert_eq!(actor_ref.divide(&client, 6, 3).await.unwrap(), 2);
+        assert!(matches!(
+            actor_ref.divide(&client, 6, 0).await,
+            Err(CallError::Reply(DivideError::DivisionByZero))
+        ));
+        assert!(matches!(
+            actor_handle.divide(&client, 6, 0).await,
+            Err(CallError::Reply(DivideError::DivisionByZero))
+        ));
+    }
+
     #[test]
     fn test_uid_macro_singleton() {
         let id = uid!(_my - singleton);
@@ -0,0 +1,27 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! An example actor plugin, built as a `cdylib`; see
+//! [`hyperactor_mesh::plugin`].
+
+use hyperactor::Actor;
+use hyperactor_mesh::plugin::PluginRegistrar;
+
+/// An actor that does nothing.
+#[derive(Debug, Default)]
+#[hyperactor::export(handlers = [])]
+#[hyperactor::spawnable]
+pub struct ExampleWorker;
+
+impl Actor for ExampleWorker {}
+
+fn register(registrar: &mut PluginRegistrar) {
+    registrar.register::<ExampleWorker>();
+}
+
+hyperactor_mesh::declare_plugin!("example", "1.0.0", register);
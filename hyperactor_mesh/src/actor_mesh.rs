@@ -11,6 +11,7 @@
 //! - **AM-1 (rank-space):** `proc_mesh` and any view derived from
 //!   it share the same dense rank space.
 
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::fmt;
 use std::hash::Hash;
@@ -20,6 +21,7 @@ use std::sync::Arc;
 use std::sync::OnceLock as OnceCell;
 use std::time::Duration;
 
+use hyperactor::ActorAddr;
 use hyperactor::ActorLocal;
 use hyperactor::ActorRef;
 use hyperactor::Endpoint as _;
@@ -33,6 +35,8 @@ use hyperactor::accum::ReducerMode;
 use hyperactor::actor::ActorStatus;
 use hyperactor::actor::Referable;
 use hyperactor::context;
+use hyperactor::id::Label;
+use hyperactor::id::Uid;
 use hyperactor::mailbox::PortReceiver;
 use hyperactor::message::Castable;
 use hyperactor::message::ErasedUnbound;
@@ -73,6 +77,7 @@ use crate::mesh_controller::Unsubscribe;
 use crate::mesh_id::ActorMeshId;
 use crate::metrics;
 use crate::proc_mesh::GET_ACTOR_STATE_MAX_IDLE;
+use crate::proc_mesh::ProcRef;
 use crate::proc_mesh::telemetry_actor_mesh_id;
 use crate::resource;
 use crate::supervision::MeshFailure;
@@ -424,6 +429,14 @@ pub struct ActorMeshRef<A: Referable> {
     /// next_supervision_event function can be used to alert that the mesh has
     /// stopped.
     controller: Option<ActorRef<ActorMeshController<A>>>,
+    /// For heterogeneous meshes, the uids of the actors that differ from
+    /// `id`'s, keyed by the create rank of their procs. See
+    /// [`ActorMeshRef::heterogeneous`].
+    rank_actors: Option<Arc<BTreeMap<usize, Uid>>>,
+    /// For sub-meshes, the stream on which casts to the sub-mesh travel
+    /// through the comm actor tree, in place of `id`'s. See
+    /// [`ActorMeshRef::submesh`].
+    stream: Option<ActorMeshId>,
 
     /// Recorded health issues with the mesh, to quickly consult before sending
     /// out any casted messages. This is a locally updated copy of the authoritative
@@ -492,25 +505,60 @@ impl<A: Referable> ActorMeshRef<A> {
     {
         self.check_cached_failure(cx)?;
         self.emit_sent_message_telemetry(cx, view::Ranked::region(self));
+        self.dispatch_cast(cx, caller_headers, message)
+    }
 
-        if let Some(root_comm_actor) = self.proc_mesh.root_comm_actor() {
-            if casting::v1_casting_enabled() {
-                self.cast_v1(cx, message, root_comm_actor, caller_headers);
-                Ok(())
-            } else {
-                self.cast_v0(
-                    cx,
-                    message,
-                    ndslice::selection::dsl::true_(),
-                    root_comm_actor,
-                    caller_headers,
-                )
+    /// Route a cast to all the actors in this mesh. Casts to a composite
+    /// mesh are bridged: each of its parts is cast to through its own
+    /// comm actor tree, and its actors receive their points in the
+    /// composite.
+    #[allow(clippy::result_large_err)]
+    fn dispatch_cast<M>(
+        &self,
+        cx: &impl context::Actor,
+        caller_headers: &Flattrs,
+        message: M,
+    ) -> crate::Result<()>
+    where
+        A: RemoteHandles<M> + RemoteHandles<IndexedErasedUnbound<M>>,
+        M: Castable + RemoteMessage + Clone,
+    {
+        if let Some(parts) = self.proc_mesh.parts() {
+            for (prefix, proc_mesh) in parts {
+                let mut headers = caller_headers.clone();
+                multicast::push_composite_prefix(&mut headers, prefix)
+                    .map_err(|e| Error::CastingError(self.id.clone(), e))?;
+                let mut part = Self::new(self.id.clone(), proc_mesh, None);
+                part.rank_actors = self.rank_actors.clone();
+                part.stream = self.stream.clone();
+                part.dispatch_cast(cx, &headers, message.clone())?;
             }
-        } else {
-            for (point, actor) in self.iter() {
-                self.post_cast_direct(cx, point, &actor, message.clone(), caller_headers)?;
+            return Ok(());
+        }
+
+        match self.proc_mesh.root_comm_actor() {
+            // Heterogeneous meshes are cast to point-to-point: the comm
+            // actors deliver to one actor uid on every proc.
+            Some(root_comm_actor) if self.rank_actors.is_none() => {
+                if casting::v1_casting_enabled() {
+                    self.cast_v1(cx, message, root_comm_actor, caller_headers);
+                    Ok(())
+                } else {
+                    self.cast_v0(
+                        cx,
+                        message,
+                        ndslice::selection::dsl::true_(),
+                        root_comm_actor,
+                        caller_headers,
+                    )
+                }
+            }
+            _ => {
+                for (point, actor) in self.iter() {
+                    self.post_cast_direct(cx, point, &actor, message.clone(), caller_headers)?;
+                }
+                Ok(())
             }
-            Ok(())
         }
     }
 
@@ -552,6 +600,70 @@ impl<A: Referable> ActorMeshRef<A> {
         }
     }
 
+    /// Cast a message to the one actor in this mesh chosen for the
+    /// affinity key `key`. Messages with the same key are sent to the
+    /// same actor, which can therefore keep per-key (session) state;
+    /// see [`casting::affinity_rank`] for how actors are chosen. Fails
+    /// if the mesh is empty.
+    #[allow(clippy::result_large_err)]
+    pub fn cast_affinity<M>(
+        &self,
+        cx: &impl context::Actor,
+        key: &impl Hash,
+        message: M,
+    ) -> crate::Result<()>
+    where
+        A: RemoteHandles<M>,
+        M: Castable + RemoteMessage,
+    {
+        self.cast_affinity_with_headers(cx, key, &Flattrs::new(), message)
+    }
+
+    /// Cast a message to the one actor in this mesh chosen for the
+    /// affinity key `key`, merging caller-supplied `caller_headers`
+    /// into the outgoing envelope. See [`ActorMeshRef::cast_affinity`].
+    #[allow(clippy::result_large_err)]
+    pub fn cast_affinity_with_headers<M>(
+        &self,
+        cx: &impl context::Actor,
+        key: &impl Hash,
+        caller_headers: &Flattrs,
+        message: M,
+    ) -> crate::Result<()>
+    where
+        A: RemoteHandles<M>,
+        M: Castable + RemoteMessage,
+    {
+        self.check_cached_failure(cx)?;
+        let Some(rank_index) = casting::affinity_rank(
+            view::Ranked::region(self).slice(),
+            casting::affinity_key(key),
+        ) else {
+            return Err(Error::CastingError(
+                self.id.clone(),
+                anyhow::anyhow!("cannot cast to an actor of an empty mesh"),
+            ));
+        };
+        self.cast_rank_direct(cx, rank_index, message, caller_headers)
+    }
+
+    /// Cast a message point-to-point to the actor at `rank_index` in
+    /// this mesh, bypassing the comm-actor tree.
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn cast_rank<M>(
+        &self,
+        cx: &impl context::Actor,
+        rank_index: usize,
+        message: M,
+    ) -> crate::Result<()>
+    where
+        A: RemoteHandles<M>,
+        M: Castable + RemoteMessage,
+    {
+        self.check_cached_failure(cx)?;
+        self.cast_rank_direct(cx, rank_index, message, &Flattrs::new())
+    }
+
     /// Cast a message to the actors in this mesh according to the provided selection.
     /// This should *only* be used for temporary support for selections in the tensor
     /// engine. If you use this for anything else, you will be fired (you too, OSS
@@ -568,6 +680,14 @@ impl<A: Referable> ActorMeshRef<A> {
         M: Castable + RemoteMessage + Clone, // Clone is required until we are fully onto comm actor
     {
         self.check_cached_failure(cx)?;
+        if self.rank_actors.is_some() {
+            return Err(Error::CastingError(
+                self.id.clone(),
+                anyhow::anyhow!(
+                    "tensor-engine selection casts to heterogeneous meshes are not supported"
+                ),
+            ));
+        }
         self.emit_sent_message_telemetry(cx, view::Ranked::region(self));
 
         let Some(root_comm_actor) = self.proc_mesh.root_comm_actor() else {
@@ -628,8 +748,24 @@ impl<A: Referable> ActorMeshRef<A> {
         }
 
         let rank_index = rand::random::<u64>() as usize % num_ranks;
+        self.cast_rank_direct(cx, rank_index, message, caller_headers)
+    }
 
-        let point = region
+    /// Post a message directly to the actor at `rank_index` of this
+    /// mesh.
+    #[allow(clippy::result_large_err)]
+    fn cast_rank_direct<M>(
+        &self,
+        cx: &impl context::Actor,
+        rank_index: usize,
+        message: M,
+        caller_headers: &Flattrs,
+    ) -> crate::Result<()>
+    where
+        A: RemoteHandles<M>,
+        M: Castable + RemoteMessage,
+    {
+        let point = view::Ranked::region(self)
             .extent()
             .point_of_rank(rank_index)
             .map_err(|err| Error::CastingError(self.id.clone(), err.into()))?;
@@ -657,6 +793,8 @@ impl<A: Referable> ActorMeshRef<A> {
         A: RemoteHandles<M>,
         M: Castable + RemoteMessage,
     {
+        let point = multicast::composite_cast_point(caller_headers, point)
+            .map_err(|e| Error::CastingError(self.id.clone(), e))?;
         let create_rank = point.rank();
         let mut headers = caller_headers.clone();
         multicast::set_cast_info_on_headers(&mut headers, point, cx.instance().self_addr().clone());
@@ -699,6 +837,7 @@ impl<A: Referable> ActorMeshRef<A> {
                 casting::cast_to_sliced_mesh::<A, M>(
                     cx,
                     actor_mesh_id,
+                    self.stream_id().clone(),
                     root_comm_actor,
                     &sel,
                     message,
@@ -711,6 +850,7 @@ impl<A: Referable> ActorMeshRef<A> {
             None => casting::actor_mesh_cast::<A, M>(
                 cx,
                 actor_mesh_id,
+                self.stream_id().clone(),
                 root_comm_actor,
                 sel,
                 &cast_mesh_shape,
@@ -737,7 +877,7 @@ impl<A: Referable> ActorMeshRef<A> {
             "message_variant" => message.arm().unwrap_or_default(),
         ));
 
-        let actor_ids: ValueMesh<_> = self.proc_mesh.map_into(|proc| proc.actor_addr(&self.id));
+        let actor_ids: ValueMesh<_> = self.proc_mesh.map_into(|proc| self.actor_addr_on(proc));
 
         let mut headers = caller_headers.clone();
         headers.set(
@@ -796,9 +936,13 @@ impl<A: Referable> ActorMeshRef<A> {
             for rank in 0..num_ranks {
                 let mut rank_data = data.clone();
 
-                let cast_point = region
-                    .point_of_base_rank(rank)
-                    .expect("rank should be valid in region");
+                let cast_point = multicast::composite_cast_point(
+                    &headers,
+                    region
+                        .point_of_base_rank(rank)
+                        .expect("rank should be valid in region"),
+                )
+                .expect("composite meshes join parts of the same extent");
 
                 rank_data
                     .visit_mut::<resource::Rank>(|resource::Rank(r)| {
@@ -872,6 +1016,89 @@ impl<A: Referable> ActorMeshRef<A> {
         &self.id
     }
 
+    /// A reference to a heterogeneous mesh, whose ranks host actors of
+    /// different types that all handle the messages of `A`, typically
+    /// a behavior. The actor at each rank of `proc_mesh` is the one
+    /// named by the corresponding element of `actors`, and `id` names
+    /// the mesh as a whole. A cast to the mesh is delivered to each
+    /// rank's actor, so that one cast addresses every actor type.
+    /// Casts are sent point-to-point to each actor, rather than through
+    /// the comm actor tree, and the mesh ref is local: serializing it
+    /// fails.
+    ///
+    /// As with [`ActorRef::attest`], the caller asserts that the actors
+    /// handle `A`'s messages. The mesh has no controller: its actors
+    /// are supervised and stopped through the meshes that spawned them.
+    #[allow(clippy::result_large_err)]
+    pub fn heterogeneous(
+        id: ActorMeshId,
+        proc_mesh: ProcMeshRef,
+        actors: impl IntoIterator<Item = ActorMeshId>,
+    ) -> crate::Result<Self> {
+        let actors: Vec<_> = actors.into_iter().collect();
+        let num_ranks = view::Ranked::region(&proc_mesh).num_ranks();
+        if actors.len() != num_ranks {
+            return Err(Error::InvalidRankCardinality {
+                expected: num_ranks,
+                actual: actors.len(),
+            });
+        }
+        let rank_actors = proc_mesh
+            .values()
+            .zip(actors)
+            .filter(|(_, actor)| actor.uid() != id.uid())
+            .map(|(proc, actor)| (proc.create_rank(), actor.uid().clone()))
+            .collect();
+        let mut mesh = Self::new(id, proc_mesh, None);
+        mesh.rank_actors = Some(Arc::new(rank_actors));
+        Ok(mesh)
+    }
+
+    /// A sub-mesh of this mesh, comprising the actors at the points
+    /// selected by `sel`, which must form a region (see
+    /// [`casting::region_of_selection`]).
+    ///
+    /// Unlike a slice of this mesh, the sub-mesh has a cast stream of
+    /// its own: its casts travel through the comm actor tree with their
+    /// own routing and sequence numbers, and do not interfere with (or
+    /// wait on) the casts to this mesh. Libraries can therefore cast
+    /// to a part of a mesh without coordinating with its other users.
+    /// The sub-mesh is otherwise a slice of this mesh: it has this
+    /// mesh's [`ActorMeshId`] and actors, and its supervision. The
+    /// stream is local to the sub-mesh ref: a sub-mesh sent to another
+    /// actor casts as a slice of this mesh.
+    #[allow(clippy::result_large_err)]
+    pub fn submesh(&self, sel: &Selection) -> crate::Result<Self> {
+        let region = casting::region_of_selection(view::Ranked::region(self), sel)
+            .map_err(|err| Error::CastingError(self.id.clone(), err.into()))?;
+        let label = self
+            .id
+            .display_label()
+            .cloned()
+            .unwrap_or_else(|| Label::strip(ActorMeshId::default_instance_label()));
+        let mut submesh = view::RankedSliceable::sliced(self, region);
+        submesh.stream = Some(ActorMeshId::instance(label));
+        Ok(submesh)
+    }
+
+    /// The id of the stream on which casts to this mesh travel through
+    /// the comm actor tree.
+    pub(crate) fn stream_id(&self) -> &ActorMeshId {
+        self.stream.as_ref().unwrap_or(&self.id)
+    }
+
+    /// The address of this mesh's actor on `proc`.
+    fn actor_addr_on(&self, proc: &ProcRef) -> ActorAddr {
+        match self
+            .rank_actors
+            .as_ref()
+            .and_then(|rank_actors| rank_actors.get(&proc.create_rank()))
+        {
+            Some(uid) => proc.proc_addr().actor_addr_uid(uid.clone()),
+            None => proc.actor_addr(&self.id),
+        }
+    }
+
     pub(crate) fn with_page_size(
         id: ActorMeshId,
         proc_mesh: ProcMeshRef,
@@ -882,6 +1109,8 @@ impl<A: Referable> ActorMeshRef<A> {
             proc_mesh,
             id,
             controller,
+            rank_actors: None,
+            stream: None,
             health_state: ActorLocal::new(),
             receiver: ActorLocal::new(),
             pages: OnceCell::new(),
@@ -944,7 +1173,7 @@ impl<A: Referable> ActorMeshRef<A> {
             );
             let proc_ref =
                 ndslice::view::Ranked::get(&self.proc_mesh, rank).expect("rank in-bounds");
-            proc_ref.attest(&self.id)
+            ActorRef::attest(self.actor_addr_on(proc_ref))
         }))
     }
 
@@ -1097,6 +1326,8 @@ impl<A: Referable> ActorMeshRef<A> {
             proc_mesh: self.proc_mesh.clone(),
             id: self.id.clone(),
             controller: self.controller.clone(),
+            rank_actors: self.rank_actors.clone(),
+            stream: self.stream.clone(),
             health_state: self.health_state.clone(),
             receiver: self.receiver.clone(),
             // Cache does not support Clone at this time.
@@ -1112,6 +1343,8 @@ impl<A: Referable> Clone for ActorMeshRef<A> {
             proc_mesh: self.proc_mesh.clone(),
             id: self.id.clone(),
             controller: self.controller.clone(),
+            rank_actors: self.rank_actors.clone(),
+            stream: self.stream.clone(),
             // Cloning should not use the same health state or receiver, because
             // it should make a new subscriber.
             health_state: ActorLocal::new(),
@@ -1130,7 +1363,10 @@ impl<A: Referable> fmt::Display for ActorMeshRef<A> {
 
 impl<A: Referable> PartialEq for ActorMeshRef<A> {
     fn eq(&self, other: &Self) -> bool {
-        self.proc_mesh == other.proc_mesh && self.id == other.id
+        self.proc_mesh == other.proc_mesh
+            && self.id == other.id
+            && self.rank_actors == other.rank_actors
+            && self.stream == other.stream
     }
 }
 impl<A: Referable> Eq for ActorMeshRef<A> {}
@@ -1158,6 +1394,12 @@ impl<A: Referable> Serialize for ActorMeshRef<A> {
     where
         S: Serializer,
     {
+        if self.rank_actors.is_some() {
+            return Err(serde::ser::Error::custom(format!(
+                "heterogeneous mesh {} cannot be serialized",
+                self.id
+            )));
+        }
         (&self.proc_mesh, &self.id, &self.controller).serialize(serializer)
     }
 }
@@ -1208,6 +1450,8 @@ impl<A: Referable> view::RankedSliceable for ActorMeshRef<A> {
             proc_mesh,
             id: self.id.clone(),
             controller: self.controller.clone(),
+            rank_actors: self.rank_actors.clone(),
+            stream: self.stream.clone(),
             health_state: self.health_state.clone(),
             receiver: ActorLocal::new(),
             pages: OnceCell::new(),
@@ -1228,6 +1472,7 @@ mod tests {
     use hyperactor::context::Mailbox as _;
     use hyperactor::id::Label;
     use hyperactor::mailbox;
+    use hyperactor_mesh_macros::sel;
     use ndslice::Extent;
     use ndslice::Region;
     use ndslice::Slice;
@@ -1242,10 +1487,12 @@ mod tests {
     use super::ActorMesh;
     use crate::ActorMeshRef;
     use crate::ProcMesh;
+    use crate::ProcMeshRef;
     use crate::host_mesh::GET_PROC_STATE_MAX_IDLE;
     use crate::host_mesh::PROC_SPAWN_MAX_IDLE;
     use crate::mesh_controller::SUPERVISION_POLL_FREQUENCY;
     use crate::mesh_id::ActorMeshId;
+    use crate::mesh_id::ProcMeshId;
     use crate::proc_mesh::ACTOR_SPAWN_MAX_IDLE;
     use crate::proc_mesh::GET_ACTOR_STATE_MAX_IDLE;
     use crate::supervision::MeshFailure;
@@ -1746,6 +1993,253 @@ mod tests {
         let _ = host_mesh.shutdown(instance).await;
     }
 
+    #[async_timed_test(timeout_secs = 60)]
+    async fn test_mesh_client() {
+        use crate::gather::GatherOpts;
+        use crate::testactor::GetCastInfoMeshClient;
+
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(crate::bootstrap::MESH_BOOTSTRAP_ENABLE_PDEATHSIG, false);
+
+        let instance = testing::instance();
+        let mut host_mesh = testing::host_mesh(2).await;
+        let proc_mesh = host_mesh
+            .spawn(instance, "test", Extent::unity(), None, None)
+            .await
+            .unwrap();
+        let actor_mesh: ActorMesh<testactor::TestActor> =
+            proc_mesh.spawn(instance, "test", &()).await.unwrap();
+
+        // The generated method casts to every actor, and gathers one
+        // reply from each, attributed to the rank that sent it.
+        let replies = actor_mesh.get_cast_info(instance).unwrap();
+        assert_eq!(replies.expected(), 2);
+        let gathered = replies.gather(GatherOpts::default()).await;
+        assert!(gathered.is_complete());
+        for (rank, (point, _actor_ref, _sender)) in &gathered.replies {
+            assert_eq!(*rank, point.rank());
+        }
+        let ranks: HashSet<usize> = gathered.replies.iter().map(|(rank, _)| *rank).collect();
+        assert_eq!(ranks, HashSet::from([0, 1]));
+
+        // Casting to a slice gathers only from the slice.
+        let sliced = actor_mesh.sliced(Region::new(
+            vec!["rank".to_string()],
+            Slice::new(1, vec![1], vec![1]).unwrap(),
+        ));
+        let replies = sliced
+            .get_cast_info(instance)
+            .unwrap()
+            .gather(GatherOpts::with_deadline(Duration::from_secs(30)))
+            .await;
+        assert!(replies.is_complete());
+        assert_eq!(replies.replies.len(), 1);
+
+        let _ = host_mesh.shutdown(instance).await;
+    }
+
+    #[async_timed_test(timeout_secs = 60)]
+    async fn test_heterogeneous_cast() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(crate::bootstrap::MESH_BOOTSTRAP_ENABLE_PDEATHSIG, false);
+
+        let instance = testing::instance();
+        let mut host_mesh = testing::host_mesh(2).await;
+        let proc_mesh = host_mesh
+            .spawn(instance, "test", Extent::unity(), None, None)
+            .await
+            .unwrap();
+        let rank = |rank| {
+            Region::new(
+                vec!["rank".to_string()],
+                Slice::new(rank, vec![1], vec![1]).unwrap(),
+            )
+        };
+
+        // Each rank hosts a differently named actor.
+        let first: ActorMesh<testactor::TestActor> = proc_mesh
+            .sliced(rank(0))
+            .spawn(instance, "first", &())
+            .await
+            .unwrap();
+        let second: ActorMesh<testactor::TestActor> = proc_mesh
+            .sliced(rank(1))
+            .spawn(instance, "second", &())
+            .await
+            .unwrap();
+        let mesh = ActorMeshRef::<testactor::TestActor>::heterogeneous(
+            ActorMeshId::instance(Label::new("mixed").unwrap()),
+            (*proc_mesh).clone(),
+            [first.id().clone(), second.id().clone()],
+        )
+        .unwrap();
+        assert_eq!(
+            mesh.get(1).unwrap().actor_addr(),
+            second.get(0).unwrap().actor_addr()
+        );
+        // The per-rank actors are not sent over the wire.
+        assert!(wirevalue::Any::serialize(&mesh).is_err());
+
+        // One cast reaches the actor at each rank.
+        let (port, mut rx) = instance.mailbox().open_port();
+        mesh.cast(instance, testactor::GetActorId(port.bind()))
+            .unwrap();
+        let mut actors = HashSet::new();
+        for _ in 0..2 {
+            let (actor, _seq_info) = rx.recv().await.unwrap();
+            actors.insert(actor);
+        }
+        assert_eq!(
+            actors,
+            HashSet::from([
+                first.get(0).unwrap().actor_addr().clone(),
+                second.get(0).unwrap().actor_addr().clone(),
+            ])
+        );
+
+        // A mesh must name an actor for each rank.
+        assert!(
+            ActorMeshRef::<testactor::TestActor>::heterogeneous(
+                ActorMeshId::instance(Label::new("mixed").unwrap()),
+                (*proc_mesh).clone(),
+                [first.id().clone()],
+            )
+            .is_err()
+        );
+
+        let _ = host_mesh.shutdown(instance).await;
+    }
+
+    #[async_timed_test(timeout_secs = 30)]
+    async fn test_submesh() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(crate::bootstrap::MESH_BOOTSTRAP_ENABLE_PDEATHSIG, false);
+
+        let instance = testing::instance();
+        let mut host_mesh = testing::host_mesh(2).await;
+        let proc_mesh = host_mesh
+            .spawn(instance, "test", Extent::unity(), None, None)
+            .await
+            .unwrap();
+        let actor_mesh: ActorMesh<testactor::TestActor> =
+            proc_mesh.spawn(instance, "test", &()).await.unwrap();
+
+        // The sub-mesh is a slice of the parent with a stream of its own.
+        let submesh = actor_mesh.submesh(&sel!(1)).unwrap();
+        assert_eq!(submesh.id(), actor_mesh.id());
+        assert_ne!(submesh.stream_id(), actor_mesh.stream_id());
+        assert_eq!(
+            submesh.region(),
+            actor_mesh.range("rank", 1).unwrap().region()
+        );
+        assert_eq!(
+            submesh.get(0).unwrap().actor_addr(),
+            actor_mesh.get(1).unwrap().actor_addr()
+        );
+
+        // Casts to the sub-mesh and to the parent are delivered
+        // independently, to the parent's actors.
+        let (port, mut rx) = instance.mailbox().open_port();
+        submesh
+            .cast(instance, testactor::GetActorId(port.bind()))
+            .unwrap();
+        actor_mesh
+            .cast(instance, testactor::GetActorId(port.bind()))
+            .unwrap();
+        let mut actors = Vec::new();
+        for _ in 0..3 {
+            let (actor, _seq_info) = rx.recv().await.unwrap();
+            actors.push(actor);
+        }
+        actors.sort();
+        let mut expected = vec![
+            actor_mesh.get(0).unwrap().actor_addr().clone(),
+            actor_mesh.get(1).unwrap().actor_addr().clone(),
+            actor_mesh.get(1).unwrap().actor_addr().clone(),
+        ];
+        expected.sort();
+        assert_eq!(actors, expected);
+
+        let _ = host_mesh.shutdown(instance).await;
+    }
+
+    #[async_timed_test(timeout_secs = 60)]
+    async fn test_joined_mesh_cast() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(crate::bootstrap::MESH_BOOTSTRAP_ENABLE_PDEATHSIG, false);
+
+        let instance = testing::instance();
+        let mut host_mesh = testing::host_mesh(2).await;
+        // Two independently allocated stages, each with its own comm
+        // actor tree.
+        let mut stages = Vec::new();
+        for name in ["first", "second"] {
+            let proc_mesh = host_mesh
+                .spawn(instance, name, extent!(gpu = 2), None, None)
+                .await
+                .unwrap();
+            stages.push((*proc_mesh).clone());
+        }
+        let part_extent = stages[0].region().extent();
+        let proc_mesh = ProcMeshRef::join(
+            ProcMeshId::instance(Label::new("joined").unwrap()),
+            "stage",
+            stages,
+        )
+        .unwrap();
+        let extent = Extent::new(vec!["stage".to_string()], vec![2])
+            .unwrap()
+            .concat(&part_extent)
+            .unwrap();
+        assert_eq!(proc_mesh.region().extent(), extent);
+
+        let actor_mesh: ActorMesh<testactor::TestActor> =
+            proc_mesh.spawn(instance, "test", &()).await.unwrap();
+
+        // Actors receive their points in the composite mesh.
+        let (port, mut rx) = instance.mailbox().open_port();
+        actor_mesh
+            .cast(
+                instance,
+                testactor::GetCastInfo {
+                    cast_info: port.bind(),
+                },
+            )
+            .unwrap();
+        let mut ranks = Vec::new();
+        for _ in 0..extent.num_ranks() {
+            let (point, actor, _sender) = rx.recv().await.unwrap();
+            assert_eq!(point.extent(), &extent);
+            assert_eq!(
+                actor.actor_addr(),
+                actor_mesh.get(point.rank()).unwrap().actor_addr()
+            );
+            ranks.push(point.rank());
+        }
+        ranks.sort();
+        assert_eq!(ranks, (0..extent.num_ranks()).collect::<Vec<_>>());
+
+        // Slices of the composite are cast to in the same way.
+        let second = actor_mesh.range("stage", 1).unwrap();
+        second
+            .cast(
+                instance,
+                testactor::GetCastInfo {
+                    cast_info: port.bind(),
+                },
+            )
+            .unwrap();
+        for _ in 0..second.region().num_ranks() {
+            let (point, actor, _sender) = rx.recv().await.unwrap();
+            assert_eq!(
+                actor.actor_addr(),
+                second.get(point.rank()).unwrap().actor_addr()
+            );
+        }
+
+        let _ = host_mesh.shutdown(instance).await;
+    }
+
     #[async_timed_test(timeout_secs = 30)]
     async fn test_cast() {
         let config = hyperactor_config::global::lock();
@@ -0,0 +1,491 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! External alerting for supervision events.
+//!
+//! Actor failures, bursts of undeliverable messages, and unhealthy
+//! meshes are forwarded to the sinks listed in
+//! [`SUPERVISION_ALERT_SINKS`], a comma-separated list of:
+//!
+//! - `webhook:<url>`: POST a JSON object with the alert's `kind`,
+//!   `subject`, `message`, and rendered `text`;
+//! - `slack:<url>`: POST a Slack-compatible `{"text": ...}` payload,
+//!   as accepted by incoming webhooks;
+//! - `syslog`: send the rendered text to the local syslog socket.
+//!
+//! The text of an alert is rendered from
+//! [`SUPERVISION_ALERT_TEMPLATE`], substituting the `{kind}`,
+//! `{subject}` and `{message}` placeholders.
+//!
+//! Alerts are rate limited to [`SUPERVISION_ALERT_RATE_LIMIT`] per
+//! [`SUPERVISION_ALERT_RATE_WINDOW`]. Alerts over the limit are
+//! dropped and counted, and the count is reported with the next alert
+//! that is sent. An undeliverable burst is raised when a proc sees
+//! [`SUPERVISION_ALERT_UNDELIVERABLE_BURST`] undeliverable messages
+//! within one window.
+//!
+//! Each failure is raised once: an actor failure by the agent of the
+//! actor's proc, and a mesh as unhealthy by its controller only when
+//! one of its procs fails, as there is then no agent to report it.
+//!
+//! The sinks are read from the configuration when the first alert is
+//! raised from within a tokio runtime. Alerts are delivered by a
+//! background task, to all sinks concurrently, each delivery bounded by
+//! [`SUPERVISION_ALERT_TIMEOUT`]; raising an alert never blocks, and
+//! delivery failures are logged.
+
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::time::Instant;
+
+use hyperactor_config::CONFIG;
+use hyperactor_config::ConfigAttr;
+use hyperactor_config::attrs::declare_attrs;
+use tokio::sync::mpsc;
+
+declare_attrs! {
+    /// Comma-separated list of sinks to forward supervision alerts
+    /// to. See [`crate::alert`].
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_SUPERVISION_ALERT_SINKS".to_string()),
+        Some("supervision_alert_sinks".to_string()),
+    ))
+    pub attr SUPERVISION_ALERT_SINKS: String = String::new();
+
+    /// Template for the text of supervision alerts.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_SUPERVISION_ALERT_TEMPLATE".to_string()),
+        Some("supervision_alert_template".to_string()),
+    ))
+    pub attr SUPERVISION_ALERT_TEMPLATE: String = "[monarch {kind}] {subject}: {message}".to_string();
+
+    /// The maximum number of supervision alerts sent per
+    /// [`SUPERVISION_ALERT_RATE_WINDOW`].
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_SUPERVISION_ALERT_RATE_LIMIT".to_string()),
+        Some("supervision_alert_rate_limit".to_string()),
+    ))
+    pub attr SUPERVISION_ALERT_RATE_LIMIT: usize = 10;
+
+    /// The window over which supervision alerts are rate limited and
+    /// undeliverable bursts are counted.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_SUPERVISION_ALERT_RATE_WINDOW".to_string()),
+        Some("supervision_alert_rate_window".to_string()),
+    ))
+    pub attr SUPERVISION_ALERT_RATE_WINDOW: Duration = Duration::from_secs(60);
+
+    /// The number of undeliverable messages within a window that
+    /// raises an undeliverable burst alert.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_SUPERVISION_ALERT_UNDELIVERABLE_BURST".to_string()),
+        Some("supervision_alert_undeliverable_burst".to_string()),
+    ))
+    pub attr SUPERVISION_ALERT_UNDELIVERABLE_BURST: usize = 100;
+
+    /// The maximum time to deliver an alert to one sink.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_SUPERVISION_ALERT_TIMEOUT".to_string()),
+        Some("supervision_alert_timeout".to_string()),
+    ))
+    pub attr SUPERVISION_ALERT_TIMEOUT: Duration = Duration::from_secs(10);
+}
+
+/// An event that operators should be alerted to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Alert {
+    /// An actor failed.
+    ActorFailure {
+        /// The failed actor.
+        actor: String,
+        /// The failure.
+        message: String,
+    },
+    /// A proc saw a burst of undeliverable messages.
+    UndeliverableBurst {
+        /// The proc.
+        proc: String,
+        /// The number of undeliverable messages.
+        count: usize,
+        /// The window in which they were seen.
+        window: Duration,
+    },
+    /// A monitored mesh became unhealthy.
+    MeshUnhealthy {
+        /// The mesh.
+        mesh: String,
+        /// The failure.
+        message: String,
+    },
+}
+
+impl Alert {
+    /// A short, stable name for the kind of alert.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Alert::ActorFailure { .. } => "actor_failure",
+            Alert::UndeliverableBurst { .. } => "undeliverable_burst",
+            Alert::MeshUnhealthy { .. } => "mesh_unhealthy",
+        }
+    }
+
+    /// What the alert is about: an actor, proc, or mesh.
+    pub fn subject(&self) -> &str {
+        match self {
+            Alert::ActorFailure { actor, .. } => actor,
+            Alert::UndeliverableBurst { proc, .. } => proc,
+            Alert::MeshUnhealthy { mesh, .. } => mesh,
+        }
+    }
+
+    /// A description of the alert.
+    pub fn message(&self) -> String {
+        match self {
+            Alert::ActorFailure { message, .. } | Alert::MeshUnhealthy { message, .. } => {
+                message.clone()
+            }
+            Alert::UndeliverableBurst { count, window, .. } => format!(
+                "{} undeliverable messages in {}",
+                count,
+                humantime::format_duration(*window)
+            ),
+        }
+    }
+
+    /// Render the alert's text from `template`.
+    pub fn render(&self, template: &str) -> String {
+        template
+            .replace("{kind}", self.kind())
+            .replace("{subject}", self.subject())
+            .replace("{message}", &self.message())
+    }
+}
+
+/// A destination for alerts.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sink {
+    /// POST a JSON description of the alert to a URL.
+    Webhook(String),
+    /// POST a Slack-compatible message to an incoming webhook URL.
+    Slack(String),
+    /// Send the alert text to the local syslog socket.
+    Syslog,
+}
+
+impl FromStr for Sink {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("webhook", url)) => Ok(Sink::Webhook(url.to_string())),
+            Some(("slack", url)) => Ok(Sink::Slack(url.to_string())),
+            None if s == "syslog" => Ok(Sink::Syslog),
+            _ => anyhow::bail!("invalid alert sink {:?}", s),
+        }
+    }
+}
+
+/// Parse a comma-separated list of sinks.
+pub fn parse_sinks(spec: &str) -> anyhow::Result<Vec<Sink>> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Limits alerts to `limit` per `window`.
+struct RateLimiter {
+    limit: usize,
+    window: Duration,
+    sent: VecDeque<Instant>,
+    suppressed: usize,
+}
+
+impl RateLimiter {
+    fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            sent: VecDeque::new(),
+            suppressed: 0,
+        }
+    }
+
+    /// Admit an alert at `now`, returning the number of alerts
+    /// suppressed since the last admitted one, or `None` if the alert
+    /// is over the limit.
+    fn admit(&mut self, now: Instant) -> Option<usize> {
+        while self
+            .sent
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= self.window)
+        {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= self.limit {
+            self.suppressed += 1;
+            return None;
+        }
+        self.sent.push_back(now);
+        Some(std::mem::take(&mut self.suppressed))
+    }
+}
+
+/// Counts undeliverable messages in fixed windows.
+#[derive(Debug)]
+struct BurstCounter {
+    start: Instant,
+    count: usize,
+}
+
+impl BurstCounter {
+    /// Count an undeliverable message at `now`, returning the count
+    /// when it reaches `threshold` within `window`.
+    fn record(&mut self, now: Instant, threshold: usize, window: Duration) -> Option<usize> {
+        if now.duration_since(self.start) >= window {
+            self.start = now;
+            self.count = 0;
+        }
+        self.count += 1;
+        (self.count == threshold).then_some(self.count)
+    }
+}
+
+/// The alerts admitted by the rate limiter, with the number of alerts
+/// suppressed before each, queued for the delivery task.
+struct Alerts {
+    limiter: Mutex<RateLimiter>,
+    queue: mpsc::Sender<(Alert, usize)>,
+}
+
+/// The alert queue, if any sinks are configured. The configuration is
+/// read, and the delivery task spawned, on the first call from within
+/// a tokio runtime.
+fn alerts() -> Option<&'static Alerts> {
+    static ALERTS: OnceLock<Option<Alerts>> = OnceLock::new();
+    if let Some(alerts) = ALERTS.get() {
+        return alerts.as_ref();
+    }
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        // Retried on the next call.
+        return None;
+    };
+    ALERTS
+        .get_or_init(|| {
+            let sinks = match parse_sinks(&hyperactor_config::global::get_cloned(
+                SUPERVISION_ALERT_SINKS,
+            )) {
+                Ok(sinks) if !sinks.is_empty() => sinks,
+                Ok(_) => return None,
+                Err(err) => {
+                    tracing::error!("supervision alerts disabled: {:#}", err);
+                    return None;
+                }
+            };
+            let limit = hyperactor_config::global::get(SUPERVISION_ALERT_RATE_LIMIT);
+            // Admitted alerts are bounded by the rate limit, so the
+            // queue only fills if delivery falls a whole window behind.
+            let (queue, rx) = mpsc::channel(limit.max(1));
+            runtime.spawn(deliver_alerts(sinks, rx));
+            Some(Alerts {
+                limiter: Mutex::new(RateLimiter::new(
+                    limit,
+                    hyperactor_config::global::get(SUPERVISION_ALERT_RATE_WINDOW),
+                )),
+                queue,
+            })
+        })
+        .as_ref()
+}
+
+/// Raise an alert, to be delivered to the configured sinks.
+pub fn raise(alert: Alert) {
+    let Some(alerts) = alerts() else {
+        return;
+    };
+    let mut limiter = alerts.limiter.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(suppressed) = limiter.admit(Instant::now()) else {
+        return;
+    };
+    if alerts.queue.try_send((alert, suppressed)).is_err() {
+        // Report the alert, and those suppressed before it, with the
+        // next one.
+        limiter.suppressed += suppressed + 1;
+    }
+}
+
+/// Counts the undeliverable messages seen by one proc, raising an
+/// [`Alert::UndeliverableBurst`] when the count reaches the burst
+/// threshold within a window.
+#[derive(Debug, Default)]
+pub(crate) struct UndeliverableBursts(Option<BurstCounter>);
+
+impl UndeliverableBursts {
+    /// Count an undeliverable message seen by `proc`.
+    pub(crate) fn record(&mut self, proc: &impl Display) {
+        if alerts().is_none() {
+            return;
+        }
+        let threshold = hyperactor_config::global::get(SUPERVISION_ALERT_UNDELIVERABLE_BURST);
+        let window = hyperactor_config::global::get(SUPERVISION_ALERT_RATE_WINDOW);
+        let now = Instant::now();
+        let count = self
+            .0
+            .get_or_insert_with(|| BurstCounter {
+                start: now,
+                count: 0,
+            })
+            .record(now, threshold, window);
+        if let Some(count) = count {
+            raise(Alert::UndeliverableBurst {
+                proc: proc.to_string(),
+                count,
+                window,
+            });
+        }
+    }
+}
+
+async fn deliver_alerts(sinks: Vec<Sink>, mut rx: mpsc::Receiver<(Alert, usize)>) {
+    let client = reqwest::Client::new();
+    while let Some((alert, suppressed)) = rx.recv().await {
+        let mut text = alert.render(&hyperactor_config::global::get_cloned(
+            SUPERVISION_ALERT_TEMPLATE,
+        ));
+        if suppressed > 0 {
+            text.push_str(&format!(" ({} earlier alerts suppressed)", suppressed));
+        }
+        let timeout = hyperactor_config::global::get(SUPERVISION_ALERT_TIMEOUT);
+        futures::future::join_all(sinks.iter().map(|sink| async {
+            match tokio::time::timeout(timeout, deliver(&client, sink, &alert, &text)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => {
+                    tracing::warn!(?sink, "failed to deliver supervision alert: {:#}", err)
+                }
+                Err(_) => tracing::warn!(
+                    ?sink,
+                    "timed out delivering supervision alert after {}",
+                    humantime::format_duration(timeout)
+                ),
+            }
+        }))
+        .await;
+    }
+}
+
+async fn deliver(
+    client: &reqwest::Client,
+    sink: &Sink,
+    alert: &Alert,
+    text: &str,
+) -> anyhow::Result<()> {
+    match sink {
+        Sink::Webhook(url) => {
+            client
+                .post(url)
+                .json(&serde_json::json!({
+                    "kind": alert.kind(),
+                    "subject": alert.subject(),
+                    "message": alert.message(),
+                    "text": text,
+                }))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Sink::Slack(url) => {
+            client
+                .post(url)
+                .json(&serde_json::json!({ "text": text }))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Sink::Syslog => {
+            // Facility user (1); severity error (3) for failures and
+            // warning (4) for bursts.
+            let severity = match alert {
+                Alert::UndeliverableBurst { .. } => 4,
+                _ => 3,
+            };
+            let socket = tokio::net::UnixDatagram::unbound()?;
+            socket
+                .send_to(
+                    format!("<{}>monarch: {}", 8 + severity, text).as_bytes(),
+                    "/dev/log",
+                )
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sinks() {
+        assert_eq!(
+            parse_sinks(
+                "webhook:https://example.com/hook, slack:https://hooks.example.com/x,syslog"
+            )
+            .unwrap(),
+            vec![
+                Sink::Webhook("https://example.com/hook".to_string()),
+                Sink::Slack("https://hooks.example.com/x".to_string()),
+                Sink::Syslog,
+            ]
+        );
+        assert_eq!(parse_sinks("").unwrap(), vec![]);
+        assert!(parse_sinks("pager:123").is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let alert = Alert::UndeliverableBurst {
+            proc: "proc[0]".to_string(),
+            count: 100,
+            window: Duration::from_secs(60),
+        };
+        assert_eq!(
+            alert.render("[monarch {kind}] {subject}: {message}"),
+            "[monarch undeliverable_burst] proc[0]: 100 undeliverable messages in 1m"
+        );
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::new(2, Duration::from_secs(10));
+        assert_eq!(limiter.admit(start), Some(0));
+        assert_eq!(limiter.admit(start), Some(0));
+        assert_eq!(limiter.admit(start + Duration::from_secs(1)), None);
+        assert_eq!(limiter.admit(start + Duration::from_secs(2)), None);
+        // The first two alerts leave the window.
+        assert_eq!(limiter.admit(start + Duration::from_secs(10)), Some(2));
+    }
+
+    #[test]
+    fn test_burst_counter() {
+        let start = Instant::now();
+        let window = Duration::from_secs(10);
+        let mut counter = BurstCounter { start, count: 0 };
+        assert_eq!(counter.record(start, 3, window), None);
+        assert_eq!(counter.record(start, 3, window), None);
+        assert_eq!(counter.record(start, 3, window), Some(3));
+        // Raised once per window.
+        assert_eq!(counter.record(start, 3, window), None);
+        assert_eq!(counter.record(start + window, 3, window), None);
+    }
+}
@@ -69,7 +69,10 @@ use tracing::Instrument;
 use tracing::Level;
 use typeuri::Named;
 
+use crate::chaos;
 use crate::config::MESH_PROC_LAUNCHER_KIND;
+use crate::handshake;
+use crate::handshake::Hello;
 use crate::host::BulkTerminate;
 use crate::host::Host;
 use crate::host::HostError;
@@ -86,6 +89,10 @@ use crate::host_mesh::host_agent::HostAgentMode;
 use crate::logging::OutputTarget;
 use crate::logging::StreamFwder;
 use crate::proc_agent::ProcAgent;
+#[cfg(target_os = "linux")]
+use crate::proc_launcher::CgroupProcLauncher;
+#[cfg(target_os = "linux")]
+use crate::proc_launcher::ContainerProcLauncher;
 use crate::proc_launcher::LaunchOptions;
 use crate::proc_launcher::NativeProcLauncher;
 use crate::proc_launcher::ProcExitKind;
@@ -352,6 +359,16 @@ pub enum Bootstrap {
         /// as the `ClientOverride` layer so the parent's effective config
         /// takes precedence over Defaults.
         config: Option<Attrs>,
+        /// The parent's side of the bootstrap handshake. If present,
+        /// the child refuses to boot unless it is compatible; see
+        /// [`crate::handshake`].
+        #[serde(default)]
+        hello: Option<Hello>,
+        /// If present, the address to which the child sends its own
+        /// side of the handshake, before calling back on
+        /// `callback_addr`.
+        #[serde(default)]
+        handshake_addr: Option<ChannelAddr>,
     },
 
     /// Bootstrap as a "v1" host bootstrap. This sets up a new `Host`,
@@ -369,6 +386,11 @@ pub enum Bootstrap {
         config: Option<Attrs>,
         /// If true, exit the process after handling a shutdown request.
         exit_on_shutdown: bool,
+        /// The parent's side of the bootstrap handshake. If present,
+        /// the child refuses to boot unless it is compatible; see
+        /// [`crate::handshake`].
+        #[serde(default)]
+        hello: Option<Hello>,
     },
 }
 
@@ -462,7 +484,9 @@ impl Bootstrap {
                 backend_addr,
                 callback_addr,
                 socket_dir_path,
-                config,
+                mut config,
+                hello,
+                handshake_addr,
             } => {
                 let entered = tracing::span!(
                     Level::INFO,
@@ -473,6 +497,11 @@ impl Bootstrap {
                     socket_dir_path = %socket_dir_path.display(),
                 )
                 .entered();
+                if let Some(hello) = hello {
+                    let agreement = handshake::accept_parent(&hello, &mut config)
+                        .context("bootstrap handshake with parent failed")?;
+                    tracing::debug!(?agreement, "bootstrap: handshake with parent");
+                }
                 if let Some(attrs) = config {
                     hyperactor_config::global::set(
                         hyperactor_config::global::Source::ClientOverride,
@@ -503,10 +532,19 @@ impl Bootstrap {
                 let proc_sender = mailbox::LocalProcDialer::new(
                     local_addr.clone(),
                     socket_dir_path,
-                    MailboxClient::dial(backend_addr)?,
+                    MailboxClient::dial_with_class(
+                        backend_addr,
+                        hyperactor::mailbox::shaping::ChannelClass::configured(),
+                    )?,
                 );
 
-                let proc = Proc::configured(proc_id.clone(), proc_sender.into_boxed());
+                let proc_sender = if hyperactor_config::global::get(chaos::MESH_CHAOS_ENABLED) {
+                    tracing::warn!("bootstrap: injecting faults into outbound messages");
+                    chaos::ChaosSender::new(proc_sender).into_boxed()
+                } else {
+                    proc_sender.into_boxed()
+                };
+                let proc = Proc::configured(proc_id.clone(), proc_sender);
 
                 let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<i32>();
                 let agent_handle = ProcAgent::boot_v1(proc.clone(), Some(shutdown_tx))
@@ -518,6 +556,13 @@ impl Bootstrap {
                 // and call back.
                 let (proc_addr, proc_rx) = channel::serve(serve_addr)?;
                 let mailbox_handle = proc.clone().serve(proc_rx);
+                if let Some(handshake_addr) = handshake_addr {
+                    channel::dial(handshake_addr)?
+                        .send(Hello::local().with_schemas())
+                        .instrument(span.clone())
+                        .await
+                        .map_err(ChannelError::from)?;
+                }
                 channel::dial(callback_addr)?
                     .send((proc_addr, agent_handle.bind::<ProcAgent>()))
                     .instrument(span)
@@ -537,9 +582,15 @@ impl Bootstrap {
             Bootstrap::Host {
                 addr,
                 command,
-                config,
+                mut config,
                 exit_on_shutdown,
+                hello,
             } => {
+                if let Some(hello) = hello {
+                    let agreement = handshake::accept_parent(&hello, &mut config)
+                        .context("bootstrap handshake with parent failed")?;
+                    tracing::debug!(?agreement, "bootstrap: handshake with parent");
+                }
                 let (_agent_handle, shutdown) = host(
                     addr,
                     command,
@@ -1545,11 +1596,18 @@ impl<T: Into<PathBuf>> From<T> for BootstrapCommand {
 /// - [`LauncherKind::Systemd`]: delegates supervision to `systemd
 ///   --user` by creating transient `.service` units and observing
 ///   lifecycle via D-Bus.
+/// - [`LauncherKind::Cgroup`]: spawns child processes like
+///   [`LauncherKind::Native`], each in its own cgroup v2 with CPU and
+///   memory limits.
+/// - [`LauncherKind::Container`]: runs each proc in a container with
+///   `docker` or `podman`.
 ///
 /// Configuration/parsing:
 /// - The empty string and `"native"` map to [`LauncherKind::Native`]
 ///   (default).
 /// - `"systemd"` maps to [`LauncherKind::Systemd`].
+/// - `"cgroup"` maps to [`LauncherKind::Cgroup`].
+/// - `"container"` maps to [`LauncherKind::Container`].
 /// - Any other value is rejected as [`io::ErrorKind::InvalidInput`].
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum LauncherKind {
@@ -1560,6 +1618,13 @@ pub(crate) enum LauncherKind {
     /// D-Bus.
     #[cfg(target_os = "linux")]
     Systemd,
+    /// Spawn OS children directly, each under its own cgroup v2
+    /// limits.
+    #[cfg(target_os = "linux")]
+    Cgroup,
+    /// Run each proc in a container of the configured image.
+    #[cfg(target_os = "linux")]
+    Container,
 }
 
 impl FromStr for LauncherKind {
@@ -1571,6 +1636,8 @@ impl FromStr for LauncherKind {
     /// ignored):
     /// - `""` or `"native"` → [`LauncherKind::Native`]
     /// - `"systemd"` → [`LauncherKind::Systemd`] (Linux only)
+    /// - `"cgroup"` → [`LauncherKind::Cgroup`] (Linux only)
+    /// - `"container"` → [`LauncherKind::Container`] (Linux only)
     ///
     /// Returns [`io::ErrorKind::InvalidInput`] for any other string.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -1578,12 +1645,16 @@ impl FromStr for LauncherKind {
             "" | "native" => Ok(Self::Native),
             #[cfg(target_os = "linux")]
             "systemd" => Ok(Self::Systemd),
+            #[cfg(target_os = "linux")]
+            "cgroup" => Ok(Self::Cgroup),
+            #[cfg(target_os = "linux")]
+            "container" => Ok(Self::Container),
             other => Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 format!(
                     "unknown proc launcher kind {other:?}; expected 'native'{}",
                     if cfg!(target_os = "linux") {
-                        " or 'systemd'"
+                        ", 'systemd', 'cgroup' or 'container'"
                     } else {
                         ""
                     }
@@ -1706,6 +1777,10 @@ impl BootstrapProcManager {
                 LauncherKind::Native => Arc::new(NativeProcLauncher::new()),
                 #[cfg(target_os = "linux")]
                 LauncherKind::Systemd => Arc::new(SystemdProcLauncher::new()),
+                #[cfg(target_os = "linux")]
+                LauncherKind::Cgroup => Arc::new(CgroupProcLauncher::new()),
+                #[cfg(target_os = "linux")]
+                LauncherKind::Container => Arc::new(ContainerProcLauncher::new()),
             }
         })
     }
@@ -1947,6 +2022,8 @@ impl ProcManager for BootstrapProcManager {
         let (callback_addr, mut callback_rx) = channel::serve::<(ChannelAddr, ActorRef<ProcAgent>)>(
             ChannelAddr::any(ChannelTransport::Unix),
         )?;
+        let (handshake_addr, mut handshake_rx) =
+            channel::serve::<Hello>(ChannelAddr::any(ChannelTransport::Unix))?;
 
         // Decide whether we need to capture stdio.
         let overrides = &config.client_config_override;
@@ -1961,6 +2038,8 @@ impl ProcManager for BootstrapProcManager {
             callback_addr,
             socket_dir_path: self.socket_dir.path().to_owned(),
             config: Some(config.client_config_override.clone()),
+            hello: Some(Hello::local()),
+            handshake_addr: Some(handshake_addr),
         };
 
         // Build LaunchOptions for the launcher.
@@ -2074,7 +2153,26 @@ impl ProcManager for BootstrapProcManager {
         tokio::spawn(async move {
             match callback_rx.recv().await {
                 Ok((addr, agent)) => {
-                    let _ = h.mark_ready(addr, agent);
+                    // The child sends its hello, if any, before calling
+                    // back; children predating the handshake send none.
+                    let hello =
+                        match tokio::time::timeout(Duration::ZERO, handshake_rx.recv()).await {
+                            Ok(Ok(hello)) => Some(hello),
+                            _ => None,
+                        };
+                    match hello.map(|hello| handshake::accept_child(&hello)) {
+                        Some(Ok(agreement)) => {
+                            tracing::debug!(proc_id = %h.proc_id, ?agreement, "bootstrap handshake");
+                            let _ = h.mark_ready(addr, agent);
+                        }
+                        Some(Err(e)) => {
+                            let _ = h.mark_failed(format!("bootstrap handshake failed: {e}"));
+                        }
+                        None => {
+                            tracing::debug!(proc_id = %h.proc_id, "bootstrap: child sent no hello");
+                            let _ = h.mark_ready(addr, agent);
+                        }
+                    }
                 }
                 Err(e) => {
                     // Child never called back; record failure.
@@ -2368,6 +2466,8 @@ mod tests {
             callback_addr: ChannelAddr::any(ChannelTransport::Unix),
             socket_dir_path: PathBuf::from("notexist"),
             config: None,
+            hello: None,
+            handshake_addr: None,
         };
 
         let safe = value.to_env_safe_string().unwrap();
@@ -2392,6 +2492,7 @@ mod tests {
             command: None,
             config: None,
             exit_on_shutdown: false,
+            hello: None,
         };
 
         let safe = value.to_env_safe_string().unwrap();
@@ -2408,6 +2509,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_bootstrap_mode_without_handshake() {
+        // Payloads from parents predating the handshake omit its
+        // fields, and still decode.
+        let value = Bootstrap::Proc {
+            proc_id: test_proc_id("foo_0"),
+            backend_addr: ChannelAddr::any(ChannelTransport::Unix),
+            callback_addr: ChannelAddr::any(ChannelTransport::Unix),
+            socket_dir_path: PathBuf::from("notexist"),
+            config: None,
+            hello: Some(Hello::local()),
+            handshake_addr: Some(ChannelAddr::any(ChannelTransport::Unix)),
+        };
+        let mut json = serde_json::to_value(&value).unwrap();
+        let fields = json["Proc"].as_object_mut().unwrap();
+        fields.remove("hello").unwrap();
+        fields.remove("handshake_addr").unwrap();
+        match serde_json::from_value(json).unwrap() {
+            Bootstrap::Proc {
+                hello: None,
+                handshake_addr: None,
+                ..
+            } => {}
+            other => panic!("expected Proc without handshake, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_bootstrap_mode_env_string_invalid() {
         // Not valid base64
@@ -2431,6 +2559,8 @@ mod tests {
                 callback_addr: ChannelAddr::any(ChannelTransport::Unix),
                 config: Some(attrs.clone()),
                 socket_dir_path: socket_dir.path().to_owned(),
+                hello: Some(Hello::local()),
+                handshake_addr: Some(ChannelAddr::any(ChannelTransport::Unix)),
             };
             let env_str = original.to_env_safe_string().expect("encode bootstrap");
             let decoded = Bootstrap::from_env_safe_string(&env_str).expect("decode bootstrap");
@@ -2451,6 +2581,7 @@ mod tests {
                 command: None,
                 config: Some(attrs.clone()),
                 exit_on_shutdown: false,
+                hello: Some(Hello::local()),
             };
             let env_str = original.to_env_safe_string().expect("encode bootstrap");
             let decoded = Bootstrap::from_env_safe_string(&env_str).expect("decode bootstrap");
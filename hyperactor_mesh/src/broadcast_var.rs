@@ -0,0 +1,713 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Versioned state replicated to every rank of a mesh.
+//!
+//! A [`BroadcastVar`] is owned by a single controller, which publishes
+//! successive versions of a value (hyperparameters, say) to a mesh of
+//! [`BroadcastVarReplica`]s, one per proc:
+//!
+//! - [`BroadcastVar::publish`] casts the new version to the replicas,
+//!   and waits until every rank has acked it. Acks are summed by an
+//!   accumulator port, so they are reduced in the comm tree rather than
+//!   delivered to the owner one by one.
+//! - Each replica installs the versions it receives in a table scoped
+//!   to its proc, keeping only the newest: versions that arrive out of
+//!   order are acked but otherwise ignored.
+//! - Actors on the replica's proc read the variable through a
+//!   [`BroadcastVarReader`], which returns the latest installed
+//!   version, or waits for a version at least as new as a given one.
+//!
+//! Each owner stamps its versions with an epoch, taken when it is
+//! created, and replicas order versions by epoch first. A restarted
+//! owner, whose version numbers start over, therefore supersedes the
+//! versions of its predecessor; a replica that has installed a version
+//! from a newer owner reports a superseded version to its publisher,
+//! whose [`BroadcastVar::publish`] then fails rather than reporting the
+//! replica as up to date.
+//!
+//! A replica that joins late (e.g. on a proc mesh spawned after the
+//! variable was created) is spawned with the variable's
+//! [`BroadcastVar::subscription`] in its [`BroadcastVarReplicaParams`].
+//! It subscribes when it starts, and the owner sends it the latest
+//! version, and every later one, until it stops.
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use hyperactor::Actor;
+use hyperactor::ActorRef;
+use hyperactor::Bind;
+use hyperactor::Client;
+use hyperactor::Context;
+use hyperactor::Endpoint as _;
+use hyperactor::Handler;
+use hyperactor::Instance;
+use hyperactor::PortRef;
+use hyperactor::ProcId;
+use hyperactor::RemoteSpawn;
+use hyperactor::Unbind;
+use hyperactor::accum;
+use hyperactor::actor::ActorError;
+use hyperactor::context;
+use hyperactor::context::Mailbox as _;
+use hyperactor_config::CONFIG;
+use hyperactor_config::ConfigAttr;
+use hyperactor_config::Flattrs;
+use hyperactor_config::attrs::declare_attrs;
+use ndslice::view::Ranked;
+use serde::Deserialize;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use typeuri::Named;
+
+use crate::ActorMeshRef;
+
+declare_attrs! {
+    /// How long [`BroadcastVar::publish`] waits for every replica to
+    /// ack a version.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_BROADCAST_VAR_ACK_TIMEOUT".to_string()),
+        Some("broadcast_var_ack_timeout".to_string()),
+    ))
+    pub attr BROADCAST_VAR_ACK_TIMEOUT: Duration = Duration::from_secs(30);
+}
+
+/// An installed version of a broadcast variable: the epoch of the
+/// owner that published it, its version number, and its serialized
+/// value.
+type Snapshot = Option<(u64, u64, wirevalue::Any)>;
+
+/// The latest installed version of each broadcast variable, by proc
+/// and name.
+static INSTALLED: LazyLock<Mutex<HashMap<ProcId, HashMap<String, watch::Sender<Snapshot>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn subscribe(proc_id: &ProcId, name: &str) -> watch::Receiver<Snapshot> {
+    INSTALLED
+        .lock()
+        .unwrap()
+        .entry(proc_id.clone())
+        .or_default()
+        .entry(name.to_string())
+        .or_insert_with(|| watch::channel(None).0)
+        .subscribe()
+}
+
+/// The outcome of [`install`].
+#[derive(Debug, PartialEq, Eq)]
+enum Install {
+    /// The version was installed.
+    Installed,
+    /// The same owner had already installed a version at least as new.
+    Stale,
+    /// A newer owner had already installed a version.
+    Superseded,
+}
+
+/// Install version `version` of the variable `name`, published by the
+/// owner with epoch `epoch`, on the proc `proc_id`, unless a version
+/// at least as new is already installed there.
+fn install(
+    proc_id: &ProcId,
+    name: &str,
+    epoch: u64,
+    version: u64,
+    value: wirevalue::Any,
+) -> Install {
+    let mut outcome = Install::Installed;
+    INSTALLED
+        .lock()
+        .unwrap()
+        .entry(proc_id.clone())
+        .or_default()
+        .entry(name.to_string())
+        .or_insert_with(|| watch::channel(None).0)
+        .send_if_modified(|snapshot| {
+            if let Some((installed_epoch, installed, _)) = snapshot {
+                if *installed_epoch > epoch {
+                    outcome = Install::Superseded;
+                    return false;
+                }
+                if *installed_epoch == epoch && *installed >= version {
+                    outcome = Install::Stale;
+                    return false;
+                }
+            }
+            *snapshot = Some((epoch, version, value));
+            true
+        });
+    outcome
+}
+
+/// Install a version of a broadcast variable on every replica it is
+/// cast to. Each replica acks with `1` on `ack`, or, if it has
+/// installed a version from a newer owner, on `superseded`.
+#[derive(Debug, Clone, Serialize, Deserialize, Named, Bind, Unbind)]
+pub struct InstallVersion {
+    /// The name of the variable.
+    pub name: String,
+    /// The epoch of the owner publishing the version.
+    pub epoch: u64,
+    /// The version number; versions are installed only if they are
+    /// newer than the installed version.
+    pub version: u64,
+    /// The serialized value.
+    pub value: wirevalue::Any,
+    #[binding(include)]
+    pub ack: PortRef<u64>,
+    #[binding(include)]
+    pub superseded: PortRef<u64>,
+}
+wirevalue::register_type!(InstallVersion);
+
+/// A replica joining or leaving the replicas of a broadcast variable
+/// after it was created; see [`BroadcastVar::subscription`].
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub enum Subscription {
+    /// Send the replica the latest version, and every later one.
+    Join(ActorRef<BroadcastVarReplica>),
+    /// Stop sending versions to the replica.
+    Leave(ActorRef<BroadcastVarReplica>),
+}
+wirevalue::register_type!(Subscription);
+
+/// Parameters for [`BroadcastVarReplica`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Named)]
+pub struct BroadcastVarReplicaParams {
+    /// The [`BroadcastVar::subscription`]s of the variables the replica
+    /// should be brought up to date with when it starts.
+    pub subscriptions: Vec<PortRef<Subscription>>,
+}
+wirevalue::register_type!(BroadcastVarReplicaParams);
+
+/// An actor, spawned on each proc of a mesh, that installs the
+/// versions of broadcast variables cast to it; see the
+/// [module documentation](self).
+///
+/// Versions are installed in a table scoped to the replica's proc,
+/// which is dropped when the replica stops, so a proc should run at
+/// most one replica.
+#[derive(Debug)]
+#[hyperactor::export(InstallVersion { cast = true })]
+#[hyperactor::spawnable]
+pub struct BroadcastVarReplica {
+    subscriptions: Vec<PortRef<Subscription>>,
+}
+
+#[async_trait]
+impl RemoteSpawn for BroadcastVarReplica {
+    type Params = BroadcastVarReplicaParams;
+
+    async fn new(params: BroadcastVarReplicaParams, _environment: Flattrs) -> anyhow::Result<Self> {
+        Ok(Self {
+            subscriptions: params.subscriptions,
+        })
+    }
+}
+
+#[async_trait]
+impl Actor for BroadcastVarReplica {
+    async fn init(&mut self, this: &Instance<Self>) -> Result<(), anyhow::Error> {
+        for subscription in &self.subscriptions {
+            subscription.post(this, Subscription::Join(this.bind()));
+        }
+        Ok(())
+    }
+
+    async fn cleanup(
+        &mut self,
+        this: &Instance<Self>,
+        _err: Option<&ActorError>,
+    ) -> Result<(), anyhow::Error> {
+        for subscription in &self.subscriptions {
+            subscription.post(this, Subscription::Leave(this.bind()));
+        }
+        INSTALLED.lock().unwrap().remove(this.proc().proc_id());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<InstallVersion> for BroadcastVarReplica {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        message: InstallVersion,
+    ) -> Result<(), anyhow::Error> {
+        match install(
+            cx.proc().proc_id(),
+            &message.name,
+            message.epoch,
+            message.version,
+            message.value,
+        ) {
+            Install::Installed => message.ack.post(cx, 1),
+            Install::Stale => {
+                tracing::debug!(
+                    "broadcast variable {}: ignoring stale version {}",
+                    message.name,
+                    message.version
+                );
+                message.ack.post(cx, 1);
+            }
+            Install::Superseded => {
+                tracing::warn!(
+                    "broadcast variable {}: version {} was superseded by a newer owner",
+                    message.name,
+                    message.version
+                );
+                message.superseded.post(cx, 1);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The state an owner shares with the task serving its
+/// [`Subscription`]s. Both are updated under one lock, so that a
+/// joining replica receives either the version being published, or
+/// the previous version followed by the published one.
+#[derive(Default)]
+struct Subscribers {
+    /// The latest published version and its value.
+    latest: Option<(u64, wirevalue::Any)>,
+    /// The replicas that joined through the subscription.
+    joined: BTreeSet<ActorRef<BroadcastVarReplica>>,
+}
+
+/// Serve the [`Subscription`]s of the variable `name`, published with
+/// `epoch`, from `client`: send the latest version to each replica
+/// that joins, and track the joined replicas in `subscribers`. Returns
+/// the subscription port and the serving task.
+fn serve_subscriptions(
+    client: Client,
+    name: String,
+    epoch: u64,
+    subscribers: Arc<Mutex<Subscribers>>,
+) -> (PortRef<Subscription>, JoinHandle<()>) {
+    let (port, mut requests) = client.mailbox().open_port::<Subscription>();
+    let subscription = port.bind();
+    let serve = tokio::spawn(async move {
+        // Replicas that join are not waited for: their acks are
+        // drained, and only keep the ports open.
+        let (ack_port, mut acks) = client.mailbox().open_accum_port(accum::sum::<u64>());
+        let (superseded_port, mut superseded) =
+            client.mailbox().open_accum_port(accum::sum::<u64>());
+        let mut ack = ack_port.bind();
+        ack.return_undeliverable(false);
+        let mut superseded_ack = superseded_port.bind();
+        superseded_ack.return_undeliverable(false);
+        let _port = port;
+        loop {
+            tokio::select! {
+                request = requests.recv() => match request {
+                    Ok(Subscription::Join(replica)) => {
+                        let latest = {
+                            let mut subscribers = subscribers.lock().unwrap();
+                            subscribers.joined.insert(replica.clone());
+                            subscribers.latest.clone()
+                        };
+                        if let Some((version, value)) = latest {
+                            replica.post(
+                                &client,
+                                InstallVersion {
+                                    name: name.clone(),
+                                    epoch,
+                                    version,
+                                    value,
+                                    ack: ack.clone(),
+                                    superseded: superseded_ack.clone(),
+                                },
+                            );
+                        }
+                    }
+                    Ok(Subscription::Leave(replica)) => {
+                        subscribers.lock().unwrap().joined.remove(&replica);
+                    }
+                    Err(_) => break,
+                },
+                _ = acks.recv() => {}
+                _ = superseded.recv() => {}
+            }
+        }
+    });
+    (subscription, serve)
+}
+
+/// The owner's handle to a broadcast variable; see the
+/// [module documentation](self).
+pub struct BroadcastVar<T> {
+    name: String,
+    epoch: u64,
+    version: u64,
+    replicas: ActorMeshRef<BroadcastVarReplica>,
+    subscribers: Arc<Mutex<Subscribers>>,
+    subscription: PortRef<Subscription>,
+    serve: JoinHandle<()>,
+    _value: PhantomData<fn(T)>,
+}
+
+impl<T: Serialize + Named> BroadcastVar<T> {
+    /// A new broadcast variable named `name`, replicated to `replicas`
+    /// and to the replicas that join through its
+    /// [subscription](Self::subscription). Nothing is sent until the
+    /// first version is published.
+    pub fn new(
+        cx: &impl context::Actor,
+        name: &str,
+        replicas: ActorMeshRef<BroadcastVarReplica>,
+    ) -> Self {
+        // Epochs only need to increase across restarts of the owner,
+        // which the wall clock does at this resolution.
+        let epoch = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        let subscribers = Arc::new(Mutex::new(Subscribers::default()));
+        let (subscription, serve) = serve_subscriptions(
+            cx.instance().proc().client("broadcast_var"),
+            name.to_string(),
+            epoch,
+            Arc::clone(&subscribers),
+        );
+        Self {
+            name: name.to_string(),
+            epoch,
+            version: 0,
+            replicas,
+            subscribers,
+            subscription,
+            serve,
+            _value: PhantomData,
+        }
+    }
+
+    /// The name of the variable.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The latest published version, or 0 if none has been published.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// The port through which late replicas join the variable: pass it
+    /// to them in [`BroadcastVarReplicaParams::subscriptions`].
+    pub fn subscription(&self) -> PortRef<Subscription> {
+        self.subscription.clone()
+    }
+
+    /// Publish `value` as the next version of the variable, and wait
+    /// until every replica has installed it. Returns the new version.
+    /// Fails if a replica has installed a version from a newer owner.
+    pub async fn publish(&mut self, cx: &impl context::Actor, value: &T) -> anyhow::Result<u64> {
+        let version = self.version + 1;
+        let value = wirevalue::Any::serialize(value)?;
+        self.version = version;
+        let joined = {
+            let mut subscribers = self.subscribers.lock().unwrap();
+            subscribers.latest = Some((version, value.clone()));
+            subscribers.joined.clone()
+        };
+
+        let (ack_port, mut acks) = cx.mailbox().open_accum_port(accum::sum::<u64>());
+        let (superseded_port, mut superseded) = cx.mailbox().open_accum_port(accum::sum::<u64>());
+        let mut ack = ack_port.bind();
+        ack.return_undeliverable(false);
+        let mut superseded_ack = superseded_port.bind();
+        superseded_ack.return_undeliverable(false);
+        let message = InstallVersion {
+            name: self.name.clone(),
+            epoch: self.epoch,
+            version,
+            value,
+            ack,
+            superseded: superseded_ack,
+        };
+        self.replicas.cast(cx, message.clone())?;
+        for replica in &joined {
+            replica.post(cx, message.clone());
+        }
+
+        let ranks = (self.replicas.region().num_ranks() + joined.len()) as u64;
+        let timeout = hyperactor_config::global::get(BROADCAST_VAR_ACK_TIMEOUT);
+        let mut acked = 0;
+        let waited = tokio::time::timeout(timeout, async {
+            while acked < ranks {
+                tokio::select! {
+                    count = acks.recv() => acked = count?,
+                    count = superseded.recv() => anyhow::bail!(
+                        "broadcast variable {}: version {} was superseded by a newer owner on {} ranks",
+                        self.name,
+                        version,
+                        count?
+                    ),
+                }
+            }
+            anyhow::Ok(())
+        })
+        .await;
+        match waited {
+            Ok(result) => result?,
+            Err(_) => anyhow::bail!(
+                "broadcast variable {}: timed out after {:?} with {} of {} ranks acking version {}",
+                self.name,
+                timeout,
+                acked,
+                ranks,
+                version
+            ),
+        }
+        Ok(version)
+    }
+}
+
+impl<T> Drop for BroadcastVar<T> {
+    fn drop(&mut self) {
+        self.serve.abort();
+    }
+}
+
+/// A reader of the versions of a broadcast variable installed on a
+/// proc; see the [module documentation](self).
+pub struct BroadcastVarReader<T> {
+    name: String,
+    receiver: watch::Receiver<Snapshot>,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned + Named> BroadcastVarReader<T> {
+    /// A reader of the variable `name` on the proc of `cx`. The
+    /// variable need not have been installed yet.
+    pub fn new(cx: &impl context::Actor, name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            receiver: subscribe(cx.instance().proc().proc_id(), name),
+            _value: PhantomData,
+        }
+    }
+
+    /// The latest installed version, or 0 if none is installed.
+    /// Version numbers start over when the variable's owner restarts.
+    pub fn version(&self) -> u64 {
+        self.receiver
+            .borrow()
+            .as_ref()
+            .map_or(0, |(_, version, _)| *version)
+    }
+
+    /// The latest installed version and its value, if any.
+    pub fn latest(&self) -> anyhow::Result<Option<(u64, T)>> {
+        let snapshot = self.receiver.borrow().clone();
+        snapshot.map(|snapshot| self.decode(snapshot)).transpose()
+    }
+
+    /// Wait until a version no older than `version` is installed, and
+    /// return the latest installed version and its value. Fails if the
+    /// proc's replica stops first.
+    pub async fn wait_for(&mut self, version: u64) -> anyhow::Result<(u64, T)> {
+        let snapshot = self
+            .receiver
+            .wait_for(|snapshot| {
+                snapshot
+                    .as_ref()
+                    .is_some_and(|(_, installed, _)| *installed >= version)
+            })
+            .await?
+            .clone()
+            .expect("waited for an installed version");
+        self.decode(snapshot)
+    }
+
+    fn decode(&self, (_, version, value): (u64, u64, wirevalue::Any)) -> anyhow::Result<(u64, T)> {
+        let value = value.deserialized().map_err(|err| {
+            anyhow::anyhow!(
+                "broadcast variable {}: failed to decode version {}: {}",
+                self.name,
+                version,
+                err
+            )
+        })?;
+        Ok((version, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor::Proc;
+
+    use super::*;
+
+    fn replica() -> BroadcastVarReplica {
+        BroadcastVarReplica {
+            subscriptions: Vec::new(),
+        }
+    }
+
+    fn install_version(
+        name: &str,
+        epoch: u64,
+        version: u64,
+        value: u64,
+        ack: &PortRef<u64>,
+        superseded: &PortRef<u64>,
+    ) -> InstallVersion {
+        InstallVersion {
+            name: name.to_string(),
+            epoch,
+            version,
+            value: wirevalue::Any::serialize(&value).unwrap(),
+            ack: ack.clone(),
+            superseded: superseded.clone(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replica_installs_newest_version() {
+        let name = "test_replica_installs_newest_version";
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let replica = proc.spawn(replica());
+        let (port, mut acks) = client.mailbox().open_accum_port(accum::sum::<u64>());
+        let ack = port.bind();
+        let (superseded_port, _superseded) = client.mailbox().open_accum_port(accum::sum::<u64>());
+        let superseded = superseded_port.bind();
+
+        let mut reader = BroadcastVarReader::<u64>::new(&client, name);
+        assert_eq!(reader.version(), 0);
+        assert!(reader.latest().unwrap().is_none());
+        let waiter = tokio::spawn(async move { reader.wait_for(2).await.unwrap() });
+
+        for (version, value) in [(1, 10u64), (3, 30), (2, 20)] {
+            replica.post(
+                &client,
+                install_version(name, 1, version, value, &ack, &superseded),
+            );
+        }
+        while acks.recv().await.unwrap() < 3 {}
+
+        // The stale version 2 was acked, but not installed.
+        assert_eq!(waiter.await.unwrap(), (3, 30));
+        let reader = BroadcastVarReader::<u64>::new(&client, name);
+        assert_eq!(reader.latest().unwrap(), Some((3, 30)));
+    }
+
+    #[tokio::test]
+    async fn test_replica_orders_owners_by_epoch() {
+        let name = "test_replica_orders_owners_by_epoch";
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let replica = proc.spawn(replica());
+        let (port, mut acks) = client.mailbox().open_accum_port(accum::sum::<u64>());
+        let ack = port.bind();
+        let (superseded_port, mut superseded) =
+            client.mailbox().open_accum_port(accum::sum::<u64>());
+        let superseded_ack = superseded_port.bind();
+        let reader = BroadcastVarReader::<u64>::new(&client, name);
+
+        replica.post(
+            &client,
+            install_version(name, 1, 5, 50, &ack, &superseded_ack),
+        );
+        assert_eq!(acks.recv().await.unwrap(), 1);
+
+        // A restarted owner starts over at version 1, and supersedes
+        // its predecessor.
+        replica.post(
+            &client,
+            install_version(name, 2, 1, 10, &ack, &superseded_ack),
+        );
+        assert_eq!(acks.recv().await.unwrap(), 2);
+        assert_eq!(reader.latest().unwrap(), Some((1, 10)));
+
+        // The old owner's next version is reported as superseded, not
+        // acked.
+        replica.post(
+            &client,
+            install_version(name, 1, 6, 60, &ack, &superseded_ack),
+        );
+        assert_eq!(superseded.recv().await.unwrap(), 1);
+        assert_eq!(reader.latest().unwrap(), Some((1, 10)));
+    }
+
+    #[tokio::test]
+    async fn test_installed_versions_are_scoped_to_procs() {
+        let name = "test_installed_versions_are_scoped_to_procs";
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let replica = proc.spawn(replica());
+        let other = Proc::isolated();
+        let other_client = other.client("client");
+        let (port, mut acks) = client.mailbox().open_accum_port(accum::sum::<u64>());
+        let ack = port.bind();
+        let (superseded_port, _superseded) = client.mailbox().open_accum_port(accum::sum::<u64>());
+        let superseded = superseded_port.bind();
+
+        replica.post(&client, install_version(name, 1, 1, 10, &ack, &superseded));
+        assert_eq!(acks.recv().await.unwrap(), 1);
+        assert_eq!(
+            BroadcastVarReader::<u64>::new(&client, name)
+                .latest()
+                .unwrap(),
+            Some((1, 10))
+        );
+        assert!(
+            BroadcastVarReader::<u64>::new(&other_client, name)
+                .latest()
+                .unwrap()
+                .is_none()
+        );
+
+        // Stopping the replica drops its proc's versions, and fails
+        // the readers waiting for one.
+        let mut reader = BroadcastVarReader::<u64>::new(&client, name);
+        replica.drain_and_stop("test").unwrap();
+        replica.await;
+        assert!(reader.wait_for(2).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_late_replica_joins() {
+        let name = "test_late_replica_joins";
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let subscribers = Arc::new(Mutex::new(Subscribers {
+            latest: Some((2, wirevalue::Any::serialize(&20u64).unwrap())),
+            joined: BTreeSet::new(),
+        }));
+        let (subscription, serve) = serve_subscriptions(
+            proc.client("broadcast_var"),
+            name.to_string(),
+            1,
+            Arc::clone(&subscribers),
+        );
+
+        // The replica subscribes when it starts, and is sent the
+        // latest version without the owner publishing again.
+        let mut reader = BroadcastVarReader::<u64>::new(&client, name);
+        let replica = proc.spawn(BroadcastVarReplica {
+            subscriptions: vec![subscription],
+        });
+        assert_eq!(reader.wait_for(2).await.unwrap(), (2, 20));
+        assert_eq!(
+            subscribers.lock().unwrap().joined,
+            BTreeSet::from([replica.bind::<BroadcastVarReplica>()])
+        );
+        serve.abort();
+    }
+}
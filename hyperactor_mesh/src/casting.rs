@@ -9,6 +9,8 @@
 //! Casting utilities for actor meshes.
 
 use std::collections::BTreeSet;
+use std::hash::Hash;
+use std::hash::Hasher;
 
 use hyperactor::ActorRef;
 use hyperactor::RemoteEndpoint as _;
@@ -24,11 +26,14 @@ use hyperactor::mailbox::Undeliverable;
 use hyperactor::message::Castable;
 use hyperactor::message::IndexedErasedUnbound;
 use hyperactor_config::Flattrs;
+use hyperactor_config::attrs::COMPACT_KEY;
 use hyperactor_config::attrs::declare_attrs;
 use ndslice::Selection;
 use ndslice::Shape;
 use ndslice::ShapeError;
+use ndslice::Slice;
 use ndslice::SliceError;
+use ndslice::ViewExt as _;
 use ndslice::reshape::Limit;
 use ndslice::reshape::ReshapeError;
 use ndslice::reshape::ReshapeSliceExt;
@@ -37,6 +42,7 @@ use ndslice::selection;
 use ndslice::selection::EvalOpts;
 use ndslice::selection::ReifySlice;
 use ndslice::selection::normal;
+use ndslice::view::Region;
 
 use crate::CommActor;
 use crate::comm::ENABLE_NATIVE_V1_CASTING;
@@ -61,10 +67,113 @@ pub(crate) fn v1_casting_enabled() -> bool {
     enabled
 }
 
+/// A 64-bit FNV-1a hasher. Unlike [`std::hash::DefaultHasher`], its
+/// output does not depend on the Rust version or on the binary, so
+/// every client of a mesh hashes an affinity key alike.
+struct AffinityHasher(u64);
+
+impl Default for AffinityHasher {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for AffinityHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The hash of the affinity key `key`, as used by [`affinity_rank`].
+pub fn affinity_key(key: &impl Hash) -> u64 {
+    let mut hasher = AffinityHasher::default();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The index, in `slice`, of the rank to which messages with the
+/// affinity key `key` are routed, chosen by jump consistent hashing
+/// (Lamping and Veach) in O(log n) for n ranks: when the slice grows
+/// or shrinks at its end, only the keys of the ranks added or removed
+/// move, so related messages stay on one rank as a mesh is narrowed.
+/// Returns `None` if the slice is empty.
+pub fn affinity_rank(slice: &Slice, key: u64) -> Option<usize> {
+    let num_ranks = slice.len() as i64;
+    if num_ranks == 0 {
+        return None;
+    }
+    let mut key = key;
+    let (mut index, mut next) = (0i64, 0i64);
+    while next < num_ranks {
+        index = next;
+        key = key.wrapping_mul(2862933555777941757).wrapping_add(1);
+        next = ((index + 1) as f64 * ((1u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as i64;
+    }
+    Some(index as usize)
+}
+
+/// The subregion of `region` comprising the points selected by `sel`.
+/// The selected points must form a region themselves: along each
+/// dimension, the selected coordinates must be evenly spaced, and
+/// every combination of them must be selected. Selections such as
+/// `range(0..2, all)` or every other rank of a dimension qualify;
+/// a diagonal does not.
+pub fn region_of_selection(region: &Region, sel: &Selection) -> Result<Region, CastError> {
+    let ranks: BTreeSet<usize> = sel
+        .eval(&EvalOpts::strict(), region.slice())
+        .map_err(|err| CastError::InvalidSelection(sel.clone(), err))?
+        .collect();
+    if ranks.is_empty() {
+        return Err(CastError::SelectionNotSupported(format!(
+            "{} selects no ranks of {}",
+            sel, region
+        )));
+    }
+
+    let mut coords = vec![BTreeSet::new(); region.labels().len()];
+    for rank in &ranks {
+        let point = region
+            .point_of_base_rank(*rank)
+            .map_err(anyhow::Error::from)?;
+        for (dim, coord) in point.coords().into_iter().enumerate() {
+            coords[dim].insert(coord);
+        }
+    }
+
+    let mut subregion = region.clone();
+    for (label, coords) in region.labels().iter().zip(coords) {
+        let coords: Vec<_> = coords.into_iter().collect();
+        let step = coords.get(1).map_or(1, |second| second - coords[0]);
+        if coords.windows(2).any(|pair| pair[1] - pair[0] != step) {
+            return Err(CastError::SelectionNotSupported(format!(
+                "{} selects unevenly spaced coordinates of dimension {}",
+                sel, label
+            )));
+        }
+        let range = ndslice::Range(coords[0], Some(coords[coords.len() - 1] + 1), step);
+        subregion = subregion.range(label, range).map_err(anyhow::Error::from)?;
+    }
+    if subregion.num_ranks() != ranks.len() {
+        return Err(CastError::SelectionNotSupported(format!(
+            "{} does not select a region of {}",
+            sel, region
+        )));
+    }
+    Ok(subregion)
+}
+
 declare_attrs! {
     /// Which mesh this message was cast to. Used for undeliverable message
     /// handling, where the CastMessageEnvelope is serialized, and its content
     /// cannot be inspected.
+    @meta(COMPACT_KEY = 67)
     pub attr CAST_ACTOR_MESH_ID: ActorMeshId;
 }
 
@@ -100,6 +209,7 @@ pub fn update_undeliverable_envelope_for_casting(
 pub(crate) fn actor_mesh_cast<A, M>(
     cx: &impl context::Actor,
     actor_mesh_id: ActorMeshId,
+    stream_id: ActorMeshId,
     comm_actor_ref: &ActorRef<CommActor>,
     selection_of_root: Selection,
     root_mesh_shape: &Shape,
@@ -129,7 +239,8 @@ where
         cast_mesh_shape.clone(),
         headers,
         message,
-    )?;
+    )?
+    .with_stream(stream_id);
 
     // Mesh's shape might have large extents on some dimensions. Those
     // dimensions would cause large fanout in our comm actor
@@ -182,6 +293,7 @@ where
 pub(crate) fn cast_to_sliced_mesh<A, M>(
     cx: &impl context::Actor,
     actor_mesh_id: ActorMeshId,
+    stream_id: ActorMeshId,
     comm_actor_ref: &ActorRef<CommActor>,
     sel_of_sliced: &Selection,
     message: M,
@@ -211,6 +323,7 @@ where
     actor_mesh_cast::<A, M>(
         cx,
         actor_mesh_id,
+        stream_id,
         comm_actor_ref,
         sel_of_root,
         root_mesh_shape,
@@ -253,3 +366,78 @@ pub enum CastError {
     #[error(transparent)]
     ReshapeError(#[from] ReshapeError),
 }
+
+#[cfg(test)]
+mod tests {
+    use std::assert_matches;
+
+    use hyperactor_mesh_macros::sel;
+    use ndslice::extent;
+    use ndslice::selection::dsl;
+
+    use super::*;
+
+    #[test]
+    fn test_affinity_rank() {
+        let slice = Slice::new_row_major(vec![4, 8]);
+        assert_eq!(affinity_rank(&Slice::new_row_major(vec![0]), 1), None);
+
+        // Keys are routed consistently, and spread over the ranks.
+        let ranks: Vec<_> = (0..1000)
+            .map(|key| affinity_rank(&slice, key).unwrap())
+            .collect();
+        for (key, rank) in ranks.iter().enumerate() {
+            assert_eq!(affinity_rank(&slice, key as u64), Some(*rank));
+        }
+        assert_eq!(ranks.iter().collect::<BTreeSet<_>>().len(), 32);
+
+        // Narrowing the slice to its first row only moves the keys of
+        // the ranks that were removed.
+        let row = Slice::new(0, vec![8], vec![1]).unwrap();
+        for (key, rank) in ranks.iter().enumerate() {
+            if *rank < 8 {
+                assert_eq!(affinity_rank(&row, key as u64), Some(*rank));
+            }
+        }
+
+        // Keys hash alike in every binary.
+        assert_eq!(affinity_key(&"session"), 0xaceb31bd204018f8);
+    }
+
+    #[test]
+    fn test_region_of_selection() {
+        let region: Region = extent!(host = 4, gpu = 8).into();
+
+        let sel = sel!(1:3, *);
+        assert_eq!(
+            region_of_selection(&region, &sel).unwrap(),
+            region.range("host", 1..3).unwrap()
+        );
+        let sel = sel!(*, 0:8:2);
+        assert_eq!(
+            region_of_selection(&region, &sel).unwrap(),
+            region.range("gpu", ndslice::Range(0, Some(8), 2)).unwrap()
+        );
+
+        // Subregions are selected relative to their own points.
+        let subregion = region.range("gpu", 4..8).unwrap();
+        assert_eq!(
+            region_of_selection(&subregion, &sel!(2, 1)).unwrap(),
+            region.range("host", 2).unwrap().range("gpu", 5).unwrap()
+        );
+
+        // Selections that do not form a region are rejected.
+        assert_matches!(
+            region_of_selection(&region, &dsl::union(sel!(0, 0), sel!(1, 1))),
+            Err(CastError::SelectionNotSupported(_))
+        );
+        assert_matches!(
+            region_of_selection(&region, &sel!(*, (0 | 1 | 3))),
+            Err(CastError::SelectionNotSupported(_))
+        );
+        assert_matches!(
+            region_of_selection(&region, &dsl::false_()),
+            Err(CastError::SelectionNotSupported(_))
+        );
+    }
+}
@@ -0,0 +1,439 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Opt-in fault injection on live meshes.
+//!
+//! Chaos is disabled unless [`MESH_CHAOS_ENABLED`] is set. When it is:
+//!
+//! - bootstrapped procs wrap their outbound mailbox sender in a
+//!   [`ChaosSender`], which randomly severs links to destination
+//!   procs, delays deliveries, and corrupts non-critical headers;
+//! - a [`ChaosActor`] spawned on a proc mesh periodically kills random
+//!   (non-system) actors on its proc.
+//!
+//! Every injected fault is logged, and recorded so that it can be
+//! retrieved with [`ChaosReport`]: a resilience test can then check
+//! that each fault was detected (e.g. by supervision) or recovered
+//! from.
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use hyperactor::Actor;
+use hyperactor::ActorAddr;
+use hyperactor::Addr;
+use hyperactor::Bind;
+use hyperactor::Context;
+use hyperactor::HandleClient;
+use hyperactor::Handler;
+use hyperactor::Instance;
+use hyperactor::PortAddr;
+use hyperactor::ProcAddr;
+use hyperactor::RefClient;
+use hyperactor::Unbind;
+use hyperactor::actor::Signal;
+use hyperactor::channel::ChannelAddr;
+use hyperactor::mailbox::DeliveryFailure;
+use hyperactor::mailbox::MailboxSender;
+use hyperactor::mailbox::MessageEnvelope;
+use hyperactor::mailbox::PortHandle;
+use hyperactor::mailbox::TransportFailure;
+use hyperactor::mailbox::TransportFailureReason;
+use hyperactor::mailbox::Undeliverable;
+use hyperactor::mailbox::UndeliverableReason;
+use hyperactor::mailbox::headers::RUST_MESSAGE_TYPE;
+use hyperactor::mailbox::headers::SEND_TIMESTAMP;
+use hyperactor_config::CONFIG;
+use hyperactor_config::ConfigAttr;
+use hyperactor_config::attrs::declare_attrs;
+use serde::Deserialize;
+use serde::Serialize;
+use typeuri::Named;
+
+declare_attrs! {
+    /// Whether to inject faults. Nothing else in this module has any
+    /// effect unless it is set.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CHAOS_ENABLED".to_string()),
+        Some("mesh_chaos_enabled".to_string()),
+    ))
+    pub attr MESH_CHAOS_ENABLED: bool = false;
+
+    /// How often a chaos actor considers killing an actor.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CHAOS_INTERVAL".to_string()),
+        Some("mesh_chaos_interval".to_string()),
+    ))
+    pub attr MESH_CHAOS_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// The probability that a chaos actor kills an actor at each
+    /// interval.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CHAOS_KILL_PROBABILITY".to_string()),
+        Some("mesh_chaos_kill_probability".to_string()),
+    ))
+    pub attr MESH_CHAOS_KILL_PROBABILITY: f64 = 0.0;
+
+    /// The probability that a message severs the link to its
+    /// destination proc.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CHAOS_SEVER_PROBABILITY".to_string()),
+        Some("mesh_chaos_sever_probability".to_string()),
+    ))
+    pub attr MESH_CHAOS_SEVER_PROBABILITY: f64 = 0.0;
+
+    /// How long a severed link stays down. Messages over it are
+    /// returned as undeliverable in the meantime.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CHAOS_SEVER_DURATION".to_string()),
+        Some("mesh_chaos_sever_duration".to_string()),
+    ))
+    pub attr MESH_CHAOS_SEVER_DURATION: Duration = Duration::from_secs(5);
+
+    /// The probability that a message is delayed.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CHAOS_DELAY_PROBABILITY".to_string()),
+        Some("mesh_chaos_delay_probability".to_string()),
+    ))
+    pub attr MESH_CHAOS_DELAY_PROBABILITY: f64 = 0.0;
+
+    /// The maximum delay of a delayed message.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CHAOS_MAX_DELAY".to_string()),
+        Some("mesh_chaos_max_delay".to_string()),
+    ))
+    pub attr MESH_CHAOS_MAX_DELAY: Duration = Duration::from_secs(1);
+
+    /// The probability that the non-critical headers of a message are
+    /// corrupted.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CHAOS_CORRUPT_PROBABILITY".to_string()),
+        Some("mesh_chaos_corrupt_probability".to_string()),
+    ))
+    pub attr MESH_CHAOS_CORRUPT_PROBABILITY: f64 = 0.0;
+}
+
+/// The number of injected faults retained by [`ChaosReport`].
+const MAX_RECORDED_FAULTS: usize = 1024;
+
+/// A fault injected by chaos.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named)]
+pub enum Fault {
+    /// An actor was killed.
+    KillActor { actor: ActorAddr },
+    /// The link to a proc was severed for `duration`.
+    SeverLink { proc: ProcAddr, duration: Duration },
+    /// A message was delayed by `delay`.
+    DelayDelivery { dest: PortAddr, delay: Duration },
+    /// The non-critical headers of a message were corrupted.
+    CorruptHeaders { dest: PortAddr },
+}
+wirevalue::register_type!(Fault);
+
+/// A fault, and when it was injected.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named)]
+pub struct InjectedFault {
+    /// When the fault was injected.
+    pub at: SystemTime,
+    /// The fault.
+    pub fault: Fault,
+}
+wirevalue::register_type!(InjectedFault);
+
+/// The faults injected in this process, oldest first.
+static FAULTS: Mutex<VecDeque<InjectedFault>> = Mutex::new(VecDeque::new());
+
+/// Record an injected fault.
+fn record(fault: Fault) {
+    tracing::warn!(?fault, "chaos: injected fault");
+    let mut faults = FAULTS.lock().unwrap();
+    if faults.len() == MAX_RECORDED_FAULTS {
+        faults.pop_front();
+    }
+    faults.push_back(InjectedFault {
+        at: SystemTime::now(),
+        fault,
+    });
+}
+
+/// The faults injected in this process, oldest first.
+pub fn injected_faults() -> Vec<InjectedFault> {
+    FAULTS.lock().unwrap().iter().cloned().collect()
+}
+
+/// Whether an event of probability `p` happens.
+fn happens(p: f64) -> bool {
+    p > 0.0 && rand::random::<f64>() < p
+}
+
+/// A [`MailboxSender`] that injects faults into the messages it
+/// forwards to `inner`. Delayed messages may be reordered.
+pub struct ChaosSender {
+    inner: Arc<dyn MailboxSender + Send + Sync>,
+    /// Severed links, and when they are restored.
+    severed: Mutex<HashMap<ProcAddr, SystemTime>>,
+}
+
+impl ChaosSender {
+    /// Inject faults into the messages forwarded to `inner`.
+    pub fn new(inner: impl MailboxSender + 'static) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            severed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the link to `proc` is severed, possibly severing it now.
+    fn severed(&self, proc: &ProcAddr) -> bool {
+        let now = SystemTime::now();
+        let mut severed = self.severed.lock().unwrap();
+        if let Some(until) = severed.get(proc) {
+            if *until > now {
+                return true;
+            }
+            severed.remove(proc);
+        }
+        if happens(hyperactor_config::global::get(MESH_CHAOS_SEVER_PROBABILITY)) {
+            let duration = hyperactor_config::global::get(MESH_CHAOS_SEVER_DURATION);
+            severed.insert(proc.clone(), now + duration);
+            record(Fault::SeverLink {
+                proc: proc.clone(),
+                duration,
+            });
+            return true;
+        }
+        false
+    }
+}
+
+#[async_trait]
+impl MailboxSender for ChaosSender {
+    fn post_unchecked(
+        &self,
+        mut envelope: MessageEnvelope,
+        return_handle: PortHandle<Undeliverable<MessageEnvelope>>,
+    ) {
+        let dest = envelope.dest().clone();
+
+        let proc = dest.actor_addr().proc_addr();
+        if self.severed(&proc) {
+            let failure =
+                DeliveryFailure::new(UndeliverableReason::Transport(TransportFailure::new(
+                    proc,
+                    TransportFailureReason::LinkUnavailable("link severed by chaos".to_string()),
+                )));
+            envelope.undeliverable(failure, return_handle);
+            return;
+        }
+
+        if happens(hyperactor_config::global::get(
+            MESH_CHAOS_CORRUPT_PROBABILITY,
+        )) {
+            // Only headers used for telemetry: routing, ordering and
+            // delivery never depend on these.
+            let headers = envelope.headers_mut();
+            headers.set(SEND_TIMESTAMP, SystemTime::UNIX_EPOCH);
+            headers.set(RUST_MESSAGE_TYPE, "<corrupted by chaos>".to_string());
+            record(Fault::CorruptHeaders { dest: dest.clone() });
+        }
+
+        if happens(hyperactor_config::global::get(MESH_CHAOS_DELAY_PROBABILITY)) {
+            let delay =
+                hyperactor_config::global::get(MESH_CHAOS_MAX_DELAY).mul_f64(rand::random::<f64>());
+            record(Fault::DelayDelivery { dest, delay });
+            let inner = self.inner.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                inner.post_unchecked(envelope, return_handle);
+            });
+            return;
+        }
+
+        self.inner.post_unchecked(envelope, return_handle);
+    }
+
+    async fn flush(&self) -> Result<(), anyhow::Error> {
+        self.inner.flush().await
+    }
+
+    fn update_address(&self, dest: &Addr, addr: &ChannelAddr) -> bool {
+        self.inner.update_address(dest, addr)
+    }
+}
+
+/// Periodic self-message of a [`ChaosActor`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named, Bind, Unbind)]
+pub struct ChaosTick;
+wirevalue::register_type!(ChaosTick);
+
+/// Request the faults injected in a chaos actor's process.
+#[derive(Debug, Serialize, Deserialize, Named, Handler, HandleClient, RefClient)]
+pub struct ChaosReport {
+    #[reply]
+    pub result: hyperactor::OncePortRef<Vec<InjectedFault>>,
+}
+wirevalue::register_type!(ChaosReport);
+
+/// An actor that periodically kills a random actor on its proc, with
+/// probability [`MESH_CHAOS_KILL_PROBABILITY`]. System actors, the
+/// proc's supervision coordinator and the chaos actor itself are
+/// spared.
+///
+/// The actor does nothing unless [`MESH_CHAOS_ENABLED`] is set.
+#[derive(Debug, Default)]
+#[hyperactor::export(ChaosTick, ChaosReport)]
+#[hyperactor::spawnable]
+pub struct ChaosActor;
+
+#[async_trait]
+impl Actor for ChaosActor {
+    async fn init(&mut self, this: &Instance<Self>) -> Result<(), anyhow::Error> {
+        if hyperactor_config::global::get(MESH_CHAOS_ENABLED) {
+            tracing::warn!("chaos: enabled on {}", this.proc().proc_addr());
+            this.post_after(
+                this,
+                ChaosTick,
+                hyperactor_config::global::get(MESH_CHAOS_INTERVAL),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<ChaosTick> for ChaosActor {
+    async fn handle(&mut self, cx: &Context<Self>, _: ChaosTick) -> Result<(), anyhow::Error> {
+        if happens(hyperactor_config::global::get(MESH_CHAOS_KILL_PROBABILITY)) {
+            let proc = cx.proc();
+            let victims: Vec<_> = proc
+                .all_actor_ids()
+                .iter()
+                .filter(|actor| *actor != cx.self_addr())
+                .filter(|actor| proc.supervision_coordinator_actor_addr() != Some(*actor))
+                .filter_map(|actor| proc.get_instance(actor))
+                .filter(|cell| !cell.is_system())
+                .collect();
+            if !victims.is_empty() {
+                let victim = &victims[rand::random::<u64>() as usize % victims.len()];
+                // Killing, unlike stopping, fails the actor, and so
+                // propagates a supervision event.
+                if victim
+                    .signal(Signal::Kill("killed by chaos".to_string()))
+                    .is_ok()
+                {
+                    record(Fault::KillActor {
+                        actor: victim.actor_addr().clone(),
+                    });
+                }
+            }
+        }
+        cx.post_after(
+            cx,
+            ChaosTick,
+            hyperactor_config::global::get(MESH_CHAOS_INTERVAL),
+        );
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<ChaosReport> for ChaosActor {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        message: ChaosReport,
+    ) -> Result<(), anyhow::Error> {
+        crate::proc_agent::reply_best_effort(cx, message.result, injected_faults());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor::Proc;
+    use hyperactor::mailbox::Mailbox;
+    use hyperactor::testing::ids::test_actor_id;
+    use hyperactor::testing::proc_supervison::ProcSupervisionCoordinator;
+    use hyperactor_config::Flattrs;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_chaos_sender() {
+        let mailbox = Mailbox::new(test_actor_id("0", "dest"));
+        let (port, mut receiver) = mailbox.open_port::<u64>();
+        let port = port.bind();
+        let sender = ChaosSender::new(mailbox.clone());
+        let (return_handle, mut returns) = Mailbox::new(test_actor_id("0", "returns"))
+            .open_port::<Undeliverable<MessageEnvelope>>();
+        let post = |value: u64| {
+            sender.post(
+                MessageEnvelope::serialize(
+                    test_actor_id("0", "sender"),
+                    port.port_addr().clone(),
+                    &value,
+                    Flattrs::new(),
+                )
+                .unwrap(),
+                return_handle.clone(),
+            )
+        };
+
+        // Without chaos probabilities, messages pass through.
+        post(1);
+        assert_eq!(receiver.recv().await.unwrap(), 1);
+
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(MESH_CHAOS_SEVER_PROBABILITY, 1.0);
+        post(2);
+        let returned = returns.recv().await.unwrap().into_message().unwrap();
+        assert_eq!(returned.deserialized::<u64>().unwrap(), 2);
+        assert!(injected_faults().iter().any(|fault| matches!(
+            &fault.fault,
+            Fault::SeverLink { proc, .. } if *proc == port.port_addr().actor_addr().proc_addr()
+        )));
+
+        // The link stays severed even once the probability drops.
+        let _guard = config.override_key(MESH_CHAOS_SEVER_PROBABILITY, 0.0);
+        post(3);
+        let returned = returns.recv().await.unwrap().into_message().unwrap();
+        assert_eq!(returned.deserialized::<u64>().unwrap(), 3);
+        assert_eq!(receiver.try_recv().unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_chaos_actor_kills() {
+        let config = hyperactor_config::global::lock();
+        let _enabled = config.override_key(MESH_CHAOS_ENABLED, true);
+        let _kill = config.override_key(MESH_CHAOS_KILL_PROBABILITY, 1.0);
+        let _interval = config.override_key(MESH_CHAOS_INTERVAL, Duration::from_millis(10));
+
+        let proc = Proc::isolated();
+        let _supervision = ProcSupervisionCoordinator::set(&proc).await.unwrap();
+        let victim = proc.spawn(crate::testactor::TestActor);
+        let _chaos = proc.spawn(ChaosActor);
+
+        let mut status = victim.status();
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            status.wait_for(|status| status.is_terminal()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(injected_faults().iter().any(|fault| matches!(
+            &fault.fault,
+            Fault::KillActor { actor } if actor == victim.actor_addr()
+        )));
+    }
+}
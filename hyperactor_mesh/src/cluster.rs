@@ -0,0 +1,239 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Scaffolding shared by the cluster backends that provision host
+//! meshes through an external scheduler ([`crate::slurm`],
+//! [`crate::kubernetes`]).
+//!
+//! The backends drive their scheduler through its command-line tools
+//! ([`run`], [`run_with_input`]), launch a host on each allocated node
+//! with the environment returned by [`host_env`], and track the
+//! launched hosts in [`LaunchedHosts`], which turns lost hosts into
+//! supervision events.
+
+use std::process::Stdio;
+
+use hyperactor::actor::ActorStatus;
+use hyperactor::channel::ChannelAddr;
+use hyperactor::supervision::ActorSupervisionEvent;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::Bootstrap;
+use crate::bootstrap::BOOTSTRAP_MODE_ENV;
+use crate::bootstrap::BootstrapCommand;
+use crate::handshake::Hello;
+use crate::host_mesh::HostRef;
+use crate::supervision::MeshFailure;
+
+/// Errors running a scheduler command.
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError {
+    /// The command could not be run.
+    #[error("failed to run {command}: {source}")]
+    Spawn {
+        command: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// The command failed.
+    #[error("{command} failed with {status}: {stderr}")]
+    Failed {
+        command: String,
+        status: std::process::ExitStatus,
+        stderr: String,
+    },
+
+    /// The output of the command could not be parsed.
+    #[error("unexpected output from {command}: {output:?}")]
+    Parse { command: String, output: String },
+}
+
+/// Run `command` to completion, returning its standard output.
+pub(crate) async fn run(command: &mut Command) -> Result<String, CommandError> {
+    let name = command_name(command);
+    let output = command
+        .output()
+        .await
+        .map_err(|source| CommandError::Spawn {
+            command: name.clone(),
+            source,
+        })?;
+    check_output(name, output)
+}
+
+/// Run `command` to completion with `input` on its standard input,
+/// returning its standard output.
+pub(crate) async fn run_with_input(
+    command: &mut Command,
+    input: &str,
+) -> Result<String, CommandError> {
+    let name = command_name(command);
+    let io_err = |source: std::io::Error| CommandError::Spawn {
+        command: name.clone(),
+        source,
+    };
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(io_err)?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin.write_all(input.as_bytes()).await.map_err(io_err)?;
+    drop(stdin);
+    let output = child.wait_with_output().await.map_err(io_err)?;
+    check_output(name, output)
+}
+
+/// The name of `command`, for error reporting.
+pub(crate) fn command_name(command: &Command) -> String {
+    command
+        .as_std()
+        .get_program()
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Return the standard output of a completed command, or an error if
+/// it failed.
+fn check_output(name: String, output: std::process::Output) -> Result<String, CommandError> {
+    if !output.status.success() {
+        return Err(CommandError::Failed {
+            command: name,
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The TCP address of a host served on `host` at `port`.
+pub(crate) fn tcp_addr(host: &str, port: u16) -> anyhow::Result<ChannelAddr> {
+    ChannelAddr::from_zmq_url(&format!("tcp://{}:{}", host, port))
+}
+
+/// The environment with which to run `command` to serve a host at
+/// `addr`: the command's own environment, and the bootstrap mode.
+pub(crate) fn host_env(
+    command: &BootstrapCommand,
+    addr: &ChannelAddr,
+) -> crate::Result<Vec<(String, String)>> {
+    let bootstrap = Bootstrap::Host {
+        addr: addr.clone(),
+        command: Some(command.clone()),
+        config: Some(hyperactor_config::global::attrs()),
+        exit_on_shutdown: true,
+        hello: Some(Hello::local()),
+    };
+    let mut env: Vec<_> = command
+        .env
+        .iter()
+        .map(|(key, val)| (key.clone(), val.clone()))
+        .collect();
+    env.push((
+        BOOTSTRAP_MODE_ENV.to_string(),
+        bootstrap.to_env_safe_string()?,
+    ));
+    Ok(env)
+}
+
+/// The hosts launched by a cluster backend, by rank, and which of
+/// them have been reported lost.
+#[derive(Debug, Default)]
+pub(crate) struct LaunchedHosts {
+    addrs: Vec<ChannelAddr>,
+    lost: Vec<bool>,
+}
+
+impl LaunchedHosts {
+    /// Record the host with the next rank.
+    pub(crate) fn push(&mut self, addr: ChannelAddr) {
+        self.addrs.push(addr);
+        self.lost.push(false);
+    }
+
+    /// The ranks of the hosts not yet reported lost.
+    pub(crate) fn live(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.lost.len()).filter(|&rank| !self.lost[rank])
+    }
+
+    /// Wait forever if there is no host left to monitor: no host was
+    /// launched, or all of them have been reported lost.
+    pub(crate) async fn wait_monitored(&self) {
+        if self.live().next().is_none() {
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// Mark `ranks`, which must not be empty, as lost, and return the
+    /// supervision event reporting them.
+    pub(crate) fn lost(&mut self, ranks: Vec<usize>, reason: String) -> MeshFailure {
+        for &rank in &ranks {
+            self.lost[rank] = true;
+        }
+        MeshFailure {
+            actor_mesh_name: None,
+            event: ActorSupervisionEvent::new(
+                HostRef::new(self.addrs[ranks[0]].clone())
+                    .mesh_agent()
+                    .actor_addr()
+                    .clone(),
+                None,
+                ActorStatus::generic_failure(reason),
+                None,
+            ),
+            crashed_ranks: ranks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tcp_addr() {
+        // Only IP literals: host names would be resolved through DNS.
+        assert_eq!(
+            tcp_addr("10.0.0.1", 22222).unwrap().to_string(),
+            "tcp:10.0.0.1:22222"
+        );
+    }
+
+    #[test]
+    fn test_launched_hosts() {
+        let mut hosts = LaunchedHosts::default();
+        hosts.push(tcp_addr("10.0.0.1", 22222).unwrap());
+        hosts.push(tcp_addr("10.0.0.2", 22222).unwrap());
+        hosts.push(tcp_addr("10.0.0.3", 22222).unwrap());
+        let failure = hosts.lost(vec![1], "lost".to_string());
+        assert_eq!(failure.crashed_ranks, vec![1]);
+        assert_eq!(hosts.live().collect::<Vec<_>>(), vec![0, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_wait_monitored() {
+        let mut hosts = LaunchedHosts::default();
+        // Nothing launched: nothing to monitor.
+        assert!(
+            tokio::time::timeout(std::time::Duration::ZERO, hosts.wait_monitored())
+                .await
+                .is_err()
+        );
+        hosts.push(tcp_addr("10.0.0.1", 22222).unwrap());
+        hosts.wait_monitored().await;
+        hosts.lost(vec![0], "lost".to_string());
+        assert!(
+            tokio::time::timeout(std::time::Duration::ZERO, hosts.wait_monitored())
+                .await
+                .is_err()
+        );
+    }
+}
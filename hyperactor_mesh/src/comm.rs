@@ -7,17 +7,30 @@
  */
 
 use crate::casting::CAST_ACTOR_MESH_ID;
+use crate::comm::intern::CastRegionFetchExpired;
+use crate::comm::intern::CastRegionFetched;
+use crate::comm::intern::FetchCastRegion;
+use crate::comm::intern::HeldCast;
+use crate::comm::intern::InternedForwardMessageV1;
 use crate::comm::multicast::CAST_ORIGINATING_SENDER;
 use crate::comm::multicast::CastEnvelope;
 use crate::comm::multicast::CastMessageV1;
 use crate::comm::multicast::ForwardMessageV1;
 use crate::mesh_id::ActorMeshId;
 use crate::resource;
+use crate::topology::CastTopologyDump;
+use crate::topology::TopologyResult;
+pub mod intern;
 pub mod multicast;
 
+use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::fmt::Debug;
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -35,11 +48,20 @@ use hyperactor::RemoteMessage;
 use hyperactor::UnboundPort;
 use hyperactor::UnboundPortKind;
 use hyperactor::accum::ReducerMode;
+use hyperactor::mailbox::DeliveryFailure;
+use hyperactor::mailbox::InvalidReference;
+use hyperactor::mailbox::InvalidReferenceReason;
 use hyperactor::mailbox::MailboxSender;
+use hyperactor::mailbox::MessageEnvelope;
+use hyperactor::mailbox::TransportFailure;
+use hyperactor::mailbox::TransportFailureReason;
 use hyperactor::mailbox::Undeliverable;
 use hyperactor::mailbox::UndeliverableMailboxSender;
 use hyperactor::mailbox::UndeliverableMessageError;
+use hyperactor::mailbox::UndeliverableReason;
+use hyperactor::mailbox::migrate;
 use hyperactor::mailbox::monitored_return_handle;
+use hyperactor::mailbox::topology::Topology;
 use hyperactor::message::ErasedUnbound;
 use hyperactor::ordering::SEQ_INFO;
 use hyperactor::ordering::SeqInfo;
@@ -52,6 +74,8 @@ use ndslice::Point;
 use ndslice::Selection;
 use ndslice::View;
 use ndslice::selection::routing::RoutingFrame;
+use ndslice::selection::routing::RoutingFrameKey;
+use ndslice::selection::routing::RoutingStep;
 use serde::Deserialize;
 use serde::Serialize;
 use typeuri::Named;
@@ -59,6 +83,7 @@ use typeuri::Named;
 use crate::comm::multicast::CastMessage;
 use crate::comm::multicast::CastMessageEnvelope;
 use crate::comm::multicast::ForwardMessage;
+use crate::comm::multicast::composite_cast_point;
 use crate::comm::multicast::set_cast_info_on_headers;
 
 declare_attrs! {
@@ -69,6 +94,37 @@ declare_attrs! {
     ))
     pub attr ENABLE_NATIVE_V1_CASTING: bool = true;
 
+    /// Casts to slices of at least this many ranks are injected at
+    /// several roots of the routing tree at once: one per selected
+    /// index of the slice's outermost dimension of more than one rank
+    /// (e.g. one per rack). 0 always injects casts at a single root.
+    /// Read when a comm actor starts.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CAST_MULTI_ROOT_THRESHOLD".to_string()),
+        Some("cast_multi_root_threshold".to_string()),
+    ))
+    pub attr CAST_MULTI_ROOT_THRESHOLD: usize = 0;
+
+    /// The number of cast regions each comm actor interns; see
+    /// [`intern`]. 0, the default, sends every cast's region inline.
+    /// Interned casts are sent as [`InternedForwardMessageV1`], which
+    /// only comm actors that intern regions accept: enable this only
+    /// once all comm actors do.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CAST_REGION_CACHE_SIZE".to_string()),
+        Some("cast_region_cache_size".to_string()),
+    ))
+    pub attr CAST_REGION_CACHE_SIZE: usize = 0;
+
+    /// How long a comm actor waits for an interned cast region that it
+    /// fetches (see [`intern`]) before it returns the casts that need
+    /// it to their senders.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CAST_REGION_FETCH_TIMEOUT".to_string()),
+        Some("cast_region_fetch_timeout".to_string()),
+    ))
+    pub attr CAST_REGION_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
     /// The multicast phase that attached context to a delivery failure.
     pub attr MULTICAST_FAILURE_PHASE: String;
 
@@ -107,16 +163,22 @@ fn annotate_multicast_failure(
     }
 }
 
+/// The number of messages of each stream that a comm actor remembers
+/// having handled, to drop those that reach it again.
+const HANDLED_WINDOW: usize = 64;
+
 /// Parameters to initialize the CommActor
 #[derive(Debug, Clone, Serialize, Deserialize, Named, Default)]
 pub struct CommActorParams {}
 wirevalue::register_type!(CommActorParams);
 
 /// A message buffered due to out-of-order delivery.
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 struct Buffered {
     /// Sequence number of this message.
     seq: usize,
+    /// The comm actor that sequenced this message.
+    sender: ActorAddr,
     /// Whether to deliver this message to this comm-actors actors.
     deliver_here: bool,
     /// Peer comm actors to forward message to.
@@ -126,8 +188,8 @@ struct Buffered {
 }
 
 /// Bookkeeping to handle sequence numbers and in-order delivery for messages
-/// sent to and through this comm actor.
-#[derive(Debug, Default)]
+/// sent to and through this comm actor, for a stream and a root.
+#[derive(Debug, Default, Serialize, Deserialize)]
 struct ReceiveState {
     /// The sequence of the last received message.
     seq: usize,
@@ -146,17 +208,46 @@ struct ReceiveState {
     CastMessage,
     ForwardMessage,
     CastMessageV1,
-    ForwardMessageV1
+    ForwardMessageV1,
+    InternedForwardMessageV1,
+    FetchCastRegion,
+    CastRegionFetched,
+    CommTakeover,
+    CommActorSnapshot,
+    CommPeerReplaced,
+    CommPeerReplacedAck,
+    CastTopologyDump
 )]
 #[hyperactor::spawnable]
 pub struct CommActor {
     /// Sequence numbers are maintained for each (actor mesh id, sender).
     send_seq: HashMap<(ActorMeshId, ActorAddr), usize>,
-    /// Each sender is a unique stream.
-    recv_state: HashMap<(ActorMeshId, ActorAddr), ReceiveState>,
+    /// For each stream, the last sequence number sent to each root, by
+    /// rank.
+    root_last_seqs: HashMap<(ActorMeshId, ActorAddr), HashMap<usize, usize>>,
+    /// Each sender is a unique stream, ordered separately for each root
+    /// at which its messages are injected.
+    recv_state: HashMap<((ActorMeshId, ActorAddr), usize), ReceiveState>,
+    /// For each stream, the sequence numbers of the messages handled
+    /// here most recently, so that a message reaching this comm actor
+    /// through several roots is handled once.
+    handled: HashMap<(ActorMeshId, ActorAddr), VecDeque<usize>>,
+    /// [`CAST_MULTI_ROOT_THRESHOLD`], read when the comm actor starts.
+    multi_root_threshold: usize,
 
     /// The comm actor's mesh configuration, or buffered messages if not yet configured.
     mesh_config: MeshConfigState,
+
+    /// The comm actor this one handed its state to, if any; see
+    /// [`CommTakeover`]. Messages are forwarded to it.
+    successor: Option<ActorRef<CommActor>>,
+
+    /// The ranks of the peers yet to acknowledge that they address
+    /// this comm actor's successor; see [`CommPeerReplaced`].
+    awaiting_acks: HashSet<usize>,
+
+    /// The cast regions interned by this comm actor.
+    regions: intern::RegionCache,
 }
 
 #[derive(Debug)]
@@ -164,6 +255,8 @@ enum PendingMessage {
     Cast(CastMessage),
     Forward(ForwardMessage),
     ForwardV1(ForwardMessageV1),
+    InternedV1(InternedForwardMessageV1),
+    PeerReplaced(CommPeerReplaced),
 }
 
 #[derive(Debug)]
@@ -187,13 +280,94 @@ pub struct CommMeshConfig {
     rank: usize,
     /// Key is the rank of the peer on the root mesh. Value is the peer's comm actor.
     peers: HashMap<usize, ActorRef<CommActor>>,
+    /// The slice of the root mesh, if known, from which the comm
+    /// actor reports the casts it forwards; see [`CastTopologyDump`].
+    slice: Option<ndslice::Slice>,
 }
 wirevalue::register_type!(CommMeshConfig);
 
+/// Hand a comm actor's state over to `successor`, a replacement comm
+/// actor on the same proc or on a neighbor, e.g. during an upgrade.
+///
+/// The comm actor sends its send sequence numbers, reorder buffers,
+/// and mesh configuration to the successor as a [`CommActorSnapshot`],
+/// and forwards the messages it subsequently receives to the successor,
+/// so that the cast streams that pass through it continue on the
+/// successor without being renumbered. It then tells each of its
+/// peers to address the successor instead ([`CommPeerReplaced`]), and
+/// stops once they have all acknowledged. Cast senders must address
+/// the successor from then on.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct CommTakeover {
+    /// The comm actor that takes over.
+    pub successor: ActorRef<CommActor>,
+}
+wirevalue::register_type!(CommTakeover);
+
+/// The state of a comm actor, handed to its successor; see
+/// [`CommTakeover`].
+#[derive(Debug, Serialize, Deserialize, Named)]
+pub struct CommActorSnapshot {
+    send_seq: Vec<((ActorMeshId, ActorAddr), usize)>,
+    root_last_seqs: Vec<((ActorMeshId, ActorAddr), HashMap<usize, usize>)>,
+    recv_state: Vec<(((ActorMeshId, ActorAddr), usize), ReceiveState)>,
+    mesh_config: Option<CommMeshConfig>,
+}
+wirevalue::register_type!(CommActorSnapshot);
+
+/// Tell a comm actor that its peer at `rank` handed its state over to
+/// `successor`; see [`CommTakeover`]. The comm actor addresses the
+/// successor from then on, and acknowledges with
+/// [`CommPeerReplacedAck`] to `predecessor`.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct CommPeerReplaced {
+    /// The rank of the replaced peer.
+    pub rank: usize,
+    /// The comm actor that took over.
+    pub successor: ActorRef<CommActor>,
+    /// The comm actor that was replaced.
+    pub predecessor: ActorRef<CommActor>,
+}
+wirevalue::register_type!(CommPeerReplaced);
+
+/// Acknowledge a [`CommPeerReplaced`]: the comm actor at `rank` no
+/// longer addresses the predecessor.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct CommPeerReplacedAck {
+    /// The rank of the acknowledging comm actor.
+    pub rank: usize,
+}
+wirevalue::register_type!(CommPeerReplacedAck);
+
 impl CommMeshConfig {
     /// Create a new mesh configuration with the given rank and peer mapping.
     pub fn new(rank: usize, peers: HashMap<usize, ActorRef<CommActor>>) -> Self {
-        Self { rank, peers }
+        Self {
+            rank,
+            peers,
+            slice: None,
+        }
+    }
+
+    /// Set the slice of the root mesh.
+    pub fn with_slice(mut self, slice: ndslice::Slice) -> Self {
+        self.slice = Some(slice);
+        self
+    }
+
+    /// The edges of the cast tree from this comm actor to the peers it
+    /// forwards casts to. Empty if the slice of the root mesh is not
+    /// known.
+    fn cast_topology(&self) -> Result<Topology> {
+        let Some(slice) = &self.slice else {
+            return Ok(Topology::new());
+        };
+        let comm_actors = self
+            .peers
+            .iter()
+            .map(|(rank, peer)| (*rank, peer.actor_addr().clone()))
+            .collect();
+        crate::topology::cast_tree_topology(slice.clone(), &comm_actors, Some(self.rank))
     }
 
     /// Return the peer comm actor for the given rank.
@@ -214,6 +388,7 @@ impl CommMeshConfig {
 impl Actor for CommActor {
     async fn init(&mut self, this: &Instance<Self>) -> Result<(), anyhow::Error> {
         this.set_system();
+        self.multi_root_threshold = hyperactor_config::global::get(CAST_MULTI_ROOT_THRESHOLD);
         Ok(())
     }
 
@@ -305,13 +480,72 @@ impl CommActor {
         CommActor: hyperactor::RemoteHandles<M>,
     {
         let child = config.peer_for_rank(rank)?;
+        Self::relay(cx, &child, message);
+        Ok(())
+    }
+
+    /// Post the message to the given comm actor, retaining the cast's
+    /// actor mesh ID.
+    fn relay<M: RemoteMessage>(cx: &Context<Self>, comm_actor: &ActorRef<CommActor>, message: M)
+    where
+        CommActor: hyperactor::RemoteHandles<M>,
+    {
         // TEMPORARY: until dropping v0 support
         if let Some(cast_actor_mesh_id) = cx.headers().get(CAST_ACTOR_MESH_ID) {
             let mut headers = Flattrs::new();
             headers.set(CAST_ACTOR_MESH_ID, cast_actor_mesh_id);
-            child.post_with_headers(cx, headers, message);
+            comm_actor.post_with_headers(cx, headers, message);
         } else {
-            child.post(cx, message);
+            comm_actor.post(cx, message);
+        }
+    }
+
+    /// Forward the message to this comm actor's successor, if it has
+    /// handed its state over. Otherwise, return the message to be
+    /// handled here.
+    fn relay_to_successor<M: RemoteMessage>(&self, cx: &Context<Self>, message: M) -> Option<M>
+    where
+        CommActor: hyperactor::RemoteHandles<M>,
+    {
+        match &self.successor {
+            Some(successor) => {
+                Self::relay(cx, successor, message);
+                None
+            }
+            None => Some(message),
+        }
+    }
+
+    /// Handle the buffered messages of a stream that are no longer out
+    /// of order.
+    fn drain_buffered(
+        cx: &Context<Self>,
+        config: &CommMeshConfig,
+        root: usize,
+        recv_state: &mut ReceiveState,
+        handled: &mut VecDeque<usize>,
+    ) -> Result<()> {
+        while let Some(Buffered {
+            seq,
+            sender,
+            deliver_here,
+            next_steps,
+            message,
+        }) = recv_state.buffer.remove(&recv_state.seq)
+        {
+            Self::handle_message(
+                cx,
+                config,
+                deliver_here,
+                next_steps,
+                sender,
+                message,
+                root,
+                seq,
+                &mut recv_state.last_seqs,
+                handled,
+            )?;
+            recv_state.seq = seq;
         }
         Ok(())
     }
@@ -323,9 +557,24 @@ impl CommActor {
         next_steps: HashMap<usize, Vec<RoutingFrame>>,
         sender: ActorAddr,
         mut message: CastMessageEnvelope,
+        root: usize,
         seq: usize,
         last_seqs: &mut HashMap<usize, usize>,
+        handled: &mut VecDeque<usize>,
     ) -> Result<()> {
+        if handled.contains(&seq) {
+            tracing::warn!(
+                seq,
+                root,
+                "dropping message already handled through another root"
+            );
+            return Ok(());
+        }
+        if handled.len() == HANDLED_WINDOW {
+            handled.pop_front();
+        }
+        handled.push_back(seq);
+
         split_ports(cx, message.data_mut(), deliver_here, &next_steps)?;
 
         // Deliver message here, if necessary.
@@ -350,6 +599,7 @@ impl CommActor {
                         dests,
                         sender: sender.clone(),
                         message: message.clone(),
+                        root,
                         seq,
                         last_seq: *last_seq,
                     },
@@ -368,7 +618,7 @@ impl CommActor {
         message: &mut M,
         config: &CommMeshConfig,
     ) -> anyhow::Result<()> {
-        let cast_point = message.cast_point(config)?;
+        let cast_point = composite_cast_point(message.headers(), message.cast_point(config)?)?;
         // Replace ranks with self ranks.
         replace_with_self_ranks(&cast_point, message.data_mut())?;
 
@@ -400,7 +650,56 @@ impl CommActor {
             );
         }
 
-        cx.post_with_external_seq_info(dest, headers, wirevalue::Any::serialize(message.data())?);
+        // The message is encoded as by the sender's binary. Bring it to
+        // the version of this proc's binary, which the destination
+        // shares, leaving the message as sent for the peers it is
+        // forwarded to.
+        let version = migrate::version_of(message.headers());
+        let mut data = Cow::Borrowed(message.data());
+        if version != migrate::local_version(data.message().typehash())
+            && let Err(err) = data.to_mut().migrate(version)
+        {
+            tracing::warn!(
+                sender = %message.sender(),
+                dest = %dest,
+                "returning cast that cannot be migrated: {}",
+                err
+            );
+            let sender = message.sender().clone();
+            let return_port =
+                PortRef::<Undeliverable<MessageEnvelope>>::attest_handler_port(&sender);
+            let mut envelope = match MessageEnvelope::serialize(
+                cx.self_addr().clone(),
+                dest.clone(),
+                message.data(),
+                headers,
+            ) {
+                Ok(envelope) => envelope,
+                Err(ser_err) => {
+                    tracing::error!(
+                        "dropping cast that cannot be migrated: {}; failed to return it: {}",
+                        err,
+                        ser_err
+                    );
+                    return Ok(());
+                }
+            };
+            envelope.push_delivery_failure(DeliveryFailure::new(InvalidReference::new(
+                dest,
+                InvalidReferenceReason::ProtocolMismatch,
+            )));
+            annotate_multicast_failure(
+                &mut envelope,
+                cx.self_addr(),
+                "deliver_here",
+                &sender,
+                return_port.port_addr(),
+            );
+            return_port.post(cx, Undeliverable::Returned(envelope));
+            return Ok(());
+        }
+        let data = wirevalue::Any::serialize_pooled(&*data, hyperactor::channel::pool::global())?;
+        cx.post_with_external_seq_info(dest, headers, data);
 
         Ok(())
     }
@@ -461,6 +760,89 @@ fn split_ports(
     )
 }
 
+/// The roots at which to inject a cast routed by `frame`, by rank, with
+/// the frames that each routes. When the cast's slice is at least
+/// `threshold` ranks, the routing tree is descended through the
+/// slice's outermost dimension of more than one rank (e.g. its racks),
+/// and each of the dimension's selected indices is its own root, so
+/// that the cast skips the top of the tree. The roots depend on the
+/// slice, not on the selection, so that each comm actor receives the
+/// casts of a stream to the slice from the same peer. Otherwise the
+/// cast has a single root, the tree's.
+fn cast_roots(frame: RoutingFrame, threshold: usize) -> Result<BTreeMap<usize, Vec<RoutingFrame>>> {
+    let fan_out = frame.slice.sizes().iter().position(|&size| size > 1);
+    let frames = match fan_out {
+        Some(dim) if threshold > 0 && frame.slice.len() >= threshold => {
+            descend(&frame, dim + 1).unwrap_or_else(|| vec![frame])
+        }
+        _ => vec![frame],
+    };
+
+    let mut roots = BTreeMap::<usize, Vec<RoutingFrame>>::new();
+    for frame in frames {
+        roots.entry(frame.location()?).or_default().push(frame);
+    }
+    Ok(roots)
+}
+
+/// The frames that `frame` routes to once `depth` dimensions are
+/// routed, deduplicated, or `None` if routing them requires a choice,
+/// which is made by the comm actors on the route, or selects nothing.
+fn descend(frame: &RoutingFrame, depth: usize) -> Option<Vec<RoutingFrame>> {
+    let mut frames = vec![frame.clone()];
+    while frames.iter().any(|frame| frame.dim < depth) {
+        let mut next = Vec::new();
+        let mut seen = HashSet::new();
+        let mut deferred = false;
+        for frame in frames {
+            if frame.dim >= depth || !frame.should_route() {
+                if seen.insert(RoutingFrameKey::new(&frame)) {
+                    next.push(frame);
+                }
+                continue;
+            }
+            let _ = frame.next_steps(&mut |_| 0, &mut |step| {
+                match step {
+                    RoutingStep::Forward(frame) => {
+                        if seen.insert(RoutingFrameKey::new(&frame)) {
+                            next.push(frame);
+                        }
+                    }
+                    RoutingStep::Choice(_) => deferred = true,
+                }
+                std::ops::ControlFlow::Continue(())
+            });
+        }
+        if deferred || next.is_empty() {
+            return None;
+        }
+        frames = next;
+    }
+    Some(frames)
+}
+
+/// The comm actor tree through which a cast to every rank of `slice`
+/// is forwarded, when the cast is sent to the comm actor at rank
+/// `root`, as actor meshes send casts: an edge from each rank to each
+/// rank that it forwards the cast to, sorted. Ranks are on the root
+/// mesh.
+pub(crate) fn cast_tree(root: usize, slice: ndslice::Slice) -> Result<Vec<(usize, usize)>> {
+    let mut edges = Vec::new();
+    let mut pending = VecDeque::from([(root, vec![RoutingFrame::root(sel!(*), slice)])]);
+    while let Some((rank, frames)) = pending.pop_front() {
+        let (_, next_steps) =
+            ndslice::selection::routing::resolve_routing(rank, frames, &mut |_| {
+                panic!("choice encountered in CommActor routing")
+            })?;
+        for (peer, frames) in next_steps {
+            edges.push((rank, peer));
+            pending.push_back((peer, frames));
+        }
+    }
+    edges.sort();
+    Ok(edges)
+}
+
 fn replace_with_self_ranks(cast_point: &Point, data: &mut ErasedUnbound) -> anyhow::Result<()> {
     data.visit_mut::<resource::Rank>(|resource::Rank(rank)| {
         *rank = Some(cast_point.rank());
@@ -471,6 +853,8 @@ fn replace_with_self_ranks(cast_point: &Point, data: &mut ErasedUnbound) -> anyh
 #[async_trait]
 impl Handler<CommMeshConfig> for CommActor {
     async fn handle(&mut self, cx: &Context<Self>, config: CommMeshConfig) -> Result<()> {
+        // The peers may have changed, and must be sent regions anew.
+        self.regions.forget_holders();
         let pending =
             match std::mem::replace(&mut self.mesh_config, MeshConfigState::Configured(config)) {
                 MeshConfigState::NotConfigured(pending) => pending,
@@ -487,6 +871,194 @@ impl Handler<CommMeshConfig> for CommActor {
                 PendingMessage::Cast(m) => self.handle(cx, m).await?,
                 PendingMessage::Forward(m) => self.handle(cx, m).await?,
                 PendingMessage::ForwardV1(m) => self.handle(cx, m).await?,
+                PendingMessage::InternedV1(m) => self.handle(cx, m).await?,
+                PendingMessage::PeerReplaced(m) => self.handle(cx, m).await?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<CommTakeover> for CommActor {
+    async fn handle(&mut self, cx: &Context<Self>, takeover: CommTakeover) -> Result<()> {
+        let CommTakeover { successor } = takeover;
+        anyhow::ensure!(
+            successor.actor_addr() != cx.self_addr(),
+            "comm actor cannot hand its state over to itself"
+        );
+        if let Some(successor) = &self.successor {
+            anyhow::bail!(
+                "comm actor already handed its state over to {}",
+                successor.actor_addr()
+            );
+        }
+
+        let (mesh_config, pending) = match std::mem::take(&mut self.mesh_config) {
+            MeshConfigState::NotConfigured(pending) => (None, pending),
+            MeshConfigState::Configured(config) => (Some(config), Vec::new()),
+        };
+        let self_rank = mesh_config.as_ref().map(|config| config.rank);
+        let peers: Vec<_> = mesh_config
+            .iter()
+            .flat_map(|config| config.peers.iter())
+            .filter(|(rank, _)| Some(**rank) != self_rank)
+            .map(|(rank, peer)| (*rank, peer.clone()))
+            .collect();
+        let snapshot = CommActorSnapshot {
+            send_seq: self.send_seq.drain().collect(),
+            root_last_seqs: self.root_last_seqs.drain().collect(),
+            recv_state: self.recv_state.drain().collect(),
+            mesh_config,
+        };
+        tracing::info!(
+            successor = %successor.actor_addr(),
+            send_streams = snapshot.send_seq.len(),
+            recv_streams = snapshot.recv_state.len(),
+            "handing comm actor state over to successor"
+        );
+        // The snapshot precedes the messages forwarded below, as messages
+        // between a pair of actors are delivered in order.
+        successor.post(cx, snapshot);
+        let predecessor = ActorRef::<CommActor>::attest(cx.self_addr().clone());
+        if let Some(rank) = self_rank {
+            for (peer_rank, peer) in peers {
+                peer.post(
+                    cx,
+                    CommPeerReplaced {
+                        rank,
+                        successor: successor.clone(),
+                        predecessor: predecessor.clone(),
+                    },
+                );
+                self.awaiting_acks.insert(peer_rank);
+            }
+        }
+        self.successor = Some(successor);
+
+        for msg in pending {
+            match msg {
+                PendingMessage::Cast(m) => self.handle(cx, m).await?,
+                PendingMessage::Forward(m) => self.handle(cx, m).await?,
+                PendingMessage::ForwardV1(m) => self.handle(cx, m).await?,
+                PendingMessage::InternedV1(m) => self.handle(cx, m).await?,
+                PendingMessage::PeerReplaced(m) => self.handle(cx, m).await?,
+            }
+        }
+        for held in self.regions.release_held() {
+            match held {
+                HeldCast::Forward(m) => self.handle(cx, m).await?,
+                HeldCast::Interned(m) => self.handle(cx, m).await?,
+            }
+        }
+
+        match self_rank {
+            // No peer addresses this comm actor.
+            Some(_) if self.awaiting_acks.is_empty() => {
+                cx.drain_and_stop("comm actor handed over to its successor")?;
+            }
+            Some(_) => {}
+            None => tracing::warn!(
+                "comm actor handed over before it was configured: its peers are \
+                unknown, and it relays to its successor until stopped"
+            ),
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<CommPeerReplaced> for CommActor {
+    async fn handle(&mut self, cx: &Context<Self>, replaced: CommPeerReplaced) -> Result<()> {
+        let Some(replaced) = self.relay_to_successor(cx, replaced) else {
+            return Ok(());
+        };
+        let config = match &mut self.mesh_config {
+            MeshConfigState::NotConfigured(pending) => {
+                pending.push(PendingMessage::PeerReplaced(replaced));
+                return Ok(());
+            }
+            MeshConfigState::Configured(config) => config,
+        };
+        config.peers.insert(replaced.rank, replaced.successor);
+        // The successor must be sent regions anew.
+        self.regions.forget_holders();
+        replaced
+            .predecessor
+            .post(cx, CommPeerReplacedAck { rank: config.rank });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<CommPeerReplacedAck> for CommActor {
+    async fn handle(&mut self, cx: &Context<Self>, ack: CommPeerReplacedAck) -> Result<()> {
+        if self.awaiting_acks.remove(&ack.rank) && self.awaiting_acks.is_empty() {
+            // No peer addresses this comm actor anymore.
+            cx.drain_and_stop("comm actor handed over to its successor")?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<CommActorSnapshot> for CommActor {
+    async fn handle(&mut self, cx: &Context<Self>, snapshot: CommActorSnapshot) -> Result<()> {
+        let CommActorSnapshot {
+            send_seq,
+            root_last_seqs,
+            recv_state,
+            mesh_config,
+        } = snapshot;
+
+        for (stream, seq) in send_seq {
+            let own = self.send_seq.entry(stream).or_default();
+            *own = (*own).max(seq);
+        }
+        for (stream, last_seqs) in root_last_seqs {
+            let own = self.root_last_seqs.entry(stream).or_default();
+            for (rank, seq) in last_seqs {
+                let own = own.entry(rank).or_default();
+                *own = (*own).max(seq);
+            }
+        }
+        for (stream, mut state) in recv_state {
+            match self.recv_state.remove(&stream) {
+                // Messages that reached this comm actor before the
+                // snapshot are buffered behind the snapshot's.
+                Some(own) if own.seq == 0 => {
+                    state.buffer.extend(own.buffer);
+                    self.recv_state.insert(stream, state);
+                }
+                Some(own) => {
+                    tracing::warn!(
+                        stream = ?stream,
+                        seq = own.seq,
+                        snapshot_seq = state.seq,
+                        "ignoring snapshot of a stream already delivered here"
+                    );
+                    self.recv_state.insert(stream, own);
+                }
+                None => {
+                    self.recv_state.insert(stream, state);
+                }
+            }
+        }
+
+        if let Some(mut config) = mesh_config
+            && matches!(self.mesh_config, MeshConfigState::NotConfigured(_))
+        {
+            // This comm actor takes its predecessor's place among the peers.
+            config
+                .peers
+                .insert(config.rank, ActorRef::attest(cx.self_addr().clone()));
+            // Replays the messages buffered before configuration.
+            self.handle(cx, config).await?;
+        }
+        if let MeshConfigState::Configured(config) = &self.mesh_config {
+            for ((stream, root), recv_state) in self.recv_state.iter_mut() {
+                let handled = self.handled.entry(stream.clone()).or_default();
+                Self::drain_buffered(cx, config, *root, recv_state, handled)?;
             }
         }
         Ok(())
@@ -498,6 +1070,9 @@ impl Handler<CommMeshConfig> for CommActor {
 impl Handler<CastMessage> for CommActor {
     #[tracing::instrument(level = "debug", skip_all)]
     async fn handle(&mut self, cx: &Context<Self>, cast_message: CastMessage) -> Result<()> {
+        let Some(cast_message) = self.relay_to_successor(cx, cast_message) else {
+            return Ok(());
+        };
         let config = match &mut self.mesh_config {
             MeshConfigState::NotConfigured(pending) => {
                 pending.push(PendingMessage::Cast(cast_message));
@@ -505,32 +1080,41 @@ impl Handler<CastMessage> for CommActor {
             }
             MeshConfigState::Configured(config) => config,
         };
-        // Always forward the message to the root rank of the slice, casting starts from there.
+        // Forward the message to the roots of the routing tree, casting starts from there.
         let slice = cast_message.dest.slice.clone();
         let selection = cast_message.dest.selection.clone();
-        let frame = RoutingFrame::root(selection, slice);
-        let rank = frame.slice.location(&frame.here)?;
-        let seq = self
-            .send_seq
-            .entry(cast_message.message.stream_key())
-            .or_default();
-        let last_seq = *seq;
+        let roots = cast_roots(
+            RoutingFrame::root(selection, slice),
+            self.multi_root_threshold,
+        )?;
+        let stream = cast_message.message.stream_key();
+        let seq = self.send_seq.entry(stream.clone()).or_default();
         *seq += 1;
-
-        let fwd_message = ForwardMessage {
-            dests: vec![frame],
-            sender: cx.self_addr().clone(),
-            message: cast_message.message,
-            seq: *seq,
-            last_seq,
-        };
-
-        // Optimization: if forwarding to ourselves, handle inline instead of
-        // going through the message queue
-        if config.self_rank() == rank {
+        let seq = *seq;
+        // Every root receives the same sequence number, and orders it
+        // after the last message of the stream that it received.
+        let last_seqs = self.root_last_seqs.entry(stream).or_default();
+
+        let mut local = None;
+        for (rank, dests) in roots {
+            let fwd_message = ForwardMessage {
+                dests,
+                sender: cx.self_addr().clone(),
+                message: cast_message.message.clone(),
+                root: rank,
+                seq,
+                last_seq: last_seqs.insert(rank, seq).unwrap_or_default(),
+            };
+            // Optimization: if forwarding to ourselves, handle inline instead of
+            // going through the message queue
+            if config.self_rank() == rank {
+                local = Some(fwd_message);
+            } else {
+                Self::forward(cx, config, rank, fwd_message)?;
+            }
+        }
+        if let Some(fwd_message) = local {
             Handler::<ForwardMessage>::handle(self, cx, fwd_message).await?;
-        } else {
-            Self::forward(cx, config, rank, fwd_message)?;
         }
         Ok(())
     }
@@ -540,6 +1124,9 @@ impl Handler<CastMessage> for CommActor {
 impl Handler<ForwardMessage> for CommActor {
     #[tracing::instrument(level = "debug", skip_all)]
     async fn handle(&mut self, cx: &Context<Self>, fwd_message: ForwardMessage) -> Result<()> {
+        let Some(fwd_message) = self.relay_to_successor(cx, fwd_message) else {
+            return Ok(());
+        };
         let config = match &mut self.mesh_config {
             MeshConfigState::NotConfigured(pending) => {
                 pending.push(PendingMessage::Forward(fwd_message));
@@ -552,6 +1139,7 @@ impl Handler<ForwardMessage> for CommActor {
             sender,
             dests,
             message,
+            root,
             seq,
             last_seq,
         } = fwd_message;
@@ -563,7 +1151,9 @@ impl Handler<ForwardMessage> for CommActor {
                 panic!("Choice encountered in CommActor routing")
             })?;
 
-        let recv_state = self.recv_state.entry(message.stream_key()).or_default();
+        let stream = message.stream_key();
+        let handled = self.handled.entry(stream.clone()).or_default();
+        let recv_state = self.recv_state.entry((stream, root)).or_default();
         match recv_state.seq.cmp(&last_seq) {
             // We got the expected next message to deliver to this host.
             Ordering::Equal => {
@@ -575,32 +1165,16 @@ impl Handler<ForwardMessage> for CommActor {
                     next_steps,
                     sender.clone(),
                     message,
+                    root,
                     seq,
                     &mut recv_state.last_seqs,
+                    handled,
                 )?;
                 recv_state.seq = seq;
 
                 // Also deliver any pending operations from the recv buffer that
                 // were received out-of-order that are now unblocked.
-                while let Some(Buffered {
-                    seq,
-                    deliver_here,
-                    next_steps,
-                    message,
-                }) = recv_state.buffer.remove(&recv_state.seq)
-                {
-                    Self::handle_message(
-                        cx,
-                        config,
-                        deliver_here,
-                        next_steps,
-                        sender.clone(),
-                        message,
-                        seq,
-                        &mut recv_state.last_seqs,
-                    )?;
-                    recv_state.seq = seq;
-                }
+                Self::drain_buffered(cx, config, root, recv_state, handled)?;
             }
             // We got an out-of-order operation, so buffer it for now, until we
             // recieved the onces sequenced before it.
@@ -616,6 +1190,7 @@ impl Handler<ForwardMessage> for CommActor {
                     last_seq,
                     Buffered {
                         seq,
+                        sender,
                         deliver_here,
                         next_steps,
                         message,
@@ -648,15 +1223,86 @@ impl Handler<CastMessageV1> for CommActor {
 #[async_trait]
 impl Handler<ForwardMessageV1> for CommActor {
     async fn handle(&mut self, cx: &Context<Self>, fwd_message: ForwardMessageV1) -> Result<()> {
+        let Some(fwd_message) = self.relay_to_successor(cx, fwd_message) else {
+            return Ok(());
+        };
+        if let MeshConfigState::NotConfigured(pending) = &mut self.mesh_config {
+            pending.push(PendingMessage::ForwardV1(fwd_message));
+            return Ok(());
+        }
+
+        // Casts are held back, in order, while the regions of earlier
+        // casts of their stream are fetched.
+        if self.regions.is_holding(&fwd_message.message) {
+            self.regions.hold(HeldCast::Forward(fwd_message));
+            return Ok(());
+        }
+        self.forward_v1(cx, fwd_message)
+    }
+}
+
+#[async_trait]
+impl Handler<InternedForwardMessageV1> for CommActor {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        interned: InternedForwardMessageV1,
+    ) -> Result<()> {
+        let Some(interned) = self.relay_to_successor(cx, interned) else {
+            return Ok(());
+        };
         let config = match &mut self.mesh_config {
             MeshConfigState::NotConfigured(pending) => {
-                pending.push(PendingMessage::ForwardV1(fwd_message));
+                pending.push(PendingMessage::InternedV1(interned));
                 return Ok(());
             }
             MeshConfigState::Configured(config) => config,
         };
 
+        let Some(region) = self.regions.get(&interned.id) else {
+            if let Some(attempt) = self.regions.start_fetch(interned.id) {
+                config.peer_for_rank(interned.holder)?.post(
+                    cx,
+                    FetchCastRegion {
+                        id: interned.id,
+                        reply: cx.port().bind(),
+                    },
+                );
+                cx.post_after(
+                    cx,
+                    CastRegionFetchExpired {
+                        id: interned.id,
+                        attempt,
+                    },
+                    hyperactor_config::global::get(CAST_REGION_FETCH_TIMEOUT),
+                );
+            }
+            self.regions.hold(HeldCast::Interned(interned));
+            return Ok(());
+        };
+        let fwd_message = interned.resolve(region.clone());
+        if self.regions.is_holding(&fwd_message.message) {
+            self.regions.hold(HeldCast::Forward(fwd_message));
+            return Ok(());
+        }
+        self.forward_v1(cx, fwd_message)
+    }
+}
+
+impl CommActor {
+    /// Route a cast whose region is inline: deliver it here, if
+    /// necessary, and forward it to the next steps, with its region
+    /// interned.
+    fn forward_v1(&mut self, cx: &Context<Self>, fwd_message: ForwardMessageV1) -> Result<()> {
+        let MeshConfigState::Configured(config) = &self.mesh_config else {
+            anyhow::bail!("comm actor routed a cast before it was configured");
+        };
         let ForwardMessageV1 { dests, mut message } = fwd_message;
+        if intern::RegionCache::enabled() {
+            // Peers refer to the regions they sent inline.
+            self.regions.insert(message.dest_region.clone());
+        }
+
         // Resolve/dedup routing frames.
         let rank_on_root_mesh = config.self_rank();
         let (deliver_here, next_steps) =
@@ -683,17 +1329,132 @@ impl Handler<ForwardMessageV1> for CommActor {
             Self::deliver_to_dest(cx, headers, &mut message, config)?;
         }
 
-        // Forward to peers.
+        // Forward to peers, with the region interned.
         for (peer_rank_on_root_mesh, dests) in next_steps {
             let forward_message = ForwardMessageV1 {
                 dests,
                 message: message.clone(),
             };
-            Self::forward(cx, config, peer_rank_on_root_mesh, forward_message)?;
+            match self
+                .regions
+                .for_peer(peer_rank_on_root_mesh, &message.dest_region)
+            {
+                Some(id) => Self::forward(
+                    cx,
+                    config,
+                    peer_rank_on_root_mesh,
+                    InternedForwardMessageV1::new(forward_message, id, rank_on_root_mesh),
+                )?,
+                None => Self::forward(cx, config, peer_rank_on_root_mesh, forward_message)?,
+            }
         }
 
         Ok(())
     }
+
+    /// Handle the held casts that no longer wait for a region, and
+    /// return those whose region could not be fetched to their senders.
+    fn release_fetched(&mut self, cx: &Context<Self>, error: &str) -> Result<()> {
+        let (ready, failed) = self.regions.take_ready();
+        for interned in failed {
+            Self::return_unfetched(cx, interned, error);
+        }
+        for fwd_message in ready {
+            self.forward_v1(cx, fwd_message)?;
+        }
+        Ok(())
+    }
+
+    /// Return `interned`, whose region could not be fetched, to its
+    /// sender as undeliverable.
+    fn return_unfetched(cx: &Context<Self>, interned: InternedForwardMessageV1, error: &str) {
+        let sender = interned.sender().clone();
+        let return_port = PortRef::<Undeliverable<MessageEnvelope>>::attest_handler_port(&sender);
+        let dest = PortRef::<InternedForwardMessageV1>::attest_handler_port(cx.self_addr());
+        let mut headers = interned.headers().clone();
+        headers.set(CAST_ORIGINATING_SENDER, sender.clone());
+        let reason = format!(
+            "cast region {:?} could not be fetched from the comm actor at rank {}: {}",
+            interned.id, interned.holder, error
+        );
+        let mut envelope = match MessageEnvelope::serialize(
+            cx.self_addr().clone(),
+            dest.port_addr().clone(),
+            &interned,
+            headers,
+        ) {
+            Ok(envelope) => envelope,
+            Err(err) => {
+                tracing::error!("dropping cast: {}; failed to return it: {}", reason, err);
+                return;
+            }
+        };
+        envelope.push_delivery_failure(DeliveryFailure::new(UndeliverableReason::Transport(
+            TransportFailure::new(
+                cx.self_addr().clone(),
+                TransportFailureReason::LinkUnavailable(reason),
+            ),
+        )));
+        annotate_multicast_failure(
+            &mut envelope,
+            cx.self_addr(),
+            "fetch_region",
+            &sender,
+            return_port.port_addr(),
+        );
+        return_port.post(cx, Undeliverable::Returned(envelope));
+    }
+}
+
+#[async_trait]
+impl Handler<FetchCastRegion> for CommActor {
+    async fn handle(&mut self, cx: &Context<Self>, fetch: FetchCastRegion) -> Result<()> {
+        let FetchCastRegion { id, reply } = fetch;
+        reply.post(
+            cx,
+            CastRegionFetched {
+                id,
+                region: self.regions.get(&id).cloned(),
+            },
+        );
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<CastRegionFetched> for CommActor {
+    async fn handle(&mut self, cx: &Context<Self>, fetched: CastRegionFetched) -> Result<()> {
+        self.regions.finish_fetch(fetched);
+        self.release_fetched(cx, "the comm actor no longer holds it")
+    }
+}
+
+#[async_trait]
+impl Handler<CastRegionFetchExpired> for CommActor {
+    async fn handle(&mut self, cx: &Context<Self>, expired: CastRegionFetchExpired) -> Result<()> {
+        if !self.regions.expire_fetch(expired.id, expired.attempt) {
+            return Ok(());
+        }
+        self.release_fetched(cx, "timed out")
+    }
+}
+
+#[async_trait]
+impl Handler<CastTopologyDump> for CommActor {
+    async fn handle(&mut self, cx: &Context<Self>, message: CastTopologyDump) -> Result<()> {
+        let CastTopologyDump {
+            mut topology,
+            result,
+        } = message;
+        if let MeshConfigState::Configured(config) = &self.mesh_config {
+            match config.cast_topology() {
+                Ok(cast) => topology.merge(cast),
+                Err(err) => tracing::warn!("failed to compute cast topology: {:#}", err),
+            }
+        }
+        crate::proc_agent::reply_best_effort(cx, result, TopologyResult { topology });
+        Ok(())
+    }
 }
 
 pub mod test_utils {
@@ -982,6 +1743,7 @@ mod tests {
             multicast::ForwardMessage {
                 sender: client.self_addr().clone(),
                 dests: vec![frame],
+                root: 0,
                 seq: next_seq,
                 last_seq,
                 message: envelope,
@@ -990,6 +1752,267 @@ mod tests {
         .await;
     }
 
+    #[async_timed_test(timeout_secs = 1)]
+    async fn cast_that_cannot_be_migrated_is_not_delivered() {
+        use ndslice::Slice;
+
+        let (client, mut rx, comm_handle, actor_mesh_id, _guards) =
+            buffering_fixture("test_unmigratable").await;
+        send_config(&client, &comm_handle);
+
+        let cast = |payload: &str, version| {
+            let slice = Slice::new_row_major(vec![1]);
+            let shape = ndslice::Shape::new(vec!["rank".to_string()], slice.clone()).unwrap();
+            let envelope = multicast::CastMessageEnvelope::new::<TestActor, TestMessage>(
+                actor_mesh_id.clone(),
+                client.self_addr().clone(),
+                shape,
+                hyperactor_config::Flattrs::new(),
+                TestMessage::Forward(payload.to_string()),
+            )
+            .unwrap()
+            .with_version(version);
+            multicast::CastMessage {
+                dest: multicast::Uslice {
+                    slice,
+                    selection: sel!(*),
+                },
+                message: envelope,
+            }
+        };
+
+        // No migration to version 0 of TestMessage is registered from
+        // version 2, so the first cast is returned rather than delivered.
+        comm_handle.post(&client, cast("newer", 2));
+        comm_handle.post(&client, cast("current", 0));
+
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            TestMessage::Forward("current".to_string()),
+        );
+        comm_handle.drain_and_stop("test done").ok();
+    }
+
+    #[test]
+    fn test_cast_roots() {
+        use ndslice::Slice;
+
+        let roots = |selection, sizes: Vec<usize>| {
+            let threshold = hyperactor_config::global::get(CAST_MULTI_ROOT_THRESHOLD);
+            cast_roots(
+                RoutingFrame::root(selection, Slice::new_row_major(sizes)),
+                threshold,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|(rank, frames)| (rank, frames.len()))
+            .collect::<Vec<_>>()
+        };
+
+        let config = hyperactor_config::global::lock();
+        // A single root by default.
+        assert_eq!(roots(sel!(*, *), vec![2, 4]), vec![(0, 1)]);
+
+        let _guard = config.override_key(CAST_MULTI_ROOT_THRESHOLD, 8);
+        // One root per row.
+        assert_eq!(roots(sel!(*, *), vec![2, 4]), vec![(0, 1), (4, 1)]);
+        // The tree is descended through its first dimension that fans out.
+        assert_eq!(
+            roots(sel!(*, *), vec![1, 8]),
+            (0..8).map(|rank| (rank, 1)).collect::<Vec<_>>()
+        );
+        // The roots do not depend on the selection: a cast to a row is
+        // injected at the row's root.
+        assert_eq!(roots(sel!(1, *), vec![2, 4]), vec![(4, 1)]);
+        // Smaller slices have a single root.
+        assert_eq!(roots(sel!(*, *), vec![2, 3]), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn test_cast_tree() {
+        use ndslice::Slice;
+
+        // Every rank receives the cast exactly once, from the root or
+        // from a rank that received it.
+        let slice = Slice::new_row_major(vec![2, 3, 2]);
+        let edges = cast_tree(0, slice.clone()).unwrap();
+        assert_eq!(edges.len(), slice.len() - 1);
+        let mut reached = HashSet::from([0]);
+        let mut remaining = edges.clone();
+        while !remaining.is_empty() {
+            let before = remaining.len();
+            remaining.retain(|(from, to)| {
+                if reached.contains(from) {
+                    assert!(reached.insert(*to), "rank {} reached twice", to);
+                    false
+                } else {
+                    true
+                }
+            });
+            assert!(
+                remaining.len() < before,
+                "unreachable edges {:?}",
+                remaining
+            );
+        }
+        assert_eq!(reached, slice.iter().collect::<HashSet<_>>());
+
+        // A cast to a sub-slice is forwarded to it by the root.
+        let sub = Slice::new(6, vec![2], vec![1]).unwrap();
+        let edges = cast_tree(0, sub).unwrap();
+        assert!(edges.contains(&(0, 6)));
+        assert!(edges.iter().all(|(_, to)| [6, 7].contains(to)));
+    }
+
+    #[async_timed_test(timeout_secs = 5)]
+    async fn takeover_continues_streams_without_renumbering() {
+        use ndslice::Slice;
+        use ndslice::selection::routing::RoutingFrame;
+
+        let (client, mut rx, comm_handle, actor_mesh_id, _guards) =
+            buffering_fixture("test_takeover").await;
+        send_config(&client, &comm_handle);
+
+        let slice = Slice::new_row_major(vec![1]);
+        let shape = ndslice::Shape::new(vec!["rank".to_string()], slice.clone()).unwrap();
+        let envelope = |payload: &str| {
+            multicast::CastMessageEnvelope::new::<TestActor, TestMessage>(
+                actor_mesh_id.clone(),
+                client.self_addr().clone(),
+                shape.clone(),
+                hyperactor_config::Flattrs::new(),
+                TestMessage::Forward(payload.to_string()),
+            )
+            .unwrap()
+        };
+        let cast = |payload: &str| multicast::CastMessage {
+            dest: multicast::Uslice {
+                slice: slice.clone(),
+                selection: sel!(*),
+            },
+            message: envelope(payload),
+        };
+
+        comm_handle.post(&client, cast("before"));
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            TestMessage::Forward("before".to_string())
+        );
+
+        // Hand the stream over, then cast through the old comm actor.
+        let successor = client.proc().spawn(CommActor::default());
+        comm_handle.post(
+            &client,
+            CommTakeover {
+                successor: successor.bind::<CommActor>(),
+            },
+        );
+        comm_handle.post(&client, cast("relayed"));
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            TestMessage::Forward("relayed".to_string())
+        );
+
+        // The successor continues the stream's numbering: the next
+        // message in the stream is delivered without waiting for
+        // earlier ones.
+        successor.post(
+            &client,
+            multicast::ForwardMessage {
+                sender: comm_handle.actor_addr().clone(),
+                dests: vec![RoutingFrame::root(sel!(*), slice.clone())],
+                root: 0,
+                seq: 3,
+                last_seq: 2,
+                message: envelope("direct"),
+            },
+        );
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            TestMessage::Forward("direct".to_string())
+        );
+
+        successor.drain_and_stop("test done").ok();
+        comm_handle.drain_and_stop("test done").ok();
+    }
+
+    #[async_timed_test(timeout_secs = 5)]
+    async fn forward_orders_per_root_and_drops_duplicates() {
+        use ndslice::Slice;
+        use ndslice::selection::routing::RoutingFrame;
+
+        let (client, mut rx, comm_handle, actor_mesh_id, _guards) =
+            buffering_fixture("test_per_root").await;
+        send_config(&client, &comm_handle);
+
+        let slice = Slice::new_row_major(vec![1]);
+        let shape = ndslice::Shape::new(vec!["rank".to_string()], slice.clone()).unwrap();
+        let forward =
+            |payload: &str, root: usize, seq: usize, last_seq: usize| multicast::ForwardMessage {
+                sender: client.self_addr().clone(),
+                dests: vec![RoutingFrame::root(sel!(*), slice.clone())],
+                root,
+                seq,
+                last_seq,
+                message: multicast::CastMessageEnvelope::new::<TestActor, TestMessage>(
+                    actor_mesh_id.clone(),
+                    client.self_addr().clone(),
+                    shape.clone(),
+                    hyperactor_config::Flattrs::new(),
+                    TestMessage::Forward(payload.to_string()),
+                )
+                .unwrap(),
+            };
+
+        // Each root chains the sequence numbers of the messages it
+        // forwards here: the first message through root 0 is not a
+        // duplicate of one received through root 1.
+        comm_handle.post(&client, forward("first", 1, 1, 0));
+        comm_handle.post(&client, forward("second", 0, 2, 0));
+        // A message already handled through another root is dropped.
+        comm_handle.post(&client, forward("second", 1, 2, 1));
+        comm_handle.post(&client, forward("third", 1, 3, 2));
+
+        for payload in ["first", "second", "third"] {
+            assert_eq!(
+                rx.recv().await.unwrap(),
+                TestMessage::Forward(payload.to_string())
+            );
+        }
+        comm_handle.drain_and_stop("test done").ok();
+    }
+
+    #[async_timed_test(timeout_secs = 5)]
+    async fn takeover_reconfigures_peers_and_stops() {
+        let (client, _rx, comm_handle, _actor_mesh_id, _guards) =
+            buffering_fixture("test_takeover_peers").await;
+        let peer = client.proc().spawn(CommActor::default());
+        let peers = HashMap::from([
+            (0, comm_handle.bind::<CommActor>()),
+            (1, peer.bind::<CommActor>()),
+        ]);
+        comm_handle.post(&client, CommMeshConfig::new(0, peers.clone()));
+        peer.post(&client, CommMeshConfig::new(1, peers));
+
+        let successor = client.proc().spawn(CommActor::default());
+        comm_handle.post(
+            &client,
+            CommTakeover {
+                successor: successor.bind::<CommActor>(),
+            },
+        );
+
+        // The old comm actor stops once its peer addresses the successor.
+        std::assert_matches::assert_matches!(
+            comm_handle.await,
+            hyperactor::actor::ActorStatus::Stopped(reason)
+                if reason == "comm actor handed over to its successor"
+        );
+
+        successor.drain_and_stop("test done").ok();
+        peer.drain_and_stop("test done").ok();
+    }
+
     #[async_timed_test(timeout_secs = 1)]
     async fn forward_v1_before_config_is_buffered_and_replayed() {
         use ndslice::Region;
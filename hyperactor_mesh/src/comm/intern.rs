@@ -0,0 +1,512 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Interning of cast regions.
+//!
+//! Every cast carries the region of its destination mesh, which the
+//! comm actors need to route it and to compute each destination's cast
+//! point. A region's labels, sizes, and strides are typically larger
+//! than a small message, and they are the same for every cast to a
+//! mesh. Comm actors therefore intern the regions they forward: the
+//! first time a comm actor forwards a region to a peer, it sends the
+//! cast as a [`ForwardMessageV1`], with the region inline; afterwards
+//! it sends an [`InternedForwardMessageV1`], which carries only the
+//! region's [`RegionId`], a hash of its contents, together with its own
+//! rank. A region is thus registered with each peer once per mesh and
+//! region (e.g. once per mesh epoch, if the mesh's region changes).
+//!
+//! A peer that does not know an interned region, for example because it
+//! evicted it from its cache or took over from another comm actor,
+//! fetches it from the comm actor that forwarded it, with
+//! [`FetchCastRegion`], holding back the casts of the same stream that
+//! it receives in the meantime so that they are still delivered in
+//! order. Casts whose region cannot be fetched within
+//! [`CAST_REGION_FETCH_TIMEOUT`](crate::comm::CAST_REGION_FETCH_TIMEOUT)
+//! are returned to their senders as undeliverable.
+//!
+//! The cache size is [`CAST_REGION_CACHE_SIZE`]; 0, the default,
+//! disables interning, so that casts are forwarded only in the form
+//! that every comm actor accepts.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+
+use hyperactor::ActorAddr;
+use hyperactor::Bind;
+use hyperactor::PortRef;
+use hyperactor::Unbind;
+use hyperactor::message::ErasedUnbound;
+use hyperactor_config::Flattrs;
+use hyperactor_config::attrs::fnv1a_hash;
+use ndslice::Region;
+use ndslice::selection::routing::RoutingFrame;
+use serde::Deserialize;
+use serde::Serialize;
+use typeuri::Named;
+use uuid::Uuid;
+
+use crate::ValueMesh;
+use crate::comm::CAST_REGION_CACHE_SIZE;
+use crate::comm::multicast::CastMessageV1;
+use crate::comm::multicast::DestinationPort;
+use crate::comm::multicast::ForwardMessageV1;
+
+/// The id of an interned region: a hash of its labels and slice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Named)]
+pub struct RegionId(u64);
+wirevalue::register_type!(RegionId);
+
+impl RegionId {
+    /// The id of `region`.
+    pub fn of(region: &Region) -> Self {
+        let slice = region.slice();
+        let mut bytes = Vec::new();
+        for label in region.labels() {
+            bytes.extend_from_slice(&(label.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(label.as_bytes());
+        }
+        bytes.extend_from_slice(&(slice.offset() as u64).to_le_bytes());
+        for &n in slice.sizes().iter().chain(slice.strides()) {
+            bytes.extend_from_slice(&(n as u64).to_le_bytes());
+        }
+        Self(fnv1a_hash(&bytes))
+    }
+}
+
+/// A [`ForwardMessageV1`] whose destination region is interned by the
+/// comm actor at rank `holder` of the root mesh, from which it can be
+/// fetched.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub(crate) struct InternedForwardMessageV1 {
+    /// The id of the destination mesh's region.
+    pub(super) id: RegionId,
+    /// The rank of the comm actor that holds the region.
+    pub(super) holder: usize,
+    /// The destination of the message.
+    dests: Vec<RoutingFrame>,
+    /// The additional end-to-end message headers.
+    headers: Flattrs,
+    /// The client who sent this message.
+    sender: ActorAddr,
+    /// The client-assigned session id of this message.
+    session_id: Uuid,
+    /// The client-assigned sequence numbers of this message.
+    seqs: ValueMesh<u64>,
+    /// The destination port of the message.
+    dest_port: DestinationPort,
+    /// The serialized message.
+    data: ErasedUnbound,
+}
+wirevalue::register_type!(InternedForwardMessageV1);
+
+impl InternedForwardMessageV1 {
+    /// Intern the region of `forward`, which has id `id` and is held
+    /// by the comm actor at rank `holder`.
+    pub(super) fn new(forward: ForwardMessageV1, id: RegionId, holder: usize) -> Self {
+        let ForwardMessageV1 { dests, message } = forward;
+        let CastMessageV1 {
+            headers,
+            sender,
+            session_id,
+            seqs,
+            dest_region: _,
+            dest_port,
+            data,
+        } = message;
+        Self {
+            id,
+            holder,
+            dests,
+            headers,
+            sender,
+            session_id,
+            seqs,
+            dest_port,
+            data,
+        }
+    }
+
+    /// The forwarded message, with its region resolved to `region`.
+    pub(super) fn resolve(self, region: Region) -> ForwardMessageV1 {
+        let Self {
+            dests,
+            headers,
+            sender,
+            session_id,
+            seqs,
+            dest_port,
+            data,
+            ..
+        } = self;
+        ForwardMessageV1 {
+            dests,
+            message: CastMessageV1 {
+                headers,
+                sender,
+                session_id,
+                seqs,
+                dest_region: region,
+                dest_port,
+                data,
+            },
+        }
+    }
+
+    /// The message's end-to-end headers.
+    pub(super) fn headers(&self) -> &Flattrs {
+        &self.headers
+    }
+
+    /// The client who sent the message.
+    pub(super) fn sender(&self) -> &ActorAddr {
+        &self.sender
+    }
+}
+
+/// Request the region with `id` from a comm actor that interned it.
+/// The comm actor replies with [`CastRegionFetched`].
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct FetchCastRegion {
+    /// The region to fetch.
+    pub id: RegionId,
+    /// Where to send the region.
+    pub reply: PortRef<CastRegionFetched>,
+}
+wirevalue::register_type!(FetchCastRegion);
+
+/// The reply to [`FetchCastRegion`]: the requested region, or `None` if
+/// the comm actor no longer holds it.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct CastRegionFetched {
+    /// The requested region's id.
+    pub id: RegionId,
+    /// The requested region.
+    pub region: Option<Region>,
+}
+wirevalue::register_type!(CastRegionFetched);
+
+/// Self-message that fails the fetch `attempt` of the region with
+/// `id` if it is still in progress.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named, Bind, Unbind)]
+pub(super) struct CastRegionFetchExpired {
+    pub(super) id: RegionId,
+    pub(super) attempt: u64,
+}
+
+/// A cast held back while the region of an earlier cast of its stream
+/// is fetched.
+#[derive(Debug)]
+pub(super) enum HeldCast {
+    /// A cast whose region is inline.
+    Forward(ForwardMessageV1),
+    /// A cast whose region is interned.
+    Interned(InternedForwardMessageV1),
+}
+
+/// The stream of a cast, within which casts are delivered in order.
+type Stream = (ActorAddr, Uuid);
+
+impl HeldCast {
+    fn stream(&self) -> Stream {
+        match self {
+            Self::Forward(forward) => (forward.message.sender.clone(), forward.message.session_id),
+            Self::Interned(interned) => (interned.sender.clone(), interned.session_id),
+        }
+    }
+}
+
+/// A comm actor's interned regions.
+#[derive(Debug, Default)]
+pub(super) struct RegionCache {
+    /// The interned regions.
+    regions: HashMap<RegionId, Region>,
+    /// The interned regions' ids, oldest first, for eviction.
+    order: VecDeque<RegionId>,
+    /// For each interned region, the peers, by rank, to which it has
+    /// been sent.
+    holders: HashMap<RegionId, HashSet<usize>>,
+    /// The regions being fetched, with the attempt of each fetch.
+    fetching: HashMap<RegionId, u64>,
+    /// The number of fetches started.
+    attempts: u64,
+    /// For each stream, the casts held back while the region of the
+    /// first is fetched, in the order in which they were received.
+    held: HashMap<Stream, VecDeque<HeldCast>>,
+}
+
+impl RegionCache {
+    /// Whether regions are interned.
+    pub(super) fn enabled() -> bool {
+        hyperactor_config::global::get(CAST_REGION_CACHE_SIZE) > 0
+    }
+
+    /// Intern `region`, evicting the oldest regions beyond the cache
+    /// size.
+    pub(super) fn insert(&mut self, region: Region) -> RegionId {
+        let id = RegionId::of(&region);
+        if self.regions.contains_key(&id) {
+            return id;
+        }
+        // Even with interning disabled, fetched regions are kept until
+        // the casts that refer to them are resolved.
+        let capacity = hyperactor_config::global::get(CAST_REGION_CACHE_SIZE).max(1);
+        self.regions.insert(id, region);
+        self.order.push_back(id);
+        while self.order.len() > capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.regions.remove(&evicted);
+                self.holders.remove(&evicted);
+            }
+        }
+        id
+    }
+
+    /// The interned region with `id`.
+    pub(super) fn get(&self, id: &RegionId) -> Option<&Region> {
+        self.regions.get(id)
+    }
+
+    /// The id under which to send `region` to the peer at `peer_rank`,
+    /// or `None` to send it inline: the first time, and whenever
+    /// interning is disabled.
+    pub(super) fn for_peer(&mut self, peer_rank: usize, region: &Region) -> Option<RegionId> {
+        if !Self::enabled() {
+            return None;
+        }
+        let id = self.insert(region.clone());
+        (!self.holders.entry(id).or_default().insert(peer_rank)).then_some(id)
+    }
+
+    /// Forget which peers hold which regions, for example because the
+    /// peers were reconfigured.
+    pub(super) fn forget_holders(&mut self) {
+        self.holders.clear();
+    }
+
+    /// Whether the casts of the stream of `message` are held back.
+    pub(super) fn is_holding(&self, message: &CastMessageV1) -> bool {
+        self.held
+            .contains_key(&(message.sender.clone(), message.session_id))
+    }
+
+    /// Hold back `cast` until the region of the first held cast of its
+    /// stream is fetched.
+    pub(super) fn hold(&mut self, cast: HeldCast) {
+        self.held.entry(cast.stream()).or_default().push_back(cast);
+    }
+
+    /// Release the casts held back, for example to hand them to a
+    /// successor.
+    pub(super) fn release_held(&mut self) -> Vec<HeldCast> {
+        self.fetching.clear();
+        self.held.drain().flat_map(|(_, casts)| casts).collect()
+    }
+
+    /// Record that the region with `id` is being fetched. Returns the
+    /// fetch's attempt, or `None` if the region already was.
+    pub(super) fn start_fetch(&mut self, id: RegionId) -> Option<u64> {
+        if self.fetching.contains_key(&id) {
+            return None;
+        }
+        self.attempts += 1;
+        self.fetching.insert(id, self.attempts);
+        Some(self.attempts)
+    }
+
+    /// Record a fetched region, and stop fetching it. Returns false if
+    /// the region is no longer held by the comm actor it was fetched
+    /// from.
+    pub(super) fn finish_fetch(&mut self, fetched: CastRegionFetched) -> bool {
+        self.fetching.remove(&fetched.id);
+        match fetched.region {
+            Some(region) => {
+                self.insert(region);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop the fetch `attempt` of the region with `id`. Returns false
+    /// if it was not in progress.
+    pub(super) fn expire_fetch(&mut self, id: RegionId, attempt: u64) -> bool {
+        if self.fetching.get(&id) != Some(&attempt) {
+            return false;
+        }
+        self.fetching.remove(&id);
+        true
+    }
+
+    /// Take the held casts that can be handled, in order: those of each
+    /// stream up to the first whose region is still being fetched.
+    /// Interned casts are resolved, except those whose region could not
+    /// be fetched, which are returned separately, to be failed.
+    pub(super) fn take_ready(&mut self) -> (Vec<ForwardMessageV1>, Vec<InternedForwardMessageV1>) {
+        let mut ready = Vec::new();
+        let mut failed = Vec::new();
+        self.held.retain(|_, casts| {
+            while let Some(cast) = casts.pop_front() {
+                match cast {
+                    HeldCast::Forward(forward) => ready.push(forward),
+                    HeldCast::Interned(interned) => match self.regions.get(&interned.id) {
+                        Some(region) => ready.push(interned.resolve(region.clone())),
+                        None if self.fetching.contains_key(&interned.id) => {
+                            casts.push_front(HeldCast::Interned(interned));
+                            return true;
+                        }
+                        None => failed.push(interned),
+                    },
+                }
+            }
+            false
+        });
+        (ready, failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor::ProcAddr;
+    use hyperactor::channel::ChannelAddr;
+    use hyperactor::id::Label;
+    use ndslice::Selection;
+    use ndslice::extent;
+
+    use super::*;
+    use crate::comm::test_utils::TestActor;
+    use crate::comm::test_utils::TestMessage;
+    use crate::mesh_id::ActorMeshId;
+
+    fn forward(sender: &str, session_id: Uuid, region: &Region) -> ForwardMessageV1 {
+        let sender = ProcAddr::singleton(ChannelAddr::Local(1), "test").actor_addr(sender);
+        let message = CastMessageV1::new::<TestActor, TestMessage>(
+            sender,
+            &ActorMeshId::instance(Label::new("mesh").unwrap()),
+            region.clone(),
+            Flattrs::new(),
+            TestMessage::Forward("hello".to_string()),
+            session_id,
+            ValueMesh::from_single(region.clone(), 0u64),
+        )
+        .unwrap();
+        ForwardMessageV1 {
+            dests: vec![RoutingFrame::root(Selection::True, region.slice().clone())],
+            message,
+        }
+    }
+
+    #[test]
+    fn test_region_id() {
+        let region: Region = extent!(host = 4, gpu = 8).into();
+        let other: Region = extent!(host = 2).into();
+        assert_eq!(RegionId::of(&region), RegionId::of(&region.clone()));
+        assert_ne!(RegionId::of(&region), RegionId::of(&other));
+    }
+
+    #[test]
+    fn test_for_peer() {
+        let region: Region = extent!(host = 4, gpu = 8).into();
+        let mut cache = RegionCache::default();
+        // Interning is disabled by default.
+        assert_eq!(cache.for_peer(1, &region), None);
+        assert_eq!(cache.for_peer(1, &region), None);
+
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(CAST_REGION_CACHE_SIZE, 16);
+        let id = RegionId::of(&region);
+        // Regions are sent inline to each peer once.
+        assert_eq!(cache.for_peer(1, &region), None);
+        assert_eq!(cache.for_peer(2, &region), None);
+        assert_eq!(cache.for_peer(1, &region), Some(id));
+        assert_eq!(cache.for_peer(2, &region), Some(id));
+        assert_eq!(cache.get(&id), Some(&region));
+
+        // Reconfigured peers receive regions inline again.
+        cache.forget_holders();
+        assert_eq!(cache.for_peer(1, &region), None);
+    }
+
+    #[test]
+    fn test_interned_round_trip() {
+        let region: Region = extent!(host = 4, gpu = 8).into();
+        let session_id = Uuid::new_v4();
+        let id = RegionId::of(&region);
+        let interned = InternedForwardMessageV1::new(forward("client", session_id, &region), id, 3);
+        assert_eq!(interned.id, id);
+        assert_eq!(interned.holder, 3);
+
+        let resolved = interned.resolve(region.clone());
+        assert_eq!(resolved.message.dest_region, region);
+        assert_eq!(resolved.message.session_id, session_id);
+        assert_eq!(resolved.dests.len(), 1);
+    }
+
+    #[test]
+    fn test_held_casts() {
+        let region: Region = extent!(host = 4, gpu = 8).into();
+        let other: Region = extent!(host = 2).into();
+        let id = RegionId::of(&region);
+        let other_id = RegionId::of(&other);
+        let session_id = Uuid::new_v4();
+        let mut cache = RegionCache::default();
+
+        // A cast whose region is fetched holds back the later casts of
+        // its stream, but not those of other streams.
+        assert_eq!(cache.start_fetch(id), Some(1));
+        assert_eq!(cache.start_fetch(id), None);
+        cache.hold(HeldCast::Interned(InternedForwardMessageV1::new(
+            forward("client", session_id, &region),
+            id,
+            0,
+        )));
+        let later = forward("client", session_id, &other);
+        assert!(cache.is_holding(&later.message));
+        cache.hold(HeldCast::Forward(later));
+        assert!(!cache.is_holding(&forward("other", session_id, &other).message));
+        let (ready, failed) = cache.take_ready();
+        assert!(ready.is_empty());
+        assert!(failed.is_empty());
+
+        // Once the region is fetched, the casts are released in order.
+        assert!(cache.finish_fetch(CastRegionFetched {
+            id,
+            region: Some(region.clone()),
+        }));
+        let (ready, failed) = cache.take_ready();
+        assert!(failed.is_empty());
+        let regions: Vec<_> = ready.iter().map(|f| &f.message.dest_region).collect();
+        assert_eq!(regions, vec![&region, &other]);
+        assert!(!cache.is_holding(&forward("client", session_id, &other).message));
+
+        // Casts whose region cannot be fetched in time are failed; the
+        // casts held behind them are released.
+        assert_eq!(cache.start_fetch(other_id), Some(2));
+        cache.hold(HeldCast::Interned(InternedForwardMessageV1::new(
+            forward("client", session_id, &other),
+            other_id,
+            0,
+        )));
+        cache.hold(HeldCast::Forward(forward("client", session_id, &region)));
+        assert!(!cache.expire_fetch(other_id, 1));
+        assert!(cache.expire_fetch(other_id, 2));
+        assert!(!cache.expire_fetch(other_id, 2));
+        let (ready, failed) = cache.take_ready();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].id, other_id);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].message.dest_region, region);
+
+        // Regions that their holder no longer has are failed likewise.
+        assert_eq!(cache.start_fetch(other_id), Some(3));
+        assert!(!cache.finish_fetch(CastRegionFetched {
+            id: other_id,
+            region: None,
+        }));
+    }
+}
@@ -8,6 +8,8 @@
 
 //! The comm actor that provides message casting and result accumulation.
 
+use std::sync::OnceLock;
+
 use hyperactor::Actor;
 use hyperactor::ActorAddr;
 use hyperactor::Context;
@@ -15,16 +17,20 @@ use hyperactor::RemoteHandles;
 use hyperactor::RemoteMessage;
 use hyperactor::actor::Referable;
 use hyperactor::id::Uid;
+use hyperactor::mailbox::migrate;
 use hyperactor::message::Castable;
 use hyperactor::message::ErasedUnbound;
 use hyperactor::message::IndexedErasedUnbound;
 use hyperactor_config::Flattrs;
+use hyperactor_config::attrs::COMPACT_KEY;
 use hyperactor_config::attrs::declare_attrs;
+use hyperactor_config::flattrs::compact_headers;
 use ndslice::Extent;
 use ndslice::Point;
 use ndslice::Region;
 use ndslice::Shape;
 use ndslice::Slice;
+use ndslice::compact::Compact;
 use ndslice::selection::Selection;
 use ndslice::selection::routing::RoutingFrame;
 use serde::Deserialize;
@@ -62,9 +68,13 @@ pub struct Uslice {
 /// An envelope that carries a message destined to a group of actors.
 #[derive(Debug, Serialize, Deserialize, Clone, Named)]
 pub struct CastMessageEnvelope {
-    /// The destination actor mesh id.
+    /// The destination actor mesh id, or the id of the stream on which
+    /// the message is cast (see [`CastMessageEnvelope::with_stream`]).
     actor_mesh_id: ActorMeshId,
-    /// The end-to-end message headers.
+    /// The end-to-end message headers. They carry the wire version of
+    /// the message in the sender's binary (see
+    /// [`hyperactor::mailbox::migrate`]), to which the message is
+    /// migrated before it is delivered.
     headers: Flattrs,
     /// The sender of this message.
     sender: ActorAddr,
@@ -73,8 +83,13 @@ pub struct CastMessageEnvelope {
     dest_port: DestinationPort,
     /// The serialized message.
     data: ErasedUnbound,
-    /// The shape of the cast.
+    /// The shape of the cast, or [`Shape::unity`] if it is carried in
+    /// the [`CAST_SHAPE`] header.
     shape: Shape,
+    /// The shape carried in the [`CAST_SHAPE`] header, decoded on
+    /// first use.
+    #[serde(skip)]
+    header_shape: OnceLock<Shape>,
 }
 wirevalue::register_type!(CastMessageEnvelope);
 
@@ -102,7 +117,7 @@ impl CastEnvelope for CastMessageEnvelope {
     fn cast_point(&self, config: &CommMeshConfig) -> anyhow::Result<Point> {
         let rank_on_root_mesh = config.self_rank();
         let cast_rank = self.relative_rank(rank_on_root_mesh)?;
-        let cast_shape = self.shape();
+        let cast_shape = self.shape()?;
         let cast_point = cast_shape
             .extent()
             .point_of_rank(cast_rank)
@@ -117,7 +132,7 @@ impl CastMessageEnvelope {
         actor_mesh_id: ActorMeshId,
         sender: ActorAddr,
         shape: Shape,
-        headers: Flattrs,
+        mut headers: Flattrs,
         message: M,
     ) -> Result<Self, anyhow::Error>
     where
@@ -125,6 +140,7 @@ impl CastMessageEnvelope {
         M: Castable + RemoteMessage,
     {
         let actor_uid = actor_mesh_id.uid().clone();
+        migrate::set_version(&mut headers, migrate::local_version(M::typehash()));
         let data = ErasedUnbound::try_from_message(message)?;
         Ok(Self {
             actor_mesh_id,
@@ -133,7 +149,9 @@ impl CastMessageEnvelope {
             dest_port: DestinationPort::new::<A, M>(actor_uid),
             data,
             shape,
-        })
+            header_shape: OnceLock::new(),
+        }
+        .compact_shape())
     }
 
     /// Create a new CastMessageEnvelope from serialized data. Only use this
@@ -144,9 +162,10 @@ impl CastMessageEnvelope {
         sender: ActorAddr,
         dest_port: DestinationPort,
         shape: Shape,
-        headers: Flattrs,
+        mut headers: Flattrs,
         data: wirevalue::Any,
     ) -> Self {
+        migrate::set_version(&mut headers, migrate::local_version(data.typehash()));
         Self {
             actor_mesh_id,
             sender,
@@ -154,17 +173,56 @@ impl CastMessageEnvelope {
             dest_port,
             data: ErasedUnbound::new(data),
             shape,
+            header_shape: OnceLock::new(),
+        }
+        .compact_shape()
+    }
+
+    /// Carry the shape in the compact [`CAST_SHAPE`] header instead of
+    /// the envelope's body if headers are compact.
+    fn compact_shape(mut self) -> Self {
+        if compact_headers() {
+            let shape = std::mem::replace(&mut self.shape, Shape::unity());
+            self.headers.set(CAST_SHAPE, Compact(shape.clone()));
+            self.header_shape = OnceLock::from(shape);
         }
+        self
     }
 
-    pub(crate) fn shape(&self) -> &Shape {
-        &self.shape
+    /// Cast this message on the stream of `stream`, rather than on the
+    /// stream of the mesh to which it is delivered; see
+    /// [`CastMessageEnvelope::stream_key`].
+    pub(crate) fn with_stream(mut self, stream: ActorMeshId) -> Self {
+        self.actor_mesh_id = stream;
+        self
+    }
+
+    /// Mark the message as encoded at wire version `version` by the
+    /// sender's binary.
+    #[cfg(test)]
+    pub(crate) fn with_version(mut self, version: migrate::MessageVersion) -> Self {
+        migrate::set_version(&mut self.headers, version);
+        self
+    }
+
+    pub(crate) fn shape(&self) -> anyhow::Result<&Shape> {
+        if let Some(shape) = self.header_shape.get() {
+            return Ok(shape);
+        }
+        if !self.headers.contains_key(CAST_SHAPE) {
+            return Ok(&self.shape);
+        }
+        let shape = self
+            .headers
+            .get(CAST_SHAPE)
+            .ok_or_else(|| anyhow::anyhow!("malformed {} header", CAST_SHAPE.name()))?;
+        Ok(self.header_shape.get_or_init(|| shape.into_inner()))
     }
 
     /// Given a rank in the root shape, return the corresponding point in the
     /// provided shape, which is a view of the root shape.
     pub(crate) fn relative_rank(&self, rank_on_root_mesh: usize) -> anyhow::Result<usize> {
-        let shape = self.shape();
+        let shape = self.shape()?;
         let coords = shape.slice().coordinates(rank_on_root_mesh).map_err(|e| {
             anyhow::anyhow!(
                 "fail to calculate coords for root rank {} due to error: {}; shape is {:?}",
@@ -260,6 +318,9 @@ pub(crate) struct ForwardMessage {
     pub(crate) sender: ActorAddr,
     /// The destination of the message.
     pub(crate) dests: Vec<RoutingFrame>,
+    /// The rank at which the message was injected into the routing
+    /// tree. Sequence numbers are chained separately for each root.
+    pub(crate) root: usize,
     /// The sequence number of this message.
     pub(crate) seq: usize,
     /// The sequence number of the previous message receieved.
@@ -323,7 +384,7 @@ impl CastMessageV1 {
         sender: ActorAddr,
         dest_mesh: &ActorMeshId,
         dest_region: Region,
-        headers: Flattrs,
+        mut headers: Flattrs,
         message: M,
         session_id: Uuid,
         seqs: ValueMesh<u64>,
@@ -332,6 +393,7 @@ impl CastMessageV1 {
         A: Referable + RemoteHandles<IndexedErasedUnbound<M>>,
         M: Castable + RemoteMessage,
     {
+        migrate::set_version(&mut headers, migrate::local_version(M::typehash()));
         let data = ErasedUnbound::try_from_message(message)?;
         Ok(Self {
             headers,
@@ -356,12 +418,84 @@ pub(super) struct ForwardMessageV1 {
     pub(super) message: CastMessageV1,
 }
 
+// Compact key ids 64-127 are reserved for hyperactor_mesh's headers.
 declare_attrs! {
     /// Used inside headers to store the originating sender of a cast.
+    @meta(COMPACT_KEY = 64)
     pub attr CAST_ORIGINATING_SENDER: ActorAddr;
 
     /// The point in the casted region that this message was sent to.
+    /// Read with [`cast_point_of`], since it is carried in
+    /// [`CAST_POINT_COMPACT`] instead when headers are compact.
     pub attr CAST_POINT: Point;
+
+    /// [`CAST_POINT`], in its compact encoding, written instead of it
+    /// when headers are compact (see
+    /// [`hyperactor_config::flattrs::COMPACT_HEADERS`]).
+    @meta(COMPACT_KEY = 65)
+    pub attr CAST_POINT_COMPACT: Compact<Point>;
+
+    /// For casts to a part of a composite mesh (see
+    /// [`crate::ProcMeshRef::join`]), the point of the part in the
+    /// composite's leading dimensions. Points in the part are
+    /// delivered as the corresponding points of the composite.
+    @meta(COMPACT_KEY = 66)
+    pub attr CAST_COMPOSITE_PREFIX: Point;
+
+    /// The shape of a cast, in its compact encoding, carried in the
+    /// headers of a [`CastMessageEnvelope`] instead of its body when
+    /// headers are compact. It is removed before the cast is delivered.
+    @meta(COMPACT_KEY = 68)
+    pub attr CAST_SHAPE: Compact<Shape>;
+}
+
+/// The point in the casted region to which the message with `headers`
+/// was sent, in either encoding.
+pub fn cast_point_of(headers: &Flattrs) -> Option<Point> {
+    match headers.get(CAST_POINT_COMPACT) {
+        Some(point) => Some(point.into_inner()),
+        None => headers.get(CAST_POINT),
+    }
+}
+
+/// Set the point to which a message with `headers` is sent, in the
+/// compact encoding if headers are compact.
+pub fn set_cast_point(headers: &mut Flattrs, point: Point) {
+    if compact_headers() {
+        headers.set(CAST_POINT_COMPACT, Compact(point));
+    } else {
+        headers.set(CAST_POINT, point);
+    }
+}
+
+/// Address a cast with `headers` to the part of a composite mesh at
+/// `prefix`, nesting it within the part that the headers are already
+/// addressed to, if any.
+pub(crate) fn push_composite_prefix(headers: &mut Flattrs, prefix: Point) -> anyhow::Result<()> {
+    let prefix = match headers.get(CAST_COMPOSITE_PREFIX) {
+        Some(outer) => {
+            let extent = outer.extent().concat(prefix.extent())?;
+            let mut coords = outer.coords();
+            coords.extend(prefix.coords());
+            extent.point(coords)?
+        }
+        None => prefix,
+    };
+    headers.set(CAST_COMPOSITE_PREFIX, prefix);
+    Ok(())
+}
+
+/// The point at which a cast with `headers` is delivered to the
+/// actor at `point` of the cast region: the corresponding point of
+/// the composite mesh for casts to its parts, and `point` otherwise.
+pub(crate) fn composite_cast_point(headers: &Flattrs, point: Point) -> anyhow::Result<Point> {
+    let Some(prefix) = headers.get(CAST_COMPOSITE_PREFIX) else {
+        return Ok(point);
+    };
+    let extent = prefix.extent().concat(point.extent())?;
+    let mut coords = prefix.coords();
+    coords.extend(point.coords());
+    Ok(extent.point(coords)?)
 }
 
 pub fn set_cast_info_on_headers(headers: &mut Flattrs, cast_point: Point, sender: ActorAddr) {
@@ -373,7 +507,8 @@ pub fn set_cast_info_on_headers(headers: &mut Flattrs, cast_point: Point, sender
         hyperactor::mailbox::headers::SENDER_ACTOR_ID_HASH,
         hyperactor_telemetry::hash_to_u64(sender.id()),
     );
-    headers.set(CAST_POINT, cast_point);
+    headers.remove(CAST_SHAPE);
+    set_cast_point(headers, cast_point);
     headers.set(CAST_ORIGINATING_SENDER, sender);
 }
 
@@ -384,11 +519,42 @@ pub trait CastInfo {
     /// which is the same as a singleton.
     fn cast_point(&self) -> Point;
     fn sender(&self) -> ActorAddr;
+
+    /// The coordinates of this actor in the cast shape, one per
+    /// dimension.
+    fn coords(&self) -> Vec<usize> {
+        self.cast_point().coords()
+    }
+
+    /// The previous and next points along the named dimension of the
+    /// cast shape, wrapping around at its ends (ring neighbors). Returns
+    /// `None` if the cast shape has no such dimension. In a dimension of
+    /// size 1, both neighbors are this actor's own point.
+    fn neighbors(&self, dim: &str) -> Option<(Point, Point)> {
+        let point = self.cast_point();
+        let extent = point.extent();
+        let axis = extent.position(dim)?;
+        let size = extent.sizes()[axis];
+        let mut coords = point.coords();
+        let coord = coords[axis];
+        coords[axis] = (coord + size - 1) % size;
+        let prev = extent.point(coords.clone()).expect("coords in bounds");
+        coords[axis] = (coord + 1) % size;
+        let next = extent.point(coords).expect("coords in bounds");
+        Some((prev, next))
+    }
+
+    /// Whether this actor leads its group along the named dimension,
+    /// i.e. has coordinate 0 in it. Every actor leads a dimension that
+    /// is absent from the cast shape, as it is alone in it.
+    fn is_leader(&self, dim: &str) -> bool {
+        self.cast_point().coord_of(dim).unwrap_or(0) == 0
+    }
 }
 
 impl<A: Actor> CastInfo for Context<'_, A> {
     fn cast_point(&self) -> Point {
-        match self.headers().get(CAST_POINT) {
+        match cast_point_of(self.headers()) {
             Some(point) => point,
             None => Extent::unity().point_of_rank(0).unwrap(),
         }
@@ -400,3 +566,106 @@ impl<A: Actor> CastInfo for Context<'_, A> {
             .expect("has sender header")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use hyperactor::Label;
+    use hyperactor::testing::ids::test_actor_id;
+    use ndslice::extent;
+
+    use super::*;
+
+    struct TestCastInfo(Point, ActorAddr);
+
+    impl TestCastInfo {
+        fn new(point: Point) -> Self {
+            Self(point, test_actor_id("client", "sender"))
+        }
+    }
+
+    impl CastInfo for TestCastInfo {
+        fn cast_point(&self) -> Point {
+            self.0.clone()
+        }
+
+        fn sender(&self) -> ActorAddr {
+            self.1.clone()
+        }
+    }
+
+    #[test]
+    fn test_cast_info_helpers() {
+        let extent = extent!(host = 2, gpu = 4);
+        let info = TestCastInfo::new(extent.point(vec![1, 3]).unwrap());
+
+        assert_eq!(info.coords(), vec![1, 3]);
+        assert_eq!(info.sender(), test_actor_id("client", "sender"));
+        let (prev, next) = info.neighbors("gpu").unwrap();
+        assert_eq!(prev.coords(), vec![1, 2]);
+        assert_eq!(next.coords(), vec![1, 0]);
+        let (prev, next) = info.neighbors("host").unwrap();
+        assert_eq!(prev.coords(), vec![0, 3]);
+        assert_eq!(next.coords(), vec![0, 3]);
+        assert!(info.neighbors("zone").is_none());
+
+        assert!(!info.is_leader("host"));
+        assert!(!info.is_leader("gpu"));
+        assert!(info.is_leader("zone"));
+        assert!(TestCastInfo::new(extent.point(vec![1, 0]).unwrap()).is_leader("gpu"));
+
+        let singleton = TestCastInfo::new(Extent::unity().point_of_rank(0).unwrap());
+        assert!(singleton.coords().is_empty());
+        assert!(singleton.is_leader("gpu"));
+    }
+
+    #[test]
+    fn test_compact_cast_headers() {
+        let shape = Shape::new(
+            vec!["host".to_string(), "gpu".to_string()],
+            Slice::new_row_major(vec![2, 4]),
+        )
+        .unwrap();
+        let point = shape.extent().point(vec![1, 3]).unwrap();
+        let envelope = || {
+            CastMessageEnvelope::from_serialized(
+                ActorMeshId::singleton(Label::new("mesh").unwrap()),
+                test_actor_id("client", "sender"),
+                DestinationPort {
+                    actor_uid: Uid::singleton(Label::new("actor").unwrap()),
+                    port: 0,
+                },
+                shape.clone(),
+                Flattrs::new(),
+                wirevalue::Any::serialize(&0u64).unwrap(),
+            )
+        };
+        let sent = |envelope: &CastMessageEnvelope| {
+            wirevalue::Any::serialize(envelope)
+                .unwrap()
+                .deserialized::<CastMessageEnvelope>()
+                .unwrap()
+        };
+
+        let config = hyperactor_config::global::lock();
+        // Points written by peers that do not compact headers are read.
+        let mut headers = Flattrs::new();
+        headers.set(CAST_POINT, point.clone());
+        assert_eq!(cast_point_of(&headers), Some(point.clone()));
+        assert_eq!(sent(&envelope()).shape().unwrap(), &shape);
+        assert!(!envelope().headers().contains_key(CAST_SHAPE));
+
+        let _guard = config.override_key(hyperactor_config::flattrs::COMPACT_HEADERS, true);
+        let mut headers = Flattrs::new();
+        set_cast_point(&mut headers, point.clone());
+        assert!(!headers.contains_key(CAST_POINT));
+        assert_eq!(cast_point_of(&headers), Some(point.clone()));
+
+        // The shape is carried in the headers, and removed on delivery.
+        let envelope = sent(&envelope());
+        assert!(envelope.headers().contains_key(CAST_SHAPE));
+        assert_eq!(envelope.shape().unwrap(), &shape);
+        let mut headers = envelope.headers().clone();
+        set_cast_info_on_headers(&mut headers, point, test_actor_id("client", "sender"));
+        assert!(!headers.contains_key(CAST_SHAPE));
+    }
+}
@@ -111,7 +111,8 @@ declare_attrs! {
     pub attr MAX_CAST_DIMENSION_SIZE: usize = 16;
 
     /// Which builtin process launcher backend to use.
-    /// Accepted values: "native" (default), "systemd".
+    /// Accepted values: "native" (default), "systemd", "cgroup",
+    /// "container".
     /// Trimmed and lowercased before matching.
     ///
     /// **Precedence:** Python spawner (via SetProcSpawner) overrides this.
@@ -121,6 +122,70 @@ declare_attrs! {
     ))
     pub attr MESH_PROC_LAUNCHER_KIND: String = String::new();
 
+    /// The cgroup v2 directory under which the "cgroup" proc launcher
+    /// creates one cgroup per proc, e.g. `/sys/fs/cgroup/monarch`.
+    /// It must be delegated to the current user, and must not itself
+    /// contain processes.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CGROUP_ROOT".to_string()),
+        Some("cgroup_root".to_string()),
+    ))
+    pub attr MESH_CGROUP_ROOT: String = String::new();
+
+    /// The `cpu.max` limit applied to each proc by the "cgroup" proc
+    /// launcher, as `"$MAX $PERIOD"` in microseconds (e.g.
+    /// `"200000 100000"` for two CPUs). Empty means unlimited.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CGROUP_CPU_MAX".to_string()),
+        Some("cgroup_cpu_max".to_string()),
+    ))
+    pub attr MESH_CGROUP_CPU_MAX: String = String::new();
+
+    /// The `memory.max` limit applied to each proc by the "cgroup" proc
+    /// launcher, in bytes with an optional K/M/G suffix (e.g. `"8G"`).
+    /// Empty means unlimited.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CGROUP_MEMORY_MAX".to_string()),
+        Some("cgroup_memory_max".to_string()),
+    ))
+    pub attr MESH_CGROUP_MEMORY_MAX: String = String::new();
+
+    /// The container runtime used by the "container" proc launcher:
+    /// `docker`, `podman`, or another binary with a compatible CLI.
+    /// Empty means `docker`.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CONTAINER_RUNTIME".to_string()),
+        Some("container_runtime".to_string()),
+    ))
+    pub attr MESH_CONTAINER_RUNTIME: String = String::new();
+
+    /// The image in which the "container" proc launcher runs procs.
+    /// The image must provide the bootstrap command at the same path
+    /// as the host, e.g. within [`MESH_CONTAINER_WORKSPACE`].
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CONTAINER_IMAGE".to_string()),
+        Some("container_image".to_string()),
+    ))
+    pub attr MESH_CONTAINER_IMAGE: String = String::new();
+
+    /// A host directory mounted at the same path, and used as the
+    /// working directory, in each container started by the
+    /// "container" proc launcher; typically the code_sync workspace.
+    /// Empty means no workspace is mounted.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CONTAINER_WORKSPACE".to_string()),
+        Some("container_workspace".to_string()),
+    ))
+    pub attr MESH_CONTAINER_WORKSPACE: String = String::new();
+
+    /// Additional arguments to the container runtime's `run`
+    /// command, e.g. `["--gpus=all"]`, as a JSON list.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CONTAINER_ARGS".to_string()),
+        Some("container_args".to_string()),
+    ))
+    pub attr MESH_CONTAINER_ARGS: Vec<String> = Vec::new();
+
     /// Default socket address for the mesh admin HTTP server.
     ///
     /// Parsed as a `SocketAddr` (e.g. `[::]:1729`, `0.0.0.0:8080`).
@@ -132,6 +197,16 @@ declare_attrs! {
     ))
     pub attr MESH_ADMIN_ADDR: SocketAddrStr = SocketAddrStr::Static("[::]:1729");
 
+    /// Socket address on which each proc serves a JSON ingestion
+    /// endpoint (see [`crate::ingest`]), e.g. `[::]:0` for an
+    /// ephemeral port per proc. Empty (the default) disables the
+    /// endpoint.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_INGEST_ADDR".to_string()),
+        Some("mesh_ingest_addr".to_string()),
+    ))
+    pub attr MESH_INGEST_ADDR: String = String::new();
+
     /// Timeout for fallback queries to actors/procs that may have been
     /// recently destroyed. The second-chance paths in `resolve_proc_node`
     /// and `resolve_actor_node` fire after the fast QueryChild lookup
@@ -202,6 +277,42 @@ declare_attrs! {
     ))
     pub attr MESH_ADMIN_CONFIG_DUMP_BRIDGE_TIMEOUT: Duration = Duration::from_secs(5);
 
+    /// Timeout for the end-to-end `/v1/routing/{proc}` bridge reply.
+    /// Like the config-dump path, it forwards a `RoutingAuditDump`
+    /// message to the proc's agent and waits for `RoutingAuditResult`.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_ADMIN_ROUTING_AUDIT_BRIDGE_TIMEOUT".to_string()),
+        Some("mesh_admin_routing_audit_bridge_timeout".to_string()),
+    ))
+    pub attr MESH_ADMIN_ROUTING_AUDIT_BRIDGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Timeout for the end-to-end `/v1/reservations/{proc}` bridge
+    /// reply. It forwards a `ReservationsDump` message to the proc's
+    /// agent and waits for `ReservationsResult`.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_ADMIN_RESERVATIONS_BRIDGE_TIMEOUT".to_string()),
+        Some("mesh_admin_reservations_bridge_timeout".to_string()),
+    ))
+    pub attr MESH_ADMIN_RESERVATIONS_BRIDGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Timeout for the end-to-end `/v1/timing/{proc}` bridge reply. It
+    /// forwards a `TimingDump` message to the proc's agent and waits
+    /// for `TimingResult`.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_ADMIN_TIMING_BRIDGE_TIMEOUT".to_string()),
+        Some("mesh_admin_timing_bridge_timeout".to_string()),
+    ))
+    pub attr MESH_ADMIN_TIMING_BRIDGE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Timeout for the end-to-end `/v1/topology/{proc}` bridge reply.
+    /// It forwards a `TopologyDump` message to the proc's agent and
+    /// waits for `TopologyResult`.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_ADMIN_TOPOLOGY_BRIDGE_TIMEOUT".to_string()),
+        Some("mesh_admin_topology_bridge_timeout".to_string()),
+    ))
+    pub attr MESH_ADMIN_TOPOLOGY_BRIDGE_TIMEOUT: Duration = Duration::from_secs(5);
+
     /// Timeout for py-spy dump requests. See PS-5 in `introspect`
     /// module doc. With `--native --native-all`, py-spy unwinds native
     /// stacks via libunwind which is significantly slower than
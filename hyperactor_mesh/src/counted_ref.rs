@@ -0,0 +1,280 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Reference-counted actor references.
+//!
+//! A [`CountedRef`] is an [`ActorRef`] to an *ephemeral* actor: one
+//! that should live only as long as somebody holds a reference to it.
+//! An actor makes itself ephemeral with [`CountedRef::new`], which
+//! publishes [`EPHEMERAL`] on it; the proc agent ignores count updates
+//! for any other actor, so that no peer can stop an actor that did not
+//! opt in.
+//!
+//! Every live `CountedRef` to an actor, in any proc, holds one count on
+//! the actor. Creating, cloning, and serializing a `CountedRef` adds a
+//! count, and dropping one removes it; each change is posted as a
+//! [`RefCountUpdate`] to the [`ProcAgent`] of the actor's proc. When an
+//! actor's count drops to zero and stays there for
+//! [`REF_COUNT_REAP_GRACE`], the proc agent stops the actor. This gives
+//! dynamically spawned workers RAII-style lifetimes across the mesh.
+//!
+//! The count added by serializing a reference is held while it is in
+//! flight, and taken over by the reference deserialized from it at its
+//! destination. A serialized reference must therefore be deserialized
+//! exactly once, as when it is sent to a single actor: one that is
+//! never delivered keeps the actor alive. References obtained with
+//! [`CountedRef::downgrade`] are plain, *weak* `ActorRef`s, and do not
+//! keep the actor alive.
+//!
+//! [`ProcAgent`]: crate::proc_agent::ProcAgent
+
+use std::fmt;
+use std::ops::Deref;
+use std::time::Duration;
+
+use hyperactor::Actor;
+use hyperactor::ActorAddr;
+use hyperactor::ActorRef;
+use hyperactor::Bind;
+use hyperactor::Client;
+use hyperactor::Endpoint as _;
+use hyperactor::Instance;
+use hyperactor::Proc;
+use hyperactor::Unbind;
+use hyperactor::actor::Binds;
+use hyperactor::actor::Referable;
+use hyperactor_config::CONFIG;
+use hyperactor_config::ConfigAttr;
+use hyperactor_config::INTROSPECT;
+use hyperactor_config::IntrospectAttr;
+use hyperactor_config::attrs::declare_attrs;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use typeuri::Named;
+
+use crate::proc_agent::PROC_AGENT_ACTOR_NAME;
+use crate::proc_agent::ProcAgent;
+
+declare_attrs! {
+    /// How long an ephemeral actor's reference count must stay at zero
+    /// before its proc agent stops it. Updates posted from different
+    /// procs may arrive out of order, dropping the count to zero
+    /// transiently; the grace period absorbs these races.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_REF_COUNT_REAP_GRACE".to_string()),
+        Some("ref_count_reap_grace".to_string()),
+    ))
+    pub attr REF_COUNT_REAP_GRACE: Duration = Duration::from_secs(5);
+
+    /// Whether the actor is ephemeral: stopped by its proc agent once
+    /// the last [`CountedRef`] to it is dropped.
+    @meta(INTROSPECT = IntrospectAttr {
+        name: "ephemeral".into(),
+        desc: "Whether the actor is stopped once its last counted reference drops".into(),
+    })
+    pub attr EPHEMERAL: bool = false;
+}
+
+/// A change to the reference count of an ephemeral actor, posted to
+/// the [`ProcAgent`] of the actor's proc. Updates for actors that are
+/// not [`EPHEMERAL`] are ignored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named, Bind, Unbind)]
+pub struct RefCountUpdate {
+    /// The ephemeral actor.
+    pub actor: ActorAddr,
+    /// The change to its count: `1` for a new reference, `-1` for a
+    /// dropped one.
+    pub delta: i64,
+}
+wirevalue::register_type!(RefCountUpdate);
+
+/// A reference-counted [`ActorRef`] to an ephemeral actor, which is
+/// stopped once the last `CountedRef` to it is dropped, anywhere in
+/// the mesh. See the [module documentation](self).
+pub struct CountedRef<A: Referable> {
+    actor_ref: ActorRef<A>,
+    notifier: Client,
+}
+
+impl<A: Referable> CountedRef<A> {
+    /// Make the actor `this` ephemeral, and return the first counted
+    /// reference to it. Only an actor can make itself ephemeral. The
+    /// actor must be a root actor, as only root actors are stopped by
+    /// its proc agent.
+    pub fn new<T: Actor>(this: &Instance<T>) -> Self
+    where
+        A: Binds<T>,
+    {
+        this.publish_attr(EPHEMERAL, true);
+        Self::counted(notifier(this.proc()), this.bind())
+    }
+
+    fn counted(notifier: Client, actor_ref: ActorRef<A>) -> Self {
+        let this = Self::adopted(notifier, actor_ref);
+        this.update(1);
+        this
+    }
+
+    /// A reference that takes over a count already added on its
+    /// behalf.
+    fn adopted(notifier: Client, actor_ref: ActorRef<A>) -> Self {
+        Self {
+            actor_ref,
+            notifier,
+        }
+    }
+
+    /// A weak reference to the actor, which does not keep it alive.
+    pub fn downgrade(&self) -> ActorRef<A> {
+        self.actor_ref.clone()
+    }
+
+    fn update(&self, delta: i64) {
+        let actor = self.actor_ref.actor_addr().clone();
+        let agent =
+            ActorRef::<ProcAgent>::attest(actor.proc_addr().actor_addr(PROC_AGENT_ACTOR_NAME));
+        agent.post(&self.notifier, RefCountUpdate { actor, delta });
+    }
+}
+
+/// A client in `proc` that posts the count updates of a reference. It
+/// is shared by the reference's clones, so that the updates made
+/// through a reference and its clones are ordered.
+fn notifier(proc: &Proc) -> Client {
+    proc.client("counted_ref")
+}
+
+impl<A: Referable> Clone for CountedRef<A> {
+    fn clone(&self) -> Self {
+        Self::counted(self.notifier.clone(), self.actor_ref.clone())
+    }
+}
+
+impl<A: Referable> Drop for CountedRef<A> {
+    fn drop(&mut self) {
+        self.update(-1);
+    }
+}
+
+impl<A: Referable> Deref for CountedRef<A> {
+    type Target = ActorRef<A>;
+
+    fn deref(&self) -> &ActorRef<A> {
+        &self.actor_ref
+    }
+}
+
+impl<A: Referable> fmt::Debug for CountedRef<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CountedRef").field(&self.actor_ref).finish()
+    }
+}
+
+impl<A: Referable> Serialize for CountedRef<A> {
+    /// Serializing a reference adds a count, held while it is in
+    /// flight.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let serialized = self.actor_ref.serialize(serializer)?;
+        self.update(1);
+        Ok(serialized)
+    }
+}
+
+impl<'de, A: Referable> Deserialize<'de> for CountedRef<A> {
+    /// Deserializing a reference takes over the count added when it was
+    /// serialized, on behalf of the current proc: the proc of the actor
+    /// that receives it.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let actor_ref = ActorRef::deserialize(deserializer)?;
+        Ok(Self::adopted(notifier(&Proc::current()), actor_ref))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use hyperactor::Proc;
+    use hyperactor::actor::ActorStatus;
+    use hyperactor::channel::ChannelTransport;
+    use tokio::sync::oneshot;
+
+    use super::*;
+
+    #[derive(Debug)]
+    #[hyperactor::export(handlers = [])]
+    struct Worker {
+        counted: Option<oneshot::Sender<CountedRef<Worker>>>,
+    }
+
+    #[async_trait]
+    impl Actor for Worker {
+        async fn init(&mut self, this: &Instance<Self>) -> Result<(), anyhow::Error> {
+            // The worker makes itself ephemeral, and hands out the
+            // first reference to it.
+            if let Some(counted) = self.counted.take() {
+                let _ = counted.send(CountedRef::new(this));
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_counted_ref_reaps_actor() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(REF_COUNT_REAP_GRACE, Duration::from_millis(200));
+
+        let proc = Proc::direct(ChannelTransport::Unix.any(), "worker_proc".to_string()).unwrap();
+        let agent = ProcAgent::boot_v1(proc.clone(), None).unwrap();
+        agent
+            .status()
+            .wait_for(|status| matches!(status, ActorStatus::Idle))
+            .await
+            .unwrap();
+        let client_proc = Proc::direct(ChannelTransport::Unix.any(), "client".to_string()).unwrap();
+        let client = client_proc.client("client");
+
+        // Updates for an actor that did not opt in are ignored.
+        let bystander = proc.spawn_with_label("bystander", Worker { counted: None });
+        agent.bind::<ProcAgent>().post(
+            &client,
+            RefCountUpdate {
+                actor: bystander.actor_addr().clone(),
+                delta: -1,
+            },
+        );
+
+        let (tx, rx) = oneshot::channel();
+        let worker = proc.spawn_with_label("worker", Worker { counted: Some(tx) });
+        let mut status = worker.status();
+        let counted = rx.await.unwrap();
+
+        // A round trip through serialization yields another counted
+        // reference, which keeps the worker alive after the original
+        // is dropped.
+        let copy: CountedRef<Worker> =
+            serde_json::from_str(&serde_json::to_string(&counted).unwrap()).unwrap();
+        let clone = copy.clone();
+        drop(counted);
+        drop(copy);
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(!status.borrow().is_terminal());
+        assert!(!bystander.status().borrow().is_terminal());
+
+        // Dropping the last reference reaps the worker.
+        drop(clone);
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            status.wait_for(|status| status.is_terminal()),
+        )
+        .await
+        .expect("worker was not reaped")
+        .unwrap();
+    }
+}
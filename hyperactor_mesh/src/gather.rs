@@ -0,0 +1,399 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Gathering cast replies, optionally with a deadline.
+//!
+//! A gather port accumulates the [`ValueOverlay`] replies of the ranks
+//! of a cast into a [`ValueMesh`] over the cast's region, and records
+//! which ranks have replied. [`GatherReceiver::gather`]
+//! waits until every rank has replied or, if the call sets a deadline,
+//! until the deadline passes, and then delivers the mesh along with the
+//! ranks that are still missing, so that one straggler cannot stall the
+//! caller indefinitely.
+//!
+//! The typed cast clients generated by `#[derive(MeshClient)]` return a
+//! [`GatherHandle`] for the replies of the actors of a mesh, which is
+//! gathered with the same per-call [`GatherOpts`].
+
+use std::time::Duration;
+
+use hyperactor::Message;
+use hyperactor::PortHandle;
+use hyperactor::PortRef;
+use hyperactor::RemoteHandles;
+use hyperactor::RemoteMessage;
+use hyperactor::accum::Accumulator;
+use hyperactor::accum::ReducerSpec;
+use hyperactor::actor::Referable;
+use hyperactor::context;
+use hyperactor::mailbox::MailboxError;
+use hyperactor::mailbox::PortReceiver;
+use hyperactor::mailbox::open_port;
+use hyperactor::message::Castable;
+use ndslice::view::Ranked;
+use ndslice::view::Region;
+use typeuri::Named;
+
+use crate::ActorMeshRef;
+use crate::ValueMesh;
+use crate::value_mesh::ValueOverlay;
+
+/// Options for a single [`GatherReceiver::gather`] call.
+#[derive(Debug, Clone, Default)]
+pub struct GatherOpts {
+    /// How long to wait for replies before delivering the partial
+    /// result. When unspecified, the gather waits until every rank has
+    /// replied, or the port is closed.
+    pub deadline: Option<Duration>,
+}
+
+impl GatherOpts {
+    /// Options that deliver the partial result after `deadline`.
+    pub fn with_deadline(deadline: Duration) -> Self {
+        Self {
+            deadline: Some(deadline),
+        }
+    }
+}
+
+/// The result of a gather: the accumulated mesh, and the ranks that
+/// had not replied when it was delivered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gathered<T> {
+    /// The accumulated replies. Ranks that did not reply hold the
+    /// gather's seed value; see `missing`.
+    pub mesh: ValueMesh<T>,
+    /// The (linearized) ranks that did not reply, in ascending order.
+    pub missing: Vec<usize>,
+}
+
+impl<T> Gathered<T> {
+    /// Whether every rank replied.
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+/// The receiving end of a gather port; see [`open_gather_port`].
+pub struct GatherReceiver<T> {
+    rx: PortReceiver<GatherState<T>>,
+    template: GatherState<T>,
+}
+
+/// The replies gathered so far, and which ranks they came from.
+#[derive(Debug, Clone)]
+struct GatherState<T> {
+    mesh: ValueMesh<T>,
+    replied: Vec<bool>,
+}
+
+impl<T> Default for GatherState<T> {
+    fn default() -> Self {
+        Self {
+            mesh: ValueMesh::default(),
+            replied: Vec::new(),
+        }
+    }
+}
+
+impl<T> GatherState<T> {
+    /// The ranks that have not replied, in ascending order.
+    fn missing(&self) -> Vec<usize> {
+        (0..self.replied.len())
+            .filter(|&rank| !self.replied[rank])
+            .collect()
+    }
+}
+
+/// Accumulates [`ValueOverlay`] replies into a [`GatherState`]. Replies
+/// are reduced on their way back as for a [`ValueMesh`] port, which
+/// keeps the ranks that each overlay covers.
+struct GatherAccumulator<T> {
+    template: GatherState<T>,
+}
+
+impl<T> Accumulator for GatherAccumulator<T>
+where
+    T: Eq + Clone + Named,
+{
+    type State = GatherState<T>;
+    type Update = ValueOverlay<T>;
+
+    fn accumulate(&self, state: &mut Self::State, update: Self::Update) -> anyhow::Result<()> {
+        // The mailbox starts with an empty state.
+        if state.replied.len() != self.template.replied.len() {
+            *state = self.template.clone();
+        }
+        let ranks: Vec<_> = update.runs().map(|(ranks, _)| ranks.clone()).collect();
+        state.mesh.merge_from_overlay(update)?;
+        for ranks in ranks {
+            state.replied[ranks].fill(true);
+        }
+        Ok(())
+    }
+
+    fn reducer_spec(&self) -> Option<ReducerSpec> {
+        self.template.mesh.reducer_spec()
+    }
+}
+
+/// Open a port that gathers replies from the ranks of `region` into a
+/// [`ValueMesh`]. Ranks that have not replied hold `seed`. Bind the
+/// returned handle into the cast message, and have each rank reply
+/// with a [`ValueOverlay`] covering its own rank.
+pub fn open_gather_port<T>(
+    cx: &impl context::Actor,
+    region: Region,
+    seed: T,
+) -> (PortHandle<ValueOverlay<T>>, GatherReceiver<T>)
+where
+    T: Eq + Clone + Named + Send + Sync + 'static,
+{
+    let template = GatherState {
+        replied: vec![false; region.num_ranks()],
+        mesh: ValueMesh::from_single(region, seed),
+    };
+    let (port, rx) = cx.mailbox().open_accum_port(GatherAccumulator {
+        template: template.clone(),
+    });
+    (port, GatherReceiver { rx, template })
+}
+
+impl<T: Clone + 'static> GatherReceiver<T> {
+    /// Wait for every rank to reply, or for the deadline in `opts` to
+    /// pass, whichever is first, and return the replies gathered so
+    /// far along with the ranks still missing. A closed port ends the
+    /// gather early in the same way.
+    pub async fn gather(mut self, opts: GatherOpts) -> Gathered<T> {
+        let deadline = opts
+            .deadline
+            .map(|deadline| tokio::time::Instant::now() + deadline);
+        let mut state = self.template;
+        loop {
+            let missing = state.missing();
+            if missing.is_empty() {
+                return Gathered {
+                    mesh: state.mesh,
+                    missing,
+                };
+            }
+            let next = match deadline {
+                Some(deadline) => match tokio::time::timeout_at(deadline, self.rx.recv()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        tracing::warn!(
+                            missing = missing.len(),
+                            ranks = state.replied.len(),
+                            "gather deadline passed; delivering partial result"
+                        );
+                        return Gathered {
+                            mesh: state.mesh,
+                            missing,
+                        };
+                    }
+                },
+                None => self.rx.recv().await,
+            };
+            match next {
+                Ok(next) => state = next,
+                Err(err) => {
+                    tracing::warn!(
+                        missing = missing.len(),
+                        error = %err,
+                        "gather port closed; delivering partial result"
+                    );
+                    return Gathered {
+                        mesh: state.mesh,
+                        missing,
+                    };
+                }
+            }
+        }
+    }
+}
+
+/// The replies to a message sent to every actor of a mesh, returned by
+/// the methods that `#[derive(MeshClient)]` generates. Each actor is
+/// sent its own reply port, so that replies are attributed to the
+/// (linearized) ranks that sent them.
+pub struct GatherHandle<R> {
+    receivers: Vec<(usize, PortReceiver<R>)>,
+    expected: usize,
+}
+
+/// The replies gathered by [`GatherHandle::gather`], and the ranks that
+/// had not replied when they were delivered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GatheredReplies<R> {
+    /// The replies, with the ranks that sent them, in the order in
+    /// which they were received.
+    pub replies: Vec<(usize, R)>,
+    /// The ranks that did not reply, in ascending order.
+    pub missing: Vec<usize>,
+}
+
+impl<R> GatheredReplies<R> {
+    /// Whether every rank replied.
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+impl<R: RemoteMessage> GatherHandle<R> {
+    /// Send a message to every actor of `mesh`, made by `message` from
+    /// a reply port opened for the actor's rank, and return a handle to
+    /// the replies.
+    #[allow(clippy::result_large_err)]
+    pub fn cast<A, M>(
+        mesh: &ActorMeshRef<A>,
+        cx: &impl context::Actor,
+        mut message: impl FnMut(PortRef<R>) -> M,
+    ) -> crate::Result<Self>
+    where
+        A: Referable + RemoteHandles<M>,
+        M: Castable + RemoteMessage,
+    {
+        let expected = Ranked::region(mesh).num_ranks();
+        let mut receivers = Vec::with_capacity(expected);
+        for rank in 0..expected {
+            let (port, rx) = open_port::<R>(cx);
+            mesh.cast_rank(cx, rank, message(port.bind()))?;
+            receivers.push((rank, rx));
+        }
+        Ok(Self {
+            receivers,
+            expected,
+        })
+    }
+}
+
+impl<R: Message> GatherHandle<R> {
+    /// The number of replies expected: one per rank of the mesh.
+    pub fn expected(&self) -> usize {
+        self.expected
+    }
+
+    /// Receive the next reply, with the rank that sent it, or `None`
+    /// once every rank has replied. Each rank replies once: its port is
+    /// closed once its reply, or an error, is received.
+    pub async fn recv(&mut self) -> Option<(usize, Result<R, MailboxError>)> {
+        if self.receivers.is_empty() {
+            return None;
+        }
+        let pending = self.receivers.iter_mut().map(|(rank, rx)| {
+            let rank = *rank;
+            Box::pin(async move { (rank, rx.recv().await) })
+        });
+        let (next, index, _) = futures::future::select_all(pending).await;
+        self.receivers.swap_remove(index);
+        Some(next)
+    }
+
+    /// Wait for a reply from every rank or, if `opts` sets a deadline,
+    /// until the deadline passes, and return the replies received along
+    /// with the ranks still missing. A rank whose port is closed before
+    /// it replies is missing.
+    pub async fn gather(mut self, opts: GatherOpts) -> GatheredReplies<R> {
+        let deadline = opts
+            .deadline
+            .map(|deadline| tokio::time::Instant::now() + deadline);
+        let mut replies = Vec::with_capacity(self.expected);
+        let mut missing = Vec::new();
+        loop {
+            let next = match deadline {
+                Some(deadline) => match tokio::time::timeout_at(deadline, self.recv()).await {
+                    Ok(next) => next,
+                    Err(_) => break,
+                },
+                None => self.recv().await,
+            };
+            match next {
+                Some((rank, Ok(reply))) => replies.push((rank, reply)),
+                Some((rank, Err(err))) => {
+                    tracing::warn!(rank, error = %err, "gather port closed before the rank replied");
+                    missing.push(rank);
+                }
+                None => break,
+            }
+        }
+        missing.extend(self.receivers.iter().map(|(rank, _)| *rank));
+        missing.sort();
+        if !missing.is_empty() {
+            tracing::warn!(
+                missing = missing.len(),
+                expected = self.expected,
+                "gather ended before every rank replied"
+            );
+        }
+        GatheredReplies { replies, missing }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor::Endpoint as _;
+    use hyperactor::Proc;
+    use ndslice::ViewExt as _;
+    use ndslice::extent;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_gather_deadline() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let region: Region = extent!(replica = 4).into();
+
+        // Every rank replies: the gather completes before its deadline.
+        let (port, rx) = open_gather_port(&client, region.clone(), 0u64);
+        for rank in 0..4 {
+            port.post(
+                &client,
+                ValueOverlay::try_from_runs(vec![(rank..rank + 1, rank as u64 + 1)]).unwrap(),
+            );
+        }
+        let gathered = rx
+            .gather(GatherOpts::with_deadline(Duration::from_secs(60)))
+            .await;
+        assert!(gathered.is_complete());
+        assert_eq!(gathered.mesh.values().collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+
+        // Ranks 1 and 3 straggle: the partial result is delivered at
+        // the deadline, reporting them as missing.
+        let (port, rx) = open_gather_port(&client, region, 0u64);
+        port.post(
+            &client,
+            ValueOverlay::try_from_runs(vec![(0..1, 7), (2..3, 9)]).unwrap(),
+        );
+        let gathered = rx
+            .gather(GatherOpts::with_deadline(Duration::from_millis(200)))
+            .await;
+        assert!(!gathered.is_complete());
+        assert_eq!(gathered.missing, vec![1, 3]);
+        assert_eq!(gathered.mesh.values().collect::<Vec<_>>(), vec![7, 0, 9, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_gather_reply_equal_to_seed() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let region: Region = extent!(replica = 2).into();
+
+        // A rank that replies with the seed value has replied.
+        let (port, rx) = open_gather_port(&client, region, 0u64);
+        port.post(
+            &client,
+            ValueOverlay::try_from_runs(vec![(0..1, 0), (1..2, 5)]).unwrap(),
+        );
+        let gathered = rx
+            .gather(GatherOpts::with_deadline(Duration::from_secs(60)))
+            .await;
+        assert!(gathered.is_complete());
+        assert_eq!(gathered.mesh.values().collect::<Vec<_>>(), vec![0, 5]);
+    }
+}
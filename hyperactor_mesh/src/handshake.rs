@@ -0,0 +1,484 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Bootstrap handshake between a host and the procs it spawns.
+//!
+//! When a process is bootstrapped, its parent's [`Hello`] is carried
+//! in the bootstrap payload, in every bootstrap mode, and the child
+//! checks it with [`accept_parent`]. Procs also send their own hello
+//! back to the spawning host, on a dedicated channel ahead of their
+//! readiness callback, and the host checks it with [`accept_child`].
+//! Both fields are optional on the wire, so that processes predating
+//! the handshake still interoperate: a missing hello skips the
+//! corresponding check.
+//!
+//! Both checks use [`negotiate`], which:
+//!
+//! - refuses peers speaking a different [`PROTOCOL_VERSION`],
+//!   sharing no message encoding, or disagreeing on the layout of a
+//!   message type (see [`wirevalue::schema`]);
+//! - refuses peers built from a different version only if
+//!   [`MESH_HANDSHAKE_STRICT_VERSION`] is set, and warns otherwise;
+//! - agrees on the encodings, transports and capabilities supported
+//!   by both sides, and on a default encoding that both can decode;
+//! - estimates the clock offset of the peer.
+//!
+//! A child whose default encoding its parent cannot decode switches
+//! to the agreed one; a parent refuses a child that cannot decode the
+//! parent's default encoding. Mismatches thus fail at bootstrap, with
+//! an explicit error, rather than later with opaque deserialization
+//! errors.
+
+use std::collections::BTreeSet;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use hyperactor::channel::ChannelTransport;
+use hyperactor_config::CONFIG;
+use hyperactor_config::ConfigAttr;
+use hyperactor_config::attrs::Attrs;
+use hyperactor_config::attrs::declare_attrs;
+use serde::Deserialize;
+use serde::Serialize;
+use strum::IntoEnumIterator as _;
+use typeuri::Named;
+use wirevalue::Encoding;
+use wirevalue::config::DEFAULT_ENCODING;
+use wirevalue::schema::SchemaDigest;
+use wirevalue::schema::SchemaMismatch;
+
+/// The version of the mesh bootstrap protocol. Peers with different
+/// protocol versions cannot interoperate.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+declare_attrs! {
+    /// Capabilities declared by this process in bootstrap handshakes.
+    /// Only capabilities declared by both sides are agreed upon.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CAPABILITIES".to_string()),
+        Some("mesh_capabilities".to_string()),
+    ))
+    pub attr MESH_CAPABILITIES: Vec<String> = Vec::new();
+
+    /// Whether to refuse peers built from a different version, rather
+    /// than warn about them.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_HANDSHAKE_STRICT_VERSION".to_string()),
+        Some("mesh_handshake_strict_version".to_string()),
+    ))
+    pub attr MESH_HANDSHAKE_STRICT_VERSION: bool = false;
+
+    /// The estimated clock offset beyond which a peer's clock is
+    /// reported as skewed.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_HANDSHAKE_MAX_CLOCK_SKEW".to_string()),
+        Some("mesh_handshake_max_clock_skew".to_string()),
+    ))
+    pub attr MESH_HANDSHAKE_MAX_CLOCK_SKEW: Duration = Duration::from_secs(5);
+}
+
+/// Errors refusing a peer in a bootstrap handshake.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum HandshakeError {
+    /// The peer speaks a different protocol version.
+    #[error("peer speaks mesh protocol version {remote}, expected {local}")]
+    ProtocolMismatch { local: u32, remote: u32 },
+
+    /// The peer was built from a different version, and versions are
+    /// required to match.
+    #[error("peer is running version {remote}, expected {local}")]
+    VersionMismatch { local: String, remote: String },
+
+    /// The peer shares no message encoding with this process.
+    #[error("peer supports none of the encodings {local:?} (it supports {remote:?})")]
+    NoCommonEncoding {
+        local: Vec<Encoding>,
+        remote: Vec<Encoding>,
+    },
+
+    /// The peer cannot decode the messages this process sends in its
+    /// default encoding.
+    #[error("peer cannot decode the default encoding {encoding} (it supports {remote:?})")]
+    UndecodableEncoding {
+        encoding: Encoding,
+        remote: Vec<Encoding>,
+    },
+
+    /// The peer disagrees on the layout of message types known to
+    /// both sides.
+    #[error(transparent)]
+    SchemaMismatch(#[from] SchemaMismatch),
+}
+
+/// One side of a bootstrap handshake.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named)]
+pub struct Hello {
+    /// The mesh protocol version; see [`PROTOCOL_VERSION`].
+    pub protocol: u32,
+    /// The version of `hyperactor_mesh` the process was built from.
+    pub version: String,
+    /// The message encodings the process can decode.
+    pub encodings: Vec<Encoding>,
+    /// The encoding in which the process sends messages by default;
+    /// see [`DEFAULT_ENCODING`]. Absent from processes predating it.
+    #[serde(default)]
+    pub default_encoding: Option<Encoding>,
+    /// The channel transports the process supports.
+    pub transports: Vec<ChannelTransport>,
+    /// The capabilities declared by the process; see
+    /// [`MESH_CAPABILITIES`].
+    pub capabilities: BTreeSet<String>,
+    /// When the hello was created, by the process's clock.
+    pub sent_at: SystemTime,
+    /// The schemas of the message types registered in the process.
+    /// Empty unless attached with [`Hello::with_schemas`]; an empty
+    /// digest is compatible with any other.
+    #[serde(default)]
+    pub schemas: SchemaDigest,
+}
+wirevalue::register_type!(Hello);
+
+impl Hello {
+    /// The hello of the current process.
+    pub fn local() -> Self {
+        Self {
+            protocol: PROTOCOL_VERSION,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            encodings: Encoding::iter().collect(),
+            default_encoding: Some(hyperactor_config::global::get(DEFAULT_ENCODING)),
+            transports: ChannelTransport::all().into(),
+            capabilities: hyperactor_config::global::get_cloned(MESH_CAPABILITIES)
+                .into_iter()
+                .collect(),
+            sent_at: SystemTime::now(),
+            schemas: SchemaDigest::default(),
+        }
+    }
+
+    /// Attach the digest of the schemas registered in this process.
+    /// The digest covers every registered type, so it is sent only
+    /// over channels, not in the size-limited bootstrap payload.
+    pub fn with_schemas(mut self) -> Self {
+        self.schemas = SchemaDigest::local();
+        self
+    }
+}
+
+/// The outcome of a successful bootstrap handshake.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Agreement {
+    /// Whether the peer was built from the same version.
+    pub same_version: bool,
+    /// The message encodings supported by both sides.
+    pub encodings: Vec<Encoding>,
+    /// The default encoding to send messages in: the local default
+    /// encoding if the peer can decode it, else the peer's if the
+    /// local process can decode it, else the first encoding supported
+    /// by both sides.
+    pub default_encoding: Encoding,
+    /// The channel transports supported by both sides.
+    pub transports: Vec<ChannelTransport>,
+    /// The capabilities declared by both sides.
+    pub capabilities: BTreeSet<String>,
+    /// The estimated offset of the peer's clock, in microseconds:
+    /// positive if it is ahead of the local clock. This is a one-way
+    /// estimate, which also includes the hello's transit time.
+    pub clock_offset_us: i64,
+}
+
+impl Agreement {
+    /// Whether the peer's clock offset exceeds
+    /// [`MESH_HANDSHAKE_MAX_CLOCK_SKEW`].
+    pub fn clock_skewed(&self) -> bool {
+        let max = hyperactor_config::global::get(MESH_HANDSHAKE_MAX_CLOCK_SKEW);
+        self.clock_offset_us.unsigned_abs() > max.as_micros() as u64
+    }
+}
+
+/// Negotiate a handshake between the `local` hello and the `remote`
+/// hello, received at `received_at` by the local clock.
+pub fn negotiate(
+    local: &Hello,
+    remote: &Hello,
+    received_at: SystemTime,
+) -> Result<Agreement, HandshakeError> {
+    if local.protocol != remote.protocol {
+        return Err(HandshakeError::ProtocolMismatch {
+            local: local.protocol,
+            remote: remote.protocol,
+        });
+    }
+
+    let same_version = local.version == remote.version;
+    if !same_version {
+        if hyperactor_config::global::get(MESH_HANDSHAKE_STRICT_VERSION) {
+            return Err(HandshakeError::VersionMismatch {
+                local: local.version.clone(),
+                remote: remote.version.clone(),
+            });
+        }
+        tracing::warn!(
+            local = local.version,
+            remote = remote.version,
+            "bootstrap handshake: peer is running a different version",
+        );
+    }
+
+    let encodings: Vec<Encoding> = local
+        .encodings
+        .iter()
+        .filter(|encoding| remote.encodings.contains(encoding))
+        .copied()
+        .collect();
+    if encodings.is_empty() {
+        return Err(HandshakeError::NoCommonEncoding {
+            local: local.encodings.clone(),
+            remote: remote.encodings.clone(),
+        });
+    }
+
+    let default_encoding = [local.default_encoding, remote.default_encoding]
+        .into_iter()
+        .flatten()
+        .find(|encoding| encodings.contains(encoding))
+        .unwrap_or(encodings[0]);
+
+    local.schemas.check(&remote.schemas)?;
+
+    let clock_offset_us = match remote.sent_at.duration_since(received_at) {
+        Ok(ahead) => ahead.as_micros() as i64,
+        Err(behind) => -(behind.duration().as_micros() as i64),
+    };
+
+    let agreement = Agreement {
+        same_version,
+        encodings,
+        default_encoding,
+        transports: local
+            .transports
+            .iter()
+            .filter(|transport| remote.transports.contains(transport))
+            .cloned()
+            .collect(),
+        capabilities: local
+            .capabilities
+            .intersection(&remote.capabilities)
+            .cloned()
+            .collect(),
+        clock_offset_us,
+    };
+    if agreement.clock_skewed() {
+        tracing::warn!(clock_offset_us, "bootstrap handshake: peer clock is skewed",);
+    }
+    Ok(agreement)
+}
+
+/// Check the hello of the parent that spawned this process, before
+/// installing the parent's `config` snapshot. If the parent cannot
+/// decode the default encoding this process would use, the agreed
+/// default encoding is set in `config`.
+pub fn accept_parent(
+    parent: &Hello,
+    config: &mut Option<Attrs>,
+) -> Result<Agreement, HandshakeError> {
+    let mut local = Hello::local();
+    if let Some(encoding) = config
+        .as_ref()
+        .and_then(|attrs| attrs.get(DEFAULT_ENCODING))
+    {
+        local.default_encoding = Some(*encoding);
+    }
+    let agreement = negotiate(&local, parent, SystemTime::now())?;
+    if local.default_encoding != Some(agreement.default_encoding) {
+        tracing::warn!(
+            local = ?local.default_encoding,
+            agreed = %agreement.default_encoding,
+            "bootstrap handshake: parent cannot decode the default encoding; switching",
+        );
+        config
+            .get_or_insert_with(Attrs::new)
+            .set(DEFAULT_ENCODING, agreement.default_encoding);
+    }
+    Ok(agreement)
+}
+
+/// Check the hello of a child process spawned by this process,
+/// refusing children that cannot decode the messages this process
+/// sends them.
+pub fn accept_child(child: &Hello) -> Result<Agreement, HandshakeError> {
+    let local = Hello::local().with_schemas();
+    let agreement = negotiate(&local, child, SystemTime::now())?;
+    if let Some(encoding) = local.default_encoding
+        && encoding != agreement.default_encoding
+    {
+        return Err(HandshakeError::UndecodableEncoding {
+            encoding,
+            remote: child.encodings.clone(),
+        });
+    }
+    Ok(agreement)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod v0 {
+        use serde::Deserialize;
+        use serde::Serialize;
+
+        /// An older layout of [`super::Hello`].
+        #[derive(typeuri::Named, Serialize, Deserialize)]
+        #[named(name = "hyperactor_mesh::handshake::Hello")]
+        pub struct Hello {
+            pub protocol: u32,
+        }
+    }
+
+    fn hello() -> Hello {
+        Hello {
+            capabilities: ["rdma", "gpu"].into_iter().map(String::from).collect(),
+            ..Hello::local()
+        }
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let local = hello();
+        let mut remote = hello();
+        remote.encodings = vec![Encoding::Multipart];
+        remote.default_encoding = Some(Encoding::Multipart);
+        remote.transports = vec![ChannelTransport::Unix];
+        remote.capabilities = ["gpu", "tls"].into_iter().map(String::from).collect();
+        remote.sent_at = local.sent_at + Duration::from_millis(10);
+
+        let agreement = negotiate(&local, &remote, local.sent_at).unwrap();
+        assert!(agreement.same_version);
+        assert_eq!(agreement.encodings, vec![Encoding::Multipart]);
+        assert_eq!(agreement.default_encoding, Encoding::Multipart);
+        assert_eq!(agreement.transports, vec![ChannelTransport::Unix]);
+        assert_eq!(
+            agreement.capabilities,
+            ["gpu"].into_iter().map(String::from).collect()
+        );
+        assert_eq!(agreement.clock_offset_us, 10_000);
+        assert!(!agreement.clock_skewed());
+
+        let agreement =
+            negotiate(&local, &remote, remote.sent_at + Duration::from_secs(60)).unwrap();
+        assert_eq!(agreement.clock_offset_us, -60_000_000);
+        assert!(agreement.clock_skewed());
+    }
+
+    #[test]
+    fn test_negotiate_mismatch() {
+        let local = hello();
+
+        let mut remote = hello();
+        remote.protocol = PROTOCOL_VERSION + 1;
+        assert_eq!(
+            negotiate(&local, &remote, SystemTime::now()),
+            Err(HandshakeError::ProtocolMismatch {
+                local: PROTOCOL_VERSION,
+                remote: PROTOCOL_VERSION + 1,
+            })
+        );
+
+        let mut remote = hello();
+        remote.encodings = vec![];
+        assert!(matches!(
+            negotiate(&local, &remote, SystemTime::now()),
+            Err(HandshakeError::NoCommonEncoding { .. })
+        ));
+
+        // The schemas of the types known to both sides must agree.
+        let local = hello().with_schemas();
+        assert!(!local.schemas.is_empty());
+        assert!(negotiate(&local, &hello().with_schemas(), SystemTime::now()).is_ok());
+        assert!(negotiate(&local, &hello(), SystemTime::now()).is_ok());
+        let mut remote = hello();
+        remote
+            .schemas
+            .insert(&wirevalue::schema::Schema::of::<v0::Hello>());
+        assert!(matches!(
+            negotiate(&local, &remote, SystemTime::now()),
+            Err(HandshakeError::SchemaMismatch(_))
+        ));
+        let local = hello();
+
+        // Version mismatches are tolerated unless strict.
+        let mut remote = hello();
+        remote.version = "0.0.0-other".to_string();
+        assert!(
+            !negotiate(&local, &remote, SystemTime::now())
+                .unwrap()
+                .same_version
+        );
+
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(MESH_HANDSHAKE_STRICT_VERSION, true);
+        assert!(matches!(
+            negotiate(&local, &remote, SystemTime::now()),
+            Err(HandshakeError::VersionMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_default_encoding() {
+        let mut local = hello();
+        local.default_encoding = Some(Encoding::Bincode);
+
+        // The local default encoding is kept if the peer can decode it.
+        let agreement = negotiate(&local, &hello(), SystemTime::now()).unwrap();
+        assert_eq!(agreement.default_encoding, Encoding::Bincode);
+
+        // Otherwise the peer's is used, if the local process can
+        // decode it.
+        let mut remote = hello();
+        remote.encodings = vec![Encoding::Json, Encoding::Cbor];
+        remote.default_encoding = Some(Encoding::Cbor);
+        let agreement = negotiate(&local, &remote, SystemTime::now()).unwrap();
+        assert_eq!(agreement.default_encoding, Encoding::Cbor);
+
+        // Peers predating default encodings get a common one.
+        remote.default_encoding = None;
+        let agreement = negotiate(&local, &remote, SystemTime::now()).unwrap();
+        assert_eq!(agreement.default_encoding, Encoding::Json);
+    }
+
+    #[test]
+    fn test_accept_parent_and_child() {
+        let default = hyperactor_config::global::get(DEFAULT_ENCODING);
+        let other = Encoding::iter()
+            .find(|encoding| *encoding != default)
+            .unwrap();
+
+        // A compatible parent leaves the config alone.
+        let mut config = None;
+        accept_parent(&Hello::local(), &mut config).unwrap();
+        assert!(config.is_none());
+
+        // A parent that cannot decode the default encoding switches the
+        // child to one it can.
+        let mut parent = Hello::local();
+        parent.encodings = vec![other];
+        parent.default_encoding = Some(other);
+        let mut config = None;
+        accept_parent(&parent, &mut config).unwrap();
+        assert_eq!(config.unwrap().get(DEFAULT_ENCODING), Some(&other));
+
+        // A child that cannot decode the default encoding is refused.
+        let mut child = Hello::local();
+        assert!(accept_child(&child).is_ok());
+        child.encodings = vec![other];
+        assert!(matches!(
+            accept_child(&child),
+            Err(HandshakeError::UndecodableEncoding { encoding, .. }) if encoding == default
+        ));
+    }
+}
@@ -72,9 +72,15 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use hyperactor::ActorAddr;
+use hyperactor::ControlPort;
+use hyperactor::PortRef;
 use hyperactor::ProcAddr;
 use hyperactor::channel::ChannelAddr;
 use hyperactor::context;
+use hyperactor::introspect::IntrospectMessage;
+use hyperactor::introspect::IntrospectResult;
+use hyperactor::introspect::IntrospectView;
+use hyperactor::mailbox::open_once_port;
 use ndslice::Extent;
 use ndslice::Region;
 use ndslice::ViewExt;
@@ -93,6 +99,7 @@ use crate::ValueMesh;
 use crate::bootstrap::BootstrapCommand;
 use crate::bootstrap::BootstrapProcManager;
 use crate::bootstrap::ProcBind;
+use crate::handshake::Hello;
 use crate::host::Host;
 use crate::host::LocalProcManager;
 use crate::host::SERVICE_PROC_NAME;
@@ -106,6 +113,8 @@ use crate::mesh_controller::ProcMeshController;
 use crate::mesh_id::HostMeshId;
 use crate::mesh_id::ProcMeshId;
 use crate::mesh_id::ResourceId;
+use crate::placement::GetGpuTopologyClient;
+use crate::placement::PlacementPolicy;
 use crate::proc_agent::ProcAgent;
 use crate::proc_mesh::ProcMeshRef;
 use crate::resource;
@@ -144,6 +153,14 @@ declare_attrs! {
         Some("get_proc_state_max_idle".to_string()),
     ))
     pub attr GET_PROC_STATE_MAX_IDLE: Duration = Duration::from_mins(1);
+
+    /// The maximum time to wait for each host's GPU topology when
+    /// spawning proc meshes with [`PlacementPolicy::GpuTopology`].
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_GPU_TOPOLOGY_TIMEOUT".to_string()),
+        Some("gpu_topology_timeout".to_string()),
+    ))
+    pub attr GPU_TOPOLOGY_TIMEOUT: Duration = Duration::from_secs(30);
 }
 
 /// A reference to a single host.
@@ -602,6 +619,7 @@ impl HostMesh {
                 command: Some(command.clone()),
                 config: Some(hyperactor_config::global::attrs()),
                 exit_on_shutdown: false,
+                hello: Some(Hello::local()),
             };
 
             let mut cmd = command.new();
@@ -1154,10 +1172,10 @@ impl HostMeshRef {
     /// Spawn a ProcMesh onto this host mesh. The per_host extent specifies the shape
     /// of the procs to spawn on each host.
     ///
-    /// `proc_bind`, when provided, is a per-process CPU/NUMA binding
+    /// `proc_bind`, when provided, is a per-process CPU/NUMA/GPU binding
     /// configuration. Its length must equal the number of ranks in
     /// `per_host`. Each entry maps binding keys (`cpunodebind`,
-    /// `membind`, `physcpubind`, `cpus`) to their values.
+    /// `membind`, `physcpubind`, `cpus`, `gpus`) to their values.
     /// Only takes effect when running on Linux.
     ///
     /// `per_rank_bootstrap`, when provided, is a function called once
@@ -1180,6 +1198,54 @@ impl HostMeshRef {
     where
         C::A: Handler<MeshFailure>,
     {
+        if let Some(proc_bind) = proc_bind.as_ref()
+            && proc_bind.len() != per_host.num_ranks()
+        {
+            return Err(crate::Error::ConfigurationError(anyhow::anyhow!(
+                "proc_bind length does not match per_host extent"
+            )));
+        }
+        // The same binding applies to the procs of every host.
+        let proc_bind = proc_bind.map(|proc_bind| {
+            (0..self.region.num_ranks())
+                .flat_map(|_| proc_bind.iter().cloned())
+                .collect()
+        });
+        self.spawn_inner(
+            cx,
+            ProcMeshId::instance(Label::strip(name)),
+            per_host,
+            proc_bind,
+            per_rank_bootstrap,
+        )
+        .await
+    }
+
+    /// Spawn a ProcMesh onto this host mesh, placing the procs of each
+    /// host according to `placement`. See [`crate::placement`].
+    ///
+    /// With [`PlacementPolicy::GpuTopology`], the GPU topology of every
+    /// host is queried, and each proc is bound to one of its host's
+    /// GPUs: spawning fails if a host has fewer GPUs than `per_host`
+    /// has ranks.
+    ///
+    /// `per_rank_bootstrap` is as for [`HostMeshRef::spawn`].
+    #[allow(clippy::result_large_err)]
+    pub async fn spawn_with_placement<C: context::Actor>(
+        &self,
+        cx: &C,
+        name: &str,
+        per_host: Extent,
+        placement: PlacementPolicy,
+        per_rank_bootstrap: Option<Box<PerRankBootstrapFn>>,
+    ) -> crate::Result<ProcMesh>
+    where
+        C::A: Handler<MeshFailure>,
+    {
+        let proc_bind = match placement {
+            PlacementPolicy::Unbound => None,
+            PlacementPolicy::GpuTopology => Some(self.gpu_proc_binds(cx, &per_host).await?),
+        };
         self.spawn_inner(
             cx,
             ProcMeshId::instance(Label::strip(name)),
@@ -1190,6 +1256,37 @@ impl HostMeshRef {
         .await
     }
 
+    /// Query the GPU topology of every host, and compute the bindings
+    /// of each proc, in rank order.
+    async fn gpu_proc_binds(
+        &self,
+        cx: &impl context::Actor,
+        per_host: &Extent,
+    ) -> crate::Result<Vec<ProcBind>> {
+        let timeout = hyperactor_config::global::get(GPU_TOPOLOGY_TIMEOUT);
+        let topologies = futures::future::join_all(self.ranks.iter().map(|host| async move {
+            let agent = host.mesh_agent();
+            let topology = tokio::time::timeout(timeout, agent.get_gpu_topology(cx))
+                .await
+                .map_err(|_| anyhow::anyhow!("timed out after {:?}", timeout))
+                .and_then(|result| result)
+                .and_then(|topology| topology.map_err(anyhow::Error::msg));
+            topology.map_err(|err| {
+                crate::Error::HostMeshAgentConfigurationError(
+                    agent.actor_addr().clone(),
+                    format!("failed while querying GPU topology: {}", err),
+                )
+            })
+        }))
+        .await;
+
+        let mut proc_bind = Vec::with_capacity(self.region.num_ranks() * per_host.num_ranks());
+        for topology in topologies {
+            proc_bind.extend(topology?.proc_binds(per_host)?);
+        }
+        Ok(proc_bind)
+    }
+
     #[hyperactor::instrument(fields(host_mesh=self.id.to_string(), proc_mesh=proc_mesh_id.to_string()))]
     async fn spawn_inner<C: context::Actor>(
         &self,
@@ -1220,6 +1317,8 @@ impl HostMeshRef {
         result
     }
 
+    /// `proc_bind`, when provided, holds the binding of each proc of
+    /// the mesh, by rank.
     async fn spawn_inner_inner<C: context::Actor>(
         &self,
         cx: &C,
@@ -1242,14 +1341,6 @@ impl HostMeshRef {
                 "per_host dims overlap with existing dims when spawning proc mesh"
             )));
         }
-        if let Some(proc_bind) = proc_bind.as_ref()
-            && proc_bind.len() != per_host.num_ranks()
-        {
-            return Err(crate::Error::ConfigurationError(anyhow::anyhow!(
-                "proc_bind length does not match per_host extent"
-            )));
-        }
-
         let extent = self
             .region
             .extent()
@@ -1297,7 +1388,7 @@ impl HostMeshRef {
                     per_host_rank
                 )));
                 proc_names.push(proc_name.clone());
-                let bind = proc_bind.as_ref().map(|v| v[per_host_rank].clone());
+                let bind = proc_bind.as_ref().map(|v| v[create_rank].clone());
                 let bootstrap_command = match per_rank_bootstrap.as_ref() {
                     Some(f) => Some(
                         f(extent
@@ -1480,6 +1571,34 @@ impl HostMeshRef {
         &self.ranks
     }
 
+    /// Whether each host, in rank order, is reachable: whether its
+    /// host agent answers an introspection query within `timeout`.
+    pub async fn probe(&self, cx: &impl context::Actor, timeout: Duration) -> Vec<bool> {
+        futures::future::join_all(self.ranks.iter().map(|host| async move {
+            let agent = host.mesh_agent();
+            let port = PortRef::<IntrospectMessage>::attest_control_port(
+                agent.actor_addr(),
+                ControlPort::Introspect,
+            );
+            let (reply_handle, reply_rx) = open_once_port::<IntrospectResult>(cx);
+            let mut reply = reply_handle.bind();
+            // An unreachable host is the expected failure here.
+            reply.return_undeliverable(false);
+            port.post(
+                cx,
+                IntrospectMessage::Query {
+                    view: IntrospectView::Actor,
+                    reply,
+                },
+            );
+            matches!(
+                tokio::time::timeout(timeout, reply_rx.recv()).await,
+                Ok(Ok(_))
+            )
+        }))
+        .await
+    }
+
     /// Stop every proc in this proc mesh.
     ///
     /// On success returns the final per-rank `StatusMesh`, in which every
@@ -2022,6 +2141,7 @@ mod tests {
                 command: None, // use current binary
                 config: None,
                 exit_on_shutdown: false,
+                hello: None,
             };
             boot.to_env(&mut cmd);
             cmd.kill_on_drop(true);
@@ -2086,6 +2206,7 @@ mod tests {
                 // The entire purpose of this is to fail:
                 command: Some(BootstrapCommand::from("false")),
                 exit_on_shutdown: false,
+                hello: None,
             };
             boot.to_env(&mut cmd);
             cmd.kill_on_drop(true);
@@ -2134,6 +2255,7 @@ mod tests {
                 config: None,
                 command,
                 exit_on_shutdown: false,
+                hello: None,
             };
             boot.to_env(&mut cmd);
             cmd.kill_on_drop(true);
@@ -59,13 +59,23 @@ use crate::host::SERVICE_PROC_NAME;
 use crate::host::SingleTerminate;
 use crate::mesh_id::HostMeshId;
 use crate::mesh_id::ResourceId;
+use crate::placement::GetGpuTopology;
+use crate::placement::GpuTopology;
 use crate::proc_agent::ProcAgent;
 use crate::pyspy::PySpyDump;
 use crate::pyspy::PySpyProfile;
 use crate::pyspy::PySpyProfileWorker;
 use crate::pyspy::PySpyWorker;
+use crate::reservations::ReservationsDump;
+use crate::reservations::ReservationsResult;
 use crate::resource;
 use crate::resource::ProcSpec;
+use crate::routing_audit::RoutingAuditDump;
+use crate::routing_audit::RoutingAuditResult;
+use crate::timing::TimingDump;
+use crate::timing::TimingResult;
+use crate::topology::TopologyDump;
+use crate::topology::TopologyResult;
 
 pub(crate) type ProcManagerSpawnFuture =
     Pin<Box<dyn Future<Output = anyhow::Result<ActorHandle<ProcAgent>>> + Send>>;
@@ -286,6 +296,11 @@ impl fmt::Debug for DrainWorker {
         PySpyDump,
         PySpyProfile,
         ConfigDump,
+        RoutingAuditDump,
+        ReservationsDump,
+        TimingDump,
+        TopologyDump,
+        GetGpuTopology,
         crate::proc_agent::SelfCheck,
     ]
 )]
@@ -1490,6 +1505,69 @@ impl Handler<ConfigDump> for HostAgent {
     }
 }
 
+#[async_trait]
+impl Handler<RoutingAuditDump> for HostAgent {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        message: RoutingAuditDump,
+    ) -> Result<(), anyhow::Error> {
+        message.result.post(cx, RoutingAuditResult::snapshot());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<ReservationsDump> for HostAgent {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        message: ReservationsDump,
+    ) -> Result<(), anyhow::Error> {
+        message
+            .result
+            .post(cx, ReservationsResult::snapshot(cx.proc()));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<TimingDump> for HostAgent {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        message: TimingDump,
+    ) -> Result<(), anyhow::Error> {
+        message.result.post(cx, TimingResult::snapshot(cx.proc()));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<TopologyDump> for HostAgent {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        message: TopologyDump,
+    ) -> Result<(), anyhow::Error> {
+        message.result.post(cx, TopologyResult::snapshot(cx.proc()));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<GetGpuTopology> for HostAgent {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        message: GetGpuTopology,
+    ) -> Result<(), anyhow::Error> {
+        let topology = GpuTopology::local().await.map_err(|err| err.to_string());
+        message.result.post(cx, topology);
+        Ok(())
+    }
+}
+
 #[cfg(all(test, fbcode_build))]
 mod tests {
     use std::assert_matches;
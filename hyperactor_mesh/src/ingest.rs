@@ -0,0 +1,592 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Schema-checked JSON ingestion of mailbox posts.
+//!
+//! An [`IngestEndpoint`] is an optional HTTP endpoint on a proc that
+//! lets scripts and dashboards post messages to a mesh without
+//! linking against it. A proc's [`crate::proc_agent::ProcAgent`]
+//! serves one on [`crate::config::MESH_INGEST_ADDR`] when it is set,
+//! and actors on the proc find it with [`IngestEndpoint::of`]. Ports
+//! are exposed under a name with [`IngestEndpoint::register`]; a JSON
+//! body posted to
+//! `POST /v1/ports/{name}` is checked against the message type's
+//! [`Schema`], converted to a [`wirevalue::Any`], and posted to the
+//! port from the endpoint's own client.
+//!
+//! | Method | Path | Description |
+//! |---|---|---|
+//! | GET | `/v1/ports` | The registered ports and their schemas. |
+//! | POST | `/v1/ports/{name}` | Post the JSON body to the named port. |
+//!
+//! Bodies are first checked against the top-level [`Layout`] of the
+//! message type, which gives precise errors for unknown fields (which
+//! decoding would otherwise silently ignore), and then decoded as the
+//! message type itself, which checks the rest. Rejected bodies are
+//! answered with `400 Bad Request` and are never posted.
+//!
+//! ## TLS transport invariant (IN-T1)
+//!
+//! - **IN-T1 (mtls):** Any registered port can be posted to by
+//!   whoever can reach the endpoint, so the endpoint requires mutual
+//!   TLS, with certificates found as for the mesh admin server (see
+//!   MA-T1 in [`crate::mesh_admin`]). Plain HTTP is served only on a
+//!   loopback address, and only outside of Meta (`fbcode_build`);
+//!   otherwise [`IngestEndpoint::serve`] fails when no certificates
+//!   are found.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::LazyLock;
+use std::sync::RwLock;
+
+use axum::Json;
+use axum::Router;
+use axum::extract::Path as AxumPath;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::routing::post;
+use hyperactor::Client;
+use hyperactor::PortAddr;
+use hyperactor::PortRef;
+use hyperactor::Proc;
+use hyperactor::ProcAddr;
+use hyperactor::RemoteMessage;
+use hyperactor::channel::try_tls_acceptor;
+use serde::Deserialize;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde::de::IntoDeserializer;
+use serde::de::MapAccess;
+use serde::de::Visitor;
+use serde_json::Value;
+use tokio::net::TcpListener;
+use wirevalue::schema::Layout;
+use wirevalue::schema::Schema;
+
+use crate::mesh_admin::ApiError;
+use crate::mesh_admin::TlsListener;
+
+/// Errors produced when ingesting a JSON post.
+#[derive(Debug, thiserror::Error)]
+pub enum IngestError {
+    /// No port is registered under the name.
+    #[error("no port is registered as {0}")]
+    UnknownPort(String),
+
+    /// The body does not match the layout of the message type.
+    #[error("body does not match {layout}: {reason}")]
+    Schema {
+        /// The expected layout.
+        layout: Layout,
+        /// Why the body does not match.
+        reason: String,
+    },
+
+    /// The body could not be decoded as the message type.
+    #[error("body could not be decoded as {typename}: {source}")]
+    Decode {
+        /// The name of the message type.
+        typename: &'static str,
+        /// The decoding error.
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// The decoded message could not be serialized.
+    #[error(transparent)]
+    Wirevalue(#[from] wirevalue::Error),
+}
+
+impl From<IngestError> for ApiError {
+    fn from(error: IngestError) -> Self {
+        match error {
+            IngestError::UnknownPort(_) => ApiError::not_found(error.to_string(), None),
+            IngestError::Schema { .. } | IngestError::Decode { .. } => {
+                ApiError::bad_request(error.to_string(), None)
+            }
+            IngestError::Wirevalue(_) => ApiError {
+                code: "internal_error".to_string(),
+                message: error.to_string(),
+                details: None,
+            },
+        }
+    }
+}
+
+/// A registered port, as listed by `GET /v1/ports`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IngestPortInfo {
+    /// The name the port is registered under.
+    pub name: String,
+    /// The port's address.
+    pub port: String,
+    /// The name of the message type.
+    pub typename: String,
+    /// The top-level layout of the message type.
+    pub layout: String,
+}
+
+/// The response to an accepted post.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IngestAccepted {
+    /// The port the message was posted to.
+    pub port: String,
+}
+
+struct IngestPort {
+    port: PortAddr,
+    schema: Schema,
+    decode: fn(Value) -> Result<wirevalue::Any, IngestError>,
+    is_field: fn(&str) -> bool,
+}
+
+impl IngestPort {
+    fn ingest(&self, body: Value) -> Result<wirevalue::Any, IngestError> {
+        check_layout(self.schema.layout(), &body, self.is_field)?;
+        (self.decode)(body)
+    }
+
+    fn info(&self, name: &str) -> IngestPortInfo {
+        IngestPortInfo {
+            name: name.to_string(),
+            port: self.port.to_string(),
+            typename: self.schema.typename().to_string(),
+            layout: self.schema.layout().to_string(),
+        }
+    }
+}
+
+fn decode<M: RemoteMessage>(body: Value) -> Result<wirevalue::Any, IngestError> {
+    let message: M = serde_json::from_value(body).map_err(|source| IngestError::Decode {
+        typename: M::typename(),
+        source,
+    })?;
+    Ok(wirevalue::Any::serialize(&message)?)
+}
+
+/// Whether `M` deserializes the field `key`, rather than ignoring it.
+/// Unlike the layout's field names, this accounts for
+/// `#[serde(alias)]`: `M` is fed a map with the single key `key`, whose
+/// value records whether it is deserialized or skipped.
+fn is_field<M: DeserializeOwned>(key: &str) -> bool {
+    let known = Cell::new(false);
+    let _ = M::deserialize(FieldProbe {
+        key: Some(key),
+        known: &known,
+    });
+    known.get()
+}
+
+/// A deserializer of a map with a single key, whose value is a
+/// [`ValueProbe`]; see [`is_field`].
+struct FieldProbe<'a> {
+    key: Option<&'a str>,
+    known: &'a Cell<bool>,
+}
+
+impl<'de> serde::Deserializer<'de> for FieldProbe<'_> {
+    type Error = serde::de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> MapAccess<'de> for FieldProbe<'_> {
+    type Error = serde::de::value::Error;
+
+    fn next_key_seed<K: serde::de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        match self.key.take() {
+            Some(key) => seed.deserialize(key.into_deserializer()).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: serde::de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        seed.deserialize(ValueProbe { known: self.known })
+    }
+}
+
+/// A deserializer that records whether it is asked for a value, as for
+/// a known field, or skipped with `deserialize_ignored_any`, as for an
+/// unknown one.
+struct ValueProbe<'a> {
+    known: &'a Cell<bool>,
+}
+
+impl<'de> serde::Deserializer<'de> for ValueProbe<'_> {
+    type Error = serde::de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        self.known.set(true);
+        Err(serde::de::Error::custom("probe"))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier
+    }
+}
+
+/// Check the outermost shape of `body` against `layout`, following
+/// serde_json's representation of each layout. Struct fields that are
+/// not in the layout are checked with `is_field`, which knows their
+/// aliases. Variant names are left to the decoder, which rejects
+/// unknown ones and knows their aliases.
+fn check_layout(
+    layout: &Layout,
+    body: &Value,
+    is_field: impl Fn(&str) -> bool,
+) -> Result<(), IngestError> {
+    let mismatch = |reason: String| IngestError::Schema {
+        layout: layout.clone(),
+        reason,
+    };
+    match layout {
+        Layout::Opaque | Layout::Newtype { .. } => Ok(()),
+        Layout::Unit { .. } => match body {
+            Value::Null => Ok(()),
+            _ => Err(mismatch("expected null".to_string())),
+        },
+        Layout::Tuple { len, .. } => match body {
+            Value::Array(items) if items.len() == *len => Ok(()),
+            Value::Array(items) => Err(mismatch(format!(
+                "expected {} elements, got {}",
+                len,
+                items.len()
+            ))),
+            _ => Err(mismatch("expected an array".to_string())),
+        },
+        Layout::Struct { fields, .. } => match body {
+            Value::Object(object) => match object
+                .keys()
+                .find(|key| !fields.contains(&key.as_str()) && !is_field(key))
+            {
+                Some(key) => Err(mismatch(format!("unknown field {}", key))),
+                None => Ok(()),
+            },
+            _ => Err(mismatch("expected an object".to_string())),
+        },
+        Layout::Enum { .. } => match body {
+            Value::String(_) => Ok(()),
+            Value::Object(object) if object.len() == 1 => Ok(()),
+            _ => Err(mismatch(
+                "expected a variant name or a single-key object".to_string(),
+            )),
+        },
+    }
+}
+
+/// The endpoints served by [`IngestEndpoint::serve_configured`], by proc.
+static ENDPOINTS: LazyLock<RwLock<HashMap<ProcAddr, IngestEndpoint>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+struct Inner {
+    client: Client,
+    ports: RwLock<BTreeMap<String, IngestPort>>,
+}
+
+/// An HTTP endpoint that posts schema-checked JSON bodies to named
+/// ports; see the [module documentation](self).
+#[derive(Clone)]
+pub struct IngestEndpoint {
+    inner: Arc<Inner>,
+}
+
+impl IngestEndpoint {
+    /// Create an endpoint that posts from a new client on `proc`. No
+    /// ports are registered, and nothing is served until
+    /// [`IngestEndpoint::serve`] is called.
+    pub fn new(proc: &Proc) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                client: proc.client("ingest"),
+                ports: RwLock::new(BTreeMap::new()),
+            }),
+        }
+    }
+
+    /// Expose `port` under `name`, replacing any port previously
+    /// registered under it.
+    pub fn register<M: RemoteMessage>(&self, name: impl Into<String>, port: &PortRef<M>) {
+        self.inner.ports.write().unwrap().insert(
+            name.into(),
+            IngestPort {
+                port: port.port_addr().clone(),
+                schema: Schema::of::<M>(),
+                decode: decode::<M>,
+                is_field: is_field::<M>,
+            },
+        );
+    }
+
+    /// Stop exposing the port registered under `name`. Returns whether
+    /// a port was registered.
+    pub fn unregister(&self, name: &str) -> bool {
+        self.inner.ports.write().unwrap().remove(name).is_some()
+    }
+
+    /// The registered ports, ordered by name.
+    pub fn ports(&self) -> Vec<IngestPortInfo> {
+        self.inner
+            .ports
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, port)| port.info(name))
+            .collect()
+    }
+
+    /// Check `body` against the schema of the port registered under
+    /// `name`, and post it. Returns the port posted to.
+    pub fn post(&self, name: &str, body: Value) -> Result<PortAddr, IngestError> {
+        let (port, message) = {
+            let ports = self.inner.ports.read().unwrap();
+            let port = ports
+                .get(name)
+                .ok_or_else(|| IngestError::UnknownPort(name.to_string()))?;
+            (port.port.clone(), port.ingest(body)?)
+        };
+        port.send(&self.inner.client, message);
+        Ok(port)
+    }
+
+    /// The endpoint's HTTP routes.
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route("/v1/ports", get(list_ports))
+            .route("/v1/ports/{name}", post(post_port))
+            .with_state(self.clone())
+    }
+
+    /// Serve the endpoint on `addr` in a background task, with mutual
+    /// TLS (see IN-T1 in the [module documentation](self)). Returns the
+    /// bound address, which differs from `addr` if its port is 0.
+    pub async fn serve(&self, addr: SocketAddr) -> anyhow::Result<SocketAddr> {
+        let tls_acceptor = try_tls_acceptor(true);
+        if tls_acceptor.is_none() && (cfg!(fbcode_build) || !addr.ip().is_loopback()) {
+            anyhow::bail!(
+                "ingest endpoint on {} requires mTLS but no TLS certificates found; \
+                 set HYPERACTOR_TLS_CERT/KEY/CA, or bind it to a loopback address",
+                addr
+            );
+        }
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let router = self.router();
+        if let Some(acceptor) = tls_acceptor {
+            let tls_listener = TlsListener {
+                tcp: listener,
+                acceptor,
+            };
+            tokio::spawn(async move {
+                if let Err(e) = axum::serve(tls_listener, router).await {
+                    tracing::error!("ingest server (mTLS) error: {}", e);
+                }
+            });
+        } else {
+            tokio::spawn(async move {
+                if let Err(e) = axum::serve(listener, router).await {
+                    tracing::error!("ingest server error: {}", e);
+                }
+            });
+        }
+        tracing::info!("ingest server listening on {}", local_addr);
+        Ok(local_addr)
+    }
+
+    /// Serve an endpoint for `proc` on [`crate::config::MESH_INGEST_ADDR`],
+    /// if it is set, and make it available through [`IngestEndpoint::of`].
+    /// Returns the bound address, if any.
+    pub async fn serve_configured(proc: &Proc) -> anyhow::Result<Option<SocketAddr>> {
+        let addr = hyperactor_config::global::get_cloned(crate::config::MESH_INGEST_ADDR);
+        if addr.is_empty() {
+            return Ok(None);
+        }
+        let addr: SocketAddr = addr
+            .parse()
+            .map_err(|e| anyhow::anyhow!("invalid MESH_INGEST_ADDR config {}: {}", addr, e))?;
+        let endpoint = Self::new(proc);
+        let local_addr = endpoint.serve(addr).await?;
+        ENDPOINTS
+            .write()
+            .unwrap()
+            .insert(proc.proc_addr(), endpoint);
+        Ok(Some(local_addr))
+    }
+
+    /// The endpoint served for `proc` by [`IngestEndpoint::serve_configured`],
+    /// with which its actors register their ports.
+    pub fn of(proc: &Proc) -> Option<IngestEndpoint> {
+        ENDPOINTS.read().unwrap().get(&proc.proc_addr()).cloned()
+    }
+}
+
+async fn list_ports(State(endpoint): State<IngestEndpoint>) -> Json<Vec<IngestPortInfo>> {
+    Json(endpoint.ports())
+}
+
+async fn post_port(
+    State(endpoint): State<IngestEndpoint>,
+    AxumPath(name): AxumPath<String>,
+    Json(body): Json<Value>,
+) -> Result<impl IntoResponse, ApiError> {
+    let port = endpoint.post(&name, body)?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(IngestAccepted {
+            port: port.to_string(),
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use typeuri::Named;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named)]
+    struct Scale {
+        #[serde(alias = "scale")]
+        factor: u32,
+        reason: Option<String>,
+    }
+
+    #[tokio::test]
+    async fn test_ingest_endpoint() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let (handle, mut receiver) = client.open_port::<Scale>();
+        let endpoint = IngestEndpoint::new(&proc);
+        endpoint.register("scale", &handle.bind());
+        let addr = endpoint
+            .serve("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+
+        let http = reqwest::Client::new();
+        let ports: Vec<IngestPortInfo> = http
+            .get(format!("http://{}/v1/ports", addr))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(ports.len(), 1);
+        assert_eq!(ports[0].name, "scale");
+        assert_eq!(ports[0].typename, Scale::typename());
+
+        let post = |name: &str, body: Value| {
+            http.post(format!("http://{}/v1/ports/{}", addr, name))
+                .json(&body)
+                .send()
+        };
+        let response = post("scale", json!({"factor": 3})).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            Scale {
+                factor: 3,
+                reason: None,
+            }
+        );
+        let response = post("scale", json!({"scale": 4})).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+        assert_eq!(receiver.recv().await.unwrap().factor, 4);
+
+        // Unknown field, wrong field type, and unknown port.
+        let response = post("scale", json!({"factor": 3, "fator": 4}))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+        let response = post("scale", json!({"factor": "3"})).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+        let response = post("resize", json!({"factor": 3})).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+        assert!(receiver.try_recv().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_check_layout() {
+        let layout = Layout::Enum {
+            name: "Command",
+            variants: &["Start", "Stop"],
+        };
+        let no_fields = |_: &str| false;
+        assert!(check_layout(&layout, &json!("Start"), no_fields).is_ok());
+        assert!(check_layout(&layout, &json!({"Stop": {"force": true}}), no_fields).is_ok());
+        assert!(check_layout(&layout, &json!(["Start"]), no_fields).is_err());
+
+        let layout = Layout::Tuple {
+            name: "Pair",
+            len: 2,
+        };
+        assert!(check_layout(&layout, &json!([1, 2]), no_fields).is_ok());
+        assert!(check_layout(&layout, &json!([1]), no_fields).is_err());
+
+        assert!(is_field::<Scale>("factor"));
+        assert!(is_field::<Scale>("scale"));
+        assert!(!is_field::<Scale>("fator"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_requires_tls() {
+        // No certificates are configured in tests, so only a loopback
+        // address may be served, over plain HTTP.
+        let endpoint = IngestEndpoint::new(&Proc::isolated());
+        assert!(endpoint.serve("0.0.0.0:0".parse().unwrap()).await.is_err());
+        assert!(endpoint.serve("127.0.0.1:0".parse().unwrap()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_serve_configured() {
+        let config = hyperactor_config::global::lock();
+        let proc = Proc::isolated();
+        assert_eq!(IngestEndpoint::serve_configured(&proc).await.unwrap(), None);
+        assert!(IngestEndpoint::of(&proc).is_none());
+
+        let _guard =
+            config.override_key(crate::config::MESH_INGEST_ADDR, "127.0.0.1:0".to_string());
+        let addr = IngestEndpoint::serve_configured(&proc)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(addr.ip().is_loopback());
+        let endpoint = IngestEndpoint::of(&proc).unwrap();
+        let client = proc.client("client");
+        let (handle, _receiver) = client.open_port::<Scale>();
+        endpoint.register("scale", &handle.bind());
+        assert_eq!(endpoint.ports()[0].name, "scale");
+    }
+}
@@ -882,6 +882,7 @@ impl ErrorAttrsView {
 
 // --- API / presentation types ---
 
+use std::collections::BTreeMap;
 use std::fmt;
 use std::str::FromStr;
 use std::time::SystemTime;
@@ -1040,6 +1041,8 @@ pub enum NodeProperties {
         last_message_handler: Option<String>,
         total_processing_time_us: u64,
         queue_depth: u64,
+        queued_bytes: u64,
+        queued_bytes_by_port: BTreeMap<String, u64>,
         flight_recorder: Option<String>,
         is_system: bool,
         inbound_ordering: Option<Box<InboundOrdering>>,
@@ -1287,6 +1290,8 @@ impl IntoNodeProperties for hyperactor::introspect::ActorAttrsView {
             last_message_handler: self.last_handler,
             total_processing_time_us: self.total_processing_time_us,
             queue_depth: self.queue_depth,
+            queued_bytes: self.queued_bytes,
+            queued_bytes_by_port: self.queued_bytes_by_port,
             flight_recorder: self.flight_recorder,
             is_system: self.is_system,
             inbound_ordering: self
@@ -2011,6 +2016,8 @@ mod tests {
                     last_message_handler: Some("handle_ping".into()),
                     total_processing_time_us: 1000,
                     queue_depth: 0,
+                    queued_bytes: 0,
+                    queued_bytes_by_port: Default::default(),
                     flight_recorder: None,
                     is_system: false,
                     inbound_ordering: None,
@@ -27,6 +27,7 @@
 //! - **HB-3 (schema-honesty):** Schema/OpenAPI are generated from these
 //!   DTO types, so the published schema reflects the actual wire format.
 
+use std::collections::BTreeMap;
 use std::time::SystemTime;
 
 use anyhow::Context;
@@ -164,6 +165,12 @@ pub enum NodePropertiesDto {
         /// `inbound_ordering`; no arithmetic contract -- see IO-3 in
         /// `hyperactor::introspect`.
         queue_depth: u64,
+        /// Approximate serialized bytes of the messages in the actor's
+        /// work queue.
+        queued_bytes: u64,
+        /// Approximate serialized bytes queued to each handler port,
+        /// keyed by message type. Ports with nothing queued are omitted.
+        queued_bytes_by_port: BTreeMap<String, u64>,
         flight_recorder: Option<String>,
         is_system: bool,
         /// Per-session reorder-buffer state. `None` means no snapshot
@@ -385,6 +392,8 @@ impl From<NodeProperties> for NodePropertiesDto {
                 last_message_handler,
                 total_processing_time_us,
                 queue_depth,
+                queued_bytes,
+                queued_bytes_by_port,
                 flight_recorder,
                 is_system,
                 inbound_ordering,
@@ -399,6 +408,8 @@ impl From<NodeProperties> for NodePropertiesDto {
                 last_message_handler,
                 total_processing_time_us,
                 queue_depth,
+                queued_bytes,
+                queued_bytes_by_port,
                 flight_recorder,
                 is_system,
                 inbound_ordering: inbound_ordering
@@ -573,6 +584,8 @@ impl TryFrom<NodePropertiesDto> for NodeProperties {
                 last_message_handler,
                 total_processing_time_us,
                 queue_depth,
+                queued_bytes,
+                queued_bytes_by_port,
                 flight_recorder,
                 is_system,
                 inbound_ordering,
@@ -592,6 +605,8 @@ impl TryFrom<NodePropertiesDto> for NodeProperties {
                 last_message_handler,
                 total_processing_time_us,
                 queue_depth,
+                queued_bytes,
+                queued_bytes_by_port,
                 flight_recorder,
                 is_system,
                 inbound_ordering: inbound_ordering
@@ -820,6 +835,8 @@ mod tests {
                 last_message_handler: Some("handle_msg".to_string()),
                 total_processing_time_us: 1500,
                 queue_depth: 0,
+                queued_bytes: 0,
+                queued_bytes_by_port: Default::default(),
                 flight_recorder: None,
                 is_system: false,
                 inbound_ordering: None,
@@ -844,6 +861,8 @@ mod tests {
                 last_message_handler: None,
                 total_processing_time_us: 500,
                 queue_depth: 0,
+                queued_bytes: 0,
+                queued_bytes_by_port: Default::default(),
                 flight_recorder: Some("trace-abc".to_string()),
                 is_system: true,
                 inbound_ordering: None,
@@ -874,6 +893,8 @@ mod tests {
                 last_message_handler: None,
                 total_processing_time_us: 0,
                 queue_depth: 0,
+                queued_bytes: 0,
+                queued_bytes_by_port: Default::default(),
                 flight_recorder: None,
                 is_system: false,
                 inbound_ordering: None,
@@ -922,6 +943,8 @@ mod tests {
                 last_message_handler: Some("handle_msg".to_string()),
                 total_processing_time_us: 900,
                 queue_depth: 5,
+                queued_bytes: 64,
+                queued_bytes_by_port: [("Msg".to_string(), 64)].into(),
                 flight_recorder: None,
                 is_system: false,
                 inbound_ordering: Some(Box::new(InboundOrdering {
@@ -958,6 +981,8 @@ mod tests {
                 last_message_handler: Some("handle_msg".to_string()),
                 total_processing_time_us: 900,
                 queue_depth: 5,
+                queued_bytes: 64,
+                queued_bytes_by_port: [("Msg".to_string(), 64)].into(),
                 flight_recorder: None,
                 is_system: false,
                 inbound_ordering: Some(Box::new(InboundOrdering {
@@ -0,0 +1,535 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Provisioning host meshes on Kubernetes.
+//!
+//! [`KubernetesHosts::create`] creates one pod per host with `kubectl`;
+//! the pods' containers only hold their resources. Once every pod is
+//! ready, [`KubernetesHosts::host_mesh`] maps the pod IPs to TCP
+//! [`ChannelAddr`]s, launches a host in each pod (`kubectl exec`), and
+//! attaches to the hosts, returning a [`HostMesh`] with one rank per
+//! pod.
+//!
+//! Pods that are deleted, or whose containers exit, are reported as
+//! supervision events on the corresponding host rank by
+//! [`KubernetesHosts::next_failure`].
+//!
+//! The bootstrap command run in each pod must exist in the container
+//! image and reach [`crate::bootstrap_or_die`].
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use hyperactor::channel::ChannelAddr;
+use hyperactor::context;
+use hyperactor::id::Label;
+use hyperactor_config::CONFIG;
+use hyperactor_config::ConfigAttr;
+use hyperactor_config::attrs::declare_attrs;
+use serde_json::Value;
+use serde_json::json;
+use tokio::process::Child;
+use tokio::process::Command;
+
+use crate::bootstrap::BootstrapCommand;
+use crate::cluster;
+use crate::cluster::CommandError;
+use crate::cluster::LaunchedHosts;
+use crate::host_mesh::HostMesh;
+use crate::mesh_id::HostMeshId;
+use crate::supervision::MeshFailure;
+
+declare_attrs! {
+    /// How often to poll the state of Kubernetes pods.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_KUBERNETES_POLL_INTERVAL".to_string()),
+        Some("kubernetes_poll_interval".to_string()),
+    ))
+    pub attr KUBERNETES_POLL_INTERVAL: Duration = Duration::from_secs(2);
+}
+
+/// The label identifying the pods of a [`KubernetesHosts`].
+const JOB_LABEL: &str = "monarch.meta.com/job";
+
+/// The label carrying the host rank of a pod.
+const RANK_LABEL: &str = "monarch.meta.com/rank";
+
+/// Errors interacting with Kubernetes.
+#[derive(Debug, thiserror::Error)]
+pub enum KubernetesError {
+    /// A `kubectl` command could not be run, failed, or produced
+    /// unexpected output.
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    /// A pod ended, or was deleted, before it became ready.
+    #[error("pod {pod} is not running: {state}")]
+    NotRunning { pod: String, state: String },
+
+    /// The pods did not become ready in time.
+    #[error("pods of {name} were not ready within {timeout:?}")]
+    Timeout { name: String, timeout: Duration },
+
+    /// A pod's address could not be resolved.
+    #[error("failed to resolve address of pod {pod}: {source}")]
+    Address {
+        pod: String,
+        #[source]
+        source: anyhow::Error,
+    },
+}
+
+/// The specification of a set of pods, each running a host.
+#[derive(Debug, Clone)]
+pub struct KubernetesSpec {
+    /// The name of the set; pods are named `<name>-<rank>`. Must be a
+    /// valid DNS subdomain name.
+    pub name: String,
+    /// The number of pods, and thus of hosts.
+    pub pods: usize,
+    /// The container image of the pods.
+    pub image: String,
+    /// The namespace of the pods. Defaults to the current context's.
+    pub namespace: Option<String>,
+    /// The TCP port on which each host is served.
+    pub port: u16,
+    /// CPUs to request for each pod, e.g. `"16"`.
+    pub cpu: Option<String>,
+    /// Memory to request for each pod, e.g. `"64Gi"`.
+    pub memory: Option<String>,
+    /// GPUs to request for each pod.
+    pub gpus: Option<usize>,
+    /// The node selector of the pods.
+    pub node_selector: BTreeMap<String, String>,
+    /// The command used to bootstrap hosts, and their procs. Defaults to
+    /// [`BootstrapCommand::current`], which requires the image to
+    /// provide the current executable at the same path.
+    pub command: Option<BootstrapCommand>,
+}
+
+impl KubernetesSpec {
+    /// A specification of `pods` pods named after `name`, running
+    /// `image`, with default settings.
+    pub fn new(name: impl Into<String>, pods: usize, image: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            pods,
+            image: image.into(),
+            namespace: None,
+            port: 22222,
+            cpu: None,
+            memory: None,
+            gpus: None,
+            node_selector: BTreeMap::new(),
+            command: None,
+        }
+    }
+
+    /// The name of the pod with the given rank.
+    fn pod_name(&self, rank: usize) -> String {
+        format!("{}-{}", self.name, rank)
+    }
+
+    /// The manifest of the pod with the given rank.
+    fn pod_manifest(&self, rank: usize) -> Value {
+        let mut limits = serde_json::Map::new();
+        if let Some(cpu) = &self.cpu {
+            limits.insert("cpu".to_string(), json!(cpu));
+        }
+        if let Some(memory) = &self.memory {
+            limits.insert("memory".to_string(), json!(memory));
+        }
+        if let Some(gpus) = self.gpus {
+            limits.insert("nvidia.com/gpu".to_string(), json!(gpus.to_string()));
+        }
+        json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {
+                "name": self.pod_name(rank),
+                "labels": {
+                    JOB_LABEL: self.name,
+                    RANK_LABEL: rank.to_string(),
+                },
+            },
+            "spec": {
+                "restartPolicy": "Never",
+                "nodeSelector": self.node_selector,
+                "containers": [{
+                    "name": "host",
+                    "image": self.image,
+                    // The container only holds the pod's resources; the
+                    // host is launched with `kubectl exec` once the pod
+                    // has an IP.
+                    "command": ["sleep", "infinity"],
+                    "ports": [{"containerPort": self.port}],
+                    "resources": {
+                        "requests": limits.clone(),
+                        "limits": limits,
+                    },
+                }],
+            },
+        })
+    }
+}
+
+/// The state of a pod.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PodState {
+    /// The pod is not yet ready.
+    Pending,
+    /// The pod is ready.
+    Ready {
+        /// The IP address of the pod.
+        ip: String,
+    },
+    /// The pod has ended with the given phase (e.g. `Failed`), is being
+    /// deleted, or no longer exists.
+    Ended(String),
+}
+
+/// A set of pods, each running a host.
+///
+/// The pods are deleted when the `KubernetesHosts` is dropped.
+#[derive(Debug)]
+pub struct KubernetesHosts {
+    spec: KubernetesSpec,
+    /// The `kubectl exec` processes running the hosts.
+    execs: Vec<Child>,
+    /// The hosts, once launched.
+    hosts: LaunchedHosts,
+    deleted: bool,
+}
+
+impl KubernetesHosts {
+    /// Create the pods according to `spec`.
+    pub async fn create(spec: KubernetesSpec) -> Result<Self, KubernetesError> {
+        let manifest = json!({
+            "apiVersion": "v1",
+            "kind": "List",
+            "items": (0..spec.pods).map(|rank| spec.pod_manifest(rank)).collect::<Vec<_>>(),
+        });
+        let mut command = kubectl(&spec);
+        command.args(["create", "-f", "-"]);
+        cluster::run_with_input(&mut command, &manifest.to_string()).await?;
+        tracing::info!(
+            name = spec.name,
+            pods = spec.pods,
+            "created kubernetes pods"
+        );
+        Ok(Self {
+            spec,
+            execs: Vec::new(),
+            hosts: LaunchedHosts::default(),
+            deleted: false,
+        })
+    }
+
+    /// The name of the pod set.
+    pub fn name(&self) -> &str {
+        &self.spec.name
+    }
+
+    /// Query the current state of the pods, by rank.
+    pub async fn states(&self) -> Result<Vec<PodState>, KubernetesError> {
+        let output = cluster::run(kubectl(&self.spec).args([
+            "get",
+            "pods",
+            "-l",
+            &format!("{}={}", JOB_LABEL, self.spec.name),
+            "-o",
+            "json",
+        ]))
+        .await?;
+        let pods = parse_pod_states(&output).ok_or_else(|| CommandError::Parse {
+            command: "kubectl get pods".to_string(),
+            output: output.clone(),
+        })?;
+        Ok((0..self.spec.pods)
+            .map(|rank| {
+                pods.get(&self.spec.pod_name(rank))
+                    .cloned()
+                    .unwrap_or_else(|| PodState::Ended("Deleted".to_string()))
+            })
+            .collect())
+    }
+
+    /// Wait for every pod to be ready, up to `timeout` if provided, and
+    /// return their IPs by rank.
+    pub async fn wait_ready(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<String>, KubernetesError> {
+        let start = tokio::time::Instant::now();
+        loop {
+            let mut ips = Vec::with_capacity(self.spec.pods);
+            for (rank, state) in self.states().await?.into_iter().enumerate() {
+                match state {
+                    PodState::Ready { ip } => ips.push(ip),
+                    PodState::Ended(state) => {
+                        return Err(KubernetesError::NotRunning {
+                            pod: self.spec.pod_name(rank),
+                            state,
+                        });
+                    }
+                    PodState::Pending => {}
+                }
+            }
+            if ips.len() == self.spec.pods {
+                tracing::info!(name = self.spec.name, ?ips, "kubernetes pods are ready");
+                return Ok(ips);
+            }
+            if let Some(timeout) = timeout
+                && start.elapsed() >= timeout
+            {
+                return Err(KubernetesError::Timeout {
+                    name: self.spec.name.clone(),
+                    timeout,
+                });
+            }
+            tokio::time::sleep(hyperactor_config::global::get(KUBERNETES_POLL_INTERVAL)).await;
+        }
+    }
+
+    /// Wait for the pods to be ready, launch a host in each of them, and
+    /// attach to them. The resulting mesh has a single `hosts`
+    /// dimension, ordered by pod rank.
+    pub async fn host_mesh(
+        &mut self,
+        cx: &impl context::Actor,
+        timeout: Option<Duration>,
+    ) -> crate::Result<HostMesh> {
+        let ips = self.wait_ready(timeout).await?;
+        let command = match &self.spec.command {
+            Some(command) => command.clone(),
+            None => BootstrapCommand::current()?,
+        };
+
+        let mut addrs = Vec::with_capacity(ips.len());
+        for (rank, ip) in ips.iter().enumerate() {
+            let pod = self.spec.pod_name(rank);
+            let addr = pod_addr(&pod, ip, self.spec.port)?;
+            let exec = kubectl(&self.spec)
+                .args(["exec", &pod, "-c", "host", "--", "env"])
+                .args(
+                    cluster::host_env(&command, &addr)?
+                        .into_iter()
+                        .map(|(key, val)| format!("{}={}", key, val)),
+                )
+                .arg(&command.program)
+                .args(&command.args)
+                .kill_on_drop(true)
+                .spawn()
+                .map_err(|source| {
+                    KubernetesError::from(CommandError::Spawn {
+                        command: "kubectl exec".to_string(),
+                        source,
+                    })
+                })?;
+            tracing::info!(name = self.spec.name, pod, %addr, "launched host");
+            self.execs.push(exec);
+            self.hosts.push(addr.clone());
+            addrs.push(addr);
+        }
+
+        HostMesh::attach(
+            cx,
+            HostMeshId::instance(Label::strip(&self.spec.name)),
+            addrs,
+        )
+        .await
+    }
+
+    /// Wait for a pod whose host was launched by [`Self::host_mesh`] to
+    /// end or be deleted, and return the corresponding supervision
+    /// event. Each lost pod is reported once.
+    ///
+    /// Never returns if there are no hosts left to monitor.
+    pub async fn next_failure(&mut self) -> Result<MeshFailure, KubernetesError> {
+        loop {
+            self.hosts.wait_monitored().await;
+            let states = self.states().await?;
+            let lost = self.hosts.live().find_map(|rank| match &states[rank] {
+                PodState::Ended(state) => Some((rank, state)),
+                _ => None,
+            });
+            if let Some((rank, state)) = lost {
+                let pod = self.spec.pod_name(rank);
+                tracing::warn!(name = self.spec.name, pod, state, "kubernetes pod lost");
+                let reason = format!("the pod running this host was lost: {} ({})", pod, state);
+                return Ok(self.hosts.lost(vec![rank], reason));
+            }
+            tokio::time::sleep(hyperactor_config::global::get(KUBERNETES_POLL_INTERVAL)).await;
+        }
+    }
+
+    /// Delete the pods, stopping their hosts.
+    pub async fn delete(mut self) -> Result<(), KubernetesError> {
+        self.deleted = true;
+        cluster::run(kubectl(&self.spec).args(self.delete_args())).await?;
+        Ok(())
+    }
+
+    /// The `kubectl` arguments that delete the pods.
+    fn delete_args(&self) -> Vec<String> {
+        vec![
+            "delete".to_string(),
+            "pods".to_string(),
+            "-l".to_string(),
+            format!("{}={}", JOB_LABEL, self.spec.name),
+            "--wait=false".to_string(),
+        ]
+    }
+}
+
+impl Drop for KubernetesHosts {
+    fn drop(&mut self) {
+        if !self.deleted {
+            // Best-effort: the pods should not outlive the hosts.
+            let mut command = std::process::Command::new("kubectl");
+            if let Some(namespace) = &self.spec.namespace {
+                command.arg(format!("--namespace={}", namespace));
+            }
+            if let Err(err) = command.args(self.delete_args()).spawn() {
+                tracing::warn!(
+                    name = self.spec.name,
+                    "failed to delete kubernetes pods: {}",
+                    err
+                );
+            }
+        }
+    }
+}
+
+/// A `kubectl` command in the namespace of `spec`.
+fn kubectl(spec: &KubernetesSpec) -> Command {
+    let mut command = Command::new("kubectl");
+    if let Some(namespace) = &spec.namespace {
+        command.arg(format!("--namespace={}", namespace));
+    }
+    command
+}
+
+/// Parse the output of `kubectl get pods -o json` into the state of
+/// each pod, by name.
+fn parse_pod_states(output: &str) -> Option<HashMap<String, PodState>> {
+    let list: Value = serde_json::from_str(output).ok()?;
+    let mut states = HashMap::new();
+    for pod in list.get("items")?.as_array()? {
+        let name = pod.pointer("/metadata/name")?.as_str()?.to_string();
+        let phase = pod
+            .pointer("/status/phase")
+            .and_then(Value::as_str)
+            .unwrap_or("Pending");
+        let ready = pod
+            .pointer("/status/conditions")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .any(|condition| condition["type"] == "Ready" && condition["status"] == "True");
+        let ip = pod.pointer("/status/podIP").and_then(Value::as_str);
+        let state = if pod.pointer("/metadata/deletionTimestamp").is_some() {
+            PodState::Ended("Terminating".to_string())
+        } else {
+            match (phase, ip) {
+                ("Succeeded" | "Failed" | "Unknown", _) => PodState::Ended(phase.to_string()),
+                ("Running", Some(ip)) if ready => PodState::Ready { ip: ip.to_string() },
+                _ => PodState::Pending,
+            }
+        };
+        states.insert(name, state);
+    }
+    Some(states)
+}
+
+/// The address of the host served in `pod` at `ip`.
+fn pod_addr(pod: &str, ip: &str, port: u16) -> Result<ChannelAddr, KubernetesError> {
+    cluster::tcp_addr(ip, port).map_err(|source| KubernetesError::Address {
+        pod: pod.to_string(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pod_manifest() {
+        let mut spec = KubernetesSpec::new("train", 2, "monarch:latest");
+        spec.gpus = Some(8);
+        spec.node_selector
+            .insert("pool".to_string(), "gpu".to_string());
+        let manifest = spec.pod_manifest(1);
+        assert_eq!(manifest["metadata"]["name"], "train-1");
+        assert_eq!(manifest["metadata"]["labels"][JOB_LABEL], "train");
+        assert_eq!(manifest["metadata"]["labels"][RANK_LABEL], "1");
+        assert_eq!(manifest["spec"]["nodeSelector"]["pool"], "gpu");
+        let container = &manifest["spec"]["containers"][0];
+        assert_eq!(container["image"], "monarch:latest");
+        assert_eq!(container["ports"][0]["containerPort"], 22222);
+        assert_eq!(container["resources"]["limits"]["nvidia.com/gpu"], "8");
+        assert!(container["resources"]["limits"].get("cpu").is_none());
+    }
+
+    #[test]
+    fn test_parse_pod_states() {
+        let output = json!({
+            "items": [
+                {
+                    "metadata": {"name": "train-0"},
+                    "status": {
+                        "phase": "Running",
+                        "podIP": "10.0.0.1",
+                        "conditions": [{"type": "Ready", "status": "True"}],
+                    },
+                },
+                {
+                    "metadata": {"name": "train-1"},
+                    "status": {
+                        "phase": "Running",
+                        "podIP": "10.0.0.2",
+                        "conditions": [{"type": "Ready", "status": "False"}],
+                    },
+                },
+                {
+                    "metadata": {"name": "train-2"},
+                    "status": {"phase": "Failed"},
+                },
+                {
+                    "metadata": {"name": "train-3", "deletionTimestamp": "2026-01-01T00:00:00Z"},
+                    "status": {"phase": "Running", "podIP": "10.0.0.4"},
+                },
+            ],
+        })
+        .to_string();
+        let states = parse_pod_states(&output).unwrap();
+        assert_eq!(
+            states["train-0"],
+            PodState::Ready {
+                ip: "10.0.0.1".to_string()
+            }
+        );
+        assert_eq!(states["train-1"], PodState::Pending);
+        assert_eq!(states["train-2"], PodState::Ended("Failed".to_string()));
+        assert_eq!(
+            states["train-3"],
+            PodState::Ended("Terminating".to_string())
+        );
+        assert!(parse_pod_states("not json").is_none());
+    }
+
+    #[test]
+    fn test_pod_addr() {
+        assert_eq!(
+            pod_addr("train-0", "10.0.0.1", 22222).unwrap().to_string(),
+            "tcp:10.0.0.1:22222"
+        );
+    }
+}
@@ -0,0 +1,897 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Named leases with fencing tokens, and leader election.
+//!
+//! A [`LeaseActor`] grants named, time-limited leases: at most one
+//! holder has an unexpired lease of a given name at any time. A holder
+//! keeps its lease by renewing it before it expires; a holder that
+//! fails, or is partitioned from the lease actor, simply stops
+//! renewing, and its lease lapses after its TTL. In addition, the
+//! lease actor periodically checks that each holder is still
+//! reachable, and releases the leases of those that have stopped,
+//! without waiting for their TTL.
+//!
+//! Holders that [wait](LeaseMessage::Wait) for a lease are granted it
+//! in the order they asked for it, as soon as it is released or
+//! lapses.
+//!
+//! Each grant carries a *fencing token*, which is strictly greater
+//! than the token of every earlier grant by the same lease actor,
+//! including grants made before the actor restarted, provided it is
+//! given a [token file](LeaseActorParams::token_file). Renewals keep
+//! the token. Because a holder may continue to act after its lease
+//! has lapsed (say, after a long pause), resources protected by a
+//! lease should reject requests carrying a token lower than the
+//! highest they have seen.
+//!
+//! [`LeaderElection`] is the usual recipe on top of this: candidates
+//! [campaign](LeaderElection::campaign) for the lease named after the
+//! role, the winner renews it while it leads, and
+//! [resigns](LeaderElection::resign) when it is done.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use hyperactor::Actor;
+use hyperactor::ActorAddr;
+use hyperactor::ActorRef;
+use hyperactor::Bind;
+use hyperactor::Context;
+use hyperactor::ControlPort;
+use hyperactor::Endpoint as _;
+use hyperactor::HandleClient;
+use hyperactor::Handler;
+use hyperactor::Instance;
+use hyperactor::OncePortRef;
+use hyperactor::PortRef;
+use hyperactor::RefClient;
+use hyperactor::RemoteEndpoint as _;
+use hyperactor::RemoteSpawn;
+use hyperactor::Unbind;
+use hyperactor::actor::handle_undeliverable_message;
+use hyperactor::context;
+use hyperactor::introspect::IntrospectMessage;
+use hyperactor::introspect::IntrospectResult;
+use hyperactor::introspect::IntrospectView;
+use hyperactor::mailbox::MessageEnvelope;
+use hyperactor::mailbox::Undeliverable;
+use hyperactor::mailbox::UndeliverableReason;
+use hyperactor::mailbox::open_once_port;
+use hyperactor_config::CONFIG;
+use hyperactor_config::ConfigAttr;
+use hyperactor_config::Flattrs;
+use hyperactor_config::attrs::declare_attrs;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::time::Instant;
+use typeuri::Named;
+
+declare_attrs! {
+    /// How often a [`LeaseActor`] checks that the holders of its
+    /// leases are reachable.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_LEASE_HOLDER_CHECK_INTERVAL".to_string()),
+        Some("lease_holder_check_interval".to_string()),
+    ))
+    pub attr LEASE_HOLDER_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// If present in a message header, the message is a lease actor's
+    /// reachability check of a holder, and its return means that the
+    /// holder has stopped.
+    attr LEASE_HOLDER_CHECK: bool;
+
+    /// If present in a message header, the message grants the lease
+    /// with this token to a waiting holder, and its return means that
+    /// the holder stopped waiting.
+    attr LEASE_GRANT_TOKEN: u64;
+}
+
+/// A granted lease.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Named)]
+pub struct Lease {
+    /// The name of the lease.
+    pub name: String,
+    /// The holder the lease was granted to.
+    pub holder: ActorAddr,
+    /// The fencing token of the grant.
+    pub token: u64,
+    /// The time remaining on the lease when it was granted or renewed.
+    pub ttl: Duration,
+}
+wirevalue::register_type!(Lease);
+
+/// Messages handled by [`LeaseActor`].
+#[derive(
+    Debug,
+    Clone,
+    Serialize,
+    Deserialize,
+    Named,
+    Handler,
+    HandleClient,
+    RefClient
+)]
+pub enum LeaseMessage {
+    /// Acquire the lease `name` for `holder`, for `ttl`. Replies with
+    /// the grant, or `None` if another holder has an unexpired lease,
+    /// or other holders are waiting for it. If `holder` already holds
+    /// the lease, it is renewed.
+    Acquire {
+        name: String,
+        holder: ActorAddr,
+        ttl: Duration,
+        #[reply]
+        reply: OncePortRef<Option<Lease>>,
+    },
+    /// Wait for the lease `name` for `holder`, for `ttl`, and send the
+    /// grant to `reply`. Waiting holders are granted the lease in the
+    /// order they asked for it. If `holder` already holds the lease,
+    /// it is renewed.
+    Wait {
+        name: String,
+        holder: ActorAddr,
+        ttl: Duration,
+        reply: OncePortRef<Lease>,
+    },
+    /// Extend the lease `name`, granted with `token`, to expire `ttl`
+    /// from now. Replies with the renewed lease, or `None` if the
+    /// lease has lapsed or been granted to another holder.
+    Renew {
+        name: String,
+        token: u64,
+        ttl: Duration,
+        #[reply]
+        reply: OncePortRef<Option<Lease>>,
+    },
+    /// Release the lease `name`, granted with `token`. Replies with
+    /// whether the lease was held with that token.
+    Release {
+        name: String,
+        token: u64,
+        #[reply]
+        reply: OncePortRef<bool>,
+    },
+    /// Reply with the unexpired lease `name`, if any.
+    Holder {
+        name: String,
+        #[reply]
+        reply: OncePortRef<Option<Lease>>,
+    },
+}
+wirevalue::register_type!(LeaseMessage);
+
+/// Self-message that lapses the lease `name` if it is still held with
+/// `token` and has expired.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named, Bind, Unbind)]
+struct Expire {
+    name: String,
+    token: u64,
+}
+
+/// Periodic self-message that checks that the holders are reachable.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named, Bind, Unbind)]
+struct CheckHolders;
+
+/// Parameters for [`LeaseActor`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Named)]
+pub struct LeaseActorParams {
+    /// A file in which the actor records the fencing token of its
+    /// latest grant, so that a restarted actor keeps granting greater
+    /// tokens. Without one, tokens are seeded from the wall clock,
+    /// which keeps them increasing only as long as it does.
+    pub token_file: Option<String>,
+}
+wirevalue::register_type!(LeaseActorParams);
+
+/// A lease as held by the lease actor.
+#[derive(Debug)]
+struct Held {
+    holder: ActorAddr,
+    token: u64,
+    expires_at: Instant,
+}
+
+impl Held {
+    fn lease(&self, name: &str, now: Instant) -> Lease {
+        Lease {
+            name: name.to_string(),
+            holder: self.holder.clone(),
+            token: self.token,
+            ttl: self.expires_at.saturating_duration_since(now),
+        }
+    }
+}
+
+/// A holder waiting for a lease.
+#[derive(Debug)]
+struct Waiter {
+    holder: ActorAddr,
+    ttl: Duration,
+    reply: OncePortRef<Lease>,
+}
+
+/// An actor that grants named leases; see the
+/// [module documentation](self).
+///
+/// Holders are checked by querying them for introspection, which
+/// actors answer, but plain [`hyperactor::Proc::client`] instances do
+/// not: leases should be held by actors.
+#[derive(Debug)]
+#[hyperactor::export(handlers = [LeaseMessage])]
+#[hyperactor::spawnable]
+pub struct LeaseActor {
+    leases: HashMap<String, Held>,
+    /// The holders waiting for each lease, in the order they asked.
+    waiting: HashMap<String, VecDeque<Waiter>>,
+    /// The fencing token of the latest grant.
+    last_token: u64,
+    token_file: Option<String>,
+}
+
+#[async_trait]
+impl RemoteSpawn for LeaseActor {
+    type Params = LeaseActorParams;
+
+    async fn new(params: LeaseActorParams, _environment: Flattrs) -> anyhow::Result<Self> {
+        Self::new(params)
+    }
+}
+
+#[async_trait]
+impl Actor for LeaseActor {
+    async fn init(&mut self, this: &Instance<Self>) -> Result<(), anyhow::Error> {
+        this.post_after(
+            this,
+            CheckHolders,
+            hyperactor_config::global::get(LEASE_HOLDER_CHECK_INTERVAL),
+        );
+        Ok(())
+    }
+
+    async fn handle_undeliverable_message(
+        &mut self,
+        cx: &Instance<Self>,
+        reason: UndeliverableReason,
+        undeliverable: Undeliverable<MessageEnvelope>,
+    ) -> Result<(), anyhow::Error> {
+        let Some(returned) = undeliverable.as_message() else {
+            return handle_undeliverable_message(cx, reason, undeliverable);
+        };
+        if let Some(true) = returned.headers().get(LEASE_HOLDER_CHECK) {
+            let holder = returned.dest().actor_id().clone();
+            let names: Vec<_> = self
+                .leases
+                .iter()
+                .filter(|(_, held)| held.holder.id() == &holder)
+                .map(|(name, _)| name.clone())
+                .collect();
+            for name in names {
+                let held = self.leases.remove(&name).unwrap();
+                tracing::warn!(
+                    "lease {}: released, as its holder {} has stopped (token {})",
+                    name,
+                    held.holder,
+                    held.token
+                );
+                self.grant_next(cx, &name).await?;
+            }
+            for waiters in self.waiting.values_mut() {
+                waiters.retain(|waiter| waiter.holder.id() != &holder);
+            }
+            Ok(())
+        } else if let Some(token) = returned.headers().get(LEASE_GRANT_TOKEN) {
+            let Some(name) = self
+                .leases
+                .iter()
+                .find(|(_, held)| held.token == token)
+                .map(|(name, _)| name.clone())
+            else {
+                return Ok(());
+            };
+            let held = self.leases.remove(&name).unwrap();
+            tracing::warn!(
+                "lease {}: released, as {} stopped waiting for it (token {})",
+                name,
+                held.holder,
+                held.token
+            );
+            self.grant_next(cx, &name).await
+        } else {
+            handle_undeliverable_message(cx, reason, undeliverable)
+        }
+    }
+}
+
+impl LeaseActor {
+    /// A lease actor with `params`, whose tokens continue from the
+    /// token file, if any.
+    pub fn new(params: LeaseActorParams) -> anyhow::Result<Self> {
+        let recorded = match &params.token_file {
+            Some(path) => match std::fs::read_to_string(path) {
+                Ok(contents) => contents
+                    .trim()
+                    .parse::<u64>()
+                    .with_context(|| format!("invalid lease token file {}", path))?,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => 0,
+                Err(err) => {
+                    return Err(err).with_context(|| format!("reading lease token file {}", path));
+                }
+            },
+            None => 0,
+        };
+        let clock = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_micros() as u64);
+        Ok(Self {
+            leases: HashMap::new(),
+            waiting: HashMap::new(),
+            last_token: recorded.max(clock),
+            token_file: params.token_file,
+        })
+    }
+
+    /// The next fencing token, recorded in the token file, if any,
+    /// before it is used.
+    async fn next_token(&mut self) -> anyhow::Result<u64> {
+        let token = self.last_token + 1;
+        if let Some(path) = &self.token_file {
+            let staged = format!("{}.tmp", path);
+            tokio::fs::write(&staged, format!("{}\n", token))
+                .await
+                .with_context(|| format!("writing lease token file {}", staged))?;
+            tokio::fs::rename(&staged, path)
+                .await
+                .with_context(|| format!("writing lease token file {}", path))?;
+        }
+        self.last_token = token;
+        Ok(token)
+    }
+
+    /// Grant the lease `name` to `holder`, for `ttl`.
+    async fn grant(
+        &mut self,
+        cx: &Instance<Self>,
+        name: String,
+        holder: ActorAddr,
+        ttl: Duration,
+    ) -> anyhow::Result<Lease> {
+        let token = self.next_token().await?;
+        let now = Instant::now();
+        let held = Held {
+            holder,
+            token,
+            expires_at: now + ttl,
+        };
+        tracing::info!(
+            "lease {}: granted to {} (token {})",
+            name,
+            held.holder,
+            held.token
+        );
+        cx.post_after(
+            cx,
+            Expire {
+                name: name.clone(),
+                token,
+            },
+            ttl,
+        );
+        let lease = held.lease(&name, now);
+        self.leases.insert(name, held);
+        Ok(lease)
+    }
+
+    /// Grant the lease `name`, if it is not held, to the first holder
+    /// waiting for it.
+    async fn grant_next(&mut self, cx: &Instance<Self>, name: &str) -> anyhow::Result<()> {
+        if self.leases.contains_key(name) {
+            return Ok(());
+        }
+        let Some(waiters) = self.waiting.get_mut(name) else {
+            return Ok(());
+        };
+        let waiter = waiters.pop_front();
+        if waiters.is_empty() {
+            self.waiting.remove(name);
+        }
+        let Some(waiter) = waiter else {
+            return Ok(());
+        };
+        let lease = self
+            .grant(cx, name.to_string(), waiter.holder, waiter.ttl)
+            .await?;
+        let mut headers = Flattrs::new();
+        headers.set(LEASE_GRANT_TOKEN, lease.token);
+        waiter.reply.post_with_headers(cx, headers, lease);
+        Ok(())
+    }
+
+    /// Drop the lease `name` if it has expired, and grant it to the
+    /// first holder waiting for it.
+    async fn lapse(&mut self, cx: &Instance<Self>, name: &str) -> anyhow::Result<()> {
+        if self
+            .leases
+            .get(name)
+            .is_some_and(|held| held.expires_at <= Instant::now())
+        {
+            let lapsed = self.leases.remove(name).unwrap();
+            tracing::info!(
+                "lease {}: lapsed for {} (token {})",
+                name,
+                lapsed.holder,
+                lapsed.token
+            );
+            self.grant_next(cx, name).await?;
+        }
+        Ok(())
+    }
+
+    /// Renew the lease `name` if `holder` holds it.
+    fn renew_held(&mut self, name: &str, holder: &ActorAddr, ttl: Duration) -> Option<Lease> {
+        let now = Instant::now();
+        let held = self
+            .leases
+            .get_mut(name)
+            .filter(|held| held.holder == *holder)?;
+        held.expires_at = now + ttl;
+        Some(held.lease(name, now))
+    }
+}
+
+#[async_trait]
+#[hyperactor::handle(LeaseMessage)]
+impl LeaseMessageHandler for LeaseActor {
+    async fn acquire(
+        &mut self,
+        cx: &Context<Self>,
+        name: String,
+        holder: ActorAddr,
+        ttl: Duration,
+    ) -> Result<Option<Lease>, anyhow::Error> {
+        self.lapse(cx, &name).await?;
+        if let Some(lease) = self.renew_held(&name, &holder, ttl) {
+            return Ok(Some(lease));
+        }
+        if self.leases.contains_key(&name) || self.waiting.contains_key(&name) {
+            return Ok(None);
+        }
+        Ok(Some(self.grant(cx, name, holder, ttl).await?))
+    }
+
+    async fn wait(
+        &mut self,
+        cx: &Context<Self>,
+        name: String,
+        holder: ActorAddr,
+        ttl: Duration,
+        reply: OncePortRef<Lease>,
+    ) -> Result<(), anyhow::Error> {
+        self.lapse(cx, &name).await?;
+        if let Some(lease) = self.renew_held(&name, &holder, ttl) {
+            reply.post(cx, lease);
+            return Ok(());
+        }
+        let waiters = self.waiting.entry(name.clone()).or_default();
+        // A holder that asks again keeps its place.
+        match waiters.iter_mut().find(|waiter| waiter.holder == holder) {
+            Some(waiter) => {
+                waiter.ttl = ttl;
+                waiter.reply = reply;
+            }
+            None => waiters.push_back(Waiter { holder, ttl, reply }),
+        }
+        self.grant_next(cx, &name).await
+    }
+
+    async fn renew(
+        &mut self,
+        cx: &Context<Self>,
+        name: String,
+        token: u64,
+        ttl: Duration,
+    ) -> Result<Option<Lease>, anyhow::Error> {
+        self.lapse(cx, &name).await?;
+        let now = Instant::now();
+        Ok(match self.leases.get_mut(&name) {
+            Some(held) if held.token == token => {
+                held.expires_at = now + ttl;
+                Some(held.lease(&name, now))
+            }
+            _ => None,
+        })
+    }
+
+    async fn release(
+        &mut self,
+        cx: &Context<Self>,
+        name: String,
+        token: u64,
+    ) -> Result<bool, anyhow::Error> {
+        self.lapse(cx, &name).await?;
+        if !self
+            .leases
+            .get(&name)
+            .is_some_and(|held| held.token == token)
+        {
+            return Ok(false);
+        }
+        self.leases.remove(&name);
+        tracing::info!("lease {}: released (token {})", name, token);
+        self.grant_next(cx, &name).await?;
+        Ok(true)
+    }
+
+    async fn holder(
+        &mut self,
+        cx: &Context<Self>,
+        name: String,
+    ) -> Result<Option<Lease>, anyhow::Error> {
+        self.lapse(cx, &name).await?;
+        let now = Instant::now();
+        Ok(self.leases.get(&name).map(|held| held.lease(&name, now)))
+    }
+}
+
+#[async_trait]
+impl Handler<Expire> for LeaseActor {
+    async fn handle(&mut self, cx: &Context<Self>, expire: Expire) -> Result<(), anyhow::Error> {
+        let Some(held) = self
+            .leases
+            .get(&expire.name)
+            .filter(|held| held.token == expire.token)
+        else {
+            return Ok(());
+        };
+        let remaining = held.expires_at.saturating_duration_since(Instant::now());
+        if !remaining.is_zero() {
+            // Renewed since the grant: check again when it expires.
+            cx.post_after(cx, expire, remaining);
+            return Ok(());
+        }
+        self.lapse(cx, &expire.name).await
+    }
+}
+
+#[async_trait]
+impl Handler<CheckHolders> for LeaseActor {
+    async fn handle(&mut self, cx: &Context<Self>, _: CheckHolders) -> Result<(), anyhow::Error> {
+        // Actors answer introspection queries from the actor runtime,
+        // whatever their type. The answer itself is dropped: a query
+        // returned as undeliverable is what shows that the holder has
+        // stopped.
+        let holders: HashSet<_> = self.leases.values().map(|held| &held.holder).collect();
+        for holder in holders {
+            let port =
+                PortRef::<IntrospectMessage>::attest_control_port(holder, ControlPort::Introspect);
+            let (reply, _) = open_once_port::<IntrospectResult>(cx);
+            let mut reply = reply.bind();
+            reply.return_undeliverable(false);
+            let mut headers = Flattrs::new();
+            headers.set(LEASE_HOLDER_CHECK, true);
+            port.post_with_headers(
+                cx,
+                headers,
+                IntrospectMessage::Query {
+                    view: IntrospectView::Actor,
+                    reply,
+                },
+            );
+        }
+
+        cx.post_after(
+            cx,
+            CheckHolders,
+            hyperactor_config::global::get(LEASE_HOLDER_CHECK_INTERVAL),
+        );
+        Ok(())
+    }
+}
+
+/// Leader election for a role, with a [`LeaseActor`]; see the
+/// [module documentation](self).
+///
+/// A leader should [renew](LeaderElection::renew) its lease well
+/// within its TTL (say, every third of it), and stop acting as leader
+/// as soon as a renewal fails.
+#[derive(Debug, Clone)]
+pub struct LeaderElection {
+    leases: ActorRef<LeaseActor>,
+    role: String,
+    ttl: Duration,
+}
+
+impl LeaderElection {
+    /// Elect leaders for `role`, whose leases last `ttl`.
+    pub fn new(leases: ActorRef<LeaseActor>, role: &str, ttl: Duration) -> Self {
+        Self {
+            leases,
+            role: role.to_string(),
+            ttl,
+        }
+    }
+
+    /// Wait until `cx` is elected leader, returning its lease.
+    /// Candidates are elected in the order they started campaigning.
+    pub async fn campaign(&self, cx: &impl context::Actor) -> anyhow::Result<Lease> {
+        let (reply, lease) = open_once_port::<Lease>(cx);
+        self.leases
+            .wait(
+                cx,
+                self.role.clone(),
+                cx.instance().self_addr().clone(),
+                self.ttl,
+                reply.bind(),
+            )
+            .await?;
+        Ok(lease.recv().await?)
+    }
+
+    /// Renew the leader's `lease`. Returns the renewed lease, or `None`
+    /// if leadership was lost.
+    pub async fn renew(
+        &self,
+        cx: &impl context::Actor,
+        lease: &Lease,
+    ) -> anyhow::Result<Option<Lease>> {
+        self.leases
+            .renew(cx, self.role.clone(), lease.token, self.ttl)
+            .await
+    }
+
+    /// Give up leadership held with `lease`.
+    pub async fn resign(&self, cx: &impl context::Actor, lease: &Lease) -> anyhow::Result<()> {
+        self.leases
+            .release(cx, self.role.clone(), lease.token)
+            .await?;
+        Ok(())
+    }
+
+    /// The current leader's lease, if there is a leader.
+    pub async fn leader(&self, cx: &impl context::Actor) -> anyhow::Result<Option<Lease>> {
+        self.leases.holder(cx, self.role.clone()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor::Proc;
+
+    use super::*;
+
+    fn lease_actor() -> LeaseActor {
+        LeaseActor::new(LeaseActorParams::default()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_lease_lifecycle() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let leases = proc.spawn(lease_actor());
+        let a = proc.client("a").self_addr().clone();
+        let b = proc.client("b").self_addr().clone();
+        let ttl = Duration::from_millis(200);
+
+        let first = leases
+            .acquire(&client, "lock".to_string(), a.clone(), ttl)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.holder, a);
+        assert!(
+            leases
+                .acquire(&client, "lock".to_string(), b.clone(), ttl)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        // Re-acquiring renews, keeping the token.
+        let renewed = leases
+            .acquire(&client, "lock".to_string(), a.clone(), ttl)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(renewed.token, first.token);
+
+        // Once the lease lapses, it is granted to the next holder with
+        // a greater token, and the lapsed holder can no longer renew.
+        tokio::time::sleep(ttl * 2).await;
+        let second = leases
+            .acquire(&client, "lock".to_string(), b.clone(), ttl)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(second.token > first.token);
+        assert!(
+            leases
+                .renew(&client, "lock".to_string(), first.token, ttl)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        assert!(
+            !leases
+                .release(&client, "lock".to_string(), first.token)
+                .await
+                .unwrap()
+        );
+        assert!(
+            leases
+                .release(&client, "lock".to_string(), second.token)
+                .await
+                .unwrap()
+        );
+        assert!(
+            leases
+                .holder(&client, "lock".to_string())
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_waiters_are_granted_in_order() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let leases = proc.spawn(lease_actor());
+        let holders: Vec<_> = ["a", "b", "c"]
+            .into_iter()
+            .map(|name| proc.client(name).self_addr().clone())
+            .collect();
+        let ttl = Duration::from_secs(60);
+
+        let mut grants = Vec::new();
+        for holder in &holders {
+            let (reply, grant) = open_once_port::<Lease>(&client);
+            leases
+                .wait(
+                    &client,
+                    "lock".to_string(),
+                    holder.clone(),
+                    ttl,
+                    reply.bind(),
+                )
+                .await
+                .unwrap();
+            grants.push(grant);
+        }
+        // A holder that does not wait cannot jump the queue.
+        let d = proc.client("d").self_addr().clone();
+        assert!(
+            leases
+                .acquire(&client, "lock".to_string(), d, ttl)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let mut last_token = 0;
+        for (holder, grant) in holders.iter().zip(grants) {
+            let lease = grant.recv().await.unwrap();
+            assert_eq!(&lease.holder, holder);
+            assert!(lease.token > last_token);
+            last_token = lease.token;
+            assert!(
+                leases
+                    .release(&client, "lock".to_string(), lease.token)
+                    .await
+                    .unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lease_released_when_holder_stops() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let leases = proc.spawn(lease_actor());
+        let holder = proc.spawn(lease_actor());
+        let waiter = proc.client("waiter").self_addr().clone();
+        let ttl = Duration::from_secs(60);
+
+        let lease = leases
+            .acquire(
+                &client,
+                "lock".to_string(),
+                holder.actor_addr().clone(),
+                ttl,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        let (reply, grant) = open_once_port::<Lease>(&client);
+        leases
+            .wait(
+                &client,
+                "lock".to_string(),
+                waiter.clone(),
+                ttl,
+                reply.bind(),
+            )
+            .await
+            .unwrap();
+
+        holder.drain_and_stop("test").unwrap();
+        holder.await;
+        leases.post(&client, CheckHolders);
+
+        // The lease is released well within its TTL, and granted to
+        // the waiting holder.
+        let next = grant.recv().await.unwrap();
+        assert_eq!(next.holder, waiter);
+        assert!(next.token > lease.token);
+    }
+
+    #[tokio::test]
+    async fn test_tokens_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let params = LeaseActorParams {
+            token_file: Some(dir.path().join("tokens").to_string_lossy().into_owned()),
+        };
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let holder = proc.client("holder").self_addr().clone();
+        let ttl = Duration::from_secs(60);
+
+        let leases = proc.spawn(LeaseActor::new(params.clone()).unwrap());
+        let first = leases
+            .acquire(&client, "lock".to_string(), holder.clone(), ttl)
+            .await
+            .unwrap()
+            .unwrap();
+        leases.drain_and_stop("test").unwrap();
+        leases.await;
+
+        // A restarted actor continues from the recorded token, even
+        // if the wall clock has gone back.
+        let mut restarted = LeaseActor::new(params).unwrap();
+        assert!(restarted.last_token >= first.token);
+        restarted.last_token = first.token;
+        let leases = proc.spawn(restarted);
+        let second = leases
+            .acquire(&client, "lock".to_string(), holder, ttl)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(second.token, first.token + 1);
+    }
+
+    #[tokio::test]
+    async fn test_leader_election() {
+        let proc = Proc::isolated();
+        let leases: ActorRef<LeaseActor> = proc.spawn(lease_actor()).bind();
+        let election = LeaderElection::new(leases, "controller", Duration::from_secs(10));
+        let first = proc.client("first");
+        let second = proc.client("second");
+
+        let lease = election.campaign(&first).await.unwrap();
+        assert_eq!(&lease.holder, first.self_addr());
+        let campaign = {
+            let election = election.clone();
+            tokio::spawn(async move { election.campaign(&second).await.unwrap() })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!campaign.is_finished());
+        assert_eq!(
+            election.leader(&first).await.unwrap().unwrap().token,
+            lease.token
+        );
+
+        election.resign(&first, &lease).await.unwrap();
+        let next = campaign.await.unwrap();
+        assert!(next.token > lease.token);
+        assert!(election.renew(&first, &lease).await.unwrap().is_none());
+    }
+}
@@ -22,33 +22,50 @@
 #![allow(unused_assignments)]
 
 pub mod actor_mesh;
+pub mod alert;
 mod assign;
 pub mod bootstrap;
+pub mod broadcast_var;
 pub mod casting;
+pub mod chaos;
+pub mod cluster;
 pub mod comm;
 pub mod config;
 pub mod config_dump;
 pub mod connect;
+pub mod counted_ref;
+pub mod gather;
 pub mod global_context;
+pub mod handshake;
 pub mod host;
 pub mod host_mesh;
+pub mod ingest;
 pub mod introspect;
+pub mod kubernetes;
+pub mod lease;
 pub mod logging;
 pub mod mesh;
 pub mod mesh_admin;
 pub mod mesh_admin_client;
+pub mod mesh_clock;
 pub mod mesh_controller;
 pub mod mesh_id;
 pub mod mesh_selection;
 mod metrics;
+pub mod placement;
+pub mod plugin;
 pub mod proc_agent;
 pub mod proc_launcher;
 pub mod proc_mesh;
 pub mod pyspy;
 pub mod reference;
+pub mod registry;
+pub mod reservations;
 pub mod resource;
+pub mod routing_audit;
 pub mod shared_cell;
 pub mod shortuuid;
+pub mod slurm;
 pub mod supervision;
 #[cfg(target_os = "linux")]
 mod systemd;
@@ -56,10 +73,13 @@ pub mod test_utils;
 pub mod testactor;
 pub mod testing;
 mod testresource;
+pub mod timing;
+pub mod topology;
 pub mod transport;
 pub mod value_mesh {
     pub use hyperactor::value_mesh::*;
 }
+pub mod wasm;
 
 use std::io;
 
@@ -82,6 +102,7 @@ use hyperactor::ActorAddr;
 use hyperactor::ActorRef;
 use hyperactor::ProcAddr;
 use hyperactor::mailbox::MailboxSenderError;
+pub use hyperactor_mesh_macros::MeshClient;
 pub use hyperactor_mesh_macros::sel;
 pub use mesh::Mesh;
 // Re-exported for internal test binaries that don't have ndslice as a direct dependency
@@ -164,6 +185,9 @@ pub enum Error {
     #[error("error while spawning actor {0}: {1}")]
     GspawnError(mesh_id::ActorMeshId, String),
 
+    #[error("error while spawning actor on proc {0}: {1}")]
+    RemoteSpawnError(ProcAddr, proc_agent::SpawnActorError),
+
     #[error("error while sending message to actor {0}: {1}")]
     SendingError(ActorAddr, Box<MailboxSenderError>),
 
@@ -179,6 +203,15 @@ pub enum Error {
     #[error(transparent)]
     ConfigPushFailed(#[from] crate::host_mesh::ConfigPushError),
 
+    #[error(transparent)]
+    KubernetesError(#[from] crate::kubernetes::KubernetesError),
+
+    #[error(transparent)]
+    PlacementError(#[from] crate::placement::PlacementError),
+
+    #[error(transparent)]
+    SlurmError(#[from] crate::slurm::SlurmError),
+
     #[error(
         "error creating proc (host rank {host_rank}) on host mesh agent {mesh_agent}, state: {state}"
     )]
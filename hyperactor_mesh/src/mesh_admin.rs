@@ -380,6 +380,7 @@ use crate::introspect::NodePayload;
 use crate::introspect::NodeProperties;
 use crate::introspect::dto::NodePayloadDto;
 use crate::introspect::to_node_payload;
+use crate::mesh_clock::MeshClock;
 use crate::proc_agent::PROC_AGENT_ACTOR_NAME;
 use crate::proc_agent::ProcAgent;
 use crate::pyspy::PySpyDump;
@@ -389,6 +390,14 @@ use crate::pyspy::PySpyProfileOpts;
 use crate::pyspy::PySpyProfileResult;
 use crate::pyspy::PySpyResult;
 use crate::pyspy::ValidatedProfileRequest;
+use crate::reservations::ReservationsDump;
+use crate::reservations::ReservationsResult;
+use crate::routing_audit::RoutingAuditDump;
+use crate::routing_audit::RoutingAuditResult;
+use crate::timing::TimingDump;
+use crate::timing::TimingResult;
+use crate::topology::TopologyDump;
+use crate::topology::TopologyResult;
 
 /// Send an `IntrospectMessage` to an actor and receive the reply.
 /// Encapsulates open_once_port + send + timeout + error handling.
@@ -873,9 +882,9 @@ fn build_http_client() -> reqwest::Client {
 /// Implements [`axum::serve::Listener`] so it can be passed directly
 /// to [`axum::serve`].  Per the trait contract, `accept` handles
 /// errors internally (logging + retrying) and never returns `Err`.
-struct TlsListener {
-    tcp: TcpListener,
-    acceptor: TlsAcceptor,
+pub(crate) struct TlsListener {
+    pub(crate) tcp: TcpListener,
+    pub(crate) acceptor: TlsAcceptor,
 }
 
 impl axum::serve::Listener for TlsListener {
@@ -1357,11 +1366,25 @@ impl MeshAdminAgent {
         )
         .await?;
 
-        Ok(crate::introspect::to_node_payload_with(
+        // The proc's agent answered, so its clock can be synchronized;
+        // the timestamps of the proc's actors are then corrected by
+        // `resolve_actor_node`.
+        let clock = MeshClock::global();
+        if clock.offset(proc_id).is_none()
+            && let Err(e) = clock
+                .sync(cx, &ActorRef::<ProcAgent>::attest(mesh_agent_id))
+                .await
+        {
+            tracing::debug!(%proc_id, "mesh admin: failed to synchronize proc clock: {}", e);
+        }
+
+        let mut payload = crate::introspect::to_node_payload_with(
             result,
             crate::introspect::NodeRef::Proc(proc_id.clone()),
             Some(crate::introspect::NodeRef::Host(agent.actor_addr().clone())),
-        ))
+        );
+        correct_clock_skew(&mut payload, proc_id);
+        Ok(payload)
     }
 
     /// Resolve a standalone proc into a proc-level `NodePayload`.
@@ -1563,11 +1586,34 @@ impl MeshAdminAgent {
                 payload.parent = Some(crate::introspect::NodeRef::Proc(proc_id.clone()));
             }
         }
+        correct_clock_skew(&mut payload, &proc_id);
 
         Ok(payload)
     }
 }
 
+/// Map the timestamps of `payload`, taken by the clock of `proc`, to
+/// the local clock, using the offset estimated by
+/// [`MeshClock::global`]. Timestamps of procs whose clock has not been
+/// synchronized are left unchanged.
+fn correct_clock_skew(payload: &mut NodePayload, proc: &ProcAddr) {
+    let clock = MeshClock::global();
+    payload.as_of = clock.to_local(proc, payload.as_of);
+    if let NodeProperties::Actor {
+        created_at,
+        failure_info,
+        ..
+    } = &mut payload.properties
+    {
+        if let Some(created_at) = created_at {
+            *created_at = clock.to_local(proc, *created_at);
+        }
+        if let Some(failure_info) = failure_info {
+            failure_info.occurred_at = clock.to_local(proc, failure_info.occurred_at);
+        }
+    }
+}
+
 /// Build the Axum router for the mesh admin HTTP server.
 ///
 /// Routes:
@@ -1580,6 +1626,10 @@ impl MeshAdminAgent {
 /// - `POST /v1/pyspy_dump/{*proc_reference}` — py-spy dump + store in Datafusion.
 /// - `POST /v1/pyspy_profile_svg/{*proc_reference}` — py-spy profile → SVG flamegraph.
 /// - `GET /v1/config/{*proc_reference}` — config snapshot for a proc.
+/// - `GET /v1/routing/{*proc_reference}` — routing audit log for a proc.
+/// - `GET /v1/reservations/{*proc_reference}` — resource reservations of a proc.
+/// - `GET /v1/timing/{*proc_reference}` — actor handler timing of a proc.
+/// - `GET /v1/topology/{*proc_reference}` — routing topology of a proc's process.
 /// - `GET /v1/admin` — admin self-identification (`AdminInfo`).
 /// - `GET /v1/{*reference}` — JSON `NodePayload` for a single reference.
 /// - `GET /SKILL.md` — agent-facing API documentation (markdown).
@@ -1604,6 +1654,13 @@ fn create_mesh_admin_router(bridge_state: Arc<BridgeState>) -> Router {
             post(pyspy_profile_svg),
         )
         .route("/v1/config/{*proc_reference}", get(config_bridge))
+        .route("/v1/routing/{*proc_reference}", get(routing_audit_bridge))
+        .route(
+            "/v1/reservations/{*proc_reference}",
+            get(reservations_bridge),
+        )
+        .route("/v1/timing/{*proc_reference}", get(timing_bridge))
+        .route("/v1/topology/{*proc_reference}", get(topology_bridge))
         .route("/v1/{*reference}", get(resolve_reference_bridge))
         .with_state(bridge_state)
 }
@@ -1800,6 +1857,155 @@ pub fn build_openapi_spec() -> serde_json::Value {
         }
     });
 
+    let routing_audit_payload = serde_json::json!({
+        "description": "RoutingAuditResult — whether auditing is enabled, and the recorded decisions",
+        "content": {
+            "application/json": {
+                "schema": {
+                    "type": "object",
+                    "properties": {
+                        "enabled": { "type": "boolean" },
+                        "entries": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "time": { "type": "string" },
+                                    "router": { "type": "string" },
+                                    "dest": { "type": "string" },
+                                    "route": {
+                                        "type": "string",
+                                        "enum": ["bound", "direct", "default", "unroutable"]
+                                    },
+                                    "binding": { "type": ["string", "null"] }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    let resources_schema = serde_json::json!({
+        "type": "object",
+        "properties": {
+            "memory_bytes": { "type": "integer", "format": "uint64", "minimum": 0 },
+            "gpus": { "type": "integer", "format": "uint64", "minimum": 0 },
+            "cpu_shares": { "type": "integer", "format": "uint64", "minimum": 0 }
+        }
+    });
+    let reservations_payload = serde_json::json!({
+        "description": "ReservationsResult — the proc's capacity, admission policy, and reservations",
+        "content": {
+            "application/json": {
+                "schema": {
+                    "type": "object",
+                    "properties": {
+                        "capacity": {
+                            "type": "object",
+                            "properties": {
+                                "memory_bytes": { "type": ["integer", "null"], "format": "uint64", "minimum": 0 },
+                                "gpus": { "type": ["integer", "null"], "format": "uint64", "minimum": 0 },
+                                "cpu_shares": { "type": ["integer", "null"], "format": "uint64", "minimum": 0 }
+                            }
+                        },
+                        "policy": { "type": "string", "enum": ["reject", "queue"] },
+                        "reserved": resources_schema.clone(),
+                        "reservations": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "actor_id": { "type": "string" },
+                                    "resources": resources_schema
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    let timing_payload = serde_json::json!({
+        "description": "TimingResult — the handler timing of the proc's running actors, by CPU time",
+        "content": {
+            "application/json": {
+                "schema": {
+                    "type": "object",
+                    "properties": {
+                        "actors": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "actor_id": { "type": "string" },
+                                    "actor_type": { "type": "string" },
+                                    "timing": {
+                                        "type": "object",
+                                        "properties": {
+                                            "messages_handled": { "type": "integer", "format": "uint64", "minimum": 0 },
+                                            "mean_latency_us": { "type": "integer", "format": "uint64", "minimum": 0 },
+                                            "p99_latency_us": { "type": "integer", "format": "uint64", "minimum": 0 },
+                                            "cpu_time_us": { "type": "integer", "format": "uint64", "minimum": 0 }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    let topology_payload = serde_json::json!({
+        "description": "TopologyResult — the routing topology of the proc's process, as a graph",
+        "content": {
+            "application/json": {
+                "schema": {
+                    "type": "object",
+                    "properties": {
+                        "topology": {
+                            "type": "object",
+                            "properties": {
+                                "nodes": {
+                                    "type": "object",
+                                    "additionalProperties": {
+                                        "type": "object",
+                                        "properties": {
+                                            "kind": {
+                                                "type": "string",
+                                                "enum": ["Gateway", "Proc", "Actor", "Port", "Router", "FallbackRouter", "DialRouter", "Channel", "Sender"]
+                                            },
+                                            "label": { "type": "string" }
+                                        }
+                                    }
+                                },
+                                "edges": {
+                                    "type": "array",
+                                    "items": {
+                                        "type": "object",
+                                        "properties": {
+                                            "from": { "type": "string" },
+                                            "to": { "type": "string" },
+                                            "kind": {
+                                                "type": "string",
+                                                "enum": ["Attached", "Serves", "Peer", "Forwards", "Binding", "Cast"]
+                                            },
+                                            "label": { "type": ["string", "null"] }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    });
+
     let mut spec = serde_json::json!({
         "openapi": "3.1.0",
         "info": {
@@ -1939,6 +2145,86 @@ pub fn build_openapi_spec() -> serde_json::Value {
                     }
                 }
             },
+            "/v1/routing/{proc_reference}": {
+                "get": {
+                    "summary": "Routing audit log for a proc",
+                    "operationId": "getRoutingAudit",
+                    "description": "Returns the most recent routing decisions of the target process's routers and muxers, oldest first. Decisions are recorded only while HYPERACTOR_ROUTING_AUDIT is enabled. Routes to ProcAgent (worker procs) or HostAgent (service proc).",
+                    "parameters": [{
+                        "name": "proc_reference",
+                        "in": "path",
+                        "required": true,
+                        "description": "URL-encoded proc reference (ProcAddr)",
+                        "schema": { "type": "string" }
+                    }],
+                    "responses": {
+                        "200": routing_audit_payload,
+                        "404": error_response("Proc not found or handler not reachable"),
+                        "500": error_response("Internal error"),
+                        "504": error_response("Gateway timeout")
+                    }
+                }
+            },
+            "/v1/reservations/{proc_reference}": {
+                "get": {
+                    "summary": "Resource reservations of a proc",
+                    "operationId": "getReservations",
+                    "description": "Returns the target proc's capacity and admission policy, and the resources reserved by its actors. Routes to ProcAgent (worker procs) or HostAgent (service proc).",
+                    "parameters": [{
+                        "name": "proc_reference",
+                        "in": "path",
+                        "required": true,
+                        "description": "URL-encoded proc reference (ProcAddr)",
+                        "schema": { "type": "string" }
+                    }],
+                    "responses": {
+                        "200": reservations_payload,
+                        "404": error_response("Proc not found or handler not reachable"),
+                        "500": error_response("Internal error"),
+                        "504": error_response("Gateway timeout")
+                    }
+                }
+            },
+            "/v1/timing/{proc_reference}": {
+                "get": {
+                    "summary": "Actor handler timing of a proc",
+                    "operationId": "getTiming",
+                    "description": "Returns the number of messages handled, the mean and p99 handler latency, and the cumulative handler CPU time of each running actor of the target proc, hottest first. Routes to ProcAgent (worker procs) or HostAgent (service proc).",
+                    "parameters": [{
+                        "name": "proc_reference",
+                        "in": "path",
+                        "required": true,
+                        "description": "URL-encoded proc reference (ProcAddr)",
+                        "schema": { "type": "string" }
+                    }],
+                    "responses": {
+                        "200": timing_payload,
+                        "404": error_response("Proc not found or handler not reachable"),
+                        "500": error_response("Internal error"),
+                        "504": error_response("Gateway timeout")
+                    }
+                }
+            },
+            "/v1/topology/{proc_reference}": {
+                "get": {
+                    "summary": "Routing topology of a proc's process",
+                    "operationId": "getTopology",
+                    "description": "Returns the routing topology of the process hosting the target proc: its gateway, attached procs and their actors, served channels, peers, and the routers through which it forwards. For a proc of a proc mesh, it also includes the Cast edges from the proc's comm actor to the comm actors it forwards casts to. Node ids are shared across processes, so the topologies of a mesh's procs can be merged. Routes to ProcAgent (worker procs) or HostAgent (service proc).",
+                    "parameters": [{
+                        "name": "proc_reference",
+                        "in": "path",
+                        "required": true,
+                        "description": "URL-encoded proc reference (ProcAddr)",
+                        "schema": { "type": "string" }
+                    }],
+                    "responses": {
+                        "200": topology_payload,
+                        "404": error_response("Proc not found or handler not reachable"),
+                        "500": error_response("Internal error"),
+                        "504": error_response("Gateway timeout")
+                    }
+                }
+            },
             "/v1/pyspy/{proc_reference}": {
                 "get": {
                     "summary": "Py-spy stack dump for a proc",
@@ -2265,6 +2551,114 @@ impl ResolvedProcHandler {
                 details: None,
             })
     }
+
+    async fn routing_audit_dump(
+        &self,
+        cx: &impl hyperactor::context::Actor,
+        timeout: std::time::Duration,
+    ) -> Result<RoutingAuditResult, ApiError> {
+        let (reply_handle, reply_rx) = open_once_port::<RoutingAuditResult>(cx);
+        let mut reply_ref = reply_handle.bind();
+        reply_ref.return_undeliverable(false);
+        let msg = RoutingAuditDump { result: reply_ref };
+        match self {
+            Self::Host(r) => r.post(cx, msg),
+            Self::Proc(r) => r.post(cx, msg),
+        };
+        tokio::time::timeout(timeout, reply_rx.recv())
+            .await
+            .map_err(|_| ApiError {
+                code: "gateway_timeout".to_string(),
+                message: "timed out waiting for routing audit".to_string(),
+                details: None,
+            })?
+            .map_err(|e| ApiError {
+                code: "internal_error".to_string(),
+                message: format!("failed to receive RoutingAuditResult: {}", e),
+                details: None,
+            })
+    }
+
+    async fn reservations_dump(
+        &self,
+        cx: &impl hyperactor::context::Actor,
+        timeout: std::time::Duration,
+    ) -> Result<ReservationsResult, ApiError> {
+        let (reply_handle, reply_rx) = open_once_port::<ReservationsResult>(cx);
+        let mut reply_ref = reply_handle.bind();
+        reply_ref.return_undeliverable(false);
+        let msg = ReservationsDump { result: reply_ref };
+        match self {
+            Self::Host(r) => r.post(cx, msg),
+            Self::Proc(r) => r.post(cx, msg),
+        };
+        tokio::time::timeout(timeout, reply_rx.recv())
+            .await
+            .map_err(|_| ApiError {
+                code: "gateway_timeout".to_string(),
+                message: "timed out waiting for reservations".to_string(),
+                details: None,
+            })?
+            .map_err(|e| ApiError {
+                code: "internal_error".to_string(),
+                message: format!("failed to receive ReservationsResult: {}", e),
+                details: None,
+            })
+    }
+
+    async fn timing_dump(
+        &self,
+        cx: &impl hyperactor::context::Actor,
+        timeout: std::time::Duration,
+    ) -> Result<TimingResult, ApiError> {
+        let (reply_handle, reply_rx) = open_once_port::<TimingResult>(cx);
+        let mut reply_ref = reply_handle.bind();
+        reply_ref.return_undeliverable(false);
+        let msg = TimingDump { result: reply_ref };
+        match self {
+            Self::Host(r) => r.post(cx, msg),
+            Self::Proc(r) => r.post(cx, msg),
+        };
+        tokio::time::timeout(timeout, reply_rx.recv())
+            .await
+            .map_err(|_| ApiError {
+                code: "gateway_timeout".to_string(),
+                message: "timed out waiting for timing".to_string(),
+                details: None,
+            })?
+            .map_err(|e| ApiError {
+                code: "internal_error".to_string(),
+                message: format!("failed to receive TimingResult: {}", e),
+                details: None,
+            })
+    }
+
+    async fn topology_dump(
+        &self,
+        cx: &impl hyperactor::context::Actor,
+        timeout: std::time::Duration,
+    ) -> Result<TopologyResult, ApiError> {
+        let (reply_handle, reply_rx) = open_once_port::<TopologyResult>(cx);
+        let mut reply_ref = reply_handle.bind();
+        reply_ref.return_undeliverable(false);
+        let msg = TopologyDump { result: reply_ref };
+        match self {
+            Self::Host(r) => r.post(cx, msg),
+            Self::Proc(r) => r.post(cx, msg),
+        };
+        tokio::time::timeout(timeout, reply_rx.recv())
+            .await
+            .map_err(|_| ApiError {
+                code: "gateway_timeout".to_string(),
+                message: "timed out waiting for topology".to_string(),
+                details: None,
+            })?
+            .map_err(|e| ApiError {
+                code: "internal_error".to_string(),
+                message: format!("failed to receive TopologyResult: {}", e),
+                details: None,
+            })
+    }
 }
 
 /// Parse + route + attest. No probe. The single `ActorRef::attest`
@@ -2594,6 +2988,62 @@ async fn config_bridge(
     Ok(Json(result))
 }
 
+/// HTTP bridge for routing audit requests.
+///
+/// Like `config_bridge`, there is no preflight probe.
+async fn routing_audit_bridge(
+    State(state): State<Arc<BridgeState>>,
+    AxumPath(proc_reference): AxumPath<String>,
+) -> Result<Json<RoutingAuditResult>, ApiError> {
+    let handler = route_proc_handler(&proc_reference)?;
+    let timeout =
+        hyperactor_config::global::get(crate::config::MESH_ADMIN_ROUTING_AUDIT_BRIDGE_TIMEOUT);
+    let result = handler
+        .routing_audit_dump(&state.bridge_cx, timeout)
+        .await?;
+    Ok(Json(result))
+}
+
+/// HTTP bridge for reservations requests.
+///
+/// Like `config_bridge`, there is no preflight probe.
+async fn reservations_bridge(
+    State(state): State<Arc<BridgeState>>,
+    AxumPath(proc_reference): AxumPath<String>,
+) -> Result<Json<ReservationsResult>, ApiError> {
+    let handler = route_proc_handler(&proc_reference)?;
+    let timeout =
+        hyperactor_config::global::get(crate::config::MESH_ADMIN_RESERVATIONS_BRIDGE_TIMEOUT);
+    let result = handler.reservations_dump(&state.bridge_cx, timeout).await?;
+    Ok(Json(result))
+}
+
+/// HTTP bridge for timing requests.
+///
+/// Like `config_bridge`, there is no preflight probe.
+async fn timing_bridge(
+    State(state): State<Arc<BridgeState>>,
+    AxumPath(proc_reference): AxumPath<String>,
+) -> Result<Json<TimingResult>, ApiError> {
+    let handler = route_proc_handler(&proc_reference)?;
+    let timeout = hyperactor_config::global::get(crate::config::MESH_ADMIN_TIMING_BRIDGE_TIMEOUT);
+    let result = handler.timing_dump(&state.bridge_cx, timeout).await?;
+    Ok(Json(result))
+}
+
+/// HTTP bridge for topology requests.
+///
+/// Like `config_bridge`, there is no preflight probe.
+async fn topology_bridge(
+    State(state): State<Arc<BridgeState>>,
+    AxumPath(proc_reference): AxumPath<String>,
+) -> Result<Json<TopologyResult>, ApiError> {
+    let handler = route_proc_handler(&proc_reference)?;
+    let timeout = hyperactor_config::global::get(crate::config::MESH_ADMIN_TOPOLOGY_BRIDGE_TIMEOUT);
+    let result = handler.topology_dump(&state.bridge_cx, timeout).await?;
+    Ok(Json(result))
+}
+
 /// Resolve an opaque reference string to a `NodePayload` via the
 /// actor-based resolver.
 ///
@@ -0,0 +1,300 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Clock offset estimation between procs.
+//!
+//! Timestamps recorded by different procs (in traces, or in the
+//! flight recorder) are taken from different clocks, and cannot be
+//! ordered reliably when these clocks are skewed. [`MeshClock`]
+//! estimates the offset of each remote proc's clock, NTP-style, by
+//! exchanging [`TimeSync`] messages with its [`ProcAgent`]:
+//!
+//! ```text
+//!   local   t0 ───────────────┐            ┌──────────► t3
+//!                    TimeSync │            │ TimeSyncReply
+//!   remote                    └─► t1    t2 ┘
+//! ```
+//!
+//! The offset of the remote clock is `((t1 - t0) + (t2 - t3)) / 2`,
+//! which is exact when both legs take the same time; its error is
+//! bounded by half the round trip `(t3 - t0) - (t2 - t1)`. Of several
+//! samples, the one with the shortest round trip is kept.
+//!
+//! Remote timestamps are then mapped to the local clock with
+//! [`MeshClock::to_local`]. The mesh admin synchronizes with each user
+//! proc it resolves, and so reports the timestamps of their actors
+//! (creation, failure) on its own clock.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::sync::RwLock;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use hyperactor::ActorRef;
+use hyperactor::HandleClient;
+use hyperactor::Handler;
+use hyperactor::ProcAddr;
+use hyperactor::RefClient;
+use hyperactor::context;
+use hyperactor::mailbox::open_once_port;
+use hyperactor_config::CONFIG;
+use hyperactor_config::ConfigAttr;
+use hyperactor_config::attrs::declare_attrs;
+use serde::Deserialize;
+use serde::Serialize;
+use typeuri::Named;
+
+use crate::proc_agent::ProcAgent;
+
+declare_attrs! {
+    /// The number of round trips exchanged per clock synchronization.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CLOCK_SYNC_SAMPLES".to_string()),
+        Some("mesh_clock_sync_samples".to_string()),
+    ))
+    pub attr MESH_CLOCK_SYNC_SAMPLES: usize = 8;
+
+    /// How long to wait for each clock synchronization reply.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_CLOCK_SYNC_TIMEOUT".to_string()),
+        Some("mesh_clock_sync_timeout".to_string()),
+    ))
+    pub attr MESH_CLOCK_SYNC_TIMEOUT: Duration = Duration::from_secs(5);
+}
+
+/// Errors synchronizing with a remote clock.
+#[derive(Debug, thiserror::Error)]
+pub enum ClockSyncError {
+    #[error("timed out waiting for a time sync reply from {0}")]
+    Timeout(ProcAddr),
+
+    #[error("failed to receive a time sync reply from {proc}: {source}")]
+    Receive {
+        proc: ProcAddr,
+        #[source]
+        source: anyhow::Error,
+    },
+}
+
+/// Request the current time of a proc.
+///
+/// Sent to a [`ProcAgent`] by [`MeshClock::sync`]; the handler replies
+/// with the time it received the request, and the time it replied.
+#[derive(Debug, Serialize, Deserialize, Named, Handler, HandleClient, RefClient)]
+pub struct TimeSync {
+    /// When the request was sent, by the requester's clock.
+    pub sent_at: SystemTime,
+    #[reply]
+    pub result: hyperactor::OncePortRef<TimeSyncReply>,
+}
+wirevalue::register_type!(TimeSync);
+
+/// Reply to a [`TimeSync`] request.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Named)]
+pub struct TimeSyncReply {
+    /// The request's `sent_at`, echoed back.
+    pub requested_at: SystemTime,
+    /// When the request was received, by the responder's clock.
+    pub received_at: SystemTime,
+    /// When the reply was sent, by the responder's clock.
+    pub sent_at: SystemTime,
+}
+wirevalue::register_type!(TimeSyncReply);
+
+impl TimeSyncReply {
+    /// Reply to a request sent at `requested_at`, received at
+    /// `received_at`.
+    pub fn new(requested_at: SystemTime, received_at: SystemTime) -> Self {
+        Self {
+            requested_at,
+            received_at,
+            sent_at: SystemTime::now(),
+        }
+    }
+}
+
+/// The estimated offset of a remote clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockOffset {
+    /// The offset of the remote clock, in microseconds: positive if it
+    /// is ahead of the local clock.
+    pub offset_us: i64,
+    /// The round trip of the sample the offset was estimated from.
+    /// The offset is accurate to within half of it.
+    pub round_trip: Duration,
+    /// When the sample was taken, by the local clock.
+    pub measured_at: SystemTime,
+}
+
+impl ClockOffset {
+    /// Estimate the offset of the remote clock from a reply received
+    /// at `received_at`, by the local clock.
+    pub fn estimate(reply: &TimeSyncReply, received_at: SystemTime) -> Self {
+        let t0 = micros(reply.requested_at);
+        let t1 = micros(reply.received_at);
+        let t2 = micros(reply.sent_at);
+        let t3 = micros(received_at);
+        let round_trip = ((t3 - t0) - (t2 - t1)).max(0);
+        Self {
+            offset_us: ((t1 - t0) + (t2 - t3)) / 2,
+            round_trip: Duration::from_micros(round_trip as u64),
+            measured_at: received_at,
+        }
+    }
+
+    /// Map `remote`, a timestamp taken by the remote clock, to the
+    /// local clock.
+    pub fn to_local(&self, remote: SystemTime) -> SystemTime {
+        let offset = Duration::from_micros(self.offset_us.unsigned_abs());
+        if self.offset_us >= 0 {
+            remote.checked_sub(offset).unwrap_or(remote)
+        } else {
+            remote + offset
+        }
+    }
+}
+
+/// Microseconds since the epoch; negative before it.
+fn micros(time: SystemTime) -> i64 {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(after) => after.as_micros() as i64,
+        Err(before) => -(before.duration().as_micros() as i64),
+    }
+}
+
+/// The estimated clock offsets of remote procs.
+///
+/// A process-wide instance is available through [`MeshClock::global`].
+#[derive(Debug, Default)]
+pub struct MeshClock {
+    offsets: RwLock<HashMap<ProcAddr, ClockOffset>>,
+}
+
+impl MeshClock {
+    /// The process-wide mesh clock.
+    pub fn global() -> &'static MeshClock {
+        static GLOBAL: OnceLock<MeshClock> = OnceLock::new();
+        GLOBAL.get_or_init(MeshClock::default)
+    }
+
+    /// Estimate the clock offset of the proc managed by `agent`, by
+    /// exchanging [`MESH_CLOCK_SYNC_SAMPLES`] round trips with it, and
+    /// record it.
+    pub async fn sync(
+        &self,
+        cx: &impl context::Actor,
+        agent: &ActorRef<ProcAgent>,
+    ) -> Result<ClockOffset, ClockSyncError> {
+        let proc = agent.actor_addr().proc_addr();
+        let samples = hyperactor_config::global::get(MESH_CLOCK_SYNC_SAMPLES).max(1);
+        let timeout = hyperactor_config::global::get(MESH_CLOCK_SYNC_TIMEOUT);
+
+        let mut best: Option<ClockOffset> = None;
+        for _ in 0..samples {
+            let (reply_handle, reply_rx) = open_once_port::<TimeSyncReply>(cx);
+            let mut reply_ref = reply_handle.bind();
+            reply_ref.return_undeliverable(false);
+            agent.post(
+                cx,
+                TimeSync {
+                    sent_at: SystemTime::now(),
+                    result: reply_ref,
+                },
+            );
+            let reply = tokio::time::timeout(timeout, reply_rx.recv())
+                .await
+                .map_err(|_| ClockSyncError::Timeout(proc.clone()))?
+                .map_err(|e| ClockSyncError::Receive {
+                    proc: proc.clone(),
+                    source: e.into(),
+                })?;
+            let sample = ClockOffset::estimate(&reply, SystemTime::now());
+            if best.is_none_or(|best| sample.round_trip < best.round_trip) {
+                best = Some(sample);
+            }
+        }
+
+        let offset = best.expect("at least one sample");
+        self.record(proc, offset);
+        Ok(offset)
+    }
+
+    /// Record the clock offset of `proc`.
+    pub fn record(&self, proc: ProcAddr, offset: ClockOffset) {
+        self.offsets.write().unwrap().insert(proc, offset);
+    }
+
+    /// The estimated clock offset of `proc`, if it has been
+    /// synchronized.
+    pub fn offset(&self, proc: &ProcAddr) -> Option<ClockOffset> {
+        self.offsets.read().unwrap().get(proc).copied()
+    }
+
+    /// Map `remote`, a timestamp taken by the clock of `proc`, to the
+    /// local clock. Timestamps of unsynchronized procs are returned
+    /// unchanged.
+    pub fn to_local(&self, proc: &ProcAddr, remote: SystemTime) -> SystemTime {
+        match self.offset(proc) {
+            Some(offset) => offset.to_local(remote),
+            None => remote,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        // The remote clock is 2s ahead; each leg takes 10ms, and the
+        // remote takes 1ms to reply.
+        let reply = TimeSyncReply {
+            requested_at: t0,
+            received_at: t0 + Duration::from_millis(2_010),
+            sent_at: t0 + Duration::from_millis(2_011),
+        };
+        let offset = ClockOffset::estimate(&reply, t0 + Duration::from_millis(21));
+        assert_eq!(offset.offset_us, 2_000_000);
+        assert_eq!(offset.round_trip, Duration::from_millis(20));
+        assert_eq!(
+            offset.to_local(t0 + Duration::from_secs(5)),
+            t0 + Duration::from_secs(3)
+        );
+
+        // The remote clock is 2s behind.
+        let reply = TimeSyncReply {
+            requested_at: t0,
+            received_at: t0 - Duration::from_millis(1_990),
+            sent_at: t0 - Duration::from_millis(1_989),
+        };
+        let offset = ClockOffset::estimate(&reply, t0 + Duration::from_millis(21));
+        assert_eq!(offset.offset_us, -2_000_000);
+        assert_eq!(offset.to_local(t0), t0 + Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_mesh_clock_to_local() {
+        let clock = MeshClock::default();
+        let proc = ProcAddr::singleton(hyperactor::channel::ChannelAddr::Local(1), "proc");
+        let now = SystemTime::now();
+        assert_eq!(clock.to_local(&proc, now), now);
+
+        clock.record(
+            proc.clone(),
+            ClockOffset {
+                offset_us: 500_000,
+                round_trip: Duration::from_millis(1),
+                measured_at: now,
+            },
+        );
+        assert_eq!(clock.to_local(&proc, now), now - Duration::from_millis(500));
+    }
+}
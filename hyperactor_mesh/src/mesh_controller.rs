@@ -49,6 +49,8 @@ use typeuri::Named;
 
 use crate::ValueMesh;
 use crate::actor_mesh::ActorMeshRef;
+use crate::alert;
+use crate::alert::Alert;
 use crate::bootstrap::ProcStatus;
 use crate::casting::CAST_ACTOR_MESH_ID;
 use crate::casting::update_undeliverable_envelope_for_casting;
@@ -289,6 +291,15 @@ fn send_state_change(
             %event,
             "detected supervision error on monitored mesh: name={mesh_name}",
         );
+        // Actor failures are raised by their proc's agent; only a
+        // failed proc, which has no agent left to report it, is raised
+        // here, once per rank.
+        if is_proc_stopped && !health_state.crashed_ranks.contains_key(&rank) {
+            alert::raise(Alert::MeshUnhealthy {
+                mesh: mesh_name.to_string(),
+                message: format!("rank {}: {}", rank, event),
+            });
+        }
     } else {
         tracing::debug!(
             name = "SupervisionEvent",
@@ -1720,6 +1731,7 @@ mod tests {
                 command: None,
                 config: Some(hyperactor_config::global::attrs()),
                 exit_on_shutdown: false,
+                hello: None,
             };
             boot.to_env(&mut cmd);
             cmd.kill_on_drop(false);
@@ -0,0 +1,513 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! GPU-topology-aware placement of procs.
+//!
+//! With [`PlacementPolicy::GpuTopology`], spawning a proc mesh (see
+//! [`HostMeshRef::spawn_with_placement`](crate::host_mesh::HostMeshRef::spawn_with_placement))
+//! queries each host's GPU topology (NVLink and PCIe connectivity, and
+//! the NUMA affinity of each GPU), and assigns one GPU to each proc so
+//! that ranks adjacent along any dimension of the per-host extent land
+//! on well-connected GPUs. Each proc is bound to its GPU through
+//! `CUDA_VISIBLE_DEVICES`, and to the GPU's NUMA node, through
+//! [`ProcBind`]. Actor meshes spawned on the proc mesh inherit the
+//! placement.
+//!
+//! The topology is read from the NVML topology matrix, as reported by
+//! `nvidia-smi topo -m`.
+
+use std::ops::Range;
+
+use hyperactor::HandleClient;
+use hyperactor::Handler;
+use hyperactor::RefClient;
+use ndslice::Extent;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::sync::OnceCell;
+use typeuri::Named;
+
+use crate::proc_launcher::ProcBind;
+
+/// How to place the procs of a mesh on each host's hardware.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlacementPolicy {
+    /// Procs are not bound to hardware.
+    #[default]
+    Unbound,
+    /// Each proc is bound to one GPU, and to the GPU's NUMA node, so
+    /// that dimension-adjacent ranks land on well-connected GPUs.
+    GpuTopology,
+}
+
+/// The connection between two GPUs, ordered from the least to the best
+/// connected.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize
+)]
+pub enum GpuLink {
+    /// PCIe, and the interconnect between NUMA nodes (`SYS`).
+    System,
+    /// PCIe, and the interconnect between PCIe host bridges within a
+    /// NUMA node (`NODE`).
+    Node,
+    /// PCIe, through a PCIe host bridge (`PHB`).
+    HostBridge,
+    /// PCIe, through multiple PCIe bridges, without a host bridge
+    /// (`PXB`).
+    MultipleBridges,
+    /// PCIe, through at most a single PCIe bridge (`PIX`).
+    SingleBridge,
+    /// A bonded set of NVLinks (`NV#`).
+    NvLink(u32),
+}
+
+impl GpuLink {
+    fn parse(link: &str) -> Option<Self> {
+        match link {
+            "SYS" => Some(Self::System),
+            "NODE" => Some(Self::Node),
+            "PHB" => Some(Self::HostBridge),
+            "PXB" => Some(Self::MultipleBridges),
+            "PIX" => Some(Self::SingleBridge),
+            _ => link.strip_prefix("NV")?.parse().ok().map(Self::NvLink),
+        }
+    }
+
+    /// A score of the link's bandwidth: any NVLink connection beats
+    /// PCIe, and more NVLinks beat fewer.
+    fn score(&self) -> u64 {
+        match self {
+            Self::System => 0,
+            Self::Node => 1,
+            Self::HostBridge => 2,
+            Self::MultipleBridges => 3,
+            Self::SingleBridge => 4,
+            Self::NvLink(links) => 16 + u64::from(*links),
+        }
+    }
+}
+
+/// The GPU topology of a host.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Named)]
+pub struct GpuTopology {
+    /// The NUMA node of each GPU, by GPU index, if known.
+    pub numa_nodes: Vec<Option<u32>>,
+    /// `links[a][b]` is the connection between GPUs `a` and `b`; it is
+    /// `None` when `a == b`.
+    pub links: Vec<Vec<Option<GpuLink>>>,
+}
+wirevalue::register_type!(GpuTopology);
+
+/// Errors placing procs.
+#[derive(Debug, thiserror::Error)]
+pub enum PlacementError {
+    /// The GPU topology could not be read.
+    #[error("failed to read GPU topology: {0}")]
+    Topology(String),
+
+    /// There are more procs per host than GPUs.
+    #[error("cannot place {requested} procs on {available} GPUs")]
+    InsufficientGpus {
+        /// The number of procs per host.
+        requested: usize,
+        /// The number of GPUs of the host.
+        available: usize,
+    },
+}
+
+/// The local host's topology, read once it is read successfully.
+static LOCAL_TOPOLOGY: OnceCell<GpuTopology> = OnceCell::const_new();
+
+impl GpuTopology {
+    /// The topology of the GPUs of this host, as reported by
+    /// `nvidia-smi`. A failure to read it is not cached, so that a
+    /// later call may succeed, e.g. once the driver is loaded.
+    pub async fn local() -> Result<Self, PlacementError> {
+        LOCAL_TOPOLOGY
+            .get_or_try_init(|| async {
+                let output = tokio::process::Command::new("nvidia-smi")
+                    .args(["topo", "-m"])
+                    .output()
+                    .await
+                    .map_err(|err| {
+                        PlacementError::Topology(format!("failed to run nvidia-smi: {}", err))
+                    })?;
+                if !output.status.success() {
+                    return Err(PlacementError::Topology(format!(
+                        "nvidia-smi failed with {}: {}",
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    )));
+                }
+                Self::parse(&String::from_utf8_lossy(&output.stdout))
+            })
+            .await
+            .cloned()
+    }
+
+    /// Parse the topology matrix printed by `nvidia-smi topo -m`.
+    pub fn parse(matrix: &str) -> Result<Self, PlacementError> {
+        let invalid = |reason: String| PlacementError::Topology(reason);
+        let matrix = strip_ansi(matrix);
+        let mut lines = matrix.lines().skip_while(|line| line.trim().is_empty());
+        let header: Vec<&str> = lines
+            .next()
+            .ok_or_else(|| invalid("empty topology matrix".to_string()))?
+            .split('\t')
+            .map(str::trim)
+            .collect();
+        let gpu_columns: Vec<usize> = header
+            .iter()
+            .enumerate()
+            .filter(|(_, name)| gpu_index(name).is_some())
+            .map(|(column, _)| column)
+            .collect();
+        let numa_column = header.iter().position(|name| *name == "NUMA Affinity");
+
+        let mut numa_nodes = Vec::new();
+        let mut links = Vec::new();
+        for line in lines.take_while(|line| !line.trim().is_empty()) {
+            let row: Vec<&str> = line.split('\t').map(str::trim).collect();
+            let Some(index) = gpu_index(row[0]) else {
+                continue;
+            };
+            if index != links.len() {
+                return Err(invalid(format!("unexpected row for GPU{}", index)));
+            }
+            let mut row_links = Vec::new();
+            for (peer, column) in gpu_columns.iter().enumerate() {
+                let link = row.get(*column).copied().unwrap_or_default();
+                row_links.push(if peer == index {
+                    None
+                } else {
+                    Some(GpuLink::parse(link).ok_or_else(|| {
+                        invalid(format!("invalid link {:?} from GPU{}", link, index))
+                    })?)
+                });
+            }
+            links.push(row_links);
+            numa_nodes.push(
+                numa_column
+                    .and_then(|column| row.get(column))
+                    .and_then(|numa| numa.parse().ok()),
+            );
+        }
+        if links.len() != gpu_columns.len() {
+            return Err(invalid(format!(
+                "{} GPU columns, but {} GPU rows",
+                gpu_columns.len(),
+                links.len()
+            )));
+        }
+        Ok(Self { numa_nodes, links })
+    }
+
+    /// The number of GPUs.
+    pub fn num_gpus(&self) -> usize {
+        self.links.len()
+    }
+
+    fn score(&self, a: usize, b: usize) -> u64 {
+        self.links[a][b].map_or(0, |link| link.score())
+    }
+
+    /// Assign a distinct GPU to each rank of `extent`, so that ranks
+    /// adjacent along any dimension land on well-connected GPUs.
+    /// Returns the GPU index of each rank.
+    ///
+    /// The extent is recursively bisected along its largest dimension,
+    /// and the GPUs into well-connected groups of matching sizes. The
+    /// assignment is then improved by swapping GPUs between ranks, or
+    /// with unassigned GPUs, until no swap improves the total
+    /// connectivity of adjacent ranks. It is deterministic: on a
+    /// uniform topology, rank `i` is assigned GPU `i`.
+    pub fn assign(&self, extent: &Extent) -> Result<Vec<usize>, PlacementError> {
+        let num_ranks = extent.num_ranks();
+        if num_ranks > self.num_gpus() {
+            return Err(PlacementError::InsufficientGpus {
+                requested: num_ranks,
+                available: self.num_gpus(),
+            });
+        }
+        let pairs = adjacent_ranks(extent);
+        let total = |gpus: &[usize]| -> u64 {
+            pairs
+                .iter()
+                .map(|(a, b)| self.score(gpus[*a], gpus[*b]))
+                .sum()
+        };
+
+        let mut gpus = vec![0; num_ranks];
+        let all: Vec<usize> = (0..self.num_gpus()).collect();
+        self.bisect(
+            extent.sizes(),
+            extent.sizes().iter().map(|size| 0..*size).collect(),
+            self.cluster(&all, num_ranks),
+            &mut gpus,
+        );
+
+        let mut score = total(&gpus);
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for rank in 0..num_ranks {
+                // Candidates are the other ranks' GPUs, and unassigned
+                // GPUs.
+                for gpu in 0..self.num_gpus() {
+                    let mut candidate = gpus.clone();
+                    match gpus.iter().position(|assigned| *assigned == gpu) {
+                        Some(peer) => candidate.swap(rank, peer),
+                        None => candidate[rank] = gpu,
+                    }
+                    let candidate_score = total(&candidate);
+                    if candidate_score > score {
+                        gpus = candidate;
+                        score = candidate_score;
+                        improved = true;
+                    }
+                }
+            }
+        }
+        Ok(gpus)
+    }
+
+    /// Assign `gpus` to the ranks in `region`, a box within an extent
+    /// of `sizes`.
+    fn bisect(
+        &self,
+        sizes: &[usize],
+        region: Vec<Range<usize>>,
+        gpus: Vec<usize>,
+        assignment: &mut [usize],
+    ) {
+        if gpus.len() == 1 {
+            let rank = region
+                .iter()
+                .zip(sizes)
+                .fold(0, |rank, (range, size)| rank * size + range.start);
+            assignment[rank] = gpus[0];
+            return;
+        }
+        let (dim, len) = region
+            .iter()
+            .map(|range| range.len())
+            .enumerate()
+            .rev()
+            .max_by_key(|(_, len)| *len)
+            .expect("non-empty region");
+        let mut first = region.clone();
+        let mut second = region;
+        let mid = first[dim].start + len / 2;
+        first[dim].end = mid;
+        second[dim].start = mid;
+
+        let group = self.cluster(&gpus, gpus.len() / len * (len / 2));
+        let rest = gpus
+            .into_iter()
+            .filter(|gpu| !group.contains(gpu))
+            .collect();
+        self.bisect(sizes, first, group, assignment);
+        self.bisect(sizes, second, rest, assignment);
+    }
+
+    /// Greedily pick `size` well-connected GPUs among `candidates`,
+    /// starting from the first one.
+    fn cluster(&self, candidates: &[usize], size: usize) -> Vec<usize> {
+        let mut group = Vec::with_capacity(size);
+        while group.len() < size {
+            let best = candidates
+                .iter()
+                .filter(|gpu| !group.contains(*gpu))
+                .max_by_key(|gpu| {
+                    let score: u64 = group.iter().map(|peer| self.score(**gpu, *peer)).sum();
+                    // Prefer lower indices among equally good GPUs.
+                    (score, std::cmp::Reverse(**gpu))
+                })
+                .expect("enough candidates");
+            group.push(*best);
+        }
+        group
+    }
+
+    /// Compute the bindings of the ranks of `extent`: each rank is bound
+    /// to its assigned GPU, and to that GPU's NUMA node, if known.
+    pub fn proc_binds(&self, extent: &Extent) -> Result<Vec<ProcBind>, PlacementError> {
+        Ok(self
+            .assign(extent)?
+            .into_iter()
+            .map(|gpu| {
+                let numa = self.numa_nodes[gpu].map(|node| node.to_string());
+                ProcBind {
+                    cpunodebind: numa.clone(),
+                    membind: numa,
+                    gpus: Some(gpu.to_string()),
+                    ..Default::default()
+                }
+            })
+            .collect())
+    }
+}
+
+/// The pairs `(a, b)`, `a < b`, of ranks of `extent` that are adjacent
+/// along some dimension.
+fn adjacent_ranks(extent: &Extent) -> Vec<(usize, usize)> {
+    let sizes = extent.sizes();
+    let mut pairs = Vec::new();
+    for rank in 0..extent.num_ranks() {
+        let mut stride = 1;
+        for size in sizes.iter().rev() {
+            if (rank / stride) % size + 1 < *size {
+                pairs.push((rank, rank + stride));
+            }
+            stride *= size;
+        }
+    }
+    pairs
+}
+
+fn gpu_index(name: &str) -> Option<usize> {
+    name.strip_prefix("GPU")?.parse().ok()
+}
+
+/// Remove the terminal escape sequences that `nvidia-smi` may use to
+/// underline the header.
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Request the GPU topology of a host.
+///
+/// Sent to HostAgent when spawning a proc mesh with
+/// [`PlacementPolicy::GpuTopology`]. The handler replies with
+/// [`GpuTopology::local`], or the error reading it.
+#[derive(Debug, Serialize, Deserialize, Named, Handler, HandleClient, RefClient)]
+pub struct GetGpuTopology {
+    #[reply]
+    pub result: hyperactor::OncePortRef<Result<GpuTopology, String>>,
+}
+wirevalue::register_type!(GetGpuTopology);
+
+#[cfg(test)]
+mod tests {
+    use ndslice::extent;
+
+    use super::*;
+
+    /// Two islands of four NVLink-connected GPUs, {0, 2, 4, 6} on NUMA
+    /// node 0, and {1, 3, 5, 7} on NUMA node 1, interleaved by index.
+    const ISLANDS: &str = "\
+\t\x1b[4mGPU0\tGPU1\tGPU2\tGPU3\tGPU4\tGPU5\tGPU6\tGPU7\tNIC0\tCPU Affinity\tNUMA Affinity\tGPU NUMA ID\x1b[0m
+GPU0\t X \tSYS\tNV12\tSYS\tNV12\tSYS\tNV12\tSYS\tPXB\t0-23\t0\t\tN/A
+GPU1\tSYS\t X \tSYS\tNV12\tSYS\tNV12\tSYS\tNV12\tSYS\t24-47\t1\t\tN/A
+GPU2\tNV12\tSYS\t X \tSYS\tNV12\tSYS\tNV12\tSYS\tPXB\t0-23\t0\t\tN/A
+GPU3\tSYS\tNV12\tSYS\t X \tSYS\tNV12\tSYS\tNV12\tSYS\t24-47\t1\t\tN/A
+GPU4\tNV12\tSYS\tNV12\tSYS\t X \tSYS\tNV12\tSYS\tPXB\t0-23\t0\t\tN/A
+GPU5\tSYS\tNV12\tSYS\tNV12\tSYS\t X \tSYS\tNV12\tSYS\t24-47\t1\t\tN/A
+GPU6\tNV12\tSYS\tNV12\tSYS\tNV12\tSYS\t X \tSYS\tPXB\t0-23\t0\t\tN/A
+GPU7\tSYS\tNV12\tSYS\tNV12\tSYS\tNV12\tSYS\t X \tSYS\t24-47\t1\t\tN/A
+NIC0\tPXB\tSYS\tPXB\tSYS\tPXB\tSYS\tPXB\tSYS\t X \t\t\t
+
+Legend:
+
+  X    = Self
+  SYS  = Connection traversing PCIe as well as the SMP interconnect between NUMA nodes (e.g., QPI/UPI)
+";
+
+    fn island(gpu: usize) -> usize {
+        gpu % 2
+    }
+
+    #[test]
+    fn test_parse() {
+        let topology = GpuTopology::parse(ISLANDS).unwrap();
+        assert_eq!(topology.num_gpus(), 8);
+        assert_eq!(topology.links[0][0], None);
+        assert_eq!(topology.links[0][2], Some(GpuLink::NvLink(12)));
+        assert_eq!(topology.links[0][1], Some(GpuLink::System));
+        assert_eq!(topology.numa_nodes[..2], [Some(0), Some(1)]);
+
+        assert!(matches!(
+            GpuTopology::parse("\tGPU0\tGPU1\nGPU0\t X \tBOGUS\n"),
+            Err(PlacementError::Topology(_))
+        ));
+    }
+
+    #[test]
+    fn test_assign_line() {
+        let topology = GpuTopology::parse(ISLANDS).unwrap();
+        let gpus = topology.assign(&extent!(gpus = 8)).unwrap();
+        let mut sorted = gpus.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..8).collect::<Vec<_>>());
+        // Only one pair of neighbors crosses the islands.
+        assert!(gpus[..4].iter().all(|gpu| island(*gpu) == island(gpus[0])));
+        assert!(gpus[4..].iter().all(|gpu| island(*gpu) == island(gpus[4])));
+    }
+
+    #[test]
+    fn test_assign_grid() {
+        let topology = GpuTopology::parse(ISLANDS).unwrap();
+        // Each island fills a 2x2 block: 8 of the 10 adjacent pairs
+        // are NVLink-connected, against 6 if each island filled a row.
+        let gpus = topology.assign(&extent!(replicas = 2, shards = 4)).unwrap();
+        let nvlinked = adjacent_ranks(&extent!(replicas = 2, shards = 4))
+            .into_iter()
+            .filter(|(a, b)| island(gpus[*a]) == island(gpus[*b]))
+            .count();
+        assert_eq!(nvlinked, 8);
+    }
+
+    #[test]
+    fn test_assign_uniform() {
+        let topology = GpuTopology::parse(
+            "\tGPU0\tGPU1\tGPU2\nGPU0\t X \tNV4\tNV4\nGPU1\tNV4\t X \tNV4\nGPU2\tNV4\tNV4\t X \n",
+        )
+        .unwrap();
+        assert_eq!(topology.assign(&extent!(gpus = 2)).unwrap(), vec![0, 1]);
+        assert!(matches!(
+            topology.assign(&extent!(gpus = 4)),
+            Err(PlacementError::InsufficientGpus {
+                requested: 4,
+                available: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_proc_binds() {
+        let topology = GpuTopology::parse(ISLANDS).unwrap();
+        let binds = topology.proc_binds(&extent!(gpus = 2)).unwrap();
+        assert_eq!(binds[0].gpus.as_deref(), Some("0"));
+        assert_eq!(binds[0].cpunodebind.as_deref(), Some("0"));
+        assert_eq!(binds[0].membind.as_deref(), Some("0"));
+        assert_eq!(binds[1].gpus.as_deref(), Some("2"));
+    }
+}
@@ -0,0 +1,472 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Actor plugins: actor implementations loaded from dynamic libraries.
+//!
+//! A plugin is a `cdylib` that exports a [`PluginDeclaration`], with
+//! [`declare_plugin!`](crate::declare_plugin). Loading the plugin into
+//! a proc, with [`load_plugin`], registers the actor types that the
+//! declaration's registration function lists, so that they can be
+//! spawned by name like any actor linked into the proc's image. This
+//! lets new actor types be rolled out to a running mesh, e.g. after
+//! syncing the plugin's library to the hosts with code sync, without
+//! restarting its procs; see [`ProcMeshRef::load_plugin`].
+//!
+//! ```ignore
+//! hyperactor::register_spawnable!(MyWorker);
+//!
+//! fn register(registrar: &mut PluginRegistrar) {
+//!     registrar.register::<MyWorker>();
+//! }
+//!
+//! hyperactor_mesh::declare_plugin!("my_workers", "1.2.0", register);
+//! ```
+//!
+//! A plugin must share the proc's copy of this crate and its
+//! dependencies, rather than link its own: a copy would come with its
+//! own actor registry, global configuration, and thread-locals (such
+//! as the current proc and runtime), which the proc never sets up. The
+//! proc and its plugins must therefore link this crate as a Rust
+//! `dylib` (e.g. with `-C prefer-dynamic`). As rustc only links a
+//! `dylib` into crates built by the same compiler, this also ensures
+//! that a plugin is built with the proc's toolchain, as Rust has no
+//! stable ABI. The declaration records the plugin ABI version, and
+//! identifies the copy of this crate that the plugin links; loading
+//! fails unless both match the proc's.
+//!
+//! Procs load plugins only from the directory configured with
+//! [`PLUGIN_DIR`], if any, as loading a library runs its code. Loaded
+//! libraries are never unloaded, as actors spawned from them may still
+//! be running. Loading a new version of a plugin replaces its actor
+//! types for subsequent spawns, while actors spawned from the earlier
+//! version keep running its code.
+//!
+//! [`ProcMeshRef::load_plugin`]: crate::ProcMeshRef::load_plugin
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use hyperactor::Bind;
+use hyperactor::PortRef;
+use hyperactor::ProcAddr;
+use hyperactor::RemoteSpawn;
+use hyperactor::Unbind;
+use hyperactor::actor::remote::Remote;
+use hyperactor::actor::remote::SpawnableActor;
+use hyperactor_config::CONFIG;
+use hyperactor_config::ConfigAttr;
+use hyperactor_config::attrs::declare_attrs;
+use serde::Deserialize;
+use serde::Serialize;
+use typeuri::Named;
+
+declare_attrs! {
+    /// How long to wait for every proc of a mesh to load a plugin.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_PLUGIN_LOAD_TIMEOUT".to_string()),
+        Some("plugin_load_timeout".to_string()),
+    ))
+    pub attr PLUGIN_LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// The directory from which procs load plugins. Plugin loading is
+    /// disabled if it is empty.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_PLUGIN_DIR".to_string()),
+        Some("plugin_dir".to_string()),
+    ))
+    pub attr PLUGIN_DIR: String = String::new();
+}
+
+/// The version of the plugin ABI: the layout of [`PluginDeclaration`]
+/// and [`PluginRegistrar`], and the protocol between them.
+pub const PLUGIN_ABI_VERSION: u32 = 2;
+
+/// The identity of the copy of this crate linked by the caller: the
+/// address of one of its statics. A plugin shares the proc's copy only
+/// if its identity is the proc's.
+pub extern "C" fn runtime_identity() -> usize {
+    static IDENTITY: u8 = 0;
+    &IDENTITY as *const u8 as usize
+}
+
+/// The symbol under which a plugin exports its [`PluginDeclaration`].
+pub const PLUGIN_DECLARATION_SYMBOL: &[u8] = b"hyperactor_plugin_declaration";
+
+/// A plugin's declaration, exported by [`declare_plugin!`](crate::declare_plugin).
+#[repr(C)]
+pub struct PluginDeclaration {
+    /// The [`PLUGIN_ABI_VERSION`] the plugin was built with.
+    pub abi_version: u32,
+    /// The [`runtime_identity`] of the copy of this crate that the
+    /// plugin links.
+    pub runtime: extern "C" fn() -> usize,
+    /// The plugin's name.
+    pub name: &'static str,
+    /// The plugin's version.
+    pub version: &'static str,
+    /// Registers the plugin's actor types.
+    pub register: fn(&mut PluginRegistrar),
+}
+
+/// Export a [`PluginDeclaration`] from a plugin library, with the given
+/// name, version, and registration function, which is passed a
+/// [`PluginRegistrar`](crate::plugin::PluginRegistrar).
+#[macro_export]
+macro_rules! declare_plugin {
+    ($name:expr, $version:expr, $register:path) => {
+        #[allow(non_upper_case_globals)]
+        #[unsafe(no_mangle)]
+        pub static hyperactor_plugin_declaration: $crate::plugin::PluginDeclaration =
+            $crate::plugin::PluginDeclaration {
+                abi_version: $crate::plugin::PLUGIN_ABI_VERSION,
+                runtime: $crate::plugin::runtime_identity,
+                name: $name,
+                version: $version,
+                register: $register,
+            };
+    };
+}
+
+/// Collects the actor types that a plugin registers.
+#[derive(Debug, Default)]
+pub struct PluginRegistrar {
+    actors: Vec<&'static SpawnableActor>,
+    unregistered: Vec<&'static str>,
+}
+
+impl PluginRegistrar {
+    /// Register actor type `A`, which must also be registered with
+    /// [`hyperactor::register_spawnable!`] in the plugin.
+    pub fn register<A: RemoteSpawn>(&mut self) {
+        match Remote::global().entry_of::<A>() {
+            Some(entry) => self.actors.push(entry),
+            None => self.unregistered.push(std::any::type_name::<A>()),
+        }
+    }
+}
+
+/// A loaded plugin.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Named)]
+pub struct PluginInfo {
+    /// The plugin's name.
+    pub name: String,
+    /// The plugin's version.
+    pub version: String,
+    /// The actor types that the plugin registered.
+    pub actor_types: Vec<String>,
+}
+wirevalue::register_type!(PluginInfo);
+
+/// Errors that occur while loading a plugin.
+#[derive(Debug, thiserror::Error)]
+pub enum PluginError {
+    #[error("plugin loading is disabled: no plugin directory is configured")]
+    Disabled,
+
+    #[error("failed to resolve plugin library {}: {source}", .path.display())]
+    Resolve {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error(
+        "plugin library {} is outside of the plugin directory {}",
+        .path.display(),
+        .dir.display()
+    )]
+    OutsideDirectory { path: PathBuf, dir: PathBuf },
+
+    #[error("failed to open plugin library {}: {source}", .path.display())]
+    Open {
+        path: PathBuf,
+        source: libloading::Error,
+    },
+
+    #[error("plugin library {} does not declare a plugin: {source}", .path.display())]
+    MissingDeclaration {
+        path: PathBuf,
+        source: libloading::Error,
+    },
+
+    #[error("plugin {name} was built for plugin ABI {found}, but this proc uses {expected}")]
+    AbiMismatch {
+        name: String,
+        expected: u32,
+        found: u32,
+    },
+
+    #[error("plugin {name} links its own copy of hyperactor_mesh, rather than the proc's")]
+    SeparateRuntime { name: String },
+
+    #[error("plugin {name} registers actor types that are not spawnable: {types:?}")]
+    Unregistered { name: String, types: Vec<String> },
+
+    #[error("plugin {name} failed to register its actor types: {source}")]
+    Register { name: String, source: anyhow::Error },
+}
+
+/// The plugins loaded into this process, by name.
+static LOADED: LazyLock<Mutex<HashMap<String, PluginInfo>>> = LazyLock::new(Default::default);
+
+/// The plugins loaded into this process.
+pub fn loaded_plugins() -> Vec<PluginInfo> {
+    LOADED
+        .lock()
+        .expect("loaded plugins poisoned")
+        .values()
+        .cloned()
+        .collect()
+}
+
+/// Load the plugin library at `path`, which must be within
+/// [`PLUGIN_DIR`], into this process, and register its actor types.
+/// Loading a version of a plugin that is already loaded does nothing.
+pub fn load_plugin(path: &Path) -> Result<PluginInfo, PluginError> {
+    let path = &check_path(path)?;
+    // Collect the actors linked into this image before the library adds
+    // its own to the inventory.
+    let _ = Remote::global();
+    // SAFETY: Loading a library runs its initializers. Plugins are
+    // trusted code, deployed to the plugin directory alongside the
+    // procs that load them.
+    let library =
+        unsafe { libloading::Library::new(path) }.map_err(|source| PluginError::Open {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    // SAFETY: The symbol is a `PluginDeclaration` static, as exported
+    // by `declare_plugin!`. The reference does not outlive the library:
+    // it is leaked below, unless the declaration is rejected first.
+    let declaration: &'static PluginDeclaration = unsafe {
+        let symbol = library
+            .get::<*const PluginDeclaration>(PLUGIN_DECLARATION_SYMBOL)
+            .map_err(|source| PluginError::MissingDeclaration {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        &**symbol
+    };
+    check_declaration(declaration)?;
+    if let Some(info) = loaded(declaration) {
+        return Ok(info);
+    }
+    // Actors spawned from the plugin run its code for as long as they
+    // live, so the library is never unloaded.
+    std::mem::forget(library);
+    let info = install(declaration)?;
+    tracing::info!(
+        name = %info.name,
+        version = %info.version,
+        actor_types = ?info.actor_types,
+        "loaded plugin from {}",
+        path.display(),
+    );
+    Ok(info)
+}
+
+/// Resolve `path`, and check that it is within [`PLUGIN_DIR`].
+fn check_path(path: &Path) -> Result<PathBuf, PluginError> {
+    let dir = hyperactor_config::global::get(PLUGIN_DIR);
+    if dir.is_empty() {
+        return Err(PluginError::Disabled);
+    }
+    let resolve = |path: &Path| {
+        path.canonicalize().map_err(|source| PluginError::Resolve {
+            path: path.to_path_buf(),
+            source,
+        })
+    };
+    let dir = resolve(Path::new(&dir))?;
+    let path = resolve(path)?;
+    if !path.starts_with(&dir) {
+        return Err(PluginError::OutsideDirectory { path, dir });
+    }
+    Ok(path)
+}
+
+/// Check that `declaration` was built for this proc's ABI, against the
+/// proc's copy of this crate. Only the ABI version may be read from a
+/// declaration that fails this check.
+fn check_declaration(declaration: &PluginDeclaration) -> Result<(), PluginError> {
+    if declaration.abi_version != PLUGIN_ABI_VERSION {
+        return Err(PluginError::AbiMismatch {
+            name: "<unknown>".to_string(),
+            expected: PLUGIN_ABI_VERSION,
+            found: declaration.abi_version,
+        });
+    }
+    if (declaration.runtime)() != runtime_identity() {
+        return Err(PluginError::SeparateRuntime {
+            name: declaration.name.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// The loaded plugin with the same name and version as `declaration`, if any.
+fn loaded(declaration: &PluginDeclaration) -> Option<PluginInfo> {
+    LOADED
+        .lock()
+        .expect("loaded plugins poisoned")
+        .get(declaration.name)
+        .filter(|info| info.version == declaration.version)
+        .cloned()
+}
+
+/// Register the actor types of a checked plugin declaration.
+fn install(declaration: &'static PluginDeclaration) -> Result<PluginInfo, PluginError> {
+    let name = declaration.name.to_string();
+    let mut registrar = PluginRegistrar::default();
+    (declaration.register)(&mut registrar);
+    if !registrar.unregistered.is_empty() {
+        return Err(PluginError::Unregistered {
+            name,
+            types: registrar
+                .unregistered
+                .iter()
+                .map(|ty| ty.to_string())
+                .collect(),
+        });
+    }
+    let mut actor_types = Vec::with_capacity(registrar.actors.len());
+    for entry in registrar.actors {
+        Remote::register_dynamic(entry).map_err(|source| PluginError::Register {
+            name: name.clone(),
+            source,
+        })?;
+        actor_types.push((**entry.name).to_string());
+    }
+    let info = PluginInfo {
+        name,
+        version: declaration.version.to_string(),
+        actor_types,
+    };
+    LOADED
+        .lock()
+        .expect("loaded plugins poisoned")
+        .insert(info.name.clone(), info.clone());
+    Ok(info)
+}
+
+/// Load a plugin into a proc, by way of its proc agent. The agent
+/// replies with a [`PluginLoaded`]. The plugin must be within the
+/// proc's [`PLUGIN_DIR`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named, Bind, Unbind)]
+pub struct LoadPlugin {
+    /// The path of the plugin library on the proc's host.
+    pub path: PathBuf,
+    /// Where to send the outcome.
+    #[binding(include)]
+    pub reply: PortRef<PluginLoaded>,
+}
+wirevalue::register_type!(LoadPlugin);
+
+/// The outcome of loading a plugin into a proc with [`LoadPlugin`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named, Bind, Unbind)]
+pub struct PluginLoaded {
+    /// The rank of the proc within the mesh the request was cast to.
+    pub rank: usize,
+    /// The proc.
+    pub proc_id: ProcAddr,
+    /// The loaded plugin, or the error loading it.
+    pub result: Result<PluginInfo, String>,
+}
+wirevalue::register_type!(PluginLoaded);
+
+#[cfg(test)]
+mod tests {
+    use std::assert_matches;
+
+    use hyperactor::Actor;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    #[hyperactor::export(handlers = [])]
+    struct PluginWorker;
+    impl Actor for PluginWorker {}
+    hyperactor::register_spawnable!(PluginWorker);
+
+    fn register(registrar: &mut PluginRegistrar) {
+        registrar.register::<PluginWorker>();
+    }
+
+    #[test]
+    fn test_install_plugin() {
+        static DECLARATION: PluginDeclaration = PluginDeclaration {
+            abi_version: PLUGIN_ABI_VERSION,
+            runtime: runtime_identity,
+            name: "test_plugin",
+            version: "1.0.0",
+            register,
+        };
+        check_declaration(&DECLARATION).unwrap();
+        let info = install(&DECLARATION).unwrap();
+        let actor_type = Remote::global().name_of::<PluginWorker>().unwrap();
+        assert_eq!(info.actor_types, vec![actor_type.to_string()]);
+        assert!(Remote::collect().contains(actor_type));
+        assert!(loaded(&DECLARATION).is_some());
+        assert!(loaded_plugins().contains(&info));
+
+        static STALE: PluginDeclaration = PluginDeclaration {
+            abi_version: PLUGIN_ABI_VERSION + 1,
+            runtime: runtime_identity,
+            name: "stale_plugin",
+            version: "1.0.0",
+            register,
+        };
+        assert_matches!(
+            check_declaration(&STALE),
+            Err(PluginError::AbiMismatch { found, .. }) if found == PLUGIN_ABI_VERSION + 1
+        );
+    }
+
+    /// The example plugin, `examples/plugin.rs`, which Cargo builds
+    /// along with the package's tests.
+    #[cfg(not(fbcode_build))]
+    fn example_plugin() -> PathBuf {
+        let exe = std::env::current_exe().unwrap();
+        exe.parent().unwrap().parent().unwrap().join(format!(
+            "examples/{}plugin{}",
+            std::env::consts::DLL_PREFIX,
+            std::env::consts::DLL_SUFFIX
+        ))
+    }
+
+    #[cfg(not(fbcode_build))]
+    #[test]
+    fn test_load_plugin() {
+        let config = hyperactor_config::global::lock();
+        let plugin = example_plugin();
+        assert_matches!(load_plugin(&plugin), Err(PluginError::Disabled));
+
+        let dir = plugin.parent().unwrap();
+        let _dir = config.override_key(PLUGIN_DIR, dir.display().to_string());
+        assert_matches!(
+            load_plugin(&dir.join("missing.so")),
+            Err(PluginError::Resolve { .. })
+        );
+        // The test binary is in a sibling directory.
+        let exe = std::env::current_exe().unwrap();
+        assert_matches!(
+            load_plugin(&dir.join("../deps").join(exe.file_name().unwrap())),
+            Err(PluginError::OutsideDirectory { .. })
+        );
+
+        // The example links its own copy of this crate, as the test
+        // binary links it statically.
+        assert_matches!(
+            load_plugin(&plugin),
+            Err(PluginError::SeparateRuntime { name }) if name == "example"
+        );
+    }
+}
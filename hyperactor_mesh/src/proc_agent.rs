@@ -34,13 +34,19 @@ use hyperactor::PortHandle;
 use hyperactor::PortRef;
 use hyperactor::RemoteEndpoint as _;
 use hyperactor::Unbind;
+use hyperactor::actor::ActorStatus;
 use hyperactor::actor::handle_undeliverable_message;
 use hyperactor::actor::remote::Remote;
 use hyperactor::id::Label;
 use hyperactor::id::Uid;
+use hyperactor::mailbox::MailboxAdminMessage;
+use hyperactor::mailbox::MailboxAdminMessageHandler;
+use hyperactor::mailbox::MailboxSender;
 use hyperactor::mailbox::MessageEnvelope;
+use hyperactor::mailbox::PortDump;
 use hyperactor::mailbox::Undeliverable;
 use hyperactor::mailbox::UndeliverableReason;
+use hyperactor::proc::InstanceCell;
 use hyperactor::proc::Proc;
 use hyperactor::supervision::ActorSupervisionEvent;
 use hyperactor_config::CONFIG;
@@ -51,15 +57,35 @@ use serde::Deserialize;
 use serde::Serialize;
 use typeuri::Named;
 
+use crate::alert;
+use crate::alert::Alert;
+use crate::alert::UndeliverableBursts;
+use crate::comm::multicast::CastInfo;
 use crate::config_dump::ConfigDump;
 use crate::config_dump::ConfigDumpResult;
+use crate::counted_ref::EPHEMERAL;
+use crate::counted_ref::REF_COUNT_REAP_GRACE;
+use crate::counted_ref::RefCountUpdate;
 use crate::introspect::ProcessMemoryStats;
+use crate::mesh_clock::TimeSync;
+use crate::mesh_clock::TimeSyncReply;
 use crate::mesh_id::ResourceId;
+use crate::plugin::LoadPlugin;
+use crate::plugin::PluginLoaded;
 use crate::pyspy::PySpyDump;
 use crate::pyspy::PySpyProfile;
 use crate::pyspy::PySpyProfileWorker;
 use crate::pyspy::PySpyWorker;
+use crate::reservations::ReservationsDump;
+use crate::reservations::ReservationsResult;
 use crate::resource;
+use crate::routing_audit::RoutingAuditDump;
+use crate::routing_audit::RoutingAuditResult;
+use crate::timing::TimingDump;
+use crate::timing::TimingResult;
+use crate::topology::CastTopologyDump;
+use crate::topology::TopologyDump;
+use crate::topology::TopologyResult;
 
 /// Actor name used when spawning the proc agent on user procs.
 pub const PROC_AGENT_ACTOR_NAME: &str = "proc_agent";
@@ -265,6 +291,14 @@ impl ActorInstanceState {
 )]
 pub(crate) struct SelfCheck {}
 
+/// Self-message that stops an ephemeral actor if its reference count
+/// has stayed at zero since it dropped there in `generation`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named, Bind, Unbind)]
+struct ReapUnreferenced {
+    actor: ActorAddr,
+    generation: u64,
+}
+
 /// A mesh agent is responsible for managing procs in a [`ProcMesh`].
 ///
 /// ## Supervision event ingestion (remote)
@@ -297,9 +331,19 @@ pub(crate) struct SelfCheck {}
         resource::GetRankStatus { cast = true },
         resource::WaitRankStatus { cast = true },
         RepublishIntrospect { cast = true },
+        DrainProc { cast = true },
+        LoadPlugin { cast = true },
         PySpyDump,
         PySpyProfile,
         ConfigDump,
+        RoutingAuditDump,
+        ReservationsDump,
+        TimingDump,
+        TopologyDump,
+        TimeSync,
+        MailboxAdminMessage,
+        RefCountUpdate,
+        SpawnActor,
     ]
 )]
 pub struct ProcAgent {
@@ -323,6 +367,20 @@ pub struct ProcAgent {
     stopping_all: bool,
     /// If set, check for expired actors whose keepalive has lapsed.
     mesh_orphan_timeout: Option<Duration>,
+    /// Reference counts of the ephemeral actors on this proc; see
+    /// [`crate::counted_ref`].
+    ref_counts: HashMap<ActorAddr, RefCount>,
+    /// Undeliverable messages seen by this proc, for alerting.
+    undeliverable_bursts: UndeliverableBursts,
+}
+
+/// The reference count of an ephemeral actor.
+#[derive(Debug, Default)]
+struct RefCount {
+    count: i64,
+    /// Incremented each time the count drops to zero, so that a reap
+    /// scheduled by an earlier drop is ignored.
+    generation: u64,
 }
 
 impl ProcAgent {
@@ -346,6 +404,8 @@ impl ProcAgent {
             shutdown_tx,
             stopping_all: false,
             mesh_orphan_timeout: orphan_timeout,
+            ref_counts: HashMap::new(),
+            undeliverable_bursts: UndeliverableBursts::default(),
         };
         proc.spawn_with_uid::<Self>(
             Uid::singleton(Label::new(PROC_AGENT_ACTOR_NAME).unwrap()),
@@ -516,6 +576,12 @@ impl Actor for ProcAgent {
         self.proc.set_supervision_coordinator(this.port())?;
         let _ = self.publish_introspect_properties(this);
 
+        // The ingestion endpoint is optional: a proc that cannot serve
+        // it (e.g. for lack of TLS certificates) runs without it.
+        if let Err(e) = crate::ingest::IngestEndpoint::serve_configured(&self.proc).await {
+            tracing::error!("proc agent: ingest endpoint not served: {}", e);
+        }
+
         // Resolve terminated actor snapshots via QueryChild so that
         // dead actors remain directly queryable by reference.
         let proc = self.proc.clone();
@@ -684,6 +750,7 @@ impl Actor for ProcAgent {
         reason: UndeliverableReason,
         envelope: Undeliverable<MessageEnvelope>,
     ) -> Result<(), anyhow::Error> {
+        self.undeliverable_bursts.record(self.proc.proc_addr());
         let Some(returned) = envelope.as_message() else {
             return handle_undeliverable_message(cx, reason, envelope);
         };
@@ -729,6 +796,12 @@ impl Handler<ActorSupervisionEvent> for ProcAgent {
         cx: &Context<Self>,
         event: ActorSupervisionEvent,
     ) -> anyhow::Result<()> {
+        if event.is_error() {
+            alert::raise(Alert::ActorFailure {
+                actor: event.actor_id.to_string(),
+                message: event.to_string(),
+            });
+        }
         if self.record_supervision_events {
             if event.is_error() {
                 tracing::warn!(
@@ -858,6 +931,19 @@ impl Handler<PySpyProfile> for ProcAgent {
     }
 }
 
+/// Reply to a diagnostic request, such as a [`ConfigDump`]. The reply
+/// is best-effort: the caller may have timed out and dropped the
+/// once-port, which must not crash the replying actor, so an
+/// undeliverable reply is dropped rather than returned.
+pub(crate) fn reply_best_effort<T: hyperactor::RemoteMessage>(
+    cx: &impl hyperactor::context::Actor,
+    mut port: hyperactor::OncePortRef<T>,
+    reply: T,
+) {
+    port.return_undeliverable(false);
+    port.post(cx, reply);
+}
+
 #[async_trait]
 impl Handler<ConfigDump> for ProcAgent {
     async fn handle(
@@ -866,15 +952,237 @@ impl Handler<ConfigDump> for ProcAgent {
         message: ConfigDump,
     ) -> Result<(), anyhow::Error> {
         let entries = hyperactor_config::global::config_entries();
-        // Reply is best-effort: the caller may have timed out and dropped
-        // the once-port.  That must not crash this actor.
-        let _ = message.result.post(cx, ConfigDumpResult { entries });
+        reply_best_effort(cx, message.result, ConfigDumpResult { entries });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<RoutingAuditDump> for ProcAgent {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        message: RoutingAuditDump,
+    ) -> Result<(), anyhow::Error> {
+        reply_best_effort(cx, message.result, RoutingAuditResult::snapshot());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<ReservationsDump> for ProcAgent {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        message: ReservationsDump,
+    ) -> Result<(), anyhow::Error> {
+        reply_best_effort(cx, message.result, ReservationsResult::snapshot(&self.proc));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<TimingDump> for ProcAgent {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        message: TimingDump,
+    ) -> Result<(), anyhow::Error> {
+        reply_best_effort(cx, message.result, TimingResult::snapshot(&self.proc));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<TopologyDump> for ProcAgent {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        message: TopologyDump,
+    ) -> Result<(), anyhow::Error> {
+        let TopologyResult { topology } = TopologyResult::snapshot(&self.proc);
+        let comm_actor = crate::topology::comm_actor_addr(&self.proc.proc_addr());
+        if self.proc.get_instance(&comm_actor).is_none() {
+            reply_best_effort(cx, message.result, TopologyResult { topology });
+            return Ok(());
+        }
+        // The proc's comm actor adds the casts it forwards, and replies.
+        hyperactor::ActorRef::<crate::comm::CommActor>::attest(comm_actor).post(
+            cx,
+            CastTopologyDump {
+                topology,
+                result: message.result,
+            },
+        );
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<TimeSync> for ProcAgent {
+    async fn handle(&mut self, cx: &Context<Self>, message: TimeSync) -> Result<(), anyhow::Error> {
+        let received_at = std::time::SystemTime::now();
+        reply_best_effort(
+            cx,
+            message.result,
+            TimeSyncReply::new(message.sent_at, received_at),
+        );
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<RefCountUpdate> for ProcAgent {
+    async fn handle(&mut self, cx: &Context<Self>, message: RefCountUpdate) -> anyhow::Result<()> {
+        if message.actor.proc_addr() != self.proc.proc_addr() {
+            tracing::warn!(
+                actor = %message.actor,
+                "ignoring reference count update for an actor on another proc"
+            );
+            return Ok(());
+        }
+        // Only the actor itself can make itself ephemeral, so that no
+        // peer can stop an actor that did not opt in.
+        let ephemeral = self
+            .proc
+            .get_instance(&message.actor)
+            .and_then(|cell| cell.published_attrs())
+            .is_some_and(|attrs| attrs.get(EPHEMERAL) == Some(&true));
+        if !ephemeral {
+            tracing::warn!(
+                actor = %message.actor,
+                "ignoring reference count update for an actor that is not ephemeral"
+            );
+            self.ref_counts.remove(&message.actor);
+            return Ok(());
+        }
+        let ref_count = self.ref_counts.entry(message.actor.clone()).or_default();
+        ref_count.count += message.delta;
+        // A reference may be dropped by one proc before another proc
+        // that received a copy of it reports it, so the count may
+        // transiently drop to zero, or below; the grace period absorbs
+        // these races.
+        if ref_count.count <= 0 && message.delta < 0 {
+            ref_count.generation += 1;
+            cx.post_after(
+                cx,
+                ReapUnreferenced {
+                    actor: message.actor,
+                    generation: ref_count.generation,
+                },
+                hyperactor_config::global::get(REF_COUNT_REAP_GRACE),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<ReapUnreferenced> for ProcAgent {
+    async fn handle(
+        &mut self,
+        _cx: &Context<Self>,
+        message: ReapUnreferenced,
+    ) -> anyhow::Result<()> {
+        let unreferenced = self
+            .ref_counts
+            .get(&message.actor)
+            .is_some_and(|ref_count| {
+                ref_count.count <= 0 && ref_count.generation == message.generation
+            });
+        if unreferenced {
+            self.ref_counts.remove(&message.actor);
+            self.stop_actor_by_id(&message.actor, "last reference dropped");
+        }
         Ok(())
     }
 }
 
 // Implement the resource behavior for managing actors:
 
+/// Port administration for the actors in this proc, to remediate a
+/// wedged port without restarting the proc. Requests for ports of
+/// actors that are not in this proc are logged and ignored, as are
+/// address updates the proc's forwarder cannot route by.
+#[async_trait]
+#[hyperactor::handle(MailboxAdminMessage)]
+impl MailboxAdminMessageHandler for ProcAgent {
+    async fn update_address(
+        &mut self,
+        _cx: &Context<Self>,
+        proc_id: hyperactor::ProcAddr,
+        addr: hyperactor::channel::ChannelAddr,
+    ) -> Result<(), anyhow::Error> {
+        if self
+            .proc
+            .forwarder()
+            .update_address(&proc_id.clone().into(), &addr)
+        {
+            tracing::info!(%proc_id, %addr, "updated proc address");
+        } else {
+            tracing::warn!(%proc_id, %addr, "cannot update proc address: forwarder does not route by address");
+        }
+        Ok(())
+    }
+
+    async fn close_port(
+        &mut self,
+        _cx: &Context<Self>,
+        port: PortAddr,
+    ) -> Result<(), anyhow::Error> {
+        match self.proc.muxer().port_mailbox(&port) {
+            Ok(mailbox) => {
+                tracing::info!(%port, "closing port");
+                mailbox.close_port(port.port());
+            }
+            Err(err) => tracing::warn!(%port, "cannot close port: {}", err),
+        }
+        Ok(())
+    }
+
+    async fn drain_port(
+        &mut self,
+        _cx: &Context<Self>,
+        port: PortAddr,
+    ) -> Result<PortDump, anyhow::Error> {
+        match self.proc.muxer().port_mailbox(&port) {
+            Ok(mailbox) => {
+                let dump = mailbox.drain_port(port.port());
+                tracing::info!(
+                    %port,
+                    messages = dump.messages.len(),
+                    dropped = dump.dropped,
+                    "drained port",
+                );
+                Ok(dump)
+            }
+            Err(err) => {
+                tracing::warn!(%port, "cannot drain port: {}", err);
+                Ok(PortDump {
+                    port,
+                    messages: Vec::new(),
+                    dropped: 0,
+                })
+            }
+        }
+    }
+
+    async fn reset_port(
+        &mut self,
+        _cx: &Context<Self>,
+        port: PortAddr,
+    ) -> Result<(), anyhow::Error> {
+        match self.proc.muxer().port_mailbox(&port) {
+            Ok(mailbox) => {
+                tracing::info!(%port, "resetting port");
+                mailbox.reset_port(port.port());
+            }
+            Err(err) => tracing::warn!(%port, "cannot reset port: {}", err),
+        }
+        Ok(())
+    }
+}
+
 /// Actor spec.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named)]
 pub struct ActorSpec {
@@ -885,6 +1193,53 @@ pub struct ActorSpec {
 }
 wirevalue::register_type!(ActorSpec);
 
+/// Spawn a root actor of a registered type on the agent's proc,
+/// replying with the spawned actor's address. Sent by
+/// [`ProcRef::spawn_remote`](crate::proc_mesh::ProcRef::spawn_remote).
+///
+/// The agent tracks the actor as it does actors created by
+/// [`resource::CreateOrUpdate`], under the resource id of the actor's
+/// uid, so that its status can be queried and its failure is reported.
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Named,
+    hyperactor::Handler,
+    hyperactor::HandleClient,
+    hyperactor::RefClient
+)]
+pub struct SpawnActor {
+    /// The registered actor type.
+    pub actor_type: String,
+    /// The serialized spawn parameters.
+    pub params_data: Data,
+    /// The rank of the proc at creation.
+    pub create_rank: usize,
+    #[reply]
+    pub result: hyperactor::OncePortRef<Result<ActorAddr, SpawnActorError>>,
+}
+wirevalue::register_type!(SpawnActor);
+
+/// Why a [`SpawnActor`] request failed.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    Named,
+    thiserror::Error
+)]
+pub enum SpawnActorError {
+    /// The actor type is not registered in the proc's image.
+    #[error("actor type {0} is not registered on the proc")]
+    NotRegistered(String),
+    /// The actor failed to spawn.
+    #[error("actor failed to spawn: {0}")]
+    Failed(String),
+}
+
 /// Actor state.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named, Bind, Unbind)]
 pub struct ActorState {
@@ -897,6 +1252,57 @@ pub struct ActorState {
 }
 wirevalue::register_type!(ActorState);
 
+#[async_trait]
+impl Handler<SpawnActor> for ProcAgent {
+    async fn handle(&mut self, cx: &Context<Self>, message: SpawnActor) -> anyhow::Result<()> {
+        let SpawnActor {
+            actor_type,
+            params_data,
+            create_rank,
+            result,
+        } = message;
+        if !self.remote.contains(&actor_type) {
+            result.post(cx, Err(SpawnActorError::NotRegistered(actor_type)));
+            return Ok(());
+        }
+
+        // Label the actor with its type's unqualified name.
+        let label = actor_type.rsplit("::").next().unwrap_or(&actor_type);
+        let id = ResourceId::new(Uid::instance(Label::strip(label)), None);
+        let spawn = self
+            .remote
+            .gspawn(
+                &self.proc,
+                &actor_type,
+                id.uid().clone(),
+                params_data,
+                cx.headers().clone(),
+            )
+            .await;
+        let spawned = match &spawn {
+            Ok(actor_addr) => Ok(actor_addr.clone()),
+            Err(err) => Err(SpawnActorError::Failed(format!("{:#}", err))),
+        };
+        self.actor_states.insert(
+            id,
+            ActorInstanceState {
+                create_rank,
+                spawn,
+                stop_initiated: false,
+                supervision_event: None,
+                subscribers: Vec::new(),
+                expiry_time: None,
+                generation: 1,
+                pending_wait_status: Vec::new(),
+            },
+        );
+        result.post(cx, spawned);
+
+        let _ = self.publish_introspect_properties(cx);
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl Handler<resource::CreateOrUpdate<ActorSpec>> for ProcAgent {
     async fn handle(
@@ -1171,6 +1577,149 @@ impl Handler<resource::KeepaliveGetState<ActorState>> for ProcAgent {
     }
 }
 
+/// Gracefully drain the user actors on a proc before it is stopped:
+/// intake is closed at each actor's mailbox, already accepted
+/// messages are given up to `timeout` to be processed, and the proc's
+/// outbound messages are flushed. A [`DrainSummary`] is sent to
+/// `reply` once draining completes or times out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named, Bind, Unbind)]
+pub struct DrainProc {
+    /// How long to wait for queued messages to drain and for the
+    /// proc's outbound messages to flush.
+    pub timeout: Duration,
+    /// Where to send the drain summary.
+    #[binding(include)]
+    pub reply: PortRef<DrainSummary>,
+}
+wirevalue::register_type!(DrainProc);
+
+/// The outcome of draining a single proc with [`DrainProc`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named, Bind, Unbind)]
+pub struct DrainSummary {
+    /// The rank of the proc within the mesh that was drained.
+    pub rank: usize,
+    /// The drained proc.
+    pub proc_id: hyperactor::ProcAddr,
+    /// The number of actors whose intake was closed.
+    pub actors: usize,
+    /// The number of messages still queued when draining finished.
+    pub remaining: u64,
+    /// Whether all queued messages were processed before the timeout.
+    pub drained: bool,
+    /// Set if flushing the proc's outbound messages failed or timed out.
+    pub flush_error: Option<String>,
+    /// How long draining took.
+    pub elapsed: Duration,
+}
+wirevalue::register_type!(DrainSummary);
+
+impl DrainSummary {
+    /// Whether the proc drained its queues and flushed cleanly.
+    pub fn is_clean(&self) -> bool {
+        self.drained && self.flush_error.is_none()
+    }
+}
+
+/// Wait until the live actors among `cells` have no queued messages
+/// and are not handling one, or until `deadline`. Returns the number of
+/// messages still queued.
+///
+/// Queued messages are processed by the actors' own loops; each one
+/// moves its actor through `Processing` and back to `Idle`, so we wake
+/// on status changes rather than polling the queues.
+async fn wait_drained(cells: &[InstanceCell], deadline: tokio::time::Instant) -> u64 {
+    loop {
+        // Mark the current statuses as seen before reading the queues,
+        // so that no change after the check is missed.
+        let mut statuses: Vec<_> = cells
+            .iter()
+            .map(|cell| cell.status().clone())
+            .filter_map(|mut status| {
+                let terminal = status.borrow_and_update().is_terminal();
+                (!terminal).then_some(status)
+            })
+            .collect();
+        let queued: u64 = cells
+            .iter()
+            .filter(|cell| !cell.status().borrow().is_terminal())
+            .map(|cell| cell.queue_depth())
+            .sum();
+        let busy = statuses
+            .iter()
+            .any(|status| matches!(*status.borrow(), ActorStatus::Processing(..)));
+        if (queued == 0 && !busy) || statuses.is_empty() {
+            return queued;
+        }
+        let changed = futures::future::select_all(
+            statuses.iter_mut().map(|status| Box::pin(status.changed())),
+        );
+        if tokio::time::timeout_at(deadline, changed).await.is_err() {
+            return queued;
+        }
+    }
+}
+
+#[async_trait]
+impl Handler<DrainProc> for ProcAgent {
+    async fn handle(&mut self, cx: &Context<Self>, message: DrainProc) -> anyhow::Result<()> {
+        let start = tokio::time::Instant::now();
+        let deadline = start + message.timeout;
+
+        let roots: Vec<_> = self
+            .actor_states
+            .values()
+            .filter_map(|state| state.spawn.as_ref().ok())
+            .map(|actor_id| actor_id.id().clone())
+            .collect();
+        let cells = self.proc.close_intake(&roots);
+        let remaining = wait_drained(&cells, deadline).await;
+
+        let flush_error = match tokio::time::timeout_at(deadline, self.proc.flush()).await {
+            Ok(Ok(())) => None,
+            Ok(Err(err)) => Some(err.to_string()),
+            Err(_) => Some(format!("flush timed out after {:?}", message.timeout)),
+        };
+
+        let summary = DrainSummary {
+            rank: cx.cast_point().rank(),
+            proc_id: self.proc.proc_addr(),
+            actors: cells.len(),
+            remaining,
+            drained: remaining == 0,
+            flush_error,
+            elapsed: start.elapsed(),
+        };
+        tracing::info!(
+            proc_id = %summary.proc_id,
+            actors = summary.actors,
+            remaining = summary.remaining,
+            "drained proc in {:?}",
+            summary.elapsed,
+        );
+        message.reply.post(cx, summary);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<LoadPlugin> for ProcAgent {
+    async fn handle(&mut self, cx: &Context<Self>, message: LoadPlugin) -> anyhow::Result<()> {
+        let result = crate::plugin::load_plugin(&message.path).map_err(|err| {
+            tracing::error!(path = %message.path.display(), "failed to load plugin: {}", err);
+            err.to_string()
+        });
+        message.reply.post(
+            cx,
+            PluginLoaded {
+                rank: cx.cast_point().rank(),
+                proc_id: self.proc.proc_addr(),
+                result,
+            },
+        );
+        Ok(())
+    }
+}
+
 /// A local handler to get a new client instance on the proc.
 /// This is used to create root client instances.
 #[derive(Debug, hyperactor::Handler, hyperactor::HandleClient)]
@@ -1277,6 +1826,52 @@ mod tests {
     struct ExtraActor;
     impl hyperactor::Actor for ExtraActor {}
     hyperactor::register_spawnable!(ExtraActor);
+
+    // An actor that fails with the reason it is sent.
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    #[hyperactor::export(handlers = [String])]
+    struct FailActor;
+    impl hyperactor::Actor for FailActor {}
+    hyperactor::register_spawnable!(FailActor);
+
+    #[async_trait]
+    impl Handler<String> for FailActor {
+        async fn handle(&mut self, _cx: &Context<Self>, reason: String) -> anyhow::Result<()> {
+            anyhow::bail!(reason)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wait_drained() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let sleeper = proc.spawn_with_label("sleeper", crate::testactor::SleepActor);
+        let port = sleeper.port::<Duration>();
+        for _ in 0..3 {
+            port.post(&client, Duration::from_millis(50));
+        }
+
+        let cells = proc.close_intake(&[sleeper.actor_addr().id().clone()]);
+        assert_eq!(cells.len(), 1);
+        let start = tokio::time::Instant::now();
+        let deadline = start + Duration::from_secs(30);
+        assert_eq!(wait_drained(&cells, deadline).await, 0);
+        assert!(start.elapsed() < Duration::from_secs(30));
+        assert!(!matches!(
+            *cells[0].status().borrow(),
+            ActorStatus::Processing(..)
+        ));
+
+        // Messages still queued at the deadline are reported.
+        let sleeper = proc.spawn_with_label("slow", crate::testactor::SleepActor);
+        let port = sleeper.port::<Duration>();
+        for _ in 0..3 {
+            port.post(&client, Duration::from_secs(60));
+        }
+        let cells = proc.close_intake(&[sleeper.actor_addr().id().clone()]);
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(100);
+        assert!(wait_drained(&cells, deadline).await > 0);
+    }
     // Verifies that QueryChild(Addr::Proc) on a ProcAgent returns
     // a live IntrospectResult whose children reflect actors spawned
     // directly on the proc — i.e. via proc.spawn_with_label(), which bypasses the
@@ -1785,4 +2380,96 @@ mod tests {
         // Unblock the actor.
         gate.notify_one();
     }
+
+    #[tokio::test]
+    async fn test_spawn_remote() {
+        use hyperactor::Proc;
+        use hyperactor::actor::ActorStatus;
+        use hyperactor::channel::ChannelTransport;
+
+        use crate::proc_mesh::ProcRef;
+
+        let proc = Proc::direct(ChannelTransport::Unix.any(), "test_proc".to_string()).unwrap();
+        let agent_handle = ProcAgent::boot_v1(proc.clone(), None).unwrap();
+        agent_handle
+            .status()
+            .wait_for(|s| matches!(s, ActorStatus::Idle))
+            .await
+            .unwrap();
+        let client_proc = Proc::direct(ChannelTransport::Unix.any(), "client".to_string()).unwrap();
+        let client = client_proc.client("client");
+        let agent: ActorRef<ProcAgent> = agent_handle.bind();
+        let proc_ref = ProcRef::new(proc.proc_addr(), 0, agent.clone());
+
+        let actor = proc_ref
+            .spawn_remote::<ExtraActor>(&client, &())
+            .await
+            .unwrap();
+        assert_eq!(actor.actor_addr().proc_addr(), proc.proc_addr());
+        assert!(proc.get_instance(actor.actor_addr()).is_some());
+
+        // A type that the proc does not register is reported as such.
+        let result = agent
+            .spawn_actor(&client, "no::such::Actor".to_string(), Vec::new(), 0)
+            .await
+            .unwrap();
+        assert_eq!(
+            result,
+            Err(SpawnActorError::NotRegistered(
+                "no::such::Actor".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_spawn_remote_failure_is_reported() {
+        use hyperactor::Proc;
+        use hyperactor::actor::ActorStatus;
+        use hyperactor::channel::ChannelTransport;
+
+        use crate::proc_mesh::ProcRef;
+        use crate::resource::GetStateClient;
+        use crate::resource::StreamStateClient;
+
+        let proc = Proc::direct(ChannelTransport::Unix.any(), "test_proc".to_string()).unwrap();
+        let agent_handle = ProcAgent::boot_v1(proc.clone(), None).unwrap();
+        agent_handle
+            .status()
+            .wait_for(|s| matches!(s, ActorStatus::Idle))
+            .await
+            .unwrap();
+        let client = proc.client("client");
+        let agent: ActorRef<ProcAgent> = agent_handle.bind();
+        let proc_ref = ProcRef::new(proc.proc_addr(), 3, agent.clone());
+
+        let actor = proc_ref
+            .spawn_remote::<FailActor>(&client, &())
+            .await
+            .unwrap();
+        // The actor is tracked under the resource id of its uid.
+        let id = ResourceId::new(actor.actor_addr().uid().clone(), None);
+        let state = agent.get_state(&client, id.clone()).await.unwrap();
+        assert_eq!(state.status, resource::Status::Running);
+        assert_eq!(state.state.unwrap().create_rank, 3);
+
+        let (sub_port, mut sub_rx) = client.open_port::<resource::State<ActorState>>();
+        agent
+            .stream_state(&client, id.clone(), sub_port.bind())
+            .await
+            .unwrap();
+        assert_eq!(
+            sub_rx.recv().await.unwrap().status,
+            resource::Status::Running
+        );
+
+        actor.post(&client, "test failure".to_string());
+        let failed = sub_rx.recv().await.unwrap();
+        let resource::Status::Failed(reason) = &failed.status else {
+            panic!("expected failure, got {:?}", failed.status);
+        };
+        assert!(reason.contains("test failure"), "{}", reason);
+        let supervision_events = failed.state.unwrap().supervision_events;
+        assert_eq!(supervision_events.len(), 1);
+        assert_eq!(supervision_events[0].actor_id.id(), actor.actor_addr().id());
+    }
 }
@@ -57,6 +57,14 @@ use typeuri::Named;
 use crate::bootstrap;
 use crate::bootstrap::BootstrapCommand;
 
+#[cfg(target_os = "linux")]
+mod cgroup;
+#[cfg(target_os = "linux")]
+pub(crate) use cgroup::CgroupProcLauncher;
+#[cfg(target_os = "linux")]
+mod container;
+#[cfg(target_os = "linux")]
+pub(crate) use container::ContainerProcLauncher;
 mod native;
 pub(crate) use native::NativeProcLauncher;
 
@@ -162,10 +170,11 @@ pub enum ProcLauncherError {
     Other(String),
 }
 
-/// Per-process CPU/NUMA binding configuration.
+/// Per-process CPU/NUMA/GPU binding configuration.
 ///
 /// When attached to a proc spec, the bootstrap command is wrapped with
-/// `numactl` (on NUMA systems) or `taskset` (Linux fallback) before launch.
+/// `numactl` (on NUMA systems) or `taskset` (Linux fallback) before launch,
+/// and the proc's visible GPUs are restricted to `gpus`.
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Named)]
 pub struct ProcBind {
     /// NUMA node for CPU binding (`numactl --cpunodebind`).
@@ -176,6 +185,8 @@ pub struct ProcBind {
     pub physcpubind: Option<String>,
     /// CPU set for taskset fallback (`taskset -c`).
     pub cpus: Option<String>,
+    /// Visible GPU list (`CUDA_VISIBLE_DEVICES`).
+    pub gpus: Option<String>,
 }
 wirevalue::register_type!(ProcBind);
 
@@ -186,6 +197,7 @@ impl From<HashMap<String, String>> for ProcBind {
             membind: map.get("membind").cloned(),
             physcpubind: map.get("physcpubind").cloned(),
             cpus: map.get("cpus").cloned(),
+            gpus: map.get("gpus").cloned(),
         }
     }
 }
@@ -257,7 +269,7 @@ pub struct LaunchOptions {
     /// wire their own forwarding mechanism.
     pub log_channel: Option<ChannelAddr>,
 
-    /// Optional CPU/NUMA binding for this proc.
+    /// Optional CPU/NUMA/GPU binding for this proc.
     ///
     /// Launchers that support binding should apply it using
     /// backend-appropriate mechanisms (e.g., `numactl` for native,
@@ -297,6 +309,24 @@ pub fn format_process_name(proc_id: &hyperactor::ProcAddr) -> String {
     format!("{} @ {}", who, host)
 }
 
+/// A name derived from `proc_id` that is safe to use as a path
+/// component or a container name: its display form, with every
+/// character other than ASCII alphanumerics, `-`, `_` and `.`
+/// replaced by `_`.
+pub(crate) fn sanitized_proc_name(proc_id: &hyperactor::ProcAddr) -> String {
+    proc_id
+        .to_string()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
 /// Strategy interface for launching and stopping a proc.
 ///
 /// This trait is internal to `hyperactor_mesh`:
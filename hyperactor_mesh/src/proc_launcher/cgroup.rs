@@ -0,0 +1,342 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! cgroup v2 process launcher.
+//!
+//! This module provides [`CgroupProcLauncher`], a [`ProcLauncher`]
+//! backend that runs bootstrap procs as local OS processes, like
+//! [`NativeProcLauncher`], but places each proc in its own cgroup
+//! under [`MESH_CGROUP_ROOT`] with the CPU and memory limits given by
+//! [`MESH_CGROUP_CPU_MAX`] and [`MESH_CGROUP_MEMORY_MAX`].
+//!
+//! ## Mechanics
+//!
+//! - The bootstrap command is wrapped in `/bin/sh`, which moves
+//!   itself into the proc's cgroup before `exec`ing the command, so
+//!   the proc and all of its descendants are accounted from the
+//!   start.
+//! - Spawning, stdio and signaling are delegated to
+//!   [`NativeProcLauncher`].
+//! - When the proc exits, any descendants left in its cgroup are
+//!   killed (`cgroup.kill`), and exits caused by the cgroup's OOM
+//!   killer are reported as [`ProcExitKind::Failed`] so that they
+//!   surface as such in supervision events.
+//! - [`ProcLauncher::kill`] kills the whole cgroup at once.
+//!
+//! ## Restarts
+//!
+//! A proc's cgroup is kept after it exits, and is emptied by then, so
+//! relaunching a proc with the same [`ProcAddr`] reuses it rather
+//! than setting up a new one. All cgroups are removed when the
+//! launcher is dropped.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use hyperactor::ProcAddr;
+use tokio::sync::oneshot;
+
+use crate::bootstrap::BootstrapCommand;
+use crate::config::MESH_CGROUP_CPU_MAX;
+use crate::config::MESH_CGROUP_MEMORY_MAX;
+use crate::config::MESH_CGROUP_ROOT;
+use crate::proc_launcher::LaunchOptions;
+use crate::proc_launcher::LaunchResult;
+use crate::proc_launcher::NativeProcLauncher;
+use crate::proc_launcher::ProcExitKind;
+use crate::proc_launcher::ProcExitResult;
+use crate::proc_launcher::ProcLauncher;
+use crate::proc_launcher::ProcLauncherError;
+use crate::proc_launcher::sanitized_proc_name;
+
+/// cgroup v2 process launcher.
+///
+/// Runs each proc as a native OS process in a dedicated cgroup with
+/// CPU and memory limits.
+pub(crate) struct CgroupProcLauncher {
+    /// Spawns and signals the procs.
+    inner: NativeProcLauncher,
+    /// The directory under which proc cgroups are created.
+    root: PathBuf,
+    /// The `cpu.max` of each proc, if limited.
+    cpu_max: Option<String>,
+    /// The `memory.max` of each proc, if limited.
+    memory_max: Option<String>,
+    /// The cgroups of launched procs, kept across restarts.
+    cgroups: Arc<Mutex<HashMap<ProcAddr, PathBuf>>>,
+}
+
+impl CgroupProcLauncher {
+    /// Create a new cgroup launcher, configured from the global
+    /// config.
+    pub fn new() -> Self {
+        let non_empty = |s: String| (!s.trim().is_empty()).then(|| s.trim().to_string());
+        Self {
+            inner: NativeProcLauncher::new(),
+            root: PathBuf::from(hyperactor_config::global::get_cloned(MESH_CGROUP_ROOT)),
+            cpu_max: non_empty(hyperactor_config::global::get_cloned(MESH_CGROUP_CPU_MAX)),
+            memory_max: non_empty(hyperactor_config::global::get_cloned(
+                MESH_CGROUP_MEMORY_MAX,
+            )),
+            cgroups: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Create (or reuse) the cgroup of `proc_id` and apply the
+    /// configured limits to it.
+    fn prepare(&self, proc_id: &ProcAddr) -> io::Result<PathBuf> {
+        if self.root.as_os_str().is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "HYPERACTOR_MESH_CGROUP_ROOT is not set",
+            ));
+        }
+
+        let mut controllers = Vec::new();
+        if self.cpu_max.is_some() {
+            controllers.push("+cpu");
+        }
+        if self.memory_max.is_some() {
+            controllers.push("+memory");
+        }
+        if !controllers.is_empty() {
+            fs::write(
+                self.root.join("cgroup.subtree_control"),
+                controllers.join(" "),
+            )?;
+        }
+
+        let cgroup = self.root.join(cgroup_name(proc_id));
+        match fs::create_dir(&cgroup) {
+            Ok(()) => {}
+            // Restart: the previous incarnation's cgroup is reused.
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+        if let Some(cpu_max) = &self.cpu_max {
+            fs::write(cgroup.join("cpu.max"), cpu_max)?;
+        }
+        if let Some(memory_max) = &self.memory_max {
+            fs::write(cgroup.join("memory.max"), memory_max)?;
+        }
+
+        self.cgroups
+            .lock()
+            .expect("cgroups mutex poisoned")
+            .insert(proc_id.clone(), cgroup.clone());
+        Ok(cgroup)
+    }
+
+    /// The cgroup of `proc_id`, if it was launched.
+    fn cgroup(&self, proc_id: &ProcAddr) -> Option<PathBuf> {
+        self.cgroups
+            .lock()
+            .expect("cgroups mutex poisoned")
+            .get(proc_id)
+            .cloned()
+    }
+}
+
+/// The name of the cgroup of `proc_id`.
+fn cgroup_name(proc_id: &ProcAddr) -> String {
+    format!("proc-{}", sanitized_proc_name(proc_id))
+}
+
+/// Wrap a [`BootstrapCommand`] so that it runs in `cgroup`: a shell
+/// moves itself into the cgroup and then `exec`s the command.
+fn wrap_command_for_cgroup(mut cmd: BootstrapCommand, cgroup: &Path) -> BootstrapCommand {
+    let mut args = vec![
+        "-c".to_string(),
+        r#"echo 0 > "$0/cgroup.procs" && exec "$@""#.to_string(),
+        cgroup.to_string_lossy().into_owned(),
+        cmd.program.to_string_lossy().into_owned(),
+    ];
+    args.append(&mut cmd.args);
+    cmd.program = "/bin/sh".into();
+    cmd.arg0 = None;
+    cmd.args = args;
+    cmd
+}
+
+/// Kill every process in `cgroup`.
+fn kill_cgroup(cgroup: &Path) -> io::Result<()> {
+    fs::write(cgroup.join("cgroup.kill"), "1")
+}
+
+/// The number of processes in `cgroup` killed by the OOM killer so
+/// far, or 0 if unknown.
+fn oom_kill_count(cgroup: &Path) -> u64 {
+    fs::read_to_string(cgroup.join("memory.events"))
+        .ok()
+        .and_then(|events| parse_oom_kill_count(&events))
+        .unwrap_or(0)
+}
+
+/// Parse the `oom_kill` counter from the contents of `memory.events`.
+fn parse_oom_kill_count(events: &str) -> Option<u64> {
+    events.lines().find_map(|line| {
+        let (key, value) = line.split_once(' ')?;
+        (key == "oom_kill").then(|| value.trim().parse().ok())?
+    })
+}
+
+#[async_trait]
+impl ProcLauncher for CgroupProcLauncher {
+    /// Launch a bootstrap proc in its cgroup.
+    ///
+    /// The proc is spawned by the inner [`NativeProcLauncher`]; its
+    /// `exit_rx` is relayed once the cgroup has been emptied, with
+    /// OOM kills reported as [`ProcExitKind::Failed`].
+    async fn launch(
+        &self,
+        proc_id: &ProcAddr,
+        mut opts: LaunchOptions,
+    ) -> Result<LaunchResult, ProcLauncherError> {
+        let cgroup = self.prepare(proc_id).map_err(ProcLauncherError::Launch)?;
+        let oom_kills = oom_kill_count(&cgroup);
+        opts.command = wrap_command_for_cgroup(opts.command, &cgroup);
+
+        let mut result = self.inner.launch(proc_id, opts).await?;
+
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let inner_exit_rx = std::mem::replace(&mut result.exit_rx, exit_rx);
+        let memory_max = self.memory_max.clone();
+        let proc_id = proc_id.clone();
+        tokio::spawn(async move {
+            let mut exit = inner_exit_rx.await.unwrap_or_else(|_| ProcExitResult {
+                kind: ProcExitKind::Failed {
+                    reason: "exit monitor dropped".to_string(),
+                },
+                stderr_tail: None,
+            });
+
+            // Descendants may outlive the proc's process group; kill
+            // them so the cgroup is empty and can be reused on restart.
+            if let Err(e) = kill_cgroup(&cgroup) {
+                tracing::debug!(%proc_id, error = %e, "failed to empty cgroup");
+            }
+
+            if let ProcExitKind::Signaled {
+                signal: libc::SIGKILL,
+                ..
+            } = exit.kind
+                && oom_kill_count(&cgroup) > oom_kills
+            {
+                exit.kind = ProcExitKind::Failed {
+                    reason: format!(
+                        "killed by the OOM killer: exceeded memory.max of {}",
+                        memory_max.as_deref().unwrap_or("max")
+                    ),
+                };
+            }
+
+            let _ = exit_tx.send(exit);
+        });
+
+        Ok(result)
+    }
+
+    /// Initiate graceful termination; delegated to the inner
+    /// launcher.
+    async fn terminate(
+        &self,
+        proc_id: &ProcAddr,
+        timeout: Duration,
+    ) -> Result<(), ProcLauncherError> {
+        self.inner.terminate(proc_id, timeout).await
+    }
+
+    /// Kill every process in the proc's cgroup, falling back to the
+    /// inner launcher where `cgroup.kill` is unavailable.
+    async fn kill(&self, proc_id: &ProcAddr) -> Result<(), ProcLauncherError> {
+        if let Some(cgroup) = self.cgroup(proc_id)
+            && kill_cgroup(&cgroup).is_ok()
+        {
+            return Ok(());
+        }
+        self.inner.kill(proc_id).await
+    }
+}
+
+impl std::fmt::Debug for CgroupProcLauncher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CgroupProcLauncher")
+            .field("root", &self.root)
+            .field("cpu_max", &self.cpu_max)
+            .field("memory_max", &self.memory_max)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for CgroupProcLauncher {
+    // Best-effort cleanup: kill anything left in the procs' cgroups,
+    // and remove the cgroups. Removal fails for cgroups whose
+    // processes have not been reaped yet; those are left behind.
+    fn drop(&mut self) {
+        let cgroups = self.cgroups.lock().expect("cgroups mutex poisoned");
+        for (proc_id, cgroup) in cgroups.iter() {
+            let _ = kill_cgroup(cgroup);
+            if let Err(e) = fs::remove_dir(cgroup) {
+                tracing::debug!(%proc_id, error = %e, "drop cleanup: failed to remove cgroup");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor::testing::ids::test_proc_id;
+
+    use super::*;
+
+    #[test]
+    fn test_cgroup_name() {
+        let name = cgroup_name(&test_proc_id("worker"));
+        assert!(name.starts_with("proc-"));
+        assert!(
+            name.chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wrap_command_for_cgroup() {
+        // A plain directory stands in for the cgroup: the wrapper
+        // writes to its `cgroup.procs` and then execs the command.
+        let dir = tempfile::tempdir().unwrap();
+        let cmd = BootstrapCommand {
+            program: PathBuf::from("/bin/sh"),
+            args: vec!["-c".into(), "exit 7".into()],
+            ..Default::default()
+        };
+        let status = wrap_command_for_cgroup(cmd, dir.path())
+            .new()
+            .status()
+            .await
+            .unwrap();
+        assert_eq!(status.code(), Some(7));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("cgroup.procs")).unwrap(),
+            "0\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_oom_kill_count() {
+        let events = "low 0\nhigh 0\nmax 12\noom 3\noom_kill 2\noom_group_kill 0\n";
+        assert_eq!(parse_oom_kill_count(events), Some(2));
+        assert_eq!(parse_oom_kill_count("low 0\n"), None);
+    }
+}
@@ -0,0 +1,419 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Container process launcher.
+//!
+//! This module provides [`ContainerProcLauncher`], a [`ProcLauncher`]
+//! backend that runs each bootstrap proc in a container of
+//! [`MESH_CONTAINER_IMAGE`], using `docker` or `podman`
+//! ([`MESH_CONTAINER_RUNTIME`]). This lets procs with different
+//! dependency sets share a host.
+//!
+//! ## Mechanics
+//!
+//! The proc is launched by [`NativeProcLauncher`] as an attached
+//! `<runtime> run --rm` invocation, so that stdio, exit status and
+//! `SIGTERM` are relayed by the runtime client. The container:
+//! - shares the host's network and IPC namespaces, so the proc can
+//!   reach, and be reached by, its host over the usual channels;
+//! - mounts the proc socket directory, and the workspace
+//!   ([`MESH_CONTAINER_WORKSPACE`], typically the code_sync
+//!   workspace) at the same paths as on the host;
+//! - receives the bootstrap environment variables set by the native
+//!   launcher.
+//!
+//! CPU and memory-node bindings become `--cpuset-*` options of the
+//! container. As signals to the runtime client do not reach the
+//! container, [`ProcLauncher::terminate`] and [`ProcLauncher::kill`]
+//! go through the runtime (`stop` and `kill`).
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use hyperactor::ProcAddr;
+use tokio::process::Command;
+use tokio::sync::oneshot;
+
+use crate::bootstrap::BOOTSTRAP_LOG_CHANNEL;
+use crate::bootstrap::BOOTSTRAP_MODE_ENV;
+use crate::bootstrap::Bootstrap;
+use crate::bootstrap::BootstrapCommand;
+use crate::bootstrap::PROCESS_NAME_ENV;
+use crate::config::MESH_CONTAINER_ARGS;
+use crate::config::MESH_CONTAINER_IMAGE;
+use crate::config::MESH_CONTAINER_RUNTIME;
+use crate::config::MESH_CONTAINER_WORKSPACE;
+use crate::proc_launcher::LaunchOptions;
+use crate::proc_launcher::LaunchResult;
+use crate::proc_launcher::NativeProcLauncher;
+use crate::proc_launcher::ProcBind;
+use crate::proc_launcher::ProcExitKind;
+use crate::proc_launcher::ProcExitResult;
+use crate::proc_launcher::ProcLauncher;
+use crate::proc_launcher::ProcLauncherError;
+use crate::proc_launcher::sanitized_proc_name;
+
+/// Container process launcher.
+///
+/// Runs each proc in its own container, via the runtime's CLI.
+pub(crate) struct ContainerProcLauncher {
+    /// Runs the runtime client.
+    inner: NativeProcLauncher,
+    /// The container runtime binary.
+    runtime: String,
+    /// The image of the containers.
+    image: String,
+    /// The workspace mounted in the containers, if any.
+    workspace: Option<PathBuf>,
+    /// Additional arguments to `<runtime> run`.
+    extra_args: Vec<String>,
+    /// The containers of running procs.
+    containers: Arc<Mutex<HashMap<ProcAddr, String>>>,
+}
+
+impl ContainerProcLauncher {
+    /// Create a new container launcher, configured from the global
+    /// config.
+    pub fn new() -> Self {
+        let runtime = hyperactor_config::global::get_cloned(MESH_CONTAINER_RUNTIME);
+        let workspace = hyperactor_config::global::get_cloned(MESH_CONTAINER_WORKSPACE);
+        Self {
+            inner: NativeProcLauncher::new(),
+            runtime: if runtime.trim().is_empty() {
+                "docker".to_string()
+            } else {
+                runtime.trim().to_string()
+            },
+            image: hyperactor_config::global::get_cloned(MESH_CONTAINER_IMAGE),
+            workspace: (!workspace.trim().is_empty()).then(|| PathBuf::from(workspace.trim())),
+            extra_args: hyperactor_config::global::get_cloned(MESH_CONTAINER_ARGS),
+            containers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// The runtime invocation that runs `cmd` in the container
+    /// `name`.
+    fn container_command(
+        &self,
+        name: &str,
+        cmd: BootstrapCommand,
+        socket_dir: Option<&Path>,
+        bind: Option<&ProcBind>,
+    ) -> BootstrapCommand {
+        let mut args: Vec<String> = [
+            "run",
+            "--rm",
+            "--name",
+            name,
+            "--network=host",
+            "--ipc=host",
+        ]
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+
+        // Pass through the variables set on the runtime client by
+        // the native launcher, and those of the command itself; unset
+        // ones are skipped by the runtime. Values stay in the client's
+        // environment rather than on its command line.
+        let mut command_vars: Vec<&str> = cmd.env.keys().map(String::as_str).collect();
+        command_vars.sort_unstable();
+        for var in [
+            BOOTSTRAP_MODE_ENV,
+            PROCESS_NAME_ENV,
+            BOOTSTRAP_LOG_CHANNEL,
+            "CUDA_VISIBLE_DEVICES",
+        ]
+        .into_iter()
+        .chain(command_vars)
+        {
+            args.push("-e".to_string());
+            args.push(var.to_string());
+        }
+
+        for dir in socket_dir.into_iter().chain(self.workspace.as_deref()) {
+            args.push("-v".to_string());
+            args.push(format!("{0}:{0}", dir.display()));
+        }
+        if let Some(workspace) = &self.workspace {
+            args.push("-w".to_string());
+            args.push(workspace.display().to_string());
+        }
+
+        if let Some(bind) = bind {
+            if let Some(cpus) = bind.physcpubind.as_deref().or(bind.cpus.as_deref()) {
+                args.push(format!("--cpuset-cpus={cpus}"));
+            }
+            if let Some(mems) = bind.membind.as_deref() {
+                args.push(format!("--cpuset-mems={mems}"));
+            }
+        }
+
+        args.extend(self.extra_args.iter().cloned());
+        args.push(self.image.clone());
+        args.push(cmd.program.to_string_lossy().into_owned());
+        args.extend(cmd.args);
+
+        BootstrapCommand {
+            program: self.runtime.clone().into(),
+            arg0: None,
+            args,
+            env: cmd.env,
+        }
+    }
+
+    /// Run `<runtime> <args>` in the background, without waiting for
+    /// it to complete.
+    fn spawn_runtime(&self, args: &[&str]) -> io::Result<()> {
+        let mut child = Command::new(&self.runtime)
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        tokio::spawn(async move {
+            let _ = child.wait().await;
+        });
+        Ok(())
+    }
+
+    /// The container of `proc_id`, if it is running.
+    fn container(&self, proc_id: &ProcAddr) -> Option<String> {
+        self.containers
+            .lock()
+            .expect("containers mutex poisoned")
+            .get(proc_id)
+            .cloned()
+    }
+}
+
+/// The name of the container of `proc_id`.
+fn container_name(proc_id: &ProcAddr) -> String {
+    format!("monarch-{}", sanitized_proc_name(proc_id))
+}
+
+#[async_trait]
+impl ProcLauncher for ContainerProcLauncher {
+    /// Launch a bootstrap proc in a container.
+    ///
+    /// The runtime client is spawned by the inner
+    /// [`NativeProcLauncher`]; its exit status, which is the
+    /// container's, is relayed on `exit_rx`.
+    async fn launch(
+        &self,
+        proc_id: &ProcAddr,
+        mut opts: LaunchOptions,
+    ) -> Result<LaunchResult, ProcLauncherError> {
+        if self.image.trim().is_empty() {
+            return Err(ProcLauncherError::Launch(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "HYPERACTOR_MESH_CONTAINER_IMAGE is not set",
+            )));
+        }
+
+        let name = container_name(proc_id);
+        // Remove any container left behind by a previous incarnation
+        // of the proc, which would otherwise hold the name.
+        let _ = Command::new(&self.runtime)
+            .args(["rm", "-f", &name])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await;
+
+        let socket_dir = match Bootstrap::from_env_safe_string(&opts.bootstrap_payload) {
+            Ok(Bootstrap::Proc {
+                socket_dir_path, ..
+            }) => Some(socket_dir_path),
+            _ => None,
+        };
+        let bind = opts.proc_bind.take();
+        opts.command =
+            self.container_command(&name, opts.command, socket_dir.as_deref(), bind.as_ref());
+        // CPUs are bound by the runtime; only the GPU binding is left
+        // to the inner launcher, which exports it to the client.
+        opts.proc_bind = bind.map(|bind| ProcBind {
+            gpus: bind.gpus,
+            ..Default::default()
+        });
+
+        self.containers
+            .lock()
+            .expect("containers mutex poisoned")
+            .insert(proc_id.clone(), name);
+        let mut result = match self.inner.launch(proc_id, opts).await {
+            Ok(result) => result,
+            Err(e) => {
+                self.containers
+                    .lock()
+                    .expect("containers mutex poisoned")
+                    .remove(proc_id);
+                return Err(e);
+            }
+        };
+
+        let (exit_tx, exit_rx) = oneshot::channel();
+        let inner_exit_rx = std::mem::replace(&mut result.exit_rx, exit_rx);
+        let containers = Arc::clone(&self.containers);
+        let proc_id = proc_id.clone();
+        tokio::spawn(async move {
+            let exit = inner_exit_rx.await.unwrap_or_else(|_| ProcExitResult {
+                kind: ProcExitKind::Failed {
+                    reason: "exit monitor dropped".to_string(),
+                },
+                stderr_tail: None,
+            });
+            containers
+                .lock()
+                .expect("containers mutex poisoned")
+                .remove(&proc_id);
+            let _ = exit_tx.send(exit);
+        });
+
+        Ok(result)
+    }
+
+    /// Initiate graceful termination with `<runtime> stop`, which
+    /// escalates to `SIGKILL` after `timeout`.
+    async fn terminate(
+        &self,
+        proc_id: &ProcAddr,
+        timeout: Duration,
+    ) -> Result<(), ProcLauncherError> {
+        let Some(name) = self.container(proc_id) else {
+            // Idempotent success: already exited or unknown.
+            return Ok(());
+        };
+        let secs = timeout.as_secs().max(1).to_string();
+        self.spawn_runtime(&["stop", "--time", &secs, &name])
+            .map_err(|e| ProcLauncherError::Terminate(format!("{} stop: {e}", self.runtime)))
+    }
+
+    /// Initiate an immediate kill with `<runtime> kill`.
+    async fn kill(&self, proc_id: &ProcAddr) -> Result<(), ProcLauncherError> {
+        let Some(name) = self.container(proc_id) else {
+            // Idempotent success.
+            return Ok(());
+        };
+        self.spawn_runtime(&["kill", &name])
+            .map_err(|e| ProcLauncherError::Kill(format!("{} kill: {e}", self.runtime)))
+    }
+}
+
+impl std::fmt::Debug for ContainerProcLauncher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContainerProcLauncher")
+            .field("runtime", &self.runtime)
+            .field("image", &self.image)
+            .field("workspace", &self.workspace)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for ContainerProcLauncher {
+    // Best-effort cleanup: killing the runtime clients (done by the
+    // inner launcher) does not stop their containers, so remove them
+    // explicitly.
+    fn drop(&mut self) {
+        let containers = self.containers.lock().expect("containers mutex poisoned");
+        for (proc_id, name) in containers.iter() {
+            if let Err(e) = std::process::Command::new(&self.runtime)
+                .args(["rm", "-f", name])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+            {
+                tracing::warn!(%proc_id, error = %e, "drop cleanup: failed to remove container");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn launcher(workspace: Option<&str>) -> ContainerProcLauncher {
+        ContainerProcLauncher {
+            inner: NativeProcLauncher::new(),
+            runtime: "podman".to_string(),
+            image: "monarch:latest".to_string(),
+            workspace: workspace.map(PathBuf::from),
+            extra_args: vec!["--gpus=all".to_string()],
+            containers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn command() -> BootstrapCommand {
+        BootstrapCommand {
+            program: PathBuf::from("/workspace/bin/bootstrap"),
+            arg0: Some("bootstrap".to_string()),
+            args: vec!["--flag".to_string()],
+            env: HashMap::from([("KEY".to_string(), "value".to_string())]),
+        }
+    }
+
+    #[test]
+    fn test_container_command() {
+        let cmd = launcher(None).container_command("monarch-proc", command(), None, None);
+        assert_eq!(cmd.program, PathBuf::from("podman"));
+        assert_eq!(cmd.arg0, None);
+        assert_eq!(cmd.env["KEY"], "value");
+        assert_eq!(
+            cmd.args,
+            vec![
+                "run",
+                "--rm",
+                "--name",
+                "monarch-proc",
+                "--network=host",
+                "--ipc=host",
+                "-e",
+                BOOTSTRAP_MODE_ENV,
+                "-e",
+                PROCESS_NAME_ENV,
+                "-e",
+                BOOTSTRAP_LOG_CHANNEL,
+                "-e",
+                "CUDA_VISIBLE_DEVICES",
+                "-e",
+                "KEY",
+                "--gpus=all",
+                "monarch:latest",
+                "/workspace/bin/bootstrap",
+                "--flag",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_container_command_mounts_and_binding() {
+        let bind = ProcBind {
+            membind: Some("1".to_string()),
+            cpus: Some("0-7".to_string()),
+            ..Default::default()
+        };
+        let cmd = launcher(Some("/workspace")).container_command(
+            "monarch-proc",
+            command(),
+            Some(Path::new("/tmp/sockets")),
+            Some(&bind),
+        );
+        let args = cmd.args.join(" ");
+        assert!(
+            args.contains("-v /tmp/sockets:/tmp/sockets -v /workspace:/workspace -w /workspace")
+        );
+        assert!(args.contains("--cpuset-cpus=0-7 --cpuset-mems=1 --gpus=all monarch:latest"));
+    }
+}
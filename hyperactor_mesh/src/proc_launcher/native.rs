@@ -331,6 +331,15 @@ impl ProcLauncher for NativeProcLauncher {
         // Diagnostics name
         cmd.env(PROCESS_NAME_ENV, opts.process_name);
 
+        // GPU binding
+        if let Some(gpus) = opts
+            .proc_bind
+            .as_ref()
+            .and_then(|bind| bind.gpus.as_deref())
+        {
+            cmd.env("CUDA_VISIBLE_DEVICES", gpus);
+        }
+
         // Manager may decide to create a log-forwarding channel; if
         // so, native passes it through via BOOTSTRAP_LOG_CHANNEL.
         if let Some(addr) = &opts.log_channel {
@@ -722,6 +731,7 @@ mod tests {
             command: None,
             config: None,
             exit_on_shutdown: false,
+            hello: None,
         };
         let proc_id = test_proc_id("7");
         let opts = LaunchOptions {
@@ -814,6 +824,7 @@ mod tests {
                 command: None,
                 config: None,
                 exit_on_shutdown: false,
+                hello: None,
             };
             let proc_id = ResourceId::proc_addr_from_name(any_unix_addr(), "stdio-captured");
             let opts = LaunchOptions {
@@ -853,6 +864,7 @@ mod tests {
                 command: None,
                 config: None,
                 exit_on_shutdown: false,
+                hello: None,
             };
             let proc_id = ResourceId::proc_addr_from_name(any_unix_addr(), "stdio-inherited");
             let opts = LaunchOptions {
@@ -896,6 +908,7 @@ mod tests {
             command: None,
             config: None,
             exit_on_shutdown: false,
+            hello: None,
         };
         let proc_id = ResourceId::proc_addr_from_name(any_unix_addr(), "exit-7");
         let opts = LaunchOptions {
@@ -940,6 +953,7 @@ mod tests {
             command: None,
             config: None,
             exit_on_shutdown: false,
+            hello: None,
         };
         let proc_id = ResourceId::proc_addr_from_name(any_unix_addr(), "killed");
         let opts = LaunchOptions {
@@ -1017,6 +1031,7 @@ mod tests {
             command: None,
             config: None,
             exit_on_shutdown: false,
+            hello: None,
         };
         let proc_id = ResourceId::proc_addr_from_name(any_unix_addr(), "term-escalate");
         let opts = LaunchOptions {
@@ -1110,6 +1125,7 @@ while True:
             command: None,
             config: None,
             exit_on_shutdown: false,
+            hello: None,
         };
         let proc_id = ResourceId::proc_addr_from_name(any_unix_addr(), "drop-cleanup-test");
         let opts = LaunchOptions {
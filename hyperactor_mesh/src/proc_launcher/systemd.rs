@@ -1197,6 +1197,7 @@ mod tests {
             command: None,
             config: None,
             exit_on_shutdown: false,
+            hello: None,
         };
         let opts = LaunchOptions {
             command: with_sh(script),
@@ -1298,6 +1299,7 @@ mod tests {
             command: None,
             config: None,
             exit_on_shutdown: false,
+            hello: None,
         };
         let proc_id = ResourceId::proc_addr_from_name(any_unix_addr(), "exit-7");
         let opts = LaunchOptions {
@@ -1345,6 +1347,7 @@ mod tests {
             command: None,
             config: None,
             exit_on_shutdown: false,
+            hello: None,
         };
         let proc_id = ResourceId::proc_addr_from_name(any_unix_addr(), "killed");
         let opts = LaunchOptions {
@@ -1412,6 +1415,7 @@ mod tests {
             command: None,
             config: None,
             exit_on_shutdown: false,
+            hello: None,
         };
         let proc_id = ResourceId::proc_addr_from_name(any_unix_addr(), "terminated");
         let opts = LaunchOptions {
@@ -1587,6 +1591,7 @@ mod tests {
             command: None,
             config: None,
             exit_on_shutdown: false,
+            hello: None,
         };
         let proc_id = ResourceId::proc_addr_from_name(any_unix_addr(), "drop-cleanup-test");
 
@@ -1694,6 +1699,7 @@ mod tests {
             command: None,
             config: None,
             exit_on_shutdown: false,
+            hello: None,
         };
         let opts = LaunchOptions {
             command: with_sh("sleep 60"),
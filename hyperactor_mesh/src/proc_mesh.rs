@@ -12,6 +12,7 @@ use std::collections::HashSet;
 use std::fmt;
 use std::hash::Hash;
 use std::ops::Deref;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -24,7 +25,6 @@ use hyperactor::RemoteMessage;
 use hyperactor::RemoteSpawn;
 use hyperactor::accum::StreamingReducerOpts;
 use hyperactor::actor::ActorStatus;
-use hyperactor::actor::Referable;
 use hyperactor::actor::remote::Remote;
 use hyperactor::context;
 use hyperactor::id::Label;
@@ -34,6 +34,8 @@ use hyperactor_config::ConfigAttr;
 use hyperactor_config::attrs::declare_attrs;
 use hyperactor_telemetry::hash_to_u64;
 use ndslice::Extent;
+use ndslice::Point;
+use ndslice::Range;
 use ndslice::ViewExt as _;
 use ndslice::view;
 use ndslice::view::CollectMeshExt;
@@ -56,9 +58,19 @@ use crate::mesh_controller::ActorMeshControlPlane;
 use crate::mesh_controller::ActorMeshController;
 use crate::mesh_id::ActorMeshId;
 use crate::mesh_id::ProcMeshId;
+use crate::plugin::LoadPlugin;
+use crate::plugin::PLUGIN_LOAD_TIMEOUT;
+use crate::plugin::PluginInfo;
+use crate::plugin::PluginLoaded;
 use crate::proc_agent;
 use crate::proc_agent::ActorState;
+use crate::proc_agent::DrainSummary;
 use crate::proc_agent::ProcAgent;
+use crate::proc_agent::SpawnActorClient;
+use crate::registry::REGISTRY_ACTOR_NAME;
+use crate::registry::Registry;
+use crate::registry::RegistryActor;
+use crate::registry::SPAWN_REGISTRY;
 use crate::resource;
 use crate::resource::GetRankStatus;
 use crate::resource::Status;
@@ -117,14 +129,40 @@ impl ProcRef {
         &self.proc_id
     }
 
+    /// The rank of this proc at creation, in the root proc mesh.
+    pub(crate) fn create_rank(&self) -> usize {
+        self.create_rank
+    }
+
     pub(crate) fn actor_addr(&self, id: &ActorMeshId) -> ActorAddr {
         self.proc_id.actor_addr_uid(id.uid().clone())
     }
 
-    /// Generic bound: `A: Referable` - required because we return
-    /// an `ActorRef<A>`.
-    pub(crate) fn attest<A: Referable>(&self, id: &ActorMeshId) -> ActorRef<A> {
-        ActorRef::attest(self.actor_addr(id))
+    /// Spawn a single actor of type `A` on this proc, shipping it the
+    /// serialized `params`, and return a reference to it. The actor is
+    /// a root actor of the proc, outside of any actor mesh. Spawning
+    /// fails with [`Error::RemoteSpawnError`] if the proc's image does
+    /// not register `A`, or if the actor fails to spawn.
+    pub async fn spawn_remote<A: RemoteSpawn>(
+        &self,
+        cx: &impl context::Actor,
+        params: &A::Params,
+    ) -> crate::Result<ActorRef<A>>
+    where
+        A::Params: RemoteMessage,
+    {
+        let actor_type = Remote::global()
+            .name_of::<A>()
+            .ok_or(Error::ActorTypeNotRegistered(type_name::<A>().to_string()))?
+            .to_string();
+        let params_data = bincode::serde::encode_to_vec(params, bincode::config::legacy())?;
+        let actor_addr = self
+            .agent
+            .spawn_actor(cx, actor_type, params_data, self.create_rank)
+            .await
+            .map_err(|err| Error::CallError(self.agent.actor_addr().clone(), err))?
+            .map_err(|err| Error::RemoteSpawnError(self.proc_id.clone(), err))?;
+        Ok(ActorRef::attest(actor_addr))
     }
 }
 
@@ -137,6 +175,7 @@ pub struct ProcMesh {
     comm_actor_name: Option<ActorMeshId>,
     current_ref: ProcMeshRef,
     controller: Option<ActorRef<crate::mesh_controller::ProcMeshController>>,
+    registry: Option<Registry>,
 }
 
 impl ProcMesh {
@@ -230,6 +269,7 @@ impl ProcMesh {
             comm_actor_name: Some(comm_actor_name.clone()),
             current_ref,
             controller: None,
+            registry: None,
         };
 
         // CommActor satisfies `Actor + Referable`, so it can be
@@ -244,14 +284,46 @@ impl ProcMesh {
             .collect();
         // Now that we have all of the spawned comm actors, kick them all into
         // mesh mode.
+        let slice = view::Ranked::region(&proc_mesh.current_ref).slice().clone();
         for (rank, comm_actor) in &address_book {
-            comm_actor.post(cx, CommMeshConfig::new(*rank, address_book.clone()));
+            comm_actor.post(
+                cx,
+                CommMeshConfig::new(*rank, address_book.clone()).with_slice(slice.clone()),
+            );
         }
         proc_mesh.current_ref.root_comm_actor = Some(root_comm_actor);
 
+        if hyperactor_config::global::get(SPAWN_REGISTRY) {
+            // Like the comm actors, the registry is a system actor.
+            let region = view::Ranked::region(&proc_mesh.current_ref);
+            let first = region
+                .labels()
+                .iter()
+                .try_fold(region.clone(), |first, label| first.range(label, 0))
+                .expect("in range");
+            let first = view::RankedSliceable::sliced(&proc_mesh.current_ref, first);
+            let _: ActorMesh<RegistryActor> = first
+                .spawn_with_name(
+                    cx,
+                    ActorMeshId::singleton(Label::new(REGISTRY_ACTOR_NAME).unwrap()),
+                    &Default::default(),
+                    None,
+                    true,
+                )
+                .await?;
+            proc_mesh.registry = Some(Registry::on_proc(first.ranks[0].proc_id.clone()));
+        }
+
         Ok(proc_mesh)
     }
 
+    /// A client of the registry spawned on the first proc of this
+    /// mesh, unless [`SPAWN_REGISTRY`] is disabled; see
+    /// [`crate::registry`].
+    pub fn registry(&self) -> Option<&Registry> {
+        self.registry.as_ref()
+    }
+
     /// Set or clear the controller actor managing this mesh.
     pub(crate) fn set_controller(
         &mut self,
@@ -331,12 +403,95 @@ impl ProcMesh {
             .map_err(anyhow::Error::from)
     }
 
+    /// Gracefully shut down this mesh. Each proc first stops intake
+    /// at its user actors' mailboxes, waits up to `timeout` for
+    /// in-flight messages to be processed, and flushes its outbound
+    /// messages; the procs are then stopped as with [`ProcMesh::stop`].
+    /// Returns the per-rank drain summaries collected along the way.
+    pub async fn drain_and_stop(
+        &mut self,
+        cx: &impl context::Actor,
+        reason: String,
+        timeout: Duration,
+    ) -> anyhow::Result<DrainReport> {
+        let (port, mut rx) = cx.mailbox().open_port::<DrainSummary>();
+        let mut port = port.bind();
+        // Procs that die while draining are reported as missing below.
+        port.return_undeliverable(false);
+        self.agent_mesh().cast(
+            cx,
+            proc_agent::DrainProc {
+                timeout,
+                reply: port,
+            },
+        )?;
+
+        let mut ranks = vec![None; self.current_ref.ranks.len()];
+        let mut pending = ranks.len();
+        let deadline = tokio::time::Instant::now()
+            + timeout
+            + hyperactor_config::global::get(GET_ACTOR_STATE_MAX_IDLE);
+        while pending > 0 {
+            let Ok(summary) = tokio::time::timeout_at(deadline, rx.recv()).await else {
+                tracing::warn!(
+                    "proc mesh {}: {} ranks did not report a drain summary",
+                    self.id,
+                    pending,
+                );
+                break;
+            };
+            let summary = summary?;
+            let rank = summary.rank;
+            match ranks.get_mut(rank) {
+                Some(slot @ None) => {
+                    *slot = Some(summary);
+                    pending -= 1;
+                }
+                _ => tracing::warn!(
+                    "proc mesh {}: unexpected drain summary for rank {}",
+                    self.id,
+                    rank
+                ),
+            }
+        }
+
+        self.stop(cx, reason).await?;
+        Ok(DrainReport { ranks })
+    }
+
     #[cfg(test)]
     pub(crate) fn ranks(&self) -> Arc<Vec<ProcRef>> {
         Arc::clone(&self.current_ref.ranks)
     }
 }
 
+/// The outcome of [`ProcMesh::drain_and_stop`].
+#[derive(Debug, Clone)]
+pub struct DrainReport {
+    /// The drain summary reported by each rank, or `None` if the rank
+    /// did not report before the deadline.
+    pub ranks: Vec<Option<DrainSummary>>,
+}
+
+impl DrainReport {
+    /// Whether every rank reported, drained its queues, and flushed
+    /// cleanly.
+    pub fn is_clean(&self) -> bool {
+        self.ranks
+            .iter()
+            .all(|summary| summary.as_ref().is_some_and(DrainSummary::is_clean))
+    }
+
+    /// The ranks that did not report a drain summary.
+    pub fn missing_ranks(&self) -> Vec<usize> {
+        self.ranks
+            .iter()
+            .enumerate()
+            .filter_map(|(rank, summary)| summary.is_none().then_some(rank))
+            .collect()
+    }
+}
+
 impl fmt::Display for ProcMesh {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.current_ref)
@@ -383,6 +538,9 @@ pub struct ProcMeshRef {
     // v0 casting requires root mesh rank 0 as the 1st hop, so we need to provide
     // it here. For v1, this can be removed since v1 can use any rank.
     pub(crate) root_comm_actor: Option<ActorRef<CommActor>>,
+    // For composite meshes (see [`ProcMeshRef::join`]), the meshes joined
+    // along the leading dimension of the root region.
+    parts: Option<Arc<Vec<ProcMeshRef>>>,
 }
 wirevalue::register_type!(ProcMeshRef);
 
@@ -410,6 +568,7 @@ impl ProcMeshRef {
             host_mesh,
             root_region,
             root_comm_actor,
+            parts: None,
         })
     }
 
@@ -424,9 +583,95 @@ impl ProcMeshRef {
             host_mesh: None,
             root_region: None,
             root_comm_actor: None,
+            parts: None,
         }
     }
 
+    /// Join `meshes`, which must all have the same extent, into a
+    /// composite mesh whose leading dimension `dim` indexes them. For
+    /// example, joining the proc meshes of 4 independently allocated
+    /// pipeline stages, each of extent `{replica: 8, gpu: 8}`, along
+    /// `stage` yields a mesh of extent `{stage: 4, replica: 8, gpu: 8}`.
+    ///
+    /// The joined meshes keep their own comm actor trees: casts to the
+    /// composite are bridged across them, with one cast routed through
+    /// each part's tree, and are delivered to actors at their points in
+    /// the composite.
+    #[allow(clippy::result_large_err)]
+    pub fn join(id: ProcMeshId, dim: &str, meshes: Vec<ProcMeshRef>) -> crate::Result<Self> {
+        let Some(first) = meshes.first() else {
+            return Err(Error::ConfigurationError(anyhow::anyhow!(
+                "cannot join an empty set of meshes"
+            )));
+        };
+        let extent = first.region.extent();
+        if let Some(mesh) = meshes.iter().find(|mesh| mesh.region.extent() != extent) {
+            return Err(Error::ConfigurationError(anyhow::anyhow!(
+                "cannot join mesh {} of extent {} with meshes of extent {}",
+                mesh,
+                mesh.region.extent(),
+                extent
+            )));
+        }
+        let region: Region = Extent::new(vec![dim.to_string()], vec![meshes.len()])
+            .and_then(|outer| outer.concat(&extent))
+            .map_err(|err| Error::ConfigurationError(err.into()))?
+            .into();
+        let ranks = meshes
+            .iter()
+            .flat_map(|mesh| mesh.ranks.iter().cloned())
+            .collect();
+        let mut mesh = Self::new(id, region, Arc::new(ranks), None, None, None)?;
+        mesh.parts = Some(Arc::new(meshes));
+        Ok(mesh)
+    }
+
+    /// For composite meshes, the parts of the joined meshes that this
+    /// mesh comprises, each with its point in this mesh's leading
+    /// dimension.
+    pub(crate) fn parts(&self) -> Option<Vec<(Point, ProcMeshRef)>> {
+        let parts = self.parts.as_ref()?;
+        let root = self.root_region.as_ref().unwrap_or(&self.region);
+        let dim = &root.labels()[0];
+        let part_extent = parts[0].region.extent();
+        let part_slice = part_extent.to_slice();
+        let outer = Extent::new(vec![dim.clone()], vec![self.region.extent().sizes()[0]])
+            .expect("valid extent");
+        Some(
+            outer
+                .points()
+                .map(|prefix| {
+                    // The ranks at `prefix` lie in a single part, where
+                    // they form a region of the part's extent.
+                    let (offset, sizes, strides) = self
+                        .region
+                        .range(dim, prefix.coords()[0])
+                        .expect("in range")
+                        .slice()
+                        .clone()
+                        .into_inner();
+                    let index = offset / part_slice.len();
+                    let begin = part_slice
+                        .coordinates(offset % part_slice.len())
+                        .expect("in range");
+                    let mut part = parts[index].clone();
+                    for (i, label) in part_extent.labels().iter().enumerate() {
+                        let step = if sizes[i + 1] > 1 {
+                            strides[i + 1] / part_slice.strides()[i]
+                        } else {
+                            1
+                        };
+                        let end = begin[i] + (sizes[i + 1] - 1) * step + 1;
+                        part = part
+                            .range(label, Range(begin[i], Some(end), step))
+                            .expect("in range");
+                    }
+                    (prefix, part)
+                })
+                .collect(),
+        )
+    }
+
     pub(crate) fn root_comm_actor(&self) -> Option<&ActorRef<CommActor>> {
         self.root_comm_actor.as_ref()
     }
@@ -458,6 +703,73 @@ impl ProcMeshRef {
         ActorMeshRef::new(id, self.clone(), None)
     }
 
+    /// Load the plugin library at `path` into every proc of this mesh,
+    /// registering its actor types so that they can then be spawned on
+    /// the mesh. The library must be present at `path` on every host,
+    /// e.g. by way of code sync, within the procs'
+    /// [`PLUGIN_DIR`](crate::plugin::PLUGIN_DIR). Returns the loaded
+    /// plugin as reported by each rank, or an error if any rank failed
+    /// to load it, or did not report within [`PLUGIN_LOAD_TIMEOUT`].
+    pub async fn load_plugin(
+        &self,
+        cx: &impl context::Actor,
+        path: &Path,
+    ) -> crate::Result<Vec<PluginInfo>> {
+        let (port, mut rx) = cx.mailbox().open_port::<PluginLoaded>();
+        let mut port = port.bind();
+        port.return_undeliverable(false);
+        self.agent_mesh().cast(
+            cx,
+            LoadPlugin {
+                path: path.to_path_buf(),
+                reply: port,
+            },
+        )?;
+
+        let mut ranks = vec![None; self.ranks.len()];
+        let mut pending = ranks.len();
+        let deadline =
+            tokio::time::Instant::now() + hyperactor_config::global::get(PLUGIN_LOAD_TIMEOUT);
+        let mut failures = Vec::new();
+        while pending > 0 {
+            let Ok(loaded) = tokio::time::timeout_at(deadline, rx.recv()).await else {
+                break;
+            };
+            let loaded = loaded?;
+            let Some(slot @ None) = ranks.get_mut(loaded.rank) else {
+                tracing::warn!(
+                    "proc mesh {}: unexpected plugin load reply from rank {}",
+                    self.id,
+                    loaded.rank
+                );
+                continue;
+            };
+            pending -= 1;
+            match loaded.result {
+                Ok(info) => *slot = Some(info),
+                Err(err) => failures.push(format!(
+                    "rank {} ({}): {}",
+                    loaded.rank, loaded.proc_id, err
+                )),
+            }
+        }
+        if pending > 0 {
+            failures.push(format!(
+                "{} ranks did not report before the deadline",
+                pending
+            ));
+        }
+        if !failures.is_empty() {
+            return Err(Error::Other(anyhow::anyhow!(
+                "failed to load plugin {} on proc mesh {}: {}",
+                path.display(),
+                self.id,
+                failures.join("; "),
+            )));
+        }
+        Ok(ranks.into_iter().flatten().collect())
+    }
+
     /// Query the state of all actors in this mesh matching the given id.
     pub async fn actor_states(
         &self,
@@ -1054,6 +1366,10 @@ impl view::RankedSliceable for ProcMeshRef {
             Some(self.root_region.as_ref().unwrap_or(&self.region).clone()),
             self.root_comm_actor.clone(),
         )
+        .map(|mut mesh| {
+            mesh.parts = self.parts.clone();
+            mesh
+        })
         .unwrap()
     }
 }
@@ -1109,6 +1425,8 @@ mod tests {
     #[cfg(fbcode_build)]
     use crate::host_mesh::PROC_SPAWN_MAX_IDLE;
     #[cfg(fbcode_build)]
+    use crate::registry::SPAWN_REGISTRY;
+    #[cfg(fbcode_build)]
     use crate::resource::RankedValues;
     #[cfg(fbcode_build)]
     use crate::resource::Status;
@@ -1407,6 +1725,44 @@ mod tests {
         let _ = hm.shutdown(instance).await;
     }
 
+    #[cfg(fbcode_build)]
+    #[assert_no_process_leak]
+    #[tokio::test]
+    async fn test_drain_and_stop() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(PROC_SPAWN_MAX_IDLE, Duration::from_secs(60));
+        let _guard2 = config.override_key(
+            hyperactor::config::HOST_SPAWN_READY_TIMEOUT,
+            Duration::from_secs(60),
+        );
+        // Count only the test actors.
+        let _guard3 = config.override_key(SPAWN_REGISTRY, false);
+
+        let instance = testing::instance();
+
+        let mut hm = testing::host_mesh(2).await;
+        let mut proc_mesh = hm
+            .spawn(&instance, "test", extent!(gpus = 2), None, None)
+            .await
+            .unwrap();
+        let _actor_mesh: ActorMesh<testactor::TestActor> =
+            proc_mesh.spawn(instance, "test", &()).await.unwrap();
+
+        let report = proc_mesh
+            .drain_and_stop(instance, "test drain".to_string(), Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert!(report.is_clean(), "{:?}", report);
+        assert!(report.missing_ranks().is_empty());
+        for (rank, summary) in report.ranks.iter().enumerate() {
+            let summary = summary.as_ref().unwrap();
+            assert_eq!(summary.rank, rank);
+            assert_eq!(summary.actors, 1);
+        }
+
+        let _ = hm.shutdown(instance).await;
+    }
+
     #[test]
     fn test_python_class_from_supervision_name() {
         use super::python_class_from_supervision_name;
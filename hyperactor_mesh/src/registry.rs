@@ -0,0 +1,498 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! A registry of named services.
+//!
+//! A [`RegistryActor`] maps human-readable service names to the actors
+//! that provide them, so that actors can find each other by name
+//! rather than by hardcoded actor ids. Unless [`SPAWN_REGISTRY`] is
+//! disabled, each root proc mesh spawns a registry as the service
+//! actor [`REGISTRY_ACTOR_NAME`] on its first proc, reached with
+//! [`crate::ProcMesh::registry`] or [`Registry::on_proc`].
+//!
+//! Registrations are leases: a service stays registered for its TTL,
+//! and keeps its registration by registering again before the TTL
+//! passes. In addition, the registry periodically checks that each
+//! registered actor is still reachable, and deregisters those that do
+//! not answer, so that lookups do not return actors that have failed
+//! well before their TTL. The checks run off the actor, which keeps
+//! serving requests while they are in flight.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::future::join_all;
+use hyperactor::Actor;
+use hyperactor::ActorAddr;
+use hyperactor::ActorRef;
+use hyperactor::Context;
+use hyperactor::ControlPort;
+use hyperactor::HandleClient;
+use hyperactor::Handler;
+use hyperactor::Instance;
+use hyperactor::OncePortRef;
+use hyperactor::PortRef;
+use hyperactor::ProcAddr;
+use hyperactor::RefClient;
+use hyperactor::actor::Referable;
+use hyperactor::context;
+use hyperactor::id::Label;
+use hyperactor::introspect::IntrospectMessage;
+use hyperactor::introspect::IntrospectResult;
+use hyperactor::introspect::IntrospectView;
+use hyperactor::mailbox::open_once_port;
+use hyperactor_config::CONFIG;
+use hyperactor_config::ConfigAttr;
+use hyperactor_config::attrs::declare_attrs;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::time::Instant;
+use typeuri::Named;
+
+use crate::mesh_id::ActorMeshId;
+
+declare_attrs! {
+    /// How often a [`RegistryActor`] checks that its registered actors
+    /// are reachable.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_REGISTRY_HEALTH_CHECK_INTERVAL".to_string()),
+        Some("registry_health_check_interval".to_string()),
+    ))
+    pub attr REGISTRY_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// How long a registered actor has to answer a health check before
+    /// it is deregistered.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_REGISTRY_HEALTH_CHECK_TIMEOUT".to_string()),
+        Some("registry_health_check_timeout".to_string()),
+    ))
+    pub attr REGISTRY_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Whether a root proc mesh spawns a [`RegistryActor`] on its
+    /// first proc.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_SPAWN_REGISTRY".to_string()),
+        Some("spawn_registry".to_string()),
+    ))
+    pub attr SPAWN_REGISTRY: bool = true;
+}
+
+/// The name of the registry service actor.
+pub const REGISTRY_ACTOR_NAME: &str = "registry";
+
+/// A service registration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Named)]
+pub struct Registration {
+    /// The name of the service.
+    pub name: String,
+    /// The actor that provides the service.
+    pub actor: ActorAddr,
+    /// The time remaining on the registration when it was made or
+    /// renewed.
+    pub ttl: Duration,
+}
+wirevalue::register_type!(Registration);
+
+/// Messages handled by [`RegistryActor`].
+#[derive(Debug, Clone, Serialize, Deserialize, Named, Handler, HandleClient, RefClient)]
+pub enum RegistryMessage {
+    /// Register `actor` as the service `name`, for `ttl`. Replies with
+    /// the registration, or `None` if another actor is registered as
+    /// the service. If `actor` is already registered as the service,
+    /// its registration is renewed.
+    Register {
+        name: String,
+        actor: ActorAddr,
+        ttl: Duration,
+        #[reply]
+        reply: OncePortRef<Option<Registration>>,
+    },
+    /// Deregister `actor` as the service `name`. Replies with whether
+    /// it was registered.
+    Deregister {
+        name: String,
+        actor: ActorAddr,
+        #[reply]
+        reply: OncePortRef<bool>,
+    },
+    /// Reply with the actor registered as the service `name`, if any.
+    Lookup {
+        name: String,
+        #[reply]
+        reply: OncePortRef<Option<ActorAddr>>,
+    },
+}
+wirevalue::register_type!(RegistryMessage);
+
+/// Periodic self-message of a [`RegistryActor`], starting a round of
+/// health checks.
+///
+/// Not exported or registered — only used internally via `post_after`,
+/// so that there is a single chain of rounds per registry.
+#[derive(Debug, Serialize, Deserialize, Named)]
+struct HealthCheck;
+
+/// The registered actors that failed a round of health checks.
+///
+/// Not exported or registered — only used internally via `PortHandle`.
+#[derive(Debug, Serialize, Deserialize, Named)]
+struct HealthChecked {
+    failed: Vec<(String, ActorAddr)>,
+}
+
+/// A registration as held by the registry.
+#[derive(Debug)]
+struct Registered {
+    actor: ActorAddr,
+    expires_at: Instant,
+}
+
+/// An actor that registers named services; see the
+/// [module documentation](self).
+#[derive(Debug, Default)]
+#[hyperactor::export(handlers = [RegistryMessage])]
+#[hyperactor::spawnable]
+pub struct RegistryActor {
+    services: HashMap<String, Registered>,
+}
+
+#[async_trait]
+impl Actor for RegistryActor {
+    async fn init(&mut self, this: &Instance<Self>) -> Result<(), anyhow::Error> {
+        this.post_after(
+            this,
+            HealthCheck,
+            hyperactor_config::global::get(REGISTRY_HEALTH_CHECK_INTERVAL),
+        );
+        Ok(())
+    }
+}
+
+impl RegistryActor {
+    /// The unexpired registration of the service `name`, if any,
+    /// dropping it if it has expired.
+    fn unexpired(&mut self, name: &str, now: Instant) -> Option<&mut Registered> {
+        if self
+            .services
+            .get(name)
+            .is_some_and(|registered| registered.expires_at <= now)
+        {
+            let lapsed = self.services.remove(name).unwrap();
+            tracing::info!("service {}: registration of {} lapsed", name, lapsed.actor);
+        }
+        self.services.get_mut(name)
+    }
+}
+
+#[async_trait]
+#[hyperactor::handle(RegistryMessage)]
+impl RegistryMessageHandler for RegistryActor {
+    async fn register(
+        &mut self,
+        _cx: &Context<Self>,
+        name: String,
+        actor: ActorAddr,
+        ttl: Duration,
+    ) -> Result<Option<Registration>, anyhow::Error> {
+        let now = Instant::now();
+        match self.unexpired(&name, now) {
+            Some(registered) if registered.actor != actor => return Ok(None),
+            Some(registered) => registered.expires_at = now + ttl,
+            None => {
+                tracing::info!("service {}: registered {}", name, actor);
+                self.services.insert(
+                    name.clone(),
+                    Registered {
+                        actor: actor.clone(),
+                        expires_at: now + ttl,
+                    },
+                );
+            }
+        }
+        Ok(Some(Registration { name, actor, ttl }))
+    }
+
+    async fn deregister(
+        &mut self,
+        _cx: &Context<Self>,
+        name: String,
+        actor: ActorAddr,
+    ) -> Result<bool, anyhow::Error> {
+        let now = Instant::now();
+        if !self
+            .unexpired(&name, now)
+            .is_some_and(|registered| registered.actor == actor)
+        {
+            return Ok(false);
+        }
+        self.services.remove(&name);
+        tracing::info!("service {}: deregistered {}", name, actor);
+        Ok(true)
+    }
+
+    async fn lookup(
+        &mut self,
+        _cx: &Context<Self>,
+        name: String,
+    ) -> Result<Option<ActorAddr>, anyhow::Error> {
+        let now = Instant::now();
+        Ok(self
+            .unexpired(&name, now)
+            .map(|registered| registered.actor.clone()))
+    }
+}
+
+#[async_trait]
+impl Handler<HealthCheck> for RegistryActor {
+    async fn handle(&mut self, cx: &Context<Self>, _: HealthCheck) -> Result<(), anyhow::Error> {
+        let now = Instant::now();
+        self.services
+            .retain(|_, registered| registered.expires_at > now);
+
+        // Registered actors answer introspection queries from the actor
+        // runtime, whatever their type; an actor that does not answer in
+        // time is presumed to have failed.
+        let timeout = hyperactor_config::global::get(REGISTRY_HEALTH_CHECK_TIMEOUT);
+        let probes: Vec<_> = self
+            .services
+            .iter()
+            .map(|(name, registered)| {
+                let (name, actor) = (name.clone(), registered.actor.clone());
+                let mut port = PortRef::<IntrospectMessage>::attest_control_port(
+                    &actor,
+                    ControlPort::Introspect,
+                );
+                port.return_undeliverable(false);
+                let (reply, rx) = open_once_port::<IntrospectResult>(cx);
+                let mut reply = reply.bind();
+                reply.return_undeliverable(false);
+                port.post(
+                    cx,
+                    IntrospectMessage::Query {
+                        view: IntrospectView::Actor,
+                        reply,
+                    },
+                );
+                async move {
+                    let healthy =
+                        matches!(tokio::time::timeout(timeout, rx.recv()).await, Ok(Ok(_)));
+                    (!healthy).then_some((name, actor))
+                }
+            })
+            .collect();
+
+        // Await the probes off the actor, so that it keeps serving
+        // requests meanwhile; the next round is scheduled once this one
+        // completes.
+        let done = cx.port::<HealthChecked>();
+        tokio::spawn(async move {
+            let failed = join_all(probes).await.into_iter().flatten().collect();
+            let client = Instance::<()>::self_client();
+            let _ = done.post(client, HealthChecked { failed });
+        });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Handler<HealthChecked> for RegistryActor {
+    async fn handle(
+        &mut self,
+        cx: &Context<Self>,
+        HealthChecked { failed }: HealthChecked,
+    ) -> Result<(), anyhow::Error> {
+        for (name, actor) in failed {
+            // The actor may have been deregistered, or the service
+            // taken over, while the round was in flight.
+            if self
+                .services
+                .get(&name)
+                .is_some_and(|registered| registered.actor == actor)
+            {
+                tracing::warn!(
+                    "service {}: deregistered {}, which failed its health check",
+                    name,
+                    actor
+                );
+                self.services.remove(&name);
+            }
+        }
+
+        cx.post_after(
+            cx,
+            HealthCheck,
+            hyperactor_config::global::get(REGISTRY_HEALTH_CHECK_INTERVAL),
+        );
+        Ok(())
+    }
+}
+
+/// A client of a [`RegistryActor`]; see the
+/// [module documentation](self).
+///
+/// A service should [register](Registry::register) itself again well
+/// within its TTL (say, every third of it) for as long as it serves.
+#[derive(Debug, Clone)]
+pub struct Registry {
+    registry: ActorRef<RegistryActor>,
+}
+
+impl Registry {
+    /// A client of `registry`.
+    pub fn new(registry: ActorRef<RegistryActor>) -> Self {
+        Self { registry }
+    }
+
+    /// A client of the registry spawned as the service actor
+    /// [`REGISTRY_ACTOR_NAME`] on `proc`.
+    pub fn on_proc(proc: ProcAddr) -> Self {
+        let id = ActorMeshId::singleton(Label::new(REGISTRY_ACTOR_NAME).unwrap());
+        Self::new(ActorRef::attest(id.actor_addr(proc)))
+    }
+
+    /// Register `actor` as the service `name` for `ttl`, or renew its
+    /// registration. Returns the registration, or `None` if another
+    /// actor is registered as the service.
+    pub async fn register<A: Referable>(
+        &self,
+        cx: &impl context::Actor,
+        name: &str,
+        actor: &ActorRef<A>,
+        ttl: Duration,
+    ) -> anyhow::Result<Option<Registration>> {
+        self.registry
+            .register(cx, name.to_string(), actor.actor_addr().clone(), ttl)
+            .await
+    }
+
+    /// Deregister `actor` as the service `name`. Returns whether it was
+    /// registered.
+    pub async fn deregister<A: Referable>(
+        &self,
+        cx: &impl context::Actor,
+        name: &str,
+        actor: &ActorRef<A>,
+    ) -> anyhow::Result<bool> {
+        self.registry
+            .deregister(cx, name.to_string(), actor.actor_addr().clone())
+            .await
+    }
+
+    /// The actor registered as the service `name`, if any. As with
+    /// [`ActorRef::attest`], the caller asserts that the service is
+    /// provided by an `A`.
+    pub async fn lookup<A: Referable>(
+        &self,
+        cx: &impl context::Actor,
+        name: &str,
+    ) -> anyhow::Result<Option<ActorRef<A>>> {
+        Ok(self
+            .registry
+            .lookup(cx, name.to_string())
+            .await?
+            .map(ActorRef::attest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor::Proc;
+
+    use super::*;
+    use crate::lease::LeaseActor;
+    use crate::lease::LeaseActorParams;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_registry() {
+        let config = hyperactor_config::global::lock();
+        let _interval =
+            config.override_key(REGISTRY_HEALTH_CHECK_INTERVAL, Duration::from_millis(50));
+        let _timeout =
+            config.override_key(REGISTRY_HEALTH_CHECK_TIMEOUT, Duration::from_millis(500));
+
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let registry = Registry::new(proc.spawn(RegistryActor::default()).bind());
+        let first = proc.spawn(LeaseActor::new(LeaseActorParams::default()).unwrap());
+        let second = proc.spawn(LeaseActor::new(LeaseActorParams::default()).unwrap());
+        let ttl = Duration::from_secs(60);
+
+        registry
+            .register(&client, "leases", &first.bind(), ttl)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            registry
+                .register(&client, "leases", &second.bind(), ttl)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let found: ActorRef<LeaseActor> =
+            registry.lookup(&client, "leases").await.unwrap().unwrap();
+        assert_eq!(found.actor_addr(), first.actor_addr());
+        assert!(
+            registry
+                .lookup::<LeaseActor>(&client, "missing")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // Live actors pass their health checks, and stay registered.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(
+            registry
+                .lookup::<LeaseActor>(&client, "leases")
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        // Once the registered actor stops, it fails its health check,
+        // and is deregistered well before its TTL.
+        first.drain_and_stop("test").unwrap();
+        first.await;
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert!(
+            registry
+                .lookup::<LeaseActor>(&client, "leases")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        registry
+            .register(&client, "leases", &second.bind(), ttl)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Registrations lapse after their TTL.
+        assert!(
+            registry
+                .deregister(&client, "leases", &second.bind())
+                .await
+                .unwrap()
+        );
+        registry
+            .register(&client, "leases", &second.bind(), Duration::from_millis(50))
+            .await
+            .unwrap()
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(
+            registry
+                .lookup::<LeaseActor>(&client, "leases")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
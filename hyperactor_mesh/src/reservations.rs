@@ -0,0 +1,138 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Reservation messages for remote per-proc resource reservations.
+//!
+//! See [`hyperactor::proc::admission`] for admission control itself.
+
+use hyperactor::HandleClient;
+use hyperactor::Handler;
+use hyperactor::Proc;
+use hyperactor::RefClient;
+use hyperactor::proc::admission::AdmissionPolicy;
+use hyperactor::proc::admission::ProcCapacity;
+use hyperactor::proc::admission::Reservation;
+use hyperactor::proc::admission::ResourceRequirements;
+use serde::Deserialize;
+use serde::Serialize;
+use typeuri::Named;
+
+/// An actor's reservation, rendered for the admin API.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct ReservationEntry {
+    /// The actor holding the reservation.
+    pub actor_id: String,
+    /// The reserved resources.
+    pub resources: ResourceRequirements,
+}
+wirevalue::register_type!(ReservationEntry);
+
+impl From<Reservation> for ReservationEntry {
+    fn from(reservation: Reservation) -> Self {
+        Self {
+            actor_id: reservation.actor_addr.to_string(),
+            resources: reservation.resources,
+        }
+    }
+}
+
+/// Result of a reservations request — the capacity of the target proc,
+/// and the reservations of its actors.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct ReservationsResult {
+    /// The proc's capacity. Unlimited resources are `null`.
+    pub capacity: ProcCapacity,
+    /// What the proc does with actors that do not fit: `reject` or
+    /// `queue`.
+    pub policy: String,
+    /// The total of all reservations.
+    pub reserved: ResourceRequirements,
+    pub reservations: Vec<ReservationEntry>,
+}
+wirevalue::register_type!(ReservationsResult);
+
+impl ReservationsResult {
+    /// Snapshot the reservations of `proc`.
+    pub fn snapshot(proc: &Proc) -> Self {
+        let (capacity, policy) = proc.capacity();
+        Self {
+            capacity,
+            policy: match policy {
+                AdmissionPolicy::Reject => "reject",
+                AdmissionPolicy::Queue => "queue",
+            }
+            .to_string(),
+            reserved: proc.reserved(),
+            reservations: proc
+                .reservations()
+                .into_iter()
+                .map(ReservationEntry::from)
+                .collect(),
+        }
+    }
+}
+
+/// Request the resource reservations of a proc.
+///
+/// Sent to ProcAgent (worker procs) or HostAgent (service proc) by the
+/// admin HTTP bridge. The handler replies with
+/// [`ReservationsResult::snapshot`] of its own proc.
+#[derive(Debug, Serialize, Deserialize, Named, Handler, HandleClient, RefClient)]
+pub struct ReservationsDump {
+    #[reply]
+    pub result: hyperactor::OncePortRef<ReservationsResult>,
+}
+wirevalue::register_type!(ReservationsDump);
+
+#[cfg(test)]
+mod tests {
+    use hyperactor::Actor;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct GpuActor;
+
+    impl Actor for GpuActor {
+        fn resources(&self) -> ResourceRequirements {
+            ResourceRequirements {
+                gpus: 1,
+                ..Default::default()
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reservations_snapshot() {
+        let proc = Proc::isolated();
+        proc.set_capacity(
+            ProcCapacity {
+                gpus: Some(4),
+                ..Default::default()
+            },
+            AdmissionPolicy::Queue,
+        );
+        let actors = [proc.spawn(GpuActor), proc.spawn(GpuActor)];
+        for actor in &actors {
+            actor
+                .status()
+                .wait_for(|status| matches!(status, hyperactor::actor::ActorStatus::Idle))
+                .await
+                .unwrap();
+        }
+
+        let result = ReservationsResult::snapshot(&proc);
+        assert_eq!(result.policy, "queue");
+        assert_eq!(result.capacity.gpus, Some(4));
+        assert_eq!(result.reserved.gpus, 2);
+        assert_eq!(result.reservations.len(), 2);
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["capacity"]["memory_bytes"], serde_json::Value::Null);
+        assert_eq!(json["reservations"][0]["resources"]["gpus"], 1);
+    }
+}
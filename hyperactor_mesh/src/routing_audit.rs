@@ -0,0 +1,122 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Routing audit messages for remote per-proc routing audit logs.
+//!
+//! See [`hyperactor::mailbox::routing`] for the audit log itself.
+
+use hyperactor::HandleClient;
+use hyperactor::Handler;
+use hyperactor::RefClient;
+use hyperactor::mailbox::routing::ChosenRoute;
+use hyperactor::mailbox::routing::RoutingDecision;
+use serde::Deserialize;
+use serde::Serialize;
+use typeuri::Named;
+
+/// A routing decision, rendered for the admin API.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct RoutingAuditEntry {
+    /// When the message was routed (ISO 8601 timestamp string).
+    pub time: String,
+    /// The kind of router that routed the message.
+    pub router: String,
+    /// The message's destination port.
+    pub dest: String,
+    /// How the message was routed: `bound`, `direct`, `default`,
+    /// `unroutable`, or `world`.
+    pub route: String,
+    /// The bound reference (for `bound`), the dialed address (for
+    /// `direct`), or the gateway uid (for `world`) through which the
+    /// message was routed.
+    pub binding: Option<String>,
+}
+wirevalue::register_type!(RoutingAuditEntry);
+
+impl From<RoutingDecision> for RoutingAuditEntry {
+    fn from(decision: RoutingDecision) -> Self {
+        let (route, binding) = match decision.route {
+            ChosenRoute::Bound(addr) => ("bound", Some(addr.to_string())),
+            ChosenRoute::Direct(addr) => ("direct", Some(addr.to_string())),
+            ChosenRoute::Default => ("default", None),
+            ChosenRoute::Unroutable => ("unroutable", None),
+            ChosenRoute::World(gateway) => ("world", Some(gateway.to_string())),
+        };
+        Self {
+            time: humantime::format_rfc3339_millis(decision.time).to_string(),
+            router: format!("{:?}", decision.router),
+            dest: decision.dest.to_string(),
+            route: route.to_string(),
+            binding,
+        }
+    }
+}
+
+/// Result of a routing audit request — the most recent routing
+/// decisions of the target process, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct RoutingAuditResult {
+    /// Whether the process is currently auditing its routing
+    /// decisions (`hyperactor::config::ROUTING_AUDIT`).
+    pub enabled: bool,
+    pub entries: Vec<RoutingAuditEntry>,
+}
+wirevalue::register_type!(RoutingAuditResult);
+
+impl RoutingAuditResult {
+    /// Snapshot this process's routing audit log.
+    pub fn snapshot() -> Self {
+        Self {
+            enabled: hyperactor_config::global::get(hyperactor::config::ROUTING_AUDIT),
+            entries: hyperactor::mailbox::routing::audit_log()
+                .into_iter()
+                .map(RoutingAuditEntry::from)
+                .collect(),
+        }
+    }
+}
+
+/// Request the routing audit log of a proc's process.
+///
+/// Sent to ProcAgent (worker procs) or HostAgent (service proc) by the
+/// admin HTTP bridge. The handler replies with
+/// [`RoutingAuditResult::snapshot`].
+#[derive(Debug, Serialize, Deserialize, Named, Handler, HandleClient, RefClient)]
+pub struct RoutingAuditDump {
+    #[reply]
+    pub result: hyperactor::OncePortRef<RoutingAuditResult>,
+}
+wirevalue::register_type!(RoutingAuditDump);
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::UNIX_EPOCH;
+
+    use hyperactor::mailbox::routing::RouterKind;
+    use hyperactor::testing::ids::test_port_id;
+    use hyperactor::testing::ids::test_proc_id;
+
+    use super::*;
+
+    #[test]
+    fn test_routing_audit_entry() {
+        let dest = test_port_id("proc", "actor", 1);
+        let entry = RoutingAuditEntry::from(RoutingDecision {
+            time: UNIX_EPOCH + Duration::from_millis(1500),
+            router: RouterKind::FallbackRouter,
+            dest: dest.clone(),
+            route: ChosenRoute::Bound(test_proc_id("proc").into()),
+        });
+        assert_eq!(entry.time, "1970-01-01T00:00:01.500Z");
+        assert_eq!(entry.router, "FallbackRouter");
+        assert_eq!(entry.dest, dest.to_string());
+        assert_eq!(entry.route, "bound");
+        assert_eq!(entry.binding, Some(test_proc_id("proc").to_string()));
+    }
+}
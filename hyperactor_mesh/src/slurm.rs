@@ -0,0 +1,456 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Provisioning host meshes through Slurm.
+//!
+//! [`SlurmJob::submit`] obtains an allocation of nodes with `sbatch`;
+//! the batch script itself only holds the allocation. Once the job is
+//! running, [`SlurmJob::host_mesh`] maps the allocated node names to
+//! TCP [`ChannelAddr`]s, launches one host on each node as a job step
+//! (`srun --overlap`), and attaches to the hosts, returning a
+//! [`HostMesh`] with one rank per node.
+//!
+//! Hosts whose job steps exit, or that are lost when the job ends, are
+//! reported as supervision events on the corresponding host rank by
+//! [`SlurmJob::next_failure`].
+//!
+//! Hosts are addressed directly by their channel addresses, so
+//! messages to their procs are routed by the gateway's dialing router
+//! without any further configuration.
+//!
+//! As with [`HostMesh::process`], the bootstrap command run on each node
+//! must reach [`crate::bootstrap_or_die`].
+
+use std::time::Duration;
+
+use hyperactor::channel::ChannelAddr;
+use hyperactor::context;
+use hyperactor::id::Label;
+use hyperactor_config::CONFIG;
+use hyperactor_config::ConfigAttr;
+use hyperactor_config::attrs::declare_attrs;
+use tokio::process::Child;
+use tokio::process::Command;
+
+use crate::bootstrap::BootstrapCommand;
+use crate::cluster;
+use crate::cluster::CommandError;
+use crate::cluster::LaunchedHosts;
+use crate::host_mesh::HostMesh;
+use crate::mesh_id::HostMeshId;
+use crate::supervision::MeshFailure;
+
+declare_attrs! {
+    /// How often to poll the state of Slurm jobs.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_SLURM_POLL_INTERVAL".to_string()),
+        Some("slurm_poll_interval".to_string()),
+    ))
+    pub attr SLURM_POLL_INTERVAL: Duration = Duration::from_secs(2);
+}
+
+/// Errors interacting with Slurm.
+#[derive(Debug, thiserror::Error)]
+pub enum SlurmError {
+    /// A Slurm command could not be run, failed, or produced
+    /// unexpected output.
+    #[error(transparent)]
+    Command(#[from] CommandError),
+
+    /// The job ended, or was not found.
+    #[error("slurm job {job_id} is not running: {state}")]
+    NotRunning { job_id: String, state: String },
+
+    /// The job did not start in time.
+    #[error("slurm job {job_id} did not start within {timeout:?}")]
+    Timeout { job_id: String, timeout: Duration },
+
+    /// The job was allocated a different number of nodes than requested.
+    #[error("slurm job {job_id} was allocated {allocated} nodes, expected {expected}")]
+    PartialAllocation {
+        job_id: String,
+        expected: usize,
+        allocated: usize,
+    },
+
+    /// A node's address could not be resolved.
+    #[error("failed to resolve address of node {node}: {source}")]
+    Address {
+        node: String,
+        #[source]
+        source: anyhow::Error,
+    },
+}
+
+/// The specification of a Slurm job running a host on each of its nodes.
+#[derive(Debug, Clone)]
+pub struct SlurmSpec {
+    /// The name of the job.
+    pub job_name: String,
+    /// The number of nodes, and thus of hosts.
+    pub nodes: usize,
+    /// The TCP port on which each host is served.
+    pub port: u16,
+    /// The partition to submit to.
+    pub partition: Option<String>,
+    /// The job's time limit, in any format accepted by `sbatch --time`.
+    pub time_limit: Option<String>,
+    /// GPUs to request on each node.
+    pub gpus_per_node: Option<usize>,
+    /// CPUs to request for each host.
+    pub cpus_per_task: Option<usize>,
+    /// Memory to request on each node, e.g. `"64G"`.
+    pub mem: Option<String>,
+    /// Whether to request exclusive use of the nodes.
+    pub exclusive: bool,
+    /// Additional `sbatch` arguments.
+    pub sbatch_args: Vec<String>,
+    /// The command used to bootstrap hosts, and their procs. Defaults to
+    /// [`BootstrapCommand::current`].
+    pub command: Option<BootstrapCommand>,
+}
+
+impl SlurmSpec {
+    /// A specification of a job named `job_name` over `nodes` nodes, with
+    /// default settings.
+    pub fn new(job_name: impl Into<String>, nodes: usize) -> Self {
+        Self {
+            job_name: job_name.into(),
+            nodes,
+            port: 22222,
+            partition: None,
+            time_limit: None,
+            gpus_per_node: None,
+            cpus_per_task: None,
+            mem: None,
+            exclusive: true,
+            sbatch_args: Vec::new(),
+            command: None,
+        }
+    }
+
+    /// The `sbatch` arguments that submit this job.
+    fn sbatch_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--parsable".to_string(),
+            format!("--job-name={}", self.job_name),
+            format!("--nodes={}", self.nodes),
+            "--ntasks-per-node=1".to_string(),
+        ];
+        if let Some(partition) = &self.partition {
+            args.push(format!("--partition={}", partition));
+        }
+        if let Some(time_limit) = &self.time_limit {
+            args.push(format!("--time={}", time_limit));
+        }
+        if let Some(gpus) = self.gpus_per_node {
+            args.push(format!("--gpus-per-node={}", gpus));
+        }
+        if let Some(cpus) = self.cpus_per_task {
+            args.push(format!("--cpus-per-task={}", cpus));
+        }
+        if let Some(mem) = &self.mem {
+            args.push(format!("--mem={}", mem));
+        }
+        if self.exclusive {
+            args.push("--exclusive".to_string());
+        }
+        args.extend(self.sbatch_args.iter().cloned());
+        // The batch script only holds the allocation; hosts are launched
+        // as job steps once the job runs.
+        args.push("--wrap=exec sleep infinity".to_string());
+        args
+    }
+}
+
+/// The state of a Slurm job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlurmJobState {
+    /// The job is waiting for its allocation.
+    Pending,
+    /// The job is running on its allocated nodes.
+    Running {
+        /// The names of the allocated nodes.
+        nodes: Vec<String>,
+    },
+    /// The job has ended, with the given Slurm state (e.g. `CANCELLED`),
+    /// or is no longer known to the scheduler.
+    Ended(String),
+}
+
+/// A Slurm job running a host on each of its nodes.
+///
+/// The job is cancelled when the `SlurmJob` is dropped.
+#[derive(Debug)]
+pub struct SlurmJob {
+    spec: SlurmSpec,
+    job_id: String,
+    /// The `srun` steps running the hosts, by rank.
+    steps: Vec<Child>,
+    /// The hosts, once launched.
+    hosts: LaunchedHosts,
+    cancelled: bool,
+}
+
+impl SlurmJob {
+    /// Submit a job according to `spec`.
+    pub async fn submit(spec: SlurmSpec) -> Result<Self, SlurmError> {
+        let output = cluster::run(Command::new("sbatch").args(spec.sbatch_args())).await?;
+        let job_id = parse_job_id(&output).ok_or_else(|| CommandError::Parse {
+            command: "sbatch".to_string(),
+            output: output.clone(),
+        })?;
+        tracing::info!(job_id, nodes = spec.nodes, "submitted slurm job");
+        Ok(Self {
+            spec,
+            job_id,
+            steps: Vec::new(),
+            hosts: LaunchedHosts::default(),
+            cancelled: false,
+        })
+    }
+
+    /// The id of the job.
+    pub fn job_id(&self) -> &str {
+        &self.job_id
+    }
+
+    /// Query the current state of the job.
+    pub async fn state(&self) -> Result<SlurmJobState, SlurmError> {
+        let output =
+            cluster::run(Command::new("squeue").args(["-h", "-j", &self.job_id, "-o", "%T|%N"]))
+                .await?;
+        let Some((state, node_list)) = output.trim().split_once('|') else {
+            return Ok(SlurmJobState::Ended("UNKNOWN".to_string()));
+        };
+        Ok(match state {
+            "RUNNING" => SlurmJobState::Running {
+                nodes: cluster::run(Command::new("scontrol").args([
+                    "show",
+                    "hostnames",
+                    node_list,
+                ]))
+                .await?
+                .lines()
+                .map(str::to_string)
+                .collect(),
+            },
+            state if is_pending(state) => SlurmJobState::Pending,
+            state => SlurmJobState::Ended(state.to_string()),
+        })
+    }
+
+    /// Wait for the job to run, up to `timeout` if provided, and return
+    /// the names of its nodes.
+    pub async fn wait_running(&self, timeout: Option<Duration>) -> Result<Vec<String>, SlurmError> {
+        let start = tokio::time::Instant::now();
+        loop {
+            match self.state().await? {
+                SlurmJobState::Running { nodes } if nodes.len() == self.spec.nodes => {
+                    tracing::info!(job_id = self.job_id, ?nodes, "slurm job is running");
+                    return Ok(nodes);
+                }
+                SlurmJobState::Running { nodes } => {
+                    return Err(SlurmError::PartialAllocation {
+                        job_id: self.job_id.clone(),
+                        expected: self.spec.nodes,
+                        allocated: nodes.len(),
+                    });
+                }
+                SlurmJobState::Ended(state) => {
+                    return Err(SlurmError::NotRunning {
+                        job_id: self.job_id.clone(),
+                        state,
+                    });
+                }
+                SlurmJobState::Pending => {}
+            }
+            if let Some(timeout) = timeout
+                && start.elapsed() >= timeout
+            {
+                return Err(SlurmError::Timeout {
+                    job_id: self.job_id.clone(),
+                    timeout,
+                });
+            }
+            tokio::time::sleep(hyperactor_config::global::get(SLURM_POLL_INTERVAL)).await;
+        }
+    }
+
+    /// Wait for the job to run, launch a host on each of its nodes, and
+    /// attach to them. The resulting mesh has a single `hosts`
+    /// dimension, ordered as the job's nodes.
+    pub async fn host_mesh(
+        &mut self,
+        cx: &impl context::Actor,
+        timeout: Option<Duration>,
+    ) -> crate::Result<HostMesh> {
+        let nodes = self.wait_running(timeout).await?;
+        let command = match &self.spec.command {
+            Some(command) => command.clone(),
+            None => BootstrapCommand::current()?,
+        };
+
+        let mut addrs = Vec::with_capacity(nodes.len());
+        for node in &nodes {
+            let addr = node_addr(node, self.spec.port)?;
+            let step = Command::new("srun")
+                .args(self.srun_args(node))
+                .arg(&command.program)
+                .args(&command.args)
+                .envs(cluster::host_env(&command, &addr)?)
+                .kill_on_drop(true)
+                .spawn()
+                .map_err(|source| {
+                    SlurmError::from(CommandError::Spawn {
+                        command: "srun".to_string(),
+                        source,
+                    })
+                })?;
+            tracing::info!(job_id = self.job_id, node, %addr, "launched host");
+            self.steps.push(step);
+            self.hosts.push(addr.clone());
+            addrs.push(addr);
+        }
+
+        HostMesh::attach(
+            cx,
+            HostMeshId::instance(Label::strip(&self.spec.job_name)),
+            addrs,
+        )
+        .await
+    }
+
+    /// Wait for a host launched by [`Self::host_mesh`] to be lost, because
+    /// its job step exited or because the job ended, and return the
+    /// corresponding supervision event. Each lost host is reported once;
+    /// the hosts lost to the end of the job are reported together.
+    ///
+    /// Never returns if there are no hosts left to monitor.
+    pub async fn next_failure(&mut self) -> Result<MeshFailure, SlurmError> {
+        loop {
+            self.hosts.wait_monitored().await;
+            let live: Vec<_> = self.hosts.live().collect();
+            for rank in live.iter().copied() {
+                let status = self.steps[rank]
+                    .try_wait()
+                    .map_err(|source| CommandError::Spawn {
+                        command: "srun".to_string(),
+                        source,
+                    })?;
+                if let Some(status) = status {
+                    let reason = format!("the job step running this host exited with {}", status);
+                    tracing::warn!(job_id = self.job_id, rank, reason, "slurm host lost");
+                    return Ok(self.hosts.lost(vec![rank], reason));
+                }
+            }
+            if let SlurmJobState::Ended(state) = self.state().await? {
+                let reason = format!("the slurm job running this host ended: {}", state);
+                tracing::warn!(job_id = self.job_id, ranks = ?live, reason, "slurm hosts lost");
+                return Ok(self.hosts.lost(live, reason));
+            }
+            tokio::time::sleep(hyperactor_config::global::get(SLURM_POLL_INTERVAL)).await;
+        }
+    }
+
+    /// The `srun` arguments that launch a host on `node`.
+    fn srun_args(&self, node: &str) -> Vec<String> {
+        vec![
+            format!("--jobid={}", self.job_id),
+            format!("--nodelist={}", node),
+            "--nodes=1".to_string(),
+            "--ntasks=1".to_string(),
+            "--overlap".to_string(),
+        ]
+    }
+
+    /// Cancel the job, stopping its hosts.
+    pub async fn cancel(mut self) -> Result<(), SlurmError> {
+        self.cancelled = true;
+        cluster::run(Command::new("scancel").arg(&self.job_id)).await?;
+        Ok(())
+    }
+}
+
+impl Drop for SlurmJob {
+    fn drop(&mut self) {
+        if !self.cancelled {
+            // Best-effort: the allocation should not outlive the job.
+            if let Err(err) = std::process::Command::new("scancel")
+                .arg(&self.job_id)
+                .spawn()
+            {
+                tracing::warn!(job_id = self.job_id, "failed to cancel slurm job: {}", err);
+            }
+        }
+    }
+}
+
+/// Parse the output of `sbatch --parsable`: `<job id>[;<cluster>]`.
+fn parse_job_id(output: &str) -> Option<String> {
+    let job_id = output.trim().split(';').next()?;
+    (!job_id.is_empty() && job_id.chars().all(|c| c.is_ascii_digit())).then(|| job_id.to_string())
+}
+
+/// Whether a job in `state` is waiting to run.
+fn is_pending(state: &str) -> bool {
+    matches!(
+        state,
+        "PENDING" | "CONFIGURING" | "REQUEUED" | "REQUEUE_FED" | "REQUEUE_HOLD" | "RESV_DEL_HOLD"
+    )
+}
+
+/// The address of the host served on `node`.
+fn node_addr(node: &str, port: u16) -> Result<ChannelAddr, SlurmError> {
+    cluster::tcp_addr(node, port).map_err(|source| SlurmError::Address {
+        node: node.to_string(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sbatch_args() {
+        let mut spec = SlurmSpec::new("train", 4);
+        spec.partition = Some("gpu".to_string());
+        spec.gpus_per_node = Some(8);
+        spec.sbatch_args = vec!["--account=research".to_string()];
+        let args = spec.sbatch_args();
+        assert_eq!(
+            args,
+            vec![
+                "--parsable",
+                "--job-name=train",
+                "--nodes=4",
+                "--ntasks-per-node=1",
+                "--partition=gpu",
+                "--gpus-per-node=8",
+                "--exclusive",
+                "--account=research",
+                "--wrap=exec sleep infinity",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_job_id() {
+        assert_eq!(parse_job_id("12345\n").as_deref(), Some("12345"));
+        assert_eq!(parse_job_id("12345;cluster\n").as_deref(), Some("12345"));
+        assert_eq!(parse_job_id("Submitted batch job 12345"), None);
+        assert_eq!(parse_job_id(""), None);
+    }
+
+    #[test]
+    fn test_is_pending() {
+        assert!(is_pending("PENDING"));
+        assert!(!is_pending("CANCELLED"));
+    }
+}
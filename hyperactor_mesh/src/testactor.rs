@@ -44,9 +44,11 @@ use typeuri::Named;
 #[cfg(test)]
 use uuid::Uuid;
 
+use crate as hyperactor_mesh; // for macros
 use crate::ActorMesh;
 #[cfg(test)]
 use crate::ActorMeshRef;
+use crate::MeshClient;
 use crate::ProcMeshRef;
 use crate::comm::multicast::CastInfo;
 use crate::mesh_id::ActorMeshId;
@@ -236,7 +238,8 @@ impl Handler<Forward> for TestActor {
     Serialize,
     Deserialize,
     Handler,
-    RefClient
+    RefClient,
+    MeshClient
 )]
 pub struct GetCastInfo {
     /// Originating actor, point, sender.
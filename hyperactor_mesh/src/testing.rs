@@ -314,6 +314,7 @@ pub async fn host_mesh(n: usize) -> HostMeshShutdownGuard {
             command: None, // use current binary
             config: None,
             exit_on_shutdown: false,
+            hello: None,
         };
         boot.to_env(&mut cmd);
         cmd.kill_on_drop(false);
@@ -0,0 +1,135 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Timing messages for remote per-proc actor handler timing.
+//!
+//! See [`hyperactor::proc::timing`] for how handlers are timed.
+
+use hyperactor::HandleClient;
+use hyperactor::Handler;
+use hyperactor::Proc;
+use hyperactor::RefClient;
+use hyperactor::proc::timing::TimingSnapshot;
+use serde::Deserialize;
+use serde::Serialize;
+use typeuri::Named;
+
+/// An actor's handler timing, rendered for the admin API.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct ActorTimingEntry {
+    /// The actor.
+    pub actor_id: String,
+    /// The actor's type.
+    pub actor_type: String,
+    /// The actor's handler timing.
+    pub timing: TimingSnapshot,
+}
+wirevalue::register_type!(ActorTimingEntry);
+
+/// Result of a timing request — the handler timing of each running
+/// actor of the target proc, hottest first: by CPU time, then by total
+/// wall time, since CPU time is measured only if
+/// [`hyperactor::config::ACTOR_CPU_TIMING`] is set.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct TimingResult {
+    pub actors: Vec<ActorTimingEntry>,
+}
+wirevalue::register_type!(TimingResult);
+
+impl TimingResult {
+    /// Snapshot the handler timing of the running actors of `proc`.
+    pub fn snapshot(proc: &Proc) -> Self {
+        let mut actors: Vec<_> = proc
+            .all_instance_keys()
+            .iter()
+            .filter_map(|actor_id| proc.get_instance_by_id(actor_id))
+            .filter(|cell| !cell.status().borrow().is_terminal())
+            .map(|cell| ActorTimingEntry {
+                actor_id: cell.actor_addr().to_string(),
+                actor_type: cell.actor_type_name().to_string(),
+                timing: cell.timing(),
+            })
+            .collect();
+        let wall_time_us =
+            |timing: &TimingSnapshot| timing.mean_latency_us * timing.messages_handled;
+        actors.sort_by(|a, b| {
+            b.timing
+                .cpu_time_us
+                .cmp(&a.timing.cpu_time_us)
+                .then_with(|| wall_time_us(&b.timing).cmp(&wall_time_us(&a.timing)))
+                .then_with(|| a.actor_id.cmp(&b.actor_id))
+        });
+        Self { actors }
+    }
+}
+
+/// Request the actor handler timing of a proc.
+///
+/// Sent to ProcAgent (worker procs) or HostAgent (service proc) by the
+/// admin HTTP bridge. The handler replies with
+/// [`TimingResult::snapshot`] of its own proc.
+#[derive(Debug, Serialize, Deserialize, Named, Handler, HandleClient, RefClient)]
+pub struct TimingDump {
+    #[reply]
+    pub result: hyperactor::OncePortRef<TimingResult>,
+}
+wirevalue::register_type!(TimingDump);
+
+#[cfg(test)]
+mod tests {
+    use hyperactor::Actor;
+    use hyperactor::Context;
+    use hyperactor::Endpoint as _;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct SpinActor;
+
+    impl Actor for SpinActor {}
+
+    #[async_trait::async_trait]
+    impl Handler<u64> for SpinActor {
+        async fn handle(&mut self, _cx: &Context<Self>, millis: u64) -> anyhow::Result<()> {
+            let start = std::time::Instant::now();
+            while start.elapsed() < std::time::Duration::from_millis(millis) {
+                std::hint::black_box(0);
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timing_snapshot() {
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(hyperactor::config::ACTOR_CPU_TIMING, true);
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let hot = proc.spawn(SpinActor);
+        let cold = proc.spawn(SpinActor);
+        hot.post(&client, 20u64);
+        cold.post(&client, 0u64);
+
+        // Wait until both messages are handled.
+        let result = loop {
+            let result = TimingResult::snapshot(&proc);
+            let handled = |id: &hyperactor::ActorAddr| {
+                result.actors.iter().any(|entry| {
+                    entry.actor_id == id.to_string() && entry.timing.messages_handled > 0
+                })
+            };
+            if handled(hot.actor_addr()) && handled(cold.actor_addr()) {
+                break result;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        };
+        assert_eq!(result.actors[0].actor_id, hot.actor_addr().to_string());
+        assert!(result.actors[0].timing.cpu_time_us >= 10_000);
+        assert!(result.actors[0].timing.mean_latency_us >= 20_000);
+    }
+}
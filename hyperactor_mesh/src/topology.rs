@@ -0,0 +1,235 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Routing topology messages for exporting the topology of a mesh.
+//!
+//! See [`hyperactor::mailbox::topology`] for the topology of a single
+//! process. The topologies of a mesh's processes are retrieved with
+//! [`TopologyDump`] and merged. A proc agent answers with its process's
+//! routing topology and, through [`CastTopologyDump`], the
+//! [`EdgeKind::Cast`] edges from the comm actor on its proc to the comm
+//! actors it forwards casts to, so the merged topologies of a mesh's
+//! procs include its cast tree. [`cast_topology`] computes the whole
+//! tree from a [`ProcMeshRef`].
+
+use std::collections::HashMap;
+
+use hyperactor::ActorAddr;
+use hyperactor::Addr;
+use hyperactor::HandleClient;
+use hyperactor::Handler;
+use hyperactor::Proc;
+use hyperactor::ProcAddr;
+use hyperactor::RefClient;
+use hyperactor::id::Label;
+use hyperactor::mailbox::topology::EdgeKind;
+use hyperactor::mailbox::topology::NodeKind;
+use hyperactor::mailbox::topology::Topology;
+use hyperactor::mailbox::topology::addr_node;
+use ndslice::view::Ranked as _;
+use serde::Deserialize;
+use serde::Serialize;
+use typeuri::Named;
+
+use crate::comm;
+use crate::mesh_id::ActorMeshId;
+use crate::proc_mesh::COMM_ACTOR_NAME;
+use crate::proc_mesh::ProcMeshRef;
+
+/// Result of a topology request — the routing topology of the process
+/// hosting the target proc.
+#[derive(Debug, Clone, Serialize, Deserialize, Named)]
+pub struct TopologyResult {
+    pub topology: Topology,
+}
+wirevalue::register_type!(TopologyResult);
+
+impl TopologyResult {
+    /// Snapshot the routing topology of the gateway of `proc`.
+    pub fn snapshot(proc: &Proc) -> Self {
+        Self {
+            topology: Topology::of_gateway(&proc.gateway()),
+        }
+    }
+}
+
+/// Request the routing topology of a proc's process.
+///
+/// Sent to ProcAgent (worker procs) or HostAgent (service proc) by the
+/// admin HTTP bridge and by `hyper topology`. The handler replies with
+/// [`TopologyResult::snapshot`] of its own proc; ProcAgent first has
+/// the proc's comm actor add its cast edges ([`CastTopologyDump`]).
+#[derive(Debug, Serialize, Deserialize, Named, Handler, HandleClient, RefClient)]
+pub struct TopologyDump {
+    #[reply]
+    pub result: hyperactor::OncePortRef<TopologyResult>,
+}
+wirevalue::register_type!(TopologyDump);
+
+/// Add the cast edges of a proc's comm actor to `topology`, and reply
+/// with the result.
+///
+/// Sent by ProcAgent, when handling [`TopologyDump`], to the comm actor
+/// on its proc, which replies in its place.
+#[derive(Debug, Serialize, Deserialize, Named)]
+pub struct CastTopologyDump {
+    /// The routing topology of the proc's process.
+    pub topology: Topology,
+    pub result: hyperactor::OncePortRef<TopologyResult>,
+}
+wirevalue::register_type!(CastTopologyDump);
+
+/// The comm actor of the proc mesh that `proc` belongs to, on `proc`.
+pub(crate) fn comm_actor_addr(proc: &ProcAddr) -> ActorAddr {
+    let comm_actor_name = ActorMeshId::singleton(Label::new(COMM_ACTOR_NAME).unwrap());
+    proc.actor_addr_uid(comm_actor_name.uid().clone())
+}
+
+/// The comm actor tree through which casts to `proc_mesh` are
+/// forwarded, as [`EdgeKind::Cast`] edges between comm actors. Casts
+/// enter the tree at the root comm actor, on the first proc of the
+/// root mesh.
+pub fn cast_topology(proc_mesh: &ProcMeshRef) -> anyhow::Result<Topology> {
+    let Some(root) = proc_mesh.root_comm_actor() else {
+        anyhow::bail!("proc mesh {} has no comm actors", proc_mesh.id());
+    };
+    let slice = proc_mesh.region().slice().clone();
+    let mut comm_actors: HashMap<usize, ActorAddr> = slice
+        .iter()
+        .zip(proc_mesh.proc_ids())
+        .map(|(rank, proc_addr)| (rank, comm_actor_addr(&proc_addr)))
+        .collect();
+    comm_actors
+        .entry(0)
+        .or_insert_with(|| root.actor_addr().clone());
+    cast_tree_topology(slice, &comm_actors, None)
+}
+
+/// The edges of the cast tree of `slice` between the comm actors
+/// `comm_actors`, by rank on the root mesh: all of them, or those from
+/// rank `from_rank` only.
+pub(crate) fn cast_tree_topology(
+    slice: ndslice::Slice,
+    comm_actors: &HashMap<usize, ActorAddr>,
+    from_rank: Option<usize>,
+) -> anyhow::Result<Topology> {
+    let mut topology = Topology::new();
+    for (source, to) in comm::cast_tree(0, slice)? {
+        if from_rank.is_some_and(|rank| rank != source) {
+            continue;
+        }
+        let [from, to] = [source, to].map(|rank| {
+            let actor = comm_actors.get(&rank).ok_or_else(|| {
+                anyhow::anyhow!("cast forwarded through rank {} outside the mesh", rank)
+            })?;
+            let id = addr_node(&Addr::Actor(actor.clone()));
+            topology.add_node(&id, NodeKind::Actor, actor.to_string());
+            Ok::<_, anyhow::Error>(id)
+        });
+        topology.add_edge(from?, to?, EdgeKind::Cast, None);
+    }
+    Ok(topology)
+}
+
+#[cfg(test)]
+mod tests {
+    use ndslice::Slice;
+
+    use super::*;
+    use crate::comm::CommActor;
+    use crate::comm::CommMeshConfig;
+
+    #[tokio::test]
+    async fn test_comm_actors_report_cast_edges() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let slice = Slice::new_row_major(vec![2, 3]);
+        let comm_actors: Vec<_> = (0..6).map(|_| proc.spawn(CommActor::default())).collect();
+        let peers: HashMap<_, _> = comm_actors
+            .iter()
+            .enumerate()
+            .map(|(rank, comm_actor)| (rank, comm_actor.bind::<CommActor>()))
+            .collect();
+        for (rank, comm_actor) in comm_actors.iter().enumerate() {
+            comm_actor.post(
+                &client,
+                CommMeshConfig::new(rank, peers.clone()).with_slice(slice.clone()),
+            );
+        }
+
+        // Each comm actor reports its own edges; together they are the
+        // whole tree.
+        let mut merged = Topology::new();
+        for comm_actor in &comm_actors {
+            let (port, receiver) = client.open_once_port::<TopologyResult>();
+            comm_actor.post(
+                &client,
+                CastTopologyDump {
+                    topology: Topology::new(),
+                    result: port.bind(),
+                },
+            );
+            merged.merge(receiver.recv().await.unwrap().topology);
+        }
+        let addrs = peers
+            .iter()
+            .map(|(rank, peer)| (*rank, peer.actor_addr().clone()))
+            .collect();
+        let expected = cast_tree_topology(slice, &addrs, None).unwrap();
+        assert!(!expected.edges.is_empty());
+        assert!(
+            expected
+                .edges
+                .iter()
+                .all(|edge| edge.kind == EdgeKind::Cast)
+        );
+        assert_eq!(merged.edges, expected.edges);
+    }
+
+    #[cfg(fbcode_build)]
+    #[tokio::test]
+    async fn test_topology_dump_includes_cast_tree() {
+        use hyperactor::ActorRef;
+        use ndslice::extent;
+
+        use crate::proc_agent::PROC_AGENT_ACTOR_NAME;
+        use crate::proc_agent::ProcAgent;
+        use crate::testing;
+
+        let instance = testing::instance();
+        let mut host_mesh = testing::host_mesh(2).await;
+        let proc_mesh = host_mesh
+            .spawn(instance, "test", extent!(gpus = 2), None, None)
+            .await
+            .unwrap();
+        let expected = cast_topology(&proc_mesh).unwrap();
+        assert!(!expected.edges.is_empty());
+
+        // The comm actors are configured concurrently with the query.
+        let cast_edges = loop {
+            let mut merged = Topology::new();
+            for proc_addr in proc_mesh.proc_ids() {
+                let agent: ActorRef<ProcAgent> =
+                    ActorRef::attest(proc_addr.actor_addr(PROC_AGENT_ACTOR_NAME));
+                merged.merge(agent.topology_dump(instance).await.unwrap().topology);
+            }
+            let cast_edges: Vec<_> = merged
+                .edges
+                .into_iter()
+                .filter(|edge| edge.kind == EdgeKind::Cast)
+                .collect();
+            if cast_edges.len() == expected.edges.len() {
+                break cast_edges;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        };
+        assert_eq!(cast_edges, expected.edges.into_iter().collect::<Vec<_>>());
+
+        let _ = host_mesh.shutdown(instance).await;
+    }
+}
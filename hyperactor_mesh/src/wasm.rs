@@ -0,0 +1,554 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Sandboxed actors whose handlers are WebAssembly modules.
+//!
+//! A [`WasmActor`] runs untrusted handler logic, compiled to
+//! WebAssembly, in a wasmtime sandbox, so that users can provide
+//! compute to a shared mesh without being able to take down, or read
+//! the memory of, the procs that run it. The actor delivers the payload
+//! of each [`WasmMessage`] to the module, and the module reaches the
+//! actor's mailbox only through the host functions listed below.
+//!
+//! Each message is handled with a budget of [`WASM_FUEL_PER_MESSAGE`]
+//! units of fuel (roughly, WebAssembly instructions), and the module's
+//! memory is capped at [`WASM_MEMORY_LIMIT`]; both may be overridden
+//! per actor with [`WasmActorParams`]. The replies and messages that a
+//! handler sends are buffered by the host until it returns, and count
+//! against its memory limit. A handler that runs out of fuel, exceeds
+//! its memory, or otherwise traps fails only the message it was
+//! handling: the sender is replied to with the error, any messages the
+//! handler sent are discarded, and the module is instantiated afresh
+//! for the next message. Modules are compiled and run on tokio's
+//! blocking threads, so that a handler does not hold up the proc's
+//! other actors for as long as its fuel lasts.
+//!
+//! # Module ABI
+//!
+//! The module must export:
+//! - `memory`: its linear memory;
+//! - `alloc(len: i32) -> i32`: allocate `len` bytes, returning their
+//!   offset in `memory`, into which the host writes each payload;
+//! - `handle(ptr: i32, len: i32)`: handle the payload at `ptr`.
+//!
+//! and may import, from the `hyperactor` module:
+//! - `reply(ptr: i32, len: i32)`: reply to the message being handled;
+//! - `send(addr_ptr: i32, addr_len: i32, ptr: i32, len: i32)`: send a
+//!   payload to the `WasmActor` whose [`ActorAddr`] is the UTF-8 string
+//!   at `addr_ptr`, which must be one of the actor's
+//!   [peers](WasmActorParams::peers);
+//! - `log(ptr: i32, len: i32)`: log the UTF-8 string at `ptr`.
+//!
+//! Replies and sends are posted once `handle` returns.
+
+use std::collections::HashSet;
+use std::mem::take;
+use std::sync::Arc;
+use std::sync::LazyLock;
+
+use async_trait::async_trait;
+use hyperactor::Actor;
+use hyperactor::ActorAddr;
+use hyperactor::ActorRef;
+use hyperactor::Bind;
+use hyperactor::Context;
+use hyperactor::Endpoint as _;
+use hyperactor::Handler;
+use hyperactor::PortRef;
+use hyperactor::Unbind;
+use hyperactor_config::CONFIG;
+use hyperactor_config::ConfigAttr;
+use hyperactor_config::Flattrs;
+use hyperactor_config::attrs::declare_attrs;
+use serde::Deserialize;
+use serde::Serialize;
+use typeuri::Named;
+use wasmtime::Caller;
+use wasmtime::Engine;
+use wasmtime::Linker;
+use wasmtime::Memory;
+use wasmtime::Module;
+use wasmtime::Store;
+use wasmtime::StoreLimits;
+use wasmtime::StoreLimitsBuilder;
+use wasmtime::TypedFunc;
+
+declare_attrs! {
+    /// The fuel with which a [`WasmActor`] handles each message.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_WASM_FUEL_PER_MESSAGE".to_string()),
+        Some("wasm_fuel_per_message".to_string()),
+    ))
+    pub attr WASM_FUEL_PER_MESSAGE: u64 = 1_000_000_000;
+
+    /// The maximum size, in bytes, of a [`WasmActor`]'s memory.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("HYPERACTOR_MESH_WASM_MEMORY_LIMIT".to_string()),
+        Some("wasm_memory_limit".to_string()),
+    ))
+    pub attr WASM_MEMORY_LIMIT: usize = 64 << 20;
+}
+
+/// The parameters of a [`WasmActor`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named)]
+pub struct WasmActorParams {
+    /// The module, in the WebAssembly binary or text format.
+    pub module: Vec<u8>,
+    /// Overrides [`WASM_FUEL_PER_MESSAGE`].
+    pub fuel_per_message: Option<u64>,
+    /// Overrides [`WASM_MEMORY_LIMIT`].
+    pub memory_limit: Option<usize>,
+    /// The actors to which the module may send messages.
+    pub peers: Vec<ActorRef<WasmActor>>,
+}
+wirevalue::register_type!(WasmActorParams);
+
+impl WasmActorParams {
+    /// Parameters for `module`, with the configured limits.
+    pub fn new(module: impl Into<Vec<u8>>) -> Self {
+        Self {
+            module: module.into(),
+            fuel_per_message: None,
+            memory_limit: None,
+            peers: Vec::new(),
+        }
+    }
+}
+
+/// A message to a [`WasmActor`], whose payload is handed to the
+/// module's `handle` export.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named, Bind, Unbind)]
+pub struct WasmMessage {
+    /// The payload.
+    pub payload: Vec<u8>,
+    /// Where to send the module's replies, or the error handling the
+    /// message.
+    #[binding(include)]
+    pub reply: Option<PortRef<WasmReply>>,
+}
+wirevalue::register_type!(WasmMessage);
+
+/// A reply from a [`WasmActor`]: a payload the module replied with,
+/// or the error that failed the message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Named, Bind, Unbind)]
+pub struct WasmReply {
+    pub result: Result<Vec<u8>, String>,
+}
+wirevalue::register_type!(WasmReply);
+
+/// The engine shared by all [`WasmActor`]s in the process.
+static ENGINE: LazyLock<Engine> = LazyLock::new(|| {
+    let mut config = wasmtime::Config::new();
+    config.consume_fuel(true);
+    Engine::new(&config).expect("invalid wasm engine configuration")
+});
+
+/// The host state of a sandbox: its limits, and the messages its
+/// current handler has sent.
+struct HostState {
+    limits: StoreLimits,
+    peers: Arc<HashSet<ActorAddr>>,
+    replies: Vec<Vec<u8>>,
+    sends: Vec<(ActorAddr, Vec<u8>)>,
+    /// The bytes that the current handler may still send.
+    outbox_budget: usize,
+}
+
+impl HostState {
+    /// Charge `len` bytes sent by the current handler to its budget.
+    fn charge(&mut self, len: i32) -> anyhow::Result<()> {
+        self.outbox_budget = self
+            .outbox_budget
+            .checked_sub(usize::try_from(len)?)
+            .ok_or_else(|| anyhow::anyhow!("handler sent more than its memory limit"))?;
+        Ok(())
+    }
+}
+
+/// An instance of the module, with its exports.
+struct Sandbox {
+    store: Store<HostState>,
+    memory_limit: usize,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    handle: TypedFunc<(i32, i32), ()>,
+}
+
+/// The output of a handler that returned.
+type Outbox = (Vec<Vec<u8>>, Vec<(ActorAddr, Vec<u8>)>);
+
+impl Sandbox {
+    fn instantiate(
+        linker: &Linker<HostState>,
+        module: &Module,
+        peers: Arc<HashSet<ActorAddr>>,
+        fuel: u64,
+        memory_limit: usize,
+    ) -> anyhow::Result<Self> {
+        let mut store = Store::new(
+            &ENGINE,
+            HostState {
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(memory_limit)
+                    .instances(1)
+                    .build(),
+                peers,
+                replies: Vec::new(),
+                sends: Vec::new(),
+                outbox_budget: memory_limit,
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+        // The module's start function runs on the budget of a message.
+        store.set_fuel(fuel)?;
+        let instance = linker.instantiate(&mut store, module)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| anyhow::anyhow!("module does not export its memory"))?;
+        let alloc = instance.get_typed_func(&mut store, "alloc")?;
+        let handle = instance.get_typed_func(&mut store, "handle")?;
+        Ok(Self {
+            store,
+            memory_limit,
+            memory,
+            alloc,
+            handle,
+        })
+    }
+
+    /// Run the handler on `payload`, with `fuel`.
+    fn handle(&mut self, payload: &[u8], fuel: u64) -> anyhow::Result<Outbox> {
+        self.store.set_fuel(fuel)?;
+        self.store.data_mut().outbox_budget = self.memory_limit;
+        let len = i32::try_from(payload.len())?;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        self.memory
+            .write(&mut self.store, usize::try_from(ptr)?, payload)?;
+        self.handle.call(&mut self.store, (ptr, len))?;
+        let state = self.store.data_mut();
+        Ok((take(&mut state.replies), take(&mut state.sends)))
+    }
+}
+
+/// Read `len` bytes at `ptr` from the memory of the calling module.
+fn read_guest(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> anyhow::Result<Vec<u8>> {
+    let memory = caller
+        .get_export("memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| anyhow::anyhow!("module does not export its memory"))?;
+    let start = usize::try_from(ptr)?;
+    let end = start.checked_add(usize::try_from(len)?);
+    let bytes = end
+        .and_then(|end| memory.data(&caller).get(start..end))
+        .ok_or_else(|| anyhow::anyhow!("out of bounds memory access"))?;
+    Ok(bytes.to_vec())
+}
+
+/// The host functions that modules may import.
+fn linker(engine: &Engine) -> anyhow::Result<Linker<HostState>> {
+    let mut linker = Linker::new(engine);
+    linker.func_wrap(
+        "hyperactor",
+        "reply",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> anyhow::Result<()> {
+            caller.data_mut().charge(len)?;
+            let payload = read_guest(&mut caller, ptr, len)?;
+            caller.data_mut().replies.push(payload);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "hyperactor",
+        "send",
+        |mut caller: Caller<'_, HostState>,
+         addr_ptr: i32,
+         addr_len: i32,
+         ptr: i32,
+         len: i32|
+         -> anyhow::Result<()> {
+            let addr = String::from_utf8(read_guest(&mut caller, addr_ptr, addr_len)?)?;
+            let addr: ActorAddr = addr.parse()?;
+            if !caller.data().peers.contains(&addr) {
+                anyhow::bail!("{} is not a peer of this actor", addr);
+            }
+            caller.data_mut().charge(len)?;
+            let payload = read_guest(&mut caller, ptr, len)?;
+            caller.data_mut().sends.push((addr, payload));
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "hyperactor",
+        "log",
+        |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> anyhow::Result<()> {
+            let line = read_guest(&mut caller, ptr, len)?;
+            tracing::info!(target: "wasm", "{}", String::from_utf8_lossy(&line));
+            Ok(())
+        },
+    )?;
+    Ok(linker)
+}
+
+/// An actor whose handler is a sandboxed WebAssembly module. See the
+/// [module documentation](self).
+#[hyperactor::export(handlers = [WasmMessage])]
+#[hyperactor::spawnable]
+pub struct WasmActor {
+    linker: Arc<Linker<HostState>>,
+    module: Module,
+    peers: Arc<HashSet<ActorAddr>>,
+    fuel_per_message: u64,
+    memory_limit: usize,
+    /// The current instance of the module; `None` after a trap, until
+    /// the next message.
+    sandbox: Option<Sandbox>,
+}
+
+impl std::fmt::Debug for WasmActor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmActor")
+            .field("fuel_per_message", &self.fuel_per_message)
+            .field("memory_limit", &self.memory_limit)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Actor for WasmActor {}
+
+#[async_trait]
+impl hyperactor::RemoteSpawn for WasmActor {
+    type Params = WasmActorParams;
+
+    async fn new(params: WasmActorParams, _environment: Flattrs) -> anyhow::Result<Self> {
+        let fuel_per_message = params
+            .fuel_per_message
+            .unwrap_or_else(|| hyperactor_config::global::get(WASM_FUEL_PER_MESSAGE));
+        let memory_limit = params
+            .memory_limit
+            .unwrap_or_else(|| hyperactor_config::global::get(WASM_MEMORY_LIMIT));
+        let peers: Arc<HashSet<_>> = Arc::new(
+            params
+                .peers
+                .iter()
+                .map(|peer| peer.actor_addr().clone())
+                .collect(),
+        );
+        tokio::task::spawn_blocking(move || {
+            let module = Module::new(&ENGINE, &params.module)?;
+            let linker = Arc::new(linker(&ENGINE)?);
+            // Instantiate eagerly, so that a module that does not
+            // satisfy the ABI fails the spawn rather than its first
+            // message.
+            let sandbox = Sandbox::instantiate(
+                &linker,
+                &module,
+                peers.clone(),
+                fuel_per_message,
+                memory_limit,
+            )?;
+            Ok(Self {
+                linker,
+                module,
+                peers,
+                fuel_per_message,
+                memory_limit,
+                sandbox: Some(sandbox),
+            })
+        })
+        .await?
+    }
+}
+
+impl WasmActor {
+    async fn handle_payload(&mut self, payload: Vec<u8>) -> anyhow::Result<Outbox> {
+        // The instance is put back only if the handler returns, as it
+        // may otherwise have been left in any state.
+        let sandbox = self.sandbox.take();
+        let linker = self.linker.clone();
+        let module = self.module.clone();
+        let peers = self.peers.clone();
+        let (fuel, memory_limit) = (self.fuel_per_message, self.memory_limit);
+        let (sandbox, outbox) = tokio::task::spawn_blocking(move || {
+            let mut sandbox = match sandbox {
+                Some(sandbox) => sandbox,
+                None => Sandbox::instantiate(&linker, &module, peers, fuel, memory_limit)?,
+            };
+            let outbox = sandbox.handle(&payload, fuel)?;
+            anyhow::Ok((sandbox, outbox))
+        })
+        .await??;
+        self.sandbox = Some(sandbox);
+        Ok(outbox)
+    }
+}
+
+#[async_trait]
+impl Handler<WasmMessage> for WasmActor {
+    async fn handle(&mut self, cx: &Context<Self>, message: WasmMessage) -> anyhow::Result<()> {
+        match self.handle_payload(message.payload).await {
+            Ok((replies, sends)) => {
+                if let Some(reply) = &message.reply {
+                    for payload in replies {
+                        reply.post(
+                            cx,
+                            WasmReply {
+                                result: Ok(payload),
+                            },
+                        );
+                    }
+                }
+                for (addr, payload) in sends {
+                    ActorRef::<WasmActor>::attest(addr).post(
+                        cx,
+                        WasmMessage {
+                            payload,
+                            reply: None,
+                        },
+                    );
+                }
+            }
+            Err(err) => {
+                tracing::warn!("wasm handler failed: {:#}", err);
+                if let Some(reply) = &message.reply {
+                    reply.post(
+                        cx,
+                        WasmReply {
+                            result: Err(format!("{:#}", err)),
+                        },
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor::Proc;
+    use hyperactor::RemoteSpawn;
+
+    use super::*;
+
+    /// Replies with each payload; spins forever on an empty payload.
+    const ECHO: &str = r#"
+        (module
+          (import "hyperactor" "reply" (func $reply (param i32 i32)))
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "handle") (param $ptr i32) (param $len i32)
+            (if (i32.eqz (local.get $len)) (then (loop $spin (br $spin))))
+            (call $reply (local.get $ptr) (local.get $len))))
+    "#;
+
+    #[tokio::test]
+    async fn test_wasm_actor() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let params = WasmActorParams {
+            fuel_per_message: Some(100_000),
+            ..WasmActorParams::new(ECHO)
+        };
+        let actor = proc.spawn(WasmActor::new(params, Flattrs::default()).await.unwrap());
+
+        let (reply, mut replies) = client.open_port::<WasmReply>();
+        let send = |payload: &[u8]| {
+            (&actor).post(
+                &client,
+                WasmMessage {
+                    payload: payload.to_vec(),
+                    reply: Some(reply.bind()),
+                },
+            )
+        };
+
+        send(b"hello");
+        assert_eq!(replies.recv().await.unwrap().result, Ok(b"hello".to_vec()));
+
+        // A handler that exhausts its fuel fails only its own message.
+        send(b"");
+        assert!(replies.recv().await.unwrap().result.is_err());
+        send(b"again");
+        assert_eq!(replies.recv().await.unwrap().result, Ok(b"again".to_vec()));
+
+        // A module that does not satisfy the ABI fails the spawn.
+        let module = "(module (memory (export \"memory\") 1))";
+        assert!(
+            WasmActor::new(WasmActorParams::new(module), Flattrs::default())
+                .await
+                .is_err()
+        );
+    }
+    /// Replies with the 16 bytes at the end of its memory, which run
+    /// past it.
+    const OUT_OF_BOUNDS: &str = r#"
+        (module
+          (import "hyperactor" "reply" (func $reply (param i32 i32)))
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "handle") (param i32 i32)
+            (call $reply (i32.const 65530) (i32.const 16))))
+    "#;
+
+    /// Sends each payload to the actor whose address it is, then
+    /// replies with it.
+    const FORWARD: &str = r#"
+        (module
+          (import "hyperactor" "reply" (func $reply (param i32 i32)))
+          (import "hyperactor" "send" (func $send (param i32 i32 i32 i32)))
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+          (func (export "handle") (param $ptr i32) (param $len i32)
+            (call $send (local.get $ptr) (local.get $len) (local.get $ptr) (local.get $len))
+            (call $reply (local.get $ptr) (local.get $len))))
+    "#;
+
+    #[tokio::test]
+    async fn test_wasm_actor_host_checks() {
+        let proc = Proc::isolated();
+        let client = proc.client("client");
+        let (reply, mut replies) = client.open_port::<WasmReply>();
+        let send = |actor: &hyperactor::ActorHandle<WasmActor>, payload: &[u8]| {
+            actor.post(
+                &client,
+                WasmMessage {
+                    payload: payload.to_vec(),
+                    reply: Some(reply.bind()),
+                },
+            )
+        };
+
+        let out_of_bounds = proc.spawn(
+            WasmActor::new(WasmActorParams::new(OUT_OF_BOUNDS), Flattrs::default())
+                .await
+                .unwrap(),
+        );
+        send(&out_of_bounds, b"read");
+        assert!(replies.recv().await.unwrap().result.is_err());
+
+        // Modules may send only to their peers.
+        let peer = proc.spawn(
+            WasmActor::new(WasmActorParams::new(ECHO), Flattrs::default())
+                .await
+                .unwrap(),
+        );
+        let params = WasmActorParams {
+            peers: vec![peer.bind()],
+            ..WasmActorParams::new(FORWARD)
+        };
+        let forward = proc.spawn(WasmActor::new(params, Flattrs::default()).await.unwrap());
+        let peer_addr = peer.actor_addr().to_string();
+        send(&forward, peer_addr.as_bytes());
+        assert_eq!(
+            replies.recv().await.unwrap().result,
+            Ok(peer_addr.into_bytes())
+        );
+        send(&forward, out_of_bounds.actor_addr().to_string().as_bytes());
+        assert!(replies.recv().await.unwrap().result.is_err());
+    }
+}
@@ -73,6 +73,8 @@ mod tests {
             last_message_handler: None,
             total_processing_time_us: 0,
             queue_depth: 0,
+            queued_bytes: 0,
+            queued_bytes_by_port: Default::default(),
             flight_recorder: None,
             is_system: false,
             inbound_ordering: None,
@@ -146,6 +148,8 @@ mod tests {
             last_message_handler: None,
             total_processing_time_us: 0,
             queue_depth: 0,
+            queued_bytes: 0,
+            queued_bytes_by_port: Default::default(),
             flight_recorder: None,
             is_system: false,
             inbound_ordering: None,
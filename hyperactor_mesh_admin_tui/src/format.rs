@@ -586,6 +586,8 @@ mod tests {
                 last_message_handler: Some("handle_task".to_string()),
                 total_processing_time_us: 1000,
                 queue_depth: 0,
+                queued_bytes: 0,
+                queued_bytes_by_port: Default::default(),
                 flight_recorder: None,
                 is_system: false,
                 inbound_ordering: None,
@@ -614,6 +616,8 @@ mod tests {
                 last_message_handler: None,
                 total_processing_time_us: 0,
                 queue_depth: 0,
+                queued_bytes: 0,
+                queued_bytes_by_port: Default::default(),
                 flight_recorder: None,
                 is_system: false,
                 inbound_ordering: None,
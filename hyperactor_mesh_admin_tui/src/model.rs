@@ -375,6 +375,8 @@ mod tests {
                 last_message_handler: None,
                 total_processing_time_us: 0,
                 queue_depth: 0,
+                queued_bytes: 0,
+                queued_bytes_by_port: Default::default(),
                 flight_recorder: None,
                 is_system: false,
                 inbound_ordering: None,
@@ -612,6 +614,8 @@ mod tests {
                 last_message_handler: None,
                 total_processing_time_us: 0,
                 queue_depth: 0,
+                queued_bytes: 0,
+                queued_bytes_by_port: Default::default(),
                 flight_recorder: None,
                 is_system: false,
                 inbound_ordering: None,
@@ -648,6 +652,8 @@ mod tests {
                 last_message_handler: None,
                 total_processing_time_us: 0,
                 queue_depth: 0,
+                queued_bytes: 0,
+                queued_bytes_by_port: Default::default(),
                 flight_recorder: None,
                 is_system: true,
                 inbound_ordering: None,
@@ -717,6 +723,8 @@ mod tests {
                 last_message_handler: None,
                 total_processing_time_us: 0,
                 queue_depth: 0,
+                queued_bytes: 0,
+                queued_bytes_by_port: Default::default(),
                 flight_recorder: None,
                 is_system: false,
                 inbound_ordering: None,
@@ -760,6 +768,8 @@ mod tests {
                 last_message_handler: None,
                 total_processing_time_us: 0,
                 queue_depth: 0,
+                queued_bytes: 0,
+                queued_bytes_by_port: Default::default(),
                 flight_recorder: None,
                 is_system: false,
                 inbound_ordering: None,
@@ -801,6 +811,8 @@ mod tests {
                 last_message_handler: None,
                 total_processing_time_us: 0,
                 queue_depth: 0,
+                queued_bytes: 0,
+                queued_bytes_by_port: Default::default(),
                 flight_recorder: None,
                 is_system: false,
                 inbound_ordering: None,
@@ -1559,6 +1559,8 @@ fn pyspy_proc_ref_actor_node_with_parent() {
             last_message_handler: None,
             total_processing_time_us: 0,
             queue_depth: 0,
+            queued_bytes: 0,
+            queued_bytes_by_port: Default::default(),
             flight_recorder: None,
             is_system: false,
             inbound_ordering: None,
@@ -2563,6 +2565,8 @@ fn help_content_actor_buffered_independence() {
         last_message_handler: None,
         total_processing_time_us: 0,
         queue_depth: 0,
+        queued_bytes: 0,
+        queued_bytes_by_port: Default::default(),
         flight_recorder: None,
         is_system: false,
         inbound_ordering: None,
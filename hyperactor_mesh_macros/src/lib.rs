@@ -8,11 +8,23 @@
 
 // Clippy can't see through quote! to use of proc-macro2
 #![allow(unused_crate_dependencies)]
+#![feature(proc_macro_def_site)]
 
 extern crate proc_macro;
 
+use convert_case::Case;
+use convert_case::Casing;
 use proc_macro::TokenStream;
+use quote::format_ident;
 use quote::quote;
+use syn::Data;
+use syn::DeriveInput;
+use syn::Field;
+use syn::Fields;
+use syn::Ident;
+use syn::Type;
+use syn::parse_macro_input;
+use syn::punctuated::Punctuated;
 
 /// Parse a compact selection expression into a [`Selection`]. See
 /// [`selection::parse`] for syntax documentation.
@@ -29,3 +41,263 @@ pub fn sel(input: TokenStream) -> TokenStream {
         }
     }
 }
+
+/// A message variant of a type deriving `MeshClient`, or the type
+/// itself if it is a struct.
+struct Variant {
+    /// The path of the variant's constructor.
+    path: proc_macro2::TokenStream,
+    /// The name of the variant.
+    name: Ident,
+    /// Whether the variant's fields are named.
+    named: bool,
+    /// The names of the fields: tuple fields are named after their
+    /// positions.
+    field_names: Vec<Ident>,
+    field_types: Vec<Type>,
+    /// The position of the `#[reply]` field, if any.
+    reply: Option<usize>,
+}
+
+impl Variant {
+    fn new(
+        path: proc_macro2::TokenStream,
+        name: Ident,
+        fields: &Fields,
+    ) -> Result<Self, syn::Error> {
+        let field_names = fields
+            .iter()
+            .enumerate()
+            .map(|(index, field)| {
+                field
+                    .ident
+                    .clone()
+                    .unwrap_or_else(|| format_ident!("arg{}", index))
+            })
+            .collect();
+        let replies: Vec<_> = fields
+            .iter()
+            .enumerate()
+            .filter(|(_, field)| is_reply(field))
+            .map(|(index, _)| index)
+            .collect();
+        if replies.len() > 1 {
+            return Err(syn::Error::new_spanned(
+                fields,
+                "`call` message expects at most one `reply` argument",
+            ));
+        }
+        Ok(Self {
+            path,
+            name,
+            named: matches!(fields, Fields::Named(_)),
+            field_names,
+            field_types: fields.iter().map(|field| field.ty.clone()).collect(),
+            reply: replies.first().copied(),
+        })
+    }
+
+    /// The name of the variant's client method.
+    fn snake_name(&self) -> Ident {
+        Ident::new(
+            &self.name.to_string().to_case(Case::Snake),
+            self.name.span(),
+        )
+    }
+
+    /// The arguments of the variant's client method: its fields other
+    /// than the reply port.
+    fn args(&self) -> (Vec<&Ident>, Vec<&Type>) {
+        self.field_names
+            .iter()
+            .zip(&self.field_types)
+            .enumerate()
+            .filter(|(index, _)| Some(*index) != self.reply)
+            .map(|(_, arg)| arg)
+            .unzip()
+    }
+
+    /// The constructor of the variant, from the field names.
+    fn constructor(&self) -> proc_macro2::TokenStream {
+        let path = &self.path;
+        let field_names = &self.field_names;
+        if self.named {
+            quote! { #path { #(#field_names),* } }
+        } else if field_names.is_empty() {
+            quote! { #path }
+        } else {
+            quote! { #path(#(#field_names),*) }
+        }
+    }
+
+    /// The reply type of the variant's `#[reply]` port, if the port is
+    /// a `PortRef`, whose replies can be gathered from many actors.
+    fn gathered_reply_type(&self) -> Option<&Type> {
+        let Type::Path(type_path) = &self.field_types[self.reply?] else {
+            return None;
+        };
+        let segment = type_path.path.segments.last()?;
+        if segment.ident != "PortRef" {
+            return None;
+        }
+        let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+            return None;
+        };
+        match args.args.first()? {
+            syn::GenericArgument::Type(reply_type) => Some(reply_type),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `field` is marked `#[reply]` (or `#[reply(..)]`).
+fn is_reply(field: &Field) -> bool {
+    field.attrs.iter().any(|attr| attr.path().is_ident("reply"))
+}
+
+/// Derives a typed cast client on `hyperactor_mesh::ActorMeshRef<Actor>`.
+///
+/// The derive generates a trait named `<Message>MeshClient`, with a
+/// method for each message variant, implemented for the mesh refs of
+/// actors that handle the message. A one-way variant's method casts the
+/// message to every actor in the mesh. A call variant whose reply port
+/// is a `PortRef<R>` sends the message to every actor in the mesh, each
+/// with a reply port of its own, and returns a
+/// `hyperactor_mesh::gather::GatherHandle<R>` that receives the replies
+/// along with the ranks that sent them. Call variants with other reply
+/// ports do not have mesh methods, as their replies cannot be gathered
+/// from many actors.
+///
+/// To cast to a subset of a mesh, call the method on a slice of it.
+///
+/// ```ignore
+/// #[derive(Clone, Debug, Serialize, Deserialize, Named, Bind, Unbind, Handler, MeshClient)]
+/// struct GetValue(#[binding(include)] #[reply] PortRef<u64>);
+///
+/// let replies = mesh.range("gpu", 0..4)?.get_value(cx)?;
+/// let gathered = replies.gather(GatherOpts::default()).await;
+/// for (rank, value) in gathered.replies { /* .. */ }
+/// ```
+#[proc_macro_derive(MeshClient, attributes(reply))]
+pub fn derive_mesh_client(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = input.ident.clone();
+
+    let variants = match &input.data {
+        Data::Struct(data) => {
+            Variant::new(quote! { #name }, name.clone(), &data.fields).map(|variant| vec![variant])
+        }
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| {
+                let variant_name = &variant.ident;
+                Variant::new(
+                    quote! { #name::#variant_name },
+                    variant_name.clone(),
+                    &variant.fields,
+                )
+            })
+            .collect(),
+        Data::Union(_) => Err(syn::Error::new_spanned(
+            &input,
+            "mesh clients can only be derived for enums and structs",
+        )),
+    };
+    let variants = match variants {
+        Ok(variants) => variants,
+        Err(err) => return TokenStream::from(err.to_compile_error()),
+    };
+
+    let mut trait_methods = Vec::new();
+    let mut impl_methods = Vec::new();
+
+    for variant in &variants {
+        let method = variant.snake_name();
+        let (arg_names, arg_types) = variant.args();
+        let constructor = variant.constructor();
+        if variant.reply.is_none() {
+            trait_methods.push(quote! {
+                #[doc = "Cast this message to the mesh."]
+                fn #method(
+                    &self,
+                    cx: &impl hyperactor::context::Actor,
+                    #(#arg_names: #arg_types),*)
+                    -> hyperactor_mesh::Result<()>;
+            });
+            impl_methods.push(quote! {
+                fn #method(
+                    &self,
+                    cx: &impl hyperactor::context::Actor,
+                    #(#arg_names: #arg_types),*)
+                    -> hyperactor_mesh::Result<()> {
+                    let message = #constructor;
+                    self.cast(cx, message)
+                }
+            });
+            continue;
+        }
+        let Some(reply_type) = variant.gathered_reply_type() else {
+            continue;
+        };
+        let reply_port = &variant.field_names[variant.reply.unwrap()];
+        trait_methods.push(quote! {
+            #[doc = "Send this message to every actor of the mesh, returning a handle to their replies."]
+            fn #method(
+                &self,
+                cx: &impl hyperactor::context::Actor,
+                #(#arg_names: #arg_types),*)
+                -> hyperactor_mesh::Result<hyperactor_mesh::gather::GatherHandle<#reply_type>>;
+        });
+        impl_methods.push(quote! {
+            fn #method(
+                &self,
+                cx: &impl hyperactor::context::Actor,
+                #(#arg_names: #arg_types),*)
+                -> hyperactor_mesh::Result<hyperactor_mesh::gather::GatherHandle<#reply_type>> {
+                hyperactor_mesh::gather::GatherHandle::cast(self, cx, |#reply_port| {
+                    #(let #arg_names = ::std::clone::Clone::clone(&#arg_names);)*
+                    #constructor
+                })
+            }
+        });
+    }
+
+    let trait_name = format_ident!("{}MeshClient", name);
+
+    let (trait_generics, ty_generics, _) = input.generics.split_for_impl();
+
+    // The impl adds the actor type parameter.
+    let actor_ident = Ident::new("A", proc_macro2::Span::from(proc_macro::Span::def_site()));
+    let mut impl_generics = input.generics.clone();
+    impl_generics.params.insert(
+        0,
+        syn::GenericParam::Type(syn::TypeParam {
+            ident: actor_ident.clone(),
+            attrs: vec![],
+            colon_token: None,
+            bounds: Punctuated::new(),
+            eq_token: None,
+            default: None,
+        }),
+    );
+    let (impl_generics, _, _) = impl_generics.split_for_impl();
+
+    let expanded = quote! {
+        #[doc = "The typed cast client trait for this message type."]
+        pub trait #trait_name #trait_generics {
+            #(#trait_methods)*
+        }
+
+        impl #impl_generics #trait_name #ty_generics for hyperactor_mesh::ActorMeshRef<#actor_ident>
+          where
+            #actor_ident: hyperactor::actor::Referable
+                + hyperactor::RemoteHandles<#name #ty_generics>
+                + hyperactor::RemoteHandles<hyperactor::message::IndexedErasedUnbound<#name #ty_generics>>,
+            #name #ty_generics: hyperactor::message::Castable + Clone {
+            #(#impl_methods)*
+        }
+    };
+
+    TokenStream::from(expanded)
+}
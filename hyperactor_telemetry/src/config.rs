@@ -9,7 +9,8 @@
 //! Configuration keys for hyperactor telemetry.
 //!
 //! This module defines configuration attributes for telemetry features including
-//! OpenTelemetry tracing/metrics, recorder output, SQLite tracing, and file logging.
+//! OpenTelemetry tracing/metrics, metric exporters, recorder output, SQLite tracing,
+//! and file logging.
 
 use std::str::FromStr;
 use std::time::Duration;
@@ -69,6 +70,61 @@ declare_attrs! {
     ))
     pub attr OTEL_METRIC_EXPORT_INTERVAL: Duration = Duration::from_secs(1);
 
+    /// Comma-separated metric exporters to install in addition to any
+    /// built-in sinks: `otlp` (OTLP over gRPC), `statsd`, and `json`
+    /// (rotating JSON-lines files). See [`crate::exporters`].
+    @meta(CONFIG = ConfigAttr::new(
+        Some("MONARCH_METRICS_EXPORTERS".to_string()),
+        Some("metrics_exporters".to_string()),
+    ))
+    pub attr METRICS_EXPORTERS: String = String::new();
+
+    /// The gRPC endpoint of the `otlp` metric exporter.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("MONARCH_METRICS_OTLP_GRPC_ENDPOINT".to_string()),
+        Some("metrics_otlp_grpc_endpoint".to_string()),
+    ))
+    pub attr METRICS_OTLP_GRPC_ENDPOINT: String = "http://localhost:4317".to_string();
+
+    /// The UDP address of the StatsD server of the `statsd` metric
+    /// exporter.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("MONARCH_METRICS_STATSD_ADDR".to_string()),
+        Some("metrics_statsd_addr".to_string()),
+    ))
+    pub attr METRICS_STATSD_ADDR: String = "127.0.0.1:8125".to_string();
+
+    /// The prefix of the metric names sent by the `statsd` metric
+    /// exporter. Empty for no prefix.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("MONARCH_METRICS_STATSD_PREFIX".to_string()),
+        Some("metrics_statsd_prefix".to_string()),
+    ))
+    pub attr METRICS_STATSD_PREFIX: String = "monarch".to_string();
+
+    /// The file written by the `json` metric exporter. Defaults to
+    /// `monarch_metrics_<pid>.jsonl` in the temporary directory.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("MONARCH_METRICS_JSON_PATH".to_string()),
+        Some("metrics_json_path".to_string()),
+    ))
+    pub attr METRICS_JSON_PATH: String = String::new();
+
+    /// The size at which the `json` metric exporter rotates its file.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("MONARCH_METRICS_JSON_MAX_BYTES".to_string()),
+        Some("metrics_json_max_bytes".to_string()),
+    ))
+    pub attr METRICS_JSON_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+    /// The number of rotated files the `json` metric exporter keeps,
+    /// in addition to the file it is writing.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("MONARCH_METRICS_JSON_MAX_FILES".to_string()),
+        Some("metrics_json_max_files".to_string()),
+    ))
+    pub attr METRICS_JSON_MAX_FILES: usize = 4;
+
     /// Enable logging of span enter/exit events to Scuba.
     @meta(CONFIG = ConfigAttr::new(
         Some("SCUBA_LOG_ENTER_EXIT".to_string()),
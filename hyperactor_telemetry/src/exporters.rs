@@ -0,0 +1,703 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Pluggable metric exporters.
+//!
+//! Metrics are recorded through the global OpenTelemetry meter provider.
+//! Outside of Meta's internal builds, the provider exports them to the
+//! exporters configured with [`METRICS_EXPORTERS`], a comma-separated
+//! list of:
+//!
+//! - `otlp`: OTLP over gRPC, to [`METRICS_OTLP_GRPC_ENDPOINT`].
+//! - `statsd`: StatsD over UDP, to [`METRICS_STATSD_ADDR`], with
+//!   attributes as DogStatsD tags.
+//! - `json`: JSON lines, one per data point, appended to
+//!   [`METRICS_JSON_PATH`] and rotated at [`METRICS_JSON_MAX_BYTES`].
+//!
+//! Metrics are also exported over OTLP/HTTP when
+//! `OTEL_EXPORTER_OTLP_ENDPOINT` is set (see [`crate::otlp`]). All
+//! exporters are flushed every [`OTEL_METRIC_EXPORT_INTERVAL`].
+//!
+//! Other backends can be added by implementing [`MetricSink`], which
+//! receives metrics flattened into [`MetricPoint`]s, and installing it
+//! with [`SinkExporter`].
+
+use std::fmt;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::net::UdpSocket;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::error::OTelSdkError;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::metrics::MeterProviderBuilder;
+use opentelemetry_sdk::metrics::PeriodicReader;
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::metrics::Temporality;
+use opentelemetry_sdk::metrics::data::AggregatedMetrics;
+use opentelemetry_sdk::metrics::data::MetricData;
+use opentelemetry_sdk::metrics::data::ResourceMetrics;
+use opentelemetry_sdk::metrics::exporter::PushMetricExporter;
+use serde::Serialize;
+
+use crate::config::METRICS_EXPORTERS;
+use crate::config::METRICS_JSON_MAX_BYTES;
+use crate::config::METRICS_JSON_MAX_FILES;
+use crate::config::METRICS_JSON_PATH;
+use crate::config::METRICS_OTLP_GRPC_ENDPOINT;
+use crate::config::METRICS_STATSD_ADDR;
+use crate::config::METRICS_STATSD_PREFIX;
+use crate::config::OTEL_METRIC_EXPORT_INTERVAL;
+
+/// The largest StatsD datagram sent, which fits in an Ethernet frame.
+const STATSD_MAX_DATAGRAM: usize = 1432;
+
+/// The value of a [`MetricPoint`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricValue {
+    /// A monotonic sum: a total with cumulative temporality, or an
+    /// increment with delta temporality.
+    Counter(f64),
+    /// A non-monotonic sum or a gauge.
+    Gauge(f64),
+    /// A histogram's summary.
+    Histogram {
+        /// The number of values recorded.
+        count: u64,
+        /// The sum of the values recorded.
+        sum: f64,
+        /// The smallest value recorded, if tracked.
+        min: Option<f64>,
+        /// The largest value recorded, if tracked.
+        max: Option<f64>,
+    },
+}
+
+/// A metric data point, flattened from OpenTelemetry's metric data.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MetricPoint {
+    /// The metric's name.
+    pub name: String,
+    /// The data point's attributes.
+    pub attributes: Vec<(String, String)>,
+    /// The data point's value.
+    pub value: MetricValue,
+}
+
+/// A metric backend that receives flattened data points; see
+/// [`SinkExporter`].
+pub trait MetricSink: fmt::Debug + Send + Sync + 'static {
+    /// Export one collection's data points.
+    fn export(&self, points: &[MetricPoint]) -> anyhow::Result<()>;
+
+    /// The temporality in which the sink expects sums.
+    fn temporality(&self) -> Temporality {
+        Temporality::Cumulative
+    }
+
+    /// Flush any buffered output.
+    fn flush(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Values that data points carry.
+trait AsF64: Copy + fmt::Debug + Send + Sync + 'static {
+    fn as_f64(self) -> f64;
+}
+
+impl AsF64 for f64 {
+    fn as_f64(self) -> f64 {
+        self
+    }
+}
+
+impl AsF64 for u64 {
+    fn as_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl AsF64 for i64 {
+    fn as_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+fn attributes<'a>(attributes: impl Iterator<Item = &'a KeyValue>) -> Vec<(String, String)> {
+    attributes
+        .map(|kv| (kv.key.as_str().to_string(), kv.value.as_str().into_owned()))
+        .collect()
+}
+
+fn flatten_data<T: AsF64>(name: &str, data: &MetricData<T>, points: &mut Vec<MetricPoint>) {
+    match data {
+        MetricData::Sum(sum) => {
+            for dp in sum.data_points() {
+                let value = dp.value().as_f64();
+                points.push(MetricPoint {
+                    name: name.to_string(),
+                    attributes: attributes(dp.attributes()),
+                    value: if sum.is_monotonic() {
+                        MetricValue::Counter(value)
+                    } else {
+                        MetricValue::Gauge(value)
+                    },
+                });
+            }
+        }
+        MetricData::Gauge(gauge) => {
+            for dp in gauge.data_points() {
+                points.push(MetricPoint {
+                    name: name.to_string(),
+                    attributes: attributes(dp.attributes()),
+                    value: MetricValue::Gauge(dp.value().as_f64()),
+                });
+            }
+        }
+        MetricData::Histogram(histogram) => {
+            for dp in histogram.data_points() {
+                points.push(MetricPoint {
+                    name: name.to_string(),
+                    attributes: attributes(dp.attributes()),
+                    value: MetricValue::Histogram {
+                        count: dp.count(),
+                        sum: dp.sum().as_f64(),
+                        min: dp.min().map(AsF64::as_f64),
+                        max: dp.max().map(AsF64::as_f64),
+                    },
+                });
+            }
+        }
+        // Exponential histograms are not produced by the instruments
+        // that hyperactor declares.
+        MetricData::ExponentialHistogram(_) => {}
+    }
+}
+
+/// Flatten `metrics` into data points.
+pub fn flatten(metrics: &ResourceMetrics) -> Vec<MetricPoint> {
+    let mut points = Vec::new();
+    for scope in metrics.scope_metrics() {
+        for metric in scope.metrics() {
+            match metric.data() {
+                AggregatedMetrics::F64(data) => flatten_data(metric.name(), data, &mut points),
+                AggregatedMetrics::U64(data) => flatten_data(metric.name(), data, &mut points),
+                AggregatedMetrics::I64(data) => flatten_data(metric.name(), data, &mut points),
+            }
+        }
+    }
+    points
+}
+
+/// A [`PushMetricExporter`] that exports to a [`MetricSink`].
+#[derive(Debug)]
+pub struct SinkExporter<S>(pub S);
+
+impl<S: MetricSink> PushMetricExporter for SinkExporter<S> {
+    async fn export(&self, metrics: &ResourceMetrics) -> OTelSdkResult {
+        self.0
+            .export(&flatten(metrics))
+            .map_err(|e| OTelSdkError::InternalFailure(e.to_string()))
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.0
+            .flush()
+            .map_err(|e| OTelSdkError::InternalFailure(e.to_string()))
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        self.force_flush()
+    }
+
+    fn temporality(&self) -> Temporality {
+        self.0.temporality()
+    }
+}
+
+/// Sends metrics to a StatsD server over UDP. Sums are sent as
+/// increments, and histograms as their count, sum, min, and max.
+#[derive(Debug)]
+pub struct StatsdSink {
+    socket: UdpSocket,
+    prefix: String,
+}
+
+impl StatsdSink {
+    /// A sink sending to the StatsD server at `addr`, prefixing metric
+    /// names with `prefix` if it is not empty.
+    pub fn new(addr: &str, prefix: impl Into<String>) -> anyhow::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(addr)?;
+        Ok(Self {
+            socket,
+            prefix: prefix.into(),
+        })
+    }
+
+    fn name(&self, name: &str, suffix: &str) -> String {
+        let name: String = name
+            .chars()
+            .map(|c| {
+                if matches!(c, ':' | '|' | '@' | '#' | ',') {
+                    '_'
+                } else {
+                    c
+                }
+            })
+            .collect();
+        match self.prefix.as_str() {
+            "" => format!("{}{}", name, suffix),
+            prefix => format!("{}.{}{}", prefix, name, suffix),
+        }
+    }
+
+    /// The StatsD lines of `point`.
+    fn lines(&self, point: &MetricPoint) -> Vec<String> {
+        let tags = if point.attributes.is_empty() {
+            String::new()
+        } else {
+            let tags: Vec<String> = point
+                .attributes
+                .iter()
+                .map(|(k, v)| format!("{}:{}", k, v.replace([',', '|'], "_")))
+                .collect();
+            format!("|#{}", tags.join(","))
+        };
+        let line = |suffix: &str, value: f64, kind: &str| {
+            format!(
+                "{}:{}|{}{}",
+                self.name(&point.name, suffix),
+                value,
+                kind,
+                tags
+            )
+        };
+        match &point.value {
+            MetricValue::Counter(value) => vec![line("", *value, "c")],
+            MetricValue::Gauge(value) => vec![line("", *value, "g")],
+            MetricValue::Histogram {
+                count,
+                sum,
+                min,
+                max,
+            } => {
+                let mut lines = vec![line(".count", *count as f64, "c"), line(".sum", *sum, "c")];
+                lines.extend(min.map(|min| line(".min", min, "g")));
+                lines.extend(max.map(|max| line(".max", max, "g")));
+                lines
+            }
+        }
+    }
+}
+
+impl MetricSink for StatsdSink {
+    fn export(&self, points: &[MetricPoint]) -> anyhow::Result<()> {
+        let mut datagram = String::new();
+        for line in points.iter().flat_map(|point| self.lines(point)) {
+            if !datagram.is_empty() && datagram.len() + 1 + line.len() > STATSD_MAX_DATAGRAM {
+                self.socket.send(datagram.as_bytes())?;
+                datagram.clear();
+            }
+            if !datagram.is_empty() {
+                datagram.push('\n');
+            }
+            datagram.push_str(&line);
+        }
+        if !datagram.is_empty() {
+            self.socket.send(datagram.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn temporality(&self) -> Temporality {
+        Temporality::Delta
+    }
+}
+
+/// A file that is rotated when it reaches a maximum size: `path` is
+/// renamed to `path.1`, `path.1` to `path.2`, and so on, keeping at
+/// most `max_files` rotated files.
+#[derive(Debug)]
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: Option<File>,
+    len: u64,
+}
+
+impl RotatingFile {
+    fn new(path: PathBuf, max_bytes: u64, max_files: usize) -> Self {
+        Self {
+            path,
+            max_bytes,
+            max_files,
+            file: None,
+            len: 0,
+        }
+    }
+
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", n));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file = None;
+        if self.max_files == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+        for n in (1..self.max_files).rev() {
+            let from = self.rotated(n);
+            if from.exists() {
+                std::fs::rename(from, self.rotated(n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.rotated(1))
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        if self.file.is_some() && self.len + bytes.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        if self.file.is_none() {
+            if let Some(parent) = self.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            self.len = file.metadata()?.len();
+            self.file = Some(file);
+        }
+        let file = self.file.as_mut().expect("file was just opened");
+        file.write_all(bytes)?;
+        self.len += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Appends metrics to a file as JSON lines, one per data point, with
+/// the time of their collection, rotating the file when it grows too
+/// large.
+#[derive(Debug)]
+pub struct JsonFileSink {
+    file: Mutex<RotatingFile>,
+}
+
+impl JsonFileSink {
+    /// A sink writing to `path`, rotating it at `max_bytes` and keeping
+    /// `max_files` rotated files.
+    pub fn new(path: impl Into<PathBuf>, max_bytes: u64, max_files: usize) -> Self {
+        Self {
+            file: Mutex::new(RotatingFile::new(path.into(), max_bytes, max_files)),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    timestamp_ms: u128,
+    #[serde(flatten)]
+    point: &'a MetricPoint,
+}
+
+impl MetricSink for JsonFileSink {
+    fn export(&self, points: &[MetricPoint]) -> anyhow::Result<()> {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_millis();
+        let mut lines = Vec::new();
+        for point in points {
+            serde_json::to_writer(
+                &mut lines,
+                &JsonRecord {
+                    timestamp_ms,
+                    point,
+                },
+            )?;
+            lines.push(b'\n');
+        }
+        self.file.lock().unwrap().write(&lines)?;
+        Ok(())
+    }
+
+    fn flush(&self) -> anyhow::Result<()> {
+        Ok(self.file.lock().unwrap().flush()?)
+    }
+}
+
+/// Exports to an OTLP gRPC endpoint. The gRPC client runs on its own
+/// runtime, as metrics are exported from the meter provider's thread.
+struct OtlpGrpcExporter {
+    exporter: opentelemetry_otlp::MetricExporter,
+    /// Always `Some` until dropped.
+    runtime: Option<tokio::runtime::Runtime>,
+}
+
+impl fmt::Debug for OtlpGrpcExporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OtlpGrpcExporter").finish_non_exhaustive()
+    }
+}
+
+impl OtlpGrpcExporter {
+    fn new(endpoint: &str) -> anyhow::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("otlp-grpc-metrics")
+            .enable_all()
+            .build()?;
+        let exporter = {
+            let _guard = runtime.enter();
+            opentelemetry_otlp::MetricExporter::builder()
+                .with_tonic()
+                .with_endpoint(endpoint)
+                .build()?
+        };
+        Ok(Self {
+            exporter,
+            runtime: Some(runtime),
+        })
+    }
+}
+
+impl Drop for OtlpGrpcExporter {
+    fn drop(&mut self) {
+        // Dropping a runtime blocks, which panics within another
+        // runtime, e.g. if the meter provider is dropped in async code.
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+impl PushMetricExporter for OtlpGrpcExporter {
+    async fn export(&self, metrics: &ResourceMetrics) -> OTelSdkResult {
+        let runtime = self.runtime.as_ref().expect("runtime is dropped last");
+        // `block_on` panics on a thread that is already in a runtime, as
+        // when the meter provider is flushed from async code, so the
+        // export runs on a thread of its own.
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| runtime.block_on(self.exporter.export(metrics)))
+                .join()
+                .unwrap_or_else(|_| {
+                    Err(OTelSdkError::InternalFailure(
+                        "OTLP gRPC export panicked".to_string(),
+                    ))
+                })
+        })
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.exporter.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.exporter.shutdown_with_timeout(timeout)
+    }
+
+    fn temporality(&self) -> Temporality {
+        self.exporter.temporality()
+    }
+}
+
+fn with_exporter<E: PushMetricExporter>(
+    builder: MeterProviderBuilder,
+    exporter: E,
+) -> MeterProviderBuilder {
+    let interval = hyperactor_config::global::get(OTEL_METRIC_EXPORT_INTERVAL);
+    builder.with_reader(
+        PeriodicReader::builder(exporter)
+            .with_interval(interval)
+            .build(),
+    )
+}
+
+/// An exporter configured with [`METRICS_EXPORTERS`].
+enum ConfiguredExporter {
+    Otlp(OtlpGrpcExporter),
+    Statsd(SinkExporter<StatsdSink>),
+    Json(SinkExporter<JsonFileSink>),
+}
+
+/// Build the exporter named `name`.
+fn configured_exporter(name: &str) -> anyhow::Result<ConfiguredExporter> {
+    Ok(match name {
+        "otlp" => {
+            let endpoint = hyperactor_config::global::get_cloned(METRICS_OTLP_GRPC_ENDPOINT);
+            ConfiguredExporter::Otlp(OtlpGrpcExporter::new(&endpoint)?)
+        }
+        "statsd" => ConfiguredExporter::Statsd(SinkExporter(StatsdSink::new(
+            &hyperactor_config::global::get_cloned(METRICS_STATSD_ADDR),
+            hyperactor_config::global::get_cloned(METRICS_STATSD_PREFIX),
+        )?)),
+        "json" => {
+            let path = match hyperactor_config::global::get_cloned(METRICS_JSON_PATH) {
+                path if path.is_empty() => std::env::temp_dir()
+                    .join(format!("monarch_metrics_{}.jsonl", std::process::id())),
+                path => PathBuf::from(path),
+            };
+            ConfiguredExporter::Json(SinkExporter(JsonFileSink::new(
+                path,
+                hyperactor_config::global::get(METRICS_JSON_MAX_BYTES) as u64,
+                hyperactor_config::global::get(METRICS_JSON_MAX_FILES),
+            )))
+        }
+        other => anyhow::bail!("unknown metrics exporter: {}", other),
+    })
+}
+
+/// Build a meter provider exporting to the configured exporters, or
+/// `None` if none are configured. Exporters that fail to build are
+/// reported and skipped.
+pub(crate) fn meter_provider() -> Option<SdkMeterProvider> {
+    let mut builder = SdkMeterProvider::builder();
+    let mut configured = false;
+    if let Some(exporter) = crate::otlp::otlp_metric_exporter() {
+        builder = with_exporter(builder, exporter);
+        configured = true;
+    }
+    let names = hyperactor_config::global::get_cloned(METRICS_EXPORTERS);
+    for name in names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        builder = match configured_exporter(name) {
+            Ok(ConfiguredExporter::Otlp(exporter)) => with_exporter(builder, exporter),
+            Ok(ConfiguredExporter::Statsd(exporter)) => with_exporter(builder, exporter),
+            Ok(ConfiguredExporter::Json(exporter)) => with_exporter(builder, exporter),
+            Err(e) => {
+                eprintln!(
+                    "[telemetry] failed to build {} metrics exporter: {}",
+                    name, e
+                );
+                continue;
+            }
+        };
+        configured = true;
+    }
+    configured.then(|| builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::metrics::MeterProvider as _;
+
+    use super::*;
+
+    fn point(name: &str, value: MetricValue) -> MetricPoint {
+        MetricPoint {
+            name: name.to_string(),
+            attributes: vec![("actor".to_string(), "a,b".to_string())],
+            value,
+        }
+    }
+
+    #[test]
+    fn test_statsd_export() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = StatsdSink::new(&server.local_addr().unwrap().to_string(), "monarch").unwrap();
+        sink.export(&[
+            point("messages:sent", MetricValue::Counter(3.0)),
+            point(
+                "latency",
+                MetricValue::Histogram {
+                    count: 2,
+                    sum: 5.5,
+                    min: Some(1.5),
+                    max: None,
+                },
+            ),
+        ])
+        .unwrap();
+
+        let mut buf = [0u8; STATSD_MAX_DATAGRAM];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&buf[..len]).unwrap(),
+            "monarch.messages_sent:3|c|#actor:a_b\n\
+             monarch.latency.count:2|c|#actor:a_b\n\
+             monarch.latency.sum:5.5|c|#actor:a_b\n\
+             monarch.latency.min:1.5|g|#actor:a_b"
+        );
+    }
+
+    #[test]
+    fn test_json_file_rotation() {
+        let dir = std::env::temp_dir().join(format!("monarch_metrics_test_{}", std::process::id()));
+        let path = dir.join("metrics.jsonl");
+        let _ = std::fs::remove_dir_all(&dir);
+        let sink = JsonFileSink::new(&path, 100, 1);
+
+        sink.export(&[point("a", MetricValue::Gauge(1.0))]).unwrap();
+        sink.flush().unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let record: serde_json::Value = serde_json::from_str(contents.trim()).unwrap();
+        assert_eq!(record["name"], "a");
+        assert_eq!(record["value"]["gauge"], 1.0);
+        assert_eq!(record["attributes"][0][1], "a,b");
+
+        // Each export exceeds the maximum size with the previous one, so
+        // each rotates the file; only one rotated file is kept.
+        sink.export(&[point("b", MetricValue::Counter(2.0))])
+            .unwrap();
+        sink.export(&[point("c", MetricValue::Counter(3.0))])
+            .unwrap();
+        sink.flush().unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().contains("\"c\""));
+        let rotated = dir.join("metrics.jsonl.1");
+        assert!(std::fs::read_to_string(&rotated).unwrap().contains("\"b\""));
+        assert!(!dir.join("metrics.jsonl.2").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unknown_exporter() {
+        assert!(configured_exporter("carrier_pigeon").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_otlp_exporter_in_runtime() {
+        // Nothing listens on the endpoint; exports fail, but flushing
+        // and dropping the exporter within a runtime must not panic.
+        let provider = with_exporter(
+            SdkMeterProvider::builder(),
+            OtlpGrpcExporter::new("http://127.0.0.1:1").unwrap(),
+        )
+        .build();
+        provider
+            .meter("test")
+            .u64_counter("count")
+            .build()
+            .add(1, &[]);
+        let _ = provider.force_flush();
+        let _ = provider.shutdown();
+        drop(OtlpGrpcExporter::new("http://127.0.0.1:1").unwrap());
+    }
+}
@@ -56,6 +56,7 @@ const ENV_VALUE_LOCAL_MAST_SIMULATOR: &str = "local_mast_simulator";
 pub const skip_record: bool = true;
 
 mod config;
+pub mod exporters;
 pub mod in_memory_reader;
 #[cfg(all(fbcode_build, target_os = "linux"))]
 mod meta;
@@ -441,6 +442,12 @@ pub struct MessageEvent {
     pub endpoint: Option<String>,
     /// Destination port index, scoped by `to_actor_id`.
     pub port_index: Option<u64>,
+    /// The id of the message whose handler sent this message, if it was
+    /// sent while handling one.
+    pub parent_message_id: Option<u64>,
+    /// The id of the first message of this message's causal chain, if
+    /// it has a parent.
+    pub root_message_id: Option<u64>,
 }
 
 /// Notify telemetry that a message was received.
@@ -28,7 +28,7 @@ pub fn init_metrics() {
     }
     #[cfg(not(all(fbcode_build, target_os = "linux")))]
     {
-        if let Some(provider) = crate::otlp::otlp_meter_provider() {
+        if let Some(provider) = crate::exporters::meter_provider() {
             opentelemetry::global::set_meter_provider(provider);
         }
     }
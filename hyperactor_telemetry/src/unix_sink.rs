@@ -499,6 +499,8 @@ fn buffer_entity_event(inner: &mut UnixSocketSinkInner, event: &EntityEvent) {
                 to_actor_id: event.to_actor_id,
                 endpoint: event.endpoint.clone(),
                 port_index: event.port_index,
+                parent_message_id: event.parent_message_id,
+                root_message_id: event.root_message_id,
             });
         }
         EntityEvent::MessageStatus(event) => {
@@ -767,6 +769,8 @@ mod tests {
                 to_actor_id: 5,
                 endpoint: Some("endpoint".to_string()),
                 port_index: Some(6),
+                parent_message_id: Some(8),
+                root_message_id: Some(9),
             })),
             TraceEvent::Entity(EntityEvent::MessageStatus(MessageStatusEvent {
                 timestamp: timestamp(),
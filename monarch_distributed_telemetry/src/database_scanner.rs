@@ -1103,6 +1103,8 @@ mod tests {
             to_actor_id: 30,
             endpoint: Some("old".to_string()),
             port_index: None,
+            parent_message_id: None,
+            root_message_id: None,
         });
         messages.insert(Message {
             id: 4,
@@ -1111,6 +1113,8 @@ mod tests {
             to_actor_id: 31,
             endpoint: Some("fresh".to_string()),
             port_index: Some(4),
+            parent_message_id: Some(3),
+            root_message_id: Some(3),
         });
         ingest_batch(scanner, MESSAGES, messages.drain_to_record_batch().unwrap()).await;
 
@@ -254,6 +254,8 @@ impl EntityBatchSink {
                     to_actor_id: event.to_actor_id,
                     endpoint: event.endpoint,
                     port_index: event.port_index,
+                    parent_message_id: event.parent_message_id,
+                    root_message_id: event.root_message_id,
                 });
                 inner.flush_messages_if_full()?;
             }
@@ -211,6 +211,18 @@ pub fn mod_init(module: &Bound<'_, PyModule>) -> PyResult<()> {
         module,
         "monarch_hyperactor.runtime",
     )?)?;
+    monarch_hyperactor::codec::register_python_bindings(&get_or_add_new_module(
+        module,
+        "monarch_hyperactor.codec",
+    )?)?;
+    monarch_hyperactor::session::register_python_bindings(&get_or_add_new_module(
+        module,
+        "monarch_hyperactor.session",
+    )?)?;
+    monarch_hyperactor::stubs::register_python_bindings(&get_or_add_new_module(
+        module,
+        "monarch_hyperactor.stubs",
+    )?)?;
     monarch_hyperactor::telemetry::register_python_bindings(&get_or_add_new_module(
         module,
         "monarch_hyperactor.telemetry",
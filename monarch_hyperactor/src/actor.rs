@@ -16,9 +16,12 @@ use std::sync::Mutex;
 use std::sync::OnceLock;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering as AtomicOrdering;
+use std::time::Instant;
 use std::time::SystemTime;
 
 use async_trait::async_trait;
+use futures::FutureExt;
+use futures::future::BoxFuture;
 use hyperactor::Actor;
 use hyperactor::ActorHandle;
 use hyperactor::Context;
@@ -37,6 +40,7 @@ use hyperactor::mailbox::MessageEnvelope;
 use hyperactor::mailbox::Undeliverable;
 use hyperactor::mailbox::UndeliverableMessageError;
 use hyperactor::mailbox::UndeliverableReason;
+use hyperactor::mailbox::error_code::ErrorCode;
 use hyperactor::message::Bind;
 use hyperactor::message::Bindings;
 use hyperactor::message::IndexedErasedUnbound;
@@ -44,8 +48,9 @@ use hyperactor::message::Unbind;
 use hyperactor::supervision::ActorSupervisionEvent;
 use hyperactor_config::Flattrs;
 use hyperactor_mesh::casting::update_undeliverable_envelope_for_casting;
-use hyperactor_mesh::comm::multicast::CAST_POINT;
 use hyperactor_mesh::comm::multicast::CastInfo;
+use hyperactor_mesh::comm::multicast::cast_point_of;
+use hyperactor_mesh::comm::multicast::set_cast_point;
 use hyperactor_mesh::introspect::ActiveHandler;
 use hyperactor_mesh::introspect::EXECUTION;
 use hyperactor_mesh::introspect::Execution;
@@ -72,10 +77,15 @@ use tokio::sync::mpsc;
 use tokio::sync::oneshot;
 use typeuri::Named;
 
+use crate::actor_mesh::QueueFull;
 use crate::buffers::FrozenBuffer;
+use crate::config::ACTOR_PYTHON_EXECUTOR;
 use crate::config::ACTOR_QUEUE_DISPATCH;
+use crate::config::ACTOR_SUBPROCESS_ISOLATION;
 use crate::config::SHARED_ASYNCIO_RUNTIME;
 use crate::context::PyInstance;
+use crate::isolated_actor::IsolatedActor;
+use crate::isolated_actor::IsolatedActorSpec;
 use crate::local_state_broker::BrokerId;
 use crate::local_state_broker::LocalStateBrokerMessage;
 use crate::mailbox::EitherPortRef;
@@ -88,6 +98,8 @@ use crate::metrics::ENDPOINT_ACTOR_PANIC;
 use crate::pickle::pickle_to_part;
 use crate::proc::PyActorAddr;
 use crate::pympsc;
+use crate::python_executor::PythonExecutor;
+use crate::python_executor::TimedAwaitable;
 use crate::pytokio::PythonTask;
 use crate::runtime::get_tokio_runtime;
 use crate::runtime::monarch_with_gil;
@@ -235,6 +247,7 @@ wirevalue::submit! {
             let msg = unsafe { &*(ptr as *const PythonMessage) };
             python_message_endpoint_name(msg)
         },
+        serialize_unchecked: wirevalue::serialize_unchecked::<PythonMessage>,
     }
 }
 
@@ -262,6 +275,7 @@ wirevalue::submit! {
                 .ok()
                 .and_then(|msg| python_message_endpoint_name(&msg))
         },
+        serialize_unchecked: wirevalue::serialize_unchecked::<IndexedErasedUnbound<PythonMessage>>,
     }
 }
 
@@ -479,6 +493,25 @@ impl std::fmt::Debug for PythonMessage {
     }
 }
 
+/// The response port of a call that was returned as undeliverable, if
+/// the returned message is one. Casts are returned as they were
+/// delivered, still unbound.
+fn returned_response_port(envelope: &MessageEnvelope) -> Option<EitherPortRef> {
+    let message = if envelope.data().is::<PythonMessage>() {
+        envelope.deserialized::<PythonMessage>().ok()?
+    } else {
+        envelope
+            .deserialized::<IndexedErasedUnbound<PythonMessage>>()
+            .ok()?
+            .bound()
+            .ok()?
+    };
+    match message.kind {
+        PythonMessageKind::CallMethod { response_port, .. } => response_port,
+        _ => None,
+    }
+}
+
 impl Unbind for PythonMessage {
     fn unbind(&self, bindings: &mut Bindings) -> anyhow::Result<()> {
         match &self.kind {
@@ -831,14 +864,16 @@ mod execution_tracker_tests {
 )]
 #[hyperactor::spawnable]
 pub struct PythonActor {
-    /// The Python object that we delegate message handling to.
-    actor: Py<PyAny>,
+    /// The Python object that we delegate message handling to. Shared so
+    /// that handlers may be invoked on the [`PythonExecutor`] without
+    /// holding the GIL to clone it.
+    actor: Arc<Py<PyAny>>,
     /// Stores a reference to the Python event loop to run Python coroutines on.
     /// This is None when using single runtime mode, Some when using per-actor mode.
-    task_locals: Option<pyo3_async_runtimes::TaskLocals>,
+    task_locals: Option<Arc<pyo3_async_runtimes::TaskLocals>>,
     /// Instance object that we keep across handle calls so that we can store
     /// information from the Init (spawn rank, controller) and provide it to other calls.
-    instance: Option<Py<crate::context::PyInstance>>,
+    instance: Option<Arc<Py<crate::context::PyInstance>>>,
     /// Dispatch mode for this actor.
     dispatch_mode: PythonActorDispatchMode,
     /// The location in the actor mesh at which this actor was spawned.
@@ -860,6 +895,11 @@ pub struct PythonActor {
     /// of this `Arc` is injected into the actor's `PyInstance` so
     /// `_Actor.handle` can bracket each invocation.
     execution_tracker: Arc<ExecutionTracker>,
+
+    /// Set when this actor is a bridge to an actor run in a subprocess
+    /// (see [`crate::isolated_actor`]). The bridge has no Python object
+    /// of its own; it forwards its messages to the subprocess.
+    isolated: Option<IsolatedActor>,
 }
 
 impl PythonActor {
@@ -879,7 +919,7 @@ impl PythonActor {
 
                 // Only create per-actor TaskLocals if not using shared runtime
                 let task_locals = (!hyperactor_config::global::get(SHARED_ASYNCIO_RUNTIME))
-                    .then(|| Python::detach(py, create_task_locals));
+                    .then(|| Arc::new(Python::detach(py, create_task_locals)));
 
                 let dispatch_mode = if use_queue_dispatch {
                     let (sender, receiver) = pympsc::channel().map_err(|e| {
@@ -895,7 +935,7 @@ impl PythonActor {
                 };
 
                 Ok(Self {
-                    actor,
+                    actor: Arc::new(actor),
                     task_locals,
                     instance: None,
                     dispatch_mode,
@@ -903,16 +943,33 @@ impl PythonActor {
                     init_message,
                     mesh_base_name,
                     execution_tracker: Arc::new(ExecutionTracker::new()),
+                    isolated: None,
                 })
             },
         )?)
     }
 
+    /// Create a bridge to an actor that will run in a subprocess,
+    /// launched when the bridge is initialized.
+    fn isolated(spec: IsolatedActorSpec) -> Self {
+        Self {
+            actor: Arc::new(monarch_with_gil_blocking(|py| py.None())),
+            task_locals: None,
+            instance: None,
+            dispatch_mode: PythonActorDispatchMode::Direct,
+            spawn_point: OnceLock::new(),
+            init_message: None,
+            mesh_base_name: spec.mesh_base_name.clone(),
+            execution_tracker: Arc::new(ExecutionTracker::new()),
+            isolated: Some(IsolatedActor::Pending(spec)),
+        }
+    }
+
     /// Get the TaskLocals to use for this actor.
     /// Returns either the shared TaskLocals or this actor's own TaskLocals based on configuration.
     fn get_task_locals(&self, py: Python) -> &pyo3_async_runtimes::TaskLocals {
         self.task_locals
-            .as_ref()
+            .as_deref()
             .unwrap_or_else(|| shared_task_locals(py))
     }
 
@@ -926,14 +983,21 @@ impl PythonActor {
         py: Python<'_>,
         src: impl Into<crate::context::PyInstance>,
     ) -> Py<crate::context::PyInstance> {
-        let tracker = self.execution_tracker.clone();
-        self.instance
-            .get_or_insert_with(|| {
-                let mut inst: crate::context::PyInstance = src.into();
-                inst.set_execution_tracker(tracker);
-                inst.into_pyobject(py).unwrap().into()
-            })
-            .clone_ref(py)
+        if self.instance.is_none() {
+            let inst = self.new_py_instance(src);
+            self.instance = Some(Arc::new(inst.into_pyobject(py).unwrap().into()));
+        }
+        self.instance.as_ref().unwrap().clone_ref(py)
+    }
+
+    /// A new `PyInstance` for this actor, carrying its execution tracker.
+    fn new_py_instance(
+        &self,
+        src: impl Into<crate::context::PyInstance>,
+    ) -> crate::context::PyInstance {
+        let mut inst: crate::context::PyInstance = src.into();
+        inst.set_execution_tracker(self.execution_tracker.clone());
+        inst
     }
 
     /// Bootstrap the root client actor, creating a new proc for it.
@@ -1201,6 +1265,10 @@ pub(crate) fn root_client_actor(py: Python<'_>) -> &'static Instance<PythonActor
 #[async_trait]
 impl Actor for PythonActor {
     async fn init(&mut self, this: &Instance<Self>) -> Result<(), anyhow::Error> {
+        if let Some(isolated) = &mut self.isolated {
+            return isolated.launch(this).await;
+        }
+
         // PE-1: install the read side eagerly so the actor reports
         // `execution` from its first handled message. The callback runs on
         // the introspect task (off the actor loop) and only reads `Arc`
@@ -1220,7 +1288,7 @@ impl Actor for PythonActor {
 
                 let tl = self
                     .task_locals
-                    .as_ref()
+                    .as_deref()
                     .unwrap_or_else(|| shared_task_locals(py));
                 let awaitable = self.actor.call_method(
                     py,
@@ -1243,7 +1311,7 @@ impl Actor for PythonActor {
         if let Some(init_message) = self.init_message.take() {
             let spawn_point = self.spawn_point.get().unwrap().as_ref().expect("PythonActor should never be spawned with init_message unless spawn_point also specified").clone();
             let mut headers = Flattrs::new();
-            headers.set(CAST_POINT, spawn_point);
+            set_cast_point(&mut headers, spawn_point);
             let cx = Context::new(this, headers);
             <Self as Handler<PythonMessage>>::handle(self, &cx, init_message).await?;
         }
@@ -1256,6 +1324,11 @@ impl Actor for PythonActor {
         this: &Instance<Self>,
         err: Option<&ActorError>,
     ) -> anyhow::Result<()> {
+        if let Some(isolated) = &mut self.isolated {
+            isolated.shutdown().await;
+            return Ok(());
+        }
+
         // Calls the "__cleanup__" method on the python instance to allow the actor
         // to control its own cleanup.
         // No headers because this isn't in the context of a message.
@@ -1346,6 +1419,35 @@ impl Actor for PythonActor {
             envelope.headers()
         );
 
+        if self.isolated.is_some() {
+            // Messages forwarded to an isolated actor's subprocess are
+            // returned to its bridge, which has no Python handler.
+            return hyperactor::actor::handle_undeliverable_message(ins, reason, envelope);
+        }
+
+        // A call rejected by an actor whose queue is full fails at the
+        // caller with `QueueFull`, rather than failing this actor.
+        if envelope.error_code() == Some(ErrorCode::QuotaExceeded)
+            && let Some(mut port) = returned_response_port(&envelope)
+        {
+            let rank = cast_point_of(envelope.headers()).map_or(0, |point| point.rank());
+            let message = monarch_with_gil(|py| {
+                let err = QueueFull::destination().into_value(py).into_any();
+                pickle_to_part(py, &err)
+            })
+            .await?;
+            // The caller may have stopped waiting for the result.
+            port.set_return_undeliverable(false);
+            port.post(
+                ins,
+                PythonMessage::new_from_buf(
+                    PythonMessageKind::Exception { rank: Some(rank) },
+                    message,
+                ),
+            )?;
+            return Ok(());
+        }
+
         let cx = Context::new(ins, envelope.headers().clone());
 
         let (envelope, handled) = monarch_with_gil(|py| {
@@ -1465,7 +1567,15 @@ impl RemoteSpawn for PythonActor {
         }: PythonActorParams,
         environment: Flattrs,
     ) -> Result<Self, anyhow::Error> {
-        let spawn_point = environment.get(CAST_POINT);
+        let spawn_point = cast_point_of(&environment);
+        if hyperactor_config::global::get(ACTOR_SUBPROCESS_ISOLATION) {
+            return Ok(Self::isolated(IsolatedActorSpec {
+                actor_type,
+                init_message,
+                spawn_point,
+                mesh_base_name,
+            }));
+        }
         Self::new(actor_type, init_message, spawn_point, mesh_base_name)
     }
 }
@@ -1555,6 +1665,9 @@ impl Handler<PythonMessage> for PythonActor {
         cx: &Context<PythonActor>,
         message: PythonMessage,
     ) -> anyhow::Result<()> {
+        if let Some(isolated) = &self.isolated {
+            return isolated.forward(cx, message);
+        }
         match &self.dispatch_mode {
             PythonActorDispatchMode::Direct => self.handle_direct(cx, message).await,
             PythonActorDispatchMode::Queue { sender, .. } => {
@@ -1579,36 +1692,43 @@ impl PythonActor {
         // See [Panics in async endpoints].
         let (sender, receiver) = oneshot::channel();
 
-        let future = monarch_with_gil(|py| -> Result<_, SerializablePyErr> {
-            let inst = self.ensure_py_instance(py, cx);
+        let panic_flag = PanicFlag {
+            sender: Some(sender),
+        };
+        let future = if hyperactor_config::global::get(ACTOR_PYTHON_EXECUTOR) {
+            self.invoke_on_executor(cx, &endpoint, resolved, panic_flag)
+                .await?
+        } else {
+            monarch_with_gil(|py| -> Result<_, SerializablePyErr> {
+                let inst = self.ensure_py_instance(py, cx);
 
-            let awaitable = self.actor.call_method(
-                py,
-                "handle",
-                (
-                    crate::context::PyContext::new(cx, inst.clone_ref(py)),
-                    resolved.method,
-                    resolved.bytes,
-                    PanicFlag {
-                        sender: Some(sender),
-                    },
-                    resolved
-                        .local_state
-                        .unwrap_or_else(|| PyList::empty(py).unbind().into()),
-                    resolved.response_port.into_py_any(py)?,
-                ),
-                None,
-            )?;
+                let awaitable = self.actor.call_method(
+                    py,
+                    "handle",
+                    (
+                        crate::context::PyContext::new(cx, inst.clone_ref(py)),
+                        resolved.method,
+                        resolved.bytes,
+                        panic_flag,
+                        resolved
+                            .local_state
+                            .unwrap_or_else(|| PyList::empty(py).unbind().into()),
+                        resolved.response_port.into_py_any(py)?,
+                    ),
+                    None,
+                )?;
 
-            let tl = self
-                .task_locals
-                .as_ref()
-                .unwrap_or_else(|| shared_task_locals(py));
+                let tl = self
+                    .task_locals
+                    .as_deref()
+                    .unwrap_or_else(|| shared_task_locals(py));
 
-            pyo3_async_runtimes::into_future_with_locals(tl, awaitable.into_bound(py))
-                .map_err(|err| err.into())
-        })
-        .await?;
+                pyo3_async_runtimes::into_future_with_locals(tl, awaitable.into_bound(py))
+                    .map(FutureExt::boxed)
+                    .map_err(|err| err.into())
+            })
+            .await?
+        };
 
         // Spawn a child actor to await the Python handler method.
         tokio::spawn(handle_async_endpoint_panic(
@@ -1621,6 +1741,70 @@ impl PythonActor {
         Ok(())
     }
 
+    /// Invoke the Python handler for `resolved` on the [`PythonExecutor`],
+    /// so that the GIL is acquired on the executor's thread rather than
+    /// on this worker. Returns the future driving the handler.
+    async fn invoke_on_executor(
+        &mut self,
+        cx: &Context<'_, PythonActor>,
+        endpoint: &str,
+        resolved: ResolvedCallMethod,
+        panic_flag: PanicFlag,
+    ) -> Result<BoxFuture<'static, PyResult<Py<PyAny>>>, SerializablePyErr> {
+        let actor = self.actor.clone();
+        let task_locals = self.task_locals.clone();
+        // The executor's closure cannot borrow `self`, so a new instance
+        // is converted there and stored once the closure returns.
+        let instance = match &self.instance {
+            Some(instance) => Ok(instance.clone()),
+            None => Err(self.new_py_instance(cx)),
+        };
+        let rank = cx.cast_point();
+        let recording_span = cx.recording_span();
+        let handler = endpoint.to_string();
+
+        let (instance, future) = PythonExecutor::global()
+            .run(endpoint, move |py| -> Result<_, SerializablePyErr> {
+                let instance = instance
+                    .unwrap_or_else(|inst| Arc::new(inst.into_pyobject(py).unwrap().into()));
+
+                let invoked = Instant::now();
+                let awaitable = actor.call_method(
+                    py,
+                    "handle",
+                    (
+                        crate::context::PyContext::from_parts(
+                            instance.clone_ref(py),
+                            rank,
+                            Some(recording_span),
+                        ),
+                        resolved.method,
+                        resolved.bytes,
+                        panic_flag,
+                        resolved
+                            .local_state
+                            .unwrap_or_else(|| PyList::empty(py).unbind().into()),
+                        resolved.response_port.into_py_any(py)?,
+                    ),
+                    None,
+                )?;
+                // Time the coroutine's steps, which hold the GIL too.
+                let awaitable =
+                    TimedAwaitable::new(awaitable.bind(py), handler, invoked.elapsed())?
+                        .into_bound_py_any(py)?;
+
+                let tl = task_locals
+                    .as_deref()
+                    .unwrap_or_else(|| shared_task_locals(py));
+                let future = pyo3_async_runtimes::into_future_with_locals(tl, awaitable)?;
+                Ok((instance, future.boxed()))
+            })
+            .await?;
+
+        self.instance.get_or_insert(instance);
+        Ok(future)
+    }
+
     /// Handle a message using queue dispatch.
     /// Resolves the message on the Rust side and enqueues it for Python to process.
     async fn handle_queue(
@@ -1660,6 +1844,9 @@ impl PythonActor {
 #[async_trait]
 impl Handler<MeshFailure> for PythonActor {
     async fn handle(&mut self, cx: &Context<Self>, message: MeshFailure) -> anyhow::Result<()> {
+        if let Some(isolated) = &self.isolated {
+            return isolated.forward(cx, message);
+        }
         // If the message is not about a failure, don't call __supervise__.
         // This includes messages like "stop", because those are not errors that
         // need to be propagated.
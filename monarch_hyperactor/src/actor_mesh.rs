@@ -10,8 +10,13 @@ use std::future::Future;
 use std::ops::Deref;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::thread;
 use std::time::Duration;
+use std::time::Instant;
 
 use async_trait::async_trait;
 use futures::future;
@@ -32,12 +37,16 @@ use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::types::PyBytes;
 use pyo3::types::PyTuple;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio::sync::Semaphore;
+use tokio::sync::TryAcquireError;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::mpsc::unbounded_channel;
 
 use crate::actor::PythonActor;
 use crate::actor::PythonMessage;
 use crate::actor::PythonMessageKind;
+use crate::config::PYTHON_CAST_QUEUE_CAPACITY;
 use crate::context::PyInstance;
 use crate::pickle::PendingMessage;
 use crate::proc::PyActorAddr;
@@ -138,6 +147,26 @@ pub(crate) trait ActorMeshProtocol: Send + Sync {
         self.cast_with_headers(message, selection, instance, caller_headers)
     }
 
+    /// Wait until the mesh has room for one more cast, and return a
+    /// permit to spend on it with `cast_unresolved_with_permit`. The
+    /// default has no bound on queued casts, and is ready immediately.
+    fn acquire_send_permit(&self) -> PyResult<PyPythonTask> {
+        PyPythonTask::new(async { Ok(PySendPermit::new(None)) })
+    }
+
+    /// Cast a pending message, spending a permit obtained from
+    /// `acquire_send_permit` instead of failing when the mesh is full.
+    /// The default has no bound on queued casts, and ignores the permit.
+    fn cast_unresolved_with_permit(
+        &self,
+        message: PendingMessage,
+        selection: AllOrChoose,
+        instance: &Instance<PythonActor>,
+        _permit: Option<OwnedSemaphorePermit>,
+    ) -> PyResult<()> {
+        self.cast_unresolved(message, selection, instance)
+    }
+
     fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyAny>)>;
 
     /// Stop the actor mesh asynchronously.
@@ -219,15 +248,28 @@ impl PythonActorMesh {
     }
 
     #[hyperactor::instrument]
+    #[pyo3(signature = (message, selection, instance, permit=None))]
     pub(crate) fn cast_unresolved(
         &self,
         message: &mut PendingMessage,
         selection: &str,
         instance: &PyInstance,
+        permit: Option<PyRefMut<'_, PySendPermit>>,
     ) -> PyResult<()> {
         let sel = to_all_or_choose(selection)?;
         let message = message.take()?;
-        self.inner.cast_unresolved(message, sel, instance)
+        match permit {
+            Some(mut permit) => {
+                let permit = permit.take()?;
+                self.inner
+                    .cast_unresolved_with_permit(message, sel, instance, permit)
+            }
+            None => self.inner.cast_unresolved(message, sel, instance),
+        }
+    }
+
+    fn acquire_send_permit(&self) -> PyResult<PyPythonTask> {
+        self.inner.acquire_send_permit()
     }
 
     fn new_with_region(&self, region: &PyRegion) -> PyResult<PythonActorMesh> {
@@ -254,6 +296,266 @@ impl PythonActorMesh {
     }
 }
 
+/// Raised when a cast finds no room: either the caller's queue of
+/// pending casts to the mesh is full, or a destination actor rejected
+/// the message because its own queue exceeded its byte limit
+/// (`hyperactor::config::ACTOR_MAX_QUEUED_BYTES`).
+///
+/// `capacity` is the size of the caller's queue, and `None` when a
+/// destination rejected the message. `retry_after` estimates, in
+/// seconds, how long it will take the caller's queue to make room, and
+/// is `None` when there is nothing to estimate from.
+#[pyclass(
+    name = "QueueFull",
+    module = "monarch._rust_bindings.monarch_hyperactor.actor_mesh",
+    extends = PyRuntimeError
+)]
+#[derive(Clone, Debug)]
+pub struct QueueFull {
+    #[pyo3(get)]
+    pub capacity: Option<usize>,
+    #[pyo3(get)]
+    pub retry_after: Option<f64>,
+}
+
+#[pymethods]
+impl QueueFull {
+    #[new]
+    #[pyo3(signature = (capacity=None, retry_after=None))]
+    fn new(capacity: Option<usize>, retry_after: Option<f64>) -> Self {
+        QueueFull {
+            capacity,
+            retry_after,
+        }
+    }
+
+    fn __str__(&self) -> String {
+        match (self.capacity, self.retry_after) {
+            (None, _) => "destination actor's message queue is full".to_string(),
+            (Some(capacity), None) => {
+                format!("cast queue is full ({} pending casts)", capacity)
+            }
+            (Some(capacity), Some(retry_after)) => format!(
+                "cast queue is full ({} pending casts); retry after {:.3}s",
+                capacity, retry_after
+            ),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "QueueFull(capacity={:?}, retry_after={:?})",
+            self.capacity, self.retry_after
+        )
+    }
+}
+
+impl QueueFull {
+    /// The error for a cast that a destination actor rejected because
+    /// its queue was full.
+    pub(crate) fn destination() -> PyErr {
+        PyErr::new::<QueueFull, _>((None::<usize>, None::<f64>))
+    }
+}
+
+/// A reservation of room for one cast, obtained by awaiting
+/// `acquire_send_permit` and spent by passing it to `cast_unresolved`.
+/// Dropping or releasing an unspent permit returns the room to the mesh.
+#[pyclass(
+    name = "SendPermit",
+    module = "monarch._rust_bindings.monarch_hyperactor.actor_mesh"
+)]
+pub(crate) struct PySendPermit {
+    permit: Option<OwnedSemaphorePermit>,
+    spent: bool,
+}
+
+impl PySendPermit {
+    fn new(permit: Option<OwnedSemaphorePermit>) -> Self {
+        PySendPermit {
+            permit,
+            spent: false,
+        }
+    }
+
+    fn take(&mut self) -> PyResult<Option<OwnedSemaphorePermit>> {
+        if self.spent {
+            return Err(PyValueError::new_err("send permit has already been used"));
+        }
+        self.spent = true;
+        Ok(self.permit.take())
+    }
+}
+
+#[pymethods]
+impl PySendPermit {
+    /// Return the permit's room to the mesh without casting.
+    fn release(&mut self) {
+        self.permit = None;
+        self.spent = true;
+    }
+}
+
+/// A cast waiting for room in the queue, which it is given as a permit.
+type WaitingCast = Box<dyn FnOnce(OwnedSemaphorePermit) + Send + 'static>;
+
+/// Bounds the casts queued on an [`AsyncActorMesh`] and the meshes
+/// sliced from it, which share its queue, to
+/// [`PYTHON_CAST_QUEUE_CAPACITY`]. Each queued cast holds a permit
+/// until it has been sent.
+///
+/// Casts whose results are awaited wait, in order, for room instead of
+/// failing. While any of them waits, other casts find no room, so that
+/// no cast overtakes an earlier one.
+struct CastBudget {
+    /// `None` when the queue is unbounded.
+    permits: Option<Arc<Semaphore>>,
+    capacity: usize,
+    /// Casts waiting for room, which are queued as it frees.
+    waiting: Option<UnboundedSender<WaitingCast>>,
+    /// The number of casts waiting for room.
+    waiters: Arc<AtomicUsize>,
+    /// Moving average of the time it takes to send a queued cast, in
+    /// microseconds; 0 before any cast has been sent.
+    send_micros: AtomicU64,
+    /// When the cast being sent, if any, started.
+    sending: Mutex<Option<Instant>>,
+}
+
+impl CastBudget {
+    fn new() -> Self {
+        Self::with_capacity(hyperactor_config::global::get(PYTHON_CAST_QUEUE_CAPACITY))
+    }
+
+    /// A budget of `capacity` casts, or an unbounded one if it is 0.
+    fn with_capacity(capacity: usize) -> Self {
+        let permits = (capacity > 0).then(|| Arc::new(Semaphore::new(capacity)));
+        let waiters = Arc::new(AtomicUsize::new(0));
+        let waiting = permits.clone().map(|permits| {
+            let (waiting, mut recv) = unbounded_channel::<WaitingCast>();
+            let waiters = waiters.clone();
+            get_tokio_runtime().spawn(async move {
+                while let Some(cast) = recv.recv().await {
+                    let permit = permits
+                        .clone()
+                        .acquire_owned()
+                        .await
+                        .expect("cast budget is never closed");
+                    // Count the cast as waiting until it is queued, so
+                    // that no other cast can be queued ahead of it.
+                    cast(permit);
+                    waiters.fetch_sub(1, Ordering::AcqRel);
+                }
+            });
+            waiting
+        });
+        CastBudget {
+            permits,
+            capacity,
+            waiting,
+            waiters,
+            send_micros: AtomicU64::new(0),
+            sending: Mutex::new(None),
+        }
+    }
+
+    /// Take room for a cast without waiting, or fail with `QueueFull`.
+    fn try_acquire(&self) -> PyResult<Option<OwnedSemaphorePermit>> {
+        let Some(permits) = &self.permits else {
+            return Ok(None);
+        };
+        if self.waiters.load(Ordering::Acquire) > 0 {
+            return Err(self.full());
+        }
+        match permits.clone().try_acquire_owned() {
+            Ok(permit) => Ok(Some(permit)),
+            Err(TryAcquireError::NoPermits) => Err(self.full()),
+            Err(TryAcquireError::Closed) => unreachable!("cast budget is never closed"),
+        }
+    }
+
+    /// Queue `cast` once there is room for it, after the casts already
+    /// waiting. Only bounded budgets run out of room.
+    fn wait(&self, cast: WaitingCast) {
+        let waiting = self
+            .waiting
+            .as_ref()
+            .expect("only bounded budgets run out of room");
+        self.waiters.fetch_add(1, Ordering::AcqRel);
+        if waiting.send(cast).is_err() {
+            unreachable!("the waiting casts are received while the budget lives");
+        }
+    }
+
+    fn full(&self) -> PyErr {
+        PyErr::new::<QueueFull, _>((
+            Some(self.capacity),
+            self.retry_after()
+                .map(|retry_after| retry_after.as_secs_f64()),
+        ))
+    }
+
+    /// Check that `permit` was acquired from this budget.
+    fn check(&self, permit: &Option<OwnedSemaphorePermit>) -> PyResult<()> {
+        let matches = match (&self.permits, permit) {
+            (None, _) => true,
+            (Some(permits), Some(permit)) => Arc::ptr_eq(permits, permit.semaphore()),
+            (Some(_), None) => false,
+        };
+        if matches {
+            Ok(())
+        } else {
+            Err(PyValueError::new_err(
+                "send permit was acquired from a different mesh",
+            ))
+        }
+    }
+
+    /// The expected time until the next queued cast has been sent and
+    /// frees its room. Casts are sent one at a time, so this is the
+    /// average time to send a cast, less the time that the cast being
+    /// sent has already taken. `None` before any cast has been sent.
+    fn retry_after(&self) -> Option<Duration> {
+        let average = self.send_micros.load(Ordering::Relaxed);
+        if average == 0 {
+            return None;
+        }
+        let elapsed = self
+            .sending
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .map_or(Duration::ZERO, |started| started.elapsed());
+        Some(
+            Duration::from_micros(average)
+                .saturating_sub(elapsed)
+                .max(Duration::from_millis(1)),
+        )
+    }
+
+    /// Note that a queued cast is being sent.
+    fn start(&self) -> Instant {
+        let started = Instant::now();
+        *self.sending.lock().unwrap_or_else(|err| err.into_inner()) = Some(started);
+        started
+    }
+
+    /// Note that the cast being sent, which started at `started`, has
+    /// been sent.
+    fn finish(&self, started: Instant) {
+        *self.sending.lock().unwrap_or_else(|err| err.into_inner()) = None;
+        let sample = (started.elapsed().as_micros().min(u64::MAX as u128) as u64).max(1);
+        let _ = self
+            .send_micros
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |avg| {
+                Some(if avg == 0 {
+                    sample
+                } else {
+                    avg - avg / 8 + sample / 8
+                })
+            });
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct ClonePyErr {
     inner: PyErr,
@@ -279,9 +581,11 @@ impl Clone for ClonePyErr {
 type ActorMeshResult = Result<Arc<dyn SupervisableActorMesh>, ClonePyErr>;
 type ActorMeshFut = Shared<Pin<Box<dyn Future<Output = ActorMeshResult> + Send + 'static>>>;
 
+#[derive(Clone)]
 pub(crate) struct AsyncActorMesh {
     mesh: ActorMeshFut,
     queue: UnboundedSender<Pin<Box<dyn Future<Output = ()> + Send + 'static>>>,
+    budget: Arc<CastBudget>,
     supervised: bool,
 }
 
@@ -300,7 +604,7 @@ impl AsyncActorMesh {
             }
         });
 
-        let mesh = AsyncActorMesh::new(queue, supervised, f);
+        let mesh = AsyncActorMesh::new(queue, Arc::new(CastBudget::new()), supervised, f);
         // Eagerly trigger the mesh initialization by pushing an init task onto
         // the queue. This ensures actors are spawned immediately rather than
         // waiting for the first endpoint call, which is critical for:
@@ -317,12 +621,14 @@ impl AsyncActorMesh {
 
     fn new(
         queue: UnboundedSender<Pin<Box<dyn Future<Output = ()> + Send + 'static>>>,
+        budget: Arc<CastBudget>,
         supervised: bool,
         f: ActorMeshFut,
     ) -> AsyncActorMesh {
         AsyncActorMesh {
             mesh: f,
             queue,
+            budget,
             supervised,
         }
     }
@@ -334,60 +640,33 @@ impl AsyncActorMesh {
         self.queue.send(f.boxed()).unwrap();
     }
 
-    pub(crate) fn from_impl(mesh: Arc<dyn SupervisableActorMesh>) -> Self {
-        let fut = future::ready(Ok::<Arc<dyn SupervisableActorMesh>, ClonePyErr>(mesh))
-            .boxed()
-            .shared();
-        // Poll the future so that its result can be observed without blocking the tokio runtime.
-        let _ = futures::executor::block_on(fut.clone());
-        Self::new_queue(fut, true)
-    }
-}
-
-impl ActorMeshProtocol for AsyncActorMesh {
-    fn cast(
-        &self,
-        _message: PythonMessage,
-        _selection: AllOrChoose,
-        _instance: &Instance<PythonActor>,
-    ) -> PyResult<()> {
-        panic!("not implemented")
-    }
-
-    fn cast_unresolved(
-        &self,
-        message: PendingMessage,
-        selection: AllOrChoose,
-        instance: &Instance<PythonActor>,
-    ) -> PyResult<()> {
-        self.cast_unresolved_with_headers(
-            message,
-            selection,
-            instance,
-            hyperactor_config::Flattrs::new(),
-        )
-    }
-
-    fn cast_unresolved_with_headers(
+    /// Queue a cast, resolving the pending message asynchronously.
+    /// `permit` is held until the cast has been sent.
+    fn enqueue_cast(
         &self,
         message: PendingMessage,
         selection: AllOrChoose,
         instance: &Instance<PythonActor>,
         caller_headers: hyperactor_config::Flattrs,
-    ) -> PyResult<()> {
+        permit: Option<OwnedSemaphorePermit>,
+    ) {
         let mesh = self.mesh.clone();
+        let budget = self.budget.clone();
         let instance = instance.clone_for_py();
         let port = match &message.kind {
             PythonMessageKind::CallMethod { response_port, .. } => response_port.clone(),
             _ => None,
         };
         self.push(async move {
+            let started = budget.start();
             let result = async {
                 let resolved = message.resolve().await?;
                 mesh.await?
                     .cast_with_headers(resolved, selection, &instance, caller_headers)
             }
             .await;
+            drop(permit);
+            budget.finish(started);
             if let (Some(mut port_ref), Err(pyerr)) = (port, result) {
                 let _ = monarch_with_gil(|py: Python<'_>| {
                     let exception_str = crate::logging::format_traceback(py, &pyerr);
@@ -433,6 +712,104 @@ impl ActorMeshProtocol for AsyncActorMesh {
                 .await;
             }
         });
+    }
+
+    pub(crate) fn from_impl(mesh: Arc<dyn SupervisableActorMesh>) -> Self {
+        let fut = future::ready(Ok::<Arc<dyn SupervisableActorMesh>, ClonePyErr>(mesh))
+            .boxed()
+            .shared();
+        // Poll the future so that its result can be observed without blocking the tokio runtime.
+        let _ = futures::executor::block_on(fut.clone());
+        Self::new_queue(fut, true)
+    }
+}
+
+impl ActorMeshProtocol for AsyncActorMesh {
+    fn cast(
+        &self,
+        _message: PythonMessage,
+        _selection: AllOrChoose,
+        _instance: &Instance<PythonActor>,
+    ) -> PyResult<()> {
+        panic!("not implemented")
+    }
+
+    fn cast_unresolved(
+        &self,
+        message: PendingMessage,
+        selection: AllOrChoose,
+        instance: &Instance<PythonActor>,
+    ) -> PyResult<()> {
+        self.cast_unresolved_with_headers(
+            message,
+            selection,
+            instance,
+            hyperactor_config::Flattrs::new(),
+        )
+    }
+
+    fn cast_unresolved_with_headers(
+        &self,
+        message: PendingMessage,
+        selection: AllOrChoose,
+        instance: &Instance<PythonActor>,
+        caller_headers: hyperactor_config::Flattrs,
+    ) -> PyResult<()> {
+        let awaited = matches!(
+            &message.kind,
+            PythonMessageKind::CallMethod {
+                response_port: Some(_),
+                ..
+            }
+        );
+        match self.budget.try_acquire() {
+            Ok(permit) => {
+                self.enqueue_cast(message, selection, instance, caller_headers, permit);
+            }
+            // The caller awaits the result, so it waits for room.
+            Err(_) if awaited => {
+                let mesh = self.clone();
+                let instance = instance.clone_for_py();
+                self.budget.wait(Box::new(move |permit| {
+                    mesh.enqueue_cast(message, selection, &instance, caller_headers, Some(permit))
+                }));
+            }
+            Err(err) => return Err(err),
+        }
+        Ok(())
+    }
+
+    fn acquire_send_permit(&self) -> PyResult<PyPythonTask> {
+        let permits = self.budget.permits.clone();
+        PyPythonTask::new(async move {
+            let permit = match permits {
+                Some(permits) => Some(
+                    permits
+                        .acquire_owned()
+                        .await
+                        .expect("cast budget is never closed"),
+                ),
+                None => None,
+            };
+            Ok(PySendPermit::new(permit))
+        })
+    }
+
+    fn cast_unresolved_with_permit(
+        &self,
+        message: PendingMessage,
+        selection: AllOrChoose,
+        instance: &Instance<PythonActor>,
+        permit: Option<OwnedSemaphorePermit>,
+    ) -> PyResult<()> {
+        self.budget.check(&permit)?;
+        self.enqueue_cast(
+            message,
+            selection,
+            instance,
+            hyperactor_config::Flattrs::new(),
+            permit,
+        );
         Ok(())
     }
 
@@ -507,6 +884,7 @@ impl SupervisableActorMesh for AsyncActorMesh {
         let region = region.clone();
         Ok(Box::new(AsyncActorMesh::new(
             self.queue.clone(),
+            self.budget.clone(),
             self.supervised,
             async move { Ok(Arc::from(mesh.await?.new_with_region(&region)?)) }
                 .boxed()
@@ -828,6 +1206,8 @@ pub fn register_python_bindings(hyperactor_mod: &Bound<'_, PyModule>) -> PyResul
     hyperactor_mod.add_class::<PythonActorMesh>()?;
     hyperactor_mod.add_class::<PythonActorMeshImpl>()?;
     hyperactor_mod.add_class::<PyActorSupervisionEvent>()?;
+    hyperactor_mod.add_class::<PySendPermit>()?;
+    hyperactor_mod.add("QueueFull", hyperactor_mod.py().get_type::<QueueFull>())?;
     Ok(())
 }
 
@@ -933,6 +1313,50 @@ mod tests {
         INSTANCE.get_or_init(init_test_instance)
     }
 
+    #[tokio::test]
+    async fn test_cast_budget() {
+        crate::pytokio::ensure_python();
+
+        let budget = CastBudget::with_capacity(1);
+        let permit = budget.try_acquire().unwrap();
+        assert!(budget.retry_after().is_none());
+        let err = budget.try_acquire().unwrap_err();
+        Python::attach(|py| {
+            let full = err.value(py).downcast::<QueueFull>().unwrap().borrow();
+            assert_eq!(full.capacity, Some(1));
+            assert_eq!(full.retry_after, None);
+        });
+
+        // Waiting casts are given room in order.
+        let (order_tx, mut order_rx) = mpsc::unbounded_channel();
+        for i in 0..2 {
+            let order_tx = order_tx.clone();
+            budget.wait(Box::new(move |permit| {
+                order_tx.send((i, permit)).unwrap();
+            }));
+        }
+        let started = budget.start();
+        drop(permit);
+        budget.finish(started);
+        let (first, permit) = order_rx.recv().await.unwrap();
+        assert_eq!(first, 0);
+        // A cast that waits holds off the others.
+        assert!(budget.try_acquire().is_err());
+        assert!(budget.retry_after().is_some());
+        drop(permit);
+        let (second, permit) = order_rx.recv().await.unwrap();
+        assert_eq!(second, 1);
+        drop(permit);
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while budget.try_acquire().is_err() {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
     /// Verify that calling `supervision_event` repeatedly through a
     /// [`PythonActorMesh`] does not increase the subscriber count on the
     /// controller.  This guards against a regression where each call
@@ -29,6 +29,7 @@ use pyo3::types::PyModuleMethods;
 use pyo3::wrap_pyfunction;
 
 use crate::host_mesh::PyHostMesh;
+use crate::isolated_actor::IsolatedActorBootstrap;
 use crate::pytokio::PyPythonTask;
 use crate::runtime::monarch_with_gil;
 
@@ -47,22 +48,41 @@ pub fn bootstrap_main(py: Python) -> PyResult<Bound<PyAny>> {
         // - This is the entry point of this program, so this will be dropped when
         // no more FB C++ code is running.
         let _destroy_guard = unsafe { fbinit::DestroyGuard::new() };
-        bootstrap()
-            .await
-            .map_err(|e| PyRuntimeError::new_err(format!("{:?}", e)))
+        let isolated = IsolatedActorBootstrap::get_from_env()
+            .map_err(|e| PyRuntimeError::new_err(format!("{:?}", e)))?;
+        match isolated {
+            Some(isolated) => isolated.run().await,
+            None => bootstrap().await,
+        }
+        .map_err(|e| PyRuntimeError::new_err(format!("{:?}", e)))
     })
 }
 
 #[pyfunction]
 pub fn run_worker_loop_forever(_py: Python<'_>, address: &str) -> PyResult<PyPythonTask> {
     let (addr, listener) = ChannelAddr::from_zmq_url_with_listener(address)?;
+    let command = Some(python_bootstrap_command()?);
+
+    PyPythonTask::new(async move {
+        let (_agent_handle, shutdown) = host(addr, command, None, true, listener, Gateway::new())
+            .await
+            .map_pyerr()?;
+        shutdown.join().await;
+        halt::<()>().await;
+        Ok(())
+    })
+}
 
+/// The command that runs the Python bootstrap entry point
+/// (`monarch._src.actor.bootstrap_main`) with the current process's
+/// interpreter and environment.
+pub(crate) fn python_bootstrap_command() -> PyResult<BootstrapCommand> {
     // Check if we're running in a PAR/XAR build by looking for FB_XAR_INVOKED_NAME environment variable
     let invoked_name = std::env::var("FB_XAR_INVOKED_NAME");
 
     let mut env: std::collections::HashMap<String, String> = std::env::vars().collect();
 
-    let command = Some(if let Ok(invoked_name) = invoked_name {
+    Ok(if let Ok(invoked_name) = invoked_name {
         // For PAR/XAR builds: use argv[0] from Python's sys.argv as the current executable
         let current_exe = std::path::PathBuf::from(&invoked_name);
 
@@ -101,15 +121,6 @@ pub fn run_worker_loop_forever(_py: Python<'_>, address: &str) -> PyResult<PyPyt
             ],
             env,
         }
-    });
-
-    PyPythonTask::new(async move {
-        let (_agent_handle, shutdown) = host(addr, command, None, true, listener, Gateway::new())
-            .await
-            .map_pyerr()?;
-        shutdown.join().await;
-        halt::<()>().await;
-        Ok(())
     })
 }
 
@@ -18,6 +18,7 @@ use hyperactor_config::CONFIG;
 use hyperactor_config::ConfigAttr;
 use hyperactor_config::attrs::declare_attrs;
 use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
 use pyo3::types::PyBytes;
 use pyo3::types::PyBytesMethods;
@@ -34,6 +35,16 @@ declare_attrs! {
         Some("small_write_threshold".to_string()),
     ))
     pub attr SMALL_WRITE_THRESHOLD: usize = 256;
+
+    /// Whether messages pickled into a buffer reference the memory of
+    /// the host tensors they carry, through `Buffer::write_view`,
+    /// instead of copying it. Such tensors must not be modified until
+    /// their messages have been delivered.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("MONARCH_HYPERACTOR_PICKLE_TENSOR_VIEWS".to_string()),
+        Some("pickle_tensor_views".to_string()),
+    ))
+    pub attr PICKLE_TENSOR_VIEWS: bool = false;
 }
 
 /// Wrapper that keeps Py<PyBytes> alive while allowing zero-copy access to its memory
@@ -70,6 +81,182 @@ unsafe impl Send for KeepPyBytesAlive {}
 // SAFETY: Py<PyBytes> is Send/Sync for immutable bytes
 unsafe impl Sync for KeepPyBytesAlive {}
 
+// DLPack ABI (https://dmlc.github.io/dlpack/latest/c_api.html), limited
+// to what is needed to view host tensors.
+
+/// `kDLCPU`
+const DL_CPU: i32 = 1;
+/// `kDLCUDAHost`: pinned host memory, addressable from the CPU.
+const DL_CUDA_HOST: i32 = 3;
+
+#[repr(C)]
+#[expect(dead_code, reason = "fields are part of the DLPack ABI")]
+struct DLDevice {
+    device_type: i32,
+    device_id: i32,
+}
+
+#[repr(C)]
+#[expect(dead_code, reason = "fields are part of the DLPack ABI")]
+struct DLDataType {
+    code: u8,
+    bits: u8,
+    lanes: u16,
+}
+
+#[repr(C)]
+struct DLTensor {
+    data: *mut c_void,
+    device: DLDevice,
+    ndim: i32,
+    dtype: DLDataType,
+    shape: *mut i64,
+    strides: *mut i64,
+    byte_offset: u64,
+}
+
+#[repr(C)]
+#[expect(dead_code, reason = "fields are part of the DLPack ABI")]
+struct DLManagedTensor {
+    dl_tensor: DLTensor,
+    manager_ctx: *mut c_void,
+    deleter: Option<unsafe extern "C" fn(*mut DLManagedTensor)>,
+}
+
+/// Host memory exported through DLPack, kept alive by the exporting
+/// capsule: the capsule releases the tensor when it is destroyed.
+struct KeepDlpackAlive {
+    _capsule: Py<PyAny>,
+    ptr: *const u8,
+    len: usize,
+}
+
+impl AsRef<[u8]> for KeepDlpackAlive {
+    fn as_ref(&self) -> &[u8] {
+        // SAFETY: ptr is valid for len bytes as long as the capsule, and
+        // thus the exported tensor, is alive.
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+// SAFETY: the exported memory is only read, and the capsule is only
+// released through Py's GIL-aware drop.
+unsafe impl Send for KeepDlpackAlive {}
+// SAFETY: the exported memory is only read.
+unsafe impl Sync for KeepDlpackAlive {}
+
+/// Whether `strides` (in elements) describe a compact row-major layout
+/// of `shape`.
+fn is_row_major(shape: &[i64], strides: &[i64]) -> bool {
+    let mut expected = 1;
+    for (&size, &stride) in shape.iter().zip(strides).rev() {
+        if size != 1 && stride != expected {
+            return false;
+        }
+        expected *= size;
+    }
+    true
+}
+
+/// Copy the elements of a strided tensor at `data` into a compact
+/// row-major buffer.
+///
+/// # Safety
+/// `data`, `shape` and `strides` (in elements of `itemsize` bytes) must
+/// describe valid memory.
+unsafe fn copy_strided(
+    data: *const u8,
+    shape: &[i64],
+    strides: &[i64],
+    itemsize: usize,
+) -> Vec<u8> {
+    let numel: i64 = shape.iter().product();
+    let mut out = Vec::with_capacity(numel as usize * itemsize);
+    if numel == 0 {
+        return out;
+    }
+    let mut index = vec![0i64; shape.len()];
+    loop {
+        let offset: i64 = index.iter().zip(strides).map(|(i, s)| i * s).sum();
+        // SAFETY: the index is within the tensor's bounds.
+        let item = unsafe {
+            std::slice::from_raw_parts(data.offset(offset as isize * itemsize as isize), itemsize)
+        };
+        out.extend_from_slice(item);
+        // Advance the index in row-major order.
+        let mut dim = shape.len();
+        loop {
+            if dim == 0 {
+                return out;
+            }
+            dim -= 1;
+            index[dim] += 1;
+            if index[dim] < shape[dim] {
+                break;
+            }
+            index[dim] = 0;
+        }
+    }
+}
+
+/// The contents of `obj`, exported through its `__dlpack__` method,
+/// in row-major order. Compact host tensors are viewed in place; other
+/// host tensors are copied.
+fn dlpack_bytes(obj: &Bound<'_, PyAny>) -> PyResult<Bytes> {
+    let py = obj.py();
+    let capsule = obj.call_method0("__dlpack__")?;
+    // SAFETY: PyCapsule_GetPointer checks the capsule's name, and fails
+    // with an exception set if it is not a (unconsumed) DLPack capsule.
+    let managed = unsafe {
+        pyo3::ffi::PyCapsule_GetPointer(capsule.as_ptr(), c"dltensor".as_ptr())
+            as *const DLManagedTensor
+    };
+    if managed.is_null() {
+        return Err(PyErr::fetch(py));
+    }
+    // SAFETY: the capsule holds a valid DLManagedTensor until it is
+    // destroyed, and we hold the capsule.
+    let tensor = unsafe { &(*managed).dl_tensor };
+    if tensor.device.device_type != DL_CPU && tensor.device.device_type != DL_CUDA_HOST {
+        return Err(PyValueError::new_err(format!(
+            "cannot view a tensor on DLPack device type {}; move it to the CPU first",
+            tensor.device.device_type
+        )));
+    }
+    let ndim = tensor.ndim as usize;
+    let shape = if ndim == 0 {
+        &[][..]
+    } else {
+        // SAFETY: shape has ndim entries.
+        unsafe { std::slice::from_raw_parts(tensor.shape, ndim) }
+    };
+    let itemsize = (tensor.dtype.bits as usize * tensor.dtype.lanes as usize).div_ceil(8);
+    let len = shape.iter().product::<i64>() as usize * itemsize;
+    if len == 0 {
+        return Ok(Bytes::new());
+    }
+    // SAFETY: byte_offset is within the tensor's allocation.
+    let ptr = unsafe { (tensor.data as *const u8).add(tensor.byte_offset as usize) };
+    // A null strides pointer denotes a compact row-major tensor.
+    let strides = (!tensor.strides.is_null()).then(|| {
+        // SAFETY: strides, when present, has ndim entries.
+        unsafe { std::slice::from_raw_parts(tensor.strides, ndim) }
+    });
+    match strides {
+        Some(strides) if !is_row_major(shape, strides) => {
+            // SAFETY: the tensor describes valid host memory.
+            Ok(Bytes::from(unsafe {
+                copy_strided(ptr, shape, strides, itemsize)
+            }))
+        }
+        _ => Ok(Bytes::from_owner(KeepDlpackAlive {
+            _capsule: capsule.unbind(),
+            ptr,
+            len,
+        })),
+    }
+}
+
 /// A fragment of data in the buffer, either a copy or a reference.
 #[derive(Clone)]
 enum Fragment {
@@ -77,6 +264,8 @@ enum Fragment {
     Copy(Bytes),
     /// Large writes stored as references to Python bytes
     Reference(Py<PyBytes>),
+    /// Tensor contents, viewed in place where possible
+    View(Bytes),
 }
 
 /// A mutable buffer for reading and writing bytes data.
@@ -109,6 +298,8 @@ pub struct Buffer {
     /// Threshold below which writes are copied into a contiguous buffer.
     /// Writes >= this size are stored as zero-copy references.
     threshold: usize,
+    /// Whether pickling into the buffer writes host tensors as views.
+    tensor_views: bool,
 }
 
 #[pymethods]
@@ -123,6 +314,7 @@ impl Buffer {
             fragments: Vec::new(),
             pending: BytesMut::new(),
             threshold: hyperactor_config::global::get(SMALL_WRITE_THRESHOLD),
+            tensor_views: hyperactor_config::global::get(PICKLE_TENSOR_VIEWS),
         }
     }
 
@@ -149,6 +341,35 @@ impl Buffer {
         bytes_written
     }
 
+    /// Writes the contents of a tensor or array to the buffer, in
+    /// row-major order.
+    ///
+    /// `tensor` must support DLPack (`__dlpack__`), as torch tensors and
+    /// numpy arrays do, and must be in host memory. Contiguous tensors
+    /// are stored as zero-copy views: messages built from the buffer
+    /// reference the tensor's memory, which is copied only if the
+    /// message is sent to another process. The tensor must therefore not
+    /// be modified until the message has been delivered. Non-contiguous
+    /// tensors are copied.
+    ///
+    /// # Returns
+    /// The number of bytes written
+    fn write_view(&mut self, tensor: &Bound<'_, PyAny>) -> PyResult<usize> {
+        let bytes = dlpack_bytes(tensor)?;
+        let bytes_written = bytes.len();
+        self.flush_pending();
+        self.fragments.push(Fragment::View(bytes));
+        Ok(bytes_written)
+    }
+
+    /// Whether objects pickled into the buffer write the contents of
+    /// host tensors with [`Buffer::write_view`], as configured by
+    /// `PICKLE_TENSOR_VIEWS` when the buffer was created.
+    #[getter]
+    fn tensor_views(&self) -> bool {
+        self.tensor_views
+    }
+
     /// Returns the total number of bytes in the buffer.
     ///
     /// This sums the lengths of all fragments (both copied and zero-copy) plus pending bytes.
@@ -160,7 +381,7 @@ impl Buffer {
             self.fragments
                 .iter()
                 .map(|frag| match frag {
-                    Fragment::Copy(bytes) => bytes.len(),
+                    Fragment::Copy(bytes) | Fragment::View(bytes) => bytes.len(),
                     Fragment::Reference(py_bytes) => py_bytes.as_bytes(py).len(),
                 })
                 .sum()
@@ -190,6 +411,7 @@ impl Default for Buffer {
             fragments: Vec::new(),
             pending: BytesMut::new(),
             threshold: hyperactor_config::global::get(SMALL_WRITE_THRESHOLD),
+            tensor_views: hyperactor_config::global::get(PICKLE_TENSOR_VIEWS),
         }
     }
 }
@@ -214,7 +436,7 @@ impl Buffer {
             fragments
                 .into_iter()
                 .map(|frag| match frag {
-                    Fragment::Copy(bytes) => bytes,
+                    Fragment::Copy(bytes) | Fragment::View(bytes) => bytes,
                     Fragment::Reference(py_bytes) => {
                         let wrapper = KeepPyBytesAlive::new(py_bytes);
                         bytes::Bytes::from_owner(wrapper)
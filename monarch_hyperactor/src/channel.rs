@@ -11,6 +11,7 @@ use std::str::FromStr;
 use hyperactor::channel::BindSpec;
 use hyperactor::channel::ChannelAddr;
 use hyperactor::channel::ChannelTransport;
+use hyperactor::channel::DnsAddr;
 use hyperactor::channel::TcpMode;
 use hyperactor::channel::TlsAddr;
 use hyperactor::channel::TlsMode;
@@ -204,6 +205,9 @@ impl PyChannelAddr {
     pub fn get_port(&self) -> PyResult<u16> {
         match &self.inner {
             ChannelAddr::Tcp(socket_addr) => Ok(socket_addr.port()),
+            ChannelAddr::Dns(DnsAddr {
+                port: Some(port), ..
+            }) => Ok(*port),
             ChannelAddr::MetaTls(TlsAddr { port, .. })
             | ChannelAddr::Tls(TlsAddr { port, .. })
             | ChannelAddr::Quic(TlsAddr { port, .. })
@@ -22,8 +22,12 @@ use pyo3::prelude::*;
 use serde::Deserialize;
 use serde::Serialize;
 use typeuri::Named;
+use wirevalue::schema::Schema;
 
 use crate::runtime::monarch_with_gil_blocking;
+use crate::stubs::PyActorProtocol;
+use crate::stubs::PyMessageStub;
+use crate::stubs::PyReplyStub;
 
 /// Message to trigger module reloading
 #[derive(Debug, Clone, Named, Serialize, Deserialize)]
@@ -32,6 +36,24 @@ pub struct AutoReloadMessage {
 }
 wirevalue::register_type!(AutoReloadMessage);
 
+inventory::submit! {
+    PyActorProtocol {
+        actor: "AutoReloadActor",
+        module: "monarch_hyperactor.code_sync",
+        doc: "Reloads the Python modules changed by code sync.",
+        messages: &[PyMessageStub {
+            schema: Schema::of::<AutoReloadMessage>,
+            method: "reload",
+            doc: "Reload the modules that changed since the last reload.",
+            field_types: &[],
+            reply: Some(PyReplyStub {
+                port: "result",
+                ty: "None",
+            }),
+        }],
+    }
+}
+
 /// Parameters for creating an AutoReloadActor
 #[derive(Debug, Clone, Named, Serialize, Deserialize)]
 pub struct AutoReloadParams {}
@@ -7,6 +7,7 @@
  */
 
 use std::collections::HashMap;
+use std::path::Path;
 use std::path::PathBuf;
 
 use anyhow::Result;
@@ -21,6 +22,7 @@ use hyperactor::Endpoint as _;
 use hyperactor::Handler;
 use hyperactor::Instance;
 use hyperactor::Unbind;
+use hyperactor::blob_cache::BlobCache;
 use hyperactor::context::Mailbox;
 use hyperactor_mesh::ActorMeshRef;
 use hyperactor_mesh::connect::Connect;
@@ -30,6 +32,7 @@ use lazy_errors::OrStash;
 use lazy_errors::StashedResult;
 use lazy_errors::TryCollectOrStash;
 use monarch_conda::sync::Action;
+use monarch_conda::sync::Receive;
 use monarch_conda::sync::receiver;
 use monarch_conda::sync::sender;
 use ndslice::view::Ranked;
@@ -41,6 +44,10 @@ use typeuri::Named;
 
 use crate::code_sync::WorkspaceLocation;
 
+/// Received files of at least this size are deduplicated against the
+/// host's blob cache.
+const DEDUPE_MIN_BYTES: u64 = 1 << 20;
+
 /// Represents the result of an conda sync operation with details about what was transferred
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Named)]
 pub struct CondaSyncResult {
@@ -105,9 +112,10 @@ impl Handler<CondaSyncMessage> for CondaSyncActor {
             let mut buf = vec![];
             read.read_to_end(&mut buf).await?;
 
-            anyhow::Ok(CondaSyncResult {
-                changes: changes_result?,
-            })
+            let changes = changes_result?;
+            dedupe_received_files(&workspace, &changes).await;
+
+            anyhow::Ok(CondaSyncResult { changes })
         }
         .await;
         result.post(cx, res.map_err(|e| format!("{:#?}", e)));
@@ -115,6 +123,33 @@ impl Handler<CondaSyncMessage> for CondaSyncActor {
     }
 }
 
+/// Deduplicate large received files against the host's blob cache, so
+/// that procs on the same host syncing the same environment share
+/// storage where the filesystem supports reflinks. Failures are
+/// logged, and leave the files as received.
+async fn dedupe_received_files(workspace: &Path, changes: &HashMap<PathBuf, Action>) {
+    let cache = match BlobCache::host() {
+        Ok(cache) => cache,
+        Err(err) => {
+            tracing::warn!("blob cache unavailable, not deduplicating files: {}", err);
+            return;
+        }
+    };
+    for (path, action) in changes {
+        if !matches!(action, Action::Receive(_, Receive::File { .. })) {
+            continue;
+        }
+        let path = workspace.join(path);
+        match tokio::fs::metadata(&path).await {
+            Ok(metadata) if metadata.len() >= DEDUPE_MIN_BYTES => {}
+            _ => continue,
+        }
+        if let Err(err) = cache.dedupe(&path).await {
+            tracing::warn!("failed to deduplicate {}: {}", path.display(), err);
+        }
+    }
+}
+
 pub async fn conda_sync_mesh(
     instance: &Instance<()>,
     actor_mesh: &ActorMeshRef<CondaSyncActor>,
@@ -0,0 +1,278 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! A payload codec for arbitrary Python objects.
+//!
+//! A [`PyPayload`] carries a cloudpickled Python object as a registered
+//! wire type, so that Rust messages exchanged between Python actors can
+//! carry arbitrary Python values without a serde mirror of each value's
+//! type. Each payload is tagged with the qualified name of its
+//! object's type, which receivers can inspect without unpickling, and
+//! which is checked again when the payload is decoded.
+//!
+//! Payloads are bounded by [`PY_PAYLOAD_MAX_BYTES`], both when they
+//! are encoded and when they are received; a received payload is
+//! rejected before its bytes are allocated.
+//!
+//! From Python, payloads are constructed from objects, and are
+//! themselves picklable, so that they can be sent through Python
+//! messages as well as Rust ones.
+//!
+//! [`PY_PAYLOAD_MAX_BYTES`]: crate::config::PY_PAYLOAD_MAX_BYTES
+
+use pyo3::exceptions::PyTypeError;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use pyo3::types::PyType;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::de::Error as _;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use typeuri::Named;
+
+use crate::config::PY_PAYLOAD_MAX_BYTES;
+
+/// A cloudpickled Python object, tagged with its type; see the
+/// [module documentation](self).
+#[derive(Debug, Clone, Serialize, Deserialize, Named, PartialEq)]
+#[pyclass(
+    name = "Payload",
+    module = "monarch._rust_bindings.monarch_hyperactor.codec",
+    frozen
+)]
+pub struct PyPayload {
+    /// The qualified name of the object's type, e.g. `builtins.dict`.
+    type_tag: String,
+    #[serde(deserialize_with = "deserialize_bounded")]
+    bytes: Vec<u8>,
+}
+wirevalue::register_type!(PyPayload);
+
+/// The qualified name of the type of `obj`.
+fn type_tag(obj: &Bound<'_, PyAny>) -> PyResult<String> {
+    let ty = obj.get_type();
+    Ok(format!("{}.{}", ty.module()?, ty.qualname()?))
+}
+
+fn check_size(len: usize) -> Result<(), String> {
+    let max = hyperactor_config::global::get(PY_PAYLOAD_MAX_BYTES);
+    if len > max {
+        return Err(format!(
+            "payload of {} bytes exceeds the limit of {} bytes",
+            len, max
+        ));
+    }
+    Ok(())
+}
+
+/// Deserialize the pickled bytes of a payload, rejecting them before
+/// they are allocated if they exceed [`PY_PAYLOAD_MAX_BYTES`].
+fn deserialize_bounded<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    struct Bounded;
+
+    impl<'de> Visitor<'de> for Bounded {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("pickled bytes")
+        }
+
+        fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Vec<u8>, E> {
+            check_size(bytes.len()).map_err(E::custom)?;
+            Ok(bytes.to_vec())
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+            let hint = seq.size_hint().unwrap_or(0);
+            check_size(hint).map_err(A::Error::custom)?;
+            let mut bytes = Vec::with_capacity(hint);
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+                check_size(bytes.len()).map_err(A::Error::custom)?;
+            }
+            Ok(bytes)
+        }
+    }
+
+    // `Vec<u8>` serializes as a sequence.
+    deserializer.deserialize_seq(Bounded)
+}
+
+impl PyPayload {
+    /// Cloudpickle `obj` into a payload.
+    pub fn encode(obj: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let pickled = obj
+            .py()
+            .import("cloudpickle")?
+            .call_method1("dumps", (obj,))?
+            .downcast_into::<PyBytes>()?;
+        check_size(pickled.as_bytes().len()).map_err(PyValueError::new_err)?;
+        Ok(Self {
+            type_tag: type_tag(obj)?,
+            bytes: pickled.as_bytes().to_vec(),
+        })
+    }
+
+    /// Unpickle the payload, checking that the result has the type the
+    /// payload was tagged with.
+    pub fn decode<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let obj = py
+            .import("cloudpickle")?
+            .call_method1("loads", (self.bytes.as_slice(),))?;
+        let tag = type_tag(&obj)?;
+        if tag != self.type_tag {
+            return Err(PyTypeError::new_err(format!(
+                "payload tagged {} decoded to {}",
+                self.type_tag, tag
+            )));
+        }
+        Ok(obj)
+    }
+
+    /// The qualified name of the payload's type.
+    pub fn type_tag(&self) -> &str {
+        &self.type_tag
+    }
+
+    /// The size of the pickled object, in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Whether the pickled object is empty.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Serialize the payload as a wire value.
+    pub fn to_any(&self) -> anyhow::Result<wirevalue::Any> {
+        wirevalue::Any::serialize(self)
+    }
+
+    /// Deserialize a payload from a wire value, rejecting payloads that
+    /// exceed the size limit.
+    pub fn from_any(any: &wirevalue::Any) -> anyhow::Result<Self> {
+        any.deserialized()
+    }
+}
+
+#[pymethods]
+impl PyPayload {
+    #[new]
+    fn py_new(obj: &Bound<'_, PyAny>) -> PyResult<Self> {
+        Self::encode(obj)
+    }
+
+    #[getter(type_tag)]
+    fn py_type_tag(&self) -> &str {
+        &self.type_tag
+    }
+
+    fn __len__(&self) -> usize {
+        self.len()
+    }
+
+    #[pyo3(name = "decode")]
+    fn py_decode<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        self.decode(py)
+    }
+
+    /// Decode the payload, checking that the result is an instance of
+    /// `expected`.
+    fn decode_as<'py>(
+        &self,
+        py: Python<'py>,
+        expected: &Bound<'py, PyType>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let obj = self.decode(py)?;
+        if !obj.is_instance(expected)? {
+            return Err(PyTypeError::new_err(format!(
+                "expected a payload of type {}, got {}",
+                expected.qualname()?,
+                self.type_tag
+            )));
+        }
+        Ok(obj)
+    }
+
+    /// The payload in its wire encoding.
+    fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let bytes = bincode::serde::encode_to_vec(self, bincode::config::legacy())
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(PyBytes::new(py, &bytes))
+    }
+
+    /// Decode a payload from its wire encoding, rejecting payloads that
+    /// exceed the size limit.
+    #[staticmethod]
+    fn from_bytes(data: &Bound<'_, PyBytes>) -> PyResult<Self> {
+        bincode::serde::decode_from_slice(data.as_bytes(), bincode::config::legacy())
+            .map(|(payload, _)| payload)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    fn __reduce__<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> PyResult<(Bound<'py, PyAny>, (Bound<'py, PyBytes>,))> {
+        let from_bytes = py.get_type::<Self>().getattr("from_bytes")?;
+        Ok((from_bytes, (self.to_bytes(py)?,)))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Payload({}, {} bytes)", self.type_tag, self.bytes.len())
+    }
+}
+
+pub fn register_python_bindings(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyPayload>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_any_size_limit() {
+        let payload = PyPayload {
+            type_tag: "builtins.bytes".to_string(),
+            bytes: vec![0; 1024],
+        };
+        let any = payload.to_any().unwrap();
+        assert_eq!(PyPayload::from_any(&any).unwrap(), payload);
+
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(PY_PAYLOAD_MAX_BYTES, 512);
+        let err = PyPayload::from_any(&any).unwrap_err();
+        assert!(format!("{:#}", err).contains("exceeds the limit of 512 bytes"));
+    }
+
+    #[test]
+    fn test_deserialize_rejects_before_allocating() {
+        // A payload claiming far more bytes than it carries is rejected
+        // on its length alone.
+        let mut encoded = bincode::serde::encode_to_vec(
+            PyPayload {
+                type_tag: "builtins.bytes".to_string(),
+                bytes: Vec::new(),
+            },
+            bincode::config::legacy(),
+        )
+        .unwrap();
+        let len = encoded.len();
+        encoded[len - 8..].copy_from_slice(&u64::MAX.to_le_bytes());
+        let err =
+            bincode::serde::decode_from_slice::<PyPayload, _>(&encoded, bincode::config::legacy())
+                .unwrap_err();
+        assert!(err.to_string().contains("exceeds the limit"));
+    }
+}
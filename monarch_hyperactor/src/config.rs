@@ -52,6 +52,8 @@ pub enum PyEncoding {
     Bincode,
     Json,
     Multipart,
+    MessagePack,
+    Cbor,
 }
 
 impl From<wirevalue::Encoding> for PyEncoding {
@@ -60,6 +62,8 @@ impl From<wirevalue::Encoding> for PyEncoding {
             wirevalue::Encoding::Bincode => PyEncoding::Bincode,
             wirevalue::Encoding::Json => PyEncoding::Json,
             wirevalue::Encoding::Multipart => PyEncoding::Multipart,
+            wirevalue::Encoding::MessagePack => PyEncoding::MessagePack,
+            wirevalue::Encoding::Cbor => PyEncoding::Cbor,
         }
     }
 }
@@ -70,6 +74,8 @@ impl From<PyEncoding> for wirevalue::Encoding {
             PyEncoding::Bincode => wirevalue::Encoding::Bincode,
             PyEncoding::Json => wirevalue::Encoding::Json,
             PyEncoding::Multipart => wirevalue::Encoding::Multipart,
+            PyEncoding::MessagePack => wirevalue::Encoding::MessagePack,
+            PyEncoding::Cbor => wirevalue::Encoding::Cbor,
         }
     }
 }
@@ -218,6 +224,60 @@ declare_attrs! {
         Some("actor_queue_dispatch".to_string()),
     ))
     pub attr ACTOR_QUEUE_DISPATCH: bool = false;
+
+    /// Run each spawned Python actor in its own subprocess, so that a
+    /// crash in one actor's native code fails only that actor rather
+    /// than its proc. See [`crate::isolated_actor`].
+    @meta(CONFIG = ConfigAttr::new(
+        Some("MONARCH_ACTOR_SUBPROCESS_ISOLATION".to_string()),
+        Some("actor_subprocess_isolation".to_string()),
+    ))
+    pub attr ACTOR_SUBPROCESS_ISOLATION: bool = false;
+
+    /// Invoke Python actor handlers on the dedicated Python executor,
+    /// rather than acquiring the GIL on tokio worker threads. See
+    /// [`crate::python_executor`].
+    @meta(CONFIG = ConfigAttr::new(
+        Some("MONARCH_ACTOR_PYTHON_EXECUTOR".to_string()),
+        Some("actor_python_executor".to_string()),
+    ))
+    pub attr ACTOR_PYTHON_EXECUTOR: bool = false;
+
+    /// The maximum number of jobs the Python executor runs under a
+    /// single GIL acquisition.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("MONARCH_PYTHON_EXECUTOR_MAX_BATCH".to_string()),
+        Some("python_executor_max_batch".to_string()),
+    ))
+    pub attr PYTHON_EXECUTOR_MAX_BATCH: usize = 32;
+
+    /// How long the Python executor may hold the GIL before releasing
+    /// it between jobs.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("MONARCH_PYTHON_EXECUTOR_MAX_GIL_HOLD".to_string()),
+        Some("python_executor_max_gil_hold".to_string()),
+    ))
+    pub attr PYTHON_EXECUTOR_MAX_GIL_HOLD: Duration = Duration::from_millis(10);
+
+    /// The maximum size, in bytes, of a pickled Python object carried
+    /// by a [`crate::codec::PyPayload`], enforced both when encoding
+    /// and when receiving.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("MONARCH_PY_PAYLOAD_MAX_BYTES".to_string()),
+        Some("py_payload_max_bytes".to_string()),
+    ))
+    pub attr PY_PAYLOAD_MAX_BYTES: usize = 1 << 30;
+
+    /// The maximum number of casts a Python actor mesh may have queued
+    /// but not yet sent. Calls whose results are awaited (`call`,
+    /// `choose`, `call_one`, `stream`) wait for room; other casts to a
+    /// full mesh raise `QueueFull`, unless the sender first awaits a
+    /// permit with `acquire_send_permit`. Zero means unbounded.
+    @meta(CONFIG = ConfigAttr::new(
+        Some("MONARCH_PYTHON_CAST_QUEUE_CAPACITY".to_string()),
+        Some("python_cast_queue_capacity".to_string()),
+    ))
+    pub attr PYTHON_CAST_QUEUE_CAPACITY: usize = 4096;
 }
 
 /// Python API for configuration management
@@ -783,7 +843,13 @@ mod tests {
         Python::initialize();
         Python::attach(|py| {
             // Test all enum variants roundtrip
-            for variant in [PyEncoding::Bincode, PyEncoding::Json, PyEncoding::Multipart] {
+            for variant in [
+                PyEncoding::Bincode,
+                PyEncoding::Json,
+                PyEncoding::Multipart,
+                PyEncoding::MessagePack,
+                PyEncoding::Cbor,
+            ] {
                 let py_obj = Bound::new(py, variant).unwrap().into_any();
                 let back: PyEncoding = py_obj.extract().unwrap();
                 assert_eq!(back, variant);
@@ -262,11 +262,21 @@ impl PyContext {
     pub(crate) fn new<T: hyperactor::actor::Actor>(
         cx: &hyperactor::Context<T>,
         instance: Py<PyInstance>,
+    ) -> PyContext {
+        Self::from_parts(instance, cx.cast_point(), Some(cx.recording_span()))
+    }
+
+    /// A context assembled from the parts of a handler's context, for
+    /// code that must build it without access to the context itself.
+    pub(crate) fn from_parts(
+        instance: Py<PyInstance>,
+        rank: Point,
+        recording_span: Option<tracing::Span>,
     ) -> PyContext {
         PyContext {
             instance,
-            rank: cx.cast_point(),
-            recording_span: Some(cx.recording_span()),
+            rank,
+            recording_span,
         }
     }
 
@@ -146,12 +146,40 @@ impl PyHostMesh {
         Self::Ref(PyHostMeshRefImpl(inner))
     }
 
-    fn mesh_ref(&self) -> Result<HostMeshRef, anyhow::Error> {
+    pub(crate) fn mesh_ref(&self) -> Result<HostMeshRef, anyhow::Error> {
         match self {
             PyHostMesh::Owned(inner) => Ok(inner.0.borrow()?.clone()),
             PyHostMesh::Ref(inner) => Ok(inner.0.clone()),
         }
     }
+
+    /// Stop the owned mesh through `instance`, draining its procs but
+    /// keeping its hosts alive; see [`HostMesh::stop`].
+    pub(crate) fn stop_owned(
+        &self,
+        instance: PyInstance,
+    ) -> PyResult<impl Future<Output = PyResult<()>> + Send + 'static> {
+        match self {
+            PyHostMesh::Owned(inner) => {
+                let mesh_borrow = inner.0.clone();
+                Ok(async move {
+                    match mesh_borrow.take().await {
+                        Ok(mut mesh) => {
+                            mesh.stop(instance.deref()).await?;
+                            Ok(())
+                        }
+                        Err(_) => {
+                            tracing::info!("stop was already called on host mesh");
+                            Ok(())
+                        }
+                    }
+                })
+            }
+            PyHostMesh::Ref(_) => Err(PyRuntimeError::new_err(
+                "cannot stop `HostMesh` that is a reference instead of owned",
+            )),
+        }
+    }
 }
 
 #[pymethods]
@@ -277,28 +305,7 @@ impl PyHostMesh {
     }
 
     fn stop(&self, instance: &PyInstance) -> PyResult<PyPythonTask> {
-        match self {
-            PyHostMesh::Owned(inner) => {
-                let instance = instance.clone();
-                let mesh_borrow = inner.0.clone();
-                let fut = async move {
-                    match mesh_borrow.take().await {
-                        Ok(mut mesh) => {
-                            mesh.stop(instance.deref()).await?;
-                            Ok(())
-                        }
-                        Err(_) => {
-                            tracing::info!("stop was already called on host mesh");
-                            Ok(())
-                        }
-                    }
-                };
-                PyPythonTask::new(fut)
-            }
-            PyHostMesh::Ref(_) => Err(PyRuntimeError::new_err(
-                "cannot stop `HostMesh` that is a reference instead of owned",
-            )),
-        }
+        PyPythonTask::new(self.stop_owned(instance.clone())?)
     }
 }
 
@@ -0,0 +1,497 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Crash isolation for Python actors.
+//!
+//! When [`ACTOR_SUBPROCESS_ISOLATION`] is enabled, each spawned
+//! [`PythonActor`] runs its Python object in a dedicated subprocess, so
+//! that a crash in a native extension (a segfault, an abort) takes down
+//! only that actor, not the proc hosting it.
+//!
+//! The actor spawned in the proc becomes a bridge. On init, it launches
+//! the subprocess (the Python bootstrap entry point, see
+//! [`crate::bootstrap::bootstrap_main`]), which runs a proc with a
+//! single [`PythonActor`] constructed from the bridge's parameters:
+//!
+//! - The subprocess's proc is advertised under a via hop of the parent
+//!   proc's location, and registered as a peer of the parent's
+//!   [`Gateway`](hyperactor::Gateway) over a local (unix) channel, so
+//!   that messages addressed to it (for example, replies to ports it
+//!   opened) route through the parent proc.
+//! - Its outbound messages are sent to a backend channel served by the
+//!   parent proc, which routes them onward.
+//! - The bridge forwards the messages it receives, with their headers,
+//!   to the subprocess's actor.
+//!
+//! The bridge watches the subprocess. If it exits while the bridge is
+//! running, the bridge is killed, which surfaces as an actor failure
+//! through the usual supervision events; a subprocess whose actor
+//! stopped cleanly stops the bridge instead. Conversely, when the bridge
+//! stops, it stops the subprocess's actor: it sends the subprocess
+//! SIGTERM, which drains and stops the actor (running its cleanup), and
+//! kills the subprocess if it has not exited within
+//! [`PROCESS_EXIT_TIMEOUT`].
+//!
+//! [`ACTOR_SUBPROCESS_ISOLATION`]: crate::config::ACTOR_SUBPROCESS_ISOLATION
+//! [`PROCESS_EXIT_TIMEOUT`]: hyperactor::config::PROCESS_EXIT_TIMEOUT
+
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+
+use hyperactor::Actor;
+use hyperactor::ActorHandle;
+use hyperactor::ActorRef;
+use hyperactor::Context;
+use hyperactor::Instance;
+use hyperactor::Label;
+use hyperactor::Location;
+use hyperactor::Proc;
+use hyperactor::ProcAddr;
+use hyperactor::ProcId;
+use hyperactor::RemoteEndpoint as _;
+use hyperactor::RemoteHandles;
+use hyperactor::RemoteMessage;
+use hyperactor::Uid;
+use hyperactor::channel;
+use hyperactor::channel::ChannelAddr;
+use hyperactor::channel::ChannelTransport;
+use hyperactor::channel::Rx;
+use hyperactor::channel::Tx;
+use hyperactor::gateway::PeerAttachGuard;
+use hyperactor::mailbox::IntoBoxedMailboxSender;
+use hyperactor::mailbox::MailboxClient;
+use hyperactor::mailbox::MailboxServer;
+use hyperactor::mailbox::MailboxServerHandle;
+use hyperactor_config::attrs::Attrs;
+use hyperactor_mesh::bootstrap::MESH_BOOTSTRAP_ENABLE_PDEATHSIG;
+use hyperactor_mesh::bootstrap::install_pdeathsig_kill;
+use monarch_types::PickledPyObject;
+use ndslice::Point;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::process::Child;
+use tokio::signal::unix::SignalKind;
+use tokio::sync::oneshot;
+
+use crate::actor::PythonActor;
+use crate::actor::PythonMessage;
+use crate::bootstrap::python_bootstrap_command;
+
+/// The environment variable carrying an [`IsolatedActorBootstrap`] to
+/// the subprocess.
+const ISOLATED_ACTOR_BOOTSTRAP: &str = "MONARCH_ISOLATED_ACTOR_BOOTSTRAP";
+
+/// The name of the actor in the subprocess's proc.
+const ISOLATED_ACTOR_NAME: &str = "isolated";
+
+/// The parameters of the [`PythonActor`] run in the subprocess.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct IsolatedActorSpec {
+    pub(crate) actor_type: PickledPyObject,
+    pub(crate) init_message: Option<PythonMessage>,
+    pub(crate) spawn_point: Option<Point>,
+    pub(crate) mesh_base_name: Option<String>,
+}
+
+/// Bootstrap configuration passed to the subprocess in
+/// [`ISOLATED_ACTOR_BOOTSTRAP`].
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct IsolatedActorBootstrap {
+    /// The address of the subprocess's proc.
+    proc_addr: ProcAddr,
+    /// The parent's channel for the subprocess's outbound messages.
+    backend_addr: ChannelAddr,
+    /// The parent's channel on which the subprocess reports its
+    /// serving address and actor.
+    callback_addr: ChannelAddr,
+    /// A file containing the [`IsolatedActorSpec`], which may be too
+    /// large for the environment. The subprocess removes it.
+    spec_path: PathBuf,
+    /// The parent's configuration, installed as the `ClientOverride`
+    /// layer.
+    config: Attrs,
+}
+
+impl IsolatedActorBootstrap {
+    /// The bootstrap configuration in the environment, if this process
+    /// was launched to run an isolated actor.
+    pub(crate) fn get_from_env() -> anyhow::Result<Option<Self>> {
+        match std::env::var(ISOLATED_ACTOR_BOOTSTRAP) {
+            Ok(boot) => Ok(Some(serde_json::from_str(&boot)?)),
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Run the isolated actor until it stops. Returns the exit code of
+    /// the process: 0 if the actor stopped, 1 if it failed.
+    pub(crate) async fn run(self) -> anyhow::Result<i32> {
+        hyperactor_config::global::set(
+            hyperactor_config::global::Source::ClientOverride,
+            self.config,
+        );
+        if hyperactor_config::global::get(MESH_BOOTSTRAP_ENABLE_PDEATHSIG) {
+            // The actor must not outlive its bridge.
+            let _ = install_pdeathsig_kill();
+        }
+
+        let spec = std::fs::read(&self.spec_path)?;
+        let _ = std::fs::remove_file(&self.spec_path);
+        let (spec, _): (IsolatedActorSpec, _) =
+            bincode::serde::decode_from_slice(&spec, bincode::config::legacy())?;
+
+        let proc = Proc::configured(
+            self.proc_addr,
+            MailboxClient::dial(self.backend_addr)?.into_boxed(),
+        );
+        let actor = PythonActor::new(
+            spec.actor_type,
+            spec.init_message,
+            spec.spawn_point,
+            spec.mesh_base_name,
+        )?;
+        let handle =
+            proc.spawn_with_uid(Uid::singleton(Label::strip(ISOLATED_ACTOR_NAME)), actor)?;
+
+        // The bridge stops the actor with SIGTERM.
+        let mut terminate = tokio::signal::unix::signal(SignalKind::terminate())?;
+        let (proc_addr, proc_rx) = channel::serve(ChannelAddr::any(ChannelTransport::Unix))?;
+        let mailbox_handle = proc.clone().serve(proc_rx);
+        channel::dial(self.callback_addr)?
+            .send((proc_addr, handle.bind::<PythonActor>()))
+            .await?;
+
+        let mut status = handle.status();
+        let failed = loop {
+            tokio::select! {
+                status = status.wait_for(|status| status.is_terminal()) => {
+                    break status.map_or(true, |status| status.is_failed());
+                }
+                Some(()) = terminate.recv() => {
+                    let _ = handle.drain_and_stop("isolated actor bridge stopped");
+                }
+            }
+        };
+        mailbox_handle.stop("isolated actor terminated");
+        let _ = mailbox_handle.await;
+        Ok(if failed { 1 } else { 0 })
+    }
+}
+
+/// The state of a bridge to an isolated actor.
+#[derive(Debug)]
+pub(crate) enum IsolatedActor {
+    /// The subprocess has not yet been launched.
+    Pending(IsolatedActorSpec),
+    /// The subprocess is running.
+    Running(ActorSubprocess),
+}
+
+impl IsolatedActor {
+    /// Launch the subprocess for a pending actor.
+    pub(crate) async fn launch(&mut self, this: &Instance<PythonActor>) -> anyhow::Result<()> {
+        if let IsolatedActor::Pending(spec) = self {
+            *self = IsolatedActor::Running(ActorSubprocess::launch(this, spec.clone()).await?);
+        }
+        Ok(())
+    }
+
+    /// Forward `message`, with the headers of `cx`, to the isolated actor.
+    pub(crate) fn forward<M>(&self, cx: &Context<PythonActor>, message: M) -> anyhow::Result<()>
+    where
+        M: RemoteMessage,
+        PythonActor: RemoteHandles<M>,
+    {
+        match self {
+            IsolatedActor::Running(subprocess) => {
+                subprocess
+                    .actor
+                    .post_with_headers(cx, cx.headers().clone(), message);
+                Ok(())
+            }
+            IsolatedActor::Pending(_) => {
+                anyhow::bail!("isolated actor subprocess has not been launched")
+            }
+        }
+    }
+
+    /// Terminate the subprocess, if it is running.
+    pub(crate) async fn shutdown(&mut self) {
+        if let IsolatedActor::Running(subprocess) = self {
+            subprocess.shutdown().await;
+        }
+    }
+}
+
+/// A running subprocess hosting an isolated actor.
+pub(crate) struct ActorSubprocess {
+    /// The actor in the subprocess.
+    actor: ActorRef<PythonActor>,
+    /// Routes messages addressed to the subprocess's proc.
+    _peer: PeerAttachGuard,
+    /// Routes messages sent by the subprocess's proc.
+    backend: Option<MailboxServerHandle>,
+    /// Asks the watcher to stop the subprocess; it replies once the
+    /// subprocess has exited.
+    stop: Option<oneshot::Sender<oneshot::Sender<()>>>,
+}
+
+impl fmt::Debug for ActorSubprocess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ActorSubprocess")
+            .field("actor", &self.actor)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ActorSubprocess {
+    async fn launch(this: &Instance<PythonActor>, spec: IsolatedActorSpec) -> anyhow::Result<Self> {
+        let proc = this.proc();
+
+        let (backend_addr, backend_rx) = channel::serve(ChannelAddr::any(ChannelTransport::Unix))?;
+        let backend = proc.clone().serve(backend_rx);
+        let (callback_addr, mut callback_rx) =
+            channel::serve::<(ChannelAddr, ActorRef<PythonActor>)>(ChannelAddr::any(
+                ChannelTransport::Unix,
+            ))?;
+
+        let proc_id = ProcId::instance(Label::strip(ISOLATED_ACTOR_NAME));
+        let uid = proc_id.uid().clone();
+        let location = nest_via(proc.proc_addr().location().clone(), uid.clone());
+
+        let spec_file = tempfile::NamedTempFile::new()?;
+        std::fs::write(
+            spec_file.path(),
+            bincode::serde::encode_to_vec(&spec, bincode::config::legacy())?,
+        )?;
+        // The subprocess removes the file once read.
+        let (_, spec_path) = spec_file.keep()?;
+        let boot = IsolatedActorBootstrap {
+            proc_addr: ProcAddr::new(proc_id, location),
+            backend_addr,
+            callback_addr,
+            spec_path: spec_path.clone(),
+            config: hyperactor_config::global::propagatable_attrs(),
+        };
+
+        let mut command = python_bootstrap_command()?;
+        command.env.remove("HYPERACTOR_MESH_BOOTSTRAP_MODE");
+        let mut command = command.new();
+        command
+            .env(ISOLATED_ACTOR_BOOTSTRAP, serde_json::to_string(&boot)?)
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                let _ = std::fs::remove_file(&spec_path);
+                return Err(err.into());
+            }
+        };
+
+        let timeout = hyperactor_config::global::get(hyperactor::config::HOST_SPAWN_READY_TIMEOUT);
+        let ready = tokio::select! {
+            ready = callback_rx.recv() => ready.map_err(anyhow::Error::from),
+            status = child.wait() => Err(anyhow::anyhow!(
+                "isolated actor subprocess exited before becoming ready: {}",
+                status?
+            )),
+            _ = tokio::time::sleep(timeout), if timeout > Duration::ZERO => Err(
+                anyhow::anyhow!("isolated actor subprocess not ready after {:?}", timeout)
+            ),
+        };
+        let (proc_addr, actor) = match ready {
+            Ok(ready) => ready,
+            Err(err) => {
+                let _ = std::fs::remove_file(&spec_path);
+                return Err(err);
+            }
+        };
+        let peer = proc
+            .gateway()
+            .attach_peer(uid, MailboxClient::dial(proc_addr)?.into_boxed())?;
+
+        let (stop_tx, stop_rx) = oneshot::channel();
+        tokio::spawn(watch(child, this.handle(), stop_rx));
+
+        Ok(Self {
+            actor,
+            _peer: peer,
+            backend: Some(backend),
+            stop: Some(stop_tx),
+        })
+    }
+
+    async fn shutdown(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let (done_tx, done_rx) = oneshot::channel();
+            if stop.send(done_tx).is_ok() {
+                let _ = done_rx.await;
+            }
+        }
+        if let Some(backend) = self.backend.take() {
+            backend.stop("isolated actor shut down");
+            let _ = backend.await;
+        }
+    }
+}
+
+/// Watch the subprocess `child` on behalf of its `bridge`. If it exits
+/// cleanly, the bridge is stopped; otherwise, the bridge is killed,
+/// which surfaces as an actor failure. A request on `stop` terminates
+/// the subprocess, and is answered once it has exited.
+async fn watch<A: Actor>(
+    mut child: Child,
+    bridge: ActorHandle<A>,
+    stop: oneshot::Receiver<oneshot::Sender<()>>,
+) {
+    tokio::select! {
+        status = child.wait() => {
+            let reason = match status {
+                Ok(status) if status.success() => {
+                    let _ = bridge.stop("isolated actor subprocess exited");
+                    return;
+                }
+                Ok(status) => format!("isolated actor subprocess exited: {}", status),
+                Err(err) => format!("failed to wait for isolated actor subprocess: {}", err),
+            };
+            tracing::error!("{}: {}", bridge.actor_addr(), reason);
+            let _ = bridge.kill(&reason);
+        }
+        Ok(done) = stop => {
+            terminate(&mut child).await;
+            let _ = done.send(());
+        }
+    }
+}
+
+/// Ask `child` to stop its actor with SIGTERM, and kill it if it has
+/// not exited within [`PROCESS_EXIT_TIMEOUT`].
+///
+/// [`PROCESS_EXIT_TIMEOUT`]: hyperactor::config::PROCESS_EXIT_TIMEOUT
+async fn terminate(child: &mut Child) {
+    if let Some(pid) = child.id() {
+        let _ = nix::sys::signal::kill(
+            nix::unistd::Pid::from_raw(pid as i32),
+            nix::sys::signal::Signal::SIGTERM,
+        );
+        let timeout = hyperactor_config::global::get(hyperactor::config::PROCESS_EXIT_TIMEOUT);
+        if tokio::time::timeout(timeout, child.wait()).await.is_ok() {
+            return;
+        }
+    }
+    let _ = child.kill().await;
+}
+
+/// Nest a via hop for `uid` innermost in `location`, so that it is
+/// peeled by the gateway serving `location`'s channel address after
+/// any outer hops have routed the message there.
+fn nest_via(location: Location, uid: Uid) -> Location {
+    match location {
+        Location::Addr(addr) => Location::Addr(addr).with_via(uid),
+        Location::Via(outer, inner) => Location::Via(outer, Box::new(nest_via(*inner, uid))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor::actor::ActorStatus;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    #[hyperactor::export]
+    struct Bridge;
+
+    impl Actor for Bridge {}
+
+    /// Spawn a bridge watching a shell running `script`, and return
+    /// its terminal status.
+    async fn watched(script: &str) -> ActorStatus {
+        let proc = Proc::isolated();
+        let bridge = proc.spawn(Bridge);
+        let child = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .spawn()
+            .unwrap();
+        let (_stop_tx, stop_rx) = oneshot::channel();
+        tokio::spawn(watch(child, bridge.clone(), stop_rx));
+        let mut status = bridge.status();
+        tokio::time::timeout(
+            Duration::from_secs(30),
+            status.wait_for(|status| status.is_terminal()),
+        )
+        .await
+        .unwrap()
+        .unwrap()
+        .clone()
+    }
+
+    #[tokio::test]
+    async fn test_subprocess_crash_fails_bridge() {
+        let status = watched("kill -SEGV $$").await;
+        assert!(status.is_failed(), "{:?}", status);
+    }
+
+    #[tokio::test]
+    async fn test_subprocess_exit_stops_bridge() {
+        let status = watched("exit 0").await;
+        assert!(!status.is_failed(), "{:?}", status);
+    }
+
+    #[tokio::test]
+    async fn test_stop_terminates_subprocess() {
+        let proc = Proc::isolated();
+        let bridge = proc.spawn(Bridge);
+        // The subprocess exits on SIGTERM, as an isolated actor does.
+        let child = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg("trap 'exit 0' TERM; while true; do sleep 0.1; done")
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let watcher = tokio::spawn(watch(child, bridge.clone(), stop_rx));
+        let (done_tx, done_rx) = oneshot::channel();
+        stop_tx.send(done_tx).unwrap();
+        tokio::time::timeout(Duration::from_secs(30), done_rx)
+            .await
+            .unwrap()
+            .unwrap();
+        watcher.await.unwrap();
+        assert!(!std::path::Path::new(&format!("/proc/{}", pid)).exists());
+    }
+
+    #[test]
+    fn test_nest_via() {
+        let addr = ChannelAddr::Local(1);
+        let child = Uid::instance(Label::strip("child"));
+        let parent = Uid::instance(Label::strip("parent"));
+
+        assert_eq!(
+            nest_via(Location::Addr(addr.clone()), child.clone()),
+            Location::Addr(addr.clone()).with_via(child.clone()),
+        );
+
+        // Outer hops are peeled first, reaching the parent's gateway,
+        // which peels the child's hop.
+        let (outer, inner) = nest_via(
+            Location::Addr(addr.clone()).with_via(parent.clone()),
+            child.clone(),
+        )
+        .pop_via()
+        .unwrap();
+        assert_eq!(outer, parent);
+        let (next, inner) = inner.pop_via().unwrap();
+        assert_eq!(next, child);
+        assert_eq!(inner, Location::Addr(addr));
+    }
+}
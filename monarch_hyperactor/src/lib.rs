@@ -16,10 +16,12 @@ pub mod bootstrap;
 pub mod buffers;
 pub mod channel;
 pub mod code_sync;
+pub mod codec;
 pub mod config;
 pub mod context;
 pub mod endpoint;
 pub mod host_mesh;
+pub mod isolated_actor;
 pub mod local_state_broker;
 pub mod logging;
 pub mod mailbox;
@@ -33,11 +35,14 @@ pub mod proc_launcher_probe;
 pub mod proc_mesh;
 pub mod py_cell;
 pub mod pympsc;
+pub mod python_executor;
 pub mod pytokio;
 pub mod pywaker;
 pub mod runtime;
 pub mod selection;
+pub mod session;
 pub mod shape;
+pub mod stubs;
 pub mod supervision;
 pub mod telemetry;
 pub mod testing;
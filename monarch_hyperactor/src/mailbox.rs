@@ -26,6 +26,7 @@ use hyperactor::mailbox::MessageEnvelope;
 use hyperactor::mailbox::OncePortReceiver;
 use hyperactor::mailbox::PortReceiver;
 use hyperactor::mailbox::Undeliverable;
+use hyperactor::mailbox::error_code::ErrorCode;
 use hyperactor::mailbox::monitored_return_handle;
 use hyperactor::message::Bind;
 use hyperactor::message::Bindings;
@@ -38,6 +39,7 @@ use pyo3::exceptions::PyEOFError;
 use pyo3::exceptions::PyRuntimeError;
 use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyo3::types::PyBytes;
 use pyo3::types::PyTuple;
 use pyo3::types::PyType;
 use serde::Deserialize;
@@ -46,6 +48,7 @@ use typeuri::Named;
 
 use crate::actor::PythonMessage;
 use crate::actor::PythonMessageKind;
+use crate::config::PyEncoding;
 use crate::context::PyInstance;
 use crate::proc::PyActorAddr;
 use crate::pytokio::PyPythonTask;
@@ -140,6 +143,40 @@ impl PyMailbox {
         Ok(())
     }
 
+    /// Post `data`, a value of the Rust type named `typename` already encoded
+    /// with `encoding` (e.g. a MessagePack-encoded dict), to the port `dest`.
+    /// The data is decoded by the receiver.
+    fn post_encoded(
+        &self,
+        dest: &PyPortId,
+        encoding: PyEncoding,
+        typename: &str,
+        data: &Bound<'_, PyBytes>,
+    ) -> PyResult<()> {
+        let typehash = typeuri::cityhasher::hash(typename);
+        if wirevalue::TypeInfo::get(typehash).is_none() {
+            return Err(PyValueError::new_err(format!(
+                "unknown message type: {}",
+                typename
+            )));
+        }
+        let message =
+            wirevalue::Any::from_encoded(encoding.into(), typehash, data.as_bytes().to_vec())
+                .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        let envelope = MessageEnvelope::new(
+            self.inner.actor_addr().clone(),
+            dest.inner.clone(),
+            message,
+            Flattrs::new(),
+        );
+        let return_handle = self
+            .inner
+            .bound_return_handle()
+            .unwrap_or(monitored_return_handle());
+        self.inner.post(envelope, return_handle);
+        Ok(())
+    }
+
     #[getter]
     pub(super) fn actor_id(&self) -> PyActorAddr {
         PyActorAddr {
@@ -371,6 +408,13 @@ impl PythonUndeliverableMessageEnvelope {
         })
     }
 
+    fn root_error_code(&self) -> PyResult<Option<ErrorCode>> {
+        Ok(match self.inner()? {
+            Undeliverable::Returned(envelope) => envelope.error_code(),
+            Undeliverable::Report(report) => report.error_code(),
+        })
+    }
+
     pub(crate) fn take(&mut self) -> anyhow::Result<Undeliverable<MessageEnvelope>> {
         self.inner.take().ok_or_else(|| {
             anyhow::anyhow!("PythonUndeliverableMessageEnvelope was already consumed")
@@ -418,6 +462,14 @@ impl PythonUndeliverableMessageEnvelope {
             Undeliverable::Report(report) => Ok(report.error_msg().unwrap_or_default()),
         }
     }
+
+    fn error_code(&self) -> PyResult<Option<u16>> {
+        Ok(self.root_error_code()?.map(|code| code.as_u16()))
+    }
+
+    fn error_category(&self) -> PyResult<Option<&'static str>> {
+        Ok(self.root_error_code()?.map(|code| code.category().as_str()))
+    }
 }
 
 #[derive(Debug)]
@@ -526,25 +578,41 @@ pub(super) struct PythonOncePortReceiver {
     inner: Arc<std::sync::Mutex<Option<OncePortReceiver<PythonMessage>>>>,
 }
 
+async fn recv_once_async(receiver: OncePortReceiver<PythonMessage>) -> PyResult<Py<PyAny>> {
+    let message = receiver
+        .recv()
+        .await
+        .map_err(|err| PyErr::new::<PyEOFError, _>(format!("Port closed: {}", err)))?;
+
+    monarch_with_gil(|py| message.into_py_any(py)).await
+}
+
 #[pymethods]
 impl PythonOncePortReceiver {
     fn recv_task(&mut self) -> PyResult<PyPythonTask> {
-        let Some(receiver) = self.inner.lock().unwrap().take() else {
-            return Err(PyErr::new::<PyValueError, _>("OncePort is already used"));
-        };
-        let fut = async move {
-            let message = receiver
-                .recv()
-                .await
-                .map_err(|err| PyErr::new::<PyEOFError, _>(format!("Port closed: {}", err)))?;
+        let receiver = self.take()?;
+        Ok(PythonTask::new(recv_once_async(receiver))?.into())
+    }
 
-            monarch_with_gil(|py| message.into_py_any(py)).await
-        };
-        Ok(PythonTask::new(fut)?.into())
+    /// Receive the message through an asyncio future on the running
+    /// event loop. Cancelling the future drops the receiver, which
+    /// closes the port: a message sent after cancellation is returned
+    /// to its sender as undeliverable.
+    fn recv_future<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let receiver = self.take()?;
+        crate::runtime::future_into_py(py, recv_once_async(receiver))
     }
 }
 
 impl PythonOncePortReceiver {
+    fn take(&self) -> PyResult<OncePortReceiver<PythonMessage>> {
+        self.inner
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| PyErr::new::<PyValueError, _>("OncePort is already used"))
+    }
+
     #[allow(dead_code)]
     pub(super) fn inner(&self) -> Arc<std::sync::Mutex<Option<OncePortReceiver<PythonMessage>>>> {
         Arc::clone(&self.inner)
@@ -70,3 +70,21 @@ declare_static_counter!(
 );
 // Tracks errors that occur during endpoint broadcast operations
 declare_static_counter!(ENDPOINT_BROADCAST_ERROR, "endpoint_broadcast_error");
+
+// PYTHON EXECUTOR METRICS
+// Tracks how long each handler run on the executor held the GIL, including
+// the steps of its coroutine, in microseconds
+declare_static_histogram!(
+    PYTHON_EXECUTOR_GIL_HOLD_US_HISTOGRAM,
+    "python_executor_gil_hold_us_histogram"
+);
+// Tracks how long executor jobs waited to run, in microseconds
+declare_static_histogram!(
+    PYTHON_EXECUTOR_QUEUE_WAIT_US_HISTOGRAM,
+    "python_executor_queue_wait_us_histogram"
+);
+// Tracks the number of jobs run per GIL acquisition
+declare_static_histogram!(
+    PYTHON_EXECUTOR_BATCH_SIZE_HISTOGRAM,
+    "python_executor_batch_size_histogram"
+);
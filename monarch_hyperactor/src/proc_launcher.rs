@@ -660,6 +660,9 @@ impl ProcLauncher for ActorProcLauncher {
                 if let Some(v) = &bind.cpus {
                     d.set_item("cpus", v).unwrap();
                 }
+                if let Some(v) = &bind.gpus {
+                    d.set_item("gpus", v).unwrap();
+                }
                 d
             });
 
@@ -130,7 +130,7 @@ pub(crate) fn probe_exit_port_via_mesh(
     let mut pending_message = PendingMessage::py_new(kind, pickling_state)?;
 
     // Cast to all actors in the mesh using cast_unresolved
-    actor_mesh_inner.cast_unresolved(&mut pending_message, "all", instance)?;
+    actor_mesh_inner.cast_unresolved(&mut pending_message, "all", instance, None)?;
 
     // Return an awaitable task that receives the result
     PyPythonTask::new(async move {
@@ -0,0 +1,272 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! A dedicated executor for work that needs the GIL.
+//!
+//! Acquiring the GIL from an async task blocks the tokio worker thread
+//! running it until the GIL is free. When Python actors hold the GIL for
+//! long stretches, the runtime's workers can all end up blocked this
+//! way, starving the tasks that drive mailboxes and channels.
+//!
+//! The [`PythonExecutor`] instead runs GIL-bound work on its own thread.
+//! Async callers submit a job and await its result, so they never block
+//! a worker. The executor acquires the GIL once per batch of queued
+//! jobs: it keeps running queued jobs under a single acquisition until
+//! it has run [`PYTHON_EXECUTOR_MAX_BATCH`] jobs or held the GIL for
+//! [`PYTHON_EXECUTOR_MAX_GIL_HOLD`], then releases the GIL so that other
+//! threads may take it.
+//!
+//! The executor records how long each job waited to run, keyed by the
+//! job's handler name, and the size of each batch. A handler invoked on
+//! the executor returns a coroutine that runs later, on the asyncio
+//! event loop, holding the GIL for each of its steps; handlers therefore
+//! wrap their coroutine in a [`TimedAwaitable`], which records the GIL
+//! held by the invocation and every step of the coroutine once it
+//! completes.
+//!
+//! [`PYTHON_EXECUTOR_MAX_BATCH`]: crate::config::PYTHON_EXECUTOR_MAX_BATCH
+//! [`PYTHON_EXECUTOR_MAX_GIL_HOLD`]: crate::config::PYTHON_EXECUTOR_MAX_GIL_HOLD
+
+use std::panic::AssertUnwindSafe;
+use std::sync::OnceLock;
+use std::sync::mpsc;
+use std::time::Duration;
+use std::time::Instant;
+
+use pyo3::prelude::*;
+use tokio::sync::oneshot;
+
+use crate::config::PYTHON_EXECUTOR_MAX_BATCH;
+use crate::config::PYTHON_EXECUTOR_MAX_GIL_HOLD;
+use crate::metrics::PYTHON_EXECUTOR_BATCH_SIZE_HISTOGRAM;
+use crate::metrics::PYTHON_EXECUTOR_GIL_HOLD_US_HISTOGRAM;
+use crate::metrics::PYTHON_EXECUTOR_QUEUE_WAIT_US_HISTOGRAM;
+use crate::runtime::monarch_with_gil_blocking;
+
+/// A unit of work for the executor.
+struct Job {
+    /// The handler on whose behalf the job runs, for metrics.
+    handler: String,
+    /// When the job was submitted.
+    submitted: Instant,
+    run: Box<dyn for<'py> FnOnce(Python<'py>) + Send>,
+}
+
+/// Runs GIL-bound work on a dedicated thread; see the
+/// [module documentation](self).
+#[derive(Debug)]
+pub struct PythonExecutor {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl PythonExecutor {
+    /// The process-wide executor, whose thread is started on first use.
+    pub fn global() -> &'static PythonExecutor {
+        static GLOBAL: OnceLock<PythonExecutor> = OnceLock::new();
+        GLOBAL.get_or_init(|| {
+            let (jobs, rx) = mpsc::channel();
+            std::thread::Builder::new()
+                .name("monarch-python-executor".to_string())
+                .spawn(move || run_jobs(rx))
+                .expect("failed to spawn the python executor thread");
+            PythonExecutor { jobs }
+        })
+    }
+
+    /// Run `f` with the GIL on the executor thread, on behalf of
+    /// `handler`, and return its result. Panics in `f` are resumed in
+    /// the caller.
+    pub async fn run<F, R>(&self, handler: impl Into<String>, f: F) -> R
+    where
+        F: for<'py> FnOnce(Python<'py>) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let job = Job {
+            handler: handler.into(),
+            submitted: Instant::now(),
+            run: Box::new(move |py| {
+                let _ = tx.send(std::panic::catch_unwind(AssertUnwindSafe(|| f(py))));
+            }),
+        };
+        self.jobs.send(job).expect("python executor thread exited");
+        match rx.await.expect("python executor dropped a job") {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+/// The executor thread's loop: wait for a job, then run it and as many
+/// queued jobs as the batch limits allow under one GIL acquisition.
+fn run_jobs(jobs: mpsc::Receiver<Job>) {
+    while let Ok(first) = jobs.recv() {
+        let max_batch = hyperactor_config::global::get(PYTHON_EXECUTOR_MAX_BATCH).max(1);
+        let max_hold = hyperactor_config::global::get(PYTHON_EXECUTOR_MAX_GIL_HOLD);
+        monarch_with_gil_blocking(|py| {
+            let acquired = Instant::now();
+            let mut batch = 0usize;
+            let mut next = Some(first);
+            while let Some(job) = next.take() {
+                PYTHON_EXECUTOR_QUEUE_WAIT_US_HISTOGRAM.record(
+                    job.submitted.elapsed().as_micros() as f64,
+                    hyperactor_telemetry::kv_pairs!("handler" => job.handler),
+                );
+                (job.run)(py);
+                batch += 1;
+                if batch < max_batch && acquired.elapsed() < max_hold {
+                    next = jobs.try_recv().ok();
+                }
+            }
+            PYTHON_EXECUTOR_BATCH_SIZE_HISTOGRAM
+                .record(batch as f64, hyperactor_telemetry::kv_pairs!());
+        });
+    }
+}
+
+/// An awaitable wrapping a handler's awaitable, which times each step
+/// of it. When the handler completes (or is dropped), the GIL held by
+/// its invocation and its steps is recorded, keyed by the handler name.
+#[pyclass(module = "monarch._rust_bindings.monarch_hyperactor.python_executor")]
+pub(crate) struct TimedAwaitable {
+    /// The iterator driving the wrapped awaitable.
+    inner: Py<PyAny>,
+    handler: String,
+    /// The GIL held so far.
+    held: Duration,
+    recorded: bool,
+}
+
+impl TimedAwaitable {
+    /// Wrap `awaitable`, returned by an invocation of `handler` that
+    /// held the GIL for `held`.
+    pub(crate) fn new(
+        awaitable: &Bound<'_, PyAny>,
+        handler: impl Into<String>,
+        held: Duration,
+    ) -> PyResult<Self> {
+        Ok(Self {
+            inner: awaitable.call_method0("__await__")?.unbind(),
+            handler: handler.into(),
+            held,
+            recorded: false,
+        })
+    }
+
+    /// Advance the wrapped awaitable with `step`, which holds the GIL.
+    /// Any error, including `StopIteration`, completes the awaitable.
+    fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        step: impl FnOnce(&Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>>,
+    ) -> PyResult<Py<PyAny>> {
+        let start = Instant::now();
+        let result = step(self.inner.bind(py));
+        self.held += start.elapsed();
+        if result.is_err() {
+            self.record();
+        }
+        result.map(Bound::unbind)
+    }
+
+    fn record(&mut self) {
+        if !self.recorded {
+            self.recorded = true;
+            PYTHON_EXECUTOR_GIL_HOLD_US_HISTOGRAM.record(
+                self.held.as_micros() as f64,
+                hyperactor_telemetry::kv_pairs!("handler" => self.handler.clone()),
+            );
+        }
+    }
+}
+
+#[pymethods]
+impl TimedAwaitable {
+    fn __await__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        self.send(py, py.None())
+    }
+
+    fn send(&mut self, py: Python<'_>, value: Py<PyAny>) -> PyResult<Py<PyAny>> {
+        self.step(py, |inner| inner.call_method1("send", (value,)))
+    }
+
+    fn throw(&mut self, py: Python<'_>, value: Py<PyAny>) -> PyResult<Py<PyAny>> {
+        self.step(py, |inner| inner.call_method1("throw", (value,)))
+    }
+
+    fn close(&mut self, py: Python<'_>) -> PyResult<()> {
+        self.inner.call_method0(py, "close")?;
+        self.record();
+        Ok(())
+    }
+}
+
+impl Drop for TimedAwaitable {
+    fn drop(&mut self) {
+        self.record();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pyo3::exceptions::PyStopIteration;
+
+    use super::*;
+    use crate::pytokio::ensure_python;
+
+    #[tokio::test]
+    async fn test_run() {
+        ensure_python();
+        let executor = PythonExecutor::global();
+        let results =
+            futures::future::join_all((0..10).map(|i| executor.run("test", move |_py| i * 2)))
+                .await;
+        assert_eq!(results, (0..10).map(|i| i * 2).collect::<Vec<_>>());
+
+        // Panics are resumed in the caller, and the executor survives
+        // them.
+        let panicked =
+            tokio::spawn(executor.run("test", |_py| -> i32 { panic!("handler panicked") })).await;
+        assert!(panicked.unwrap_err().is_panic());
+        assert_eq!(executor.run("test", |_py| 42).await, 42);
+    }
+
+    #[test]
+    fn test_timed_awaitable() {
+        ensure_python();
+        Python::attach(|py| {
+            let module = PyModule::from_code(
+                py,
+                c"import time\n\
+                  async def handler():\n    time.sleep(0.05)\n    return 42\n",
+                c"timed.py",
+                c"timed",
+            )
+            .unwrap();
+            let coroutine = module.getattr("handler").unwrap().call0().unwrap();
+            let mut timed = TimedAwaitable::new(&coroutine, "handler", Duration::ZERO).unwrap();
+
+            // The coroutine's body, which runs only when it is awaited,
+            // is timed.
+            let err = timed.send(py, py.None()).unwrap_err();
+            assert!(err.is_instance_of::<PyStopIteration>(py));
+            let result: i32 = err.value(py).getattr("value").unwrap().extract().unwrap();
+            assert_eq!(result, 42);
+            assert!(timed.recorded);
+            assert!(timed.held >= Duration::from_millis(50));
+        });
+    }
+}
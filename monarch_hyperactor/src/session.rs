@@ -0,0 +1,418 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Interactive mesh sessions for notebooks and REPLs.
+//!
+//! Notebook cells are re-executed freely, so code that attaches to a
+//! mesh or spawns actors at the top level of a cell would otherwise
+//! attach again, or spawn again, every time the cell runs. A
+//! [`PySession`] makes this idempotent:
+//!
+//! - Sessions are named, and constructing a session with the name of
+//!   an existing session (with the same workers) returns a handle to
+//!   the existing session. A session with the name of an existing
+//!   session on other workers replaces it: the replaced session is
+//!   closed, and its host mesh stopped.
+//! - The session attaches to its workers lazily, on the first call to
+//!   `host_mesh`, and keeps the resulting host mesh and the client
+//!   instance (and thus its mailbox) used to attach for its lifetime.
+//! - `bind(name, factory)` calls `factory` only if nothing is bound to
+//!   `name` yet, and otherwise returns the existing binding.
+//!
+//! Sessions also provide reprs, including a `_repr_html_` for Jupyter,
+//! summarizing the connection state, the health of the hosts as of the
+//! last `check_health`, the shape of the host mesh, and the bound
+//! actors.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use hyperactor::channel::ChannelAddr;
+use hyperactor::id::Label;
+use hyperactor_mesh::host_mesh::HostMesh;
+use hyperactor_mesh::mesh_id::HostMeshId;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::context::PyInstance;
+use crate::host_mesh::PyHostMesh;
+use crate::mailbox::PyMailbox;
+use crate::pytokio::PyPythonTask;
+use crate::runtime::get_tokio_runtime;
+use crate::runtime::monarch_with_gil;
+
+/// All sessions in this process, by name.
+static SESSIONS: LazyLock<Mutex<HashMap<String, Arc<SessionState>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The connection state of a session.
+enum Connection {
+    /// The session has not yet attached to its workers.
+    Disconnected,
+    /// The session is attached.
+    Connected {
+        /// The client instance used to attach, kept so that its
+        /// mailbox outlives the cell that connected.
+        instance: PyInstance,
+        host_mesh: Py<PyHostMesh>,
+    },
+    /// The last attempt to attach failed; the next call to `host_mesh`
+    /// retries.
+    Failed(String),
+    /// The session was replaced by a session with the same name on
+    /// other workers.
+    Closed,
+}
+
+struct SessionState {
+    name: String,
+    workers: Vec<String>,
+    connection: tokio::sync::Mutex<Connection>,
+    actors: Mutex<BTreeMap<String, Py<PyAny>>>,
+    /// Whether each host was reachable at the last health check, or
+    /// `None` if the session is not connected.
+    health: Mutex<Option<Vec<bool>>>,
+}
+
+impl SessionState {
+    /// Close the session after it has been replaced: drop its bindings
+    /// and stop its host mesh, draining the procs spawned on it. The
+    /// workers themselves stay up, so that other sessions can attach
+    /// to them.
+    fn close(self: Arc<Self>) {
+        get_tokio_runtime().spawn(async move {
+            let connection =
+                std::mem::replace(&mut *self.connection.lock().await, Connection::Closed);
+            *self.health.lock().unwrap() = None;
+            let actors = std::mem::take(&mut *self.actors.lock().unwrap());
+            if let Connection::Connected {
+                instance,
+                host_mesh,
+            } = connection
+            {
+                let result = async {
+                    monarch_with_gil(|py| host_mesh.borrow(py).stop_owned(instance))
+                        .await?
+                        .await
+                }
+                .await;
+                if let Err(err) = result {
+                    tracing::warn!(
+                        "session {}: failed to stop the replaced host mesh: {}",
+                        self.name,
+                        err
+                    );
+                }
+            }
+            monarch_with_gil(|_py| drop(actors)).await;
+        });
+    }
+
+    /// The health of the hosts, for the reprs.
+    fn health(&self) -> String {
+        match &*self.health.lock().unwrap() {
+            None => "unknown".to_string(),
+            Some(reachable) if reachable.iter().all(|&ok| ok) => "healthy".to_string(),
+            Some(reachable) => {
+                let unreachable: Vec<_> = reachable
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, ok)| !**ok)
+                    .map(|(rank, _)| rank)
+                    .collect();
+                format!("unhealthy (unreachable hosts: {:?})", unreachable)
+            }
+        }
+    }
+}
+
+/// A summary of a session's state, for its reprs.
+struct Summary {
+    state: String,
+    health: String,
+    extent: Option<String>,
+    actors: Vec<(String, String)>,
+}
+
+/// A named, re-entrant session on a mesh of workers; see the
+/// [module documentation](self).
+#[pyclass(
+    name = "Session",
+    module = "monarch._rust_bindings.monarch_hyperactor.session"
+)]
+pub struct PySession {
+    state: Arc<SessionState>,
+}
+
+impl PySession {
+    fn summary(&self, py: Python<'_>) -> PyResult<Summary> {
+        let (state, extent) = match self.state.connection.try_lock() {
+            Err(_) => ("connecting".to_string(), None),
+            Ok(connection) => match &*connection {
+                Connection::Disconnected => ("disconnected".to_string(), None),
+                Connection::Connected { host_mesh, .. } => {
+                    let mesh_ref = host_mesh.borrow(py).mesh_ref()?;
+                    (
+                        "connected".to_string(),
+                        Some(mesh_ref.region().extent().to_string()),
+                    )
+                }
+                Connection::Failed(err) => (format!("failed: {}", err), None),
+                Connection::Closed => ("closed".to_string(), None),
+            },
+        };
+        let actors = self
+            .state
+            .actors
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, actor)| Ok((name.clone(), actor.bind(py).repr()?.to_string())))
+            .collect::<PyResult<_>>()?;
+        Ok(Summary {
+            state,
+            health: self.state.health(),
+            extent,
+            actors,
+        })
+    }
+}
+
+#[pymethods]
+impl PySession {
+    /// Get or create the session named `name` on `workers`, given as
+    /// ZMQ-style URLs. If a session with this name exists on different
+    /// workers, it is closed and replaced by a new session.
+    #[new]
+    fn new(name: &str, workers: Vec<String>) -> PyResult<Self> {
+        if workers.is_empty() {
+            return Err(PyValueError::new_err(format!(
+                "session {}: no workers",
+                name
+            )));
+        }
+        let mut sessions = SESSIONS.lock().unwrap();
+        if let Some(state) = sessions.get(name)
+            && state.workers == workers
+        {
+            return Ok(Self {
+                state: state.clone(),
+            });
+        }
+        let state = Arc::new(SessionState {
+            name: name.to_string(),
+            workers,
+            connection: tokio::sync::Mutex::new(Connection::Disconnected),
+            actors: Mutex::new(BTreeMap::new()),
+            health: Mutex::new(None),
+        });
+        if let Some(replaced) = sessions.insert(name.to_string(), state.clone()) {
+            replaced.close();
+        }
+        Ok(Self { state })
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.state.name
+    }
+
+    #[getter]
+    fn workers(&self) -> Vec<String> {
+        self.state.workers.clone()
+    }
+
+    /// Whether the session is attached to its workers.
+    #[getter]
+    fn connected(&self) -> bool {
+        self.state
+            .connection
+            .try_lock()
+            .is_ok_and(|connection| matches!(*connection, Connection::Connected { .. }))
+    }
+
+    /// The session's host mesh, attaching to the workers through
+    /// `instance` if the session is not yet connected. Once connected,
+    /// every call returns the same host mesh.
+    fn host_mesh(&self, instance: &PyInstance) -> PyResult<PyPythonTask> {
+        let state = self.state.clone();
+        let instance = instance.clone();
+        PyPythonTask::new(async move {
+            let mut connection = state.connection.lock().await;
+            match &*connection {
+                Connection::Connected { host_mesh, .. } => {
+                    return Ok(monarch_with_gil(|py| host_mesh.clone_ref(py)).await);
+                }
+                Connection::Closed => {
+                    return Err(PyRuntimeError::new_err(format!(
+                        "session {} was replaced",
+                        state.name
+                    )));
+                }
+                Connection::Disconnected | Connection::Failed(_) => {}
+            }
+
+            let addresses = state
+                .workers
+                .iter()
+                .map(|url| Ok(ChannelAddr::from_zmq_url(url)?.into_dial_addr()))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let id = HostMeshId::instance(Label::strip(&state.name));
+            let host_mesh = match HostMesh::attach(&*instance, id, addresses).await {
+                Ok(host_mesh) => host_mesh,
+                Err(err) => {
+                    *connection = Connection::Failed(err.to_string());
+                    return Err(PyRuntimeError::new_err(format!(
+                        "session {}: attach failed: {}",
+                        state.name, err
+                    )));
+                }
+            };
+            // Attaching pushed the config to every host.
+            *state.health.lock().unwrap() = Some(vec![true; state.workers.len()]);
+            let (host_mesh, result) = monarch_with_gil(|py| -> PyResult<_> {
+                let host_mesh = Py::new(py, PyHostMesh::new_owned(host_mesh))?;
+                let result = host_mesh.clone_ref(py);
+                Ok((host_mesh, result))
+            })
+            .await?;
+            *connection = Connection::Connected {
+                instance,
+                host_mesh,
+            };
+            Ok(result)
+        })
+    }
+
+    /// Probe the hosts of the connected session, waiting up to
+    /// `timeout` seconds for each, and return whether each host is
+    /// reachable. The result is shown in the reprs.
+    #[pyo3(signature = (instance, timeout = 5.0))]
+    fn check_health(&self, instance: &PyInstance, timeout: f64) -> PyResult<PyPythonTask> {
+        let state = self.state.clone();
+        let instance = instance.clone();
+        let timeout = Duration::try_from_secs_f64(timeout)
+            .map_err(|err| PyValueError::new_err(format!("invalid timeout: {}", err)))?;
+        PyPythonTask::new(async move {
+            let mesh_ref = {
+                let connection = state.connection.lock().await;
+                let Connection::Connected { host_mesh, .. } = &*connection else {
+                    return Err(PyRuntimeError::new_err(format!(
+                        "session {} is not connected",
+                        state.name
+                    )));
+                };
+                monarch_with_gil(|py| host_mesh.borrow(py).mesh_ref()).await?
+            };
+            let reachable = mesh_ref.probe(&*instance, timeout).await;
+            *state.health.lock().unwrap() = Some(reachable.clone());
+            Ok(reachable)
+        })
+    }
+
+    /// The mailbox of the client instance the session connected with.
+    #[getter]
+    fn mailbox(&self) -> PyResult<PyMailbox> {
+        match self.state.connection.try_lock().as_deref() {
+            Ok(Connection::Connected { instance, .. }) => Ok(instance._mailbox()),
+            _ => Err(PyRuntimeError::new_err(format!(
+                "session {} is not connected",
+                self.state.name
+            ))),
+        }
+    }
+
+    /// Return the object bound to `name`, first binding it to the
+    /// result of `factory()` if nothing is bound yet.
+    fn bind(&self, py: Python<'_>, name: &str, factory: Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        if let Some(actor) = self.state.actors.lock().unwrap().get(name) {
+            return Ok(actor.clone_ref(py));
+        }
+        // The factory runs without the lock held, as it may itself use
+        // the session.
+        let actor = factory.call0()?.unbind();
+        Ok(self
+            .state
+            .actors
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_insert(actor)
+            .clone_ref(py))
+    }
+
+    /// Remove and return the object bound to `name`, if any.
+    fn unbind(&self, name: &str) -> Option<Py<PyAny>> {
+        self.state.actors.lock().unwrap().remove(name)
+    }
+
+    /// The names of the bound objects, in order.
+    #[getter]
+    fn actors(&self) -> Vec<String> {
+        self.state.actors.lock().unwrap().keys().cloned().collect()
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        let summary = self.summary(py)?;
+        let mut repr = format!(
+            "Session(name={:?}, state={}, health={}",
+            self.state.name, summary.state, summary.health
+        );
+        if let Some(extent) = summary.extent {
+            repr.push_str(&format!(", hosts={}", extent));
+        }
+        let actors: Vec<_> = summary
+            .actors
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        repr.push_str(&format!(", actors={:?})", actors));
+        Ok(repr)
+    }
+
+    fn _repr_html_(&self, py: Python<'_>) -> PyResult<String> {
+        let summary = self.summary(py)?;
+        let mut html = String::from("<table>");
+        let mut row = |key: &str, value: &str| {
+            html.push_str(&format!(
+                "<tr><th style=\"text-align:left\">{}</th><td style=\"text-align:left\">{}</td></tr>",
+                escape_html(key),
+                escape_html(value)
+            ));
+        };
+        row("Session", &self.state.name);
+        row("State", &summary.state);
+        row("Health", &summary.health);
+        row("Workers", &self.state.workers.join(", "));
+        if let Some(extent) = &summary.extent {
+            row("Hosts", extent);
+        }
+        for (name, repr) in &summary.actors {
+            row(name, repr);
+        }
+        html.push_str("</table>");
+        Ok(html)
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn register_python_bindings(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PySession>()?;
+    Ok(())
+}
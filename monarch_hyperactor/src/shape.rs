@@ -313,6 +313,16 @@ impl PyShape {
         })
     }
 
+    fn group_by(&self, label: &str) -> PyResult<Vec<PyShape>> {
+        Ok(self
+            .inner
+            .group_by(label)
+            .map_err(|e| PyErr::new::<PyValueError, _>(e.to_string()))?
+            .into_iter()
+            .map(PyShape::from)
+            .collect())
+    }
+
     #[staticmethod]
     fn from_bytes(bytes: &Bound<'_, PyBytes>) -> PyResult<Self> {
         let shape: Shape =
@@ -335,6 +345,12 @@ impl PyShape {
         self.inner.slice().iter().collect()
     }
 
+    fn query(&self, selection: &str) -> PyResult<Vec<usize>> {
+        self.inner
+            .query(selection)
+            .map_err(|e| PyErr::new::<PyValueError, _>(e.to_string()))
+    }
+
     fn __len__(&self) -> usize {
         self.inner.slice().len()
     }
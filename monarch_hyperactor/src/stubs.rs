@@ -0,0 +1,366 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Typed Python stubs for Rust actor protocols.
+//!
+//! Rust actors that are called from Python register a
+//! [`PyActorProtocol`] describing the messages they accept. A message
+//! is described by its Rust type, through its registered
+//! [`Schema`], together with the Python types of its fields and of its
+//! reply. The field names themselves come from the schema, so a stub
+//! cannot silently drift from the Rust definition: [`generate`] fails
+//! if the registered field types no longer line up with the message's
+//! fields, or if the reply port is no longer one of them.
+//!
+//! For each protocol, [`generate`] emits:
+//!
+//! - a frozen dataclass per message;
+//! - a `<Actor>Ref` class, with one method per message, for calling a
+//!   single actor;
+//! - a `<Actor>Mesh` class, with the same methods returning value
+//!   meshes, for calling a mesh of actors.
+//!
+//! Stubs are generated per binding module, from Python:
+//!
+//! ```python
+//! from monarch._rust_bindings.monarch_hyperactor.stubs import generate_stubs
+//! print(generate_stubs("monarch_hyperactor.code_sync"))
+//! ```
+//!
+//! The generated stubs are checked in next to the other binding stubs,
+//! and a test keeps them in sync with the registered protocols.
+
+use std::fmt::Write as _;
+
+use anyhow::Context as _;
+use pyo3::prelude::*;
+use wirevalue::schema::Layout;
+use wirevalue::schema::Schema;
+
+/// A message accepted by a [`PyActorProtocol`].
+#[derive(Debug)]
+pub struct PyMessageStub {
+    /// The schema of the Rust message type, typically
+    /// `Schema::of::<Message>`.
+    pub schema: fn() -> Schema,
+    /// The name of the Python method that sends the message.
+    pub method: &'static str,
+    /// Documentation for the message.
+    pub doc: &'static str,
+    /// The Python types of the message's fields, in declaration order,
+    /// excluding the reply port.
+    pub field_types: &'static [&'static str],
+    /// The reply to the message, or `None` if the message is one-way.
+    pub reply: Option<PyReplyStub>,
+}
+
+/// The reply to a [`PyMessageStub`].
+#[derive(Debug)]
+pub struct PyReplyStub {
+    /// The name of the message field carrying the reply port. The
+    /// field is filled in by the caller, and so is not a parameter of
+    /// the Python method.
+    pub port: &'static str,
+    /// The Python type of the reply.
+    pub ty: &'static str,
+}
+
+/// The protocol of a Rust actor exposed to Python, registered with
+/// `inventory::submit!`.
+#[derive(Debug)]
+pub struct PyActorProtocol {
+    /// The Python name of the actor.
+    pub actor: &'static str,
+    /// The binding module, relative to `monarch._rust_bindings`, whose
+    /// stubs include the protocol.
+    pub module: &'static str,
+    /// Documentation for the actor.
+    pub doc: &'static str,
+    /// The messages the actor accepts.
+    pub messages: &'static [PyMessageStub],
+}
+
+inventory::collect!(PyActorProtocol);
+
+/// A message's fields, as `(name, python type)` pairs.
+fn fields(message: &PyMessageStub, schema: &Schema) -> anyhow::Result<Vec<(String, String)>> {
+    let mut names: Vec<String> = match schema.layout() {
+        Layout::Unit { .. } => Vec::new(),
+        Layout::Newtype { .. } => vec!["value".to_string()],
+        Layout::Tuple { len, .. } => (0..*len).map(|i| format!("_{}", i)).collect(),
+        Layout::Struct { fields, .. } => fields.iter().map(|f| f.to_string()).collect(),
+        layout => anyhow::bail!(
+            "message {} has unsupported layout {}",
+            schema.typename(),
+            layout
+        ),
+    };
+    if let Some(reply) = &message.reply {
+        let Some(index) = names.iter().position(|name| name == reply.port) else {
+            anyhow::bail!(
+                "message {} has no reply port field {}",
+                schema.typename(),
+                reply.port
+            );
+        };
+        names.remove(index);
+    }
+    anyhow::ensure!(
+        names.len() == message.field_types.len(),
+        "message {} has {} fields, but {} field types are registered",
+        schema.typename(),
+        names.len(),
+        message.field_types.len()
+    );
+    Ok(names
+        .into_iter()
+        .zip(message.field_types.iter().map(|ty| ty.to_string()))
+        .collect())
+}
+
+/// The Python class name of a message: the last path component of its
+/// typename.
+fn class_name(schema: &Schema) -> &str {
+    let typename = schema.typename();
+    typename.rsplit("::").next().unwrap_or(typename)
+}
+
+fn write_doc(out: &mut String, indent: &str, doc: &str) {
+    if !doc.is_empty() {
+        writeln!(out, "{}\"\"\"{}\"\"\"", indent, doc).unwrap();
+    }
+}
+
+fn write_protocol(out: &mut String, protocol: &PyActorProtocol) -> anyhow::Result<()> {
+    let mut methods = Vec::new();
+    for message in protocol.messages {
+        let schema = (message.schema)();
+        let fields =
+            fields(message, &schema).with_context(|| format!("actor {}", protocol.actor))?;
+
+        writeln!(out, "\n@final\n@dataclass(frozen=True)").unwrap();
+        writeln!(out, "class {}:", class_name(&schema)).unwrap();
+        write_doc(out, "    ", message.doc);
+        for (name, ty) in &fields {
+            writeln!(out, "    {}: {}", name, ty).unwrap();
+        }
+        if message.doc.is_empty() && fields.is_empty() {
+            writeln!(out, "    pass").unwrap();
+        }
+
+        let params: String = fields
+            .iter()
+            .map(|(name, ty)| format!(", {}: {}", name, ty))
+            .collect();
+        methods.push((message, params));
+    }
+
+    writeln!(out, "\n@final\nclass {}Ref:", protocol.actor).unwrap();
+    write_doc(out, "    ", protocol.doc);
+    for (message, params) in &methods {
+        let ret = match &message.reply {
+            Some(reply) => format!("Future[{}]", reply.ty),
+            None => "None".to_string(),
+        };
+        writeln!(
+            out,
+            "    def {}(self{}) -> {}: ...",
+            message.method, params, ret
+        )
+        .unwrap();
+    }
+    if protocol.doc.is_empty() && methods.is_empty() {
+        writeln!(out, "    pass").unwrap();
+    }
+
+    writeln!(out, "\n@final\nclass {}Mesh:", protocol.actor).unwrap();
+    write_doc(out, "    ", protocol.doc);
+    for (message, params) in &methods {
+        let ret = match &message.reply {
+            Some(reply) => format!("Future[ValueMesh[{}]]", reply.ty),
+            None => "None".to_string(),
+        };
+        writeln!(
+            out,
+            "    def {}(self{}) -> {}: ...",
+            message.method, params, ret
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "    def slice(self, **kwargs: int | slice) -> {}Mesh: ...",
+        protocol.actor
+    )
+    .unwrap();
+    Ok(())
+}
+
+/// Generate the stubs for the protocols registered in `module`, in
+/// actor name order.
+pub fn generate(module: &str) -> anyhow::Result<String> {
+    let mut protocols: Vec<_> = inventory::iter::<PyActorProtocol>()
+        .filter(|protocol| protocol.module == module)
+        .collect();
+    protocols.sort_by_key(|protocol| protocol.actor);
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "# @{} by monarch_hyperactor::stubs from the protocols registered in {}.",
+        "generated", module
+    )
+    .unwrap();
+    writeln!(out, "\nfrom dataclasses import dataclass").unwrap();
+    writeln!(out, "from typing import final").unwrap();
+    writeln!(out, "\nfrom monarch._src.actor.actor_mesh import ValueMesh").unwrap();
+    writeln!(out, "from monarch._src.actor.future import Future").unwrap();
+    for protocol in protocols {
+        write_protocol(&mut out, protocol)?;
+    }
+    Ok(out)
+}
+
+/// Generate the typed stubs for the actor protocols registered in the
+/// given binding module.
+#[pyfunction]
+#[pyo3(name = "generate_stubs")]
+fn py_generate_stubs(module: &str) -> PyResult<String> {
+    Ok(generate(module)?)
+}
+
+pub fn register_python_bindings(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(py_generate_stubs, module)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyperactor::PortRef;
+    use serde::Deserialize;
+    use typeuri::Named;
+
+    use super::*;
+
+    #[derive(Deserialize, Named)]
+    struct Echo {
+        #[allow(dead_code)]
+        text: String,
+        #[allow(dead_code)]
+        times: usize,
+        #[allow(dead_code)]
+        reply: PortRef<String>,
+    }
+
+    #[derive(Deserialize, Named)]
+    struct Flush;
+
+    inventory::submit! {
+        PyActorProtocol {
+            actor: "EchoActor",
+            module: "test.stubs",
+            doc: "Echoes text.",
+            messages: &[
+                PyMessageStub {
+                    schema: Schema::of::<Echo>,
+                    method: "echo",
+                    doc: "Echo `text`, `times` times.",
+                    field_types: &["str", "int"],
+                    reply: Some(PyReplyStub {
+                        port: "reply",
+                        ty: "str",
+                    }),
+                },
+                PyMessageStub {
+                    schema: Schema::of::<Flush>,
+                    method: "flush",
+                    doc: "",
+                    field_types: &[],
+                    reply: None,
+                },
+            ],
+        }
+    }
+
+    inventory::submit! {
+        PyActorProtocol {
+            actor: "StaleActor",
+            module: "test.stubs.stale",
+            doc: "",
+            messages: &[PyMessageStub {
+                schema: Schema::of::<Echo>,
+                method: "echo",
+                doc: "",
+                field_types: &["str"],
+                reply: Some(PyReplyStub {
+                    port: "reply",
+                    ty: "str",
+                }),
+            }],
+        }
+    }
+
+    inventory::submit! {
+        PyActorProtocol {
+            actor: "StaleReplyActor",
+            module: "test.stubs.stale_reply",
+            doc: "",
+            messages: &[PyMessageStub {
+                schema: Schema::of::<Echo>,
+                method: "echo",
+                doc: "",
+                field_types: &["str", "int"],
+                reply: Some(PyReplyStub {
+                    port: "result",
+                    ty: "str",
+                }),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_generate() {
+        let stubs = generate("test.stubs").unwrap();
+        assert!(stubs.contains(
+            "@dataclass(frozen=True)\nclass Echo:\n    \"\"\"Echo `text`, `times` times.\"\"\"\n    text: str\n    times: int\n"
+        ));
+        assert!(stubs.contains("class Flush:\n    pass\n"));
+        assert!(stubs.contains("class EchoActorRef:"));
+        assert!(stubs.contains("    def echo(self, text: str, times: int) -> Future[str]: ..."));
+        assert!(stubs.contains("    def flush(self) -> None: ..."));
+        assert!(stubs.contains("class EchoActorMesh:"));
+        assert!(
+            stubs.contains(
+                "    def echo(self, text: str, times: int) -> Future[ValueMesh[str]]: ..."
+            )
+        );
+        assert!(stubs.contains("    def slice(self, **kwargs: int | slice) -> EchoActorMesh: ..."));
+        assert!(!stubs.contains("StaleActor"));
+    }
+
+    #[test]
+    fn test_generate_stale_fields() {
+        let err = generate("test.stubs.stale").unwrap_err();
+        assert!(format!("{:#}", err).contains("has 2 fields, but 1 field types are registered"));
+    }
+
+    #[test]
+    fn test_generate_stale_reply() {
+        let err = generate("test.stubs.stale_reply").unwrap_err();
+        assert!(format!("{:#}", err).contains("has no reply port field result"));
+    }
+
+    #[test]
+    fn test_checked_in_stubs() {
+        // Regenerate with `generate_stubs("monarch_hyperactor.code_sync")`.
+        assert_eq!(
+            generate("monarch_hyperactor.code_sync").unwrap(),
+            include_str!("../../python/monarch/_rust_bindings/monarch_hyperactor/code_sync.pyi")
+        );
+    }
+}
@@ -371,6 +371,8 @@ mod tests {
                         flight_recorder: None,
                         instance_id: String::new(),
                         queue_depth: 0,
+                        queued_bytes: 0,
+                        queued_bytes_by_port: Default::default(),
                         inbound_ordering: None,
                         is_system: false,
                         failure_info: None,
@@ -393,6 +395,8 @@ mod tests {
                         flight_recorder: None,
                         instance_id: String::new(),
                         queue_depth: 0,
+                        queued_bytes: 0,
+                        queued_bytes_by_port: Default::default(),
                         inbound_ordering: None,
                         is_system: false,
                         failure_info: None,
@@ -488,6 +492,8 @@ mod tests {
                         flight_recorder: None,
                         instance_id: String::new(),
                         queue_depth: 0,
+                        queued_bytes: 0,
+                        queued_bytes_by_port: Default::default(),
                         inbound_ordering: None,
                         is_system: false,
                         failure_info: None,
@@ -510,6 +516,8 @@ mod tests {
                         flight_recorder: None,
                         instance_id: String::new(),
                         queue_depth: 0,
+                        queued_bytes: 0,
+                        queued_bytes_by_port: Default::default(),
                         inbound_ordering: None,
                         is_system: false,
                         failure_info: None,
@@ -532,6 +540,8 @@ mod tests {
                         flight_recorder: None,
                         instance_id: String::new(),
                         queue_depth: 0,
+                        queued_bytes: 0,
+                        queued_bytes_by_port: Default::default(),
                         inbound_ordering: None,
                         is_system: false,
                         failure_info: None,
@@ -629,6 +639,8 @@ mod tests {
                         flight_recorder: None,
                         instance_id: String::new(),
                         queue_depth: 0,
+                        queued_bytes: 0,
+                        queued_bytes_by_port: Default::default(),
                         inbound_ordering: None,
                         is_system: false,
                         failure_info: None,
@@ -651,6 +663,8 @@ mod tests {
                         flight_recorder: None,
                         instance_id: String::new(),
                         queue_depth: 0,
+                        queued_bytes: 0,
+                        queued_bytes_by_port: Default::default(),
                         inbound_ordering: None,
                         is_system: false,
                         failure_info: None,
@@ -598,6 +598,8 @@ mod tests {
                 flight_recorder: None,
                 instance_id: String::new(),
                 queue_depth: 0,
+                queued_bytes: 0,
+                queued_bytes_by_port: Default::default(),
                 inbound_ordering: None,
                 is_system: false,
                 failure_info: None,
@@ -648,6 +650,8 @@ mod tests {
                 flight_recorder: None,
                 instance_id: "019e5661-7d33-7380-9afe-699ffc567531".to_owned(),
                 queue_depth: 8,
+                queued_bytes: 0,
+                queued_bytes_by_port: Default::default(),
                 is_system: false,
                 inbound_ordering: Some(Box::new(hyperactor_mesh::introspect::InboundOrdering {
                     enabled: true,
@@ -748,6 +752,8 @@ mod tests {
                 last_message_handler: None,
                 total_processing_time_us: 500,
                 queue_depth: 0,
+                queued_bytes: 0,
+                queued_bytes_by_port: Default::default(),
                 flight_recorder: Some("trace-abc".to_owned()),
                 inbound_ordering: None,
                 is_system: true,
@@ -959,6 +965,8 @@ mod tests {
                     last_message_handler: None,
                     total_processing_time_us: 0,
                     queue_depth: 0,
+                    queued_bytes: 0,
+                    queued_bytes_by_port: Default::default(),
                     flight_recorder: None,
                     inbound_ordering: None,
                     is_system: false,
@@ -1008,6 +1016,8 @@ mod tests {
                 flight_recorder: None,
                 instance_id: String::new(),
                 queue_depth: 0,
+                queued_bytes: 0,
+                queued_bytes_by_port: Default::default(),
                 inbound_ordering: None,
                 is_system: false,
                 failure_info: None,
@@ -547,6 +547,8 @@ mod tests {
                     last_message_handler: Some("handle_msg".to_owned()),
                     total_processing_time_us: 5000,
                     queue_depth: 0,
+                    queued_bytes: 0,
+                    queued_bytes_by_port: Default::default(),
                     flight_recorder: None,
                     inbound_ordering: None,
                     is_system: false,
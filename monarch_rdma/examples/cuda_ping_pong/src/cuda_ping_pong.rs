@@ -79,6 +79,7 @@ use hyperactor_mesh::Bootstrap;
 use hyperactor_mesh::HostMeshRef;
 use hyperactor_mesh::ProcMesh;
 use hyperactor_mesh::context;
+use hyperactor_mesh::handshake::Hello;
 use hyperactor_mesh::host_mesh::HostMesh;
 use hyperactor_mesh::mesh_id::HostMeshId;
 use monarch_rdma::IbvConfig;
@@ -787,6 +788,7 @@ pub async fn run() -> Result<(), anyhow::Error> {
             command: None,
             config: None,
             exit_on_shutdown: false,
+            hello: Some(Hello::local()),
         };
         boot.to_env(&mut cmd);
         cmd.kill_on_drop(true);
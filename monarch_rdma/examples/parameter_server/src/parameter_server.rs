@@ -78,6 +78,7 @@ use hyperactor_mesh::Bootstrap;
 use hyperactor_mesh::HostMeshRef;
 use hyperactor_mesh::comm::multicast::CastInfo;
 use hyperactor_mesh::context;
+use hyperactor_mesh::handshake::Hello;
 use hyperactor_mesh::mesh_id::HostMeshId;
 use monarch_rdma::IbvConfig;
 use monarch_rdma::RdmaManagerActor;
@@ -511,6 +512,7 @@ pub async fn run(num_workers: usize, num_steps: usize) -> Result<(), anyhow::Err
         command: None, // use current binary
         config: None,
         exit_on_shutdown: false,
+        hello: Some(Hello::local()),
     };
     boot.to_env(&mut command);
     command.kill_on_drop(true);
@@ -201,6 +201,8 @@ pub mod entity_tables {
         pub to_actor_id: u64,
         pub endpoint: Option<String>,
         pub port_index: Option<u64>,
+        pub parent_message_id: Option<u64>,
+        pub root_message_id: Option<u64>,
     }
 
     /// Row data for the message status events table.
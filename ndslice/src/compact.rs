@@ -0,0 +1,364 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Compact binary encodings of points and shapes.
+//!
+//! The derived serde encodings of [`Point`] and [`Shape`] spend a
+//! fixed eight bytes on every integer and length, which dominates the
+//! size of small messages that carry them in their headers. The
+//! encodings here use variable-length integers instead, and encode a
+//! shape's strides as deltas from the row-major strides implied by its
+//! sizes, so that the strides of contiguous shapes cost one byte each.
+//!
+//! [`Compact`] wraps a point or a shape so that it serializes in this
+//! encoding, for example as a header value.
+
+use std::fmt;
+
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::Serializer;
+use serde::de;
+
+use crate::Point;
+use crate::Shape;
+use crate::Slice;
+use crate::view::Extent;
+
+/// Errors that occur while decoding compact encodings.
+#[derive(Debug, thiserror::Error)]
+pub enum CompactError {
+    /// The encoding ended before the value was decoded.
+    #[error("truncated compact encoding")]
+    Truncated,
+    /// The encoding has bytes after the value.
+    #[error("{0} trailing bytes after compact encoding")]
+    Trailing(usize),
+    /// An integer does not fit in its type.
+    #[error("integer overflow in compact encoding")]
+    Overflow,
+    /// A label is not valid UTF-8.
+    #[error("invalid label in compact encoding")]
+    InvalidLabel,
+    /// The decoded value is invalid.
+    #[error("invalid value in compact encoding: {0}")]
+    Invalid(String),
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn put_usize(buf: &mut Vec<u8>, value: usize) {
+    put_varint(buf, value as u64);
+}
+
+/// A cursor over an encoding being decoded.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn varint(&mut self) -> Result<u64, CompactError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.0.split_first().ok_or(CompactError::Truncated)?;
+            self.0 = rest;
+            value |= u64::from(byte & 0x7f)
+                .checked_shl(shift)
+                .ok_or(CompactError::Overflow)?;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(CompactError::Overflow)
+    }
+
+    fn usize(&mut self) -> Result<usize, CompactError> {
+        usize::try_from(self.varint()?).map_err(|_| CompactError::Overflow)
+    }
+
+    fn label(&mut self) -> Result<String, CompactError> {
+        let len = self.usize()?;
+        if len > self.0.len() {
+            return Err(CompactError::Truncated);
+        }
+        let (label, rest) = self.0.split_at(len);
+        self.0 = rest;
+        String::from_utf8(label.to_vec()).map_err(|_| CompactError::InvalidLabel)
+    }
+
+    fn finish(self) -> Result<(), CompactError> {
+        match self.0.len() {
+            0 => Ok(()),
+            n => Err(CompactError::Trailing(n)),
+        }
+    }
+}
+
+fn put_labels(buf: &mut Vec<u8>, labels: &[String]) {
+    put_usize(buf, labels.len());
+    for label in labels {
+        put_usize(buf, label.len());
+        buf.extend_from_slice(label.as_bytes());
+    }
+}
+
+fn read_labels(reader: &mut Reader<'_>) -> Result<Vec<String>, CompactError> {
+    let num_dims = reader.usize()?;
+    (0..num_dims).map(|_| reader.label()).collect()
+}
+
+/// Encode `point` as its extent's labels and sizes, followed by its
+/// rank.
+pub fn encode_point(point: &Point) -> Vec<u8> {
+    let extent = point.extent();
+    let mut buf = Vec::new();
+    put_labels(&mut buf, extent.labels());
+    for &size in extent.sizes() {
+        put_usize(&mut buf, size);
+    }
+    put_usize(&mut buf, point.rank());
+    buf
+}
+
+/// Decode a point encoded with [`encode_point`].
+pub fn decode_point(bytes: &[u8]) -> Result<Point, CompactError> {
+    let mut reader = Reader(bytes);
+    let labels = read_labels(&mut reader)?;
+    let sizes = (0..labels.len())
+        .map(|_| reader.usize())
+        .collect::<Result<Vec<_>, _>>()?;
+    let rank = reader.usize()?;
+    reader.finish()?;
+    Extent::new(labels, sizes)
+        .map_err(|err| CompactError::Invalid(err.to_string()))?
+        .point_of_rank(rank)
+        .map_err(|err| CompactError::Invalid(err.to_string()))
+}
+
+/// Zigzag-encode a signed delta so that small deltas of either sign
+/// are small.
+fn zigzag(delta: i64) -> u64 {
+    ((delta << 1) ^ (delta >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+/// Encode `shape` as its labels, offset, sizes, and strides, each
+/// stride as its delta from the corresponding row-major stride.
+pub fn encode_shape(shape: &Shape) -> Vec<u8> {
+    let slice = shape.slice();
+    let row_major = Slice::new_row_major(slice.sizes());
+    let mut buf = Vec::new();
+    put_labels(&mut buf, shape.labels());
+    put_usize(&mut buf, slice.offset());
+    for &size in slice.sizes() {
+        put_usize(&mut buf, size);
+    }
+    for (&stride, &expected) in slice.strides().iter().zip(row_major.strides()) {
+        put_varint(&mut buf, zigzag(stride as i64 - expected as i64));
+    }
+    buf
+}
+
+/// Decode a shape encoded with [`encode_shape`].
+pub fn decode_shape(bytes: &[u8]) -> Result<Shape, CompactError> {
+    let mut reader = Reader(bytes);
+    let labels = read_labels(&mut reader)?;
+    let offset = reader.usize()?;
+    let sizes = (0..labels.len())
+        .map(|_| reader.usize())
+        .collect::<Result<Vec<_>, _>>()?;
+    let row_major = Slice::new_row_major(sizes.clone());
+    let strides = row_major
+        .strides()
+        .iter()
+        .map(|&expected| {
+            let stride = expected as i64 + unzigzag(reader.varint()?);
+            usize::try_from(stride).map_err(|_| CompactError::Overflow)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    reader.finish()?;
+    let slice =
+        Slice::new(offset, sizes, strides).map_err(|err| CompactError::Invalid(err.to_string()))?;
+    Shape::new(labels, slice).map_err(|err| CompactError::Invalid(err.to_string()))
+}
+
+/// A value with a compact encoding.
+pub trait CompactEncode: Sized {
+    /// Encode the value.
+    fn encode_compact(&self) -> Vec<u8>;
+    /// Decode a value encoded with [`CompactEncode::encode_compact`].
+    fn decode_compact(bytes: &[u8]) -> Result<Self, CompactError>;
+}
+
+impl CompactEncode for Point {
+    fn encode_compact(&self) -> Vec<u8> {
+        encode_point(self)
+    }
+
+    fn decode_compact(bytes: &[u8]) -> Result<Self, CompactError> {
+        decode_point(bytes)
+    }
+}
+
+impl CompactEncode for Shape {
+    fn encode_compact(&self) -> Vec<u8> {
+        encode_shape(self)
+    }
+
+    fn decode_compact(bytes: &[u8]) -> Result<Self, CompactError> {
+        decode_shape(bytes)
+    }
+}
+
+/// A point or shape that serializes in its compact encoding.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Compact<T>(pub T);
+
+impl<T> Compact<T> {
+    /// The wrapped value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Compact<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Compact<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: CompactEncode> Serialize for Compact<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0.encode_compact())
+    }
+}
+
+impl<'de, T: CompactEncode> Deserialize<'de> for Compact<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> de::Visitor<'de> for BytesVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a compact encoding")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Ok(v.to_vec())
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(v)
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(bytes)
+            }
+        }
+
+        let bytes = deserializer.deserialize_bytes(BytesVisitor)?;
+        T::decode_compact(&bytes)
+            .map(Compact)
+            .map_err(de::Error::custom)
+    }
+}
+
+impl typeuri::Named for Compact<Point> {
+    fn typename() -> &'static str {
+        "ndslice::Compact<ndslice::Point>"
+    }
+}
+
+impl typeuri::Named for Compact<Shape> {
+    fn typename() -> &'static str {
+        "ndslice::Compact<ndslice::Shape>"
+    }
+}
+
+impl<T> hyperactor_config::attrs::AttrValue for Compact<T>
+where
+    T: hyperactor_config::attrs::AttrValue + CompactEncode,
+    Compact<T>: typeuri::Named,
+{
+    fn display(&self) -> String {
+        self.0.display()
+    }
+
+    fn parse(value: &str) -> Result<Self, anyhow::Error> {
+        T::parse(value).map(Compact)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extent;
+    use crate::shape;
+
+    #[test]
+    fn test_point_round_trip() {
+        let extent = extent!(hosts = 16, gpus = 8);
+        for rank in [0, 1, 77, 127] {
+            let point = extent.point_of_rank(rank).unwrap();
+            let encoded = encode_point(&point);
+            assert_eq!(decode_point(&encoded).unwrap(), point);
+        }
+        let point = extent.point_of_rank(77).unwrap();
+        // 1 + (1 + 5) + (1 + 4) dims and labels, 2 sizes, 1 rank.
+        assert_eq!(encode_point(&point).len(), 15);
+        let unity = Extent::unity().point_of_rank(0).unwrap();
+        assert_eq!(decode_point(&encode_point(&unity)).unwrap(), unity);
+
+        let encoded = encode_point(&point);
+        assert!(matches!(
+            decode_point(&encoded[..encoded.len() - 1]),
+            Err(CompactError::Truncated)
+        ));
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert!(matches!(
+            decode_point(&trailing),
+            Err(CompactError::Trailing(1))
+        ));
+    }
+
+    #[test]
+    fn test_shape_round_trip() {
+        let shape = shape!(zone = 2, host = 4, gpu = 8);
+        let encoded = encode_shape(&shape);
+        assert_eq!(decode_shape(&encoded).unwrap(), shape);
+        // Row-major strides cost one byte each.
+        assert_eq!(encoded.len(), 1 + (1 + 4) + (1 + 4) + (1 + 3) + 1 + 3 + 3);
+
+        let strided = shape
+            .select("gpu", crate::shape::Range(1, None, 2))
+            .unwrap();
+        assert_eq!(decode_shape(&encode_shape(&strided)).unwrap(), strided);
+        let sliced = shape.at("host", 3).unwrap();
+        assert_eq!(decode_shape(&encode_shape(&sliced)).unwrap(), sliced);
+    }
+}
@@ -48,11 +48,34 @@
 //!     - The union applies at the top level, not just within a dimension.
 //!     - To apply the union **only to the third dimension**, parentheses must be used:
 //!       e.g., `*,*,(1:4|5:6)`
+//!
+//! ## Labeled selections
+//!
+//! When the labels of the target shape are known, dimensions may
+//! instead be addressed by name (see [`parse_labeled`]):
+//! ```text
+//! labeled          ::= term ( "," term )*
+//! term             ::= label "=" dimension
+//! dimension        ::= dim_intersection ( "|" dim_intersection )*
+//! dim_intersection ::= dim_group ( "&" dim_group )*
+//! dim_group        ::= range
+//!                    | index
+//!                    | wildcard
+//!                    | any
+//!                    | "(" dimension ")"
+//! label            ::= [A-Za-z0-9_-]+
+//! ```
+//!
+//! For example, `host=0:8, gpu=*` selects every GPU on the first 8
+//! hosts. Each term constrains only its own dimension: terms may
+//! appear in any order, each label may appear at most once, and
+//! dimensions that are not mentioned are selected in full (`*`).
 
 use nom::IResult;
 use nom::Parser as _;
 use nom::branch::alt;
 use nom::bytes::complete::tag;
+use nom::bytes::complete::take_while1;
 use nom::character::complete::char;
 use nom::character::complete::digit1;
 use nom::combinator::map;
@@ -61,6 +84,7 @@ use nom::combinator::opt;
 use nom::multi::separated_list1;
 use nom::sequence::delimited;
 use nom::sequence::preceded;
+use nom::sequence::separated_pair;
 
 use crate::selection::Selection;
 use crate::selection::dsl;
@@ -179,6 +203,85 @@ pub fn parse(input: &str) -> anyhow::Result<Selection> {
     Ok(selection)
 }
 
+// A selection within a single dimension of a labeled selection.
+fn dimension(input: &str) -> IResult<&str, Selection> {
+    map(separated_list1(char('|'), dim_intersection), |items| {
+        items.into_iter().reduce(dsl::union).unwrap()
+    })
+    .parse(input)
+}
+
+fn dim_intersection(input: &str) -> IResult<&str, Selection> {
+    map(separated_list1(char('&'), dim_group), |items| {
+        items.into_iter().reduce(dsl::intersection).unwrap()
+    })
+    .parse(input)
+}
+
+fn dim_group(input: &str) -> IResult<&str, Selection> {
+    alt((
+        delimited(char('('), dimension, char(')')),
+        range,
+        index,
+        wildcard,
+        any,
+    ))
+    .parse(input)
+}
+
+fn label(input: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| c.is_alphanumeric() || c == '_' || c == '-').parse(input)
+}
+
+fn labeled(input: &str) -> IResult<&str, Vec<(&str, Selection)>> {
+    separated_list1(char(','), separated_pair(label, char('='), dimension)).parse(input)
+}
+
+/// Parses a labeled selection expression such as `host=0:8, gpu=*`
+/// from a string, ignoring all whitespace. Terms are resolved against
+/// `labels`, the labels of the shape being selected from, in order;
+/// dimensions without a term are selected in full.
+///
+/// # Arguments
+///
+/// * `input` - A string slice containing the labeled selection
+///   expression to parse.
+/// * `labels` - The dimension labels of the target shape.
+///
+/// # Returns
+///
+/// * `Ok(Selection)` if parsing succeeds
+/// * `Err(anyhow::Error)` if the input is malformed, or names a label
+///   that is unknown or repeated
+pub fn parse_labeled(input: &str, labels: &[String]) -> anyhow::Result<Selection> {
+    use nom::combinator::all_consuming;
+
+    let input: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+    let (_, terms) = all_consuming(labeled).parse(&input).map_err(|err| {
+        anyhow::anyhow!("Failed to parse labeled selection: {err:?} (input: {input:?})")
+    })?;
+
+    let mut dims: Vec<Option<Selection>> = vec![None; labels.len()];
+    for (label, selection) in terms {
+        let Some(position) = labels.iter().position(|l| l == label) else {
+            anyhow::bail!("unknown label {label:?}; expected one of {labels:?}");
+        };
+        anyhow::ensure!(
+            dims[position].is_none(),
+            "label {label:?} is selected more than once"
+        );
+        dims[position] = Some(selection);
+    }
+
+    Ok(dims
+        .into_iter()
+        .rev()
+        .fold(dsl::true_(), |acc, dim| match dim {
+            Some(dim) => nest(dim, acc),
+            None => dsl::all(acc),
+        }))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::selection::Selection;
@@ -312,6 +415,40 @@ mod tests {
         assert_parses_to!("((1:4),2)", range(1..4, range(2, true_())));
     }
 
+    #[test]
+    fn test_labeled() {
+        use crate::selection::dsl::*;
+
+        let labels = vec!["zone".to_string(), "host".to_string(), "gpu".to_string()];
+        let parse_labeled = |input| super::parse_labeled(input, &labels).unwrap();
+
+        crate::assert_structurally_eq!(
+            parse_labeled("host=0:8, gpu=*"),
+            all(range(0..8, all(true_())))
+        );
+        crate::assert_structurally_eq!(
+            parse_labeled("gpu=1::2,zone=0"),
+            range(0, all(range(shape::Range(1, None, 2), true_())))
+        );
+        crate::assert_structurally_eq!(
+            parse_labeled("host=(0|3),gpu=?"),
+            all(union(range(0, any(true_())), range(3, any(true_()))))
+        );
+        crate::assert_structurally_eq!(
+            parse_labeled("zone=0:2&1:"),
+            intersection(
+                range(0..2, all(all(true_()))),
+                range(shape::Range(1, None, 1), all(all(true_())))
+            )
+        );
+
+        assert!(super::parse_labeled("rack=0", &labels).is_err());
+        assert!(super::parse_labeled("host=0,host=1", &labels).is_err());
+        assert!(super::parse_labeled("host=0,1", &labels).is_err());
+        assert!(super::parse_labeled("host=(0,1)", &labels).is_err());
+        assert!(super::parse_labeled("", &labels).is_err());
+    }
+
     #[test]
     fn test_12() {
         use crate::dsl::all;
@@ -16,6 +16,7 @@ use crate::DimSliceIterator;
 use crate::Region;
 use crate::Slice;
 use crate::SliceError;
+use crate::selection::EvalOpts;
 use crate::selection::Selection;
 use crate::view::Extent;
 
@@ -55,6 +56,9 @@ pub enum ShapeError {
     #[error("failed to parse shape: {reason}")]
     ParseError { reason: String },
 
+    #[error("failed to parse selection: {reason}")]
+    SelectionParseError { reason: String },
+
     #[error(transparent)]
     SliceError(#[from] SliceError),
 }
@@ -129,6 +133,21 @@ impl Shape {
         Ok(Self { labels, slice })
     }
 
+    /// Partition this shape along a named dimension, producing one
+    /// subshape per index of the dimension, in order. Each subshape
+    /// retains the dimension with size 1, so its labels continue to
+    /// describe its ranks.
+    ///
+    /// For example, `shape.select("host", 3..5)?.group_by("gpu")?`
+    /// yields, for each GPU index, the shape of that GPU on hosts 3
+    /// and 4.
+    pub fn group_by(&self, label: &str) -> Result<Vec<Self>, ShapeError> {
+        let dim = self.dim(label)?;
+        (0..self.slice.sizes()[dim])
+            .map(|index| self.select(label, index))
+            .collect()
+    }
+
     /// Produces an iterator over subshapes by fixing the first `dims`
     /// dimensions.
     ///
@@ -169,6 +188,33 @@ impl Shape {
         Ok(shape)
     }
 
+    /// Parse a textual selection over this shape. Expressions containing
+    /// `=` are labeled selections such as `host=0:8, gpu=*`, resolved
+    /// against this shape's labels; all others use the positional
+    /// syntax. See [`crate::selection::parse`] for both.
+    pub fn parse_selection(&self, expr: &str) -> Result<Selection, ShapeError> {
+        let result = if expr.contains('=') {
+            crate::selection::parse::parse_labeled(expr, &self.labels)
+        } else {
+            crate::selection::parse::parse(expr)
+        };
+        result.map_err(|err| ShapeError::SelectionParseError {
+            reason: err.to_string(),
+        })
+    }
+
+    /// The ranks selected by the textual selection `expr` (see
+    /// [`Shape::parse_selection`]), in ascending order. Ranges that
+    /// are empty or begin outside of their dimension are errors, as
+    /// with [`Shape::select`].
+    pub fn query(&self, expr: &str) -> Result<Vec<usize>, ShapeError> {
+        let selection = self.parse_selection(expr)?;
+        let mut ranks: Vec<usize> = selection.eval(&EvalOpts::strict(), &self.slice)?.collect();
+        ranks.sort_unstable();
+        ranks.dedup();
+        Ok(ranks)
+    }
+
     /// The per-dimension labels of this shape.
     pub fn labels(&self) -> &[String] {
         &self.labels
@@ -814,4 +860,47 @@ mod tests {
             assert!(result.is_err(), "expected error for input: {}", input);
         }
     }
+
+    #[test]
+    fn test_group_by() {
+        let s = shape!(host = 8, gpu = 4);
+        let groups = s.select("host", 3..5).unwrap().group_by("gpu").unwrap();
+        assert_eq!(groups.len(), 4);
+        for (gpu, group) in groups.iter().enumerate() {
+            assert_eq!(group.labels(), s.labels());
+            assert_eq!(group.slice().sizes(), &[2, 1]);
+            assert_eq!(
+                group.slice().iter().collect::<Vec<_>>(),
+                vec![3 * 4 + gpu, 4 * 4 + gpu]
+            );
+        }
+
+        assert_matches!(s.group_by("zone"), Err(ShapeError::InvalidLabels { .. }));
+    }
+
+    #[test]
+    fn test_query() {
+        let s = shape!(host = 4, gpu = 8);
+
+        assert_eq!(s.query("host=1, gpu=0:2").unwrap(), vec![8, 9]);
+        assert_eq!(s.query("gpu=7").unwrap(), vec![7, 15, 23, 31]);
+        assert_eq!(s.query("host=(0|3), gpu=::4").unwrap(), vec![0, 4, 24, 28]);
+        assert_eq!(s.query("1:3, 6:").unwrap(), vec![14, 15, 22, 23]);
+        assert_eq!(s.query("*").unwrap(), (0..32).collect::<Vec<_>>());
+        assert_eq!(s.query("gpu=?").unwrap().len(), 4);
+
+        // Queries over a subshape yield ranks in the base slice.
+        let sub = s.select("host", 2..4).unwrap();
+        assert_eq!(sub.query("host=0, gpu=1").unwrap(), vec![17]);
+
+        assert_matches!(
+            s.query("rack=0"),
+            Err(ShapeError::SelectionParseError { .. })
+        );
+        assert_matches!(
+            s.query("host=0,"),
+            Err(ShapeError::SelectionParseError { .. })
+        );
+        assert_matches!(s.query("host=4"), Err(ShapeError::EmptyRange { .. }));
+    }
 }
@@ -703,6 +703,23 @@ impl Point {
             .expect("coord(i): axis out of bounds")
     }
 
+    /// Returns the coordinate of this [`Point`] along the dimension
+    /// with the given label, or `None` if its [`Extent`] has no such
+    /// dimension.
+    ///
+    /// # Examples
+    /// ```
+    /// use ndslice::extent;
+    ///
+    /// let ext = extent!(host = 2, gpu = 4);
+    /// let point = ext.point(vec![1, 3]).unwrap();
+    /// assert_eq!(point.coord_of("gpu"), Some(3));
+    /// assert_eq!(point.coord_of("zone"), None);
+    /// ```
+    pub fn coord_of(&self, label: &str) -> Option<usize> {
+        self.extent.position(label).map(|i| self.coord(i))
+    }
+
     /// Returns the full coordinate vector for this [`Point`]
     /// (allocates).
     ///
@@ -1082,6 +1099,12 @@ pub struct Region {
     slice: Slice,
 }
 
+impl typeuri::Named for Region {
+    fn typename() -> &'static str {
+        "ndslice::Region"
+    }
+}
+
 impl Region {
     #[allow(dead_code)]
     fn empty() -> Region {
@@ -39,8 +39,23 @@ class ActorMeshProtocol(Protocol):
         message: PendingMessage,
         selection: str,
         instance: Instance,
+        permit: SendPermit | None = None,
     ) -> None:
-        """Cast a PendingMessage (which may contain unresolved async values) to actors."""
+        """
+        Cast a PendingMessage (which may contain unresolved async values) to actors.
+
+        If the mesh already has `python_cast_queue_capacity` casts pending,
+        calls whose results are awaited wait for room, in order, and other
+        casts raise QueueFull, unless a permit from `acquire_send_permit`
+        is given.
+        """
+        ...
+
+    def acquire_send_permit(self) -> PythonTask[SendPermit]:
+        """
+        Wait until the mesh has room for one more cast, and return a permit
+        to pass to `cast_unresolved`.
+        """
         ...
     # pyrefly: ignore [not-a-type]
     def new_with_region(self, region: Region) -> Self: ...
@@ -51,6 +66,43 @@ class ActorMeshProtocol(Protocol):
 class PythonActorMesh(ActorMeshProtocol):
     pass
 
+class QueueFull(RuntimeError):
+    """
+    Raised when casting to a mesh whose queue of pending casts is full,
+    or by calls to an actor that rejected the message because its own
+    message queue was full.
+    """
+
+    def __init__(
+        self, capacity: int | None = None, retry_after: float | None = None
+    ) -> None: ...
+    @property
+    def capacity(self) -> int | None:
+        """
+        The number of casts the mesh may have pending, or None if the
+        destination actor rejected the message.
+        """
+        ...
+
+    @property
+    def retry_after(self) -> float | None:
+        """
+        Estimated seconds until the queue has room for another cast, or
+        None if there is nothing to estimate from.
+        """
+        ...
+
+@final
+class SendPermit:
+    """
+    Room for one cast, reserved by `acquire_send_permit`. Dropping or
+    releasing an unused permit returns the room to the mesh.
+    """
+
+    def release(self) -> None:
+        """Return the permit's room to the mesh without casting."""
+        ...
+
 @final
 class ActorSupervisionEvent:
     @property
@@ -6,7 +6,7 @@
 
 # pyre-strict
 
-from typing import final
+from typing import Any, final
 
 class FrozenBuffer:
     """
@@ -170,6 +170,30 @@ class Buffer:
         """
         ...
 
+    @property
+    def tensor_views(self) -> bool:
+        """
+        Whether objects pickled into the buffer write the contents of host
+        tensors with `write_view`, as configured by `pickle_tensor_views` when
+        the buffer was created.
+        """
+        ...
+
+    def write_view(self, tensor: Any) -> int:
+        """
+        Write the contents of a tensor or array to the buffer, in row-major order.
+
+        `tensor` must support DLPack (`__dlpack__`), as torch tensors and numpy
+        arrays do, and must be in host memory. Contiguous tensors are stored as
+        zero-copy views, copied only if the message is sent to another process;
+        they must not be modified until the message has been delivered.
+        Non-contiguous tensors are copied.
+
+        Returns:
+        The number of bytes written
+        """
+        ...
+
     def __len__(self) -> int:
         """
         Return the total number of bytes in the buffer.
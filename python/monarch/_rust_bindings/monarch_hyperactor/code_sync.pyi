@@ -0,0 +1,23 @@
+# @generated by monarch_hyperactor::stubs from the protocols registered in monarch_hyperactor.code_sync.
+
+from dataclasses import dataclass
+from typing import final
+
+from monarch._src.actor.actor_mesh import ValueMesh
+from monarch._src.actor.future import Future
+
+@final
+@dataclass(frozen=True)
+class AutoReloadMessage:
+    """Reload the modules that changed since the last reload."""
+
+@final
+class AutoReloadActorRef:
+    """Reloads the Python modules changed by code sync."""
+    def reload(self) -> Future[None]: ...
+
+@final
+class AutoReloadActorMesh:
+    """Reloads the Python modules changed by code sync."""
+    def reload(self) -> Future[ValueMesh[None]]: ...
+    def slice(self, **kwargs: int | slice) -> AutoReloadActorMesh: ...
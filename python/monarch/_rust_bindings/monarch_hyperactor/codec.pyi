@@ -0,0 +1,66 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+# All rights reserved.
+#
+# This source code is licensed under the BSD-style license found in the
+# LICENSE file in the root directory of this source tree.
+
+# pyre-strict
+
+from typing import Any, final, Type, TypeVar
+
+T = TypeVar("T")
+
+@final
+class Payload:
+    """
+    A cloudpickled Python object, tagged with the qualified name of its type,
+    that can be carried inside Rust messages.
+
+    Payloads larger than the `py_payload_max_bytes` config are rejected, both
+    when encoded and when received. Payloads are picklable, so they can also
+    be sent through Python messages.
+    """
+
+    def __init__(self, obj: Any) -> None:
+        """
+        Cloudpickle `obj` into a payload. Raises ValueError if the pickled
+        object exceeds the size limit.
+        """
+        ...
+
+    @property
+    def type_tag(self) -> str:
+        """The qualified name of the object's type, e.g. "builtins.dict"."""
+        ...
+
+    def __len__(self) -> int:
+        """The size of the pickled object, in bytes."""
+        ...
+
+    def decode(self) -> Any:
+        """
+        Unpickle the payload. Raises TypeError if the result does not have the
+        type the payload was tagged with.
+        """
+        ...
+
+    def decode_as(self, expected: Type[T]) -> T:
+        """
+        Unpickle the payload, raising TypeError unless the result is an
+        instance of `expected`.
+        """
+        ...
+
+    def to_bytes(self) -> bytes:
+        """The payload in its wire encoding."""
+        ...
+
+    @staticmethod
+    def from_bytes(data: bytes) -> "Payload":
+        """
+        Decode a payload from its wire encoding. Raises ValueError if the
+        payload exceeds the size limit.
+        """
+        ...
+
+    def __repr__(self) -> str: ...
@@ -21,6 +21,8 @@ class Encoding(Enum):
     Bincode: int
     Json: int
     Multipart: int
+    MessagePack: int
+    Cbor: int
 
 def reload_config_from_env() -> None:
     """
@@ -59,12 +61,14 @@ def configure(
     default_encoding: Encoding = ...,
     channel_net_rx_buffer_full_check_interval: str = ...,
     message_latency_sampling_rate: float = ...,
+    actor_cpu_timing: bool = ...,
     enable_dest_actor_reordering_buffer: bool = ...,
     mesh_bootstrap_enable_pdeathsig: bool = ...,
     mesh_terminate_concurrency: int = ...,
     mesh_terminate_timeout: str = ...,
     shared_asyncio_runtime: bool = ...,
     small_write_threshold: int = ...,
+    pickle_tensor_views: bool = ...,
     max_cast_dimension_size: int = ...,
     remote_alloc_bind_to_inaddr_any: bool = ...,
     remote_alloc_bootstrap_addr: str = ...,
@@ -78,6 +82,11 @@ def configure(
     proc_stop_max_idle: str = ...,
     get_proc_state_max_idle: str = ...,
     actor_queue_dispatch: bool = ...,
+    actor_subprocess_isolation: bool = ...,
+    actor_python_executor: bool = ...,
+    python_executor_max_batch: int = ...,
+    python_executor_max_gil_hold: str = ...,
+    py_payload_max_bytes: int = ...,
     mesh_admin_addr: str = ...,
     mesh_attach_config_timeout: str = ...,
     mesh_orphan_timeout: str = ...,
@@ -129,11 +138,14 @@ def configure(
         stop_actor_timeout: Timeout for stopping actors (humantime)
         cleanup_timeout: Timeout for cleanup operations (humantime)
         default_encoding: Default message encoding (Encoding.Bincode,
-            Encoding.Json, or Encoding.Multipart)
+            Encoding.Json, or Encoding.Multipart; Encoding.MessagePack and
+            Encoding.Cbor fall back to Encoding.Multipart, and are instead
+            used per message with Mailbox.post_encoded)
         channel_net_rx_buffer_full_check_interval: Network receive buffer
             check interval (humantime)
         message_latency_sampling_rate: Sampling rate for message latency
             (0.0 to 1.0)
+        actor_cpu_timing: Measure the CPU time of actor message handlers
         enable_dest_actor_reordering_buffer: Enable client-side sequence
             assignment
         mesh_bootstrap_enable_pdeathsig: Enable parent-death signal for
@@ -145,6 +157,8 @@ def configure(
         shared_asyncio_runtime: Share asyncio runtime across actors
         small_write_threshold: Threshold below which writes are copied
             (bytes)
+        pickle_tensor_views: Pickle host tensors as views of their
+            memory, which must not be modified until delivery
         max_cast_dimension_size: Maximum dimension size for cast
             operations
         remote_alloc_bind_to_inaddr_any: Bind remote allocators to
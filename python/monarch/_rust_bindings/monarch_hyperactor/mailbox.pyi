@@ -6,9 +6,11 @@
 
 # pyre-strict
 
+import asyncio
 from typing import final, Protocol
 
 from monarch._rust_bindings.monarch_hyperactor.actor import PythonMessage
+from monarch._rust_bindings.monarch_hyperactor.config import Encoding
 from monarch._rust_bindings.monarch_hyperactor.context import Instance
 from monarch._rust_bindings.monarch_hyperactor.proc import ActorAddr
 from monarch._rust_bindings.monarch_hyperactor.pytokio import PythonTask
@@ -145,7 +147,13 @@ class OncePortRef:
 
 @final
 class OncePortReceiver(PortReceiverBase):
-    pass
+    def recv_future(self) -> asyncio.Future[PythonMessage]:
+        """
+        Receive the message through an asyncio future on the running event loop.
+        Cancelling the future closes the port: a message sent afterwards is
+        returned to its sender as undeliverable.
+        """
+        ...
 
 @final
 class Mailbox:
@@ -174,6 +182,16 @@ class Mailbox:
         """
         ...
 
+    def post_encoded(
+        self, dest: PortId, encoding: Encoding, typename: str, data: bytes
+    ) -> None:
+        """
+        Post `data`, a value of the Rust type named `typename` already encoded
+        with `encoding` (e.g. a MessagePack-encoded dict), to the port `dest`.
+        The data is decoded by the receiver.
+        """
+        ...
+
     @property
     def actor_id(self) -> ActorAddr: ...
 
@@ -239,3 +257,19 @@ class UndeliverableMessageEnvelope:
         The error message describing why the message could not be delivered.
         """
         ...
+
+    def error_code(self) -> int | None:
+        """
+        The stable numeric code of the root delivery failure, if any.
+        Codes are grouped by category, in blocks of 100 (see
+        `error_category`).
+        """
+        ...
+
+    def error_category(self) -> str | None:
+        """
+        The category of the root delivery failure's code, if any: one of
+        "routing", "serialization", "capacity", "auth", "lifecycle", or
+        "other".
+        """
+        ...
@@ -0,0 +1,87 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+# All rights reserved.
+#
+# This source code is licensed under the BSD-style license found in the
+# LICENSE file in the root directory of this source tree.
+
+# pyre-strict
+
+from typing import Any, Callable, final, List, Optional, TypeVar
+
+from monarch._rust_bindings.monarch_hyperactor.context import Instance
+from monarch._rust_bindings.monarch_hyperactor.host_mesh import HostMesh
+from monarch._rust_bindings.monarch_hyperactor.mailbox import Mailbox
+from monarch._rust_bindings.monarch_hyperactor.pytokio import PythonTask
+
+T = TypeVar("T")
+
+@final
+class Session:
+    """
+    A named session on a mesh of workers, for notebooks and REPLs.
+
+    Constructing a session with the name of an existing session on the same
+    workers returns the existing session, so re-executing a cell does not
+    reattach or respawn.
+    """
+
+    def __init__(self, name: str, workers: List[str]) -> None:
+        """
+        Get or create the session `name` on `workers`, given as ZMQ-style URLs.
+        A session with this name on different workers is closed, stopping
+        its host mesh, and replaced.
+        """
+        ...
+
+    @property
+    def name(self) -> str: ...
+    @property
+    def workers(self) -> List[str]: ...
+    @property
+    def connected(self) -> bool:
+        """Whether the session is attached to its workers."""
+        ...
+
+    def host_mesh(self, instance: Instance) -> PythonTask[HostMesh]:
+        """
+        The session's host mesh, attaching to the workers through `instance`
+        on first use. Once connected, every call returns the same host mesh.
+        """
+        ...
+
+    def check_health(
+        self, instance: Instance, timeout: float = 5.0
+    ) -> PythonTask[List[bool]]:
+        """
+        Probe the hosts of the connected session, waiting up to `timeout`
+        seconds for each, and return whether each host is reachable. The
+        result is shown in the reprs.
+        """
+        ...
+
+    @property
+    def mailbox(self) -> Mailbox:
+        """
+        The mailbox of the client instance the session connected with.
+        Raises RuntimeError if the session is not connected.
+        """
+        ...
+
+    def bind(self, name: str, factory: Callable[[], T]) -> T:
+        """
+        Return the object bound to `name`, first binding it to `factory()`
+        if nothing is bound yet.
+        """
+        ...
+
+    def unbind(self, name: str) -> Optional[Any]:
+        """Remove and return the object bound to `name`, if any."""
+        ...
+
+    @property
+    def actors(self) -> List[str]:
+        """The names of the bound objects, in order."""
+        ...
+
+    def __repr__(self) -> str: ...
+    def _repr_html_(self) -> str: ...
@@ -150,6 +150,14 @@ class Shape:
         """
         ...
 
+    def group_by(self, label: str) -> List["Shape"]:
+        """
+        Partition this shape along a named dimension, one shape per index:
+            shapes = shape.select("host", slice(3, 5)).group_by("gpu")
+        Each shape keeps the dimension, with size 1.
+        """
+        ...
+
     def index(self, **kwargs: Dict[str, int]) -> "Shape":
         """
         Create a sub-slice of this shape:
@@ -164,6 +172,14 @@ class Shape:
         Create an explicit list of all the ranks included in this Shape
         """
         ...
+    def query(self, selection: str) -> List[int]:
+        """
+        The ranks selected by a textual selection, e.g.
+            shape.query("host=0:8, gpu=*")
+        Dimensions that are not named are selected in full. Positional
+        expressions such as "0:8, *" are also accepted.
+        """
+        ...
     def __len__(self) -> int: ...
     def __eq__(self, value: object) -> bool: ...
     @staticmethod
@@ -0,0 +1,29 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+# All rights reserved.
+#
+# This source code is licensed under the BSD-style license found in the
+# LICENSE file in the root directory of this source tree.
+
+# pyre-strict
+
+"""
+Type hints for the stubs module.
+"""
+
+def generate_stubs(module: str) -> str:
+    """
+    Generate typed stubs for the Rust actor protocols registered in a
+    binding module.
+
+    Arguments:
+    - `module`: The binding module, relative to `monarch._rust_bindings`
+      (e.g. "monarch_hyperactor.code_sync").
+
+    Returns the contents of a .pyi file with a dataclass per message and
+    `<Actor>Ref` and `<Actor>Mesh` classes with a method per message.
+
+    Raises:
+        Exception: If a registered protocol no longer matches the Rust
+        message definitions.
+    """
+    ...
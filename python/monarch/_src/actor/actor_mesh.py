@@ -7,6 +7,7 @@
 # pyre-strict
 
 import abc
+import asyncio
 import collections
 import contextvars
 import functools
@@ -56,6 +57,7 @@ from monarch._rust_bindings.monarch_hyperactor.endpoint import ActorEndpoint
 from monarch._rust_bindings.monarch_hyperactor.logging import log_endpoint_exception
 from monarch._rust_bindings.monarch_hyperactor.mailbox import (
     Mailbox,
+    OncePortReceiver,
     OncePortRef,
     PortRef,
     UndeliverableMessageEnvelope,
@@ -1121,8 +1123,21 @@ class PortReceiver(Generic[R]):
                 raise ValueError(f"Unexpected message kind: {msg.kind}")
 
     def recv(self) -> "Future[R]":
+        if (
+            isinstance(self._receiver, OncePortReceiver)
+            and self._monitor is None
+            and asyncio._get_running_loop() is not None
+        ):
+            # Receive on the running asyncio loop, so that cancelling the
+            # future drops the receiver and closes the port: late replies
+            # are then returned to their senders as undeliverable.
+            return Future.from_asyncio(asyncio.ensure_future(self._recv_asyncio()))
         return Future(coro=self._recv())
 
+    async def _recv_asyncio(self) -> R:
+        # pyrefly: ignore [missing-attribute]
+        return self._process(await self._receiver.recv_future())
+
     def ranked(self) -> "RankedPortReceiver[R]":
         return RankedPortReceiver[R](
             self._mailbox, self._receiver, self._monitor, self._endpoint
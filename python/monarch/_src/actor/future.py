@@ -106,6 +106,14 @@ class Future(Generic[R]):
             coro if isinstance(coro, PythonTask) else PythonTask.from_coroutine(coro)
         )
 
+    @staticmethod
+    def from_asyncio(fut: "asyncio.Future[R]") -> "Future[R]":
+        """Wrap an asyncio future, which must then be awaited from its own
+        event loop. Cancelling the awaiting task cancels `fut`."""
+        future: Future[R] = Future.__new__(Future)
+        future._status = _Asyncio(fut)
+        return future
+
     def get(self, timeout: Optional[float] = None) -> R:
         """Get the result of the Future.
 
@@ -114,10 +114,10 @@ class HostMesh(MeshTrait):
             per_host: shape of procs per host, e.g. ``{"gpus": 4}``.
             bootstrap: optional setup callable run on each proc.
             name: optional name for the proc mesh.
-            proc_bind: optional per-process CPU/NUMA binding config.
+            proc_bind: optional per-process CPU/NUMA/GPU binding config.
                 Length must equal ``math.prod(per_host.values())``.
                 Each dict maps binding keys (``cpunodebind``,
-                ``membind``, ``physcpubind``, ``cpus``) to values.
+                ``membind``, ``physcpubind``, ``cpus``, ``gpus``) to values.
             bootstrap_command: optional BootstrapCommand or callable that
                 returns a BootstrapCommand for each coordinate. The callable
                 receives a ``Point`` (combined coordinate across host and
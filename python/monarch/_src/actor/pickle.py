@@ -15,7 +15,7 @@ from collections import ChainMap
 from collections.abc import Generator
 from contextlib import contextmanager, ExitStack
 from contextvars import ContextVar
-from typing import Any, Callable, Iterable, List, Tuple
+from typing import Any, Callable, Dict, Iterable, List, Tuple
 
 import cloudpickle
 from monarch._rust_bindings.monarch_hyperactor.buffers import Buffer, FrozenBuffer
@@ -137,6 +137,42 @@ def torch_loads(data: FrozenBuffer | bytes) -> Any:
         return cloudpickle.loads(data)
 
 
+def _typed_storage_from_buffer(data: bytearray, dtype: Any) -> Any:
+    import torch  # we only get here if torch is already imported
+
+    # pyre-ignore[16]: dynamic torch attribute
+    untyped = (
+        torch.frombuffer(data, dtype=torch.uint8).untyped_storage()
+        if len(data)
+        else torch.UntypedStorage(0)
+    )
+    # pyre-ignore[16]: dynamic torch attribute
+    return torch.TypedStorage(wrap_storage=untyped, dtype=dtype, _internal=True)
+
+
+class _BufferWriter:
+    """
+    The file of a pickler writing into a Buffer with tensor views enabled
+    (see the `pickle_tensor_views` config). The pickler registers the
+    in-band buffers of host tensor storages in `views`; when pickle writes
+    one of them directly (as it does for large buffers), its tensor is
+    written with `Buffer.write_view`, referencing the tensor's memory
+    instead of copying it.
+    """
+
+    def __init__(self, buffer: Buffer) -> None:
+        self.buffer = buffer
+        self.views: Dict[int, Tuple[pickle.PickleBuffer, Any]] = {}
+
+    def write(self, data: Any) -> int:
+        view = self.views.pop(id(data), None)
+        if view is not None and view[0] is data:
+            return self.buffer.write_view(view[1])
+        if not isinstance(data, bytes):
+            data = bytes(data)
+        return self.buffer.write(data)
+
+
 class _Pickler(cloudpickle.Pickler):
     _torch_initialized = False
     _dispatch_table: dict[Any, Any] = {}
@@ -150,7 +186,10 @@ class _Pickler(cloudpickle.Pickler):
 
     def __init__(self, filter: Callable[[Any], bool], f: Buffer | io.BytesIO) -> None:
         self.f = f
-        super().__init__(self.f)
+        self._writer: _BufferWriter | None = (
+            _BufferWriter(f) if isinstance(f, Buffer) and f.tensor_views else None
+        )
+        super().__init__(self.f if self._writer is None else self._writer)
         self._filter: Callable[[Any], bool] = filter
         self._saved: List[Any] = []
         _Pickler._init_torch_dispatch()
@@ -171,6 +210,31 @@ class _Pickler(cloudpickle.Pickler):
                 cls._dispatch_table[key] = _torch_storage
             cls._torch_initialized = True
 
+    def reducer_override(self, obj: Any) -> Any:
+        if self._writer is not None:
+            torch = maybe_torch()
+            if (
+                torch is not None
+                # pyre-ignore[16]: dynamic torch attribute
+                and type(obj) is torch.storage.TypedStorage
+                and obj.device.type == "cpu"
+            ):
+                try:
+                    # A byte tensor over the whole storage. Its numpy view
+                    # pickles in-band as a PickleBuffer, which the writer
+                    # replaces with a view of the tensor.
+                    # pyre-ignore[16]: dynamic torch attribute
+                    data = torch.empty(0, dtype=torch.uint8).set_(
+                        obj._untyped_storage
+                    )
+                    buffer = pickle.PickleBuffer(data.numpy())
+                except RuntimeError:
+                    # numpy is not available: copy the storage as usual.
+                    return super().reducer_override(obj)
+                self._writer.views[id(buffer)] = (buffer, data)
+                return (_typed_storage_from_buffer, (buffer, obj.dtype))
+        return super().reducer_override(obj)
+
     def persistent_id(self, obj: Any) -> int | None:
         if not self._filter(obj):
             return None
@@ -40,8 +40,8 @@ class LaunchOptions:
             (0 = none).
         log_channel: Optional ChannelAddr string for mesh log
             forwarding.
-        proc_bind: Optional CPU/NUMA binding configuration dict.
-            Keys may include cpunodebind, membind, physcpubind, cpus.
+        proc_bind: Optional CPU/NUMA/GPU binding configuration dict.
+            Keys may include cpunodebind, membind, physcpubind, cpus, gpus.
     """
 
     bootstrap_payload: str
@@ -62,12 +62,14 @@ if TYPE_CHECKING:
             default_encoding: NotRequired[Encoding]
             channel_net_rx_buffer_full_check_interval: NotRequired[str]
             message_latency_sampling_rate: NotRequired[float]
+            actor_cpu_timing: NotRequired[bool]
             enable_dest_actor_reordering_buffer: NotRequired[bool]
             mesh_bootstrap_enable_pdeathsig: NotRequired[bool]
             mesh_terminate_concurrency: NotRequired[int]
             mesh_terminate_timeout: NotRequired[str]
             shared_asyncio_runtime: NotRequired[bool]
             small_write_threshold: NotRequired[int]
+            pickle_tensor_views: NotRequired[bool]
             max_cast_dimension_size: NotRequired[int]
             remote_alloc_bind_to_inaddr_any: NotRequired[bool]
             remote_alloc_bootstrap_addr: NotRequired[str]
@@ -81,6 +83,12 @@ if TYPE_CHECKING:
             proc_stop_max_idle: NotRequired[str]
             get_proc_state_max_idle: NotRequired[str]
             actor_queue_dispatch: NotRequired[bool]
+            actor_subprocess_isolation: NotRequired[bool]
+            actor_python_executor: NotRequired[bool]
+            python_executor_max_batch: NotRequired[int]
+            python_executor_max_gil_hold: NotRequired[str]
+            py_payload_max_bytes: NotRequired[int]
+            python_cast_queue_capacity: NotRequired[int]
             mesh_admin_addr: NotRequired[str]
             mesh_attach_config_timeout: NotRequired[str]
             mesh_orphan_timeout: NotRequired[str]
@@ -133,6 +141,7 @@ def configure(**kwargs: "ConfigureKwargsType") -> None:
             default_encoding: Default message encoding (Encoding.Bincode, Encoding.Json, or Encoding.Multipart).
             channel_net_rx_buffer_full_check_interval: Network receive buffer check interval (humantime).
             message_latency_sampling_rate: Sampling rate for message latency tracking (0.0 to 1.0).
+            actor_cpu_timing: Measure the CPU time of actor message handlers.
             enable_dest_actor_reordering_buffer: Enable reordering buffer in dest actor.
 
         Mesh bootstrap configuration:
@@ -143,6 +152,7 @@ def configure(**kwargs: "ConfigureKwargsType") -> None:
         Runtime and buffering:
             shared_asyncio_runtime: Share asyncio runtime across actors.
             small_write_threshold: Threshold below which writes are copied (bytes).
+            pickle_tensor_views: Pickle host tensors as views of their memory.
 
         Mesh configuration:
             max_cast_dimension_size: Maximum dimension size for cast operations.
@@ -0,0 +1,57 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+# All rights reserved.
+#
+# This source code is licensed under the BSD-style license found in the
+# LICENSE file in the root directory of this source tree.
+
+# pyre-strict
+
+import pytest
+from monarch._rust_bindings.monarch_hyperactor.buffers import Buffer
+from monarch._src.actor.pickle import flatten, unflatten
+from monarch.config import configured
+
+np = pytest.importorskip("numpy")
+
+
+def test_write_view_contiguous() -> None:
+    array = np.arange(12, dtype=np.float32).reshape(3, 4)
+    buffer = Buffer()
+    buffer.write(b"header")
+    assert buffer.write_view(array) == array.nbytes
+    assert len(buffer) == len(b"header") + array.nbytes
+    assert buffer.freeze().read() == b"header" + array.tobytes()
+
+
+def test_write_view_strided() -> None:
+    array = np.arange(12, dtype=np.int64).reshape(3, 4).T
+    buffer = Buffer()
+    assert buffer.write_view(array) == array.nbytes
+    # Non-contiguous arrays are written in row-major order.
+    assert buffer.freeze().read() == np.ascontiguousarray(array).tobytes()
+
+
+def test_write_view_empty() -> None:
+    buffer = Buffer()
+    assert buffer.write_view(np.zeros((0, 4), dtype=np.float64)) == 0
+    assert len(buffer) == 0
+
+
+def test_write_view_requires_dlpack() -> None:
+    with pytest.raises(AttributeError):
+        Buffer().write_view(b"not a tensor")
+
+
+def test_flatten_tensor_views() -> None:
+    torch = pytest.importorskip("torch")
+    tensor = torch.arange(1 << 16, dtype=torch.float32)
+    small = torch.ones(4, dtype=torch.int64)
+    with configured(pickle_tensor_views=True):
+        args, buffer = flatten({"large": tensor, "small": small}, lambda x: False)
+    assert buffer.tensor_views
+    restored = unflatten(buffer.freeze(), args)
+    assert torch.equal(restored["large"], tensor)
+    assert torch.equal(restored["small"], small)
+    # The restored tensors own their memory.
+    restored["large"][0] = 42
+    assert tensor[0] == 0
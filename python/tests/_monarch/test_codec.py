@@ -0,0 +1,56 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+# All rights reserved.
+#
+# This source code is licensed under the BSD-style license found in the
+# LICENSE file in the root directory of this source tree.
+
+# pyre-strict
+
+import pickle
+from dataclasses import dataclass
+
+import pytest
+from monarch._rust_bindings.monarch_hyperactor.codec import Payload
+from monarch.config import configured
+
+
+@dataclass
+class Point:
+    x: int
+    y: int
+
+
+def test_payload_roundtrip() -> None:
+    payload = Payload({"point": Point(1, 2)})
+    assert payload.type_tag == "builtins.dict"
+    assert len(payload) > 0
+    assert payload.decode() == {"point": Point(1, 2)}
+
+
+def test_payload_decode_as() -> None:
+    payload = Payload(Point(1, 2))
+    assert payload.type_tag.endswith("Point")
+    assert payload.decode_as(Point) == Point(1, 2)
+    with pytest.raises(TypeError, match="expected a payload of type dict"):
+        payload.decode_as(dict)
+
+
+def test_payload_size_limit() -> None:
+    with configured(py_payload_max_bytes=64):
+        with pytest.raises(ValueError, match="exceeds the limit of 64 bytes"):
+            Payload(b"x" * 1024)
+
+
+def test_payload_pickle() -> None:
+    payload = Payload({"point": Point(1, 2)})
+    restored = pickle.loads(pickle.dumps(payload))
+    assert restored.type_tag == "builtins.dict"
+    assert restored.decode() == {"point": Point(1, 2)}
+    assert Payload.from_bytes(payload.to_bytes()).decode() == {"point": Point(1, 2)}
+
+
+def test_payload_from_bytes_size_limit() -> None:
+    encoded = Payload(b"x" * 1024).to_bytes()
+    with configured(py_payload_max_bytes=64):
+        with pytest.raises(ValueError, match="exceeds the limit of 64 bytes"):
+            Payload.from_bytes(encoded)
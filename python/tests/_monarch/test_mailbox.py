@@ -6,6 +6,7 @@
 
 # pyre-strict
 
+import asyncio
 import pickle
 from typing import (
     Any,
@@ -20,6 +21,8 @@ from typing import (
     TypeVar,
 )
 
+import pytest
+
 from monarch._rust_bindings.monarch_hyperactor.actor import (
     MethodSpecifier,
     PanicFlag,
@@ -44,7 +47,7 @@ from monarch._rust_bindings.monarch_hyperactor.mailbox import (
     PortRef,
 )
 from monarch._rust_bindings.monarch_hyperactor.proc_mesh import ProcMesh
-from monarch._src.actor.actor_mesh import context, Instance
+from monarch._src.actor.actor_mesh import Channel, context, Instance
 
 
 def _to_frozen_buffer(data: bytes) -> FrozenBuffer:
@@ -182,6 +185,56 @@ async def test_accumulator() -> None:
     assert await recv_message() == "init+1+2+3+4"
 
 
+def test_once_port_recv_future() -> None:
+    async def run() -> None:
+        ins: Instance = context().actor_instance
+        handle, receiver = ins._mailbox.open_once_port()
+        port_ref = handle.bind()
+        port_ref.send(
+            ins._as_rust(),
+            PythonMessage(
+                # pyrefly: ignore [bad-argument-type]
+                PythonMessageKind.Result(None),
+                _to_frozen_buffer(pickle.dumps("hello")),
+            ),
+        )
+        message = await asyncio.wait_for(receiver.recv_future(), timeout=5)
+        assert pickle.loads(message.message) == "hello"
+
+    asyncio.run(run())
+
+
+def test_once_port_recv_future_cancel() -> None:
+    async def run() -> None:
+        ins: Instance = context().actor_instance
+        _handle, receiver = ins._mailbox.open_once_port()
+        future = receiver.recv_future()
+        future.cancel()
+        with pytest.raises(asyncio.CancelledError):
+            await future
+        # The receiver was consumed by the cancelled future.
+        with pytest.raises(ValueError, match="already used"):
+            receiver.recv_future()
+
+    asyncio.run(run())
+
+
+def test_once_channel_recv_asyncio() -> None:
+    async def run() -> None:
+        port, receiver = Channel[str].open(once=True)
+        port.send("hello")
+        assert await asyncio.wait_for(receiver.recv(), timeout=5) == "hello"
+
+        # Cancelling a pending receive closes the port.
+        _port, receiver = Channel[str].open(once=True)
+        with pytest.raises(asyncio.TimeoutError):
+            await asyncio.wait_for(receiver.recv(), timeout=0.1)
+        with pytest.raises(ValueError, match="already used"):
+            await receiver.recv()
+
+    asyncio.run(run())
+
+
 class MyActor:
     async def handle(
         self,
@@ -0,0 +1,71 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+# All rights reserved.
+#
+# This source code is licensed under the BSD-style license found in the
+# LICENSE file in the root directory of this source tree.
+
+# pyre-strict
+
+import time
+from typing import List
+
+import pytest
+from monarch._rust_bindings.monarch_hyperactor.session import Session
+
+
+WORKERS: List[str] = ["tcp://127.0.0.1:26600"]
+
+
+def test_session_is_reused_by_name() -> None:
+    first = Session("test_session_is_reused_by_name", WORKERS)
+    first.bind("trainers", lambda: "trainers")
+
+    # Re-executing the cell yields the same session.
+    second = Session("test_session_is_reused_by_name", WORKERS)
+    assert second.actors == ["trainers"]
+    assert not second.connected
+
+    # A session on different workers replaces it, closing the old one.
+    third = Session("test_session_is_reused_by_name", ["tcp://127.0.0.1:26601"])
+    assert third.actors == []
+    deadline = time.monotonic() + 10
+    while "state=closed" not in repr(first):
+        assert time.monotonic() < deadline, repr(first)
+        time.sleep(0.01)
+    assert first.actors == []
+
+
+def test_session_bind_is_idempotent() -> None:
+    session = Session("test_session_bind_is_idempotent", WORKERS)
+    calls: List[int] = []
+
+    def factory() -> List[int]:
+        calls.append(1)
+        return [len(calls)]
+
+    first = session.bind("actors", factory)
+    second = session.bind("actors", factory)
+    assert first is second
+    assert calls == [1]
+
+    assert session.unbind("actors") is first
+    assert session.bind("actors", factory) == [2]
+
+
+def test_session_repr() -> None:
+    session = Session("test_session_repr", WORKERS)
+    session.bind("echo", lambda: "<echo>")
+    assert repr(session) == (
+        "Session(name=\"test_session_repr\", state=disconnected, health=unknown, "
+        "actors=[\"echo\"])"
+    )
+    html = session._repr_html_()
+    assert "disconnected" in html
+    assert "<th style=\"text-align:left\">Health</th><td style=\"text-align:left\">unknown</td>" in html
+    assert "'&lt;echo&gt;'" in html
+
+
+def test_session_mailbox_requires_connection() -> None:
+    session = Session("test_session_mailbox_requires_connection", WORKERS)
+    with pytest.raises(RuntimeError, match="not connected"):
+        session.mailbox
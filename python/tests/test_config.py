@@ -304,6 +304,8 @@ def test_integer_params(param_name, test_value, default_value):
         ("prefix_with_rank", True),
         # Actor queue dispatch
         ("actor_queue_dispatch", False),
+        ("actor_subprocess_isolation", False),
+        ("actor_python_executor", False),
     ],
 )
 def test_boolean_params(param_name, default_value):
@@ -942,6 +942,8 @@ def test_messages_table(cleanup_callbacks) -> None:
             "to_actor_id",
             "endpoint",
             "port_index",
+            "parent_message_id",
+            "root_message_id",
         ], f"Unexpected columns: {column_names}"
 
         # Verify rows exist
@@ -40,6 +40,7 @@ from monarch._rust_bindings.monarch_hyperactor.mailbox import (
     PortRef,
     UndeliverableMessageEnvelope,
 )
+from monarch._rust_bindings.monarch_hyperactor.actor_mesh import QueueFull
 from monarch._rust_bindings.monarch_hyperactor.proc import ActorAddr
 from monarch._rust_bindings.monarch_hyperactor.pytokio import PythonTask, Shared
 from monarch._src.actor.actor_mesh import ActorMesh, Channel, context, Port
@@ -117,6 +118,22 @@ async def test_choose():
     await proc.stop()
 
 
+@pytest.mark.timeout(60)
+async def test_cast_queue_full():
+    with configured(python_cast_queue_capacity=1):
+        proc = this_host().spawn_procs(per_host={"gpus": 2})
+        v = proc.spawn("counter_queue_full", Counter, 0)
+        # Broadcasts queue behind the mesh's spawn, and fill its queue.
+        with pytest.raises(QueueFull) as exc_info:
+            for _ in range(1000):
+                v.incr.broadcast()
+        assert exc_info.value.capacity == 1
+        # Awaited calls wait for room instead, behind the broadcasts.
+        values = await asyncio.gather(*(v.value.call() for _ in range(10)))
+        assert len({tuple(value.values()) for value in values}) == 1
+        await proc.stop()
+
+
 @pytest.mark.timeout(60)
 @parametrize_config(actor_queue_dispatch={True, False})
 async def test_stream():
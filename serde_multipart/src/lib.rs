@@ -43,6 +43,7 @@ use bytes::buf::UninitSlice;
 mod codec;
 mod de;
 mod part;
+pub mod pool;
 mod ser;
 use bytes::Bytes;
 use bytes::BytesMut;
@@ -368,17 +369,43 @@ unsafe impl BufMut for UnsafeBufCellRef {
 pub fn serialize_bincode<S: ?Sized + serde::Serialize>(
     value: &S,
 ) -> Result<Message, bincode::Error> {
-    let buffer = UnsafeBufCell::from_bytes_mut(BytesMut::new());
+    let mut body = BytesMut::new();
+    let parts = serialize_bincode_into(value, &mut body)?;
+    Ok(Message {
+        body: Part::from_fragments(vec![body.freeze()]),
+        parts,
+    })
+}
+
+/// Like [`serialize_bincode`], but encodes the message body into a buffer
+/// taken from `pool`, which it returns to once the message is dropped.
+pub fn serialize_bincode_pooled<S: ?Sized + serde::Serialize>(
+    value: &S,
+    pool: &std::sync::Arc<pool::BufferPool>,
+) -> Result<Message, bincode::Error> {
+    let mut body = pool.take(0);
+    let parts = serialize_bincode_into(value, &mut body)?;
+    Ok(Message {
+        body: Part::from_fragments(vec![body.freeze()]),
+        parts,
+    })
+}
+
+/// Encode the body of `value` into `body`, returning its parts.
+fn serialize_bincode_into<S: ?Sized + serde::Serialize>(
+    value: &S,
+    body: &mut BytesMut,
+) -> Result<Vec<Part>, bincode::Error> {
+    let buffer = UnsafeBufCell::from_bytes_mut(std::mem::take(body));
     // SAFETY: we know here that, once the below "value.serialize()" is done, there are no more
     // extant references to this buffer; we are thus safe to reclaim the buffer into the message
     let buffer_borrow = unsafe { buffer.borrow_unchecked() };
     let mut serializer: part::BincodeSerializer =
         ser::bincode::Serializer::new(bincode::Serializer::new(buffer_borrow.writer(), options()));
-    value.serialize(&mut serializer)?;
-    Ok(Message {
-        body: Part::from_fragments(vec![buffer.into_inner().freeze()]),
-        parts: serializer.into_parts(),
-    })
+    let result = value.serialize(&mut serializer);
+    let parts = serializer.into_parts();
+    *body = buffer.into_inner();
+    result.map(|()| parts)
 }
 
 /// Deserialize a message serialized by `[serialize]`, stitching together the original
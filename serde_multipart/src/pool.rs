@@ -0,0 +1,333 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! A pool of reusable buffers for serialized messages.
+//!
+//! A [`PooledBuffer`] is taken from a [`BufferPool`], filled, and then
+//! frozen into [`Bytes`]. When the last reference to those bytes is
+//! dropped, the buffer returns to its pool, so that message buffers are
+//! recycled instead of being freed and reallocated at high message rates.
+//!
+//! Buffers are kept in power-of-two size classes between the pool's
+//! minimum and maximum buffer sizes; larger requests are allocated
+//! directly and never pooled.
+
+use std::ops::Deref;
+use std::ops::DerefMut;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use bytes::Bytes;
+use bytes::BytesMut;
+
+/// The configuration of a [`BufferPool`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// The capacity of the smallest pooled buffer. Smaller requests are
+    /// rounded up to it.
+    pub min_buffer_size: usize,
+    /// The capacity of the largest pooled buffer. Larger requests are
+    /// allocated directly.
+    pub max_buffer_size: usize,
+    /// The number of idle buffers kept in each size class. 0 disables
+    /// pooling.
+    pub buffers_per_class: usize,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            min_buffer_size: 4 * 1024,
+            max_buffer_size: 4 * 1024 * 1024,
+            buffers_per_class: 64,
+        }
+    }
+}
+
+/// A snapshot of a [`BufferPool`]'s counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Buffers taken from the pool's idle buffers.
+    pub reused: u64,
+    /// Buffers that had to be allocated.
+    pub allocated: u64,
+    /// Buffers returned to the pool's idle buffers.
+    pub recycled: u64,
+    /// Buffers freed on return, because they were out of range or their
+    /// size class was full.
+    pub discarded: u64,
+}
+
+impl PoolStats {
+    /// The fraction of taken buffers that were reused.
+    pub fn reuse_rate(&self) -> f64 {
+        let taken = self.reused + self.allocated;
+        if taken == 0 {
+            0.0
+        } else {
+            self.reused as f64 / taken as f64
+        }
+    }
+}
+
+/// A pool of reusable buffers; see the [module documentation](self).
+#[derive(Debug)]
+pub struct BufferPool {
+    config: PoolConfig,
+    /// Class `i` holds idle buffers with a capacity of at least
+    /// `min_buffer_size << i`.
+    classes: Vec<Mutex<Vec<BytesMut>>>,
+    reused: AtomicU64,
+    allocated: AtomicU64,
+    recycled: AtomicU64,
+    discarded: AtomicU64,
+}
+
+impl BufferPool {
+    /// Create a new pool with the provided configuration.
+    pub fn new(config: PoolConfig) -> Arc<Self> {
+        let min = config.min_buffer_size.max(1);
+        let mut num_classes = 0;
+        while config.max_buffer_size >> num_classes >= min {
+            num_classes += 1;
+        }
+        Arc::new(Self {
+            config: PoolConfig {
+                min_buffer_size: min,
+                ..config
+            },
+            classes: (0..num_classes).map(|_| Mutex::new(Vec::new())).collect(),
+            reused: AtomicU64::new(0),
+            allocated: AtomicU64::new(0),
+            recycled: AtomicU64::new(0),
+            discarded: AtomicU64::new(0),
+        })
+    }
+
+    /// The pool's configuration.
+    pub fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+
+    /// Take an empty buffer with a capacity of at least `capacity` bytes.
+    pub fn take(self: &Arc<Self>, capacity: usize) -> PooledBuffer {
+        let Some(class) = self.class_for_take(capacity) else {
+            self.allocated.fetch_add(1, Ordering::Relaxed);
+            return PooledBuffer {
+                buf: BytesMut::with_capacity(capacity),
+                pool: None,
+            };
+        };
+        let buf = match self.classes[class].lock().unwrap().pop() {
+            Some(buf) => {
+                self.reused.fetch_add(1, Ordering::Relaxed);
+                buf
+            }
+            None => {
+                self.allocated.fetch_add(1, Ordering::Relaxed);
+                BytesMut::with_capacity(self.config.min_buffer_size << class)
+            }
+        };
+        PooledBuffer {
+            buf,
+            pool: Some(Arc::clone(self)),
+        }
+    }
+
+    /// A snapshot of the pool's counters.
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            reused: self.reused.load(Ordering::Relaxed),
+            allocated: self.allocated.load(Ordering::Relaxed),
+            recycled: self.recycled.load(Ordering::Relaxed),
+            discarded: self.discarded.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The smallest class whose buffers hold `capacity` bytes.
+    fn class_for_take(&self, capacity: usize) -> Option<usize> {
+        (0..self.classes.len()).find(|&class| self.config.min_buffer_size << class >= capacity)
+    }
+
+    /// The largest class whose buffers `capacity` bytes can serve.
+    fn class_for_put(&self, capacity: usize) -> Option<usize> {
+        (0..self.classes.len())
+            .rev()
+            .find(|&class| self.config.min_buffer_size << class <= capacity)
+    }
+
+    fn put(&self, mut buf: BytesMut) {
+        let Some(class) = self.class_for_put(buf.capacity()) else {
+            self.discarded.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        let mut idle = self.classes[class].lock().unwrap();
+        if idle.len() >= self.config.buffers_per_class {
+            self.discarded.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        buf.clear();
+        idle.push(buf);
+        self.recycled.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A buffer taken from a [`BufferPool`], which it returns to when dropped.
+/// Dereferences to the underlying [`BytesMut`].
+#[derive(Debug, Default)]
+pub struct PooledBuffer {
+    buf: BytesMut,
+    /// None for buffers that are not pooled.
+    pool: Option<Arc<BufferPool>>,
+}
+
+impl PooledBuffer {
+    /// Freeze the buffer into [`Bytes`]. The buffer is returned to its
+    /// pool once the bytes, and all slices of them, have been dropped.
+    ///
+    /// Contents smaller than half the pool's minimum buffer size are
+    /// instead copied out, and the buffer is returned immediately, so
+    /// that small, long-lived messages do not each pin a whole buffer.
+    pub fn freeze(mut self) -> Bytes {
+        let Some(pool) = &self.pool else {
+            return std::mem::take(&mut self.buf).freeze();
+        };
+        if self.buf.len() < pool.config.min_buffer_size / 2 {
+            return Bytes::copy_from_slice(&self.buf);
+        }
+        Bytes::from_owner(self)
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = BytesMut;
+
+    fn deref(&self) -> &BytesMut {
+        &self.buf
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut BytesMut {
+        &mut self.buf
+    }
+}
+
+impl AsRef<[u8]> for PooledBuffer {
+    fn as_ref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.put(std::mem::take(&mut self.buf));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(buffers_per_class: usize) -> Arc<BufferPool> {
+        BufferPool::new(PoolConfig {
+            min_buffer_size: 16,
+            max_buffer_size: 64,
+            buffers_per_class,
+        })
+    }
+
+    #[test]
+    fn test_buffers_are_reused_once_bytes_are_dropped() {
+        let pool = pool(4);
+        let mut buf = pool.take(10);
+        assert!(buf.capacity() >= 16);
+        buf.extend_from_slice(b"hello world");
+        let bytes = buf.freeze();
+        let slice = bytes.slice(1..3);
+        drop(bytes);
+        assert_eq!(pool.stats().recycled, 0);
+        assert_eq!(&slice[..], b"el");
+        drop(slice);
+        assert_eq!(pool.stats().recycled, 1);
+
+        let buf = pool.take(16);
+        assert!(buf.is_empty());
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                reused: 1,
+                allocated: 1,
+                recycled: 1,
+                discarded: 0,
+            }
+        );
+        assert_eq!(pool.stats().reuse_rate(), 0.5);
+    }
+
+    #[test]
+    fn test_small_contents_are_copied_out() {
+        let pool = pool(4);
+        let mut buf = pool.take(0);
+        buf.extend_from_slice(b"hi");
+        let bytes = buf.freeze();
+        // The buffer is back in the pool while the bytes are alive.
+        assert_eq!(pool.stats().recycled, 1);
+        assert_eq!(&bytes[..], b"hi");
+    }
+
+    #[test]
+    fn test_buffers_are_pooled_by_size_class() {
+        let pool = pool(4);
+        drop(pool.take(20));
+        // The idle buffer is too small for a larger class.
+        assert_eq!(pool.take(40).capacity(), 64);
+        assert_eq!(pool.stats().reused, 0);
+        assert_eq!(pool.take(32).capacity(), 32);
+        assert_eq!(pool.stats().reused, 1);
+    }
+
+    #[test]
+    fn test_out_of_range_and_excess_buffers_are_discarded() {
+        let pool = pool(1);
+        let large = pool.take(1000);
+        assert!(large.capacity() >= 1000);
+        drop(large);
+        assert_eq!(pool.stats().recycled, 0);
+        assert_eq!(pool.stats().discarded, 0);
+
+        let first = pool.take(16);
+        let second = pool.take(16);
+        drop(first);
+        drop(second);
+        assert_eq!(pool.stats().recycled, 1);
+        assert_eq!(pool.stats().discarded, 1);
+
+        let disabled = self::pool(0);
+        drop(disabled.take(16));
+        assert_eq!(disabled.stats().discarded, 1);
+    }
+
+    #[test]
+    fn test_pooled_serialization_round_trip() {
+        let pool = pool(4);
+        let value = (String::from("hello"), crate::Part::from(vec![1u8, 2, 3]));
+        let message = crate::serialize_bincode_pooled(&value, &pool).unwrap();
+        let decoded: (String, crate::Part) = crate::deserialize_bincode(message).unwrap();
+        assert_eq!(decoded.0, "hello");
+        assert_eq!(decoded.1.to_bytes(), vec![1u8, 2, 3]);
+        assert_eq!(pool.stats().recycled, 1);
+
+        crate::serialize_bincode_pooled(&value, &pool).unwrap();
+        assert_eq!(pool.stats().reused, 1);
+    }
+}
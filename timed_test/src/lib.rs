@@ -9,14 +9,22 @@
 use proc_macro::TokenStream;
 use quote::quote;
 use syn::Expr;
+use syn::ExprLit;
 use syn::ItemFn;
 use syn::Lit;
 use syn::MetaNameValue;
+use syn::Token;
 use syn::parse_macro_input;
 use syn::parse_quote;
+use syn::punctuated::Punctuated;
 
 /// A test macro that wraps tokio::test and adds a configurable timeout.
 ///
+/// With `start_paused = true`, the test runs on a current-thread
+/// runtime whose clock starts paused: tokio timers (sleeps, timeouts,
+/// alarms) then fire as soon as the runtime is idle, rather than after
+/// real time has elapsed. The timeout itself is always real time.
+///
 /// # Examples
 ///
 /// ```rust
@@ -25,40 +33,67 @@ use syn::parse_quote;
 ///     // Test that should complete within 5 seconds
 ///     tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
 /// }
+///
+/// #[async_timed_test(timeout_secs = 5, start_paused = true)]
+/// async fn my_paused_test() {
+///     // Completes immediately: the clock is advanced by an hour.
+///     tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
+/// }
 /// ```
 #[proc_macro_attribute]
 pub fn async_timed_test(attr: TokenStream, input: TokenStream) -> TokenStream {
-    let attr = parse_macro_input!(attr as MetaNameValue);
+    let attrs =
+        parse_macro_input!(attr with Punctuated::<MetaNameValue, Token![,]>::parse_terminated);
     let input_fn = parse_macro_input!(input as ItemFn);
 
-    if !attr.path.is_ident("timeout_secs") {
-        return TokenStream::from(
-            syn::Error::new_spanned(attr.path, "only timeout_secs allowed as argument")
-                .to_compile_error(),
-        );
-    }
-    let timeout_secs = match attr.value {
-        Expr::Lit(ref lit) => match &lit.lit {
-            Lit::Int(val) => val.base10_parse::<u64>().unwrap(),
-            _ => {
-                return TokenStream::from(
-                    syn::Error::new_spanned(
-                        attr.value,
-                        "unexpected value for timeout_secs, please pass an integer literal",
-                    )
-                    .to_compile_error(),
-                );
+    let mut timeout_secs = None;
+    let mut start_paused = false;
+    for attr in attrs {
+        if attr.path.is_ident("timeout_secs") {
+            match &attr.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Int(val), ..
+                }) => timeout_secs = Some(val.base10_parse::<u64>().unwrap()),
+                _ => {
+                    return TokenStream::from(
+                        syn::Error::new_spanned(
+                            &attr.value,
+                            "unexpected value for timeout_secs, please pass an integer literal",
+                        )
+                        .to_compile_error(),
+                    );
+                }
             }
-        },
-        _ => {
+        } else if attr.path.is_ident("start_paused") {
+            match &attr.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Bool(val),
+                    ..
+                }) => start_paused = val.value,
+                _ => {
+                    return TokenStream::from(
+                        syn::Error::new_spanned(
+                            &attr.value,
+                            "unexpected value for start_paused, please pass a boolean literal",
+                        )
+                        .to_compile_error(),
+                    );
+                }
+            }
+        } else {
             return TokenStream::from(
                 syn::Error::new_spanned(
-                    attr.value,
-                    "unexpected value for timeout_secs, please pass an integer literal",
+                    attr.path,
+                    "only timeout_secs and start_paused allowed as arguments",
                 )
                 .to_compile_error(),
             );
         }
+    }
+    let Some(timeout_secs) = timeout_secs else {
+        return TokenStream::from(
+            syn::Error::new_spanned(&input_fn.sig, "timeout_secs is required").to_compile_error(),
+        );
     };
 
     let fn_block = &input_fn.block;
@@ -74,6 +109,25 @@ pub fn async_timed_test(attr: TokenStream, input: TokenStream) -> TokenStream {
         );
     }
 
+    // Paused time is only supported by the current-thread runtime.
+    let runtime = if start_paused {
+        quote! {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .start_paused(true)
+                .build()
+                .unwrap()
+        }
+    } else {
+        quote! {
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(8)
+                .enable_all()
+                .build()
+                .unwrap()
+        }
+    };
+
     let output = quote! {
         #[test]
         #(#fn_attrs)*
@@ -89,11 +143,7 @@ pub fn async_timed_test(attr: TokenStream, input: TokenStream) -> TokenStream {
             // ensure that even if the runtime gets stuck somehow, we will still
             // be able to enforce the timeout.
             thread::spawn(move || {
-                let test_rt = tokio::runtime::Builder::new_multi_thread()
-                    .worker_threads(8)
-                    .enable_all()
-                    .build()
-                    .unwrap();
+                let test_rt = #runtime;
                 let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
                     test_rt.block_on(async #fn_block)
                 }));
@@ -13,6 +13,13 @@ async fn good() {
     tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
 }
 
+#[async_timed_test(timeout_secs = 5, start_paused = true)]
+async fn paused() {
+    let start = std::time::Instant::now();
+    tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+}
+
 #[async_timed_test(timeout_secs = 1)]
 #[should_panic]
 async fn bad() {
@@ -20,6 +20,7 @@ declare_attrs! {
         env_name: Some("HYPERACTOR_DEFAULT_ENCODING".to_string()),
         py_name: Some("default_encoding".to_string()),
         propagate: true,
+        secret: false,
     })
     pub attr DEFAULT_ENCODING: Encoding = Encoding::Multipart;
 }
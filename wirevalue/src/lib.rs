@@ -12,6 +12,12 @@
 //! Wirevalues also provide encoding polymorphism, allowing the same representation
 //! to carry multiple serialization formats, and to transcode between them for
 //! types that are registered through [`register_type!`].
+//!
+//! Values are always tagged with their typehash, whatever their encoding, so
+//! a mesh whose messages use different encodings remains routable: only the
+//! eventual receiver decodes a value. MessagePack and CBOR values are
+//! produced outside of Rust, such as by Python code, where bincode is awkward
+//! to produce, and are wrapped with [`Any::from_encoded`].
 
 use std::any::TypeId;
 use std::collections::HashMap;
@@ -28,6 +34,7 @@ pub use typeuri::Named;
 pub use typeuri::intern_typename;
 
 pub mod config;
+pub mod schema;
 
 /// Typehash value indicating a broken (unknown type, no value) Any.
 pub const BROKEN_TYPEHASH: u64 = 0;
@@ -69,6 +76,8 @@ pub struct TypeInfo {
     /// their payload. Types that use `register_type!` get a default that
     /// delegates to `arm_unchecked`, which works for Rust enum handlers.
     pub endpoint_name: unsafe fn(*const ()) -> Option<String>,
+    /// Serialize a value of this type.
+    pub serialize_unchecked: unsafe fn(*const ()) -> Result<Any>,
 }
 
 #[allow(dead_code)]
@@ -134,6 +143,26 @@ impl TypeInfo {
         // SAFETY: This isn't safe, we're passing it on.
         unsafe { (self.endpoint_name)(value) }
     }
+
+    /// Serialize a value of this type.
+    ///
+    /// # Safety
+    /// The caller must ensure the value pointer is valid for this type.
+    pub unsafe fn serialize_unchecked(&self, value: *const ()) -> Result<Any> {
+        // SAFETY: This isn't safe, we're passing it on.
+        unsafe { (self.serialize_unchecked)(value) }
+    }
+}
+
+/// Serialize the T-typed value behind `value`. This is used to populate
+/// [`TypeInfo::serialize_unchecked`].
+///
+/// # Safety
+/// The caller must ensure the value pointer points to a valid T.
+#[doc(hidden)]
+pub unsafe fn serialize_unchecked<T: Serialize + Named>(value: *const ()) -> Result<Any> {
+    // SAFETY: guaranteed by the caller.
+    Any::serialize(unsafe { &*(value as *const T) })
 }
 
 inventory::collect!(TypeInfo);
@@ -159,9 +188,17 @@ static TYPE_INFO_BY_TYPE_ID: LazyLock<HashMap<std::any::TypeId, &'static TypeInf
 /// payloads under type erasure.
 ///
 /// The provided type must implement [`typeuri::Named`], and must be concrete.
+///
+/// Registration also records the type's [`schema::Schema`], so that peers can
+/// negotiate layout compatibility through [`schema::SchemaDigest`].
 #[macro_export]
 macro_rules! register_type {
     ($type:ty) => {
+        $crate::submit! {
+            $crate::schema::SchemaInfo {
+                schema: $crate::schema::Schema::of::<$type>,
+            }
+        }
         $crate::submit! {
             $crate::TypeInfo {
                 typename: <$type as $crate::Named>::typename,
@@ -174,6 +211,7 @@ macro_rules! register_type {
                     // SAFETY: ptr points to a value of type $type, as guaranteed by the caller.
                     unsafe { <$type as $crate::Named>::arm_unchecked(ptr).map(|s| s.to_string()) }
                 },
+                serialize_unchecked: $crate::serialize_unchecked::<$type>,
             }
         }
     };
@@ -208,6 +246,25 @@ pub enum Encoding {
     /// Serde multipart encoding.
     #[strum(to_string = "serde_multipart")]
     Multipart,
+    /// MessagePack encoding, with structs encoded as maps.
+    #[strum(to_string = "msgpack")]
+    MessagePack,
+    /// CBOR encoding.
+    #[strum(to_string = "cbor")]
+    Cbor,
+}
+
+/// The encoding with which [`Any::serialize`] serializes values: the
+/// configured [`config::DEFAULT_ENCODING`]. MessagePack and CBOR are
+/// selected per port, for values produced outside of Rust (see
+/// [`Any::from_encoded`]); configured as the default, they fall back to
+/// multipart, so that a single switch cannot change the encoding of every
+/// message.
+pub fn default_encoding() -> Encoding {
+    match hyperactor_config::global::get(config::DEFAULT_ENCODING) {
+        Encoding::MessagePack | Encoding::Cbor => Encoding::Multipart,
+        encoding => encoding,
+    }
 }
 
 /// The encoding used for a serialized value.
@@ -216,6 +273,8 @@ enum Encoded {
     Bincode(bytes::Bytes),
     Json(bytes::Bytes),
     Multipart(serde_multipart::Message),
+    MessagePack(bytes::Bytes),
+    Cbor(bytes::Bytes),
 }
 
 impl Encoded {
@@ -225,6 +284,8 @@ impl Encoded {
             Encoded::Bincode(data) => data.len(),
             Encoded::Json(data) => data.len(),
             Encoded::Multipart(message) => message.len(),
+            Encoded::MessagePack(data) => data.len(),
+            Encoded::Cbor(data) => data.len(),
         }
     }
 
@@ -234,6 +295,8 @@ impl Encoded {
             Encoded::Bincode(data) => data.is_empty(),
             Encoded::Json(data) => data.is_empty(),
             Encoded::Multipart(message) => message.is_empty(),
+            Encoded::MessagePack(data) => data.is_empty(),
+            Encoded::Cbor(data) => data.is_empty(),
         }
     }
 
@@ -243,6 +306,8 @@ impl Encoded {
             Encoded::Bincode(_) => Encoding::Bincode,
             Encoded::Json(_) => Encoding::Json,
             Encoded::Multipart(_) => Encoding::Multipart,
+            Encoded::MessagePack(_) => Encoding::MessagePack,
+            Encoded::Cbor(_) => Encoding::Cbor,
         }
     }
 
@@ -251,6 +316,8 @@ impl Encoded {
         match &self {
             Encoded::Bincode(data) => crc32fast::hash(data),
             Encoded::Json(data) => crc32fast::hash(data),
+            Encoded::MessagePack(data) => crc32fast::hash(data),
+            Encoded::Cbor(data) => crc32fast::hash(data),
             Encoded::Multipart(message) => {
                 let mut hasher = crc32fast::Hasher::new();
                 for fragment in message.body().iter() {
@@ -275,6 +342,8 @@ impl std::fmt::Debug for Encoded {
         match self {
             Encoded::Bincode(data) => write!(f, "Encoded::Bincode({})", HexFmt(data)),
             Encoded::Json(data) => write!(f, "Encoded::Json({})", HexFmt(data)),
+            Encoded::MessagePack(data) => write!(f, "Encoded::MessagePack({})", HexFmt(data)),
+            Encoded::Cbor(data) => write!(f, "Encoded::Cbor({})", HexFmt(data)),
             Encoded::Multipart(message) => {
                 write!(
                     f,
@@ -315,6 +384,22 @@ pub enum Error {
     #[error(transparent)]
     Json(#[from] serde_json::Error),
 
+    /// Errors returned from MessagePack encoding.
+    #[error(transparent)]
+    MessagePackEncode(#[from] rmp_serde::encode::Error),
+
+    /// Errors returned from MessagePack decoding.
+    #[error(transparent)]
+    MessagePackDecode(#[from] rmp_serde::decode::Error),
+
+    /// Errors returned from CBOR encoding.
+    #[error(transparent)]
+    CborEncode(#[from] ciborium::ser::Error<std::io::Error>),
+
+    /// Errors returned from CBOR decoding.
+    #[error(transparent)]
+    CborDecode(#[from] ciborium::de::Error<std::io::Error>),
+
     /// The encoding was not recognized.
     #[error("unknown encoding: {0}")]
     InvalidEncoding(String),
@@ -378,24 +463,37 @@ impl std::fmt::Display for Any {
 
 impl Any {
     /// Construct a new serialized value by serializing the provided T-typed value.
-    /// Serialize uses the default encoding defined by the configuration key
+    /// Serialize uses the [`default_encoding`], defined by the configuration key
     /// [`config::DEFAULT_ENCODING`] in the global configuration; use [`serialize_with_encoding`]
     /// to serialize values with a specific encoding.
     pub fn serialize<T: Serialize + Named>(value: &T) -> Result<Self> {
-        Self::serialize_with_encoding(
-            hyperactor_config::global::get(config::DEFAULT_ENCODING),
-            value,
-        )
+        Self::serialize_with_encoding(default_encoding(), value)
+    }
+
+    /// Like [`Any::serialize`], but a multipart-encoded value takes its
+    /// body buffer from `pool`, returning it once the value is dropped.
+    pub fn serialize_pooled<T: Serialize + Named>(
+        value: &T,
+        pool: &std::sync::Arc<serde_multipart::pool::BufferPool>,
+    ) -> Result<Self> {
+        let encoding = default_encoding();
+        if encoding != Encoding::Multipart {
+            return Self::serialize_with_encoding(encoding, value);
+        }
+        Ok(Self {
+            encoded: Encoded::Multipart(
+                serde_multipart::serialize_bincode_pooled(value, pool)
+                    .map_err(|e| Error::InvalidEncoding(e.to_string()))?,
+            ),
+            typehash: T::typehash(),
+        })
     }
 
     /// Serialize U-typed value as a T-typed value. This should be used with care
     /// (typically only in testing), as the value's representation may be illegally
     /// coerced.
     pub fn serialize_as<T: Named, U: Serialize>(value: &U) -> Result<Self> {
-        Self::serialize_with_encoding_as::<T, U>(
-            hyperactor_config::global::get(config::DEFAULT_ENCODING),
-            value,
-        )
+        Self::serialize_with_encoding_as::<T, U>(default_encoding(), value)
     }
 
     /// Serialize the value with the using the provided encoding.
@@ -423,11 +521,50 @@ impl Any {
                     serde_multipart::serialize_bincode(value)
                         .map_err(|e| Error::InvalidEncoding(e.to_string()))?,
                 ),
+                Encoding::MessagePack => {
+                    Encoded::MessagePack(rmp_serde::to_vec_named(value)?.into())
+                }
+                Encoding::Cbor => {
+                    let mut data = Vec::new();
+                    ciborium::into_writer(value, &mut data)?;
+                    Encoded::Cbor(data.into())
+                }
             },
             typehash: T::typehash(),
         })
     }
 
+    /// Construct a serialized value from data that was encoded with `encoding`,
+    /// e.g. by a producer outside of Rust, as a value of the type with typehash
+    /// `typehash`. The data is not validated until it is deserialized. Multipart
+    /// values cannot be constructed from a single buffer.
+    pub fn from_encoded(
+        encoding: Encoding,
+        typehash: u64,
+        data: impl Into<bytes::Bytes>,
+    ) -> Result<Self> {
+        let data = data.into();
+        let encoded = match encoding {
+            Encoding::Bincode => Encoded::Bincode(data),
+            Encoding::Json => Encoded::Json(data),
+            Encoding::MessagePack => Encoded::MessagePack(data),
+            Encoding::Cbor => Encoded::Cbor(data),
+            Encoding::Multipart => return Err(Error::InvalidEncoding(encoding.to_string())),
+        };
+        Ok(Self { encoded, typehash })
+    }
+
+    /// The encoded data of a value that is not multipart-encoded.
+    pub fn encoded_bytes(&self) -> Option<&bytes::Bytes> {
+        match &self.encoded {
+            Encoded::Bincode(data)
+            | Encoded::Json(data)
+            | Encoded::MessagePack(data)
+            | Encoded::Cbor(data) => Some(data),
+            Encoded::Multipart(_) => None,
+        }
+    }
+
     /// Create a new broken Any value. A broken value has unknown type and
     /// no valid data. Attempting to deserialize a broken value will fail.
     pub fn new_broken() -> Self {
@@ -471,6 +608,8 @@ impl Any {
                 Ok(serde_multipart::deserialize_bincode(message.clone())
                     .map_err(|e| Error::InvalidEncoding(e.to_string()))?)
             }
+            Encoded::MessagePack(data) => Ok(rmp_serde::from_slice(data)?),
+            Encoded::Cbor(data) => Ok(ciborium::from_reader(&data[..])?),
         }
     }
 
@@ -478,7 +617,10 @@ impl Any {
     /// is embedded in the value, and the corresponding type is available in this binary.
     pub fn transcode_to_json(self) -> std::result::Result<Self, Self> {
         match self.encoded {
-            Encoded::Bincode(_) | Encoded::Multipart(_) => {
+            Encoded::Bincode(_)
+            | Encoded::Multipart(_)
+            | Encoded::MessagePack(_)
+            | Encoded::Cbor(_) => {
                 let json_value = match self.dump() {
                     Ok(json_value) => json_value,
                     Err(_) => return Err(self),
@@ -500,7 +642,10 @@ impl Any {
     /// in the serialized value; 2) the named type is linked into the binary.
     pub fn dump(&self) -> Result<serde_json::Value> {
         match &self.encoded {
-            Encoded::Bincode(_) | Encoded::Multipart(_) => {
+            Encoded::Bincode(_)
+            | Encoded::Multipart(_)
+            | Encoded::MessagePack(_)
+            | Encoded::Cbor(_) => {
                 let Some(typeinfo) = TYPE_INFO.get(&self.typehash) else {
                     return Err(Error::MissingTypeInfo(self.typehash));
                 };
@@ -926,6 +1071,68 @@ mod tests {
         assert!(!result3.is_empty());
     }
 
+    #[derive(typeuri::Named, Serialize, Deserialize, PartialEq, Eq, Debug)]
+    struct TestMessagePack {
+        name: String,
+        count: u64,
+    }
+    crate::register_type!(TestMessagePack);
+
+    #[test]
+    fn test_external_encodings() {
+        let value = TestMessagePack {
+            name: "hello".to_string(),
+            count: 3,
+        };
+        let ser = Any::serialize_with_encoding(Encoding::MessagePack, &value).unwrap();
+        assert_eq!(ser.encoding(), Encoding::MessagePack);
+        assert_eq!(ser.deserialized::<TestMessagePack>().unwrap(), value);
+        assert_eq!(
+            ser.dump().unwrap(),
+            serde_json::json!({"name": "hello", "count": 3})
+        );
+
+        // Values produced outside of Rust, e.g. Python dicts.
+        let dict = serde_json::json!({"name": "from python", "count": 7});
+        let expected = TestMessagePack {
+            name: "from python".to_string(),
+            count: 7,
+        };
+        let msgpack = Any::from_encoded(
+            Encoding::MessagePack,
+            TestMessagePack::typehash(),
+            rmp_serde::to_vec_named(&dict).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(msgpack.deserialized::<TestMessagePack>().unwrap(), expected);
+        let mut cbor_data = Vec::new();
+        ciborium::into_writer(&dict, &mut cbor_data).unwrap();
+        let cbor =
+            Any::from_encoded(Encoding::Cbor, TestMessagePack::typehash(), cbor_data).unwrap();
+        assert_eq!(cbor.deserialized::<TestMessagePack>().unwrap(), expected);
+        assert_eq!(
+            cbor.encoded_bytes().map(|data| data.len()),
+            Some(cbor.len())
+        );
+        assert!(Any::from_encoded(Encoding::Multipart, 0, Vec::new()).is_err());
+
+        // The encoding is carried on the wire, so it survives re-framing.
+        let framed = bincode::serde::encode_to_vec(&cbor, bincode::config::legacy()).unwrap();
+        let (reframed, _): (Any, _) =
+            bincode::serde::decode_from_slice(&framed, bincode::config::legacy()).unwrap();
+        assert_eq!(reframed.encoding(), Encoding::Cbor);
+        assert_eq!(reframed.typehash(), TestMessagePack::typehash());
+
+        // External encodings are never the default.
+        let config = hyperactor_config::global::lock();
+        let _guard = config.override_key(config::DEFAULT_ENCODING, Encoding::Cbor);
+        assert_eq!(default_encoding(), Encoding::Multipart);
+        assert_eq!(
+            Any::serialize(&value).unwrap().encoding(),
+            Encoding::Multipart
+        );
+    }
+
     #[test]
     fn test_encodings() {
         let value = TestDumpStruct {
@@ -0,0 +1,465 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ * All rights reserved.
+ *
+ * This source code is licensed under the BSD-style license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Schema registry for registered types.
+//!
+//! Typehashes are derived from type *names*, so two binaries that
+//! disagree on the field layout of a type still agree on its
+//! typehash. Decoding a payload produced by the other binary then
+//! fails in confusing ways, or worse, succeeds with garbage.
+//!
+//! This module records, for every type registered through
+//! [`crate::register_type!`], a [`Schema`] describing its top-level
+//! serde layout (struct fields, enum variants, and so on) together
+//! with a stable fingerprint of that layout. A [`SchemaDigest`]
+//! summarizes the schemas known to a binary; two procs exchange
+//! digests and call [`SchemaDigest::check`] to detect incompatible
+//! types before any payload is decoded.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::LazyLock;
+
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde::de::Visitor;
+use typeuri::Named;
+
+/// The top-level serde layout of a type, as observed through its
+/// [`Deserialize`] implementation.
+///
+/// Only the outermost shape is recorded: nested types contribute
+/// their own schemas when registered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Layout {
+    /// A type whose layout is not described by serde metadata
+    /// (primitives, sequences, maps, and custom deserializers that
+    /// do not name their shape).
+    Opaque,
+    /// A unit struct.
+    Unit {
+        /// The serde name of the struct.
+        name: &'static str,
+    },
+    /// A newtype struct.
+    Newtype {
+        /// The serde name of the struct.
+        name: &'static str,
+    },
+    /// A tuple struct with `len` fields.
+    Tuple {
+        /// The serde name of the struct.
+        name: &'static str,
+        /// The number of fields.
+        len: usize,
+    },
+    /// A struct with named fields, in declaration order.
+    Struct {
+        /// The serde name of the struct.
+        name: &'static str,
+        /// The field names, in declaration order.
+        fields: &'static [&'static str],
+    },
+    /// An enum with the given variants, in declaration order.
+    Enum {
+        /// The serde name of the enum.
+        name: &'static str,
+        /// The variant names, in declaration order.
+        variants: &'static [&'static str],
+    },
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Layout::Opaque => write!(f, "opaque"),
+            Layout::Unit { name } => write!(f, "unit {}", name),
+            Layout::Newtype { name } => write!(f, "newtype {}", name),
+            Layout::Tuple { name, len } => write!(f, "tuple {}({})", name, len),
+            Layout::Struct { name, fields } => {
+                write!(f, "struct {} {{{}}}", name, fields.join(","))
+            }
+            Layout::Enum { name, variants } => {
+                write!(f, "enum {} {{{}}}", name, variants.join(","))
+            }
+        }
+    }
+}
+
+/// The schema of a registered type: its identity plus its layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schema {
+    typename: &'static str,
+    typehash: u64,
+    layout: Layout,
+    fingerprint: u64,
+}
+
+impl Schema {
+    /// Compute the schema of `T` by tracing its [`Deserialize`]
+    /// implementation.
+    pub fn of<T: Named + DeserializeOwned>() -> Self {
+        let mut layout = None;
+        // The tracer always fails after recording the layout; the
+        // error carries no information.
+        let _ = T::deserialize(LayoutTracer {
+            layout: &mut layout,
+        });
+        let layout = layout.unwrap_or(Layout::Opaque);
+        let fingerprint = typeuri::cityhasher::hash(format!("{}", layout));
+        Self {
+            typename: T::typename(),
+            typehash: T::typehash(),
+            layout,
+            fingerprint,
+        }
+    }
+
+    /// The typename of the type.
+    pub fn typename(&self) -> &'static str {
+        self.typename
+    }
+
+    /// The typehash of the type.
+    pub fn typehash(&self) -> u64 {
+        self.typehash
+    }
+
+    /// The observed layout of the type.
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// A stable hash of the layout. Two binaries with the same
+    /// fingerprint for a typehash agree on its wire layout.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} ({:016x})",
+            self.typename, self.layout, self.fingerprint
+        )
+    }
+}
+
+#[doc(hidden)]
+/// Registration record submitted by [`crate::register_type!`].
+#[derive(Debug)]
+pub struct SchemaInfo {
+    /// Compute the schema of the registered type.
+    pub schema: fn() -> Schema,
+}
+
+inventory::collect!(SchemaInfo);
+
+/// Schemas for all registered types linked into the binary, keyed by typehash.
+static SCHEMAS: LazyLock<HashMap<u64, Schema>> = LazyLock::new(|| {
+    inventory::iter::<SchemaInfo>()
+        .map(|info| {
+            let schema = (info.schema)();
+            (schema.typehash(), schema)
+        })
+        .collect()
+});
+
+/// Look up the schema registered for the provided typehash.
+pub fn lookup(typehash: u64) -> Option<&'static Schema> {
+    SCHEMAS.get(&typehash)
+}
+
+/// Look up the layout fingerprint registered for the provided typehash.
+pub fn fingerprint(typehash: u64) -> Option<u64> {
+    lookup(typehash).map(Schema::fingerprint)
+}
+
+/// Iterate over all registered schemas.
+pub fn registered() -> impl Iterator<Item = &'static Schema> {
+    SCHEMAS.values()
+}
+
+/// A compact, serializable summary of the schemas known to a binary,
+/// exchanged between procs to negotiate compatibility.
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    typeuri::Named
+)]
+pub struct SchemaDigest {
+    /// typehash -> (typename, fingerprint)
+    entries: BTreeMap<u64, (String, u64)>,
+}
+
+impl SchemaDigest {
+    /// The digest of all schemas registered in this binary.
+    pub fn local() -> Self {
+        registered().collect()
+    }
+
+    /// Add a schema to the digest, replacing any previous entry for
+    /// the same typehash.
+    pub fn insert(&mut self, schema: &Schema) {
+        self.entries.insert(
+            schema.typehash(),
+            (schema.typename().to_string(), schema.fingerprint()),
+        );
+    }
+
+    /// The fingerprint recorded for the provided typehash, if any.
+    pub fn fingerprint(&self, typehash: u64) -> Option<u64> {
+        self.entries
+            .get(&typehash)
+            .map(|(_, fingerprint)| *fingerprint)
+    }
+
+    /// The number of types in the digest.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the digest is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Check this digest against a peer's. Types known to only one
+    /// side are not a conflict (they cannot be exchanged anyway);
+    /// types known to both sides must have equal fingerprints.
+    pub fn check(&self, remote: &SchemaDigest) -> Result<(), SchemaMismatch> {
+        let typenames: Vec<String> = self
+            .entries
+            .iter()
+            .filter_map(|(typehash, (typename, fingerprint))| {
+                let (_, remote_fingerprint) = remote.entries.get(typehash)?;
+                (remote_fingerprint != fingerprint).then(|| typename.clone())
+            })
+            .collect();
+        if typenames.is_empty() {
+            Ok(())
+        } else {
+            Err(SchemaMismatch { typenames })
+        }
+    }
+}
+
+impl<'a> FromIterator<&'a Schema> for SchemaDigest {
+    fn from_iter<I: IntoIterator<Item = &'a Schema>>(iter: I) -> Self {
+        let mut digest = SchemaDigest::default();
+        for schema in iter {
+            digest.insert(schema);
+        }
+        digest
+    }
+}
+
+/// Error returned when two digests disagree on the layout of one or
+/// more shared types.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "incompatible message schemas for {} type(s): {}",
+    typenames.len(),
+    typenames.join(", ")
+)]
+pub struct SchemaMismatch {
+    /// The names of the types whose layouts differ.
+    pub typenames: Vec<String>,
+}
+
+/// A [`Deserializer`] that records the first shape hint it is given
+/// and then aborts.
+struct LayoutTracer<'a> {
+    layout: &'a mut Option<Layout>,
+}
+
+#[derive(Debug)]
+struct Traced;
+
+impl fmt::Display for Traced {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "layout traced")
+    }
+}
+
+impl std::error::Error for Traced {}
+
+impl serde::de::Error for Traced {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        Traced
+    }
+}
+
+impl<'de> Deserializer<'de> for LayoutTracer<'_> {
+    type Error = Traced;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Traced> {
+        Err(Traced)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _visitor: V,
+    ) -> Result<V::Value, Traced> {
+        *self.layout = Some(Layout::Unit { name });
+        Err(Traced)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _visitor: V,
+    ) -> Result<V::Value, Traced> {
+        *self.layout = Some(Layout::Newtype { name });
+        Err(Traced)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        _visitor: V,
+    ) -> Result<V::Value, Traced> {
+        *self.layout = Some(Layout::Tuple { name, len });
+        Err(Traced)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Traced> {
+        *self.layout = Some(Layout::Struct { name, fields });
+        Err(Traced)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Traced> {
+        *self.layout = Some(Layout::Enum { name, variants });
+        Err(Traced)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit seq tuple map identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod v1 {
+        use serde::Deserialize;
+        use serde::Serialize;
+
+        #[derive(typeuri::Named, Serialize, Deserialize)]
+        #[named(name = "wirevalue::schema::tests::Message")]
+        pub struct Message {
+            pub a: u64,
+        }
+    }
+
+    mod v2 {
+        use serde::Deserialize;
+        use serde::Serialize;
+
+        #[derive(typeuri::Named, Serialize, Deserialize)]
+        #[named(name = "wirevalue::schema::tests::Message")]
+        pub struct Message {
+            pub a: u64,
+            pub b: String,
+        }
+    }
+
+    #[derive(typeuri::Named, Serialize, Deserialize)]
+    enum Command {
+        Start,
+        Stop(u64),
+    }
+    crate::register_type!(Command);
+
+    #[test]
+    fn test_layouts() {
+        assert_eq!(
+            Schema::of::<v1::Message>().layout(),
+            &Layout::Struct {
+                name: "Message",
+                fields: &["a"]
+            }
+        );
+        assert_eq!(
+            Schema::of::<Command>().layout(),
+            &Layout::Enum {
+                name: "Command",
+                variants: &["Start", "Stop"]
+            }
+        );
+        assert_eq!(Schema::of::<u64>().layout(), &Layout::Opaque);
+    }
+
+    #[test]
+    fn test_fingerprint_tracks_layout() {
+        let v1 = Schema::of::<v1::Message>();
+        let v2 = Schema::of::<v2::Message>();
+        assert_eq!(v1.typehash(), v2.typehash());
+        assert_ne!(v1.fingerprint(), v2.fingerprint());
+        assert_eq!(v1, Schema::of::<v1::Message>());
+    }
+
+    #[test]
+    fn test_registered_lookup() {
+        let schema = lookup(Command::typehash()).unwrap();
+        assert_eq!(schema.typename(), Command::typename());
+        assert_eq!(
+            fingerprint(Command::typehash()),
+            Some(Schema::of::<Command>().fingerprint())
+        );
+        assert!(
+            SchemaDigest::local()
+                .fingerprint(Command::typehash())
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_digest_check() {
+        let command = Schema::of::<Command>();
+        let local: SchemaDigest = [&Schema::of::<v1::Message>(), &command]
+            .into_iter()
+            .collect();
+        let compatible: SchemaDigest = [&Schema::of::<v1::Message>()].into_iter().collect();
+        let incompatible: SchemaDigest = [&Schema::of::<v2::Message>(), &command]
+            .into_iter()
+            .collect();
+
+        assert!(local.check(&compatible).is_ok());
+        assert!(local.check(&SchemaDigest::default()).is_ok());
+        let err = local.check(&incompatible).unwrap_err();
+        assert_eq!(
+            err.typenames,
+            vec!["wirevalue::schema::tests::Message".to_string()]
+        );
+    }
+}